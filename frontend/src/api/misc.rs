@@ -1,6 +1,9 @@
-//! Read-only reporting types: the time timeline (tiempo) and CFDIs.
+//! Read-only reporting types: the time timeline (tiempo), CFDIs, the
+//! burn-rate and net-worth reports, and the user's dashboard layout.
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::ApiError;
 
 // --- tiempo (timeline) ----------------------------------------------------
 
@@ -82,3 +85,85 @@ pub struct CfdiList {
     #[serde(default)]
     pub items: Vec<Cfdi>,
 }
+
+// --- burn rate (read-only) ------------------------------------------------
+
+/// Flows of one window. Mirrors the backend `BurnRatePeriod`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BurnRatePeriod {
+    pub from: String,
+    pub to: String,
+    pub avg_monthly_income: f64,
+    pub gross_burn: f64,
+    pub net_burn: f64,
+}
+
+/// Change vs the previous window, as a fraction; `None` without a baseline.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BurnRateDeltas {
+    pub income: Option<f64>,
+    pub gross_burn: Option<f64>,
+    pub net_burn: Option<f64>,
+}
+
+/// `GET /api/admin/reports/burn-rate`. Mirrors the backend `BurnRateResponse`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BurnRate {
+    pub currency: String,
+    pub months: u32,
+    pub cash_balance: f64,
+    pub current: BurnRatePeriod,
+    pub previous: BurnRatePeriod,
+    pub deltas: BurnRateDeltas,
+    pub projected_monthly_net: f64,
+    pub runway_basis: String,
+    pub runway_months: Option<f64>,
+}
+
+// --- net worth (read-only) ------------------------------------------------
+
+/// An account of the net-worth report. Mirrors the backend `NetWorthAccount`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct NetWorthAccount {
+    pub name: String,
+    pub currency: String,
+    pub value: f64,
+}
+
+/// `GET /api/admin/reports/net-worth`. Mirrors the backend `NetWorthResponse`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct NetWorth {
+    pub currency: String,
+    pub net_worth: f64,
+    pub accounts: Vec<NetWorthAccount>,
+}
+
+// --- dashboard layout -----------------------------------------------------
+
+/// A widget the dashboard can show. Mirrors the backend `DashboardWidgetData`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct DashboardWidget {
+    pub key: String,
+    pub label: String,
+    pub description: String,
+    pub data_url: String,
+}
+
+/// `GET /api/me/dashboard`. Mirrors the backend `DashboardLayout`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct DashboardLayout {
+    pub widgets: Vec<DashboardWidget>,
+    pub available: Vec<DashboardWidget>,
+    pub is_default: bool,
+}
+
+#[derive(Serialize)]
+struct DashboardLayoutPayload {
+    widgets: Option<Vec<String>>,
+}
+
+/// Stores the widgets to show, in order; `None` goes back to the default
+/// layout.
+pub async fn save_dashboard_layout(widgets: Option<Vec<String>>) -> Result<(), ApiError> {
+    super::post_json("/api/me/dashboard", &DashboardLayoutPayload { widgets }).await
+}
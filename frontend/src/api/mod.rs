@@ -11,12 +11,15 @@
 //!     plans, planned entries, forecasts.
 //!   - [`operations`] — orders, projects, resources, resource logs, concept
 //!     statuses, project concepts, the hourly grid.
-//!   - [`misc`] — timeline (tiempo) and CFDIs.
+//!   - [`misc`] — timeline (tiempo), CFDIs, the burn-rate and net-worth
+//!     reports and the dashboard layout.
 //!   - [`admin`] — companies, users, SAT configs, own account profile.
 
 use gloo_net::http::Request;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, closure::Closure};
+use web_sys::{EventSource, MessageEvent};
 
 mod admin;
 mod finance;
@@ -114,6 +117,7 @@ pub(crate) async fn err_with_body(resp: gloo_net::http::Response) -> ApiError {
 struct LoginBody<'a> {
     username: &'a str,
     code: &'a str,
+    remember: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -140,10 +144,15 @@ pub async fn get_me() -> Result<Me, ApiError> {
     }
 }
 
-/// Passwordless TOTP login. The server sets the session cookie on success.
-pub async fn login(username: &str, code: &str) -> Result<LoginOk, ApiError> {
+/// Passwordless TOTP login. The server sets the session cookie on success,
+/// plus a longer-lived refresh cookie when `remember` is set.
+pub async fn login(username: &str, code: &str, remember: bool) -> Result<LoginOk, ApiError> {
     let resp = Request::post("/login")
-        .json(&LoginBody { username, code })
+        .json(&LoginBody {
+            username,
+            code,
+            remember,
+        })
         .map_err(|e| ApiError::Transport(e.to_string()))?
         .send()
         .await
@@ -213,6 +222,45 @@ pub async fn post_empty(url: &str) -> Result<(), ApiError> {
     }
 }
 
+/// Events streamed by `GET /events` for the active company. `resync` means
+/// some were missed and everything should be reloaded.
+pub const COMPANY_EVENTS: [&str; 4] = [
+    "transaction_created",
+    "entry_covered",
+    "forecast_ready",
+    "resync",
+];
+
+/// Open `GET /events` stream; closed when dropped.
+pub struct EventSubscription {
+    source: EventSource,
+    _listener: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Calls `on_event` with the event name whenever the server reports a change
+/// in the active company. `None` when the stream cannot be opened; the
+/// browser reconnects on its own after network errors.
+pub fn subscribe_events(mut on_event: impl FnMut(String) + 'static) -> Option<EventSubscription> {
+    let source = EventSource::new("/events").ok()?;
+    let listener =
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| on_event(event.type_()));
+    for name in COMPANY_EVENTS {
+        source
+            .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+            .ok()?;
+    }
+    Some(EventSubscription {
+        source,
+        _listener: listener,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -8,8 +8,8 @@ use leptos_router::path;
 
 use crate::api::{self, ApiError, Me};
 use crate::components::{
-    Badge, BadgeTone, Button, ButtonVariant, Card, CardContent, CardHeader, CardTitle, Input,
-    Select,
+    Badge, BadgeTone, Button, ButtonVariant, Card, CardContent, CardHeader, CardTitle, Checkbox,
+    Input, Select,
 };
 use crate::pages::switch_company_href;
 use crate::pages::{
@@ -65,14 +65,15 @@ enum LoginOutcome {
 fn LoginView(auth: RwSignal<Auth>) -> impl IntoView {
     let email = RwSignal::new(String::new());
     let code = RwSignal::new(String::new());
+    let remember = RwSignal::new(false);
     let error = RwSignal::new(Option::<String>::None);
 
     // Action drives the async login + bootstrap; gives us pending() for free.
     // `new_local` because the web fetch future (JsFuture) is not `Send`.
-    let login_action = Action::new_local(move |input: &(String, String)| {
-        let (email, code) = input.clone();
+    let login_action = Action::new_local(move |input: &(String, String, bool)| {
+        let (email, code, remember) = input.clone();
         async move {
-            match api::login(&email, &code).await {
+            match api::login(&email, &code, remember).await {
                 Ok(ok) => match ok.redirect_url {
                     // Full navigation to the tenant subdomain re-bootstraps the
                     // SPA under the correct host.
@@ -106,7 +107,7 @@ fn LoginView(auth: RwSignal<Auth>) -> impl IntoView {
     let submit = move |ev: web_sys::SubmitEvent| {
         ev.prevent_default();
         error.set(None);
-        login_action.dispatch((email.get(), code.get()));
+        login_action.dispatch((email.get(), code.get(), remember.get()));
     };
 
     view! {
@@ -142,6 +143,8 @@ fn LoginView(auth: RwSignal<Auth>) -> impl IntoView {
                             />
                         </div>
 
+                        <Checkbox checked=remember label="Mantener la sesión iniciada" />
+
                         {move || {
                             error
                                 .get()
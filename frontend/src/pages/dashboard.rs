@@ -1,15 +1,498 @@
+use std::collections::HashMap;
+
 use leptos::prelude::*;
+use leptos::task::spawn_local;
+use serde::de::DeserializeOwned;
+
+use super::charts::donut;
+use super::{money, switch_company_href};
+use crate::api::{
+    self, ApiError, BurnRate, DashboardLayout, DashboardWidget, Me, NetWorth, PlannedEntry,
+    Transaction,
+};
+use crate::components::{Button, ButtonVariant};
+
+/// Change vs the previous period as a colored `+12.5%`. `higher_is_better`
+/// picks which direction reads as good news.
+fn delta_view(delta: Option<f64>, higher_is_better: bool) -> AnyView {
+    let Some(delta) = delta else {
+        return view! { <span class="text-xs text-muted-foreground">"sin periodo previo"</span> }
+            .into_any();
+    };
+    let good = (delta >= 0.0) == higher_is_better;
+    let cls = if delta == 0.0 {
+        "text-muted-foreground"
+    } else if good {
+        "text-emerald-600"
+    } else {
+        "text-rose-600"
+    };
+    let text = format!(
+        "{}{:.1}% vs periodo previo",
+        if delta > 0.0 { "+" } else { "" },
+        delta * 100.0
+    );
+    view! { <span class=format!("text-xs font-semibold {cls}")>{text}</span> }.into_any()
+}
+
+/// Burn rate, runway and cash of the active company.
+fn burn_rate_panel(report: BurnRate) -> AnyView {
+    let months = report.months;
+    let currency = report.currency.clone();
+    let (runway, runway_cls) = match report.runway_months {
+        None => ("La caja no se agota".to_string(), "text-emerald-600"),
+        Some(m) if m < 3.0 => (format!("{m:.1} meses"), "text-rose-600"),
+        Some(m) if m < 6.0 => (format!("{m:.1} meses"), "text-amber-600"),
+        Some(m) => (format!("{m:.1} meses"), "text-foreground"),
+    };
+    let basis = if report.runway_basis == "planned" {
+        format!("Según los compromisos de los próximos {months} meses")
+    } else {
+        format!("Según el promedio de los últimos {months} meses")
+    };
+    let kpi = |label: &str, value: String, cls: &str, footer: AnyView| {
+        view! {
+            <div class="rounded-xl border border-border bg-card p-4">
+                <p class="text-[11px] font-semibold uppercase tracking-wide text-muted-foreground">
+                    {label.to_string()}
+                </p>
+                <p class=format!("mt-1 text-2xl font-bold {cls}")>{value}</p>
+                <div class="mt-1">{footer}</div>
+            </div>
+        }
+    };
+    let note = |text: String| {
+        view! { <span class="text-xs text-muted-foreground">{text}</span> }.into_any()
+    };
+    view! {
+        <div class="space-y-2">
+            <p class="text-xs text-muted-foreground">
+                {format!("{} · últimos {months} meses", currency)}
+            </p>
+            <div class="grid gap-3 sm:grid-cols-2 xl:grid-cols-4">
+                {kpi(
+                    "Consumo neto mensual",
+                    money(report.current.net_burn),
+                    if report.current.net_burn > 0.0 { "text-rose-600" } else { "text-emerald-600" },
+                    delta_view(report.deltas.net_burn, false),
+                )}
+                {kpi(
+                    "Pista de caja",
+                    runway,
+                    runway_cls,
+                    note(basis),
+                )}
+                {kpi(
+                    "Caja disponible",
+                    money(report.cash_balance),
+                    "text-foreground",
+                    note(format!("Flujo esperado: {} al mes", money(report.projected_monthly_net))),
+                )}
+                {kpi(
+                    "Gasto mensual",
+                    money(report.current.gross_burn),
+                    "text-foreground",
+                    delta_view(report.deltas.gross_burn, false),
+                )}
+            </div>
+            <p class="text-xs text-muted-foreground">
+                {format!(
+                    "Ingreso mensual promedio {} ({})",
+                    money(report.current.avg_monthly_income),
+                    report.current.from,
+                )}
+                " · "
+                {delta_view(report.deltas.income, true)}
+            </p>
+        </div>
+    }
+    .into_any()
+}
+
+/// Balance of each active account and the total in the company currency.
+fn cash_position_panel(report: NetWorth) -> AnyView {
+    let rows = report
+        .accounts
+        .into_iter()
+        .map(|account| {
+            view! {
+                <li class="flex justify-between py-1 text-sm">
+                    <span class="text-muted-foreground">{account.name}</span>
+                    <span class="font-medium">
+                        {format!("{} {}", money(account.value), account.currency)}
+                    </span>
+                </li>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! {
+        <div class="rounded-xl border border-border bg-card p-4">
+            <p class="text-2xl font-bold">
+                {format!("{} {}", money(report.net_worth), report.currency)}
+            </p>
+            <ul class="mt-2 divide-y divide-border">{rows}</ul>
+        </div>
+    }
+    .into_any()
+}
 
-use super::switch_company_href;
-use crate::api::Me;
+/// Commitments past their due date, oldest first.
+fn overdue_panel(entries: Vec<PlannedEntry>) -> AnyView {
+    let mut overdue: Vec<PlannedEntry> = entries
+        .into_iter()
+        .filter(|entry| entry.status == "overdue")
+        .collect();
+    if overdue.is_empty() {
+        return view! { <p class="text-sm text-muted-foreground">"Sin compromisos vencidos."</p> }
+            .into_any();
+    }
+    overdue.sort_by(|a, b| a.due_date.cmp(&b.due_date));
+    let rows = overdue
+        .into_iter()
+        .take(10)
+        .map(|entry| {
+            let cls = if entry.flow_type == "income" {
+                "text-emerald-600"
+            } else {
+                "text-rose-600"
+            };
+            view! {
+                <li class="flex justify-between gap-3 py-1 text-sm">
+                    <span>
+                        {entry.name}
+                        <span class="ml-2 text-xs text-muted-foreground">
+                            {entry.due_date.get(..10).unwrap_or(&entry.due_date).to_string()}
+                        </span>
+                    </span>
+                    <span class=format!("font-medium {cls}")>{money(entry.amount_estimated)}</span>
+                </li>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! {
+        <ul class="divide-y divide-border rounded-xl border border-border bg-card px-4 py-2">
+            {rows}
+        </ul>
+    }
+    .into_any()
+}
+
+/// Confirmed expenses of the current month split by category.
+fn category_chart_panel(list: Vec<Transaction>) -> AnyView {
+    const COLORS: [&str; 6] = [
+        "#f43f5e", "#f59e0b", "#2563eb", "#10b981", "#8b5cf6", "#64748b",
+    ];
+    let month = js_sys::Date::new_0()
+        .to_iso_string()
+        .as_string()
+        .and_then(|s| s.get(..7).map(str::to_string))
+        .unwrap_or_default();
+    let mut totals: HashMap<String, f64> = HashMap::new();
+    for t in list
+        .iter()
+        .filter(|t| t.tx_type == "expense" && t.is_confirmed && t.date.starts_with(&month))
+    {
+        let key = if t.category.is_empty() {
+            "Sin categoría".to_string()
+        } else {
+            t.category.clone()
+        };
+        *totals.entry(key).or_default() += t.amount;
+    }
+    let mut ranked: Vec<(String, f64)> = totals.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    // Past the first five, the rest share one slice.
+    if ranked.len() > COLORS.len() {
+        let rest: f64 = ranked.drain(COLORS.len() - 1..).map(|(_, v)| v).sum();
+        ranked.push(("Otras".to_string(), rest));
+    }
+    let total: f64 = ranked.iter().map(|(_, v)| v).sum();
+    let segments: Vec<(String, f64, String)> = ranked
+        .iter()
+        .zip(COLORS)
+        .map(|((name, value), color)| (name.clone(), *value, color.to_string()))
+        .collect();
+    let svg = donut(&segments, &money(total), "gasto del mes");
+    let legend = segments
+        .into_iter()
+        .map(|(name, value, color)| {
+            view! {
+                <li class="flex items-center justify-between gap-3 text-sm">
+                    <span class="flex items-center gap-2">
+                        <span class="h-2 w-2 rounded-full" style=format!("background:{color}")></span>
+                        {name}
+                    </span>
+                    <span class="font-medium">{money(value)}</span>
+                </li>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! {
+        <div class="flex flex-wrap items-center gap-6 rounded-xl border border-border bg-card p-4">
+            <div class="h-36 w-36" inner_html=svg></div>
+            <ul class="min-w-48 flex-1 space-y-1">{legend}</ul>
+        </div>
+    }
+    .into_any()
+}
+
+/// The latest movements; the list comes newest first.
+fn recent_transactions_panel(list: Vec<Transaction>) -> AnyView {
+    if list.is_empty() {
+        return view! { <p class="text-sm text-muted-foreground">"Sin movimientos."</p> }
+            .into_any();
+    }
+    let rows = list
+        .into_iter()
+        .take(8)
+        .map(|t| {
+            let cls = match t.tx_type.as_str() {
+                "income" => "text-emerald-600",
+                "expense" => "text-rose-600",
+                _ => "text-foreground",
+            };
+            view! {
+                <li class="flex justify-between gap-3 py-1 text-sm">
+                    <span>
+                        <span class="mr-2 text-xs text-muted-foreground">{t.date}</span>
+                        {t.description}
+                    </span>
+                    <span class=format!("font-medium {cls}")>{money(t.amount)}</span>
+                </li>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! {
+        <ul class="divide-y divide-border rounded-xl border border-border bg-card px-4 py-2">
+            {rows}
+        </ul>
+    }
+    .into_any()
+}
+
+/// Loads a widget's data now and again whenever `reload` changes, keeping the
+/// old figures on screen until the new ones arrive.
+fn load_widget<T>(url: String, reload: RwSignal<u32>, panel: fn(T) -> AnyView) -> AnyView
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    let data = RwSignal::new(None::<Result<T, ApiError>>);
+    Effect::new(move |_| {
+        reload.track();
+        let url = url.clone();
+        spawn_local(async move {
+            data.set(Some(api::get_json::<T>(&url).await));
+        });
+    });
+    (move || match data.get() {
+        None => view! { <p class="text-sm text-muted-foreground">"Cargando…"</p> }.into_any(),
+        Some(Err(e)) => {
+            let message = api::humanize(&e, "No se pudieron cargar los datos");
+            view! { <p class="text-sm text-red-600">{message}</p> }.into_any()
+        }
+        Some(Ok(value)) => panel(value),
+    })
+    .into_any()
+}
+
+/// The view of each widget key the backend registry lists. Keys this build
+/// does not know yet are left out.
+fn widget_view(widget: DashboardWidget, reload: RwSignal<u32>) -> AnyView {
+    let url = widget.data_url.clone();
+    let body = match widget.key.as_str() {
+        "burn_rate" => load_widget::<BurnRate>(url, reload, burn_rate_panel),
+        "cash_position" => load_widget::<NetWorth>(url, reload, cash_position_panel),
+        "overdue" => load_widget::<Vec<PlannedEntry>>(url, reload, overdue_panel),
+        "category_chart" => load_widget::<Vec<Transaction>>(url, reload, category_chart_panel),
+        "recent_transactions" => {
+            load_widget::<Vec<Transaction>>(url, reload, recent_transactions_panel)
+        }
+        _ => return ().into_any(),
+    };
+    view! {
+        <section class="mb-6 space-y-2" data-widget=widget.key.clone()>
+            <h2 class="text-lg font-semibold">{widget.label}</h2>
+            {body}
+        </section>
+    }
+    .into_any()
+}
+
+/// Adds the widget at the end of the layout, or takes it out.
+fn toggle_widget(draft: RwSignal<Vec<String>>, key: &str) {
+    draft.update(|keys| match keys.iter().position(|k| k == key) {
+        Some(idx) => {
+            keys.remove(idx);
+        }
+        None => keys.push(key.to_string()),
+    })
+}
+
+/// Moves a chosen widget `by` places, staying within the layout.
+fn shift_widget(draft: RwSignal<Vec<String>>, key: &str, by: isize) {
+    draft.update(|keys| {
+        if let Some(idx) = keys.iter().position(|k| k == key) {
+            let target = idx as isize + by;
+            if target >= 0 && (target as usize) < keys.len() {
+                keys.swap(idx, target as usize);
+            }
+        }
+    })
+}
+
+/// Checkbox and ordering controls over the widgets the user may show.
+fn layout_editor(
+    layout: DashboardLayout,
+    draft: RwSignal<Vec<String>>,
+    on_save: impl Fn(Option<Vec<String>>) + Clone + 'static,
+) -> AnyView {
+    let rows = layout
+        .available
+        .into_iter()
+        .map(|widget| {
+            let key = widget.key.clone();
+            let (checked_key, toggle_key, up_key, down_key) =
+                (key.clone(), key.clone(), key.clone(), key.clone());
+            view! {
+                <li class="flex items-center justify-between gap-3 py-2">
+                    <label class="flex items-start gap-2 text-sm">
+                        <input
+                            type="checkbox"
+                            class="mt-0.5 h-4 w-4 rounded border-input"
+                            prop:checked=move || draft.get().contains(&checked_key)
+                            on:change=move |_| toggle_widget(draft, &toggle_key)
+                        />
+                        <span>
+                            <span class="font-medium">{widget.label}</span>
+                            <span class="block text-xs text-muted-foreground">
+                                {widget.description}
+                            </span>
+                        </span>
+                    </label>
+                    <span class="flex items-center gap-1 text-xs text-muted-foreground">
+                        {move || {
+                            draft
+                                .get()
+                                .iter()
+                                .position(|k| *k == key)
+                                .map(|idx| format!("#{}", idx + 1))
+                        }}
+                        <Button
+                            variant=ButtonVariant::Ghost
+                            on:click=move |_| shift_widget(draft, &up_key, -1)
+                        >
+                            "↑"
+                        </Button>
+                        <Button
+                            variant=ButtonVariant::Ghost
+                            on:click=move |_| shift_widget(draft, &down_key, 1)
+                        >
+                            "↓"
+                        </Button>
+                    </span>
+                </li>
+            }
+        })
+        .collect::<Vec<_>>();
+    let save = on_save.clone();
+    view! {
+        <div class="mb-6 rounded-xl border border-border bg-card p-4">
+            <p class="text-sm text-muted-foreground">
+                "Elige qué mostrar en el inicio y en qué orden."
+            </p>
+            <ul class="mt-2 divide-y divide-border">{rows}</ul>
+            <div class="mt-3 flex justify-end gap-2">
+                <Button variant=ButtonVariant::Outline on:click=move |_| on_save(None)>
+                    "Restablecer"
+                </Button>
+                <Button on:click=move |_| save(Some(draft.get_untracked()))>"Guardar"</Button>
+            </div>
+        </div>
+    }
+    .into_any()
+}
 
 #[component]
 pub fn Dashboard() -> impl IntoView {
     let me = use_context::<Me>().expect("Me context");
     let companies = me.companies.clone();
+    let is_admin = me.role == "admin";
+
+    let layout = RwSignal::new(None::<Result<DashboardLayout, ApiError>>);
+    let load_layout = move || {
+        spawn_local(async move {
+            layout.set(Some(
+                api::get_json::<DashboardLayout>("/api/me/dashboard").await,
+            ));
+        })
+    };
+    load_layout();
+
+    // New transactions, covered entries and forecasts change the figures;
+    // the widgets reload in place, keeping the old numbers until then.
+    let reload = RwSignal::new(0_u32);
+    if is_admin {
+        let events =
+            StoredValue::new_local(api::subscribe_events(move |_| reload.update(|n| *n += 1)));
+        on_cleanup(move || events.dispose());
+    }
+
+    let editing = RwSignal::new(false);
+    let draft = RwSignal::new(Vec::<String>::new());
+    let save_error = RwSignal::new(None::<String>);
+    let save = move |widgets: Option<Vec<String>>| {
+        spawn_local(async move {
+            match api::save_dashboard_layout(widgets).await {
+                Ok(()) => {
+                    save_error.set(None);
+                    editing.set(false);
+                    load_layout();
+                }
+                Err(e) => save_error.set(Some(api::humanize(&e, "No se pudo guardar el inicio"))),
+            }
+        })
+    };
+    let start_editing = move || {
+        if let Some(Ok(current)) = layout.get_untracked() {
+            draft.set(current.widgets.iter().map(|w| w.key.clone()).collect());
+        }
+        editing.update(|on| *on = !*on);
+    };
 
     view! {
-        <h1 class="mb-4 text-xl font-semibold">"Inicio"</h1>
+        <div class="mb-4 flex items-center justify-between">
+            <h1 class="text-xl font-semibold">"Inicio"</h1>
+            {move || {
+                matches!(layout.get(), Some(Ok(ref l)) if !l.available.is_empty())
+                    .then(|| {
+                        view! {
+                            <Button variant=ButtonVariant::Outline on:click=move |_| start_editing()>
+                                "Personalizar"
+                            </Button>
+                        }
+                    })
+            }}
+        </div>
+        {move || save_error.get().map(|message| view! { <p class="mb-4 text-red-600">{message}</p> })}
+        {move || match (editing.get(), layout.get_untracked()) {
+            (true, Some(Ok(current))) => Some(layout_editor(current, draft, save)),
+            _ => None,
+        }}
+        {move || match layout.get() {
+            None => view! { <p class="mb-6 text-muted-foreground">"Cargando…"</p> }.into_any(),
+            Some(Err(e)) => {
+                let message = api::humanize(&e, "No se pudo cargar el inicio");
+                view! { <p class="mb-6 text-red-600">{message}</p> }.into_any()
+            }
+            Some(Ok(current)) => {
+                current
+                    .widgets
+                    .into_iter()
+                    .map(|widget| widget_view(widget, reload))
+                    .collect::<Vec<_>>()
+                    .into_any()
+            }
+        }}
         <h2 class="mb-2 text-lg font-semibold">"Compañías"</h2>
         <ul class="space-y-1">
             {companies
@@ -175,7 +175,7 @@ pub(crate) fn status_badge(label: &str) -> AnyView {
         BadgeTone::Neutral
     } else if l.contains("vencid") || l.contains("atras") || l.contains("overdue") {
         BadgeTone::Danger
-    } else if l.contains("parcial") || l.contains("partial") {
+    } else if l.contains("parcial") || l.contains("partial") || l.contains("excedent") {
         BadgeTone::Warning
     } else if l.contains("cubiert")
         || l.contains("pagad")
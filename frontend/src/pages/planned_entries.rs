@@ -15,6 +15,7 @@ const STATUSES: &[(&str, &str)] = &[
     ("planned", "Planificado"),
     ("partially_covered", "Parcial"),
     ("covered", "Cubierto"),
+    ("over_covered", "Excedente"),
     ("overdue", "Vencido"),
     ("cancelled", "Cancelado"),
 ];
@@ -71,7 +71,14 @@ pub fn RecurringPlansPage() -> impl IntoView {
     let save = Action::new_local(move |_: &()| {
         let contact_val = contact.get_untracked();
         let end_val = end.get_untracked();
-        let dom_val = dom.get_untracked();
+        let frequency_val = frequency.get_untracked();
+        // Only monthly plans fall on a day of the month; the API rejects it
+        // for weekly ones.
+        let dom_val = if frequency_val == "monthly" {
+            dom.get_untracked()
+        } else {
+            String::new()
+        };
         let notes_val = notes.get_untracked();
         let payload = RecurringPlanPayload {
             name: name.get_untracked().trim().to_string(),
@@ -80,7 +87,7 @@ pub fn RecurringPlansPage() -> impl IntoView {
             account_expected_id: account.get_untracked(),
             contact_id: (!contact_val.is_empty()).then_some(contact_val),
             amount_estimated: amount.get_untracked().trim().parse().unwrap_or(0.0),
-            frequency: frequency.get_untracked(),
+            frequency: frequency_val,
             day_of_month: dom_val.trim().parse::<i32>().ok(),
             start_date: date_to_rfc3339(&start.get_untracked()),
             end_date: (!end_val.trim().is_empty()).then(|| date_to_rfc3339(&end_val)),
@@ -294,7 +301,7 @@ pub fn RecurringPlansPage() -> impl IntoView {
                                     </div>
                                     <div class="space-y-1">
                                         <label class="block text-sm font-medium text-foreground">
-                                            "Día del mes (opcional)"
+                                            "Día del mes (solo mensual)"
                                         </label>
                                         <Input
                                             value=dom
@@ -185,9 +185,9 @@ const TIEMPO_JS: &str = r##"
               <span class="truncate text-foreground" title="${tx.description}">${tx.description}</span>
             </div>`).join("");
           const plannedItems = bucket.planned_entries.map((pe) => {
-            const payBtn = (pe.status !== "covered" && pe.status !== "cancelled")
+            const payBtn = (pe.status !== "covered" && pe.status !== "over_covered" && pe.status !== "cancelled")
               ? `<a href="/admin/planned_entries/${pe.id}/pay?return_to=/v2/tiempo" class="ml-2 shrink-0 rounded px-2 py-0.5 text-[11px] font-semibold bg-emerald-50 text-emerald-700 ring-1 ring-emerald-200 hover:bg-emerald-100 transition">Pagar</a>` : "";
-            const selectBox = (pe.status !== "covered" && pe.status !== "cancelled")
+            const selectBox = (pe.status !== "covered" && pe.status !== "over_covered" && pe.status !== "cancelled")
               ? `<input type="checkbox" data-timeline-bulk-pay value="${pe.id}" ${selectedPlannedEntries.has(pe.id) ? "checked" : ""} class="shrink-0 rounded border-border text-emerald-600 focus:ring-emerald-500" />` : "";
             return `<div class="flex items-center justify-between gap-2 rounded border border-dashed border-border px-2 py-1">
               ${selectBox}
@@ -0,0 +1,102 @@
+/// Reports references to accounts, categories and contacts that no longer
+/// exist, per company, and optionally fixes the category and contact ones.
+/// Exits with 1 while any orphan is left.
+/// Usage: cargo run --bin check_references -- [--company <id>]
+///        [--reassign-categories] [--clear-contacts]
+use std::process::ExitCode;
+
+use alfredodev::state::{
+    AppState, clear_orphaned_contacts, find_orphaned_references, fix_orphaned_categories,
+    get_company_by_id, init_state, list_companies,
+};
+use bson::oid::ObjectId;
+use clap::Parser;
+use dotenvy::dotenv;
+
+#[derive(Parser)]
+#[command(about = "Find references to deleted records")]
+struct Args {
+    /// Only check this company.
+    #[arg(long)]
+    company: Option<ObjectId>,
+    /// Point orphaned category references at the "Sin categoría" placeholder.
+    #[arg(long)]
+    reassign_categories: bool,
+    /// Remove orphaned contact references.
+    #[arg(long)]
+    clear_contacts: bool,
+}
+
+/// Prints the company's orphans and returns how many references it found.
+async fn report(state: &AppState, company_id: &ObjectId, name: &str) -> anyhow::Result<u64> {
+    let groups = find_orphaned_references(state, company_id).await?;
+    let count = groups.iter().map(|group| group.count).sum();
+    if count == 0 {
+        return Ok(0);
+    }
+    println!("{name} ({company_id}): {count} orphaned references");
+    for group in groups {
+        println!("  {} ({})", group.label, group.count);
+        for reference in group.references {
+            let ids: Vec<String> = reference.missing_ids.iter().map(|id| id.to_hex()).collect();
+            println!(
+                "    {}.{}: {} documents -> {}",
+                reference.collection,
+                reference.field,
+                reference.count,
+                ids.join(", ")
+            );
+        }
+    }
+    Ok(count)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    dotenv().ok();
+    let args = Args::parse();
+    let state = init_state().await?;
+
+    let companies = match args.company {
+        Some(id) => match get_company_by_id(&state, &id).await? {
+            Some(company) => vec![company],
+            None => anyhow::bail!("company {id} not found"),
+        },
+        None => list_companies(&state).await?,
+    };
+
+    let mut remaining = 0;
+    for company in companies {
+        let Some(company_id) = company.id else {
+            continue;
+        };
+        let found = report(&state, &company_id, &company.name).await?;
+        if found == 0 {
+            continue;
+        }
+        if args.reassign_categories {
+            let fix = fix_orphaned_categories(&state, &company_id).await?;
+            println!(
+                "  reassigned {} documents ({} placeholder categories created)",
+                fix.updated, fix.created_categories
+            );
+        }
+        if args.clear_contacts {
+            let fix = clear_orphaned_contacts(&state, &company_id).await?;
+            println!("  cleared the contact of {} documents", fix.updated);
+        }
+        if args.reassign_categories || args.clear_contacts {
+            let groups = find_orphaned_references(&state, &company_id).await?;
+            remaining += groups.iter().map(|group| group.count).sum::<u64>();
+        } else {
+            remaining += found;
+        }
+    }
+
+    if remaining == 0 {
+        println!("No orphaned references");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
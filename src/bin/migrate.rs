@@ -0,0 +1,62 @@
+/// Shows and applies the schema migrations of the database in MONGODB_URI /
+/// MONGODB_DB. The server also applies them at startup unless
+/// MIGRATE_ON_STARTUP=0. `--reindex` builds the app's indexes in the
+/// background instead, for servers started with INDEXES_ON_STARTUP=0.
+/// Usage: cargo run --bin migrate -- [--status | --dry-run | --reindex]
+use std::{env, sync::Arc};
+
+use alfredodev::state::{migration_status, run_migrations, run_reindex};
+use clap::Parser;
+use dotenvy::dotenv;
+use mongodb::Client;
+use tokio::sync::Mutex;
+
+#[derive(Parser)]
+#[command(about = "Apply pending schema migrations")]
+struct Args {
+    /// List every migration and when it was applied, without running any.
+    #[arg(long)]
+    status: bool,
+    /// Count the documents each pending migration would change, without
+    /// changing or recording anything.
+    #[arg(long, conflicts_with = "status")]
+    dry_run: bool,
+    /// Build every index of the app in the background and record the run,
+    /// without applying migrations.
+    #[arg(long, conflicts_with_all = ["status", "dry_run"])]
+    reindex: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let args = Args::parse();
+
+    let uri = env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+    let db_name = env::var("MONGODB_DB").unwrap_or_else(|_| "totp".to_string());
+    let db = Client::with_uri_str(&uri).await?.database(&db_name);
+
+    if args.status {
+        for status in migration_status(&db).await? {
+            let applied = status
+                .applied_at
+                .map(|at| at.to_chrono().format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "pending".to_string());
+            println!("{:>4} {:<40} {applied}", status.version, status.name);
+        }
+        return Ok(());
+    }
+
+    if args.reindex {
+        run_reindex(&db, &Arc::new(Mutex::new(None))).await?;
+        return Ok(());
+    }
+
+    let runs = run_migrations(&db, args.dry_run).await?;
+    if runs.is_empty() {
+        println!("No pending migrations");
+    } else if args.dry_run {
+        println!("{} migrations pending; nothing was changed", runs.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+/// Loads a YAML seed (see data/seed.example.yaml) into an existing company
+/// of the database in MONGODB_URI / MONGODB_DB that has no accounts yet.
+/// Usage: cargo run --bin seed -- --company <slug> <file.yaml>
+use std::{env, fs};
+
+use alfredodev::state::seed_company_from_yaml;
+use anyhow::Context;
+use clap::Parser;
+use dotenvy::dotenv;
+use mongodb::Client;
+
+#[derive(Parser)]
+#[command(about = "Seed a company from a YAML file")]
+struct Args {
+    /// Slug of the company to fill.
+    #[arg(long)]
+    company: String,
+    /// YAML seed file.
+    file: String,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let args = Args::parse();
+
+    let contents =
+        fs::read_to_string(&args.file).with_context(|| format!("reading {}", args.file))?;
+    let uri = env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+    let db_name = env::var("MONGODB_DB").unwrap_or_else(|_| "totp".to_string());
+    let db = Client::with_uri_str(&uri).await?.database(&db_name);
+
+    seed_company_from_yaml(&db, &args.company, &contents)
+        .await
+        .with_context(|| format!("loading {}", args.file))?;
+    println!("Seeded {} from {}", args.company, args.file);
+    Ok(())
+}
@@ -0,0 +1,323 @@
+// demo.rs
+// Demo mode: every visitor gets a throwaway MongoDB database seeded with the
+// sample data, and that database is dropped again when they log out.
+//
+// Enabled with DEMO_MODE=1. The regular router is built once per sandbox
+// against the sandbox's own AppState; `dispatch` forwards each request to the
+// router picked by the `demo_sandbox` cookie. Visitors without a known
+// sandbox land on `/demo`, and only sending its form provisions one (already
+// logged in as the first seeded user), so crawlers and scripts never create
+// databases. At most DEMO_MAX_SANDBOXES (default 20) live at once, and a
+// background sweep drops those left idle for longer than a session lasts
+// (DEMO_IDLE_TTL_SECONDS overrides it). Real data in MONGODB_DB is never
+// opened.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderValue, Method, StatusCode, header::SET_COOKIE},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use mongodb::bson::doc;
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    session::{SESSION_COOKIE_NAME, extract_cookies},
+    state::{
+        AppState, create_session, drop_database, init_state_with_db_name, session_ttl_seconds,
+    },
+};
+
+pub const DEMO_COOKIE_NAME: &str = "demo_sandbox";
+
+/// Page that explains the demo; sending its form provisions a sandbox.
+pub const DEMO_ENTRY_PATH: &str = "/demo";
+
+const DEFAULT_MAX_SANDBOXES: usize = 20;
+
+/// How often expired sandboxes are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const LANDING_HTML: &str = r#"<!doctype html>
+<html lang="es">
+<head><meta charset="utf-8"><title>Demo</title></head>
+<body>
+  <h1>Demo</h1>
+  <p>Entra a una copia temporal con datos de ejemplo. Se borra al cerrar sesion.</p>
+  <form method="post" action="/demo"><button type="submit">Entrar al demo</button></form>
+</body>
+</html>
+"#;
+
+/// `DEMO_MODE` accepts `1`, `true` or `yes` (case-insensitive).
+pub fn demo_mode_enabled() -> bool {
+    env::var("DEMO_MODE")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+struct Sandbox {
+    state: Arc<AppState>,
+    router: Router,
+    /// Last request routed to the sandbox.
+    last_seen: Instant,
+}
+
+#[derive(Clone)]
+pub struct DemoHub {
+    uri: String,
+    base_db: String,
+    build: fn(Arc<AppState>) -> Router,
+    max_sandboxes: usize,
+    idle_ttl: Option<Duration>,
+    sandboxes: Arc<Mutex<HashMap<String, Sandbox>>>,
+}
+
+impl DemoHub {
+    pub fn new(uri: &str, base_db: &str, build: fn(Arc<AppState>) -> Router) -> Self {
+        Self {
+            uri: uri.to_string(),
+            base_db: base_db.to_string(),
+            build,
+            max_sandboxes: DEFAULT_MAX_SANDBOXES,
+            idle_ttl: None,
+            sandboxes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Caps how many sandboxes may live at once.
+    pub fn with_max_sandboxes(mut self, max_sandboxes: usize) -> Self {
+        self.max_sandboxes = max_sandboxes;
+        self
+    }
+
+    /// How long a sandbox may go without requests before the sweep drops it.
+    /// Defaults to the session lifetime.
+    pub fn with_idle_ttl(mut self, idle_ttl: Duration) -> Self {
+        self.idle_ttl = Some(idle_ttl);
+        self
+    }
+
+    pub fn from_env(build: fn(Arc<AppState>) -> Router) -> Self {
+        let uri =
+            env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+        let db_name = env::var("MONGODB_DB").unwrap_or_else(|_| "totp".to_string());
+        let max_sandboxes = env::var("DEMO_MAX_SANDBOXES")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_SANDBOXES);
+        let hub = Self::new(&uri, &db_name, build).with_max_sandboxes(max_sandboxes);
+        match env::var("DEMO_IDLE_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+        {
+            Some(seconds) => hub.with_idle_ttl(Duration::from_secs(seconds)),
+            None => hub,
+        }
+    }
+
+    /// Router that sends every request to the visitor's sandbox.
+    pub fn router(self) -> Router {
+        Router::new().fallback(dispatch).with_state(self)
+    }
+
+    pub async fn sandbox_count(&self) -> usize {
+        self.sandboxes.lock().await.len()
+    }
+
+    /// Drops expired sandboxes every few minutes, so abandoned ones do not
+    /// wait for the next visitor.
+    pub fn spawn_sweeper(&self) {
+        let hub = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                ticker.tick().await;
+                hub.sweep_expired().await;
+            }
+        });
+    }
+
+    /// Creates and seeds a fresh database, registers it and returns the
+    /// sandbox id plus a session token for the seeded demo user. `None` when
+    /// the cap of live sandboxes is reached.
+    async fn provision(&self) -> Result<Option<(String, String)>> {
+        self.sweep_expired().await;
+        if self.sandbox_count().await >= self.max_sandboxes {
+            return Ok(None);
+        }
+
+        let id = Uuid::new_v4().simple().to_string();
+        let db_name = format!("{}_demo_{}", self.base_db, id);
+        let state = Arc::new(init_state_with_db_name(&self.uri, &db_name).await?);
+        let user = state
+            .users
+            .find_one(doc! {})
+            .await?
+            .context("demo seed produced no users")?;
+        let token = create_session(&state, &user.username).await?;
+        let router = (self.build)(state.clone());
+
+        self.sandboxes.lock().await.insert(
+            id.clone(),
+            Sandbox {
+                state,
+                router,
+                last_seen: Instant::now(),
+            },
+        );
+        Ok(Some((id, token)))
+    }
+
+    async fn discard(&self, id: &str) {
+        let removed = self.sandboxes.lock().await.remove(id);
+        if let Some(sandbox) = removed
+            && let Err(err) = drop_database(&sandbox.state).await
+        {
+            eprintln!("failed to drop demo sandbox {id}: {err:?}");
+        }
+    }
+
+    /// Sandboxes outlive their session when the visitor never logs out; drop
+    /// them once nobody has used them for as long as a session lasts. Active
+    /// visitors keep theirs however long ago it was created.
+    async fn sweep_expired(&self) {
+        let ttl = self
+            .idle_ttl
+            .unwrap_or_else(|| Duration::from_secs(session_ttl_seconds()));
+        let expired: Vec<String> = self
+            .sandboxes
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, sandbox)| sandbox.last_seen.elapsed() > ttl)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            self.discard(&id).await;
+        }
+    }
+}
+
+pub async fn dispatch(State(hub): State<DemoHub>, request: Request) -> Response {
+    let known = {
+        let mut sandboxes = hub.sandboxes.lock().await;
+        extract_cookies(request.headers(), DEMO_COOKIE_NAME)
+            .into_iter()
+            .find_map(|id| {
+                let sandbox = sandboxes.get_mut(&id)?;
+                sandbox.last_seen = Instant::now();
+                Some((id, sandbox.router.clone()))
+            })
+    };
+
+    let Some((id, router)) = known else {
+        return enter_demo(&hub, request.method(), request.uri().path()).await;
+    };
+
+    let is_logout = request.method() == Method::POST && request.uri().path() == "/logout";
+    let mut response = match router.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+
+    if is_logout && response.status().is_success() {
+        hub.discard(&id).await;
+        append_cookie(
+            &mut response,
+            &format!("{DEMO_COOKIE_NAME}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0"),
+        );
+    }
+    response
+}
+
+/// Visitor without a sandbox: the form of `/demo` provisions one and signs
+/// in to it; any other request is sent to that page.
+async fn enter_demo(hub: &DemoHub, method: &Method, path: &str) -> Response {
+    if path != DEMO_ENTRY_PATH {
+        return Redirect::to(DEMO_ENTRY_PATH).into_response();
+    }
+    if method != Method::POST {
+        return Html(LANDING_HTML).into_response();
+    }
+
+    let (id, token) = match hub.provision().await {
+        Ok(Some((id, token))) => {
+            println!(
+                "demo sandbox {id} ready ({} active)",
+                hub.sandbox_count().await
+            );
+            (id, token)
+        }
+        Ok(None) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "demo lleno, intenta mas tarde",
+            )
+                .into_response();
+        }
+        Err(err) => {
+            eprintln!("failed to provision demo sandbox: {err:?}");
+            return (StatusCode::SERVICE_UNAVAILABLE, "demo no disponible").into_response();
+        }
+    };
+
+    // The sandbox cookie has no Max-Age: the sweep decides when an idle
+    // sandbox goes, however long ago it was created.
+    let max_age = session_ttl_seconds();
+    let mut response = Redirect::to("/").into_response();
+    append_cookie(
+        &mut response,
+        &format!("{DEMO_COOKIE_NAME}={id}; Path=/; HttpOnly; SameSite=Lax"),
+    );
+    append_cookie(
+        &mut response,
+        &format!(
+            "{SESSION_COOKIE_NAME}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age}"
+        ),
+    );
+    response
+}
+
+fn append_cookie(response: &mut Response, cookie: &str) {
+    if let Ok(value) = HeaderValue::from_str(cookie) {
+        response.headers_mut().append(SET_COOKIE, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_mode_reads_env_flag() {
+        let _guard = crate::session::test_env_lock();
+        unsafe {
+            env::remove_var("DEMO_MODE");
+        }
+        assert!(!demo_mode_enabled());
+        for value in ["1", "true", "YES"] {
+            unsafe {
+                env::set_var("DEMO_MODE", value);
+            }
+            assert!(demo_mode_enabled(), "{value}");
+        }
+        unsafe {
+            env::set_var("DEMO_MODE", "0");
+        }
+        assert!(!demo_mode_enabled());
+        unsafe {
+            env::remove_var("DEMO_MODE");
+        }
+    }
+}
@@ -1 +1,253 @@
 pub use askama::filters::*;
+
+use std::{fmt::Display, future::Future};
+
+use chrono::FixedOffset;
+
+use crate::models::{DateFormat, FormatPreferences};
+
+tokio::task_local! {
+    /// Formatting of the user behind the request being rendered.
+    static FORMAT: FormatPreferences;
+}
+
+/// Runs `future` (a request handler) with `preferences` applied by the
+/// `amount`, `money`, `date` and `human_date` filters of every template it
+/// renders.
+pub async fn with_format<F: Future>(preferences: FormatPreferences, future: F) -> F::Output {
+    FORMAT.scope(preferences, future).await
+}
+
+pub(crate) fn current_format() -> FormatPreferences {
+    FORMAT.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// `1234567.5` -> `1,234,567.50` with `,` for thousands and `.` for decimals.
+pub fn format_amount(value: f64, preferences: &FormatPreferences) -> String {
+    let fixed = format!("{:.2}", value.abs());
+    let (int_part, frac_part) = fixed.split_once('.').unwrap_or((&fixed, "00"));
+    let mut grouped = String::new();
+    for (idx, digit) in int_part.chars().enumerate() {
+        if idx > 0 && (int_part.len() - idx) % 3 == 0 {
+            grouped.push_str(&preferences.thousands_separator);
+        }
+        grouped.push(digit);
+    }
+    let sign = if value < 0.0 && fixed != "0.00" {
+        "-"
+    } else {
+        ""
+    };
+    format!(
+        "{sign}{grouped}{}{frac_part}",
+        preferences.decimal_separator
+    )
+}
+
+/// Rewrites a date that starts with `YYYY-MM-DD`; whatever follows (a time)
+/// is kept. Anything else comes back unchanged.
+pub fn format_date(value: &str, preferences: &FormatPreferences) -> String {
+    let Some(date) = value
+        .get(..10)
+        .and_then(|prefix| chrono::NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok())
+    else {
+        return value.to_string();
+    };
+    let pattern = match preferences.date_format {
+        DateFormat::Iso => return value.to_string(),
+        DateFormat::DayMonthYear => "%d/%m/%Y",
+        DateFormat::MonthDayYear => "%m/%d/%Y",
+    };
+    let rest = &value[10..];
+    format!(
+        "{}{}",
+        date.format(pattern),
+        rest.strip_prefix('T')
+            .map_or(rest.to_string(), |time| format!(" {time}"))
+    )
+}
+
+/// Symbol written before amounts in `currency`, for the codes that have one.
+pub fn currency_symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "MXN" | "USD" | "CAD" | "AUD" | "ARS" | "CLP" | "COP" => Some("$"),
+        "EUR" => Some("€"),
+        "GBP" => Some("£"),
+        "JPY" | "CNY" => Some("¥"),
+        "BRL" => Some("R$"),
+        _ => None,
+    }
+}
+
+/// `-1234.5` in `mxn` -> `-$1,234.50 MXN`: the amount as `format_amount`,
+/// the currency symbol before it and the code after, since `$` alone does
+/// not tell pesos from dollars. Without a currency only the amount is left.
+pub fn format_money(value: f64, currency: &str, preferences: &FormatPreferences) -> String {
+    let amount = format_amount(value, preferences);
+    let code = currency.trim().to_ascii_uppercase();
+    if code.is_empty() {
+        return amount;
+    }
+    let (sign, digits) = match amount.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", amount.as_str()),
+    };
+    let symbol = currency_symbol(&code).unwrap_or_default();
+    format!("{sign}{symbol}{digits} {code}")
+}
+
+/// Offset times are shown in; UTC when unset or unreadable.
+fn display_offset(preferences: &FormatPreferences) -> FixedOffset {
+    preferences
+        .utc_offset
+        .parse()
+        .unwrap_or_else(|_| FixedOffset::east_opt(0).expect("zero offset"))
+}
+
+/// An RFC 3339 timestamp (`2024-01-31T15:30:00Z`) moved to the user's offset
+/// and written as their date plus `HH:MM`. Anything else goes through
+/// `format_date`.
+pub fn format_human_date(value: &str, preferences: &FormatPreferences) -> String {
+    let Ok(moment) = chrono::DateTime::parse_from_rfc3339(value.trim()) else {
+        return format_date(value, preferences);
+    };
+    let local = moment.with_timezone(&display_offset(preferences));
+    format!(
+        "{} {}",
+        format_date(&local.format("%Y-%m-%d").to_string(), preferences),
+        local.format("%H:%M")
+    )
+}
+
+/// Amount with two decimals and the user's separators. Values that are not
+/// numbers are shown as they are.
+pub fn amount<T: Display>(value: T, _: &dyn askama::Values) -> askama::Result<String> {
+    let text = value.to_string();
+    Ok(match text.trim().parse::<f64>() {
+        Ok(amount) => format_amount(amount, &current_format()),
+        Err(_) => text,
+    })
+}
+
+/// Amount in `currency`, with its symbol and code: `{{ total|money(currency) }}`.
+/// Values that are not numbers are shown as they are.
+pub fn money<T: Display, C: Display>(
+    value: T,
+    _: &dyn askama::Values,
+    currency: C,
+) -> askama::Result<String> {
+    let text = value.to_string();
+    Ok(match text.trim().parse::<f64>() {
+        Ok(amount) => format_money(amount, &currency.to_string(), &current_format()),
+        Err(_) => text,
+    })
+}
+
+/// Date in the user's preferred layout.
+pub fn date<T: Display>(value: T, _: &dyn askama::Values) -> askama::Result<String> {
+    Ok(format_date(&value.to_string(), &current_format()))
+}
+
+/// Timestamp in the user's date layout and time zone.
+pub fn human_date<T: Display>(value: T, _: &dyn askama::Values) -> askama::Result<String> {
+    Ok(format_human_date(&value.to_string(), &current_format()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn european() -> FormatPreferences {
+        FormatPreferences {
+            decimal_separator: ",".into(),
+            thousands_separator: ".".into(),
+            date_format: DateFormat::DayMonthYear,
+            utc_offset: "+01:00".into(),
+        }
+    }
+
+    #[test]
+    fn amounts_use_the_separators() {
+        let plain = FormatPreferences::default();
+        assert_eq!(format_amount(1234567.5, &plain), "1234567.50");
+        assert_eq!(format_amount(1234567.5, &european()), "1.234.567,50");
+        assert_eq!(format_amount(-999.999, &european()), "-1.000,00");
+        assert_eq!(format_amount(-0.001, &european()), "0,00");
+        assert_eq!(format_amount(12.0, &european()), "12,00");
+    }
+
+    #[test]
+    fn money_shows_symbol_and_code() {
+        let mexican = FormatPreferences {
+            thousands_separator: ",".into(),
+            ..FormatPreferences::default()
+        };
+        assert_eq!(format_money(1234.5, "mxn", &mexican), "$1,234.50 MXN");
+        assert_eq!(format_money(-1234.5, "EUR", &european()), "-€1.234,50 EUR");
+        assert_eq!(format_money(10.0, "CHF", &mexican), "10.00 CHF");
+        assert_eq!(format_money(10.0, " ", &mexican), "10.00");
+    }
+
+    #[test]
+    fn timestamps_move_to_the_user_offset() {
+        let mexico = FormatPreferences {
+            utc_offset: "-06:00".into(),
+            ..FormatPreferences::default()
+        };
+        assert_eq!(
+            format_human_date("2024-02-01T03:15:00Z", &mexico),
+            "2024-01-31 21:15"
+        );
+        assert_eq!(
+            format_human_date("2024-01-31T23:30:00.000Z", &european()),
+            "01/02/2024 00:30"
+        );
+        assert_eq!(
+            format_human_date("2024-01-31T09:30:00Z", &FormatPreferences::default()),
+            "2024-01-31 09:30"
+        );
+        assert_eq!(format_human_date("2024-01-31", &european()), "31/01/2024");
+        assert_eq!(format_human_date("Nunca", &mexico), "Nunca");
+    }
+
+    #[test]
+    fn dates_keep_time_and_ignore_other_text() {
+        let us = FormatPreferences {
+            date_format: DateFormat::MonthDayYear,
+            ..FormatPreferences::default()
+        };
+        assert_eq!(format_date("2024-01-31", &european()), "31/01/2024");
+        assert_eq!(format_date("2024-01-31T09:30", &us), "01/31/2024 09:30");
+        assert_eq!(
+            format_date("2024-01-31 09:30", &FormatPreferences::default()),
+            "2024-01-31 09:30"
+        );
+        assert_eq!(format_date("Sin fecha", &european()), "Sin fecha");
+    }
+
+    #[tokio::test]
+    async fn filters_read_the_request_preferences() {
+        let values = askama::NO_VALUES;
+        assert_eq!(amount(1500.0, values).unwrap(), "1500.00");
+        let rendered = with_format(european(), async {
+            (
+                amount(1500.0, values).unwrap(),
+                amount("n/a", values).unwrap(),
+                date("2024-01-31", values).unwrap(),
+                money(1500.0, values, "USD").unwrap(),
+                human_date("2024-01-31T09:30:00Z", values).unwrap(),
+            )
+        })
+        .await;
+        assert_eq!(
+            rendered,
+            (
+                "1.500,00".into(),
+                "n/a".into(),
+                "31/01/2024".into(),
+                "$1.500,00 USD".into(),
+                "31/01/2024 10:30".into()
+            )
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// Flash messages: a one-off banner ("Cuenta creada.") for the page an action
+// redirects to. Handlers return a `Flash` next to their redirect; the session
+// middleware stores it on the session and hands it, once, to the next page
+// the browser opens, where `layouts/base.html` shows it.
+
+use std::convert::Infallible;
+
+use axum::response::{IntoResponseParts, ResponseParts};
+
+pub use crate::models::Flash;
+
+tokio::task_local! {
+    /// Message taken from the session for the page being rendered.
+    static FLASH: Option<Flash>;
+}
+
+/// Runs `future` (a request handler) with `flash` available to the templates
+/// it renders.
+pub async fn with_flash<F: Future>(flash: Option<Flash>, future: F) -> F::Output {
+    FLASH.scope(flash, future).await
+}
+
+/// The message to show on this page, if any. Called from the base layout.
+pub fn current_flash() -> Option<Flash> {
+    FLASH.try_with(Clone::clone).ok().flatten()
+}
+
+/// `(Flash::success("..."), Redirect::to(...))` leaves the message for the
+/// page the redirect leads to.
+impl IntoResponseParts for Flash {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.extensions_mut().insert(self);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::{IntoResponse, Redirect};
+
+    #[tokio::test]
+    async fn flash_is_read_inside_the_scope_only() {
+        let flash = Flash::success("Cuenta creada.");
+        let seen = with_flash(Some(flash.clone()), async { current_flash() }).await;
+        assert_eq!(seen, Some(flash));
+        assert_eq!(current_flash(), None);
+    }
+
+    #[test]
+    fn flash_travels_with_the_redirect() {
+        let response = (Flash::error("No se pudo borrar."), Redirect::to("/")).into_response();
+        let flash = response.extensions().get::<Flash>().unwrap();
+        assert!(flash.is_error());
+        assert_eq!(flash.message, "No se pudo borrar.");
+    }
+}
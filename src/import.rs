@@ -0,0 +1,880 @@
+// import.rs
+// Bank statements exported by other systems (OFX, QIF or CSV): detects the
+// format from the file contents and reads its movements as plain statement
+// lines, ready to be recorded as transactions of one account. CSV files can
+// also be read with an explicit column mapping, for banks whose headers are
+// not recognized. Also reads the spreadsheets of recurring plans companies
+// bring from Excel.
+
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+
+use crate::models::{CsvMapping, DateOrder, DecimalMark};
+
+/// File formats accepted as bank statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementFormat {
+    Ofx,
+    Qif,
+    Csv,
+}
+
+impl StatementFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatementFormat::Ofx => "OFX",
+            StatementFormat::Qif => "QIF",
+            StatementFormat::Csv => "CSV",
+        }
+    }
+}
+
+/// One movement of the statement. `amount` is signed from the account's
+/// point of view: positive money came in, negative money went out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub description: String,
+    /// Bank reference of the movement (OFX `FITID`, QIF check number).
+    pub reference: Option<String>,
+}
+
+/// Reads the text of a statement file. Files that are not UTF-8 are taken
+/// as Latin-1, which is what older bank portals export.
+pub fn decode_statement(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// Guesses the format from the contents, not the file name.
+pub fn detect_format(text: &str) -> Option<StatementFormat> {
+    let start = text.trim_start();
+    let upper = start
+        .chars()
+        .take(2048)
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if upper.starts_with("OFXHEADER") || upper.contains("<OFX>") {
+        return Some(StatementFormat::Ofx);
+    }
+    if upper.starts_with("!TYPE:") || upper.starts_with("!ACCOUNT") || upper.starts_with("!OPTION")
+    {
+        return Some(StatementFormat::Qif);
+    }
+    let header = start.lines().next()?;
+    csv_columns(header).map(|_| StatementFormat::Csv)
+}
+
+/// Detects the format and parses the movements of a statement file.
+pub fn parse_statement(bytes: &[u8]) -> Result<(StatementFormat, Vec<StatementLine>)> {
+    let text = decode_statement(bytes);
+    let format = detect_format(&text).context("unrecognized statement format")?;
+    let lines = match format {
+        StatementFormat::Ofx => parse_ofx(&text)?,
+        StatementFormat::Qif => parse_qif(&text)?,
+        StatementFormat::Csv => parse_csv(&text)?,
+    };
+    if lines.is_empty() {
+        bail!("statement has no movements");
+    }
+    Ok((format, lines))
+}
+
+/// Parses an amount as banks write it: optional currency sign, parentheses
+/// for negatives and either `1,234.56` or `1.234,56`. With a single kind of
+/// separator, a group of three digits after the last one is read as
+/// thousands.
+fn parse_amount(raw: &str) -> Option<f64> {
+    parse_amount_with(raw, None)
+}
+
+/// `parse_amount` with the decimal separator given instead of guessed; the
+/// other one is then taken as thousands.
+fn parse_amount_with(raw: &str, decimal: Option<char>) -> Option<f64> {
+    let cleaned: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '$' | ' ' | '\u{a0}'))
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+    let (negative, digits) = match cleaned.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => (true, inner.to_string()),
+        None => (false, cleaned),
+    };
+    let decimal = match (decimal, digits.rfind('.'), digits.rfind(',')) {
+        (Some(mark), _, _) => Some(mark),
+        (None, Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (None, Some(pos), None) | (None, None, Some(pos)) => {
+            let separator = digits[pos..].chars().next()?;
+            let decimals = digits.len() - pos - 1;
+            (digits.matches(separator).count() == 1 && decimals != 3).then_some(separator)
+        }
+        (None, None, None) => None,
+    };
+    let normalized: String = digits
+        .chars()
+        .filter_map(|c| match c {
+            '.' | ',' if Some(c) == decimal => Some('.'),
+            '.' | ',' => None,
+            c => Some(c),
+        })
+        .collect();
+    let value: f64 = normalized.parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+// OFX
+
+/// Value of `<TAG>` inside an OFX block. SGML files (OFX 1.x) leave leaf
+/// tags unclosed, so the value runs up to the next tag or line end.
+fn ofx_value(block: &str, tag: &str) -> Option<String> {
+    let upper = block.to_ascii_uppercase();
+    let open = format!("<{tag}>");
+    let start = upper.find(&open)? + open.len();
+    let rest = &block[start..];
+    let end = rest.find(['<', '\n', '\r']).unwrap_or(rest.len());
+    let value = decode_entities(rest[..end].trim());
+    (!value.is_empty()).then_some(value)
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Reads `YYYYMMDD[HHMMSS[.XXX]][TZ]`; only the day matters.
+fn parse_ofx_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw.get(..8)?, "%Y%m%d").ok()
+}
+
+/// Parses the `<STMTTRN>` records of an OFX 1.x (SGML) or 2.x (XML) file.
+pub fn parse_ofx(text: &str) -> Result<Vec<StatementLine>> {
+    let upper = text.to_ascii_uppercase();
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = upper[offset..].find("<STMTTRN>") {
+        let start = offset + found + "<STMTTRN>".len();
+        let end = upper[start..]
+            .find("</STMTTRN>")
+            .or_else(|| upper[start..].find("<STMTTRN>"))
+            .map(|pos| start + pos)
+            .unwrap_or(text.len());
+        let block = &text[start..end];
+        offset = end;
+
+        let date = ofx_value(block, "DTPOSTED")
+            .as_deref()
+            .and_then(parse_ofx_date)
+            .context("OFX movement without a valid DTPOSTED")?;
+        let amount = ofx_value(block, "TRNAMT")
+            .as_deref()
+            .and_then(parse_amount)
+            .context("OFX movement without a valid TRNAMT")?;
+        let name = ofx_value(block, "NAME");
+        let memo = ofx_value(block, "MEMO");
+        let description = match (name, memo) {
+            (Some(name), Some(memo)) if memo != name => format!("{name} · {memo}"),
+            (Some(name), _) => name,
+            (None, Some(memo)) => memo,
+            (None, None) => String::new(),
+        };
+        let reference = ofx_value(block, "FITID").or_else(|| ofx_value(block, "CHECKNUM"));
+        lines.push(StatementLine {
+            date,
+            amount,
+            description,
+            reference,
+        });
+    }
+    Ok(lines)
+}
+
+// QIF
+
+/// Splits a QIF or CSV date into its three numbers, accepting `/`, `-`, `.`
+/// and Quicken's `'` before short years. ISO dates (year first) come back in
+/// the same `(day, month, year)` order as the others.
+fn date_parts(raw: &str) -> Option<(u32, u32, i32)> {
+    let parts: Vec<&str> = raw
+        .trim()
+        .split(['/', '-', '.', '\''])
+        .map(str::trim)
+        .collect();
+    let [a, b, c] = parts.as_slice() else {
+        return None;
+    };
+    if a.len() == 4 {
+        // ISO order: year first.
+        return Some((c.parse().ok()?, b.parse().ok()?, a.parse().ok()?));
+    }
+    let year: i32 = c.parse().ok()?;
+    let year = if c.len() <= 2 { 2000 + year } else { year };
+    Some((a.parse().ok()?, b.parse().ok()?, year))
+}
+
+/// `date_parts` of a value, with whether it is ISO (year first).
+fn dated_parts(value: &str) -> Option<(u32, u32, i32, bool)> {
+    let iso = value.trim().split(['/', '-', '.']).next().map(str::len) == Some(4);
+    date_parts(value).map(|(a, b, y)| (a, b, y, iso))
+}
+
+/// Day/month order of a file, taken from all its dates: any first number
+/// above 12 means day first, any second number above 12 means month first,
+/// and otherwise day first as in Mexico. ISO dates do not count.
+fn month_comes_first(parts: &[(u32, u32, i32, bool)]) -> bool {
+    !parts.iter().any(|p| !p.3 && p.0 > 12) && parts.iter().any(|p| !p.3 && p.1 > 12)
+}
+
+fn build_date((a, b, year, iso): (u32, u32, i32, bool), month_first: bool) -> Option<NaiveDate> {
+    let (day, month) = if iso || !month_first { (a, b) } else { (b, a) };
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Builds the dates of a file once its day/month order is known (see
+/// `month_comes_first`). ISO dates keep their own order.
+fn resolve_dates(raw: &[&str]) -> Result<Vec<NaiveDate>> {
+    let parts: Vec<(u32, u32, i32, bool)> = raw
+        .iter()
+        .map(|value| dated_parts(value).with_context(|| format!("invalid date {value:?}")))
+        .collect::<Result<_>>()?;
+    let month_first = month_comes_first(&parts);
+    parts
+        .into_iter()
+        .zip(raw)
+        .map(|(parts, value)| {
+            build_date(parts, month_first).with_context(|| format!("invalid date {value:?}"))
+        })
+        .collect()
+}
+
+/// Parses the records of a QIF file (`D` date, `T`/`U` amount, `P` payee,
+/// `M` memo, `N` number, `^` end of record). Account and category lists are
+/// ignored.
+pub fn parse_qif(text: &str) -> Result<Vec<StatementLine>> {
+    struct Record {
+        date: String,
+        amount: f64,
+        description: String,
+        reference: Option<String>,
+    }
+
+    let mut records = Vec::new();
+    let mut in_transactions = true;
+    let (mut date, mut amount, mut payee, mut memo, mut number) = (
+        None::<String>,
+        None::<f64>,
+        None::<String>,
+        None::<String>,
+        None::<String>,
+    );
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('!') {
+            let header = header.to_ascii_lowercase();
+            in_transactions = [
+                "type:bank",
+                "type:cash",
+                "type:ccard",
+                "type:oth a",
+                "type:oth l",
+            ]
+            .iter()
+            .any(|kind| header.starts_with(kind));
+            continue;
+        }
+        if !in_transactions {
+            continue;
+        }
+        let (code, value) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+        let value = value.trim();
+        match code {
+            "D" => date = Some(value.to_string()),
+            "T" | "U" => amount = amount.or(parse_amount(value)),
+            "P" => payee = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "M" => memo = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "N" => number = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "^" => {
+                let (Some(record_date), Some(record_amount)) = (date.take(), amount.take()) else {
+                    bail!("QIF record without date or amount");
+                };
+                let description = match (payee.take(), memo.take()) {
+                    (Some(payee), Some(memo)) if memo != payee => format!("{payee} · {memo}"),
+                    (Some(payee), _) => payee,
+                    (None, Some(memo)) => memo,
+                    (None, None) => String::new(),
+                };
+                records.push(Record {
+                    date: record_date,
+                    amount: record_amount,
+                    description,
+                    reference: number.take(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let raw_dates: Vec<&str> = records.iter().map(|r| r.date.as_str()).collect();
+    let dates = resolve_dates(&raw_dates)?;
+    Ok(records
+        .into_iter()
+        .zip(dates)
+        .map(|(record, date)| StatementLine {
+            date,
+            amount: record.amount,
+            description: record.description,
+            reference: record.reference,
+        })
+        .collect())
+}
+
+// CSV
+
+/// The delimiter a CSV header row uses most.
+fn detect_delimiter(header: &str) -> Option<char> {
+    [';', ',', '\t']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d).count())
+        .filter(|d| header.contains(*d))
+}
+
+fn normalize_header(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches('"')
+        .to_lowercase()
+        .replace(['á', 'à'], "a")
+        .replace(['é', 'è'], "e")
+        .replace('í', "i")
+        .replace('ó', "o")
+        .replace('ú', "u")
+}
+
+/// Recognizes the header row of a CSV statement, in Spanish or English.
+fn csv_columns(header: &str) -> Option<CsvMapping> {
+    let delimiter = detect_delimiter(header)?;
+    let names: Vec<String> = split_csv_row(header, delimiter)
+        .iter()
+        .map(|name| normalize_header(name))
+        .collect();
+    mapping_from_names(&names, delimiter)
+}
+
+/// Finds the date, description and amount columns (one signed amount, or
+/// separate withdrawals and deposits) by their normalized names.
+fn mapping_from_names(names: &[String], delimiter: char) -> Option<CsvMapping> {
+    let find = |candidates: &[&str]| {
+        names
+            .iter()
+            .position(|name| candidates.iter().any(|c| name == c))
+    };
+    let date = find(&["fecha", "fecha operacion", "date"])?;
+    let description = find(&["descripcion", "concepto", "description", "memo", "detalle"])?;
+    let (amount, debit, credit) = match find(&["monto", "importe", "amount"]) {
+        Some(column) => (Some(column), None, None),
+        None => (
+            None,
+            Some(find(&["cargo", "cargos", "retiro", "retiros", "debit"])?),
+            Some(find(&[
+                "abono",
+                "abonos",
+                "deposito",
+                "depositos",
+                "credit",
+            ])?),
+        ),
+    };
+    Some(CsvMapping {
+        delimiter,
+        date,
+        description,
+        amount,
+        debit,
+        credit,
+        reference: find(&["referencia", "reference", "folio"]),
+        date_order: DateOrder::Auto,
+        decimal_mark: DecimalMark::Auto,
+        negate: false,
+    })
+}
+
+/// Splits one CSV row, honoring double quotes and `""` escapes.
+fn split_csv_row(row: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+/// A CSV file split into cells, before deciding what each column holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvSheet {
+    pub delimiter: char,
+    /// Column names as written.
+    pub header: Vec<String>,
+    /// Data rows with the line of the file each is on, counting the header
+    /// as 1. Blank lines are skipped.
+    pub rows: Vec<(usize, Vec<String>)>,
+}
+
+impl CsvSheet {
+    /// Splits the text of a CSV file on `delimiter`, or on the one its header
+    /// row uses.
+    pub fn read(text: &str, delimiter: Option<char>) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, header) = lines.next().context("CSV file is empty")?;
+        let delimiter = delimiter
+            .or_else(|| detect_delimiter(header))
+            .context("CSV header without a delimiter")?;
+        let rows: Vec<(usize, Vec<String>)> = lines
+            .map(|(index, row)| (index + 1, split_csv_row(row, delimiter)))
+            .collect();
+        if rows.is_empty() {
+            bail!("CSV file has no rows");
+        }
+        Ok(CsvSheet {
+            delimiter,
+            header: split_csv_row(header, delimiter)
+                .into_iter()
+                .map(|name| name.trim().trim_matches('"').to_string())
+                .collect(),
+            rows,
+        })
+    }
+
+    /// Column names trimmed, lowercased and without accents, to recognize
+    /// other files of the same bank.
+    pub fn header_key(&self) -> Vec<String> {
+        self.header
+            .iter()
+            .map(|name| normalize_header(name))
+            .collect()
+    }
+
+    /// The mapping the column names give, when they are recognized.
+    pub fn detect_mapping(&self) -> Option<CsvMapping> {
+        mapping_from_names(&self.header_key(), self.delimiter)
+    }
+
+    /// Starting point for a header that is not recognized: the first three
+    /// columns as date, description and signed amount.
+    pub fn default_mapping(&self) -> CsvMapping {
+        let last = self.header.len().saturating_sub(1);
+        CsvMapping {
+            delimiter: self.delimiter,
+            date: 0,
+            description: last.min(1),
+            amount: Some(last.min(2)),
+            debit: None,
+            credit: None,
+            reference: None,
+            date_order: DateOrder::Auto,
+            decimal_mark: DecimalMark::Auto,
+            negate: false,
+        }
+    }
+}
+
+/// How one data row of a CSV statement reads with a mapping.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvRowOutcome {
+    Line(StatementLine),
+    /// Rows without an amount are balances or subtotals.
+    Skipped,
+    /// What is wrong with the row.
+    Invalid(String),
+}
+
+/// Reads each data row of `sheet` with `mapping`, in order. With
+/// `DateOrder::Auto` the day/month order is taken from the whole file.
+pub fn map_csv_rows(sheet: &CsvSheet, mapping: &CsvMapping) -> Vec<CsvRowOutcome> {
+    fn cell(fields: &[String], index: usize) -> &str {
+        fields.get(index).map(|v| v.trim()).unwrap_or_default()
+    }
+    let decimal = mapping.decimal_mark.separator();
+    let read_amount = |fields: &[String], index: usize| -> Result<Option<f64>, String> {
+        match cell(fields, index) {
+            "" => Ok(None),
+            raw => parse_amount_with(raw, decimal)
+                .map(Some)
+                .ok_or_else(|| format!("monto {raw:?} no es un número")),
+        }
+    };
+    let row_amount = |fields: &[String]| -> Result<Option<f64>, String> {
+        if let Some(column) = mapping.amount {
+            let amount = read_amount(fields, column)?;
+            return Ok(amount.map(|amount| if mapping.negate { -amount } else { amount }));
+        }
+        let out = mapping.debit.map(|c| read_amount(fields, c)).transpose()?;
+        let inn = mapping.credit.map(|c| read_amount(fields, c)).transpose()?;
+        Ok(match (out.flatten(), inn.flatten()) {
+            (None, None) => None,
+            (out, inn) => Some(inn.unwrap_or(0.0) - out.unwrap_or(0.0)).filter(|v| *v != 0.0),
+        })
+    };
+
+    let amounts: Vec<Result<Option<f64>, String>> = sheet
+        .rows
+        .iter()
+        .map(|(_, fields)| row_amount(fields))
+        .collect();
+    let dates: Vec<Option<(u32, u32, i32, bool)>> = sheet
+        .rows
+        .iter()
+        .map(|(_, fields)| dated_parts(cell(fields, mapping.date)))
+        .collect();
+    let month_first = match mapping.date_order {
+        DateOrder::Auto => {
+            let known: Vec<_> = amounts
+                .iter()
+                .zip(&dates)
+                .filter(|(amount, _)| matches!(amount, Ok(Some(_))))
+                .filter_map(|(_, parts)| *parts)
+                .collect();
+            month_comes_first(&known)
+        }
+        DateOrder::DayFirst => false,
+        DateOrder::MonthFirst => true,
+    };
+
+    sheet
+        .rows
+        .iter()
+        .zip(amounts)
+        .zip(dates)
+        .map(|(((_, fields), amount), parts)| {
+            let amount = match amount {
+                Ok(Some(amount)) => amount,
+                Ok(None) => return CsvRowOutcome::Skipped,
+                Err(message) => return CsvRowOutcome::Invalid(message),
+            };
+            let Some(date) = parts.and_then(|parts| build_date(parts, month_first)) else {
+                return CsvRowOutcome::Invalid(format!(
+                    "fecha {:?} no es válida",
+                    cell(fields, mapping.date)
+                ));
+            };
+            CsvRowOutcome::Line(StatementLine {
+                date,
+                amount,
+                description: cell(fields, mapping.description).to_string(),
+                reference: mapping
+                    .reference
+                    .map(|column| cell(fields, column))
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// The movements of `sheet` read with `mapping`; fails on the first row that
+/// cannot be read.
+pub fn csv_statement_lines(sheet: &CsvSheet, mapping: &CsvMapping) -> Result<Vec<StatementLine>> {
+    let mut lines = Vec::new();
+    for ((line, _), outcome) in sheet.rows.iter().zip(map_csv_rows(sheet, mapping)) {
+        match outcome {
+            CsvRowOutcome::Line(statement_line) => lines.push(statement_line),
+            CsvRowOutcome::Skipped => {}
+            CsvRowOutcome::Invalid(message) => bail!("line {line}: {message}"),
+        }
+    }
+    Ok(lines)
+}
+
+/// Parses a CSV statement with a header row naming its date, description and
+/// amount columns (one signed amount, or separate withdrawals and deposits).
+pub fn parse_csv(text: &str) -> Result<Vec<StatementLine>> {
+    let sheet = CsvSheet::read(text, None)?;
+    let mapping = sheet
+        .detect_mapping()
+        .context("CSV header without date, description and amount")?;
+    csv_statement_lines(&sheet, &mapping)
+}
+
+// Recurring plan spreadsheets
+
+/// One data row of a recurring plan spreadsheet, as written. References and
+/// values are checked when the plans are imported, so each row can report
+/// its own errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlanSheetRow {
+    /// Line of the file the row is on, counting the header as 1.
+    pub line: usize,
+    pub name: String,
+    pub flow: String,
+    pub category: String,
+    pub account: String,
+    pub amount: String,
+    pub frequency: String,
+    pub day: String,
+    pub start: String,
+}
+
+struct PlanSheetColumns {
+    delimiter: char,
+    name: usize,
+    flow: usize,
+    category: usize,
+    account: usize,
+    amount: usize,
+    frequency: usize,
+    day: Option<usize>,
+    start: usize,
+}
+
+/// Recognizes the header row of a plan spreadsheet, in Spanish or English.
+fn plan_sheet_columns(header: &str) -> Option<PlanSheetColumns> {
+    let delimiter = detect_delimiter(header)?;
+    let names: Vec<String> = split_csv_row(header, delimiter)
+        .iter()
+        .map(|name| normalize_header(name))
+        .collect();
+    let find = |candidates: &[&str]| {
+        names
+            .iter()
+            .position(|name| candidates.iter().any(|c| name == c))
+    };
+    Some(PlanSheetColumns {
+        delimiter,
+        name: find(&["nombre", "name", "concepto"])?,
+        flow: find(&["flujo", "tipo", "flow", "flow_type", "type"])?,
+        category: find(&["categoria", "category"])?,
+        account: find(&["cuenta", "account"])?,
+        amount: find(&["monto", "importe", "amount"])?,
+        frequency: find(&["frecuencia", "frequency"])?,
+        day: find(&["dia", "dia del mes", "day", "day_of_month"]),
+        start: find(&[
+            "inicio",
+            "fecha inicio",
+            "fecha de inicio",
+            "start",
+            "start_date",
+        ])?,
+    })
+}
+
+/// Reads the rows of a CSV of recurring plans. The header names the columns
+/// (name, flow, category, account, amount, frequency, optional day of month
+/// and start date) in any order; blank lines are skipped.
+pub fn parse_plan_sheet(bytes: &[u8]) -> Result<Vec<PlanSheetRow>> {
+    let text = decode_statement(bytes);
+    let mut rows = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = rows.next().context("plan sheet is empty")?;
+    let columns = plan_sheet_columns(header).context(
+        "plan sheet header without name, flow, category, account, amount, frequency and start",
+    )?;
+    let plans: Vec<PlanSheetRow> = rows
+        .map(|(index, row)| {
+            let fields = split_csv_row(row, columns.delimiter);
+            let field = |index: usize| {
+                fields
+                    .get(index)
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default()
+            };
+            PlanSheetRow {
+                line: index + 1,
+                name: field(columns.name),
+                flow: field(columns.flow),
+                category: field(columns.category),
+                account: field(columns.account),
+                amount: field(columns.amount),
+                frequency: field(columns.frequency),
+                day: columns.day.map(field).unwrap_or_default(),
+                start: field(columns.start),
+            }
+        })
+        .collect();
+    if plans.is_empty() {
+        bail!("plan sheet has no rows");
+    }
+    Ok(plans)
+}
+
+/// Amount of a spreadsheet cell, written as in a bank statement.
+pub fn parse_sheet_amount(raw: &str) -> Option<f64> {
+    parse_amount(raw)
+}
+
+/// Date of a spreadsheet cell: ISO, or day first unless the second number
+/// can only be a day.
+pub fn parse_sheet_date(raw: &str) -> Option<NaiveDate> {
+    resolve_dates(&[raw]).ok()?.pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    const OFX_SGML: &str = "OFXHEADER:100\r\nDATA:OFXSGML\r\nVERSION:102\r\n\r\n<OFX>\r\n<BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>\r\n<STMTTRN>\r\n<TRNTYPE>DEBIT\r\n<DTPOSTED>20240305120000[-6:CST]\r\n<TRNAMT>-1,250.50\r\n<FITID>A001\r\n<NAME>CFE SUMINISTRO\r\n<MEMO>PAGO DE SERVICIO\r\n</STMTTRN>\r\n<STMTTRN>\r\n<TRNTYPE>CREDIT\r\n<DTPOSTED>20240306\r\n<TRNAMT>5000.00\r\n<FITID>A002\r\n<MEMO>DEPOSITO CLIENTE &amp; CIA\r\n</STMTTRN>\r\n</BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1>\r\n</OFX>\r\n";
+
+    #[test]
+    fn ofx_sgml_movements_are_read() {
+        let (format, lines) = parse_statement(OFX_SGML.as_bytes()).unwrap();
+        assert_eq!(format, StatementFormat::Ofx);
+        assert_eq!(
+            lines,
+            vec![
+                StatementLine {
+                    date: day(2024, 3, 5),
+                    amount: -1250.5,
+                    description: "CFE SUMINISTRO · PAGO DE SERVICIO".into(),
+                    reference: Some("A001".into()),
+                },
+                StatementLine {
+                    date: day(2024, 3, 6),
+                    amount: 5000.0,
+                    description: "DEPOSITO CLIENTE & CIA".into(),
+                    reference: Some("A002".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ofx_xml_closes_its_tags() {
+        let text = "<?xml version=\"1.0\"?><?OFX OFXHEADER=\"200\"?><OFX><STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240110</DTPOSTED><TRNAMT>-99.9</TRNAMT><FITID>X1</FITID><NAME>OXXO</NAME></STMTTRN></OFX>";
+        let (format, lines) = parse_statement(text.as_bytes()).unwrap();
+        assert_eq!(format, StatementFormat::Ofx);
+        assert_eq!(lines[0].date, day(2024, 1, 10));
+        assert_eq!(lines[0].amount, -99.9);
+        assert_eq!(lines[0].description, "OXXO");
+    }
+
+    #[test]
+    fn qif_day_month_order_is_taken_from_the_file() {
+        let text = "!Type:Bank\nD03/04/2024\nT-200.00\nPRenta\nN15\n^\nD25/04/2024\nT1,000.00\nMTransferencia\n^\n";
+        let (format, lines) = parse_statement(text.as_bytes()).unwrap();
+        assert_eq!(format, StatementFormat::Qif);
+        assert_eq!(lines[0].date, day(2024, 4, 3));
+        assert_eq!(lines[0].amount, -200.0);
+        assert_eq!(lines[0].reference.as_deref(), Some("15"));
+        assert_eq!(lines[1].date, day(2024, 4, 25));
+        assert_eq!(lines[1].description, "Transferencia");
+
+        let us = "!Type:CCard\nD4/25'24\nT-12.5\nPCafe\n^\nD4/3'24\nT-1\n^\n";
+        let lines = parse_qif(us).unwrap();
+        assert_eq!(lines[0].date, day(2024, 4, 25));
+        assert_eq!(lines[1].date, day(2024, 4, 3));
+    }
+
+    #[test]
+    fn csv_with_split_amount_columns_and_latin1() {
+        let mut bytes = b"Fecha;Descripci".to_vec();
+        bytes.push(0xF3); // "ó" in Latin-1
+        bytes.extend_from_slice(
+            b"n;Cargo;Abono;Saldo\n05/03/2024;\"Pago; luz\";350,00;;1000\n06/03/2024;Dep\xF3sito;;1.200,50;\n07/03/2024;Saldo final;;;2000\n",
+        );
+        let (format, lines) = parse_statement(&bytes).unwrap();
+        assert_eq!(format, StatementFormat::Csv);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].description, "Pago; luz");
+        assert_eq!(lines[0].amount, -350.0);
+        assert_eq!(lines[1].description, "Depósito");
+        assert_eq!(lines[1].date, day(2024, 3, 6));
+    }
+
+    #[test]
+    fn csv_rows_follow_an_explicit_mapping() {
+        let text = "Fec.;Movimiento;Importe MXN;Ref\n03/14/2024;Renta;1.250,00;R-1\n03/15/2024;Saldo;;\n13/15/2024;Luz;abc;\n";
+        let sheet = CsvSheet::read(text, None).unwrap();
+        assert_eq!(sheet.delimiter, ';');
+        assert_eq!(sheet.header_key()[0], "fec.");
+        assert!(sheet.detect_mapping().is_none());
+        let mapping = CsvMapping {
+            reference: Some(3),
+            date_order: DateOrder::MonthFirst,
+            decimal_mark: DecimalMark::Comma,
+            negate: true,
+            ..sheet.default_mapping()
+        };
+        let rows = map_csv_rows(&sheet, &mapping);
+        assert_eq!(
+            rows[0],
+            CsvRowOutcome::Line(StatementLine {
+                date: day(2024, 3, 14),
+                amount: -1250.0,
+                description: "Renta".into(),
+                reference: Some("R-1".into()),
+            })
+        );
+        assert_eq!(rows[1], CsvRowOutcome::Skipped);
+        assert!(matches!(&rows[2], CsvRowOutcome::Invalid(message) if message.contains("abc")));
+        assert!(csv_statement_lines(&sheet, &mapping).is_err());
+    }
+
+    #[test]
+    fn unknown_files_are_rejected() {
+        assert!(parse_statement(b"hola mundo").is_err());
+        assert!(detect_format("fecha,monto\n").is_none());
+    }
+
+    #[test]
+    fn plan_sheet_columns_are_found_by_name() {
+        let text = "Frecuencia;Nombre;Tipo;Categoría;Cuenta;Monto;Día;Inicio\n\nmensual;Renta oficina;gasto;Renta;Banco;\"12,500.00\";5;01/02/2025\nsemanal;Nómina;gasto;Sueldos;Banco;8000;;2025-02-07\n";
+        let rows = parse_plan_sheet(text.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            PlanSheetRow {
+                line: 3,
+                name: "Renta oficina".into(),
+                flow: "gasto".into(),
+                category: "Renta".into(),
+                account: "Banco".into(),
+                amount: "12,500.00".into(),
+                frequency: "mensual".into(),
+                day: "5".into(),
+                start: "01/02/2025".into(),
+            }
+        );
+        assert_eq!(rows[1].line, 4);
+        assert_eq!(rows[1].day, "");
+        assert_eq!(parse_sheet_amount(&rows[0].amount), Some(12500.0));
+        assert_eq!(parse_sheet_date(&rows[0].start), Some(day(2025, 2, 1)));
+        assert_eq!(parse_sheet_date(&rows[1].start), Some(day(2025, 2, 7)));
+        assert_eq!(parse_sheet_date("02/25/2025"), Some(day(2025, 2, 25)));
+
+        assert!(parse_plan_sheet(b"nombre,monto\nRenta,100\n").is_err());
+        assert!(
+            parse_plan_sheet(b"nombre,tipo,categoria,cuenta,monto,frecuencia,inicio\n").is_err()
+        );
+    }
+}
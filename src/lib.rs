@@ -1,8 +1,24 @@
+#[cfg(all(feature = "dev-factory", not(debug_assertions)))]
+compile_error!("the `dev-factory` feature is only for debug builds");
+
+pub mod bank_sync;
 pub mod cfdi;
+pub mod demo;
 pub mod filters;
+pub mod flash;
+pub mod import;
+pub mod mailer;
+pub mod metrics;
 pub mod models;
+pub mod notifier;
+pub mod ocr;
+pub mod oidc;
 pub mod routes;
 pub mod sat;
 pub mod session;
 pub mod state;
+pub mod template_context;
+#[cfg(feature = "test-harness")]
+pub mod test_harness;
 pub mod totp;
+pub mod uploads;
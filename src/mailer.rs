@@ -0,0 +1,112 @@
+// mailer.rs
+// Outgoing email: staff and portal sign-in links, email-change confirmations
+// and the notification emails of `chat_notifications` go through the `Mailer`
+// kept in `AppState`. `MAIL_API_URL` points it at an HTTP
+// mail relay; without one nothing is sent. Message bodies carry one-time
+// tokens, so they are never written to the log.
+
+use std::{env, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use serde_json::json;
+
+/// One plain-text message to one recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+}
+
+/// Something that delivers an email.
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>>;
+}
+
+fn read_env(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// HTTP mail relay. `MAIL_API_URL` enables it and receives a JSON
+/// `{ from, to, subject, text }` per message; `MAIL_API_TOKEN`, when set, is
+/// sent as a bearer token and `MAIL_FROM` is the sender address.
+#[derive(Debug, Clone)]
+pub struct HttpMailer {
+    pub api_url: String,
+    pub from: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl HttpMailer {
+    pub fn new(api_url: String, from: String, token: Option<String>) -> Self {
+        Self {
+            api_url,
+            from,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `None` when no relay is configured.
+    pub fn from_env() -> Option<Self> {
+        Some(Self::new(
+            read_env("MAIL_API_URL")?,
+            read_env("MAIL_FROM").unwrap_or_else(|| "no-reply@localhost".to_string()),
+            read_env("MAIL_API_TOKEN"),
+        ))
+    }
+}
+
+impl Mailer for HttpMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut request = self.client.post(&self.api_url).json(&json!({
+                "from": self.from,
+                "to": email.to,
+                "subject": email.subject,
+                "text": email.text,
+            }));
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().await.context("mail request failed")?;
+            if !response.status().is_success() {
+                bail!("mail relay answered {}", response.status());
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Used when no relay is configured: every send fails, so callers report the
+/// message as not delivered instead of losing it silently.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisabledMailer;
+
+impl Mailer for DisabledMailer {
+    fn send<'a>(&'a self, _email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { bail!("no mail transport configured (MAIL_API_URL)") })
+    }
+}
+
+/// The relay configured in the environment, or `DisabledMailer`.
+pub fn mailer_from_env() -> Arc<dyn Mailer> {
+    match HttpMailer::from_env() {
+        Some(mailer) => Arc::new(mailer),
+        None => Arc::new(DisabledMailer),
+    }
+}
+
+/// Absolute link to `path` for an email, under `APP_URL` (such as
+/// `https://app.example.com`). Without it the bare path is used.
+pub fn app_link(path: &str) -> String {
+    match read_env("APP_URL") {
+        Some(base) => format!("{}{path}", base.trim_end_matches('/')),
+        None => path.to_string(),
+    }
+}
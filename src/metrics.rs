@@ -0,0 +1,632 @@
+//! Process-wide service metrics, served at `/metrics` in the Prometheus text
+//! format.
+//!
+//! Counters and histograms live in one registry shared by the whole process:
+//! the request middleware, the MongoDB command monitor, the login handlers,
+//! the planned entry generator and the background tasks all write to it. Labels come from a small
+//! known set (route templates, command names), never from user input, so the
+//! number of series stays bounded.
+//!
+//! The command monitor also keeps the last slow commands: collection, filter
+//! shape with the values blanked out, and duration, for the superadmin page
+//! at `/admin/slow_queries`.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env,
+    fmt::Write,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use mongodb::{
+    bson::{Bson, Document},
+    event::{EventHandler, command::CommandEvent},
+};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+
+/// Route label of requests that matched no route.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Slow commands kept for the superadmin page; older ones are dropped.
+const SLOW_QUERY_LOG_SIZE: usize = 200;
+
+/// Commands started and not yet finished the monitor remembers. A command
+/// whose outcome never arrives is forgotten once this many pile up.
+const MAX_PENDING_COMMANDS: usize = 10_000;
+
+/// Default of `SLOW_QUERY_MS`.
+const DEFAULT_SLOW_QUERY_MS: u64 = 500;
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last slot is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let slot = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[slot] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+/// Last runs of a background task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskRun {
+    pub last_run_at: SystemTime,
+    /// `None` until a run succeeds.
+    pub last_success_at: Option<SystemTime>,
+}
+
+/// A MongoDB command that took longer than the slow query threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowQuery {
+    pub at: SystemTime,
+    /// `find`, `aggregate`, `update`...
+    pub command: String,
+    pub collection: String,
+    /// Filter (or pipeline) with every value replaced by `?`, e.g.
+    /// `{ "company_id": "?", "due_date": { "$lt": "?" } }`.
+    pub shape: String,
+    pub duration: Duration,
+    pub failed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// By method, route template and status code.
+    http_requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// By method and route template.
+    http_durations: Mutex<BTreeMap<(String, String), Histogram>>,
+    /// By command name and outcome.
+    mongo_durations: Mutex<BTreeMap<(String, &'static str), Histogram>>,
+    login_successes: AtomicU64,
+    login_failures: AtomicU64,
+    planned_entries_generated: AtomicU64,
+    /// By task name.
+    task_runs: Mutex<BTreeMap<&'static str, TaskRun>>,
+    /// Newest last.
+    slow_queries: Mutex<VecDeque<SlowQuery>>,
+    slow_queries_total: AtomicU64,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// The process-wide registry.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    pub fn record_http_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut requests = self.http_requests.lock().unwrap();
+        *requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+        drop(requests);
+        self.http_durations
+            .lock()
+            .unwrap()
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn record_mongo_command(&self, command: &str, succeeded: bool, elapsed: Duration) {
+        let outcome = if succeeded { "ok" } else { "error" };
+        self.mongo_durations
+            .lock()
+            .unwrap()
+            .entry((command.to_string(), outcome))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn record_slow_query(&self, query: SlowQuery) {
+        self.slow_queries_total.fetch_add(1, Ordering::Relaxed);
+        let mut log = self.slow_queries.lock().unwrap();
+        if log.len() == SLOW_QUERY_LOG_SIZE {
+            log.pop_front();
+        }
+        log.push_back(query);
+    }
+
+    /// The slow commands kept, newest first.
+    pub fn slow_queries(&self) -> Vec<SlowQuery> {
+        self.slow_queries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    /// A TOTP or SSO login: `true` when it opened a session.
+    pub fn record_login(&self, succeeded: bool) {
+        let counter = if succeeded {
+            &self.login_successes
+        } else {
+            &self.login_failures
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_planned_entries_generated(&self, count: u64) {
+        self.planned_entries_generated
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// A pass of one of the background tasks spawned at startup.
+    pub fn record_task_run(&self, task: &'static str, succeeded: bool) {
+        let now = SystemTime::now();
+        let mut runs = self.task_runs.lock().unwrap();
+        let run = runs.entry(task).or_insert(TaskRun {
+            last_run_at: now,
+            last_success_at: None,
+        });
+        run.last_run_at = now;
+        if succeeded {
+            run.last_success_at = Some(now);
+        }
+    }
+
+    /// Last runs of every background task that has run, by name.
+    pub fn task_runs(&self) -> Vec<(&'static str, TaskRun)> {
+        self.task_runs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(task, run)| (*task, *run))
+            .collect()
+    }
+
+    /// Everything recorded so far, in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total HTTP requests handled, by route and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route, status), count) in self.http_requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {count}",
+                escape_label(route)
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds Time to answer an HTTP request.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((method, route), histogram) in self.http_durations.lock().unwrap().iter() {
+            let labels = format!("method=\"{method}\",route=\"{}\"", escape_label(route));
+            histogram.render(&mut out, "http_request_duration_seconds", &labels);
+        }
+
+        out.push_str(
+            "# HELP mongodb_command_duration_seconds Time MongoDB took to run a command.\n",
+        );
+        out.push_str("# TYPE mongodb_command_duration_seconds histogram\n");
+        for ((command, outcome), histogram) in self.mongo_durations.lock().unwrap().iter() {
+            let labels = format!(
+                "command=\"{}\",outcome=\"{outcome}\"",
+                escape_label(command)
+            );
+            histogram.render(&mut out, "mongodb_command_duration_seconds", &labels);
+        }
+
+        out.push_str(
+            "# HELP mongodb_slow_commands_total MongoDB commands slower than SLOW_QUERY_MS.\n",
+        );
+        out.push_str("# TYPE mongodb_slow_commands_total counter\n");
+        let _ = writeln!(
+            out,
+            "mongodb_slow_commands_total {}",
+            self.slow_queries_total.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP logins_total Login attempts of known users, by outcome.\n");
+        out.push_str("# TYPE logins_total counter\n");
+        let _ = writeln!(
+            out,
+            "logins_total{{outcome=\"success\"}} {}",
+            self.login_successes.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "logins_total{{outcome=\"failure\"}} {}",
+            self.login_failures.load(Ordering::Relaxed)
+        );
+
+        out.push_str(
+            "# HELP planned_entries_generated_total Planned entries generated from recurring plans.\n",
+        );
+        out.push_str("# TYPE planned_entries_generated_total counter\n");
+        let _ = writeln!(
+            out,
+            "planned_entries_generated_total {}",
+            self.planned_entries_generated.load(Ordering::Relaxed)
+        );
+
+        out.push_str(
+            "# HELP background_task_last_run_timestamp_seconds When a background task last ran.\n",
+        );
+        out.push_str("# TYPE background_task_last_run_timestamp_seconds gauge\n");
+        let runs = self.task_runs();
+        for (task, run) in &runs {
+            let _ = writeln!(
+                out,
+                "background_task_last_run_timestamp_seconds{{task=\"{task}\"}} {}",
+                unix_seconds(run.last_run_at)
+            );
+        }
+        out.push_str(
+            "# HELP background_task_last_success_timestamp_seconds When a background task last succeeded.\n",
+        );
+        out.push_str("# TYPE background_task_last_success_timestamp_seconds gauge\n");
+        for (task, run) in &runs {
+            if let Some(at) = run.last_success_at {
+                let _ = writeln!(
+                    out,
+                    "background_task_last_success_timestamp_seconds{{task=\"{task}\"}} {}",
+                    unix_seconds(at)
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn unix_seconds(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Middleware counting every request and timing it, labelled with the route
+/// template (`/admin/transactions/{id}`) rather than the concrete path.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().as_str().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics().record_http_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
+}
+
+/// Commands slower than `SLOW_QUERY_MS` milliseconds (500 by default) go
+/// to the slow query log; `0` turns it off.
+pub fn slow_query_threshold_from_env() -> Option<Duration> {
+    let millis = env::var("SLOW_QUERY_MS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_SLOW_QUERY_MS);
+    (millis > 0).then(|| Duration::from_millis(millis))
+}
+
+/// MongoDB command monitor feeding `mongodb_command_duration_seconds` and,
+/// with a `slow_threshold`, the slow query log.
+pub fn mongo_command_handler(slow_threshold: Option<Duration>) -> EventHandler<CommandEvent> {
+    // Collection and shape of the commands in flight, by request id; the
+    // outcome events carry only the duration.
+    let pending: Mutex<HashMap<i32, (String, String)>> = Mutex::new(HashMap::new());
+    EventHandler::callback(move |event: CommandEvent| match event {
+        CommandEvent::Started(event) if slow_threshold.is_some() => {
+            let mut pending = pending.lock().unwrap();
+            if pending.len() >= MAX_PENDING_COMMANDS {
+                pending.clear();
+            }
+            pending.insert(
+                event.request_id,
+                (
+                    command_collection(&event.command, &event.command_name),
+                    command_shape(&event.command, &event.command_name),
+                ),
+            );
+        }
+        CommandEvent::Succeeded(event) => {
+            metrics().record_mongo_command(&event.command_name, true, event.duration);
+            finish_command(
+                &pending,
+                slow_threshold,
+                event.request_id,
+                &event.command_name,
+                event.duration,
+                false,
+            );
+        }
+        CommandEvent::Failed(event) => {
+            metrics().record_mongo_command(&event.command_name, false, event.duration);
+            finish_command(
+                &pending,
+                slow_threshold,
+                event.request_id,
+                &event.command_name,
+                event.duration,
+                true,
+            );
+        }
+        _ => {}
+    })
+}
+
+fn finish_command(
+    pending: &Mutex<HashMap<i32, (String, String)>>,
+    slow_threshold: Option<Duration>,
+    request_id: i32,
+    command: &str,
+    duration: Duration,
+    failed: bool,
+) {
+    let Some(threshold) = slow_threshold else {
+        return;
+    };
+    let Some((collection, shape)) = pending.lock().unwrap().remove(&request_id) else {
+        return;
+    };
+    if duration < threshold {
+        return;
+    }
+    eprintln!(
+        "[slow query] {command} {collection} {shape} took {} ms",
+        duration.as_millis()
+    );
+    metrics().record_slow_query(SlowQuery {
+        at: SystemTime::now(),
+        command: command.to_string(),
+        collection,
+        shape,
+        duration,
+        failed,
+    });
+}
+
+/// Collection a command works on: the value of its name (`{ find: "users" }`),
+/// or `collection` for `getMore`.
+fn command_collection(command: &Document, name: &str) -> String {
+    command
+        .get_str(name)
+        .or_else(|_| command.get_str("collection"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Filter of a command with the values blanked out, so commands that differ
+/// only in the ids or dates they look for read the same.
+fn command_shape(command: &Document, name: &str) -> String {
+    let first_statement = |list: &str, field: &str| {
+        command
+            .get_array(list)
+            .ok()
+            .and_then(|statements| statements.first())
+            .and_then(Bson::as_document)
+            .and_then(|statement| statement.get(field))
+            .cloned()
+    };
+    let filter = match name {
+        "find" => command.get("filter").cloned(),
+        "aggregate" => command.get("pipeline").cloned(),
+        "count" | "distinct" | "findAndModify" => command.get("query").cloned(),
+        "update" => first_statement("updates", "q"),
+        "delete" => first_statement("deletes", "q"),
+        _ => None,
+    };
+    filter
+        .map(|filter| blank_values(&filter).to_string())
+        .unwrap_or_default()
+}
+
+/// Keeps field names, operators and pipeline stages; replaces every value
+/// with `?`. The branches of `$and`, `$or` and `$nor` and the stages of a
+/// pipeline keep their own shape.
+fn blank_values(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(document) => Bson::Document(
+            document
+                .iter()
+                .map(|(key, value)| {
+                    let shaped = match value {
+                        Bson::Document(_) => blank_values(value),
+                        Bson::Array(items) if matches!(key.as_str(), "$and" | "$or" | "$nor") => {
+                            Bson::Array(items.iter().map(blank_values).collect())
+                        }
+                        _ => Bson::String("?".to_string()),
+                    };
+                    (key.clone(), shaped)
+                })
+                .collect(),
+        ),
+        Bson::Array(stages) => Bson::Array(stages.iter().map(blank_values).collect()),
+        _ => Bson::String("?".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+    #[test]
+    fn renders_counters_and_cumulative_histograms() {
+        let metrics = Metrics::default();
+        metrics.record_http_request(
+            "GET",
+            "/admin/transactions/{id}",
+            200,
+            Duration::from_millis(3),
+        );
+        metrics.record_http_request(
+            "GET",
+            "/admin/transactions/{id}",
+            200,
+            Duration::from_millis(30),
+        );
+        metrics.record_http_request(
+            "GET",
+            "/admin/transactions/{id}",
+            404,
+            Duration::from_secs(20),
+        );
+        metrics.record_mongo_command("find", true, Duration::from_millis(2));
+        metrics.record_login(true);
+        metrics.record_login(false);
+        metrics.record_login(false);
+        metrics.record_planned_entries_generated(12);
+        metrics.record_task_run("retention", true);
+        metrics.record_task_run("bank_sync", false);
+        metrics.record_slow_query(SlowQuery {
+            at: SystemTime::now(),
+            command: "find".to_string(),
+            collection: "transactions".to_string(),
+            shape: String::new(),
+            duration: Duration::from_secs(1),
+            failed: false,
+        });
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "http_requests_total{method=\"GET\",route=\"/admin/transactions/{id}\",status=\"200\"} 2"
+        ));
+        assert!(text.contains(
+            "http_requests_total{method=\"GET\",route=\"/admin/transactions/{id}\",status=\"404\"} 1"
+        ));
+        let labels = "method=\"GET\",route=\"/admin/transactions/{id}\"";
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{labels},le=\"0.005\"}} 1"
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{labels},le=\"0.05\"}} 2"
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{labels},le=\"10\"}} 2"
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 3"
+        )));
+        assert!(text.contains(&format!(
+            "http_request_duration_seconds_count{{{labels}}} 3"
+        )));
+        assert!(
+            text.contains(
+                "mongodb_command_duration_seconds_count{command=\"find\",outcome=\"ok\"} 1"
+            )
+        );
+        assert!(text.contains("logins_total{outcome=\"success\"} 1"));
+        assert!(text.contains("logins_total{outcome=\"failure\"} 2"));
+        assert!(text.contains("planned_entries_generated_total 12"));
+        assert!(text.contains("background_task_last_run_timestamp_seconds{task=\"bank_sync\"}"));
+        assert!(
+            text.contains("background_task_last_success_timestamp_seconds{task=\"retention\"}")
+        );
+        assert!(
+            !text.contains("background_task_last_success_timestamp_seconds{task=\"bank_sync\"}")
+        );
+        assert!(text.contains("mongodb_slow_commands_total 1"));
+    }
+
+    #[test]
+    fn shapes_keep_fields_and_operators_but_not_values() {
+        let find = doc! {
+            "find": "planned_entries",
+            "filter": {
+                "company_id": ObjectId::new(),
+                "due_date": { "$lt": DateTime::now() },
+                "$or": [{ "status": "planned" }, { "status": "partial" }],
+            },
+        };
+        assert_eq!(command_collection(&find, "find"), "planned_entries");
+        assert_eq!(
+            command_shape(&find, "find"),
+            doc! {
+                "company_id": "?",
+                "due_date": { "$lt": "?" },
+                "$or": [{ "status": "?" }, { "status": "?" }],
+            }
+            .to_string()
+        );
+
+        let aggregate = doc! {
+            "aggregate": "transactions",
+            "pipeline": [
+                { "$match": { "company_id": ObjectId::new(), "type": { "$in": ["income"] } } },
+                { "$group": { "_id": "$category_id", "total": { "$sum": "$amount" } } },
+            ],
+        };
+        assert_eq!(
+            command_shape(&aggregate, "aggregate"),
+            Bson::Array(vec![
+                doc! { "$match": { "company_id": "?", "type": { "$in": "?" } } }.into(),
+                doc! { "$group": { "_id": "?", "total": { "$sum": "?" } } }.into(),
+            ])
+            .to_string()
+        );
+        let get_more = doc! { "getMore": 42_i64, "collection": "users" };
+        assert_eq!(command_collection(&get_more, "getMore"), "users");
+        assert_eq!(command_shape(&get_more, "getMore"), "");
+    }
+
+    #[test]
+    fn slow_query_log_keeps_the_newest() {
+        let metrics = Metrics::default();
+        for idx in 0..SLOW_QUERY_LOG_SIZE + 5 {
+            metrics.record_slow_query(SlowQuery {
+                at: SystemTime::now(),
+                command: "find".to_string(),
+                collection: format!("c{idx}"),
+                shape: String::new(),
+                duration: Duration::from_secs(1),
+                failed: false,
+            });
+        }
+        let log = metrics.slow_queries();
+        assert_eq!(log.len(), SLOW_QUERY_LOG_SIZE);
+        assert_eq!(log[0].collection, format!("c{}", SLOW_QUERY_LOG_SIZE + 4));
+        assert_eq!(log.last().unwrap().collection, "c5");
+    }
+}
@@ -1,7 +1,7 @@
 // models.rs
 // Domain models for auth/multitenancy and finance entities (MongoDB).
 
-use mongodb::bson::{DateTime, oid::ObjectId};
+use mongodb::bson::{DateTime, Document, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 /// ---------- AUTH / PLATFORM LAYER ----------
@@ -12,6 +12,9 @@ use serde::{Deserialize, Serialize};
 pub enum UserRole {
     Admin,
     Staff,
+    /// Sees everything an admin sees and can export it, but every request
+    /// that would change data is turned away by the session middleware.
+    Auditor,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -45,12 +48,27 @@ impl UserRole {
         match self {
             UserRole::Admin => "admin",
             UserRole::Staff => "staff",
+            UserRole::Auditor => "auditor",
         }
     }
 
+    /// Runs the company: changes anything and reads the secrets of its users
+    /// and integrations.
     pub fn is_admin(&self) -> bool {
         matches!(self, UserRole::Admin)
     }
+
+    /// Can open the admin pages. Auditors can too; `is_read_only` keeps them
+    /// from changing anything, and pages that show secrets still ask for
+    /// `is_admin`.
+    pub fn can_view_admin(&self) -> bool {
+        matches!(self, UserRole::Admin | UserRole::Auditor)
+    }
+
+    /// Only reads: the session middleware rejects its writes.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, UserRole::Auditor)
+    }
 }
 
 impl Default for UserRole {
@@ -72,6 +90,9 @@ pub struct SeedUser {
     pub companies: Vec<String>,
     #[serde(default = "UserRole::default_admin")]
     pub role: UserRole,
+    /// Grants the platform-wide settings; see `User::is_superadmin`.
+    #[serde(default)]
+    pub is_superadmin: bool,
 }
 
 /// Company document stored in MongoDB.
@@ -103,441 +124,2424 @@ pub struct Company {
     /// Optional notes / description.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
-}
 
-fn default_true() -> bool {
-    true
+    /// Email domains (lowercase, e.g. "acme.com") whose SSO users are
+    /// provisioned into this company on their first login.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sso_domains: Vec<String>,
+
+    /// Whether the company's staff may sign in with a link sent by email,
+    /// besides or instead of their TOTP code.
+    #[serde(default, skip_serializing_if = "LoginLinkMode::is_off")]
+    pub login_links: LoginLinkMode,
+
+    /// Set when the company was off-boarded; its documents carry the same
+    /// `archived_at` tombstone until the hard deletion at `purge_after`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_after: Option<DateTime>,
+
+    /// Logo, color and footer shown on the company's pages and PDFs.
+    #[serde(default, skip_serializing_if = "CompanyBranding::is_empty")]
+    pub branding: CompanyBranding,
+
+    /// Chat where overdue alerts and the daily digest are pushed.
+    #[serde(default, skip_serializing_if = "ChatNotifications::is_empty")]
+    pub chat_notifications: ChatNotifications,
+
+    /// Channels, events, digest schedule and quiet hours of the company's
+    /// notifications. Unset until saved; until then the chat settings decide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationSettings>,
+
+    /// Progress through the getting-started checklist of the overview.
+    #[serde(default, skip_serializing_if = "CompanyOnboarding::is_empty")]
+    pub onboarding: CompanyOnboarding,
+
+    /// Bytes taken by the company's uploaded files and the most it may use.
+    #[serde(default, skip_serializing_if = "CompanyStorage::is_empty")]
+    pub storage: CompanyStorage,
+
+    /// Endpoint that receives the company's events for other systems.
+    #[serde(default, skip_serializing_if = "CompanyWebhooks::is_empty")]
+    pub webhooks: CompanyWebhooks,
+
+    /// Periods the reports, budgets and forecasts group by.
+    #[serde(default, skip_serializing_if = "FiscalCalendar::is_default")]
+    pub fiscal_calendar: FiscalCalendar,
+
+    /// Optional fields the company's plans and transactions must fill.
+    #[serde(default, skip_serializing_if = "RequiredFieldPolicy::is_empty")]
+    pub required_fields: RequiredFieldPolicy,
 }
 
-fn default_zero() -> f64 {
-    0.0
+/// Sign-in by emailed link for a company's staff. Admins and auditors always
+/// sign in with their TOTP code.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginLinkMode {
+    /// TOTP only.
+    #[default]
+    Off,
+    /// Staff may ask for a link or use their TOTP code.
+    Supplement,
+    /// Staff only sign in with a link; their TOTP code is refused.
+    Alternative,
 }
 
-fn default_mxn() -> String {
-    "MXN".to_string()
+impl LoginLinkMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginLinkMode::Off => "off",
+            LoginLinkMode::Supplement => "supplement",
+            LoginLinkMode::Alternative => "alternative",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(LoginLinkMode::Off),
+            "supplement" => Some(LoginLinkMode::Supplement),
+            "alternative" => Some(LoginLinkMode::Alternative),
+            _ => None,
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        *self == LoginLinkMode::Off
+    }
 }
 
-/// User document stored in MongoDB referencing the company by ObjectId.
-/// Each user belongs to exactly one company (tenant) in this first version.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct User {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
-    /// Login identifier — a unique handle, not a validated email address.
-    /// Accepts the legacy `email` key so docs not yet touched by the rename
-    /// migration still load.
-    #[serde(alias = "email")]
-    pub username: String,
-    pub secret: String,
+/// Look of a company's pages and generated PDFs. Every part is optional;
+/// what is unset falls back to the application's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompanyBranding {
+    /// Stored logo file (PNG or JPEG) and its content type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo_content_type: Option<String>,
+    /// Accent color as `#rrggbb`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Line printed at the bottom of pages and PDFs (address, RFC, phone).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub footer_text: Option<String>,
+}
 
-    /// Primary tenant the user belongs to (for compatibility).
-    #[serde(rename = "company", skip_serializing_if = "Option::is_none")]
-    pub company_id: Option<ObjectId>,
+impl CompanyBranding {
+    pub fn is_empty(&self) -> bool {
+        self.logo_path.is_none() && self.color.is_none() && self.footer_text.is_none()
+    }
+}
 
-    /// All companies the user can access.
-    #[serde(rename = "companies", default)]
-    pub company_ids: Vec<ObjectId>,
+/// Getting-started steps a company has done. A step stays done even if its
+/// records are deleted later, and the checklist is gone once all are done.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompanyOnboarding {
+    /// Keys of the steps done so far (see `OnboardingStep::as_str`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completed_steps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime>,
 }
 
-/// User-company membership with per-company role.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UserCompany {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
-    pub user_id: ObjectId,
-    pub company_id: ObjectId,
-    pub role: UserRole,
+impl CompanyOnboarding {
+    pub fn is_empty(&self) -> bool {
+        self.completed_steps.is_empty() && self.completed_at.is_none()
+    }
+}
 
+/// Disk space of a company's receipts, logo and SAT certificates. The
+/// total is kept up to date as files are stored and removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompanyStorage {
     #[serde(default)]
-    pub permissions: Vec<UserPermission>,
+    pub used_bytes: i64,
+    /// Set by a superadmin; no limit when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<i64>,
 }
 
-/// Session document stored in MongoDB linking a token to a user and expiry.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Session {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
-    pub token: String,
-    pub user_email: String,
-    pub expires_at: DateTime,
+impl CompanyStorage {
+    pub fn is_empty(&self) -> bool {
+        self.used_bytes == 0 && self.quota_bytes.is_none()
+    }
 }
 
-/// ---------- SHARED ENUMS FOR FINANCE DOMAIN ----------
-
-/// Basic income/expense kind used by categories, recurring plans, planned entries.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum FlowType {
-    Income,
-    Expense,
+/// Messaging service a company's alerts are pushed through.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatChannel {
+    Telegram,
+    Whatsapp,
 }
 
-impl FlowType {
+impl ChatChannel {
     pub fn as_str(&self) -> &'static str {
         match self {
-            FlowType::Income => "income",
-            FlowType::Expense => "expense",
+            ChatChannel::Telegram => "telegram",
+            ChatChannel::Whatsapp => "whatsapp",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "telegram" => Some(ChatChannel::Telegram),
+            "whatsapp" => Some(ChatChannel::Whatsapp),
+            _ => None,
         }
     }
 }
 
-/// Account type (bank, cash, etc.).
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Chat a company's alerts go to, which of them it wants, and how far the
+/// notification task has got.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatNotifications {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<ChatChannel>,
+    /// Telegram chat id, or the WhatsApp number with country code.
+    #[serde(default)]
+    pub chat_id: String,
+    #[serde(default)]
+    pub overdue_alerts: bool,
+    #[serde(default)]
+    pub daily_digest: bool,
+    /// Categories crossing 80% or 100% of their monthly budget.
+    #[serde(default)]
+    pub budget_alerts: bool,
+    /// Planned entries due before this instant were already alerted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overdue_checked_at: Option<DateTime>,
+    /// UTC day (`YYYY-MM-DD`) of the last digest sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_digest_on: Option<String>,
+}
+
+impl ChatNotifications {
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_none() && self.chat_id.is_empty()
+    }
+
+    /// Channel and chat to push to, when both are set.
+    pub fn target(&self) -> Option<(ChatChannel, &str)> {
+        let chat_id = self.chat_id.trim();
+        Some((self.channel?, chat_id)).filter(|_| !chat_id.is_empty())
+    }
+}
+
+/// How often a company's digest goes out.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum AccountType {
-    Bank,
-    Cash,
-    CreditCard,
-    Investment,
-    Other,
+pub enum DigestFrequency {
+    #[default]
+    Never,
+    Daily,
+    Weekly,
 }
 
-impl AccountType {
+impl DigestFrequency {
     pub fn as_str(&self) -> &'static str {
         match self {
-            AccountType::Bank => "bank",
-            AccountType::Cash => "cash",
-            AccountType::CreditCard => "credit_card",
-            AccountType::Investment => "investment",
-            AccountType::Other => "other",
+            DigestFrequency::Never => "never",
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "never" => Some(DigestFrequency::Never),
+            "daily" => Some(DigestFrequency::Daily),
+            "weekly" => Some(DigestFrequency::Weekly),
+            _ => None,
         }
     }
 }
 
-/// Transaction type: income, expense or internal transfer.
+/// Where a company's alerts and digest are delivered, which of them it
+/// wants, when the digest goes out and when nothing should be sent. Hours
+/// are in the company's `utc_offset`.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum TransactionType {
-    Income,
-    Expense,
-    Transfer,
+pub struct NotificationSettings {
+    /// Send to `email_recipients`.
+    #[serde(default)]
+    pub email: bool,
+    #[serde(default)]
+    pub email_recipients: Vec<String>,
+    /// Send to the company's webhook URL.
+    #[serde(default)]
+    pub webhook: bool,
+    /// Send to the chat of `chat_notifications` (Telegram or WhatsApp).
+    #[serde(default)]
+    pub chat: bool,
+    #[serde(default)]
+    pub overdue_alerts: bool,
+    #[serde(default)]
+    pub budget_alerts: bool,
+    #[serde(default)]
+    pub digest: DigestFrequency,
+    /// Hour of the day the digest goes out from, 0 to 23.
+    #[serde(default)]
+    pub digest_hour: u32,
+    /// Day of the weekly digest, 0 for Monday to 6 for Sunday.
+    #[serde(default)]
+    pub digest_weekday: u32,
+    /// Offset from UTC the hours are in, as `-06:00`; empty for UTC.
+    #[serde(default)]
+    pub utc_offset: String,
+    /// Hours with no messages, from `start` up to `end`, which may wrap
+    /// past midnight (22 to 7). What falls due then waits until they end.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
 }
 
-impl TransactionType {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            TransactionType::Income => "income",
-            TransactionType::Expense => "expense",
-            TransactionType::Transfer => "transfer",
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            email: false,
+            email_recipients: Vec::new(),
+            webhook: false,
+            chat: false,
+            overdue_alerts: false,
+            budget_alerts: false,
+            digest: DigestFrequency::Never,
+            digest_hour: 7,
+            digest_weekday: 0,
+            utc_offset: "-06:00".to_string(),
+            quiet_hours: None,
         }
     }
 }
 
-/// Contact type (customer, supplier, service, etc.).
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum ContactType {
-    Customer,
-    Supplier,
-    Service,
-    Other,
+/// Start and end hour, 0 to 23, of a company's quiet hours.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
 }
 
-impl ContactType {
+impl QuietHours {
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+/// URL a company's events are POSTed to as signed JSON, which of them it
+/// wants, and how the last delivery went.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompanyWebhooks {
+    #[serde(default)]
+    pub url: String,
+    /// Key of the `X-Webhook-Signature` HMAC, generated when the URL is
+    /// first saved.
+    #[serde(default)]
+    pub secret: String,
+    /// Send `forecast.completed` when forecasts are generated.
+    #[serde(default)]
+    pub forecast_completed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery_at: Option<DateTime>,
+    /// Why the last delivery failed; unset when it went through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl CompanyWebhooks {
+    pub fn is_empty(&self) -> bool {
+        self.url.is_empty() && self.secret.is_empty()
+    }
+}
+
+/// How a company's fiscal year is cut into twelve reporting periods.
+/// `Months` uses calendar months; the week patterns give each quarter
+/// 13 weeks split as named (4-4-5 is two 4-week periods and a 5-week one).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FiscalPattern {
+    #[default]
+    #[serde(rename = "months")]
+    Months,
+    #[serde(rename = "4-4-5")]
+    Weeks445,
+    #[serde(rename = "4-5-4")]
+    Weeks454,
+    #[serde(rename = "5-4-4")]
+    Weeks544,
+}
+
+impl FiscalPattern {
+    pub const ALL: [FiscalPattern; 4] = [
+        FiscalPattern::Months,
+        FiscalPattern::Weeks445,
+        FiscalPattern::Weeks454,
+        FiscalPattern::Weeks544,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
-            ContactType::Customer => "customer",
-            ContactType::Supplier => "supplier",
-            ContactType::Service => "service",
-            ContactType::Other => "other",
+            FiscalPattern::Months => "months",
+            FiscalPattern::Weeks445 => "4-4-5",
+            FiscalPattern::Weeks454 => "4-5-4",
+            FiscalPattern::Weeks544 => "5-4-4",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FiscalPattern::Months => "Meses de calendario",
+            FiscalPattern::Weeks445 => "Semanas 4-4-5",
+            FiscalPattern::Weeks454 => "Semanas 4-5-4",
+            FiscalPattern::Weeks544 => "Semanas 5-4-4",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pattern| pattern.as_str() == value)
+    }
+
+    /// Weeks of the three periods of each quarter; `None` for calendar months.
+    pub fn quarter_weeks(&self) -> Option<[u32; 3]> {
+        match self {
+            FiscalPattern::Months => None,
+            FiscalPattern::Weeks445 => Some([4, 4, 5]),
+            FiscalPattern::Weeks454 => Some([4, 5, 4]),
+            FiscalPattern::Weeks544 => Some([5, 4, 4]),
         }
     }
 }
 
-/// Status of a planned entry (commitment/budget item).
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A company's fiscal calendar. The fiscal year is named after the calendar
+/// year it starts in. With calendar months it starts on the 1st of
+/// `start_month`; with a week pattern, on the first `start_weekday` of
+/// `start_month`, so some years have 53 weeks and the extra one goes to the
+/// last period.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FiscalCalendar {
+    #[serde(default)]
+    pub pattern: FiscalPattern,
+    /// 1 (January) to 12.
+    #[serde(default = "default_fiscal_start_month")]
+    pub start_month: u32,
+    /// Days from Monday (0) to Sunday (6); only used by the week patterns.
+    #[serde(default)]
+    pub start_weekday: u32,
+}
+
+fn default_fiscal_start_month() -> u32 {
+    1
+}
+
+impl Default for FiscalCalendar {
+    fn default() -> Self {
+        FiscalCalendar {
+            pattern: FiscalPattern::Months,
+            start_month: 1,
+            start_weekday: 0,
+        }
+    }
+}
+
+impl FiscalCalendar {
+    /// Calendar months from January, what companies get without a setting.
+    pub fn is_default(&self) -> bool {
+        self.pattern == FiscalPattern::Months && self.start_month == 1
+    }
+}
+
+/// Optional field of a plan or transaction a company can make mandatory.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum PlannedStatus {
-    Planned,
-    PartiallyCovered,
-    Covered,
-    Overdue,
-    Cancelled,
+pub enum RequiredField {
+    Contact,
+    Notes,
+    /// Recurring plans only.
+    EndDate,
+    /// Transactions only: the planned entry the movement covers.
+    PlannedEntry,
 }
 
-impl PlannedStatus {
+impl RequiredField {
+    pub const ALL: [RequiredField; 4] = [
+        RequiredField::Contact,
+        RequiredField::Notes,
+        RequiredField::EndDate,
+        RequiredField::PlannedEntry,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.as_str() == value)
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
-            PlannedStatus::Planned => "planned",
-            PlannedStatus::PartiallyCovered => "partially_covered",
-            PlannedStatus::Covered => "covered",
-            PlannedStatus::Overdue => "overdue",
-            PlannedStatus::Cancelled => "cancelled",
+            RequiredField::Contact => "contact",
+            RequiredField::Notes => "notes",
+            RequiredField::EndDate => "end_date",
+            RequiredField::PlannedEntry => "planned_entry",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RequiredField::Contact => "Contacto",
+            RequiredField::Notes => "Notas",
+            RequiredField::EndDate => "Fecha de término",
+            RequiredField::PlannedEntry => "Compromiso ligado",
         }
     }
 }
 
-/// ---------- FINANCE ENTITIES (SCOPED BY COMPANY/TENANT) ----------
+/// Records a required-field policy applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequiredFieldTarget {
+    Plans,
+    Transactions,
+}
 
-/// Financial account (bank account, cash, credit card, etc.).
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Account {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
+impl RequiredFieldTarget {
+    pub const ALL: [RequiredFieldTarget; 2] =
+        [RequiredFieldTarget::Plans, RequiredFieldTarget::Transactions];
 
-    /// Tenant this account belongs to.
-    pub company_id: ObjectId,
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequiredFieldTarget::Plans => "plans",
+            RequiredFieldTarget::Transactions => "transactions",
+        }
+    }
 
-    pub name: String,
-    pub account_type: AccountType,
+    /// Plural name used in messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            RequiredFieldTarget::Plans => "planes",
+            RequiredFieldTarget::Transactions => "movimientos",
+        }
+    }
 
-    /// ISO-like currency code, e.g. "MXN", "USD".
-    pub currency: String,
+    /// Fields the forms and the API of these records take.
+    pub fn fields(&self) -> &'static [RequiredField] {
+        match self {
+            RequiredFieldTarget::Plans => &[
+                RequiredField::Contact,
+                RequiredField::Notes,
+                RequiredField::EndDate,
+            ],
+            RequiredFieldTarget::Transactions => {
+                &[RequiredField::Notes, RequiredField::PlannedEntry]
+            }
+        }
+    }
+}
+
+/// Which records of a target a rule applies to, by flow. Transfers only
+/// match `All`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RequiredFieldScope {
+    #[default]
+    All,
+    Income,
+    Expense,
+}
+
+impl RequiredFieldScope {
+    pub const ALL: [RequiredFieldScope; 3] = [
+        RequiredFieldScope::All,
+        RequiredFieldScope::Income,
+        RequiredFieldScope::Expense,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequiredFieldScope::All => "all",
+            RequiredFieldScope::Income => "income",
+            RequiredFieldScope::Expense => "expense",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RequiredFieldScope::All => "Siempre",
+            RequiredFieldScope::Income => "Solo ingresos",
+            RequiredFieldScope::Expense => "Solo gastos",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// `flow` is `None` for transfers.
+    pub fn applies_to(&self, flow: Option<&FlowType>) -> bool {
+        match self {
+            RequiredFieldScope::All => true,
+            RequiredFieldScope::Income => flow == Some(&FlowType::Income),
+            RequiredFieldScope::Expense => flow == Some(&FlowType::Expense),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequiredFieldRule {
+    pub field: RequiredField,
+    #[serde(default)]
+    pub applies_to: RequiredFieldScope,
+}
+
+/// Fields a company makes mandatory on top of what every plan and
+/// transaction needs, e.g. a contact on every expense plan. At most one rule
+/// per field and target.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequiredFieldPolicy {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plans: Vec<RequiredFieldRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<RequiredFieldRule>,
+}
+
+impl RequiredFieldPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty() && self.transactions.is_empty()
+    }
+
+    pub fn rules(&self, target: RequiredFieldTarget) -> &[RequiredFieldRule] {
+        match target {
+            RequiredFieldTarget::Plans => &self.plans,
+            RequiredFieldTarget::Transactions => &self.transactions,
+        }
+    }
+
+    /// The rule of `target` for `field`, if any.
+    pub fn rule(
+        &self,
+        target: RequiredFieldTarget,
+        field: RequiredField,
+    ) -> Option<RequiredFieldRule> {
+        self.rules(target)
+            .iter()
+            .find(|rule| rule.field == field)
+            .copied()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_zero() -> f64 {
+    0.0
+}
+
+fn default_mxn() -> String {
+    "MXN".to_string()
+}
+
+/// User document stored in MongoDB referencing the company by ObjectId.
+/// Each user belongs to exactly one company (tenant) in this first version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// Login identifier — a unique handle, not a validated email address.
+    /// Accepts the legacy `email` key so docs not yet touched by the rename
+    /// migration still load.
+    #[serde(alias = "email")]
+    pub username: String,
+    pub secret: String,
+
+    /// Primary tenant the user belongs to (for compatibility).
+    #[serde(rename = "company", skip_serializing_if = "Option::is_none")]
+    pub company_id: Option<ObjectId>,
+
+    /// All companies the user can access.
+    #[serde(rename = "companies", default)]
+    pub company_ids: Vec<ObjectId>,
 
+    /// Inactive users cannot log in and their sessions are rejected.
     #[serde(default = "default_true")]
     pub is_active: bool,
 
+    /// How amounts and dates are shown to this user.
+    #[serde(default)]
+    pub format_preferences: FormatPreferences,
+
+    /// Runs the platform rather than a company: may change settings no
+    /// company admin can, such as storage quotas. Set in the seed users file
+    /// or directly in the database; no page grants it.
+    #[serde(default)]
+    pub is_superadmin: bool,
+
+    /// Name shown instead of the username in headers, comments and the
+    /// access log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Stored avatar file (PNG, JPEG or WEBP) and its content type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_content_type: Option<String>,
+}
+
+/// Date layout used when rendering dates.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateFormat {
+    /// 2024-01-31
+    #[default]
+    Iso,
+    /// 31/01/2024
+    DayMonthYear,
+    /// 01/31/2024
+    MonthDayYear,
+}
+
+impl DateFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateFormat::Iso => "iso",
+            DateFormat::DayMonthYear => "day_month_year",
+            DateFormat::MonthDayYear => "month_day_year",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "iso" => Some(DateFormat::Iso),
+            "day_month_year" => Some(DateFormat::DayMonthYear),
+            "month_day_year" => Some(DateFormat::MonthDayYear),
+            _ => None,
+        }
+    }
+}
+
+/// Per-user number and date formatting. The defaults match the plain
+/// rendering used before preferences existed: `1234.50` and `2024-01-31`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FormatPreferences {
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: String,
+    /// Empty means digits are not grouped.
+    #[serde(default)]
+    pub thousands_separator: String,
+    #[serde(default)]
+    pub date_format: DateFormat,
+    /// Offset from UTC dates with a time are shown in, as `-06:00`; empty
+    /// for UTC.
+    #[serde(default)]
+    pub utc_offset: String,
+}
+
+impl Default for FormatPreferences {
+    fn default() -> Self {
+        Self {
+            decimal_separator: default_decimal_separator(),
+            thousands_separator: String::new(),
+            date_format: DateFormat::Iso,
+            utc_offset: String::new(),
+        }
+    }
+}
+
+fn default_decimal_separator() -> String {
+    ".".to_string()
+}
+
+/// User-company membership with per-company role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserCompany {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub company_id: ObjectId,
+    pub role: UserRole,
+
+    #[serde(default)]
+    pub permissions: Vec<UserPermission>,
+
+    /// Accounts the user may see and move money through in this company;
+    /// empty for every account.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_ids: Vec<ObjectId>,
+
+    /// Keys of the widgets the user's dashboard shows in this company, in
+    /// order; `None` for the default layout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dashboard_widgets: Option<Vec<String>>,
+}
+
+/// Session document stored in MongoDB linking a token to a user and expiry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token: String,
+    /// Sessions point at the user id, so renaming the login identifier does
+    /// not invalidate them.
+    pub user_id: ObjectId,
+    pub expires_at: DateTime,
+    /// Message for the next page the user opens, left by the action that
+    /// redirected there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flash: Option<Flash>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashKind {
+    Success,
+    Error,
+}
+
+/// One-off banner shown at the top of the next page, e.g. "Cuenta creada.".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flash {
+    pub kind: FlashKind,
+    pub message: String,
+}
+
+impl Flash {
+    pub fn success(message: impl Into<String>) -> Self {
+        Flash {
+            kind: FlashKind::Success,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Flash {
+            kind: FlashKind::Error,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.kind == FlashKind::Error
+    }
+}
+
+/// Long-lived token of a "remember me" login, sent in its own cookie. While it
+/// lasts, a request without a valid session opens a new one with it. Only the
+/// SHA-256 of the token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub token_hash: String,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// One-time sign-in link of a staff user, sent by email. Only the SHA-256 of
+/// the token is stored; the link is deleted when used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginLink {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub token_hash: String,
+    /// Page to open after signing in, already checked as a return path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    pub expires_at: DateTime,
+}
+
+/// Pending change of a user's login identifier. The new value only replaces
+/// the current one once the token sent to the new address is confirmed. Only
+/// the SHA-256 of the token is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailChange {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    pub new_username: String,
+    pub token_hash: String,
+    pub expires_at: DateTime,
+}
+
+/// One-time sign-in link to the contact portal, sent by email. Only the
+/// SHA-256 of the token is stored; redeeming it deletes it and opens a
+/// `PortalSession`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortalLink {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token_hash: String,
+    pub company_id: ObjectId,
+    pub contact_id: ObjectId,
+    pub expires_at: DateTime,
+}
+
+/// Session of a contact in the portal. Kept apart from user sessions: it only
+/// opens the `/portal` pages, and only for the contact's own records. The
+/// cookie holds the token and only its hash is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortalSession {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub token_hash: String,
+    pub company_id: ObjectId,
+    pub contact_id: ObjectId,
+    pub expires_at: DateTime,
+}
+
+/// Personal access token a user creates for scripts and dashboards. Only the
+/// SHA-256 of the token is stored; the token itself is shown once, when it is
+/// created. It opens the read-only data endpoints and nothing else.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    /// What the user called it, to tell their tokens apart.
+    pub name: String,
+    pub token_hash: String,
+    /// First characters of the token, shown in the list.
+    pub prefix: String,
+    pub created_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime>,
+}
+
+/// What an [`AccessEvent`] records.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessEventKind {
+    /// Login with TOTP or SSO that opened a session.
+    SessionCreated,
+    /// Login of a known user rejected (wrong code or inactive user).
+    LoginFailed,
+    /// Change made by an admin through an `/admin` or `/api/admin` route.
+    AdminAction,
+}
+
+impl AccessEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessEventKind::SessionCreated => "session_created",
+            AccessEventKind::LoginFailed => "login_failed",
+            AccessEventKind::AdminAction => "admin_action",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccessEventKind::SessionCreated => "Inicio de sesión",
+            AccessEventKind::LoginFailed => "Acceso fallido",
+            AccessEventKind::AdminAction => "Acción de administrador",
+        }
+    }
+}
+
+/// Last number handed out by a company numbering sequence. Keyed by
+/// `<company_id>:<kind>:<year>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequenceCounter {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub company_id: ObjectId,
+    /// Reference prefix, e.g. "TX".
+    pub kind: String,
+    pub year: i32,
+    pub value: i64,
+}
+
+/// Schema migration already applied to the database, keyed by its version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    #[serde(rename = "_id")]
+    pub version: i32,
+    pub name: String,
+    pub applied_at: DateTime,
+    /// Documents the migration changed.
+    pub documents: i64,
+}
+
+/// Background index rebuild that finished, kept in `migrations` under a
+/// string id next to the numbered migrations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexRecord {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub started_at: DateTime,
+    pub applied_at: DateTime,
+    /// Indexes built.
+    pub indexes: i64,
+}
+
+/// Entry of the access log shown to company admins on `/admin/security`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessEvent {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// Companies whose admins see the event: every company of the user for
+    /// logins, the active company for admin actions.
+    pub company_ids: Vec<ObjectId>,
+    pub kind: AccessEventKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<ObjectId>,
+    pub username: String,
+    /// Client address from `X-Forwarded-For` / `X-Real-IP`, when a proxy
+    /// sets it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    /// Login method, failure reason, or `METHOD /path` of the admin action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub created_at: DateTime,
+}
+
+/// Identity at an OpenID Connect provider linked to a local user. A user can
+/// log in through any of their linked identities instead of TOTP.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SsoIdentity {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: ObjectId,
+    /// `iss` claim of the provider; together with `subject` it is unique.
+    pub issuer: String,
+    /// `sub` claim: the provider's stable id for the person.
+    pub subject: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub linked_at: DateTime,
+}
+
+/// ---------- SHARED ENUMS FOR FINANCE DOMAIN ----------
+
+/// Basic income/expense kind used by categories, recurring plans, planned entries.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowType {
+    Income,
+    Expense,
+}
+
+impl FlowType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlowType::Income => "income",
+            FlowType::Expense => "expense",
+        }
+    }
+}
+
+/// Account type (bank, cash, etc.).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountType {
+    Bank,
+    Cash,
+    CreditCard,
+    Investment,
+    Other,
+}
+
+impl AccountType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::Bank => "bank",
+            AccountType::Cash => "cash",
+            AccountType::CreditCard => "credit_card",
+            AccountType::Investment => "investment",
+            AccountType::Other => "other",
+        }
+    }
+}
+
+/// Transaction type: income, expense or internal transfer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    Income,
+    Expense,
+    Transfer,
+}
+
+impl TransactionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionType::Income => "income",
+            TransactionType::Expense => "expense",
+            TransactionType::Transfer => "transfer",
+        }
+    }
+}
+
+/// Contact type (customer, supplier, service, etc.).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactType {
+    Customer,
+    Supplier,
+    Service,
+    Other,
+}
+
+impl ContactType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContactType::Customer => "customer",
+            ContactType::Supplier => "supplier",
+            ContactType::Service => "service",
+            ContactType::Other => "other",
+        }
+    }
+}
+
+/// Status of a planned entry (commitment/budget item).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedStatus {
+    Planned,
+    PartiallyCovered,
+    Covered,
+    /// Payments exceed the amount; the excess still needs an adjustment
+    /// entry (see `PlannedEntry::coverage_adjustment`).
+    OverCovered,
+    Overdue,
+    Cancelled,
+}
+
+impl PlannedStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlannedStatus::Planned => "planned",
+            PlannedStatus::PartiallyCovered => "partially_covered",
+            PlannedStatus::Covered => "covered",
+            PlannedStatus::OverCovered => "over_covered",
+            PlannedStatus::Overdue => "overdue",
+            PlannedStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Paid in full, with or without an excess.
+    pub fn is_covered(&self) -> bool {
+        matches!(self, PlannedStatus::Covered | PlannedStatus::OverCovered)
+    }
+}
+
+/// ---------- FINANCE ENTITIES (SCOPED BY COMPANY/TENANT) ----------
+
+/// Financial account (bank account, cash, credit card, etc.).
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Account {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this account belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    pub name: String,
+    pub account_type: AccountType,
+
+    /// ISO-like currency code, e.g. "MXN", "USD".
+    pub currency: String,
+
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Balance the account already had when the company started recording
+    /// its movements here.
+    #[serde(default)]
+    pub opening_balance: f64,
+
+    /// Start of the UTC day the opening balance is as of. Transactions dated
+    /// before it are part of the opening balance and not counted again.
+    /// Without it the opening balance precedes every transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub opening_date: Option<DateTime>,
+
+    /// Every adjustment of the opening balance, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub opening_balance_changes: Vec<OpeningBalanceChange>,
+
+    /// Group the account is listed under; ungrouped accounts go last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub group_id: Option<ObjectId>,
+
+    /// Order within its group, set by dragging the accounts around.
+    #[serde(default)]
+    pub position: i32,
+}
+
+/// Audit entry of an opening balance adjustment: who changed it, when, and
+/// the values before and after.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct OpeningBalanceChange {
+    #[schema(value_type = String, format = DateTime)]
+    pub changed_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub user_id: Option<ObjectId>,
+    pub username: String,
+    pub previous_balance: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub previous_date: Option<DateTime>,
+    pub balance: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub date: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Balance of an account at the start of a UTC day (`as_of`), i.e. the sum of
+/// the confirmed transactions dated before it. Written by the nightly balance
+/// job so historical balances do not replay every transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountBalanceSnapshot {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub account_id: ObjectId,
+    pub as_of: DateTime,
+    pub balance: f64,
+    pub created_at: DateTime,
+}
+
+/// Market value of an investment account on a day, as reported by the
+/// broker. A non-cash adjustment: transactions and balances are untouched and
+/// the gap between the market value and the balance only shows in the net
+/// worth as an unrealized gain (or loss).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountValuation {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub account_id: ObjectId,
+    /// Start of the UTC day the value is as of; one valuation per day.
+    pub as_of: DateTime,
+    pub market_value: f64,
+    /// Balance of the account at the end of that day.
+    pub book_balance: f64,
+    /// `market_value - book_balance`.
+    pub unrealized_gain: f64,
+    /// Change of the unrealized gain since the previous valuation.
+    pub adjustment: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_by: Option<ObjectId>,
+    pub created_at: DateTime,
+}
+
+/// How many transactions of one type used a category with an account. A
+/// summary of the transactions, counted up and down as they are saved, that
+/// the transaction form reads to suggest the account's usual categories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountCategoryUsage {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub account_id: ObjectId,
+    pub category_id: ObjectId,
+    pub transaction_type: TransactionType,
+    pub count: i64,
+    pub last_used_at: DateTime,
+}
+
+/// Confirmed income and expense of one company month (or fiscal period),
+/// per category. A summary of the transactions, dropped whenever one of the
+/// period changes and rebuilt on the next read, that the income statement
+/// reads instead of the transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthlySummary {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    /// Key of the fiscal period: `YYYY-MM` for calendar months, e.g.
+    /// `FY2025-P03` for a week pattern.
+    pub month: String,
+    /// Bounds of the period, so a transaction drops the summary it falls in
+    /// whatever the calendar. Missing on summaries of calendar months built
+    /// before fiscal calendars existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime>,
+    pub lines: Vec<MonthlySummaryLine>,
+    pub built_at: DateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonthlySummaryLine {
+    pub category_id: ObjectId,
+    pub flow_type: FlowType,
+    pub amount: f64,
+}
+
+/// Open banking service an account's movements are pulled from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BankProvider {
+    Belvo,
+}
+
+impl BankProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BankProvider::Belvo => "belvo",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BankProvider::Belvo => "Belvo",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "belvo" => Some(BankProvider::Belvo),
+            _ => None,
+        }
+    }
+}
+
+/// Link between an account and the bank account a provider reads it from.
+/// The sync task pulls its movements into `bank_transactions` for review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankConnection {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub account_id: ObjectId,
+    pub provider: BankProvider,
+    /// Link (Belvo) or consent the provider granted for the bank login.
+    pub external_link_id: String,
+    /// Account inside that link.
+    pub external_account_id: String,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    /// Movements up to this instant were already pulled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_synced_at: Option<DateTime>,
+    /// Why the last pull failed; cleared by the next one that works.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: DateTime,
+}
+
+/// Review state of a movement pulled from a bank.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BankSyncStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+impl BankSyncStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BankSyncStatus::Pending => "pending",
+            BankSyncStatus::Accepted => "accepted",
+            BankSyncStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// Movement pulled from a bank connection, staged until someone accepts it
+/// into the ledger or discards it. Kept after review so the next pull does
+/// not bring it back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedBankTransaction {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub connection_id: ObjectId,
+    pub account_id: ObjectId,
+    /// Id of the movement at the provider, unique per connection.
+    pub external_id: String,
+    pub date: DateTime,
+    pub description: String,
+    /// Positive for deposits, negative for withdrawals.
+    pub amount: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub status: BankSyncStatus,
+    /// Transaction recorded when it was accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<ObjectId>,
+    pub created_at: DateTime,
+}
+
+/// Category for incomes/expenses.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Category {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this category belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    pub name: String,
+    pub flow_type: FlowType,
+
+    /// Optional parent category for hierarchy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub parent_id: Option<ObjectId>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Amount the category may move per calendar month. Crossing 80% and
+    /// 100% of it raises an alert.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget: Option<f64>,
+
+    /// Highest budget threshold crossed this month, so each one is announced
+    /// once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_alert: Option<BudgetAlert>,
+
+    /// Tax profile of the category's movements; without one the company's
+    /// default profile applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub tax_profile_id: Option<ObjectId>,
+}
+
+/// A budget threshold a category crossed in one month.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+pub struct BudgetAlert {
+    /// `YYYY-MM` (UTC).
+    pub month: String,
+    /// Percentage of the budget: 80 or 100.
+    pub threshold: u32,
+    /// Whether the company chat was told.
+    #[serde(default)]
+    pub notified: bool,
+}
+
+/// Contact: customer, supplier, service (CFE, landlord, etc.).
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Contact {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this contact belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    pub name: String,
+    pub contact_type: ContactType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfc: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+
+    /// Payment terms in days ("net 30"): due dates of its planned entries
+    /// default to this many days after they are issued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_terms_days: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Values of the company's custom fields, keyed by `CustomFieldDefinition::key`.
+    #[serde(default, skip_serializing_if = "Document::is_empty")]
+    #[schema(value_type = Object)]
+    pub custom_fields: Document,
+}
+
+/// RecurringPlan: template for recurring income/expense,
+/// e.g. "Electricity CFE every month on day 10, estimated 2000 MXN".
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RecurringPlan {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this plan belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    pub name: String,
+    pub flow_type: FlowType,
+
+    #[schema(value_type = String)]
+    pub category_id: ObjectId,
+    #[schema(value_type = String)]
+    pub account_expected_id: ObjectId,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+
+    pub amount_estimated: f64,
+
+    /// Frequency: usually "monthly", "weekly", "yearly".
+    pub frequency: String,
+
+    /// Day of month (1–31) if frequency is monthly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_of_month: Option<i32>,
+
+    #[schema(value_type = String, format = DateTime)]
+    pub start_date: DateTime,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub end_date: Option<DateTime>,
+
+    /// Whether this recurring plan is active.
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+
+    /// Version number for detecting outdated PlannedEntries.
+    #[serde(default = "default_one")]
+    pub version: i32,
+
+    /// Share of the estimated amount expected in each forecast scenario.
+    /// Only used for income plans; when unset, generation falls back to the
+    /// weights given for the whole run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scenario_weights: Option<ScenarioWeights>,
+
+    /// Chance, in percent, that the income actually arrives. Only used for
+    /// income plans; projections show the amount weighted by it next to the
+    /// nominal one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+
+    /// Due days (UTC midnight) whose entry was deleted by hand. Generation
+    /// never brings them back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<String>)]
+    pub skipped_due_dates: Vec<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl RecurringPlan {
+    /// Share of the estimate expected to arrive: the probability of an
+    /// income plan, or all of it otherwise.
+    pub fn expected_share(&self) -> f64 {
+        match (&self.flow_type, self.probability) {
+            (FlowType::Income, Some(pct)) => pct / 100.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Immutable copy of a recurring plan as it was at one version. A snapshot
+/// is stored whenever a significant change bumps `RecurringPlan::version`, so
+/// planned entries can point at the exact plan that produced them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringPlanVersion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub company_id: ObjectId,
+    pub recurring_plan_id: ObjectId,
+    pub version: i32,
+
+    /// The plan fields at this version.
+    pub plan: RecurringPlan,
+
+    pub created_at: DateTime,
+}
+
+fn default_one() -> i32 {
+    1
+}
+
+/// Multipliers applied to estimated income per forecast scenario, e.g. a
+/// worst case of 0.7 means only 70% of the expected sales arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ScenarioWeights {
+    pub best: f64,
+    pub expected: f64,
+    pub worst: f64,
+}
+
+impl Default for ScenarioWeights {
+    fn default() -> Self {
+        Self {
+            best: 1.1,
+            expected: 1.0,
+            worst: 0.7,
+        }
+    }
+}
+
+/// The three variants produced by scenario forecast generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForecastScenario {
+    Best,
+    Expected,
+    Worst,
+}
+
+impl ForecastScenario {
+    pub const ALL: [ForecastScenario; 3] = [
+        ForecastScenario::Best,
+        ForecastScenario::Expected,
+        ForecastScenario::Worst,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForecastScenario::Best => "best",
+            ForecastScenario::Expected => "expected",
+            ForecastScenario::Worst => "worst",
+        }
+    }
+
+    pub fn weight(&self, weights: &ScenarioWeights) -> f64 {
+        match self {
+            ForecastScenario::Best => weights.best,
+            ForecastScenario::Expected => weights.expected,
+            ForecastScenario::Worst => weights.worst,
+        }
+    }
+}
+
+/// PlannedEntry: concrete commitment/budget item with a due date,
+/// used by the "traffic light" (semaphore) and to match with real transactions.
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PlannedEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this planned entry belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    /// Optional link to the recurring plan that generated this entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub recurring_plan_id: Option<ObjectId>,
+
+    /// Version of the RecurringPlan at the time this entry was generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurring_plan_version: Option<i32>,
+
+    /// Optional link to the service order that generated this entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub service_order_id: Option<ObjectId>,
+
+    /// Optional project this commitment belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
+
+    /// Optional estimated commitment that this real commitment helps cover.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub parent_planned_entry_id: Option<ObjectId>,
+
+    pub name: String,
+    pub flow_type: FlowType,
+
+    #[schema(value_type = String)]
+    pub category_id: ObjectId,
+    #[schema(value_type = String)]
+    pub account_expected_id: ObjectId,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+
+    pub amount_estimated: f64,
+
+    /// Snapshot of amount_estimated before the first real payment aligned it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_amount_estimated: Option<f64>,
+
+    /// Due date of this specific commitment (e.g. 10th of November).
+    #[schema(value_type = String, format = DateTime)]
+    pub due_date: DateTime,
+
+    /// Snapshot of due_date before the first real payment aligned it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub original_due_date: Option<DateTime>,
+
+    /// Due date the recurring plan generated this entry for. Unlike
+    /// `due_date` it never moves, and a plan version gets one entry per
+    /// generated date (a unique index), however many extension runs overlap.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub generated_due_date: Option<DateTime>,
+
+    pub status: PlannedStatus,
+
+    /// Set once the entry is edited by hand. Plan edits and regeneration
+    /// keep customized entries instead of replacing them.
+    #[serde(default)]
+    pub is_customized: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// UUID of the CFDI/factura that originated this commitment, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfdi_uuid: Option<String>,
+
+    /// Currency code from the CFDI (e.g. "MXN", "USD").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+
+    /// Serie-Folio of the CFDI (e.g. "REGT-474850").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfdi_folio: Option<String>,
+
+    /// Entry created for the excess of an over-covered commitment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage_adjustment: Option<CoverageAdjustment>,
+
+    /// Times the entry was rolled forward after missing its due date; how
+    /// reliable the supplier or customer is.
+    #[serde(default)]
+    pub slip_count: i32,
+
+    /// Every hand edit of `amount_estimated`, oldest first. The first one
+    /// keeps the figure the plan generated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amount_revisions: Vec<AmountRevision>,
+
+    /// Units of the company's currency per unit of the entry's currency when
+    /// it was booked. Income that settles it at another rate realizes the
+    /// difference as an exchange gain or loss.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>,
+
+    /// Income split across deposit accounts; what the shares leave is
+    /// expected in `account_expected_id`. Empty when it all lands there.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deposit_allocations: Vec<DepositAllocation>,
+}
+
+/// Hand edit of the estimated amount of a planned entry: who changed it,
+/// when, and from what to what.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct AmountRevision {
+    #[schema(value_type = String, format = DateTime)]
+    pub changed_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub user_id: Option<ObjectId>,
+    pub username: String,
+    pub previous_amount: f64,
+    pub amount: f64,
+}
+
+/// Excess payment of a planned entry moved to an entry of the opposite flow:
+/// a credit owed to the customer, or a refund owed by the supplier.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct CoverageAdjustment {
+    #[schema(value_type = String)]
+    pub entry_id: ObjectId,
+    pub amount: f64,
+}
+
+/// Share of a planned income expected in one deposit account: a percentage
+/// of the estimate or a fixed amount, never both.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, utoipa::ToSchema)]
+pub struct DepositAllocation {
+    #[schema(value_type = String)]
+    pub account_id: ObjectId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+}
+
+/// Transaction: real movement (income, expense, transfer).
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Transaction {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+
+    /// Tenant this transaction belongs to.
+    #[schema(value_type = String)]
+    pub company_id: ObjectId,
+
+    #[schema(value_type = String, format = DateTime)]
+    pub date: DateTime,
+    pub description: String,
+
+    pub transaction_type: TransactionType,
+    #[schema(value_type = String)]
+    pub category_id: ObjectId,
+
+    /// For expenses or transfers (money goes out).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub account_from_id: Option<ObjectId>,
+
+    /// For incomes or transfers (money goes in).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub account_to_id: Option<ObjectId>,
+
+    pub amount: f64,
+
+    /// Optional link to the planned entry this transaction is covering.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub planned_entry_id: Option<ObjectId>,
+
+    /// Optional project this real movement belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
+
+    #[serde(default = "default_true")]
+    pub is_confirmed: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub created_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<DateTime>,
+
+    /// Contact (client/supplier) linked to this transaction, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+
+    /// UUID of the CFDI/factura that originated this transaction, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfdi_uuid: Option<String>,
+
+    /// Currency code from the CFDI (e.g. "MXN", "USD").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+
+    /// Serie-Folio of the CFDI (e.g. "REGT-474850").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfdi_folio: Option<String>,
+
+    /// Sequential reference within the company, e.g. "TX-2025-0001".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+
+    /// Id of the movement in an integrator's system. Unique within the
+    /// company, so sending the same movement twice records it once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+
+    /// Reference the bank gave the movement (OFX `FITID`, SPEI tracking key).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bank_reference: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Free-form labels, e.g. added in bulk after an import.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Values of the company's custom fields, keyed by `CustomFieldDefinition::key`.
+    #[serde(default, skip_serializing_if = "Document::is_empty")]
+    #[schema(value_type = Object)]
+    pub custom_fields: Document,
+
+    /// Tax included in `amount`, taken from the category's tax profile unless
+    /// the rate was set by hand. Transfers carry none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tax: Option<TransactionTax>,
+
+    /// Transaction this one refunds. A refund repeats the type, category and
+    /// accounts of the original with a negative amount, so it nets out of
+    /// balances, category reports and planned-entry coverage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub refund_of: Option<ObjectId>,
+
+    /// Units of the company's currency per unit of the transaction's
+    /// currency (the CFDI's, or else its account's) on its date; for a
+    /// transfer from the company's currency into a foreign one, per unit of
+    /// the destination's. Only used on foreign-currency movements, to compute
+    /// realized exchange results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>,
+
+    /// Set on the adjustments that record a realized exchange gain or loss.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fx_adjustment: Option<FxAdjustment>,
+
+    /// Set on card pre-authorizations. A held or voided transaction stays
+    /// unconfirmed, and so out of balances, until it is captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold: Option<CardHold>,
+}
+
+/// Lifecycle of a card pre-authorization.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldStatus {
+    /// Authorized by the card but not charged yet.
+    Held,
+    /// Charged, for the final amount.
+    Captured,
+    /// Released by the merchant, or expired before being captured.
+    Voided,
+}
+
+impl HoldStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HoldStatus::Held => "held",
+            HoldStatus::Captured => "captured",
+            HoldStatus::Voided => "voided",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HoldStatus::Held => "Retenido",
+            HoldStatus::Captured => "Cobrado",
+            HoldStatus::Voided => "Anulado",
+        }
+    }
+}
+
+/// Card hold on a transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CardHold {
+    pub status: HoldStatus,
+    /// Amount first authorized; the capture may change `amount`.
+    pub authorized_amount: f64,
+    /// A hold still open by then is voided by the expiry task.
+    #[schema(value_type = String, format = DateTime)]
+    pub expires_at: DateTime,
+    /// When it was captured or voided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub settled_at: Option<DateTime>,
+    /// Voided by the expiry task rather than by hand.
+    #[serde(default)]
+    pub expired: bool,
+}
+
+/// What an exchange adjustment records: the foreign amount of the
+/// settlement it comes from and the rates it was booked and settled at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct FxAdjustment {
+    /// Transaction whose settlement realized the result.
+    #[schema(value_type = String)]
+    pub source_id: ObjectId,
+    pub currency: String,
+    /// In `currency`.
+    pub amount: f64,
+    pub booked_rate: f64,
+    pub settled_rate: f64,
+}
+
+/// Tax part of a transaction's amount.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct TransactionTax {
+    /// Profile the rate came from; `None` when it was set by hand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub profile_id: Option<ObjectId>,
+    /// Profile name at the time, e.g. "IVA 16%", or "Manual".
+    pub name: String,
+    /// Percentage, e.g. 16.0.
+    pub rate: f64,
+    #[serde(default)]
+    pub exempt: bool,
+    /// Tax included in the amount: `amount * rate / (100 + rate)`.
+    pub amount: f64,
+    /// The rate was typed on the transaction instead of coming from its
+    /// category.
+    #[serde(default)]
+    pub is_override: bool,
+}
+
+/// Transaction text a find-and-replace can rewrite.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextField {
+    Description,
+    Notes,
+}
+
+impl TextField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextField::Description => "description",
+            TextField::Notes => "notes",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextField::Description => "Descripción",
+            TextField::Notes => "Notas",
+        }
+    }
+}
+
+/// Day and month order of the dates of a CSV statement.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOrder {
+    /// Taken from the file: a first number above 12 means day first, a
+    /// second one above 12 month first, and day first otherwise.
+    #[default]
+    Auto,
+    DayFirst,
+    MonthFirst,
+}
+
+impl DateOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateOrder::Auto => "auto",
+            DateOrder::DayFirst => "day_first",
+            DateOrder::MonthFirst => "month_first",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(DateOrder::Auto),
+            "day_first" => Some(DateOrder::DayFirst),
+            "month_first" => Some(DateOrder::MonthFirst),
+            _ => None,
+        }
+    }
+}
+
+/// Decimal separator of the amounts of a CSV statement.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecimalMark {
+    /// Guessed for each amount (`1,234.56` or `1.234,56`).
+    #[default]
+    Auto,
+    Point,
+    Comma,
+}
+
+impl DecimalMark {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecimalMark::Auto => "auto",
+            DecimalMark::Point => "point",
+            DecimalMark::Comma => "comma",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(DecimalMark::Auto),
+            "point" => Some(DecimalMark::Point),
+            "comma" => Some(DecimalMark::Comma),
+            _ => None,
+        }
+    }
+
+    /// The separator, when it is not guessed.
+    pub fn separator(&self) -> Option<char> {
+        match self {
+            DecimalMark::Auto => None,
+            DecimalMark::Point => Some('.'),
+            DecimalMark::Comma => Some(','),
+        }
+    }
+}
+
+/// Which column of a CSV statement holds each field (counting from 0) and
+/// how its dates and amounts are written.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CsvMapping {
+    /// `,`, `;` or a tab.
+    pub delimiter: char,
+    pub date: usize,
+    pub description: usize,
+    /// One signed amount column; unset when the file has separate columns
+    /// for withdrawals (`debit`) and deposits (`credit`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<usize>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime>,
+    pub debit: Option<usize>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
-
+    pub credit: Option<usize>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+    pub reference: Option<usize>,
+    #[serde(default)]
+    pub date_order: DateOrder,
+    #[serde(default)]
+    pub decimal_mark: DecimalMark,
+    /// The signed column has withdrawals positive and deposits negative.
+    #[serde(default)]
+    pub negate: bool,
 }
 
-/// Category for incomes/expenses.
+/// A company's saved way of reading the CSV statements of one bank. Files
+/// whose header matches `header` start from its mapping.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Category {
+pub struct ImportProfile {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
-
-    /// Tenant this category belongs to.
     pub company_id: ObjectId,
-
     pub name: String,
-    pub flow_type: FlowType,
-
-    /// Optional parent category for hierarchy.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub parent_id: Option<ObjectId>,
-
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
-
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+    /// Column names of the file, trimmed and lowercased.
+    pub header: Vec<String>,
+    pub mapping: CsvMapping,
+    pub updated_at: DateTime,
 }
 
-/// Contact: customer, supplier, service (CFE, landlord, etc.).
+/// CSV statement uploaded and waiting for its columns to be confirmed,
+/// with the account and categories chosen for it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Contact {
+pub struct ImportDraft {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
-
-    /// Tenant this contact belongs to.
     pub company_id: ObjectId,
+    pub account_id: ObjectId,
+    pub income_category_id: ObjectId,
+    pub expense_category_id: ObjectId,
+    pub file_name: String,
+    /// The file as text.
+    pub content: String,
+    pub expires_at: DateTime,
+}
 
-    pub name: String,
-    pub contact_type: ContactType,
-
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub rfc: Option<String>,
-
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub email: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub phone: Option<String>,
+/// Find-and-replace run over transaction texts, kept as its audit: what was
+/// searched, who ran it and every value before and after.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextReplacement {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
+    pub company_id: ObjectId,
+    pub find: String,
+    pub replace: String,
+    pub fields: Vec<TextField>,
+    pub case_sensitive: bool,
+    /// First and last day searched, both included.
+    pub from: DateTime,
+    pub to: DateTime,
+    pub changes: Vec<TextChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<ObjectId>,
+    pub username: String,
+    pub created_at: DateTime,
+}
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+/// One text rewritten by a [`TextReplacement`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextChange {
+    pub transaction_id: ObjectId,
+    pub field: TextField,
+    pub before: String,
+    /// Empty when the notes were cleared.
+    pub after: String,
 }
 
-/// RecurringPlan: template for recurring income/expense,
-/// e.g. "Electricity CFE every month on day 10, estimated 2000 MXN".
+/// Receipt image or PDF uploaded to capture a transaction. OCR suggestions are
+/// kept so the form can be prefilled; the receipt is linked to the
+/// transaction once the user saves it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RecurringPlan {
+pub struct Receipt {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
 
-    /// Tenant this plan belongs to.
     pub company_id: ObjectId,
 
-    pub name: String,
-    pub flow_type: FlowType,
-
-    pub category_id: ObjectId,
-    pub account_expected_id: ObjectId,
-
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub contact_id: Option<ObjectId>,
-
-    pub amount_estimated: f64,
+    pub transaction_id: Option<ObjectId>,
 
-    /// Frequency: usually "monthly", "weekly", "yearly".
-    pub frequency: String,
+    pub file_name: String,
+    pub content_type: String,
+    /// Location of the stored file, relative to the working directory.
+    pub path: String,
 
-    /// Day of month (1–31) if frequency is monthly.
+    /// Raw text returned by the OCR backend, if one ran.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub day_of_month: Option<i32>,
+    pub ocr_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_amount: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_date: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_merchant: Option<String>,
 
-    pub start_date: DateTime,
+    pub uploaded_at: DateTime,
+}
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub end_date: Option<DateTime>,
+/// ---------- CUSTOM FIELDS ----------
 
-    /// Whether this recurring plan is active.
-    #[serde(default = "default_true")]
-    pub is_active: bool,
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomFieldEntity {
+    Transaction,
+    Contact,
+}
 
-    /// Version number for detecting outdated PlannedEntries.
-    #[serde(default = "default_one")]
-    pub version: i32,
+impl CustomFieldEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldEntity::Transaction => "transaction",
+            CustomFieldEntity::Contact => "contact",
+        }
+    }
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
+    pub fn label(&self) -> &'static str {
+        match self {
+            CustomFieldEntity::Transaction => "Movimientos",
+            CustomFieldEntity::Contact => "Contactos",
+        }
+    }
+}
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+/// Type of value a custom field holds. Values are stored with the matching
+/// BSON type so they can be filtered and sorted.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomFieldType {
+    Text,
+    Number,
+    Date,
+    Boolean,
 }
 
-fn default_one() -> i32 {
-    1
+impl CustomFieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldType::Text => "text",
+            CustomFieldType::Number => "number",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Boolean => "boolean",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CustomFieldType::Text => "Texto",
+            CustomFieldType::Number => "Número",
+            CustomFieldType::Date => "Fecha",
+            CustomFieldType::Boolean => "Sí / No",
+        }
+    }
 }
 
-/// PlannedEntry: concrete commitment/budget item with a due date,
-/// used by the "traffic light" (semaphore) and to match with real transactions.
+/// Field a company adds to its transactions or contacts, e.g. "Centro de
+/// costos". Values live in the `custom_fields` map of each document.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PlannedEntry {
+pub struct CustomFieldDefinition {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
 
-    /// Tenant this planned entry belongs to.
     pub company_id: ObjectId,
+    pub entity: CustomFieldEntity,
 
-    /// Optional link to the recurring plan that generated this entry.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub recurring_plan_id: Option<ObjectId>,
+    /// Name of the value inside `custom_fields`; derived from the label and
+    /// unique per company and entity.
+    pub key: String,
+    pub label: String,
+    pub field_type: CustomFieldType,
 
-    /// Version of the RecurringPlan at the time this entry was generated.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub recurring_plan_version: Option<i32>,
+    #[serde(default)]
+    pub required: bool,
 
-    /// Optional link to the service order that generated this entry.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub service_order_id: Option<ObjectId>,
+    pub created_at: Option<DateTime>,
+}
 
-    /// Optional project this commitment belongs to.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub project_id: Option<ObjectId>,
+// ---------- TAX PROFILES ----------
 
-    /// Optional estimated commitment that this real commitment helps cover.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub parent_planned_entry_id: Option<ObjectId>,
+/// Tax a company applies to categories, e.g. "IVA 16%" or "Exento". Amounts
+/// are recorded with the tax included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxProfile {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
 
+    pub company_id: ObjectId,
     pub name: String,
-    pub flow_type: FlowType,
-
-    pub category_id: ObjectId,
-    pub account_expected_id: ObjectId,
-
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub contact_id: Option<ObjectId>,
-
-    pub amount_estimated: f64,
+    /// Percentage, e.g. 16.0. Zero on exempt profiles.
+    pub rate: f64,
+    /// Movements carry no tax but are reported apart from the 0% ones.
+    #[serde(default)]
+    pub exempt: bool,
+    /// Applies to categories without a profile of their own. At most one
+    /// per company.
+    #[serde(default)]
+    pub is_default: bool,
 
-    /// Snapshot of amount_estimated before the first real payment aligned it.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub original_amount_estimated: Option<f64>,
+    pub created_at: Option<DateTime>,
+}
 
-    /// Due date of this specific commitment (e.g. 10th of November).
-    pub due_date: DateTime,
+// ---------- ACCOUNT GROUPS ----------
 
-    /// Snapshot of due_date before the first real payment aligned it.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub original_due_date: Option<DateTime>,
+/// Heading accounts are listed under, e.g. "Bancos" or "Tarjetas", with its
+/// subtotal on the accounts page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountGroup {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
 
-    pub status: PlannedStatus,
+    pub company_id: ObjectId,
+    pub name: String,
+    /// Order among the company's groups.
+    #[serde(default)]
+    pub position: i32,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub created_at: Option<DateTime>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
+}
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+// ---------- COMMENTS ----------
 
-    /// UUID of the CFDI/factura that originated this commitment, if any.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub cfdi_uuid: Option<String>,
+/// Record a comment thread hangs off.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentEntity {
+    Transaction,
+    PlannedEntry,
+    RecurringPlan,
+}
 
-    /// Currency code from the CFDI (e.g. "MXN", "USD").
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub currency: Option<String>,
+impl CommentEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentEntity::Transaction => "transaction",
+            CommentEntity::PlannedEntry => "planned_entry",
+            CommentEntity::RecurringPlan => "recurring_plan",
+        }
+    }
 
-    /// Serie-Folio of the CFDI (e.g. "REGT-474850").
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub cfdi_folio: Option<String>,
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "transaction" => Some(CommentEntity::Transaction),
+            "planned_entry" => Some(CommentEntity::PlannedEntry),
+            "recurring_plan" => Some(CommentEntity::RecurringPlan),
+            _ => None,
+        }
+    }
+
+    /// Edit page of the record, where its comments are shown.
+    pub fn edit_path(&self, id: &ObjectId) -> String {
+        let base = match self {
+            CommentEntity::Transaction => "transactions",
+            CommentEntity::PlannedEntry => "planned_entries",
+            CommentEntity::RecurringPlan => "recurring_plans",
+        };
+        format!("/admin/{base}/{}/edit", id.to_hex())
+    }
 }
 
-/// Transaction: real movement (income, expense, transfer).
+/// Comment left on a transaction, planned entry or plan. Replies point at
+/// the top-level comment they answer; threads are one level deep.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
+pub struct Comment {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
 
-    /// Tenant this transaction belongs to.
     pub company_id: ObjectId,
+    pub entity: CommentEntity,
+    pub entity_id: ObjectId,
 
-    pub date: DateTime,
-    pub description: String,
-
-    pub transaction_type: TransactionType,
-    pub category_id: ObjectId,
-
-    /// For expenses or transfers (money goes out).
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub account_from_id: Option<ObjectId>,
-
-    /// For incomes or transfers (money goes in).
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub account_to_id: Option<ObjectId>,
-
-    pub amount: f64,
-
-    /// Optional link to the planned entry this transaction is covering.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub planned_entry_id: Option<ObjectId>,
-
-    /// Optional project this real movement belongs to.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub project_id: Option<ObjectId>,
-
-    #[serde(default = "default_true")]
-    pub is_confirmed: bool,
+    pub parent_id: Option<ObjectId>,
 
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub created_at: Option<DateTime>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub updated_at: Option<DateTime>,
+    pub author_id: ObjectId,
+    /// Username of the author when the comment was written.
+    pub author_name: String,
+    pub body: String,
 
-    /// Contact (client/supplier) linked to this transaction, if any.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub contact_id: Option<ObjectId>,
+    /// Users mentioned with `@username` in the body.
+    #[serde(default)]
+    pub mentions: Vec<ObjectId>,
 
-    /// UUID of the CFDI/factura that originated this transaction, if any.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub cfdi_uuid: Option<String>,
+    pub created_at: DateTime,
+}
 
-    /// Currency code from the CFDI (e.g. "MXN", "USD").
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub currency: Option<String>,
+/// Tells a user they were mentioned in a comment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
 
-    /// Serie-Folio of the CFDI (e.g. "REGT-474850").
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub cfdi_folio: Option<String>,
+    pub company_id: ObjectId,
+    pub user_id: ObjectId,
+    pub comment_id: ObjectId,
+    pub entity: CommentEntity,
+    pub entity_id: ObjectId,
+    pub author_name: String,
+    /// Start of the comment body.
+    pub excerpt: String,
+    pub created_at: DateTime,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub notes: Option<String>,
+    pub read_at: Option<DateTime>,
 }
 
 /// ---------- SERVICE ORDERS ----------
@@ -653,6 +2657,11 @@ pub struct Forecast {
     pub projected_expense_total: f64,
     pub projected_net: f64,
 
+    /// Projected income with the probability of each income plan applied,
+    /// i.e. its expected value. Only set on generated forecasts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weighted_income_total: Option<f64>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub initial_balance: Option<f64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -667,6 +2676,14 @@ pub struct Forecast {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scenario_name: Option<String>,
 
+    /// Shared by the best/expected/worst forecasts generated together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scenario_group_id: Option<ObjectId>,
+
+    /// Sequential reference within the company, e.g. "FC-2025-0001".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
 }
@@ -1077,6 +3094,18 @@ mod tests {
         assert!(!UserRole::Staff.is_admin());
         assert_eq!(UserRole::Admin.as_str(), "admin");
         assert_eq!(UserRole::Staff.as_str(), "staff");
+        assert_eq!(UserRole::Auditor.as_str(), "auditor");
+    }
+
+    #[test]
+    fn auditors_see_admin_pages_read_only() {
+        assert!(UserRole::Auditor.can_view_admin());
+        assert!(!UserRole::Auditor.is_admin());
+        assert!(UserRole::Admin.can_view_admin());
+        assert!(!UserRole::Staff.can_view_admin());
+        assert!(UserRole::Auditor.is_read_only());
+        assert!(!UserRole::Admin.is_read_only());
+        assert!(!UserRole::Staff.is_read_only());
     }
 
     #[test]
@@ -0,0 +1,182 @@
+// notifier.rs
+// Chat notifications: pushes text messages to a company's Telegram chat or
+// WhatsApp number through a pluggable backend per channel. Notification
+// emails go through the app's `Mailer`.
+
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::env;
+
+use crate::{
+    mailer::{Email, Mailer},
+    models::ChatChannel,
+};
+
+/// Something that delivers a text message to a chat. Implementations call the
+/// messaging service; `chat_id` is whatever identifies the chat there.
+pub trait ChatNotifier: Send + Sync {
+    fn send_message<'a>(&'a self, chat_id: &'a str, text: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+fn read_env(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Telegram bot. `TELEGRAM_BOT_TOKEN` enables it; the bot must have been
+/// added to the chat (or started by the user) before it can write there.
+#[derive(Debug, Clone)]
+pub struct TelegramNotifier {
+    pub api_url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl TelegramNotifier {
+    pub fn new(api_url: String, token: String) -> Self {
+        Self {
+            api_url,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `None` when the bot is not configured. `TELEGRAM_API_URL` overrides
+    /// the Bot API host.
+    pub fn from_env() -> Option<Self> {
+        Some(Self::new(
+            read_env("TELEGRAM_API_URL").unwrap_or_else(|| "https://api.telegram.org".to_string()),
+            read_env("TELEGRAM_BOT_TOKEN")?,
+        ))
+    }
+}
+
+impl ChatNotifier for TelegramNotifier {
+    fn send_message<'a>(&'a self, chat_id: &'a str, text: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/bot{}/sendMessage",
+                self.api_url.trim_end_matches('/'),
+                self.token
+            );
+            let response = self
+                .client
+                .post(url)
+                .json(&json!({ "chat_id": chat_id, "text": text }))
+                .send()
+                .await
+                .context("telegram request failed")?;
+            if !response.status().is_success() {
+                bail!("telegram answered {}", response.status());
+            }
+            Ok(())
+        })
+    }
+}
+
+/// WhatsApp Business Cloud API. `WHATSAPP_TOKEN` and
+/// `WHATSAPP_PHONE_NUMBER_ID` enable it. WhatsApp only delivers free-form
+/// text inside the 24 hours after the recipient last wrote to the number.
+#[derive(Debug, Clone)]
+pub struct WhatsappNotifier {
+    pub api_url: String,
+    pub phone_number_id: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl WhatsappNotifier {
+    pub fn new(api_url: String, phone_number_id: String, token: String) -> Self {
+        Self {
+            api_url,
+            phone_number_id,
+            token,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `None` when WhatsApp is not configured. `WHATSAPP_API_URL` overrides
+    /// the Graph API base, version included.
+    pub fn from_env() -> Option<Self> {
+        Some(Self::new(
+            read_env("WHATSAPP_API_URL")
+                .unwrap_or_else(|| "https://graph.facebook.com/v21.0".to_string()),
+            read_env("WHATSAPP_PHONE_NUMBER_ID")?,
+            read_env("WHATSAPP_TOKEN")?,
+        ))
+    }
+}
+
+impl ChatNotifier for WhatsappNotifier {
+    fn send_message<'a>(&'a self, chat_id: &'a str, text: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/{}/messages",
+                self.api_url.trim_end_matches('/'),
+                self.phone_number_id
+            );
+            let response = self
+                .client
+                .post(url)
+                .bearer_auth(&self.token)
+                .json(&json!({
+                    "messaging_product": "whatsapp",
+                    "to": chat_id,
+                    "type": "text",
+                    "text": { "body": text },
+                }))
+                .send()
+                .await
+                .context("whatsapp request failed")?;
+            if !response.status().is_success() {
+                bail!("whatsapp answered {}", response.status());
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Emails a notification to each recipient. Every one is tried; the error
+/// says how many were not delivered.
+pub async fn send_notification_email(
+    mailer: &dyn Mailer,
+    recipients: &[String],
+    subject: &str,
+    text: &str,
+) -> Result<()> {
+    let mut failed = 0;
+    let mut last_error = None;
+    for to in recipients {
+        let email = Email {
+            to: to.clone(),
+            subject: subject.to_string(),
+            text: text.to_string(),
+        };
+        if let Err(err) = mailer.send(&email).await {
+            failed += 1;
+            last_error = Some(err);
+        }
+    }
+    match last_error {
+        Some(err) => Err(err.context(format!(
+            "{failed} of {} notification emails not sent",
+            recipients.len()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The configured backend of `channel`, if any.
+pub fn chat_notifier_from_env(channel: ChatChannel) -> Option<Box<dyn ChatNotifier>> {
+    match channel {
+        ChatChannel::Telegram => {
+            TelegramNotifier::from_env().map(|notifier| Box::new(notifier) as Box<dyn ChatNotifier>)
+        }
+        ChatChannel::Whatsapp => {
+            WhatsappNotifier::from_env().map(|notifier| Box::new(notifier) as Box<dyn ChatNotifier>)
+        }
+    }
+}
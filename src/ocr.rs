@@ -0,0 +1,245 @@
+// ocr.rs
+// Receipt OCR: turns an uploaded image/PDF into text through a pluggable
+// backend, then guesses amount, date and merchant to prefill a transaction.
+
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::env;
+
+/// Content types accepted as receipts.
+pub const RECEIPT_CONTENT_TYPES: [&str; 4] =
+    ["image/jpeg", "image/png", "image/webp", "application/pdf"];
+
+/// Something that reads the text of a receipt. Implementations may call an
+/// external service; they only have to return the recognized text.
+pub trait OcrBackend: Send + Sync {
+    fn extract_text<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<String>>;
+}
+
+/// Generic HTTP OCR service: the file is posted as the multipart field `file`
+/// and the service answers `{"text": "..."}`. `OCR_API_URL` enables it,
+/// `OCR_API_KEY` is sent as a bearer token when set.
+#[derive(Debug, Clone)]
+pub struct HttpOcrBackend {
+    pub url: String,
+    pub api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct HttpOcrResponse {
+    text: String,
+}
+
+impl HttpOcrBackend {
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        Self {
+            url,
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// `None` when OCR is not configured.
+    pub fn from_env() -> Option<Self> {
+        let read = |key: &str| {
+            env::var(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Some(Self::new(read("OCR_API_URL")?, read("OCR_API_KEY")))
+    }
+}
+
+impl OcrBackend for HttpOcrBackend {
+    fn extract_text<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        content_type: &'a str,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let part = reqwest::multipart::Part::bytes(bytes.to_vec())
+                .file_name("receipt")
+                .mime_str(content_type)?;
+            let mut request = self
+                .client
+                .post(&self.url)
+                .multipart(reqwest::multipart::Form::new().part("file", part));
+            if let Some(key) = &self.api_key {
+                request = request.bearer_auth(key);
+            }
+            let response = request.send().await.context("ocr request failed")?;
+            if !response.status().is_success() {
+                bail!("ocr service answered {}", response.status());
+            }
+            Ok(response.json::<HttpOcrResponse>().await?.text)
+        })
+    }
+}
+
+/// The configured backend, if any.
+pub fn ocr_backend_from_env() -> Option<Box<dyn OcrBackend>> {
+    HttpOcrBackend::from_env().map(|backend| Box::new(backend) as Box<dyn OcrBackend>)
+}
+
+/// Transaction fields guessed from a receipt. Every field is a suggestion the
+/// user confirms in the form.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReceiptSuggestion {
+    pub amount: Option<f64>,
+    pub date: Option<NaiveDate>,
+    pub merchant: Option<String>,
+}
+
+/// Runs the receipt through `backend` and parses the text. Returns the raw
+/// text too so it can be stored next to the receipt.
+pub async fn recognize_receipt(
+    backend: &dyn OcrBackend,
+    bytes: &[u8],
+    content_type: &str,
+) -> Result<(String, ReceiptSuggestion)> {
+    let text = backend.extract_text(bytes, content_type).await?;
+    let suggestion = suggest_from_text(&text);
+    Ok((text, suggestion))
+}
+
+pub fn suggest_from_text(text: &str) -> ReceiptSuggestion {
+    ReceiptSuggestion {
+        amount: find_total(text),
+        date: text.lines().find_map(find_date),
+        merchant: find_merchant(text),
+    }
+}
+
+/// Amount on the last line mentioning a total (but not a subtotal); falls back
+/// to the largest amount in the text.
+fn find_total(text: &str) -> Option<f64> {
+    let total_line = text
+        .lines()
+        .rev()
+        .filter(|line| {
+            let upper = line.to_uppercase();
+            upper.contains("TOTAL") && !upper.contains("SUBTOTAL") && !upper.contains("SUB TOTAL")
+        })
+        .find_map(|line| amounts_in(line).last().copied());
+    total_line.or_else(|| {
+        text.lines()
+            .flat_map(amounts_in)
+            .fold(None, |max: Option<f64>, v| {
+                Some(max.map_or(v, |m| m.max(v)))
+            })
+    })
+}
+
+/// Numbers with two decimals in a line, e.g. `$1,234.56` or `1.234,56`.
+fn amounts_in(line: &str) -> Vec<f64> {
+    line.split(|c: char| !(c.is_ascii_digit() || c == '.' || c == ','))
+        .filter_map(parse_amount)
+        .collect()
+}
+
+fn parse_amount(token: &str) -> Option<f64> {
+    let token = token.trim_matches(|c| c == '.' || c == ',');
+    let (int_part, decimals) = token.split_at(token.len().checked_sub(3)?);
+    let mut decimals = decimals.chars();
+    if !matches!(decimals.next(), Some('.' | ',')) {
+        return None;
+    }
+    let decimals: String = decimals.collect();
+    if !decimals.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let int_part: String = int_part.chars().filter(|c| c.is_ascii_digit()).collect();
+    if int_part.is_empty() {
+        return None;
+    }
+    format!("{int_part}.{decimals}").parse().ok()
+}
+
+/// First date in a line: `dd/mm/yyyy`, `dd-mm-yyyy`, `dd/mm/yy` or
+/// `yyyy-mm-dd`. Day comes before month, as on Mexican receipts.
+fn find_date(line: &str) -> Option<NaiveDate> {
+    line.split(|c: char| !(c.is_ascii_digit() || c == '/' || c == '-'))
+        .find_map(|token| {
+            let parts: Vec<&str> = token.split(['/', '-']).collect();
+            let [a, b, c] = parts.as_slice() else {
+                return None;
+            };
+            let (a, b, c): (u32, u32, i32) = (a.parse().ok()?, b.parse().ok()?, c.parse().ok()?);
+            if a > 999 {
+                NaiveDate::from_ymd_opt(a as i32, b, c as u32)
+            } else {
+                let year = if c < 100 { 2000 + c } else { c };
+                NaiveDate::from_ymd_opt(year, b, a)
+            }
+        })
+}
+
+/// First line that reads like a name: mostly letters and not a header such as
+/// "TICKET" or an RFC line.
+fn find_merchant(text: &str) -> Option<String> {
+    const SKIP: [&str; 5] = ["TICKET", "RFC", "FACTURA", "FECHA", "RECIBO"];
+    text.lines()
+        .map(str::trim)
+        .filter(|line| {
+            let upper = line.to_uppercase();
+            !SKIP.iter().any(|word| upper.starts_with(word))
+        })
+        .find(|line| {
+            let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+            letters >= 3 && letters * 2 >= line.chars().filter(|c| !c.is_whitespace()).count()
+        })
+        .map(|line| line.chars().take(80).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedText(&'static str);
+
+    impl OcrBackend for FixedText {
+        fn extract_text<'a>(&'a self, _: &'a [u8], _: &'a str) -> BoxFuture<'a, Result<String>> {
+            Box::pin(async move { Ok(self.0.to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn suggests_fields_from_a_ticket() {
+        let text = "TICKET 00123\nAbarrotes La Esperanza SA de CV\nRFC AES010101AB1\n\
+                    Fecha: 14/03/2025 13:02\nLeche 2 x 28.50   57.00\nSUBTOTAL  1,120.69\n\
+                    IVA 179.31\nTOTAL $1,300.00\n";
+        let (raw, suggestion) = recognize_receipt(&FixedText(text), b"", "image/png")
+            .await
+            .unwrap();
+        assert_eq!(raw, text);
+        assert_eq!(suggestion.amount, Some(1300.0));
+        assert_eq!(suggestion.date, NaiveDate::from_ymd_opt(2025, 3, 14));
+        assert_eq!(
+            suggestion.merchant.as_deref(),
+            Some("Abarrotes La Esperanza SA de CV")
+        );
+    }
+
+    #[test]
+    fn parses_amount_and_date_variants() {
+        assert_eq!(parse_amount("1.234,56"), Some(1234.56));
+        assert_eq!(parse_amount("99.90"), Some(99.9));
+        assert_eq!(parse_amount("2025"), None);
+        assert_eq!(find_total("importe 15.00\notro 120.50"), Some(120.5));
+        assert_eq!(
+            find_date("2025-01-31 10:00"),
+            NaiveDate::from_ymd_opt(2025, 1, 31)
+        );
+        assert_eq!(find_date("01/02/25"), NaiveDate::from_ymd_opt(2025, 2, 1));
+        assert_eq!(find_date("31/31/2025"), None);
+        assert_eq!(suggest_from_text(""), ReceiptSuggestion::default());
+    }
+}
@@ -0,0 +1,402 @@
+// oidc.rs
+// OpenID Connect client for SSO login: authorization code flow against one
+// provider configured through env, with RS256 id_token verification.
+
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use data_encoding::BASE32_NOPAD;
+use openssl::{bn::BigNum, hash::MessageDigest, pkey::PKey, rsa::Rsa, sign::Verifier};
+use rand::RngCore;
+use serde::Deserialize;
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Allowed clock difference with the provider when checking `exp`.
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// Provider settings. `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET`
+/// and `OIDC_REDIRECT_URL` are required; `OIDC_PROVIDER_NAME` is the label of
+/// the login button.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Absolute URL of `/sso/callback`, registered at the provider.
+    pub redirect_url: String,
+    pub provider_name: String,
+}
+
+impl OidcConfig {
+    /// `None` when SSO is not configured.
+    pub fn from_env() -> Option<Self> {
+        let read = |key: &str| {
+            env::var(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Some(Self {
+            issuer: read("OIDC_ISSUER")?,
+            client_id: read("OIDC_CLIENT_ID")?,
+            client_secret: read("OIDC_CLIENT_SECRET")?,
+            redirect_url: read("OIDC_REDIRECT_URL")?,
+            provider_name: read("OIDC_PROVIDER_NAME").unwrap_or_else(|| "SSO".to_string()),
+        })
+    }
+}
+
+/// The subset of the discovery document the flow needs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == client_id,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    aud: Audience,
+    pub exp: i64,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+}
+
+impl IdTokenClaims {
+    /// The email, but only when the provider vouches for it. Unverified
+    /// addresses are never used to pick a company or a user.
+    pub fn verified_email(&self) -> Option<&str> {
+        if self.email_verified == Some(true) {
+            self.email.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Random value for the `state` and `nonce` parameters.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+pub async fn discover(client: &reqwest::Client, config: &OidcConfig) -> Result<ProviderMetadata> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        config.issuer.trim_end_matches('/')
+    );
+    let metadata: ProviderMetadata = client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if metadata.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+        anyhow::bail!(
+            "discovery issuer {} does not match {}",
+            metadata.issuer,
+            config.issuer
+        );
+    }
+    Ok(metadata)
+}
+
+pub fn authorization_url(
+    metadata: &ProviderMetadata,
+    config: &OidcConfig,
+    state: &str,
+    nonce: &str,
+) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &config.redirect_url)
+        .append_pair("scope", "openid email profile")
+        .append_pair("state", state)
+        .append_pair("nonce", nonce)
+        .finish();
+    let separator = if metadata.authorization_endpoint.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    format!("{}{}{}", metadata.authorization_endpoint, separator, query)
+}
+
+/// Trades the authorization code for the raw id_token.
+pub async fn exchange_code(
+    client: &reqwest::Client,
+    metadata: &ProviderMetadata,
+    config: &OidcConfig,
+    code: &str,
+) -> Result<String> {
+    let response = client
+        .post(&metadata.token_endpoint)
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", config.redirect_url.as_str()),
+        ])
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("token endpoint returned {status}: {body}");
+    }
+    let token: TokenResponse = response.json().await?;
+    Ok(token.id_token)
+}
+
+pub async fn fetch_jwks(client: &reqwest::Client, metadata: &ProviderMetadata) -> Result<Jwks> {
+    Ok(client
+        .get(&metadata.jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Checks the signature (RS256 only), issuer, audience, expiry and nonce of an
+/// id_token and returns its claims. `now` is in Unix seconds.
+pub fn verify_id_token(
+    token: &str,
+    jwks: &Jwks,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<IdTokenClaims> {
+    let mut parts = token.split('.');
+    let (Some(header_b64), Some(payload_b64), Some(signature_b64), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("malformed id_token");
+    };
+
+    let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header_b64)?)?;
+    if header.alg != "RS256" {
+        anyhow::bail!("unsupported id_token algorithm {}", header.alg);
+    }
+    let key = jwks
+        .keys
+        .iter()
+        .filter(|key| key.kty == "RSA")
+        .find(|key| header.kid.is_none() || key.kid == header.kid)
+        .context("no signing key matches the id_token")?;
+    let modulus = URL_SAFE_NO_PAD.decode(key.n.as_deref().context("signing key missing n")?)?;
+    let exponent = URL_SAFE_NO_PAD.decode(key.e.as_deref().context("signing key missing e")?)?;
+    let rsa = Rsa::from_public_components(
+        BigNum::from_slice(&modulus)?,
+        BigNum::from_slice(&exponent)?,
+    )?;
+    let public_key = PKey::from_rsa(rsa)?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key)?;
+    verifier.update(header_b64.as_bytes())?;
+    verifier.update(b".")?;
+    verifier.update(payload_b64.as_bytes())?;
+    if !verifier.verify(&URL_SAFE_NO_PAD.decode(signature_b64)?)? {
+        anyhow::bail!("invalid id_token signature");
+    }
+
+    let claims: IdTokenClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload_b64)?)?;
+    if claims.iss != issuer {
+        anyhow::bail!("unexpected id_token issuer {}", claims.iss);
+    }
+    if !claims.aud.contains(client_id) {
+        anyhow::bail!("id_token was not issued for this client");
+    }
+    if claims.exp + CLOCK_SKEW_SECONDS < now {
+        anyhow::bail!("id_token expired");
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        anyhow::bail!("id_token nonce mismatch");
+    }
+    Ok(claims)
+}
+
+/// Runs the back-channel half of the flow for the `code` the provider sent
+/// to the callback: discovery, code exchange, key fetch and verification.
+pub async fn complete_login(config: &OidcConfig, code: &str, nonce: &str) -> Result<IdTokenClaims> {
+    let client = reqwest::Client::new();
+    let metadata = discover(&client, config).await?;
+    let id_token = exchange_code(&client, &metadata, config, code).await?;
+    let jwks = fetch_jwks(&client, &metadata).await?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    verify_id_token(
+        &id_token,
+        &jwks,
+        &metadata.issuer,
+        &config.client_id,
+        nonce,
+        now,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::{pkey::Private, sign::Signer};
+
+    const NOW: i64 = 1_800_000_000;
+
+    fn signing_key() -> PKey<Private> {
+        PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+    }
+
+    fn jwks_for(key: &PKey<Private>, kid: &str) -> Jwks {
+        let rsa = key.rsa().unwrap();
+        Jwks {
+            keys: vec![Jwk {
+                kty: "RSA".into(),
+                kid: Some(kid.into()),
+                n: Some(URL_SAFE_NO_PAD.encode(rsa.n().to_vec())),
+                e: Some(URL_SAFE_NO_PAD.encode(rsa.e().to_vec())),
+            }],
+        }
+    }
+
+    fn sign(key: &PKey<Private>, kid: &str, claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD
+            .encode(serde_json::json!({ "alg": "RS256", "kid": kid, "typ": "JWT" }).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer
+            .update(format!("{header}.{payload}").as_bytes())
+            .unwrap();
+        let signature = URL_SAFE_NO_PAD.encode(signer.sign_to_vec().unwrap());
+        format!("{header}.{payload}.{signature}")
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": "https://idp.example.com",
+            "sub": "user-123",
+            "aud": ["other-client", "alfredo"],
+            "exp": NOW + 300,
+            "nonce": "n-1",
+            "email": "ana@acme.com",
+            "email_verified": true,
+        })
+    }
+
+    #[test]
+    fn verify_id_token_accepts_a_valid_token() {
+        let key = signing_key();
+        let token = sign(&key, "k1", claims());
+        let verified = verify_id_token(
+            &token,
+            &jwks_for(&key, "k1"),
+            "https://idp.example.com",
+            "alfredo",
+            "n-1",
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(verified.sub, "user-123");
+        assert_eq!(verified.verified_email(), Some("ana@acme.com"));
+    }
+
+    #[test]
+    fn verify_id_token_rejects_bad_signature_and_claims() {
+        let key = signing_key();
+        let jwks = jwks_for(&key, "k1");
+        let verify = |token: &str| {
+            verify_id_token(
+                token,
+                &jwks,
+                "https://idp.example.com",
+                "alfredo",
+                "n-1",
+                NOW,
+            )
+        };
+
+        let other_key = signing_key();
+        assert!(verify(&sign(&other_key, "k1", claims())).is_err());
+
+        let token = sign(&key, "k1", claims());
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let (header, _) = signed.split_once('.').unwrap();
+        let mut forged = claims();
+        forged["sub"] = "someone-else".into();
+        let forged_payload = URL_SAFE_NO_PAD.encode(forged.to_string());
+        assert!(verify(&format!("{header}.{forged_payload}.{signature}")).is_err());
+
+        for (field, value) in [
+            ("iss", serde_json::json!("https://evil.example.com")),
+            ("aud", serde_json::json!("other-client")),
+            ("exp", serde_json::json!(NOW - 600)),
+            ("nonce", serde_json::json!("replayed")),
+        ] {
+            let mut bad = claims();
+            bad[field] = value;
+            assert!(verify(&sign(&key, "k1", bad)).is_err(), "{field} accepted");
+        }
+    }
+
+    #[test]
+    fn unverified_email_is_not_exposed() {
+        let mut raw = claims();
+        raw["email_verified"] = false.into();
+        let parsed: IdTokenClaims = serde_json::from_value(raw).unwrap();
+        assert_eq!(parsed.verified_email(), None);
+    }
+}
@@ -4,10 +4,12 @@
 // The whole document is gated behind the session middleware (see main.rs), so
 // the interactive docs are only reachable by a logged-in user. Authentication
 // itself is a session cookie named `session`, modeled below as an apiKey scheme.
+// The read-only data endpoints also take a personal access token as a bearer
+// `token`.
 
 use utoipa::{
     Modify, OpenApi,
-    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
 };
 
 struct SecurityAddon;
@@ -21,6 +23,10 @@ impl Modify for SecurityAddon {
                 crate::session::SESSION_COOKIE_NAME,
             ))),
         );
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
     }
 }
 
@@ -43,11 +49,17 @@ impl Modify for SecurityAddon {
     paths(
         // auth / profile / misc
         crate::routes::login::login,
+        crate::routes::login_link::login_link_request,
         crate::routes::logout::logout,
         crate::routes::setup::setup,
         crate::routes::secret::secret_generate,
         crate::routes::profile::me_companies,
         crate::routes::profile::me,
+        crate::routes::dashboard::dashboard_layout,
+        crate::routes::dashboard::dashboard_layout_update,
+        crate::routes::session_status::session_status,
+        crate::routes::session_status::session_renew,
+        crate::routes::schema::model_schema,
         crate::routes::tiempo::tiempo_data,
         crate::routes::pdf::pdf_preview,
         crate::routes::admin::account::account_profile_data_api,
@@ -59,6 +71,8 @@ impl Modify for SecurityAddon {
         crate::routes::admin::finance::accounts::account_data_api,
         crate::routes::admin::finance::accounts::account_update_api,
         crate::routes::admin::finance::accounts::account_delete_api,
+        crate::routes::admin::finance::accounts::accounts_revaluation_api,
+        crate::routes::admin::finance::accounts::account_valuations_api,
         crate::routes::admin::finance::categories::categories_data_api,
         crate::routes::admin::finance::categories::categories_create_api,
         crate::routes::admin::finance::categories::category_data_api,
@@ -69,6 +83,22 @@ impl Modify for SecurityAddon {
         crate::routes::admin::finance::contacts::contact_data_api,
         crate::routes::admin::finance::contacts::contact_update_api,
         crate::routes::admin::finance::contacts::contact_delete_api,
+        crate::routes::admin::finance::contacts::contact_erase_api,
+        crate::routes::admin::integrity::entity_dependencies,
+        crate::routes::admin::integrity::entity_archive,
+        crate::routes::admin::integrity::entity_restore,
+        crate::routes::admin::integrity::orphans_report,
+        crate::routes::admin::integrity::orphans_fix,
+        crate::routes::admin::finance::options::options_search_api,
+        crate::routes::admin::finance::options::options_bundle_api,
+        crate::routes::admin::finance::custom_fields::custom_fields_data_api,
+        crate::routes::admin::finance::custom_fields::custom_fields_create_api,
+        crate::routes::admin::finance::custom_fields::custom_field_delete_api,
+        crate::routes::admin::finance::tax_profiles::tax_profiles_data_api,
+        crate::routes::admin::finance::tax_profiles::tax_profiles_create_api,
+        crate::routes::admin::finance::account_groups::account_groups_data_api,
+        crate::routes::admin::finance::account_groups::account_groups_create_api,
+        crate::routes::admin::finance::account_groups::accounts_reorder_api,
 
         // finance — recurring plans / planned entries
         crate::routes::admin::finance::recurring_plans::recurring_plans_data_api,
@@ -80,22 +110,51 @@ impl Modify for SecurityAddon {
         crate::routes::admin::finance::planned_entries::planned_entries_data_api,
         crate::routes::admin::finance::planned_entries::planned_entries_create_api,
         crate::routes::admin::finance::planned_entries::planned_entries_bulk_pay_api,
+        crate::routes::admin::finance::planned_entries::planned_entries_roll_forward_api,
         crate::routes::admin::finance::planned_entries::planned_entry_data_api,
         crate::routes::admin::finance::planned_entries::planned_entry_update_api,
         crate::routes::admin::finance::planned_entries::planned_entry_delete_api,
         crate::routes::admin::finance::planned_entries::planned_entry_pay_api,
+        crate::routes::admin::finance::maintenance::maintenance_counts_api,
+        crate::routes::admin::finance::maintenance::maintenance_archive_api,
+        crate::routes::admin::finance::maintenance::maintenance_restore_api,
+        crate::routes::admin::finance::maintenance::reindex_status_api,
+        crate::routes::admin::finance::maintenance::reindex_start_api,
 
         // finance — transactions / forecasts
         crate::routes::admin::finance::transactions::transactions_data_api,
         crate::routes::admin::finance::transactions::transactions_create_api,
+        crate::routes::admin::finance::receipts::transactions_receipt_upload_api,
         crate::routes::admin::finance::transactions::transaction_data_api,
+        crate::routes::admin::finance::transactions::transaction_by_external_id_api,
         crate::routes::admin::finance::transactions::transaction_update_api,
         crate::routes::admin::finance::transactions::transaction_delete_api,
+        crate::routes::admin::finance::transactions::transactions_pending_api,
+        crate::routes::admin::finance::transactions::transactions_confirm_api,
+        crate::routes::admin::finance::transactions::transactions_bulk_api,
+        crate::routes::admin::finance::refunds::transaction_refund_api,
+        crate::routes::admin::finance::card_holds::transaction_hold_api,
+        crate::routes::admin::finance::card_holds::transaction_capture_api,
+        crate::routes::admin::finance::card_holds::transaction_void_api,
+        crate::routes::admin::finance::deposit_allocations::planned_entry_deposits_data_api,
+        crate::routes::admin::finance::deposit_allocations::planned_entry_deposits_api,
+        crate::routes::admin::finance::text_replace::transactions_replace_api,
         crate::routes::admin::finance::forecasts::forecasts_data_api,
         crate::routes::admin::finance::forecasts::forecasts_create_api,
         crate::routes::admin::finance::forecasts::forecast_data_api,
+        crate::routes::admin::finance::forecasts::forecast_chart_data,
         crate::routes::admin::finance::forecasts::forecast_update_api,
         crate::routes::admin::finance::forecasts::forecast_delete_api,
+        crate::routes::admin::finance::forecasts::forecasts_generate_api,
+
+        // finance — reports
+        crate::routes::admin::finance::reports::reports_burn_rate_api,
+        crate::routes::admin::finance::reports::reports_net_worth_api,
+        crate::routes::admin::finance::reports::reports_cash_calendar_api,
+        crate::routes::admin::finance::reports::reports_income_statement_api,
+        crate::routes::admin::finance::reports::reports_taxes_api,
+        crate::routes::admin::finance::reports::reports_fx_api,
+        crate::routes::admin::finance::reports::reports_fx_record_api,
 
         // operations — orders
         crate::routes::admin::finance::orders::orders_data_api,
@@ -154,11 +213,20 @@ impl Modify for SecurityAddon {
         crate::routes::admin::companies::company_create_api,
         crate::routes::admin::companies::company_data_api,
         crate::routes::admin::companies::company_update_api,
+        crate::routes::admin::companies::company_export_api,
+        crate::routes::admin::companies::company_offboard_api,
+        crate::routes::admin::storage::company_storage_api,
+        crate::routes::admin::storage::company_storage_update_api,
+        crate::routes::admin::fiscal_calendar::company_fiscal_calendar_api,
+        crate::routes::admin::fiscal_calendar::company_fiscal_calendar_update_api,
+        crate::routes::admin::required_fields::company_required_fields_api,
+        crate::routes::admin::required_fields::company_required_fields_update_api,
         crate::routes::admin::users_api::api_users_index,
         crate::routes::admin::users_api::api_user_detail,
         crate::routes::admin::users_api::api_users_create,
         crate::routes::admin::users_api::api_users_update,
         crate::routes::admin::users_api::api_users_delete,
+        crate::routes::admin::users_api::api_user_accounts_update,
 
         // cfdi — reads / download jobs
         crate::routes::admin::cfdis::cfdis_data_api,
@@ -205,5 +273,9 @@ mod tests {
             json["components"]["securitySchemes"]["session"].is_object(),
             "session security scheme must be registered"
         );
+        assert!(
+            json["components"]["securitySchemes"]["token"].is_object(),
+            "personal access token scheme must be registered"
+        );
     }
 }
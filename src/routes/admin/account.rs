@@ -1,19 +1,62 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect},
+    extract::{Form, Multipart, Path, Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
 };
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tokio::fs;
+
+#[allow(unused_imports)]
+use crate::filters;
 
 use crate::{
+    mailer::{Email, app_link},
+    models::{DateFormat, FormatPreferences},
+    oidc::OidcConfig,
+    routes::Routes,
     session::SessionUser,
-    state::{AppState, get_user_by_id, update_user},
+    state::{
+        AppState, DECIMAL_SEPARATORS, MAX_DISPLAY_NAME_CHARS, THOUSANDS_SEPARATORS, UTC_OFFSETS,
+        confirm_email_change, create_api_token, get_user_by_id, list_api_tokens,
+        list_sso_identities, pending_email_change, request_email_change, revoke_api_token,
+        set_user_avatar, set_user_display_name, set_user_format_preferences, update_user,
+        user_avatar,
+    },
+    uploads::{BodyLimits, sniff_content_type},
 };
 
+/// The signed-in user's account page, profile, display preferences and API
+/// tokens, and the avatars shown next to user names.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .route("/account", get(account_edit).post(account_update))
+        .route("/account/confirm_email", get(account_confirm_email))
+        .route(
+            "/account/profile",
+            post(account_profile_update).layer(limits.upload_layer()),
+        )
+        .route("/account/format", post(account_format_update))
+        .route("/account/tokens", post(account_tokens_create))
+        .route("/account/tokens/{id}/revoke", post(account_tokens_revoke))
+        .route(
+            "/api/account",
+            get(account_profile_data_api).post(account_profile_update_api),
+        )
+        .route("/users/{id}/avatar", get(user_avatar_image))
+}
+
+const MAX_AVATAR_BYTES: usize = 512 * 1024;
+
+/// Avatar formats browsers draw everywhere.
+const AVATAR_CONTENT_TYPES: [&str; 3] = ["image/png", "image/jpeg", "image/webp"];
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -24,8 +67,75 @@ fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
 #[template(path = "account/edit.html")]
 struct AccountTemplate {
     form: AccountFormView,
+    profile: ProfileView,
     message: Option<String>,
     errors: Option<String>,
+    pending_email: Option<String>,
+    /// Linked SSO identities; `None` when OIDC is not configured.
+    sso: Option<SsoAccountView>,
+    format: FormatView,
+    /// Personal access tokens of the user.
+    tokens: Vec<ApiTokenRow>,
+    /// Token just created, shown once.
+    new_token: Option<String>,
+}
+
+struct ApiTokenRow {
+    id: String,
+    name: String,
+    prefix: String,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+
+struct FormatView {
+    decimal_separators: Vec<FormatOption>,
+    thousands_separators: Vec<FormatOption>,
+    date_formats: Vec<FormatOption>,
+    utc_offsets: Vec<FormatOption>,
+}
+
+struct FormatOption {
+    value: &'static str,
+    label: &'static str,
+    selected: bool,
+}
+
+struct SsoAccountView {
+    provider_name: String,
+    identities: Vec<SsoIdentityRow>,
+}
+
+struct SsoIdentityRow {
+    id: String,
+    email: String,
+    linked_at: String,
+}
+
+struct ProfileView {
+    display_name: String,
+    /// Where the current avatar is served, if the user has one.
+    avatar_url: Option<String>,
+    /// Shown in place of the avatar when there is none.
+    initial: String,
+    max_chars: usize,
+}
+
+fn profile_view(session_user: &SessionUser) -> ProfileView {
+    let user = session_user.user();
+    ProfileView {
+        display_name: user.display_name.clone().unwrap_or_default(),
+        avatar_url: user
+            .has_avatar
+            .then(|| format!("/users/{}/avatar", user.id.to_hex())),
+        initial: user
+            .label()
+            .chars()
+            .next()
+            .map(|c| c.to_uppercase().to_string())
+            .unwrap_or_default(),
+        max_chars: MAX_DISPLAY_NAME_CHARS,
+    }
 }
 
 #[derive(Clone)]
@@ -40,10 +150,24 @@ pub(crate) struct AccountFormData {
     secret: String,
 }
 
+#[derive(Deserialize)]
+pub struct FormatFormData {
+    decimal_separator: String,
+    thousands_separator: String,
+    date_format: String,
+    #[serde(default)]
+    utc_offset: String,
+}
+
 #[derive(Serialize)]
 pub struct AccountData {
     id: String,
     username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+    /// New username awaiting confirmation, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_username: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -53,35 +177,445 @@ pub struct AccountPayload {
     /// blank value here means "keep the existing one" — letting a client save
     /// a username-only change without knowing or rotating the authenticator.
     secret: String,
+    /// Name shown instead of the username; blank clears it and leaving it
+    /// out keeps the current one.
+    #[serde(default)]
+    display_name: Option<String>,
 }
 
 #[derive(Deserialize, Default)]
 pub(crate) struct AccountQuery {
     saved: Option<bool>,
+    pending: Option<bool>,
+    confirmed: Option<bool>,
+    invalid: Option<bool>,
+    sso_linked: Option<bool>,
+    sso_unlinked: Option<bool>,
+    format_saved: Option<bool>,
+    format_invalid: Option<bool>,
+    token_revoked: Option<bool>,
+    profile_saved: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenFormData {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailQuery {
+    token: String,
+}
+
+/// Emails the confirmation link for a pending email change to the new
+/// address. The token only travels in the message, never in the log.
+pub(crate) async fn send_email_change_link(
+    state: &AppState,
+    new_username: &str,
+    token: &str,
+) -> anyhow::Result<()> {
+    let email = Email {
+        to: new_username.to_string(),
+        subject: "Confirma tu nuevo usuario".to_string(),
+        text: format!(
+            "Abre este enlace con tu sesion iniciada para usar {new_username} como usuario:\n\n{}",
+            app_link(&format!("/account/confirm_email?token={token}"))
+        ),
+    };
+    state.mailer.send(&email).await
+}
+
+/// Starts the confirmation flow when `requested` differs from the current
+/// username. Returns `Ok(true)` when a change is now pending. A link that
+/// could not be sent is logged and the change stays pending, so asking
+/// again sends a new one.
+pub(crate) async fn start_email_change(
+    state: &AppState,
+    user_id: &ObjectId,
+    current: &str,
+    requested: &str,
+) -> anyhow::Result<bool> {
+    if requested == current {
+        return Ok(false);
+    }
+    let token = request_email_change(state, user_id, requested).await?;
+    if let Err(err) = send_email_change_link(state, requested, &token).await {
+        eprintln!("[account] email change link not sent to {requested}: {err}");
+    }
+    Ok(true)
+}
+
+async fn load_sso_view(state: &AppState, user_id: &ObjectId) -> Option<SsoAccountView> {
+    let config = OidcConfig::from_env()?;
+    let identities = list_sso_identities(state, user_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|identity| {
+            Some(SsoIdentityRow {
+                id: identity.id?.to_hex(),
+                email: identity.email.unwrap_or(identity.subject),
+                linked_at: identity
+                    .linked_at
+                    .to_chrono()
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            })
+        })
+        .collect();
+    Some(SsoAccountView {
+        provider_name: config.provider_name,
+        identities,
+    })
+}
+
+async fn load_token_rows(state: &AppState, user_id: &ObjectId) -> Vec<ApiTokenRow> {
+    let day = |date: mongodb::bson::DateTime| date.to_chrono().format("%Y-%m-%d").to_string();
+    list_api_tokens(state, user_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|token| {
+            Some(ApiTokenRow {
+                id: token.id?.to_hex(),
+                name: token.name,
+                prefix: token.prefix,
+                created_at: day(token.created_at),
+                last_used_at: token.last_used_at.map(day),
+            })
+        })
+        .collect()
+}
+
+fn separator_label(separator: &str) -> &'static str {
+    match separator {
+        "." => "Punto (.)",
+        "," => "Coma (,)",
+        " " => "Espacio",
+        _ => "Ninguno",
+    }
+}
+
+fn format_view(preferences: &FormatPreferences) -> FormatView {
+    let separators = |choices: &[&'static str], selected: &str| {
+        choices
+            .iter()
+            .map(|value| FormatOption {
+                value,
+                label: separator_label(value),
+                selected: *value == selected,
+            })
+            .collect()
+    };
+    FormatView {
+        decimal_separators: separators(&DECIMAL_SEPARATORS, &preferences.decimal_separator),
+        thousands_separators: separators(&THOUSANDS_SEPARATORS, &preferences.thousands_separator),
+        date_formats: [
+            (DateFormat::Iso, "2024-01-31"),
+            (DateFormat::DayMonthYear, "31/01/2024"),
+            (DateFormat::MonthDayYear, "01/31/2024"),
+        ]
+        .into_iter()
+        .map(|(format, label)| FormatOption {
+            value: format.as_str(),
+            label,
+            selected: format == preferences.date_format,
+        })
+        .collect(),
+        utc_offsets: UTC_OFFSETS
+            .iter()
+            .map(|value| FormatOption {
+                value,
+                label: if value.is_empty() { "UTC" } else { value },
+                selected: *value == preferences.utc_offset,
+            })
+            .collect(),
+    }
 }
 
 pub async fn account_edit(
-    SessionUser(session): SessionUser,
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
     Query(query): Query<AccountQuery>,
 ) -> Result<Html<String>, StatusCode> {
     let message = if query.saved.unwrap_or(false) {
         Some("Tu información se guardó correctamente".to_string())
+    } else if query.pending.unwrap_or(false) {
+        Some("Te enviamos un enlace para confirmar el nuevo email".to_string())
+    } else if query.confirmed.unwrap_or(false) {
+        Some("Tu nuevo email quedó confirmado".to_string())
+    } else if query.sso_linked.unwrap_or(false) {
+        Some("La identidad quedó vinculada a tu usuario".to_string())
+    } else if query.sso_unlinked.unwrap_or(false) {
+        Some("La identidad se desvinculó de tu usuario".to_string())
+    } else if query.format_saved.unwrap_or(false) {
+        Some("Tus preferencias de formato se guardaron".to_string())
+    } else if query.token_revoked.unwrap_or(false) {
+        Some("El token quedó revocado".to_string())
+    } else if query.profile_saved.unwrap_or(false) {
+        Some("Tu perfil se guardó".to_string())
     } else {
         None
     };
+    let errors = if query.invalid.unwrap_or(false) {
+        Some("El enlace de confirmación no es válido o ya expiró".to_string())
+    } else if query.format_invalid.unwrap_or(false) {
+        Some("Los separadores de miles y decimales deben ser distintos".to_string())
+    } else {
+        None
+    };
+
+    render_account(&state, &session_user, message, errors, None).await
+}
 
+async fn render_account(
+    state: &AppState,
+    session_user: &SessionUser,
+    message: Option<String>,
+    errors: Option<String>,
+    new_token: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let pending_email = pending_email_change(state, session_user.user_id())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(|change| change.new_username);
+
+    let user = session_user.user();
     let form = AccountFormView {
-        email: session.user.username.clone(),
-        secret: session.user.secret.clone(),
+        email: user.username.clone(),
+        secret: user.secret.clone(),
     };
 
     render(AccountTemplate {
         form,
+        profile: profile_view(session_user),
         message,
-        errors: None,
+        errors,
+        pending_email,
+        sso: load_sso_view(state, session_user.user_id()).await,
+        format: format_view(&user.format_preferences),
+        tokens: load_token_rows(state, session_user.user_id()).await,
+        new_token,
     })
 }
 
+/// Creates a personal access token and shows it once on the account page.
+pub async fn account_tokens_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ApiTokenFormData>,
+) -> impl IntoResponse {
+    let name = form.name.trim();
+    if name.is_empty() {
+        let errors = Some("El token necesita un nombre".to_string());
+        return render_account(&state, &session_user, None, errors, None)
+            .await
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response());
+    }
+    let token = match create_api_token(&state, session_user.user_id(), name).await {
+        Ok(token) => token,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let message = Some("Copia el token ahora: no se volverá a mostrar".to_string());
+    render_account(&state, &session_user, message, None, Some(token))
+        .await
+        .map(IntoResponse::into_response)
+        .unwrap_or_else(|status| status.into_response())
+}
+
+pub async fn account_tokens_revoke(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(token_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match revoke_api_token(&state, session_user.user_id(), &token_id).await {
+        Ok(true) => Redirect::to("/account?token_revoked=1").into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn invalid_profile(
+    state: &AppState,
+    session_user: &SessionUser,
+    message: String,
+) -> Response {
+    render_account(state, session_user, None, Some(message), None)
+        .await
+        .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+        .unwrap_or_else(|status| status.into_response())
+}
+
+/// Saves the display name and replaces or removes the avatar. The avatar is
+/// kept unless a new one is sent or `remove_avatar` is checked.
+pub async fn account_profile_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Response {
+    let mut display_name = String::new();
+    let mut remove_avatar = false;
+    let mut avatar: Option<Vec<u8>> = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "display_name" => display_name = field.text().await.unwrap_or_default(),
+            "remove_avatar" => remove_avatar = field.text().await.unwrap_or_default() == "true",
+            "avatar" => {
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
+                if data.len() > MAX_AVATAR_BYTES {
+                    return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+                }
+                if !data.is_empty() {
+                    avatar = Some(data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if display_name.trim().chars().count() > MAX_DISPLAY_NAME_CHARS {
+        let message = format!("El nombre admite hasta {MAX_DISPLAY_NAME_CHARS} caracteres.");
+        return invalid_profile(&state, &session_user, message).await;
+    }
+    let user_id = *session_user.user_id();
+    let new_avatar = match avatar {
+        Some(data) => {
+            let Some(content_type) =
+                sniff_content_type(&data).filter(|ct| AVATAR_CONTENT_TYPES.contains(ct))
+            else {
+                let message = "La foto debe ser una imagen PNG, JPG o WEBP.".to_string();
+                return invalid_profile(&state, &session_user, message).await;
+            };
+            let extension = match content_type {
+                "image/png" => "png",
+                "image/webp" => "webp",
+                _ => "jpg",
+            };
+            let upload_dir = PathBuf::from("uploads")
+                .join("avatars")
+                .join(user_id.to_hex());
+            if let Err(e) = fs::create_dir_all(&upload_dir).await {
+                eprintln!("[account] failed to create avatar dir: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            let path = upload_dir.join(format!("avatar-{}.{extension}", ObjectId::new().to_hex()));
+            if let Err(e) = fs::write(&path, &data).await {
+                eprintln!("[account] failed to write avatar: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Some((path.to_string_lossy().to_string(), content_type))
+        }
+        None => None,
+    };
+
+    if set_user_display_name(&state, &user_id, &display_name)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if new_avatar.is_some() || remove_avatar {
+        let avatar = new_avatar
+            .as_ref()
+            .map(|(path, content_type)| (path.as_str(), *content_type));
+        match set_user_avatar(&state, &user_id, avatar).await {
+            Ok(Some(old)) => {
+                let _ = fs::remove_file(old).await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("[account] avatar update error: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+    Redirect::to("/account?profile_saved=1").into_response()
+}
+
+/// Avatar of a user, for anyone who shares a company with them.
+pub async fn user_avatar_image(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let Ok(user_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if &user_id != session_user.user_id() {
+        let shares_company = match get_user_by_id(&state, &user_id).await {
+            Ok(Some(user)) => user
+                .company_ids
+                .iter()
+                .any(|company_id| session_user.user().company_ids.contains(company_id)),
+            Ok(None) => false,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        if !shares_company {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+    let (path, content_type) = match user_avatar(&state, &user_id).await {
+        Ok(Some(avatar)) => avatar,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match fs::read(&path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "private, max-age=300".to_string()),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Saves how amounts and dates are shown to the user. Takes effect on the
+/// next page, since preferences are read with the session.
+pub async fn account_format_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<FormatFormData>,
+) -> impl IntoResponse {
+    let Some(date_format) = DateFormat::parse(&form.date_format) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let preferences = FormatPreferences {
+        decimal_separator: form.decimal_separator,
+        thousands_separator: form.thousands_separator,
+        date_format,
+        utc_offset: form.utc_offset,
+    };
+    match set_user_format_preferences(&state, session_user.user_id(), &preferences).await {
+        Ok(()) => Redirect::to("/account?format_saved=1").into_response(),
+        Err(_) => Redirect::to("/account?format_invalid=1").into_response(),
+    }
+}
+
+/// Confirms a pending email change from the link sent to the new address.
+/// The session stays valid because it is keyed by user id.
+pub async fn account_confirm_email(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ConfirmEmailQuery>,
+) -> impl IntoResponse {
+    match confirm_email_change(&state, session_user.user_id(), query.token.trim()).await {
+        Ok(Some(_)) => Redirect::to("/account?confirmed=1").into_response(),
+        Ok(None) => Redirect::to("/account?invalid=1").into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/account",
@@ -93,11 +627,21 @@ pub async fn account_edit(
     ),
     security(("session" = []))
 )]
-pub async fn account_profile_data_api(SessionUser(session): SessionUser) -> Json<AccountData> {
-    Json(AccountData {
-        id: session.user.id.to_hex(),
-        username: session.user.username,
-    })
+pub async fn account_profile_data_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AccountData>, StatusCode> {
+    let pending_username = pending_email_change(&state, session_user.user_id())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(|change| change.new_username);
+    let user = session_user.user();
+    Ok(Json(AccountData {
+        id: user.id.to_hex(),
+        username: user.username.clone(),
+        display_name: user.display_name.clone(),
+        pending_username,
+    }))
 }
 
 #[utoipa::path(
@@ -106,7 +650,7 @@ pub async fn account_profile_data_api(SessionUser(session): SessionUser) -> Json
     tag = "auth",
     request_body = AccountPayload,
     responses(
-        (status = 200, description = "Account profile updated"),
+        (status = 200, description = "Account profile updated; a username change stays pending until confirmed"),
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Forbidden"),
         (status = 400, description = "Invalid input")
@@ -122,6 +666,13 @@ pub async fn account_profile_update_api(
     if username.is_empty() {
         return StatusCode::BAD_REQUEST.into_response();
     }
+    if payload
+        .display_name
+        .as_deref()
+        .is_some_and(|name| name.trim().chars().count() > MAX_DISPLAY_NAME_CHARS)
+    {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
     let user = session_user.user();
     // Blank secret keeps the existing one (the endpoint never reveals it). Read
     // it fresh from the DB — the session cache can be stale after a prior edit.
@@ -142,17 +693,36 @@ pub async fn account_profile_update_api(
         .zip(user.company_roles.iter())
         .map(|(id, role)| (id.clone(), role.clone()))
         .collect();
-    match update_user(
+    // The username only changes once the new address confirms it.
+    if update_user(
         &state,
         session_user.user_id(),
-        &username,
+        &user.username,
         &secret,
         &company_roles,
     )
     .await
+    .is_err()
     {
-        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if let Some(display_name) = &payload.display_name
+        && set_user_display_name(&state, session_user.user_id(), display_name)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    match start_email_change(&state, session_user.user_id(), &user.username, &username).await {
+        Ok(true) => {
+            Json(serde_json::json!({ "ok": true, "pending_username": username })).into_response()
+        }
+        Ok(false) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(_) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "El nombre de usuario ya existe" })),
+        )
+            .into_response(),
     }
 }
 
@@ -168,12 +738,20 @@ pub async fn account_update(
         email: email.clone(),
         secret: secret.clone(),
     };
+    let sso = load_sso_view(&state, session_user.user_id()).await;
+    let tokens = load_token_rows(&state, session_user.user_id()).await;
 
     if email.is_empty() || secret.is_empty() {
         return render(AccountTemplate {
             form: form_view,
+            profile: profile_view(&session_user),
             message: None,
             errors: Some("Email y secreto son obligatorios".into()),
+            pending_email: None,
+            sso,
+            format: format_view(&session_user.user().format_preferences),
+            tokens,
+            new_token: None,
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response());
@@ -189,18 +767,40 @@ pub async fn account_update(
     let update_result = update_user(
         &state,
         session_user.user_id(),
-        &email,
+        &user.username,
         &secret,
         &company_roles,
     )
     .await;
+    if update_result.is_err() {
+        return render(AccountTemplate {
+            form: form_view,
+            profile: profile_view(&session_user),
+            message: None,
+            errors: Some("No se pudo guardar la información".into()),
+            pending_email: None,
+            sso,
+            format: format_view(&session_user.user().format_preferences),
+            tokens,
+            new_token: None,
+        })
+        .map(IntoResponse::into_response)
+        .unwrap_or_else(|status| status.into_response());
+    }
 
-    match update_result {
-        Ok(_) => Redirect::to("/account?saved=1").into_response(),
+    match start_email_change(&state, session_user.user_id(), &user.username, &email).await {
+        Ok(true) => Redirect::to("/account?pending=1").into_response(),
+        Ok(false) => Redirect::to("/account?saved=1").into_response(),
         Err(_) => render(AccountTemplate {
             form: form_view,
+            profile: profile_view(&session_user),
             message: None,
-            errors: Some("No se pudo guardar la información".into()),
+            errors: Some("Ese email ya está en uso".into()),
+            pending_email: None,
+            sso,
+            format: format_view(&session_user.user().format_preferences),
+            tokens,
+            new_token: None,
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response()),
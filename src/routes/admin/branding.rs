@@ -0,0 +1,290 @@
+// Company branding: logo, accent color and footer text, shown in the page
+// header and footer and stamped on the PDFs the company generates.
+
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use bson::oid::ObjectId;
+use tokio::fs;
+
+use crate::{
+    flash::Flash,
+    models::CompanyBranding,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, get_company_by_id, quota_exceeded, release_storage, reserve_storage,
+        stored_file_bytes, update_company_branding,
+    },
+    uploads::BodyLimits,
+    uploads::sniff_content_type,
+};
+
+/// Company branding and the logo it serves.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/branding",
+            get(company_branding_edit)
+                .post(company_branding_update)
+                .layer(limits.upload_layer()),
+        )
+        .route("/branding/logo", get(company_logo))
+}
+
+const MAX_LOGO_BYTES: usize = 1024 * 1024;
+const MAX_FOOTER_CHARS: usize = 200;
+
+/// Logo formats every consumer (browsers and Typst) can draw.
+const LOGO_CONTENT_TYPES: [&str; 2] = ["image/png", "image/jpeg"];
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.can_view_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// `#rrggbb`, lowercased; anything else is rejected.
+pub fn normalize_brand_color(value: &str) -> Option<String> {
+    let value = value.trim();
+    let hex = value.strip_prefix('#')?;
+    (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| value.to_lowercase())
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/branding.html")]
+struct CompanyBrandingTemplate {
+    company_id: String,
+    company_name: String,
+    color: String,
+    footer_text: String,
+    has_logo: bool,
+    errors: Option<String>,
+}
+
+fn branding_template(
+    company_id: &str,
+    company_name: String,
+    branding: &CompanyBranding,
+    errors: Option<String>,
+) -> CompanyBrandingTemplate {
+    CompanyBrandingTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        color: branding.color.clone().unwrap_or_default(),
+        footer_text: branding.footer_text.clone().unwrap_or_default(),
+        has_logo: branding.logo_path.is_some(),
+        errors,
+    }
+}
+
+pub async fn company_branding_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(branding_template(
+        &company_id,
+        company.name,
+        &company.branding,
+        None,
+    ))
+}
+
+/// Saves color and footer, and replaces or removes the logo. Fields left
+/// blank clear their setting; the logo is kept unless a new one is sent or
+/// `remove_logo` is checked.
+pub async fn company_branding_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    mut multipart: Multipart,
+) -> Response {
+    let company_object_id = match ObjectId::from_str(&company_id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    if let Err(status) = require_company_admin(&session_user, &company_object_id) {
+        return status.into_response();
+    }
+    let company = match get_company_by_id(&state, &company_object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut color = String::new();
+    let mut footer_text = String::new();
+    let mut remove_logo = false;
+    let mut logo: Option<Vec<u8>> = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "color" => color = field.text().await.unwrap_or_default(),
+            "footer_text" => footer_text = field.text().await.unwrap_or_default(),
+            "remove_logo" => remove_logo = field.text().await.unwrap_or_default() == "true",
+            "logo" => {
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
+                if data.len() > MAX_LOGO_BYTES {
+                    return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+                }
+                if !data.is_empty() {
+                    logo = Some(data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut branding = company.branding.clone();
+    let invalid = |message: &str, branding: &CompanyBranding| {
+        render(branding_template(
+            &company_id,
+            company.name.clone(),
+            branding,
+            Some(message.to_string()),
+        ))
+        .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+        .unwrap_or_else(|s| s.into_response())
+    };
+
+    let footer_text = footer_text.trim();
+    branding.footer_text = (!footer_text.is_empty()).then(|| footer_text.to_string());
+    branding.color = match color.trim() {
+        "" => None,
+        value => match normalize_brand_color(value) {
+            Some(color) => Some(color),
+            None => {
+                return invalid(
+                    "El color debe tener el formato #rrggbb, por ejemplo #0369a1.",
+                    &branding,
+                );
+            }
+        },
+    };
+    if footer_text.chars().count() > MAX_FOOTER_CHARS {
+        return invalid(
+            &format!("El pie de página admite hasta {MAX_FOOTER_CHARS} caracteres."),
+            &branding,
+        );
+    }
+
+    let old_logo = branding.logo_path.clone();
+    let mut reserved = 0;
+    if let Some(data) = logo {
+        let Some(content_type) =
+            sniff_content_type(&data).filter(|ct| LOGO_CONTENT_TYPES.contains(ct))
+        else {
+            return invalid("El logo debe ser una imagen PNG o JPG.", &branding);
+        };
+        if let Err(err) = reserve_storage(&state, &company_object_id, data.len() as u64).await {
+            return match quota_exceeded(&err) {
+                Some(exceeded) => invalid(&exceeded.to_string(), &branding),
+                None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+        }
+        reserved = data.len() as u64;
+        let extension = if content_type == "image/png" {
+            "png"
+        } else {
+            "jpg"
+        };
+        let upload_dir = PathBuf::from("uploads")
+            .join("branding")
+            .join(company_object_id.to_hex());
+        if let Err(e) = fs::create_dir_all(&upload_dir).await {
+            eprintln!("[branding] failed to create upload dir: {e}");
+            let _ = release_storage(&state, &company_object_id, reserved).await;
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        let path = upload_dir.join(format!("logo-{}.{extension}", ObjectId::new().to_hex()));
+        if let Err(e) = fs::write(&path, &data).await {
+            eprintln!("[branding] failed to write logo: {e}");
+            let _ = release_storage(&state, &company_object_id, reserved).await;
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        branding.logo_path = Some(path.to_string_lossy().to_string());
+        branding.logo_content_type = Some(content_type.to_string());
+    } else if remove_logo {
+        branding.logo_path = None;
+        branding.logo_content_type = None;
+    }
+
+    if let Err(e) = update_company_branding(&state, &company_object_id, &branding).await {
+        eprintln!("[branding] db update error: {e}");
+        let _ = release_storage(&state, &company_object_id, reserved).await;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if let Some(old) = old_logo.filter(|old| Some(old) != branding.logo_path.as_ref()) {
+        let size = stored_file_bytes(&old).await;
+        if fs::remove_file(old).await.is_ok() {
+            let _ = release_storage(&state, &company_object_id, size).await;
+        }
+    }
+    (
+        Flash::success("Marca de la compañía guardada."),
+        Redirect::to(&format!("/admin/companies/{company_id}/edit")),
+    )
+        .into_response()
+}
+
+/// Logo of the active company, for any of its members.
+pub async fn company_logo(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let company = match get_company_by_id(&state, session_user.active_company_id()).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let (Some(path), Some(content_type)) = (
+        company.branding.logo_path,
+        company.branding.logo_content_type,
+    ) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match fs::read(&path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "private, max-age=300".to_string()),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
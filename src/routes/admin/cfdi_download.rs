@@ -5,6 +5,7 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::IntoResponse,
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use chrono::{Datelike, NaiveDate};
@@ -15,6 +16,7 @@ use uuid::Uuid;
 use crate::{
     cfdi,
     models::{ContactType, FlowType},
+    routes::Routes,
     sat::{CfdiDownloadRequest, DownloadType, download_cfdis},
     session::SessionUser,
     state::{
@@ -24,6 +26,35 @@ use crate::{
     },
 };
 
+/// SAT CFDI download jobs of a company, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/api/admin/companies/{id}/cfdi/download",
+            post(company_cfdi_download_api),
+        )
+        .route(
+            "/api/admin/companies/{id}/cfdi/jobs",
+            get(company_cfdi_jobs_list),
+        )
+        .route(
+            "/api/admin/companies/{id}/cfdi/jobs/{job_id}",
+            get(company_cfdi_job_status),
+        )
+        .route(
+            "/admin/companies/{id}/cfdi/download",
+            post(company_cfdi_download),
+        )
+        .route(
+            "/admin/companies/{id}/cfdi/jobs",
+            get(company_cfdi_jobs_list),
+        )
+        .route(
+            "/admin/companies/{id}/cfdi/jobs/{job_id}",
+            get(company_cfdi_job_status),
+        )
+}
+
 fn require_company_admin(
     session_user: &SessionUser,
     company_id: &ObjectId,
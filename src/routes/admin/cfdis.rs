@@ -6,6 +6,7 @@ use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     response::Html,
+    routing::get,
 };
 use futures::stream::TryStreamExt;
 use serde::{Deserialize, Serialize};
@@ -13,10 +14,19 @@ use serde::{Deserialize, Serialize};
 #[allow(unused_imports)]
 use crate::filters;
 use crate::{
+    routes::Routes,
     session::SessionUser,
     state::{AppState, list_sat_configs},
 };
 
+/// Downloaded CFDIs.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/cfdis", get(cfdis_index))
+        .route("/api/admin/cfdis/data", get(cfdis_data_api))
+        .route("/api/admin/cfdis/{uuid}", get(cfdi_data_api))
+}
+
 const PER_PAGE: u64 = 50;
 const API_LIMIT: i64 = 5000;
 
@@ -68,7 +78,7 @@ pub async fn cfdis_index(
     State(state): State<Arc<AppState>>,
     Query(q): Query<PageQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
     let active_company = session_user.active_company_id();
@@ -208,7 +218,7 @@ pub async fn cfdis_data_api(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<CfdiDataResponse>, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
     let active_company = session_user.active_company_id();
@@ -343,7 +353,7 @@ pub async fn cfdi_data_api(
     State(state): State<Arc<AppState>>,
     Path(uuid): Path<String>,
 ) -> Result<Json<CfdiDetailResponse>, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
     let active_company = session_user.active_company_id();
@@ -0,0 +1,308 @@
+// Company chat: the Telegram chat or WhatsApp number that receives the
+// overdue alerts and the daily digest, plus a test message to check it. Once
+// the company saves its notification settings, those choose the messages
+// and this page only sets the chat.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use bson::oid::ObjectId;
+use serde::Deserialize;
+
+use crate::{
+    flash::Flash,
+    models::{ChatChannel, ChatNotifications},
+    notifier::chat_notifier_from_env,
+    routes::Routes,
+    session::SessionUser,
+    state::{AppState, get_company_by_id, update_company_chat_notifications},
+};
+
+/// Chat notification settings of a company.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/notifications",
+            get(company_chat_notifications_edit).post(company_chat_notifications_update),
+        )
+        .route(
+            "/admin/companies/{id}/notifications/test",
+            post(company_chat_notifications_test),
+        )
+}
+
+const MAX_CHAT_ID_CHARS: usize = 64;
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.is_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Checks the chat id for the channel. Telegram ids are numbers (negative
+/// for groups) or `@channel` names; WhatsApp takes the number with country
+/// code, `+` optional.
+fn validate_chat_id(channel: ChatChannel, chat_id: &str) -> Result<String, &'static str> {
+    if chat_id.is_empty() {
+        return Err("Escribe el chat o número que recibirá los avisos.");
+    }
+    if chat_id.chars().count() > MAX_CHAT_ID_CHARS {
+        return Err("El chat o número es demasiado largo.");
+    }
+    match channel {
+        ChatChannel::Telegram => {
+            let valid = chat_id
+                .strip_prefix('@')
+                .map(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or_else(|| {
+                    let digits = chat_id.strip_prefix('-').unwrap_or(chat_id);
+                    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+                });
+            if valid {
+                Ok(chat_id.to_string())
+            } else {
+                Err("El chat de Telegram es un número (negativo en grupos) o un @canal.")
+            }
+        }
+        ChatChannel::Whatsapp => {
+            let digits: String = chat_id
+                .strip_prefix('+')
+                .unwrap_or(chat_id)
+                .chars()
+                .filter(|c| !matches!(c, ' ' | '-'))
+                .collect();
+            if (8..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+                Ok(digits)
+            } else {
+                Err("El número de WhatsApp lleva código de país, por ejemplo +52 55 1234 5678.")
+            }
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/chat_notifications.html")]
+struct ChatNotificationsTemplate {
+    company_id: String,
+    company_name: String,
+    channel: String,
+    chat_id: String,
+    overdue_alerts: bool,
+    daily_digest: bool,
+    budget_alerts: bool,
+    /// The company saved notification settings, which choose the messages.
+    managed: bool,
+    telegram_available: bool,
+    whatsapp_available: bool,
+    message: Option<String>,
+    errors: Option<String>,
+}
+
+fn chat_template(
+    company_id: &str,
+    company_name: String,
+    managed: bool,
+    settings: &ChatNotifications,
+    message: Option<String>,
+    errors: Option<String>,
+) -> ChatNotificationsTemplate {
+    ChatNotificationsTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        channel: settings
+            .channel
+            .map(|channel| channel.as_str().to_string())
+            .unwrap_or_default(),
+        chat_id: settings.chat_id.clone(),
+        overdue_alerts: settings.overdue_alerts,
+        daily_digest: settings.daily_digest,
+        budget_alerts: settings.budget_alerts,
+        managed,
+        telegram_available: chat_notifier_from_env(ChatChannel::Telegram).is_some(),
+        whatsapp_available: chat_notifier_from_env(ChatChannel::Whatsapp).is_some(),
+        message,
+        errors,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChatNotificationsForm {
+    #[serde(default)]
+    channel: String,
+    #[serde(default)]
+    chat_id: String,
+    overdue_alerts: Option<bool>,
+    daily_digest: Option<bool>,
+    budget_alerts: Option<bool>,
+}
+
+pub async fn company_chat_notifications_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(chat_template(
+        &company_id,
+        company.name,
+        company.notifications.is_some(),
+        &company.chat_notifications,
+        None,
+        None,
+    ))
+}
+
+/// Saves the chat and which messages it receives. An empty channel turns the
+/// chat notifications off.
+pub async fn company_chat_notifications_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    Form(form): Form<ChatNotificationsForm>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&company_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let company = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut settings = ChatNotifications {
+        channel: ChatChannel::parse(form.channel.trim()),
+        chat_id: form.chat_id.trim().to_string(),
+        overdue_alerts: form.overdue_alerts.unwrap_or(false),
+        daily_digest: form.daily_digest.unwrap_or(false),
+        budget_alerts: form.budget_alerts.unwrap_or(false),
+        ..company.chat_notifications.clone()
+    };
+    if company.notifications.is_some() {
+        // The notification settings choose the messages; only the chat
+        // changes here.
+        let saved = &company.chat_notifications;
+        settings.overdue_alerts = saved.overdue_alerts;
+        settings.daily_digest = saved.daily_digest;
+        settings.budget_alerts = saved.budget_alerts;
+    }
+    if let Some(channel) = settings.channel {
+        match validate_chat_id(channel, &settings.chat_id) {
+            Ok(chat_id) => settings.chat_id = chat_id,
+            Err(message) => {
+                return render(chat_template(
+                    &company_id,
+                    company.name,
+                    company.notifications.is_some(),
+                    &settings,
+                    None,
+                    Some(message.to_string()),
+                ))
+                .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+                .unwrap_or_else(|status| status.into_response());
+            }
+        }
+    }
+
+    if let Err(e) = update_company_chat_notifications(&state, &object_id, &settings).await {
+        eprintln!("[chat notifications] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Notificaciones guardadas."),
+        Redirect::to(&format!("/admin/companies/{company_id}/notifications")),
+    )
+        .into_response()
+}
+
+/// Sends a test message to the saved chat and reports how it went.
+pub async fn company_chat_notifications_test(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let settings = &company.chat_notifications;
+    let outcome = match settings.target() {
+        None => Err("Guarda primero un canal y un chat.".to_string()),
+        Some((channel, chat_id)) => match chat_notifier_from_env(channel) {
+            None => Err(format!(
+                "El servidor no tiene configurado {}.",
+                channel.as_str()
+            )),
+            Some(notifier) => {
+                let text = format!("✅ Prueba de avisos de {}", company.name);
+                notifier
+                    .send_message(chat_id, &text)
+                    .await
+                    .map_err(|err| format!("No se pudo enviar el mensaje: {err}"))
+            }
+        },
+    };
+    let (message, errors) = match outcome {
+        Ok(()) => (Some("Mensaje de prueba enviado.".to_string()), None),
+        Err(error) => (None, Some(error)),
+    };
+    render(chat_template(
+        &company_id,
+        company.name.clone(),
+        company.notifications.is_some(),
+        settings,
+        message,
+        errors,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_ids_are_checked_per_channel() {
+        assert!(validate_chat_id(ChatChannel::Telegram, "-100123456").is_ok());
+        assert!(validate_chat_id(ChatChannel::Telegram, "@avisos_acme").is_ok());
+        assert!(validate_chat_id(ChatChannel::Telegram, "acme").is_err());
+        assert_eq!(
+            validate_chat_id(ChatChannel::Whatsapp, "+52 55 1234-5678"),
+            Ok("525512345678".to_string())
+        );
+        assert!(validate_chat_id(ChatChannel::Whatsapp, "1234").is_err());
+    }
+}
@@ -1,13 +1,14 @@
-use std::{collections::HashSet, str::FromStr, sync::Arc};
+use std::{collections::HashSet, str::FromStr, sync::Arc, time::SystemTime};
 
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Path, State},
-    http::StatusCode,
+    extract::{Form, Path, Query, State},
+    http::{StatusCode, header},
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
-use bson::doc;
+use bson::{Bson, doc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use slug::slugify;
@@ -15,18 +16,70 @@ use slug::slugify;
 #[allow(unused_imports)]
 use crate::filters;
 
-use super::sat_configs::{SatConfigRow, load_sat_configs_for_company};
+use super::{
+    confirm::{ConfirmForm, Confirmation, company_delete_requires_name, confirm_page, verify},
+    sat_configs::{SatConfigRow, load_sat_configs_for_company},
+};
 use crate::{
+    flash::Flash,
     models::UserRole,
+    routes::Routes,
     session::SessionUser,
     state::{
-        AppState, add_user_to_company, create_company, delete_company, get_company_by_id,
-        list_companies, update_company,
+        AppState, RetentionPolicy, add_user_to_company, clear_monthly_summaries, create_company,
+        delete_company, export_company_bundle, get_company_by_id, list_companies, offboard_company,
+        save_company_export, set_company_sso_domains, sso_domain_owner, update_company,
     },
 };
 
 use super::finance::helpers::require_admin_active;
 
+/// Companies, their export, offboarding and bulk deletes.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies",
+            get(companies_index).post(companies_create),
+        )
+        .route(
+            "/api/admin/companies",
+            get(companies_data_api).post(company_create_api),
+        )
+        .route("/api/admin/companies/{id}", get(company_data_api))
+        .route("/api/admin/companies/{id}/update", post(company_update_api))
+        .route("/api/admin/companies/{id}/delete", post(company_delete_api))
+        .route("/api/admin/companies/{id}/export", get(company_export_api))
+        .route(
+            "/api/admin/companies/{id}/offboard",
+            post(company_offboard_api),
+        )
+        .route(
+            "/api/admin/companies/{id}/cfdis/delete_all",
+            post(company_cfdis_delete_all_api),
+        )
+        .route(
+            "/api/admin/companies/{id}/transactions/delete_all",
+            post(company_transactions_delete_all_api),
+        )
+        .route("/admin/companies/new", get(companies_new))
+        .route("/admin/companies/{id}/edit", get(companies_edit))
+        .route("/admin/companies/{id}/update", post(companies_update))
+        .route(
+            "/admin/companies/{id}/delete",
+            get(companies_delete_confirm).post(companies_delete),
+        )
+        .route(
+            "/admin/companies/{id}/cfdis/delete_all",
+            get(companies_delete_all_cfdis_confirm).post(companies_delete_all_cfdis),
+        )
+        .route(
+            "/admin/companies/{id}/transactions/delete_all",
+            get(companies_delete_all_transactions_confirm).post(companies_delete_all_transactions),
+        )
+        .route("/admin/companies/{id}/export", get(companies_export))
+        .route("/admin/companies/{id}/offboard", post(companies_offboard))
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -37,6 +90,27 @@ fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
 #[template(path = "admin/companies/index.html")]
 struct CompaniesIndexTemplate {
     companies: Vec<CompanyRow>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CompaniesIndexQuery {
+    /// Slug of a company that was just off-boarded.
+    #[serde(default)]
+    offboarded: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CompanyEditQuery {
+    #[serde(default)]
+    offboard_error: Option<bool>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CompanyOffboardPayload {
+    /// Must repeat the company slug, as a guard against off-boarding the
+    /// wrong company.
+    confirm_slug: String,
 }
 
 struct CompanyRow {
@@ -60,6 +134,7 @@ pub struct CompanyData {
     default_currency: String,
     is_active: bool,
     notes: Option<String>,
+    sso_domains: Vec<String>,
     is_current: bool,
 }
 
@@ -74,6 +149,10 @@ pub struct CompanyPayload {
     is_active: Option<bool>,
     #[serde(default)]
     notes: Option<String>,
+    /// Email domains provisioned into this company on SSO login. Left
+    /// untouched when omitted.
+    #[serde(default)]
+    sso_domains: Option<Vec<String>>,
 }
 
 #[derive(Template)]
@@ -90,6 +169,8 @@ struct CompanyFormTemplate {
     is_current: bool,
     company_id: String,
     sat_configs: Vec<SatConfigRow>,
+    /// Comma-separated SSO email domains.
+    sso_domains: String,
 }
 
 #[derive(Deserialize)]
@@ -102,6 +183,8 @@ pub(crate) struct CompanyFormData {
     is_active: bool,
     #[serde(default)]
     notes: Option<String>,
+    #[serde(default)]
+    sso_domains: Option<String>,
 }
 
 fn has_admin_role_for(session_user: &SessionUser, company_id: &ObjectId) -> bool {
@@ -110,7 +193,7 @@ fn has_admin_role_for(session_user: &SessionUser, company_id: &ObjectId) -> bool
         .company_ids
         .iter()
         .zip(session_user.user().company_roles.iter())
-        .any(|(cid, role)| cid == company_id && role.is_admin())
+        .any(|(cid, role)| cid == company_id && role.can_view_admin())
 }
 
 fn company_data(
@@ -125,6 +208,7 @@ fn company_data(
         default_currency: company.default_currency,
         is_active: company.is_active,
         notes: company.notes,
+        sso_domains: company.sso_domains,
         is_current: &id == session_user.active_company_id(),
     })
 }
@@ -258,10 +342,27 @@ pub async fn company_create_api(
     if let Err(status) = require_admin_active(&session_user) {
         return status.into_response();
     }
+    let raw_sso_domains = payload
+        .sso_domains
+        .as_ref()
+        .map(|domains| domains.join(","));
     let (name, slug, default_currency, is_active, notes) = match parse_company_payload(payload) {
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    let sso_domains = match raw_sso_domains {
+        Some(raw) => match check_sso_domains(&state, &raw, None).await {
+            Ok(domains) => Some(domains),
+            Err(msg) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": msg })),
+                )
+                    .into_response();
+            }
+        },
+        None => None,
+    };
     match slug_conflicts(&state, &slug, None).await {
         Ok(true) => {
             return (
@@ -275,6 +376,13 @@ pub async fn company_create_api(
     }
     match create_company(&state, &name, &slug, &default_currency, is_active, notes).await {
         Ok(company_id) => {
+            if let Some(domains) = sso_domains
+                && set_company_sso_domains(&state, &company_id, &domains)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             match add_user_to_company(&state, session_user.user_id(), &company_id, UserRole::Admin)
                 .await
             {
@@ -326,10 +434,27 @@ pub async fn company_update_api(
     {
         return StatusCode::NOT_FOUND.into_response();
     }
+    let raw_sso_domains = payload
+        .sso_domains
+        .as_ref()
+        .map(|domains| domains.join(","));
     let (name, slug, default_currency, is_active, notes) = match parse_company_payload(payload) {
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    let sso_domains = match raw_sso_domains {
+        Some(raw) => match check_sso_domains(&state, &raw, Some(&object_id)).await {
+            Ok(domains) => Some(domains),
+            Err(msg) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": msg })),
+                )
+                    .into_response();
+            }
+        },
+        None => None,
+    };
     match slug_conflicts(&state, &slug, Some(&object_id)).await {
         Ok(true) => {
             return (
@@ -352,7 +477,16 @@ pub async fn company_update_api(
     )
     .await
     {
-        Ok(_) => Json(serde_json::json!({ "ok": true, "slug": slug })).into_response(),
+        Ok(_) => {
+            if let Some(domains) = sso_domains
+                && set_company_sso_domains(&state, &object_id, &domains)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Json(serde_json::json!({ "ok": true, "slug": slug })).into_response()
+        }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -451,11 +585,12 @@ pub async fn company_transactions_delete_all_api(
         return StatusCode::FORBIDDEN.into_response();
     }
     // Transactions store `company_id` as an ObjectId (see finance inserts).
-    match state
+    let deleted = state
         .transactions
         .delete_many(doc! { "company_id": object_id })
-        .await
-    {
+        .await;
+    let _ = clear_monthly_summaries(&state, &object_id).await;
+    match deleted {
         Ok(res) => Json(serde_json::json!({ "ok": true, "deleted": res.deleted_count }))
             .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
@@ -465,6 +600,7 @@ pub async fn company_transactions_delete_all_api(
 pub async fn companies_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<CompaniesIndexQuery>,
 ) -> Result<Html<String>, StatusCode> {
     let allowed: HashSet<_> = session_user.user().company_ids.iter().cloned().collect();
     let active_id = session_user.active_company_id().clone();
@@ -489,7 +625,13 @@ pub async fn companies_index(
         })
         .collect();
 
-    render(CompaniesIndexTemplate { companies })
+    let message = query.offboarded.map(|slug| {
+        format!(
+            "La compañía {slug} quedó archivada. Su exportación se guardó en el servidor y se borrará definitivamente al terminar el periodo de gracia."
+        )
+    });
+
+    render(CompaniesIndexTemplate { companies, message })
 }
 
 pub async fn companies_new(
@@ -508,6 +650,7 @@ pub async fn companies_new(
         is_current: false,
         company_id: String::new(),
         sat_configs: vec![],
+        sso_domains: String::new(),
     })
 }
 
@@ -533,6 +676,7 @@ pub async fn companies_create(
             is_current: false,
             company_id: String::new(),
             sat_configs: vec![],
+            sso_domains: form.sso_domains.clone().unwrap_or_default(),
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response());
@@ -559,6 +703,7 @@ pub async fn companies_create(
             is_current: false,
             company_id: String::new(),
             sat_configs: vec![],
+            sso_domains: form.sso_domains.clone().unwrap_or_default(),
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response());
@@ -590,12 +735,41 @@ pub async fn companies_create(
                 is_current: false,
                 company_id: String::new(),
                 sat_configs: vec![],
+                sso_domains: form.sso_domains.clone().unwrap_or_default(),
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     }
 
+    let sso_domains = match check_sso_domains(
+        &state,
+        form.sso_domains.as_deref().unwrap_or_default(),
+        None,
+    )
+    .await
+    {
+        Ok(domains) => domains,
+        Err(msg) => {
+            return render(CompanyFormTemplate {
+                action: "/admin/companies".into(),
+                name: name.to_string(),
+                slug: slug_val.to_string(),
+                default_currency: form.default_currency.clone(),
+                is_active,
+                notes: form.notes.clone().unwrap_or_default(),
+                is_edit: false,
+                errors: Some(msg),
+                is_current: false,
+                company_id: String::new(),
+                sat_configs: vec![],
+                sso_domains: form.sso_domains.clone().unwrap_or_default(),
+            })
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+
     match create_company(
         &state,
         name,
@@ -607,10 +781,20 @@ pub async fn companies_create(
     .await
     {
         Ok(company_id) => {
+            if set_company_sso_domains(&state, &company_id, &sso_domains)
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             match add_user_to_company(&state, session_user.user_id(), &company_id, UserRole::Admin)
                 .await
             {
-                Ok(_) => Redirect::to("/admin/companies").into_response(),
+                Ok(_) => (
+                    Flash::success("Compañía creada."),
+                    Redirect::to("/admin/companies"),
+                )
+                    .into_response(),
                 Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             }
         }
@@ -622,6 +806,7 @@ pub async fn companies_edit(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(query): Query<CompanyEditQuery>,
 ) -> Result<Html<String>, StatusCode> {
     let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
 
@@ -644,10 +829,14 @@ pub async fn companies_edit(
         is_active: company.is_active,
         notes: company.notes.unwrap_or_default(),
         is_edit: true,
-        errors: None,
+        errors: query
+            .offboard_error
+            .unwrap_or(false)
+            .then(|| "Escribe el slug exacto de la compañía para confirmar la baja.".to_string()),
         is_current: company.id.as_ref() == Some(session_user.active_company_id()),
         company_id: id.clone(),
         sat_configs,
+        sso_domains: company.sso_domains.join(", "),
     })
 }
 
@@ -683,6 +872,7 @@ pub async fn companies_update(
             is_current: &object_id == session_user.active_company_id(),
             company_id: id.clone(),
             sat_configs: load_sat_configs_for_company(&state, &object_id).await,
+            sso_domains: form.sso_domains.clone().unwrap_or_default(),
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response());
@@ -709,6 +899,7 @@ pub async fn companies_update(
             is_current: &object_id == session_user.active_company_id(),
             company_id: id.clone(),
             sat_configs: load_sat_configs_for_company(&state, &object_id).await,
+            sso_domains: form.sso_domains.clone().unwrap_or_default(),
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response());
@@ -740,49 +931,203 @@ pub async fn companies_update(
                 is_current: &object_id == session_user.active_company_id(),
                 company_id: id.clone(),
                 sat_configs: load_sat_configs_for_company(&state, &object_id).await,
+                sso_domains: form.sso_domains.clone().unwrap_or_default(),
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     }
 
+    let sso_domains = match check_sso_domains(
+        &state,
+        form.sso_domains.as_deref().unwrap_or_default(),
+        Some(&object_id),
+    )
+    .await
+    {
+        Ok(domains) => domains,
+        Err(msg) => {
+            return render(CompanyFormTemplate {
+                action: format!("/admin/companies/{}/update", id),
+                name: name.to_string(),
+                slug: slug_val.to_string(),
+                default_currency: form.default_currency.clone(),
+                is_active,
+                notes: form.notes.clone().unwrap_or_default(),
+                is_edit: true,
+                errors: Some(msg),
+                is_current: &object_id == session_user.active_company_id(),
+                company_id: id.clone(),
+                sat_configs: load_sat_configs_for_company(&state, &object_id).await,
+                sso_domains: form.sso_domains.clone().unwrap_or_default(),
+            })
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+
     match update_company(
         &state, &object_id, name, slug_val, currency, is_active, notes,
     )
     .await
     {
         Ok(_) => {
+            if set_company_sso_domains(&state, &object_id, &sso_domains)
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             if &object_id == session_user.active_company_id() {
                 return axum::Json(CompanyUpdateResponse { slug: final_slug }).into_response();
             }
-            Redirect::to("/admin/companies").into_response()
+            (
+                Flash::success("Compañía actualizada."),
+                Redirect::to("/admin/companies"),
+            )
+                .into_response()
         }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
+/// A company the user may delete or empty, with its name for the
+/// confirmation page. The active company cannot be deleted.
+async fn company_for_delete(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, String), StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !has_admin_role_for(session_user, &object_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let company = get_company_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((object_id, company.name))
+}
+
+/// Confirmation of a company-wide delete, asking for the company's name
+/// when `COMPANY_DELETE_REQUIRE_NAME` is on.
+fn company_confirmation(
+    id: &str,
+    name: &str,
+    title: &str,
+    message: String,
+    action: &str,
+) -> Confirmation {
+    Confirmation {
+        title: title.to_string(),
+        message,
+        action: format!("/admin/companies/{id}/{action}"),
+        cancel_url: format!("/admin/companies/{id}/edit"),
+        button_label: title.to_string(),
+        expected_name: company_delete_requires_name().then(|| name.to_string()),
+    }
+}
+
+fn company_delete_confirmation(id: &str, name: &str) -> Confirmation {
+    Confirmation {
+        cancel_url: "/admin/companies".to_string(),
+        ..company_confirmation(
+            id,
+            name,
+            "Eliminar compañía",
+            format!(
+                "Se eliminará la compañía «{name}». Si aún tiene registros que la usan, se archivará en su lugar."
+            ),
+            "delete",
+        )
+    }
+}
+
+/// GET /admin/companies/{id}/delete — asks before deleting.
+pub async fn companies_delete_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (object_id, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    if &object_id == session_user.active_company_id() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    confirm_page(&session_user, &company_delete_confirmation(&id, &name))
+}
+
 pub async fn companies_delete(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ConfirmForm>,
 ) -> impl IntoResponse {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    let (object_id, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
     };
 
     // Do not allow deleting the currently active company for this session.
     if &object_id == session_user.active_company_id() {
         return StatusCode::FORBIDDEN.into_response();
     }
-
-    if !has_admin_role_for(&session_user, &object_id) {
-        return StatusCode::FORBIDDEN.into_response();
+    if let Err(response) = verify(
+        &session_user,
+        &company_delete_confirmation(&id, &name),
+        &form,
+    ) {
+        return *response;
     }
 
     match delete_company(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/companies").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Compañía eliminada."),
+            Redirect::to("/admin/companies"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar la compañía."),
+            Redirect::to("/admin/companies"),
+        )
+            .into_response(),
+    }
+}
+
+async fn cfdis_delete_confirmation(
+    state: &AppState,
+    id: &str,
+    name: &str,
+) -> Result<Confirmation, StatusCode> {
+    let count = state
+        .cfdis
+        .count_documents(bson::doc! { "company_id": id })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(company_confirmation(
+        id,
+        name,
+        "Borrar todos los CFDIs",
+        format!("Se borrarán los {count} CFDIs de «{name}»."),
+        "cfdis/delete_all",
+    ))
+}
+
+/// GET /admin/companies/{id}/cfdis/delete_all — asks before emptying.
+pub async fn companies_delete_all_cfdis_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (_, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    match cfdis_delete_confirmation(&state, &id, &name).await {
+        Ok(confirmation) => confirm_page(&session_user, &confirmation),
+        Err(status) => status.into_response(),
     }
 }
 
@@ -790,38 +1135,309 @@ pub async fn companies_delete_all_cfdis(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ConfirmForm>,
 ) -> impl IntoResponse {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    let (_, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
     };
-    if !has_admin_role_for(&session_user, &object_id) {
-        return StatusCode::FORBIDDEN.into_response();
+    let confirmation = match cfdis_delete_confirmation(&state, &id, &name).await {
+        Ok(confirmation) => confirmation,
+        Err(status) => return status.into_response(),
+    };
+    if let Err(response) = verify(&session_user, &confirmation, &form) {
+        return *response;
     }
     let _ = state
         .cfdis
         .delete_many(bson::doc! { "company_id": &id })
         .await;
-    Redirect::to(&format!("/admin/companies/{id}/edit")).into_response()
+    (
+        Flash::success("Facturas de la compañía eliminadas."),
+        Redirect::to(&format!("/admin/companies/{id}/edit")),
+    )
+        .into_response()
+}
+
+async fn transactions_delete_confirmation(
+    state: &AppState,
+    id: &str,
+    object_id: &ObjectId,
+    name: &str,
+) -> Result<Confirmation, StatusCode> {
+    let count = state
+        .transactions
+        .count_documents(bson::doc! { "company_id": object_id })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(company_confirmation(
+        id,
+        name,
+        "Borrar todas las transacciones",
+        format!("Se borrarán las {count} transacciones de «{name}»."),
+        "transactions/delete_all",
+    ))
+}
+
+/// GET /admin/companies/{id}/transactions/delete_all — asks before emptying.
+pub async fn companies_delete_all_transactions_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (object_id, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    match transactions_delete_confirmation(&state, &id, &object_id, &name).await {
+        Ok(confirmation) => confirm_page(&session_user, &confirmation),
+        Err(status) => status.into_response(),
+    }
 }
 
 pub async fn companies_delete_all_transactions(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ConfirmForm>,
 ) -> impl IntoResponse {
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    let (object_id, name) = match company_for_delete(&state, &session_user, &id).await {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
     };
-    if !has_admin_role_for(&session_user, &object_id) {
-        return StatusCode::FORBIDDEN.into_response();
+    let confirmation = match transactions_delete_confirmation(&state, &id, &object_id, &name).await
+    {
+        Ok(confirmation) => confirmation,
+        Err(status) => return status.into_response(),
+    };
+    if let Err(response) = verify(&session_user, &confirmation, &form) {
+        return *response;
     }
     let _ = state
         .transactions
         .delete_many(bson::doc! { "company_id": object_id })
         .await;
-    Redirect::to(&format!("/admin/companies/{id}/edit")).into_response()
+    let _ = clear_monthly_summaries(&state, &object_id).await;
+    (
+        Flash::success("Movimientos de la compañía eliminados."),
+        Redirect::to(&format!("/admin/companies/{id}/edit")),
+    )
+        .into_response()
+}
+
+/// Splits the SSO domains field (commas or whitespace) into lowercase
+/// domains, dropping duplicates and a leading `@`.
+fn parse_sso_domains(raw: &str) -> Result<Vec<String>, String> {
+    let mut domains: Vec<String> = Vec::new();
+    for part in raw.split(|c: char| c == ',' || c.is_whitespace()) {
+        let domain = part.trim().trim_start_matches('@').to_lowercase();
+        if domain.is_empty() {
+            continue;
+        }
+        let is_valid = domain.contains('.')
+            && domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+            && !domain.starts_with(['.', '-'])
+            && !domain.ends_with(['.', '-']);
+        if !is_valid {
+            return Err(format!("Dominio SSO inválido: {domain}"));
+        }
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    Ok(domains)
+}
+
+async fn check_sso_domains(
+    state: &AppState,
+    raw: &str,
+    company_id: Option<&ObjectId>,
+) -> Result<Vec<String>, String> {
+    let domains = parse_sso_domains(raw)?;
+    match sso_domain_owner(state, &domains, company_id).await {
+        Ok(Some(owner)) => Err(format!(
+            "Un dominio SSO ya está asignado a la compañía {}.",
+            owner.name
+        )),
+        Ok(None) => Ok(domains),
+        Err(_) => Err("No se pudieron validar los dominios SSO.".into()),
+    }
+}
+
+// ── Off-boarding ──────────────────────────────────────────────────────────
+
+fn bundle_to_json(bundle: bson::Document) -> serde_json::Value {
+    Bson::Document(bundle).into_relaxed_extjson()
+}
+
+/// Exports, archives and revokes access in one go. Returns the saved export
+/// path and the report, or the status to answer with.
+async fn run_offboarding(
+    state: &AppState,
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+    confirm_slug: &str,
+) -> Result<(String, crate::state::OffboardReport), StatusCode> {
+    if company_id == session_user.active_company_id() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !has_admin_role_for(session_user, company_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let company = get_company_by_id(state, company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if confirm_slug.trim() != company.slug {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = SystemTime::now();
+    let bundle = export_company_bundle(state, company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Nothing is archived unless the export made it to disk.
+    let path = save_company_export(&bundle, &company.slug, now)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let report = offboard_company(
+        state,
+        company_id,
+        RetentionPolicy::from_env().company_purge_grace(),
+        now,
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((path.display().to_string(), report))
+}
+
+/// Downloads the company's export bundle as JSON.
+pub async fn companies_export(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !has_admin_role_for(&session_user, &object_id) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let slug = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company.slug,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match export_company_bundle(&state, &object_id).await {
+        Ok(bundle) => (
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{slug}-export.json\""),
+                ),
+            ],
+            Json(bundle_to_json(bundle)),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn companies_offboard(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<CompanyOffboardPayload>,
+) -> impl IntoResponse {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match run_offboarding(&state, &session_user, &object_id, &form.confirm_slug).await {
+        Ok(_) => Redirect::to(&format!(
+            "/admin/companies?offboarded={}",
+            form.confirm_slug.trim()
+        ))
+        .into_response(),
+        Err(StatusCode::BAD_REQUEST) => {
+            Redirect::to(&format!("/admin/companies/{id}/edit?offboard_error=1")).into_response()
+        }
+        Err(status) => status.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/companies/{id}/export",
+    tag = "admin",
+    params(("id" = String, Path, description = "Record id")),
+    responses(
+        (status = 200, description = "Every document of the company, one array per collection"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_export_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !has_admin_role_for(&session_user, &object_id) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match get_company_by_id(&state, &object_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+    match export_company_bundle(&state, &object_id).await {
+        Ok(bundle) => Json(bundle_to_json(bundle)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/companies/{id}/offboard",
+    tag = "admin",
+    params(("id" = String, Path, description = "Record id")),
+    request_body = CompanyOffboardPayload,
+    responses(
+        (status = 200, description = "Company exported, archived and scheduled for deletion"),
+        (status = 400, description = "confirm_slug does not match the company slug"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden — not an admin, or the active company"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_offboard_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<CompanyOffboardPayload>,
+) -> impl IntoResponse {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match run_offboarding(&state, &session_user, &object_id, &payload.confirm_slug).await {
+        Ok((export_path, report)) => Json(serde_json::json!({
+            "ok": true,
+            "export_path": export_path,
+            "archived_documents": report.archived_documents,
+            "revoked_members": report.revoked_members,
+            "deactivated_users": report.deactivated_users,
+            "purge_after": report.purge_after.try_to_rfc3339_string().unwrap_or_default(),
+        }))
+        .into_response(),
+        Err(status) => status.into_response(),
+    }
 }
 
 fn validate_slug(slug: &str) -> Result<(), String> {
@@ -862,4 +1478,15 @@ mod tests {
         assert!(validate_slug("acme_test").is_err());
         assert!(validate_slug(&"a".repeat(65)).is_err());
     }
+
+    #[test]
+    fn parse_sso_domains_normalizes_and_rejects_invalid_entries() {
+        assert_eq!(
+            parse_sso_domains(" Acme.com, @acme.mx\nacme.com ").unwrap(),
+            vec!["acme.com".to_string(), "acme.mx".to_string()]
+        );
+        assert!(parse_sso_domains("").unwrap().is_empty());
+        assert!(parse_sso_domains("localhost").is_err());
+        assert!(parse_sso_domains("acme.com/evil").is_err());
+    }
 }
@@ -0,0 +1,127 @@
+// Second step of destructive admin actions. The delete buttons open a page
+// that says what will be removed; only the form on that page carries the
+// token the POST checks, so a stray click or a cross-site form lands on the
+// page instead of deleting. Company deletes can also ask for the company's
+// name to be typed (`COMPANY_DELETE_REQUIRE_NAME`).
+
+use std::env;
+
+use askama::Template;
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use data_encoding::HEXLOWER;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::session::SessionUser;
+
+/// `COMPANY_DELETE_REQUIRE_NAME` accepts `1`, `true` or `yes`
+/// (case-insensitive).
+pub fn company_delete_requires_name() -> bool {
+    env::var("COMPANY_DELETE_REQUIRE_NAME")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Token of the confirmation form for `action`, tied to the session so it
+/// cannot be guessed from another site.
+fn confirm_token(session_token: &str, action: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(b"confirm:")
+        .chain_update(session_token.as_bytes())
+        .chain_update(b":")
+        .chain_update(action.as_bytes())
+        .finalize();
+    HEXLOWER.encode(&digest[..16])
+}
+
+/// What the confirmation page shows and where it posts.
+pub struct Confirmation {
+    pub title: String,
+    /// What is removed, e.g. "Se eliminará el plan «Renta» y sus compromisos
+    /// pendientes."
+    pub message: String,
+    /// Path the confirmed form posts to; the page itself is a GET to it.
+    pub action: String,
+    pub cancel_url: String,
+    pub button_label: String,
+    /// Name to type before the button works, when required.
+    pub expected_name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfirmForm {
+    #[serde(default)]
+    confirm_token: String,
+    #[serde(default)]
+    confirm_name: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/confirm.html")]
+struct ConfirmTemplate<'a> {
+    confirmation: &'a Confirmation,
+    token: String,
+    error: Option<&'static str>,
+}
+
+fn render_page(
+    session_user: &SessionUser,
+    confirmation: &Confirmation,
+    error: Option<&'static str>,
+) -> Response {
+    let template = ConfirmTemplate {
+        confirmation,
+        token: confirm_token(session_user.token(), &confirmation.action),
+        error,
+    };
+    match template.render() {
+        Ok(html) if error.is_some() => (StatusCode::BAD_REQUEST, Html(html)).into_response(),
+        Ok(html) => Html(html).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The confirmation page.
+pub fn confirm_page(session_user: &SessionUser, confirmation: &Confirmation) -> Response {
+    render_page(session_user, confirmation, None)
+}
+
+/// Lets the action run once the form of the confirmation page was sent.
+/// Without its token the user is sent to the page; a mistyped name shows it
+/// again with the error.
+pub fn verify(
+    session_user: &SessionUser,
+    confirmation: &Confirmation,
+    form: &ConfirmForm,
+) -> Result<(), Box<Response>> {
+    if form.confirm_token != confirm_token(session_user.token(), &confirmation.action) {
+        return Err(Box::new(Redirect::to(&confirmation.action).into_response()));
+    }
+    if let Some(expected) = &confirmation.expected_name
+        && form.confirm_name.trim() != expected.trim()
+    {
+        return Err(Box::new(render_page(
+            session_user,
+            confirmation,
+            Some("El nombre no coincide. Escríbelo exactamente como aparece."),
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_depends_on_the_session_and_the_action() {
+        let token = confirm_token("session-a", "/admin/users/1/delete");
+        assert_eq!(token.len(), 32);
+        assert_eq!(token, confirm_token("session-a", "/admin/users/1/delete"));
+        assert_ne!(token, confirm_token("session-b", "/admin/users/1/delete"));
+        assert_ne!(token, confirm_token("session-a", "/admin/users/2/delete"));
+    }
+}
@@ -0,0 +1,269 @@
+// Account groups: the headings of the accounts page and the order of the
+// accounts under them. Groups are created and deleted from the accounts
+// page; the order is saved by the page's drag and drop through the reorder
+// endpoint. See `state::account_groups`.
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flash::Flash,
+    models::AccountGroup,
+    routes::{Routes, form_fields::optional_object_id},
+    session::SessionUser,
+    state::{
+        AccountOrderGroup, AppState, create_account_group, delete_account_group,
+        get_account_group_by_id, list_account_groups, reorder_accounts,
+    },
+};
+
+use super::helpers::*;
+
+/// Account groups and the order of the accounts page.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/account_groups", post(account_groups_create))
+        .route(
+            "/admin/account_groups/{id}/delete",
+            post(account_groups_delete),
+        )
+        .route(
+            "/api/admin/account_groups",
+            get(account_groups_data_api).post(account_groups_create_api),
+        )
+        .route("/api/admin/accounts/reorder", post(accounts_reorder_api))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AccountGroupRow {
+    pub id: String,
+    pub name: String,
+    pub position: i32,
+}
+
+#[derive(Deserialize)]
+pub struct AccountGroupFormData {
+    name: String,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AccountGroupPayload {
+    pub name: String,
+}
+
+/// One group of the accounts page, top to bottom.
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AccountOrderGroupPayload {
+    /// Empty or absent for the accounts without a group.
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub group_id: Option<ObjectId>,
+    #[serde(default)]
+    #[schema(value_type = Vec<String>)]
+    pub account_ids: Vec<ObjectId>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AccountsReorderPayload {
+    /// The groups in their new order, each with its accounts.
+    pub groups: Vec<AccountOrderGroupPayload>,
+}
+
+fn account_group_rows(groups: Vec<AccountGroup>) -> Vec<AccountGroupRow> {
+    groups
+        .into_iter()
+        .filter_map(|group| {
+            group.id.map(|id| AccountGroupRow {
+                id: id.to_hex(),
+                name: group.name,
+                position: group.position,
+            })
+        })
+        .collect()
+}
+
+/// The company's groups for the group select of the account form, led by
+/// "Sin grupo".
+pub(super) async fn account_group_options(
+    state: &AppState,
+    company_id: &ObjectId,
+    selected: Option<&ObjectId>,
+) -> Result<Vec<SimpleOption>, StatusCode> {
+    let groups = list_account_groups(state, company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut options = vec![SimpleOption {
+        value: String::new(),
+        label: "Sin grupo".to_string(),
+        selected: selected.is_none(),
+    }];
+    options.extend(groups.into_iter().filter_map(|group| {
+        group.id.map(|id| SimpleOption {
+            value: id.to_hex(),
+            label: group.name,
+            selected: selected == Some(&id),
+        })
+    }));
+    Ok(options)
+}
+
+pub async fn account_groups_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<AccountGroupFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    match create_account_group(&state, &company_id, &form.name).await {
+        Ok(_) => (
+            Flash::success("Grupo creado."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
+        Err(err) => (
+            Flash::error(err.to_string()),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn account_groups_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let group = match get_account_group_by_id(&state, &object_id).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&group.company_id, &company_id) {
+        return status.into_response();
+    }
+    match delete_account_group(&state, &group).await {
+        Ok(_) => (
+            Flash::success("Grupo eliminado; sus cuentas quedaron sin grupo."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/account_groups",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Account groups of the active company, in order", body = [AccountGroupRow]),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn account_groups_data_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<AccountGroupRow>>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    list_account_groups(&state, &company_id)
+        .await
+        .map(|groups| Json(account_group_rows(groups)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/account_groups",
+    tag = "finance",
+    request_body = AccountGroupPayload,
+    responses(
+        (status = 201, description = "Account group created; returns its id"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Missing or duplicated name")
+    ),
+    security(("session" = []))
+)]
+pub async fn account_groups_create_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AccountGroupPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    match create_account_group(&state, &company_id, &payload.name).await {
+        Ok(id) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "id": id.to_hex() })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/accounts/reorder",
+    tag = "finance",
+    request_body = AccountsReorderPayload,
+    responses(
+        (status = 200, description = "Order saved"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Unknown or repeated group or account")
+    ),
+    security(("session" = []))
+)]
+pub async fn accounts_reorder_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AccountsReorderPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let order: Vec<AccountOrderGroup> = payload
+        .groups
+        .into_iter()
+        .map(|group| AccountOrderGroup {
+            group_id: group.group_id,
+            account_ids: group.account_ids,
+        })
+        .collect();
+    match reorder_accounts(&state, &company_id, &order).await {
+        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response(),
+    }
+}
@@ -3,31 +3,99 @@ use std::{str::FromStr, sync::Arc};
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Path, State},
+    extract::{Form, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
+    flash::Flash,
+    models::{Account, AccountType, AccountValuation},
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
-        AppState, create_account, delete_account, get_account_by_id, list_accounts, update_account,
+        AppState, ValuationInput, account_balance_at, create_account, currency_subtotals,
+        delete_account, get_account_by_id, group_accounts, latest_account_valuation,
+        list_accessible_accounts, list_account_groups, list_account_movements,
+        list_account_valuations, list_accounts, list_balance_snapshots, pending_deposits,
+        record_account_valuations, set_account_group, set_opening_balance, update_account,
+        utc_day_start,
     },
 };
 
-use super::helpers::*;
+use super::{account_groups::account_group_options, helpers::*};
+
+/// Accounts, statements and investment revaluations.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/accounts", get(accounts_index).post(accounts_create))
+        .route(
+            "/api/admin/accounts",
+            get(accounts_data_api).post(accounts_create_api),
+        )
+        .route("/api/admin/accounts/{id}", get(account_data_api))
+        .route("/api/admin/accounts/{id}/update", post(account_update_api))
+        .route("/api/admin/accounts/{id}/delete", post(account_delete_api))
+        .route("/admin/accounts/new", get(accounts_new))
+        .route("/admin/accounts/{id}/edit", get(accounts_edit))
+        .route("/admin/accounts/{id}/statement", get(accounts_statement))
+        .route(
+            "/admin/accounts/{id}/opening_balance",
+            get(accounts_opening_balance_form).post(accounts_opening_balance_update),
+        )
+        .route(
+            "/admin/accounts/revaluation",
+            get(accounts_revaluation_form).post(accounts_revaluation_create),
+        )
+        .route(
+            "/api/admin/accounts/revaluations",
+            post(accounts_revaluation_api),
+        )
+        .route(
+            "/api/admin/accounts/{id}/valuations",
+            get(account_valuations_api),
+        )
+        .route("/admin/accounts/{id}/update", post(accounts_update))
+        .route("/admin/accounts/{id}/delete", post(accounts_delete))
+}
 
 #[derive(Template)]
 #[template(path = "admin/accounts/index.html")]
 struct AccountsIndexTemplate {
-    accounts: Vec<AccountRow>,
+    sections: Vec<AccountSectionView>,
+    has_accounts: bool,
+}
+
+/// A group of the accounts page. `id` is empty for the accounts without a
+/// group.
+struct AccountSectionView {
+    id: String,
+    name: String,
+    accounts: Vec<AccountBalanceRow>,
+    /// Balance of the section's accounts, one per currency.
+    subtotals: Vec<(String, f64)>,
 }
 
+struct AccountBalanceRow {
+    id: String,
+    name: String,
+    account_type: String,
+    currency: String,
+    is_active: bool,
+    balance: f64,
+    /// Open income expected in the next `PENDING_DEPOSIT_DAYS`, by its splits.
+    pending_deposits: f64,
+}
+
+/// How far ahead the accounts index looks for income still to arrive.
+const PENDING_DEPOSIT_DAYS: i64 = 30;
+
 #[derive(Serialize)]
 pub struct AccountRow {
     pub id: String,
@@ -36,6 +104,8 @@ pub struct AccountRow {
     pub account_type: String,
     pub currency: String,
     pub is_active: bool,
+    /// Group the account is listed under, if any.
+    pub group_id: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -48,6 +118,9 @@ pub struct AccountDetail {
     pub currency: String,
     pub is_active: bool,
     pub notes: Option<String>,
+    pub opening_balance: f64,
+    /// `YYYY-MM-DD`.
+    pub opening_date: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -91,6 +164,7 @@ pub async fn accounts_data_api(
 ) -> Result<Json<Vec<AccountRow>>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let active_name = session_user.user().company_name.clone();
+    let access = session_user.account_access();
     let accounts = list_accounts(&state)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -98,6 +172,7 @@ pub async fn accounts_data_api(
     let rows = accounts
         .into_iter()
         .filter(|acc| acc.company_id == active_company)
+        .filter(|acc| acc.id.is_some_and(|id| access.allows(&id)))
         .filter_map(|acc| {
             acc.id.map(|id| AccountRow {
                 id: id.to_hex(),
@@ -106,6 +181,7 @@ pub async fn accounts_data_api(
                 account_type: account_type_value(&acc.account_type).to_string(),
                 currency: acc.currency,
                 is_active: acc.is_active,
+                group_id: acc.group_id.map(|id| id.to_hex()),
             })
         })
         .collect();
@@ -216,6 +292,8 @@ pub async fn account_data_api(
         currency: account.currency,
         is_active: account.is_active,
         notes: account.notes,
+        opening_balance: account.opening_balance,
+        opening_date: account.opening_date.map(day_string),
     }))
 }
 
@@ -345,6 +423,58 @@ pub async fn account_delete_api(
     }
 }
 
+/// Movements listed under the chart on the statement page.
+const STATEMENT_MOVEMENTS: i64 = 50;
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 160.0;
+
+#[derive(Deserialize)]
+pub struct StatementQuery {
+    /// Days of history in the chart, 90 by default.
+    #[serde(default)]
+    days: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/accounts/statement.html")]
+struct AccountStatementTemplate {
+    id: String,
+    name: String,
+    currency: String,
+    balance: f64,
+    opening_balance: f64,
+    opening_date: Option<String>,
+    /// Whether every movement since the opening is listed, so the list ends
+    /// with the opening balance.
+    shows_opening: bool,
+    days: i64,
+    chart: Option<BalanceChart>,
+    movements: Vec<StatementMovement>,
+    /// Market valuations, newest first; investment accounts only.
+    is_investment: bool,
+    valuations: Vec<ValuationRow>,
+}
+
+/// SVG polyline of the balance over time.
+#[derive(Debug, PartialEq)]
+struct BalanceChart {
+    points: String,
+    /// Y of the zero line, when zero is inside the plotted range.
+    zero_y: Option<f64>,
+    min: f64,
+    max: f64,
+    from: String,
+    to: String,
+}
+
+struct StatementMovement {
+    date: String,
+    description: String,
+    amount: f64,
+    balance_after: f64,
+}
+
 #[derive(Template)]
 #[template(path = "admin/accounts/form.html")]
 struct AccountFormTemplate {
@@ -356,6 +486,7 @@ struct AccountFormTemplate {
     notes: String,
     companies: Vec<SimpleOption>,
     account_type_options: Vec<SimpleOption>,
+    group_options: Vec<SimpleOption>,
     is_edit: bool,
     errors: Option<String>,
 }
@@ -370,13 +501,15 @@ pub struct AccountFormData {
     is_active: bool,
     #[serde(default)]
     notes: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    group_id: Option<ObjectId>,
 }
 
 pub async fn accounts_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Html<String>, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
 
@@ -384,24 +517,62 @@ pub async fn accounts_index(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let active_company = session_user.active_company_id().clone();
-    let active_name = session_user.user().company_name.clone();
+    let access = session_user.account_access();
+    let groups = list_account_groups(&state, &active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let rows = accounts
+    let accounts: Vec<Account> = accounts
         .into_iter()
         .filter(|acc| acc.company_id == active_company)
-        .filter_map(|acc| {
-            acc.id.map(|id| AccountRow {
+        .filter(|acc| acc.id.is_some_and(|id| access.allows(&id)))
+        .collect();
+    let has_accounts = !accounts.is_empty();
+    let now = DateTime::now();
+    let until =
+        DateTime::from_millis(now.timestamp_millis() + PENDING_DEPOSIT_DAYS * 24 * 60 * 60 * 1000);
+    let pending = pending_deposits(&state, &active_company, until)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut sections = Vec::new();
+    for section in group_accounts(groups, accounts) {
+        let mut rows = Vec::new();
+        for acc in section.accounts {
+            let Some(id) = acc.id else { continue };
+            let balance = account_balance_at(&state, &id, now)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            rows.push(AccountBalanceRow {
                 id: id.to_hex(),
                 name: acc.name,
-                company: active_name.clone(),
                 account_type: account_type_value(&acc.account_type).to_string(),
                 currency: acc.currency,
                 is_active: acc.is_active,
-            })
-        })
-        .collect();
+                balance,
+                pending_deposits: pending.get(&id).copied().unwrap_or(0.0),
+            });
+        }
+        let subtotals =
+            currency_subtotals(rows.iter().map(|row| (row.currency.as_str(), row.balance)));
+        let (id, name) = match section.group {
+            Some(group) => (
+                group.id.map(|id| id.to_hex()).unwrap_or_default(),
+                group.name,
+            ),
+            None => (String::new(), "Sin grupo".to_string()),
+        };
+        sections.push(AccountSectionView {
+            id,
+            name,
+            accounts: rows,
+            subtotals,
+        });
+    }
 
-    render(AccountsIndexTemplate { accounts: rows })
+    render(AccountsIndexTemplate {
+        sections,
+        has_accounts,
+    })
 }
 
 pub async fn accounts_new(
@@ -410,6 +581,7 @@ pub async fn accounts_new(
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let companies = company_options(&state, &active_company).await?;
+    let group_options = account_group_options(&state, &active_company, None).await?;
 
     render(AccountFormTemplate {
         action: "/admin/accounts".into(),
@@ -420,6 +592,7 @@ pub async fn accounts_new(
         notes: String::new(),
         companies,
         account_type_options: account_type_options("bank"),
+        group_options,
         is_edit: false,
         errors: None,
     })
@@ -442,6 +615,9 @@ pub async fn accounts_create(
     let account_type = match parse_account_type(&form.account_type) {
         Ok(t) => t,
         Err(msg) => {
+            let group_options = account_group_options(&state, &company_id, form.group_id.as_ref())
+                .await
+                .unwrap_or_default();
             return render(AccountFormTemplate {
                 action: "/admin/accounts".into(),
                 name: form.name.clone(),
@@ -451,6 +627,7 @@ pub async fn accounts_create(
                 notes: form.notes.clone().unwrap_or_default(),
                 companies,
                 account_type_options: account_type_options(&form.account_type),
+                group_options,
                 is_edit: false,
                 errors: Some(msg),
             })
@@ -467,7 +644,7 @@ pub async fn accounts_create(
 
     let notes = clean_opt(form.notes);
 
-    match create_account(
+    let created = match create_account(
         &state,
         &company_id,
         form.name.trim(),
@@ -478,7 +655,15 @@ pub async fn accounts_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/accounts").into_response(),
+        Ok(id) => set_account_group(&state, &company_id, &id, form.group_id).await,
+        Err(err) => Err(err),
+    };
+    match created {
+        Ok(_) => (
+            Flash::success("Cuenta creada."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -498,6 +683,8 @@ pub async fn accounts_edit(
     ensure_same_company(&account.company_id, &active_company)?;
 
     let companies = company_options(&state, &active_company).await?;
+    let group_options =
+        account_group_options(&state, &active_company, account.group_id.as_ref()).await?;
 
     render(AccountFormTemplate {
         action: format!("/admin/accounts/{}/update", id),
@@ -508,6 +695,7 @@ pub async fn accounts_edit(
         notes: account.notes.unwrap_or_default(),
         companies,
         account_type_options: account_type_options(account_type_value(&account.account_type)),
+        group_options,
         is_edit: true,
         errors: None,
     })
@@ -545,6 +733,9 @@ pub async fn accounts_update(
             let companies = company_options(&state, session_user.active_company_id())
                 .await
                 .unwrap_or_default();
+            let group_options = account_group_options(&state, &company_id, form.group_id.as_ref())
+                .await
+                .unwrap_or_default();
             return render(AccountFormTemplate {
                 action: format!("/admin/accounts/{}/update", id),
                 name: form.name.clone(),
@@ -554,6 +745,7 @@ pub async fn accounts_update(
                 notes: form.notes.clone().unwrap_or_default(),
                 companies,
                 account_type_options: account_type_options(&form.account_type),
+                group_options,
                 is_edit: true,
                 errors: Some(msg),
             })
@@ -570,7 +762,7 @@ pub async fn accounts_update(
 
     let notes = clean_opt(form.notes);
 
-    match update_account(
+    let updated = match update_account(
         &state,
         &object_id,
         &company_id,
@@ -582,7 +774,15 @@ pub async fn accounts_update(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/accounts").into_response(),
+        Ok(_) => set_account_group(&state, &company_id, &object_id, form.group_id).await,
+        Err(err) => Err(err),
+    };
+    match updated {
+        Ok(_) => (
+            Flash::success("Cuenta actualizada."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -613,7 +813,668 @@ pub async fn accounts_delete(
     }
 
     match delete_account(&state, &object_id, &company_id).await {
-        Ok(_) => Redirect::to("/admin/accounts").into_response(),
+        Ok(_) => (
+            Flash::success("Cuenta eliminada."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar la cuenta."),
+            Redirect::to("/admin/accounts"),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn accounts_statement(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let account = get_account_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&account.company_id, &active_company)?;
+
+    let days = query.days.unwrap_or(90).clamp(7, 730);
+    let now = DateTime::now();
+    let from = utc_day_start(DateTime::from_millis(
+        now.timestamp_millis() - days * 24 * 60 * 60 * 1000,
+    ));
+    let balance = account_balance_at(&state, &object_id, now)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut points: Vec<(DateTime, f64)> = list_balance_snapshots(&state, &object_id, from)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|snapshot| (snapshot.as_of, snapshot.balance))
+        .collect();
+    points.push((now, balance));
+
+    let mut running = balance;
+    let movements = list_account_movements(&state, &object_id, now, STATEMENT_MOVEMENTS)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|tx| {
+            let amount = if tx.account_to_id == Some(object_id) {
+                tx.amount
+            } else {
+                -tx.amount
+            };
+            let movement = StatementMovement {
+                date: tx.date.to_chrono().format("%Y-%m-%d").to_string(),
+                description: tx.description,
+                amount,
+                balance_after: running,
+            };
+            running -= amount;
+            movement
+        })
+        .collect::<Vec<_>>();
+
+    let is_investment = account.account_type == AccountType::Investment;
+    let valuations = if is_investment {
+        list_account_valuations(&state, &object_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .map(valuation_row)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    render(AccountStatementTemplate {
+        id,
+        name: account.name,
+        currency: account.currency,
+        balance,
+        opening_balance: account.opening_balance,
+        opening_date: account.opening_date.map(day_string),
+        shows_opening: (movements.len() as i64) < STATEMENT_MOVEMENTS,
+        days,
+        chart: balance_chart(&points),
+        movements,
+        is_investment,
+        valuations,
+    })
+}
+
+fn day_string(date: DateTime) -> String {
+    date.to_chrono().format("%Y-%m-%d").to_string()
+}
+
+#[derive(Template)]
+#[template(path = "admin/accounts/opening_balance.html")]
+struct OpeningBalanceTemplate {
+    id: String,
+    name: String,
+    currency: String,
+    opening_balance: String,
+    opening_date: String,
+    reason: String,
+    changes: Vec<OpeningBalanceChangeRow>,
+    errors: Option<String>,
+}
+
+struct OpeningBalanceChangeRow {
+    changed_at: String,
+    username: String,
+    previous_balance: f64,
+    previous_date: Option<String>,
+    balance: f64,
+    date: Option<String>,
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OpeningBalanceFormData {
+    #[serde(default)]
+    opening_balance: Option<String>,
+    #[serde(default)]
+    opening_date: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+fn opening_balance_template(
+    id: String,
+    account: Account,
+    form: Option<&OpeningBalanceFormData>,
+    errors: Option<String>,
+) -> OpeningBalanceTemplate {
+    let field = |value: Option<&Option<String>>, current: String| {
+        value
+            .map(|value| value.clone().unwrap_or_default())
+            .unwrap_or(current)
+    };
+    OpeningBalanceTemplate {
+        id,
+        opening_balance: field(
+            form.map(|form| &form.opening_balance),
+            account.opening_balance.to_string(),
+        ),
+        opening_date: field(
+            form.map(|form| &form.opening_date),
+            account.opening_date.map(day_string).unwrap_or_default(),
+        ),
+        reason: field(form.map(|form| &form.reason), String::new()),
+        changes: account
+            .opening_balance_changes
+            .into_iter()
+            .rev()
+            .map(|change| OpeningBalanceChangeRow {
+                changed_at: datetime_to_string(&change.changed_at),
+                username: change.username,
+                previous_balance: change.previous_balance,
+                previous_date: change.previous_date.map(day_string),
+                balance: change.balance,
+                date: change.date.map(day_string),
+                reason: change.reason,
+            })
+            .collect(),
+        name: account.name,
+        currency: account.currency,
+        errors,
+    }
+}
+
+/// GET /admin/accounts/{id}/opening_balance — the opening balance form with
+/// the log of its past adjustments, newest first.
+pub async fn accounts_opening_balance_form(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let account = get_account_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&account.company_id, &active_company)?;
+
+    render(opening_balance_template(id, account, None, None))
+}
+
+/// POST /admin/accounts/{id}/opening_balance — adjusts the opening balance
+/// and logs who did it. An empty amount is zero and an empty date means the
+/// balance precedes every transaction.
+pub async fn accounts_opening_balance_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<OpeningBalanceFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let account = match get_account_by_id(&state, &object_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&account.company_id, &company_id) {
+        return status.into_response();
+    }
+
+    let balance = parse_optional_f64_field(form.opening_balance.clone(), "Saldo inicial");
+    let date = match clean_opt(form.opening_date.clone()) {
+        Some(value) => parse_date_field(&value)
+            .map(Some)
+            .ok_or_else(|| "Fecha del saldo inicial inválida".to_string()),
+        None => Ok(None),
+    };
+    let (balance, date) = match (balance, date) {
+        (Ok(balance), Ok(date)) => (balance.unwrap_or(0.0), date),
+        (Err(msg), _) | (_, Err(msg)) => {
+            return render(opening_balance_template(
+                id,
+                account,
+                Some(&form),
+                Some(msg),
+            ))
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+
+    let user = session_user.user();
+    match set_opening_balance(
+        &state,
+        &account,
+        balance,
+        date,
+        Some(user.id),
+        &user.username,
+        clean_opt(form.reason),
+    )
+    .await
+    {
+        Ok(_) => (
+            Flash::success("Saldo inicial guardado."),
+            Redirect::to(&format!("/admin/accounts/{id}/statement")),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
+
+struct ValuationRow {
+    as_of: String,
+    market_value: f64,
+    book_balance: f64,
+    unrealized_gain: f64,
+    adjustment: f64,
+    notes: Option<String>,
+}
+
+fn valuation_row(valuation: AccountValuation) -> ValuationRow {
+    ValuationRow {
+        as_of: day_string(valuation.as_of),
+        market_value: valuation.market_value,
+        book_balance: valuation.book_balance,
+        unrealized_gain: valuation.unrealized_gain,
+        adjustment: valuation.adjustment,
+        notes: valuation.notes,
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/accounts/revaluation.html")]
+struct RevaluationTemplate {
+    as_of: String,
+    notes: String,
+    rows: Vec<RevaluationRow>,
+    recorded: Option<usize>,
+    errors: Option<String>,
+}
+
+struct RevaluationRow {
+    id: String,
+    name: String,
+    currency: String,
+    book_balance: f64,
+    last_valued_at: Option<String>,
+    last_market_value: Option<f64>,
+    /// What was typed in, when the form comes back with errors.
+    value: String,
+}
+
+#[derive(Deserialize)]
+pub struct RevaluationQuery {
+    #[serde(default)]
+    recorded: Option<usize>,
+}
+
+/// Active investment accounts the user may work with, with their balance and
+/// latest valuation.
+async fn revaluation_rows(
+    state: &AppState,
+    session_user: &SessionUser,
+    values: &[(String, String)],
+) -> anyhow::Result<Vec<RevaluationRow>> {
+    let now = DateTime::now();
+    let accounts = list_accessible_accounts(
+        state,
+        session_user.active_company_id(),
+        &session_user.account_access(),
+    )
+    .await?;
+    let mut rows = Vec::new();
+    for account in accounts {
+        let Some(id) = account.id else {
+            continue;
+        };
+        if !account.is_active || account.account_type != AccountType::Investment {
+            continue;
+        }
+        let book_balance = account_balance_at(state, &id, now).await?;
+        let latest = latest_account_valuation(state, &id, now).await?;
+        let id = id.to_hex();
+        rows.push(RevaluationRow {
+            value: values
+                .iter()
+                .find(|(key, _)| *key == id)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default(),
+            book_balance,
+            last_valued_at: latest.as_ref().map(|v| day_string(v.as_of)),
+            last_market_value: latest.map(|v| v.market_value),
+            id,
+            name: account.name,
+            currency: account.currency,
+        });
+    }
+    Ok(rows)
+}
+
+/// Reads the batch form: the day, shared notes and one `value_<account id>`
+/// field per account. Blank values leave the account out.
+fn parse_revaluation_form(
+    pairs: &[(String, String)],
+) -> Result<(DateTime, Vec<ValuationInput>), String> {
+    let field = |name: &str| {
+        pairs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let as_of = parse_date_field(&field("as_of").unwrap_or_default())
+        .ok_or_else(|| "Fecha de la valuación inválida".to_string())?;
+    let notes = clean_opt(field("notes"));
+    let mut inputs = Vec::new();
+    for (key, value) in pairs {
+        let Some(id) = key.strip_prefix("value_") else {
+            continue;
+        };
+        let Some(market_value) = parse_optional_f64_field(Some(value.clone()), "Valor de mercado")?
+        else {
+            continue;
+        };
+        let account_id = ObjectId::from_str(id).map_err(|_| "Cuenta inválida".to_string())?;
+        inputs.push(ValuationInput {
+            account_id,
+            market_value,
+            notes: notes.clone(),
+        });
+    }
+    if inputs.is_empty() {
+        return Err("Captura el valor de mercado de al menos una cuenta".to_string());
+    }
+    Ok((as_of, inputs))
+}
+
+/// Shown when the batch is refused, e.g. for a day before the latest valuation.
+const REVALUATION_FAILED: &str = "No se pudo registrar la revaluación: revisa que las cuentas sean de inversión y que la fecha no sea anterior a su última valuación";
+
+/// GET /admin/accounts/revaluation — market values of every investment
+/// account at once, e.g. from the month-end broker statements.
+pub async fn accounts_revaluation_form(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RevaluationQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    let rows = revaluation_rows(&state, &session_user, &[])
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    render(RevaluationTemplate {
+        as_of: day_string(DateTime::now()),
+        notes: String::new(),
+        rows,
+        recorded: query.recorded,
+        errors: None,
+    })
+}
+
+/// POST /admin/accounts/revaluation — records the valuations typed in. No
+/// transaction is created; the statement and the net worth pick them up.
+pub async fn accounts_revaluation_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let result = match parse_revaluation_form(&pairs) {
+        Ok((as_of, inputs)) => {
+            if let Err(status) =
+                ensure_account_access(&session_user, inputs.iter().map(|i| &i.account_id))
+            {
+                return status.into_response();
+            }
+            let recorded_by = Some(session_user.user().id);
+            record_account_valuations(&state, &company_id, as_of, &inputs, recorded_by)
+                .await
+                .map_err(|_| REVALUATION_FAILED.to_string())
+        }
+        Err(msg) => Err(msg),
+    };
+
+    match result {
+        Ok(ids) => Redirect::to(&format!(
+            "/admin/accounts/revaluation?recorded={}",
+            ids.len()
+        ))
+        .into_response(),
+        Err(msg) => {
+            let values: Vec<(String, String)> = pairs
+                .iter()
+                .filter_map(|(key, value)| {
+                    key.strip_prefix("value_")
+                        .map(|id| (id.to_string(), value.clone()))
+                })
+                .collect();
+            let rows = match revaluation_rows(&state, &session_user, &values).await {
+                Ok(rows) => rows,
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+            let field = |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            };
+            render(RevaluationTemplate {
+                as_of: field("as_of"),
+                notes: field("notes"),
+                rows,
+                recorded: None,
+                errors: Some(msg),
+            })
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response())
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AccountValuationPayload {
+    pub account_id: String,
+    pub market_value: f64,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AccountRevaluationPayload {
+    /// `YYYY-MM-DD`; a second valuation on the same day replaces the first.
+    pub as_of: String,
+    pub valuations: Vec<AccountValuationPayload>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AccountValuationData {
+    /// `YYYY-MM-DD`.
+    pub as_of: String,
+    pub market_value: f64,
+    /// Balance from the transactions at the end of that day.
+    pub book_balance: f64,
+    pub unrealized_gain: f64,
+    /// Change of the unrealized gain since the previous valuation.
+    pub adjustment: f64,
+    pub notes: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/accounts/revaluations",
+    tag = "finance",
+    request_body = AccountRevaluationPayload,
+    responses(
+        (status = 201, description = "Valuations recorded"),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 422, description = "Not an investment account of the company, or a later valuation exists")
+    ),
+    security(("session" = []))
+)]
+pub async fn accounts_revaluation_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AccountRevaluationPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(as_of) = parse_date_field(&payload.as_of) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let mut inputs = Vec::with_capacity(payload.valuations.len());
+    for valuation in payload.valuations {
+        let Ok(account_id) = ObjectId::from_str(&valuation.account_id) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        inputs.push(ValuationInput {
+            account_id,
+            market_value: valuation.market_value,
+            notes: clean_opt(valuation.notes),
+        });
+    }
+    if let Err(status) = ensure_account_access(&session_user, inputs.iter().map(|i| &i.account_id))
+    {
+        return status.into_response();
+    }
+
+    match record_account_valuations(
+        &state,
+        &company_id,
+        as_of,
+        &inputs,
+        Some(session_user.user().id),
+    )
+    .await
+    {
+        Ok(ids) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({
+                "ids": ids.iter().map(|id| id.to_hex()).collect::<Vec<_>>(),
+            })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/accounts/{id}/valuations",
+    tag = "finance",
+    params(("id" = String, Path, description = "Record id")),
+    responses(
+        (status = 200, description = "Valuations of the account, newest first", body = [AccountValuationData]),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn account_valuations_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AccountValuationData>>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let account = get_account_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&account.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&object_id])?;
+
+    let valuations = list_account_valuations(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(
+        valuations
+            .into_iter()
+            .map(|valuation| AccountValuationData {
+                as_of: day_string(valuation.as_of),
+                market_value: valuation.market_value,
+                book_balance: valuation.book_balance,
+                unrealized_gain: valuation.unrealized_gain,
+                adjustment: valuation.adjustment,
+                notes: valuation.notes,
+            })
+            .collect(),
+    ))
+}
+
+/// Scales the points to the chart box; `None` with fewer than two points.
+fn balance_chart(points: &[(DateTime, f64)]) -> Option<BalanceChart> {
+    let (first, last) = (points.first()?, points.last()?);
+    if points.len() < 2 {
+        return None;
+    }
+    let start = first.0.timestamp_millis() as f64;
+    let span = (last.0.timestamp_millis() as f64 - start).max(1.0);
+    let min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let y = |value: f64| CHART_HEIGHT - (value - min) / range * CHART_HEIGHT;
+
+    let points_attr = points
+        .iter()
+        .map(|(date, value)| {
+            let x = (date.timestamp_millis() as f64 - start) / span * CHART_WIDTH;
+            format!("{:.1},{:.1}", x, y(*value))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(BalanceChart {
+        points: points_attr,
+        zero_y: (min < 0.0 && max > 0.0).then(|| y(0.0)),
+        min,
+        max,
+        from: first.0.to_chrono().format("%Y-%m-%d").to_string(),
+        to: last.0.to_chrono().format("%Y-%m-%d").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chart_scales_points_to_the_box() {
+        let day = 24 * 60 * 60 * 1000;
+        let points = [
+            (DateTime::from_millis(0), -50.0),
+            (DateTime::from_millis(day), 150.0),
+            (DateTime::from_millis(2 * day), 50.0),
+        ];
+        let chart = balance_chart(&points).unwrap();
+        assert_eq!(chart.points, "0.0,160.0 300.0,0.0 600.0,80.0");
+        assert_eq!(chart.zero_y, Some(120.0));
+        assert_eq!((chart.min, chart.max), (-50.0, 150.0));
+        assert_eq!(chart.from, "1970-01-01");
+        assert!(balance_chart(&points[..1]).is_none());
+    }
+}
@@ -0,0 +1,404 @@
+// Bank sync. Connections tie an account to the bank account an open banking
+// provider reads; the movements it pulls wait here until they are accepted
+// into the ledger under a category or discarded.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use chrono::Utc;
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::Deserialize;
+
+use crate::{
+    bank_sync::bank_provider_from_env,
+    filters,
+    flash::Flash,
+    models::{BankProvider, FlowType, TransactionType},
+    routes::{Routes, form_fields::optional_object_id},
+    session::SessionUser,
+    state::{
+        AppState, accept_bank_transaction, create_bank_connection, delete_bank_connection,
+        get_bank_connection, list_accounts, list_bank_connections, list_pending_bank_transactions,
+        pull_bank_connection, reject_bank_transaction, suggested_categories,
+    },
+};
+
+use super::helpers::*;
+use super::options::{account_options, flow_category_options};
+
+/// Bank connections and the movements they bring in for review.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/bank_sync", get(bank_sync_index))
+        .route(
+            "/admin/bank_sync/connections",
+            post(bank_connections_create),
+        )
+        .route(
+            "/admin/bank_sync/connections/{id}/sync",
+            post(bank_connections_sync),
+        )
+        .route(
+            "/admin/bank_sync/connections/{id}/delete",
+            post(bank_connections_delete),
+        )
+        .route(
+            "/admin/bank_sync/transactions/{id}/accept",
+            post(bank_transactions_accept),
+        )
+        .route(
+            "/admin/bank_sync/transactions/{id}/reject",
+            post(bank_transactions_reject),
+        )
+}
+
+struct BankConnectionRow {
+    id: String,
+    account: String,
+    provider: &'static str,
+    external_account_id: String,
+    last_synced_at: Option<String>,
+    last_error: Option<String>,
+}
+
+struct BankReviewRow {
+    id: String,
+    date: String,
+    description: String,
+    reference: Option<String>,
+    account: String,
+    is_income: bool,
+    amount: f64,
+    categories: Vec<SimpleOption>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/bank_sync/index.html")]
+struct BankSyncTemplate {
+    connections: Vec<BankConnectionRow>,
+    rows: Vec<BankReviewRow>,
+    accounts: Vec<SimpleOption>,
+    providers: Vec<SimpleOption>,
+    notice: Option<String>,
+    errors: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BankSyncQuery {
+    staged: Option<usize>,
+    accepted: Option<usize>,
+}
+
+async fn bank_sync_page(
+    state: &AppState,
+    session_user: &SessionUser,
+    notice: Option<String>,
+    errors: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = session_user.active_company_id();
+    let internal = |_| StatusCode::INTERNAL_SERVER_ERROR;
+    let account_names: HashMap<ObjectId, String> = list_accounts(state)
+        .await
+        .map_err(internal)?
+        .into_iter()
+        .filter(|account| account.company_id == *company_id)
+        .filter_map(|account| account.id.map(|id| (id, account.name)))
+        .collect();
+    let account_name = |id: &ObjectId| account_names.get(id).cloned().unwrap_or_default();
+
+    let connections = list_bank_connections(state, company_id)
+        .await
+        .map_err(internal)?
+        .into_iter()
+        .filter_map(|connection| {
+            Some(BankConnectionRow {
+                id: connection.id?.to_hex(),
+                account: account_name(&connection.account_id),
+                provider: connection.provider.label(),
+                external_account_id: connection.external_account_id,
+                last_synced_at: connection
+                    .last_synced_at
+                    .map(|at| at.to_chrono().format("%Y-%m-%d %H:%M").to_string()),
+                last_error: connection.last_error,
+            })
+        })
+        .collect();
+
+    let income = flow_category_options(state, None, company_id, Some(&FlowType::Income)).await?;
+    let expense = flow_category_options(state, None, company_id, Some(&FlowType::Expense)).await?;
+    // The category the account uses most for that flow comes preselected.
+    let mut suggestions: HashMap<(ObjectId, bool), Option<String>> = HashMap::new();
+    let mut rows = Vec::new();
+    for staged in list_pending_bank_transactions(state, company_id)
+        .await
+        .map_err(internal)?
+    {
+        let Some(id) = staged.id else {
+            continue;
+        };
+        let is_income = staged.amount > 0.0;
+        let key = (staged.account_id, is_income);
+        let suggested = match suggestions.get(&key) {
+            Some(suggested) => suggested.clone(),
+            None => {
+                let transaction_type = if is_income {
+                    TransactionType::Income
+                } else {
+                    TransactionType::Expense
+                };
+                let suggested = suggested_categories(state, &staged.account_id, &transaction_type)
+                    .await
+                    .map_err(internal)?
+                    .first()
+                    .map(|id| id.to_hex());
+                suggestions.insert(key, suggested.clone());
+                suggested
+            }
+        };
+        let options = if is_income { &income } else { &expense };
+        let categories = options
+            .iter()
+            .map(|option| SimpleOption {
+                value: option.value.clone(),
+                label: option.label.clone(),
+                selected: suggested.as_deref() == Some(option.value.as_str()),
+            })
+            .collect();
+        rows.push(BankReviewRow {
+            id: id.to_hex(),
+            date: staged.date.to_chrono().format("%Y-%m-%d").to_string(),
+            account: account_name(&staged.account_id),
+            description: staged.description,
+            reference: staged.reference,
+            is_income,
+            amount: staged.amount.abs(),
+            categories,
+        });
+    }
+
+    render(BankSyncTemplate {
+        connections,
+        rows,
+        accounts: account_options(state, None, company_id, &session_user.account_access()).await?,
+        providers: [BankProvider::Belvo]
+            .iter()
+            .map(|provider| SimpleOption {
+                value: provider.as_str().to_string(),
+                label: provider.label().to_string(),
+                selected: false,
+            })
+            .collect(),
+        notice,
+        errors,
+    })
+}
+
+/// The page again with what went wrong.
+async fn bank_sync_failed(state: &AppState, session_user: &SessionUser, message: &str) -> Response {
+    match bank_sync_page(state, session_user, None, Some(message.to_string())).await {
+        Ok(html) => (StatusCode::BAD_REQUEST, html).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Connections of the company and the movements waiting for review.
+pub async fn bank_sync_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BankSyncQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    let notice = match (query.staged, query.accepted) {
+        (Some(count), _) => Some(format!("{count} movimiento(s) nuevo(s) por revisar.")),
+        (_, Some(_)) => Some("Movimiento registrado.".to_string()),
+        _ => None,
+    };
+    bank_sync_page(&state, &session_user, notice, None).await
+}
+
+#[derive(Deserialize)]
+pub struct BankConnectionForm {
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_id: Option<ObjectId>,
+    provider: String,
+    external_link_id: String,
+    external_account_id: String,
+}
+
+pub async fn bank_connections_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<BankConnectionForm>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let (Some(account_id), Some(provider)) =
+        (form.account_id, BankProvider::parse(form.provider.trim()))
+    else {
+        return bank_sync_failed(
+            &state,
+            &session_user,
+            "Selecciona la cuenta y el proveedor.",
+        )
+        .await;
+    };
+    if !session_user.account_access().allows(&account_id) {
+        return bank_sync_failed(&state, &session_user, "No tienes acceso a esa cuenta.").await;
+    }
+    match create_bank_connection(
+        &state,
+        &company_id,
+        &account_id,
+        provider,
+        &form.external_link_id,
+        &form.external_account_id,
+    )
+    .await
+    {
+        Ok(_) => (
+            Flash::success("Conexión bancaria creada."),
+            Redirect::to("/admin/bank_sync"),
+        )
+            .into_response(),
+        Err(err) => {
+            eprintln!("[bank_sync] connection create failed: {err:?}");
+            bank_sync_failed(
+                &state,
+                &session_user,
+                "No se pudo conectar: revisa la cuenta y los identificadores del enlace y de la cuenta bancaria, y que no esté conectada ya.",
+            )
+            .await
+        }
+    }
+}
+
+pub async fn bank_connections_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match delete_bank_connection(&state, &company_id, &id).await {
+        Ok(true) => (
+            Flash::success("Conexión bancaria eliminada."),
+            Redirect::to("/admin/bank_sync"),
+        )
+            .into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Pulls the connection right away instead of waiting for the sync task.
+pub async fn bank_connections_sync(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let connection = match get_bank_connection(&state, &company_id, &id).await {
+        Ok(Some(connection)) => connection,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Some(provider) = bank_provider_from_env(connection.provider) else {
+        let message = format!(
+            "{} no está configurado en el servidor.",
+            connection.provider.label()
+        );
+        return bank_sync_failed(&state, &session_user, &message).await;
+    };
+    let now = DateTime::from_chrono(Utc::now());
+    match pull_bank_connection(&state, &connection, provider.as_ref(), now).await {
+        Ok(staged) => Redirect::to(&format!("/admin/bank_sync?staged={staged}")).into_response(),
+        Err(err) => {
+            eprintln!("[bank_sync] manual pull failed: {err:?}");
+            bank_sync_failed(
+                &state,
+                &session_user,
+                "El banco no respondió; se volverá a intentar en la próxima sincronización.",
+            )
+            .await
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BankAcceptForm {
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+}
+
+pub async fn bank_transactions_accept(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<BankAcceptForm>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let Some(category_id) = form.category_id else {
+        return bank_sync_failed(&state, &session_user, "Selecciona una categoría.").await;
+    };
+    match accept_bank_transaction(&state, &company_id, &id, &category_id).await {
+        Ok(_) => Redirect::to("/admin/bank_sync?accepted=1").into_response(),
+        Err(err) => {
+            eprintln!("[bank_sync] accept failed: {err:?}");
+            bank_sync_failed(
+                &state,
+                &session_user,
+                "No se pudo registrar: el movimiento ya se revisó o la categoría no corresponde.",
+            )
+            .await
+        }
+    }
+}
+
+pub async fn bank_transactions_reject(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match reject_bank_transaction(&state, &company_id, &id).await {
+        Ok(true) => (
+            Flash::success("Movimiento bancario descartado."),
+            Redirect::to("/admin/bank_sync"),
+        )
+            .into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
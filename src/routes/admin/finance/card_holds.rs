@@ -0,0 +1,355 @@
+// Card holds of transactions, placed, captured and voided from the edit page
+// of the transaction or through the API. See `state::card_holds`.
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::post,
+};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flash::Flash,
+    models::{CardHold, HoldStatus, Transaction, TransactionType},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, DEFAULT_HOLD_DAYS, MAX_HOLD_DAYS, capture_card_hold, get_transaction_by_id,
+        place_card_hold, void_card_hold,
+    },
+};
+
+use super::helpers::*;
+
+/// Card holds of transactions.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/transactions/{id}/hold", post(transactions_hold))
+        .route(
+            "/admin/transactions/{id}/capture",
+            post(transactions_capture),
+        )
+        .route("/admin/transactions/{id}/void", post(transactions_void))
+        .route(
+            "/api/admin/transactions/{id}/hold",
+            post(transaction_hold_api),
+        )
+        .route(
+            "/api/admin/transactions/{id}/capture",
+            post(transaction_capture_api),
+        )
+        .route(
+            "/api/admin/transactions/{id}/void",
+            post(transaction_void_api),
+        )
+}
+
+#[derive(Deserialize)]
+pub struct HoldFormData {
+    #[serde(default)]
+    days: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CaptureFormData {
+    #[serde(default)]
+    amount: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct HoldPayload {
+    /// Days the hold stays open before it is voided; 7 by default, 31 at
+    /// most.
+    #[serde(default)]
+    pub days: Option<i64>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CapturePayload {
+    /// Final amount charged; the authorized amount by default.
+    #[serde(default)]
+    pub amount: Option<f64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CardHoldData {
+    /// `held`, `captured` or `voided`.
+    pub status: String,
+    pub authorized_amount: f64,
+    /// Current amount of the transaction.
+    pub amount: f64,
+    pub expires_at: String,
+    pub settled_at: Option<String>,
+    /// Voided by the expiry task.
+    pub expired: bool,
+}
+
+fn card_hold_data(hold: &CardHold, amount: f64) -> CardHoldData {
+    CardHoldData {
+        status: hold.status.as_str().to_string(),
+        authorized_amount: hold.authorized_amount,
+        amount,
+        expires_at: datetime_to_string(&hold.expires_at),
+        settled_at: hold.settled_at.as_ref().map(datetime_to_string),
+        expired: hold.expired,
+    }
+}
+
+/// Card hold section of the edit page: the state of the hold and the forms
+/// to settle it, or the form placing one.
+pub(super) struct HoldPanel {
+    pub status: String,
+    pub status_label: String,
+    pub authorized_amount: f64,
+    pub amount: f64,
+    pub expires_at: String,
+    pub settled_at: Option<String>,
+    pub expired: bool,
+    pub is_open: bool,
+    pub hold_action: String,
+    pub capture_action: String,
+    pub void_action: String,
+    pub default_days: i64,
+    pub max_days: i64,
+}
+
+/// The hold section for `transaction`; transfers, refunds and exchange
+/// adjustments get none.
+pub(super) fn hold_panel(transaction: &Transaction) -> Option<HoldPanel> {
+    let id = transaction.id?.to_hex();
+    if transaction.hold.is_none()
+        && (transaction.transaction_type == TransactionType::Transfer
+            || transaction.refund_of.is_some()
+            || transaction.fx_adjustment.is_some())
+    {
+        return None;
+    }
+    let hold = transaction.hold.as_ref();
+    Some(HoldPanel {
+        status: hold
+            .map(|hold| hold.status.as_str().to_string())
+            .unwrap_or_default(),
+        status_label: hold
+            .map(|hold| hold.status.label().to_string())
+            .unwrap_or_default(),
+        authorized_amount: hold.map_or(transaction.amount, |hold| hold.authorized_amount),
+        amount: transaction.amount,
+        expires_at: hold
+            .map(|hold| datetime_to_string(&hold.expires_at))
+            .unwrap_or_default(),
+        settled_at: hold
+            .and_then(|hold| hold.settled_at.as_ref())
+            .map(datetime_to_string),
+        expired: hold.is_some_and(|hold| hold.expired),
+        is_open: hold.is_some_and(|hold| hold.status == HoldStatus::Held),
+        hold_action: format!("/admin/transactions/{id}/hold"),
+        capture_action: format!("/admin/transactions/{id}/capture"),
+        void_action: format!("/admin/transactions/{id}/void"),
+        default_days: DEFAULT_HOLD_DAYS,
+        max_days: MAX_HOLD_DAYS,
+    })
+}
+
+async fn load_transaction(
+    state: &AppState,
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+    id: &str,
+) -> Result<ObjectId, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let tx = get_transaction_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&tx.company_id, company_id)?;
+    ensure_transaction_access(session_user, &tx)?;
+    Ok(object_id)
+}
+
+/// Company and transaction of a hold request, or the response refusing it.
+async fn hold_target(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, ObjectId), Response> {
+    let company_id = require_admin_active(session_user).map_err(IntoResponse::into_response)?;
+    let tx_id = load_transaction(state, session_user, &company_id, id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    Ok((company_id, tx_id))
+}
+
+fn edit_page(id: &str) -> Redirect {
+    Redirect::to(&format!("/admin/transactions/{id}/edit"))
+}
+
+fn flash_result(result: anyhow::Result<()>, success: &str, id: &str) -> Response {
+    match result {
+        Ok(()) => (Flash::success(success), edit_page(id)).into_response(),
+        Err(err) => (Flash::error(err.to_string()), edit_page(id)).into_response(),
+    }
+}
+
+/// POST /admin/transactions/{id}/hold — marks the transaction as a card hold
+/// and returns to its edit page.
+pub async fn transactions_hold(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<HoldFormData>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let days = match parse_optional_i32_field(form.days, "Días") {
+        Ok(days) => days.map_or(DEFAULT_HOLD_DAYS, i64::from),
+        Err(message) => return (Flash::error(message), edit_page(&id)).into_response(),
+    };
+    let result = place_card_hold(&state, &company_id, &tx_id, days).await;
+    flash_result(result, "Movimiento marcado como retención de tarjeta.", &id)
+}
+
+/// POST /admin/transactions/{id}/capture — confirms the hold for the amount
+/// charged.
+pub async fn transactions_capture(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<CaptureFormData>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let amount = match parse_optional_f64_field(form.amount, "Monto") {
+        Ok(amount) => amount,
+        Err(message) => return (Flash::error(message), edit_page(&id)).into_response(),
+    };
+    let result = capture_card_hold(&state, &company_id, &tx_id, amount).await;
+    flash_result(result, "Retención cobrada.", &id)
+}
+
+/// POST /admin/transactions/{id}/void — releases the hold.
+pub async fn transactions_void(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let result = void_card_hold(&state, &company_id, &tx_id).await;
+    flash_result(result, "Retención anulada.", &id)
+}
+
+/// The hold of the transaction after a change, or the error that stopped it.
+async fn hold_response(state: &AppState, id: &ObjectId, result: anyhow::Result<()>) -> Response {
+    if let Err(err) = result {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response();
+    }
+    match get_transaction_by_id(state, id).await {
+        Ok(Some(tx)) => match &tx.hold {
+            Some(hold) => Json(card_hold_data(hold, tx.amount)).into_response(),
+            None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        },
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/{id}/hold",
+    tag = "finance",
+    params(("id" = String, Path, description = "Transaction put on hold")),
+    request_body = HoldPayload,
+    responses(
+        (status = 200, description = "The hold placed", body = CardHoldData),
+        (status = 400, description = "Transfer, refund, already a hold, or days out of range"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn transaction_hold_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<HoldPayload>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let days = payload.days.unwrap_or(DEFAULT_HOLD_DAYS);
+    let result = place_card_hold(&state, &company_id, &tx_id, days).await;
+    hold_response(&state, &tx_id, result).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/{id}/capture",
+    tag = "finance",
+    params(("id" = String, Path, description = "Transaction on hold")),
+    request_body = CapturePayload,
+    responses(
+        (status = 200, description = "The hold captured; the transaction is confirmed", body = CardHoldData),
+        (status = 400, description = "Not an open hold, or amount not above zero"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn transaction_capture_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<CapturePayload>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let result = capture_card_hold(&state, &company_id, &tx_id, payload.amount).await;
+    hold_response(&state, &tx_id, result).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/{id}/void",
+    tag = "finance",
+    params(("id" = String, Path, description = "Transaction on hold")),
+    responses(
+        (status = 200, description = "The hold voided", body = CardHoldData),
+        (status = 400, description = "Not an open hold"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn transaction_void_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let (company_id, tx_id) = match hold_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let result = void_card_hold(&state, &company_id, &tx_id).await;
+    hold_response(&state, &tx_id, result).await
+}
@@ -6,27 +6,59 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
+    flash::Flash,
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
-        AppState, create_category, delete_category, get_category_by_id, list_categories,
-        update_category,
+        AppState, BudgetUsage, budget_usage, company_default_currency, create_category,
+        delete_category, get_category_by_id, get_tax_profile_by_id, list_categories,
+        list_tax_profiles, set_category_budget, set_category_tax_profile, update_category,
     },
 };
 
 use super::helpers::*;
 
+/// Categories, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/categories",
+            get(categories_index).post(categories_create),
+        )
+        .route(
+            "/api/admin/categories",
+            get(categories_data_api).post(categories_create_api),
+        )
+        .route("/api/admin/categories/{id}", get(category_data_api))
+        .route(
+            "/api/admin/categories/{id}/update",
+            post(category_update_api),
+        )
+        .route(
+            "/api/admin/categories/{id}/delete",
+            post(category_delete_api),
+        )
+        .route("/admin/categories/new", get(categories_new))
+        .route("/admin/categories/{id}/edit", get(categories_edit))
+        .route("/admin/categories/{id}/update", post(categories_update))
+        .route("/admin/categories/{id}/delete", post(categories_delete))
+}
+
 #[derive(Template)]
 #[template(path = "admin/categories/index.html")]
 struct CategoriesIndexTemplate {
     categories: Vec<CategoryRow>,
+    /// Of the budgets.
+    currency: String,
 }
 
 #[derive(Serialize)]
@@ -36,6 +68,36 @@ pub struct CategoryRow {
     pub company: String,
     pub flow_type: String,
     pub parent: String,
+    /// This month's use of the monthly budget, when the category has one.
+    pub budget: Option<BudgetCell>,
+}
+
+#[derive(Serialize)]
+pub struct BudgetCell {
+    pub amount: f64,
+    pub spent: f64,
+    pub percent: u32,
+    /// Alert threshold reached (80 or 100), which flags the row.
+    pub threshold: Option<u32>,
+}
+
+fn budget_cell(usage: Option<&BudgetUsage>) -> Option<BudgetCell> {
+    usage.map(|usage| BudgetCell {
+        amount: usage.budget,
+        spent: usage.spent,
+        percent: usage.percent().round() as u32,
+        threshold: usage.threshold(),
+    })
+}
+
+/// A monthly budget as entered: empty for none, otherwise a positive amount.
+fn parse_monthly_budget(value: Option<String>) -> Result<Option<f64>, String> {
+    match parse_optional_f64_field(value, "Presupuesto mensual")? {
+        Some(amount) if amount <= 0.0 => {
+            Err("Presupuesto mensual debe ser mayor a cero".to_string())
+        }
+        amount => Ok(amount),
+    }
 }
 
 #[derive(Serialize)]
@@ -48,22 +110,42 @@ pub struct CategoryDetail {
     pub parent_id: Option<String>,
     pub parent: Option<String>,
     pub notes: Option<String>,
+    pub monthly_budget: Option<f64>,
+    pub tax_profile_id: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct CategoryCreatePayload {
     pub name: String,
     pub flow_type: String,
-    pub parent_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub parent_id: Option<ObjectId>,
     pub notes: Option<String>,
+    #[serde(default)]
+    pub monthly_budget: Option<f64>,
+    /// Tax profile of the category's movements; without one the company's
+    /// default profile applies.
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub tax_profile_id: Option<ObjectId>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct CategoryUpdatePayload {
     pub name: String,
     pub flow_type: String,
-    pub parent_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub parent_id: Option<ObjectId>,
     pub notes: Option<String>,
+    #[serde(default)]
+    pub monthly_budget: Option<f64>,
+    /// Tax profile of the category's movements; without one the company's
+    /// default profile applies.
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub tax_profile_id: Option<ObjectId>,
 }
 
 #[utoipa::path(
@@ -95,6 +177,9 @@ pub async fn categories_data_api(
             .collect(),
     );
     let active_name = session_user.user().company_name.clone();
+    let usage = budget_usage(&state, &active_company, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let rows = categories
         .into_iter()
@@ -108,6 +193,7 @@ pub async fn categories_data_api(
                     .parent_id
                     .and_then(|pid| category_map.get(&pid).cloned())
                     .unwrap_or_else(|| "-".into()),
+                budget: budget_cell(usage.get(&id)),
             })
         })
         .collect();
@@ -155,32 +241,31 @@ pub async fn categories_create_api(
         )
             .into_response();
     }
-    let parent_id = match clean_opt(payload.parent_id) {
-        Some(parent_id) => match ObjectId::from_str(&parent_id) {
-            Ok(id) => {
-                match get_category_by_id(&state, &id).await {
-                    Ok(Some(parent)) => {
-                        if let Err(status) = ensure_same_company(&parent.company_id, &company_id) {
-                            return status.into_response();
-                        }
-                    }
-                    Ok(None) => return StatusCode::BAD_REQUEST.into_response(),
-                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    let parent_id = payload.parent_id;
+    if let Some(id) = parent_id {
+        match get_category_by_id(&state, &id).await {
+            Ok(Some(parent)) => {
+                if let Err(status) = ensure_same_company(&parent.company_id, &company_id) {
+                    return status.into_response();
                 }
-                Some(id)
             }
-            Err(_) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({ "error": "parent_id is invalid" })),
-                )
-                    .into_response();
-            }
-        },
-        None => None,
-    };
+            Ok(None) => return StatusCode::BAD_REQUEST.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    if payload.monthly_budget.is_some_and(|amount| amount <= 0.0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "monthly_budget must be positive" })),
+        )
+            .into_response();
+    }
+    if let Err(status) = ensure_tax_profile(&state, payload.tax_profile_id, &company_id).await {
+        return status.into_response();
+    }
 
-    match create_category(
+    let id = match create_category(
         &state,
         &company_id,
         name,
@@ -190,13 +275,28 @@ pub async fn categories_create_api(
     )
     .await
     {
-        Ok(id) => (
-            StatusCode::CREATED,
-            Json(serde_json::json!({ "id": id.to_hex() })),
-        )
-            .into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if payload.monthly_budget.is_some()
+        && set_category_budget(&state, &id, payload.monthly_budget)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
+    if payload.tax_profile_id.is_some()
+        && set_category_tax_profile(&state, &company_id, &id, payload.tax_profile_id)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "id": id.to_hex() })),
+    )
+        .into_response()
 }
 
 #[utoipa::path(
@@ -243,6 +343,8 @@ pub async fn category_data_api(
         parent_id: category.parent_id.map(|id| id.to_hex()),
         parent,
         notes: category.notes,
+        monthly_budget: category.monthly_budget,
+        tax_profile_id: category.tax_profile_id.map(|id| id.to_hex()),
     }))
 }
 
@@ -275,14 +377,22 @@ pub async fn category_update_api(
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
-    match get_category_by_id(&state, &object_id).await {
+    let (current_budget, current_tax_profile) = match get_category_by_id(&state, &object_id).await {
         Ok(Some(category)) => {
             if let Err(status) = ensure_same_company(&category.company_id, &company_id) {
                 return status.into_response();
             }
+            (category.monthly_budget, category.tax_profile_id)
         }
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if payload.monthly_budget.is_some_and(|amount| amount <= 0.0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "monthly_budget must be positive" })),
+        )
+            .into_response();
     }
     let flow_type = match parse_flow_type(&payload.flow_type) {
         Ok(value) => value,
@@ -302,39 +412,30 @@ pub async fn category_update_api(
         )
             .into_response();
     }
-    let parent_id = match clean_opt(payload.parent_id) {
-        Some(parent_id) => match ObjectId::from_str(&parent_id) {
-            Ok(parent_id) => {
-                if parent_id == object_id {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(serde_json::json!({ "error": "parent_id cannot be the category itself" })),
-                    )
-                    .into_response();
-                }
-                match get_category_by_id(&state, &parent_id).await {
-                    Ok(Some(parent)) => {
-                        if let Err(status) = ensure_same_company(&parent.company_id, &company_id) {
-                            return status.into_response();
-                        }
-                    }
-                    Ok(None) => return StatusCode::BAD_REQUEST.into_response(),
-                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    let parent_id = payload.parent_id;
+    if let Some(parent_id) = parent_id {
+        if parent_id == object_id {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "parent_id cannot be the category itself" })),
+            )
+                .into_response();
+        }
+        match get_category_by_id(&state, &parent_id).await {
+            Ok(Some(parent)) => {
+                if let Err(status) = ensure_same_company(&parent.company_id, &company_id) {
+                    return status.into_response();
                 }
-                Some(parent_id)
-            }
-            Err(_) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({ "error": "parent_id is invalid" })),
-                )
-                    .into_response();
             }
-        },
-        None => None,
-    };
+            Ok(None) => return StatusCode::BAD_REQUEST.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+    if let Err(status) = ensure_tax_profile(&state, payload.tax_profile_id, &company_id).await {
+        return status.into_response();
+    }
 
-    match update_category(
+    if update_category(
         &state,
         &object_id,
         &company_id,
@@ -344,10 +445,25 @@ pub async fn category_update_api(
         clean_opt(payload.notes),
     )
     .await
+    .is_err()
     {
-        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if payload.monthly_budget != current_budget
+        && set_category_budget(&state, &object_id, payload.monthly_budget)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if payload.tax_profile_id != current_tax_profile
+        && set_category_tax_profile(&state, &company_id, &object_id, payload.tax_profile_id)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
+    Json(serde_json::json!({ "ok": true })).into_response()
 }
 
 #[utoipa::path(
@@ -402,9 +518,11 @@ struct CategoryFormTemplate {
     name: String,
     flow_type: String,
     parent_id: Option<String>,
+    monthly_budget: String,
     companies: Vec<SimpleOption>,
     flow_options: Vec<SimpleOption>,
     parent_options: Vec<SimpleOption>,
+    tax_profile_options: Vec<SimpleOption>,
     is_edit: bool,
     errors: Option<String>,
 }
@@ -414,8 +532,12 @@ pub struct CategoryFormData {
     name: String,
     company_id: String,
     flow_type: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    parent_id: Option<ObjectId>,
     #[serde(default)]
-    parent_id: Option<String>,
+    monthly_budget: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    tax_profile_id: Option<ObjectId>,
 }
 
 pub async fn categories_index(
@@ -438,6 +560,12 @@ pub async fn categories_index(
     );
     let active_company = session_user.active_company_id().clone();
     let active_name = session_user.user().company_name.clone();
+    let usage = budget_usage(&state, &active_company, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let currency = company_default_currency(&state, &active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let rows = categories
         .into_iter()
@@ -452,11 +580,15 @@ pub async fn categories_index(
                     .parent_id
                     .and_then(|pid| category_map.get(&pid).cloned())
                     .unwrap_or_else(|| "-".into()),
+                budget: budget_cell(usage.get(&id)),
             })
         })
         .collect();
 
-    render(CategoriesIndexTemplate { categories: rows })
+    render(CategoriesIndexTemplate {
+        categories: rows,
+        currency,
+    })
 }
 
 pub async fn categories_new(
@@ -467,15 +599,18 @@ pub async fn categories_new(
 
     let companies = company_options(&state, &active_company).await?;
     let parents = category_parent_options(&state, None, &active_company).await?;
+    let tax_profiles = tax_profile_options(&state, None, &active_company).await?;
 
     render(CategoryFormTemplate {
         action: "/admin/categories".into(),
         name: String::new(),
         flow_type: "income".into(),
         parent_id: None,
+        monthly_budget: String::new(),
         companies,
         flow_options: flow_options("income"),
         parent_options: parents,
+        tax_profile_options: tax_profiles,
         is_edit: false,
         errors: None,
     })
@@ -497,20 +632,29 @@ pub async fn categories_create(
     let parents = category_parent_options(&state, None, &company_id)
         .await
         .unwrap_or_default();
+    let tax_profiles = tax_profile_options(&state, form.tax_profile_id.as_ref(), &company_id)
+        .await
+        .unwrap_or_default();
 
-    let flow_type = match parse_flow_type(&form.flow_type) {
-        Ok(f) => f,
+    let parsed = parse_flow_type(&form.flow_type).and_then(|flow_type| {
+        Ok((
+            flow_type,
+            parse_monthly_budget(form.monthly_budget.clone())?,
+        ))
+    });
+    let (flow_type, monthly_budget) = match parsed {
+        Ok(parsed) => parsed,
         Err(msg) => {
             return render(CategoryFormTemplate {
                 action: "/admin/categories".into(),
                 name: form.name.clone(),
                 flow_type: form.flow_type.clone(),
-                parent_id: form.parent_id.clone(),
+                parent_id: form.parent_id.map(|id| id.to_hex()),
+                monthly_budget: form.monthly_budget.clone().unwrap_or_default(),
                 companies,
                 flow_options: flow_options(&form.flow_type),
-                parent_options: category_parent_options(&state, None, &company_id)
-                    .await
-                    .unwrap_or_default(),
+                parent_options: parents,
+                tax_profile_options: tax_profiles,
                 is_edit: false,
                 errors: Some(msg),
             })
@@ -519,42 +663,7 @@ pub async fn categories_create(
         }
     };
 
-    let parent_id = match form.parent_id.clone().and_then(|pid| {
-        let trimmed = pid.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(pid) => Some(
-            parse_object_id(&pid, "Categoría padre")
-                .map_err(|e| e)
-                .map_err(|msg| {
-                    render(CategoryFormTemplate {
-                        action: "/admin/categories".into(),
-                        name: form.name.clone(),
-                        flow_type: form.flow_type.clone(),
-                        parent_id: Some(pid.clone()),
-                        companies: companies.clone(),
-                        flow_options: flow_options(&form.flow_type),
-                        parent_options: parents.clone(),
-                        is_edit: false,
-                        errors: Some(msg),
-                    })
-                    .map(IntoResponse::into_response)
-                    .unwrap_or_else(|status| status.into_response())
-                }),
-        ),
-        None => None,
-    };
-
-    let parent_id = match parent_id {
-        Some(Ok(id)) => Some(id),
-        Some(Err(resp)) => return resp,
-        None => None,
-    };
-
+    let parent_id = form.parent_id;
     if let Some(pid) = parent_id {
         match get_category_by_id(&state, &pid).await {
             Ok(Some(cat)) => {
@@ -566,8 +675,11 @@ pub async fn categories_create(
             Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
+    if let Err(status) = ensure_tax_profile(&state, form.tax_profile_id, &company_id).await {
+        return status.into_response();
+    }
 
-    match create_category(
+    let id = match create_category(
         &state,
         &company_id,
         form.name.trim(),
@@ -577,9 +689,28 @@ pub async fn categories_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/categories").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if monthly_budget.is_some()
+        && set_category_budget(&state, &id, monthly_budget)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
+    if form.tax_profile_id.is_some()
+        && set_category_tax_profile(&state, &company_id, &id, form.tax_profile_id)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Categoría creada."),
+        Redirect::to("/admin/categories"),
+    )
+        .into_response()
 }
 
 pub async fn categories_edit(
@@ -599,15 +730,22 @@ pub async fn categories_edit(
     let companies = company_options(&state, &active_company).await?;
     let parents =
         category_parent_options(&state, category.parent_id.as_ref(), &active_company).await?;
+    let tax_profiles =
+        tax_profile_options(&state, category.tax_profile_id.as_ref(), &active_company).await?;
 
     render(CategoryFormTemplate {
         action: format!("/admin/categories/{}/update", id),
         name: category.name,
         flow_type: flow_type_value(&category.flow_type).to_string(),
         parent_id: opt_to_string(&category.parent_id),
+        monthly_budget: category
+            .monthly_budget
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
         companies,
         flow_options: flow_options(flow_type_value(&category.flow_type)),
         parent_options: parents,
+        tax_profile_options: tax_profiles,
         is_edit: true,
         errors: None,
     })
@@ -629,18 +767,25 @@ pub async fn categories_update(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    match get_category_by_id(&state, &object_id).await {
+    let (current_budget, current_tax_profile) = match get_category_by_id(&state, &object_id).await {
         Ok(Some(cat)) => {
             if let Err(status) = ensure_same_company(&cat.company_id, &company_id) {
                 return status.into_response();
             }
+            (cat.monthly_budget, cat.tax_profile_id)
         }
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    }
+    };
 
-    let flow_type = match parse_flow_type(&form.flow_type) {
-        Ok(f) => f,
+    let parsed = parse_flow_type(&form.flow_type).and_then(|flow_type| {
+        Ok((
+            flow_type,
+            parse_monthly_budget(form.monthly_budget.clone())?,
+        ))
+    });
+    let (flow_type, monthly_budget) = match parsed {
+        Ok(parsed) => parsed,
         Err(msg) => {
             let companies = company_options(&state, &company_id)
                 .await
@@ -648,14 +793,20 @@ pub async fn categories_update(
             let parents = category_parent_options(&state, None, &company_id)
                 .await
                 .unwrap_or_default();
+            let tax_profiles =
+                tax_profile_options(&state, form.tax_profile_id.as_ref(), &company_id)
+                    .await
+                    .unwrap_or_default();
             return render(CategoryFormTemplate {
                 action: format!("/admin/categories/{}/update", id),
                 name: form.name.clone(),
                 flow_type: form.flow_type.clone(),
-                parent_id: form.parent_id.clone(),
+                parent_id: form.parent_id.map(|id| id.to_hex()),
+                monthly_budget: form.monthly_budget.clone().unwrap_or_default(),
                 companies,
                 flow_options: flow_options(&form.flow_type),
                 parent_options: parents,
+                tax_profile_options: tax_profiles,
                 is_edit: true,
                 errors: Some(msg),
             })
@@ -664,20 +815,7 @@ pub async fn categories_update(
         }
     };
 
-    let parent_id = match form.parent_id.clone().and_then(|pid| {
-        let trimmed = pid.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(pid) => match parse_object_id(&pid, "Categoría padre") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
+    let parent_id = form.parent_id;
 
     if let Some(pid) = parent_id {
         match get_category_by_id(&state, &pid).await {
@@ -690,8 +828,11 @@ pub async fn categories_update(
             Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         }
     }
+    if let Err(status) = ensure_tax_profile(&state, form.tax_profile_id, &company_id).await {
+        return status.into_response();
+    }
 
-    match update_category(
+    if update_category(
         &state,
         &object_id,
         &company_id,
@@ -701,10 +842,31 @@ pub async fn categories_update(
         None,
     )
     .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    // Saved only when changed: a new amount restarts the month's alerts.
+    if monthly_budget != current_budget
+        && set_category_budget(&state, &object_id, monthly_budget)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    // Changing it recalculates the tax of the category's movements.
+    if form.tax_profile_id != current_tax_profile
+        && set_category_tax_profile(&state, &company_id, &object_id, form.tax_profile_id)
+            .await
+            .is_err()
     {
-        Ok(_) => Redirect::to("/admin/categories").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
+    (
+        Flash::success("Categoría actualizada."),
+        Redirect::to("/admin/categories"),
+    )
+        .into_response()
 }
 
 pub async fn categories_delete(
@@ -733,11 +895,59 @@ pub async fn categories_delete(
     }
 
     match delete_category(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/categories").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Categoría eliminada."),
+            Redirect::to("/admin/categories"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar la categoría."),
+            Redirect::to("/admin/categories"),
+        )
+            .into_response(),
     }
 }
 
+/// Tax profiles of the company, after the choice of following its default.
+async fn tax_profile_options(
+    state: &AppState,
+    selected: Option<&ObjectId>,
+    company_id: &ObjectId,
+) -> Result<Vec<SimpleOption>, StatusCode> {
+    let profiles = list_tax_profiles(state, company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut options = vec![SimpleOption {
+        value: "".into(),
+        label: "Impuesto predeterminado de la compañía".into(),
+        selected: selected.is_none(),
+    }];
+    options.extend(profiles.into_iter().filter_map(|profile| {
+        profile.id.map(|id| SimpleOption {
+            value: id.to_hex(),
+            label: profile.name,
+            selected: selected == Some(&id),
+        })
+    }));
+    Ok(options)
+}
+
+/// A chosen tax profile must exist and belong to the company.
+async fn ensure_tax_profile(
+    state: &AppState,
+    tax_profile_id: Option<ObjectId>,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    let Some(id) = tax_profile_id else {
+        return Ok(());
+    };
+    let profile = get_tax_profile_by_id(state, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    ensure_same_company(&profile.company_id, company_id)
+}
+
 async fn category_parent_options(
     state: &AppState,
     selected: Option<&ObjectId>,
@@ -0,0 +1,288 @@
+// Comment threads on transactions, planned entries and recurring plans,
+// shown on their edit pages, and the mention notifications they send.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    models::{Comment, CommentEntity},
+    routes::{Routes, form_fields::optional_object_id},
+    session::SessionUser,
+    state::{
+        AppState, COMMENT_MAX_CHARS, add_comment, delete_comment, get_comment,
+        get_planned_entry_by_id, get_recurring_plan_by_id, get_transaction_by_id, list_comments,
+        list_notifications, mark_notifications_read,
+    },
+};
+
+use super::helpers::*;
+
+/// Comments and the notifications they raise.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/comments", post(comments_create))
+        .route("/admin/comments/{id}/delete", post(comments_delete))
+        .route("/admin/notifications", get(notifications_index))
+}
+
+const NOTIFICATIONS_SHOWN: i64 = 100;
+
+/// Comments of one record, as rendered by `admin/comments/thread.html`.
+pub struct CommentThread {
+    pub entity: &'static str,
+    pub entity_id: String,
+    pub max_chars: usize,
+    pub comments: Vec<CommentView>,
+}
+
+pub struct CommentView {
+    pub id: String,
+    pub author: String,
+    pub created_at: String,
+    pub body: String,
+    pub can_delete: bool,
+    pub replies: Vec<CommentView>,
+}
+
+/// Only the author of a comment may delete it.
+fn comment_view(comment: &Comment, session_user: &SessionUser) -> CommentView {
+    CommentView {
+        id: opt_to_string(&comment.id).unwrap_or_default(),
+        author: comment.author_name.clone(),
+        created_at: comment
+            .created_at
+            .to_chrono()
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        body: comment.body.clone(),
+        can_delete: &comment.author_id == session_user.user_id(),
+        replies: Vec::new(),
+    }
+}
+
+/// Thread of the record for its edit page. Replies are grouped under the
+/// comment they answer.
+pub(super) async fn comment_thread(
+    state: &AppState,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+    session_user: &SessionUser,
+) -> Result<CommentThread, StatusCode> {
+    let comments = list_comments(state, entity, entity_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut views: Vec<CommentView> = Vec::new();
+    let mut roots: Vec<ObjectId> = Vec::new();
+    for comment in comments.iter().filter(|c| c.parent_id.is_none()) {
+        views.push(comment_view(comment, session_user));
+        roots.push(comment.id.unwrap_or_default());
+    }
+    for comment in &comments {
+        let Some(parent_id) = comment.parent_id else {
+            continue;
+        };
+        if let Some(pos) = roots.iter().position(|id| id == &parent_id) {
+            views[pos].replies.push(comment_view(comment, session_user));
+        }
+    }
+    Ok(CommentThread {
+        entity: entity.as_str(),
+        entity_id: entity_id.to_hex(),
+        max_chars: COMMENT_MAX_CHARS,
+        comments: views,
+    })
+}
+
+/// Fails unless the record exists in the active company and touches one of
+/// the accounts the user may see.
+async fn ensure_entity_in_company(
+    state: &AppState,
+    session_user: &SessionUser,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+    active_company: &ObjectId,
+) -> Result<(), StatusCode> {
+    match entity {
+        CommentEntity::Transaction => {
+            let tx = get_transaction_by_id(state, entity_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            ensure_same_company(&tx.company_id, active_company)?;
+            ensure_transaction_access(session_user, &tx)
+        }
+        CommentEntity::PlannedEntry => {
+            let entry = get_planned_entry_by_id(state, entity_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            ensure_same_company(&entry.company_id, active_company)?;
+            ensure_account_access(session_user, [&entry.account_expected_id])
+        }
+        CommentEntity::RecurringPlan => {
+            let plan = get_recurring_plan_by_id(state, entity_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            ensure_same_company(&plan.company_id, active_company)?;
+            ensure_account_access(session_user, [&plan.account_expected_id])
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommentFormData {
+    pub entity: String,
+    pub entity_id: String,
+    pub body: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    pub parent_id: Option<ObjectId>,
+}
+
+pub async fn comments_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<CommentFormData>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Some(entity) = CommentEntity::parse(&form.entity) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let Ok(entity_id) = ObjectId::from_str(&form.entity_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let body = form.body.trim();
+    if body.is_empty() || body.chars().count() > COMMENT_MAX_CHARS {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    if let Err(status) =
+        ensure_entity_in_company(&state, &session_user, entity, &entity_id, &active_company).await
+    {
+        return status.into_response();
+    }
+
+    match add_comment(
+        &state,
+        &active_company,
+        entity,
+        &entity_id,
+        form.parent_id,
+        session_user.user_id(),
+        session_user.user().label(),
+        body,
+    )
+    .await
+    {
+        Ok(comment) => Redirect::to(&format!(
+            "{}#comment-{}",
+            entity.edit_path(&entity_id),
+            opt_to_string(&comment.id).unwrap_or_default()
+        ))
+        .into_response(),
+        // A parent from another thread.
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+pub async fn comments_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let comment = match get_comment(&state, &object_id).await {
+        Ok(Some(comment)) => comment,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&comment.company_id, &active_company) {
+        return status.into_response();
+    }
+    if &comment.author_id != session_user.user_id() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match delete_comment(&state, &object_id).await {
+        Ok(_) => Redirect::to(&format!(
+            "{}#comments",
+            comment.entity.edit_path(&comment.entity_id)
+        ))
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/comments/notifications.html")]
+struct NotificationsTemplate {
+    notifications: Vec<NotificationRow>,
+}
+
+struct NotificationRow {
+    author: String,
+    excerpt: String,
+    created_at: String,
+    link: String,
+    unread: bool,
+}
+
+/// Mentions of the user in the active company. Opening the page marks them
+/// as read.
+pub async fn notifications_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let notifications = list_notifications(
+        &state,
+        session_user.user_id(),
+        &active_company,
+        NOTIFICATIONS_SHOWN,
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    mark_notifications_read(&state, session_user.user_id(), &active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let notifications = notifications
+        .into_iter()
+        .map(|n| NotificationRow {
+            author: n.author_name,
+            excerpt: n.excerpt,
+            created_at: n
+                .created_at
+                .to_chrono()
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            link: format!(
+                "{}#comment-{}",
+                n.entity.edit_path(&n.entity_id),
+                n.comment_id.to_hex()
+            ),
+            unread: n.read_at.is_none(),
+        })
+        .collect();
+    render(NotificationsTemplate { notifications })
+}
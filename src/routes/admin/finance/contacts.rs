@@ -1,11 +1,12 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Path, State},
+    extract::{Form, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
@@ -14,18 +15,59 @@ use serde::{Deserialize, Serialize};
 use crate::filters;
 
 use crate::{
+    flash::Flash,
+    models::{Contact, CustomFieldDefinition, CustomFieldEntity},
+    routes::Routes,
     session::SessionUser,
     state::{
-        AppState, create_contact, delete_contact, get_contact_by_id, list_contacts, update_contact,
+        AppState, ContactErasure, MAX_PAYMENT_TERMS_DAYS, create_contact, create_portal_link,
+        custom_field_display, custom_field_filter, custom_field_values, delete_contact,
+        erase_contact_personal_data, find_by_custom_fields, get_contact_by_id,
+        revoke_portal_access, set_contact_payment_terms, set_custom_field_values, update_contact,
     },
 };
 
+use super::custom_fields::{
+    CustomFieldInput, CustomFieldsForm, custom_field_filters, custom_field_inputs,
+    custom_field_inputs_from_raw, custom_fields_json, entity_custom_fields, raw_custom_values,
+};
 use super::helpers::*;
 
+/// Contacts, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/contacts", get(contacts_index).post(contacts_create))
+        .route(
+            "/api/admin/contacts",
+            get(contacts_data_api).post(contacts_create_api),
+        )
+        .route("/api/admin/contacts/{id}", get(contact_data_api))
+        .route("/api/admin/contacts/{id}/update", post(contact_update_api))
+        .route("/api/admin/contacts/{id}/delete", post(contact_delete_api))
+        .route("/api/admin/contacts/{id}/erase", post(contact_erase_api))
+        .route("/admin/contacts/new", get(contacts_new))
+        .route("/admin/contacts/{id}/edit", get(contacts_edit))
+        .route("/admin/contacts/{id}/update", post(contacts_update))
+        .route("/admin/contacts/{id}/delete", post(contacts_delete))
+        .route("/admin/contacts/{id}/erase", post(contacts_erase))
+        .route(
+            "/admin/contacts/{id}/portal/link",
+            post(contacts_portal_link),
+        )
+        .route(
+            "/admin/contacts/{id}/portal/revoke",
+            post(contacts_portal_revoke),
+        )
+}
+
 #[derive(Template)]
 #[template(path = "admin/contacts/index.html")]
 struct ContactsIndexTemplate {
     contacts: Vec<ContactRow>,
+    /// Labels of the custom field columns, in the order of `ContactRow::custom_values`.
+    custom_columns: Vec<String>,
+    custom_filters: Vec<CustomFieldInput>,
+    filter_action: String,
 }
 
 #[derive(Serialize)]
@@ -35,6 +77,9 @@ pub struct ContactRow {
     pub company: String,
     pub kind: String,
     pub email: String,
+    pub custom_fields: serde_json::Value,
+    #[serde(skip)]
+    pub custom_values: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -47,7 +92,9 @@ pub struct ContactDetail {
     pub rfc: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
+    pub payment_terms_days: Option<i32>,
     pub notes: Option<String>,
+    pub custom_fields: serde_json::Value,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -57,7 +104,13 @@ pub struct ContactCreatePayload {
     pub rfc: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
+    /// Days the contact has to pay, e.g. 30 for "net 30".
+    pub payment_terms_days: Option<i32>,
     pub notes: Option<String>,
+    /// Custom field values keyed by field key.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub custom_fields: Option<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -67,45 +120,108 @@ pub struct ContactUpdatePayload {
     pub rfc: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
+    /// Days the contact has to pay, e.g. 30 for "net 30". Omitted or null
+    /// clears them.
+    pub payment_terms_days: Option<i32>,
     pub notes: Option<String>,
+    /// Custom field values keyed by field key. When omitted the stored values
+    /// are kept.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub custom_fields: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ContactErasePayload {
+    /// `purge` drops email, phone and notes; `anonymize` also replaces the
+    /// name and drops the RFC.
+    pub mode: String,
+}
+
+/// Payment terms as entered: empty for none, otherwise whole days up to a
+/// year.
+fn parse_payment_terms(value: Option<String>) -> Result<Option<i32>, String> {
+    match parse_optional_i32_field(value, "Días de crédito")? {
+        Some(days) if !(0..=MAX_PAYMENT_TERMS_DAYS).contains(&days) => Err(format!(
+            "Días de crédito debe estar entre 0 y {MAX_PAYMENT_TERMS_DAYS}"
+        )),
+        days => Ok(days),
+    }
+}
+
+fn payment_terms_error() -> axum::response::Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "error": format!("payment_terms_days must be between 0 and {MAX_PAYMENT_TERMS_DAYS}")
+        })),
+    )
+        .into_response()
+}
+
+fn contact_row(
+    contact: Contact,
+    fields: &[CustomFieldDefinition],
+    company: &str,
+) -> Option<ContactRow> {
+    let id = contact.id?;
+    Some(ContactRow {
+        id: id.to_hex(),
+        name: contact.name,
+        company: company.to_string(),
+        kind: contact_type_value(&contact.contact_type).to_string(),
+        email: contact.email.unwrap_or_else(|| "-".into()),
+        custom_values: fields
+            .iter()
+            .map(|field| custom_field_display(field, contact.custom_fields.get(&field.key)))
+            .collect(),
+        custom_fields: custom_fields_json(&contact.custom_fields),
+    })
+}
+
+/// Contacts of the company matching the `cf_<key>` parameters, as rows.
+async fn contact_rows(
+    state: &AppState,
+    company_id: &ObjectId,
+    company: &str,
+    fields: &[CustomFieldDefinition],
+    params: &HashMap<String, String>,
+) -> Result<Vec<ContactRow>, StatusCode> {
+    let filter = custom_field_filter(fields, params).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let contacts = find_by_custom_fields(&state.contacts, company_id, filter)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(contacts
+        .into_iter()
+        .filter_map(|c| contact_row(c, fields, company))
+        .collect())
 }
 
 #[utoipa::path(
     get,
     path = "/api/admin/contacts",
     tag = "finance",
+    params(("cf_<key>" = Option<String>, Query, description = "Filter by a custom field value; text matches partially")),
     responses(
         (status = 200, description = "List of contacts"),
         (status = 401, description = "Not authenticated"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid custom field filter")
     ),
     security(("session" = []))
 )]
 pub async fn contacts_data_api(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Vec<ContactRow>>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let active_name = session_user.user().company_name.clone();
-    let contacts = list_contacts(&state)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let fields = entity_custom_fields(&state, &active_company, CustomFieldEntity::Contact).await?;
 
-    let rows = contacts
-        .into_iter()
-        .filter(|c| c.company_id == active_company)
-        .filter_map(|c| {
-            c.id.map(|id| ContactRow {
-                id: id.to_hex(),
-                name: c.name,
-                company: active_name.clone(),
-                kind: contact_type_value(&c.contact_type).to_string(),
-                email: c.email.unwrap_or_else(|| "-".into()),
-            })
-        })
-        .collect();
-
-    Ok(Json(rows))
+    contact_rows(&state, &active_company, &active_name, &fields, &params)
+        .await
+        .map(Json)
 }
 
 #[utoipa::path(
@@ -148,6 +264,27 @@ pub async fn contacts_create_api(
         )
             .into_response();
     }
+    if payload
+        .payment_terms_days
+        .is_some_and(|days| !(0..=MAX_PAYMENT_TERMS_DAYS).contains(&days))
+    {
+        return payment_terms_error();
+    }
+    let fields = match entity_custom_fields(&state, &company_id, CustomFieldEntity::Contact).await {
+        Ok(fields) => fields,
+        Err(status) => return status.into_response(),
+    };
+    let custom_values =
+        match custom_field_values(&fields, &raw_custom_values(payload.custom_fields)) {
+            Ok(values) => values,
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": message })),
+                )
+                    .into_response();
+            }
+        };
 
     match create_contact(
         &state,
@@ -161,11 +298,32 @@ pub async fn contacts_create_api(
     )
     .await
     {
-        Ok(id) => (
-            StatusCode::CREATED,
-            Json(serde_json::json!({ "id": id.to_hex() })),
-        )
-            .into_response(),
+        Ok(id) => {
+            if payload.payment_terms_days.is_some()
+                && set_contact_payment_terms(&state, &id, payload.payment_terms_days)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if set_custom_field_values(
+                &state,
+                CustomFieldEntity::Contact,
+                &company_id,
+                &id,
+                custom_values,
+            )
+            .await
+            .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({ "id": id.to_hex() })),
+            )
+                .into_response()
+        }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -205,7 +363,9 @@ pub async fn contact_data_api(
         rfc: contact.rfc,
         email: contact.email,
         phone: contact.phone,
+        payment_terms_days: contact.payment_terms_days,
         notes: contact.notes,
+        custom_fields: custom_fields_json(&contact.custom_fields),
     }))
 }
 
@@ -265,6 +425,32 @@ pub async fn contact_update_api(
         )
             .into_response();
     }
+    if payload
+        .payment_terms_days
+        .is_some_and(|days| !(0..=MAX_PAYMENT_TERMS_DAYS).contains(&days))
+    {
+        return payment_terms_error();
+    }
+    let custom_values = match payload.custom_fields {
+        Some(values) => {
+            let fields =
+                match entity_custom_fields(&state, &company_id, CustomFieldEntity::Contact).await {
+                    Ok(fields) => fields,
+                    Err(status) => return status.into_response(),
+                };
+            match custom_field_values(&fields, &raw_custom_values(Some(values))) {
+                Ok(values) => Some(values),
+                Err(message) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(serde_json::json!({ "error": message })),
+                    )
+                        .into_response();
+                }
+            }
+        }
+        None => None,
+    };
 
     match update_contact(
         &state,
@@ -279,7 +465,28 @@ pub async fn contact_update_api(
     )
     .await
     {
-        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Ok(_) => {
+            if set_contact_payment_terms(&state, &object_id, payload.payment_terms_days)
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if let Some(values) = custom_values
+                && set_custom_field_values(
+                    &state,
+                    CustomFieldEntity::Contact,
+                    &company_id,
+                    &object_id,
+                    values,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Json(serde_json::json!({ "ok": true })).into_response()
+        }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -329,6 +536,57 @@ pub async fn contact_delete_api(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/admin/contacts/{id}/erase",
+    tag = "finance",
+    params(("id" = String, Path, description = "Record id")),
+    request_body = ContactErasePayload,
+    responses(
+        (status = 200, description = "Personal data erased; linked records are kept"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 400, description = "Invalid mode")
+    ),
+    security(("session" = []))
+)]
+pub async fn contact_erase_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<ContactErasePayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let Some(mode) = ContactErasure::parse(&payload.mode) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Modo de borrado inválido" })),
+        )
+            .into_response();
+    };
+    match get_contact_by_id(&state, &object_id).await {
+        Ok(Some(contact)) => {
+            if let Err(status) = ensure_same_company(&contact.company_id, &company_id) {
+                return status.into_response();
+            }
+        }
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+    match erase_contact_personal_data(&state, &object_id, &company_id, mode).await {
+        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
 #[derive(Template)]
 #[template(path = "admin/contacts/form.html")]
 struct ContactFormTemplate {
@@ -338,11 +596,21 @@ struct ContactFormTemplate {
     contact_type: String,
     email: String,
     phone: String,
+    payment_terms_days: String,
     notes: String,
     companies: Vec<SimpleOption>,
     contact_options: Vec<SimpleOption>,
     is_edit: bool,
+    erase_action: String,
+    /// Base path of the portal access actions, `/admin/contacts/{id}/portal`.
+    portal_action: String,
     errors: Option<String>,
+    custom_fields: Vec<CustomFieldInput>,
+}
+
+#[derive(Deserialize)]
+pub struct ContactEraseForm {
+    mode: String,
 }
 
 #[derive(Deserialize)]
@@ -357,38 +625,64 @@ pub struct ContactFormData {
     #[serde(default)]
     phone: Option<String>,
     #[serde(default)]
+    payment_terms_days: Option<String>,
+    #[serde(default)]
     notes: Option<String>,
 }
 
+/// Re-renders the submitted form with an error message.
+fn contact_form_error(
+    id: Option<&str>,
+    form: &ContactFormData,
+    companies: Vec<SimpleOption>,
+    custom_fields: Vec<CustomFieldInput>,
+    message: String,
+) -> axum::response::Response {
+    render(ContactFormTemplate {
+        action: match id {
+            Some(id) => format!("/admin/contacts/{}/update", id),
+            None => "/admin/contacts".into(),
+        },
+        name: form.name.clone(),
+        rfc: form.rfc.clone().unwrap_or_default(),
+        contact_type: form.contact_type.clone(),
+        email: form.email.clone().unwrap_or_default(),
+        phone: form.phone.clone().unwrap_or_default(),
+        payment_terms_days: form.payment_terms_days.clone().unwrap_or_default(),
+        notes: form.notes.clone().unwrap_or_default(),
+        companies,
+        contact_options: contact_type_options(&form.contact_type),
+        is_edit: id.is_some(),
+        erase_action: id
+            .map(|id| format!("/admin/contacts/{}/erase", id))
+            .unwrap_or_default(),
+        portal_action: id
+            .map(|id| format!("/admin/contacts/{}/portal", id))
+            .unwrap_or_default(),
+        errors: Some(message),
+        custom_fields,
+    })
+    .map(IntoResponse::into_response)
+    .unwrap_or_else(|status| status.into_response())
+}
+
 pub async fn contacts_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
-
-    let contacts = list_contacts(&state)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .into_iter()
-        .filter(|c| c.company_id == active_company)
-        .collect::<Vec<_>>();
     let active_name = session_user.user().company_name.clone();
+    let fields = entity_custom_fields(&state, &active_company, CustomFieldEntity::Contact).await?;
 
-    let rows = contacts
-        .into_iter()
-        .filter(|c| c.company_id == active_company)
-        .filter_map(|c| {
-            c.id.map(|id| ContactRow {
-                id: id.to_hex(),
-                name: c.name,
-                company: active_name.clone(),
-                kind: contact_type_value(&c.contact_type).to_string(),
-                email: c.email.unwrap_or_else(|| "-".into()),
-            })
-        })
-        .collect();
-
-    render(ContactsIndexTemplate { contacts: rows })
+    let rows = contact_rows(&state, &active_company, &active_name, &fields, &params).await?;
+
+    render(ContactsIndexTemplate {
+        contacts: rows,
+        custom_columns: fields.iter().map(|field| field.label.clone()).collect(),
+        custom_filters: custom_field_filters(&fields, &params),
+        filter_action: "/admin/contacts".into(),
+    })
 }
 
 pub async fn contacts_new(
@@ -397,6 +691,7 @@ pub async fn contacts_new(
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let companies = company_options(&state, &active_company).await?;
+    let fields = entity_custom_fields(&state, &active_company, CustomFieldEntity::Contact).await?;
 
     render(ContactFormTemplate {
         action: "/admin/contacts".into(),
@@ -405,18 +700,22 @@ pub async fn contacts_new(
         contact_type: "customer".into(),
         email: String::new(),
         phone: String::new(),
+        payment_terms_days: String::new(),
         notes: String::new(),
         companies,
         contact_options: contact_type_options("customer"),
         is_edit: false,
+        erase_action: String::new(),
+        portal_action: String::new(),
         errors: None,
+        custom_fields: custom_field_inputs(&fields, &Default::default()),
     })
 }
 
 pub async fn contacts_create(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
-    Form(form): Form<ContactFormData>,
+    CustomFieldsForm { form, custom }: CustomFieldsForm<ContactFormData>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -426,25 +725,30 @@ pub async fn contacts_create(
     let companies = company_options(&state, &company_id)
         .await
         .unwrap_or_default();
+    let fields = match entity_custom_fields(&state, &company_id, CustomFieldEntity::Contact).await {
+        Ok(fields) => fields,
+        Err(status) => return status.into_response(),
+    };
 
     let contact_type = match parse_contact_type(&form.contact_type) {
         Ok(c) => c,
         Err(msg) => {
-            return render(ContactFormTemplate {
-                action: "/admin/contacts".into(),
-                name: form.name.clone(),
-                rfc: form.rfc.clone().unwrap_or_default(),
-                contact_type: form.contact_type.clone(),
-                email: form.email.clone().unwrap_or_default(),
-                phone: form.phone.clone().unwrap_or_default(),
-                notes: form.notes.clone().unwrap_or_default(),
-                companies,
-                contact_options: contact_type_options(&form.contact_type),
-                is_edit: false,
-                errors: Some(msg),
-            })
-            .map(IntoResponse::into_response)
-            .unwrap_or_else(|status| status.into_response());
+            let inputs = custom_field_inputs_from_raw(&fields, &custom);
+            return contact_form_error(None, &form, companies, inputs, msg);
+        }
+    };
+    let custom_values = match custom_field_values(&fields, &custom) {
+        Ok(values) => values,
+        Err(msg) => {
+            let inputs = custom_field_inputs_from_raw(&fields, &custom);
+            return contact_form_error(None, &form, companies, inputs, msg);
+        }
+    };
+    let payment_terms_days = match parse_payment_terms(form.payment_terms_days.clone()) {
+        Ok(days) => days,
+        Err(msg) => {
+            let inputs = custom_field_inputs_from_raw(&fields, &custom);
+            return contact_form_error(None, &form, companies, inputs, msg);
         }
     };
 
@@ -453,7 +757,7 @@ pub async fn contacts_create(
     let phone = clean_opt(form.phone);
     let notes = clean_opt(form.notes);
 
-    match create_contact(
+    let id = match create_contact(
         &state,
         &company_id,
         form.name.trim(),
@@ -465,7 +769,30 @@ pub async fn contacts_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/contacts").into_response(),
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if payment_terms_days.is_some()
+        && set_contact_payment_terms(&state, &id, payment_terms_days)
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    match set_custom_field_values(
+        &state,
+        CustomFieldEntity::Contact,
+        &company_id,
+        &id,
+        custom_values,
+    )
+    .await
+    {
+        Ok(_) => (
+            Flash::success("Contacto creado."),
+            Redirect::to("/admin/contacts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -485,6 +812,7 @@ pub async fn contacts_edit(
     ensure_same_company(&contact.company_id, &active_company)?;
 
     let companies = company_options(&state, &active_company).await?;
+    let fields = entity_custom_fields(&state, &active_company, CustomFieldEntity::Contact).await?;
 
     render(ContactFormTemplate {
         action: format!("/admin/contacts/{}/update", id),
@@ -493,11 +821,18 @@ pub async fn contacts_edit(
         contact_type: contact_type_value(&contact.contact_type).to_string(),
         email: contact.email.unwrap_or_default(),
         phone: contact.phone.unwrap_or_default(),
+        payment_terms_days: contact
+            .payment_terms_days
+            .map(|days| days.to_string())
+            .unwrap_or_default(),
         notes: contact.notes.unwrap_or_default(),
         companies,
         contact_options: contact_type_options(contact_type_value(&contact.contact_type)),
         is_edit: true,
+        erase_action: format!("/admin/contacts/{}/erase", id),
+        portal_action: format!("/admin/contacts/{}/portal", id),
         errors: None,
+        custom_fields: custom_field_inputs(&fields, &contact.custom_fields),
     })
 }
 
@@ -505,7 +840,7 @@ pub async fn contacts_update(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Form(form): Form<ContactFormData>,
+    CustomFieldsForm { form, custom }: CustomFieldsForm<ContactFormData>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -526,28 +861,24 @@ pub async fn contacts_update(
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
+    let fields = match entity_custom_fields(&state, &company_id, CustomFieldEntity::Contact).await {
+        Ok(fields) => fields,
+        Err(status) => return status.into_response(),
+    };
 
-    let contact_type = match parse_contact_type(&form.contact_type) {
-        Ok(c) => c,
+    let parsed = parse_contact_type(&form.contact_type).and_then(|contact_type| {
+        let values = custom_field_values(&fields, &custom)?;
+        let terms = parse_payment_terms(form.payment_terms_days.clone())?;
+        Ok((contact_type, values, terms))
+    });
+    let (contact_type, custom_values, payment_terms_days) = match parsed {
+        Ok(parsed) => parsed,
         Err(msg) => {
             let companies = company_options(&state, session_user.active_company_id())
                 .await
                 .unwrap_or_default();
-            return render(ContactFormTemplate {
-                action: format!("/admin/contacts/{}/update", id),
-                name: form.name.clone(),
-                rfc: form.rfc.clone().unwrap_or_default(),
-                contact_type: form.contact_type.clone(),
-                email: form.email.clone().unwrap_or_default(),
-                phone: form.phone.clone().unwrap_or_default(),
-                notes: form.notes.clone().unwrap_or_default(),
-                companies,
-                contact_options: contact_type_options(&form.contact_type),
-                is_edit: true,
-                errors: Some(msg),
-            })
-            .map(IntoResponse::into_response)
-            .unwrap_or_else(|status| status.into_response());
+            let inputs = custom_field_inputs_from_raw(&fields, &custom);
+            return contact_form_error(Some(&id), &form, companies, inputs, msg);
         }
     };
 
@@ -556,7 +887,7 @@ pub async fn contacts_update(
     let phone = clean_opt(form.phone);
     let notes = clean_opt(form.notes);
 
-    match update_contact(
+    if update_contact(
         &state,
         &object_id,
         &company_id,
@@ -568,8 +899,30 @@ pub async fn contacts_update(
         notes,
     )
     .await
+    .is_err()
     {
-        Ok(_) => Redirect::to("/admin/contacts").into_response(),
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if set_contact_payment_terms(&state, &object_id, payment_terms_days)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    match set_custom_field_values(
+        &state,
+        CustomFieldEntity::Contact,
+        &company_id,
+        &object_id,
+        custom_values,
+    )
+    .await
+    {
+        Ok(_) => (
+            Flash::success("Contacto actualizado."),
+            Redirect::to("/admin/contacts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -600,7 +953,123 @@ pub async fn contacts_delete(
     }
 
     match delete_contact(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/contacts").into_response(),
+        Ok(_) => (
+            Flash::success("Contacto eliminado."),
+            Redirect::to("/admin/contacts"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el contacto."),
+            Redirect::to("/admin/contacts"),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn contacts_erase(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<ContactEraseForm>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let Some(mode) = ContactErasure::parse(&form.mode) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    match get_contact_by_id(&state, &object_id).await {
+        Ok(Some(contact)) => {
+            if let Err(status) = ensure_same_company(&contact.company_id, &company_id) {
+                return status.into_response();
+            }
+        }
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    match erase_contact_personal_data(&state, &object_id, &company_id, mode).await {
+        Ok(_) => (
+            Flash::success("Datos personales del contacto borrados."),
+            Redirect::to(&format!("/admin/contacts/{}/edit", id)),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/contacts/portal_link.html")]
+struct ContactPortalLinkTemplate {
+    contact_id: String,
+    contact_name: String,
+    link: String,
+}
+
+/// Creates a portal sign-in link for the contact and shows it once, for the
+/// admin to send by other means.
+pub async fn contacts_portal_link(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let contact = get_contact_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&contact.company_id, &active_company)?;
+
+    let token = create_portal_link(&state, &contact)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    render(ContactPortalLinkTemplate {
+        contact_id: id,
+        contact_name: contact.name,
+        link: format!("/portal/auth?token={token}"),
+    })
+}
+
+/// Drops the contact's pending portal link and open portal sessions.
+pub async fn contacts_portal_revoke(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    match get_contact_by_id(&state, &object_id).await {
+        Ok(Some(contact)) => {
+            if let Err(status) = ensure_same_company(&contact.company_id, &company_id) {
+                return status.into_response();
+            }
+        }
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    match revoke_portal_access(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Acceso al portal revocado."),
+            Redirect::to(&format!("/admin/contacts/{}/edit", id)),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
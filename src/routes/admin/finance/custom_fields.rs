@@ -0,0 +1,555 @@
+// Custom fields: per-company fields added to transactions and contacts. The
+// definitions are managed here; the forms of each entity render and submit
+// the values as `cf_<key>` inputs.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{Form, FromRequest, Path, Request, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use mongodb::bson::{Bson, Document, oid::ObjectId};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::{CustomFieldDefinition, CustomFieldEntity, CustomFieldType},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, CUSTOM_FIELD_PARAM_PREFIX, create_custom_field, custom_field_display,
+        delete_custom_field, get_custom_field_by_id, list_custom_fields,
+    },
+};
+
+use super::helpers::*;
+
+/// Custom field definitions.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/custom_fields",
+            get(custom_fields_index).post(custom_fields_create),
+        )
+        .route(
+            "/admin/custom_fields/{id}/delete",
+            post(custom_fields_delete),
+        )
+        .route(
+            "/api/admin/custom_fields",
+            get(custom_fields_data_api).post(custom_fields_create_api),
+        )
+        .route(
+            "/api/admin/custom_fields/{id}/delete",
+            post(custom_field_delete_api),
+        )
+}
+
+/// A URL-encoded form plus its `cf_<key>` inputs, keyed by field key.
+pub struct CustomFieldsForm<T> {
+    pub form: T,
+    pub custom: HashMap<String, String>,
+}
+
+impl<T, S> FromRequest<S> for CustomFieldsForm<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let custom = form_urlencoded::parse(&bytes)
+            .filter_map(|(name, value)| {
+                name.strip_prefix(CUSTOM_FIELD_PARAM_PREFIX)
+                    .map(|key| (key.to_string(), value.into_owned()))
+            })
+            .collect();
+        let Form(form) =
+            Form::<T>::from_request(Request::from_parts(parts, Body::from(bytes)), state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        Ok(Self { form, custom })
+    }
+}
+
+/// One custom field input of an entity form.
+pub struct CustomFieldInput {
+    pub key: String,
+    pub label: String,
+    /// HTML input type: `text`, `number`, `date` or `checkbox`.
+    pub input_type: &'static str,
+    pub value: String,
+    pub checked: bool,
+    pub required: bool,
+}
+
+fn custom_field_input(
+    field: &CustomFieldDefinition,
+    value: String,
+    checked: bool,
+) -> CustomFieldInput {
+    CustomFieldInput {
+        key: field.key.clone(),
+        label: field.label.clone(),
+        input_type: match field.field_type {
+            CustomFieldType::Text => "text",
+            CustomFieldType::Number => "number",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Boolean => "checkbox",
+        },
+        value,
+        checked,
+        required: field.required && field.field_type != CustomFieldType::Boolean,
+    }
+}
+
+/// Inputs filled with the values stored on a document.
+pub(super) fn custom_field_inputs(
+    fields: &[CustomFieldDefinition],
+    values: &Document,
+) -> Vec<CustomFieldInput> {
+    fields
+        .iter()
+        .map(|field| {
+            let value = match values.get(&field.key) {
+                Some(Bson::Double(number)) => number.to_string(),
+                other => custom_field_display(field, other),
+            };
+            let checked = matches!(values.get(&field.key), Some(Bson::Boolean(true)));
+            custom_field_input(field, value, checked)
+        })
+        .collect()
+}
+
+/// Inputs filled with what the user submitted, to re-render a form.
+pub(super) fn custom_field_inputs_from_raw(
+    fields: &[CustomFieldDefinition],
+    raw: &HashMap<String, String>,
+) -> Vec<CustomFieldInput> {
+    fields
+        .iter()
+        .map(|field| {
+            let value = raw.get(&field.key).cloned().unwrap_or_default();
+            let checked = !value.is_empty() && value != "false";
+            custom_field_input(field, value, checked)
+        })
+        .collect()
+}
+
+/// Filter inputs for a listing, filled from its `cf_<key>` query parameters.
+pub(super) fn custom_field_filters(
+    fields: &[CustomFieldDefinition],
+    params: &HashMap<String, String>,
+) -> Vec<CustomFieldInput> {
+    fields
+        .iter()
+        .map(|field| {
+            let value = params
+                .get(&format!("{CUSTOM_FIELD_PARAM_PREFIX}{}", field.key))
+                .cloned()
+                .unwrap_or_default();
+            let mut input = custom_field_input(field, value, false);
+            input.required = false;
+            input
+        })
+        .collect()
+}
+
+/// Definitions of the active company for an entity, or the status to answer with.
+pub(super) async fn entity_custom_fields(
+    state: &AppState,
+    company_id: &ObjectId,
+    entity: CustomFieldEntity,
+) -> Result<Vec<CustomFieldDefinition>, StatusCode> {
+    list_custom_fields(state, company_id, Some(entity))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// JSON payload values as the strings the forms would submit.
+pub(super) fn raw_custom_values(
+    values: Option<HashMap<String, serde_json::Value>>,
+) -> HashMap<String, String> {
+    values
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| {
+            let raw = match value {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(text) => text,
+                other => other.to_string(),
+            };
+            (key, raw)
+        })
+        .collect()
+}
+
+/// Stored values as JSON; dates as `YYYY-MM-DD`.
+pub(super) fn custom_fields_json(values: &Document) -> serde_json::Value {
+    let map = values
+        .iter()
+        .map(|(key, value)| {
+            let json = match value {
+                Bson::String(text) => serde_json::Value::from(text.as_str()),
+                Bson::Double(number) => serde_json::Value::from(*number),
+                Bson::Boolean(flag) => serde_json::Value::from(*flag),
+                Bson::DateTime(date) => {
+                    serde_json::Value::from(date.to_chrono().format("%Y-%m-%d").to_string())
+                }
+                other => other.clone().into_relaxed_extjson(),
+            };
+            (key.clone(), json)
+        })
+        .collect();
+    serde_json::Value::Object(map)
+}
+
+fn parse_entity(value: &str) -> Result<CustomFieldEntity, String> {
+    match value {
+        "transaction" => Ok(CustomFieldEntity::Transaction),
+        "contact" => Ok(CustomFieldEntity::Contact),
+        _ => Err("Entidad inválida".into()),
+    }
+}
+
+fn parse_field_type(value: &str) -> Result<CustomFieldType, String> {
+    match value {
+        "text" => Ok(CustomFieldType::Text),
+        "number" => Ok(CustomFieldType::Number),
+        "date" => Ok(CustomFieldType::Date),
+        "boolean" => Ok(CustomFieldType::Boolean),
+        _ => Err("Tipo de campo inválido".into()),
+    }
+}
+
+fn entity_options(selected: &str) -> Vec<SimpleOption> {
+    [CustomFieldEntity::Transaction, CustomFieldEntity::Contact]
+        .into_iter()
+        .map(|entity| SimpleOption {
+            value: entity.as_str().into(),
+            label: entity.label().into(),
+            selected: entity.as_str() == selected,
+        })
+        .collect()
+}
+
+fn field_type_options(selected: &str) -> Vec<SimpleOption> {
+    [
+        CustomFieldType::Text,
+        CustomFieldType::Number,
+        CustomFieldType::Date,
+        CustomFieldType::Boolean,
+    ]
+    .into_iter()
+    .map(|field_type| SimpleOption {
+        value: field_type.as_str().into(),
+        label: field_type.label().into(),
+        selected: field_type.as_str() == selected,
+    })
+    .collect()
+}
+
+#[derive(Template)]
+#[template(path = "admin/custom_fields/index.html")]
+struct CustomFieldsIndexTemplate {
+    fields: Vec<CustomFieldRow>,
+    label: String,
+    entity_options: Vec<SimpleOption>,
+    type_options: Vec<SimpleOption>,
+    required: bool,
+    errors: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CustomFieldRow {
+    pub id: String,
+    pub entity: String,
+    pub entity_label: String,
+    pub key: String,
+    pub label: String,
+    pub field_type: String,
+    pub field_type_label: String,
+    pub required: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CustomFieldFormData {
+    label: String,
+    entity: String,
+    field_type: String,
+    #[serde(default)]
+    required: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CustomFieldPayload {
+    pub label: String,
+    /// `transaction` or `contact`.
+    pub entity: String,
+    /// `text`, `number`, `date` or `boolean`.
+    pub field_type: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn custom_field_rows(fields: Vec<CustomFieldDefinition>) -> Vec<CustomFieldRow> {
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            field.id.map(|id| CustomFieldRow {
+                id: id.to_hex(),
+                entity: field.entity.as_str().into(),
+                entity_label: field.entity.label().into(),
+                key: field.key,
+                label: field.label,
+                field_type: field.field_type.as_str().into(),
+                field_type_label: field.field_type.label().into(),
+                required: field.required,
+            })
+        })
+        .collect()
+}
+
+async fn company_custom_field_rows(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<CustomFieldRow>, StatusCode> {
+    list_custom_fields(state, company_id, None)
+        .await
+        .map(custom_field_rows)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Definition of the active company, or the status to answer with.
+async fn load_company_custom_field(
+    state: &AppState,
+    id: &str,
+    company_id: &ObjectId,
+) -> Result<CustomFieldDefinition, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let field = get_custom_field_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&field.company_id, company_id)?;
+    Ok(field)
+}
+
+pub async fn custom_fields_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let fields = company_custom_field_rows(&state, &company_id).await?;
+
+    render(CustomFieldsIndexTemplate {
+        fields,
+        label: String::new(),
+        entity_options: entity_options("transaction"),
+        type_options: field_type_options("text"),
+        required: false,
+        errors: None,
+    })
+}
+
+pub async fn custom_fields_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<CustomFieldFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let required = form.required.is_some();
+    let parsed = parse_entity(&form.entity)
+        .and_then(|entity| parse_field_type(&form.field_type).map(|t| (entity, t)));
+    let result = match parsed {
+        Ok((entity, field_type)) => create_custom_field(
+            &state,
+            &company_id,
+            entity,
+            &form.label,
+            field_type,
+            required,
+        )
+        .await
+        .map_err(|err| err.to_string()),
+        Err(message) => Err(message),
+    };
+    match result {
+        Ok(_) => (
+            Flash::success("Campo creado."),
+            Redirect::to("/admin/custom_fields"),
+        )
+            .into_response(),
+        Err(message) => {
+            let fields = match company_custom_field_rows(&state, &company_id).await {
+                Ok(fields) => fields,
+                Err(status) => return status.into_response(),
+            };
+            render(CustomFieldsIndexTemplate {
+                fields,
+                label: form.label,
+                entity_options: entity_options(&form.entity),
+                type_options: field_type_options(&form.field_type),
+                required,
+                errors: Some(message),
+            })
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response())
+        }
+    }
+}
+
+pub async fn custom_fields_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let field = match load_company_custom_field(&state, &id, &company_id).await {
+        Ok(field) => field,
+        Err(status) => return status.into_response(),
+    };
+    match delete_custom_field(&state, &field).await {
+        Ok(_) => (
+            Flash::success("Campo eliminado."),
+            Redirect::to("/admin/custom_fields"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el campo."),
+            Redirect::to("/admin/custom_fields"),
+        )
+            .into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/custom_fields",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Custom field definitions of the active company"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn custom_fields_data_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CustomFieldRow>>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    company_custom_field_rows(&state, &company_id)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/custom_fields",
+    tag = "finance",
+    request_body = CustomFieldPayload,
+    responses(
+        (status = 201, description = "Custom field created; returns its id and key"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid input or duplicated name")
+    ),
+    security(("session" = []))
+)]
+pub async fn custom_fields_create_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CustomFieldPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response()
+    };
+    let entity = match parse_entity(&payload.entity) {
+        Ok(entity) => entity,
+        Err(message) => return bad_request(message),
+    };
+    let field_type = match parse_field_type(&payload.field_type) {
+        Ok(field_type) => field_type,
+        Err(message) => return bad_request(message),
+    };
+    match create_custom_field(
+        &state,
+        &company_id,
+        entity,
+        &payload.label,
+        field_type,
+        payload.required,
+    )
+    .await
+    {
+        Ok(id) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({
+                "id": id.to_hex(),
+                "key": crate::state::custom_field_key(&payload.label),
+            })),
+        )
+            .into_response(),
+        Err(err) => bad_request(err.to_string()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/custom_fields/{id}/delete",
+    tag = "finance",
+    params(("id" = String, Path, description = "Record id")),
+    responses(
+        (status = 200, description = "Custom field deleted along with its stored values"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn custom_field_delete_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let field = match load_company_custom_field(&state, &id, &company_id).await {
+        Ok(field) => field,
+        Err(status) => return status.into_response(),
+    };
+    match delete_custom_field(&state, &field).await {
+        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
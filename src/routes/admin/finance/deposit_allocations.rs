@@ -0,0 +1,391 @@
+// Deposit splits of planned income, set from the edit page of the entry or
+// through the API. See `state::deposit_allocations`.
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::post,
+};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flash::Flash,
+    models::{DepositAllocation, FlowType, PlannedEntry},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, DepositProgress, MAX_DEPOSIT_ALLOCATIONS, deposit_progress,
+        get_planned_entry_by_id, list_accounts, set_deposit_allocations,
+    },
+};
+
+use super::helpers::*;
+
+/// Deposit splits of planned income.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/planned_entries/{id}/deposits",
+            post(planned_entry_deposits_update),
+        )
+        .route(
+            "/api/admin/planned-entries/{id}/deposits",
+            post(planned_entry_deposits_api).get(planned_entry_deposits_data_api),
+        )
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DepositAllocationPayload {
+    pub account_id: String,
+    /// Percentage of the estimate; send it or `amount`, not both.
+    #[serde(default)]
+    pub percentage: Option<f64>,
+    #[serde(default)]
+    pub amount: Option<f64>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DepositAllocationsPayload {
+    /// Replaces the split; empty expects the whole income in the entry's
+    /// account again.
+    pub allocations: Vec<DepositAllocationPayload>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DepositAccountData {
+    pub account_id: String,
+    pub percentage: Option<f64>,
+    /// Fixed amount of the split.
+    pub amount: Option<f64>,
+    /// Share of the current estimate expected in the account.
+    pub expected: f64,
+    /// Confirmed income of the entry that landed in the account.
+    pub received: f64,
+    pub pending: f64,
+    /// The entry's own account, which keeps what the split leaves.
+    pub is_remainder: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DepositAllocationsData {
+    pub amount_estimated: f64,
+    pub accounts: Vec<DepositAccountData>,
+}
+
+fn deposit_data(entry: &PlannedEntry, progress: Vec<DepositProgress>) -> DepositAllocationsData {
+    DepositAllocationsData {
+        amount_estimated: entry.amount_estimated,
+        accounts: progress
+            .into_iter()
+            .map(|row| {
+                let allocation = entry
+                    .deposit_allocations
+                    .iter()
+                    .find(|allocation| allocation.account_id == row.account_id);
+                DepositAccountData {
+                    account_id: row.account_id.to_hex(),
+                    percentage: allocation.and_then(|allocation| allocation.percentage),
+                    amount: allocation.and_then(|allocation| allocation.amount),
+                    expected: row.expected,
+                    received: row.received,
+                    pending: row.pending(),
+                    is_remainder: allocation.is_none(),
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Account row of the split form.
+pub(super) struct DepositRow {
+    pub account_id: String,
+    pub name: String,
+    /// As typed: `30%` or `1500`; empty when the account is not in the split.
+    pub share: String,
+    pub expected: f64,
+    pub received: f64,
+}
+
+/// Split section of the edit page of an income entry.
+pub(super) struct DepositPanel {
+    pub action: String,
+    /// The entry's own account, which keeps what the split leaves.
+    pub remainder: DepositRow,
+    /// Every other active account of the company the user can see.
+    pub rows: Vec<DepositRow>,
+    pub max_accounts: usize,
+}
+
+fn share_value(allocation: &DepositAllocation) -> String {
+    match (allocation.percentage, allocation.amount) {
+        (Some(pct), _) => format!("{pct}%"),
+        (None, Some(amount)) => amount.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+/// The split section for `entry`; expenses get none.
+pub(super) async fn deposit_panel(
+    state: &AppState,
+    session_user: &SessionUser,
+    entry: &PlannedEntry,
+) -> Result<Option<DepositPanel>, StatusCode> {
+    let Some(id) = entry.id else {
+        return Ok(None);
+    };
+    if entry.flow_type != FlowType::Income {
+        return Ok(None);
+    }
+    let progress = deposit_progress(state, entry)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let accounts = list_accounts(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let access = session_user.account_access();
+    let row = |account_id: ObjectId, name: String| {
+        let progress = progress.iter().find(|row| row.account_id == account_id);
+        DepositRow {
+            account_id: account_id.to_hex(),
+            name,
+            share: entry
+                .deposit_allocations
+                .iter()
+                .find(|allocation| allocation.account_id == account_id)
+                .map(share_value)
+                .unwrap_or_default(),
+            expected: progress.map_or(0.0, |row| row.expected),
+            received: progress.map_or(0.0, |row| row.received),
+        }
+    };
+
+    let mut remainder = row(entry.account_expected_id, String::new());
+    let mut rows = Vec::new();
+    for account in accounts {
+        let Some(account_id) = account.id else {
+            continue;
+        };
+        if account.company_id != entry.company_id {
+            continue;
+        }
+        if account_id == entry.account_expected_id {
+            remainder.name = account.name;
+        } else if account.is_active && access.allows(&account_id) {
+            rows.push(row(account_id, account.name));
+        }
+    }
+    Ok(Some(DepositPanel {
+        action: format!("/admin/planned_entries/{}/deposits", id.to_hex()),
+        remainder,
+        rows,
+        max_accounts: MAX_DEPOSIT_ALLOCATIONS,
+    }))
+}
+
+/// `30%` is a percentage of the estimate, anything else a fixed amount.
+fn parse_share(account_id: ObjectId, value: &str) -> Result<DepositAllocation, String> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(pct) => Ok(DepositAllocation {
+            account_id,
+            percentage: Some(parse_f64_field(pct, "El porcentaje")?),
+            amount: None,
+        }),
+        None => Ok(DepositAllocation {
+            account_id,
+            percentage: None,
+            amount: Some(parse_f64_field(value, "El monto")?),
+        }),
+    }
+}
+
+/// `share_<account id>` fields of the form; blank ones are left out.
+fn parse_deposit_form(pairs: &[(String, String)]) -> Result<Vec<DepositAllocation>, String> {
+    pairs
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("share_")?, value)))
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(account, value)| parse_share(parse_object_id(account, "Cuenta")?, value))
+        .collect()
+}
+
+fn parse_deposit_payload(
+    payload: DepositAllocationsPayload,
+) -> Result<Vec<DepositAllocation>, String> {
+    payload
+        .allocations
+        .into_iter()
+        .map(|allocation| {
+            Ok(DepositAllocation {
+                account_id: parse_object_id(&allocation.account_id, "Cuenta")?,
+                percentage: allocation.percentage,
+                amount: allocation.amount,
+            })
+        })
+        .collect()
+}
+
+/// Entry of a split request, or the response refusing it.
+async fn deposit_target(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, ObjectId, PlannedEntry), Response> {
+    let company_id = require_admin_active(session_user).map_err(IntoResponse::into_response)?;
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST.into_response())?;
+    let entry = get_planned_entry_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+    ensure_same_company(&entry.company_id, &company_id).map_err(IntoResponse::into_response)?;
+    ensure_account_access(session_user, [&entry.account_expected_id])
+        .map_err(IntoResponse::into_response)?;
+    Ok((company_id, object_id, entry))
+}
+
+fn edit_page(id: &str) -> Redirect {
+    Redirect::to(&format!("/admin/planned_entries/{id}/edit"))
+}
+
+/// POST /admin/planned_entries/{id}/deposits — saves the split and returns to
+/// the edit page.
+pub async fn planned_entry_deposits_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> Response {
+    let (company_id, entry_id, _) = match deposit_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let allocations = match parse_deposit_form(&pairs) {
+        Ok(allocations) => allocations,
+        Err(message) => return (Flash::error(message), edit_page(&id)).into_response(),
+    };
+    if let Err(status) =
+        ensure_account_access(&session_user, allocations.iter().map(|a| &a.account_id))
+    {
+        return status.into_response();
+    }
+    match set_deposit_allocations(&state, &company_id, &entry_id, allocations).await {
+        Ok(()) => (
+            Flash::success("Reparto de depósitos guardado."),
+            edit_page(&id),
+        )
+            .into_response(),
+        Err(err) => (Flash::error(err.to_string()), edit_page(&id)).into_response(),
+    }
+}
+
+async fn deposits_response(state: &AppState, id: &ObjectId) -> Response {
+    let entry = match get_planned_entry_by_id(state, id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match deposit_progress(state, &entry).await {
+        Ok(progress) => Json(deposit_data(&entry, progress)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/planned-entries/{id}/deposits",
+    tag = "finance",
+    params(("id" = String, Path, description = "Planned entry")),
+    responses(
+        (status = 200, description = "Amount expected and received in each deposit account", body = DepositAllocationsData),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn planned_entry_deposits_data_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    match deposit_target(&state, &session_user, &id).await {
+        Ok((_, entry_id, _)) => deposits_response(&state, &entry_id).await,
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/planned-entries/{id}/deposits",
+    tag = "finance",
+    params(("id" = String, Path, description = "Planned income")),
+    request_body = DepositAllocationsPayload,
+    responses(
+        (status = 200, description = "The split saved, with each account's expected and received amount", body = DepositAllocationsData),
+        (status = 400, description = "Not an income, repeated account, or shares above the estimate"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn planned_entry_deposits_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<DepositAllocationsPayload>,
+) -> Response {
+    let (company_id, entry_id, _) = match deposit_target(&state, &session_user, &id).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response()
+    };
+    let allocations = match parse_deposit_payload(payload) {
+        Ok(allocations) => allocations,
+        Err(message) => return bad_request(message),
+    };
+    if let Err(status) =
+        ensure_account_access(&session_user, allocations.iter().map(|a| &a.account_id))
+    {
+        return status.into_response();
+    }
+    if let Err(err) = set_deposit_allocations(&state, &company_id, &entry_id, allocations).await {
+        return bad_request(err.to_string());
+    }
+    deposits_response(&state, &entry_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_shares_are_percentages_or_amounts() {
+        let (savings, payroll) = (ObjectId::new(), ObjectId::new());
+        let pairs = vec![
+            (format!("share_{}", savings.to_hex()), " 30% ".to_string()),
+            (format!("share_{}", payroll.to_hex()), "1500".to_string()),
+            (format!("share_{}", ObjectId::new().to_hex()), String::new()),
+            ("csrf".to_string(), "x".to_string()),
+        ];
+        let allocations = parse_deposit_form(&pairs).unwrap();
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[0].percentage, Some(30.0));
+        assert_eq!(allocations[1].amount, Some(1500.0));
+        assert_eq!(share_value(&allocations[0]), "30%");
+
+        let pairs = vec![(format!("share_{}", savings.to_hex()), "mucho".to_string())];
+        assert!(parse_deposit_form(&pairs).is_err());
+    }
+}
@@ -1,23 +1,27 @@
-use std::{str::FromStr, sync::Arc};
+use std::{cmp::Reverse, str::FromStr, sync::Arc};
 
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Path, State},
+    extract::{Form, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
-    models::Forecast,
+    flash::Flash,
+    models::{Forecast, ScenarioWeights},
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
-        AppState, create_forecast, delete_forecast, get_forecast_by_id, list_forecasts,
+        AppState, ForecastChartSeries, create_forecast, delete_forecast, forecast_chart,
+        generate_scenario_forecasts, get_forecast_by_id, list_forecasts, list_scenario_forecasts,
         update_forecast,
     },
 };
@@ -25,21 +29,87 @@ use crate::{
 use super::helpers::*;
 use super::options::user_options;
 
+/// Forecasts and their scenarios.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/forecasts",
+            get(forecasts_index).post(forecasts_create),
+        )
+        .route(
+            "/api/admin/forecasts",
+            get(forecasts_data_api).post(forecasts_create_api),
+        )
+        .route("/api/admin/forecasts/{id}", get(forecast_data_api))
+        .route(
+            "/api/admin/forecasts/{id}/update",
+            post(forecast_update_api),
+        )
+        .route(
+            "/api/admin/forecasts/{id}/delete",
+            post(forecast_delete_api),
+        )
+        .route(
+            "/api/admin/forecasts/generate",
+            post(forecasts_generate_api),
+        )
+        .route("/admin/forecasts/generate", post(forecasts_generate))
+        .route(
+            "/admin/forecasts/scenarios/{group_id}",
+            get(forecasts_scenarios),
+        )
+        .route("/admin/forecasts/new", get(forecasts_new))
+        .route("/admin/forecasts/{id}/edit", get(forecasts_edit))
+        .route("/admin/forecasts/{id}/chart", get(forecast_chart_data))
+        .route("/admin/forecasts/{id}/update", post(forecasts_update))
+        .route("/admin/forecasts/{id}/delete", post(forecasts_delete))
+        .route("/admin/forecasts/{id}/clone", post(forecasts_clone))
+}
+
 #[derive(Template)]
 #[template(path = "admin/forecasts/index.html")]
 struct ForecastsIndexTemplate {
     forecasts: Vec<ForecastRow>,
+    generate: ScenarioGenerateView,
+    errors: Option<String>,
+}
+
+/// Values of the "generate scenarios" form on the index page. Weights are
+/// shown as percentages of the estimated income.
+struct ScenarioGenerateView {
+    start_date: String,
+    end_date: String,
+    initial_balance: String,
+    best_pct: String,
+    expected_pct: String,
+    worst_pct: String,
+}
+
+impl Default for ScenarioGenerateView {
+    fn default() -> Self {
+        let weights = ScenarioWeights::default();
+        Self {
+            start_date: String::new(),
+            end_date: String::new(),
+            initial_balance: String::new(),
+            best_pct: weight_to_pct(weights.best),
+            expected_pct: weight_to_pct(weights.expected),
+            worst_pct: weight_to_pct(weights.worst),
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct ForecastRow {
     pub id: String,
+    pub reference: Option<String>,
     pub company: String,
     pub currency: String,
     pub projected_net: f64,
     pub start_date: String,
     pub end_date: String,
     pub scenario_name: Option<String>,
+    pub scenario_group_id: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -55,17 +125,24 @@ pub struct ForecastDetail {
     pub projected_income_total: f64,
     pub projected_expense_total: f64,
     pub projected_net: f64,
+    /// Projected income at the probability of each income plan; only on
+    /// generated forecasts.
+    pub weighted_income_total: Option<f64>,
     pub initial_balance: Option<f64>,
     pub final_balance: Option<f64>,
     pub details: Option<String>,
     pub scenario_name: Option<String>,
+    pub scenario_group_id: Option<String>,
+    pub reference: Option<String>,
     pub notes: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct ForecastPayload {
     pub generated_at: String,
-    pub generated_by_user_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub generated_by_user_id: Option<ObjectId>,
     pub start_date: String,
     pub end_date: String,
     pub currency: String,
@@ -100,6 +177,52 @@ struct ForecastFormTemplate {
     users: Vec<SimpleOption>,
     is_edit: bool,
     errors: Option<String>,
+    /// Set when the form is prefilled from another forecast.
+    clone_notice: Option<String>,
+    /// Projected vs actual chart, on the edit page only.
+    chart: Option<ForecastChartView>,
+}
+
+/// Where the edit page reads its chart from, and the other forecasts of the
+/// company it can be compared with.
+struct ForecastChartView {
+    url: String,
+    compare: Vec<SimpleOption>,
+}
+
+#[derive(Deserialize)]
+pub struct ForecastChartQuery {
+    /// Another forecast of the company drawn over this one.
+    #[serde(default)]
+    compare: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ForecastChartPointData {
+    pub period: String,
+    pub start: String,
+    pub end: String,
+    pub projected_income: f64,
+    pub projected_expense: f64,
+    pub projected_net: f64,
+    /// `null` for periods that have not started.
+    pub actual_income: Option<f64>,
+    pub actual_expense: Option<f64>,
+    pub actual_net: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct ForecastChartSeriesData {
+    pub id: String,
+    pub label: String,
+    pub currency: String,
+    pub points: Vec<ForecastChartPointData>,
+}
+
+#[derive(Serialize)]
+pub struct ForecastChartData {
+    pub forecast: ForecastChartSeriesData,
+    pub compare: Option<ForecastChartSeriesData>,
 }
 
 #[derive(Deserialize)]
@@ -131,29 +254,51 @@ pub async fn forecasts_index(
     State(state): State<Arc<AppState>>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
+    render_forecasts_index(
+        &state,
+        &session_user,
+        &active_company,
+        ScenarioGenerateView::default(),
+        None,
+    )
+    .await
+}
 
-    let forecasts = list_forecasts(&state)
+async fn render_forecasts_index(
+    state: &AppState,
+    session_user: &SessionUser,
+    active_company: &ObjectId,
+    generate: ScenarioGenerateView,
+    errors: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let forecasts = list_forecasts(state)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let active_name = session_user.user().company_name.clone();
 
     let rows = forecasts
         .into_iter()
-        .filter(|f| f.company_id == active_company)
+        .filter(|f| f.company_id == *active_company)
         .filter_map(|f| {
             f.id.map(|id| ForecastRow {
                 id: id.to_hex(),
+                reference: f.reference,
                 company: active_name.clone(),
                 currency: f.currency,
                 projected_net: f.projected_net,
                 start_date: datetime_to_string(&f.start_date),
                 end_date: datetime_to_string(&f.end_date),
                 scenario_name: f.scenario_name,
+                scenario_group_id: f.scenario_group_id.map(|id| id.to_hex()),
             })
         })
         .collect();
 
-    render(ForecastsIndexTemplate { forecasts: rows })
+    render(ForecastsIndexTemplate {
+        forecasts: rows,
+        generate,
+        errors,
+    })
 }
 
 #[utoipa::path(
@@ -183,12 +328,14 @@ pub async fn forecasts_data_api(
         .filter_map(|forecast| {
             forecast.id.map(|id| ForecastRow {
                 id: id.to_hex(),
+                reference: forecast.reference,
                 company: active_name.clone(),
                 currency: forecast.currency,
                 projected_net: forecast.projected_net,
                 start_date: datetime_to_string(&forecast.start_date),
                 end_date: datetime_to_string(&forecast.end_date),
                 scenario_name: forecast.scenario_name,
+                scenario_group_id: forecast.scenario_group_id.map(|id| id.to_hex()),
             })
         })
         .collect();
@@ -390,6 +537,297 @@ pub async fn forecast_delete_api(
     }
 }
 
+// ── Scenario generation ───────────────────────────────────────────────────
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ScenarioGeneratePayload {
+    pub start_date: String,
+    pub end_date: String,
+    pub initial_balance: Option<f64>,
+    /// Fallback weights (fractions, 1.0 = estimate) for income plans without
+    /// their own. Each defaults to 1.1 / 1.0 / 0.7.
+    pub best_weight: Option<f64>,
+    pub expected_weight: Option<f64>,
+    pub worst_weight: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct ScenarioGenerateForm {
+    pub start_date: String,
+    pub end_date: String,
+    pub initial_balance: Option<String>,
+    pub best_pct: String,
+    pub expected_pct: String,
+    pub worst_pct: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/forecasts/scenarios.html")]
+struct ScenariosTemplate {
+    start_date: String,
+    end_date: String,
+    currency: String,
+    scenarios: Vec<ScenarioColumn>,
+    /// Net of each scenario per fiscal period, in the order of `scenarios`.
+    periods: Vec<ScenarioPeriodRow>,
+}
+
+struct ScenarioPeriodRow {
+    period: String,
+    nets: Vec<f64>,
+}
+
+/// `(period, net)` of each period in a generated forecast's details.
+fn period_nets(details: Option<&str>) -> Vec<(String, f64)> {
+    let Some(details) = details.and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+    else {
+        return Vec::new();
+    };
+    details["periods"]
+        .as_array()
+        .map(|periods| {
+            periods
+                .iter()
+                .filter_map(|p| Some((p["period"].as_str()?.to_string(), p["net"].as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn scenario_period_rows(forecasts: &[Forecast]) -> Vec<ScenarioPeriodRow> {
+    let nets: Vec<Vec<(String, f64)>> = forecasts
+        .iter()
+        .map(|f| period_nets(f.details.as_deref()))
+        .collect();
+    let Some(first) = nets.first() else {
+        return Vec::new();
+    };
+    first
+        .iter()
+        .map(|(period, _)| ScenarioPeriodRow {
+            period: period.clone(),
+            nets: nets
+                .iter()
+                .map(|scenario| {
+                    scenario
+                        .iter()
+                        .find(|(key, _)| key == period)
+                        .map_or(0.0, |(_, net)| *net)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+struct ScenarioColumn {
+    id: String,
+    label: &'static str,
+    income: f64,
+    expense: f64,
+    net: f64,
+    /// Income and net with each income plan's probability applied.
+    weighted_income: Option<f64>,
+    weighted_net: Option<f64>,
+    final_balance: Option<f64>,
+}
+
+fn weight_to_pct(weight: f64) -> String {
+    format!("{}", (weight * 100.0).round())
+}
+
+fn scenario_label(name: Option<&str>) -> &'static str {
+    match name {
+        Some("best") => "Optimista",
+        Some("expected") => "Esperado",
+        Some("worst") => "Pesimista",
+        _ => "Escenario",
+    }
+}
+
+type ParsedScenarioForm = (
+    mongodb::bson::DateTime,
+    mongodb::bson::DateTime,
+    Option<f64>,
+    ScenarioWeights,
+);
+
+fn parse_generate_form(form: &ScenarioGenerateForm) -> Result<ParsedScenarioForm, String> {
+    let start_date = parse_datetime_field(&form.start_date, "Inicio")?;
+    let end_date = parse_datetime_field(&form.end_date, "Fin")?;
+    if end_date < start_date {
+        return Err("La fecha fin no puede ser anterior al inicio".to_string());
+    }
+    let initial_balance = parse_optional_f64_field(form.initial_balance.clone(), "Saldo inicial")?;
+    let weights = parse_scenario_weight_pcts(&form.best_pct, &form.expected_pct, &form.worst_pct)?;
+    Ok((start_date, end_date, initial_balance, weights))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/forecasts/generate",
+    tag = "finance",
+    request_body = ScenarioGeneratePayload,
+    responses(
+        (status = 201, description = "Best, expected and worst forecasts created"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid input")
+    ),
+    security(("session" = []))
+)]
+pub async fn forecasts_generate_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ScenarioGeneratePayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let start_date = match parse_datetime_field(&payload.start_date, "start_date") {
+        Ok(dt) => dt,
+        Err(message) => return json_bad_request(&message),
+    };
+    let end_date = match parse_datetime_field(&payload.end_date, "end_date") {
+        Ok(dt) => dt,
+        Err(message) => return json_bad_request(&message),
+    };
+    if end_date < start_date {
+        return json_bad_request("end_date must not precede start_date");
+    }
+    let defaults = ScenarioWeights::default();
+    let weights = match validate_scenario_weights(ScenarioWeights {
+        best: payload.best_weight.unwrap_or(defaults.best),
+        expected: payload.expected_weight.unwrap_or(defaults.expected),
+        worst: payload.worst_weight.unwrap_or(defaults.worst),
+    }) {
+        Ok(weights) => weights,
+        Err(message) => return json_bad_request(&message),
+    };
+
+    let group_id = match generate_scenario_forecasts(
+        &state,
+        &company_id,
+        Some(*session_user.user_id()),
+        start_date,
+        end_date,
+        payload.initial_balance,
+        weights,
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let forecasts = match list_scenario_forecasts(&state, &company_id, &group_id).await {
+        Ok(items) => items,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let company = session_user.user().company_name.clone();
+    let forecasts: Vec<ForecastDetail> = forecasts
+        .into_iter()
+        .filter_map(|f| {
+            f.id.map(|id| forecast_detail(id.to_hex(), f, company.clone()))
+        })
+        .collect();
+
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "group_id": group_id.to_hex(),
+            "forecasts": forecasts,
+        })),
+    )
+        .into_response()
+}
+
+pub async fn forecasts_generate(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ScenarioGenerateForm>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let (start_date, end_date, initial_balance, weights) = match parse_generate_form(&form) {
+        Ok(values) => values,
+        Err(message) => {
+            let generate = ScenarioGenerateView {
+                start_date: form.start_date,
+                end_date: form.end_date,
+                initial_balance: form.initial_balance.unwrap_or_default(),
+                best_pct: form.best_pct,
+                expected_pct: form.expected_pct,
+                worst_pct: form.worst_pct,
+            };
+            return render_forecasts_index(
+                &state,
+                &session_user,
+                &company_id,
+                generate,
+                Some(message),
+            )
+            .await
+            .into_response();
+        }
+    };
+
+    match generate_scenario_forecasts(
+        &state,
+        &company_id,
+        Some(*session_user.user_id()),
+        start_date,
+        end_date,
+        initial_balance,
+        weights,
+    )
+    .await
+    {
+        Ok(group_id) => Redirect::to(&format!("/admin/forecasts/scenarios/{}", group_id.to_hex()))
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn forecasts_scenarios(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let group_id = ObjectId::from_str(&group_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let forecasts = list_scenario_forecasts(&state, &active_company, &group_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let first = forecasts.first().ok_or(StatusCode::NOT_FOUND)?;
+
+    render(ScenariosTemplate {
+        start_date: datetime_to_string(&first.start_date),
+        end_date: datetime_to_string(&first.end_date),
+        currency: first.currency.clone(),
+        scenarios: forecasts
+            .iter()
+            .filter_map(|f| {
+                f.id.map(|id| ScenarioColumn {
+                    id: id.to_hex(),
+                    label: scenario_label(f.scenario_name.as_deref()),
+                    income: f.projected_income_total,
+                    expense: f.projected_expense_total,
+                    net: f.projected_net,
+                    weighted_income: f.weighted_income_total,
+                    weighted_net: f
+                        .weighted_income_total
+                        .map(|income| income - f.projected_expense_total),
+                    final_balance: f.final_balance,
+                })
+            })
+            .collect(),
+        periods: scenario_period_rows(&forecasts),
+    })
+}
+
 struct ParsedForecastPayload {
     generated_at: mongodb::bson::DateTime,
     generated_by_user_id: Option<ObjectId>,
@@ -421,22 +859,15 @@ async fn parse_forecast_payload(
     if currency.is_empty() {
         return Err(json_bad_request("currency is required"));
     }
-    let generated_by_user_id = match clean_opt(payload.generated_by_user_id) {
-        Some(value) => match ObjectId::from_str(&value) {
-            Ok(id) => {
-                if let Err(status) = validate_user_in_company(state, &id, company_id).await {
-                    return Err(status.into_response());
-                }
-                Some(id)
-            }
-            Err(_) => return Err(json_bad_request("generated_by_user_id is invalid")),
-        },
-        None => None,
-    };
+    if let Some(id) = payload.generated_by_user_id.as_ref()
+        && let Err(status) = validate_user_in_company(state, id, company_id).await
+    {
+        return Err(status.into_response());
+    }
 
     Ok(ParsedForecastPayload {
         generated_at,
-        generated_by_user_id,
+        generated_by_user_id: payload.generated_by_user_id,
         start_date,
         end_date,
         currency,
@@ -464,10 +895,13 @@ fn forecast_detail(id: String, forecast: Forecast, company: String) -> ForecastD
         projected_income_total: forecast.projected_income_total,
         projected_expense_total: forecast.projected_expense_total,
         projected_net: forecast.projected_net,
+        weighted_income_total: forecast.weighted_income_total,
         initial_balance: forecast.initial_balance,
         final_balance: forecast.final_balance,
         details: forecast.details,
         scenario_name: forecast.scenario_name,
+        scenario_group_id: forecast.scenario_group_id.map(|id| id.to_hex()),
+        reference: forecast.reference,
         notes: forecast.notes,
     }
 }
@@ -508,6 +942,8 @@ pub async fn forecasts_new(
         users,
         is_edit: false,
         errors: None,
+        clone_notice: None,
+        chart: None,
     })
 }
 
@@ -600,7 +1036,11 @@ pub async fn forecasts_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/forecasts").into_response(),
+        Ok(_) => (
+            Flash::success("Pronóstico generado."),
+            Redirect::to("/admin/forecasts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -655,9 +1095,185 @@ pub async fn forecasts_edit(
         users,
         is_edit: true,
         errors: None,
+        clone_notice: None,
+        chart: Some(ForecastChartView {
+            url: format!("/admin/forecasts/{id}/chart"),
+            compare: compare_options(&state, &active_company, &object_id).await?,
+        }),
     })
 }
 
+/// POST /admin/forecasts/{id}/clone — the new-forecast form prefilled with
+/// the forecast's figures for the period right after it.
+pub async fn forecasts_clone(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let forecast = get_forecast_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&forecast.company_id, &active_company)?;
+
+    let companies = company_options(&state, &active_company).await?;
+    let users = user_options(&state, Some(session_user.user_id()), &active_company).await?;
+
+    let (start_date, end_date) = next_period(forecast.start_date, forecast.end_date);
+    let source = forecast
+        .reference
+        .clone()
+        .unwrap_or_else(|| datetime_to_string(&forecast.start_date));
+    render(ForecastFormTemplate {
+        action: "/admin/forecasts".into(),
+        currency: forecast.currency,
+        projected_income_total: forecast.projected_income_total.to_string(),
+        projected_expense_total: forecast.projected_expense_total.to_string(),
+        projected_net: forecast.projected_net.to_string(),
+        // The new period starts where the copied one was expected to end.
+        initial_balance: forecast
+            .final_balance
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+        final_balance: forecast
+            .final_balance
+            .map(|v| (v + forecast.projected_net).to_string())
+            .unwrap_or_default(),
+        generated_at: datetime_to_string(&DateTime::now()),
+        start_date: datetime_to_string(&start_date),
+        end_date: datetime_to_string(&end_date),
+        generated_by_user_id: session_user.user_id().to_hex(),
+        details: forecast.details.unwrap_or_default(),
+        scenario_name: forecast.scenario_name.unwrap_or_default(),
+        notes: forecast.notes.unwrap_or_default(),
+        companies,
+        users,
+        is_edit: false,
+        errors: None,
+        clone_notice: Some(format!(
+            "Copia del pronóstico {source} para el periodo siguiente. Revisa los montos antes de crearlo."
+        )),
+        chart: None,
+    })
+}
+
+/// How a forecast is named in the chart and its compare list.
+fn forecast_label(forecast: &Forecast) -> String {
+    if let Some(reference) = forecast.reference.as_deref().filter(|r| !r.is_empty()) {
+        return reference.to_string();
+    }
+    let name = match forecast.scenario_name.as_deref() {
+        Some(name) => scenario_label(Some(name)),
+        None => "Pronóstico",
+    };
+    format!(
+        "{name} {} – {}",
+        forecast.start_date.to_chrono().format("%Y-%m-%d"),
+        forecast.end_date.to_chrono().format("%Y-%m-%d")
+    )
+}
+
+/// The other forecasts of the company, newest first, for the compare select.
+async fn compare_options(
+    state: &AppState,
+    company_id: &ObjectId,
+    forecast_id: &ObjectId,
+) -> Result<Vec<SimpleOption>, StatusCode> {
+    let mut forecasts: Vec<Forecast> = list_forecasts(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|f| f.company_id == *company_id && f.id.as_ref() != Some(forecast_id))
+        .collect();
+    forecasts.sort_by_key(|f| Reverse(f.start_date));
+    Ok(forecasts
+        .iter()
+        .filter_map(|f| {
+            f.id.map(|id| SimpleOption {
+                value: id.to_hex(),
+                label: forecast_label(f),
+                selected: false,
+            })
+        })
+        .collect())
+}
+
+fn chart_series_data(forecast: &Forecast, series: ForecastChartSeries) -> ForecastChartSeriesData {
+    ForecastChartSeriesData {
+        id: series.forecast_id.to_hex(),
+        label: forecast_label(forecast),
+        currency: series.currency,
+        points: series
+            .points
+            .into_iter()
+            .map(|point| ForecastChartPointData {
+                period: point.period,
+                start: datetime_to_string(&point.start),
+                end: datetime_to_string(&point.end),
+                projected_income: point.projected_income,
+                projected_expense: point.projected_expense,
+                projected_net: point.projected_net,
+                actual_income: point.actual_income,
+                actual_expense: point.actual_expense,
+                actual_net: point.actual_net,
+            })
+            .collect(),
+    }
+}
+
+/// A forecast of the active company with its chart series.
+async fn company_forecast_series(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &str,
+) -> Result<ForecastChartSeriesData, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let forecast = get_forecast_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&forecast.company_id, company_id)?;
+    let series = forecast_chart(state, company_id, &forecast)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(chart_series_data(&forecast, series))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/forecasts/{id}/chart",
+    tag = "finance",
+    params(
+        ("id" = String, Path, description = "Record id"),
+        ("compare" = Option<String>, Query, description = "Another forecast of the company to overlay")
+    ),
+    responses(
+        (status = 200, description = "Projected vs actual income, expense and net per fiscal period, plus the compared forecast's series"),
+        (status = 400, description = "Invalid id"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Either forecast belongs to another company"),
+        (status = 404, description = "Forecast not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn forecast_chart_data(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ForecastChartQuery>,
+) -> Result<Json<ForecastChartData>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let forecast = company_forecast_series(&state, &active_company, &id).await?;
+    let compare = match query.compare.as_deref().filter(|c| !c.is_empty()) {
+        Some(compare) => Some(company_forecast_series(&state, &active_company, compare).await?),
+        None => None,
+    };
+    Ok(Json(ForecastChartData { forecast, compare }))
+}
+
 pub async fn forecasts_update(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
@@ -762,7 +1378,11 @@ pub async fn forecasts_update(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/forecasts").into_response(),
+        Ok(_) => (
+            Flash::success("Pronóstico actualizado."),
+            Redirect::to("/admin/forecasts"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -791,7 +1411,15 @@ pub async fn forecasts_delete(
     }
 
     match delete_forecast(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/forecasts").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Pronóstico eliminado."),
+            Redirect::to("/admin/forecasts"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el pronóstico."),
+            Redirect::to("/admin/forecasts"),
+        )
+            .into_response(),
     }
 }
@@ -2,22 +2,27 @@ use std::{collections::HashMap, str::FromStr};
 
 use askama::Template;
 use axum::{http::StatusCode, response::Html};
+use chrono::{Datelike, Duration, Months};
 use mongodb::bson::{DateTime, oid::ObjectId};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
-    models::{AccountType, ContactType, FlowType, PlannedStatus, TransactionType},
+    models::{
+        AccountType, ContactType, FlowType, PlannedStatus, RequiredFieldPolicy, ScenarioWeights,
+        Transaction, TransactionType,
+    },
     session::SessionUser,
     state::{
-        AppState, get_account_by_id, get_category_by_id, get_company_by_id, get_contact_by_id,
-        get_planned_entry_by_id, get_recurring_plan_by_id, get_user_by_id,
+        AppState, company_required_fields, get_account_by_id, get_category_by_id,
+        get_company_by_id, get_contact_by_id, get_planned_entry_by_id, get_recurring_plan_by_id,
+        get_user_by_id,
     },
 };
 
 pub fn require_admin_active(session_user: &SessionUser) -> Result<ObjectId, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
     Ok(session_user.active_company_id().clone())
@@ -38,6 +43,33 @@ pub fn ensure_same_company(
     }
 }
 
+/// Records on accounts outside the user's access list are refused like those
+/// of other companies.
+pub fn ensure_account_access<'a>(
+    session_user: &SessionUser,
+    account_ids: impl IntoIterator<Item = &'a ObjectId>,
+) -> Result<(), StatusCode> {
+    session_user
+        .account_access()
+        .ensure(account_ids)
+        .map_err(|_| StatusCode::FORBIDDEN)
+}
+
+/// Transactions are refused unless they touch one of the user's accounts.
+pub fn ensure_transaction_access(
+    session_user: &SessionUser,
+    transaction: &Transaction,
+) -> Result<(), StatusCode> {
+    if session_user
+        .account_access()
+        .allows_transaction(transaction)
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
 pub(super) async fn validate_company_refs(
     state: &AppState,
     active_company: &ObjectId,
@@ -93,6 +125,16 @@ pub(super) async fn validate_planned_entry_company(
     }
 }
 
+/// The active company's required-field policy.
+pub(super) async fn required_fields(
+    state: &AppState,
+    active_company: &ObjectId,
+) -> Result<RequiredFieldPolicy, StatusCode> {
+    company_required_fields(state, active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub(super) async fn validate_user_in_company(
     state: &AppState,
     user_id: &ObjectId,
@@ -139,6 +181,12 @@ pub(super) fn parse_object_id(value: &str, label: &str) -> Result<ObjectId, Stri
     ObjectId::from_str(value).map_err(|_| format!("{} inválido", label))
 }
 
+/// A required id of a form read with `optional_object_id`; `None` is a
+/// select left blank.
+pub(super) fn required_object_id(id: Option<ObjectId>, label: &str) -> Result<ObjectId, String> {
+    id.ok_or_else(|| format!("{} inválido", label))
+}
+
 pub(super) fn parse_f64_field(value: &str, label: &str) -> Result<f64, String> {
     value
         .trim()
@@ -173,6 +221,56 @@ pub(super) fn parse_optional_f64_field(
     }
 }
 
+/// An exchange rate typed on a transaction or planned entry: empty for none,
+/// otherwise above zero.
+pub(super) fn parse_exchange_rate(value: Option<String>) -> Result<Option<f64>, String> {
+    match parse_optional_f64_field(value, "Tipo de cambio")? {
+        Some(rate) if rate <= 0.0 => Err("Tipo de cambio debe ser mayor a cero".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+pub(super) fn exchange_rate_value(rate: Option<f64>) -> String {
+    rate.map(|rate| rate.to_string()).unwrap_or_default()
+}
+
+pub(super) fn validate_scenario_weights(
+    weights: ScenarioWeights,
+) -> Result<ScenarioWeights, String> {
+    if [weights.best, weights.expected, weights.worst]
+        .iter()
+        .any(|w| !w.is_finite() || *w < 0.0)
+    {
+        return Err("Los pesos de escenario deben ser números no negativos".into());
+    }
+    Ok(weights)
+}
+
+/// Parses weights entered as percentages (`70` means 0.7 of the estimate).
+pub(super) fn parse_scenario_weight_pcts(
+    best: &str,
+    expected: &str,
+    worst: &str,
+) -> Result<ScenarioWeights, String> {
+    validate_scenario_weights(ScenarioWeights {
+        best: parse_f64_field(best, "Optimista")? / 100.0,
+        expected: parse_f64_field(expected, "Esperado")? / 100.0,
+        worst: parse_f64_field(worst, "Pesimista")? / 100.0,
+    })
+}
+
+/// Checks the chance, in percent, that a plan's money arrives: above 0 and at
+/// most 100, and only on income plans.
+pub(super) fn validate_probability(flow_type: &FlowType, pct: f64) -> Result<f64, String> {
+    if !matches!(flow_type, FlowType::Income) {
+        return Err("La probabilidad solo aplica a planes de ingreso".into());
+    }
+    if !pct.is_finite() || pct <= 0.0 || pct > 100.0 {
+        return Err("La probabilidad debe ser mayor que 0 y hasta 100".into());
+    }
+    Ok(pct)
+}
+
 pub(super) fn parse_datetime_field(value: &str, label: &str) -> Result<DateTime, String> {
     let trimmed = value.trim();
     if trimmed.is_empty() {
@@ -229,6 +327,7 @@ pub(super) fn parse_planned_status(value: &str) -> Result<PlannedStatus, String>
         "planned" => Ok(PlannedStatus::Planned),
         "partially_covered" => Ok(PlannedStatus::PartiallyCovered),
         "covered" => Ok(PlannedStatus::Covered),
+        "over_covered" => Ok(PlannedStatus::OverCovered),
         "overdue" => Ok(PlannedStatus::Overdue),
         "cancelled" => Ok(PlannedStatus::Cancelled),
         _ => Err("Estado inválido".into()),
@@ -275,16 +374,18 @@ pub(super) fn planned_status_value(value: &PlannedStatus) -> &'static str {
         PlannedStatus::Planned => "planned",
         PlannedStatus::PartiallyCovered => "partially_covered",
         PlannedStatus::Covered => "covered",
+        PlannedStatus::OverCovered => "over_covered",
         PlannedStatus::Overdue => "overdue",
         PlannedStatus::Cancelled => "cancelled",
     }
 }
 
-pub(super) fn planned_status_label(value: &PlannedStatus) -> &'static str {
+pub(crate) fn planned_status_label(value: &PlannedStatus) -> &'static str {
     match value {
         PlannedStatus::Planned => "Planificado",
         PlannedStatus::PartiallyCovered => "Parcial",
         PlannedStatus::Covered => "Cubierto",
+        PlannedStatus::OverCovered => "Excedente",
         PlannedStatus::Overdue => "Vencido",
         PlannedStatus::Cancelled => "Cancelado",
     }
@@ -298,6 +399,14 @@ pub(super) fn transaction_type_value(value: &TransactionType) -> &'static str {
     }
 }
 
+pub(super) fn transaction_type_label(value: &TransactionType) -> &'static str {
+    match value {
+        TransactionType::Income => "Ingreso",
+        TransactionType::Expense => "Gasto",
+        TransactionType::Transfer => "Transferencia",
+    }
+}
+
 pub(super) async fn company_options(
     state: &AppState,
     active: &ObjectId,
@@ -403,6 +512,11 @@ pub(super) fn planned_status_options(selected: &str) -> Vec<SimpleOption> {
             label: "Cubierto".into(),
             selected: selected == "covered",
         },
+        SimpleOption {
+            value: "over_covered".into(),
+            label: "Excedente".into(),
+            selected: selected == "over_covered",
+        },
         SimpleOption {
             value: "overdue".into(),
             label: "Vencido".into(),
@@ -477,3 +591,86 @@ pub(super) fn parse_date_field(s: &str) -> Option<DateTime> {
     let utc = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(dt, chrono::Utc);
     Some(DateTime::from_millis(utc.timestamp_millis()))
 }
+
+/// Dates of a copied plan: it starts on `today` and, when it has an end date,
+/// keeps the same length.
+pub(super) fn shift_to_start(
+    start: DateTime,
+    end: Option<DateTime>,
+    today: DateTime,
+) -> (DateTime, Option<DateTime>) {
+    let offset = today.timestamp_millis() - start.timestamp_millis();
+    (
+        today,
+        end.map(|end| DateTime::from_millis(end.timestamp_millis() + offset)),
+    )
+}
+
+/// The period right after `[start, end]` and as long: in whole months when it
+/// spans whole months, so a quarter is followed by the next quarter, and in
+/// days otherwise.
+pub(super) fn next_period(start: DateTime, end: DateTime) -> (DateTime, DateTime) {
+    let (start, end) = (start.to_chrono(), end.to_chrono());
+    let after = end + Duration::days(1);
+    let months = (after.year() - start.year()) * 12 + after.month() as i32 - start.month() as i32;
+    if start.day() == 1 && after.day() == 1 && months > 0 {
+        let shift = Months::new(months as u32);
+        if let (Some(next_start), Some(next_after)) = (
+            start.checked_add_months(shift),
+            after.checked_add_months(shift),
+        ) {
+            return (
+                DateTime::from_chrono(next_start),
+                DateTime::from_chrono(next_after - Duration::days(1)),
+            );
+        }
+    }
+    let shift = end - start + Duration::days(1);
+    (
+        DateTime::from_chrono(start + shift),
+        DateTime::from_chrono(end + shift),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> DateTime {
+        parse_date_field(value).unwrap()
+    }
+
+    #[test]
+    fn copies_move_to_the_next_period() {
+        assert_eq!(
+            next_period(date("2025-01-01"), date("2025-03-31")),
+            (date("2025-04-01"), date("2025-06-30"))
+        );
+        assert_eq!(
+            next_period(date("2026-01-01"), date("2026-12-31")),
+            (date("2027-01-01"), date("2027-12-31"))
+        );
+        assert_eq!(
+            next_period(date("2025-01-06"), date("2025-01-12")),
+            (date("2025-01-13"), date("2025-01-19"))
+        );
+        assert_eq!(
+            shift_to_start(
+                date("2024-03-01"),
+                Some(date("2024-12-31")),
+                date("2025-03-01")
+            ),
+            (date("2025-03-01"), Some(date("2025-12-31")))
+        );
+    }
+
+    #[test]
+    fn probability_is_a_percentage_of_income() {
+        assert_eq!(validate_probability(&FlowType::Income, 60.0), Ok(60.0));
+        assert_eq!(validate_probability(&FlowType::Income, 100.0), Ok(100.0));
+        assert!(validate_probability(&FlowType::Income, 0.0).is_err());
+        assert!(validate_probability(&FlowType::Income, 120.0).is_err());
+        assert!(validate_probability(&FlowType::Income, f64::NAN).is_err());
+        assert!(validate_probability(&FlowType::Expense, 50.0).is_err());
+    }
+}
@@ -0,0 +1,859 @@
+// Bank statement imports. An OFX, QIF or CSV file exported by the bank is
+// recorded as unconfirmed transactions of one account, to be reviewed from
+// the pending list. A CSV is first previewed with its columns detected, or
+// taken from the profile saved for the same header, and can be remapped
+// before it is imported. Recurring plans can be brought the same way from a
+// CSV spreadsheet.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::Deserialize;
+
+use crate::{
+    flash::Flash,
+    import::{
+        CsvRowOutcome, CsvSheet, StatementFormat, csv_statement_lines, decode_statement,
+        detect_format, map_csv_rows, parse_plan_sheet, parse_statement,
+    },
+    models::{CsvMapping, DateOrder, DecimalMark, FlowType, ImportDraft},
+    routes::{Routes, form_fields::parse_optional_object_id},
+    session::SessionUser,
+    state::{
+        AppState, PlanImportError, create_import_draft, delete_import_draft, delete_import_profile,
+        find_import_profile_for_header, get_import_draft, import_recurring_plans,
+        import_statement_lines, list_import_profiles, save_import_profile,
+    },
+    uploads::BodyLimits,
+};
+
+use super::helpers::*;
+use super::options::{account_options, flow_category_options};
+
+/// File imports of plans and transactions, with the larger body limit.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .route(
+            "/admin/recurring_plans/import",
+            get(recurring_plans_import_form)
+                .post(recurring_plans_import)
+                .layer(limits.upload_layer()),
+        )
+        .route(
+            "/admin/transactions/import",
+            get(transactions_import_form)
+                .post(transactions_import)
+                .layer(limits.upload_layer()),
+        )
+        .route(
+            "/admin/transactions/import/{id}",
+            get(csv_import_preview).post(csv_import_remap),
+        )
+        .route(
+            "/admin/transactions/import/profiles/{id}/delete",
+            post(import_profile_delete),
+        )
+}
+
+const MAX_STATEMENT_BYTES: usize = 5 * 1024 * 1024;
+
+/// Rows of a CSV shown in its preview.
+const PREVIEW_ROWS: usize = 20;
+
+/// Problem rows listed under a CSV preview.
+const PREVIEW_ERRORS: usize = 10;
+
+/// A saved CSV profile, as listed on the import form.
+struct ImportProfileRow {
+    id: String,
+    name: String,
+    columns: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transactions/import.html")]
+struct StatementImportTemplate {
+    accounts: Vec<SimpleOption>,
+    income_categories: Vec<SimpleOption>,
+    expense_categories: Vec<SimpleOption>,
+    profiles: Vec<ImportProfileRow>,
+    errors: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transactions/import_result.html")]
+struct StatementImportResultTemplate {
+    format: &'static str,
+    imported: usize,
+    duplicates: usize,
+    /// Profile the CSV mapping was saved as.
+    profile_name: Option<String>,
+}
+
+/// Fields of the import form; the file may come before or after the selects.
+#[derive(Default)]
+struct StatementUpload {
+    file_name: String,
+    data: Vec<u8>,
+    account_id: Option<ObjectId>,
+    income_category_id: Option<ObjectId>,
+    expense_category_id: Option<ObjectId>,
+}
+
+async fn read_statement_upload(multipart: &mut Multipart) -> Result<StatementUpload, StatusCode> {
+    let mut upload = StatementUpload::default();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return Err(err.status()),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "statement" => {
+                upload.file_name = field.file_name().unwrap_or_default().to_string();
+                // A body cut short by the upload limit surfaces here as 413.
+                upload.data = field.bytes().await.map_err(|err| err.status())?.to_vec();
+                if upload.data.len() > MAX_STATEMENT_BYTES {
+                    return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                }
+            }
+            "account_id" | "income_category_id" | "expense_category_id" => {
+                let text = field.text().await.unwrap_or_default();
+                let value = parse_optional_object_id(&text).map_err(|_| StatusCode::BAD_REQUEST)?;
+                match name.as_str() {
+                    "account_id" => upload.account_id = value,
+                    "income_category_id" => upload.income_category_id = value,
+                    _ => upload.expense_category_id = value,
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(upload)
+}
+
+async fn import_form(
+    state: &AppState,
+    session_user: &SessionUser,
+    upload: Option<&StatementUpload>,
+    errors: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = session_user.active_company_id();
+    let account_id = upload.and_then(|u| u.account_id);
+    let income_id = upload.and_then(|u| u.income_category_id);
+    let expense_id = upload.and_then(|u| u.expense_category_id);
+    render(StatementImportTemplate {
+        accounts: account_options(
+            state,
+            account_id.as_ref(),
+            company_id,
+            &session_user.account_access(),
+        )
+        .await?,
+        income_categories: flow_category_options(
+            state,
+            income_id.as_ref(),
+            company_id,
+            Some(&FlowType::Income),
+        )
+        .await?,
+        expense_categories: flow_category_options(
+            state,
+            expense_id.as_ref(),
+            company_id,
+            Some(&FlowType::Expense),
+        )
+        .await?,
+        profiles: list_import_profiles(state, company_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .map(|profile| ImportProfileRow {
+                id: profile.id.map(|id| id.to_hex()).unwrap_or_default(),
+                name: profile.name,
+                columns: profile.header.join(", "),
+            })
+            .collect(),
+        errors,
+    })
+}
+
+/// Shows the form again with the choices made and what went wrong.
+async fn import_failed(
+    state: &AppState,
+    session_user: &SessionUser,
+    upload: &StatementUpload,
+    message: &str,
+) -> axum::response::Response {
+    match import_form(state, session_user, Some(upload), Some(message.to_string())).await {
+        Ok(html) => (StatusCode::BAD_REQUEST, html).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Form to upload a bank statement.
+pub async fn transactions_import_form(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    import_form(&state, &session_user, None, None).await
+}
+
+/// Detects the format of the uploaded statement and records its movements.
+pub async fn transactions_import(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let upload = match read_statement_upload(&mut multipart).await {
+        Ok(upload) => upload,
+        Err(status) => return status.into_response(),
+    };
+    let (Some(account_id), Some(income_id), Some(expense_id)) = (
+        upload.account_id,
+        upload.income_category_id,
+        upload.expense_category_id,
+    ) else {
+        return import_failed(
+            &state,
+            &session_user,
+            &upload,
+            "Selecciona la cuenta y las categorías de ingresos y gastos.",
+        )
+        .await;
+    };
+    if !session_user.account_access().allows(&account_id) {
+        return import_failed(
+            &state,
+            &session_user,
+            &upload,
+            "No tienes acceso a esa cuenta.",
+        )
+        .await;
+    }
+    if upload.data.is_empty() {
+        return import_failed(
+            &state,
+            &session_user,
+            &upload,
+            "Adjunta el archivo del estado de cuenta.",
+        )
+        .await;
+    }
+    let unreadable = "No se pudo leer el archivo. Usa un estado de cuenta OFX, QIF o CSV con columnas de fecha, descripción y monto.";
+    let text = decode_statement(&upload.data);
+    if !matches!(
+        detect_format(&text),
+        Some(StatementFormat::Ofx | StatementFormat::Qif)
+    ) {
+        // CSV columns are confirmed on a preview before anything is recorded.
+        if CsvSheet::read(&text, None).is_err() {
+            return import_failed(&state, &session_user, &upload, unreadable).await;
+        }
+        let draft = ImportDraft {
+            id: None,
+            company_id,
+            account_id,
+            income_category_id: income_id,
+            expense_category_id: expense_id,
+            file_name: upload.file_name.clone(),
+            content: text,
+            expires_at: DateTime::now(),
+        };
+        return match create_import_draft(&state, draft).await {
+            Ok(id) => {
+                Redirect::to(&format!("/admin/transactions/import/{}", id.to_hex())).into_response()
+            }
+            Err(err) => {
+                eprintln!("[imports] draft save failed: {err:?}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        };
+    }
+    let Ok((format, lines)) = parse_statement(&upload.data) else {
+        return import_failed(&state, &session_user, &upload, unreadable).await;
+    };
+    match import_statement_lines(
+        &state,
+        &company_id,
+        &account_id,
+        &income_id,
+        &expense_id,
+        format,
+        &lines,
+    )
+    .await
+    {
+        Ok(summary) => render(StatementImportResultTemplate {
+            format: format.label(),
+            imported: summary.imported,
+            duplicates: summary.duplicates,
+            profile_name: None,
+        })
+        .into_response(),
+        Err(err) => {
+            eprintln!("[imports] statement import failed: {err:?}");
+            import_failed(
+                &state,
+                &session_user,
+                &upload,
+                "La cuenta o las categorías no son válidas para esta empresa.",
+            )
+            .await
+        }
+    }
+}
+
+struct PreviewRow {
+    line: usize,
+    cells: Vec<String>,
+    /// `ok`, `skipped` or `error`.
+    status: &'static str,
+    date: String,
+    description: String,
+    amount: String,
+    message: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transactions/import_preview.html")]
+struct CsvPreviewTemplate {
+    draft_id: String,
+    file_name: String,
+    header: Vec<String>,
+    rows: Vec<PreviewRow>,
+    total_rows: usize,
+    line_count: usize,
+    skipped_count: usize,
+    invalid_count: usize,
+    /// First rows that cannot be read, with their line.
+    row_errors: Vec<String>,
+    delimiters: Vec<SimpleOption>,
+    date_columns: Vec<SimpleOption>,
+    description_columns: Vec<SimpleOption>,
+    amount_columns: Vec<SimpleOption>,
+    debit_columns: Vec<SimpleOption>,
+    credit_columns: Vec<SimpleOption>,
+    reference_columns: Vec<SimpleOption>,
+    date_orders: Vec<SimpleOption>,
+    decimal_marks: Vec<SimpleOption>,
+    negate: bool,
+    profile_name: String,
+    /// Saved profile the mapping was taken from.
+    applied_profile: Option<String>,
+    errors: Option<String>,
+}
+
+const DELIMITERS: [(char, &str, &str); 3] = [
+    (',', "comma", "Coma (,)"),
+    (';', "semicolon", "Punto y coma (;)"),
+    ('\t', "tab", "Tabulador"),
+];
+
+fn options<T: PartialEq>(choices: &[(T, &str, &str)], selected: &T) -> Vec<SimpleOption> {
+    choices
+        .iter()
+        .map(|(value, key, label)| SimpleOption {
+            value: key.to_string(),
+            label: label.to_string(),
+            selected: value == selected,
+        })
+        .collect()
+}
+
+/// One option per column of the file, plus a "none" one when the field is
+/// optional.
+fn column_options(
+    header: &[String],
+    selected: Option<usize>,
+    none: Option<&str>,
+) -> Vec<SimpleOption> {
+    let none = none.map(|label| SimpleOption {
+        value: String::new(),
+        label: label.to_string(),
+        selected: selected.is_none(),
+    });
+    none.into_iter()
+        .chain(header.iter().enumerate().map(|(index, name)| SimpleOption {
+            value: index.to_string(),
+            label: match name.as_str() {
+                "" => format!("Columna {}", index + 1),
+                name => format!("{} · {name}", index + 1),
+            },
+            selected: selected == Some(index),
+        }))
+        .collect()
+}
+
+fn preview_template(
+    draft_id: &ObjectId,
+    draft: &ImportDraft,
+    sheet: &CsvSheet,
+    mapping: &CsvMapping,
+    profile_name: String,
+    applied_profile: Option<String>,
+    errors: Option<String>,
+) -> CsvPreviewTemplate {
+    let outcomes = map_csv_rows(sheet, mapping);
+    let count = |wanted: fn(&CsvRowOutcome) -> bool| outcomes.iter().filter(|o| wanted(o)).count();
+    let line_count = count(|o| matches!(o, CsvRowOutcome::Line(_)));
+    let skipped_count = count(|o| matches!(o, CsvRowOutcome::Skipped));
+    let row_errors = sheet
+        .rows
+        .iter()
+        .zip(&outcomes)
+        .filter_map(|((line, _), outcome)| match outcome {
+            CsvRowOutcome::Invalid(message) => Some(format!("Línea {line}: {message}")),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let rows = sheet
+        .rows
+        .iter()
+        .zip(outcomes)
+        .take(PREVIEW_ROWS)
+        .map(|((line, cells), outcome)| {
+            let mut row = PreviewRow {
+                line: *line,
+                cells: cells.clone(),
+                status: "ok",
+                date: String::new(),
+                description: String::new(),
+                amount: String::new(),
+                message: String::new(),
+            };
+            match outcome {
+                CsvRowOutcome::Line(statement_line) => {
+                    row.date = statement_line.date.format("%Y-%m-%d").to_string();
+                    row.description = statement_line.description;
+                    row.amount = format!("{:.2}", statement_line.amount);
+                }
+                CsvRowOutcome::Skipped => {
+                    row.status = "skipped";
+                    row.message = "Sin monto; se omite".to_string();
+                }
+                CsvRowOutcome::Invalid(message) => {
+                    row.status = "error";
+                    row.message = message;
+                }
+            }
+            row
+        })
+        .collect();
+    let header = &sheet.header;
+    CsvPreviewTemplate {
+        draft_id: draft_id.to_hex(),
+        file_name: draft.file_name.clone(),
+        header: header.clone(),
+        rows,
+        total_rows: sheet.rows.len(),
+        line_count,
+        skipped_count,
+        invalid_count: row_errors.len(),
+        row_errors: row_errors.into_iter().take(PREVIEW_ERRORS).collect(),
+        delimiters: options(&DELIMITERS, &sheet.delimiter),
+        date_columns: column_options(header, Some(mapping.date), None),
+        description_columns: column_options(header, Some(mapping.description), None),
+        amount_columns: column_options(
+            header,
+            mapping.amount,
+            Some("— Cargo y abono por separado"),
+        ),
+        debit_columns: column_options(header, mapping.debit, Some("—")),
+        credit_columns: column_options(header, mapping.credit, Some("—")),
+        reference_columns: column_options(header, mapping.reference, Some("Sin referencia")),
+        date_orders: options(
+            &[
+                (DateOrder::Auto, DateOrder::Auto.as_str(), "Detectar"),
+                (
+                    DateOrder::DayFirst,
+                    DateOrder::DayFirst.as_str(),
+                    "Día/mes/año",
+                ),
+                (
+                    DateOrder::MonthFirst,
+                    DateOrder::MonthFirst.as_str(),
+                    "Mes/día/año",
+                ),
+            ],
+            &mapping.date_order,
+        ),
+        decimal_marks: options(
+            &[
+                (DecimalMark::Auto, DecimalMark::Auto.as_str(), "Detectar"),
+                (
+                    DecimalMark::Point,
+                    DecimalMark::Point.as_str(),
+                    "Punto (1,234.56)",
+                ),
+                (
+                    DecimalMark::Comma,
+                    DecimalMark::Comma.as_str(),
+                    "Coma (1.234,56)",
+                ),
+            ],
+            &mapping.decimal_mark,
+        ),
+        negate: mapping.negate,
+        profile_name,
+        applied_profile,
+        errors,
+    }
+}
+
+/// The company's draft and its file split into cells.
+async fn load_draft(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, ImportDraft), StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    let draft_id = ObjectId::from_str(id).map_err(|_| StatusCode::NOT_FOUND)?;
+    let draft = get_import_draft(state, &company_id, &draft_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((draft_id, draft))
+}
+
+/// First preview of an uploaded CSV: the mapping of the profile saved for
+/// its header, else the one its column names give, else the first columns.
+pub async fn csv_import_preview(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (draft_id, draft) = load_draft(&state, &session_user, &id).await?;
+    let mut sheet =
+        CsvSheet::read(&draft.content, None).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    let profile = find_import_profile_for_header(&state, &draft.company_id, &sheet.header_key())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let (mapping, profile_name, applied) = match profile {
+        Some(profile) => {
+            if profile.mapping.delimiter != sheet.delimiter {
+                sheet = CsvSheet::read(&draft.content, Some(profile.mapping.delimiter))
+                    .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+            }
+            (profile.mapping, profile.name.clone(), Some(profile.name))
+        }
+        None => {
+            let mapping = sheet
+                .detect_mapping()
+                .unwrap_or_else(|| sheet.default_mapping());
+            let name = draft
+                .file_name
+                .rsplit_once('.')
+                .map_or(draft.file_name.as_str(), |(stem, _)| stem)
+                .to_string();
+            (mapping, name, None)
+        }
+    };
+    render(preview_template(
+        &draft_id,
+        &draft,
+        &sheet,
+        &mapping,
+        profile_name,
+        applied,
+        None,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct CsvMappingForm {
+    #[serde(default)]
+    delimiter: String,
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    amount: String,
+    #[serde(default)]
+    debit: String,
+    #[serde(default)]
+    credit: String,
+    #[serde(default)]
+    reference: String,
+    #[serde(default)]
+    date_order: String,
+    #[serde(default)]
+    decimal_mark: String,
+    negate: Option<bool>,
+    #[serde(default)]
+    profile_name: String,
+    /// `import` to record the movements; anything else previews again.
+    #[serde(default)]
+    action: String,
+}
+
+/// Reads the mapping chosen on the preview; columns beyond the file are
+/// dropped.
+fn mapping_from_form(form: &CsvMappingForm, delimiter: char, columns: usize) -> CsvMapping {
+    let column = |value: &str| value.trim().parse().ok().filter(|index| *index < columns);
+    CsvMapping {
+        delimiter,
+        date: column(&form.date).unwrap_or(0),
+        description: column(&form.description).unwrap_or(0),
+        amount: column(&form.amount),
+        debit: column(&form.debit),
+        credit: column(&form.credit),
+        reference: column(&form.reference),
+        date_order: DateOrder::parse(form.date_order.trim()).unwrap_or_default(),
+        decimal_mark: DecimalMark::parse(form.decimal_mark.trim()).unwrap_or_default(),
+        negate: form.negate.unwrap_or(false),
+    }
+}
+
+/// Previews the CSV again with the columns and formats chosen, or imports it
+/// with them and saves them as a profile for the next files of that bank.
+pub async fn csv_import_remap(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<CsvMappingForm>,
+) -> impl IntoResponse {
+    let (draft_id, draft) = match load_draft(&state, &session_user, &id).await {
+        Ok(loaded) => loaded,
+        Err(status) => return status.into_response(),
+    };
+    let delimiter = DELIMITERS
+        .iter()
+        .find(|(_, key, _)| *key == form.delimiter)
+        .map(|(delimiter, _, _)| *delimiter);
+    let Ok(sheet) = CsvSheet::read(&draft.content, delimiter) else {
+        return StatusCode::UNPROCESSABLE_ENTITY.into_response();
+    };
+    let mapping = mapping_from_form(&form, sheet.delimiter, sheet.header.len());
+    let profile_name = form.profile_name.trim().to_string();
+    let failed = |message: &str| {
+        let html = render(preview_template(
+            &draft_id,
+            &draft,
+            &sheet,
+            &mapping,
+            profile_name.clone(),
+            None,
+            Some(message.to_string()),
+        ));
+        match html {
+            Ok(html) => (StatusCode::BAD_REQUEST, html).into_response(),
+            Err(status) => status.into_response(),
+        }
+    };
+    if form.action != "import" {
+        return render(preview_template(
+            &draft_id,
+            &draft,
+            &sheet,
+            &mapping,
+            profile_name.clone(),
+            None,
+            None,
+        ))
+        .into_response();
+    }
+    if mapping.amount.is_none() && (mapping.debit.is_none() || mapping.credit.is_none()) {
+        return failed("Elige la columna del monto, o las de cargo y abono.");
+    }
+    if profile_name.is_empty() {
+        return failed(
+            "Ponle nombre al perfil para reconocer los siguientes archivos de este banco.",
+        );
+    }
+    let lines = match csv_statement_lines(&sheet, &mapping) {
+        Ok(lines) if lines.is_empty() => {
+            return failed("Ninguna fila tiene monto con estas columnas.");
+        }
+        Ok(lines) => lines,
+        Err(_) => {
+            return failed("Hay filas que no se pueden leer con estas columnas; corrígelas abajo.");
+        }
+    };
+    let summary = match import_statement_lines(
+        &state,
+        &draft.company_id,
+        &draft.account_id,
+        &draft.income_category_id,
+        &draft.expense_category_id,
+        StatementFormat::Csv,
+        &lines,
+    )
+    .await
+    {
+        Ok(summary) => summary,
+        Err(err) => {
+            eprintln!("[imports] statement import failed: {err:?}");
+            return failed("La cuenta o las categorías no son válidas para esta empresa.");
+        }
+    };
+    if let Err(err) = save_import_profile(
+        &state,
+        &draft.company_id,
+        &profile_name,
+        &sheet.header_key(),
+        &mapping,
+    )
+    .await
+    {
+        eprintln!("[imports] profile save failed: {err:?}");
+    }
+    if let Err(err) = delete_import_draft(&state, &draft.company_id, &draft_id).await {
+        eprintln!("[imports] draft delete failed: {err:?}");
+    }
+    render(StatementImportResultTemplate {
+        format: StatementFormat::Csv.label(),
+        imported: summary.imported,
+        duplicates: summary.duplicates,
+        profile_name: Some(profile_name),
+    })
+    .into_response()
+}
+
+/// Forgets a saved CSV profile; its bank's files are detected again.
+pub async fn import_profile_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let Ok(profile_id) = ObjectId::from_str(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match delete_import_profile(&state, &company_id, &profile_id).await {
+        Ok(true) => (
+            Flash::success("Perfil de importación eliminado."),
+            Redirect::to("/admin/transactions/import"),
+        )
+            .into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            eprintln!("[imports] profile delete failed: {err:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/recurring_plans/import.html")]
+struct PlanImportTemplate {
+    generate_entries: bool,
+    row_errors: Vec<PlanImportError>,
+    errors: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/recurring_plans/import_result.html")]
+struct PlanImportResultTemplate {
+    imported: usize,
+    entries_generated: u64,
+    generate_entries: bool,
+}
+
+fn plan_import_failed(
+    generate_entries: bool,
+    row_errors: Vec<PlanImportError>,
+    message: &str,
+) -> axum::response::Response {
+    match render(PlanImportTemplate {
+        generate_entries,
+        row_errors,
+        errors: Some(message.to_string()),
+    }) {
+        Ok(html) => (StatusCode::BAD_REQUEST, html).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Form to upload a spreadsheet of recurring plans.
+pub async fn recurring_plans_import_form(
+    session_user: SessionUser,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    render(PlanImportTemplate {
+        generate_entries: true,
+        row_errors: Vec::new(),
+        errors: None,
+    })
+}
+
+/// Creates the plans of the uploaded CSV, or lists what is wrong with each
+/// row and creates none.
+pub async fn recurring_plans_import(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let mut data = Vec::new();
+    let mut generate_entries = false;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return err.status().into_response(),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "sheet" => {
+                data = match field.bytes().await {
+                    Ok(bytes) => bytes.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
+                if data.len() > MAX_STATEMENT_BYTES {
+                    return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+                }
+            }
+            "generate_entries" => generate_entries = true,
+            _ => {}
+        }
+    }
+    if data.is_empty() {
+        return plan_import_failed(generate_entries, Vec::new(), "Adjunta el archivo CSV.");
+    }
+    let Ok(rows) = parse_plan_sheet(&data) else {
+        return plan_import_failed(
+            generate_entries,
+            Vec::new(),
+            "No se pudo leer el archivo. Usa un CSV con columnas de nombre, flujo, categoría, cuenta, monto, frecuencia, día e inicio.",
+        );
+    };
+    match import_recurring_plans(&state, &company_id, &rows, generate_entries).await {
+        Ok(summary) if !summary.errors.is_empty() => plan_import_failed(
+            generate_entries,
+            summary.errors,
+            "Corrige las filas marcadas y vuelve a subir el archivo; no se creó ningún plan.",
+        ),
+        Ok(summary) => render(PlanImportResultTemplate {
+            imported: summary.imported,
+            entries_generated: summary.entries_generated,
+            generate_entries,
+        })
+        .into_response(),
+        Err(err) => {
+            eprintln!("[imports] plan import failed: {err:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
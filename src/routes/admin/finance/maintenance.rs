@@ -0,0 +1,430 @@
+// Maintenance of the active company's data: how many planned entries are
+// live and archived, archiving the old ones without waiting for the
+// background job, and moving archived entries back. Superadmins also rebuild
+// the database indexes from here; see `state::reindex`.
+
+use std::{sync::Arc, time::SystemTime};
+
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Form, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, PlannedArchiveCounts, PlannedArchivePolicy, app_database,
+        archive_planned_entries, index_plan, last_reindex, planned_archive_counts,
+        restore_archived_planned_entries, start_reindex,
+    },
+};
+
+use super::helpers::*;
+
+/// Maintenance page and its JSON counterparts.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/maintenance", get(maintenance_index))
+        .route(
+            "/admin/maintenance/planned_entries/archive",
+            post(maintenance_archive),
+        )
+        .route(
+            "/admin/maintenance/planned_entries/restore",
+            post(maintenance_restore),
+        )
+        .route("/admin/maintenance/reindex", post(maintenance_reindex))
+        .route(
+            "/api/admin/maintenance/reindex",
+            get(reindex_status_api).post(reindex_start_api),
+        )
+        .route(
+            "/api/admin/maintenance/planned-entries",
+            get(maintenance_counts_api),
+        )
+        .route(
+            "/api/admin/maintenance/planned-entries/archive",
+            post(maintenance_archive_api),
+        )
+        .route(
+            "/api/admin/maintenance/planned-entries/restore",
+            post(maintenance_restore_api),
+        )
+}
+
+#[derive(Template)]
+#[template(path = "admin/maintenance/index.html")]
+struct MaintenanceTemplate {
+    counts: PlannedArchiveCounts,
+    /// Age in days after which entries are archived; 0 when it is off.
+    age_days: u64,
+    /// Index rebuild section, for superadmins only.
+    reindex: Option<ReindexResponse>,
+}
+
+/// The rebuild this process started last, and when the last one recorded in
+/// `migrations` finished. Progress is empty after a restart.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReindexResponse {
+    pub running: bool,
+    /// Indexes in the plan.
+    pub total: usize,
+    /// Indexes already built.
+    pub built: usize,
+    /// `collection.index` being built now.
+    pub current: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// Why the last rebuild stopped early.
+    pub error: Option<String>,
+    pub last_completed_at: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PlannedArchiveResponse {
+    /// Planned entries in the regular listings.
+    pub live: u64,
+    /// Live entries old enough to archive now.
+    pub archivable: u64,
+    pub archived: u64,
+    /// Covered and cancelled entries due longer ago than this are archived;
+    /// 0 when archiving is off.
+    pub age_days: u64,
+    /// Entries moved by the request, on archive and restore.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreFormData {
+    #[serde(default)]
+    since: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RestorePayload {
+    /// `YYYY-MM-DD`; restores the entries due on or after it. Without it
+    /// every archived entry comes back.
+    #[serde(default)]
+    pub since: Option<String>,
+}
+
+fn parse_since(value: Option<String>) -> Result<Option<DateTime>, String> {
+    match clean_opt(value) {
+        Some(value) => parse_date_field(&value)
+            .map(Some)
+            .ok_or_else(|| "La fecha debe tener el formato AAAA-MM-DD.".to_string()),
+        None => Ok(None),
+    }
+}
+
+async fn company_counts(
+    state: &AppState,
+    company_id: &ObjectId,
+    policy: &PlannedArchivePolicy,
+) -> Result<PlannedArchiveCounts, StatusCode> {
+    planned_archive_counts(state, company_id, policy, SystemTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn archive_response(
+    state: &AppState,
+    company_id: &ObjectId,
+    policy: &PlannedArchivePolicy,
+    moved: Option<u64>,
+) -> Result<PlannedArchiveResponse, StatusCode> {
+    let counts = company_counts(state, company_id, policy).await?;
+    Ok(PlannedArchiveResponse {
+        live: counts.live,
+        archivable: counts.archivable,
+        archived: counts.archived,
+        age_days: policy.age_days,
+        moved,
+    })
+}
+
+/// Archives the company's old entries now; `None` when archiving is off.
+async fn archive_company(
+    state: &AppState,
+    company_id: &ObjectId,
+    policy: &PlannedArchivePolicy,
+) -> Result<Option<u64>, StatusCode> {
+    if !policy.is_enabled() {
+        return Ok(None);
+    }
+    let now = SystemTime::now();
+    archive_planned_entries(state, Some(company_id), policy.cutoff(now), now)
+        .await
+        .map(Some)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn reindex_response(state: &AppState) -> Result<ReindexResponse, StatusCode> {
+    let progress = state.reindex.lock().await.clone();
+    let last = last_reindex(&app_database(state))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let last_completed_at = last.map(|record| datetime_to_string(&record.applied_at));
+    Ok(match progress {
+        Some(progress) => ReindexResponse {
+            running: progress.is_running(),
+            total: progress.total,
+            built: progress.built,
+            current: progress.current,
+            started_at: Some(datetime_to_string(&progress.started_at)),
+            finished_at: progress.finished_at.as_ref().map(datetime_to_string),
+            error: progress.error,
+            last_completed_at,
+        },
+        None => ReindexResponse {
+            running: false,
+            total: index_plan().len(),
+            built: 0,
+            current: None,
+            started_at: None,
+            finished_at: None,
+            error: None,
+            last_completed_at,
+        },
+    })
+}
+
+pub async fn maintenance_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let policy = PlannedArchivePolicy::from_env();
+    let counts = company_counts(&state, &company_id, &policy).await?;
+    let reindex = if session_user.is_superadmin() {
+        Some(reindex_response(&state).await?)
+    } else {
+        None
+    };
+    render(MaintenanceTemplate {
+        counts,
+        age_days: policy.age_days,
+        reindex,
+    })
+}
+
+pub async fn maintenance_archive(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let flash = match archive_company(&state, &company_id, &PlannedArchivePolicy::from_env()).await
+    {
+        Ok(Some(moved)) => Flash::success(format!("{moved} compromisos archivados.")),
+        Ok(None) => Flash::error("El archivo de compromisos está desactivado."),
+        Err(_) => Flash::error("No se pudieron archivar los compromisos."),
+    };
+    (flash, Redirect::to("/admin/maintenance")).into_response()
+}
+
+pub async fn maintenance_restore(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<RestoreFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let flash = match parse_since(form.since) {
+        Ok(since) => match restore_archived_planned_entries(&state, &company_id, since).await {
+            Ok(moved) => Flash::success(format!("{moved} compromisos restaurados.")),
+            Err(_) => Flash::error("No se pudieron restaurar los compromisos."),
+        },
+        Err(message) => Flash::error(message),
+    };
+    (flash, Redirect::to("/admin/maintenance")).into_response()
+}
+
+/// POST /admin/maintenance/reindex — starts the index rebuild and returns to
+/// the page, which follows its progress.
+pub async fn maintenance_reindex(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !session_user.is_superadmin() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let flash = if start_reindex(state).await {
+        Flash::success("Reconstrucción de índices iniciada.")
+    } else {
+        Flash::error("Ya hay una reconstrucción de índices en curso.")
+    };
+    (flash, Redirect::to("/admin/maintenance")).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/maintenance/reindex",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Progress of the index rebuild and when the last one finished", body = ReindexResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not a superadmin")
+    ),
+    security(("session" = []))
+)]
+pub async fn reindex_status_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ReindexResponse>, StatusCode> {
+    if !session_user.is_superadmin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    reindex_response(&state).await.map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/maintenance/reindex",
+    tag = "finance",
+    responses(
+        (status = 202, description = "Rebuild started; poll the GET for its progress", body = ReindexResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not a superadmin"),
+        (status = 409, description = "A rebuild is already running")
+    ),
+    security(("session" = []))
+)]
+pub async fn reindex_start_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !session_user.is_superadmin() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !start_reindex(state.clone()).await {
+        return (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "Ya hay una reconstrucción de índices en curso." })),
+        )
+            .into_response();
+    }
+    match reindex_response(&state).await {
+        Ok(response) => (StatusCode::ACCEPTED, Json(response)).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/maintenance/planned-entries",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Live, archivable and archived planned entries of the active company", body = PlannedArchiveResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn maintenance_counts_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<PlannedArchiveResponse>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let policy = PlannedArchivePolicy::from_env();
+    archive_response(&state, &company_id, &policy, None)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/maintenance/planned-entries/archive",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Old covered and cancelled entries archived; `moved` says how many", body = PlannedArchiveResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Archiving is turned off")
+    ),
+    security(("session" = []))
+)]
+pub async fn maintenance_archive_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let policy = PlannedArchivePolicy::from_env();
+    let moved = match archive_company(&state, &company_id, &policy).await {
+        Ok(Some(moved)) => moved,
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "error": "El archivo de compromisos está desactivado." })),
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    };
+    match archive_response(&state, &company_id, &policy, Some(moved)).await {
+        Ok(response) => Json(response).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/maintenance/planned-entries/restore",
+    tag = "finance",
+    request_body = RestorePayload,
+    responses(
+        (status = 200, description = "Archived entries moved back; `moved` says how many", body = PlannedArchiveResponse),
+        (status = 400, description = "Invalid date"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn maintenance_restore_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RestorePayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let since = match parse_since(payload.since) {
+        Ok(since) => since,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    let moved = match restore_archived_planned_entries(&state, &company_id, since).await {
+        Ok(moved) => moved,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let policy = PlannedArchivePolicy::from_env();
+    match archive_response(&state, &company_id, &policy, Some(moved)).await {
+        Ok(response) => Json(response).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
@@ -1,22 +1,81 @@
+pub mod account_groups;
 pub mod accounts;
+pub mod bank_sync;
+pub mod card_holds;
 pub mod categories;
+pub mod comments;
 pub mod contacts;
+pub mod custom_fields;
+pub mod deposit_allocations;
 pub mod forecasts;
 pub mod helpers;
+pub mod imports;
+pub mod maintenance;
 pub mod options;
 pub mod orders;
 pub mod planned_entries;
+pub mod print;
+pub mod receipts;
 pub mod recurring_plans;
+pub mod refunds;
+pub mod reports;
+pub mod tax_profiles;
+pub mod text_replace;
+pub mod totals;
 pub mod transactions;
 
+pub use account_groups::*;
 pub use accounts::*;
+pub use bank_sync::*;
+pub use card_holds::*;
 pub use categories::*;
+pub use comments::*;
 pub use contacts::*;
+pub use custom_fields::*;
+pub use deposit_allocations::*;
 pub use forecasts::*;
+pub use imports::*;
+pub use maintenance::*;
 pub use orders::*;
 pub use planned_entries::*;
+pub use print::*;
+pub use receipts::*;
 pub use recurring_plans::*;
+pub use refunds::*;
+pub use reports::*;
+pub use tax_profiles::*;
+pub use text_replace::*;
 pub use transactions::*;
 
 pub use helpers::{SimpleOption, ensure_same_company, require_admin_active};
-pub use options::{account_options, category_options, contact_options};
+pub use options::{account_options, category_options, contact_options, options_search_api};
+
+use crate::{routes::Routes, uploads::BodyLimits};
+
+/// Every finance page and API route.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .merge(accounts::router())
+        .merge(account_groups::router())
+        .merge(categories::router())
+        .merge(options::router())
+        .merge(contacts::router())
+        .merge(custom_fields::router())
+        .merge(tax_profiles::router())
+        .merge(recurring_plans::router())
+        .merge(imports::router(limits))
+        .merge(planned_entries::router())
+        .merge(deposit_allocations::router())
+        .merge(maintenance::router())
+        .merge(print::router())
+        .merge(transactions::router())
+        .merge(refunds::router())
+        .merge(card_holds::router())
+        .merge(text_replace::router())
+        .merge(receipts::router(limits))
+        .merge(reports::router())
+        .merge(bank_sync::router())
+        .merge(comments::router())
+        .merge(forecasts::router())
+        .merge(orders::router())
+}
@@ -1,24 +1,81 @@
-use axum::http::StatusCode;
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use data_encoding::HEXLOWER;
 use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::state::{
-    AppState, list_accounts, list_categories, list_contacts, list_planned_entries,
-    list_recurring_plans, list_users,
+use crate::{
+    models::FlowType,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AccountAccess, AppState, company_option_records, get_category_by_id, get_contact_by_id,
+        list_accessible_accounts, list_planned_entries, list_recurring_plans, list_users,
+        search_categories, search_contacts,
+    },
 };
 
-use super::helpers::SimpleOption;
+use super::helpers::{SimpleOption, parse_flow_type, require_admin_active};
+
+/// Option search behind the form pickers, and the whole option set in one
+/// payload for front-ends that cache it.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/api/options/bundle", get(options_bundle_api))
+        .route("/api/options/{entity}", get(options_search_api))
+}
+
+/// Largest category or contact list rendered in full inside a form. Bigger
+/// ones render the first few names and the selected one, and rely on the
+/// search picker for the rest.
+pub const FULL_LIST_LIMIT: i64 = 200;
+
+const SEARCH_DEFAULT_LIMIT: i64 = 20;
+const SEARCH_MAX_LIMIT: i64 = 50;
 
 pub async fn category_options(
     state: &AppState,
     selected: Option<&ObjectId>,
     company_id: &ObjectId,
 ) -> Result<Vec<SimpleOption>, StatusCode> {
-    let categories = list_categories(state)
+    flow_category_options(state, selected, company_id, None).await
+}
+
+/// Categories of one flow type, or all of them without `flow_type`. The
+/// selected category is always listed if it qualifies, even past the list
+/// limit.
+pub async fn flow_category_options(
+    state: &AppState,
+    selected: Option<&ObjectId>,
+    company_id: &ObjectId,
+    flow_type: Option<&FlowType>,
+) -> Result<Vec<SimpleOption>, StatusCode> {
+    let mut categories = search_categories(state, company_id, "", flow_type, FULL_LIST_LIMIT + 1)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if categories.len() as i64 > FULL_LIST_LIMIT {
+        // Too many to list; the picker searches the rest.
+        categories.truncate(SEARCH_DEFAULT_LIMIT as usize);
+    }
+    let missing = selected.filter(|id| !categories.iter().any(|c| c.id.as_ref() == Some(*id)));
+    if let Some(id) = missing {
+        let category = get_category_by_id(state, id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        categories.extend(category.filter(|c| {
+            c.company_id == *company_id && flow_type.is_none_or(|flow| c.flow_type == *flow)
+        }));
+    }
     Ok(categories
         .into_iter()
-        .filter(|c| c.company_id == *company_id)
         .filter_map(|c| {
             c.id.map(|id| SimpleOption {
                 value: id.to_hex(),
@@ -29,17 +86,19 @@ pub async fn category_options(
         .collect())
 }
 
+/// Active accounts of the company the user may use.
 pub async fn account_options(
     state: &AppState,
     selected: Option<&ObjectId>,
     company_id: &ObjectId,
+    access: &AccountAccess,
 ) -> Result<Vec<SimpleOption>, StatusCode> {
-    let accounts = list_accounts(state)
+    let accounts = list_accessible_accounts(state, company_id, access)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(accounts
         .into_iter()
-        .filter(|a| a.company_id == *company_id && a.is_active)
+        .filter(|a| a.is_active)
         .filter_map(|a| {
             a.id.map(|id| SimpleOption {
                 value: id.to_hex(),
@@ -55,27 +114,33 @@ pub async fn contact_options(
     selected: Option<&ObjectId>,
     company_id: &ObjectId,
 ) -> Result<Vec<SimpleOption>, StatusCode> {
-    let contacts = list_contacts(state)
+    let mut contacts = search_contacts(state, company_id, "", FULL_LIST_LIMIT + 1)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if contacts.len() as i64 > FULL_LIST_LIMIT {
+        // Too many to list; the picker searches the rest.
+        contacts.truncate(SEARCH_DEFAULT_LIMIT as usize);
+        let missing = selected.filter(|id| !contacts.iter().any(|c| c.id.as_ref() == Some(*id)));
+        if let Some(id) = missing {
+            let contact = get_contact_by_id(state, id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            contacts.extend(contact.filter(|c| c.company_id == *company_id));
+        }
+    }
     let mut options = Vec::new();
     options.push(SimpleOption {
         value: "".into(),
         label: "Sin contacto".into(),
         selected: selected.is_none(),
     });
-    options.extend(
-        contacts
-            .into_iter()
-            .filter(|c| c.company_id == *company_id)
-            .filter_map(|c| {
-                c.id.map(|id| SimpleOption {
-                    value: id.to_hex(),
-                    label: c.name,
-                    selected: selected.map(|s| *s == id).unwrap_or(false),
-                })
-            }),
-    );
+    options.extend(contacts.into_iter().filter_map(|c| {
+        c.id.map(|id| SimpleOption {
+            value: id.to_hex(),
+            label: c.name,
+            selected: selected.map(|s| *s == id).unwrap_or(false),
+        })
+    }));
     Ok(options)
 }
 
@@ -163,3 +228,256 @@ pub(super) async fn user_options(
     );
     Ok(options)
 }
+
+#[derive(Deserialize)]
+pub struct OptionSearchQuery {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<i64>,
+    /// Categories only: `income` or `expense`.
+    #[serde(default)]
+    pub flow_type: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OptionItem {
+    pub value: String,
+    pub label: String,
+}
+
+/// Options whose label starts with `q`, for the search pickers of the
+/// finance forms. `entity` is `categories` or `contacts`.
+#[utoipa::path(
+    get,
+    path = "/api/options/{entity}",
+    tag = "finance",
+    params(
+        ("entity" = String, Path, description = "`categories` or `contacts`"),
+        ("q" = Option<String>, Query, description = "Name prefix, any case"),
+        ("limit" = Option<i64>, Query, description = "At most 50; 20 by default"),
+        ("flow_type" = Option<String>, Query, description = "Categories of this flow type only: `income` or `expense`")
+    ),
+    responses(
+        (status = 200, description = "Matching options sorted by name", body = [OptionItem]),
+        (status = 400, description = "Unknown flow type"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Unknown entity")
+    ),
+    security(("session" = []))
+)]
+pub async fn options_search_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(entity): Path<String>,
+    Query(query): Query<OptionSearchQuery>,
+) -> Result<Json<Vec<OptionItem>>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let limit = query
+        .limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let flow_type = match query.flow_type.as_deref().map(str::trim) {
+        Some(value) if !value.is_empty() => {
+            Some(parse_flow_type(value).map_err(|_| StatusCode::BAD_REQUEST)?)
+        }
+        _ => None,
+    };
+    let items: Vec<(Option<ObjectId>, String)> = match entity.as_str() {
+        "categories" => {
+            search_categories(&state, &active_company, &query.q, flow_type.as_ref(), limit)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .into_iter()
+                .map(|c| (c.id, c.name))
+                .collect()
+        }
+        "contacts" => search_contacts(&state, &active_company, &query.q, limit)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .map(|c| (c.id, c.name))
+            .collect(),
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+    Ok(Json(
+        items
+            .into_iter()
+            .filter_map(|(id, label)| {
+                id.map(|id| OptionItem {
+                    value: id.to_hex(),
+                    label,
+                })
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BundleAccount {
+    pub id: String,
+    pub name: String,
+    pub currency: String,
+    pub account_type: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BundleCategory {
+    pub id: String,
+    pub name: String,
+    pub flow_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BundleContact {
+    pub id: String,
+    pub name: String,
+    pub contact_type: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BundlePlan {
+    pub id: String,
+    pub name: String,
+    pub flow_type: String,
+    pub category_id: String,
+    pub account_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact_id: Option<String>,
+    pub amount: f64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OptionBundle {
+    pub accounts: Vec<BundleAccount>,
+    pub categories: Vec<BundleCategory>,
+    pub contacts: Vec<BundleContact>,
+    /// Active recurring plans.
+    pub plans: Vec<BundlePlan>,
+}
+
+/// The bundle changes whenever any record in it does, so clients revalidate
+/// on every use and get a `304` while their copy is current.
+const BUNDLE_CACHE_CONTROL: &str = "private, no-cache";
+
+/// Strong ETag for a serialized bundle.
+fn bundle_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", HEXLOWER.encode(&digest[..16]))
+}
+
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag)
+        })
+}
+
+/// Accounts, categories, contacts and recurring plans of the active company
+/// in one compact payload, with an ETag so front-ends can keep their copy
+/// instead of querying each form's options.
+#[utoipa::path(
+    get,
+    path = "/api/options/bundle",
+    tag = "finance",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the bundle the client holds")
+    ),
+    responses(
+        (status = 200, description = "Every option of the active company, by name", body = OptionBundle),
+        (status = 304, description = "The bundle matching If-None-Match is still current"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn options_bundle_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let records = company_option_records(&state, &active_company, &session_user.account_access())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let bundle = OptionBundle {
+        accounts: records
+            .accounts
+            .into_iter()
+            .filter_map(|a| {
+                a.id.map(|id| BundleAccount {
+                    id: id.to_hex(),
+                    name: a.name,
+                    currency: a.currency,
+                    account_type: a.account_type.as_str().to_string(),
+                })
+            })
+            .collect(),
+        categories: records
+            .categories
+            .into_iter()
+            .filter_map(|c| {
+                c.id.map(|id| BundleCategory {
+                    id: id.to_hex(),
+                    name: c.name,
+                    flow_type: c.flow_type.as_str().to_string(),
+                    parent_id: c.parent_id.map(|p| p.to_hex()),
+                })
+            })
+            .collect(),
+        contacts: records
+            .contacts
+            .into_iter()
+            .filter_map(|c| {
+                c.id.map(|id| BundleContact {
+                    id: id.to_hex(),
+                    name: c.name,
+                    contact_type: c.contact_type.as_str().to_string(),
+                })
+            })
+            .collect(),
+        plans: records
+            .plans
+            .into_iter()
+            .filter_map(|p| {
+                p.id.map(|id| BundlePlan {
+                    id: id.to_hex(),
+                    name: p.name,
+                    flow_type: p.flow_type.as_str().to_string(),
+                    category_id: p.category_id.to_hex(),
+                    account_id: p.account_expected_id.to_hex(),
+                    contact_id: p.contact_id.map(|c| c.to_hex()),
+                    amount: p.amount_estimated,
+                })
+            })
+            .collect(),
+    };
+    let body = serde_json::to_vec(&bundle).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let etag = bundle_etag(&body);
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag.as_str()),
+                (header::CACHE_CONTROL, BUNDLE_CACHE_CONTROL),
+            ],
+        )
+            .into_response());
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::ETAG, etag.as_str()),
+            (header::CACHE_CONTROL, BUNDLE_CACHE_CONTROL),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -6,6 +6,7 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
@@ -14,11 +15,13 @@ use serde::{Deserialize, Serialize};
 use crate::filters;
 
 use crate::{
+    flash::Flash,
     models::{OrderItem, OrderStatus, PlannedStatus},
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
-        AppState, complete_order, confirm_order, create_order, delete_order, get_order_by_id,
-        get_planned_entry_by_id, list_orders, update_order,
+        AppState, company_default_currency, complete_order, confirm_order, create_order,
+        delete_order, get_order_by_id, get_planned_entry_by_id, list_orders, update_order,
     },
 };
 
@@ -29,6 +32,25 @@ use super::helpers::{
 use super::options::{account_options, category_options, contact_options};
 use crate::state::get_contact_by_id;
 
+/// Service orders.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/orders", get(orders_index).post(orders_create))
+        .route(
+            "/api/admin/orders",
+            get(orders_data_api).post(orders_create_api),
+        )
+        .route("/api/admin/orders/{id}", get(order_data_api))
+        .route("/api/admin/orders/{id}/update", post(order_update_api))
+        .route("/api/admin/orders/{id}/delete", post(order_delete_api))
+        .route("/api/admin/orders/{id}/complete", post(order_complete_api))
+        .route("/admin/orders/new", get(orders_new))
+        .route("/admin/orders/{id}/edit", get(orders_edit))
+        .route("/admin/orders/{id}/update", post(orders_update))
+        .route("/admin/orders/{id}/delete", post(orders_delete))
+        .route("/admin/orders/{id}/complete", post(orders_complete))
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -41,6 +63,7 @@ fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
 #[template(path = "admin/orders/index.html")]
 struct OrdersIndexTemplate {
     orders: Vec<OrderRow>,
+    currency: String,
 }
 
 struct OrderRow {
@@ -77,9 +100,15 @@ pub struct OrderData {
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct OrderPayload {
     pub title: String,
-    pub contact_id: Option<String>,
-    pub category_id: Option<String>,
-    pub account_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub account_id: Option<ObjectId>,
     #[serde(default = "default_pending")]
     pub status: String,
     pub amount: f64,
@@ -110,6 +139,9 @@ pub async fn orders_index(
     let orders = list_orders(&state, &company_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let currency = company_default_currency(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let mut rows = Vec::new();
     for o in orders {
@@ -129,7 +161,7 @@ pub async fn orders_index(
                 .await
                 .ok()
                 .flatten()
-                .map(|e| matches!(e.status, PlannedStatus::Covered | PlannedStatus::Cancelled))
+                .map(|e| e.status.is_covered() || e.status == PlannedStatus::Cancelled)
                 .unwrap_or(false);
             (pid.to_hex(), paid)
         } else {
@@ -149,7 +181,7 @@ pub async fn orders_index(
                     let ms = d.timestamp_millis();
                     let secs = ms / 1000;
                     let dt = chrono::DateTime::from_timestamp(secs, 0).unwrap_or_default();
-                    dt.format("%d/%m/%Y").to_string()
+                    dt.format("%Y-%m-%d").to_string()
                 })
                 .unwrap_or_default(),
             planned_entry_id,
@@ -157,7 +189,10 @@ pub async fn orders_index(
         });
     }
 
-    render(OrdersIndexTemplate { orders: rows })
+    render(OrdersIndexTemplate {
+        orders: rows,
+        currency,
+    })
 }
 
 #[utoipa::path(
@@ -266,12 +301,12 @@ fn parse_status_strict(s: &str) -> Result<OrderStatus, StatusCode> {
 #[derive(Deserialize)]
 pub struct OrderFormData {
     title: String,
-    #[serde(default)]
-    contact_id: Option<String>,
-    #[serde(default)]
-    category_id: Option<String>,
-    #[serde(default)]
-    account_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_id: Option<ObjectId>,
     #[serde(default = "default_pending")]
     status: String,
     amount: String,
@@ -314,11 +349,6 @@ fn parse_scheduled_at(s: &str) -> Option<bson::DateTime> {
     Some(bson::DateTime::from_millis(utc.timestamp_millis()))
 }
 
-fn parse_oid(s: Option<&str>) -> Option<ObjectId> {
-    s.filter(|s| !s.is_empty())
-        .and_then(|s| ObjectId::from_str(s).ok())
-}
-
 pub async fn orders_new(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
@@ -326,7 +356,8 @@ pub async fn orders_new(
     let company_id = require_admin_active(&session_user)?;
     let contacts = contact_options(&state, None, &company_id).await?;
     let categories = category_options(&state, None, &company_id).await?;
-    let accounts = account_options(&state, None, &company_id).await?;
+    let accounts =
+        account_options(&state, None, &company_id, &session_user.account_access()).await?;
 
     render(OrderFormTemplate {
         action: "/admin/orders".into(),
@@ -356,9 +387,9 @@ pub async fn orders_create(
     };
 
     let amount: f64 = form.amount.trim().parse().unwrap_or(0.0);
-    let contact_id = parse_oid(form.contact_id.as_deref());
-    let category_id = parse_oid(form.category_id.as_deref());
-    let account_id = parse_oid(form.account_id.as_deref());
+    let contact_id = form.contact_id;
+    let category_id = form.category_id;
+    let account_id = form.account_id;
     let scheduled_at = form
         .scheduled_at
         .as_deref()
@@ -392,7 +423,11 @@ pub async fn orders_create(
         }
     }
 
-    Redirect::to("/admin/orders").into_response()
+    (
+        Flash::success("Orden creada."),
+        Redirect::to("/admin/orders"),
+    )
+        .into_response()
 }
 
 #[utoipa::path(
@@ -476,7 +511,13 @@ pub async fn orders_edit(
 
     let contacts = contact_options(&state, order.contact_id.as_ref(), &company_id).await?;
     let categories = category_options(&state, order.category_id.as_ref(), &company_id).await?;
-    let accounts = account_options(&state, order.account_id.as_ref(), &company_id).await?;
+    let accounts = account_options(
+        &state,
+        order.account_id.as_ref(),
+        &company_id,
+        &session_user.account_access(),
+    )
+    .await?;
 
     let items_json = serde_json::to_string(
         &order
@@ -547,9 +588,9 @@ pub async fn orders_update(
     };
 
     let amount: f64 = form.amount.trim().parse().unwrap_or(0.0);
-    let contact_id = parse_oid(form.contact_id.as_deref());
-    let category_id = parse_oid(form.category_id.as_deref());
-    let account_id = parse_oid(form.account_id.as_deref());
+    let contact_id = form.contact_id;
+    let category_id = form.category_id;
+    let account_id = form.account_id;
     let scheduled_at = form
         .scheduled_at
         .as_deref()
@@ -587,7 +628,11 @@ pub async fn orders_update(
         }
     }
 
-    Redirect::to("/admin/orders").into_response()
+    (
+        Flash::success("Orden actualizada."),
+        Redirect::to("/admin/orders"),
+    )
+        .into_response()
 }
 
 #[utoipa::path(
@@ -698,8 +743,16 @@ pub async fn orders_delete(
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
     match delete_order(&state, &oid).await {
-        Ok(_) => Redirect::to("/admin/orders").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Orden eliminada."),
+            Redirect::to("/admin/orders"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar la orden."),
+            Redirect::to("/admin/orders"),
+        )
+            .into_response(),
     }
 }
 
@@ -772,7 +825,11 @@ pub async fn orders_complete(
     }
 
     match complete_order(&state, &oid).await {
-        Ok(_) => Redirect::to("/admin/orders").into_response(),
+        Ok(_) => (
+            Flash::success("Orden completada."),
+            Redirect::to("/admin/orders"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -839,9 +896,9 @@ async fn parse_order_payload(
     if title.is_empty() || payload.amount < 0.0 {
         return Err(StatusCode::BAD_REQUEST);
     }
-    let contact_id = parse_optional_object_id(payload.contact_id)?;
-    let category_id = parse_optional_object_id(payload.category_id)?;
-    let account_id = parse_optional_object_id(payload.account_id)?;
+    let contact_id = payload.contact_id;
+    let category_id = payload.category_id;
+    let account_id = payload.account_id;
     validate_company_refs(
         state,
         company_id,
@@ -875,15 +932,6 @@ async fn parse_order_payload(
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, StatusCode> {
-    match clean_opt(value) {
-        Some(value) => ObjectId::from_str(&value)
-            .map(Some)
-            .map_err(|_| StatusCode::BAD_REQUEST),
-        None => Ok(None),
-    }
-}
-
 fn order_data(order: crate::models::ServiceOrder) -> Option<OrderData> {
     let id = order.id?.to_hex();
     Some(OrderData {
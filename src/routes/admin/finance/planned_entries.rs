@@ -1,4 +1,4 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use askama::Template;
 use axum::{
@@ -6,30 +6,123 @@ use axum::{
     extract::{Form, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
-    models::PlannedEntry,
+    flash::Flash,
+    models::{AmountRevision, CommentEntity, FlowType, PlannedEntry, PlannedStatus},
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
-        AppState, create_planned_entry, delete_planned_entry, get_planned_entry_by_id,
-        get_project_by_id_for_company, list_planned_entries, list_projects,
-        pay_planned_entry_with_project, update_planned_entry, update_planned_entry_project_links,
+        AccountAccess, AppState, check_planned_status_change, contact_due_date, coverage_excess,
+        create_coverage_adjustment, create_planned_entry, delete_planned_entry,
+        detach_transaction_from_planned_entry, get_category_by_id, get_planned_entry_by_id,
+        get_project_by_id_for_company, get_recurring_plan_by_id, get_transaction_by_id,
+        list_planned_entries, list_projects, pay_planned_entry_with_project,
+        planned_entry_covered_amount, planned_entry_transactions, roll_forward_due_date,
+        roll_forward_planned_entry, set_planned_entry_exchange_rate, set_planned_entry_status,
+        update_planned_entry, update_planned_entry_project_links, utc_day_start,
     },
 };
 
+use super::comments::{CommentThread, comment_thread};
+use super::deposit_allocations::{DepositPanel, deposit_panel};
 use super::helpers::*;
 use super::options::{account_options, category_options, contact_options, recurring_plan_options};
+use super::totals::{CurrencyResolver, IndexTotals};
+
+/// Planned entries, their payments and roll-forward.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/planned_entries",
+            get(planned_entries_index).post(planned_entries_create),
+        )
+        .route(
+            "/api/admin/planned-entries",
+            get(planned_entries_data_api).post(planned_entries_create_api),
+        )
+        .route(
+            "/api/admin/planned-entries/bulk-pay",
+            post(planned_entries_bulk_pay_api),
+        )
+        .route(
+            "/api/admin/planned-entries/roll-forward",
+            post(planned_entries_roll_forward_api),
+        )
+        .route(
+            "/api/admin/planned-entries/{id}",
+            get(planned_entry_data_api),
+        )
+        .route(
+            "/api/admin/planned-entries/{id}/update",
+            post(planned_entry_update_api),
+        )
+        .route(
+            "/api/admin/planned-entries/{id}/delete",
+            post(planned_entry_delete_api),
+        )
+        .route(
+            "/api/admin/planned-entries/{id}/pay",
+            post(planned_entry_pay_api),
+        )
+        .route("/admin/planned_entries/new", get(planned_entries_new))
+        .route(
+            "/admin/planned_entries/bulk_pay",
+            get(planned_entries_bulk_pay_form).post(planned_entries_bulk_pay),
+        )
+        .route(
+            "/admin/planned_entries/roll_forward",
+            post(planned_entries_bulk_roll_forward),
+        )
+        .route(
+            "/admin/planned_entries/{id}/edit",
+            get(planned_entries_edit),
+        )
+        .route(
+            "/admin/planned_entries/{id}/update",
+            post(planned_entries_update),
+        )
+        .route(
+            "/admin/planned_entries/{id}/delete",
+            post(planned_entries_delete),
+        )
+        .route(
+            "/admin/planned_entries/{id}/status",
+            post(planned_entries_status),
+        )
+        .route(
+            "/admin/planned_entries/{id}/roll_forward",
+            post(planned_entries_roll_forward),
+        )
+        .route(
+            "/admin/planned_entries/{id}/adjust",
+            post(planned_entries_adjust),
+        )
+        .route(
+            "/admin/planned_entries/{id}/transactions/{transaction_id}/detach",
+            post(planned_entries_detach_transaction),
+        )
+        .route(
+            "/admin/planned_entries/{id}/pay",
+            get(planned_entries_pay_form).post(planned_entries_pay),
+        )
+}
 
 #[derive(Template)]
 #[template(path = "admin/planned_entries/index.html")]
 struct PlannedEntriesIndexTemplate {
     entries: Vec<PlannedEntryRow>,
+    /// Of the entries that are not cancelled.
+    totals: IndexTotals,
+    /// Why the last quick status change was rejected.
+    errors: Option<String>,
 }
 
 struct PlannedEntryRow {
@@ -39,8 +132,17 @@ struct PlannedEntryRow {
     flow_type: String,
     amount: f64,
     original_amount: f64,
+    currency: String,
     status: String,
     status_label: String,
+    /// Version of the recurring plan that generated the entry, with the link
+    /// to that version in the plan history.
+    plan_version: Option<(i32, String)>,
+    /// Open and due before today.
+    can_roll_forward: bool,
+    /// Rolling forward without days goes to the plan's next date.
+    has_plan: bool,
+    slip_count: i32,
 }
 
 #[derive(Serialize)]
@@ -67,7 +169,29 @@ pub struct PlannedEntryData {
     pub notes: Option<String>,
     pub cfdi_uuid: Option<String>,
     pub currency: Option<String>,
+    /// Booked units of the company currency per unit of the account's.
+    pub exchange_rate: Option<f64>,
     pub cfdi_folio: Option<String>,
+    /// Times the entry was rolled forward after missing its due date.
+    pub slip_count: i32,
+    /// Hand edits of `amount_estimated`, oldest first.
+    pub amount_revisions: Vec<AmountRevisionRow>,
+}
+
+#[derive(Serialize)]
+pub struct AmountRevisionRow {
+    pub changed_at: String,
+    pub username: String,
+    pub previous_amount: f64,
+    pub amount: f64,
+}
+
+/// Amount changes of the edit page: the figure the plan generated, how far
+/// the current amount is from it, and the edits newest first.
+struct AmountHistory {
+    planned_amount: f64,
+    variance: f64,
+    revisions: Vec<AmountRevisionRow>,
 }
 
 #[derive(Template)]
@@ -91,6 +215,18 @@ struct PlannedEntryFormTemplate {
     recurring_plan_version: String,
     is_edit: bool,
     errors: Option<String>,
+    /// Only shown when editing.
+    coverage: Option<CoverageBreakdown>,
+    /// Only shown when editing an income.
+    deposits: Option<DepositPanel>,
+    /// Only shown when editing.
+    comments: Option<CommentThread>,
+    /// Only shown when editing an entry whose amount was changed by hand.
+    amount_history: Option<AmountHistory>,
+    /// Booked rate; empty when the entry is in the company currency.
+    exchange_rate: String,
+    /// Of the amounts in the editing sections.
+    currency: String,
 }
 
 #[derive(Deserialize)]
@@ -100,19 +236,21 @@ pub struct PlannedEntryFormData {
     flow_type: String,
     category_id: String,
     account_expected_id: String,
-    #[serde(default)]
-    contact_id: Option<String>,
-    #[serde(default)]
-    project_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    project_id: Option<ObjectId>,
     amount_estimated: String,
     due_date: String,
     status: String,
-    #[serde(default)]
-    recurring_plan_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    recurring_plan_id: Option<ObjectId>,
     #[serde(default)]
     recurring_plan_version: Option<String>,
     #[serde(default)]
     notes: Option<String>,
+    #[serde(default)]
+    exchange_rate: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -121,14 +259,27 @@ pub struct PlannedEntryPayload {
     pub flow_type: String,
     pub category_id: String,
     pub account_expected_id: String,
-    pub contact_id: Option<String>,
-    pub project_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
     pub amount_estimated: f64,
+    /// RFC3339. When empty, today plus the contact's payment terms.
+    #[serde(default)]
     pub due_date: String,
     pub status: String,
-    pub recurring_plan_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub recurring_plan_id: Option<ObjectId>,
     pub recurring_plan_version: Option<i32>,
     pub notes: Option<String>,
+    /// Units of the company currency per unit of the account's at booking,
+    /// which collections are compared against. When omitted on update the
+    /// stored rate is kept.
+    #[serde(default)]
+    pub exchange_rate: Option<f64>,
 }
 
 struct ParsedPlannedEntryPayload {
@@ -144,6 +295,7 @@ struct ParsedPlannedEntryPayload {
     recurring_plan_id: Option<ObjectId>,
     recurring_plan_version: Option<i32>,
     notes: Option<String>,
+    exchange_rate: Option<f64>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -151,8 +303,12 @@ pub struct PlannedEntryPayPayload {
     pub paid_at: String,
     pub amount: f64,
     pub account_id: String,
-    pub project_id: Option<String>,
-    pub parent_planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub parent_planned_entry_id: Option<ObjectId>,
     pub notes: Option<String>,
 }
 
@@ -161,27 +317,64 @@ pub struct PlannedEntryBulkPayPayload {
     pub entry_ids: Vec<String>,
     pub paid_at: String,
     pub account_id: String,
-    pub project_id: Option<String>,
-    pub parent_planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub parent_planned_entry_id: Option<ObjectId>,
     pub notes: Option<String>,
 }
 
 pub async fn planned_entries_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
-    let entries = list_planned_entries(&state)
+    render_index(&state, &session_user, &active_company, &params, None).await
+}
+
+async fn render_index(
+    state: &AppState,
+    session_user: &SessionUser,
+    active_company: &ObjectId,
+    params: &HashMap<String, String>,
+    errors: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let access = session_user.account_access();
+    let entries: Vec<PlannedEntry> = list_planned_entries(state)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|e| &e.company_id == active_company)
+        .filter(|e| access.allows(&e.account_expected_id))
+        .collect();
     let active_name = session_user.user().company_name.clone();
+    let today = utc_day_start(DateTime::now());
+
+    let currencies = CurrencyResolver::load(state, active_company).await?;
+    let totals = IndexTotals::build(
+        &currencies.base,
+        entries
+            .iter()
+            .filter(|e| e.status != PlannedStatus::Cancelled)
+            .map(|e| {
+                (
+                    currencies.resolve(e.currency.as_deref(), Some(&e.account_expected_id)),
+                    e.flow_type.clone(),
+                    e.amount_estimated,
+                )
+            }),
+        params,
+    );
 
     let rows = entries
         .into_iter()
-        .filter(|e| e.company_id == active_company)
         .filter_map(|e| {
             e.id.map(|id| PlannedEntryRow {
                 id: id.to_hex(),
+                currency: currencies.resolve(e.currency.as_deref(), Some(&e.account_expected_id)),
                 name: e.name,
                 company: active_name.clone(),
                 flow_type: flow_type_value(&e.flow_type).to_string(),
@@ -189,11 +382,35 @@ pub async fn planned_entries_index(
                 original_amount: e.original_amount_estimated.unwrap_or(0.0),
                 status: planned_status_value(&e.status).to_string(),
                 status_label: planned_status_label(&e.status).to_string(),
+                plan_version: e.recurring_plan_id.zip(e.recurring_plan_version).map(
+                    |(plan_id, version)| {
+                        (
+                            version,
+                            format!(
+                                "/admin/recurring_plans/{}/versions#v{}",
+                                plan_id.to_hex(),
+                                version
+                            ),
+                        )
+                    },
+                ),
+                can_roll_forward: matches!(
+                    e.status,
+                    PlannedStatus::Planned
+                        | PlannedStatus::PartiallyCovered
+                        | PlannedStatus::Overdue
+                ) && e.due_date < today,
+                has_plan: e.recurring_plan_id.is_some(),
+                slip_count: e.slip_count,
             })
         })
         .collect();
 
-    render(PlannedEntriesIndexTemplate { entries: rows })
+    render(PlannedEntriesIndexTemplate {
+        entries: rows,
+        totals,
+        errors,
+    })
 }
 
 #[utoipa::path(
@@ -213,6 +430,7 @@ pub async fn planned_entries_data_api(
 ) -> Result<Json<Vec<PlannedEntryData>>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let active_name = session_user.user().company_name.clone();
+    let access = session_user.account_access();
     let entries = list_planned_entries(&state)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -220,6 +438,7 @@ pub async fn planned_entries_data_api(
     let rows = entries
         .into_iter()
         .filter(|entry| entry.company_id == active_company)
+        .filter(|entry| access.allows(&entry.account_expected_id))
         .filter_map(|entry| planned_entry_data(entry, active_name.clone()))
         .collect();
 
@@ -251,6 +470,7 @@ pub async fn planned_entry_data_api(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&entry.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&entry.account_expected_id])?;
 
     planned_entry_data(entry, session_user.user().company_name.clone())
         .map(Json)
@@ -283,6 +503,9 @@ pub async fn planned_entries_create_api(
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&parsed.account_expected_id]) {
+        return status.into_response();
+    }
 
     match create_planned_entry(
         &state,
@@ -317,6 +540,13 @@ pub async fn planned_entries_create_api(
                     return StatusCode::INTERNAL_SERVER_ERROR.into_response();
                 }
             }
+            if parsed.exchange_rate.is_some()
+                && set_planned_entry_exchange_rate(&state, &id, &company_id, parsed.exchange_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             (
                 StatusCode::CREATED,
                 Json(serde_json::json!({
@@ -360,7 +590,8 @@ pub async fn planned_entry_update_api(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
     if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
-        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id),
+        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id)
+            .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
@@ -370,6 +601,9 @@ pub async fn planned_entry_update_api(
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&parsed.account_expected_id]) {
+        return status.into_response();
+    }
 
     match update_planned_entry(
         &state,
@@ -386,6 +620,8 @@ pub async fn planned_entry_update_api(
         parsed.due_date,
         parsed.status,
         parsed.notes,
+        Some(session_user.user().id),
+        &session_user.user().username,
     )
     .await
     {
@@ -402,6 +638,18 @@ pub async fn planned_entry_update_api(
             {
                 return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
+            if parsed.exchange_rate.is_some()
+                && set_planned_entry_exchange_rate(
+                    &state,
+                    &object_id,
+                    &company_id,
+                    parsed.exchange_rate,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             Json(serde_json::json!({
                 "ok": true,
                 "side_effects": { "planned_entry_recalculated": object_id.to_hex() }
@@ -439,7 +687,8 @@ pub async fn planned_entry_delete_api(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
     if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
-        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id),
+        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id)
+            .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
@@ -452,29 +701,101 @@ pub async fn planned_entry_delete_api(
     }
 }
 
+/// Values a link to the new-entry form comes prefilled with. The references
+/// are checked against the active company.
+#[derive(Deserialize, Default)]
+pub struct PlannedEntryNewQuery {
+    #[serde(default)]
+    name: Option<String>,
+    /// `income` or `expense`; defaults to the category's.
+    #[serde(default)]
+    flow_type: Option<String>,
+    #[serde(default)]
+    amount: Option<String>,
+    /// `YYYY-MM-DD` or RFC 3339.
+    #[serde(default)]
+    due_date: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_expected_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    project_id: Option<ObjectId>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
 pub async fn planned_entries_new(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<PlannedEntryNewQuery>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
 
+    validate_company_refs(
+        &state,
+        &active_company,
+        query.category_id.as_ref(),
+        query.account_expected_id.as_ref(),
+        query.contact_id.as_ref(),
+    )
+    .await?;
+    ensure_account_access(&session_user, query.account_expected_id.iter())?;
+    let project_id = validate_project_id(&state, &active_company, query.project_id).await?;
+    let flow_type = match clean_opt(query.flow_type) {
+        Some(value) => parse_flow_type(&value).map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => match query.category_id.as_ref() {
+            Some(id) => get_category_by_id(&state, id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .map_or(FlowType::Expense, |category| category.flow_type),
+            None => FlowType::Expense,
+        },
+    };
+    let amount_estimated = match clean_opt(query.amount) {
+        Some(value) => {
+            let value = parse_f64_field(&value, "Monto").map_err(|_| StatusCode::BAD_REQUEST)?;
+            format!("{value:.2}")
+        }
+        None => "0".into(),
+    };
+    let due_date = match clean_opt(query.due_date) {
+        Some(value) => {
+            let value = match parse_date_field(&value) {
+                Some(value) => value,
+                None => parse_datetime_field(&value, "Fecha de vencimiento")
+                    .map_err(|_| StatusCode::BAD_REQUEST)?,
+            };
+            datetime_to_string(&value)
+        }
+        None => String::new(),
+    };
+
     let companies = company_options(&state, &active_company).await?;
-    let categories = category_options(&state, None, &active_company).await?;
-    let accounts = account_options(&state, None, &active_company).await?;
-    let contacts = contact_options(&state, None, &active_company).await?;
-    let projects = project_options(&state, &active_company, None).await?;
+    let categories = category_options(&state, query.category_id.as_ref(), &active_company).await?;
+    let accounts = account_options(
+        &state,
+        query.account_expected_id.as_ref(),
+        &active_company,
+        &session_user.account_access(),
+    )
+    .await?;
+    let contacts = contact_options(&state, query.contact_id.as_ref(), &active_company).await?;
+    let projects = project_options(&state, &active_company, project_id.as_ref()).await?;
     let recurring_plans = recurring_plan_options(&state, None, &active_company).await?;
 
     render(PlannedEntryFormTemplate {
         action: "/admin/planned_entries".into(),
-        name: String::new(),
-        flow_type: "expense".into(),
-        amount_estimated: "0".into(),
-        due_date: String::new(),
+        name: clean_opt(query.name).unwrap_or_default(),
+        flow_type: flow_type.as_str().into(),
+        amount_estimated,
+        due_date,
         status: "planned".into(),
-        notes: String::new(),
+        notes: clean_opt(query.notes).unwrap_or_default(),
         companies,
-        flow_options: flow_options("expense"),
+        flow_options: flow_options(flow_type.as_str()),
         status_options: planned_status_options("planned"),
         categories,
         accounts,
@@ -484,6 +805,12 @@ pub async fn planned_entries_new(
         recurring_plan_version: String::new(),
         is_edit: false,
         errors: None,
+        coverage: None,
+        deposits: None,
+        comments: None,
+        amount_history: None,
+        exchange_rate: String::new(),
+        currency: String::new(),
     })
 }
 
@@ -511,36 +838,12 @@ pub async fn planned_entries_create(
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&account_expected_id]) {
+        return status.into_response();
+    }
 
-    let contact_id = match form.contact_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(cid) => match parse_object_id(&cid, "Contacto") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
-
-    let recurring_plan_id = match form.recurring_plan_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(rpid) => match parse_object_id(&rpid, "Plan recurrente") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
+    let contact_id = form.contact_id;
+    let recurring_plan_id = form.recurring_plan_id;
 
     let recurring_plan_version = match form
         .recurring_plan_version
@@ -558,18 +861,16 @@ pub async fn planned_entries_create(
         Ok(v) => v,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
-    let due_date = match parse_datetime_field(&form.due_date, "Fecha de vencimiento") {
-        Ok(dt) => dt,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
     let status = match parse_planned_status(&form.status) {
         Ok(s) => s,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
     let notes = clean_opt(form.notes);
-    let project_id =
-        parse_optional_project_id(&state, &company_id, form.project_id.as_deref()).await;
-    let project_id = match project_id {
+    let exchange_rate = match parse_exchange_rate(form.exchange_rate) {
+        Ok(rate) => rate,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let project_id = match validate_project_id(&state, &company_id, form.project_id).await {
         Ok(project_id) => project_id,
         Err(status) => return status.into_response(),
     };
@@ -585,6 +886,10 @@ pub async fn planned_entries_create(
     {
         return status.into_response();
     }
+    let due_date = match due_date_or_terms(&state, &form.due_date, contact_id.as_ref()).await {
+        Ok(dt) => dt,
+        Err(status) => return status.into_response(),
+    };
 
     if let Some(ref plan_id) = recurring_plan_id {
         if let Err(status) = validate_recurring_plan_company(&state, plan_id, &company_id).await {
@@ -621,7 +926,23 @@ pub async fn planned_entries_create(
                 )
                 .await;
             }
-            Redirect::to("/admin/planned_entries").into_response()
+            if exchange_rate.is_some()
+                && set_planned_entry_exchange_rate(
+                    &state,
+                    &planned_entry_id,
+                    &company_id,
+                    exchange_rate,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            (
+                Flash::success("Compromiso creado."),
+                Redirect::to("/admin/planned_entries"),
+            )
+                .into_response()
         }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
@@ -640,15 +961,34 @@ pub async fn planned_entries_edit(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&entry.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&entry.account_expected_id])?;
 
     let companies = company_options(&state, &active_company).await?;
     let categories = category_options(&state, Some(&entry.category_id), &active_company).await?;
-    let accounts =
-        account_options(&state, Some(&entry.account_expected_id), &active_company).await?;
+    let accounts = account_options(
+        &state,
+        Some(&entry.account_expected_id),
+        &active_company,
+        &session_user.account_access(),
+    )
+    .await?;
     let contacts = contact_options(&state, entry.contact_id.as_ref(), &active_company).await?;
     let projects = project_options(&state, &active_company, entry.project_id.as_ref()).await?;
     let recurring_plans =
         recurring_plan_options(&state, entry.recurring_plan_id.as_ref(), &active_company).await?;
+    let coverage = coverage_breakdown(&state, &entry, &object_id).await?;
+    let deposits = deposit_panel(&state, &session_user, &entry).await?;
+    let comments = comment_thread(
+        &state,
+        CommentEntity::PlannedEntry,
+        &object_id,
+        &session_user,
+    )
+    .await?;
+    let amount_history = amount_history(&entry);
+    let currency = CurrencyResolver::load(&state, &active_company)
+        .await?
+        .resolve(entry.currency.as_deref(), Some(&entry.account_expected_id));
 
     render(PlannedEntryFormTemplate {
         action: format!("/admin/planned_entries/{}/update", id),
@@ -672,159 +1012,590 @@ pub async fn planned_entries_edit(
             .unwrap_or_default(),
         is_edit: true,
         errors: None,
+        coverage: Some(coverage),
+        deposits,
+        comments: Some(comments),
+        amount_history,
+        exchange_rate: exchange_rate_value(entry.exchange_rate),
+        currency,
+    })
+}
+
+pub async fn planned_entries_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<PlannedEntryFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
+        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id)
+            .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id])),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    } {
+        return status.into_response();
+    }
+    let flow_type = match parse_flow_type(&form.flow_type) {
+        Ok(v) => v,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let category_id = match parse_object_id(&form.category_id, "Categoría") {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let account_expected_id = match parse_object_id(&form.account_expected_id, "Cuenta esperada") {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    if let Err(status) = ensure_account_access(&session_user, [&account_expected_id]) {
+        return status.into_response();
+    }
+
+    let contact_id = form.contact_id;
+    let recurring_plan_id = form.recurring_plan_id;
+
+    let recurring_plan_version = match form
+        .recurring_plan_version
+        .clone()
+        .and_then(|v| if v.trim().is_empty() { None } else { Some(v) })
+    {
+        Some(v) => match parse_i32_field(&v, "Versión del plan") {
+            Ok(num) => Some(num),
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        },
+        None => None,
+    };
+
+    let amount_estimated = match parse_f64_field(&form.amount_estimated, "Monto estimado") {
+        Ok(v) => v,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let status_enum = match parse_planned_status(&form.status) {
+        Ok(s) => s,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let notes = clean_opt(form.notes);
+    let exchange_rate = match parse_exchange_rate(form.exchange_rate) {
+        Ok(rate) => rate,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let project_id = match validate_project_id(&state, &company_id, form.project_id).await {
+        Ok(project_id) => project_id,
+        Err(status) => return status.into_response(),
+    };
+
+    if let Err(status) = validate_company_refs(
+        &state,
+        &company_id,
+        Some(&category_id),
+        Some(&account_expected_id),
+        contact_id.as_ref(),
+    )
+    .await
+    {
+        return status.into_response();
+    }
+    let due_date = match due_date_or_terms(&state, &form.due_date, contact_id.as_ref()).await {
+        Ok(dt) => dt,
+        Err(status) => return status.into_response(),
+    };
+
+    if let Some(ref plan_id) = recurring_plan_id {
+        if let Err(status) = validate_recurring_plan_company(&state, plan_id, &company_id).await {
+            return status.into_response();
+        }
+    }
+
+    match update_planned_entry(
+        &state,
+        &object_id,
+        &company_id,
+        recurring_plan_id,
+        recurring_plan_version,
+        form.name.trim(),
+        flow_type,
+        &category_id,
+        &account_expected_id,
+        contact_id,
+        amount_estimated,
+        due_date,
+        status_enum,
+        notes,
+        Some(session_user.user().id),
+        &session_user.user().username,
+    )
+    .await
+    {
+        Ok(_) => {
+            let _ = update_planned_entry_project_links(
+                &state,
+                &object_id,
+                &company_id,
+                project_id,
+                None,
+            )
+            .await;
+            // An empty rate clears the booked one.
+            if set_planned_entry_exchange_rate(&state, &object_id, &company_id, exchange_rate)
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            (
+                Flash::success("Compromiso actualizado."),
+                Redirect::to("/admin/planned_entries"),
+            )
+                .into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn planned_entries_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
+        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &active_company)
+            .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id])),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    } {
+        return status.into_response();
+    }
+
+    match delete_planned_entry(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Compromiso eliminado."),
+            Redirect::to("/admin/planned_entries"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el compromiso."),
+            Redirect::to("/admin/planned_entries"),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PlannedEntryStatusForm {
+    status: String,
+}
+
+/// Quick status change from the list. Rejected changes render the list again
+/// with the reason; see [`check_planned_status_change`] for what is allowed.
+pub async fn planned_entries_status(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<PlannedEntryStatusForm>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let target = match parse_planned_status(&form.status) {
+        Ok(status) => status,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let entry = match get_planned_entry_by_id(&state, &object_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&entry.company_id, &active_company)
+        .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id]))
+    {
+        return status.into_response();
+    }
+
+    let covered_amount = match planned_entry_covered_amount(&state, &object_id).await {
+        Ok(amount) => amount,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(message) = check_planned_status_change(&entry, covered_amount, &target) {
+        return match render_index(
+            &state,
+            &session_user,
+            &active_company,
+            &HashMap::new(),
+            Some(message),
+        )
+        .await
+        {
+            Ok(html) => (StatusCode::CONFLICT, html).into_response(),
+            Err(status) => status.into_response(),
+        };
+    }
+
+    match set_planned_entry_status(&state, &object_id, &active_company, target).await {
+        Ok(_) => (
+            Flash::success("Estado del compromiso actualizado."),
+            Redirect::to("/admin/planned_entries"),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// ── Roll forward ───────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct RollForwardForm {
+    #[serde(default)]
+    days: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkRollForwardForm {
+    ids: String,
+    #[serde(default)]
+    days: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PlannedEntryRollForwardPayload {
+    pub entry_ids: Vec<String>,
+    /// Days from today; without them each entry moves to the next date of its
+    /// recurring plan.
+    pub days: Option<u32>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RolledEntryData {
+    pub id: String,
+    pub due_date: String,
+    pub slip_count: i32,
+}
+
+fn parse_roll_forward_days(value: Option<String>) -> Result<Option<u32>, String> {
+    clean_opt(value)
+        .map(|days| {
+            days.parse::<u32>()
+                .map_err(|_| "Los días deben ser un número entero".to_string())
+        })
+        .transpose()
+}
+
+/// New due date of every entry, all checked before any is moved. The outer
+/// error refuses the request; the inner one says why an entry cannot move.
+async fn roll_forward_targets(
+    state: &AppState,
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+    entry_ids: &[ObjectId],
+    days: Option<u32>,
+) -> Result<Result<Vec<(ObjectId, DateTime)>, String>, StatusCode> {
+    let now = chrono::Utc::now();
+    let mut targets = Vec::with_capacity(entry_ids.len());
+    for entry_id in entry_ids {
+        let entry = get_planned_entry_by_id(state, entry_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        ensure_same_company(&entry.company_id, company_id)?;
+        ensure_account_access(session_user, [&entry.account_expected_id])?;
+        let plan = match entry.recurring_plan_id {
+            Some(plan_id) if days.is_none() => get_recurring_plan_by_id(state, &plan_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            _ => None,
+        };
+        match roll_forward_due_date(&entry, plan.as_ref(), days, now) {
+            Ok(due_date) => targets.push((*entry_id, due_date)),
+            Err(message) => return Ok(Err(message)),
+        }
+    }
+    Ok(Ok(targets))
+}
+
+/// Moves the entries and returns their slip counts, in order.
+async fn apply_roll_forward(
+    state: &AppState,
+    company_id: &ObjectId,
+    targets: &[(ObjectId, DateTime)],
+) -> anyhow::Result<Vec<i32>> {
+    let mut slips = Vec::with_capacity(targets.len());
+    for (entry_id, due_date) in targets {
+        slips.push(roll_forward_planned_entry(state, entry_id, company_id, *due_date).await?);
+    }
+    Ok(slips)
+}
+
+/// Shared by the single and bulk forms: moves the entries or renders the list
+/// again with the reason none was moved.
+async fn roll_forward_from_form(
+    state: &AppState,
+    session_user: &SessionUser,
+    entry_ids: &[ObjectId],
+    days: Option<String>,
+) -> axum::response::Response {
+    let company_id = match require_admin_active(session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let targets = match parse_roll_forward_days(days) {
+        Ok(days) => {
+            match roll_forward_targets(state, session_user, &company_id, entry_ids, days).await {
+                Ok(targets) => targets,
+                Err(status) => return status.into_response(),
+            }
+        }
+        Err(message) => Err(message),
+    };
+    let targets = match targets {
+        Ok(targets) => targets,
+        Err(message) => {
+            return match render_index(
+                state,
+                session_user,
+                &company_id,
+                &HashMap::new(),
+                Some(message),
+            )
+            .await
+            {
+                Ok(html) => (StatusCode::CONFLICT, html).into_response(),
+                Err(status) => status.into_response(),
+            };
+        }
+    };
+
+    match apply_roll_forward(state, &company_id, &targets).await {
+        Ok(_) => (
+            Flash::success("Compromiso movido al siguiente periodo."),
+            Redirect::to("/admin/planned_entries"),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Rolls an overdue entry forward from the list, to the next date of its
+/// plan or by the days given. See [`roll_forward_due_date`].
+pub async fn planned_entries_roll_forward(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<RollForwardForm>,
+) -> impl IntoResponse {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    roll_forward_from_form(&state, &session_user, &[object_id], form.days).await
+}
+
+/// Rolls the entries checked in the list forward; none moves unless all can.
+pub async fn planned_entries_bulk_roll_forward(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<BulkRollForwardForm>,
+) -> impl IntoResponse {
+    let Ok(entry_ids) = parse_entry_ids(&form.ids) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    roll_forward_from_form(&state, &session_user, &entry_ids, form.days).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/planned-entries/roll-forward",
+    tag = "finance",
+    request_body = PlannedEntryRollForwardPayload,
+    responses(
+        (status = 200, description = "Entries rolled forward", body = [RolledEntryData]),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 409, description = "An entry is not overdue or has no next date; none moved")
+    ),
+    security(("session" = []))
+)]
+pub async fn planned_entries_roll_forward_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<PlannedEntryRollForwardPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let entry_ids = match parse_entry_ids(&payload.entry_ids.join(",")) {
+        Ok(ids) => ids,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let targets =
+        match roll_forward_targets(&state, &session_user, &company_id, &entry_ids, payload.days)
+            .await
+        {
+            Ok(Ok(targets)) => targets,
+            Ok(Err(message)) => {
+                return (
+                    StatusCode::CONFLICT,
+                    Json(serde_json::json!({ "error": message })),
+                )
+                    .into_response();
+            }
+            Err(status) => return status.into_response(),
+        };
+
+    match apply_roll_forward(&state, &company_id, &targets).await {
+        Ok(slips) => Json(
+            targets
+                .iter()
+                .zip(slips)
+                .map(|((id, due_date), slip_count)| RolledEntryData {
+                    id: id.to_hex(),
+                    due_date: datetime_to_string(due_date),
+                    slip_count,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// ── Coverage ───────────────────────────────────────────────────────────────
+
+/// Payments linked to an entry, listed on its edit page.
+struct CoverageBreakdown {
+    entry_id: String,
+    transactions: Vec<CoverageRow>,
+    /// Sum of the confirmed payments.
+    covered: f64,
+    remaining: f64,
+    /// Paid above the amount and not moved to an adjustment entry yet.
+    excess: f64,
+    /// Entry holding the excess already moved out, and how much it was.
+    adjustment: Option<(String, f64)>,
+}
+
+struct CoverageRow {
+    id: String,
+    date: String,
+    description: String,
+    amount: f64,
+    is_confirmed: bool,
+}
+
+async fn coverage_breakdown(
+    state: &AppState,
+    entry: &PlannedEntry,
+    id: &ObjectId,
+) -> Result<CoverageBreakdown, StatusCode> {
+    let transactions = planned_entry_transactions(state, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let covered: f64 = transactions
+        .iter()
+        .filter(|tx| tx.is_confirmed)
+        .map(|tx| tx.amount)
+        .sum();
+    Ok(CoverageBreakdown {
+        entry_id: id.to_hex(),
+        transactions: transactions
+            .into_iter()
+            .map(|tx| CoverageRow {
+                id: tx.id.map(|id| id.to_hex()).unwrap_or_default(),
+                date: datetime_to_string(&tx.date),
+                description: tx.description,
+                amount: tx.amount,
+                is_confirmed: tx.is_confirmed,
+            })
+            .collect(),
+        covered,
+        remaining: (entry.amount_estimated - covered).max(0.0),
+        excess: coverage_excess(entry, covered),
+        adjustment: entry
+            .coverage_adjustment
+            .as_ref()
+            .map(|adjustment| (adjustment.entry_id.to_hex(), adjustment.amount)),
     })
 }
 
-pub async fn planned_entries_update(
+/// Unlinks one of the entry's payments, from the breakdown on its edit page.
+/// The transaction itself stays.
+pub async fn planned_entries_detach_transaction(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
-    Path(id): Path<String>,
-    Form(form): Form<PlannedEntryFormData>,
+    Path((id, transaction_id)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    let company_id = match require_admin_active(&session_user) {
+    let active_company = match require_admin_active(&session_user) {
         Ok(id) => id,
         Err(status) => return status.into_response(),
     };
 
-    let object_id = match ObjectId::from_str(&id) {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-
-    if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
-        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &company_id),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    } {
-        return status.into_response();
-    }
-    let flow_type = match parse_flow_type(&form.flow_type) {
-        Ok(v) => v,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-    let category_id = match parse_object_id(&form.category_id, "Categoría") {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-    let account_expected_id = match parse_object_id(&form.account_expected_id, "Cuenta esperada") {
-        Ok(id) => id,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-
-    let contact_id = match form.contact_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(cid) => match parse_object_id(&cid, "Contacto") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
-
-    let recurring_plan_id = match form.recurring_plan_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(rpid) => match parse_object_id(&rpid, "Plan recurrente") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
-
-    let recurring_plan_version = match form
-        .recurring_plan_version
-        .clone()
-        .and_then(|v| if v.trim().is_empty() { None } else { Some(v) })
-    {
-        Some(v) => match parse_i32_field(&v, "Versión del plan") {
-            Ok(num) => Some(num),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
-
-    let amount_estimated = match parse_f64_field(&form.amount_estimated, "Monto estimado") {
-        Ok(v) => v,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-    let due_date = match parse_datetime_field(&form.due_date, "Fecha de vencimiento") {
-        Ok(dt) => dt,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-    };
-    let status_enum = match parse_planned_status(&form.status) {
-        Ok(s) => s,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    let (object_id, transaction_id) =
+        match (ObjectId::from_str(&id), ObjectId::from_str(&transaction_id)) {
+            (Ok(id), Ok(transaction_id)) => (id, transaction_id),
+            _ => return StatusCode::BAD_REQUEST.into_response(),
+        };
+    let entry = match get_planned_entry_by_id(&state, &object_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
-    let notes = clean_opt(form.notes);
-    let project_id =
-        parse_optional_project_id(&state, &company_id, form.project_id.as_deref()).await;
-    let project_id = match project_id {
-        Ok(project_id) => project_id,
-        Err(status) => return status.into_response(),
+    let transaction = match get_transaction_by_id(&state, &transaction_id).await {
+        Ok(Some(tx)) => tx,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
-
-    if let Err(status) = validate_company_refs(
-        &state,
-        &company_id,
-        Some(&category_id),
-        Some(&account_expected_id),
-        contact_id.as_ref(),
-    )
-    .await
+    if let Err(status) = ensure_same_company(&entry.company_id, &active_company)
+        .and_then(|_| ensure_same_company(&transaction.company_id, &active_company))
+        .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id]))
+        .and_then(|_| ensure_transaction_access(&session_user, &transaction))
     {
         return status.into_response();
     }
-
-    if let Some(ref plan_id) = recurring_plan_id {
-        if let Err(status) = validate_recurring_plan_company(&state, plan_id, &company_id).await {
-            return status.into_response();
-        }
+    if transaction.planned_entry_id != Some(object_id) {
+        return StatusCode::NOT_FOUND.into_response();
     }
 
-    match update_planned_entry(
-        &state,
-        &object_id,
-        &company_id,
-        recurring_plan_id,
-        recurring_plan_version,
-        form.name.trim(),
-        flow_type,
-        &category_id,
-        &account_expected_id,
-        contact_id,
-        amount_estimated,
-        due_date,
-        status_enum,
-        notes,
-    )
-    .await
-    {
-        Ok(_) => {
-            let _ = update_planned_entry_project_links(
-                &state,
-                &object_id,
-                &company_id,
-                project_id,
-                None,
-            )
-            .await;
-            Redirect::to("/admin/planned_entries").into_response()
-        }
+    match detach_transaction_from_planned_entry(&state, &transaction_id, &active_company).await {
+        Ok(_) => (
+            Flash::success("Movimiento desvinculado del compromiso."),
+            Redirect::to(&format!("/admin/planned_entries/{id}/edit")),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
-pub async fn planned_entries_delete(
+/// Moves the excess of an over-covered entry to an adjustment entry (see
+/// [`create_coverage_adjustment`]) and opens that entry, whose due date and
+/// account are worth a look.
+pub async fn planned_entries_adjust(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -838,17 +1609,30 @@ pub async fn planned_entries_delete(
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
-
-    if let Err(status) = match get_planned_entry_by_id(&state, &object_id).await {
-        Ok(Some(entry)) => ensure_same_company(&entry.company_id, &active_company),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    } {
+    let entry = match get_planned_entry_by_id(&state, &object_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&entry.company_id, &active_company)
+        .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id]))
+    {
         return status.into_response();
     }
+    let covered_amount = match planned_entry_covered_amount(&state, &object_id).await {
+        Ok(amount) => amount,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if coverage_excess(&entry, covered_amount) <= 0.0 {
+        return StatusCode::CONFLICT.into_response();
+    }
 
-    match delete_planned_entry(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/planned_entries").into_response(),
+    match create_coverage_adjustment(&state, &object_id, &active_company).await {
+        Ok(adjustment_id) => Redirect::to(&format!(
+            "/admin/planned_entries/{}/edit",
+            adjustment_id.to_hex()
+        ))
+        .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -907,10 +1691,10 @@ pub struct BulkPayFormData {
     entry_ids: String,
     paid_at: String,
     account_id: String,
-    #[serde(default)]
-    project_id: Option<String>,
-    #[serde(default)]
-    parent_planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    project_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    parent_planned_entry_id: Option<ObjectId>,
     #[serde(default)]
     notes: Option<String>,
     #[serde(default)]
@@ -925,7 +1709,8 @@ pub async fn planned_entries_bulk_pay_form(
     let company_id = require_admin_active(&session_user)?;
     let entry_ids = parse_entry_ids(&query.ids).map_err(|_| StatusCode::BAD_REQUEST)?;
     let entries = load_payable_entries(&state, &company_id, &entry_ids).await?;
-    let accounts = account_options(&state, None, &company_id).await?;
+    let accounts =
+        account_options(&state, None, &company_id, &session_user.account_access()).await?;
     let projects = project_options(&state, &company_id, None).await?;
     let parent_entries = parent_entry_options(&state, &company_id, None, None).await?;
     let total_amount = entries.iter().map(|entry| entry.amount_estimated).sum();
@@ -970,11 +1755,12 @@ pub async fn planned_entries_bulk_pay(
         Err(status) => return status.into_response(),
     };
     let account_id = match parse_object_id(&form.account_id, "Cuenta") {
-        Ok(id) => id,
-        Err(_) => {
+        Ok(id) if session_user.account_access().allows(&id) => id,
+        _ => {
             return render_bulk_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 &form,
                 &entries,
                 "Selecciona una cuenta válida",
@@ -989,6 +1775,7 @@ pub async fn planned_entries_bulk_pay(
             return render_bulk_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 &form,
                 &entries,
                 "Captura una fecha de pago válida",
@@ -997,25 +1784,25 @@ pub async fn planned_entries_bulk_pay(
             .into_response();
         }
     };
-    let project_id =
-        match parse_optional_project_id(&state, &company_id, form.project_id.as_deref()).await {
-            Ok(project_id) => project_id,
-            Err(_) => {
-                return render_bulk_pay_form_error(
-                    &state,
-                    &company_id,
-                    &form,
-                    &entries,
-                    "Selecciona un proyecto válido",
-                )
-                .await
-                .into_response();
-            }
-        };
-    let parent_planned_entry_id = match parse_optional_parent_entry_id(
+    let project_id = match validate_project_id(&state, &company_id, form.project_id).await {
+        Ok(project_id) => project_id,
+        Err(_) => {
+            return render_bulk_pay_form_error(
+                &state,
+                &company_id,
+                &session_user.account_access(),
+                &form,
+                &entries,
+                "Selecciona un proyecto válido",
+            )
+            .await
+            .into_response();
+        }
+    };
+    let parent_planned_entry_id = match validate_parent_entry_id(
         &state,
         &company_id,
-        form.parent_planned_entry_id.as_deref(),
+        form.parent_planned_entry_id,
         project_id.as_ref(),
     )
     .await
@@ -1025,6 +1812,7 @@ pub async fn planned_entries_bulk_pay(
             return render_bulk_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 &form,
                 &entries,
                 "Selecciona un compromiso estimado válido",
@@ -1075,7 +1863,8 @@ pub async fn planned_entries_pay_form(
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&entry.company_id, &company_id)?;
 
-    let accounts = account_options(&state, None, &company_id).await?;
+    let accounts =
+        account_options(&state, None, &company_id, &session_user.account_access()).await?;
     let projects = project_options(&state, &company_id, entry.project_id.as_ref()).await?;
     let parent_entries = parent_entry_options(
         &state,
@@ -1107,10 +1896,10 @@ pub struct PayFormData {
     paid_at: String,
     amount: String,
     account_id: String,
-    #[serde(default)]
-    project_id: Option<String>,
-    #[serde(default)]
-    parent_planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    project_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    parent_planned_entry_id: Option<ObjectId>,
     #[serde(default)]
     notes: Option<String>,
     #[serde(default)]
@@ -1133,7 +1922,9 @@ pub async fn planned_entries_pay(
     };
     let entry = match get_planned_entry_by_id(&state, &oid).await {
         Ok(Some(entry)) => {
-            if let Err(status) = ensure_same_company(&entry.company_id, &company_id) {
+            if let Err(status) = ensure_same_company(&entry.company_id, &company_id)
+                .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id]))
+            {
                 return status.into_response();
             }
             entry
@@ -1143,11 +1934,12 @@ pub async fn planned_entries_pay(
     };
 
     let account_id = match parse_object_id(&form.account_id, "Cuenta") {
-        Ok(id) => id,
-        Err(_) => {
+        Ok(id) if session_user.account_access().allows(&id) => id,
+        _ => {
             return render_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 id,
                 &entry.name,
                 &form,
@@ -1164,6 +1956,7 @@ pub async fn planned_entries_pay(
             return render_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 id,
                 &entry.name,
                 &form,
@@ -1180,6 +1973,7 @@ pub async fn planned_entries_pay(
             return render_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 id,
                 &entry.name,
                 &form,
@@ -1191,82 +1985,37 @@ pub async fn planned_entries_pay(
         }
     };
     let notes = clean_opt(form.notes.clone());
-    let project_id = match clean_opt(form.project_id.clone()) {
-        Some(value) => match parse_object_id(&value, "Proyecto") {
-            Ok(project_oid) => {
-                match get_project_by_id_for_company(&state, &project_oid, &company_id).await {
-                    Ok(Some(_)) => Some(project_oid),
-                    Ok(None) => {
-                        return render_pay_form_error(
-                            &state,
-                            &company_id,
-                            id,
-                            &entry.name,
-                            &form,
-                            entry.original_amount_estimated.unwrap_or(0.0),
-                            "Selecciona un proyecto válido",
-                        )
-                        .await
-                        .into_response();
-                    }
-                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    let project_id = match form.project_id {
+        Some(project_oid) => {
+            match get_project_by_id_for_company(&state, &project_oid, &company_id).await {
+                Ok(Some(_)) => Some(project_oid),
+                Ok(None) => {
+                    return render_pay_form_error(
+                        &state,
+                        &company_id,
+                        &session_user.account_access(),
+                        id,
+                        &entry.name,
+                        &form,
+                        entry.original_amount_estimated.unwrap_or(0.0),
+                        "Selecciona un proyecto válido",
+                    )
+                    .await
+                    .into_response();
                 }
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             }
-            Err(_) => {
-                return render_pay_form_error(
-                    &state,
-                    &company_id,
-                    id,
-                    &entry.name,
-                    &form,
-                    entry.original_amount_estimated.unwrap_or(0.0),
-                    "Selecciona un proyecto válido",
-                )
-                .await
-                .into_response();
-            }
-        },
-        None => entry.project_id.clone(),
-    };
-    let parent_planned_entry_id = match clean_opt(form.parent_planned_entry_id.clone()) {
-        Some(value) => match parse_object_id(&value, "Compromiso estimado") {
-            Ok(parent_id) => match get_planned_entry_by_id(&state, &parent_id).await {
-                Ok(Some(parent)) => {
-                    if parent.company_id != company_id || parent.id.as_ref() == Some(&oid) {
-                        return render_pay_form_error(
-                            &state,
-                            &company_id,
-                            id,
-                            &entry.name,
-                            &form,
-                            entry.original_amount_estimated.unwrap_or(0.0),
-                            "Selecciona un compromiso estimado válido",
-                        )
-                        .await
-                        .into_response();
-                    }
-                    if let (Some(parent_project), Some(project)) = (&parent.project_id, &project_id)
-                    {
-                        if parent_project != project {
-                            return render_pay_form_error(
-                                &state,
-                                &company_id,
-                                id,
-                                &entry.name,
-                                &form,
-                                entry.original_amount_estimated.unwrap_or(0.0),
-                                "El compromiso estimado debe pertenecer al proyecto seleccionado",
-                            )
-                            .await
-                            .into_response();
-                        }
-                    }
-                    Some(parent_id)
-                }
-                Ok(None) => {
+        }
+        None => entry.project_id,
+    };
+    let parent_planned_entry_id = match form.parent_planned_entry_id {
+        Some(parent_id) => match get_planned_entry_by_id(&state, &parent_id).await {
+            Ok(Some(parent)) => {
+                if parent.company_id != company_id || parent.id.as_ref() == Some(&oid) {
                     return render_pay_form_error(
                         &state,
                         &company_id,
+                        &session_user.account_access(),
                         id,
                         &entry.name,
                         &form,
@@ -1276,12 +2025,29 @@ pub async fn planned_entries_pay(
                     .await
                     .into_response();
                 }
-                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-            },
-            Err(_) => {
+                if let (Some(parent_project), Some(project)) = (&parent.project_id, &project_id)
+                    && parent_project != project
+                {
+                    return render_pay_form_error(
+                        &state,
+                        &company_id,
+                        &session_user.account_access(),
+                        id,
+                        &entry.name,
+                        &form,
+                        entry.original_amount_estimated.unwrap_or(0.0),
+                        "El compromiso estimado debe pertenecer al proyecto seleccionado",
+                    )
+                    .await
+                    .into_response();
+                }
+                Some(parent_id)
+            }
+            Ok(None) => {
                 return render_pay_form_error(
                     &state,
                     &company_id,
+                    &session_user.account_access(),
                     id,
                     &entry.name,
                     &form,
@@ -1291,8 +2057,9 @@ pub async fn planned_entries_pay(
                 .await
                 .into_response();
             }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         },
-        None => entry.parent_planned_entry_id.clone(),
+        None => entry.parent_planned_entry_id,
     };
 
     match pay_planned_entry_with_project(
@@ -1318,6 +2085,7 @@ pub async fn planned_entries_pay(
             render_pay_form_error(
                 &state,
                 &company_id,
+                &session_user.account_access(),
                 id,
                 &entry.name,
                 &form,
@@ -1361,7 +2129,9 @@ pub async fn planned_entry_pay_api(
     };
     let entry = match get_planned_entry_by_id(&state, &object_id).await {
         Ok(Some(entry)) => {
-            if let Err(status) = ensure_same_company(&entry.company_id, &company_id) {
+            if let Err(status) = ensure_same_company(&entry.company_id, &company_id)
+                .and_then(|_| ensure_account_access(&session_user, [&entry.account_expected_id]))
+            {
                 return status.into_response();
             }
             entry
@@ -1369,10 +2139,7 @@ pub async fn planned_entry_pay_api(
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
-    if matches!(
-        entry.status,
-        crate::models::PlannedStatus::Covered | crate::models::PlannedStatus::Cancelled
-    ) {
+    if entry.status.is_covered() || entry.status == crate::models::PlannedStatus::Cancelled {
         return StatusCode::BAD_REQUEST.into_response();
     }
     let payment =
@@ -1382,6 +2149,9 @@ pub async fn planned_entry_pay_api(
             Ok(payment) => payment,
             Err(status) => return status.into_response(),
         };
+    if let Err(status) = ensure_account_access(&session_user, [&payment.account_id]) {
+        return status.into_response();
+    }
 
     match pay_planned_entry_with_project(
         &state,
@@ -1441,6 +2211,9 @@ pub async fn planned_entries_bulk_pay_api(
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&account_id]) {
+        return status.into_response();
+    }
     if let Err(status) =
         validate_company_refs(&state, &company_id, None, Some(&account_id), None).await
     {
@@ -1450,15 +2223,14 @@ pub async fn planned_entries_bulk_pay_api(
         Ok(date) => date,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
-    let project_id =
-        match parse_optional_project_id(&state, &company_id, payload.project_id.as_deref()).await {
-            Ok(project_id) => project_id,
-            Err(status) => return status.into_response(),
-        };
-    let parent_planned_entry_id = match parse_optional_parent_entry_id(
+    let project_id = match validate_project_id(&state, &company_id, payload.project_id).await {
+        Ok(project_id) => project_id,
+        Err(status) => return status.into_response(),
+    };
+    let parent_planned_entry_id = match validate_parent_entry_id(
         &state,
         &company_id,
-        payload.parent_planned_entry_id.as_deref(),
+        payload.parent_planned_entry_id,
         project_id.as_ref(),
     )
     .await
@@ -1512,21 +2284,22 @@ struct ParsedPaymentPayload {
 async fn render_pay_form_error(
     state: &AppState,
     company_id: &ObjectId,
+    access: &AccountAccess,
     entry_id: String,
     entry_name: &str,
     form: &PayFormData,
     original_amount: f64,
     message: &str,
 ) -> Result<Html<String>, StatusCode> {
-    let accounts = account_options(state, None, company_id).await?;
-    let project_id = clean_opt(form.project_id.clone()).unwrap_or_default();
-    let parent_planned_entry_id =
-        clean_opt(form.parent_planned_entry_id.clone()).unwrap_or_default();
-    let selected_project = ObjectId::from_str(&project_id).ok();
-    let selected_parent = ObjectId::from_str(&parent_planned_entry_id).ok();
-    let projects = project_options(state, company_id, selected_project.as_ref()).await?;
-    let parent_entries =
-        parent_entry_options(state, company_id, None, selected_parent.as_ref()).await?;
+    let accounts = account_options(state, None, company_id, access).await?;
+    let projects = project_options(state, company_id, form.project_id.as_ref()).await?;
+    let parent_entries = parent_entry_options(
+        state,
+        company_id,
+        None,
+        form.parent_planned_entry_id.as_ref(),
+    )
+    .await?;
     render(PayFormTemplate {
         entry_id,
         entry_name: entry_name.to_string(),
@@ -1546,18 +2319,20 @@ async fn render_pay_form_error(
 async fn render_bulk_pay_form_error(
     state: &AppState,
     company_id: &ObjectId,
+    access: &AccountAccess,
     form: &BulkPayFormData,
     entries: &[crate::models::PlannedEntry],
     message: &str,
 ) -> Result<Html<String>, StatusCode> {
-    let selected_project =
-        clean_opt(form.project_id.clone()).and_then(|id| ObjectId::from_str(&id).ok());
-    let selected_parent =
-        clean_opt(form.parent_planned_entry_id.clone()).and_then(|id| ObjectId::from_str(&id).ok());
-    let accounts = account_options(state, None, company_id).await?;
-    let projects = project_options(state, company_id, selected_project.as_ref()).await?;
-    let parent_entries =
-        parent_entry_options(state, company_id, None, selected_parent.as_ref()).await?;
+    let accounts = account_options(state, None, company_id, access).await?;
+    let projects = project_options(state, company_id, form.project_id.as_ref()).await?;
+    let parent_entries = parent_entry_options(
+        state,
+        company_id,
+        None,
+        form.parent_planned_entry_id.as_ref(),
+    )
+    .await?;
     let total_amount = entries.iter().map(|entry| entry.amount_estimated).sum();
     let rows = entries
         .iter()
@@ -1600,6 +2375,23 @@ fn parse_entry_ids(value: &str) -> Result<Vec<ObjectId>, ()> {
     Ok(ids)
 }
 
+/// The due date as entered or, when left blank, today plus the payment terms
+/// of the contact. Blank with no terms to go by is a bad request.
+async fn due_date_or_terms(
+    state: &AppState,
+    value: &str,
+    contact_id: Option<&ObjectId>,
+) -> Result<mongodb::bson::DateTime, StatusCode> {
+    if !value.trim().is_empty() {
+        return parse_datetime_field(value, "Fecha de vencimiento")
+            .map_err(|_| StatusCode::BAD_REQUEST);
+    }
+    contact_due_date(state, contact_id, mongodb::bson::DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
 async fn parse_planned_entry_payload(
     state: &AppState,
     company_id: &ObjectId,
@@ -1614,13 +2406,12 @@ async fn parse_planned_entry_payload(
         .map_err(|_| StatusCode::BAD_REQUEST)?;
     let account_expected_id = parse_object_id(&payload.account_expected_id, "account_expected_id")
         .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let contact_id = parse_optional_object_id(payload.contact_id)?;
-    let project_id =
-        parse_optional_project_id(state, company_id, payload.project_id.as_deref()).await?;
-    let due_date =
-        parse_datetime_field(&payload.due_date, "due_date").map_err(|_| StatusCode::BAD_REQUEST)?;
+    let contact_id = payload.contact_id;
+    let project_id = validate_project_id(state, company_id, payload.project_id).await?;
     let status = parse_planned_status(&payload.status).map_err(|_| StatusCode::BAD_REQUEST)?;
-    let recurring_plan_id = parse_optional_object_id(payload.recurring_plan_id)?;
+    let recurring_plan_id = payload.recurring_plan_id;
+    let exchange_rate = parse_exchange_rate(payload.exchange_rate.map(|rate| rate.to_string()))
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
 
     validate_company_refs(
         state,
@@ -1630,6 +2421,7 @@ async fn parse_planned_entry_payload(
         contact_id.as_ref(),
     )
     .await?;
+    let due_date = due_date_or_terms(state, &payload.due_date, contact_id.as_ref()).await?;
     if let Some(ref plan_id) = recurring_plan_id {
         validate_recurring_plan_company(state, plan_id, company_id).await?;
     }
@@ -1647,6 +2439,7 @@ async fn parse_planned_entry_payload(
         recurring_plan_id,
         recurring_plan_version: payload.recurring_plan_version,
         notes: clean_opt(payload.notes),
+        exchange_rate,
     })
 }
 
@@ -1665,23 +2458,22 @@ async fn parse_planned_entry_payment_payload(
     validate_company_refs(state, company_id, None, Some(&account_id), None).await?;
     let paid_at =
         parse_datetime_field(&payload.paid_at, "paid_at").map_err(|_| StatusCode::BAD_REQUEST)?;
-    let project_id = match payload.project_id.as_deref() {
-        Some(value) if !value.trim().is_empty() => {
-            parse_optional_project_id(state, company_id, Some(value)).await?
-        }
-        _ => entry.project_id.clone(),
-    };
-    let parent_planned_entry_id = match payload.parent_planned_entry_id.as_deref() {
-        Some(value) if !value.trim().is_empty() => {
-            let parent_id =
-                parse_optional_parent_entry_id(state, company_id, Some(value), project_id.as_ref())
-                    .await?;
-            if parent_id.as_ref() == Some(entry_id) {
-                return Err(StatusCode::BAD_REQUEST);
-            }
-            parent_id
+    let project_id = match payload.project_id {
+        Some(_) => validate_project_id(state, company_id, payload.project_id).await?,
+        None => entry.project_id,
+    };
+    let parent_planned_entry_id = match payload.parent_planned_entry_id {
+        Some(ref value) if value == entry_id => return Err(StatusCode::BAD_REQUEST),
+        Some(_) => {
+            validate_parent_entry_id(
+                state,
+                company_id,
+                payload.parent_planned_entry_id,
+                project_id.as_ref(),
+            )
+            .await?
         }
-        _ => entry.parent_planned_entry_id.clone(),
+        None => entry.parent_planned_entry_id,
     };
 
     Ok(ParsedPaymentPayload {
@@ -1694,15 +2486,6 @@ async fn parse_planned_entry_payment_payload(
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, StatusCode> {
-    match clean_opt(value) {
-        Some(value) => ObjectId::from_str(&value)
-            .map(Some)
-            .map_err(|_| StatusCode::BAD_REQUEST),
-        None => Ok(None),
-    }
-}
-
 async fn load_payable_entries(
     state: &AppState,
     company_id: &ObjectId,
@@ -1716,7 +2499,9 @@ async fn load_payable_entries(
             .ok_or(StatusCode::NOT_FOUND)?;
         ensure_same_company(&entry.company_id, company_id)?;
         match entry.status {
-            crate::models::PlannedStatus::Covered | crate::models::PlannedStatus::Cancelled => {
+            crate::models::PlannedStatus::Covered
+            | crate::models::PlannedStatus::OverCovered
+            | crate::models::PlannedStatus::Cancelled => {
                 return Err(StatusCode::BAD_REQUEST);
             }
             _ => entries.push(entry),
@@ -1747,15 +2532,14 @@ async fn project_options(
         .collect())
 }
 
-async fn parse_optional_project_id(
+async fn validate_project_id(
     state: &AppState,
     company_id: &ObjectId,
-    value: Option<&str>,
+    project_id: Option<ObjectId>,
 ) -> Result<Option<ObjectId>, StatusCode> {
-    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+    let Some(project_id) = project_id else {
         return Ok(None);
     };
-    let project_id = ObjectId::from_str(value).map_err(|_| StatusCode::BAD_REQUEST)?;
     match get_project_by_id_for_company(state, &project_id, company_id).await {
         Ok(Some(_)) => Ok(Some(project_id)),
         Ok(None) => Err(StatusCode::BAD_REQUEST),
@@ -1763,16 +2547,15 @@ async fn parse_optional_project_id(
     }
 }
 
-async fn parse_optional_parent_entry_id(
+async fn validate_parent_entry_id(
     state: &AppState,
     company_id: &ObjectId,
-    value: Option<&str>,
+    parent_id: Option<ObjectId>,
     project_id: Option<&ObjectId>,
 ) -> Result<Option<ObjectId>, StatusCode> {
-    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+    let Some(parent_id) = parent_id else {
         return Ok(None);
     };
-    let parent_id = ObjectId::from_str(value).map_err(|_| StatusCode::BAD_REQUEST)?;
     let parent = get_planned_entry_by_id(state, &parent_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -1852,6 +2635,37 @@ fn planned_entry_data(entry: PlannedEntry, company: String) -> Option<PlannedEnt
         notes: entry.notes,
         cfdi_uuid: entry.cfdi_uuid,
         currency: entry.currency,
+        exchange_rate: entry.exchange_rate,
         cfdi_folio: entry.cfdi_folio,
+        slip_count: entry.slip_count,
+        amount_revisions: entry
+            .amount_revisions
+            .into_iter()
+            .map(amount_revision_row)
+            .collect(),
+    })
+}
+
+fn amount_revision_row(revision: AmountRevision) -> AmountRevisionRow {
+    AmountRevisionRow {
+        changed_at: datetime_to_string(&revision.changed_at),
+        username: revision.username,
+        previous_amount: revision.previous_amount,
+        amount: revision.amount,
+    }
+}
+
+fn amount_history(entry: &PlannedEntry) -> Option<AmountHistory> {
+    let planned_amount = entry.amount_revisions.first()?.previous_amount;
+    Some(AmountHistory {
+        planned_amount,
+        variance: entry.amount_estimated - planned_amount,
+        revisions: entry
+            .amount_revisions
+            .iter()
+            .rev()
+            .cloned()
+            .map(amount_revision_row)
+            .collect(),
     })
 }
@@ -0,0 +1,593 @@
+// Printable copies for the accountant: a single transaction as a receipt and
+// a month of planned entries as a schedule. Each is a page laid out for paper
+// (the browser prints it) and the same content as a PDF through Typst.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::Deserialize;
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    filters::{current_format, format_amount, format_date, format_money},
+    models::{FlowType, PlannedStatus, TransactionType},
+    routes::{
+        Routes,
+        pdf::{compile_typst, load_pdf_branding, typst_string},
+    },
+    session::SessionUser,
+    state::{
+        AppState, get_account_by_id, get_category_by_id, get_company_by_id, get_contact_by_id,
+        get_planned_entry_by_id, get_transaction_by_id, list_categories, list_contacts,
+        list_planned_entries, month_bounds,
+    },
+};
+
+use super::helpers::*;
+use super::totals::{CurrencyResolver, IndexTotals};
+
+/// Print views and their PDFs.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/print/transactions/{id}", get(print_transaction))
+        .route("/print/transactions/{id}/pdf", get(print_transaction_pdf))
+        .route("/print/planned_entries", get(print_planned_entries))
+        .route("/print/planned_entries/pdf", get(print_planned_entries_pdf))
+}
+
+const MONTHS_ES: [&str; 12] = [
+    "enero",
+    "febrero",
+    "marzo",
+    "abril",
+    "mayo",
+    "junio",
+    "julio",
+    "agosto",
+    "septiembre",
+    "octubre",
+    "noviembre",
+    "diciembre",
+];
+
+/// Company header and footer of the printed page.
+struct PrintBranding {
+    company_name: String,
+    has_logo: bool,
+    color: String,
+    footer_text: Option<String>,
+}
+
+async fn print_branding(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<PrintBranding, StatusCode> {
+    let company = get_company_by_id(state, company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(PrintBranding {
+        company_name: company.name,
+        has_logo: company.branding.logo_path.is_some(),
+        color: company
+            .branding
+            .color
+            .unwrap_or_else(|| "#0f172a".to_string()),
+        footer_text: company.branding.footer_text,
+    })
+}
+
+async fn account_name(
+    state: &AppState,
+    account_id: Option<ObjectId>,
+) -> Result<Option<String>, StatusCode> {
+    let Some(account_id) = account_id else {
+        return Ok(None);
+    };
+    get_account_by_id(state, &account_id)
+        .await
+        .map(|account| account.map(|account| account.name))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn money(amount: f64) -> String {
+    format_amount(amount, &current_format())
+}
+
+fn money_in(amount: f64, currency: &str) -> String {
+    format_money(amount, currency, &current_format())
+}
+
+fn day(value: &DateTime) -> String {
+    value.to_chrono().format("%Y-%m-%d").to_string()
+}
+
+/// Content fields of the Typst table: one `[#"value"]` cell per value.
+fn typst_cells<'a>(values: impl IntoIterator<Item = &'a str>) -> String {
+    values
+        .into_iter()
+        .map(|value| format!("[#{}]", typst_string(value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Sends the Typst document compiled with the company's branding.
+async fn pdf_response(
+    state: &AppState,
+    session_user: &SessionUser,
+    source: &str,
+    file_name: &str,
+) -> Response {
+    let branding = load_pdf_branding(state, session_user).await;
+    match compile_typst(source, branding.as_ref()).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{file_name}\""),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(err) => {
+            eprintln!("[print] pdf failed: {err}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("No se pudo generar el PDF: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// What a transaction receipt shows.
+struct TransactionReceipt {
+    /// The transaction's reference, or its id when it has none.
+    reference: String,
+    date: String,
+    description: String,
+    type_label: String,
+    category: String,
+    account_from: Option<String>,
+    account_to: Option<String>,
+    contact: Option<String>,
+    planned_entry: Option<String>,
+    amount: f64,
+    currency: String,
+    is_confirmed: bool,
+    cfdi_folio: Option<String>,
+    cfdi_uuid: Option<String>,
+    notes: Option<String>,
+}
+
+async fn load_receipt(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(TransactionReceipt, ObjectId), StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let tx = get_transaction_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&tx.company_id, &company_id)?;
+    ensure_transaction_access(session_user, &tx)?;
+
+    let account_from = account_name(state, tx.account_from_id).await?;
+    let account_to = account_name(state, tx.account_to_id).await?;
+    let category = get_category_by_id(state, &tx.category_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(|category| category.name)
+        .unwrap_or_default();
+    let contact = match tx.contact_id {
+        Some(contact_id) => get_contact_by_id(state, &contact_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|contact| contact.name),
+        None => None,
+    };
+    let planned_entry = match tx.planned_entry_id {
+        Some(entry_id) => get_planned_entry_by_id(state, &entry_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|entry| entry.name),
+        None => None,
+    };
+    let currencies = CurrencyResolver::load(state, &company_id).await?;
+    let account = match tx.transaction_type {
+        TransactionType::Income => tx.account_to_id.as_ref(),
+        _ => tx.account_from_id.as_ref(),
+    };
+
+    let receipt = TransactionReceipt {
+        reference: tx.reference.unwrap_or_else(|| id.to_string()),
+        date: day(&tx.date),
+        description: tx.description,
+        type_label: transaction_type_label(&tx.transaction_type).to_string(),
+        category,
+        account_from,
+        account_to,
+        contact,
+        planned_entry,
+        amount: tx.amount,
+        currency: currencies.resolve(tx.currency.as_deref(), account),
+        is_confirmed: tx.is_confirmed,
+        cfdi_folio: tx.cfdi_folio,
+        cfdi_uuid: tx.cfdi_uuid,
+        notes: tx.notes,
+    };
+    Ok((receipt, company_id))
+}
+
+/// Label and value of each line of the receipt, in print order.
+fn receipt_lines(receipt: &TransactionReceipt) -> Vec<(&'static str, String)> {
+    let mut lines = vec![
+        ("Referencia", receipt.reference.clone()),
+        ("Fecha", format_date(&receipt.date, &current_format())),
+        ("Tipo", receipt.type_label.clone()),
+        ("Concepto", receipt.description.clone()),
+        ("Categoría", receipt.category.clone()),
+    ];
+    let optional = [
+        ("Cuenta origen", &receipt.account_from),
+        ("Cuenta destino", &receipt.account_to),
+        ("Contacto", &receipt.contact),
+        ("Compromiso", &receipt.planned_entry),
+        ("Folio CFDI", &receipt.cfdi_folio),
+        ("UUID CFDI", &receipt.cfdi_uuid),
+    ];
+    lines.extend(
+        optional
+            .into_iter()
+            .filter_map(|(label, value)| value.clone().map(|value| (label, value))),
+    );
+    lines.push((
+        "Estado",
+        if receipt.is_confirmed {
+            "Confirmado"
+        } else {
+            "Pendiente de confirmar"
+        }
+        .to_string(),
+    ));
+    lines
+}
+
+fn receipt_typst(receipt: &TransactionReceipt) -> String {
+    let rows = receipt_lines(receipt)
+        .iter()
+        .map(|(label, value)| format!("  [*{label}*], [#{}],\n", typst_string(value)))
+        .collect::<String>();
+    let mut source = format!(
+        "= Comprobante de movimiento\n\n#table(\n  columns: (auto, 1fr),\n  stroke: none,\n{rows})\n\n#align(right)[#text(size: 16pt, weight: \"bold\")[#{}]]\n",
+        typst_string(&money_in(receipt.amount, &receipt.currency)),
+    );
+    if let Some(notes) = &receipt.notes {
+        source.push_str(&format!("\n*Notas:* #{}\n", typst_string(notes)));
+    }
+    source
+}
+
+#[derive(Template)]
+#[template(path = "print/transaction.html")]
+struct PrintTransactionTemplate {
+    branding: PrintBranding,
+    id: String,
+    lines: Vec<(&'static str, String)>,
+    receipt: TransactionReceipt,
+}
+
+/// GET /print/transactions/{id} — the transaction as a printable receipt.
+pub async fn print_transaction(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (receipt, company_id) = load_receipt(&state, &session_user, &id).await?;
+    render(PrintTransactionTemplate {
+        branding: print_branding(&state, &company_id).await?,
+        id,
+        lines: receipt_lines(&receipt),
+        receipt,
+    })
+}
+
+/// GET /print/transactions/{id}/pdf — the same receipt as a PDF.
+pub async fn print_transaction_pdf(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    let receipt = match load_receipt(&state, &session_user, &id).await {
+        Ok((receipt, _)) => receipt,
+        Err(status) => return status.into_response(),
+    };
+    let file_name = format!("movimiento-{}.pdf", receipt.reference);
+    pdf_response(&state, &session_user, &receipt_typst(&receipt), &file_name).await
+}
+
+#[derive(Deserialize)]
+pub struct PrintMonthQuery {
+    /// `YYYY-MM`; the current month when missing.
+    #[serde(default)]
+    month: Option<String>,
+}
+
+/// First day of the month asked for, or of the current one.
+fn parse_month(value: Option<&str>) -> Result<NaiveDate, StatusCode> {
+    match value.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => NaiveDate::parse_from_str(&format!("{value}-01"), "%Y-%m-%d")
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        None => {
+            let today = Utc::now().date_naive();
+            Ok(today.with_day(1).unwrap_or(today))
+        }
+    }
+}
+
+struct ScheduleRow {
+    due_date: String,
+    name: String,
+    is_income: bool,
+    category: String,
+    contact: String,
+    status_label: String,
+    amount: f64,
+    currency: String,
+}
+
+/// A month of planned entries, earliest due first.
+struct PlannedSchedule {
+    /// `YYYY-MM`, for the links.
+    month: String,
+    /// e.g. `marzo de 2025`.
+    title: String,
+    rows: Vec<ScheduleRow>,
+    totals: IndexTotals,
+}
+
+async fn load_schedule(
+    state: &AppState,
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+    first_day: NaiveDate,
+) -> Result<PlannedSchedule, StatusCode> {
+    let (start, end) = month_bounds(DateTime::from_chrono(
+        first_day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc(),
+    ));
+    let access = session_user.account_access();
+    let mut entries: Vec<_> = list_planned_entries(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|e| &e.company_id == company_id)
+        .filter(|e| access.allows(&e.account_expected_id))
+        .filter(|e| e.status != PlannedStatus::Cancelled)
+        .filter(|e| e.due_date >= start && e.due_date < end)
+        .collect();
+    entries.sort_by(|a, b| (a.due_date, &a.name).cmp(&(b.due_date, &b.name)));
+
+    let categories: HashMap<ObjectId, String> = build_lookup_map(
+        list_categories(state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .filter(|c| &c.company_id == company_id)
+            .filter_map(|c| c.id.map(|id| (id, c.name)))
+            .collect(),
+    );
+    let contacts: HashMap<ObjectId, String> = build_lookup_map(
+        list_contacts(state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .filter(|c| &c.company_id == company_id)
+            .filter_map(|c| c.id.map(|id| (id, c.name)))
+            .collect(),
+    );
+    let currencies = CurrencyResolver::load(state, company_id).await?;
+    let totals = IndexTotals::build(
+        &currencies.base,
+        entries.iter().map(|e| {
+            (
+                currencies.resolve(e.currency.as_deref(), Some(&e.account_expected_id)),
+                e.flow_type.clone(),
+                e.amount_estimated,
+            )
+        }),
+        &HashMap::new(),
+    );
+
+    let rows = entries
+        .into_iter()
+        .map(|e| ScheduleRow {
+            due_date: day(&e.due_date),
+            is_income: matches!(e.flow_type, FlowType::Income),
+            category: categories.get(&e.category_id).cloned().unwrap_or_default(),
+            contact: e
+                .contact_id
+                .and_then(|id| contacts.get(&id).cloned())
+                .unwrap_or_default(),
+            status_label: planned_status_label(&e.status).to_string(),
+            amount: e.amount_estimated,
+            currency: currencies.resolve(e.currency.as_deref(), Some(&e.account_expected_id)),
+            name: e.name,
+        })
+        .collect();
+
+    Ok(PlannedSchedule {
+        month: first_day.format("%Y-%m").to_string(),
+        title: format!(
+            "{} de {}",
+            MONTHS_ES[first_day.month0() as usize],
+            first_day.year()
+        ),
+        rows,
+        totals,
+    })
+}
+
+fn schedule_typst(schedule: &PlannedSchedule) -> String {
+    let preferences = current_format();
+    let mut source = format!(
+        "= Compromisos de {}\n\n#table(\n  columns: (auto, 1fr, auto, auto, auto, auto),\n  align: (left, left, left, left, left, right),\n  [*Vence*], [*Concepto*], [*Categoría*], [*Contacto*], [*Estado*], [*Monto*],\n",
+        schedule.title
+    );
+    for row in &schedule.rows {
+        let date = format_date(&row.due_date, &preferences);
+        let amount = format!(
+            "{}{}",
+            if row.is_income { "" } else { "-" },
+            money_in(row.amount, &row.currency)
+        );
+        source.push_str(&format!(
+            "  {},\n",
+            typst_cells([
+                date.as_str(),
+                row.name.as_str(),
+                row.category.as_str(),
+                row.contact.as_str(),
+                row.status_label.as_str(),
+                amount.as_str(),
+            ])
+        ));
+    }
+    source.push_str(")\n");
+    if schedule.rows.is_empty() {
+        source.push_str("\nSin compromisos en el mes.\n");
+    }
+    for total in &schedule.totals.currencies {
+        source.push_str(&format!(
+            "\n*{}:* ingresos #{}, egresos #{}, neto #{}\n",
+            total.currency,
+            typst_string(&money(total.income)),
+            typst_string(&money(total.expense)),
+            typst_string(&money(total.net())),
+        ));
+    }
+    source
+}
+
+#[derive(Template)]
+#[template(path = "print/planned_entries.html")]
+struct PrintPlannedEntriesTemplate {
+    branding: PrintBranding,
+    schedule: PlannedSchedule,
+    previous_month: String,
+    next_month: String,
+}
+
+/// GET /print/planned_entries?month=YYYY-MM — the month's schedule.
+pub async fn print_planned_entries(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PrintMonthQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let first_day = parse_month(query.month.as_deref())?;
+    let schedule = load_schedule(&state, &session_user, &company_id, first_day).await?;
+    let shift = |months: i32| {
+        let month = first_day.month0() as i32 + months;
+        NaiveDate::from_ymd_opt(
+            first_day.year() + month.div_euclid(12),
+            month.rem_euclid(12) as u32 + 1,
+            1,
+        )
+        .map(|date| date.format("%Y-%m").to_string())
+        .unwrap_or_default()
+    };
+    render(PrintPlannedEntriesTemplate {
+        branding: print_branding(&state, &company_id).await?,
+        previous_month: shift(-1),
+        next_month: shift(1),
+        schedule,
+    })
+}
+
+/// GET /print/planned_entries/pdf?month=YYYY-MM — the schedule as a PDF.
+pub async fn print_planned_entries_pdf(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PrintMonthQuery>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let first_day = match parse_month(query.month.as_deref()) {
+        Ok(day) => day,
+        Err(status) => return status.into_response(),
+    };
+    let schedule = match load_schedule(&state, &session_user, &company_id, first_day).await {
+        Ok(schedule) => schedule,
+        Err(status) => return status.into_response(),
+    };
+    let file_name = format!("compromisos-{}.pdf", schedule.month);
+    pdf_response(
+        &state,
+        &session_user,
+        &schedule_typst(&schedule),
+        &file_name,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn month_is_read_as_year_and_month() {
+        assert_eq!(
+            parse_month(Some(" 2025-03 ")),
+            Ok(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap())
+        );
+        assert_eq!(parse_month(Some("2025-13")), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(parse_month(Some("marzo")), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(parse_month(None).unwrap().day(), 1);
+    }
+
+    #[test]
+    fn schedule_pdf_escapes_entry_text() {
+        let schedule = PlannedSchedule {
+            month: "2025-03".into(),
+            title: "marzo de 2025".into(),
+            rows: vec![ScheduleRow {
+                due_date: "2025-03-05".into(),
+                name: "Renta \"local\" #2".into(),
+                is_income: false,
+                category: "Renta".into(),
+                contact: String::new(),
+                status_label: "Planificado".into(),
+                amount: 1500.0,
+                currency: "MXN".into(),
+            }],
+            totals: IndexTotals::build(
+                "MXN",
+                [("MXN".to_string(), FlowType::Expense, 1500.0)],
+                &HashMap::new(),
+            ),
+        };
+        let source = schedule_typst(&schedule);
+        assert!(source.starts_with("= Compromisos de marzo de 2025"));
+        assert!(source.contains("[#\"Renta \\\"local\\\" #2\"]"));
+        assert!(source.contains("[#\"-$1500.00 MXN\"]"));
+        assert!(
+            source.contains("*MXN:* ingresos #\"0.00\", egresos #\"1500.00\", neto #\"-1500.00\"")
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// Receipt uploads for transactions. The file is stored, run through OCR when
+// a backend is configured, and its suggestions prefill the transaction form.
+
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Multipart, Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::oid::ObjectId;
+use tokio::fs;
+
+use crate::{
+    models::Receipt,
+    ocr::{RECEIPT_CONTENT_TYPES, ReceiptSuggestion, ocr_backend_from_env, recognize_receipt},
+    routes::Routes,
+    routes::admin::sat_configs::safe_upload_filename,
+    session::SessionUser,
+    state::{
+        AppState, StorageQuotaExceeded, create_receipt, get_receipt_by_id, quota_exceeded,
+        release_storage, reserve_storage,
+    },
+    uploads::BodyLimits,
+    uploads::sniff_content_type,
+};
+
+use super::helpers::*;
+
+/// Receipt uploads, with the larger body limit.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .route(
+            "/admin/transactions/receipt",
+            post(transactions_receipt_upload).layer(limits.upload_layer()),
+        )
+        .route(
+            "/admin/transactions/receipts/{id}",
+            get(transactions_receipt_file),
+        )
+        .route(
+            "/api/admin/transactions/receipts",
+            post(transactions_receipt_upload_api).layer(limits.upload_layer()),
+        )
+}
+
+const MAX_RECEIPT_BYTES: usize = 2 * 1024 * 1024;
+
+struct ReceiptUpload {
+    file_name: String,
+    content_type: String,
+    data: Vec<u8>,
+}
+
+/// Reads the `receipt` field of the form.
+async fn read_receipt_upload(multipart: &mut Multipart) -> Result<ReceiptUpload, StatusCode> {
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() != Some("receipt") {
+            continue;
+        }
+        let file_name = safe_upload_filename(field.file_name(), "receipt");
+        // A body cut short by the upload limit surfaces here as 413.
+        let data = field.bytes().await.map_err(|err| err.status())?.to_vec();
+        if data.len() > MAX_RECEIPT_BYTES {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        if data.is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        // The declared type and the extension are up to the client; only
+        // what the bytes actually are counts.
+        let content_type = sniff_content_type(&data)
+            .filter(|ct| RECEIPT_CONTENT_TYPES.contains(ct))
+            .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?
+            .to_string();
+        return Ok(ReceiptUpload {
+            file_name,
+            content_type,
+            data,
+        });
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+/// Why a receipt was not stored.
+enum StoreError {
+    /// The company has no room left for the file.
+    Quota(StorageQuotaExceeded),
+    Status(StatusCode),
+}
+
+/// Stores the file and its OCR suggestions. An OCR failure only loses the
+/// suggestions; the receipt is kept either way.
+async fn store_receipt(
+    state: &AppState,
+    company_id: &ObjectId,
+    upload: ReceiptUpload,
+) -> Result<(ObjectId, ReceiptSuggestion), StoreError> {
+    let size = upload.data.len() as u64;
+    if let Err(err) = reserve_storage(state, company_id, size).await {
+        return Err(match quota_exceeded(&err) {
+            Some(exceeded) => StoreError::Quota(exceeded),
+            None => StoreError::Status(StatusCode::INTERNAL_SERVER_ERROR),
+        });
+    }
+    let upload_dir = PathBuf::from("uploads")
+        .join("receipts")
+        .join(company_id.to_hex())
+        .join(ObjectId::new().to_hex());
+    if let Err(e) = fs::create_dir_all(&upload_dir).await {
+        eprintln!("[receipts] failed to create upload dir: {e}");
+        let _ = release_storage(state, company_id, size).await;
+        return Err(StoreError::Status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+    let path = upload_dir.join(&upload.file_name);
+    if let Err(e) = fs::write(&path, &upload.data).await {
+        eprintln!("[receipts] failed to write receipt: {e}");
+        let _ = release_storage(state, company_id, size).await;
+        return Err(StoreError::Status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    let (ocr_text, suggestion) = match ocr_backend_from_env() {
+        Some(backend) => {
+            match recognize_receipt(backend.as_ref(), &upload.data, &upload.content_type).await {
+                Ok((text, suggestion)) => (Some(text), suggestion),
+                Err(e) => {
+                    eprintln!("[receipts] ocr failed: {e:?}");
+                    (None, ReceiptSuggestion::default())
+                }
+            }
+        }
+        None => (None, ReceiptSuggestion::default()),
+    };
+
+    let created = create_receipt(
+        state,
+        company_id,
+        &upload.file_name,
+        &upload.content_type,
+        &path.to_string_lossy(),
+        ocr_text,
+        &suggestion,
+    )
+    .await;
+    match created {
+        Ok(id) => Ok((id, suggestion)),
+        Err(e) => {
+            eprintln!("[receipts] db insert error: {e}");
+            let _ = fs::remove_file(&path).await;
+            let _ = release_storage(state, company_id, size).await;
+            Err(StoreError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+/// Receipt of the active company, or the status to answer with.
+pub(super) async fn load_company_receipt(
+    state: &AppState,
+    id: &str,
+    company_id: &ObjectId,
+) -> Result<Receipt, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let receipt = get_receipt_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&receipt.company_id, company_id)?;
+    Ok(receipt)
+}
+
+/// Uploads a receipt and opens the new transaction form prefilled with it.
+pub async fn transactions_receipt_upload(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let upload = match read_receipt_upload(&mut multipart).await {
+        Ok(upload) => upload,
+        Err(StatusCode::INTERNAL_SERVER_ERROR) => {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        Err(_) => return Redirect::to("/admin/transactions/new?receipt_error=1").into_response(),
+    };
+    match store_receipt(&state, &company_id, upload).await {
+        Ok((id, _)) => Redirect::to(&format!("/admin/transactions/new?receipt={}", id.to_hex()))
+            .into_response(),
+        Err(StoreError::Quota(_)) => {
+            Redirect::to("/admin/transactions/new?storage_full=1").into_response()
+        }
+        Err(StoreError::Status(status)) => status.into_response(),
+    }
+}
+
+/// Serves the stored receipt file.
+pub async fn transactions_receipt_file(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let receipt = match load_company_receipt(&state, &id, &company_id).await {
+        Ok(receipt) => receipt,
+        Err(status) => return status.into_response(),
+    };
+    match fs::read(&receipt.path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, receipt.content_type),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{}\"", receipt.file_name),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/receipts",
+    tag = "finance",
+    responses(
+        (status = 201, description = "Receipt stored; returns its id and the OCR suggestions (amount, date, merchant) to confirm"),
+        (status = 400, description = "Missing `receipt` file field"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 413, description = "File larger than 2 MB"),
+        (status = 415, description = "Not a JPEG, PNG, WebP or PDF file"),
+        (status = 507, description = "The company's storage quota has no room for the file")
+    ),
+    security(("session" = []))
+)]
+pub async fn transactions_receipt_upload_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let upload = match read_receipt_upload(&mut multipart).await {
+        Ok(upload) => upload,
+        Err(status) => return status.into_response(),
+    };
+    match store_receipt(&state, &company_id, upload).await {
+        Ok((id, suggestion)) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({
+                "receipt_id": id.to_hex(),
+                "suggestion": {
+                    "amount": suggestion.amount,
+                    "date": suggestion.date.map(|d| d.format("%Y-%m-%d").to_string()),
+                    "merchant": suggestion.merchant,
+                },
+            })),
+        )
+            .into_response(),
+        Err(StoreError::Quota(exceeded)) => (
+            StatusCode::INSUFFICIENT_STORAGE,
+            Json(serde_json::json!({ "error": exceeded.to_string() })),
+        )
+            .into_response(),
+        Err(StoreError::Status(status)) => status.into_response(),
+    }
+}
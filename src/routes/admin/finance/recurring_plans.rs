@@ -3,32 +3,111 @@ use std::{str::FromStr, sync::Arc};
 use askama::Template;
 use axum::{
     Json,
-    extract::{Form, Path, State},
+    extract::{Form, Path, Query, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
 };
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
-    models::RecurringPlan,
+    flash::Flash,
+    models::{CommentEntity, PlannedEntry, RecurringPlan, ScenarioWeights},
+    routes::{
+        Routes,
+        admin::confirm::{ConfirmForm, Confirmation, confirm_page, verify},
+        form_fields::{id_or_empty, optional_object_id},
+    },
     session::SessionUser,
     state::{
-        AppState, create_recurring_plan, delete_recurring_plan, get_recurring_plan_by_id,
-        list_recurring_plans, regenerate_planned_entries_for_plan_id, update_recurring_plan,
+        AppState, PlanFieldChange, PlanInput, PlanRegenerationPreview,
+        count_planned_entries_per_plan_version, create_recurring_plan, delete_recurring_plan,
+        diff_plan_versions, get_recurring_plan_by_id, list_accounts, list_categories,
+        list_contacts, list_plan_versions, list_recurring_plans, plan_changed_significantly,
+        preview_recurring_plan_update, recurring_plan_coverage,
+        regenerate_planned_entries_for_plan_id, set_recurring_plan_probability,
+        set_recurring_plan_scenario_weights, update_recurring_plan, utc_day_start,
     },
 };
 
+use super::comments::{CommentThread, comment_thread};
 use super::helpers::*;
 use super::options::{account_options, category_options, contact_options};
+use super::totals::{CurrencyResolver, IndexTotals};
+
+/// Recurring plans and the entries they generate.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/recurring_plans",
+            get(recurring_plans_index).post(recurring_plans_create),
+        )
+        .route(
+            "/api/admin/recurring-plans",
+            get(recurring_plans_data_api).post(recurring_plans_create_api),
+        )
+        .route(
+            "/api/admin/recurring-plans/{id}",
+            get(recurring_plan_data_api),
+        )
+        .route(
+            "/api/admin/recurring-plans/{id}/update",
+            post(recurring_plan_update_api),
+        )
+        .route(
+            "/api/admin/recurring-plans/{id}/delete",
+            post(recurring_plan_delete_api),
+        )
+        .route(
+            "/api/admin/recurring-plans/{id}/generate",
+            post(recurring_plan_generate_api),
+        )
+        .route("/admin/recurring_plans/new", get(recurring_plans_new))
+        .route(
+            "/admin/recurring_plans/{id}/edit",
+            get(recurring_plans_edit),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/update",
+            post(recurring_plans_update),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/delete",
+            get(recurring_plans_delete_confirm).post(recurring_plans_delete),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/scenario_weights",
+            post(recurring_plans_scenario_weights),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/probability",
+            post(recurring_plans_probability),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/generate",
+            post(recurring_plans_generate),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/versions",
+            get(recurring_plans_versions),
+        )
+        .route(
+            "/admin/recurring_plans/{id}/clone",
+            post(recurring_plans_clone),
+        )
+}
 
 #[derive(Template)]
 #[template(path = "admin/recurring_plans/index.html")]
 struct RecurringPlansIndexTemplate {
     plans: Vec<RecurringPlanRow>,
+    /// Amount per occurrence of the active plans.
+    totals: IndexTotals,
 }
 
 struct RecurringPlanRow {
@@ -38,6 +117,34 @@ struct RecurringPlanRow {
     flow_type: String,
     amount: f64,
     active: bool,
+    next_due_date: Option<String>,
+    open_entries: i64,
+    committed_next_12_months: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/recurring_plans/versions.html")]
+struct RecurringPlanVersionsTemplate {
+    plan_id: String,
+    name: String,
+    current_version: i32,
+    versions: Vec<PlanVersionView>,
+}
+
+struct PlanVersionView {
+    version: i32,
+    saved_at: String,
+    /// Planned entries generated from this version.
+    entries: usize,
+    /// False for the oldest stored version, which has nothing to compare to.
+    has_previous: bool,
+    changes: Vec<PlanChangeView>,
+}
+
+struct PlanChangeView {
+    label: &'static str,
+    before: String,
+    after: String,
 }
 
 #[derive(Serialize)]
@@ -57,6 +164,8 @@ pub struct RecurringPlanData {
     pub end_date: Option<String>,
     pub is_active: bool,
     pub version: i32,
+    pub scenario_weights: Option<ScenarioWeights>,
+    pub probability: Option<f64>,
     pub notes: Option<String>,
 }
 
@@ -81,6 +190,64 @@ struct RecurringPlanFormTemplate {
     contacts: Vec<SimpleOption>,
     is_edit: bool,
     errors: Option<String>,
+    /// Only shown when editing, since the weights post to their own route.
+    scenario_weights: Option<ScenarioWeightsView>,
+    comments: Option<CommentThread>,
+    /// Set when the form is prefilled from another plan.
+    clone_notice: Option<String>,
+}
+
+/// Shown when saving a plan would regenerate its entries, before anything is
+/// written. Confirming posts the same form again with `confirm` set.
+#[derive(Template)]
+#[template(path = "admin/recurring_plans/confirm.html")]
+struct RecurringPlanConfirmTemplate {
+    action: String,
+    edit_url: String,
+    name: String,
+    fields: Vec<(&'static str, String)>,
+    removed: Vec<PreviewEntryView>,
+    kept: Vec<PreviewEntryView>,
+    created: Vec<String>,
+    amount_estimated: f64,
+}
+
+struct PreviewEntryView {
+    name: String,
+    due_date: String,
+    amount: f64,
+    status: &'static str,
+}
+
+struct ScenarioWeightsView {
+    action: String,
+    custom: bool,
+    best_pct: String,
+    expected_pct: String,
+    worst_pct: String,
+    /// Probability form, only for income plans.
+    probability: Option<ProbabilityView>,
+}
+
+struct ProbabilityView {
+    action: String,
+    pct: String,
+}
+
+#[derive(Deserialize)]
+pub struct ScenarioWeightsFormData {
+    #[serde(default)]
+    custom: bool,
+    best_pct: String,
+    expected_pct: String,
+    worst_pct: String,
+}
+
+#[derive(Deserialize)]
+pub struct ProbabilityFormData {
+    /// Blank counts the income in full.
+    #[serde(default)]
+    probability_pct: String,
 }
 
 #[derive(Deserialize)]
@@ -88,10 +255,12 @@ pub struct RecurringPlanFormData {
     name: String,
     company_id: String,
     flow_type: String,
-    category_id: String,
-    account_expected_id: String,
-    #[serde(default)]
-    contact_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_expected_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    contact_id: Option<ObjectId>,
     amount_estimated: String,
     frequency: String,
     #[serde(default)]
@@ -104,6 +273,9 @@ pub struct RecurringPlanFormData {
     version: String,
     #[serde(default)]
     notes: Option<String>,
+    /// Set by the confirmation page once the entry changes were reviewed.
+    #[serde(default)]
+    confirm: bool,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -112,9 +284,14 @@ pub struct RecurringPlanPayload {
     pub flow_type: String,
     pub category_id: String,
     pub account_expected_id: String,
-    pub contact_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
     pub amount_estimated: f64,
+    /// `monthly` or `weekly`.
     pub frequency: String,
+    /// Day the plan falls on, 1 to 31. Required for monthly plans and
+    /// rejected for weekly ones.
     pub day_of_month: Option<i32>,
     pub start_date: String,
     pub end_date: Option<String>,
@@ -122,6 +299,12 @@ pub struct RecurringPlanPayload {
     pub is_active: bool,
     #[serde(default = "default_version")]
     pub version: i32,
+    /// Income weights for scenario forecasts. Left untouched on update when
+    /// omitted.
+    pub scenario_weights: Option<ScenarioWeights>,
+    /// Chance, in percent, that the income arrives: above 0 and at most 100.
+    /// Income plans only; left untouched on update when omitted.
+    pub probability: Option<f64>,
     pub notes: Option<String>,
 }
 
@@ -138,6 +321,8 @@ struct ParsedRecurringPlanPayload {
     end_date: Option<mongodb::bson::DateTime>,
     is_active: bool,
     version: i32,
+    scenario_weights: Option<ScenarioWeights>,
+    probability: Option<f64>,
     notes: Option<String>,
 }
 
@@ -152,6 +337,7 @@ fn default_version() -> i32 {
 pub async fn recurring_plans_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
 
@@ -160,24 +346,56 @@ pub async fn recurring_plans_index(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .into_iter()
         .filter(|p| p.company_id == active_company)
+        .filter(|p| session_user.account_access().allows(&p.account_expected_id))
         .collect::<Vec<_>>();
     let active_name = session_user.user().company_name.clone();
+    let mut coverage = recurring_plan_coverage(&state, &active_company, chrono::Utc::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let currencies = CurrencyResolver::load(&state, &active_company).await?;
+    let totals = IndexTotals::build(
+        &currencies.base,
+        plans.iter().filter(|p| p.is_active).map(|p| {
+            (
+                currencies.resolve(None, Some(&p.account_expected_id)),
+                p.flow_type.clone(),
+                p.amount_estimated,
+            )
+        }),
+        &params,
+    );
 
     let rows = plans
         .into_iter()
         .filter_map(|p| {
-            p.id.map(|id| RecurringPlanRow {
-                id: id.to_hex(),
-                name: p.name,
-                company: active_name.clone(),
-                flow_type: flow_type_value(&p.flow_type).to_string(),
-                amount: p.amount_estimated,
-                active: p.is_active,
+            p.id.map(|id| {
+                let coverage = coverage.remove(&id);
+                RecurringPlanRow {
+                    id: id.to_hex(),
+                    name: p.name,
+                    company: active_name.clone(),
+                    flow_type: flow_type_value(&p.flow_type).to_string(),
+                    amount: p.amount_estimated,
+                    active: p.is_active,
+                    next_due_date: coverage
+                        .as_ref()
+                        .and_then(|c| c.next_due_date)
+                        .map(|d| d.to_chrono().format("%Y-%m-%d").to_string()),
+                    open_entries: coverage.as_ref().map_or(0, |c| c.open_entries),
+                    committed_next_12_months: format!(
+                        "{:.2}",
+                        coverage.map_or(0.0, |c| c.committed_next_12_months)
+                    ),
+                }
             })
         })
         .collect();
 
-    render(RecurringPlansIndexTemplate { plans: rows })
+    render(RecurringPlansIndexTemplate {
+        plans: rows,
+        totals,
+    })
 }
 
 #[utoipa::path(
@@ -197,6 +415,7 @@ pub async fn recurring_plans_data_api(
 ) -> Result<Json<Vec<RecurringPlanData>>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let active_name = session_user.user().company_name.clone();
+    let access = session_user.account_access();
     let plans = list_recurring_plans(&state)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -204,6 +423,7 @@ pub async fn recurring_plans_data_api(
     let rows = plans
         .into_iter()
         .filter(|plan| plan.company_id == active_company)
+        .filter(|plan| access.allows(&plan.account_expected_id))
         .filter_map(|plan| recurring_plan_data(plan, active_name.clone()))
         .collect();
 
@@ -235,6 +455,7 @@ pub async fn recurring_plan_data_api(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&plan.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&plan.account_expected_id])?;
 
     recurring_plan_data(plan, session_user.user().company_name.clone())
         .map(Json)
@@ -267,6 +488,9 @@ pub async fn recurring_plans_create_api(
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&parsed.account_expected_id]) {
+        return status.into_response();
+    }
     match create_recurring_plan(
         &state,
         &company_id,
@@ -287,6 +511,25 @@ pub async fn recurring_plans_create_api(
     .await
     {
         Ok(id) => {
+            if parsed.scenario_weights.is_some()
+                && set_recurring_plan_scenario_weights(
+                    &state,
+                    &id,
+                    &company_id,
+                    parsed.scenario_weights,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if parsed.probability.is_some()
+                && set_recurring_plan_probability(&state, &id, &company_id, parsed.probability)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             let generated_count = count_plan_entries(&state, &id).await.unwrap_or(0);
             (
                 StatusCode::CREATED,
@@ -332,7 +575,9 @@ pub async fn recurring_plan_update_api(
     };
     let existing = match get_recurring_plan_by_id(&state, &object_id).await {
         Ok(Some(plan)) => {
-            if let Err(status) = ensure_same_company(&plan.company_id, &company_id) {
+            if let Err(status) = ensure_same_company(&plan.company_id, &company_id)
+                .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id]))
+            {
                 return status.into_response();
             }
             plan
@@ -344,6 +589,9 @@ pub async fn recurring_plan_update_api(
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&parsed.account_expected_id]) {
+        return status.into_response();
+    }
     let before_count = count_plan_entries(&state, &object_id).await.unwrap_or(0);
 
     match update_recurring_plan(
@@ -367,6 +615,30 @@ pub async fn recurring_plan_update_api(
     .await
     {
         Ok(_) => {
+            if parsed.scenario_weights.is_some()
+                && set_recurring_plan_scenario_weights(
+                    &state,
+                    &object_id,
+                    &company_id,
+                    parsed.scenario_weights,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if parsed.probability.is_some()
+                && set_recurring_plan_probability(
+                    &state,
+                    &object_id,
+                    &company_id,
+                    parsed.probability,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             let after_count = count_plan_entries(&state, &object_id).await.unwrap_or(0);
             Json(serde_json::json!({
                 "ok": true,
@@ -409,7 +681,8 @@ pub async fn recurring_plan_delete_api(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
     if let Err(status) = match get_recurring_plan_by_id(&state, &object_id).await {
-        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &company_id),
+        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &company_id)
+            .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
@@ -460,7 +733,8 @@ pub async fn recurring_plan_generate_api(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
     if let Err(status) = match get_recurring_plan_by_id(&state, &object_id).await {
-        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &company_id),
+        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &company_id)
+            .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
@@ -497,7 +771,13 @@ pub async fn recurring_plans_new(
 
     let companies = company_options(&state, &active_company).await?;
     let categories = category_options(&state, None, &active_company).await?;
-    let accounts = account_options(&state, None, &active_company).await?;
+    let accounts = account_options(
+        &state,
+        None,
+        &active_company,
+        &session_user.account_access(),
+    )
+    .await?;
     let contacts = contact_options(&state, None, &active_company).await?;
 
     render(RecurringPlanFormTemplate {
@@ -519,6 +799,9 @@ pub async fn recurring_plans_new(
         contacts,
         is_edit: false,
         errors: None,
+        scenario_weights: None,
+        comments: None,
+        clone_notice: None,
     })
 }
 
@@ -538,7 +821,7 @@ pub async fn recurring_plans_create(
     let categories = category_options(&state, None, &company_id)
         .await
         .unwrap_or_default();
-    let accounts = account_options(&state, None, &company_id)
+    let accounts = account_options(&state, None, &company_id, &session_user.account_access())
         .await
         .unwrap_or_default();
     let contacts = contact_options(&state, None, &company_id)
@@ -567,13 +850,16 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
 
-    let category_id = match parse_object_id(&form.category_id, "Categoría") {
+    let category_id = match required_object_id(form.category_id, "Categoría") {
         Ok(id) => id,
         Err(msg) => {
             return render(RecurringPlanFormTemplate {
@@ -595,13 +881,17 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
 
-    let account_expected_id = match parse_object_id(&form.account_expected_id, "Cuenta esperada") {
+    let account_expected_id = match required_object_id(form.account_expected_id, "Cuenta esperada")
+    {
         Ok(id) => id,
         Err(msg) => {
             return render(RecurringPlanFormTemplate {
@@ -623,22 +913,36 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
+    if let Err(status) = ensure_account_access(&session_user, [&account_expected_id]) {
+        return status.into_response();
+    }
 
-    let contact_id = match form.contact_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(cid) => Some(parse_object_id(&cid, "Contacto").map_err(|msg| {
-            render(RecurringPlanFormTemplate {
+    let contact_id = form.contact_id;
+
+    if let Err(status) = validate_company_refs(
+        &state,
+        &company_id,
+        Some(&category_id),
+        Some(&account_expected_id),
+        contact_id.as_ref(),
+    )
+    .await
+    {
+        return status.into_response();
+    }
+
+    let amount_estimated = match parse_f64_field(&form.amount_estimated, "Monto estimado") {
+        Ok(v) => v,
+        Err(msg) => {
+            return render(RecurringPlanFormTemplate {
                 action: "/admin/recurring_plans".into(),
                 name: form.name.clone(),
                 flow_type: form.flow_type.clone(),
@@ -657,32 +961,16 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
-            .unwrap_or_else(|status| status.into_response())
-        })),
-        None => None,
-    };
-
-    let contact_id = match contact_id {
-        Some(Ok(id)) => Some(id),
-        Some(Err(resp)) => return resp,
-        None => None,
+            .unwrap_or_else(|status| status.into_response());
+        }
     };
 
-    if let Err(status) = validate_company_refs(
-        &state,
-        &company_id,
-        Some(&category_id),
-        Some(&account_expected_id),
-        contact_id.as_ref(),
-    )
-    .await
-    {
-        return status.into_response();
-    }
-
-    let amount_estimated = match parse_f64_field(&form.amount_estimated, "Monto estimado") {
+    let day_of_month = match parse_optional_i32_field(form.day_of_month.clone(), "Día del mes") {
         Ok(v) => v,
         Err(msg) => {
             return render(RecurringPlanFormTemplate {
@@ -704,14 +992,17 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
 
-    let day_of_month = match parse_optional_i32_field(form.day_of_month.clone(), "Día del mes") {
-        Ok(v) => v,
+    let start_date = match parse_datetime_field(&form.start_date, "Fecha de inicio") {
+        Ok(dt) => dt,
         Err(msg) => {
             return render(RecurringPlanFormTemplate {
                 action: "/admin/recurring_plans".into(),
@@ -732,13 +1023,16 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
 
-    let start_date = match parse_datetime_field(&form.start_date, "Fecha de inicio") {
+    let end_date = match parse_optional_datetime_field(form.end_date.clone(), "Fecha fin") {
         Ok(dt) => dt,
         Err(msg) => {
             return render(RecurringPlanFormTemplate {
@@ -760,15 +1054,32 @@ pub async fn recurring_plans_create(
                 contacts: contacts.clone(),
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
         }
     };
 
-    let end_date = match parse_optional_datetime_field(form.end_date.clone(), "Fecha fin") {
-        Ok(dt) => dt,
-        Err(msg) => {
+    let required = match required_fields(&state, &company_id).await {
+        Ok(policy) => policy,
+        Err(status) => return status.into_response(),
+    };
+    let schedule = PlanInput {
+        frequency: &form.frequency,
+        day_of_month,
+        start_date,
+        end_date,
+        flow_type: Some(&flow_type),
+        contact_id: contact_id.as_ref(),
+        notes: form.notes.as_deref(),
+        required: &required,
+    };
+    let frequency = match schedule.validate() {
+        Ok(frequency) => frequency,
+        Err(err) => {
             return render(RecurringPlanFormTemplate {
                 action: "/admin/recurring_plans".into(),
                 name: form.name.clone(),
@@ -787,7 +1098,10 @@ pub async fn recurring_plans_create(
                 accounts: accounts.clone(),
                 contacts: contacts.clone(),
                 is_edit: false,
-                errors: Some(msg),
+                errors: Some(err.to_string()),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
@@ -816,6 +1130,9 @@ pub async fn recurring_plans_create(
                 contacts,
                 is_edit: false,
                 errors: Some(msg),
+                scenario_weights: None,
+                comments: None,
+                clone_notice: None,
             })
             .map(IntoResponse::into_response)
             .unwrap_or_else(|status| status.into_response());
@@ -833,7 +1150,7 @@ pub async fn recurring_plans_create(
         &account_expected_id,
         contact_id,
         amount_estimated,
-        form.frequency.trim(),
+        &frequency,
         day_of_month,
         start_date,
         end_date,
@@ -843,7 +1160,11 @@ pub async fn recurring_plans_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/recurring_plans").into_response(),
+        Ok(_) => (
+            Flash::success("Plan creado."),
+            Redirect::to("/admin/recurring_plans"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -861,6 +1182,7 @@ pub async fn recurring_plans_edit(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&plan.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&plan.account_expected_id])?;
 
     let companies = company_options(&state, &active_company).await?;
     let categories = category_options(&state, Some(&plan.category_id), &active_company).await?;
@@ -868,6 +1190,7 @@ pub async fn recurring_plans_edit(
         &state,
         Some(&plan.account_expected_id),
         session_user.active_company_id(),
+        &session_user.account_access(),
     )
     .await?;
     let contacts = contact_options(
@@ -876,6 +1199,14 @@ pub async fn recurring_plans_edit(
         session_user.active_company_id(),
     )
     .await?;
+    let comments = comment_thread(
+        &state,
+        CommentEntity::RecurringPlan,
+        &object_id,
+        &session_user,
+    )
+    .await?;
+    let scenario_weights = scenario_weights_view(&id, &plan);
 
     render(RecurringPlanFormTemplate {
         action: format!("/admin/recurring_plans/{}/update", id),
@@ -899,6 +1230,116 @@ pub async fn recurring_plans_edit(
         contacts,
         is_edit: true,
         errors: None,
+        scenario_weights: Some(scenario_weights),
+        comments: Some(comments),
+        clone_notice: None,
+    })
+}
+
+/// POST /admin/recurring_plans/{id}/clone — the new-plan form prefilled with
+/// the plan's values, starting today. Nothing is saved until it is submitted.
+pub async fn recurring_plans_clone(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let plan = get_recurring_plan_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&plan.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&plan.account_expected_id])?;
+
+    let companies = company_options(&state, &active_company).await?;
+    let categories = category_options(&state, Some(&plan.category_id), &active_company).await?;
+    let accounts = account_options(
+        &state,
+        Some(&plan.account_expected_id),
+        &active_company,
+        &session_user.account_access(),
+    )
+    .await?;
+    let contacts = contact_options(&state, plan.contact_id.as_ref(), &active_company).await?;
+
+    let today = utc_day_start(DateTime::now());
+    let (start_date, end_date) = shift_to_start(plan.start_date, plan.end_date, today);
+    render(RecurringPlanFormTemplate {
+        action: "/admin/recurring_plans".into(),
+        flow_type: flow_type_value(&plan.flow_type).to_string(),
+        amount_estimated: plan.amount_estimated.to_string(),
+        frequency: plan.frequency,
+        day_of_month: plan.day_of_month.map(|d| d.to_string()).unwrap_or_default(),
+        start_date: datetime_to_string(&start_date),
+        end_date: end_date.map(|d| datetime_to_string(&d)).unwrap_or_default(),
+        version: "1".into(),
+        is_active: true,
+        notes: plan.notes.unwrap_or_default(),
+        companies,
+        flow_options: flow_options(flow_type_value(&plan.flow_type)),
+        categories,
+        accounts,
+        contacts,
+        is_edit: false,
+        errors: None,
+        scenario_weights: None,
+        comments: None,
+        clone_notice: Some(format!(
+            "Copia de «{}». Empieza hoy; revisa los datos antes de crear el plan.",
+            plan.name
+        )),
+        name: plan.name,
+    })
+}
+
+/// The edit form again with the submitted values and `errors`, for input that
+/// only fails once it is checked as a whole.
+async fn plan_edit_form_with_error(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+    existing: &RecurringPlan,
+    form: &RecurringPlanFormData,
+    errors: String,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = session_user.active_company_id();
+    let contact_id = form.contact_id;
+
+    let companies = company_options(state, active_company).await?;
+    let categories = category_options(state, form.category_id.as_ref(), active_company).await?;
+    let accounts = account_options(
+        state,
+        form.account_expected_id.as_ref(),
+        active_company,
+        &session_user.account_access(),
+    )
+    .await?;
+    let contacts = contact_options(state, contact_id.as_ref(), active_company).await?;
+
+    render(RecurringPlanFormTemplate {
+        action: format!("/admin/recurring_plans/{}/update", id),
+        name: form.name.clone(),
+        flow_type: form.flow_type.clone(),
+        amount_estimated: form.amount_estimated.clone(),
+        frequency: form.frequency.clone(),
+        day_of_month: form.day_of_month.clone().unwrap_or_default(),
+        start_date: form.start_date.clone(),
+        end_date: form.end_date.clone().unwrap_or_default(),
+        version: form.version.clone(),
+        is_active: form.is_active,
+        notes: form.notes.clone().unwrap_or_default(),
+        companies,
+        flow_options: flow_options(&form.flow_type),
+        categories,
+        accounts,
+        contacts,
+        is_edit: true,
+        errors: Some(errors),
+        scenario_weights: Some(scenario_weights_view(id, existing)),
+        comments: None,
+        clone_notice: None,
     })
 }
 
@@ -918,43 +1359,39 @@ pub async fn recurring_plans_update(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    if let Err(status) = match get_recurring_plan_by_id(&state, &object_id).await {
-        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &active_company),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-    } {
+    let existing = match get_recurring_plan_by_id(&state, &object_id).await {
+        Ok(Some(plan)) => plan,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&existing.company_id, &active_company)
+        .and_then(|_| ensure_account_access(&session_user, [&existing.account_expected_id]))
+    {
         return status.into_response();
     }
 
+    let fields = plan_form_fields(&form);
+
     let flow_type = match parse_flow_type(&form.flow_type) {
         Ok(f) => f,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    let category_id = match parse_object_id(&form.category_id, "Categoría") {
+    let category_id = match required_object_id(form.category_id, "Categoría") {
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    let account_expected_id = match parse_object_id(&form.account_expected_id, "Cuenta esperada") {
+    let account_expected_id = match required_object_id(form.account_expected_id, "Cuenta esperada")
+    {
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
+    if let Err(status) = ensure_account_access(&session_user, [&account_expected_id]) {
+        return status.into_response();
+    }
 
-    let contact_id = match form.contact_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(cid) => match parse_object_id(&cid, "Contacto") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
+    let contact_id = form.contact_id;
 
     let amount_estimated = match parse_f64_field(&form.amount_estimated, "Monto estimado") {
         Ok(v) => v,
@@ -976,6 +1413,37 @@ pub async fn recurring_plans_update(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
+    let required = match required_fields(&state, &active_company).await {
+        Ok(policy) => policy,
+        Err(status) => return status.into_response(),
+    };
+    let schedule = PlanInput {
+        frequency: &form.frequency,
+        day_of_month,
+        start_date,
+        end_date,
+        flow_type: Some(&flow_type),
+        contact_id: contact_id.as_ref(),
+        notes: form.notes.as_deref(),
+        required: &required,
+    };
+    let frequency = match schedule.validate() {
+        Ok(frequency) => frequency,
+        Err(err) => {
+            return plan_edit_form_with_error(
+                &state,
+                &session_user,
+                &id,
+                &existing,
+                &form,
+                err.to_string(),
+            )
+            .await
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+
     let version = match parse_i32_field(&form.version, "Versión") {
         Ok(v) => v,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
@@ -995,6 +1463,35 @@ pub async fn recurring_plans_update(
         return status.into_response();
     }
 
+    if !form.confirm {
+        let updated = RecurringPlan {
+            name: form.name.trim().to_string(),
+            flow_type: flow_type.clone(),
+            category_id,
+            account_expected_id,
+            contact_id,
+            amount_estimated,
+            frequency: frequency.clone(),
+            day_of_month,
+            start_date,
+            end_date,
+            is_active: form.is_active,
+            notes: notes.clone(),
+            ..existing.clone()
+        };
+        // Saving only notes regenerates the same entries; no need to ask.
+        if plan_changed_significantly(&existing, &updated) {
+            let preview = match preview_recurring_plan_update(&state, &existing, &updated).await {
+                Ok(preview) => preview,
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+            if !preview.is_empty() {
+                return render(plan_confirm_template(&id, &updated, fields, preview))
+                    .into_response();
+            }
+        }
+    }
+
     match update_recurring_plan(
         &state,
         &object_id,
@@ -1005,7 +1502,7 @@ pub async fn recurring_plans_update(
         &account_expected_id,
         contact_id,
         amount_estimated,
-        form.frequency.trim(),
+        &frequency,
         day_of_month,
         start_date,
         end_date,
@@ -1015,15 +1512,89 @@ pub async fn recurring_plans_update(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/recurring_plans").into_response(),
+        Ok(_) => (
+            Flash::success("Plan actualizado."),
+            Redirect::to("/admin/recurring_plans"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
+/// A plan of the active company the user may delete and the confirmation
+/// of deleting it.
+async fn plan_delete_confirmation(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, Confirmation), StatusCode> {
+    let active_company = require_admin_active(session_user)?;
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let plan = get_recurring_plan_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&plan.company_id, &active_company)?;
+    ensure_account_access(session_user, [&plan.account_expected_id])?;
+    let confirmation = Confirmation {
+        title: "Eliminar plan".to_string(),
+        message: format!(
+            "Se desactivará el plan «{}» y se borrarán sus compromisos futuros que sigan abiertos. Los pagados se conservan.",
+            plan.name
+        ),
+        action: format!("/admin/recurring_plans/{id}/delete"),
+        cancel_url: "/admin/recurring_plans".to_string(),
+        button_label: "Eliminar plan".to_string(),
+        expected_name: None,
+    };
+    Ok((object_id, confirmation))
+}
+
+/// GET /admin/recurring_plans/{id}/delete — asks before deleting.
+pub async fn recurring_plans_delete_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Response {
+    match plan_delete_confirmation(&state, &session_user, &id).await {
+        Ok((_, confirmation)) => confirm_page(&session_user, &confirmation),
+        Err(status) => status.into_response(),
+    }
+}
+
 pub async fn recurring_plans_delete(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ConfirmForm>,
+) -> impl IntoResponse {
+    let (object_id, confirmation) = match plan_delete_confirmation(&state, &session_user, &id).await
+    {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    if let Err(response) = verify(&session_user, &confirmation, &form) {
+        return *response;
+    }
+
+    match delete_recurring_plan(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Plan eliminado."),
+            Redirect::to("/admin/recurring_plans"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el plan."),
+            Redirect::to("/admin/recurring_plans"),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn recurring_plans_generate(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
 ) -> impl IntoResponse {
     let active_company = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -1036,23 +1607,31 @@ pub async fn recurring_plans_delete(
     };
 
     if let Err(status) = match get_recurring_plan_by_id(&state, &object_id).await {
-        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &active_company),
+        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &active_company)
+            .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
         return status.into_response();
     }
 
-    match delete_recurring_plan(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/recurring_plans").into_response(),
+    match regenerate_planned_entries_for_plan_id(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Compromisos del plan generados."),
+            Redirect::to("/admin/recurring_plans"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
-pub async fn recurring_plans_generate(
+/// Saves the plan's own scenario weights, or clears them when `custom` is
+/// unchecked so forecast generation falls back to the run's weights.
+pub async fn recurring_plans_scenario_weights(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ScenarioWeightsFormData>,
 ) -> impl IntoResponse {
     let active_company = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -1065,51 +1644,291 @@ pub async fn recurring_plans_generate(
     };
 
     if let Err(status) = match get_recurring_plan_by_id(&state, &object_id).await {
-        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &active_company),
+        Ok(Some(plan)) => ensure_same_company(&plan.company_id, &active_company)
+            .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id])),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
         return status.into_response();
     }
 
-    match regenerate_planned_entries_for_plan_id(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/recurring_plans").into_response(),
+    let weights = if form.custom {
+        match parse_scenario_weight_pcts(&form.best_pct, &form.expected_pct, &form.worst_pct) {
+            Ok(weights) => Some(weights),
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    } else {
+        None
+    };
+
+    match set_recurring_plan_scenario_weights(&state, &object_id, &active_company, weights).await {
+        Ok(_) => (
+            Flash::success("Pesos de escenario guardados."),
+            Redirect::to(&format!("/admin/recurring_plans/{}/edit", id)),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Saves the chance, in percent, that an income plan's money arrives, or
+/// clears it when left blank so its entries count in full.
+pub async fn recurring_plans_probability(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<ProbabilityFormData>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let plan = match get_recurring_plan_by_id(&state, &object_id).await {
+        Ok(Some(plan)) => plan,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Err(status) = ensure_same_company(&plan.company_id, &active_company)
+        .and_then(|_| ensure_account_access(&session_user, [&plan.account_expected_id]))
+    {
+        return status.into_response();
+    }
+
+    let probability = match parse_optional_f64_field(Some(form.probability_pct), "Probabilidad")
+        .and_then(|pct| {
+            pct.map(|pct| validate_probability(&plan.flow_type, pct))
+                .transpose()
+        }) {
+        Ok(probability) => probability,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    match set_recurring_plan_probability(&state, &object_id, &active_company, probability).await {
+        Ok(_) => (
+            Flash::success("Probabilidad guardada."),
+            Redirect::to(&format!("/admin/recurring_plans/{}/edit", id)),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
+/// Stored versions of a plan, newest first, each with the fields it changed
+/// from the one before.
+pub async fn recurring_plans_versions(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let plan = get_recurring_plan_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&plan.company_id, &active_company)?;
+    ensure_account_access(&session_user, [&plan.account_expected_id])?;
+
+    let versions = list_plan_versions(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let entries_per_version = count_planned_entries_per_plan_version(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let names = plan_reference_names(&state, &active_company).await?;
+    let mut views: Vec<PlanVersionView> = versions
+        .iter()
+        .enumerate()
+        .map(|(idx, version)| {
+            let changes = match idx.checked_sub(1).map(|prev| &versions[prev].plan) {
+                Some(previous) => diff_plan_versions(previous, &version.plan)
+                    .into_iter()
+                    .map(|change| plan_change_view(change, &names))
+                    .collect(),
+                None => Vec::new(),
+            };
+            PlanVersionView {
+                version: version.version,
+                saved_at: version
+                    .created_at
+                    .to_chrono()
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                entries: entries_per_version
+                    .get(&version.version)
+                    .copied()
+                    .unwrap_or(0),
+                has_previous: idx > 0,
+                changes,
+            }
+        })
+        .collect();
+    views.reverse();
+
+    render(RecurringPlanVersionsTemplate {
+        plan_id: id,
+        name: plan.name,
+        current_version: plan.version,
+        versions: views,
+    })
+}
+
+/// Names of the company's categories, accounts and contacts keyed by hex id,
+/// to show references in version diffs.
+async fn plan_reference_names(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<HashMap<String, String>, StatusCode> {
+    let mut names = HashMap::new();
+    let categories = list_categories(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for category in categories
+        .into_iter()
+        .filter(|c| c.company_id == *company_id)
+    {
+        if let Some(id) = category.id {
+            names.insert(id.to_hex(), category.name);
+        }
+    }
+    let accounts = list_accounts(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for account in accounts.into_iter().filter(|a| a.company_id == *company_id) {
+        if let Some(id) = account.id {
+            names.insert(id.to_hex(), account.name);
+        }
+    }
+    let contacts = list_contacts(state)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for contact in contacts.into_iter().filter(|c| c.company_id == *company_id) {
+        if let Some(id) = contact.id {
+            names.insert(id.to_hex(), contact.name);
+        }
+    }
+    Ok(names)
+}
+
+fn plan_change_view(change: PlanFieldChange, names: &HashMap<String, String>) -> PlanChangeView {
+    let field = change.field;
+    let display = |value: Option<String>| -> String {
+        let Some(value) = value else {
+            return "—".to_string();
+        };
+        match (field, value.as_str()) {
+            ("category_id" | "account_expected_id" | "contact_id", _) => {
+                names.get(&value).cloned().unwrap_or(value)
+            }
+            ("flow_type", "income") => "Ingreso".to_string(),
+            ("flow_type", "expense") => "Gasto".to_string(),
+            ("is_active", "true") => "Sí".to_string(),
+            ("is_active", "false") => "No".to_string(),
+            ("frequency", frequency) => frequency_label(frequency).to_string(),
+            _ => value,
+        }
+    };
+    PlanChangeView {
+        label: match field {
+            "name" => "Nombre",
+            "flow_type" => "Tipo de flujo",
+            "category_id" => "Categoría",
+            "account_expected_id" => "Cuenta esperada",
+            "contact_id" => "Contacto",
+            "amount_estimated" => "Monto estimado",
+            "frequency" => "Frecuencia",
+            "day_of_month" => "Día del mes",
+            "start_date" => "Fecha de inicio",
+            "end_date" => "Fecha de término",
+            "is_active" => "Activo",
+            "notes" => "Notas",
+            other => other,
+        },
+        before: display(change.before),
+        after: display(change.after),
+    }
+}
+
+fn frequency_label(frequency: &str) -> &str {
+    match frequency {
+        "daily" => "Diaria",
+        "weekly" => "Semanal",
+        "biweekly" => "Quincenal",
+        "monthly" => "Mensual",
+        "quarterly" => "Trimestral",
+        "yearly" => "Anual",
+        other => other,
+    }
+}
+
 async fn parse_recurring_plan_payload(
     state: &AppState,
     company_id: &ObjectId,
     payload: RecurringPlanPayload,
-) -> Result<ParsedRecurringPlanPayload, StatusCode> {
+) -> Result<ParsedRecurringPlanPayload, Response> {
+    let bad_request = |_: String| StatusCode::BAD_REQUEST.into_response();
     let name = payload.name.trim().to_string();
     if name.is_empty() || payload.amount_estimated < 0.0 {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-    let frequency = payload.frequency.trim().to_lowercase();
-    if !matches!(frequency.as_str(), "monthly" | "weekly") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
-    if let Some(day) = payload.day_of_month {
-        if !(1..=31).contains(&day) {
-            return Err(StatusCode::BAD_REQUEST);
-        }
-    }
-    let flow_type = parse_flow_type(&payload.flow_type).map_err(|_| StatusCode::BAD_REQUEST)?;
-    let category_id = parse_object_id(&payload.category_id, "category_id")
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let flow_type = parse_flow_type(&payload.flow_type).map_err(bad_request)?;
+    let scenario_weights = payload
+        .scenario_weights
+        .map(validate_scenario_weights)
+        .transpose()
+        .map_err(bad_request)?;
+    let probability = payload
+        .probability
+        .map(|pct| validate_probability(&flow_type, pct))
+        .transpose()
+        .map_err(|message| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response()
+        })?;
+    let category_id = parse_object_id(&payload.category_id, "category_id").map_err(bad_request)?;
     let account_expected_id = parse_object_id(&payload.account_expected_id, "account_expected_id")
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let contact_id = parse_optional_object_id(payload.contact_id)?;
-    let start_date = parse_datetime_field(&payload.start_date, "start_date")
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let end_date = parse_optional_datetime_field(payload.end_date, "end_date")
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    if let Some(end_date) = end_date.as_ref() {
-        if end_date < &start_date {
-            return Err(StatusCode::BAD_REQUEST);
-        }
+        .map_err(bad_request)?;
+    let contact_id = payload.contact_id;
+    let start_date =
+        parse_datetime_field(&payload.start_date, "start_date").map_err(bad_request)?;
+    let end_date =
+        parse_optional_datetime_field(payload.end_date, "end_date").map_err(bad_request)?;
+
+    let required = required_fields(state, company_id)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let schedule = PlanInput {
+        frequency: &payload.frequency,
+        day_of_month: payload.day_of_month,
+        start_date,
+        end_date,
+        flow_type: Some(&flow_type),
+        contact_id: contact_id.as_ref(),
+        notes: payload.notes.as_deref(),
+        required: &required,
+    };
+    let frequency = schedule.validate().map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response()
+    })?;
+    // The API accepts a narrower set than the admin form.
+    if !matches!(frequency.as_str(), "monthly" | "weekly") {
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
 
     validate_company_refs(
@@ -1119,7 +1938,8 @@ async fn parse_recurring_plan_payload(
         Some(&account_expected_id),
         contact_id.as_ref(),
     )
-    .await?;
+    .await
+    .map_err(IntoResponse::into_response)?;
 
     Ok(ParsedRecurringPlanPayload {
         name,
@@ -1134,19 +1954,12 @@ async fn parse_recurring_plan_payload(
         end_date,
         is_active: payload.is_active,
         version: payload.version,
+        scenario_weights,
+        probability,
         notes: clean_opt(payload.notes),
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, StatusCode> {
-    match clean_opt(value) {
-        Some(value) => ObjectId::from_str(&value)
-            .map(Some)
-            .map_err(|_| StatusCode::BAD_REQUEST),
-        None => Ok(None),
-    }
-}
-
 async fn count_plan_entries(state: &AppState, plan_id: &ObjectId) -> Result<usize, StatusCode> {
     let entries = crate::state::list_planned_entries(state)
         .await
@@ -1175,6 +1988,82 @@ fn recurring_plan_data(plan: RecurringPlan, company: String) -> Option<Recurring
         end_date: plan.end_date.map(|date| datetime_to_string(&date)),
         is_active: plan.is_active,
         version: plan.version,
+        scenario_weights: plan.scenario_weights,
+        probability: plan.probability,
         notes: plan.notes,
     })
 }
+
+/// Submitted values of the plan form, to post them again from the
+/// confirmation page.
+fn plan_form_fields(form: &RecurringPlanFormData) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("name", form.name.clone()),
+        ("company_id", form.company_id.clone()),
+        ("flow_type", form.flow_type.clone()),
+        ("category_id", id_or_empty(form.category_id)),
+        ("account_expected_id", id_or_empty(form.account_expected_id)),
+        ("contact_id", id_or_empty(form.contact_id)),
+        ("amount_estimated", form.amount_estimated.clone()),
+        ("frequency", form.frequency.clone()),
+        (
+            "day_of_month",
+            form.day_of_month.clone().unwrap_or_default(),
+        ),
+        ("start_date", form.start_date.clone()),
+        ("end_date", form.end_date.clone().unwrap_or_default()),
+        ("version", form.version.clone()),
+        ("notes", form.notes.clone().unwrap_or_default()),
+    ];
+    if form.is_active {
+        fields.push(("is_active", "true".to_string()));
+    }
+    fields
+}
+
+fn plan_confirm_template(
+    id: &str,
+    updated: &RecurringPlan,
+    fields: Vec<(&'static str, String)>,
+    preview: PlanRegenerationPreview,
+) -> RecurringPlanConfirmTemplate {
+    let entry_view = |entry: PlannedEntry| PreviewEntryView {
+        name: entry.name,
+        due_date: entry.due_date.to_chrono().format("%Y-%m-%d").to_string(),
+        amount: entry.amount_estimated,
+        status: planned_status_label(&entry.status),
+    };
+    RecurringPlanConfirmTemplate {
+        action: format!("/admin/recurring_plans/{}/update", id),
+        edit_url: format!("/admin/recurring_plans/{}/edit", id),
+        name: updated.name.clone(),
+        fields,
+        removed: preview.removed.into_iter().map(entry_view).collect(),
+        kept: preview.kept.into_iter().map(entry_view).collect(),
+        created: preview
+            .created
+            .iter()
+            .map(|due| due.to_chrono().format("%Y-%m-%d").to_string())
+            .collect(),
+        amount_estimated: updated.amount_estimated,
+    }
+}
+
+fn scenario_weights_view(id: &str, plan: &RecurringPlan) -> ScenarioWeightsView {
+    let weights = plan.scenario_weights;
+    let shown = weights.unwrap_or_default();
+    let pct = |weight: f64| format!("{}", (weight * 100.0).round());
+    ScenarioWeightsView {
+        action: format!("/admin/recurring_plans/{}/scenario_weights", id),
+        custom: weights.is_some(),
+        best_pct: pct(shown.best),
+        expected_pct: pct(shown.expected),
+        worst_pct: pct(shown.worst),
+        probability: matches!(plan.flow_type, crate::models::FlowType::Income).then(|| {
+            ProbabilityView {
+                action: format!("/admin/recurring_plans/{}/probability", id),
+                pct: plan.probability.map(|p| p.to_string()).unwrap_or_default(),
+            }
+        }),
+    }
+}
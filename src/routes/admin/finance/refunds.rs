@@ -0,0 +1,242 @@
+// Refunds of incomes and expenses, recorded from the edit page of the
+// original transaction or through the API. See `state::refunds`.
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::post,
+};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::Deserialize;
+
+use crate::{
+    flash::Flash,
+    models::{Transaction, TransactionType},
+    routes::Routes,
+    session::SessionUser,
+    state::{AppState, create_refund, get_transaction_by_id, list_refunds, refunded_amount},
+};
+
+use super::helpers::*;
+
+/// Refunds of transactions.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/transactions/{id}/refund", post(transactions_refund))
+        .route(
+            "/api/admin/transactions/{id}/refund",
+            post(transaction_refund_api),
+        )
+}
+
+#[derive(Deserialize)]
+pub struct RefundFormData {
+    amount: String,
+    date: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RefundPayload {
+    /// Amount given back, positive.
+    pub amount: f64,
+    pub date: String,
+    /// Defaults to "Reembolso: " and the description of the original.
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Refund section of the edit page: the refunds of an income or expense and
+/// the form for a new one, or the link back to the original of a refund.
+pub(super) struct RefundsPanel {
+    /// Set when the edited transaction is itself a refund.
+    pub original: Option<RefundLink>,
+    pub refunds: Vec<RefundLink>,
+    pub refunded: f64,
+    /// What is left to refund; the form is hidden at zero.
+    pub available: f64,
+    pub action: String,
+    pub date: String,
+}
+
+pub(super) struct RefundLink {
+    pub id: String,
+    pub description: String,
+    pub date: String,
+    pub amount: f64,
+}
+
+fn refund_link(tx: Transaction) -> Option<RefundLink> {
+    Some(RefundLink {
+        id: tx.id?.to_hex(),
+        description: tx.description,
+        date: datetime_to_string(&tx.date),
+        amount: tx.amount,
+    })
+}
+
+/// The refund section for `transaction`; transfers and drafts get none.
+pub(super) async fn refunds_panel(
+    state: &AppState,
+    transaction: &Transaction,
+) -> Result<Option<RefundsPanel>, StatusCode> {
+    let Some(id) = transaction.id else {
+        return Ok(None);
+    };
+    let now = datetime_to_string(&DateTime::now());
+    if let Some(original_id) = transaction.refund_of {
+        let original = get_transaction_by_id(state, &original_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Some(RefundsPanel {
+            original: original.and_then(refund_link),
+            refunds: Vec::new(),
+            refunded: 0.0,
+            available: 0.0,
+            action: String::new(),
+            date: now,
+        }));
+    }
+    if transaction.transaction_type == TransactionType::Transfer || !transaction.is_confirmed {
+        return Ok(None);
+    }
+    let refunds = list_refunds(state, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let refunded = refunded_amount(&refunds);
+    Ok(Some(RefundsPanel {
+        original: None,
+        refunds: refunds.into_iter().filter_map(refund_link).collect(),
+        refunded,
+        available: (transaction.amount - refunded).max(0.0),
+        action: format!("/admin/transactions/{}/refund", id.to_hex()),
+        date: now,
+    }))
+}
+
+async fn load_refundable(
+    state: &AppState,
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+    id: &str,
+) -> Result<ObjectId, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let tx = get_transaction_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&tx.company_id, company_id)?;
+    ensure_transaction_access(session_user, &tx)?;
+    Ok(object_id)
+}
+
+/// POST /admin/transactions/{id}/refund — records a refund from the edit
+/// page of the original and returns to it.
+pub async fn transactions_refund(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<RefundFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let original_id = match load_refundable(&state, &session_user, &company_id, &id).await {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let back = Redirect::to(&format!("/admin/transactions/{id}/edit"));
+    let parsed = parse_f64_field(&form.amount, "Monto")
+        .and_then(|amount| parse_datetime_field(&form.date, "Fecha").map(|date| (amount, date)));
+    let (amount, date) = match parsed {
+        Ok(parsed) => parsed,
+        Err(message) => return (Flash::error(message), back).into_response(),
+    };
+    match create_refund(
+        &state,
+        &company_id,
+        &original_id,
+        date,
+        amount,
+        &form.description,
+        clean_opt(form.notes),
+    )
+    .await
+    {
+        Ok(_) => (Flash::success("Reembolso registrado."), back).into_response(),
+        Err(err) => (Flash::error(err.to_string()), back).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/{id}/refund",
+    tag = "finance",
+    params(("id" = String, Path, description = "Transaction being refunded")),
+    request_body = RefundPayload,
+    responses(
+        (status = 201, description = "Refund recorded; returns its id"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 400, description = "Transfer, draft or refund, or more than what is left to refund")
+    ),
+    security(("session" = []))
+)]
+pub async fn transaction_refund_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<RefundPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let original_id = match load_refundable(&state, &session_user, &company_id, &id).await {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let date = match parse_datetime_field(&payload.date, "date") {
+        Ok(date) => date,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    match create_refund(
+        &state,
+        &company_id,
+        &original_id,
+        date,
+        payload.amount,
+        payload.description.as_deref().unwrap_or_default(),
+        clean_opt(payload.notes),
+    )
+    .await
+    {
+        Ok(id) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "id": id.to_hex() })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": err.to_string() })),
+        )
+            .into_response(),
+    }
+}
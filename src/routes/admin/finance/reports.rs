@@ -0,0 +1,1145 @@
+// Aging report: open commitments past their due date, bucketed by days late,
+// split into receivables (income) and payables (expense) per contact.
+// Burn rate: average monthly spend, runway and the change from the period
+// before, as JSON for the dashboard.
+// Cash calendar: net cash movement per day around today, as a heatmap page
+// drawn from one JSON endpoint.
+// Net worth: balance of every active account plus the unrealized gains of the
+// revalued investment accounts, as JSON.
+// Income statement: income and expense per category and month against the
+// same months a year before, as a page and as JSON.
+// Taxes: tax collected on income and paid on expenses in a month, per tax
+// profile, as a page and as JSON.
+// Exchange results: gains and losses realized by foreign-currency
+// settlements in a date range, and the action that records them as
+// adjustments, as a page and as JSON.
+
+use std::{collections::HashMap, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use chrono::Datelike;
+use futures::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::FlowType,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AGING_BUCKETS, AgingRow, AppState, BURN_RATE_DEFAULT_MONTHS, BURN_RATE_MAX_MONTHS,
+        BurnPeriod, CASH_CALENDAR_DEFAULT_DAYS, CASH_CALENDAR_MAX_DAYS, FxReport,
+        INCOME_STATEMENT_DEFAULT_MONTHS, INCOME_STATEMENT_MAX_MONTHS, IncomeStatement, TaxReport,
+        aging_report, burn_rate_analytics, cash_calendar, fx_report, income_statement,
+        net_worth_report, record_fx_adjustments, tax_report, trend_delta,
+    },
+};
+
+use super::helpers::*;
+
+/// Finance reports.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/reports/aging", get(reports_aging))
+        .route("/api/admin/reports/burn-rate", get(reports_burn_rate_api))
+        .route("/api/admin/reports/net-worth", get(reports_net_worth_api))
+        .route("/admin/reports/cash_calendar", get(reports_cash_calendar))
+        .route(
+            "/api/admin/reports/cash-calendar",
+            get(reports_cash_calendar_api),
+        )
+        .route(
+            "/admin/reports/income_statement",
+            get(reports_income_statement),
+        )
+        .route(
+            "/api/admin/reports/income-statement",
+            get(reports_income_statement_api),
+        )
+        .route("/admin/reports/taxes", get(reports_taxes))
+        .route("/api/admin/reports/taxes", get(reports_taxes_api))
+        .route("/admin/reports/fx", get(reports_fx))
+        .route("/api/admin/reports/fx", get(reports_fx_api))
+        .route("/admin/reports/fx/record", post(reports_fx_record))
+        .route("/api/admin/reports/fx/record", post(reports_fx_record_api))
+}
+
+#[derive(Deserialize)]
+pub struct AgingQuery {
+    /// `csv` downloads the report instead of rendering it.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+struct AgingLine {
+    contact: String,
+    buckets: [f64; 4],
+    total: f64,
+    entries: i64,
+}
+
+struct AgingSection {
+    title: &'static str,
+    lines: Vec<AgingLine>,
+    totals: [f64; 4],
+    total: f64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/reports/aging.html")]
+struct AgingTemplate {
+    as_of: String,
+    buckets: [&'static str; 4],
+    sections: Vec<AgingSection>,
+}
+
+fn section_title(flow_type: &FlowType) -> &'static str {
+    match flow_type {
+        FlowType::Income => "Por cobrar",
+        FlowType::Expense => "Por pagar",
+    }
+}
+
+fn aging_sections(rows: Vec<AgingRow>, contacts: &HashMap<ObjectId, String>) -> Vec<AgingSection> {
+    [FlowType::Income, FlowType::Expense]
+        .into_iter()
+        .map(|flow_type| {
+            let lines: Vec<AgingLine> = rows
+                .iter()
+                .filter(|row| row.flow_type == flow_type)
+                .map(|row| AgingLine {
+                    contact: row
+                        .contact_id
+                        .and_then(|id| contacts.get(&id).cloned())
+                        .unwrap_or_else(|| "Sin contacto".to_string()),
+                    buckets: row.buckets,
+                    total: row.total(),
+                    entries: row.entries,
+                })
+                .collect();
+            let mut totals = [0.0; 4];
+            for line in &lines {
+                for (total, amount) in totals.iter_mut().zip(line.buckets) {
+                    *total += amount;
+                }
+            }
+            AgingSection {
+                title: section_title(&flow_type),
+                lines,
+                totals,
+                total: totals.iter().sum(),
+            }
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn aging_csv(sections: &[AgingSection]) -> String {
+    let mut out = format!(
+        "tipo,contacto,{},total,compromisos\n",
+        AGING_BUCKETS.join(",")
+    );
+    for section in sections {
+        for line in &section.lines {
+            out.push_str(&format!(
+                "{},{},{:.2},{:.2},{:.2},{:.2},{:.2},{}\n",
+                csv_field(section.title),
+                csv_field(&line.contact),
+                line.buckets[0],
+                line.buckets[1],
+                line.buckets[2],
+                line.buckets[3],
+                line.total,
+                line.entries,
+            ));
+        }
+    }
+    out
+}
+
+pub async fn reports_aging(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AgingQuery>,
+) -> Result<Response, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let now = DateTime::now();
+    let rows = aging_report(&state, &company_id, now)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let contacts: HashMap<ObjectId, String> = state
+        .contacts
+        .find(doc! { "company_id": company_id })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter_map(|c| c.id.map(|id| (id, c.name)))
+        .collect();
+    let sections = aging_sections(rows, &contacts);
+    let as_of = now.to_chrono().format("%Y-%m-%d").to_string();
+
+    if query.format.as_deref() == Some("csv") {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"antiguedad-{as_of}.csv\""),
+                ),
+            ],
+            aging_csv(&sections),
+        )
+            .into_response());
+    }
+
+    render(AgingTemplate {
+        as_of,
+        buckets: AGING_BUCKETS,
+        sections,
+    })
+    .map(IntoResponse::into_response)
+}
+
+#[derive(Deserialize)]
+pub struct BurnRateQuery {
+    /// Months averaged, 1 to `BURN_RATE_MAX_MONTHS`.
+    #[serde(default)]
+    months: Option<u32>,
+}
+
+/// Flows of one window, totals and monthly averages.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BurnRatePeriod {
+    /// First day of the window, `YYYY-MM-DD`.
+    pub from: String,
+    /// First day after the window.
+    pub to: String,
+    pub income: f64,
+    pub expense: f64,
+    pub avg_monthly_income: f64,
+    /// Average monthly expense.
+    pub gross_burn: f64,
+    /// Average monthly expense minus income; negative when the company earns
+    /// more than it spends.
+    pub net_burn: f64,
+}
+
+/// Change from the previous window as a fraction (0.25 is 25% more); `null`
+/// when the previous value is zero.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BurnRateDeltas {
+    pub income: Option<f64>,
+    pub gross_burn: Option<f64>,
+    pub net_burn: Option<f64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BurnRateResponse {
+    pub currency: String,
+    pub months: u32,
+    /// Balance of the active accounts in the company currency.
+    pub cash_balance: f64,
+    pub current: BurnRatePeriod,
+    pub previous: BurnRatePeriod,
+    pub deltas: BurnRateDeltas,
+    /// Monthly net flow the runway assumes.
+    pub projected_monthly_net: f64,
+    /// `planned` when it comes from the open planned entries of the next
+    /// `months` months, `history` when nothing is planned.
+    pub runway_basis: String,
+    /// Months until cash runs out; `null` when the projected flow is not
+    /// negative.
+    pub runway_months: Option<f64>,
+}
+
+fn burn_rate_period(period: &BurnPeriod, months: u32) -> BurnRatePeriod {
+    BurnRatePeriod {
+        from: period.from.to_chrono().format("%Y-%m-%d").to_string(),
+        to: period.to.to_chrono().format("%Y-%m-%d").to_string(),
+        income: period.income,
+        expense: period.expense,
+        avg_monthly_income: period.income / f64::from(months),
+        gross_burn: period.gross_burn(months),
+        net_burn: period.net_burn(months),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/burn-rate",
+    tag = "finance",
+    params(("months" = Option<u32>, Query, description = "Whole months averaged, before the current one; 3 by default, 24 at most")),
+    responses(
+        (status = 200, description = "Burn rate, runway and trend of the active company", body = BurnRateResponse),
+        (status = 400, description = "Invalid number of months"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_burn_rate_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BurnRateQuery>,
+) -> Result<Json<BurnRateResponse>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let months = query.months.unwrap_or(BURN_RATE_DEFAULT_MONTHS);
+    if !(1..=BURN_RATE_MAX_MONTHS).contains(&months) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let analytics = burn_rate_analytics(&state, &company_id, months, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let current = burn_rate_period(&analytics.current, months);
+    let previous = burn_rate_period(&analytics.previous, months);
+    let deltas = BurnRateDeltas {
+        income: trend_delta(current.avg_monthly_income, previous.avg_monthly_income),
+        gross_burn: trend_delta(current.gross_burn, previous.gross_burn),
+        net_burn: trend_delta(current.net_burn, previous.net_burn),
+    };
+    let (projected_monthly_net, basis) = analytics.projected_monthly_net();
+    Ok(Json(BurnRateResponse {
+        currency: analytics.currency.clone(),
+        months,
+        cash_balance: analytics.cash_balance,
+        current,
+        previous,
+        deltas,
+        projected_monthly_net,
+        runway_basis: basis.as_str().to_string(),
+        runway_months: analytics.runway_months(),
+    }))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct NetWorthAccount {
+    pub account_id: String,
+    pub name: String,
+    pub account_type: String,
+    pub currency: String,
+    /// Balance from the recorded transactions.
+    pub book_balance: f64,
+    /// Gain of the latest valuation; zero unless the account is a revalued
+    /// investment.
+    pub unrealized_gain: f64,
+    pub value: f64,
+    /// Day of the latest valuation, `YYYY-MM-DD`.
+    pub valued_at: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct NetWorthResponse {
+    pub currency: String,
+    /// Totals of the accounts in the company currency; accounts in other
+    /// currencies are listed but left out rather than mixed in.
+    pub book_balance: f64,
+    pub unrealized_gain: f64,
+    pub net_worth: f64,
+    pub accounts: Vec<NetWorthAccount>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/net-worth",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Net worth of the active company with unrealized gains", body = NetWorthResponse),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_net_worth_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<NetWorthResponse>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let mut report = net_worth_report(&state, &company_id, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let access = session_user.account_access();
+    report.lines.retain(|line| access.allows(&line.account_id));
+
+    let totals = report
+        .totals()
+        .get(&report.currency)
+        .copied()
+        .unwrap_or_default();
+    let accounts = report
+        .lines
+        .iter()
+        .map(|line| NetWorthAccount {
+            account_id: line.account_id.to_hex(),
+            name: line.name.clone(),
+            account_type: account_type_value(&line.account_type).to_string(),
+            currency: line.currency.clone(),
+            book_balance: line.book_balance,
+            unrealized_gain: line.unrealized_gain,
+            value: line.value(),
+            valued_at: line
+                .valued_at
+                .map(|at| at.to_chrono().format("%Y-%m-%d").to_string()),
+        })
+        .collect();
+    Ok(Json(NetWorthResponse {
+        currency: report.currency,
+        book_balance: totals.book_balance,
+        unrealized_gain: totals.unrealized_gain,
+        net_worth: totals.value,
+        accounts,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct CashCalendarQuery {
+    /// Days on each side of today, 1 to `CASH_CALENDAR_MAX_DAYS`.
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/reports/cash_calendar.html")]
+struct CashCalendarTemplate {
+    days: u32,
+    options: [u32; 5],
+}
+
+/// GET /admin/reports/cash_calendar — the heatmap; the page loads its data
+/// from `/api/admin/reports/cash-calendar`.
+pub async fn reports_cash_calendar(
+    session_user: SessionUser,
+    Query(query): Query<CashCalendarQuery>,
+) -> Result<Response, StatusCode> {
+    require_admin_active(&session_user)?;
+    let days = query.days.unwrap_or(CASH_CALENDAR_DEFAULT_DAYS);
+    if !(1..=CASH_CALENDAR_MAX_DAYS).contains(&days) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    render(CashCalendarTemplate {
+        days,
+        options: [30, 60, 90, 180, 365],
+    })
+    .map(IntoResponse::into_response)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CashCalendarDay {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    /// Confirmed income minus expense of the day.
+    pub actual: f64,
+    /// Still owed on the open planned entries due that day, income minus
+    /// expense; zero before today.
+    pub planned: f64,
+    /// `planned` with income weighted by the probability of its recurring
+    /// plan.
+    pub planned_expected: f64,
+    pub net: f64,
+    /// `net` counting planned income at its expected value.
+    pub expected_net: f64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CashCalendarResponse {
+    pub currency: String,
+    /// `YYYY-MM-DD` of today, where actual movements end and planned ones
+    /// start.
+    pub today: String,
+    /// Every day of the window, oldest first.
+    pub days: Vec<CashCalendarDay>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/cash-calendar",
+    tag = "finance",
+    params(("days" = Option<u32>, Query, description = "Days on each side of today; 90 by default, 366 at most")),
+    responses(
+        (status = 200, description = "Net cash movement per day of the active company", body = CashCalendarResponse),
+        (status = 400, description = "Invalid number of days"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_cash_calendar_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CashCalendarQuery>,
+) -> Result<Json<CashCalendarResponse>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let days = query.days.unwrap_or(CASH_CALENDAR_DEFAULT_DAYS);
+    if !(1..=CASH_CALENDAR_MAX_DAYS).contains(&days) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let calendar = cash_calendar(&state, &company_id, days, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let day_string = |date: DateTime| date.to_chrono().format("%Y-%m-%d").to_string();
+    Ok(Json(CashCalendarResponse {
+        today: day_string(calendar.today),
+        days: calendar
+            .days
+            .iter()
+            .map(|day| CashCalendarDay {
+                date: day_string(day.date),
+                actual: day.actual,
+                planned: day.planned,
+                planned_expected: day.planned_expected,
+                net: day.net(),
+                expected_net: day.expected_net(),
+            })
+            .collect(),
+        currency: calendar.currency,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct IncomeStatementQuery {
+    /// Months shown, 1 to `INCOME_STATEMENT_MAX_MONTHS`.
+    #[serde(default)]
+    months: Option<u32>,
+}
+
+/// One line of the income statement: a category, a section total or the net.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct IncomeStatementRow {
+    /// `null` on totals.
+    pub category_id: Option<String>,
+    pub name: String,
+    /// One per month, in the order of `months`.
+    pub amounts: Vec<f64>,
+    pub total: f64,
+    /// The same months a year before, in the order of `previous_months`.
+    pub previous_amounts: Vec<f64>,
+    pub previous_total: f64,
+    /// Change from the year before as a fraction (0.25 is 25% more); `null`
+    /// when the year before is zero.
+    pub change: Option<f64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct IncomeStatementSection {
+    /// One per category, by name.
+    pub rows: Vec<IncomeStatementRow>,
+    pub total: IncomeStatementRow,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct IncomeStatementResponse {
+    pub currency: String,
+    /// `YYYY-MM`, oldest first; the last is the current month.
+    pub months: Vec<String>,
+    pub previous_months: Vec<String>,
+    pub income: IncomeStatementSection,
+    pub expense: IncomeStatementSection,
+    /// Income minus expense.
+    pub net: IncomeStatementRow,
+}
+
+impl IncomeStatementRow {
+    /// `change` as a signed whole percentage, for the page.
+    fn change_label(&self) -> Option<String> {
+        self.change.map(|change| format!("{:+.0}%", change * 100.0))
+    }
+}
+
+fn statement_row(
+    category_id: Option<ObjectId>,
+    name: String,
+    amounts: Vec<f64>,
+    previous_amounts: Vec<f64>,
+) -> IncomeStatementRow {
+    let total = amounts.iter().sum();
+    let previous_total = previous_amounts.iter().sum();
+    IncomeStatementRow {
+        category_id: category_id.map(|id| id.to_hex()),
+        name,
+        amounts,
+        total,
+        previous_amounts,
+        previous_total,
+        change: trend_delta(total, previous_total),
+    }
+}
+
+fn statement_section(
+    statement: &IncomeStatement,
+    flow_type: FlowType,
+    categories: &HashMap<ObjectId, String>,
+) -> IncomeStatementSection {
+    let mut rows: Vec<IncomeStatementRow> = statement
+        .lines
+        .iter()
+        .filter(|line| line.flow_type == flow_type)
+        .map(|line| {
+            let name = categories
+                .get(&line.category_id)
+                .cloned()
+                .unwrap_or_else(|| "Categoría eliminada".to_string());
+            statement_row(
+                Some(line.category_id),
+                name,
+                line.amounts.clone(),
+                line.previous.clone(),
+            )
+        })
+        .collect();
+    rows.sort_by_key(|row| row.name.to_lowercase());
+    let (amounts, previous) = statement.totals(&flow_type);
+    let name = match flow_type {
+        FlowType::Income => "Total ingresos",
+        FlowType::Expense => "Total egresos",
+    };
+    IncomeStatementSection {
+        rows,
+        total: statement_row(None, name.to_string(), amounts, previous),
+    }
+}
+
+fn income_statement_response(
+    statement: IncomeStatement,
+    categories: &HashMap<ObjectId, String>,
+) -> IncomeStatementResponse {
+    let income = statement_section(&statement, FlowType::Income, categories);
+    let expense = statement_section(&statement, FlowType::Expense, categories);
+    let difference = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a - b).collect();
+    let net = statement_row(
+        None,
+        "Resultado".to_string(),
+        difference(&income.total.amounts, &expense.total.amounts),
+        difference(
+            &income.total.previous_amounts,
+            &expense.total.previous_amounts,
+        ),
+    );
+    IncomeStatementResponse {
+        currency: statement.currency,
+        months: statement.months,
+        previous_months: statement.previous_months,
+        income,
+        expense,
+        net,
+    }
+}
+
+async fn load_income_statement(
+    session_user: &SessionUser,
+    state: &AppState,
+    query: &IncomeStatementQuery,
+) -> Result<IncomeStatementResponse, StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    let months = query.months.unwrap_or(INCOME_STATEMENT_DEFAULT_MONTHS);
+    if !(1..=INCOME_STATEMENT_MAX_MONTHS).contains(&months) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let statement = income_statement(state, &company_id, months, DateTime::now())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let categories: HashMap<ObjectId, String> = state
+        .categories
+        .find(doc! { "company_id": company_id })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter_map(|c| c.id.map(|id| (id, c.name)))
+        .collect();
+    Ok(income_statement_response(statement, &categories))
+}
+
+#[derive(Template)]
+#[template(path = "admin/reports/income_statement.html")]
+struct IncomeStatementTemplate {
+    currency: String,
+    month_labels: Vec<String>,
+    sections: [(&'static str, IncomeStatementSection); 2],
+    net: IncomeStatementRow,
+    months: u32,
+    options: [u32; 4],
+}
+
+/// GET /admin/reports/income_statement — the statement as a table, with the
+/// year-before totals next to each line.
+pub async fn reports_income_statement(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<IncomeStatementQuery>,
+) -> Result<Response, StatusCode> {
+    let report = load_income_statement(&session_user, &state, &query).await?;
+    render(IncomeStatementTemplate {
+        currency: report.currency,
+        months: report.months.len() as u32,
+        month_labels: report.months,
+        sections: [("Ingresos", report.income), ("Egresos", report.expense)],
+        net: report.net,
+        options: [3, 6, 12, 24],
+    })
+    .map(IntoResponse::into_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/income-statement",
+    tag = "finance",
+    params(("months" = Option<u32>, Query, description = "Months up to and including the current one; 12 by default, 24 at most")),
+    responses(
+        (status = 200, description = "Confirmed income and expense per category and month of the active company, with the same months a year before", body = IncomeStatementResponse),
+        (status = 400, description = "Invalid number of months"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_income_statement_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<IncomeStatementQuery>,
+) -> Result<Json<IncomeStatementResponse>, StatusCode> {
+    load_income_statement(&session_user, &state, &query)
+        .await
+        .map(Json)
+}
+
+#[derive(Deserialize)]
+pub struct TaxReportQuery {
+    /// `YYYY-MM`; the current month when omitted.
+    #[serde(default)]
+    month: Option<String>,
+}
+
+/// Taxes of one name and rate.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TaxReportRow {
+    pub name: String,
+    /// Percentage, e.g. 16.0.
+    pub rate: f64,
+    pub exempt: bool,
+    /// Income before tax.
+    pub collected_base: f64,
+    /// Tax included in that income.
+    pub collected: f64,
+    /// Expenses before tax.
+    pub paid_base: f64,
+    /// Tax included in those expenses.
+    pub paid: f64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TaxReportResponse {
+    pub currency: String,
+    /// `YYYY-MM`.
+    pub month: String,
+    /// Highest rate first, exempt last.
+    pub lines: Vec<TaxReportRow>,
+    pub collected: f64,
+    pub paid: f64,
+    /// Collected minus paid: to pay when positive, in favor when negative.
+    pub net: f64,
+    /// Confirmed income and expense of the month that carry no tax.
+    pub untaxed_count: u64,
+}
+
+fn tax_report_response(report: TaxReport) -> TaxReportResponse {
+    TaxReportResponse {
+        currency: report.currency,
+        month: report.month,
+        lines: report
+            .lines
+            .into_iter()
+            .map(|line| TaxReportRow {
+                name: line.name,
+                rate: line.rate,
+                exempt: line.exempt,
+                collected_base: line.collected_base,
+                collected: line.collected,
+                paid_base: line.paid_base,
+                paid: line.paid,
+            })
+            .collect(),
+        collected: report.collected,
+        paid: report.paid,
+        net: report.net,
+        untaxed_count: report.untaxed_count,
+    }
+}
+
+/// First day of a `YYYY-MM` month.
+fn parse_report_month(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", value.trim()), "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+async fn load_tax_report(
+    session_user: &SessionUser,
+    state: &AppState,
+    query: &TaxReportQuery,
+) -> Result<TaxReportResponse, StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    let at = match query
+        .month
+        .as_deref()
+        .filter(|month| !month.trim().is_empty())
+    {
+        Some(month) => parse_report_month(month).ok_or(StatusCode::BAD_REQUEST)?,
+        None => DateTime::now().to_chrono(),
+    };
+    tax_report(state, &company_id, at)
+        .await
+        .map(tax_report_response)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Template)]
+#[template(path = "admin/reports/taxes.html")]
+struct TaxReportTemplate {
+    report: TaxReportResponse,
+}
+
+/// GET /admin/reports/taxes — the month's taxes per profile, with what is
+/// left to pay.
+pub async fn reports_taxes(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TaxReportQuery>,
+) -> Result<Response, StatusCode> {
+    let report = load_tax_report(&session_user, &state, &query).await?;
+    render(TaxReportTemplate { report }).map(IntoResponse::into_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/taxes",
+    tag = "finance",
+    params(("month" = Option<String>, Query, description = "Month as YYYY-MM; the current one by default")),
+    responses(
+        (status = 200, description = "Tax collected on income and paid on expenses of the active company in the month, per tax profile", body = TaxReportResponse),
+        (status = 400, description = "Invalid month"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_taxes_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TaxReportQuery>,
+) -> Result<Json<TaxReportResponse>, StatusCode> {
+    load_tax_report(&session_user, &state, &query)
+        .await
+        .map(Json)
+}
+
+#[derive(Deserialize)]
+pub struct FxReportQuery {
+    /// `YYYY-MM-DD`; January 1st of the current year when omitted.
+    #[serde(default)]
+    from: Option<String>,
+    /// `YYYY-MM-DD`, included; today when omitted.
+    #[serde(default)]
+    to: Option<String>,
+}
+
+/// Result realized by one settlement.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FxReportRow {
+    pub transaction_id: String,
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub description: String,
+    pub reference: Option<String>,
+    /// `collection` or `transfer`.
+    pub kind: String,
+    pub kind_label: String,
+    /// Currency the settled amount is in.
+    pub currency: String,
+    pub amount: f64,
+    pub booked_rate: f64,
+    pub settled_rate: f64,
+    /// Gain (positive) or loss in the company's currency.
+    pub result: f64,
+    /// What the adjustment recorded for it says, if there is one.
+    pub recorded: Option<f64>,
+    /// The adjustment is missing or out of date.
+    pub pending: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FxReportResponse {
+    /// The company's currency, which results are in.
+    pub currency: String,
+    /// `YYYY-MM-DD`.
+    pub from: String,
+    /// `YYYY-MM-DD`, included.
+    pub to: String,
+    /// Oldest first.
+    pub lines: Vec<FxReportRow>,
+    pub gains: f64,
+    pub losses: f64,
+    pub net: f64,
+    /// Adjustments to create, update or remove.
+    pub pending: usize,
+}
+
+fn fx_report_response(report: FxReport, from: &str, to: &str) -> FxReportResponse {
+    FxReportResponse {
+        currency: report.currency,
+        from: from.to_string(),
+        to: to.to_string(),
+        lines: report
+            .lines
+            .into_iter()
+            .map(|line| FxReportRow {
+                pending: line.is_pending(),
+                recorded: line.recorded,
+                transaction_id: line.result.transaction_id.to_hex(),
+                date: line.result.date.to_chrono().format("%Y-%m-%d").to_string(),
+                description: line.result.description,
+                reference: line.result.reference,
+                kind: line.result.kind.as_str().to_string(),
+                kind_label: line.result.kind.label().to_string(),
+                currency: line.result.currency,
+                amount: line.result.amount,
+                booked_rate: line.result.booked_rate,
+                settled_rate: line.result.settled_rate,
+                result: line.result.result,
+            })
+            .collect(),
+        gains: report.gains,
+        losses: report.losses,
+        net: report.net,
+        pending: report.pending,
+    }
+}
+
+async fn load_fx_report(
+    session_user: &SessionUser,
+    state: &AppState,
+    query: &FxReportQuery,
+) -> Result<FxReportResponse, StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    let today = DateTime::now().to_chrono().date_naive();
+    let date = |value: &Option<String>, default: chrono::NaiveDate| match value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(value) => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        None => Ok(default),
+    };
+    let from = date(&query.from, today.with_ordinal(1).unwrap_or(today))?;
+    let to = date(&query.to, today)?;
+    if to < from {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let start = |day: chrono::NaiveDate| {
+        DateTime::from_chrono(day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+    };
+    let end = to.succ_opt().ok_or(StatusCode::BAD_REQUEST)?;
+    fx_report(state, &company_id, start(from), start(end))
+        .await
+        .map(|report| fx_report_response(report, &from.to_string(), &to.to_string()))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Template)]
+#[template(path = "admin/reports/fx.html")]
+struct FxReportTemplate {
+    report: FxReportResponse,
+}
+
+/// GET /admin/reports/fx — realized exchange gains and losses in the range,
+/// with the adjustments still to record.
+pub async fn reports_fx(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FxReportQuery>,
+) -> Result<Response, StatusCode> {
+    let report = load_fx_report(&session_user, &state, &query).await?;
+    render(FxReportTemplate { report }).map(IntoResponse::into_response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/reports/fx",
+    tag = "finance",
+    params(
+        ("from" = Option<String>, Query, description = "First day as YYYY-MM-DD; January 1st of the current year by default"),
+        ("to" = Option<String>, Query, description = "Last day as YYYY-MM-DD; today by default")
+    ),
+    responses(
+        (status = 200, description = "Exchange gains and losses realized by the active company's settlements in the range", body = FxReportResponse),
+        (status = 400, description = "Invalid range"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_fx_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FxReportQuery>,
+) -> Result<Json<FxReportResponse>, StatusCode> {
+    load_fx_report(&session_user, &state, &query)
+        .await
+        .map(Json)
+}
+
+/// POST /admin/reports/fx/record — brings the adjustment entries in line
+/// with every realized result, whatever the range shown.
+pub async fn reports_fx_record(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let summary = record_fx_adjustments(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let message = if summary.created + summary.updated + summary.removed == 0 {
+        "Los ajustes cambiarios ya estaban al día.".to_string()
+    } else {
+        format!(
+            "Ajustes cambiarios: {} creados, {} actualizados, {} eliminados.",
+            summary.created, summary.updated, summary.removed
+        )
+    };
+    Ok((Flash::success(message), Redirect::to("/admin/reports/fx")).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/reports/fx/record",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Adjustment entries created, updated and removed so that they match the realized exchange results"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn reports_fx_record_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let summary = record_fx_adjustments(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(serde_json::json!({
+        "created": summary.created,
+        "updated": summary.updated,
+        "removed": summary.removed,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_split_flows_and_csv_escapes_names() {
+        let acme = ObjectId::new();
+        let rows = vec![
+            AgingRow {
+                flow_type: FlowType::Income,
+                contact_id: Some(acme),
+                buckets: [100.0, 0.0, 50.0, 0.0],
+                entries: 2,
+            },
+            AgingRow {
+                flow_type: FlowType::Expense,
+                contact_id: None,
+                buckets: [0.0, 0.0, 0.0, 75.5],
+                entries: 1,
+            },
+        ];
+        let contacts = HashMap::from([(acme, "Acme, \"SA\"".to_string())]);
+        let sections = aging_sections(rows, &contacts);
+
+        assert_eq!(sections[0].title, "Por cobrar");
+        assert_eq!(sections[0].total, 150.0);
+        assert_eq!(sections[1].totals, [0.0, 0.0, 0.0, 75.5]);
+        assert_eq!(
+            aging_csv(&sections),
+            "tipo,contacto,0-30,31-60,61-90,90+,total,compromisos\n\
+             Por cobrar,\"Acme, \"\"SA\"\"\",100.00,0.00,50.00,0.00,150.00,2\n\
+             Por pagar,Sin contacto,0.00,0.00,0.00,75.50,75.50,1\n"
+        );
+    }
+
+    #[test]
+    fn statement_sections_total_and_net_against_the_year_before() {
+        use crate::state::IncomeStatementLine;
+
+        let (sales, rent, gone) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let line =
+            |category_id, flow_type, amounts: [f64; 2], previous: [f64; 2]| IncomeStatementLine {
+                category_id,
+                flow_type,
+                amounts: amounts.to_vec(),
+                previous: previous.to_vec(),
+            };
+        let statement = IncomeStatement {
+            currency: "MXN".into(),
+            months: vec!["2025-02".into(), "2025-03".into()],
+            previous_months: vec!["2024-02".into(), "2024-03".into()],
+            lines: vec![
+                line(sales, FlowType::Income, [100.0, 200.0], [150.0, 50.0]),
+                line(rent, FlowType::Expense, [40.0, 40.0], [0.0, 0.0]),
+                line(gone, FlowType::Expense, [0.0, 10.0], [0.0, 0.0]),
+            ],
+        };
+        let categories =
+            HashMap::from([(sales, "Ventas".to_string()), (rent, "Renta".to_string())]);
+        let report = income_statement_response(statement, &categories);
+
+        assert_eq!(report.income.rows[0].name, "Ventas");
+        assert_eq!(report.income.total.total, 300.0);
+        assert_eq!(report.income.total.change, Some(0.5));
+        assert_eq!(report.income.total.change_label().as_deref(), Some("+50%"));
+        assert_eq!(report.expense.rows[0].name, "Categoría eliminada");
+        assert_eq!(report.expense.rows[1].name, "Renta");
+        assert_eq!(report.expense.total.amounts, vec![40.0, 50.0]);
+        assert_eq!(report.expense.total.change, None);
+        assert_eq!(report.net.amounts, vec![60.0, 150.0]);
+        assert_eq!(report.net.previous_amounts, vec![150.0, 50.0]);
+    }
+
+    #[test]
+    fn report_month_is_year_and_month() {
+        let start = parse_report_month("2025-03").unwrap();
+        assert_eq!(
+            start.format("%Y-%m-%d %H:%M").to_string(),
+            "2025-03-01 00:00"
+        );
+        assert!(parse_report_month("2025-13").is_none());
+        assert!(parse_report_month("marzo").is_none());
+    }
+}
@@ -0,0 +1,319 @@
+// Tax profiles: the taxes a company assigns to its categories, e.g. "IVA 16%"
+// or "Exento", and the default one for categories without their own. The
+// movements take the tax from them; see `state::taxes`.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::TaxProfile,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, create_tax_profile, delete_tax_profile, get_tax_profile_by_id, list_tax_profiles,
+        set_default_tax_profile,
+    },
+};
+
+use super::helpers::*;
+
+/// Tax profiles, as a page and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/tax_profiles",
+            get(tax_profiles_index).post(tax_profiles_create),
+        )
+        .route(
+            "/admin/tax_profiles/{id}/default",
+            post(tax_profiles_default),
+        )
+        .route("/admin/tax_profiles/{id}/delete", post(tax_profiles_delete))
+        .route(
+            "/api/admin/tax_profiles",
+            get(tax_profiles_data_api).post(tax_profiles_create_api),
+        )
+}
+
+#[derive(Template)]
+#[template(path = "admin/tax_profiles/index.html")]
+struct TaxProfilesIndexTemplate {
+    profiles: Vec<TaxProfileRow>,
+    name: String,
+    rate: String,
+    exempt: bool,
+    errors: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TaxProfileRow {
+    pub id: String,
+    pub name: String,
+    /// Percentage, e.g. 16.0.
+    pub rate: f64,
+    pub exempt: bool,
+    /// Applies to categories without a profile of their own.
+    pub is_default: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TaxProfileFormData {
+    name: String,
+    #[serde(default)]
+    rate: Option<String>,
+    #[serde(default)]
+    exempt: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TaxProfilePayload {
+    pub name: String,
+    /// Percentage from 0 to 100; ignored on exempt profiles.
+    #[serde(default)]
+    pub rate: f64,
+    #[serde(default)]
+    pub exempt: bool,
+    /// Makes it the company's default profile.
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+fn tax_profile_rows(profiles: Vec<TaxProfile>) -> Vec<TaxProfileRow> {
+    profiles
+        .into_iter()
+        .filter_map(|profile| {
+            profile.id.map(|id| TaxProfileRow {
+                id: id.to_hex(),
+                name: profile.name,
+                rate: profile.rate,
+                exempt: profile.exempt,
+                is_default: profile.is_default,
+            })
+        })
+        .collect()
+}
+
+async fn company_tax_profile_rows(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<TaxProfileRow>, StatusCode> {
+    list_tax_profiles(state, company_id)
+        .await
+        .map(tax_profile_rows)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Profile of the active company, or the status to answer with.
+async fn load_company_tax_profile(
+    state: &AppState,
+    id: &str,
+    company_id: &ObjectId,
+) -> Result<TaxProfile, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let profile = get_tax_profile_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&profile.company_id, company_id)?;
+    Ok(profile)
+}
+
+pub async fn tax_profiles_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let profiles = company_tax_profile_rows(&state, &company_id).await?;
+
+    render(TaxProfilesIndexTemplate {
+        profiles,
+        name: String::new(),
+        rate: String::new(),
+        exempt: false,
+        errors: None,
+    })
+}
+
+pub async fn tax_profiles_create(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<TaxProfileFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let exempt = form.exempt.is_some();
+    let result = match parse_optional_f64_field(form.rate.clone(), "Tasa") {
+        Ok(rate) => {
+            create_tax_profile(&state, &company_id, &form.name, rate.unwrap_or(0.0), exempt)
+                .await
+                .map_err(|err| err.to_string())
+        }
+        Err(message) => Err(message),
+    };
+    match result {
+        Ok(_) => (
+            Flash::success("Impuesto creado."),
+            Redirect::to("/admin/tax_profiles"),
+        )
+            .into_response(),
+        Err(message) => {
+            let profiles = match company_tax_profile_rows(&state, &company_id).await {
+                Ok(profiles) => profiles,
+                Err(status) => return status.into_response(),
+            };
+            render(TaxProfilesIndexTemplate {
+                profiles,
+                name: form.name,
+                rate: form.rate.unwrap_or_default(),
+                exempt,
+                errors: Some(message),
+            })
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response())
+        }
+    }
+}
+
+/// Makes the profile the company's default, or stops it from being the
+/// default when it already is.
+pub async fn tax_profiles_default(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let profile = match load_company_tax_profile(&state, &id, &company_id).await {
+        Ok(profile) => profile,
+        Err(status) => return status.into_response(),
+    };
+    let (default, message) = if profile.is_default {
+        (None, "Ya no hay impuesto predeterminado.")
+    } else {
+        (profile.id.as_ref(), "Impuesto predeterminado actualizado.")
+    };
+    match set_default_tax_profile(&state, &company_id, default).await {
+        Ok(_) => (Flash::success(message), Redirect::to("/admin/tax_profiles")).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn tax_profiles_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let profile = match load_company_tax_profile(&state, &id, &company_id).await {
+        Ok(profile) => profile,
+        Err(status) => return status.into_response(),
+    };
+    match delete_tax_profile(&state, &profile).await {
+        Ok(_) => (
+            Flash::success("Impuesto eliminado."),
+            Redirect::to("/admin/tax_profiles"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el impuesto."),
+            Redirect::to("/admin/tax_profiles"),
+        )
+            .into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/tax_profiles",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Tax profiles of the active company", body = [TaxProfileRow]),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn tax_profiles_data_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<TaxProfileRow>>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    company_tax_profile_rows(&state, &company_id)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/tax_profiles",
+    tag = "finance",
+    request_body = TaxProfilePayload,
+    responses(
+        (status = 201, description = "Tax profile created; returns its id"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid rate or duplicated name")
+    ),
+    security(("session" = []))
+)]
+pub async fn tax_profiles_create_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TaxProfilePayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let id = match create_tax_profile(
+        &state,
+        &company_id,
+        &payload.name,
+        payload.rate,
+        payload.exempt,
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": err.to_string() })),
+            )
+                .into_response();
+        }
+    };
+    if payload.is_default
+        && set_default_tax_profile(&state, &company_id, Some(&id))
+            .await
+            .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "id": id.to_hex() })),
+    )
+        .into_response()
+}
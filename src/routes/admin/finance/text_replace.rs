@@ -0,0 +1,449 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Form, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use chrono::{Datelike, Utc};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    models::{TextField, TextReplacement},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, TextReplacePreview, TextReplaceQuery, apply_text_replacement,
+        list_text_replacements, preview_text_replacement,
+    },
+};
+
+use super::helpers::*;
+
+/// Find-and-replace over transaction texts.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/api/admin/transactions/replace",
+            post(transactions_replace_api),
+        )
+        .route(
+            "/admin/transactions/replace",
+            get(transactions_replace_form).post(transactions_replace_apply),
+        )
+}
+
+/// Past runs listed under the form.
+const HISTORY_LIMIT: i64 = 20;
+
+/// Shown when the replacement fails after a clean preview.
+const REPLACE_FAILED: &str = "No se pudo aplicar el reemplazo; vuelve a generar la vista previa";
+
+#[derive(Template)]
+#[template(path = "admin/transactions/replace.html")]
+struct TextReplaceTemplate {
+    find: String,
+    replace: String,
+    in_description: bool,
+    in_notes: bool,
+    case_sensitive: bool,
+    from: String,
+    to: String,
+    /// Whether the form was submitted and `rows` holds its preview.
+    previewed: bool,
+    rows: Vec<TextReplaceRowView>,
+    transaction_count: usize,
+    /// Why the previewed replacement cannot be applied.
+    blocked: Option<String>,
+    applied: Option<usize>,
+    errors: Option<String>,
+    history: Vec<TextReplacementRow>,
+}
+
+struct TextReplaceRowView {
+    transaction_id: String,
+    date: String,
+    field: &'static str,
+    before: String,
+    after: String,
+}
+
+struct TextReplacementRow {
+    created_at: String,
+    username: String,
+    find: String,
+    replace: String,
+    fields: String,
+    from: String,
+    to: String,
+    changes: usize,
+}
+
+/// Fields of the form, also read from the query string for the preview.
+#[derive(Deserialize, Default, Clone)]
+pub struct TextReplaceForm {
+    #[serde(default)]
+    find: Option<String>,
+    #[serde(default)]
+    replace: Option<String>,
+    #[serde(default)]
+    in_description: Option<String>,
+    #[serde(default)]
+    in_notes: Option<String>,
+    #[serde(default)]
+    case_sensitive: Option<String>,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    applied: Option<usize>,
+}
+
+impl TextReplaceForm {
+    fn fields(&self) -> Vec<TextField> {
+        let mut fields = Vec::new();
+        if self.in_description.is_some() {
+            fields.push(TextField::Description);
+        }
+        if self.in_notes.is_some() {
+            fields.push(TextField::Notes);
+        }
+        fields
+    }
+}
+
+fn day_string(date: DateTime) -> String {
+    date.to_chrono().format("%Y-%m-%d").to_string()
+}
+
+/// The search the form describes. `find` is kept as typed, spaces included,
+/// so a trailing space can be matched; `replace` may be empty to delete it.
+fn parse_replace_query(
+    company_id: ObjectId,
+    find: &str,
+    replace: &str,
+    fields: Vec<TextField>,
+    from: &str,
+    to: &str,
+    case_sensitive: bool,
+) -> Result<TextReplaceQuery, String> {
+    let from = parse_date_field(from).ok_or_else(|| "Fecha inicial inválida".to_string())?;
+    let to = parse_date_field(to).ok_or_else(|| "Fecha final inválida".to_string())?;
+    let query = TextReplaceQuery {
+        company_id,
+        find: find.to_string(),
+        replace: replace.to_string(),
+        fields,
+        from,
+        to,
+        case_sensitive,
+    };
+    query.validate()?;
+    Ok(query)
+}
+
+fn history_rows(history: Vec<TextReplacement>) -> Vec<TextReplacementRow> {
+    history
+        .into_iter()
+        .map(|run| TextReplacementRow {
+            created_at: datetime_to_string(&run.created_at),
+            username: run.username,
+            find: run.find,
+            replace: run.replace,
+            fields: run
+                .fields
+                .iter()
+                .map(TextField::label)
+                .collect::<Vec<_>>()
+                .join(", "),
+            from: day_string(run.from),
+            to: day_string(run.to),
+            changes: run.changes.len(),
+        })
+        .collect()
+}
+
+/// Renders the page for `form`, previewing it when it was submitted.
+async fn render_replace_page(
+    state: &AppState,
+    session_user: &SessionUser,
+    form: &TextReplaceForm,
+    errors: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = *session_user.active_company_id();
+    let submitted = form.find.is_some();
+    let today = Utc::now().date_naive();
+    let from = form.from.clone().unwrap_or_else(|| {
+        today
+            .with_month(1)
+            .and_then(|d| d.with_day(1))
+            .unwrap_or(today)
+            .format("%Y-%m-%d")
+            .to_string()
+    });
+    let to = form
+        .to
+        .clone()
+        .unwrap_or_else(|| today.format("%Y-%m-%d").to_string());
+    let find = form.find.clone().unwrap_or_default();
+    let replace = form.replace.clone().unwrap_or_default();
+    let fields = if submitted {
+        form.fields()
+    } else {
+        vec![TextField::Description]
+    };
+    let case_sensitive = form.case_sensitive.is_some();
+
+    let mut preview = TextReplacePreview::default();
+    let mut errors = errors;
+    if submitted && errors.is_none() {
+        match parse_replace_query(
+            company_id,
+            &find,
+            &replace,
+            fields.clone(),
+            &from,
+            &to,
+            case_sensitive,
+        ) {
+            Ok(query) => {
+                preview = preview_text_replacement(state, &query, &session_user.account_access())
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            }
+            Err(msg) => errors = Some(msg),
+        }
+    }
+    let history = list_text_replacements(state, &company_id, HISTORY_LIMIT)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    render(TextReplaceTemplate {
+        in_description: fields.contains(&TextField::Description),
+        in_notes: fields.contains(&TextField::Notes),
+        find,
+        replace,
+        case_sensitive,
+        from,
+        to,
+        previewed: submitted && errors.is_none(),
+        transaction_count: preview.transaction_count(),
+        blocked: preview.error(),
+        rows: preview
+            .rows
+            .into_iter()
+            .map(|row| TextReplaceRowView {
+                transaction_id: row.transaction_id.to_hex(),
+                date: day_string(row.date),
+                field: row.field.label(),
+                before: row.before,
+                after: row.after,
+            })
+            .collect(),
+        applied: form.applied,
+        errors,
+        history: history_rows(history),
+    })
+}
+
+/// GET /admin/transactions/replace — the form and, once submitted, a preview
+/// of every text the replacement would change.
+pub async fn transactions_replace_form(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(form): Query<TextReplaceForm>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    render_replace_page(&state, &session_user, &form, None).await
+}
+
+/// POST /admin/transactions/replace — applies the previewed replacement and
+/// records it with every value before and after.
+pub async fn transactions_replace_apply(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<TextReplaceForm>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let result = match parse_replace_query(
+        company_id,
+        form.find.as_deref().unwrap_or_default(),
+        form.replace.as_deref().unwrap_or_default(),
+        form.fields(),
+        form.from.as_deref().unwrap_or_default(),
+        form.to.as_deref().unwrap_or_default(),
+        form.case_sensitive.is_some(),
+    ) {
+        Ok(query) => apply_text_replacement(
+            &state,
+            &query,
+            &session_user.account_access(),
+            Some(session_user.user().id),
+            &session_user.user().username,
+        )
+        .await
+        .map_err(|_| REPLACE_FAILED.to_string()),
+        Err(msg) => Err(msg),
+    };
+
+    match result {
+        Ok(run) => Redirect::to(&format!(
+            "/admin/transactions/replace?applied={}",
+            run.changes.len()
+        ))
+        .into_response(),
+        Err(msg) => render_replace_page(&state, &session_user, &form, Some(msg))
+            .await
+            .map(IntoResponse::into_response)
+            .unwrap_or_else(|status| status.into_response()),
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TextReplacePayload {
+    pub find: String,
+    /// Empty deletes the text found.
+    #[serde(default)]
+    pub replace: String,
+    /// `description`, `notes` or both.
+    pub fields: Vec<TextField>,
+    /// `YYYY-MM-DD`, first and last day, both included.
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only report what would change.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TextChangeData {
+    pub transaction_id: String,
+    pub field: TextField,
+    pub before: String,
+    /// Empty when the notes were cleared.
+    pub after: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TextReplaceResponse {
+    /// Id of the recorded run; absent on a dry run.
+    pub id: Option<String>,
+    pub dry_run: bool,
+    pub changes: Vec<TextChangeData>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/replace",
+    tag = "finance",
+    request_body = TextReplacePayload,
+    responses(
+        (status = 200, description = "Texts changed, or that would change on a dry run", body = TextReplaceResponse),
+        (status = 400, description = "Invalid input"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 422, description = "Too many matches, an empty description or nothing to replace")
+    ),
+    security(("session" = []))
+)]
+pub async fn transactions_replace_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TextReplacePayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let query = match parse_replace_query(
+        company_id,
+        &payload.find,
+        &payload.replace,
+        payload.fields,
+        &payload.from,
+        &payload.to,
+        payload.case_sensitive,
+    ) {
+        Ok(query) => query,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    let access = session_user.account_access();
+    let preview = match preview_text_replacement(&state, &query, &access).await {
+        Ok(preview) => preview,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let refusal = preview.error().or_else(|| {
+        (preview.rows.is_empty() && !payload.dry_run).then(|| "Ningún texto coincide".to_string())
+    });
+    if let Some(message) = refusal {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response();
+    }
+
+    if payload.dry_run {
+        return Json(TextReplaceResponse {
+            id: None,
+            dry_run: true,
+            changes: preview
+                .rows
+                .into_iter()
+                .map(|row| TextChangeData {
+                    transaction_id: row.transaction_id.to_hex(),
+                    field: row.field,
+                    before: row.before,
+                    after: row.after,
+                })
+                .collect(),
+        })
+        .into_response();
+    }
+
+    match apply_text_replacement(
+        &state,
+        &query,
+        &access,
+        Some(session_user.user().id),
+        &session_user.user().username,
+    )
+    .await
+    {
+        Ok(run) => Json(TextReplaceResponse {
+            id: run.id.map(|id| id.to_hex()),
+            dry_run: false,
+            changes: run
+                .changes
+                .into_iter()
+                .map(|change| TextChangeData {
+                    transaction_id: change.transaction_id.to_hex(),
+                    field: change.field,
+                    before: change.before,
+                    after: change.after,
+                })
+                .collect(),
+        })
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
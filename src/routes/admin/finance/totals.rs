@@ -0,0 +1,217 @@
+// Footer totals of the finance index pages: what the listed records add up
+// to in each currency, and everything in the company's currency when the
+// query gives a rate (`rate_USD=17.5`, units of the company's currency per
+// unit of USD) for every other currency listed.
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use mongodb::bson::oid::ObjectId;
+
+use crate::{
+    models::FlowType,
+    state::{AppState, conversion_rate, get_company_by_id, list_accounts},
+};
+
+const RATE_PARAM_PREFIX: &str = "rate_";
+
+pub(super) struct CurrencyTotal {
+    pub(super) currency: String,
+    pub(super) count: usize,
+    pub(super) income: f64,
+    pub(super) expense: f64,
+}
+
+impl CurrencyTotal {
+    fn new(currency: &str) -> Self {
+        Self {
+            currency: currency.to_string(),
+            count: 0,
+            income: 0.0,
+            expense: 0.0,
+        }
+    }
+
+    fn add(&mut self, flow_type: &FlowType, amount: f64) {
+        self.count += 1;
+        match flow_type {
+            FlowType::Income => self.income += amount,
+            FlowType::Expense => self.expense += amount,
+        }
+    }
+
+    pub(super) fn net(&self) -> f64 {
+        self.income - self.expense
+    }
+}
+
+/// Rate input for a currency other than the company's.
+pub(super) struct RateField {
+    pub(super) currency: String,
+    pub(super) value: String,
+}
+
+pub(super) struct IndexTotals {
+    pub(super) base_currency: String,
+    /// One per currency listed, the company's first.
+    pub(super) currencies: Vec<CurrencyTotal>,
+    /// Only when there is more than one currency and each has a rate.
+    pub(super) converted: Option<CurrencyTotal>,
+    pub(super) rate_fields: Vec<RateField>,
+    /// Query parameters other than rates, kept by the rates form so the
+    /// page keeps its filters.
+    pub(super) kept_params: Vec<(String, String)>,
+}
+
+impl IndexTotals {
+    pub(super) fn build<I>(
+        base_currency: &str,
+        records: I,
+        params: &HashMap<String, String>,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (String, FlowType, f64)>,
+    {
+        let mut currencies: Vec<CurrencyTotal> = Vec::new();
+        for (currency, flow_type, amount) in records {
+            match currencies.iter_mut().find(|t| t.currency == currency) {
+                Some(total) => total.add(&flow_type, amount),
+                None => {
+                    let mut total = CurrencyTotal::new(&currency);
+                    total.add(&flow_type, amount);
+                    currencies.push(total);
+                }
+            }
+        }
+        currencies.sort_by(|a, b| {
+            (a.currency != base_currency, &a.currency)
+                .cmp(&(b.currency != base_currency, &b.currency))
+        });
+
+        let mut rates = HashMap::new();
+        let mut rate_fields = Vec::new();
+        let mut kept_params: Vec<(String, String)> = Vec::new();
+        for (key, value) in params {
+            match key.strip_prefix(RATE_PARAM_PREFIX) {
+                Some(currency) => {
+                    if let Ok(rate) = value.trim().parse::<f64>()
+                        && rate.is_finite()
+                        && rate > 0.0
+                    {
+                        rates.insert(currency.to_string(), rate);
+                    }
+                }
+                None => kept_params.push((key.clone(), value.clone())),
+            }
+        }
+        kept_params.sort();
+        for total in currencies.iter().filter(|t| t.currency != base_currency) {
+            rate_fields.push(RateField {
+                currency: total.currency.clone(),
+                value: params
+                    .get(&format!("{RATE_PARAM_PREFIX}{}", total.currency))
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        }
+
+        let converted = if rate_fields.is_empty() {
+            None
+        } else {
+            currencies
+                .iter()
+                .try_fold(CurrencyTotal::new(base_currency), |mut sum, total| {
+                    let rate = conversion_rate(&total.currency, base_currency, &rates)?;
+                    sum.count += total.count;
+                    sum.income += total.income * rate;
+                    sum.expense += total.expense * rate;
+                    Some(sum)
+                })
+        };
+
+        Self {
+            base_currency: base_currency.to_string(),
+            currencies,
+            converted,
+            rate_fields,
+            kept_params,
+        }
+    }
+}
+
+/// Currency of a company's records: their own when they carry one, else
+/// that of their account, else the company's default.
+pub(super) struct CurrencyResolver {
+    pub(super) base: String,
+    accounts: HashMap<ObjectId, String>,
+}
+
+impl CurrencyResolver {
+    pub(super) async fn load(state: &AppState, company_id: &ObjectId) -> Result<Self, StatusCode> {
+        let base = get_company_by_id(state, company_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|c| c.default_currency.trim().to_uppercase())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| "MXN".to_string());
+        let accounts = list_accounts(state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .filter(|a| &a.company_id == company_id)
+            .filter_map(|a| a.id.map(|id| (id, a.currency)))
+            .collect();
+        Ok(Self { base, accounts })
+    }
+
+    pub(super) fn resolve(&self, own: Option<&str>, account: Option<&ObjectId>) -> String {
+        own.map(str::trim)
+            .filter(|c| !c.is_empty())
+            .or_else(|| {
+                account
+                    .and_then(|id| self.accounts.get(id))
+                    .map(|c| c.trim())
+                    .filter(|c| !c.is_empty())
+            })
+            .unwrap_or(self.base.as_str())
+            .to_uppercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_group_by_currency_and_convert_once_every_rate_is_given() {
+        let records = || {
+            vec![
+                ("MXN".to_string(), FlowType::Income, 100.0),
+                ("USD".to_string(), FlowType::Expense, 10.0),
+                ("MXN".to_string(), FlowType::Expense, 30.0),
+            ]
+        };
+        let totals = IndexTotals::build("MXN", records(), &HashMap::new());
+        assert_eq!(totals.currencies.len(), 2);
+        assert_eq!(totals.currencies[0].currency, "MXN");
+        assert_eq!(totals.currencies[0].count, 2);
+        assert_eq!(totals.currencies[0].net(), 70.0);
+        assert_eq!(totals.currencies[1].net(), -10.0);
+        assert!(totals.converted.is_none());
+        assert_eq!(totals.rate_fields.len(), 1);
+
+        let params = HashMap::from([
+            ("rate_USD".to_string(), "17.5".to_string()),
+            ("page".to_string(), "2".to_string()),
+        ]);
+        let totals = IndexTotals::build("MXN", records(), &params);
+        let converted = totals.converted.unwrap();
+        assert_eq!(converted.count, 3);
+        assert_eq!(converted.net(), 70.0 - 175.0);
+        assert_eq!(totals.rate_fields[0].value, "17.5");
+        assert_eq!(
+            totals.kept_params,
+            vec![("page".to_string(), "2".to_string())]
+        );
+    }
+}
@@ -6,25 +6,101 @@ use axum::{
     extract::{Form, Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use futures::TryStreamExt;
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use crate::filters;
 
 use crate::{
-    models::Transaction,
+    flash::Flash,
+    models::{
+        CommentEntity, CustomFieldEntity, FlowType, Transaction, TransactionTax, TransactionType,
+    },
+    routes::{
+        Routes,
+        form_fields::{id_or_empty, optional_object_id},
+    },
     session::SessionUser,
     state::{
-        AppState, create_transaction, delete_transaction, get_transaction_by_id, list_transactions,
-        update_transaction,
+        AccountAccess, AppState, TransactionBulkAction, attach_receipt_to_transaction,
+        bulk_edit_transactions, check_transaction_required_fields, confirm_transactions,
+        create_transaction, custom_field_display, custom_field_filter, custom_field_values,
+        delete_transaction, detach_transaction_from_planned_entry, find_by_custom_fields,
+        find_receipt_for_transaction, find_transaction_by_external_id, get_account_by_id,
+        get_category_by_id, get_contact_by_id, get_planned_entry_by_id, get_transaction_by_id,
+        list_pending_transactions, planned_entry_covered_amount, set_custom_field_values,
+        set_transaction_exchange_rate, set_transaction_external_refs, set_transaction_tax_rate,
+        suggested_categories, transaction_tax_label, update_transaction, utc_day_start,
     },
 };
 
+use super::card_holds::{HoldPanel, hold_panel};
+use super::comments::{CommentThread, comment_thread};
+use super::custom_fields::{
+    CustomFieldInput, CustomFieldsForm, custom_field_filters, custom_field_inputs,
+    custom_fields_json, entity_custom_fields, raw_custom_values,
+};
 use super::helpers::*;
-use super::options::{account_options, category_options, planned_entry_options};
+use super::options::{
+    account_options, category_options, flow_category_options, planned_entry_options,
+};
+use super::receipts::load_company_receipt;
+use super::refunds::{RefundsPanel, refunds_panel};
+use super::totals::{CurrencyResolver, IndexTotals};
+
+/// Transactions, the review queue and bulk edits.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/api/admin/transactions/data", get(transactions_data_api))
+        .route("/api/admin/transactions", post(transactions_create_api))
+        .route("/api/admin/transactions/{id}", get(transaction_data_api))
+        .route(
+            "/api/v1/transactions/by_external/{id}",
+            get(transaction_by_external_id_api),
+        )
+        .route(
+            "/api/admin/transactions/{id}/update",
+            post(transaction_update_api),
+        )
+        .route(
+            "/api/admin/transactions/{id}/delete",
+            post(transaction_delete_api),
+        )
+        .route(
+            "/admin/transactions",
+            get(transactions_index).post(transactions_create),
+        )
+        .route("/admin/transactions/new", get(transactions_new))
+        .route("/admin/transactions/fields", get(transactions_type_fields))
+        .route(
+            "/api/admin/transactions/pending",
+            get(transactions_pending_api),
+        )
+        .route(
+            "/api/admin/transactions/confirm",
+            post(transactions_confirm_api),
+        )
+        .route("/api/admin/transactions/bulk", post(transactions_bulk_api))
+        .route("/admin/transactions/pending", get(transactions_pending))
+        .route("/admin/transactions/confirm", post(transactions_confirm))
+        .route("/admin/transactions/{id}/edit", get(transactions_edit))
+        .route("/admin/transactions/{id}/update", post(transactions_update))
+        .route("/admin/transactions/{id}/delete", post(transactions_delete))
+        .route("/admin/transactions/{id}/clone", post(transactions_clone))
+        .route("/admin/transactions/{id}/detach", post(transactions_detach))
+        .route(
+            "/admin/transactions/{id}/row",
+            get(transactions_row).post(transactions_row_update),
+        )
+        .route(
+            "/admin/transactions/{id}/row/edit",
+            get(transactions_row_edit),
+        )
+}
 
 const TX_PER_PAGE: usize = 50;
 
@@ -35,6 +111,10 @@ struct TransactionsIndexTemplate {
     page: usize,
     total_pages: usize,
     total: usize,
+    /// Over every transaction matching the filters, not only this page.
+    totals: IndexTotals,
+    custom_filters: Vec<CustomFieldInput>,
+    filter_action: String,
 }
 
 struct TransactionRow {
@@ -56,33 +136,152 @@ struct TransactionFormTemplate {
     notes: String,
     is_confirmed: bool,
     companies: Vec<SimpleOption>,
-    categories: Vec<SimpleOption>,
-    accounts: Vec<SimpleOption>,
+    type_fields: TransactionTypeFields,
     planned_entries: Vec<SimpleOption>,
     transaction_options: Vec<SimpleOption>,
     is_edit: bool,
     errors: Option<String>,
+    /// Receipt the new transaction will be linked to.
+    receipt_id: Option<String>,
+    /// Where the prefilled values came from.
+    receipt_notice: Option<String>,
+    /// Link to the receipt file, when there is one.
+    receipt_url: Option<String>,
+    custom_fields: Vec<CustomFieldInput>,
+    /// Rate typed on the transaction; empty follows the category.
+    tax_rate: String,
+    /// Tax the saved transaction carries.
+    tax_label: Option<String>,
+    /// Rate typed on the transaction; empty when it is in the company currency.
+    exchange_rate: String,
+    /// Only shown when editing.
+    covered_entry: Option<CoveredEntry>,
+    /// Only shown when editing.
+    comments: Option<CommentThread>,
+    /// Printable receipt; only when editing.
+    print_url: Option<String>,
+    /// The category came with the form, so changing the type keeps it.
+    category_chosen: bool,
+    /// Only shown when editing.
+    refunds: Option<RefundsPanel>,
+    /// Only shown when editing.
+    hold: Option<HoldPanel>,
+}
+
+/// Planned entry an edited transaction covers, with the form detaching it.
+struct CoveredEntry {
+    id: String,
+    name: String,
+    detach_action: String,
+}
+
+/// First step of a new transaction: its type decides which fields follow.
+#[derive(Template)]
+#[template(path = "admin/transactions/choose_type.html")]
+struct TransactionTypeChoiceTemplate {
+    choices: Vec<TransactionTypeChoice>,
+    errors: Option<String>,
+}
+
+struct TransactionTypeChoice {
+    value: &'static str,
+    label: &'static str,
+    hint: &'static str,
+}
+
+/// Category and account fields of the transaction form. Which accounts are
+/// asked for, and which categories are offered, depend on the type.
+struct TransactionTypeFields {
+    category_search: String,
+    categories: Vec<SimpleOption>,
+    /// Categories used most with the chosen account, offered as shortcuts.
+    suggested_categories: Vec<SimpleOption>,
+    /// Only for expenses and transfers.
+    accounts_from: Option<Vec<SimpleOption>>,
+    /// Only for income and transfers.
+    accounts_to: Option<Vec<SimpleOption>>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transactions/fields.html")]
+struct TransactionTypeFieldsFragment {
+    type_fields: TransactionTypeFields,
+}
+
+#[derive(Deserialize, Default)]
+pub struct TransactionNewQuery {
+    #[serde(default)]
+    receipt: Option<String>,
+    #[serde(default)]
+    receipt_error: Option<bool>,
+    /// The receipt did not fit in the company's storage quota.
+    #[serde(default)]
+    storage_full: Option<bool>,
+    #[serde(default)]
+    transaction_type: Option<String>,
+    /// Links such as "registrar pago" open the form prefilled: the planned
+    /// entry brings its type, category, account, name and pending amount,
+    /// and the other fields replace them.
+    #[serde(default, deserialize_with = "optional_object_id")]
+    planned_entry_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_from_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_to_id: Option<ObjectId>,
+    #[serde(default)]
+    amount: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    /// `YYYY-MM-DD` or RFC 3339.
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
 }
 
 #[derive(Deserialize)]
+pub struct TransactionFieldsQuery {
+    transaction_type: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_from_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_to_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    planned_entry_id: Option<ObjectId>,
+}
+
+#[derive(Deserialize, Clone)]
 pub struct TransactionFormData {
     #[serde(default)]
     company_id: String,
     date: String,
     description: String,
     transaction_type: String,
-    category_id: String,
-    #[serde(default)]
-    account_from_id: Option<String>,
-    #[serde(default)]
-    account_to_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    category_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_from_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    account_to_id: Option<ObjectId>,
     amount: String,
-    #[serde(default)]
-    planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    planned_entry_id: Option<ObjectId>,
     #[serde(default)]
     is_confirmed: bool,
     #[serde(default)]
     notes: Option<String>,
+    #[serde(default)]
+    receipt_id: Option<String>,
+    /// Only on the full form; the inline row form leaves the tax as it is.
+    #[serde(default)]
+    tax_rate: Option<String>,
+    /// Only on the full form, like `tax_rate`.
+    #[serde(default)]
+    exchange_rate: Option<String>,
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -91,13 +290,46 @@ pub struct TransactionPayload {
     pub description: String,
     pub transaction_type: String,
     pub category_id: String,
-    pub account_from_id: Option<String>,
-    pub account_to_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub account_from_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub account_to_id: Option<ObjectId>,
     pub amount: f64,
-    pub planned_entry_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub planned_entry_id: Option<ObjectId>,
     #[serde(default = "default_confirmed")]
     pub is_confirmed: bool,
     pub notes: Option<String>,
+    /// Receipt uploaded through `/api/admin/transactions/receipts` to link to
+    /// the new transaction. Ignored on update.
+    #[serde(default)]
+    pub receipt_id: Option<String>,
+    /// Id of the movement in the caller's system, unique within the company.
+    /// Creating with one already recorded returns that transaction instead.
+    /// When omitted on update the stored one is kept.
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// Reference the bank gave the movement. When omitted on update the
+    /// stored one is kept.
+    #[serde(default)]
+    pub bank_reference: Option<String>,
+    /// Custom field values keyed by field key. When omitted on update the
+    /// stored values are kept.
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub custom_fields: Option<HashMap<String, serde_json::Value>>,
+    /// Percentage of tax included in `amount`, replacing the one of the
+    /// category's tax profile. When omitted on update the stored tax is kept.
+    #[serde(default)]
+    pub tax_rate: Option<f64>,
+    /// Units of the company currency per unit of the account's, used for the
+    /// exchange gain/loss report. When omitted on update the stored rate is
+    /// kept.
+    #[serde(default)]
+    pub exchange_rate: Option<f64>,
 }
 
 struct ParsedTransactionPayload {
@@ -117,6 +349,31 @@ fn default_confirmed() -> bool {
     true
 }
 
+/// A tax rate typed on a transaction: empty follows the category, otherwise
+/// a percentage from 0 to 100. Transfers carry no tax.
+fn parse_tax_rate(
+    value: Option<String>,
+    transaction_type: &TransactionType,
+) -> Result<Option<f64>, String> {
+    match parse_optional_f64_field(value, "Tasa de impuesto")? {
+        Some(_) if *transaction_type == TransactionType::Transfer => {
+            Err("Las transferencias no llevan impuestos".to_string())
+        }
+        Some(rate) if !(0.0..=100.0).contains(&rate) => {
+            Err("Tasa de impuesto debe estar entre 0 y 100".to_string())
+        }
+        rate => Ok(rate),
+    }
+}
+
+/// The rate field of the form: the one typed on the transaction, if any.
+fn override_rate(tax: &Option<TransactionTax>) -> String {
+    tax.as_ref()
+        .filter(|tax| tax.is_override)
+        .map(|tax| tax.rate.to_string())
+        .unwrap_or_default()
+}
+
 #[derive(Deserialize)]
 pub struct TxPageQuery {
     #[serde(default = "default_tx_page")]
@@ -130,17 +387,41 @@ pub async fn transactions_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Query(q): Query<TxPageQuery>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
 
-    let all = list_transactions(&state)
+    let fields =
+        entity_custom_fields(&state, &active_company, CustomFieldEntity::Transaction).await?;
+    let filter = custom_field_filter(&fields, &params).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut all = find_by_custom_fields(&state.transactions, &active_company, filter)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let access = session_user.account_access();
+    all.retain(|t| access.allows_transaction(t));
     let active_name = session_user.user().company_name.clone();
 
+    // Transfers only move money between the company's own accounts.
+    let currencies = CurrencyResolver::load(&state, &active_company).await?;
+    let totals = IndexTotals::build(
+        &currencies.base,
+        all.iter().filter_map(|t| {
+            let (flow_type, account) = match t.transaction_type {
+                TransactionType::Income => (FlowType::Income, t.account_to_id.as_ref()),
+                TransactionType::Expense => (FlowType::Expense, t.account_from_id.as_ref()),
+                TransactionType::Transfer => return None,
+            };
+            Some((
+                currencies.resolve(t.currency.as_deref(), account),
+                flow_type,
+                t.amount,
+            ))
+        }),
+        &params,
+    );
+
     let mut rows: Vec<TransactionRow> = all
         .into_iter()
-        .filter(|t| t.company_id == active_company)
         .filter_map(|t| {
             t.id.map(|id| TransactionRow {
                 id: id.to_hex(),
@@ -165,42 +446,205 @@ pub async fn transactions_index(
         page,
         total_pages,
         total,
+        totals,
+        custom_filters: custom_field_filters(&fields, &params),
+        filter_action: "/admin/transactions".into(),
     })
 }
 
 pub async fn transactions_new(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<TransactionNewQuery>,
 ) -> Result<Html<String>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
 
+    let upload_error = if query.storage_full.unwrap_or(false) {
+        Some(
+            "La compañía llegó a su límite de almacenamiento y el comprobante no se guardó. Pide a un superadministrador que amplíe la cuota."
+                .to_string(),
+        )
+    } else {
+        query.receipt_error.unwrap_or(false).then(|| {
+            "No se pudo subir el comprobante. Usa una imagen JPG, PNG o WebP, o un PDF de hasta 2 MB."
+                .to_string()
+        })
+    };
+    let requested_type = clean_opt(query.transaction_type)
+        .map(|value| parse_transaction_type(&value))
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let entry = match query.planned_entry_id.as_ref() {
+        Some(id) => {
+            let entry = get_planned_entry_by_id(&state, id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            ensure_same_company(&entry.company_id, &active_company)?;
+            Some(entry)
+        }
+        None => None,
+    };
+    let category = match query.category_id.as_ref() {
+        Some(id) => {
+            let category = get_category_by_id(&state, id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            ensure_same_company(&category.company_id, &active_company)?;
+            Some(category)
+        }
+        None => None,
+    };
+    for account_id in [&query.account_from_id, &query.account_to_id] {
+        validate_company_refs(&state, &active_company, None, account_id.as_ref(), None).await?;
+    }
+    ensure_account_access(
+        &session_user,
+        query
+            .account_from_id
+            .iter()
+            .chain(query.account_to_id.iter()),
+    )?;
+
+    let implied_flow = entry
+        .as_ref()
+        .map(|entry| &entry.flow_type)
+        .or(category.as_ref().map(|category| &category.flow_type));
+    let transaction_type = match (requested_type, implied_flow, &query.receipt) {
+        (Some(transaction_type), _, _) => transaction_type,
+        (None, Some(FlowType::Income), _) => TransactionType::Income,
+        (None, Some(FlowType::Expense), _) => TransactionType::Expense,
+        // Receipts are tickets and invoices paid by the company.
+        (None, None, Some(_)) => TransactionType::Expense,
+        (None, None, None) => {
+            return render(TransactionTypeChoiceTemplate {
+                choices: transaction_type_choices(),
+                errors: upload_error,
+            });
+        }
+    };
+
+    let mut description = String::new();
+    let mut amount = "0".to_string();
+    let mut category_id = query.category_id;
+    let mut account_from_id = query.account_from_id;
+    let mut account_to_id = query.account_to_id;
+    if let (Some(entry), Some(entry_id)) = (&entry, query.planned_entry_id.as_ref()) {
+        description = entry.name.clone();
+        let covered = planned_entry_covered_amount(&state, entry_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let pending = entry.amount_estimated - covered;
+        if pending > 0.0 {
+            amount = format!("{pending:.2}");
+        }
+        category_id = category_id.or(Some(entry.category_id));
+        if account_from_id.is_none() && account_to_id.is_none() {
+            match transaction_type {
+                TransactionType::Income => account_to_id = Some(entry.account_expected_id),
+                _ => account_from_id = Some(entry.account_expected_id),
+            }
+        }
+    }
+
     let companies = company_options(&state, &active_company).await?;
-    let categories = category_options(&state, None, &active_company).await?;
-    let accounts = account_options(&state, None, &active_company).await?;
-    let planned_entries = planned_entry_options(&state, None, &active_company).await?;
+    let type_fields = type_fields(
+        &state,
+        &session_user,
+        &transaction_type,
+        entry.is_some(),
+        category_id.as_ref(),
+        account_from_id.as_ref(),
+        account_to_id.as_ref(),
+    )
+    .await?;
+    let planned_entries =
+        planned_entry_options(&state, query.planned_entry_id.as_ref(), &active_company).await?;
+    let fields =
+        entity_custom_fields(&state, &active_company, CustomFieldEntity::Transaction).await?;
+
+    let receipt = match query.receipt.as_deref() {
+        Some(id) => Some(load_company_receipt(&state, id, &active_company).await?),
+        None => None,
+    };
+    let mut date = String::new();
+    let mut receipt_notice = None;
+    if let Some(receipt) = &receipt {
+        if let Some(merchant) = &receipt.suggested_merchant {
+            description = merchant.clone();
+        }
+        if let Some(value) = receipt.suggested_amount {
+            amount = format!("{value:.2}");
+        }
+        if let Some(value) = &receipt.suggested_date {
+            date = datetime_to_string(value);
+        }
+        receipt_notice = Some(if receipt.ocr_text.is_some() {
+            format!(
+                "Llenamos los campos con lo que leímos en {}. Revísalos antes de guardar.",
+                receipt.file_name
+            )
+        } else {
+            format!(
+                "Se adjuntará {}. No se pudo leer automáticamente, captura los datos.",
+                receipt.file_name
+            )
+        });
+    }
+    if let Some(value) = clean_opt(query.description) {
+        description = value;
+    }
+    if let Some(value) = clean_opt(query.amount) {
+        let value = parse_f64_field(&value, "Monto").map_err(|_| StatusCode::BAD_REQUEST)?;
+        amount = format!("{value:.2}");
+    }
+    if let Some(value) = clean_opt(query.date) {
+        let value = match parse_date_field(&value) {
+            Some(value) => value,
+            None => parse_datetime_field(&value, "Fecha").map_err(|_| StatusCode::BAD_REQUEST)?,
+        };
+        date = datetime_to_string(&value);
+    }
 
     render(TransactionFormTemplate {
         action: "/admin/transactions".into(),
-        description: String::new(),
-        amount: "0".into(),
-        transaction_type: "expense".into(),
-        date: String::new(),
-        notes: String::new(),
+        description,
+        amount,
+        transaction_type: transaction_type_value(&transaction_type).to_string(),
+        date,
+        notes: clean_opt(query.notes).unwrap_or_default(),
         is_confirmed: true,
         companies,
-        categories,
-        accounts,
+        type_fields,
         planned_entries,
-        transaction_options: transaction_type_options("expense"),
+        transaction_options: transaction_type_options(transaction_type_value(&transaction_type)),
         is_edit: false,
-        errors: None,
+        errors: upload_error,
+        receipt_url: receipt
+            .as_ref()
+            .and_then(|r| r.id)
+            .map(|id| format!("/admin/transactions/receipts/{}", id.to_hex())),
+        receipt_id: receipt.and_then(|r| r.id).map(|id| id.to_hex()),
+        receipt_notice,
+        custom_fields: custom_field_inputs(&fields, &Default::default()),
+        tax_rate: String::new(),
+        tax_label: None,
+        exchange_rate: String::new(),
+        covered_entry: None,
+        comments: None,
+        print_url: None,
+        category_chosen: category_id.is_some(),
+        refunds: None,
+        hold: None,
     })
 }
 
 pub async fn transactions_create(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
-    Form(form): Form<TransactionFormData>,
+    CustomFieldsForm { form, custom }: CustomFieldsForm<TransactionFormData>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -212,55 +656,21 @@ pub async fn transactions_create(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    let category_id = match parse_object_id(&form.category_id, "Categoría") {
+    let category_id = match required_object_id(form.category_id, "Categoría") {
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
-    let account_from_id = match form.account_from_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(val) => match parse_object_id(&val, "Cuenta origen") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
+    let account_from_id = form.account_from_id;
+    let account_to_id = form.account_to_id;
+    if let Err(status) = ensure_account_access(
+        &session_user,
+        account_from_id.iter().chain(account_to_id.iter()),
+    ) {
+        return status.into_response();
+    }
 
-    let account_to_id = match form.account_to_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(val) => match parse_object_id(&val, "Cuenta destino") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
-
-    let planned_entry_id = match form.planned_entry_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(val) => match parse_object_id(&val, "Compromiso planificado") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
-    };
+    let planned_entry_id = form.planned_entry_id;
 
     let amount = match parse_f64_field(&form.amount, "Monto") {
         Ok(v) => v,
@@ -272,7 +682,52 @@ pub async fn transactions_create(
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
 
+    match missing_required_field(
+        &state,
+        &company_id,
+        &transaction_type,
+        form.notes.as_deref(),
+        planned_entry_id.as_ref(),
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(message)) => {
+            return (
+                Flash::error(message),
+                Redirect::to(&new_form_retry_url(&form)),
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    }
+
     let notes = clean_opt(form.notes);
+    let tax_rate = match parse_tax_rate(form.tax_rate, &transaction_type) {
+        Ok(rate) => rate,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let exchange_rate = match parse_exchange_rate(form.exchange_rate) {
+        Ok(rate) => rate,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let custom_values =
+        match entity_custom_fields(&state, &company_id, CustomFieldEntity::Transaction).await {
+            Ok(fields) => match custom_field_values(&fields, &custom) {
+                Ok(values) => values,
+                Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+            },
+            Err(status) => return status.into_response(),
+        };
+
+    let receipt_id = match clean_opt(form.receipt_id) {
+        Some(id) => match load_company_receipt(&state, &id, &company_id).await {
+            Ok(receipt) => receipt.id,
+            Err(status) => return status.into_response(),
+        },
+        None => None,
+    };
 
     if let Err(status) = validate_company_refs(
         &state,
@@ -327,7 +782,51 @@ pub async fn transactions_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/transactions").into_response(),
+        Ok(transaction_id) => {
+            if set_custom_field_values(
+                &state,
+                CustomFieldEntity::Transaction,
+                &company_id,
+                &transaction_id,
+                custom_values,
+            )
+            .await
+            .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if tax_rate.is_some()
+                && set_transaction_tax_rate(&state, &transaction_id, &company_id, tax_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if exchange_rate.is_some()
+                && set_transaction_exchange_rate(
+                    &state,
+                    &transaction_id,
+                    &company_id,
+                    exchange_rate,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if let Some(receipt_id) = receipt_id
+                && let Err(e) =
+                    attach_receipt_to_transaction(&state, &receipt_id, &company_id, &transaction_id)
+                        .await
+            {
+                eprintln!("[receipts] failed to attach receipt: {e:?}");
+            }
+            (
+                Flash::success("Movimiento creado."),
+                Redirect::to("/admin/transactions"),
+            )
+                .into_response()
+        }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -345,17 +844,17 @@ pub async fn transactions_edit(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&transaction.company_id, &active_company)?;
+    ensure_transaction_access(&session_user, &transaction)?;
 
     let companies = company_options(&state, &active_company).await?;
-    let categories =
-        category_options(&state, Some(&transaction.category_id), &active_company).await?;
-    let accounts = account_options(
+    let type_fields = type_fields(
         &state,
-        transaction
-            .account_from_id
-            .as_ref()
-            .or(transaction.account_to_id.as_ref()),
-        &active_company,
+        &session_user,
+        &transaction.transaction_type,
+        transaction.planned_entry_id.is_some(),
+        Some(&transaction.category_id),
+        transaction.account_from_id.as_ref(),
+        transaction.account_to_id.as_ref(),
     )
     .await?;
     let planned_entries = planned_entry_options(
@@ -364,6 +863,36 @@ pub async fn transactions_edit(
         &active_company,
     )
     .await?;
+    let receipt_url = find_receipt_for_transaction(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .and_then(|r| r.id)
+        .map(|id| format!("/admin/transactions/receipts/{}", id.to_hex()));
+    let fields =
+        entity_custom_fields(&state, &active_company, CustomFieldEntity::Transaction).await?;
+    let custom_fields = custom_field_inputs(&fields, &transaction.custom_fields);
+    let covered_entry = match transaction.planned_entry_id {
+        Some(entry_id) => get_planned_entry_by_id(&state, &entry_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|entry| CoveredEntry {
+                id: entry_id.to_hex(),
+                name: entry.name,
+                detach_action: format!("/admin/transactions/{id}/detach"),
+            }),
+        None => None,
+    };
+    let comments = comment_thread(
+        &state,
+        CommentEntity::Transaction,
+        &object_id,
+        &session_user,
+    )
+    .await?;
+    let tax_rate = override_rate(&transaction.tax);
+    let tax_label = transaction_tax_label(&transaction);
+    let refunds = refunds_panel(&state, &transaction).await?;
+    let hold = hold_panel(&transaction);
 
     render(TransactionFormTemplate {
         action: format!("/admin/transactions/{}/update", id),
@@ -374,14 +903,95 @@ pub async fn transactions_edit(
         notes: transaction.notes.unwrap_or_default(),
         is_confirmed: transaction.is_confirmed,
         companies,
-        categories,
-        accounts,
+        type_fields,
         planned_entries,
         transaction_options: transaction_type_options(transaction_type_value(
             &transaction.transaction_type,
         )),
         is_edit: true,
         errors: None,
+        receipt_id: None,
+        receipt_notice: None,
+        receipt_url,
+        custom_fields,
+        tax_rate,
+        tax_label,
+        exchange_rate: exchange_rate_value(transaction.exchange_rate),
+        covered_entry,
+        comments: Some(comments),
+        print_url: Some(format!("/print/transactions/{}", id)),
+        category_chosen: true,
+        refunds,
+        hold,
+    })
+}
+
+/// POST /admin/transactions/{id}/clone — the new-transaction form prefilled
+/// with the transaction's values, dated today. The planned entry, receipt and
+/// CFDI stay with the original.
+pub async fn transactions_clone(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let transaction = get_transaction_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&transaction.company_id, &active_company)?;
+    ensure_transaction_access(&session_user, &transaction)?;
+
+    let companies = company_options(&state, &active_company).await?;
+    let type_fields = type_fields(
+        &state,
+        &session_user,
+        &transaction.transaction_type,
+        false,
+        Some(&transaction.category_id),
+        transaction.account_from_id.as_ref(),
+        transaction.account_to_id.as_ref(),
+    )
+    .await?;
+    let planned_entries = planned_entry_options(&state, None, &active_company).await?;
+    let fields =
+        entity_custom_fields(&state, &active_company, CustomFieldEntity::Transaction).await?;
+    let custom_fields = custom_field_inputs(&fields, &transaction.custom_fields);
+
+    render(TransactionFormTemplate {
+        action: "/admin/transactions".into(),
+        amount: transaction.amount.to_string(),
+        transaction_type: transaction_type_value(&transaction.transaction_type).to_string(),
+        date: datetime_to_string(&utc_day_start(DateTime::now())),
+        notes: transaction.notes.unwrap_or_default(),
+        is_confirmed: transaction.is_confirmed,
+        companies,
+        type_fields,
+        planned_entries,
+        transaction_options: transaction_type_options(transaction_type_value(
+            &transaction.transaction_type,
+        )),
+        is_edit: false,
+        errors: None,
+        receipt_id: None,
+        receipt_notice: Some(format!(
+            "Copia de «{}» con fecha de hoy. Revisa los datos antes de guardar.",
+            transaction.description
+        )),
+        receipt_url: None,
+        custom_fields,
+        tax_rate: override_rate(&transaction.tax),
+        tax_label: None,
+        exchange_rate: exchange_rate_value(transaction.exchange_rate),
+        covered_entry: None,
+        comments: None,
+        print_url: None,
+        category_chosen: true,
+        refunds: None,
+        hold: None,
+        description: transaction.description,
     })
 }
 
@@ -389,7 +999,7 @@ pub async fn transactions_update(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Form(form): Form<TransactionFormData>,
+    CustomFieldsForm { form, custom }: CustomFieldsForm<TransactionFormData>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -402,135 +1012,652 @@ pub async fn transactions_update(
     };
 
     if let Err(status) = match get_transaction_by_id(&state, &object_id).await {
-        Ok(Some(tx)) => ensure_same_company(&tx.company_id, &company_id),
+        Ok(Some(tx)) => ensure_same_company(&tx.company_id, &company_id)
+            .and_then(|_| ensure_transaction_access(&session_user, &tx)),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
         return status.into_response();
     }
 
-    let transaction_type = match parse_transaction_type(&form.transaction_type) {
-        Ok(t) => t,
+    let tax_rate = form.tax_rate.clone();
+    let exchange_rate = form.exchange_rate.clone();
+    let parsed = match parse_transaction_form(&state, &company_id, &session_user, form).await {
+        Ok(parsed) => parsed,
+        Err((status, _)) => return status.into_response(),
+    };
+    match missing_required_field(
+        &state,
+        &company_id,
+        &parsed.transaction_type,
+        parsed.notes.as_deref(),
+        parsed.planned_entry_id.as_ref(),
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(message)) => {
+            return (
+                Flash::error(message),
+                Redirect::to(&format!("/admin/transactions/{id}/edit")),
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    }
+    let tax_rate = match parse_tax_rate(tax_rate, &parsed.transaction_type) {
+        Ok(rate) => rate,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
-
-    let category_id = match parse_object_id(&form.category_id, "Categoría") {
-        Ok(id) => id,
+    let exchange_rate = match parse_exchange_rate(exchange_rate) {
+        Ok(rate) => rate,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
     };
+    let custom_values =
+        match entity_custom_fields(&state, &company_id, CustomFieldEntity::Transaction).await {
+            Ok(fields) => match custom_field_values(&fields, &custom) {
+                Ok(values) => values,
+                Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+            },
+            Err(status) => return status.into_response(),
+        };
+
+    if update_transaction(
+        &state,
+        &object_id,
+        &company_id,
+        parsed.date,
+        &parsed.description,
+        parsed.transaction_type,
+        &parsed.category_id,
+        parsed.account_from_id,
+        parsed.account_to_id,
+        parsed.amount,
+        parsed.planned_entry_id,
+        parsed.is_confirmed,
+        parsed.notes,
+    )
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    // An empty rate goes back to the category's tax.
+    if set_transaction_tax_rate(&state, &object_id, &company_id, tax_rate)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if set_transaction_exchange_rate(&state, &object_id, &company_id, exchange_rate)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    match set_custom_field_values(
+        &state,
+        CustomFieldEntity::Transaction,
+        &company_id,
+        &object_id,
+        custom_values,
+    )
+    .await
+    {
+        Ok(_) => (
+            Flash::success("Movimiento actualizado."),
+            Redirect::to("/admin/transactions"),
+        )
+            .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
 
-    let account_from_id = match form.account_from_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    }) {
-        Some(val) => match parse_object_id(&val, "Cuenta origen") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+fn transaction_type_choices() -> Vec<TransactionTypeChoice> {
+    vec![
+        TransactionTypeChoice {
+            value: "expense",
+            label: "Gasto",
+            hint: "Dinero que sale de una de tus cuentas.",
         },
-        None => None,
-    };
+        TransactionTypeChoice {
+            value: "income",
+            label: "Ingreso",
+            hint: "Dinero que entra a una de tus cuentas.",
+        },
+        TransactionTypeChoice {
+            value: "transfer",
+            label: "Transferencia",
+            hint: "Dinero que pasa de una cuenta propia a otra.",
+        },
+    ]
+}
 
-    let account_to_id = match form.account_to_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
+/// Fields that depend on the transaction type, following the rules the
+/// transaction is validated with: income goes into an account, expenses come
+/// out of one and transfers need both. Categories are those of the matching
+/// flow type unless a planned entry is linked, since the entry then decides
+/// the flow. With an account chosen, the categories it is used with most are
+/// suggested and, while no category is chosen, the first one is preselected.
+async fn type_fields(
+    state: &AppState,
+    session_user: &SessionUser,
+    transaction_type: &TransactionType,
+    linked_to_planned_entry: bool,
+    category_id: Option<&ObjectId>,
+    account_from_id: Option<&ObjectId>,
+    account_to_id: Option<&ObjectId>,
+) -> Result<TransactionTypeFields, StatusCode> {
+    let company_id = session_user.active_company_id();
+    let access = &session_user.account_access();
+    let flow_type = match transaction_type {
+        _ if linked_to_planned_entry => None,
+        TransactionType::Income => Some(FlowType::Income),
+        TransactionType::Expense => Some(FlowType::Expense),
+        TransactionType::Transfer => None,
+    };
+    let category_search = match &flow_type {
+        Some(flow_type) => format!("/api/options/categories?flow_type={}", flow_type.as_str()),
+        None => "/api/options/categories".to_string(),
+    };
+    let accounts_from = match transaction_type {
+        TransactionType::Expense | TransactionType::Transfer => {
+            Some(account_options(state, account_from_id, company_id, access).await?)
         }
-    }) {
-        Some(val) => match parse_object_id(&val, "Cuenta destino") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
+        TransactionType::Income => None,
     };
-
-    let planned_entry_id = match form.planned_entry_id.clone().and_then(|v| {
-        let trimmed = v.trim().to_string();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
+    let accounts_to = match transaction_type {
+        TransactionType::Income | TransactionType::Transfer => {
+            Some(account_options(state, account_to_id, company_id, access).await?)
         }
-    }) {
-        Some(val) => match parse_object_id(&val, "Compromiso planificado") {
-            Ok(id) => Some(id),
-            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
-        },
-        None => None,
+        TransactionType::Expense => None,
     };
-
-    let amount = match parse_f64_field(&form.amount, "Monto") {
-        Ok(v) => v,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    // Suggestions follow the account the money moves through: the source for
+    // expenses and transfers, the destination for income.
+    let suggestion_account = match transaction_type {
+        TransactionType::Income => account_to_id,
+        TransactionType::Expense | TransactionType::Transfer => account_from_id,
     };
-
-    let date = match parse_datetime_field(&form.date, "Fecha") {
-        Ok(dt) => dt,
-        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    let suggested = match suggestion_account {
+        Some(account_id) => suggested_categories(state, account_id, transaction_type)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => Vec::new(),
     };
+    let categories = flow_category_options(
+        state,
+        category_id.or(suggested.first()),
+        company_id,
+        flow_type.as_ref(),
+    )
+    .await?;
+    let suggested_categories = suggested
+        .iter()
+        .filter_map(|id| {
+            categories
+                .iter()
+                .find(|option| option.value == id.to_hex())
+                .cloned()
+        })
+        .collect();
+    Ok(TransactionTypeFields {
+        category_search,
+        categories,
+        suggested_categories,
+        accounts_from,
+        accounts_to,
+    })
+}
 
-    let notes = clean_opt(form.notes);
-
-    if let Err(status) = validate_company_refs(
+/// GET /admin/transactions/fields — the type-dependent fields of the form,
+/// swapped in when the type changes. Current choices stay selected where the
+/// new type still uses them.
+pub async fn transactions_type_fields(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TransactionFieldsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    let transaction_type = parse_transaction_type(query.transaction_type.trim())
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let type_fields = type_fields(
         &state,
-        &company_id,
+        &session_user,
+        &transaction_type,
+        query.planned_entry_id.is_some(),
+        query.category_id.as_ref(),
+        query.account_from_id.as_ref(),
+        query.account_to_id.as_ref(),
+    )
+    .await?;
+    render(TransactionTypeFieldsFragment { type_fields })
+}
+
+/// Validates an HTML transaction form against the active company and the
+/// user's accounts. Parse problems come back as `BAD_REQUEST` with a message
+/// for the form; foreign references keep the status from the company checks.
+/// The message of the first field the company's policy requires and the
+/// transaction leaves empty.
+async fn missing_required_field(
+    state: &AppState,
+    company_id: &ObjectId,
+    transaction_type: &TransactionType,
+    notes: Option<&str>,
+    planned_entry_id: Option<&ObjectId>,
+) -> Result<Option<String>, StatusCode> {
+    let policy = required_fields(state, company_id).await?;
+    Ok(
+        check_transaction_required_fields(&policy, transaction_type, notes, planned_entry_id)
+            .err()
+            .map(|missing| missing.to_string()),
+    )
+}
+
+/// The new-transaction form prefilled with a submission it rejected.
+fn new_form_retry_url(form: &TransactionFormData) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    query
+        .append_pair("transaction_type", &form.transaction_type)
+        .append_pair("description", &form.description)
+        .append_pair("amount", &form.amount)
+        .append_pair("date", &form.date);
+    for (key, value) in [
+        ("category_id", form.category_id),
+        ("account_from_id", form.account_from_id),
+        ("account_to_id", form.account_to_id),
+        ("planned_entry_id", form.planned_entry_id),
+    ] {
+        if let Some(value) = value {
+            query.append_pair(key, &value.to_hex());
+        }
+    }
+    if let Some(notes) = form.notes.as_deref() {
+        query.append_pair("notes", notes);
+    }
+    if let Some(receipt) = form.receipt_id.as_deref() {
+        query.append_pair("receipt", receipt);
+    }
+    format!("/admin/transactions/new?{}", query.finish())
+}
+
+async fn parse_transaction_form(
+    state: &AppState,
+    company_id: &ObjectId,
+    session_user: &SessionUser,
+    form: TransactionFormData,
+) -> Result<ParsedTransactionPayload, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+    let invalid_refs = |status: StatusCode| {
+        (
+            status,
+            "Las referencias no pertenecen a la empresa activa".to_string(),
+        )
+    };
+
+    let transaction_type = parse_transaction_type(&form.transaction_type).map_err(bad_request)?;
+    let category_id = required_object_id(form.category_id, "Categoría").map_err(bad_request)?;
+    let account_from_id = form.account_from_id;
+    let account_to_id = form.account_to_id;
+    let planned_entry_id = form.planned_entry_id;
+    let amount = parse_f64_field(&form.amount, "Monto").map_err(bad_request)?;
+    let date = parse_datetime_field(&form.date, "Fecha").map_err(bad_request)?;
+
+    ensure_account_access(
+        session_user,
+        account_from_id.iter().chain(account_to_id.iter()),
+    )
+    .map_err(|status| (status, "No tienes acceso a esa cuenta".to_string()))?;
+    validate_company_refs(
+        state,
+        company_id,
         Some(&category_id),
         account_from_id.as_ref(),
         None,
     )
     .await
-    {
-        return status.into_response();
-    }
-
+    .map_err(invalid_refs)?;
     if let Some(ref account_to) = account_to_id {
-        if let Err(status) = validate_company_refs(
-            &state,
-            &company_id,
+        validate_company_refs(
+            state,
+            company_id,
             Some(&category_id),
             Some(account_to),
             None,
         )
         .await
+        .map_err(invalid_refs)?;
+    }
+    if let Some(ref entry_id) = planned_entry_id {
+        validate_planned_entry_company(state, entry_id, company_id)
+            .await
+            .map_err(invalid_refs)?;
+    }
+
+    Ok(ParsedTransactionPayload {
+        date,
+        description: form.description.trim().to_string(),
+        transaction_type,
+        category_id,
+        account_from_id,
+        account_to_id,
+        amount,
+        planned_entry_id,
+        is_confirmed: form.is_confirmed,
+        notes: clean_opt(form.notes),
+    })
+}
+
+// ── Inline row fragments ──────────────────────────────────────────────────
+//
+// Fragments let the transactions table swap a single row for an edit form
+// and back without a full page round trip. They render bare `<tr>` markup.
+
+#[derive(Template)]
+#[template(path = "admin/transactions/row.html")]
+struct TransactionRowFragment {
+    id: String,
+    date: String,
+    tx_type: String,
+    tx_type_label: String,
+    description: String,
+    category: String,
+    account: String,
+    contact: String,
+    amount: f64,
+    is_confirmed: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin/transactions/row_form.html")]
+struct TransactionRowFormFragment {
+    id: String,
+    description: String,
+    amount: String,
+    date: String,
+    notes: String,
+    is_confirmed: bool,
+    categories: Vec<SimpleOption>,
+    accounts_from: Vec<SimpleOption>,
+    accounts_to: Vec<SimpleOption>,
+    planned_entry_id: String,
+    transaction_options: Vec<SimpleOption>,
+    errors: Option<String>,
+}
+
+async fn load_company_transaction(
+    state: &AppState,
+    id: &str,
+    session_user: &SessionUser,
+) -> Result<Transaction, StatusCode> {
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let tx = get_transaction_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_same_company(&tx.company_id, session_user.active_company_id())?;
+    ensure_transaction_access(session_user, &tx)?;
+    Ok(tx)
+}
+
+async fn row_fragment(
+    state: &AppState,
+    tx: Transaction,
+) -> Result<TransactionRowFragment, StatusCode> {
+    let id = tx.id.ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let category = get_category_by_id(state, &tx.category_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(|c| c.name)
+        .unwrap_or_default();
+    let mut account_names = Vec::new();
+    for account_id in [tx.account_from_id.as_ref(), tx.account_to_id.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(account) = get_account_by_id(state, account_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         {
-            return status.into_response();
+            account_names.push(account.name);
         }
     }
+    let contact = match tx.contact_id.as_ref() {
+        Some(contact_id) => get_contact_by_id(state, contact_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|c| c.name)
+            .unwrap_or_default(),
+        None => String::new(),
+    };
 
-    if let Some(ref entry_id) = planned_entry_id {
-        if let Err(status) = validate_planned_entry_company(&state, entry_id, &company_id).await {
-            return status.into_response();
+    Ok(TransactionRowFragment {
+        id: id.to_hex(),
+        date: tx.date.to_chrono().format("%Y-%m-%d").to_string(),
+        tx_type: transaction_type_value(&tx.transaction_type).to_string(),
+        tx_type_label: transaction_type_label(&tx.transaction_type).to_string(),
+        description: tx.description,
+        category,
+        account: account_names.join(" → "),
+        contact,
+        amount: tx.amount,
+        is_confirmed: tx.is_confirmed,
+    })
+}
+
+async fn row_form_fragment(
+    state: &AppState,
+    company_id: &ObjectId,
+    access: &AccountAccess,
+    id: String,
+    form: TransactionFormData,
+    errors: Option<String>,
+) -> Result<TransactionRowFormFragment, StatusCode> {
+    let category_id = form.category_id;
+    let account_from_id = form.account_from_id;
+    let account_to_id = form.account_to_id;
+
+    Ok(TransactionRowFormFragment {
+        id,
+        categories: category_options(state, category_id.as_ref(), company_id).await?,
+        accounts_from: account_options(state, account_from_id.as_ref(), company_id, access).await?,
+        accounts_to: account_options(state, account_to_id.as_ref(), company_id, access).await?,
+        planned_entry_id: id_or_empty(form.planned_entry_id),
+        transaction_options: transaction_type_options(form.transaction_type.trim()),
+        description: form.description,
+        amount: form.amount,
+        date: form.date,
+        notes: form.notes.unwrap_or_default(),
+        is_confirmed: form.is_confirmed,
+        errors,
+    })
+}
+
+/// GET /admin/transactions/{id}/row — the read-only table row.
+pub async fn transactions_row(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin_active(&session_user)?;
+    let tx = load_company_transaction(&state, &id, &session_user).await?;
+    render(row_fragment(&state, tx).await?)
+}
+
+/// GET /admin/transactions/{id}/row/edit — the inline edit form for one row.
+pub async fn transactions_row_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let tx = load_company_transaction(&state, &id, &session_user).await?;
+    let form = TransactionFormData {
+        company_id: tx.company_id.to_hex(),
+        date: datetime_to_string(&tx.date),
+        description: tx.description,
+        transaction_type: transaction_type_value(&tx.transaction_type).to_string(),
+        category_id: Some(tx.category_id),
+        account_from_id: tx.account_from_id,
+        account_to_id: tx.account_to_id,
+        amount: tx.amount.to_string(),
+        planned_entry_id: tx.planned_entry_id,
+        is_confirmed: tx.is_confirmed,
+        notes: tx.notes,
+        receipt_id: None,
+        tax_rate: None,
+        exchange_rate: None,
+    };
+    render(
+        row_form_fragment(
+            &state,
+            &company_id,
+            &session_user.account_access(),
+            id,
+            form,
+            None,
+        )
+        .await?,
+    )
+}
+
+/// POST /admin/transactions/{id}/row — saves the inline form and answers with
+/// the refreshed row, or with the form again (422) when validation fails.
+pub async fn transactions_row_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<TransactionFormData>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let object_id = match load_company_transaction(&state, &id, &session_user).await {
+        Ok(tx) => tx.id.unwrap_or_default(),
+        Err(status) => return status.into_response(),
+    };
+
+    let submitted = form.clone();
+    let parsed = match parse_transaction_form(&state, &company_id, &session_user, form).await {
+        Ok(parsed) => parsed,
+        Err((StatusCode::BAD_REQUEST, message)) => {
+            return match row_form_fragment(
+                &state,
+                &company_id,
+                &session_user.account_access(),
+                id,
+                submitted,
+                Some(message),
+            )
+            .await
+            .and_then(render)
+            {
+                Ok(html) => (StatusCode::UNPROCESSABLE_ENTITY, html).into_response(),
+                Err(status) => status.into_response(),
+            };
+        }
+        Err((status, _)) => return status.into_response(),
+    };
+    match missing_required_field(
+        &state,
+        &company_id,
+        &parsed.transaction_type,
+        parsed.notes.as_deref(),
+        parsed.planned_entry_id.as_ref(),
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(message)) => {
+            return match row_form_fragment(
+                &state,
+                &company_id,
+                &session_user.account_access(),
+                id,
+                submitted,
+                Some(message),
+            )
+            .await
+            .and_then(render)
+            {
+                Ok(html) => (StatusCode::UNPROCESSABLE_ENTITY, html).into_response(),
+                Err(status) => status.into_response(),
+            };
         }
+        Err(status) => return status.into_response(),
+    }
+
+    if update_transaction(
+        &state,
+        &object_id,
+        &company_id,
+        parsed.date,
+        &parsed.description,
+        parsed.transaction_type,
+        &parsed.category_id,
+        parsed.account_from_id,
+        parsed.account_to_id,
+        parsed.amount,
+        parsed.planned_entry_id,
+        parsed.is_confirmed,
+        parsed.notes,
+    )
+    .await
+    .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    match load_company_transaction(&state, &id, &session_user).await {
+        Ok(tx) => match row_fragment(&state, tx).await.and_then(render) {
+            Ok(html) => html.into_response(),
+            Err(status) => status.into_response(),
+        },
+        Err(status) => status.into_response(),
+    }
+}
+
+pub async fn transactions_delete(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    if let Err(status) = match get_transaction_by_id(&state, &object_id).await {
+        Ok(Some(tx)) => ensure_same_company(&tx.company_id, &active_company)
+            .and_then(|_| ensure_transaction_access(&session_user, &tx)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    } {
+        return status.into_response();
     }
 
-    match update_transaction(
-        &state,
-        &object_id,
-        &company_id,
-        date,
-        form.description.trim(),
-        transaction_type,
-        &category_id,
-        account_from_id,
-        account_to_id,
-        amount,
-        planned_entry_id,
-        form.is_confirmed,
-        notes,
-    )
-    .await
-    {
-        Ok(_) => Redirect::to("/admin/transactions").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    match delete_transaction(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Movimiento eliminado."),
+            Redirect::to("/admin/transactions"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el movimiento."),
+            Redirect::to("/admin/transactions"),
+        )
+            .into_response(),
     }
 }
 
-pub async fn transactions_delete(
+/// POST /admin/transactions/{id}/detach — unlinks the transaction from the
+/// planned entry it covers, which is recalculated.
+pub async fn transactions_detach(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -546,15 +1673,20 @@ pub async fn transactions_delete(
     };
 
     if let Err(status) = match get_transaction_by_id(&state, &object_id).await {
-        Ok(Some(tx)) => ensure_same_company(&tx.company_id, &active_company),
+        Ok(Some(tx)) => ensure_same_company(&tx.company_id, &active_company)
+            .and_then(|_| ensure_transaction_access(&session_user, &tx)),
         Ok(None) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     } {
         return status.into_response();
     }
 
-    match delete_transaction(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/transactions").into_response(),
+    match detach_transaction_from_planned_entry(&state, &object_id, &active_company).await {
+        Ok(_) => (
+            Flash::success("Movimiento desvinculado del compromiso."),
+            Redirect::to(&format!("/admin/transactions/{id}/edit")),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -566,25 +1698,99 @@ pub async fn transactions_delete(
     request_body = TransactionPayload,
     responses(
         (status = 201, description = "Transaction created"),
+        (status = 200, description = "A transaction with this external_id already exists; its id is returned"),
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Forbidden"),
-        (status = 400, description = "Invalid input")
+        (status = 400, description = "Invalid input"),
+        (status = 409, description = "external_id taken by a concurrent request")
     ),
     security(("session" = []))
 )]
 pub async fn transactions_create_api(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<TransactionPayload>,
+    Json(mut payload): Json<TransactionPayload>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
         Err(status) => return status.into_response(),
     };
-    let parsed = match parse_transaction_payload(&state, &company_id, payload).await {
+    let external_id = clean_opt(payload.external_id.take());
+    let bank_reference = clean_opt(payload.bank_reference.take());
+    if let Some(external_id) = external_id.as_deref() {
+        match find_transaction_by_external_id(&state, &company_id, external_id).await {
+            Ok(Some(existing)) => {
+                return Json(serde_json::json!({
+                    "id": existing.id.map(|id| id.to_hex()),
+                    "duplicate": true,
+                }))
+                .into_response();
+            }
+            Ok(None) => {}
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+    let receipt_id = match payload.receipt_id.as_deref().map(str::trim) {
+        Some(id) if !id.is_empty() => match load_company_receipt(&state, id, &company_id).await {
+            Ok(receipt) => receipt.id,
+            Err(status) => return status.into_response(),
+        },
+        _ => None,
+    };
+    let custom_values =
+        match payload_custom_values(&state, &company_id, payload.custom_fields.take()).await {
+            Ok(values) => values,
+            Err(response) => return response,
+        };
+    let tax_rate = payload.tax_rate.take();
+    let exchange_rate = payload.exchange_rate.take();
+    let parsed = match parse_transaction_payload(&state, &company_id, &session_user, payload).await
+    {
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    match missing_required_field(
+        &state,
+        &company_id,
+        &parsed.transaction_type,
+        parsed.notes.as_deref(),
+        parsed.planned_entry_id.as_ref(),
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(message)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    }
+    let tax_rate = match parse_tax_rate(
+        tax_rate.map(|rate| rate.to_string()),
+        &parsed.transaction_type,
+    ) {
+        Ok(rate) => rate,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    let exchange_rate = match parse_exchange_rate(exchange_rate.map(|rate| rate.to_string())) {
+        Ok(rate) => rate,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
     let planned_entry_side_effect = parsed.planned_entry_id.map(|id| id.to_hex());
 
     match create_transaction(
@@ -608,14 +1814,67 @@ pub async fn transactions_create_api(
     )
     .await
     {
-        Ok(id) => (
-            StatusCode::CREATED,
-            Json(serde_json::json!({
-                "id": id.to_hex(),
-                "side_effects": { "planned_entry_recalculated": planned_entry_side_effect }
-            })),
-        )
-            .into_response(),
+        Ok(id) => {
+            if set_transaction_external_refs(
+                &state,
+                &id,
+                &company_id,
+                external_id.as_deref(),
+                bank_reference.as_deref(),
+            )
+            .await
+            .is_err()
+            {
+                // Another request recorded the same external id meanwhile.
+                let _ = delete_transaction(&state, &id).await;
+                return external_id_conflict();
+            }
+            if set_custom_field_values(
+                &state,
+                CustomFieldEntity::Transaction,
+                &company_id,
+                &id,
+                custom_values,
+            )
+            .await
+            .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if tax_rate.is_some()
+                && set_transaction_tax_rate(&state, &id, &company_id, tax_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if exchange_rate.is_some()
+                && set_transaction_exchange_rate(&state, &id, &company_id, exchange_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            let receipt_attached = match receipt_id {
+                Some(receipt_id) => {
+                    attach_receipt_to_transaction(&state, &receipt_id, &company_id, &id)
+                        .await
+                        .is_ok()
+                }
+                None => false,
+            };
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({
+                    "id": id.to_hex(),
+                    "side_effects": {
+                        "planned_entry_recalculated": planned_entry_side_effect,
+                        "receipt_attached": receipt_attached,
+                    }
+                })),
+            )
+                .into_response()
+        }
         Err(err) => (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "error": err.to_string() })),
@@ -635,7 +1894,8 @@ pub async fn transactions_create_api(
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "Not found"),
-        (status = 400, description = "Invalid input")
+        (status = 400, description = "Invalid input"),
+        (status = 409, description = "external_id belongs to another transaction")
     ),
     security(("session" = []))
 )]
@@ -643,7 +1903,7 @@ pub async fn transaction_update_api(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-    Json(payload): Json<TransactionPayload>,
+    Json(mut payload): Json<TransactionPayload>,
 ) -> impl IntoResponse {
     let company_id = match require_admin_active(&session_user) {
         Ok(id) => id,
@@ -655,7 +1915,9 @@ pub async fn transaction_update_api(
     };
     let previous_planned_entry_id = match get_transaction_by_id(&state, &object_id).await {
         Ok(Some(tx)) => {
-            if let Err(status) = ensure_same_company(&tx.company_id, &company_id) {
+            if let Err(status) = ensure_same_company(&tx.company_id, &company_id)
+                .and_then(|_| ensure_transaction_access(&session_user, &tx))
+            {
                 return status.into_response();
             }
             tx.planned_entry_id.map(|id| id.to_hex())
@@ -663,10 +1925,71 @@ pub async fn transaction_update_api(
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
-    let parsed = match parse_transaction_payload(&state, &company_id, payload).await {
+    let external_id = clean_opt(payload.external_id.take());
+    let bank_reference = clean_opt(payload.bank_reference.take());
+    if let Some(external_id) = external_id.as_deref() {
+        match find_transaction_by_external_id(&state, &company_id, external_id).await {
+            Ok(Some(other)) if other.id != Some(object_id) => return external_id_conflict(),
+            Ok(_) => {}
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+    let custom_values = match payload.custom_fields.take() {
+        Some(values) => match payload_custom_values(&state, &company_id, Some(values)).await {
+            Ok(values) => Some(values),
+            Err(response) => return response,
+        },
+        None => None,
+    };
+    let tax_rate = payload.tax_rate.take();
+    let exchange_rate = payload.exchange_rate.take();
+    let parsed = match parse_transaction_payload(&state, &company_id, &session_user, payload).await
+    {
         Ok(parsed) => parsed,
         Err(status) => return status.into_response(),
     };
+    match missing_required_field(
+        &state,
+        &company_id,
+        &parsed.transaction_type,
+        parsed.notes.as_deref(),
+        parsed.planned_entry_id.as_ref(),
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(message)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    }
+    let tax_rate = match parse_tax_rate(
+        tax_rate.map(|rate| rate.to_string()),
+        &parsed.transaction_type,
+    ) {
+        Ok(rate) => rate,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    let exchange_rate = match parse_exchange_rate(exchange_rate.map(|rate| rate.to_string())) {
+        Ok(rate) => rate,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
     let planned_entry_side_effect = parsed.planned_entry_id.map(|id| id.to_hex());
 
     match update_transaction(
@@ -686,14 +2009,55 @@ pub async fn transaction_update_api(
     )
     .await
     {
-        Ok(_) => Json(serde_json::json!({
+        Ok(_) => {
+            if set_transaction_external_refs(
+                &state,
+                &object_id,
+                &company_id,
+                external_id.as_deref(),
+                bank_reference.as_deref(),
+            )
+            .await
+            .is_err()
+            {
+                return external_id_conflict();
+            }
+            if let Some(values) = custom_values
+                && set_custom_field_values(
+                    &state,
+                    CustomFieldEntity::Transaction,
+                    &company_id,
+                    &object_id,
+                    values,
+                )
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if tax_rate.is_some()
+                && set_transaction_tax_rate(&state, &object_id, &company_id, tax_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            if exchange_rate.is_some()
+                && set_transaction_exchange_rate(&state, &object_id, &company_id, exchange_rate)
+                    .await
+                    .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            Json(serde_json::json!({
             "ok": true,
             "side_effects": {
                 "previous_planned_entry_recalculated": previous_planned_entry_id,
                 "planned_entry_recalculated": planned_entry_side_effect
             }
-        }))
-        .into_response(),
+            }))
+            .into_response()
+        }
         Err(err) => (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "error": err.to_string() })),
@@ -730,7 +2094,9 @@ pub async fn transaction_delete_api(
     };
     let planned_entry_side_effect = match get_transaction_by_id(&state, &object_id).await {
         Ok(Some(tx)) => {
-            if let Err(status) = ensure_same_company(&tx.company_id, &company_id) {
+            if let Err(status) = ensure_same_company(&tx.company_id, &company_id)
+                .and_then(|_| ensure_transaction_access(&session_user, &tx))
+            {
                 return status.into_response();
             }
             tx.planned_entry_id.map(|id| id.to_hex())
@@ -753,26 +2119,57 @@ pub async fn transaction_delete_api(
     }
 }
 
+fn external_id_conflict() -> axum::response::Response {
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({
+            "error": "external_id is already used by another transaction"
+        })),
+    )
+        .into_response()
+}
+
+/// Custom field values of a JSON payload. Invalid or missing required values
+/// answer `400` with the message.
+async fn payload_custom_values(
+    state: &AppState,
+    company_id: &ObjectId,
+    values: Option<HashMap<String, serde_json::Value>>,
+) -> Result<mongodb::bson::Document, axum::response::Response> {
+    let fields = entity_custom_fields(state, company_id, CustomFieldEntity::Transaction)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    custom_field_values(&fields, &raw_custom_values(values)).map_err(|message| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response()
+    })
+}
+
 async fn parse_transaction_payload(
     state: &AppState,
     company_id: &ObjectId,
+    session_user: &SessionUser,
     payload: TransactionPayload,
 ) -> Result<ParsedTransactionPayload, StatusCode> {
     let transaction_type =
         parse_transaction_type(&payload.transaction_type).map_err(|_| StatusCode::BAD_REQUEST)?;
     let category_id = parse_object_id(&payload.category_id, "category_id")
         .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let account_from_id =
-        parse_optional_object_id(payload.account_from_id).map_err(|_| StatusCode::BAD_REQUEST)?;
-    let account_to_id =
-        parse_optional_object_id(payload.account_to_id).map_err(|_| StatusCode::BAD_REQUEST)?;
-    let planned_entry_id =
-        parse_optional_object_id(payload.planned_entry_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let account_from_id = payload.account_from_id;
+    let account_to_id = payload.account_to_id;
+    let planned_entry_id = payload.planned_entry_id;
     let date = parse_datetime_field(&payload.date, "date").map_err(|_| StatusCode::BAD_REQUEST)?;
     if payload.description.trim().is_empty() || payload.amount < 0.0 {
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    ensure_account_access(
+        session_user,
+        account_from_id.iter().chain(account_to_id.iter()),
+    )?;
     validate_company_refs(
         state,
         company_id,
@@ -809,10 +2206,267 @@ async fn parse_transaction_payload(
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, String> {
-    match clean_opt(value) {
-        Some(value) => parse_object_id(&value, "id").map(Some),
-        None => Ok(None),
+// ── Pending review queue ──────────────────────────────────────────────────
+
+#[derive(Template)]
+#[template(path = "admin/transactions/pending.html")]
+struct PendingTransactionsTemplate {
+    rows: Vec<PendingTransactionRow>,
+    confirmed: Option<u64>,
+}
+
+struct PendingTransactionRow {
+    id: String,
+    date: String,
+    description: String,
+    type_label: &'static str,
+    transaction_type: String,
+    category: String,
+    amount: f64,
+    covers_planned_entry: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PendingQuery {
+    #[serde(default)]
+    confirmed: Option<u64>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TransactionsConfirmPayload {
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TransactionsBulkPayload {
+    pub ids: Vec<String>,
+    /// `set_category`, `add_tag` or `confirm`.
+    pub action: String,
+    /// Required by `set_category`.
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub category_id: Option<ObjectId>,
+    /// Required by `add_tag`.
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+fn parse_bulk_action(payload: &TransactionsBulkPayload) -> Result<TransactionBulkAction, String> {
+    match payload.action.as_str() {
+        "set_category" => payload
+            .category_id
+            .map(TransactionBulkAction::SetCategory)
+            .ok_or_else(|| "category_id es obligatorio".to_string()),
+        "add_tag" => clean_opt(payload.tag.clone())
+            .map(TransactionBulkAction::AddTag)
+            .ok_or_else(|| "tag es obligatorio".to_string()),
+        "confirm" => Ok(TransactionBulkAction::Confirm),
+        other => Err(format!("acción desconocida: {other}")),
+    }
+}
+
+fn parse_transaction_ids<'a>(
+    values: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<ObjectId>, StatusCode> {
+    values
+        .into_iter()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| ObjectId::from_str(v.trim()).map_err(|_| StatusCode::BAD_REQUEST))
+        .collect()
+}
+
+pub async fn transactions_pending(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PendingQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let access = session_user.account_access();
+    let pending = list_pending_transactions(&state, &active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let categories: HashMap<ObjectId, String> = state
+        .categories
+        .find(bson::doc! { "company_id": active_company })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter_map(|c| c.id.map(|id| (id, c.name)))
+        .collect();
+
+    let rows = pending
+        .into_iter()
+        .filter(|tx| access.allows_transaction(tx))
+        .filter_map(|tx| {
+            tx.id.map(|id| PendingTransactionRow {
+                id: id.to_hex(),
+                date: tx.date.to_chrono().format("%Y-%m-%d").to_string(),
+                description: tx.description,
+                type_label: transaction_type_label(&tx.transaction_type),
+                transaction_type: transaction_type_value(&tx.transaction_type).to_string(),
+                category: categories.get(&tx.category_id).cloned().unwrap_or_default(),
+                amount: tx.amount,
+                covers_planned_entry: tx.planned_entry_id.is_some(),
+            })
+        })
+        .collect();
+
+    render(PendingTransactionsTemplate {
+        rows,
+        confirmed: query.confirmed,
+    })
+}
+
+/// Bulk confirm from the review queue. The form posts one `ids` field per
+/// checked row, so it is read as raw pairs.
+pub async fn transactions_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let active_company = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let ids = match parse_transaction_ids(
+        pairs
+            .iter()
+            .filter(|(key, _)| key == "ids")
+            .map(|(_, value)| value.as_str()),
+    ) {
+        Ok(ids) => ids,
+        Err(status) => return status.into_response(),
+    };
+
+    match confirm_transactions(&state, &active_company, &ids).await {
+        Ok(count) => {
+            Redirect::to(&format!("/admin/transactions/pending?confirmed={count}")).into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/transactions/pending",
+    tag = "finance",
+    responses(
+        (status = 200, description = "Unconfirmed transactions, oldest first"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn transactions_pending_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<TransactionData>>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let company = session_user.user().company_name.clone();
+    let pending = list_pending_transactions(&state, &active_company)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(
+        pending
+            .into_iter()
+            .filter(|tx| session_user.account_access().allows_transaction(tx))
+            .filter_map(|tx| transaction_data(tx, company.clone()))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/confirm",
+    tag = "finance",
+    request_body = TransactionsConfirmPayload,
+    responses(
+        (status = 200, description = "Transactions confirmed"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid input")
+    ),
+    security(("session" = []))
+)]
+pub async fn transactions_confirm_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TransactionsConfirmPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let ids = match parse_transaction_ids(payload.ids.iter().map(String::as_str)) {
+        Ok(ids) => ids,
+        Err(status) => return status.into_response(),
+    };
+
+    match confirm_transactions(&state, &company_id, &ids).await {
+        Ok(count) => Json(serde_json::json!({ "ok": true, "confirmed": count })).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/transactions/bulk",
+    tag = "finance",
+    request_body = TransactionsBulkPayload,
+    responses(
+        (status = 200, description = "Per-transaction results: `results[].error` is null for updated rows and explains why the others were skipped"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid ids or action")
+    ),
+    security(("session" = []))
+)]
+pub async fn transactions_bulk_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TransactionsBulkPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let action = match parse_bulk_action(&payload) {
+        Ok(action) => action,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    let ids = match parse_transaction_ids(payload.ids.iter().map(String::as_str)) {
+        Ok(ids) => ids,
+        Err(status) => return status.into_response(),
+    };
+
+    match bulk_edit_transactions(&state, &company_id, &ids, &action).await {
+        Ok(results) => {
+            let failed = results.iter().filter(|r| r.error.is_some()).count();
+            Json(serde_json::json!({
+                "updated": results.len() - failed,
+                "failed": failed,
+                "results": results
+                    .into_iter()
+                    .map(|r| serde_json::json!({
+                        "id": r.id.to_hex(),
+                        "ok": r.error.is_none(),
+                        "error": r.error,
+                    }))
+                    .collect::<Vec<_>>(),
+            }))
+            .into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
@@ -830,9 +2484,15 @@ pub struct TxApiItem {
     pub account_to: String,
     pub contact: String,
     pub is_confirmed: bool,
+    /// `held`, `captured` or `voided` on card holds.
+    pub hold_status: Option<String>,
     pub cfdi_folio: String,
+    pub reference: String,
     pub currency: String,
     pub notes: String,
+    pub tags: Vec<String>,
+    /// Custom field values keyed by label, formatted for display.
+    pub custom_fields: Vec<(String, String)>,
 }
 
 #[derive(Serialize)]
@@ -854,7 +2514,25 @@ pub struct TransactionData {
     pub cfdi_uuid: Option<String>,
     pub currency: Option<String>,
     pub cfdi_folio: Option<String>,
+    pub reference: Option<String>,
+    pub external_id: Option<String>,
+    pub bank_reference: Option<String>,
     pub notes: Option<String>,
+    pub tags: Vec<String>,
+    pub custom_fields: serde_json::Value,
+    /// Name of the tax profile, or "Manual" for a typed rate.
+    pub tax_name: Option<String>,
+    pub tax_rate: Option<f64>,
+    /// Tax included in `amount`.
+    pub tax_amount: Option<f64>,
+    /// Transaction this one refunds.
+    pub refund_of: Option<String>,
+    /// Units of the company currency per unit of the account's.
+    pub exchange_rate: Option<f64>,
+    /// `held`, `captured` or `voided` on card holds.
+    pub hold_status: Option<String>,
+    /// When an open hold is voided if not captured.
+    pub hold_expires_at: Option<String>,
 }
 
 #[utoipa::path(
@@ -882,6 +2560,37 @@ pub async fn transaction_data_api(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
     ensure_same_company(&tx.company_id, &active_company)?;
+    ensure_transaction_access(&session_user, &tx)?;
+
+    transaction_data(tx, session_user.user().company_name.clone())
+        .map(Json)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/transactions/by_external/{id}",
+    tag = "finance",
+    params(("id" = String, Path, description = "External id given when the transaction was written")),
+    responses(
+        (status = 200, description = "Transaction detail"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "No transaction of the company has this external id")
+    ),
+    security(("session" = []), ("token" = []))
+)]
+pub async fn transaction_by_external_id_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(external_id): Path<String>,
+) -> Result<Json<TransactionData>, StatusCode> {
+    let active_company = require_admin_active(&session_user)?;
+    let tx = find_transaction_by_external_id(&state, &active_company, external_id.trim())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ensure_transaction_access(&session_user, &tx)?;
 
     transaction_data(tx, session_user.user().company_name.clone())
         .map(Json)
@@ -892,19 +2601,29 @@ pub async fn transaction_data_api(
     get,
     path = "/api/admin/transactions/data",
     tag = "finance",
+    params(("cf_<key>" = Option<String>, Query, description = "Filter by a custom field value; text matches partially")),
     responses(
         (status = 200, description = "List transactions"),
         (status = 401, description = "Not authenticated"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 400, description = "Invalid custom field filter")
     ),
     security(("session" = []))
 )]
 pub async fn transactions_data_api(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Vec<TxApiItem>>, StatusCode> {
     let active_company = require_admin_active(&session_user)?;
     let filter = bson::doc! { "company_id": active_company };
+    let fields =
+        entity_custom_fields(&state, &active_company, CustomFieldEntity::Transaction).await?;
+    let mut tx_filter = filter.clone();
+    tx_filter.extend(custom_field_filter(&fields, &params).map_err(|_| StatusCode::BAD_REQUEST)?);
+    let tx_filter = session_user
+        .account_access()
+        .restrict_transactions(tx_filter);
 
     // Parallel lookup fetches
     let (accs, cats, contacts, txs) = tokio::try_join!(
@@ -945,7 +2664,7 @@ pub async fn transactions_data_api(
                 .build();
             state
                 .transactions
-                .find(filter.clone())
+                .find(tx_filter.clone())
                 .with_options(opts)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -1009,9 +2728,23 @@ pub async fn transactions_data_api(
                     .cloned()
                     .unwrap_or_default(),
                 is_confirmed: tx.is_confirmed,
+                hold_status: tx
+                    .hold
+                    .as_ref()
+                    .map(|hold| hold.status.as_str().to_string()),
                 cfdi_folio: tx.cfdi_folio.unwrap_or_default(),
+                reference: tx.reference.unwrap_or_default(),
                 currency: tx.currency.unwrap_or_else(|| "MXN".into()),
                 notes: tx.notes.unwrap_or_default(),
+                tags: tx.tags,
+                custom_fields: fields
+                    .iter()
+                    .filter(|field| tx.custom_fields.contains_key(&field.key))
+                    .map(|field| {
+                        let value = custom_field_display(field, tx.custom_fields.get(&field.key));
+                        (field.label.clone(), value)
+                    })
+                    .collect(),
             })
         })
         .collect();
@@ -1039,6 +2772,24 @@ fn transaction_data(tx: Transaction, company: String) -> Option<TransactionData>
         cfdi_uuid: tx.cfdi_uuid,
         currency: tx.currency,
         cfdi_folio: tx.cfdi_folio,
+        reference: tx.reference,
+        external_id: tx.external_id,
+        bank_reference: tx.bank_reference,
         notes: tx.notes,
+        tags: tx.tags,
+        custom_fields: custom_fields_json(&tx.custom_fields),
+        tax_name: tx.tax.as_ref().map(|tax| tax.name.clone()),
+        tax_rate: tx.tax.as_ref().map(|tax| tax.rate),
+        exchange_rate: tx.exchange_rate,
+        tax_amount: tx.tax.map(|tax| tax.amount),
+        refund_of: tx.refund_of.map(|id| id.to_hex()),
+        hold_status: tx
+            .hold
+            .as_ref()
+            .map(|hold| hold.status.as_str().to_string()),
+        hold_expires_at: tx
+            .hold
+            .as_ref()
+            .map(|hold| datetime_to_string(&hold.expires_at)),
     })
 }
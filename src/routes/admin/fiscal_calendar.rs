@@ -0,0 +1,393 @@
+// Company fiscal calendar: calendar months or a 4-4-5 style week pattern,
+// and when the fiscal year starts. The page lists the periods of the current
+// fiscal year so the admin can check them against their closing calendar.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form, Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use bson::{DateTime, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::{FiscalCalendar, FiscalPattern},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, FISCAL_PERIODS_PER_YEAR, fiscal_period, fiscal_period_by_number,
+        get_company_by_id, update_company_fiscal_calendar,
+    },
+};
+
+/// Fiscal calendar of a company.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/fiscal_calendar",
+            get(company_fiscal_calendar_edit).post(company_fiscal_calendar_update),
+        )
+        .route(
+            "/api/admin/companies/{id}/fiscal_calendar",
+            get(company_fiscal_calendar_api).post(company_fiscal_calendar_update_api),
+        )
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "Enero",
+    "Febrero",
+    "Marzo",
+    "Abril",
+    "Mayo",
+    "Junio",
+    "Julio",
+    "Agosto",
+    "Septiembre",
+    "Octubre",
+    "Noviembre",
+    "Diciembre",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Lunes",
+    "Martes",
+    "Miércoles",
+    "Jueves",
+    "Viernes",
+    "Sábado",
+    "Domingo",
+];
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.can_view_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// A period as listed on the page and returned by the API; `end` is the
+/// first instant after it.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FiscalPeriodRow {
+    pub key: String,
+    pub number: u32,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FiscalCalendarData {
+    /// `months`, `4-4-5`, `4-5-4` or `5-4-4`.
+    pub pattern: String,
+    pub start_month: u32,
+    /// Days from Monday (0) to Sunday (6).
+    pub start_weekday: u32,
+    /// The periods of the current fiscal year.
+    pub periods: Vec<FiscalPeriodRow>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct FiscalCalendarPayload {
+    pub pattern: String,
+    #[serde(default = "default_start_month")]
+    pub start_month: u32,
+    #[serde(default)]
+    pub start_weekday: u32,
+}
+
+fn default_start_month() -> u32 {
+    1
+}
+
+fn parse_calendar(payload: &FiscalCalendarPayload) -> Result<FiscalCalendar, &'static str> {
+    let pattern = FiscalPattern::parse(payload.pattern.trim())
+        .ok_or("El calendario es de meses o de semanas 4-4-5, 4-5-4 o 5-4-4.")?;
+    if !(1..=12).contains(&payload.start_month) {
+        return Err("El mes de inicio va de 1 a 12.");
+    }
+    if payload.start_weekday > 6 {
+        return Err("El día de inicio va de lunes (0) a domingo (6).");
+    }
+    Ok(FiscalCalendar {
+        pattern,
+        start_month: payload.start_month,
+        // Calendar months always start on the 1st.
+        start_weekday: if pattern == FiscalPattern::Months {
+            0
+        } else {
+            payload.start_weekday
+        },
+    })
+}
+
+/// The periods of the fiscal year `now` falls in.
+fn current_year_periods(calendar: &FiscalCalendar, now: DateTime) -> Vec<FiscalPeriodRow> {
+    let year = fiscal_period(calendar, now.to_chrono()).year;
+    (1..=FISCAL_PERIODS_PER_YEAR)
+        .map(|number| {
+            let period = fiscal_period_by_number(calendar, year, number);
+            FiscalPeriodRow {
+                key: period.key,
+                number,
+                start: period.start.format("%Y-%m-%d").to_string(),
+                end: period.end.format("%Y-%m-%d").to_string(),
+            }
+        })
+        .collect()
+}
+
+fn calendar_data(calendar: &FiscalCalendar) -> FiscalCalendarData {
+    FiscalCalendarData {
+        pattern: calendar.pattern.as_str().to_string(),
+        start_month: calendar.start_month,
+        start_weekday: calendar.start_weekday,
+        periods: current_year_periods(calendar, DateTime::now()),
+    }
+}
+
+struct ChoiceOption {
+    value: String,
+    label: &'static str,
+    selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/fiscal_calendar.html")]
+struct FiscalCalendarTemplate {
+    company_id: String,
+    company_name: String,
+    patterns: Vec<ChoiceOption>,
+    months: Vec<ChoiceOption>,
+    weekdays: Vec<ChoiceOption>,
+    periods: Vec<FiscalPeriodRow>,
+    errors: Option<String>,
+}
+
+fn calendar_template(
+    company_id: &str,
+    company_name: String,
+    calendar: &FiscalCalendar,
+    errors: Option<String>,
+) -> FiscalCalendarTemplate {
+    FiscalCalendarTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        patterns: FiscalPattern::ALL
+            .iter()
+            .map(|pattern| ChoiceOption {
+                value: pattern.as_str().to_string(),
+                label: pattern.label(),
+                selected: *pattern == calendar.pattern,
+            })
+            .collect(),
+        months: (1..=12)
+            .map(|month: u32| ChoiceOption {
+                value: month.to_string(),
+                label: MONTH_NAMES[(month - 1) as usize],
+                selected: month == calendar.start_month,
+            })
+            .collect(),
+        weekdays: (0..7)
+            .map(|day: u32| ChoiceOption {
+                value: day.to_string(),
+                label: WEEKDAY_NAMES[day as usize],
+                selected: day == calendar.start_weekday,
+            })
+            .collect(),
+        periods: current_year_periods(calendar, DateTime::now()),
+        errors,
+    }
+}
+
+pub async fn company_fiscal_calendar_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(calendar_template(
+        &company_id,
+        company.name,
+        &company.fiscal_calendar,
+        None,
+    ))
+}
+
+/// Saves the calendar; the income statement is rebuilt with the new periods.
+pub async fn company_fiscal_calendar_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    Form(form): Form<FiscalCalendarPayload>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&company_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let company = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let calendar = match parse_calendar(&form) {
+        Ok(calendar) => calendar,
+        Err(message) => {
+            return render(calendar_template(
+                &company_id,
+                company.name,
+                &company.fiscal_calendar,
+                Some(message.to_string()),
+            ))
+            .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+    if let Err(e) = update_company_fiscal_calendar(&state, &object_id, &calendar).await {
+        eprintln!("[fiscal calendar] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Calendario fiscal guardado."),
+        Redirect::to(&format!("/admin/companies/{company_id}/fiscal_calendar")),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/companies/{id}/fiscal_calendar",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    responses(
+        (status = 200, description = "Fiscal calendar and the periods of the current fiscal year", body = FiscalCalendarData),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_fiscal_calendar_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<FiscalCalendarData>, StatusCode> {
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(calendar_data(&company.fiscal_calendar)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/companies/{id}/fiscal_calendar",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    request_body = FiscalCalendarPayload,
+    responses(
+        (status = 200, description = "Calendar saved; returns it with the periods of the current fiscal year", body = FiscalCalendarData),
+        (status = 400, description = "Unknown pattern, month or weekday"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_fiscal_calendar_update_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<FiscalCalendarPayload>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let calendar = match parse_calendar(&payload) {
+        Ok(calendar) => calendar,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    match get_company_by_id(&state, &object_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+    match update_company_fiscal_calendar(&state, &object_id, &calendar).await {
+        Ok(()) => Json(calendar_data(&calendar)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(pattern: &str, start_month: u32, start_weekday: u32) -> FiscalCalendarPayload {
+        FiscalCalendarPayload {
+            pattern: pattern.to_string(),
+            start_month,
+            start_weekday,
+        }
+    }
+
+    #[test]
+    fn calendars_are_checked_before_saving() {
+        assert_eq!(
+            parse_calendar(&payload("4-4-5", 2, 6)),
+            Ok(FiscalCalendar {
+                pattern: FiscalPattern::Weeks445,
+                start_month: 2,
+                start_weekday: 6,
+            })
+        );
+        assert_eq!(
+            parse_calendar(&payload("months", 4, 3)).map(|c| c.start_weekday),
+            Ok(0)
+        );
+        assert!(parse_calendar(&payload("13-periods", 1, 0)).is_err());
+        assert!(parse_calendar(&payload("months", 13, 0)).is_err());
+        assert!(parse_calendar(&payload("5-4-4", 1, 7)).is_err());
+    }
+}
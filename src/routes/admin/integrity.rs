@@ -0,0 +1,331 @@
+// Dependencies, archive and restore for the records whose deletion is guarded
+// by referential integrity checks (accounts, categories, contacts, companies),
+// plus the report and fix-ups of references to records that are already gone.
+
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use bson::oid::ObjectId;
+use serde::Deserialize;
+
+use super::finance::helpers::{ensure_same_company, require_admin_active};
+use crate::{
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, IntegrityEntity, clear_orphaned_contacts, find_dependencies,
+        find_orphaned_references, fix_orphaned_categories, get_account_by_id, get_category_by_id,
+        get_company_by_id, get_contact_by_id, set_archived,
+    },
+};
+
+/// Dependency checks, archiving and restoring of any entity, and the
+/// orphaned references report.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/integrity/orphans", get(orphans_report))
+        .route("/admin/integrity/orphans/fix", post(orphans_fix))
+        .route(
+            "/admin/{entity}/{id}/dependencies",
+            get(entity_dependencies),
+        )
+        .route("/admin/{entity}/{id}/archive", post(entity_archive))
+        .route("/admin/{entity}/{id}/restore", post(entity_restore))
+}
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.can_view_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Record addressed by `/admin/{entity}/{id}`, after the access checks.
+struct Target {
+    entity: IntegrityEntity,
+    id: ObjectId,
+    /// Company the dependency lookups are scoped to.
+    company_id: ObjectId,
+    /// `is_active` of archivable records.
+    is_active: Option<bool>,
+}
+
+/// Companies are reachable by any of their admins; other records only
+/// within the active company.
+async fn resolve_target(
+    session_user: &SessionUser,
+    state: &AppState,
+    entity: &str,
+    id: &str,
+) -> Result<Target, StatusCode> {
+    let entity = IntegrityEntity::from_slug(entity).ok_or(StatusCode::NOT_FOUND)?;
+    let id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let internal = |_| StatusCode::INTERNAL_SERVER_ERROR;
+
+    let (company_id, is_active) = match entity {
+        IntegrityEntity::Company => {
+            require_company_admin(session_user, &id)?;
+            let company = get_company_by_id(state, &id)
+                .await
+                .map_err(internal)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (id, Some(company.is_active))
+        }
+        IntegrityEntity::Account => {
+            let account = get_account_by_id(state, &id)
+                .await
+                .map_err(internal)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (account.company_id, Some(account.is_active))
+        }
+        IntegrityEntity::Category => {
+            let category = get_category_by_id(state, &id)
+                .await
+                .map_err(internal)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (category.company_id, None)
+        }
+        IntegrityEntity::Contact => {
+            let contact = get_contact_by_id(state, &id)
+                .await
+                .map_err(internal)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (contact.company_id, None)
+        }
+    };
+    if entity != IntegrityEntity::Company {
+        let active_company = require_admin_active(session_user)?;
+        ensure_same_company(&company_id, &active_company)?;
+    }
+    Ok(Target {
+        entity,
+        id,
+        company_id,
+        is_active,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/{entity}/{id}/dependencies",
+    tag = "admin",
+    params(
+        ("entity" = String, Path, description = "accounts, categories, contacts or companies"),
+        ("id" = String, Path, description = "Record id")
+    ),
+    responses(
+        (status = 200, description = "Records that block deleting this one, by collection, and whether it can be archived instead"),
+        (status = 400, description = "Invalid id"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Unknown entity or record")
+    ),
+    security(("session" = []))
+)]
+pub async fn entity_dependencies(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path((entity, id)): Path<(String, String)>,
+) -> Response {
+    let target = match resolve_target(&session_user, &state, &entity, &id).await {
+        Ok(target) => target,
+        Err(status) => return status.into_response(),
+    };
+    let dependencies =
+        match find_dependencies(&state, target.entity, &target.id, &target.company_id).await {
+            Ok(dependencies) => dependencies,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+    Json(serde_json::json!({
+        "entity": target.entity.as_str(),
+        "id": target.id.to_hex(),
+        "can_delete": dependencies.is_empty(),
+        "can_archive": target.entity.can_archive(),
+        "is_active": target.is_active,
+        "dependencies": dependencies,
+    }))
+    .into_response()
+}
+
+async fn update_archived(
+    session_user: SessionUser,
+    state: &AppState,
+    entity: &str,
+    id: &str,
+    archived: bool,
+) -> Response {
+    let target = match resolve_target(&session_user, state, entity, id).await {
+        Ok(target) => target,
+        Err(status) => return status.into_response(),
+    };
+    if !target.entity.can_archive() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Este registro no se puede archivar." })),
+        )
+            .into_response();
+    }
+    // Same rule as deleting: the session's active company stays active.
+    if archived
+        && target.entity == IntegrityEntity::Company
+        && &target.id == session_user.active_company_id()
+    {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match set_archived(state, target.entity, &target.id, archived).await {
+        Ok(_) => Json(serde_json::json!({ "ok": true, "is_active": !archived })).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/{entity}/{id}/archive",
+    tag = "admin",
+    params(
+        ("entity" = String, Path, description = "accounts or companies"),
+        ("id" = String, Path, description = "Record id")
+    ),
+    responses(
+        (status = 200, description = "Record deactivated"),
+        (status = 400, description = "Invalid id, or the entity cannot be archived"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden — not an admin, or the active company"),
+        (status = 404, description = "Unknown entity or record")
+    ),
+    security(("session" = []))
+)]
+pub async fn entity_archive(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path((entity, id)): Path<(String, String)>,
+) -> Response {
+    update_archived(session_user, &state, &entity, &id, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/{entity}/{id}/restore",
+    tag = "admin",
+    params(
+        ("entity" = String, Path, description = "accounts or companies"),
+        ("id" = String, Path, description = "Record id")
+    ),
+    responses(
+        (status = 200, description = "Record active again"),
+        (status = 400, description = "Invalid id, or the entity cannot be archived"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Unknown entity or record")
+    ),
+    security(("session" = []))
+)]
+pub async fn entity_restore(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path((entity, id)): Path<(String, String)>,
+) -> Response {
+    update_archived(session_user, &state, &entity, &id, false).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/integrity/orphans",
+    tag = "admin",
+    responses(
+        (status = 200, description = "References of the active company to missing accounts, categories or contacts, grouped by the kind of record that is missing"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("session" = []))
+)]
+pub async fn orphans_report(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    match find_orphaned_references(&state, &company_id).await {
+        Ok(groups) => Json(serde_json::json!({
+            "company_id": company_id.to_hex(),
+            "count": groups.iter().map(|group| group.count).sum::<u64>(),
+            "groups": groups,
+        }))
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Fix-up offered for a kind of orphaned reference.
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanFixAction {
+    /// Point category references at the "Sin categoría" placeholder.
+    ReassignCategories,
+    /// Remove contact references.
+    ClearContacts,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct OrphanFixPayload {
+    action: OrphanFixAction,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/integrity/orphans/fix",
+    tag = "admin",
+    request_body = OrphanFixPayload,
+    responses(
+        (status = 200, description = "Documents updated, placeholder categories created and the remaining orphans"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 422, description = "Unknown action")
+    ),
+    security(("session" = []))
+)]
+pub async fn orphans_fix(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<OrphanFixPayload>,
+) -> Response {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let fixed = match payload.action {
+        OrphanFixAction::ReassignCategories => fix_orphaned_categories(&state, &company_id).await,
+        OrphanFixAction::ClearContacts => clear_orphaned_contacts(&state, &company_id).await,
+    };
+    let fix = match fixed {
+        Ok(fix) => fix,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match find_orphaned_references(&state, &company_id).await {
+        Ok(groups) => Json(serde_json::json!({
+            "updated": fix.updated,
+            "created_categories": fix.created_categories,
+            "groups": groups,
+        }))
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
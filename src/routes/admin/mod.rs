@@ -1,24 +1,41 @@
 pub mod account;
+pub mod branding;
 pub mod cfdi_download;
 pub mod cfdis;
+pub mod chat_notifications;
 pub mod companies;
+pub mod confirm;
 pub mod finance;
+pub mod fiscal_calendar;
+pub mod integrity;
+pub mod notification_settings;
 pub mod project_backend;
 pub mod projects;
+pub mod required_fields;
 pub mod resource_logs;
 pub mod resources;
 pub mod sat_configs;
+pub mod security;
+pub mod slow_queries;
+pub mod storage;
 pub mod users;
 pub mod users_api;
+pub mod webhooks;
 
 pub use account::*;
+pub use branding::{company_branding_edit, company_branding_update, company_logo};
 pub use cfdi_download::{
     company_cfdi_download, company_cfdi_download_api, company_cfdi_job_status,
     company_cfdi_jobs_list,
 };
 pub use cfdis::{cfdi_data_api, cfdis_data_api, cfdis_index};
+pub use chat_notifications::{
+    company_chat_notifications_edit, company_chat_notifications_test,
+    company_chat_notifications_update,
+};
 pub use companies::*;
 pub use finance::*;
+pub use integrity::{entity_archive, entity_dependencies, entity_restore};
 pub use project_backend::*;
 pub use projects::*;
 pub use resource_logs::*;
@@ -28,7 +45,38 @@ pub use sat_configs::{
     sat_config_upload_api, sat_configs_create, sat_configs_data_api, sat_configs_delete,
     sat_configs_new,
 };
+pub use security::security_index;
 pub use users::*;
 pub use users_api::{
-    api_user_detail, api_users_create, api_users_delete, api_users_index, api_users_update,
+    api_user_accounts_update, api_user_detail, api_users_create, api_users_delete,
+    api_users_index, api_users_update,
 };
+
+use crate::{routes::Routes, uploads::BodyLimits};
+
+/// Every route under `/admin` and `/api/admin`, plus the account pages.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .merge(account::router(limits))
+        .merge(users::router())
+        .merge(users_api::router())
+        .merge(companies::router())
+        .merge(cfdi_download::router())
+        .merge(branding::router(limits))
+        .merge(chat_notifications::router())
+        .merge(notification_settings::router())
+        .merge(webhooks::router())
+        .merge(fiscal_calendar::router())
+        .merge(required_fields::router())
+        .merge(integrity::router())
+        .merge(cfdis::router())
+        .merge(sat_configs::router(limits))
+        .merge(security::router())
+        .merge(slow_queries::router())
+        .merge(storage::router())
+        .merge(finance::router(limits))
+        .merge(projects::router())
+        .merge(project_backend::router())
+        .merge(resources::router())
+        .merge(resource_logs::router())
+}
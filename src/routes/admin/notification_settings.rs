@@ -0,0 +1,368 @@
+// Notification settings of the active company: the channels its alerts and
+// digest go through (email, webhook, chat), which alerts it wants, how often
+// the digest goes out and the quiet hours. The chat and the webhook URL are
+// still set on their own pages; this one only turns them on.
+
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{
+    flash::Flash,
+    models::{Company, DigestFrequency, NotificationSettings, QuietHours},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, UTC_OFFSETS, get_company_by_id, notification_settings,
+        update_company_notification_settings,
+    },
+};
+
+use super::finance::helpers::require_admin_active;
+
+/// Notification settings of the active company.
+pub fn router() -> Routes {
+    Routes::new().route(
+        "/admin/notifications/settings",
+        get(notification_settings_edit).post(notification_settings_update),
+    )
+}
+
+/// Most addresses the notification emails go to.
+pub const MAX_EMAIL_RECIPIENTS: usize = 10;
+
+const WEEKDAYS: [&str; 7] = [
+    "Lunes",
+    "Martes",
+    "Miércoles",
+    "Jueves",
+    "Viernes",
+    "Sábado",
+    "Domingo",
+];
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+struct SelectOption {
+    value: String,
+    label: String,
+    selected: bool,
+}
+
+fn hour_options(selected: Option<u32>) -> Vec<SelectOption> {
+    (0..24)
+        .map(|hour| SelectOption {
+            value: hour.to_string(),
+            label: format!("{hour:02}:00"),
+            selected: selected == Some(hour),
+        })
+        .collect()
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/notification_settings.html")]
+struct NotificationSettingsTemplate {
+    company_id: String,
+    company_name: String,
+    email: bool,
+    /// One address per line.
+    email_recipients: String,
+    max_email_recipients: usize,
+    webhook: bool,
+    /// Empty when the company has no webhook URL yet.
+    webhook_url: String,
+    chat: bool,
+    /// `telegram -1001234` and the like; empty when no chat is set.
+    chat_target: String,
+    overdue_alerts: bool,
+    budget_alerts: bool,
+    digest: String,
+    digest_hours: Vec<SelectOption>,
+    weekdays: Vec<SelectOption>,
+    utc_offsets: Vec<SelectOption>,
+    quiet_starts: Vec<SelectOption>,
+    quiet_ends: Vec<SelectOption>,
+    has_quiet_hours: bool,
+    errors: Option<String>,
+}
+
+fn settings_template(
+    company: &Company,
+    settings: &NotificationSettings,
+    errors: Option<String>,
+) -> NotificationSettingsTemplate {
+    let quiet = settings.quiet_hours;
+    NotificationSettingsTemplate {
+        company_id: company.id.map(|id| id.to_hex()).unwrap_or_default(),
+        company_name: company.name.clone(),
+        email: settings.email,
+        email_recipients: settings.email_recipients.join("\n"),
+        max_email_recipients: MAX_EMAIL_RECIPIENTS,
+        webhook: settings.webhook,
+        webhook_url: company.webhooks.url.clone(),
+        chat: settings.chat,
+        chat_target: company
+            .chat_notifications
+            .target()
+            .map(|(channel, chat_id)| format!("{} {chat_id}", channel.as_str()))
+            .unwrap_or_default(),
+        overdue_alerts: settings.overdue_alerts,
+        budget_alerts: settings.budget_alerts,
+        digest: settings.digest.as_str().to_string(),
+        digest_hours: hour_options(Some(settings.digest_hour)),
+        weekdays: WEEKDAYS
+            .iter()
+            .enumerate()
+            .map(|(day, label)| SelectOption {
+                value: day.to_string(),
+                label: label.to_string(),
+                selected: day as u32 == settings.digest_weekday,
+            })
+            .collect(),
+        utc_offsets: UTC_OFFSETS
+            .iter()
+            .map(|value| SelectOption {
+                value: value.to_string(),
+                label: if value.is_empty() { "UTC" } else { value }.to_string(),
+                selected: *value == settings.utc_offset,
+            })
+            .collect(),
+        quiet_starts: hour_options(quiet.map(|quiet| quiet.start)),
+        quiet_ends: hour_options(quiet.map(|quiet| quiet.end)),
+        has_quiet_hours: quiet.is_some(),
+        errors,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NotificationSettingsForm {
+    email: Option<bool>,
+    #[serde(default)]
+    email_recipients: String,
+    webhook: Option<bool>,
+    chat: Option<bool>,
+    overdue_alerts: Option<bool>,
+    budget_alerts: Option<bool>,
+    #[serde(default)]
+    digest: String,
+    #[serde(default)]
+    digest_hour: String,
+    #[serde(default)]
+    digest_weekday: String,
+    #[serde(default)]
+    utc_offset: String,
+    #[serde(default)]
+    quiet_start: String,
+    #[serde(default)]
+    quiet_end: String,
+}
+
+fn parse_hour(value: &str) -> Option<u32> {
+    value.trim().parse().ok().filter(|hour| *hour < 24)
+}
+
+/// Addresses separated by commas, semicolons or new lines, lowercased and
+/// without repeats.
+fn parse_recipients(value: &str) -> Result<Vec<String>, &'static str> {
+    let mut recipients: Vec<String> = Vec::new();
+    for address in value
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+    {
+        let address = address.to_lowercase();
+        let valid = address
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'));
+        if !valid {
+            return Err("Revisa los correos: cada uno lleva usuario@dominio.");
+        }
+        if !recipients.contains(&address) {
+            recipients.push(address);
+        }
+    }
+    if recipients.len() > MAX_EMAIL_RECIPIENTS {
+        return Err("Los avisos por correo van a 10 direcciones como máximo.");
+    }
+    Ok(recipients)
+}
+
+/// Reads and checks the form against what the company has set up: the chat
+/// and the webhook can only be turned on once they exist.
+fn settings_from_form(
+    company: &Company,
+    form: &NotificationSettingsForm,
+) -> Result<NotificationSettings, &'static str> {
+    let email = form.email.unwrap_or(false);
+    let email_recipients = parse_recipients(&form.email_recipients)?;
+    if email && email_recipients.is_empty() {
+        return Err("Escribe al menos un correo para los avisos por correo.");
+    }
+    let webhook = form.webhook.unwrap_or(false);
+    if webhook && company.webhooks.url.is_empty() {
+        return Err("Configura primero la URL del webhook de la compañía.");
+    }
+    let chat = form.chat.unwrap_or(false);
+    if chat && company.chat_notifications.target().is_none() {
+        return Err("Configura primero el chat de la compañía.");
+    }
+    let digest =
+        DigestFrequency::parse(form.digest.trim()).ok_or("Elige cada cuánto va el resumen.")?;
+    let digest_hour = parse_hour(&form.digest_hour).ok_or("Elige la hora del resumen.")?;
+    let digest_weekday = form
+        .digest_weekday
+        .trim()
+        .parse()
+        .ok()
+        .filter(|day| *day < 7)
+        .unwrap_or(0);
+    if !UTC_OFFSETS.contains(&form.utc_offset.as_str()) {
+        return Err("Elige una zona horaria de la lista.");
+    }
+    let quiet_hours = match (form.quiet_start.trim(), form.quiet_end.trim()) {
+        ("", "") => None,
+        (start, end) => {
+            let (Some(start), Some(end)) = (parse_hour(start), parse_hour(end)) else {
+                return Err("Indica el inicio y el fin de las horas de silencio.");
+            };
+            if start == end {
+                return Err("Las horas de silencio empiezan y terminan a horas distintas.");
+            }
+            Some(QuietHours { start, end })
+        }
+    };
+    Ok(NotificationSettings {
+        email,
+        email_recipients,
+        webhook,
+        chat,
+        overdue_alerts: form.overdue_alerts.unwrap_or(false),
+        budget_alerts: form.budget_alerts.unwrap_or(false),
+        digest,
+        digest_hour,
+        digest_weekday,
+        utc_offset: form.utc_offset.clone(),
+        quiet_hours,
+    })
+}
+
+/// The form as sent, read leniently, to show it again next to its error.
+fn submitted_settings(form: &NotificationSettingsForm) -> NotificationSettings {
+    let defaults = NotificationSettings::default();
+    NotificationSettings {
+        email: form.email.unwrap_or(false),
+        email_recipients: form
+            .email_recipients
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        webhook: form.webhook.unwrap_or(false),
+        chat: form.chat.unwrap_or(false),
+        overdue_alerts: form.overdue_alerts.unwrap_or(false),
+        budget_alerts: form.budget_alerts.unwrap_or(false),
+        digest: DigestFrequency::parse(form.digest.trim()).unwrap_or_default(),
+        digest_hour: parse_hour(&form.digest_hour).unwrap_or(defaults.digest_hour),
+        digest_weekday: form.digest_weekday.trim().parse().unwrap_or(0),
+        utc_offset: form.utc_offset.clone(),
+        quiet_hours: match (parse_hour(&form.quiet_start), parse_hour(&form.quiet_end)) {
+            (Some(start), Some(end)) => Some(QuietHours { start, end }),
+            _ => None,
+        },
+    }
+}
+
+async fn active_company(
+    state: &AppState,
+    session_user: &SessionUser,
+) -> Result<Company, StatusCode> {
+    let company_id = require_admin_active(session_user)?;
+    get_company_by_id(state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The saved settings, or what the notification task follows until they are
+/// saved.
+pub async fn notification_settings_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let company = active_company(&state, &session_user).await?;
+    let mut settings = notification_settings(&company);
+    if company.notifications.is_none() {
+        // The chat-only fallback counts hours in UTC; a first save starts
+        // from the page's defaults instead.
+        settings.digest_hour = NotificationSettings::default().digest_hour;
+        settings.utc_offset = NotificationSettings::default().utc_offset;
+    }
+    render(settings_template(&company, &settings, None))
+}
+
+pub async fn notification_settings_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<NotificationSettingsForm>,
+) -> Response {
+    let company = match active_company(&state, &session_user).await {
+        Ok(company) => company,
+        Err(status) => return status.into_response(),
+    };
+    let settings = match settings_from_form(&company, &form) {
+        Ok(settings) => settings,
+        Err(message) => {
+            let shown = submitted_settings(&form);
+            return render(settings_template(
+                &company,
+                &shown,
+                Some(message.to_string()),
+            ))
+            .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+    let Some(company_id) = company.id else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Err(e) = update_company_notification_settings(&state, &company_id, &settings).await {
+        eprintln!("[notification settings] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Configuración de avisos guardada."),
+        Redirect::to("/admin/notifications/settings"),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipients_are_split_checked_and_deduplicated() {
+        assert_eq!(
+            parse_recipients("Ana@Acme.mx, ana@acme.mx;\nbeto@acme.mx"),
+            Ok(vec!["ana@acme.mx".to_string(), "beto@acme.mx".to_string()])
+        );
+        assert_eq!(parse_recipients("  "), Ok(Vec::new()));
+        assert!(parse_recipients("ana@acme").is_err());
+        assert!(parse_recipients("@acme.mx").is_err());
+        let many: Vec<String> = (0..11).map(|n| format!("u{n}@acme.mx")).collect();
+        assert!(parse_recipients(&many.join(",")).is_err());
+    }
+}
@@ -10,6 +10,7 @@ pub(crate) use axum::{
     http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
 };
+use axum::routing::{get, post};
 pub(crate) use bson::{DateTime, oid::ObjectId};
 pub(crate) use serde::{Deserialize, Serialize};
 
@@ -20,22 +21,151 @@ pub(crate) use crate::{
     },
     session::SessionUser,
     state::{
-        AppState, advance_project_concept_status, create_concept_status, create_project_concept,
-        create_resource_usage, delete_concept_status, delete_project_concept,
-        delete_resource_usage, get_concept_status_by_id_for_company, get_initial_concept_status,
-        get_project_by_id_for_company, get_project_concept_by_id_for_company,
-        get_resource_usage_by_id_for_company, list_active_project_concepts,
-        list_active_project_concepts_for_status, list_concept_statuses, list_project_concepts,
-        list_projects, list_resource_usage_allocations, list_resource_usages,
-        list_resource_usages_for_slot, list_resources, project_status_summary_by_quantity,
-        replace_hourly_resource_usage_grid, replace_resource_usage_allocations,
-        replace_resource_usage_allocations_equal, update_concept_status, update_project_concept,
-        update_resource_usage,
+        AppState, advance_project_concept_status, company_default_currency, create_concept_status,
+        create_project_concept, create_resource_usage, delete_concept_status,
+        delete_project_concept, delete_resource_usage, get_concept_status_by_id_for_company,
+        get_initial_concept_status, get_project_by_id_for_company,
+        get_project_concept_by_id_for_company, get_resource_usage_by_id_for_company,
+        list_active_project_concepts, list_active_project_concepts_for_status,
+        list_concept_statuses, list_project_concepts, list_projects,
+        list_resource_usage_allocations, list_resource_usages, list_resource_usages_for_slot,
+        list_resources, project_status_summary_by_quantity, replace_hourly_resource_usage_grid,
+        replace_resource_usage_allocations, replace_resource_usage_allocations_equal,
+        update_concept_status, update_project_concept, update_resource_usage,
     },
 };
 
+use crate::routes::Routes;
+
+#[allow(unused_imports)]
+use crate::filters;
+
 use super::finance::helpers::{SimpleOption, require_active_company, require_admin_active};
 
+/// Project detail, concepts, concept statuses and resource usages.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/projects/{id}", get(project_detail))
+        .route(
+            "/admin/projects/{project_id}/concepts/new",
+            get(project_concepts_new),
+        )
+        .route(
+            "/admin/projects/{project_id}/concepts",
+            post(project_concepts_create_form),
+        )
+        .route(
+            "/admin/project_concepts/{id}/edit",
+            get(project_concepts_edit),
+        )
+        .route(
+            "/admin/project_concepts/{id}/update",
+            post(project_concepts_update_form),
+        )
+        .route(
+            "/admin/project_concepts/{id}/advance",
+            post(project_concepts_advance_form),
+        )
+        .route(
+            "/admin/project_concepts/{id}/delete",
+            post(project_concepts_delete_form),
+        )
+        .route(
+            "/admin/concept_statuses",
+            get(concept_statuses_index).post(concept_statuses_create),
+        )
+        .route("/admin/concept_statuses/new", get(concept_statuses_new))
+        .route(
+            "/admin/concept_statuses/{id}/edit",
+            get(concept_statuses_edit),
+        )
+        .route(
+            "/admin/concept_statuses/{id}/update",
+            post(concept_statuses_update_form),
+        )
+        .route(
+            "/admin/concept_statuses/{id}/delete",
+            post(concept_statuses_delete_form),
+        )
+        .route(
+            "/admin/resource_usages",
+            get(resource_usages_index).post(resource_usages_save_grid),
+        )
+        .route(
+            "/admin/resource_usages/create",
+            post(resource_usages_create_form),
+        )
+        .route("/admin/resource_usages/new", get(resource_usages_new))
+        .route(
+            "/admin/resource_usages/{id}/edit",
+            get(resource_usages_edit),
+        )
+        .route(
+            "/admin/resource_usages/{id}/update",
+            post(resource_usages_update_form),
+        )
+        .route(
+            "/admin/resource_usages/{id}/delete",
+            post(resource_usages_delete_form),
+        )
+        .route(
+            "/api/admin/concept_statuses",
+            get(api_concept_statuses_index).post(api_concept_statuses_create),
+        )
+        .route(
+            "/api/admin/concept_statuses/{id}/update",
+            post(api_concept_statuses_update),
+        )
+        .route(
+            "/api/admin/concept_statuses/{id}/delete",
+            post(api_concept_statuses_delete),
+        )
+        .route(
+            "/api/admin/projects/{project_id}/concepts",
+            get(api_project_concepts_index).post(api_project_concepts_create),
+        )
+        .route(
+            "/api/admin/projects/{project_id}/status_summary",
+            get(api_project_status_summary),
+        )
+        .route(
+            "/api/admin/project_concepts/{id}/update",
+            post(api_project_concepts_update),
+        )
+        .route(
+            "/api/admin/project_concepts/{id}/advance",
+            post(api_project_concepts_advance),
+        )
+        .route(
+            "/api/admin/project_concepts/{id}/delete",
+            post(api_project_concepts_delete),
+        )
+        .route(
+            "/api/admin/resource_usages",
+            get(api_resource_usages_index).post(api_resource_usages_create),
+        )
+        .route(
+            "/api/admin/resource_usages/grid",
+            get(api_resource_usages_grid_view).post(api_resource_usages_grid_save),
+        )
+        .route(
+            "/api/admin/resource_usages/{id}",
+            get(api_resource_usage_detail),
+        )
+        .route(
+            "/api/admin/resource_usages/{id}/update",
+            post(api_resource_usages_update),
+        )
+        .route(
+            "/api/admin/resource_usages/{id}/delete",
+            post(api_resource_usages_delete),
+        )
+        .route(
+            "/api/admin/resource_usages/{id}/allocations",
+            get(api_resource_usage_allocations_index).post(api_resource_usage_allocations_replace),
+        )
+}
+
 pub(crate) fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -1211,6 +1341,7 @@ pub(crate) struct ProjectDetailTemplate {
     concepts: Vec<ProjectConceptRow>,
     can_edit: bool,
     can_view_money: bool,
+    currency: String,
 }
 
 pub(crate) struct ProjectSummaryRow {
@@ -1287,6 +1418,9 @@ pub async fn project_detail(
     let concepts = list_project_concepts(&state, &company_id, &project_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let currency = company_default_currency(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let summary = project_status_summary_by_quantity(&state, &company_id, &project_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -1332,6 +1466,7 @@ pub async fn project_detail(
         concepts: rows,
         can_edit,
         can_view_money,
+        currency,
     })
 }
 
@@ -1612,7 +1747,7 @@ pub async fn resource_usages_index(
 ) -> Result<Html<String>, StatusCode> {
     let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
     let requested_date = params.get("date").cloned().unwrap_or_else(|| today.clone());
-    let can_view_requested_date = session_user.is_admin()
+    let can_view_requested_date = session_user.can_view_admin()
         || session_user.has_permission(UserPermission::ViewResourceUsageHistory)
         || can_save_resource_usage_date(
             session_user.active_role(),
@@ -2107,7 +2242,7 @@ pub async fn api_resource_usages_grid_view(
 ) -> Result<Json<GridViewJson>, StatusCode> {
     let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
     let date = params.get("date").cloned().unwrap_or_else(|| today.clone());
-    let can_view = session_user.is_admin()
+    let can_view = session_user.can_view_admin()
         || session_user.has_permission(UserPermission::ViewResourceUsageHistory)
         || can_save_resource_usage_date(
             session_user.active_role(),
@@ -6,6 +6,7 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
@@ -14,10 +15,15 @@ use serde::{Deserialize, Serialize};
 use crate::filters;
 
 use crate::{
+    flash::Flash,
     models::{Project, ProjectPriority, UserPermission},
+    routes::{
+        Routes,
+        form_fields::{id_or_empty, optional_object_id},
+    },
     session::SessionUser,
     state::{
-        AppState, advance_project_phase, create_project, delete_project,
+        AppState, advance_project_phase, company_default_currency, create_project, delete_project,
         get_project_by_id_for_company, list_projects, update_project,
     },
 };
@@ -27,6 +33,28 @@ use super::finance::helpers::{
 };
 use super::finance::{category_options, contact_options};
 
+/// Projects, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/projects", get(projects_index).post(projects_create))
+        .route(
+            "/api/admin/projects",
+            get(projects_data_api).post(projects_create_api),
+        )
+        .route("/api/admin/projects/{id}", get(project_data_api))
+        .route("/api/admin/projects/{id}/update", post(project_update_api))
+        .route("/api/admin/projects/{id}/delete", post(project_delete_api))
+        .route(
+            "/api/admin/projects/{id}/advance",
+            post(project_advance_api),
+        )
+        .route("/admin/projects/new", get(projects_new))
+        .route("/admin/projects/{id}/edit", get(projects_edit))
+        .route("/admin/projects/{id}/update", post(projects_update))
+        .route("/admin/projects/{id}/delete", post(projects_delete))
+        .route("/admin/projects/{id}/advance", post(projects_advance))
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -39,6 +67,7 @@ struct ProjectsIndexTemplate {
     projects: Vec<ProjectRow>,
     can_edit: bool,
     can_view_money: bool,
+    currency: String,
 }
 
 struct ProjectRow {
@@ -77,8 +106,12 @@ pub struct ProjectData {
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct ProjectPayload {
     pub title: String,
-    pub contact_id: Option<String>,
-    pub category_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub category_id: Option<ObjectId>,
     pub description: Option<String>,
     #[serde(default = "default_priority")]
     pub priority: String,
@@ -116,6 +149,9 @@ pub async fn projects_index(
     let projects = list_projects(&state, &company_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let currency = company_default_currency(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let mut rows = Vec::new();
     for p in projects {
@@ -161,7 +197,7 @@ pub async fn projects_index(
                     let ms = d.timestamp_millis();
                     let secs = ms / 1000;
                     let dt = chrono::DateTime::from_timestamp(secs, 0).unwrap_or_default();
-                    dt.format("%d/%m/%Y").to_string()
+                    dt.format("%Y-%m-%d").to_string()
                 })
                 .unwrap_or_default(),
             completed_at: p
@@ -170,7 +206,7 @@ pub async fn projects_index(
                     let ms = d.timestamp_millis();
                     let secs = ms / 1000;
                     let dt = chrono::DateTime::from_timestamp(secs, 0).unwrap_or_default();
-                    dt.format("%d/%m/%Y").to_string()
+                    dt.format("%Y-%m-%d").to_string()
                 })
                 .unwrap_or_default(),
         });
@@ -180,6 +216,7 @@ pub async fn projects_index(
         projects: rows,
         can_edit,
         can_view_money,
+        currency,
     })
 }
 
@@ -300,8 +337,10 @@ fn parse_priority_strict(s: &str) -> Result<ProjectPriority, StatusCode> {
 #[derive(Debug, Deserialize)]
 pub struct ProjectForm {
     pub title: String,
-    pub contact_id: String,
-    pub category_id: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    pub contact_id: Option<ObjectId>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    pub category_id: Option<ObjectId>,
     pub description: String,
     pub priority: String,
     pub total_budget: String,
@@ -364,8 +403,8 @@ pub async fn projects_create(
             id: String::new(),
             title: form.title,
             description: form.description,
-            contact_id: form.contact_id,
-            category_id: form.category_id,
+            contact_id: id_or_empty(form.contact_id),
+            category_id: id_or_empty(form.category_id),
             priority: form.priority,
             total_budget: form.total_budget,
             scheduled_at: form.scheduled_at,
@@ -379,16 +418,8 @@ pub async fn projects_create(
         .into_response();
     }
 
-    let contact_id = if form.contact_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.contact_id).ok()
-    };
-    let category_id = if form.category_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.category_id).ok()
-    };
+    let contact_id = form.contact_id;
+    let category_id = form.category_id;
     let total_budget = form.total_budget.parse().ok();
     let scheduled_at = parse_datetime(&form.scheduled_at);
     let priority = parse_priority(&form.priority);
@@ -417,7 +448,11 @@ pub async fn projects_create(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/projects").into_response(),
+        Ok(_) => (
+            Flash::success("Proyecto creado."),
+            Redirect::to("/admin/projects"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -552,8 +587,8 @@ pub async fn projects_update(
             id: id.clone(),
             title: form.title,
             description: form.description,
-            contact_id: form.contact_id,
-            category_id: form.category_id,
+            contact_id: id_or_empty(form.contact_id),
+            category_id: id_or_empty(form.category_id),
             priority: form.priority,
             total_budget: form.total_budget,
             scheduled_at: form.scheduled_at,
@@ -567,16 +602,8 @@ pub async fn projects_update(
         .into_response();
     }
 
-    let contact_id = if form.contact_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.contact_id).ok()
-    };
-    let category_id = if form.category_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.category_id).ok()
-    };
+    let contact_id = form.contact_id;
+    let category_id = form.category_id;
     let total_budget = form.total_budget.parse().ok();
     let scheduled_at = parse_datetime(&form.scheduled_at);
     let priority = parse_priority(&form.priority);
@@ -606,7 +633,11 @@ pub async fn projects_update(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/projects").into_response(),
+        Ok(_) => (
+            Flash::success("Proyecto actualizado."),
+            Redirect::to("/admin/projects"),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -682,7 +713,11 @@ pub async fn projects_delete(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/projects").into_response())
+    Ok((
+        Flash::success("Proyecto eliminado."),
+        Redirect::to("/admin/projects"),
+    )
+        .into_response())
 }
 
 #[utoipa::path(
@@ -729,7 +764,11 @@ pub async fn projects_advance(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/projects").into_response())
+    Ok((
+        Flash::success("Proyecto avanzado a la siguiente etapa."),
+        Redirect::to("/admin/projects"),
+    )
+        .into_response())
 }
 
 #[utoipa::path(
@@ -815,8 +854,8 @@ async fn parse_project_payload(
     if title.is_empty() || payload.total_budget.is_some_and(|amount| amount < 0.0) {
         return Err(StatusCode::BAD_REQUEST);
     }
-    let contact_id = parse_optional_object_id(payload.contact_id)?;
-    let category_id = parse_optional_object_id(payload.category_id)?;
+    let contact_id = payload.contact_id;
+    let category_id = payload.category_id;
     validate_project_refs(state, company_id, category_id.as_ref(), contact_id.as_ref()).await?;
     let scheduled_at = match clean_opt(payload.scheduled_at) {
         Some(value) => {
@@ -837,15 +876,6 @@ async fn parse_project_payload(
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, StatusCode> {
-    match clean_opt(value) {
-        Some(value) => ObjectId::parse_str(&value)
-            .map(Some)
-            .map_err(|_| StatusCode::BAD_REQUEST),
-        None => Ok(None),
-    }
-}
-
 fn clean_opt(input: Option<String>) -> Option<String> {
     input.and_then(|value| {
         let trimmed = value.trim().to_string();
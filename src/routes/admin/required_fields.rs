@@ -0,0 +1,406 @@
+// Company required-field policy: the optional fields of recurring plans and
+// transactions the company's bookkeeping needs, each for every record or
+// only incomes or expenses. The forms, the API and the plan import reject
+// records that leave them empty.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form, Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::{
+        RequiredField, RequiredFieldPolicy, RequiredFieldRule, RequiredFieldScope,
+        RequiredFieldTarget,
+    },
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, check_required_field_policy, get_company_by_id, update_company_required_fields,
+    },
+};
+
+/// Required-field policy of a company.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/required_fields",
+            get(company_required_fields_edit).post(company_required_fields_update),
+        )
+        .route(
+            "/api/admin/companies/{id}/required_fields",
+            get(company_required_fields_api).post(company_required_fields_update_api),
+        )
+}
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.can_view_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RequiredFieldRuleData {
+    /// `contact`, `notes` or `end_date` for plans; `notes` or
+    /// `planned_entry` for transactions.
+    pub field: String,
+    /// `all` (the default), `income` or `expense`.
+    #[serde(default = "default_applies_to")]
+    pub applies_to: String,
+}
+
+fn default_applies_to() -> String {
+    RequiredFieldScope::All.as_str().to_string()
+}
+
+/// The policy as the API takes and returns it; fields without a rule are
+/// optional.
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RequiredFieldsData {
+    #[serde(default)]
+    pub plans: Vec<RequiredFieldRuleData>,
+    #[serde(default)]
+    pub transactions: Vec<RequiredFieldRuleData>,
+}
+
+fn policy_data(policy: &RequiredFieldPolicy) -> RequiredFieldsData {
+    let rules = |target| {
+        policy
+            .rules(target)
+            .iter()
+            .map(|rule| RequiredFieldRuleData {
+                field: rule.field.as_str().to_string(),
+                applies_to: rule.applies_to.as_str().to_string(),
+            })
+            .collect()
+    };
+    RequiredFieldsData {
+        plans: rules(RequiredFieldTarget::Plans),
+        transactions: rules(RequiredFieldTarget::Transactions),
+    }
+}
+
+fn parse_rules(rules: &[RequiredFieldRuleData]) -> Result<Vec<RequiredFieldRule>, String> {
+    rules
+        .iter()
+        .map(|rule| {
+            let field = RequiredField::parse(rule.field.trim())
+                .ok_or_else(|| format!("Campo desconocido: {}", rule.field))?;
+            let applies_to = RequiredFieldScope::parse(rule.applies_to.trim())
+                .ok_or_else(|| format!("{} debe aplicar a all, income o expense", rule.field))?;
+            Ok(RequiredFieldRule { field, applies_to })
+        })
+        .collect()
+}
+
+fn parse_policy(data: &RequiredFieldsData) -> Result<RequiredFieldPolicy, String> {
+    let policy = RequiredFieldPolicy {
+        plans: parse_rules(&data.plans)?,
+        transactions: parse_rules(&data.transactions)?,
+    };
+    check_required_field_policy(&policy)?;
+    Ok(policy)
+}
+
+/// Form field of a target's field, e.g. `plans.contact`; empty means
+/// optional.
+fn form_key(target: RequiredFieldTarget, field: RequiredField) -> String {
+    format!("{}.{}", target.as_str(), field.as_str())
+}
+
+fn parse_form(form: &HashMap<String, String>) -> Result<RequiredFieldPolicy, String> {
+    let mut data = RequiredFieldsData {
+        plans: Vec::new(),
+        transactions: Vec::new(),
+    };
+    for target in RequiredFieldTarget::ALL {
+        for field in target.fields() {
+            let value = form
+                .get(&form_key(target, *field))
+                .map(|value| value.trim())
+                .unwrap_or_default();
+            if value.is_empty() {
+                continue;
+            }
+            let rule = RequiredFieldRuleData {
+                field: field.as_str().to_string(),
+                applies_to: value.to_string(),
+            };
+            match target {
+                RequiredFieldTarget::Plans => data.plans.push(rule),
+                RequiredFieldTarget::Transactions => data.transactions.push(rule),
+            }
+        }
+    }
+    parse_policy(&data)
+}
+
+struct ChoiceOption {
+    value: &'static str,
+    label: &'static str,
+    selected: bool,
+}
+
+struct FieldRow {
+    name: String,
+    label: &'static str,
+    options: Vec<ChoiceOption>,
+}
+
+struct TargetSection {
+    title: &'static str,
+    rows: Vec<FieldRow>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/required_fields.html")]
+struct RequiredFieldsTemplate {
+    company_id: String,
+    company_name: String,
+    sections: Vec<TargetSection>,
+    errors: Option<String>,
+}
+
+fn policy_template(
+    company_id: &str,
+    company_name: String,
+    policy: &RequiredFieldPolicy,
+    errors: Option<String>,
+) -> RequiredFieldsTemplate {
+    let sections =
+        RequiredFieldTarget::ALL
+            .into_iter()
+            .map(|target| TargetSection {
+                title: match target {
+                    RequiredFieldTarget::Plans => "Planes recurrentes",
+                    RequiredFieldTarget::Transactions => "Movimientos",
+                },
+                rows: target
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        let current = policy.rule(target, *field).map(|rule| rule.applies_to);
+                        let mut options = vec![ChoiceOption {
+                            value: "",
+                            label: "Opcional",
+                            selected: current.is_none(),
+                        }];
+                        options.extend(RequiredFieldScope::ALL.into_iter().map(|scope| {
+                            ChoiceOption {
+                                value: scope.as_str(),
+                                label: scope.label(),
+                                selected: current == Some(scope),
+                            }
+                        }));
+                        FieldRow {
+                            name: form_key(target, *field),
+                            label: field.label(),
+                            options,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+    RequiredFieldsTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        sections,
+        errors,
+    }
+}
+
+pub async fn company_required_fields_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(policy_template(
+        &company_id,
+        company.name,
+        &company.required_fields,
+        None,
+    ))
+}
+
+pub async fn company_required_fields_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&company_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let company = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let policy = match parse_form(&form) {
+        Ok(policy) => policy,
+        Err(message) => {
+            return render(policy_template(
+                &company_id,
+                company.name,
+                &company.required_fields,
+                Some(message),
+            ))
+            .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+    if let Err(e) = update_company_required_fields(&state, &object_id, &policy).await {
+        eprintln!("[required fields] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Campos obligatorios guardados."),
+        Redirect::to(&format!("/admin/companies/{company_id}/required_fields")),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/companies/{id}/required_fields",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    responses(
+        (status = 200, description = "Fields the company's plans and transactions must fill", body = RequiredFieldsData),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_required_fields_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<RequiredFieldsData>, StatusCode> {
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(policy_data(&company.required_fields)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/companies/{id}/required_fields",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    request_body = RequiredFieldsData,
+    responses(
+        (status = 200, description = "Policy replaced; returns it", body = RequiredFieldsData),
+        (status = 400, description = "Unknown field or scope, a field the target does not take, or two rules for one field"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_required_fields_update_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<RequiredFieldsData>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let policy = match parse_policy(&payload) {
+        Ok(policy) => policy,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+        }
+    };
+    match get_company_by_id(&state, &object_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+    match update_company_required_fields(&state, &object_id, &policy).await {
+        Ok(()) => Json(policy_data(&policy)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_selects_become_rules() {
+        let form: HashMap<String, String> = [
+            ("plans.contact", "expense"),
+            ("plans.notes", ""),
+            ("transactions.notes", "all"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let policy = parse_form(&form).unwrap();
+        assert_eq!(
+            policy.rule(RequiredFieldTarget::Plans, RequiredField::Contact),
+            Some(RequiredFieldRule {
+                field: RequiredField::Contact,
+                applies_to: RequiredFieldScope::Expense,
+            })
+        );
+        assert_eq!(policy.plans.len(), 1);
+        assert_eq!(policy.transactions.len(), 1);
+
+        let bad: HashMap<String, String> = [("plans.contact".to_string(), "siempre".to_string())]
+            .into_iter()
+            .collect();
+        assert!(parse_form(&bad).is_err());
+    }
+}
@@ -6,6 +6,7 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use chrono::Utc;
@@ -15,7 +16,9 @@ use serde::{Deserialize, Serialize};
 use crate::filters;
 
 use crate::{
+    flash::Flash,
     models::ResourceLog,
+    routes::{Routes, form_fields::optional_object_id},
     session::SessionUser,
     state::{
         AppState, create_resource_log, delete_resource_log, end_resource_log, get_project_by_id,
@@ -28,6 +31,43 @@ use crate::{
 
 use super::finance::helpers::{SimpleOption, ensure_same_company, require_admin_active};
 
+/// Resource logs, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/resource_logs",
+            get(resource_logs_index).post(resource_logs_create),
+        )
+        .route(
+            "/api/admin/resource_logs",
+            get(resource_logs_data_api).post(resource_logs_create_api),
+        )
+        .route("/api/admin/resource_logs/{id}", get(resource_log_data_api))
+        .route(
+            "/api/admin/resource_logs/{id}/update",
+            post(resource_log_update_api),
+        )
+        .route(
+            "/api/admin/resource_logs/{id}/delete",
+            post(resource_log_delete_api),
+        )
+        .route(
+            "/api/admin/resource_logs/{id}/end",
+            post(resource_log_end_api),
+        )
+        .route("/admin/resource_logs/new", get(resource_logs_new))
+        .route("/admin/resource_logs/{id}/edit", get(resource_logs_edit))
+        .route(
+            "/admin/resource_logs/{id}/update",
+            post(resource_logs_update),
+        )
+        .route(
+            "/admin/resource_logs/{id}/delete",
+            post(resource_logs_delete),
+        )
+        .route("/admin/resource_logs/{id}/end", post(resource_logs_end))
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -89,9 +129,13 @@ pub struct ResourceLogData {
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct ResourceLogPayload {
-    pub project_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub project_id: Option<ObjectId>,
     pub phase: Option<String>,
-    pub resource_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub resource_id: Option<ObjectId>,
     pub started_at: String,
     pub ended_at: Option<String>,
     pub operator_name: Option<String>,
@@ -250,9 +294,11 @@ struct ResourceLogFormTemplate {
 
 #[derive(Debug, Deserialize)]
 pub struct ResourceLogForm {
-    pub project_id: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    pub project_id: Option<ObjectId>,
     pub phase: String,
-    pub resource_id: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    pub resource_id: Option<ObjectId>,
     pub started_at: String,
     pub ended_at: String,
     pub operator_name: String,
@@ -314,16 +360,8 @@ pub async fn resource_logs_create(
 ) -> Result<impl IntoResponse, StatusCode> {
     let company_id = require_admin_active(&session_user)?;
 
-    let project_id = if form.project_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.project_id).ok()
-    };
-    let resource_id = if form.resource_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.resource_id).ok()
-    };
+    let project_id = form.project_id;
+    let resource_id = form.resource_id;
     let started_at = parse_datetime(&form.started_at).map_err(|_| StatusCode::BAD_REQUEST)?;
 
     let resource_name = if let Some(ref rid) = resource_id {
@@ -366,7 +404,10 @@ pub async fn resource_logs_create(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/resource_logs"))
+    Ok((
+        Flash::success("Registro creado."),
+        Redirect::to("/admin/resource_logs"),
+    ))
 }
 
 #[utoipa::path(
@@ -507,16 +548,8 @@ pub async fn resource_logs_update(
     let company_id = require_admin_active(&session_user)?;
     let oid = ObjectId::parse_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
 
-    let project_id = if form.project_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.project_id).ok()
-    };
-    let resource_id = if form.resource_id.is_empty() {
-        None
-    } else {
-        ObjectId::parse_str(&form.resource_id).ok()
-    };
+    let project_id = form.project_id;
+    let resource_id = form.resource_id;
     let started_at = parse_datetime(&form.started_at).map_err(|_| StatusCode::BAD_REQUEST)?;
     let ended_at = if form.ended_at.is_empty() {
         None
@@ -566,7 +599,10 @@ pub async fn resource_logs_update(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/resource_logs"))
+    Ok((
+        Flash::success("Registro actualizado."),
+        Redirect::to("/admin/resource_logs"),
+    ))
 }
 
 #[utoipa::path(
@@ -642,7 +678,10 @@ pub async fn resource_logs_end(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/resource_logs"))
+    Ok((
+        Flash::success("Registro terminado."),
+        Redirect::to("/admin/resource_logs"),
+    ))
 }
 
 #[utoipa::path(
@@ -697,7 +736,10 @@ pub async fn resource_logs_delete(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/admin/resource_logs"))
+    Ok((
+        Flash::success("Registro eliminado."),
+        Redirect::to("/admin/resource_logs"),
+    ))
 }
 
 #[utoipa::path(
@@ -737,8 +779,8 @@ async fn parse_resource_log_payload(
     company_id: &ObjectId,
     payload: ResourceLogPayload,
 ) -> Result<ParsedResourceLogPayload, StatusCode> {
-    let project_id = parse_optional_object_id(payload.project_id)?;
-    let resource_id = parse_optional_object_id(payload.resource_id)?;
+    let project_id = payload.project_id;
+    let resource_id = payload.resource_id;
     if let Some(project_id) = &project_id {
         let project = get_project_by_id(state, project_id)
             .await
@@ -777,15 +819,6 @@ async fn parse_resource_log_payload(
     })
 }
 
-fn parse_optional_object_id(value: Option<String>) -> Result<Option<ObjectId>, StatusCode> {
-    match clean_opt(value) {
-        Some(value) => ObjectId::parse_str(&value)
-            .map(Some)
-            .map_err(|_| StatusCode::BAD_REQUEST),
-        None => Ok(None),
-    }
-}
-
 fn clean_opt(input: Option<String>) -> Option<String> {
     input.and_then(|value| {
         let trimmed = value.trim().to_string();
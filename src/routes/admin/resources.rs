@@ -6,6 +6,7 @@ use axum::{
     extract::{Form, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -14,7 +15,9 @@ use serde::{Deserialize, Deserializer, Serialize};
 use crate::filters;
 
 use crate::{
+    flash::Flash,
     models::{Resource, ResourceType},
+    routes::Routes,
     session::SessionUser,
     state::{
         AppState, create_resource_with_cost, delete_resource, get_resource_by_id,
@@ -25,6 +28,32 @@ use crate::{
 
 use super::finance::helpers::*;
 
+/// Resources, as pages and JSON.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/resources",
+            get(resources_index).post(resources_create),
+        )
+        .route(
+            "/api/admin/resources",
+            get(resources_data_api).post(resources_create_api),
+        )
+        .route("/api/admin/resources/{id}", get(resource_data_api))
+        .route(
+            "/api/admin/resources/{id}/update",
+            post(resource_update_api),
+        )
+        .route(
+            "/api/admin/resources/{id}/delete",
+            post(resource_delete_api),
+        )
+        .route("/admin/resources/new", get(resources_new))
+        .route("/admin/resources/{id}/edit", get(resources_edit))
+        .route("/admin/resources/{id}/update", post(resources_update))
+        .route("/admin/resources/{id}/delete", post(resources_delete))
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -424,7 +453,11 @@ pub async fn resources_create(
         Ok(id) => {
             let allowed = parse_status_ids(form.allowed_status_ids);
             match update_resource_allowed_statuses(&state, &id, &company_id, allowed).await {
-                Ok(_) => Redirect::to("/admin/resources").into_response(),
+                Ok(_) => (
+                    Flash::success("Recurso creado."),
+                    Redirect::to("/admin/resources"),
+                )
+                    .into_response(),
                 Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             }
         }
@@ -596,7 +629,11 @@ pub async fn resources_update(
                 return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
             match update_resource_allowed_statuses(&state, &oid, &company_id, allowed).await {
-                Ok(_) => Redirect::to("/admin/resources").into_response(),
+                Ok(_) => (
+                    Flash::success("Recurso actualizado."),
+                    Redirect::to("/admin/resources"),
+                )
+                    .into_response(),
                 Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             }
         }
@@ -740,8 +777,16 @@ pub async fn resources_delete(
     };
 
     match delete_resource(&state, &oid, &company_id).await {
-        Ok(_) => Redirect::to("/admin/resources").into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Recurso eliminado."),
+            Redirect::to("/admin/resources"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el recurso."),
+            Redirect::to("/admin/resources"),
+        )
+            .into_response(),
     }
 }
 
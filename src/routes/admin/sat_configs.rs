@@ -6,6 +6,7 @@ use axum::{
     extract::{Multipart, Path, State},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
@@ -15,18 +16,69 @@ use tokio::fs;
 use crate::filters;
 
 use crate::{
+    flash::Flash,
     models::SatConfig,
+    routes::Routes,
     session::SessionUser,
     state::{
         AppState, create_sat_config, delete_sat_config, get_company_by_id,
-        get_sat_config_for_company, list_sat_configs,
+        get_sat_config_for_company, list_sat_configs, quota_exceeded, release_storage,
+        reserve_storage, stored_file_bytes,
     },
+    uploads::BodyLimits,
+    uploads::is_der_encoded,
 };
 
 use super::finance::helpers::require_admin_active;
 
+/// SAT configurations of a company. The certificate uploads get the larger body limit.
+pub fn router(limits: &BodyLimits) -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/sat_configs",
+            post(sat_configs_create).layer(limits.upload_layer()),
+        )
+        .route(
+            "/admin/companies/{id}/sat_configs/new",
+            get(sat_configs_new),
+        )
+        .route(
+            "/admin/companies/{id}/sat_configs/{config_id}/delete",
+            post(sat_configs_delete),
+        )
+        .route(
+            "/api/admin/sat-configs",
+            get(sat_configs_data_api).post(sat_config_create_api),
+        )
+        .route(
+            "/api/admin/sat-configs/upload",
+            post(sat_config_upload_api).layer(limits.upload_layer()),
+        )
+        .route("/api/admin/sat-configs/{id}", get(sat_config_data_api))
+        .route(
+            "/api/admin/sat-configs/{id}/update",
+            post(sat_config_update_api),
+        )
+        .route(
+            "/api/admin/sat-configs/{id}/delete",
+            post(sat_config_delete_api),
+        )
+}
+
 const MAX_SAT_FILE_BYTES: usize = 2 * 1024 * 1024;
 
+/// The FIEL certificate and key the SAT issues are binary DER files; PEM or
+/// anything else would only fail later, when signing.
+fn sat_files_are_der(
+    cer_bytes: &Option<(String, Vec<u8>)>,
+    key_bytes: &Option<(String, Vec<u8>)>,
+) -> bool {
+    [cer_bytes, key_bytes]
+        .into_iter()
+        .flatten()
+        .all(|(_, data)| is_der_encoded(data))
+}
+
 fn require_company_admin(
     session_user: &SessionUser,
     company_id: &ObjectId,
@@ -44,7 +96,7 @@ fn require_company_admin(
     }
 }
 
-fn safe_upload_filename(filename: Option<&str>, fallback: &str) -> String {
+pub(crate) fn safe_upload_filename(filename: Option<&str>, fallback: &str) -> String {
     let name = filename
         .and_then(|raw| raw.rsplit(['/', '\\']).next())
         .unwrap_or(fallback)
@@ -207,7 +259,8 @@ pub async fn sat_config_create_api(
         (status = 201, description = "SAT config created from multipart/form-data file upload (.cer + .key)"),
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Forbidden"),
-        (status = 400, description = "Invalid input")
+        (status = 400, description = "Invalid input"),
+        (status = 507, description = "The company's storage quota has no room for the files")
     ),
     security(("session" = []))
 )]
@@ -245,7 +298,10 @@ pub async fn sat_config_upload_api(
             }
             "cer_file" => {
                 let filename = safe_upload_filename(field.file_name(), "cert.cer");
-                let data = field.bytes().await.unwrap_or_default().to_vec();
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
                 if data.len() > MAX_SAT_FILE_BYTES {
                     return StatusCode::PAYLOAD_TOO_LARGE.into_response();
                 }
@@ -255,7 +311,10 @@ pub async fn sat_config_upload_api(
             }
             "key_file" => {
                 let filename = safe_upload_filename(field.file_name(), "private.key");
-                let data = field.bytes().await.unwrap_or_default().to_vec();
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
                 if data.len() > MAX_SAT_FILE_BYTES {
                     return StatusCode::PAYLOAD_TOO_LARGE.into_response();
                 }
@@ -276,26 +335,43 @@ pub async fn sat_config_upload_api(
         )
             .into_response();
     }
+    if !sat_files_are_der(&cer_bytes, &key_bytes) {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Json(serde_json::json!({
+                "error": "cer_file and key_file must be DER-encoded"
+            })),
+        )
+            .into_response();
+    }
+
+    let (cer_filename, cer_data) = cer_bytes.unwrap();
+    let (key_filename, key_data) = key_bytes.unwrap();
+    let size = (cer_data.len() + key_data.len()) as u64;
+    if let Err(err) = reserve_storage(&state, &company_id, size).await {
+        return match quota_exceeded(&err) {
+            Some(exceeded) => (
+                StatusCode::INSUFFICIENT_STORAGE,
+                Json(serde_json::json!({ "error": exceeded.to_string() })),
+            )
+                .into_response(),
+            None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+    }
 
     let config_id = ObjectId::new();
     let upload_dir = PathBuf::from("uploads")
         .join("sat")
         .join(company_id.to_hex())
         .join(config_id.to_hex());
-
-    if fs::create_dir_all(&upload_dir).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
-
-    let (cer_filename, cer_data) = cer_bytes.unwrap();
-    let (key_filename, key_data) = key_bytes.unwrap();
     let cer_path = upload_dir.join(&cer_filename);
     let key_path = upload_dir.join(&key_filename);
 
-    if fs::write(&cer_path, &cer_data).await.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
-    if fs::write(&key_path, &key_data).await.is_err() {
+    if fs::create_dir_all(&upload_dir).await.is_err()
+        || fs::write(&cer_path, &cer_data).await.is_err()
+        || fs::write(&key_path, &key_data).await.is_err()
+    {
+        let _ = release_storage(&state, &company_id, size).await;
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
@@ -316,7 +392,10 @@ pub async fn sat_config_upload_api(
             Json(serde_json::json!({ "id": id.to_hex() })),
         )
             .into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(_) => {
+            let _ = release_storage(&state, &company_id, size).await;
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
     }
 }
 
@@ -499,7 +578,10 @@ pub async fn sat_configs_create(
             }
             "cer_file" => {
                 let filename = safe_upload_filename(field.file_name(), "cert.cer");
-                let data = field.bytes().await.unwrap_or_default().to_vec();
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
                 if data.len() > MAX_SAT_FILE_BYTES {
                     return StatusCode::PAYLOAD_TOO_LARGE.into_response();
                 }
@@ -509,7 +591,10 @@ pub async fn sat_configs_create(
             }
             "key_file" => {
                 let filename = safe_upload_filename(field.file_name(), "private.key");
-                let data = field.bytes().await.unwrap_or_default().to_vec();
+                let data = match field.bytes().await {
+                    Ok(data) => data.to_vec(),
+                    Err(err) => return err.status().into_response(),
+                };
                 if data.len() > MAX_SAT_FILE_BYTES {
                     return StatusCode::PAYLOAD_TOO_LARGE.into_response();
                 }
@@ -534,6 +619,39 @@ pub async fn sat_configs_create(
         .map(IntoResponse::into_response)
         .unwrap_or_else(|s| s.into_response());
     }
+    if !sat_files_are_der(&cer_bytes, &key_bytes) {
+        let company = get_company_by_id(&state, &company_object_id)
+            .await
+            .ok()
+            .flatten();
+        return render(SatConfigFormTemplate {
+            company_id: company_id.clone(),
+            company_name: company.map(|c| c.name).unwrap_or_default(),
+            errors: Some("Los archivos .cer y .key deben estar en formato DER (binario).".into()),
+        })
+        .map(|html| (StatusCode::UNSUPPORTED_MEDIA_TYPE, html).into_response())
+        .unwrap_or_else(|s| s.into_response());
+    }
+
+    let (cer_filename, cer_data) = cer_bytes.unwrap();
+    let (key_filename, key_data) = key_bytes.unwrap();
+    let size = (cer_data.len() + key_data.len()) as u64;
+    if let Err(err) = reserve_storage(&state, &company_object_id, size).await {
+        let Some(exceeded) = quota_exceeded(&err) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        let company = get_company_by_id(&state, &company_object_id)
+            .await
+            .ok()
+            .flatten();
+        return render(SatConfigFormTemplate {
+            company_id: company_id.clone(),
+            company_name: company.map(|c| c.name).unwrap_or_default(),
+            errors: Some(exceeded.to_string()),
+        })
+        .map(|html| (StatusCode::INSUFFICIENT_STORAGE, html).into_response())
+        .unwrap_or_else(|s| s.into_response());
+    }
 
     let config_id = ObjectId::new();
     let upload_dir = PathBuf::from("uploads")
@@ -543,21 +661,21 @@ pub async fn sat_configs_create(
 
     if let Err(e) = fs::create_dir_all(&upload_dir).await {
         eprintln!("[sat_configs] failed to create upload dir: {e}");
+        let _ = release_storage(&state, &company_object_id, size).await;
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
-    let (cer_filename, cer_data) = cer_bytes.unwrap();
-    let (key_filename, key_data) = key_bytes.unwrap();
-
     let cer_path = upload_dir.join(&cer_filename);
     let key_path = upload_dir.join(&key_filename);
 
     if let Err(e) = fs::write(&cer_path, &cer_data).await {
         eprintln!("[sat_configs] failed to write cer: {e}");
+        let _ = release_storage(&state, &company_object_id, size).await;
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
     if let Err(e) = fs::write(&key_path, &key_data).await {
         eprintln!("[sat_configs] failed to write key: {e}");
+        let _ = release_storage(&state, &company_object_id, size).await;
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
@@ -573,9 +691,14 @@ pub async fn sat_configs_create(
     )
     .await
     {
-        Ok(_) => Redirect::to(&format!("/admin/companies/{}/edit", company_id)).into_response(),
+        Ok(_) => (
+            Flash::success("Configuración SAT guardada."),
+            Redirect::to(&format!("/admin/companies/{}/edit", company_id)),
+        )
+            .into_response(),
         Err(e) => {
             eprintln!("[sat_configs] db insert error: {e}");
+            let _ = release_storage(&state, &company_object_id, size).await;
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
     }
@@ -611,8 +734,11 @@ pub async fn sat_configs_delete(
     };
 
     {
+        let size =
+            stored_file_bytes(&config.cer_path).await + stored_file_bytes(&config.key_path).await;
         let _ = fs::remove_file(&config.cer_path).await;
         let _ = fs::remove_file(&config.key_path).await;
+        let _ = release_storage(&state, &company_object_id, size).await;
         // Remove directory if empty
         if let Some(parent) = std::path::Path::new(&config.cer_path).parent() {
             let _ = fs::remove_dir(parent).await;
@@ -620,8 +746,16 @@ pub async fn sat_configs_delete(
     }
 
     match delete_sat_config(&state, &config_object_id).await {
-        Ok(_) => Redirect::to(&format!("/admin/companies/{}/edit", company_id)).into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Ok(_) => (
+            Flash::success("Configuración SAT eliminada."),
+            Redirect::to(&format!("/admin/companies/{}/edit", company_id)),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar la configuración SAT."),
+            Redirect::to(&format!("/admin/companies/{}/edit", company_id)),
+        )
+            .into_response(),
     }
 }
 
@@ -0,0 +1,227 @@
+// Security page: sessions, failed logins and admin actions of the company over
+// a period, with the anomalies found in them and a CSV export of the log. It
+// also sets whether the company's staff may sign in with an emailed link.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::Deserialize;
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    flash::Flash,
+    models::{AccessEvent, LoginLinkMode},
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AccessAnomaly, AccessDay, AppState, access_anomalies, get_company_by_id,
+        list_access_events, summarize_access_by_day, update_company_login_links, user_labels,
+    },
+};
+
+use super::finance::helpers::require_admin_active;
+
+/// The access log and the sign-in link setting.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/security", get(security_index))
+        .route("/admin/security/login-links", post(security_login_links))
+}
+
+/// Periods offered on the page, in days.
+const PERIODS: [u64; 4] = [7, 30, 90, 365];
+const DEFAULT_PERIOD: u64 = 30;
+/// Events listed on the page; the CSV export has all of them.
+const RECENT_EVENTS: usize = 100;
+/// Sign-in link modes offered on the page, with their labels.
+const LOGIN_LINK_MODES: [(LoginLinkMode, &str); 3] = [
+    (LoginLinkMode::Off, "Solo código TOTP"),
+    (LoginLinkMode::Supplement, "Código TOTP o enlace por correo"),
+    (LoginLinkMode::Alternative, "Solo enlace por correo"),
+];
+
+#[derive(Deserialize)]
+pub struct SecurityQuery {
+    #[serde(default)]
+    days: Option<u64>,
+    /// `csv` downloads the log instead of rendering the page.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+struct EventRow {
+    at: String,
+    kind: &'static str,
+    username: String,
+    /// Current display name of the user, when it is not the username.
+    display_name: Option<String>,
+    ip: String,
+    detail: String,
+}
+
+impl EventRow {
+    /// `labels` maps user ids to their current display label.
+    fn new(event: &AccessEvent, labels: &HashMap<ObjectId, String>) -> Self {
+        EventRow {
+            at: event
+                .created_at
+                .to_chrono()
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            kind: event.kind.label(),
+            username: event.username.clone(),
+            display_name: event
+                .user_id
+                .and_then(|id| labels.get(&id))
+                .filter(|label| **label != event.username)
+                .cloned(),
+            ip: event.ip.clone().unwrap_or_default(),
+            detail: event.detail.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/security/index.html")]
+struct SecurityTemplate {
+    days: u64,
+    periods: [u64; 4],
+    totals: AccessDay,
+    summary: Vec<AccessDay>,
+    anomalies: Vec<AccessAnomaly>,
+    events: Vec<EventRow>,
+    total_events: usize,
+    login_links: &'static str,
+    login_link_modes: Vec<(&'static str, &'static str)>,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn access_csv(events: &[AccessEvent]) -> String {
+    let mut out = String::from("fecha_utc,tipo,usuario,ip,detalle\n");
+    for event in events {
+        out.push_str(&format!(
+            "{},{},{},{},{}\n",
+            event.created_at.to_chrono().format("%Y-%m-%d %H:%M:%S"),
+            event.kind.as_str(),
+            csv_field(&event.username),
+            csv_field(event.ip.as_deref().unwrap_or("")),
+            csv_field(event.detail.as_deref().unwrap_or("")),
+        ));
+    }
+    out
+}
+
+pub async fn security_index(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SecurityQuery>,
+) -> Result<Response, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let company = get_company_by_id(&state, &company_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let days = query
+        .days
+        .filter(|days| PERIODS.contains(days))
+        .unwrap_or(DEFAULT_PERIOD);
+    let since = DateTime::from_system_time(
+        std::time::SystemTime::now() - Duration::from_secs(days * 60 * 60 * 24),
+    );
+    let events = list_access_events(&state, &company_id, since)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if query.format.as_deref() == Some("csv") {
+        let today = DateTime::now().to_chrono().format("%Y-%m-%d").to_string();
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"accesos-{today}.csv\""),
+                ),
+            ],
+            access_csv(&events),
+        )
+            .into_response());
+    }
+
+    let recent = &events[..events.len().min(RECENT_EVENTS)];
+    let mut user_ids: Vec<ObjectId> = recent.iter().filter_map(|event| event.user_id).collect();
+    user_ids.sort();
+    user_ids.dedup();
+    let labels = user_labels(&state, &user_ids)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let summary = summarize_access_by_day(&events);
+    let totals = summary.iter().fold(AccessDay::default(), |mut acc, day| {
+        acc.sessions += day.sessions;
+        acc.failed_logins += day.failed_logins;
+        acc.admin_actions += day.admin_actions;
+        acc
+    });
+    SecurityTemplate {
+        days,
+        periods: PERIODS,
+        totals,
+        summary,
+        anomalies: access_anomalies(&events),
+        events: recent
+            .iter()
+            .map(|event| EventRow::new(event, &labels))
+            .collect(),
+        total_events: events.len(),
+        login_links: company.login_links.as_str(),
+        login_link_modes: LOGIN_LINK_MODES
+            .iter()
+            .map(|(mode, label)| (mode.as_str(), *label))
+            .collect(),
+    }
+    .render()
+    .map(|html| Html(html).into_response())
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct LoginLinksForm {
+    #[serde(default)]
+    mode: String,
+}
+
+/// Lets the company's staff sign in with an emailed link, besides or instead
+/// of their TOTP code, or stops it. Links already sent stop working too.
+pub async fn security_login_links(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<LoginLinksForm>,
+) -> Result<Response, StatusCode> {
+    let company_id = require_admin_active(&session_user)?;
+    let mode = LoginLinkMode::parse(form.mode.trim()).ok_or(StatusCode::BAD_REQUEST)?;
+    update_company_login_links(&state, &company_id, mode)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((
+        Flash::success("Acceso con enlace actualizado."),
+        Redirect::to("/admin/security"),
+    )
+        .into_response())
+}
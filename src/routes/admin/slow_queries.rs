@@ -0,0 +1,164 @@
+// Slow query log: the MongoDB commands that took longer than `SLOW_QUERY_MS`
+// since the process started, grouped by collection and filter shape so the
+// ones worth an index stand out. Superadmins only; the log is process-wide.
+
+use std::{collections::HashMap, time::Duration};
+
+use askama::Template;
+use axum::{http::StatusCode, response::Html, routing::get};
+use chrono::{DateTime, Utc};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    metrics::{SlowQuery, metrics, slow_query_threshold_from_env},
+    routes::Routes,
+    session::SessionUser,
+};
+
+/// The slow query page.
+pub fn router() -> Routes {
+    Routes::new().route("/admin/slow_queries", get(slow_queries_index))
+}
+
+/// Commands listed one by one below the groups.
+const RECENT_QUERIES: usize = 50;
+
+struct SlowQueryRow {
+    at: String,
+    command: String,
+    collection: String,
+    shape: String,
+    millis: u128,
+    failed: bool,
+}
+
+impl SlowQueryRow {
+    fn new(query: &SlowQuery) -> Self {
+        SlowQueryRow {
+            at: format_at(query),
+            command: query.command.clone(),
+            collection: query.collection.clone(),
+            shape: query.shape.clone(),
+            millis: query.duration.as_millis(),
+            failed: query.failed,
+        }
+    }
+}
+
+/// Commands with the same collection and shape.
+struct SlowQueryGroup {
+    command: String,
+    collection: String,
+    shape: String,
+    count: usize,
+    max_millis: u128,
+    avg_millis: u128,
+    last_at: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin/slow_queries/index.html")]
+struct SlowQueriesTemplate {
+    /// `None` when the log is off.
+    threshold_ms: Option<u128>,
+    groups: Vec<SlowQueryGroup>,
+    recent: Vec<SlowQueryRow>,
+}
+
+fn format_at(query: &SlowQuery) -> String {
+    DateTime::<Utc>::from(query.at)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Groups `queries` (newest first), the most frequent first and the slowest
+/// among equals.
+fn group_slow_queries(queries: &[SlowQuery]) -> Vec<SlowQueryGroup> {
+    let mut groups: Vec<SlowQueryGroup> = Vec::new();
+    let mut totals: Vec<Duration> = Vec::new();
+    let mut index: HashMap<(&str, &str, &str), usize> = HashMap::new();
+    for query in queries {
+        let key = (
+            query.collection.as_str(),
+            query.command.as_str(),
+            query.shape.as_str(),
+        );
+        let idx = *index.entry(key).or_insert_with(|| {
+            groups.push(SlowQueryGroup {
+                command: query.command.clone(),
+                collection: query.collection.clone(),
+                shape: query.shape.clone(),
+                count: 0,
+                max_millis: 0,
+                avg_millis: 0,
+                last_at: format_at(query),
+            });
+            totals.push(Duration::ZERO);
+            groups.len() - 1
+        });
+        let group = &mut groups[idx];
+        group.count += 1;
+        group.max_millis = group.max_millis.max(query.duration.as_millis());
+        totals[idx] += query.duration;
+    }
+    for (group, total) in groups.iter_mut().zip(totals) {
+        group.avg_millis = total.as_millis() / group.count as u128;
+    }
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then(b.max_millis.cmp(&a.max_millis)));
+    groups
+}
+
+pub async fn slow_queries_index(session_user: SessionUser) -> Result<Html<String>, StatusCode> {
+    if !session_user.is_superadmin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let queries = metrics().slow_queries();
+    SlowQueriesTemplate {
+        threshold_ms: slow_query_threshold_from_env().map(|threshold| threshold.as_millis()),
+        groups: group_slow_queries(&queries),
+        recent: queries
+            .iter()
+            .take(RECENT_QUERIES)
+            .map(SlowQueryRow::new)
+            .collect(),
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn query(collection: &str, shape: &str, millis: u64) -> SlowQuery {
+        SlowQuery {
+            at: SystemTime::now(),
+            command: "find".to_string(),
+            collection: collection.to_string(),
+            shape: shape.to_string(),
+            duration: Duration::from_millis(millis),
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn groups_by_collection_and_shape_most_frequent_first() {
+        let groups = group_slow_queries(&[
+            query("transactions", r#"{ "company_id": "?" }"#, 900),
+            query("users", r#"{ "username": "?" }"#, 2_000),
+            query("transactions", r#"{ "company_id": "?" }"#, 600),
+            query("transactions", r#"{ "date": "?" }"#, 700),
+        ]);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].collection, "transactions");
+        assert_eq!(groups[0].count, 2);
+        assert_eq!(groups[0].max_millis, 900);
+        assert_eq!(groups[0].avg_millis, 750);
+        assert_eq!(groups[1].collection, "users");
+        assert_eq!(groups[2].shape, r#"{ "date": "?" }"#);
+    }
+}
@@ -0,0 +1,310 @@
+// Company storage: how much its uploaded files take and the quota a
+// superadmin sets. Company admins can see the usage; only a superadmin can
+// change the quota, and saving it also recounts the files on disk.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form, Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flash::Flash,
+    models::CompanyStorage,
+    routes::Routes,
+    session::SessionUser,
+    state::{AppState, format_bytes, get_company_by_id, recount_storage, set_storage_quota},
+};
+
+/// Storage usage and quota of a company.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/storage",
+            get(company_storage_edit).post(company_storage_update),
+        )
+        .route(
+            "/api/admin/companies/{id}/storage",
+            get(company_storage_api).post(company_storage_update_api),
+        )
+}
+
+const BYTES_PER_MB: i64 = 1024 * 1024;
+
+/// Company admins and superadmins may see the usage.
+fn require_storage_viewer(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user.is_superadmin()
+        || session_user
+            .user()
+            .company_ids
+            .iter()
+            .zip(session_user.user().company_roles.iter())
+            .any(|(cid, role)| cid == company_id && role.is_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Quota typed in whole megabytes; blank means no limit.
+fn parse_quota_mb(value: &str) -> Result<Option<i64>, &'static str> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.parse::<i64>() {
+        Ok(mb) if mb > 0 => mb
+            .checked_mul(BYTES_PER_MB)
+            .map(Some)
+            .ok_or("La cuota es demasiado grande."),
+        _ => Err("La cuota debe ser un número entero de MB mayor que cero."),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/storage.html")]
+struct CompanyStorageTemplate {
+    company_id: String,
+    company_name: String,
+    used: String,
+    quota: Option<String>,
+    /// Share of the quota in use, capped at 100 for the bar.
+    percent: i64,
+    over_quota: bool,
+    quota_mb: String,
+    can_edit: bool,
+    errors: Option<String>,
+}
+
+fn storage_template(
+    company_id: &str,
+    company_name: String,
+    storage: &CompanyStorage,
+    can_edit: bool,
+    errors: Option<String>,
+) -> CompanyStorageTemplate {
+    let percent = storage
+        .quota_bytes
+        .filter(|quota| *quota > 0)
+        .map(|quota| (storage.used_bytes.saturating_mul(100) / quota).min(100))
+        .unwrap_or(0);
+    CompanyStorageTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        used: format_bytes(storage.used_bytes),
+        quota: storage.quota_bytes.map(format_bytes),
+        percent,
+        over_quota: storage
+            .quota_bytes
+            .is_some_and(|quota| storage.used_bytes >= quota),
+        quota_mb: storage
+            .quota_bytes
+            .map(|quota| (quota / BYTES_PER_MB).to_string())
+            .unwrap_or_default(),
+        can_edit,
+        errors,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StorageQuotaForm {
+    #[serde(default)]
+    quota_mb: String,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct StorageQuotaPayload {
+    /// Most bytes the company may store; `null` lifts the limit.
+    #[serde(default)]
+    quota_bytes: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct StorageData {
+    used_bytes: i64,
+    quota_bytes: Option<i64>,
+}
+
+pub async fn company_storage_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_storage_viewer(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(storage_template(
+        &company_id,
+        company.name,
+        &company.storage,
+        session_user.is_superadmin(),
+        None,
+    ))
+}
+
+/// Saves the quota and recounts the usage. Superadmins only.
+pub async fn company_storage_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    Form(form): Form<StorageQuotaForm>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&company_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !session_user.is_superadmin() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let company = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let quota_bytes = match parse_quota_mb(&form.quota_mb) {
+        Ok(quota_bytes) => quota_bytes,
+        Err(message) => {
+            return render(storage_template(
+                &company_id,
+                company.name,
+                &company.storage,
+                true,
+                Some(message.to_string()),
+            ))
+            .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+            .unwrap_or_else(|status| status.into_response());
+        }
+    };
+
+    if let Err(e) = set_storage_quota(&state, &object_id, quota_bytes).await {
+        eprintln!("[storage] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if let Err(e) = recount_storage(&state, &object_id).await {
+        eprintln!("[storage] recount error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Límite de almacenamiento guardado."),
+        Redirect::to(&format!("/admin/companies/{company_id}/storage")),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/companies/{id}/storage",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    responses(
+        (status = 200, description = "Bytes used by the company's files and its quota (`null` when unlimited)"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_storage_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let object_id = ObjectId::from_str(&id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_storage_viewer(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(StorageData {
+        used_bytes: company.storage.used_bytes,
+        quota_bytes: company.storage.quota_bytes,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/companies/{id}/storage",
+    tag = "admin",
+    params(("id" = String, Path, description = "Company id")),
+    request_body = StorageQuotaPayload,
+    responses(
+        (status = 200, description = "Quota saved; returns the recounted usage"),
+        (status = 400, description = "Quota not greater than zero"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Not a superadmin"),
+        (status = 404, description = "Not found")
+    ),
+    security(("session" = []))
+)]
+pub async fn company_storage_update_api(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<StorageQuotaPayload>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !session_user.is_superadmin() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if payload.quota_bytes.is_some_and(|quota| quota <= 0) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "quota_bytes must be greater than zero" })),
+        )
+            .into_response();
+    }
+    match get_company_by_id(&state, &object_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+    if set_storage_quota(&state, &object_id, payload.quota_bytes)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    match recount_storage(&state, &object_id).await {
+        Ok(used_bytes) => Json(StorageData {
+            used_bytes,
+            quota_bytes: payload.quota_bytes,
+        })
+        .into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_is_read_in_whole_megabytes() {
+        assert_eq!(parse_quota_mb(""), Ok(None));
+        assert_eq!(parse_quota_mb(" 250 "), Ok(Some(250 * BYTES_PER_MB)));
+        assert!(parse_quota_mb("0").is_err());
+        assert!(parse_quota_mb("1.5").is_err());
+        assert!(parse_quota_mb("mucho").is_err());
+    }
+}
@@ -6,28 +6,49 @@ use std::{
 
 use askama::Template;
 use axum::{
-    body::Body,
+    Form,
     extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect, Response},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
 };
 use mongodb::bson::oid::ObjectId;
 
 #[allow(unused_imports)]
 use crate::filters;
 
+use super::{
+    account::start_email_change,
+    confirm::{ConfirmForm, Confirmation, confirm_page, verify},
+};
 use crate::{
+    flash::Flash,
     models::{UserPermission, UserRole},
+    routes::Routes,
+    routes::form_fields::parse_optional_object_id,
+    routes::qrcode::qr_png_response,
     session::SessionUser,
     state::{
         AppState, create_user_with_permissions, delete_user, get_user_by_id, list_companies,
-        list_users, update_user_with_permissions,
+        list_users, set_user_active, update_user_with_permissions,
     },
     totp::{DEFAULT_SECRET_BYTES, build_totp, generate_base32_secret_n},
 };
-use image::{DynamicImage, ImageFormat, Luma};
-use qrcode::QrCode;
-use std::io::Cursor;
+
+/// User administration pages.
+pub fn router() -> Routes {
+    Routes::new()
+        .route("/admin/users", get(users_index).post(users_create))
+        .route("/admin/users/new", get(users_new))
+        .route("/admin/users/{id}/edit", get(users_edit))
+        .route("/admin/users/{id}/update", post(users_update))
+        .route(
+            "/admin/users/{id}/delete",
+            get(users_delete_confirm).post(users_delete),
+        )
+        .route("/admin/users/{id}/toggle_active", post(users_toggle_active))
+        .route("/admin/users/{id}/qrcode", get(users_qrcode))
+}
 
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
@@ -47,6 +68,7 @@ struct UserRow {
     company: String,
     role: String,
     is_self: bool,
+    is_active: bool,
 }
 
 #[derive(Template)]
@@ -59,6 +81,8 @@ struct UserFormTemplate {
     can_edit_role: bool,
     user_id: Option<String>,
     errors: Option<String>,
+    is_active: bool,
+    can_toggle_active: bool,
 }
 
 #[derive(Clone)]
@@ -82,7 +106,7 @@ struct CompanyOption {
 pub(crate) struct UserFormData {
     email: String,
     secret: String,
-    company_ids: Vec<String>,
+    company_ids: Vec<ObjectId>,
     role_map: std::collections::HashMap<String, String>,
     permission_map: HashMap<String, HashSet<String>>,
 }
@@ -98,7 +122,7 @@ fn parse_user_form(body: &str) -> Result<UserFormData, String> {
         match key.as_ref() {
             "email" => email = value.into_owned(),
             "secret" => secret = value.into_owned(),
-            "company_ids" => company_ids.push(value.into_owned()),
+            "company_ids" => company_ids.extend(parse_optional_object_id(&value)?),
             key if key.starts_with("role_") => {
                 role_map.insert(key.to_string(), value.into_owned());
             }
@@ -155,7 +179,7 @@ pub async fn users_index(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
 ) -> Result<Html<String>, StatusCode> {
-    if !session_user.is_admin() {
+    if !session_user.can_view_admin() {
         return Err(StatusCode::FORBIDDEN);
     }
 
@@ -181,6 +205,7 @@ pub async fn users_index(
                 company: company_label,
                 role: user.role.as_str().to_string(),
                 is_self: current_id == user.id,
+                is_active: user.is_active,
             }
         })
         .collect();
@@ -215,6 +240,8 @@ pub async fn users_new(
         user_id: None,
         can_edit_role: true,
         errors: None,
+        is_active: true,
+        can_toggle_active: false,
     })
 }
 
@@ -238,7 +265,11 @@ pub async fn users_create(
     }
 
     match process_user_form(form, None, &state, true, admin_companies.as_slice()).await {
-        Ok(_) => Redirect::to("/admin/users").into_response(),
+        Ok(_) => (
+            Flash::success("Usuario creado."),
+            Redirect::to("/admin/users"),
+        )
+            .into_response(),
         Err((form_view, companies, message)) => render(UserFormTemplate {
             form: form_view,
             companies,
@@ -247,6 +278,8 @@ pub async fn users_create(
             user_id: None,
             can_edit_role: true,
             errors: Some(message),
+            is_active: true,
+            can_toggle_active: false,
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response()),
@@ -258,6 +291,11 @@ pub async fn users_edit(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Html<String>, StatusCode> {
+    // The form shows the TOTP secret; read-only roles never get it, not even
+    // their own.
+    if session_user.active_role().is_read_only() {
+        return Err(StatusCode::FORBIDDEN);
+    }
     let admin_companies = admin_company_ids(&session_user);
     if admin_companies.is_empty() {
         return Err(StatusCode::FORBIDDEN);
@@ -307,6 +345,8 @@ pub async fn users_edit(
         user_id: Some(user.id.to_hex()),
         can_edit_role,
         errors: None,
+        is_active: user.is_active,
+        can_toggle_active: can_edit_role && !is_self,
     })
 }
 
@@ -352,6 +392,7 @@ pub async fn users_update(
         form,
         Some((
             &object_id,
+            target_user.username.as_str(),
             target_user
                 .company_ids
                 .iter()
@@ -366,7 +407,11 @@ pub async fn users_update(
     )
     .await
     {
-        Ok(_) => Redirect::to("/admin/users").into_response(),
+        Ok(_) => (
+            Flash::success("Usuario actualizado."),
+            Redirect::to("/admin/users"),
+        )
+            .into_response(),
         Err((form_view, companies, message)) => render(UserFormTemplate {
             form: form_view,
             companies,
@@ -375,16 +420,99 @@ pub async fn users_update(
             user_id: Some(id),
             can_edit_role,
             errors: Some(message),
+            is_active: target_user.is_active,
+            can_toggle_active: can_edit_role && !is_self,
         })
         .map(IntoResponse::into_response)
         .unwrap_or_else(|status| status.into_response()),
     }
 }
 
+/// A user the admin may delete and the confirmation of deleting it. Admins
+/// cannot delete themselves.
+async fn user_delete_confirmation(
+    state: &AppState,
+    session_user: &SessionUser,
+    id: &str,
+) -> Result<(ObjectId, Confirmation), StatusCode> {
+    if !session_user.is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let object_id = ObjectId::from_str(id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if session_user.user_id() == &object_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let target_user = get_user_by_id(state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let admin_companies = admin_company_ids(session_user);
+    if admin_companies.is_empty()
+        || !user_shares_admin_company(&target_user.company_ids, &admin_companies)
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let confirmation = Confirmation {
+        title: "Eliminar usuario".to_string(),
+        message: format!(
+            "Se eliminará al usuario {} junto con sus sesiones, tokens y accesos a las compañías.",
+            target_user.username
+        ),
+        action: format!("/admin/users/{id}/delete"),
+        cancel_url: "/admin/users".to_string(),
+        button_label: "Eliminar usuario".to_string(),
+        expected_name: None,
+    };
+    Ok((object_id, confirmation))
+}
+
+/// GET /admin/users/{id}/delete — asks before deleting.
+pub async fn users_delete_confirm(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match user_delete_confirmation(&state, &session_user, &id).await {
+        Ok((_, confirmation)) => confirm_page(&session_user, &confirmation),
+        Err(status) => status.into_response(),
+    }
+}
+
 pub async fn users_delete(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Form(form): Form<ConfirmForm>,
+) -> impl IntoResponse {
+    let (object_id, confirmation) = match user_delete_confirmation(&state, &session_user, &id).await
+    {
+        Ok(found) => found,
+        Err(status) => return status.into_response(),
+    };
+    if let Err(response) = verify(&session_user, &confirmation, &form) {
+        return *response;
+    }
+
+    match delete_user(&state, &object_id).await {
+        Ok(_) => (
+            Flash::success("Usuario eliminado."),
+            Redirect::to("/admin/users"),
+        )
+            .into_response(),
+        Err(_) => (
+            Flash::error("No se pudo eliminar el usuario."),
+            Redirect::to("/admin/users"),
+        )
+            .into_response(),
+    }
+}
+
+/// Flips a user's active flag. Deactivation revokes every session the user
+/// holds; admins cannot deactivate themselves.
+pub async fn users_toggle_active(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
 ) -> impl IntoResponse {
     if !session_user.is_admin() {
         return StatusCode::FORBIDDEN.into_response();
@@ -410,8 +538,12 @@ pub async fn users_delete(
         return StatusCode::FORBIDDEN.into_response();
     }
 
-    match delete_user(&state, &object_id).await {
-        Ok(_) => Redirect::to("/admin/users").into_response(),
+    match set_user_active(&state, &object_id, !target_user.is_active).await {
+        Ok(_) => (
+            Flash::success("Estado del usuario actualizado."),
+            Redirect::to(&format!("/admin/users/{}/edit", id)),
+        )
+            .into_response(),
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
@@ -420,7 +552,12 @@ pub async fn users_qrcode(
     session_user: SessionUser,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
+    // The QR embeds the TOTP secret, like the edit form.
+    if session_user.active_role().is_read_only() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
     let object_id = match ObjectId::from_str(&id) {
         Ok(id) => id,
         Err(_) => return StatusCode::BAD_REQUEST.into_response(),
@@ -447,55 +584,41 @@ pub async fn users_qrcode(
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
-    let url = totp.get_url();
-    let code = match QrCode::new(url.as_bytes()) {
-        Ok(code) => code,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
-
-    let img = code.render::<Luma<u8>>().min_dimensions(400, 400).build();
-    let mut cursor = Cursor::new(Vec::<u8>::new());
-    if DynamicImage::ImageLuma8(img)
-        .write_to(&mut cursor, ImageFormat::Png)
-        .is_err()
-    {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    }
-
-    let png = cursor.into_inner();
-    Response::builder()
-        .header("Content-Type", "image/png")
-        .body(Body::from(png))
-        .unwrap()
+    qr_png_response(&totp.get_url(), &headers)
 }
 
+/// Id, current username and company roles of the user being edited.
+type ExistingUser<'a> = (
+    &'a ObjectId,
+    &'a str,
+    Vec<(ObjectId, UserRole, Vec<UserPermission>)>,
+);
+
 async fn process_user_form(
     form: UserFormData,
-    existing: Option<(&ObjectId, Vec<(ObjectId, UserRole, Vec<UserPermission>)>)>,
+    existing: Option<ExistingUser<'_>>,
     state: &Arc<AppState>,
     allow_role_change: bool,
     allowed_company_ids: &[ObjectId],
 ) -> Result<(), (UserFormView, Vec<CompanyOption>, String)> {
     let allowed: HashSet<ObjectId> = allowed_company_ids.iter().cloned().collect();
     let mut company_roles: Vec<(ObjectId, UserRole, Vec<UserPermission>)> = Vec::new();
-    for raw in &form.company_ids {
-        if let Ok(id) = ObjectId::from_str(raw.trim()) {
-            if allowed.contains(&id) && !company_roles.iter().any(|(cid, _, _)| cid == &id) {
-                let role_key = format!("role_{}", id.to_hex());
-                let role_val = form
-                    .role_map
-                    .get(&role_key)
-                    .cloned()
-                    .unwrap_or_else(|| "staff".to_string());
-                let role = role_from_str(&role_val);
-                let permission_values = form
-                    .permission_map
-                    .get(&id.to_hex())
-                    .cloned()
-                    .unwrap_or_default();
-                let permissions = permissions_from_values(&permission_values);
-                company_roles.push((id, role, permissions));
-            }
+    for &id in &form.company_ids {
+        if allowed.contains(&id) && !company_roles.iter().any(|(cid, _, _)| cid == &id) {
+            let role_key = format!("role_{}", id.to_hex());
+            let role_val = form
+                .role_map
+                .get(&role_key)
+                .cloned()
+                .unwrap_or_else(|| "staff".to_string());
+            let role = role_from_str(&role_val);
+            let permission_values = form
+                .permission_map
+                .get(&id.to_hex())
+                .cloned()
+                .unwrap_or_default();
+            let permissions = permissions_from_values(&permission_values);
+            company_roles.push((id, role, permissions));
         }
     }
 
@@ -511,7 +634,7 @@ async fn process_user_form(
     }
 
     if !allow_role_change {
-        if let Some((_id, existing_roles)) = &existing {
+        if let Some((_id, _, existing_roles)) = &existing {
             company_roles = company_roles
                 .into_iter()
                 .map(|(cid, _, permissions)| {
@@ -549,11 +672,21 @@ async fn process_user_form(
         ));
     }
 
-    if let Some((id, _existing_roles)) = existing {
-        if let Err(_) =
-            update_user_with_permissions(state, id, &email_trimmed, &secret_trimmed, &company_roles)
-                .await
-        {
+    if let Some((id, current_username, _existing_roles)) = existing {
+        // The username only changes once the new address confirms it.
+        let updated = update_user_with_permissions(
+            state,
+            id,
+            current_username,
+            &secret_trimmed,
+            &company_roles,
+        )
+        .await;
+        let email_change = match updated {
+            Ok(_) => start_email_change(state, id, current_username, &email_trimmed).await,
+            Err(err) => Err(err),
+        };
+        if email_change.is_err() {
             let companies = load_company_options(
                 state,
                 Some(&company_roles),
@@ -683,6 +816,7 @@ async fn load_company_options(
 fn role_from_str(value: &str) -> UserRole {
     match value {
         "admin" => UserRole::Admin,
+        "auditor" => UserRole::Auditor,
         _ => UserRole::Staff,
     }
 }
@@ -17,29 +17,53 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
+    routing::{get, post},
 };
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     models::{UserPermission, UserRole},
+    routes::{
+        Routes,
+        form_fields::{object_ids, optional_object_id},
+    },
     session::SessionUser,
     state::{
         AppState, UserWithCompany, create_user_with_permissions, delete_user, get_user_by_id,
-        list_users, update_user_with_permissions, username_taken,
+        list_users, set_account_access, update_user_with_permissions, username_taken,
     },
     totp::{DEFAULT_SECRET_BYTES, generate_base32_secret_n},
 };
 
+use super::account::start_email_change;
 use super::finance::helpers::require_admin_active;
 use super::users::{admin_company_ids, user_shares_admin_company};
 
+/// JSON API for user administration, account access lists included.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/api/admin/users",
+            get(api_users_index).post(api_users_create),
+        )
+        .route("/api/admin/users/{id}", get(api_user_detail))
+        .route("/api/admin/users/{id}/update", post(api_users_update))
+        .route("/api/admin/users/{id}/delete", post(api_users_delete))
+        .route(
+            "/api/admin/users/{id}/accounts",
+            post(api_user_accounts_update),
+        )
+}
+
 #[derive(Serialize)]
 pub struct UserMembershipData {
     pub company_id: String,
     pub company_name: String,
     pub role: String,
     pub permissions: Vec<String>,
+    /// Accounts the user is limited to in the company; empty for all.
+    pub account_ids: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -49,6 +73,7 @@ pub struct UserRowData {
     pub role: String,
     pub companies: Vec<String>,
     pub memberships: Vec<UserMembershipData>,
+    pub is_active: bool,
     /// TOTP secret, only populated by the single-user detail endpoint (so an
     /// admin can copy it when provisioning); omitted from the list.
     #[serde(default, skip_serializing_if = "String::is_empty")]
@@ -57,7 +82,9 @@ pub struct UserRowData {
 
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct UserMembershipPayload {
-    pub company_id: String,
+    #[serde(default, deserialize_with = "optional_object_id")]
+    #[schema(value_type = Option<String>)]
+    pub company_id: Option<ObjectId>,
     #[serde(default)]
     pub role: Option<String>,
     #[serde(default)]
@@ -82,6 +109,15 @@ pub struct UserUpdatePayload {
     pub memberships: Vec<UserMembershipPayload>,
 }
 
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UserAccountsPayload {
+    /// Accounts of the active company the user may work with; empty lifts
+    /// the limit.
+    #[serde(default, deserialize_with = "object_ids")]
+    #[schema(value_type = Vec<String>)]
+    pub account_ids: Vec<ObjectId>,
+}
+
 fn json_error(status: StatusCode, message: &str) -> axum::response::Response {
     (status, Json(serde_json::json!({ "error": message }))).into_response()
 }
@@ -89,6 +125,7 @@ fn json_error(status: StatusCode, message: &str) -> axum::response::Response {
 fn role_from_str(value: &str) -> UserRole {
     match value {
         "admin" => UserRole::Admin,
+        "auditor" => UserRole::Auditor,
         _ => UserRole::Staff,
     }
 }
@@ -122,6 +159,11 @@ fn user_row_data(user: UserWithCompany) -> UserRowData {
                 .get(idx)
                 .map(|perms| perms.iter().map(|p| p.as_str().to_string()).collect())
                 .unwrap_or_default(),
+            account_ids: user
+                .company_account_ids
+                .get(idx)
+                .map(|ids| ids.iter().map(|id| id.to_hex()).collect())
+                .unwrap_or_default(),
         })
         .collect();
 
@@ -131,6 +173,7 @@ fn user_row_data(user: UserWithCompany) -> UserRowData {
         role: user.role.as_str().to_string(),
         companies: user.company_names.clone(),
         memberships,
+        is_active: user.is_active,
         secret: String::new(),
     }
 }
@@ -144,7 +187,7 @@ fn parse_memberships(
     let allowed: HashSet<ObjectId> = allowed.iter().cloned().collect();
     let mut out: Vec<(ObjectId, UserRole, Vec<UserPermission>)> = Vec::new();
     for membership in payloads {
-        let Ok(cid) = ObjectId::parse_str(membership.company_id.trim()) else {
+        let Some(cid) = membership.company_id else {
             continue;
         };
         if !allowed.contains(&cid) || out.iter().any(|(existing, _, _)| existing == &cid) {
@@ -370,8 +413,25 @@ pub async fn api_users_update(
         .map(|value| value.to_string())
         .unwrap_or_else(|| target.secret.clone());
 
-    match update_user_with_permissions(&state, &object_id, &username, &secret, &company_roles).await {
-        Ok(_) => Json(serde_json::json!({ "ok": true })).into_response(),
+    // A new username stays pending until the user confirms it from the link
+    // sent to the new address.
+    if update_user_with_permissions(
+        &state,
+        &object_id,
+        &target.username,
+        &secret,
+        &company_roles,
+    )
+    .await
+    .is_err()
+    {
+        return json_error(StatusCode::BAD_REQUEST, "could not update user");
+    }
+    match start_email_change(&state, &object_id, &target.username, &username).await {
+        Ok(true) => {
+            Json(serde_json::json!({ "ok": true, "pending_username": username })).into_response()
+        }
+        Ok(false) => Json(serde_json::json!({ "ok": true })).into_response(),
         Err(_) => json_error(StatusCode::BAD_REQUEST, "could not update user"),
     }
 }
@@ -422,3 +482,50 @@ pub async fn api_users_delete(
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/accounts",
+    tag = "admin",
+    params(("id" = String, Path, description = "Record id")),
+    request_body = UserAccountsPayload,
+    responses(
+        (status = 200, description = "Account access updated for the active company"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Not found"),
+        (status = 422, description = "Validation error")
+    ),
+    security(("session" = []))
+)]
+pub async fn api_user_accounts_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(payload): Json<UserAccountsPayload>,
+) -> impl IntoResponse {
+    let company_id = match require_admin_active(&session_user) {
+        Ok(id) => id,
+        Err(status) => return status.into_response(),
+    };
+    let object_id = match ObjectId::from_str(&id) {
+        Ok(id) => id,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let target = match get_user_by_id(&state, &object_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if !target.company_ids.contains(&company_id) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match set_account_access(&state, &object_id, &company_id, &payload.account_ids).await {
+        Ok(()) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(_) => json_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "every account must belong to the active company",
+        ),
+    }
+}
@@ -0,0 +1,246 @@
+// Company webhooks: the URL that receives the company's events as signed
+// JSON, which events it gets, and a ping to check it.
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use bson::oid::ObjectId;
+use serde::Deserialize;
+
+use crate::{
+    flash::Flash,
+    models::CompanyWebhooks,
+    routes::Routes,
+    session::SessionUser,
+    state::{
+        AppState, get_company_by_id, new_webhook_secret, send_test_webhook, update_company_webhooks,
+    },
+};
+
+/// Webhook settings of a company.
+pub fn router() -> Routes {
+    Routes::new()
+        .route(
+            "/admin/companies/{id}/webhooks",
+            get(company_webhooks_edit).post(company_webhooks_update),
+        )
+        .route(
+            "/admin/companies/{id}/webhooks/test",
+            post(company_webhooks_test),
+        )
+}
+
+const MAX_URL_CHARS: usize = 500;
+
+fn require_company_admin(
+    session_user: &SessionUser,
+    company_id: &ObjectId,
+) -> Result<(), StatusCode> {
+    if session_user
+        .user()
+        .company_ids
+        .iter()
+        .zip(session_user.user().company_roles.iter())
+        .any(|(cid, role)| cid == company_id && role.is_admin())
+    {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Accepts absolute `http` and `https` URLs with a host.
+fn validate_webhook_url(url: &str) -> Result<(), &'static str> {
+    if url.chars().count() > MAX_URL_CHARS {
+        return Err("La URL es demasiado larga.");
+    }
+    match reqwest::Url::parse(url) {
+        Ok(parsed)
+            if matches!(parsed.scheme(), "http" | "https") && parsed.host_str().is_some() =>
+        {
+            Ok(())
+        }
+        _ => Err("Escribe una URL completa, por ejemplo https://bi.example.com/hooks/alfredo."),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/companies/webhooks.html")]
+struct WebhooksTemplate {
+    company_id: String,
+    company_name: String,
+    url: String,
+    secret: String,
+    forecast_completed: bool,
+    last_delivery_at: Option<String>,
+    last_error: Option<String>,
+    message: Option<String>,
+    errors: Option<String>,
+}
+
+fn webhooks_template(
+    company_id: &str,
+    company_name: String,
+    settings: &CompanyWebhooks,
+    message: Option<String>,
+    errors: Option<String>,
+) -> WebhooksTemplate {
+    WebhooksTemplate {
+        company_id: company_id.to_string(),
+        company_name,
+        url: settings.url.clone(),
+        secret: settings.secret.clone(),
+        forecast_completed: settings.forecast_completed,
+        last_delivery_at: settings
+            .last_delivery_at
+            .map(|at| at.to_chrono().format("%Y-%m-%d %H:%M").to_string()),
+        last_error: settings.last_error.clone(),
+        message,
+        errors,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WebhooksForm {
+    #[serde(default)]
+    url: String,
+    forecast_completed: Option<bool>,
+    /// Replaces the signing secret with a new one.
+    rotate_secret: Option<bool>,
+}
+
+pub async fn company_webhooks_edit(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render(webhooks_template(
+        &company_id,
+        company.name,
+        &company.webhooks,
+        None,
+        None,
+    ))
+}
+
+/// Saves the URL and events. An empty URL turns the webhooks off; the secret
+/// is created with the first URL and kept unless rotated.
+pub async fn company_webhooks_update(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+    Form(form): Form<WebhooksForm>,
+) -> Response {
+    let Ok(object_id) = ObjectId::from_str(&company_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if let Err(status) = require_company_admin(&session_user, &object_id) {
+        return status.into_response();
+    }
+    let company = match get_company_by_id(&state, &object_id).await {
+        Ok(Some(company)) => company,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let mut settings = CompanyWebhooks {
+        url: form.url.trim().to_string(),
+        forecast_completed: form.forecast_completed.unwrap_or(false),
+        ..company.webhooks.clone()
+    };
+    if !settings.url.is_empty() {
+        if let Err(message) = validate_webhook_url(&settings.url) {
+            return render(webhooks_template(
+                &company_id,
+                company.name,
+                &settings,
+                None,
+                Some(message.to_string()),
+            ))
+            .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+            .unwrap_or_else(|status| status.into_response());
+        }
+        if settings.secret.is_empty() || form.rotate_secret.unwrap_or(false) {
+            settings.secret = new_webhook_secret();
+        }
+    }
+
+    if let Err(e) = update_company_webhooks(&state, &object_id, &settings).await {
+        eprintln!("[webhooks] db update error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    (
+        Flash::success("Webhooks guardados."),
+        Redirect::to(&format!("/admin/companies/{company_id}/webhooks")),
+    )
+        .into_response()
+}
+
+/// Sends a `ping` to the saved URL and reports how it went.
+pub async fn company_webhooks_test(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(company_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let object_id = ObjectId::from_str(&company_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_company_admin(&session_user, &object_id)?;
+    let company = get_company_by_id(&state, &object_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let settings = &company.webhooks;
+    let outcome = if settings.url.is_empty() {
+        Err("Guarda primero una URL.".to_string())
+    } else {
+        send_test_webhook(&state, &settings.url, &settings.secret)
+            .await
+            .map_err(|err| {
+                eprintln!("[webhooks] ping for {company_id} failed: {err}");
+                "No se pudo entregar el ping.".to_string()
+            })
+    };
+    let (message, errors) = match outcome {
+        Ok(()) => (Some("El endpoint recibió el ping.".to_string()), None),
+        Err(error) => (None, Some(error)),
+    };
+    render(webhooks_template(
+        &company_id,
+        company.name.clone(),
+        settings,
+        message,
+        errors,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_absolute_http_urls_are_accepted() {
+        assert!(validate_webhook_url("https://bi.example.com/hooks").is_ok());
+        assert!(validate_webhook_url("http://127.0.0.1:9000/hook").is_ok());
+        assert!(validate_webhook_url("ftp://bi.example.com/hooks").is_err());
+        assert!(validate_webhook_url("bi.example.com/hooks").is_err());
+    }
+}
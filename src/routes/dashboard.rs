@@ -0,0 +1,215 @@
+// routes/dashboard.rs
+// GET /api/me/dashboard -> the widgets the user's dashboard shows in the
+// active company, in order, and every widget they may add. POST
+// /api/me/dashboard -> stores a new choice and order, or goes back to the
+// default layout.
+//
+// The SPA draws each widget by its key from the data at `data_url`; adding a
+// widget means an entry in `DASHBOARD_WIDGETS` and its view in
+// frontend/src/pages/dashboard.rs.
+
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    session::SessionUser,
+    state::{AppState, set_user_dashboard_widgets, user_dashboard_widgets},
+};
+
+/// A panel the dashboard can show.
+pub struct DashboardWidget {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    /// Endpoint the widget loads its figures from.
+    pub data_url: &'static str,
+    /// Reads finance figures, which only admins of the company may see.
+    pub admin_only: bool,
+}
+
+/// Every widget, in the order of the default layout.
+pub const DASHBOARD_WIDGETS: &[DashboardWidget] = &[
+    DashboardWidget {
+        key: "burn_rate",
+        label: "Ritmo de gasto",
+        description: "Consumo neto mensual, pista de caja y gasto frente al periodo previo.",
+        data_url: "/api/admin/reports/burn-rate",
+        admin_only: true,
+    },
+    DashboardWidget {
+        key: "cash_position",
+        label: "Posición de caja",
+        description: "Saldo de cada cuenta activa y el total en la moneda de la compañía.",
+        data_url: "/api/admin/reports/net-worth",
+        admin_only: true,
+    },
+    DashboardWidget {
+        key: "overdue",
+        label: "Compromisos vencidos",
+        description: "Cobros y pagos que pasaron su fecha sin cubrirse.",
+        data_url: "/api/admin/planned-entries",
+        admin_only: true,
+    },
+    DashboardWidget {
+        key: "category_chart",
+        label: "Gasto por categoría",
+        description: "Reparto del gasto confirmado del mes entre categorías.",
+        data_url: "/api/admin/transactions/data",
+        admin_only: true,
+    },
+    DashboardWidget {
+        key: "recent_transactions",
+        label: "Últimos movimientos",
+        description: "Los movimientos más recientes de la compañía.",
+        data_url: "/api/admin/transactions/data",
+        admin_only: true,
+    },
+];
+
+fn visible_widgets(session: &SessionUser) -> impl Iterator<Item = &'static DashboardWidget> {
+    let is_admin = session.can_view_admin();
+    DASHBOARD_WIDGETS
+        .iter()
+        .filter(move |widget| is_admin || !widget.admin_only)
+}
+
+/// The stored keys the user can still see, in their order: widgets dropped
+/// from the registry or out of reach for the user's role are skipped.
+fn chosen_widgets(session: &SessionUser, keys: &[String]) -> Vec<&'static DashboardWidget> {
+    keys.iter()
+        .filter_map(|key| visible_widgets(session).find(|widget| widget.key == key))
+        .collect()
+}
+
+/// Checks the keys as sent against the widgets the user can see.
+fn validate_widgets(session: &SessionUser, keys: &[String]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for key in keys {
+        if !visible_widgets(session).any(|widget| widget.key == key) {
+            return Err(format!("Widget desconocido: {key}"));
+        }
+        if !seen.insert(key) {
+            return Err(format!("Widget repetido: {key}"));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DashboardWidgetData {
+    pub key: String,
+    pub label: String,
+    pub description: String,
+    pub data_url: String,
+}
+
+impl From<&DashboardWidget> for DashboardWidgetData {
+    fn from(widget: &DashboardWidget) -> Self {
+        Self {
+            key: widget.key.to_string(),
+            label: widget.label.to_string(),
+            description: widget.description.to_string(),
+            data_url: widget.data_url.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DashboardLayout {
+    /// What the dashboard shows, in order.
+    pub widgets: Vec<DashboardWidgetData>,
+    /// Every widget the user may show, in the default order.
+    pub available: Vec<DashboardWidgetData>,
+    /// The user has not picked their own layout.
+    pub is_default: bool,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct DashboardLayoutPayload {
+    /// Widget keys in the order to show them; `null` goes back to the
+    /// default layout.
+    pub widgets: Option<Vec<String>>,
+}
+
+async fn layout_response(session: &SessionUser, state: &AppState) -> Result<Response, StatusCode> {
+    let stored = user_dashboard_widgets(state, session.user_id(), session.active_company_id())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let widgets = match &stored {
+        Some(keys) => chosen_widgets(session, keys),
+        None => visible_widgets(session).collect(),
+    };
+    Ok(Json(DashboardLayout {
+        widgets: widgets.into_iter().map(DashboardWidgetData::from).collect(),
+        available: visible_widgets(session)
+            .map(DashboardWidgetData::from)
+            .collect(),
+        is_default: stored.is_none(),
+    })
+    .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/me/dashboard",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Widgets of the user's dashboard in the active company, in order, and the ones they may add", body = DashboardLayout),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(("session" = []))
+)]
+pub async fn dashboard_layout(
+    session: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, StatusCode> {
+    layout_response(&session, &state).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/me/dashboard",
+    tag = "auth",
+    request_body = DashboardLayoutPayload,
+    responses(
+        (status = 200, description = "The stored layout", body = DashboardLayout),
+        (status = 400, description = "Unknown or repeated widget"),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "The user has no membership record in the active company")
+    ),
+    security(("session" = []))
+)]
+pub async fn dashboard_layout_update(
+    session: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<DashboardLayoutPayload>,
+) -> Result<Response, StatusCode> {
+    if let Some(keys) = &payload.widgets
+        && let Err(message) = validate_widgets(&session, keys)
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response());
+    }
+    let stored = set_user_dashboard_widgets(
+        &state,
+        session.user_id(),
+        session.active_company_id(),
+        payload.widgets.as_deref(),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !stored {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    layout_response(&session, &state).await
+}
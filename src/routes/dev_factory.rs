@@ -0,0 +1,63 @@
+// routes/dev_factory.rs
+// POST /dev/factory/{entity} -> creates `count` fake records of `entity`
+// (accounts, categories, contacts, planned_entries, transactions) for the
+// active company. Only compiled with the `dev-factory` feature, for manual QA
+// and load tests of pagination and reports.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    session::SessionUser,
+    state::{AppState, FACTORY_MAX_COUNT, FactoryEntity, generate_fake_records},
+};
+
+#[derive(Deserialize)]
+pub struct FactoryPayload {
+    pub count: usize,
+}
+
+/// Admins of the active company only.
+pub async fn dev_factory(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(entity): Path<String>,
+    Json(payload): Json<FactoryPayload>,
+) -> Response {
+    if !session_user.is_admin() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let Some(kind) = FactoryEntity::parse(&entity) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if payload.count == 0 || payload.count > FACTORY_MAX_COUNT {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": format!("count debe estar entre 1 y {FACTORY_MAX_COUNT}")
+            })),
+        )
+            .into_response();
+    }
+
+    let company_id = *session_user.active_company_id();
+    match generate_fake_records(&state, &company_id, kind, payload.count).await {
+        Ok(ids) => Json(serde_json::json!({
+            "entity": entity,
+            "created": ids.len(),
+            "ids": ids.iter().map(|id| id.to_hex()).collect::<Vec<_>>(),
+        }))
+        .into_response(),
+        Err(err) => {
+            eprintln!("dev factory: generating {entity} failed: {err:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
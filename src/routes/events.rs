@@ -0,0 +1,126 @@
+// routes/events.rs
+// GET /events -> Server-Sent Events stream of the active company's events
+// (transaction created, entry covered, forecast ready), so open pages refresh
+// when something changes instead of polling.
+
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::stream::{self, Stream};
+use mongodb::bson::oid::ObjectId;
+use serde_json::json;
+use tokio::{
+    sync::broadcast::{Receiver, error::RecvError},
+    time::{Instant, sleep_until},
+};
+
+use crate::{
+    session::SessionUser,
+    state::{AccountAccess, AppState, CompanyEvent, find_session_user},
+};
+
+/// How often a stream checks that its session still holds.
+const SESSION_RECHECK: Duration = Duration::from_secs(60);
+
+/// One open stream: what it listens to and the session it answers to.
+struct Listener {
+    state: Arc<AppState>,
+    rx: Receiver<CompanyEvent>,
+    token: String,
+    company_id: ObjectId,
+    access: AccountAccess,
+    checked_at: Instant,
+}
+
+impl Listener {
+    /// Reads the session again, keeping the accounts it may see current.
+    /// False once it was revoked, expired or lost the company's admin pages.
+    async fn session_holds(&mut self) -> bool {
+        self.checked_at = Instant::now();
+        let Ok(Some((user, _))) = find_session_user(&self.state, &self.token).await else {
+            return false;
+        };
+        let Some(index) = user
+            .company_ids
+            .iter()
+            .position(|id| *id == self.company_id)
+        else {
+            return false;
+        };
+        if !user
+            .company_roles
+            .get(index)
+            .is_some_and(|role| role.can_view_admin())
+        {
+            return false;
+        }
+        self.access = AccountAccess::from_ids(
+            user.company_account_ids
+                .get(index)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+        );
+        true
+    }
+}
+
+/// Each event carries the id of the record it is about as `{"id": "..."}`.
+/// A listener that fell too far behind gets a `resync` event instead of the
+/// ones it missed and should reload everything. Admins only, like the
+/// finance pages the events come from, and only about the records those
+/// pages show them: a user limited to some accounts hears of the
+/// transactions and planned entries on those. The session is checked again
+/// every `SESSION_RECHECK`, busy or not, and the stream ends once it no
+/// longer holds.
+pub async fn events(
+    session: SessionUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if !session.can_view_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let listener = Listener {
+        rx: state.events.subscribe(),
+        state,
+        token: session.token().to_string(),
+        company_id: *session.active_company_id(),
+        access: session.account_access(),
+        checked_at: Instant::now(),
+    };
+    let stream = stream::unfold(listener, |mut listener| async move {
+        loop {
+            let recheck_at = listener.checked_at + SESSION_RECHECK;
+            let received = tokio::select! {
+                biased;
+                _ = sleep_until(recheck_at) => {
+                    if !listener.session_holds().await {
+                        return None;
+                    }
+                    continue;
+                }
+                received = listener.rx.recv() => received,
+            };
+            match received {
+                Ok(event)
+                    if event.company_id == listener.company_id
+                        && listener.access.allows_event(&event) =>
+                {
+                    let sse = Event::default()
+                        .event(event.kind.as_str())
+                        .data(json!({ "id": event.id.to_hex() }).to_string());
+                    return Some((Ok(sse), listener));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => {
+                    return Some((Ok(Event::default().event("resync").data("{}")), listener));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,118 @@
+// routes/form_fields.rs
+// Serde helpers for the id fields of forms and JSON payloads. A select left
+// on its blank option posts an empty string, and pasted ids may carry spaces;
+// every entity reads them the same way through these.
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Deserializer, de::Error};
+
+/// An optional id: missing, `null`, blank or whitespace-only is `None`, and
+/// anything else must be an `ObjectId` once trimmed. Use it with
+/// `#[serde(default, deserialize_with = "optional_object_id")]` so an absent
+/// field is `None` too.
+pub fn optional_object_id<'de, D>(deserializer: D) -> Result<Option<ObjectId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => parse_optional_object_id(&value).map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// A list of ids, such as a multiple select or a JSON array: blank values are
+/// skipped and the rest must be `ObjectId`s once trimmed. Use it with
+/// `#[serde(default, deserialize_with = "object_ids")]`.
+pub fn object_ids<'de, D>(deserializer: D) -> Result<Vec<ObjectId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut ids = Vec::new();
+    for value in Vec::<String>::deserialize(deserializer)? {
+        if let Some(id) = parse_optional_object_id(&value).map_err(D::Error::custom)? {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// What `optional_object_id` does with one value, for fields read by hand
+/// (multipart uploads, repeated form keys).
+pub fn parse_optional_object_id(value: &str) -> Result<Option<ObjectId>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    ObjectId::parse_str(value)
+        .map(Some)
+        .map_err(|_| format!("invalid id `{value}`"))
+}
+
+/// The hex of an optional id, or an empty string to select nothing.
+pub fn id_or_empty(id: Option<ObjectId>) -> String {
+    id.map(|id| id.to_hex()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Picked {
+        #[serde(default, deserialize_with = "optional_object_id")]
+        contact_id: Option<ObjectId>,
+    }
+
+    fn picked(query: &str) -> Result<Option<ObjectId>, String> {
+        let pairs: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let json = serde_json::Value::Object(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k, serde_json::Value::String(v)))
+                .collect(),
+        );
+        serde_json::from_value::<Picked>(json)
+            .map(|p| p.contact_id)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn blank_selects_are_none_and_ids_are_trimmed() {
+        let id = ObjectId::new();
+        assert_eq!(picked(""), Ok(None));
+        assert_eq!(picked("contact_id="), Ok(None));
+        assert_eq!(picked("contact_id=+++"), Ok(None));
+        assert_eq!(picked(&format!("contact_id=+{id}+")), Ok(Some(id)));
+        assert!(picked("contact_id=acme").unwrap_err().contains("invalid id `acme`"));
+        assert_eq!(
+            serde_json::from_str::<Picked>(r#"{"contact_id":null}"#)
+                .unwrap()
+                .contact_id,
+            None
+        );
+        assert_eq!(id_or_empty(Some(id)), id.to_hex());
+        assert_eq!(id_or_empty(None), "");
+    }
+
+    #[derive(Deserialize)]
+    struct Listed {
+        #[serde(default, deserialize_with = "object_ids")]
+        account_ids: Vec<ObjectId>,
+    }
+
+    #[test]
+    fn id_lists_skip_blanks_and_reject_bad_ids() {
+        let id = ObjectId::new();
+        let listed = |json: &str| serde_json::from_str::<Listed>(json).map(|l| l.account_ids);
+        assert_eq!(listed("{}").unwrap(), Vec::<ObjectId>::new());
+        assert_eq!(
+            listed(&format!(r#"{{"account_ids":["", " {id} "]}}"#)).unwrap(),
+            vec![id]
+        );
+        assert!(listed(r#"{"account_ids":["acme"]}"#).is_err());
+        assert_eq!(parse_optional_object_id("  "), Ok(None));
+        assert_eq!(parse_optional_object_id(&id.to_hex()), Ok(Some(id)));
+    }
+}
@@ -1,16 +1,52 @@
 // routes/home.rs
 // GET / -> renders the login page using Askama templates.
 
+use std::sync::Arc;
+
 use askama::Template;
-use axum::{http::StatusCode, response::Html};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Html,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    oidc::OidcConfig,
+    session::{optional_template_context, safe_return_path},
+    state::AppState,
+    template_context::with_template_context,
+};
 
 #[derive(Template)]
 #[template(path = "home.html")]
-struct HomeTemplate;
+struct HomeTemplate {
+    /// Label of the SSO button; `None` hides it when OIDC is not configured.
+    sso_provider: Option<String>,
+    /// Page to return to after signing in, sent along with the code.
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HomeQuery {
+    #[serde(default)]
+    next: Option<String>,
+}
 
-pub async fn home() -> Result<Html<String>, StatusCode> {
-    HomeTemplate
-        .render()
+/// A signed-in user gets the navigation of their role, as on any other page.
+pub async fn home(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<HomeQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let template = HomeTemplate {
+        sso_provider: OidcConfig::from_env().map(|config| config.provider_name),
+        next: query.next.as_deref().and_then(safe_return_path),
+    };
+    let context = optional_template_context(&state, &headers).await;
+    with_template_context(context, async { template.render() })
+        .await
         .map(Html)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
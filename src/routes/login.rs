@@ -9,10 +9,19 @@ use axum::{
 use serde::Deserialize;
 use std::{env, net::IpAddr, sync::Arc};
 
-use crate::session::SESSION_COOKIE_NAME;
-use crate::state::{AppState, SESSION_TTL_SECONDS, create_session, find_user};
+use crate::metrics::metrics;
+use crate::models::{AccessEventKind, LoginLinkMode};
+use crate::session::{REFRESH_COOKIE_NAME, SESSION_COOKIE_NAME, client_ip, safe_return_path};
+use crate::state::{
+    AppState, UserWithCompany, create_refresh_token, create_session, find_user, login_link_mode,
+    record_access_event, remember_me_ttl_seconds, session_ttl_seconds,
+};
 use crate::totp::build_totp;
 
+/// Answer to a TOTP login of staff whose company only lets them in by link.
+const LINK_ONLY_MESSAGE: &str =
+    "Tu compañía usa enlaces de acceso: pide uno con tu correo para entrar.";
+
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct LoginRequest {
     /// Login identifier. `email` is accepted as an alias during the rename
@@ -20,9 +29,18 @@ pub struct LoginRequest {
     #[serde(alias = "email")]
     pub username: String,
     pub code: String,
+    /// "Remember me": also issue a long-lived refresh token, so the browser
+    /// stays signed in after the session expires.
+    #[serde(default)]
+    pub remember: bool,
+    /// Page the user was sent to sign in from; returned in `redirect_url`
+    /// when it is a page of the app.
+    #[serde(default)]
+    pub next: Option<String>,
 }
 
 /// Verifies the current TOTP code with a small skew (±1 step) defined in TOTP::new().
+/// Staff of a company that only allows sign-in links are turned away.
 #[utoipa::path(
     post,
     path = "/login",
@@ -40,19 +58,72 @@ pub async fn login(
     headers: HeaderMap,
     Json(body): Json<LoginRequest>,
 ) -> Response {
-    match find_user(&st, &body.username).await {
+    let found = find_user(&st, &body.username).await;
+    let links_only = match &found {
+        Ok(Some(user)) if user.is_active => match login_link_mode(&st, user).await {
+            Ok(mode) => mode == LoginLinkMode::Alternative,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": "login failed" })),
+                )
+                    .into_response();
+            }
+        },
+        _ => false,
+    };
+    match found {
+        Ok(Some(user)) if !user.is_active => {
+            record_login(
+                &st,
+                AccessEventKind::LoginFailed,
+                &user,
+                &headers,
+                "Usuario desactivado",
+            )
+            .await;
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "ok": false })),
+            )
+                .into_response()
+        }
+        Ok(Some(user)) if links_only => {
+            record_login(
+                &st,
+                AccessEventKind::LoginFailed,
+                &user,
+                &headers,
+                "Solo con enlace de acceso",
+            )
+            .await;
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "ok": false, "error": LINK_ONLY_MESSAGE })),
+            )
+                .into_response()
+        }
         Ok(Some(user)) => match build_totp(&user.company_name, &user.username, &user.secret) {
             Ok(totp) => {
                 let ok = totp.check_current(&body.code).unwrap_or(false);
                 if ok {
                     match create_session(&st, &user.username).await {
                         Ok(token) => {
-                            let redirect_url = compute_redirect_url(
+                            record_login(
+                                &st,
+                                AccessEventKind::SessionCreated,
+                                &user,
+                                &headers,
+                                "TOTP",
+                            )
+                            .await;
+                            let redirect_url = login_redirect_url(
                                 headers
                                     .get("host")
                                     .and_then(|h| h.to_str().ok())
                                     .unwrap_or("localhost"),
                                 &user.company_slug,
+                                body.next.as_deref(),
                             );
                             let mut response = (
                                 StatusCode::OK,
@@ -67,6 +138,17 @@ pub async fn login(
                                 .and_then(|h| h.to_str().ok())
                                 .unwrap_or("localhost");
                             set_cookies_for_host(&mut response, &token, host, &user.company_slug);
+                            if body.remember {
+                                match create_refresh_token(&st, &user.id).await {
+                                    Ok(refresh) => set_refresh_cookies_for_host(
+                                        &mut response,
+                                        &refresh,
+                                        host,
+                                        &user.company_slug,
+                                    ),
+                                    Err(e) => eprintln!("[login] refresh token error: {e}"),
+                                }
+                            }
                             response
                         }
                         Err(e) => (
@@ -76,6 +158,14 @@ pub async fn login(
                             .into_response(),
                     }
                 } else {
+                    record_login(
+                        &st,
+                        AccessEventKind::LoginFailed,
+                        &user,
+                        &headers,
+                        "Código TOTP incorrecto",
+                    )
+                    .await;
                     (
                         StatusCode::UNAUTHORIZED,
                         Json(serde_json::json!({ "ok": false })),
@@ -101,7 +191,69 @@ pub async fn login(
             .into_response(),
     }
 }
-fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug: &str) {
+
+/// Adds the login attempt of a known user to the access log of each of their
+/// companies and to the login metrics. Unknown usernames belong to no company
+/// and are not logged.
+pub(crate) async fn record_login(
+    state: &AppState,
+    kind: AccessEventKind,
+    user: &UserWithCompany,
+    headers: &HeaderMap,
+    detail: &str,
+) {
+    metrics().record_login(kind == AccessEventKind::SessionCreated);
+    if let Err(err) = record_access_event(
+        state,
+        kind,
+        user.company_ids.clone(),
+        Some(user.id),
+        &user.username,
+        client_ip(headers),
+        Some(detail.to_string()),
+    )
+    .await
+    {
+        eprintln!("[access log] failed to record login: {err}");
+    }
+}
+
+pub(crate) fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug: &str) {
+    append_cookies_for_host(
+        response,
+        SESSION_COOKIE_NAME,
+        token,
+        session_ttl_seconds(),
+        host,
+        slug,
+    );
+}
+
+/// The "remember me" refresh cookie, set on the same hosts as the session.
+pub(crate) fn set_refresh_cookies_for_host(
+    response: &mut Response,
+    token: &str,
+    host: &str,
+    slug: &str,
+) {
+    append_cookies_for_host(
+        response,
+        REFRESH_COOKIE_NAME,
+        token,
+        remember_me_ttl_seconds(),
+        host,
+        slug,
+    );
+}
+
+fn append_cookies_for_host(
+    response: &mut Response,
+    name: &str,
+    token: &str,
+    max_age: u64,
+    host: &str,
+    slug: &str,
+) {
     let host_base = host
         .split(':')
         .next()
@@ -111,7 +263,7 @@ fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug:
     // Host-only cookie (current host)
     if let Ok(header_value) = HeaderValue::from_str(&format!(
         "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
-        SESSION_COOKIE_NAME, token, SESSION_TTL_SECONDS
+        name, token, max_age
     )) {
         response.headers_mut().append(SET_COOKIE, header_value);
     }
@@ -120,7 +272,7 @@ fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug:
     for domain in [format!(".{}", host_base), host_base.to_string()] {
         if let Ok(header_value) = HeaderValue::from_str(&format!(
             "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}; Domain={}",
-            SESSION_COOKIE_NAME, token, SESSION_TTL_SECONDS, domain
+            name, token, max_age, domain
         )) {
             response.headers_mut().append(SET_COOKIE, header_value);
         }
@@ -132,7 +284,7 @@ fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug:
         for domain in [format!(".{}", root_no_dot), root_no_dot.to_string()] {
             if let Ok(header_value) = HeaderValue::from_str(&format!(
                 "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}; Domain={}",
-                SESSION_COOKIE_NAME, token, SESSION_TTL_SECONDS, domain
+                name, token, max_age, domain
             )) {
                 response.headers_mut().append(SET_COOKIE, header_value);
             }
@@ -144,7 +296,7 @@ fn set_cookies_for_host(response: &mut Response, token: &str, host: &str, slug:
             for domain in [slug_host.clone(), format!(".{}", slug_host)] {
                 if let Ok(header_value) = HeaderValue::from_str(&format!(
                     "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}; Domain={}",
-                    SESSION_COOKIE_NAME, token, SESSION_TTL_SECONDS, domain
+                    name, token, max_age, domain
                 )) {
                     response.headers_mut().append(SET_COOKIE, header_value);
                 }
@@ -191,7 +343,7 @@ pub fn compute_cookie_domain(host: &str) -> Option<String> {
     compute_root_domain(base)
 }
 
-fn compute_redirect_url(host: &str, slug: &str) -> Option<String> {
+pub(crate) fn compute_redirect_url(host: &str, slug: &str) -> Option<String> {
     if slug.is_empty() {
         return None;
     }
@@ -210,6 +362,17 @@ fn compute_redirect_url(host: &str, slug: &str) -> Option<String> {
     Some(format!("{}://{}{}", scheme, target_host, port))
 }
 
+/// Where the browser goes after signing in: the page it came from when that
+/// is a safe return path, on the company subdomain when the login happened
+/// elsewhere.
+pub(crate) fn login_redirect_url(host: &str, slug: &str, next: Option<&str>) -> Option<String> {
+    let next = next.and_then(safe_return_path);
+    match (compute_redirect_url(host, slug), next) {
+        (Some(base), Some(next)) => Some(format!("{base}{next}")),
+        (base, next) => base.or(next),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,4 +430,29 @@ mod tests {
         assert_eq!(compute_redirect_url("acme.miapp.local:8090", "acme"), None);
         assert_eq!(compute_redirect_url("miapp.local:8090", ""), None);
     }
+
+    #[test]
+    fn login_returns_to_the_requested_page() {
+        let _guard = env_lock();
+        unsafe {
+            env::remove_var("BASE_DOMAIN");
+        }
+
+        assert_eq!(
+            login_redirect_url("acme.miapp.local:8090", "acme", Some("/admin/accounts?q=1")),
+            Some("/admin/accounts?q=1".into())
+        );
+        assert_eq!(
+            login_redirect_url("miapp.local:8090", "acme", Some("/tiempo")),
+            Some("http://acme.miapp.local:8090/tiempo".into())
+        );
+        assert_eq!(
+            login_redirect_url("acme.miapp.local:8090", "acme", Some("//evil.test/admin")),
+            None
+        );
+        assert_eq!(
+            login_redirect_url("miapp.local:8090", "acme", Some("https://evil.test")),
+            Some("http://acme.miapp.local:8090".into())
+        );
+    }
 }
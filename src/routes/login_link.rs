@@ -0,0 +1,148 @@
+// routes/login_link.rs
+// POST /login/link { "username": "..." } -> { "ok": true } and a link by email
+// GET  /login/link?token=...            -> session cookie and redirect
+//
+// Sign-in by emailed link for staff of companies that allow it (see
+// `LoginLinkMode`). The request answers the same whether or not a link was
+// sent, so it does not tell which usernames exist.
+
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Json, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    mailer::{Email, app_link},
+    models::AccessEventKind,
+    routes::login::{login_redirect_url, record_login, set_cookies_for_host},
+    session::safe_return_path,
+    state::{AppState, LOGIN_LINK_TTL_SECONDS, redeem_login_link, request_login_link},
+};
+
+#[derive(Template)]
+#[template(path = "login_link_error.html")]
+struct LoginLinkErrorTemplate {
+    message: String,
+}
+
+/// Emails a sign-in link to `username`. The token only travels in the
+/// message, never in the log.
+pub(crate) async fn send_login_link(
+    state: &AppState,
+    username: &str,
+    token: &str,
+) -> anyhow::Result<()> {
+    let email = Email {
+        to: username.to_string(),
+        subject: "Tu enlace de acceso".to_string(),
+        text: format!(
+            "Abre este enlace para entrar. Sirve una sola vez y caduca en {} minutos:\n\n{}",
+            LOGIN_LINK_TTL_SECONDS / 60,
+            app_link(&format!("/login/link?token={token}"))
+        ),
+    };
+    state.mailer.send(&email).await
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct LoginLinkRequest {
+    #[serde(alias = "email")]
+    pub username: String,
+    /// Page to open once the link is used, when it is a page of the app.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// Sends a one-time sign-in link to staff whose company allows it. Always
+/// answers `ok`.
+#[utoipa::path(
+    post,
+    path = "/login/link",
+    tag = "auth",
+    request_body = LoginLinkRequest,
+    responses(
+        (status = 200, description = "Link sent if the user may sign in with one")
+    )
+)]
+pub async fn login_link_request(
+    State(st): State<Arc<AppState>>,
+    Json(body): Json<LoginLinkRequest>,
+) -> Response {
+    let next = body.next.as_deref().and_then(safe_return_path);
+    match request_login_link(&st, &body.username, next).await {
+        Ok(link) => {
+            if let Some((user, token)) = link
+                && let Err(e) = send_login_link(&st, &user.username, &token).await
+            {
+                eprintln!("[login link] not sent to {}: {e}", user.username);
+            }
+            Json(serde_json::json!({ "ok": true })).into_response()
+        }
+        Err(e) => {
+            eprintln!("[login link] request error: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "login link failed" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginLinkQuery {
+    #[serde(default)]
+    token: String,
+}
+
+/// Redeems a sign-in link: opens a session and goes to the page the link was
+/// asked from, on the company subdomain.
+pub async fn login_link_auth(
+    State(st): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<LoginLinkQuery>,
+) -> Response {
+    let redeemed = match redeem_login_link(&st, query.token.trim()).await {
+        Ok(Some(redeemed)) => redeemed,
+        Ok(None) => {
+            return LoginLinkErrorTemplate {
+                message: "El enlace ya se usó o caducó. Pide uno nuevo.".to_string(),
+            }
+            .render()
+            .map(|html| (StatusCode::BAD_REQUEST, Html(html)).into_response())
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+        Err(e) => {
+            eprintln!("[login link] redeem error: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let user = &redeemed.user;
+    record_login(
+        &st,
+        AccessEventKind::SessionCreated,
+        user,
+        &headers,
+        "Enlace de acceso",
+    )
+    .await;
+    let host = headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    let target = login_redirect_url(host, &user.company_slug, redeemed.next.as_deref())
+        .unwrap_or_else(|| "/".to_string());
+    let mut response = Redirect::to(&target).into_response();
+    set_cookies_for_host(
+        &mut response,
+        &redeemed.session_token,
+        host,
+        &user.company_slug,
+    );
+    response
+}
@@ -1,5 +1,6 @@
 // routes/logout.rs
-// POST /logout -> clears session cookie and removes the session entry.
+// POST /logout -> clears the session and "remember me" cookies and removes
+// the session and refresh token entries.
 
 use axum::{
     Json,
@@ -10,8 +11,8 @@ use axum::{
 use std::sync::Arc;
 
 use crate::routes::login::compute_cookie_domain;
-use crate::session::SessionUser;
-use crate::state::{AppState, delete_session};
+use crate::session::{REFRESH_COOKIE_NAME, SESSION_COOKIE_NAME, SessionUser, extract_cookies};
+use crate::state::{AppState, delete_refresh_token, delete_session};
 
 #[utoipa::path(
     post,
@@ -29,41 +30,42 @@ pub async fn logout(
     headers: HeaderMap,
     session: SessionUser,
 ) -> Response {
-    let delete_result = delete_session(&st, session.token()).await;
+    let mut delete_result = delete_session(&st, session.token()).await;
+    for refresh in extract_cookies(&headers, REFRESH_COOKIE_NAME) {
+        if delete_result.is_ok() {
+            delete_result = delete_refresh_token(&st, &refresh).await;
+        }
+    }
 
     let host = headers
         .get("host")
         .and_then(|h| h.to_str().ok())
         .unwrap_or("localhost");
     let domain = compute_cookie_domain(host);
-    let host_cookie = format!(
-        "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
-        crate::session::SESSION_COOKIE_NAME,
-    );
-    let domain_cookies: Vec<String> = match domain {
-        Some(d) => vec![
-            format!(
+    let mut cookies = Vec::new();
+    for name in [SESSION_COOKIE_NAME, REFRESH_COOKIE_NAME] {
+        cookies.push(format!(
+            "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
+            name
+        ));
+        if let Some(d) = &domain {
+            cookies.push(format!(
                 "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0; Domain={}",
-                crate::session::SESSION_COOKIE_NAME,
-                d
-            ),
-            format!(
+                name, d
+            ));
+            cookies.push(format!(
                 "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0; Domain=.{}",
-                crate::session::SESSION_COOKIE_NAME,
+                name,
                 d.trim_start_matches('.')
-            ),
-        ],
-        None => Vec::new(),
-    };
+            ));
+        }
+    }
 
     match delete_result {
         Ok(_) => {
             let mut response =
                 (StatusCode::OK, Json(serde_json::json!({ "ok": true }))).into_response();
-            if let Ok(header_value) = HeaderValue::from_str(&host_cookie) {
-                response.headers_mut().append(SET_COOKIE, header_value);
-            }
-            for dc in domain_cookies.iter() {
+            for dc in cookies.iter() {
                 if let Ok(header_value) = HeaderValue::from_str(dc) {
                     response.headers_mut().append(SET_COOKIE, header_value);
                 }
@@ -76,10 +78,7 @@ pub async fn logout(
                 Json(serde_json::json!({ "error": format!("session error: {e}") })),
             )
                 .into_response();
-            if let Ok(header_value) = HeaderValue::from_str(&host_cookie) {
-                response.headers_mut().append(SET_COOKIE, header_value);
-            }
-            for dc in domain_cookies.iter() {
+            for dc in cookies.iter() {
                 if let Ok(header_value) = HeaderValue::from_str(dc) {
                     response.headers_mut().append(SET_COOKIE, header_value);
                 }
@@ -0,0 +1,38 @@
+// routes/metrics.rs
+// GET /metrics -> Prometheus metrics of the whole service (HTTP requests,
+// MongoDB latencies, logins, generated planned entries).
+
+use axum::{
+    Extension,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    metrics::metrics as registry,
+    session::{MetricsScrape, SessionUser},
+};
+
+/// The figures cover every company: scrapers send the `METRICS_TOKEN` bearer
+/// and people open it with a superadmin session. Personal access tokens are
+/// not accepted.
+pub async fn metrics(
+    scrape: Option<Extension<MetricsScrape>>,
+    session: Result<SessionUser, Response>,
+) -> Response {
+    if scrape.is_none() {
+        match session {
+            Ok(session) if session.is_superadmin() => {}
+            Ok(_) => return StatusCode::FORBIDDEN.into_response(),
+            Err(rejection) => return rejection,
+        }
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        registry().render(),
+    )
+        .into_response()
+}
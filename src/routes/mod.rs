@@ -1,28 +1,120 @@
 // routes/mod.rs
-// Public re-exports of all route handlers.
+// Public re-exports of all route handlers, and the routers built from them.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::{state::AppState, uploads::BodyLimits};
 
 pub mod admin;
+pub mod dashboard;
+#[cfg(feature = "dev-factory")]
+pub mod dev_factory;
+pub mod events;
+pub mod form_fields;
 pub mod home;
 pub mod login;
+pub mod login_link;
 pub mod logout;
+pub mod metrics;
+pub mod overview;
 pub mod pdf;
+pub mod portal;
 pub mod profile;
 pub mod qrcode;
+mod registry;
 pub mod sat;
+pub mod schema;
 pub mod secret;
+pub mod session_status;
 pub mod setup;
+pub mod sso;
+pub mod status;
 pub mod test_dashboard;
 pub mod tiempo;
 
 pub use admin::*;
+pub use dashboard::{dashboard_layout, dashboard_layout_update};
+#[cfg(feature = "dev-factory")]
+pub use dev_factory::dev_factory;
+pub use events::events;
 pub use home::home;
 pub use login::login;
+pub use login_link::{login_link_auth, login_link_request};
 pub use logout::logout;
+pub use metrics::metrics;
+pub use overview::{overview, overview_consolidated};
 pub use pdf::*;
+pub use portal::portal_router;
 pub use profile::{me, me_companies};
 pub use qrcode::qrcode;
+pub use registry::Routes;
 pub use sat::sat_cfdi_download;
+pub use schema::model_schema;
 pub use secret::secret_generate;
+pub use session_status::{session_renew, session_status};
 pub use setup::setup;
+pub use sso::{sso_callback, sso_link, sso_login, sso_unlink};
+pub use status::status;
 pub use test_dashboard::test_dashboard;
 pub use tiempo::{tiempo_data, tiempo_page};
+
+/// Routes open without a session: sign-in, the status page and the contact
+/// portal.
+pub fn public_router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(home))
+        .route("/login", post(login))
+        .route("/login/link", get(login_link_auth).post(login_link_request))
+        .route("/sso/login", get(sso_login))
+        .route("/sso/callback", get(sso_callback))
+        .route("/status", get(status))
+        .merge(portal_router(state))
+}
+
+/// Every route behind the session middleware. The pages outside `/admin` are
+/// listed here; the admin modules register their own.
+pub fn protected_routes(limits: &BodyLimits) -> Routes {
+    let routes = Routes::new()
+        .route("/setup", get(setup))
+        .route("/qrcode", get(qrcode))
+        .route("/secret", get(secret_generate))
+        .route("/api/tiempo", get(tiempo_data))
+        .route("/api/sat/cfdi/download", post(sat_cfdi_download))
+        .route("/logout", post(logout))
+        .route("/account/sso/{id}/unlink", post(sso_unlink))
+        .route("/sso/link", get(sso_link))
+        .route("/pdf", get(pdf_editor))
+        .route("/pdf/preview", post(pdf_preview))
+        .route("/tiempo", get(tiempo_page))
+        .route("/overview", get(overview))
+        .route("/overview/consolidated", get(overview_consolidated))
+        .route("/events", get(events))
+        .route("/metrics", get(metrics))
+        .route("/api/me", get(me))
+        .route("/api/me/companies", get(me_companies))
+        .route(
+            "/api/me/dashboard",
+            get(dashboard_layout).post(dashboard_layout_update),
+        )
+        .route("/api/session", get(session_status))
+        .route("/api/session/renew", post(session_renew))
+        .route("/api/v1/schema", get(model_schema))
+        .merge(admin::router(limits));
+    dev_routes(routes)
+}
+
+/// Fake data for manual QA and load tests, only in `dev-factory` builds.
+#[cfg(feature = "dev-factory")]
+fn dev_routes(routes: Routes) -> Routes {
+    routes.route("/dev/factory/{entity}", post(dev_factory))
+}
+
+#[cfg(not(feature = "dev-factory"))]
+fn dev_routes(routes: Routes) -> Routes {
+    routes
+}
@@ -0,0 +1,448 @@
+// routes/overview.rs
+// GET /overview -> rollup of cash position, overdue commitments and this
+// month's net across every company the user belongs to.
+// GET /overview/consolidated -> actual and projected flows of a group of
+// companies the user administers, converted to one currency.
+
+use std::{collections::HashMap, sync::Arc};
+
+use askama::Template;
+use axum::{
+    extract::{RawQuery, State},
+    http::{HeaderMap, StatusCode},
+    response::Html,
+};
+use chrono::{Duration, NaiveDate};
+use mongodb::bson::{DateTime, oid::ObjectId};
+
+#[allow(unused_imports)]
+use crate::filters;
+
+use crate::{
+    routes::login::compute_redirect_url,
+    session::SessionUser,
+    state::{
+        AppState, CompanyPeriodFigures, company_overview, company_period_figures, conversion_rate,
+        month_bounds, onboarding_checklist,
+    },
+};
+
+#[derive(Template)]
+#[template(path = "overview/index.html")]
+struct OverviewTemplate {
+    totals: Vec<CurrencyTotals>,
+    companies: Vec<CompanyCard>,
+    /// Getting-started checklist of the active company, for its admins and
+    /// until every step is done.
+    onboarding: Option<OnboardingCard>,
+}
+
+struct OnboardingCard {
+    done: usize,
+    steps: Vec<OnboardingItem>,
+}
+
+struct OnboardingItem {
+    label: &'static str,
+    href: &'static str,
+    done: bool,
+}
+
+/// Sums per currency; companies in different currencies are never added up.
+struct CurrencyTotals {
+    currency: String,
+    cash_position: f64,
+    overdue_count: u64,
+    overdue_amount: f64,
+    month_net: f64,
+}
+
+struct CompanyCard {
+    name: String,
+    active: bool,
+    /// Base URL of the company's subdomain; empty when it is the current host.
+    base_url: String,
+    /// Finance numbers are only shown where the user is an admin, matching the
+    /// access rules of the finance pages themselves.
+    figures: Option<CompanyFigures>,
+}
+
+struct CompanyFigures {
+    currency: String,
+    cash_position: f64,
+    overdue_count: u64,
+    overdue_amount: f64,
+    month_net: f64,
+}
+
+pub async fn overview(
+    session: SessionUser,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Html<String>, StatusCode> {
+    let host = headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    let now = DateTime::now();
+    let user = session.user();
+
+    let mut totals: Vec<CurrencyTotals> = Vec::new();
+    let mut companies = Vec::new();
+    let mut onboarding = None;
+    for (idx, company_id) in user.company_ids.iter().enumerate() {
+        let slug = user.company_slugs.get(idx).cloned().unwrap_or_default();
+        let is_admin = user
+            .company_roles
+            .get(idx)
+            .map(|role| role.can_view_admin())
+            .unwrap_or(false);
+
+        let figures = if is_admin {
+            let summary = company_overview(&state, company_id, now)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            match totals.iter_mut().find(|t| t.currency == summary.currency) {
+                Some(total) => {
+                    total.cash_position += summary.cash_position;
+                    total.overdue_count += summary.overdue_count;
+                    total.overdue_amount += summary.overdue_amount;
+                    total.month_net += summary.month_net;
+                }
+                None => totals.push(CurrencyTotals {
+                    currency: summary.currency.clone(),
+                    cash_position: summary.cash_position,
+                    overdue_count: summary.overdue_count,
+                    overdue_amount: summary.overdue_amount,
+                    month_net: summary.month_net,
+                }),
+            }
+            Some(CompanyFigures {
+                currency: summary.currency,
+                cash_position: summary.cash_position,
+                overdue_count: summary.overdue_count,
+                overdue_amount: summary.overdue_amount,
+                month_net: summary.month_net,
+            })
+        } else {
+            None
+        };
+
+        if is_admin && company_id == session.active_company_id() {
+            onboarding = onboarding_checklist(&state, company_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .map(|checklist| OnboardingCard {
+                    done: checklist.done_count(),
+                    steps: checklist
+                        .steps
+                        .iter()
+                        .map(|(step, done)| OnboardingItem {
+                            label: step.label(),
+                            href: step.href(),
+                            done: *done,
+                        })
+                        .collect(),
+                });
+        }
+
+        companies.push(CompanyCard {
+            name: user.company_names.get(idx).cloned().unwrap_or_default(),
+            active: company_id == session.active_company_id(),
+            base_url: compute_redirect_url(host, &slug).unwrap_or_default(),
+            figures,
+        });
+    }
+
+    companies.sort_by_key(|c| c.name.to_lowercase());
+    totals.sort_by(|a, b| a.currency.cmp(&b.currency));
+
+    OverviewTemplate {
+        totals,
+        companies,
+        onboarding,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Template)]
+#[template(path = "overview/consolidated.html")]
+struct ConsolidatedTemplate {
+    companies: Vec<CompanyChoice>,
+    currencies: Vec<String>,
+    currency: String,
+    rates: Vec<RateField>,
+    from: String,
+    to: String,
+    error: Option<String>,
+    rows: Vec<ConsolidatedRow>,
+    total: ConsolidatedAmounts,
+}
+
+struct CompanyChoice {
+    id: String,
+    name: String,
+    currency: String,
+    selected: bool,
+}
+
+/// Rate input for a currency other than the reporting one.
+struct RateField {
+    currency: String,
+    value: String,
+}
+
+struct ConsolidatedRow {
+    name: String,
+    currency: String,
+    rate: f64,
+    amounts: ConsolidatedAmounts,
+}
+
+/// Flows converted to the reporting currency.
+#[derive(Default)]
+struct ConsolidatedAmounts {
+    income: f64,
+    expense: f64,
+    net: f64,
+    projected_income: f64,
+    projected_expense: f64,
+    projected_net: f64,
+}
+
+impl ConsolidatedAmounts {
+    fn converted(figures: &CompanyPeriodFigures, rate: f64) -> Self {
+        Self {
+            income: figures.income * rate,
+            expense: figures.expense * rate,
+            net: (figures.income - figures.expense) * rate,
+            projected_income: figures.projected_income * rate,
+            projected_expense: figures.projected_expense * rate,
+            projected_net: (figures.projected_income - figures.projected_expense) * rate,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.income += other.income;
+        self.expense += other.expense;
+        self.net += other.net;
+        self.projected_income += other.projected_income;
+        self.projected_expense += other.projected_expense;
+        self.projected_net += other.projected_net;
+    }
+}
+
+/// Query of the consolidated report. `company` repeats once per selected
+/// company and each `rate_<CUR>` gives units of the reporting currency per
+/// unit of `<CUR>`, so it is parsed by hand instead of through `Query`.
+#[derive(Debug, Default, PartialEq)]
+struct ConsolidatedParams {
+    companies: Vec<String>,
+    currency: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    rates: HashMap<String, String>,
+}
+
+fn parse_consolidated_query(query: &str) -> ConsolidatedParams {
+    let mut params = ConsolidatedParams::default();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let value = value.trim().to_string();
+        match key.as_ref() {
+            "company" => params.companies.push(value),
+            "currency" if !value.is_empty() => params.currency = Some(value),
+            "from" if !value.is_empty() => params.from = Some(value),
+            "to" if !value.is_empty() => params.to = Some(value),
+            key => {
+                if let Some(currency) = key.strip_prefix("rate_") {
+                    params.rates.insert(currency.to_string(), value);
+                }
+            }
+        }
+    }
+    params
+}
+
+pub async fn overview_consolidated(
+    session: SessionUser,
+    State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
+) -> Result<Html<String>, StatusCode> {
+    let user = session.user();
+    let params = parse_consolidated_query(query.as_deref().unwrap_or_default());
+
+    // Only companies the user administers can be consolidated, the same rule
+    // that gates their finance pages.
+    let admin_companies: Vec<(ObjectId, String)> = user
+        .company_ids
+        .iter()
+        .zip(&user.company_names)
+        .zip(&user.company_roles)
+        .filter(|(_, role)| role.can_view_admin())
+        .map(|((id, name), _)| (*id, name.clone()))
+        .collect();
+    if admin_companies.is_empty() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let selected: Vec<ObjectId> = if params.companies.is_empty() {
+        admin_companies.iter().map(|(id, _)| *id).collect()
+    } else {
+        let mut ids = Vec::new();
+        for hex in &params.companies {
+            let id = ObjectId::parse_str(hex).map_err(|_| StatusCode::BAD_REQUEST)?;
+            if !admin_companies.iter().any(|(admin_id, _)| *admin_id == id) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            ids.push(id);
+        }
+        ids
+    };
+
+    let (month_start, month_end) = month_bounds(DateTime::now());
+    let from = params
+        .from
+        .clone()
+        .unwrap_or_else(|| month_start.to_chrono().format("%Y-%m-%d").to_string());
+    let to = params.to.clone().unwrap_or_else(|| {
+        (month_end.to_chrono() - Duration::days(1))
+            .format("%Y-%m-%d")
+            .to_string()
+    });
+    let parse_day = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+    let mut error = None;
+    let period = match (parse_day(&from), parse_day(&to)) {
+        (Some(start), Some(end)) if start <= end => Some((
+            DateTime::from_chrono(start.and_time(chrono::NaiveTime::MIN).and_utc()),
+            // `to` is inclusive in the form.
+            DateTime::from_chrono(
+                (end + Duration::days(1))
+                    .and_time(chrono::NaiveTime::MIN)
+                    .and_utc(),
+            ),
+        )),
+        _ => {
+            error = Some("El periodo no es válido".to_string());
+            None
+        }
+    };
+
+    let (period_start, period_end) = period.unwrap_or((month_start, month_end));
+    let mut figures = Vec::new();
+    for (id, name) in &admin_companies {
+        let company = company_period_figures(&state, id, period_start, period_end)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        figures.push((name.clone(), company));
+    }
+
+    let mut currencies: Vec<String> = figures.iter().map(|(_, f)| f.currency.clone()).collect();
+    currencies.sort();
+    currencies.dedup();
+    let active_currency = figures
+        .iter()
+        .find(|(_, f)| f.company_id == *session.active_company_id())
+        .map(|(_, f)| f.currency.clone());
+    let currency = params
+        .currency
+        .clone()
+        .filter(|c| currencies.contains(c))
+        .or(active_currency)
+        .unwrap_or_else(|| currencies[0].clone());
+
+    let mut rates = HashMap::new();
+    let mut missing = Vec::new();
+    for code in currencies.iter().filter(|c| **c != currency) {
+        let used = figures
+            .iter()
+            .any(|(_, f)| f.currency == *code && selected.contains(&f.company_id));
+        match params.rates.get(code).and_then(|v| v.parse::<f64>().ok()) {
+            Some(rate) if rate.is_finite() && rate > 0.0 => {
+                rates.insert(code.clone(), rate);
+            }
+            _ if used => missing.push(code.clone()),
+            _ => {}
+        }
+    }
+    if error.is_none() && !missing.is_empty() {
+        error = Some(format!(
+            "Indica el tipo de cambio a {currency} de: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let mut rows = Vec::new();
+    let mut total = ConsolidatedAmounts::default();
+    if error.is_none() {
+        for (name, company) in figures
+            .iter()
+            .filter(|(_, f)| selected.contains(&f.company_id))
+        {
+            let Some(rate) = conversion_rate(&company.currency, &currency, &rates) else {
+                continue;
+            };
+            let amounts = ConsolidatedAmounts::converted(company, rate);
+            total.add(&amounts);
+            rows.push(ConsolidatedRow {
+                name: name.clone(),
+                currency: company.currency.clone(),
+                rate,
+                amounts,
+            });
+        }
+        rows.sort_by_key(|row| row.name.to_lowercase());
+    }
+
+    let rate_fields = currencies
+        .iter()
+        .filter(|c| **c != currency)
+        .map(|code| RateField {
+            currency: code.clone(),
+            value: params.rates.get(code).cloned().unwrap_or_default(),
+        })
+        .collect();
+    let mut companies: Vec<CompanyChoice> = figures
+        .iter()
+        .map(|(name, f)| CompanyChoice {
+            id: f.company_id.to_hex(),
+            name: name.clone(),
+            currency: f.currency.clone(),
+            selected: selected.contains(&f.company_id),
+        })
+        .collect();
+    companies.sort_by_key(|c| c.name.to_lowercase());
+
+    ConsolidatedTemplate {
+        companies,
+        currencies,
+        currency,
+        rates: rate_fields,
+        from,
+        to,
+        error,
+        rows,
+        total,
+    }
+    .render()
+    .map(Html)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consolidated_query_collects_companies_and_rates() {
+        let params = parse_consolidated_query(
+            "company=a&company=b&currency=MXN&rate_USD=17.5&rate_EUR=&from=2024-01-01&to=",
+        );
+        assert_eq!(params.companies, vec!["a", "b"]);
+        assert_eq!(params.currency.as_deref(), Some("MXN"));
+        assert_eq!(params.from.as_deref(), Some("2024-01-01"));
+        assert_eq!(params.to, None);
+        assert_eq!(params.rates.get("USD").map(String::as_str), Some("17.5"));
+        assert_eq!(params.rates.get("EUR").map(String::as_str), Some(""));
+    }
+}
@@ -10,11 +10,94 @@ use rand::{Rng, distr::Alphanumeric};
 use serde::{Deserialize, Serialize};
 use tokio::{fs, process::Command, time};
 
-use crate::{session::SessionUser, state::AppState};
+use crate::{
+    models::CompanyBranding,
+    session::SessionUser,
+    state::{AppState, get_company_by_id},
+};
 
 const MAX_TYPST_SOURCE_BYTES: usize = 256 * 1024;
 const TYPST_TIMEOUT_SECONDS: u64 = 10;
 
+/// Company look stamped on every PDF: `preamble` goes before the user's
+/// source, so `set` rules written in the document still take precedence.
+pub(crate) struct PdfBranding {
+    preamble: String,
+    /// File name the preamble refers to, and the logo bytes.
+    logo: Option<(&'static str, Vec<u8>)>,
+}
+
+/// Typst string literal for `value`, to embed text as `#"..."`.
+pub(crate) fn typst_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Header with the logo and company name in its accent color, footer with
+/// the company's text and the page number, and headings in the accent color.
+fn branding_preamble(
+    company_name: &str,
+    branding: &CompanyBranding,
+    logo_file: Option<&str>,
+) -> String {
+    let color = branding
+        .color
+        .as_deref()
+        .map(|color| format!("rgb({})", typst_string(color)))
+        .unwrap_or_else(|| "luma(30)".to_string());
+    let logo = logo_file
+        .map(|file| format!("#image({}, height: 0.8cm) ", typst_string(file)))
+        .unwrap_or_default();
+    let footer = branding
+        .footer_text
+        .as_deref()
+        .map(|text| format!("#{} ", typst_string(text)))
+        .unwrap_or_default();
+    let mut preamble = format!(
+        "#set page(\n  header: [{logo}#h(1fr) #text(fill: {color}, weight: \"bold\")[#{name}]],\n  footer: text(size: 8pt, fill: luma(110))[{footer}#h(1fr) #context counter(page).display()],\n)\n",
+        name = typst_string(company_name),
+    );
+    if branding.color.is_some() {
+        preamble.push_str(&format!("#show heading: set text(fill: {color})\n"));
+    }
+    preamble
+}
+
+/// Branding of the company, when it has any.
+pub(crate) async fn load_pdf_branding(
+    state: &AppState,
+    session_user: &SessionUser,
+) -> Option<PdfBranding> {
+    let company = get_company_by_id(state, session_user.active_company_id())
+        .await
+        .ok()
+        .flatten()?;
+    if company.branding.is_empty() {
+        return None;
+    }
+    let logo = match (
+        &company.branding.logo_path,
+        &company.branding.logo_content_type,
+    ) {
+        (Some(path), Some(content_type)) => {
+            let file = if content_type == "image/png" {
+                "brand-logo.png"
+            } else {
+                "brand-logo.jpg"
+            };
+            fs::read(path).await.ok().map(|bytes| (file, bytes))
+        }
+        _ => None,
+    };
+    Some(PdfBranding {
+        preamble: branding_preamble(
+            &company.name,
+            &company.branding,
+            logo.as_ref().map(|(file, _)| *file),
+        ),
+        logo,
+    })
+}
+
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
     tpl.render()
         .map(Html)
@@ -58,11 +141,12 @@ pub async fn pdf_editor(SessionUser(session): SessionUser) -> Result<Html<String
     security(("session" = []))
 )]
 pub async fn pdf_preview(
-    SessionUser(_session): SessionUser,
-    State(_state): State<Arc<AppState>>,
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
     Json(payload): Json<PdfPreviewRequest>,
 ) -> impl IntoResponse {
-    match compile_typst(&payload.source).await {
+    let branding = load_pdf_branding(&state, &session_user).await;
+    match compile_typst(&payload.source, branding.as_ref()).await {
         Ok(bytes) => {
             let encoded = data_encoding::BASE64.encode(&bytes);
             Json(PdfPreviewResponse {
@@ -79,7 +163,10 @@ pub async fn pdf_preview(
     }
 }
 
-async fn compile_typst(source: &str) -> Result<Vec<u8>, String> {
+pub(crate) async fn compile_typst(
+    source: &str,
+    branding: Option<&PdfBranding>,
+) -> Result<Vec<u8>, String> {
     if source.len() > MAX_TYPST_SOURCE_BYTES {
         return Err("El documento es demasiado grande".to_string());
     }
@@ -101,7 +188,13 @@ async fn compile_typst(source: &str) -> Result<Vec<u8>, String> {
     let input_path = tmp_dir.join("input.typ");
     let output_path = tmp_dir.join("output.pdf");
 
-    let write_result = fs::write(&input_path, source).await;
+    let mut write_result = match branding {
+        Some(branding) => fs::write(&input_path, format!("{}{source}", branding.preamble)).await,
+        None => fs::write(&input_path, source).await,
+    };
+    if let Some((file, bytes)) = branding.and_then(|b| b.logo.as_ref()) {
+        write_result = write_result.and(fs::write(tmp_dir.join(file), bytes).await);
+    }
     if let Err(err) = write_result {
         let _ = fs::remove_dir_all(&tmp_dir).await;
         return Err(format!("No se pudo escribir archivo temporal: {err}"));
@@ -159,3 +252,25 @@ async fn compile_typst(source: &str) -> Result<Vec<u8>, String> {
     let _ = fs::remove_dir_all(&tmp_dir).await;
     Ok(pdf_bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branding_preamble_escapes_company_text() {
+        let branding = CompanyBranding {
+            color: Some("#0369a1".into()),
+            footer_text: Some("RFC \"XAXX\" \\ #1".into()),
+            ..Default::default()
+        };
+        let preamble = branding_preamble("Acme *S.A.*", &branding, Some("brand-logo.png"));
+        assert!(preamble.contains("#image(\"brand-logo.png\", height: 0.8cm)"));
+        assert!(preamble.contains("[#\"Acme *S.A.*\"]"));
+        assert!(preamble.contains("#\"RFC \\\"XAXX\\\" \\\\ #1\""));
+        assert!(preamble.contains("#show heading: set text(fill: rgb(\"#0369a1\"))"));
+
+        let plain = branding_preamble("Acme", &CompanyBranding::default(), None);
+        assert!(!plain.contains("#image") && !plain.contains("#show heading"));
+    }
+}
@@ -0,0 +1,241 @@
+// routes/portal.rs
+// Contact portal under /portal: a customer or supplier signs in with a
+// one-time link sent to their email and sees their own invoices and planned
+// payments. It has its own cookie and middleware (`require_portal_session`),
+// so neither session opens the pages of the other.
+
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    Form, Router,
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header::SET_COOKIE},
+    middleware,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use serde::Deserialize;
+
+use crate::{
+    filters,
+    mailer::{Email, app_link},
+    models::FlowType,
+    routes::admin::finance::helpers::planned_status_label,
+    session::{
+        PORTAL_COOKIE_NAME, PortalContact, require_portal_session, tenant_subdomain_from_host,
+    },
+    state::{
+        AppState, PORTAL_LINK_TTL_SECONDS, PORTAL_SESSION_TTL_SECONDS, PortalInvoice,
+        delete_portal_session, portal_invoices, portal_planned_entries, redeem_portal_link,
+        request_portal_links,
+    },
+};
+
+const LINK_SENT_MESSAGE: &str =
+    "Si el correo está registrado, te enviamos un enlace para entrar. Revisa tu bandeja.";
+
+/// The `/portal` routes: sign-in pages open, the rest behind
+/// `require_portal_session`.
+pub fn portal_router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    let protected = Router::new()
+        .route("/portal", get(portal_index))
+        .route("/portal/logout", post(portal_logout))
+        .route_layer(middleware::from_fn_with_state(
+            state,
+            require_portal_session,
+        ));
+    Router::new()
+        .route(
+            "/portal/login",
+            get(portal_login).post(portal_login_request),
+        )
+        .route("/portal/auth", get(portal_auth))
+        .merge(protected)
+}
+
+fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
+    tpl.render()
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Emails a portal sign-in link to the contact. The token only travels in the
+/// message, never in the log.
+pub(crate) async fn send_portal_link(
+    state: &AppState,
+    email: &str,
+    contact_name: &str,
+    token: &str,
+) -> anyhow::Result<()> {
+    let email = Email {
+        to: email.to_string(),
+        subject: "Tu enlace al portal".to_string(),
+        text: format!(
+            "Hola {contact_name}, abre este enlace para ver tus facturas y pagos. Sirve una sola vez y caduca en {} horas:\n\n{}",
+            PORTAL_LINK_TTL_SECONDS / 3600,
+            app_link(&format!("/portal/auth?token={token}"))
+        ),
+    };
+    state.mailer.send(&email).await
+}
+
+fn portal_cookie(token: &str, max_age: u64) -> Option<HeaderValue> {
+    HeaderValue::from_str(&format!(
+        "{PORTAL_COOKIE_NAME}={token}; Path=/portal; HttpOnly; SameSite=Lax; Max-Age={max_age}"
+    ))
+    .ok()
+}
+
+#[derive(Template)]
+#[template(path = "portal/login.html")]
+struct PortalLoginTemplate {
+    message: Option<String>,
+    errors: Option<String>,
+}
+
+pub async fn portal_login() -> Result<Html<String>, StatusCode> {
+    render(PortalLoginTemplate {
+        message: None,
+        errors: None,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct PortalLoginForm {
+    #[serde(default)]
+    email: String,
+}
+
+/// Sends a link to every contact with the email, limited to the company of the
+/// tenant subdomain when there is one. The answer is the same whether the
+/// email is known or not.
+pub async fn portal_login_request(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<PortalLoginForm>,
+) -> Result<Html<String>, StatusCode> {
+    let tenant = headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .and_then(tenant_subdomain_from_host);
+    let links = request_portal_links(&state, &form.email, tenant)
+        .await
+        .map_err(|e| {
+            eprintln!("[portal] link request error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    // The answer stays the same when a link is not delivered, so it does not
+    // tell which emails are registered.
+    for (contact, token) in links {
+        let email = contact.email.as_deref().unwrap_or_default();
+        if let Err(e) = send_portal_link(&state, email, &contact.name, &token).await {
+            eprintln!("[portal] link not sent to {email}: {e}");
+        }
+    }
+    render(PortalLoginTemplate {
+        message: Some(LINK_SENT_MESSAGE.to_string()),
+        errors: None,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct PortalAuthQuery {
+    #[serde(default)]
+    token: String,
+}
+
+/// Redeems a sign-in link and opens the portal.
+pub async fn portal_auth(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PortalAuthQuery>,
+) -> Response {
+    match redeem_portal_link(&state, query.token.trim()).await {
+        Ok(Some(token)) => {
+            let mut response = Redirect::to("/portal").into_response();
+            if let Some(cookie) = portal_cookie(&token, PORTAL_SESSION_TTL_SECONDS) {
+                response.headers_mut().append(SET_COOKIE, cookie);
+            }
+            response
+        }
+        Ok(None) => render(PortalLoginTemplate {
+            message: None,
+            errors: Some("El enlace ya se usó o caducó. Pide uno nuevo.".to_string()),
+        })
+        .map(|html| (StatusCode::BAD_REQUEST, html).into_response())
+        .unwrap_or_else(|status| status.into_response()),
+        Err(e) => {
+            eprintln!("[portal] link redeem error: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+struct PortalEntryRow {
+    name: String,
+    kind: &'static str,
+    status: &'static str,
+    due_date: String,
+    amount: f64,
+    currency: String,
+}
+
+#[derive(Template)]
+#[template(path = "portal/index.html")]
+struct PortalIndexTemplate {
+    company_name: String,
+    contact_name: String,
+    entries: Vec<PortalEntryRow>,
+    invoices: Vec<PortalInvoice>,
+}
+
+pub async fn portal_index(
+    portal: PortalContact,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let contact = portal.contact();
+    let company = portal.company();
+    let contact_id = contact.id.ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let entries = portal_planned_entries(&state, &contact.company_id, &contact_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let invoices = portal_invoices(&state, &contact.company_id, contact.rfc.as_deref())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| PortalEntryRow {
+            // Seen from the contact: what the company collects, they pay.
+            kind: match entry.flow_type {
+                FlowType::Income => "Por pagar",
+                FlowType::Expense => "Por cobrar",
+            },
+            status: planned_status_label(&entry.status),
+            due_date: entry.due_date.to_chrono().format("%Y-%m-%d").to_string(),
+            amount: entry.amount_estimated,
+            currency: entry
+                .currency
+                .unwrap_or_else(|| company.default_currency.clone()),
+            name: entry.name,
+        })
+        .collect();
+    render(PortalIndexTemplate {
+        company_name: company.name.clone(),
+        contact_name: contact.name.clone(),
+        entries,
+        invoices,
+    })
+}
+
+pub async fn portal_logout(portal: PortalContact, State(state): State<Arc<AppState>>) -> Response {
+    if let Err(e) = delete_portal_session(&state, portal.token()).await {
+        eprintln!("[portal] logout error: {e}");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let mut response = Redirect::to("/portal/login").into_response();
+    if let Some(cookie) = portal_cookie("", 0) {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
@@ -14,6 +14,11 @@ pub struct CompanySummary {
     pub name: String,
     pub slug: String,
     pub active: bool,
+    /// Branding of the company. The logo is served for the active company
+    /// only, at `/branding/logo`, so `logo_url` is set just for that one.
+    pub brand_color: Option<String>,
+    pub footer_text: Option<String>,
+    pub logo_url: Option<String>,
 }
 
 /// Consolidated bootstrap payload: profile + active-tenant role/permissions +
@@ -85,11 +90,16 @@ async fn collect_companies(
             .as_ref()
             .map(|cid| cid == &active_company)
             .unwrap_or(false);
+        let logo_url =
+            (active && company.branding.logo_path.is_some()).then(|| "/branding/logo".to_string());
         companies.push(CompanySummary {
             id: company.id.unwrap().to_hex(),
             name: company.name,
             slug,
             active,
+            brand_color: company.branding.color,
+            footer_text: company.branding.footer_text,
+            logo_url,
         });
     }
 
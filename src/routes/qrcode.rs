@@ -1,42 +1,219 @@
 // routes/qrcode.rs
 // GET /qrcode -> returns a PNG QR code of the otpauth URL for the logged-in user.
+//
+// Rendering a QR code is the most CPU-heavy thing the admin user pages do, so
+// the PNGs are kept in a small in-memory LRU and served with validators
+// (ETag = hash of the otpauth URL, Last-Modified = render time) so browsers
+// revalidate instead of downloading them again.
 
 use crate::session::SessionUser;
 use crate::totp::build_totp;
 use axum::{
-    body::Body,
-    http::StatusCode,
+    body::{Body, Bytes},
+    http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
 use image::{ImageFormat, Luma};
 use qrcode::QrCode;
-use std::io::Cursor;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::VecDeque,
+    io::Cursor,
+    sync::{Mutex, OnceLock},
+};
+
+/// Rendered QR codes kept in memory.
+const QR_CACHE_CAPACITY: usize = 128;
+
+/// The URL embeds the TOTP secret: only the user's browser may store the
+/// image, and it must revalidate on every use.
+const QR_CACHE_CONTROL: &str = "private, no-cache";
+
+#[derive(Clone)]
+struct RenderedQr {
+    etag: String,
+    /// Shared with the responses; cloning it does not copy the image.
+    png: Bytes,
+    rendered_at: DateTime<Utc>,
+}
+
+/// Least recently used entries sit at the front.
+struct QrCache {
+    capacity: usize,
+    entries: VecDeque<RenderedQr>,
+}
+
+impl QrCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, etag: &str) -> Option<RenderedQr> {
+        let pos = self.entries.iter().position(|entry| entry.etag == etag)?;
+        let entry = self.entries.remove(pos)?;
+        self.entries.push_back(entry.clone());
+        Some(entry)
+    }
+
+    fn insert(&mut self, entry: RenderedQr) {
+        self.entries.retain(|cached| cached.etag != entry.etag);
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+fn qr_cache() -> &'static Mutex<QrCache> {
+    static CACHE: OnceLock<Mutex<QrCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(QrCache::new(QR_CACHE_CAPACITY)))
+}
+
+/// Strong ETag for an otpauth URL. Hashed so the secret never leaves in a header.
+fn qr_etag(otpauth_url: &str) -> String {
+    let digest = Sha256::digest(otpauth_url.as_bytes());
+    format!("\"{}\"", HEXLOWER.encode(&digest[..16]))
+}
+
+fn render_png(otpauth_url: &str) -> Option<Vec<u8>> {
+    let code = QrCode::new(otpauth_url.as_bytes()).ok()?;
+    let img = code.render::<Luma<u8>>().min_dimensions(400, 400).build();
+
+    // image 0.25: write_to requires Write + Seek -> Cursor<Vec<u8>>
+    let mut cursor = Cursor::new(Vec::<u8>::new());
+    image::DynamicImage::ImageLuma8(img)
+        .write_to(&mut cursor, ImageFormat::Png)
+        .ok()?;
+    Some(cursor.into_inner())
+}
+
+/// The lock is only held to look up and to store: rendering happens without
+/// it, so one slow render does not hold up every other QR request. Two
+/// requests missing the same code at once both render it, and the second
+/// insert replaces the first.
+fn cached_qr(otpauth_url: &str) -> Option<RenderedQr> {
+    let etag = qr_etag(otpauth_url);
+    let cache = || qr_cache().lock().unwrap_or_else(|p| p.into_inner());
+    if let Some(entry) = cache().get(&etag) {
+        return Some(entry);
+    }
+    let entry = RenderedQr {
+        etag,
+        png: Bytes::from(render_png(otpauth_url)?),
+        rendered_at: Utc::now(),
+    };
+    cache().insert(entry.clone());
+    Some(entry)
+}
+
+fn http_date(date: &DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// `If-None-Match` wins over `If-Modified-Since` when both are sent.
+fn not_modified(headers: &HeaderMap, qr: &RenderedQr) -> bool {
+    if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+        let Ok(value) = value.to_str() else {
+            return false;
+        };
+        return value
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == qr.etag);
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| qr.rendered_at.timestamp() <= since.timestamp())
+}
+
+/// PNG response for an otpauth URL, or `304 Not Modified` when the request
+/// validators still match the rendered image.
+pub fn qr_png_response(otpauth_url: &str, headers: &HeaderMap) -> Response {
+    let Some(qr) = cached_qr(otpauth_url) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to build qr").into_response();
+    };
+    let builder = Response::builder()
+        .header(header::ETAG, &qr.etag)
+        .header(header::LAST_MODIFIED, http_date(&qr.rendered_at))
+        .header(header::CACHE_CONTROL, QR_CACHE_CONTROL);
+    if not_modified(headers, &qr) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+    builder
+        .header(header::CONTENT_TYPE, "image/png")
+        .body(Body::from(qr.png))
+        .unwrap()
+}
 
 /// Builds and returns a PNG QR code so clients can scan and enroll.
-pub async fn qrcode(session: SessionUser) -> Response {
+pub async fn qrcode(session: SessionUser, headers: HeaderMap) -> Response {
     let current = session.user();
 
     match build_totp(&current.company_name, &current.username, &current.secret) {
-        Ok(totp) => {
-            let url = totp.get_url();
-            if let Ok(code) = QrCode::new(url.as_bytes()) {
-                let img = code.render::<Luma<u8>>().min_dimensions(400, 400).build();
-
-                // image 0.25: write_to requires Write + Seek -> Cursor<Vec<u8>>
-                let mut cursor = Cursor::new(Vec::<u8>::new());
-                if image::DynamicImage::ImageLuma8(img)
-                    .write_to(&mut cursor, ImageFormat::Png)
-                    .is_ok()
-                {
-                    let png = cursor.into_inner();
-                    return Response::builder()
-                        .header("Content-Type", "image/png")
-                        .body(Body::from(png))
-                        .unwrap();
-                }
-            }
-            (StatusCode::INTERNAL_SERVER_ERROR, "failed to build qr").into_response()
-        }
+        Ok(totp) => qr_png_response(&totp.get_url(), &headers),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "invalid secret").into_response(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const URL: &str = "otpauth://totp/Acme:ana?secret=JBSWY3DPEHPK3PXP&issuer=Acme";
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let entry = |etag: &str| RenderedQr {
+            etag: etag.into(),
+            png: Bytes::new(),
+            rendered_at: Utc::now(),
+        };
+        let mut cache = QrCache::new(2);
+        cache.insert(entry("a"));
+        cache.insert(entry("b"));
+        assert!(cache.get("a").is_some());
+        cache.insert(entry("c"));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn conditional_requests_get_not_modified() {
+        let first = qr_png_response(URL, &HeaderMap::new());
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].clone();
+        let last_modified = first.headers()[header::LAST_MODIFIED].clone();
+        assert!(!etag.to_str().unwrap().contains("JBSWY3DPEHPK3PXP"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let again = qr_png_response(URL, &headers);
+        assert_eq!(again.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(again.headers()[header::ETAG], etag);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MODIFIED_SINCE, last_modified);
+        assert_eq!(
+            qr_png_response(URL, &headers).status(),
+            StatusCode::NOT_MODIFIED
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert_eq!(qr_png_response(URL, &headers).status(), StatusCode::OK);
+        let rotated = qr_png_response(&URL.replace("JBSW", "KRSX"), &HeaderMap::new());
+        assert_ne!(rotated.headers()[header::ETAG], etag);
+    }
+}
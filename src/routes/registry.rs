@@ -0,0 +1,54 @@
+// routes/registry.rs
+// Route registry. Each routes module lists its own paths in a `router()`
+// function returning `Routes`; `admin::router()` and `finance::router()`
+// merge their submodules and `protected_routes` merges everything behind the
+// session middleware, for both the server and the test harness.
+
+use std::sync::Arc;
+
+use axum::{Router, routing::MethodRouter};
+
+use crate::state::AppState;
+
+/// A router that remembers the path of every route added to it, so the
+/// authorization tests can sweep them all. `Router` does not list its routes.
+pub struct Routes {
+    router: Router<Arc<AppState>>,
+    paths: Vec<&'static str>,
+}
+
+impl Default for Routes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Self {
+            router: Router::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    pub fn route(mut self, path: &'static str, method_router: MethodRouter<Arc<AppState>>) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.paths.push(path);
+        self
+    }
+
+    pub fn merge(mut self, other: Routes) -> Self {
+        self.router = self.router.merge(other.router);
+        self.paths.extend(other.paths);
+        self
+    }
+
+    /// Paths in the order they were registered.
+    pub fn paths(&self) -> &[&'static str] {
+        &self.paths
+    }
+
+    pub fn into_router(self) -> Router<Arc<AppState>> {
+        self.router
+    }
+}
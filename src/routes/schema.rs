@@ -0,0 +1,85 @@
+// routes/schema.rs
+// GET /api/v1/schema -> JSON Schema of the persisted models, so integrators can
+// validate payloads before sending them.
+
+use axum::Json;
+use utoipa::OpenApi;
+
+use crate::session::SessionUser;
+
+/// Persisted models published as JSON Schema for import tooling. ObjectIds are
+/// 24-character hex strings and dates RFC 3339 strings, as in the JSON API.
+#[derive(OpenApi)]
+#[openapi(components(schemas(
+    crate::models::Account,
+    crate::models::Category,
+    crate::models::Contact,
+    crate::models::RecurringPlan,
+    crate::models::PlannedEntry,
+    crate::models::Transaction,
+)))]
+struct ModelSchemas;
+
+/// JSON Schema (draft 2020-12) document with one `$defs` entry per model.
+/// Built from the same `ToSchema` derives as the OpenAPI components, with the
+/// references rewritten to point into `$defs`.
+pub fn model_json_schema() -> serde_json::Value {
+    let schemas = ModelSchemas::openapi()
+        .components
+        .map(|components| components.schemas)
+        .unwrap_or_default();
+    let defs = serde_json::to_string(&schemas)
+        .expect("model schemas must serialize")
+        .replace("#/components/schemas/", "#/$defs/");
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "alfredodev models",
+        "$defs": serde_json::from_str::<serde_json::Value>(&defs)
+            .expect("model schemas must round-trip"),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/schema",
+    tag = "admin",
+    responses(
+        (status = 200, description = "JSON Schema (draft 2020-12) with a `$defs` entry per model: Account, Category, Contact, RecurringPlan, PlannedEntry, Transaction"),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(("session" = []))
+)]
+pub async fn model_schema(_session: SessionUser) -> Json<serde_json::Value> {
+    Json(model_json_schema())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_json_schema_defines_models_with_local_refs() {
+        let schema = model_json_schema();
+        let defs = schema["$defs"].as_object().expect("$defs object");
+        for model in ["Transaction", "PlannedEntry", "RecurringPlan", "Contact"] {
+            assert!(defs.contains_key(model), "missing {model}");
+        }
+
+        let tx = &defs["Transaction"];
+        let required: Vec<&str> = tx["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert!(required.contains(&"company_id") && required.contains(&"amount"));
+        assert!(!required.contains(&"_id") && !required.contains(&"notes"));
+        assert_eq!(tx["properties"]["company_id"]["type"], "string");
+        assert_eq!(tx["properties"]["date"]["format"], "date-time");
+
+        let text = schema.to_string();
+        assert!(text.contains("#/$defs/TransactionType"));
+        assert!(!text.contains("#/components/"));
+        assert!(defs.contains_key("TransactionType"));
+    }
+}
@@ -15,6 +15,7 @@ pub struct SecretQuery {
 }
 
 /// Only generates and returns a Base32 secret (NOPAD). No I/O or persistence.
+/// Read-only roles are refused, as on every page that hands out secrets.
 #[utoipa::path(
     get,
     path = "/secret",
@@ -27,9 +28,12 @@ pub struct SecretQuery {
     security(("session" = []))
 )]
 pub async fn secret_generate(
-    SessionUser { .. }: SessionUser,
+    session_user: SessionUser,
     Query(q): Query<SecretQuery>,
 ) -> impl IntoResponse {
+    if session_user.active_role().is_read_only() {
+        return axum::http::StatusCode::FORBIDDEN.into_response();
+    }
     let mut n = q.bytes.or(q.s).unwrap_or(DEFAULT_SECRET_BYTES);
     if n < MIN_SECRET_BYTES {
         n = MIN_SECRET_BYTES;
@@ -49,4 +53,5 @@ pub async fn secret_generate(
         axum::http::StatusCode::OK,
         Json(serde_json::Value::Object(body)),
     )
+        .into_response()
 }
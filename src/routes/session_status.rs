@@ -0,0 +1,108 @@
+// routes/session_status.rs
+// GET /api/session -> how long the browser session has left; POST
+// /api/session/renew -> pushes its expiry back. Pages poll the first and
+// call the second while the user is working, so a long form is not lost to
+// a redirect to the login.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use mongodb::bson::{DateTime, doc};
+use serde::Serialize;
+
+use crate::{
+    session::{SESSION_EXPIRES_HEADER, SessionUser, seconds_until},
+    state::{AppState, renew_session, session_warning_seconds},
+};
+
+/// Where pages renew the session.
+const RENEW_URL: &str = "/api/session/renew";
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SessionStatus {
+    /// RFC 3339.
+    pub expires_at: String,
+    /// Seconds left, zero once expired.
+    pub expires_in: i64,
+    /// Seconds before expiry the page starts warning.
+    pub warn_before: u64,
+    /// POST here to renew the session.
+    pub renew_url: String,
+}
+
+fn status_response(expires_at: DateTime) -> Response {
+    let expires_in = seconds_until(expires_at);
+    let mut response = Json(SessionStatus {
+        expires_at: expires_at.try_to_rfc3339_string().unwrap_or_default(),
+        expires_in,
+        warn_before: session_warning_seconds(),
+        renew_url: RENEW_URL.to_string(),
+    })
+    .into_response();
+    response
+        .headers_mut()
+        .insert(SESSION_EXPIRES_HEADER, HeaderValue::from(expires_in));
+    response
+}
+
+/// Personal access tokens have no browser session to report on.
+fn no_browser_session() -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "error": "Sin sesión de navegador" })),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/session",
+    tag = "auth",
+    responses(
+        (status = 200, description = "When the current session expires; does not renew it", body = SessionStatus),
+        (status = 400, description = "Requested with an access token instead of a session"),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(("session" = []))
+)]
+pub async fn session_status(session: SessionUser, State(state): State<Arc<AppState>>) -> Response {
+    if session.token().is_empty() {
+        return no_browser_session();
+    }
+    match state
+        .sessions
+        .find_one(doc! { "token": session.token() })
+        .await
+    {
+        Ok(Some(found)) => status_response(found.expires_at),
+        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/session/renew",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Session renewed; returns its new expiry", body = SessionStatus),
+        (status = 400, description = "Requested with an access token instead of a session"),
+        (status = 401, description = "Not authenticated or the session already expired")
+    ),
+    security(("session" = []))
+)]
+pub async fn session_renew(session: SessionUser, State(state): State<Arc<AppState>>) -> Response {
+    if session.token().is_empty() {
+        return no_browser_session();
+    }
+    match renew_session(&state, session.token()).await {
+        Ok(Some(expires_at)) => status_response(expires_at),
+        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -10,7 +10,9 @@ use axum::{
 use crate::session::SessionUser;
 use crate::totp::build_totp;
 
-/// Returns a JSON with { email, company, otpauth_url } to enroll in authenticator apps.
+/// Returns a JSON with { username, display_name, avatar_url, company, otpauth_url }:
+/// the header reads who is signed in from it, and authenticator apps enroll
+/// with the URL.
 #[utoipa::path(
     get,
     path = "/setup",
@@ -37,6 +39,10 @@ pub async fn setup(session: SessionUser) -> Response {
                 StatusCode::OK,
                 Json(serde_json::json!({
                     "username": current.username,
+                    "display_name": current.label(),
+                    "avatar_url": current
+                        .has_avatar
+                        .then(|| format!("/users/{}/avatar", current.id.to_hex())),
                     "company": current.company_name,
                     "role": current.role.as_str(),
                     "permissions": permissions,
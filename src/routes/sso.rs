@@ -0,0 +1,350 @@
+// routes/sso.rs
+// OpenID Connect login, as an alternative to TOTP.
+// GET  /sso/login               -> redirects to the provider
+// GET  /sso/link                -> same, to link the identity to the logged-in user
+// GET  /sso/callback            -> verifies the provider response and opens a session
+// POST /account/sso/{id}/unlink -> removes a linked identity
+
+use std::{str::FromStr, sync::Arc};
+
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header::SET_COOKIE},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+
+use crate::{
+    models::AccessEventKind,
+    oidc::{IdTokenClaims, OidcConfig, authorization_url, complete_login, discover, random_token},
+    routes::login::{
+        compute_cookie_domain, compute_redirect_url, record_login, set_cookies_for_host,
+    },
+    session::{SESSION_COOKIE_NAME, SessionUser, extract_cookies},
+    state::{
+        AppState, SsoLoginOutcome, create_session, find_user, find_user_by_session,
+        link_sso_identity, sso_login_user, unlink_sso_identity,
+    },
+};
+
+/// Holds `state`, `nonce` and the flow mode between the redirect to the
+/// provider and the callback.
+const FLOW_COOKIE_NAME: &str = "oidc_flow";
+const FLOW_TTL_SECONDS: u64 = 600;
+
+#[derive(Template)]
+#[template(path = "sso/error.html")]
+struct SsoErrorTemplate {
+    message: String,
+}
+
+#[derive(Deserialize)]
+pub struct SsoCallbackQuery {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+struct Flow {
+    state: String,
+    nonce: String,
+    /// Link the identity to the current session instead of logging in.
+    link: bool,
+}
+
+impl Flow {
+    fn new(link: bool) -> Self {
+        Self {
+            state: random_token(),
+            nonce: random_token(),
+            link,
+        }
+    }
+
+    fn cookie_value(&self) -> String {
+        let mode = if self.link { "link" } else { "login" };
+        format!("{}.{}.{}", self.state, self.nonce, mode)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split('.');
+        let (Some(state), Some(nonce), Some(mode), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        let link = match mode {
+            "link" => true,
+            "login" => false,
+            _ => return None,
+        };
+        Some(Self {
+            state: state.to_string(),
+            nonce: nonce.to_string(),
+            link,
+        })
+    }
+}
+
+fn host_of(headers: &HeaderMap) -> &str {
+    headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost")
+}
+
+/// Host-only flow cookie, plus one on the root domain when there is one so a
+/// login started on a tenant subdomain survives a callback on another host.
+fn flow_cookies(host: &str, value: &str, max_age: u64) -> Vec<HeaderValue> {
+    let base = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        FLOW_COOKIE_NAME, value, max_age
+    );
+    let mut cookies = vec![base.clone()];
+    if let Some(domain) = compute_cookie_domain(host) {
+        cookies.push(format!(
+            "{}; Domain={}",
+            base,
+            domain.trim_start_matches('.')
+        ));
+    }
+    cookies
+        .into_iter()
+        .filter_map(|cookie| HeaderValue::from_str(&cookie).ok())
+        .collect()
+}
+
+fn sso_error(status: StatusCode, message: &str) -> Response {
+    match (SsoErrorTemplate {
+        message: message.to_string(),
+    })
+    .render()
+    {
+        Ok(html) => (status, Html(html)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn start_flow(headers: &HeaderMap, link: bool) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let metadata = match discover(&reqwest::Client::new(), &config).await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            eprintln!("oidc discovery failed: {err:?}");
+            return sso_error(
+                StatusCode::BAD_GATEWAY,
+                "No se pudo contactar al proveedor de identidad.",
+            );
+        }
+    };
+    let flow = Flow::new(link);
+    let mut response = Redirect::to(&authorization_url(
+        &metadata,
+        &config,
+        &flow.state,
+        &flow.nonce,
+    ))
+    .into_response();
+    for cookie in flow_cookies(host_of(headers), &flow.cookie_value(), FLOW_TTL_SECONDS) {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+pub async fn sso_login(headers: HeaderMap) -> Response {
+    start_flow(&headers, false).await
+}
+
+pub async fn sso_link(_session_user: SessionUser, headers: HeaderMap) -> Response {
+    start_flow(&headers, true).await
+}
+
+pub async fn sso_callback(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SsoCallbackQuery>,
+) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let host = host_of(&headers);
+    let flow = extract_cookies(&headers, FLOW_COOKIE_NAME)
+        .iter()
+        .filter_map(|value| Flow::parse(value))
+        .find(|flow| query.state.as_deref() == Some(flow.state.as_str()));
+
+    let mut response = match (flow, query.error, query.code) {
+        (None, _, _) => sso_error(
+            StatusCode::BAD_REQUEST,
+            "La solicitud de inicio de sesión expiró o no es válida. Intenta de nuevo.",
+        ),
+        (Some(_), Some(error), _) => {
+            eprintln!("oidc provider returned error: {error}");
+            sso_error(
+                StatusCode::UNAUTHORIZED,
+                "El proveedor de identidad rechazó el inicio de sesión.",
+            )
+        }
+        (Some(_), None, None) => sso_error(
+            StatusCode::BAD_REQUEST,
+            "La respuesta del proveedor no incluye un código de autorización.",
+        ),
+        (Some(flow), None, Some(code)) => match complete_login(&config, &code, &flow.nonce).await {
+            Ok(claims) if flow.link => link_to_session(&state, &headers, &claims).await,
+            Ok(claims) => log_in(&state, &headers, host, &claims).await,
+            Err(err) => {
+                eprintln!("oidc login failed: {err:?}");
+                sso_error(
+                    StatusCode::UNAUTHORIZED,
+                    "No se pudo validar la respuesta del proveedor de identidad.",
+                )
+            }
+        },
+    };
+
+    for cookie in flow_cookies(host, "", 0) {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+async fn log_in(
+    state: &AppState,
+    headers: &HeaderMap,
+    host: &str,
+    claims: &IdTokenClaims,
+) -> Response {
+    let outcome =
+        match sso_login_user(state, &claims.iss, &claims.sub, claims.verified_email()).await {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                eprintln!("sso login failed: {err:?}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+    let username = match outcome {
+        SsoLoginOutcome::Existing(username) | SsoLoginOutcome::Provisioned(username) => username,
+        SsoLoginOutcome::Inactive => {
+            return sso_error(StatusCode::FORBIDDEN, "Tu usuario está desactivado.");
+        }
+        SsoLoginOutcome::MissingEmail => {
+            return sso_error(
+                StatusCode::FORBIDDEN,
+                "El proveedor no confirmó tu email, así que no podemos crear tu usuario.",
+            );
+        }
+        SsoLoginOutcome::AccountExists => {
+            return sso_error(
+                StatusCode::CONFLICT,
+                "Ya existe un usuario con ese email. Entra con tu código TOTP y vincula la identidad desde Mi cuenta.",
+            );
+        }
+        SsoLoginOutcome::NoCompanyForDomain => {
+            return sso_error(
+                StatusCode::FORBIDDEN,
+                "Ninguna compañía tiene habilitado el acceso para el dominio de tu email.",
+            );
+        }
+    };
+
+    let (token, slug) = match find_user(state, &username).await {
+        Ok(Some(user)) => match create_session(state, &username).await {
+            Ok(token) => {
+                record_login(
+                    state,
+                    AccessEventKind::SessionCreated,
+                    &user,
+                    headers,
+                    "SSO",
+                )
+                .await;
+                (token, user.company_slug)
+            }
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        },
+        _ => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let target = compute_redirect_url(host, &slug)
+        .map(|base| format!("{base}/"))
+        .unwrap_or_else(|| "/".to_string());
+    let mut response = Redirect::to(&target).into_response();
+    set_cookies_for_host(&mut response, &token, host, &slug);
+    response
+}
+
+/// Link mode is only trusted with a live session; the flow cookie never says
+/// which user to link to.
+async fn link_to_session(
+    state: &AppState,
+    headers: &HeaderMap,
+    claims: &IdTokenClaims,
+) -> Response {
+    let mut user = None;
+    for token in extract_cookies(headers, SESSION_COOKIE_NAME) {
+        if let Ok(Some(found)) = find_user_by_session(state, &token).await {
+            user = Some(found);
+            break;
+        }
+    }
+    let Some(user) = user else {
+        return sso_error(
+            StatusCode::UNAUTHORIZED,
+            "Tu sesión expiró. Entra de nuevo para vincular la identidad.",
+        );
+    };
+    match link_sso_identity(
+        state,
+        &user.id,
+        &claims.iss,
+        &claims.sub,
+        claims.email.as_deref(),
+    )
+    .await
+    {
+        Ok(()) => Redirect::to("/account?sso_linked=1").into_response(),
+        Err(_) => sso_error(
+            StatusCode::CONFLICT,
+            "Esa identidad ya está vinculada a otro usuario.",
+        ),
+    }
+}
+
+pub async fn sso_unlink(
+    session_user: SessionUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Ok(identity_id) = ObjectId::from_str(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match unlink_sso_identity(&state, session_user.user_id(), &identity_id).await {
+        Ok(true) => Redirect::to("/account?sso_unlinked=1").into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_cookie_round_trips_and_rejects_garbage() {
+        let flow = Flow::new(true);
+        let parsed = Flow::parse(&flow.cookie_value()).unwrap();
+        assert_eq!(parsed.state, flow.state);
+        assert_eq!(parsed.nonce, flow.nonce);
+        assert!(parsed.link);
+
+        assert!(Flow::parse("").is_none());
+        assert!(Flow::parse("a.b.admin").is_none());
+        assert!(Flow::parse("a.b.login.extra").is_none());
+    }
+}
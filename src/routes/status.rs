@@ -0,0 +1,131 @@
+// routes/status.rs
+// GET /status -> public JSON summary for uptime monitors and dashboards:
+// build version, MongoDB reachability, last runs of the background tasks, CFDI
+// download jobs still pending and the webhook delivery backlog. Nothing tenant-
+// or user-specific is shown.
+
+use std::{
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use serde::Serialize;
+
+use crate::{
+    metrics::metrics,
+    state::{AppState, CfdiJobStatus, failed_webhook_companies},
+};
+
+/// How long the database ping may take before it counts as down.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct StatusReport {
+    /// `ok`, or `degraded` when the database does not answer.
+    status: &'static str,
+    version: &'static str,
+    /// Commit the binary was built from, when `BUILD_COMMIT` was set at build
+    /// time.
+    commit: Option<&'static str>,
+    database: DatabaseStatus,
+    /// Background tasks that have run at least once since the process started.
+    tasks: Vec<TaskStatus>,
+    /// CFDI download jobs queued or running.
+    pending_jobs: usize,
+    webhook_deliveries: WebhookDeliveries,
+}
+
+#[derive(Serialize)]
+struct WebhookDeliveries {
+    /// Queued or still retrying in this process.
+    pending: usize,
+    /// Companies whose last delivery failed; `None` while the database does
+    /// not answer.
+    failed: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DatabaseStatus {
+    ok: bool,
+    latency_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TaskStatus {
+    name: &'static str,
+    last_run_at: String,
+    last_success_at: Option<String>,
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339()
+}
+
+async fn ping_database(state: &AppState) -> DatabaseStatus {
+    let db = state.users.client().database(&state.users.namespace().db);
+    let started = Instant::now();
+    match tokio::time::timeout(PING_TIMEOUT, db.run_command(doc! { "ping": 1 })).await {
+        Ok(Ok(_)) => DatabaseStatus {
+            ok: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+        },
+        _ => DatabaseStatus {
+            ok: false,
+            latency_ms: None,
+        },
+    }
+}
+
+/// No session needed. Answers 503 while the database is unreachable so
+/// monitors can alert on the status code alone.
+pub async fn status(State(state): State<Arc<AppState>>) -> Response {
+    let database = ping_database(&state).await;
+    let tasks = metrics()
+        .task_runs()
+        .into_iter()
+        .map(|(name, run)| TaskStatus {
+            name,
+            last_run_at: rfc3339(run.last_run_at),
+            last_success_at: run.last_success_at.map(rfc3339),
+        })
+        .collect();
+    let pending_jobs = state
+        .jobs
+        .lock()
+        .await
+        .values()
+        .filter(|job| matches!(job.status, CfdiJobStatus::Queued | CfdiJobStatus::Running))
+        .count();
+    let webhook_deliveries = WebhookDeliveries {
+        pending: state.webhook_deliveries.load(Ordering::Relaxed),
+        failed: if database.ok {
+            failed_webhook_companies(&state).await.ok()
+        } else {
+            None
+        },
+    };
+
+    let code = if database.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let report = StatusReport {
+        status: if database.ok { "ok" } else { "degraded" },
+        version: env!("CARGO_PKG_VERSION"),
+        commit: option_env!("BUILD_COMMIT"),
+        database,
+        tasks,
+        pending_jobs,
+        webhook_deliveries,
+    };
+    (code, Json(report)).into_response()
+}
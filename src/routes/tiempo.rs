@@ -8,16 +8,20 @@ use axum::{
     response::Html,
 };
 use chrono::{
-    DateTime as ChronoDateTime, Datelike, Months, SecondsFormat, TimeZone, Timelike, Utc,
+    DateTime as ChronoDateTime, Datelike, Months, NaiveDate, NaiveTime, SecondsFormat, TimeZone,
+    Timelike, Utc,
 };
 use futures::stream::TryStreamExt;
-use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use mongodb::{
+    Collection,
+    bson::{DateTime, Document, doc, oid::ObjectId},
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::{FlowType, PlannedStatus, TransactionType, UserPermission},
+    models::{PlannedStatus, UserPermission},
     session::SessionUser,
-    state::AppState,
+    state::{AppState, PLANNED_ENTRIES_ARCHIVE},
 };
 
 fn render<T: Template>(tpl: T) -> Result<Html<String>, StatusCode> {
@@ -39,25 +43,45 @@ pub async fn tiempo_page(session: SessionUser) -> Result<Html<String>, StatusCod
 
 #[derive(Deserialize)]
 pub struct TiempoQuery {
-    mode: String,
-    from: String,
-    to: String,
+    /// Bucket size: `day`, `week`, `month` or `year`. Defaults to `month`.
+    #[serde(alias = "granularity")]
+    mode: Option<String>,
+    /// Window start, RFC 3339 or `YYYY-MM-DD`. Defaults to twelve buckets
+    /// before `to`.
+    from: Option<String>,
+    /// Window end (exclusive). Defaults to the end of the current bucket.
+    to: Option<String>,
+    /// Comma separated `real` and `planned`. Both when omitted.
+    metrics: Option<String>,
+    /// Whether each bucket lists its transactions and planned entries.
+    /// Defaults to `true`.
+    items: Option<bool>,
 }
 
 #[derive(Serialize)]
 pub struct TimelineBucket {
     start: String,
     end: String,
-    real_income: f64,
-    real_expense: f64,
-    planned_income: f64,
-    planned_expense: f64,
-    net_real: f64,
-    net_planned: f64,
-    cumulative_real: f64,
-    cumulative_planned: f64,
-    transactions: Vec<TxItem>,
-    planned_entries: Vec<PlannedItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    real_income: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    real_expense: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    planned_income: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    planned_expense: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    net_real: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    net_planned: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cumulative_real: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cumulative_planned: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transactions: Option<Vec<TxItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    planned_entries: Option<Vec<PlannedItem>>,
 }
 
 #[derive(Serialize)]
@@ -97,25 +121,76 @@ impl Mode {
             _ => None,
         }
     }
+
+    /// Unit of Mongo's `$dateTrunc`.
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Day => "day",
+            Mode::Week => "week",
+            Mode::Month => "month",
+            Mode::Year => "year",
+        }
+    }
+}
+
+/// Series included in the buckets.
+#[derive(Clone, Copy)]
+struct Metrics {
+    real: bool,
+    planned: bool,
 }
 
+impl Metrics {
+    fn parse(value: Option<&str>) -> Option<Self> {
+        let Some(value) = value.filter(|v| !v.trim().is_empty()) else {
+            return Some(Metrics {
+                real: true,
+                planned: true,
+            });
+        };
+        let mut metrics = Metrics {
+            real: false,
+            planned: false,
+        };
+        for name in value.split(',') {
+            match name.trim() {
+                "real" => metrics.real = true,
+                "planned" => metrics.planned = true,
+                _ => return None,
+            }
+        }
+        Some(metrics)
+    }
+}
+
+/// Buckets shown when the window has no `from`.
+const DEFAULT_BUCKETS: u32 = 12;
+
 #[utoipa::path(
     get,
     path = "/api/tiempo",
     tag = "auth",
+    params(
+        ("mode" = Option<String>, Query, description = "day, week, month or year (alias: granularity); month by default"),
+        ("from" = Option<String>, Query, description = "Window start, RFC 3339 or YYYY-MM-DD; twelve buckets before `to` by default"),
+        ("to" = Option<String>, Query, description = "Window end, exclusive; end of the current bucket by default"),
+        ("metrics" = Option<String>, Query, description = "Comma separated real and planned; both by default"),
+        ("items" = Option<bool>, Query, description = "List the transactions and planned entries of each bucket; true by default")
+    ),
     responses(
-        (status = 200, description = "Returns timeline buckets"),
+        (status = 200, description = "Returns timeline buckets with the selected metrics"),
+        (status = 400, description = "Invalid window, granularity or metric"),
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Forbidden")
     ),
-    security(("session" = []))
+    security(("session" = []), ("token" = []))
 )]
 pub async fn tiempo_data(
     SessionUser(session): SessionUser,
     State(state): State<Arc<AppState>>,
     Query(query): Query<TiempoQuery>,
 ) -> Result<Json<Vec<TimelineBucket>>, StatusCode> {
-    if !session.user.role.is_admin()
+    if !session.user.role.can_view_admin()
         && !session
             .user
             .permissions
@@ -123,158 +198,235 @@ pub async fn tiempo_data(
     {
         return Err(StatusCode::FORBIDDEN);
     }
-    let mode = Mode::parse(&query.mode).ok_or(StatusCode::BAD_REQUEST)?;
+    let mode = match query.mode.as_deref() {
+        Some(value) => Mode::parse(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => Mode::Month,
+    };
+    let metrics = Metrics::parse(query.metrics.as_deref()).ok_or(StatusCode::BAD_REQUEST)?;
+    let with_items = query.items.unwrap_or(true);
 
-    let start = parse_iso(&query.from).ok_or(StatusCode::BAD_REQUEST)?;
-    let mut end = parse_iso(&query.to).ok_or(StatusCode::BAD_REQUEST)?;
+    let mut end = match query.to.as_deref() {
+        Some(value) => parse_iso(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => next_bucket(bucket_start(Utc::now(), mode), mode),
+    };
+    let start = match query.from.as_deref() {
+        Some(value) => parse_iso(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => buckets_before(bucket_start(end, mode), mode, DEFAULT_BUCKETS),
+    };
 
     let max_future = Utc::now() + chrono::Duration::days(365 * 5);
     if end > max_future {
         end = max_future;
     }
+    if start >= end {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let company_id = &session.user.company_id;
+    let window = doc! { "$gte": DateTime::from_chrono(start), "$lt": DateTime::from_chrono(end) };
 
-    let (base_income, base_expense) =
-        sum_transactions_before(&state, &session.user.company_id, start)
+    // Opening balance and per-bucket totals of each selected series.
+    let real = if metrics.real {
+        let (base_income, base_expense) = sum_transactions_before(&state, company_id, start)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let (base_planned_income, base_planned_expense) =
-        sum_planned_before(&state, &session.user.company_id, start)
+        let totals = bucket_totals(
+            &state.transactions,
+            doc! {
+                "company_id": company_id,
+                "date": window.clone(),
+                "is_confirmed": { "$ne": false },
+            },
+            "date",
+            "transaction_type",
+            "amount",
+            mode,
+            None,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Some((base_income - base_expense, totals))
+    } else {
+        None
+    };
+    let planned = if metrics.planned {
+        let (base_income, base_expense) = sum_planned_before(&state, company_id, start)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Load transactions
-    let mut tx_cursor = state
-        .transactions
-        .find(doc! {
-            "company_id": &session.user.company_id,
-            "date": { "$gte": DateTime::from_chrono(start), "$lt": DateTime::from_chrono(end) }
-        })
+        let totals = bucket_totals(
+            &state.planned_entries,
+            planned_window_filter(company_id, &window),
+            "due_date",
+            "flow_type",
+            "amount_estimated",
+            mode,
+            Some(PLANNED_ENTRIES_ARCHIVE),
+        )
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Some((base_income - base_expense, totals))
+    } else {
+        None
+    };
 
-    let mut buckets: HashMap<ChronoDateTime<Utc>, TimelineBucket> = HashMap::new();
-
-    while let Some(tx) = tx_cursor
-        .try_next()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    {
-        let key = bucket_start(tx.date.to_chrono(), mode);
-        let bucket = buckets
-            .entry(key)
-            .or_insert_with(|| empty_bucket(key, mode));
-
-        match tx.transaction_type {
-            TransactionType::Income => bucket.real_income += tx.amount,
-            TransactionType::Expense => bucket.real_expense += tx.amount,
-            TransactionType::Transfer => {}
+    let mut tx_items: HashMap<ChronoDateTime<Utc>, Vec<TxItem>> = HashMap::new();
+    if with_items && metrics.real {
+        let mut tx_cursor = state
+            .transactions
+            .find(doc! {
+                "company_id": company_id,
+                "date": window.clone(),
+                "is_confirmed": { "$ne": false },
+            })
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        while let Some(tx) = tx_cursor
+            .try_next()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            let key = bucket_start(tx.date.to_chrono(), mode);
+            tx_items.entry(key).or_default().push(TxItem {
+                id: tx.id.map(|i| i.to_hex()).unwrap_or_default(),
+                description: tx.description,
+                amount: tx.amount,
+                date: fmt_iso(tx.date.to_chrono()),
+                r#type: tx.transaction_type.as_str().to_string(),
+            });
         }
-        bucket.net_real = bucket.real_income - bucket.real_expense;
-        bucket.transactions.push(TxItem {
-            id: tx.id.map(|i| i.to_hex()).unwrap_or_default(),
-            description: tx.description,
-            amount: tx.amount,
-            date: fmt_iso(tx.date.to_chrono()),
-            r#type: tx.transaction_type.as_str().to_string(),
-        });
     }
 
-    // Planned entries
-    let mut pe_cursor = state
-        .planned_entries
-        .find(doc! {
-            "company_id": &session.user.company_id,
-            "due_date": { "$gte": DateTime::from_chrono(start), "$lt": DateTime::from_chrono(end) }
-        })
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    while let Some(pe) = pe_cursor
-        .try_next()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    {
-        if matches!(pe.status, PlannedStatus::Cancelled) {
-            continue;
+    let mut planned_items: HashMap<ChronoDateTime<Utc>, Vec<PlannedItem>> = HashMap::new();
+    if with_items && metrics.planned {
+        let mut entries = Vec::new();
+        for collection in [&state.planned_entries, &state.planned_entries_archive] {
+            let found: Vec<_> = collection
+                .find(planned_window_filter(company_id, &window))
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .try_collect()
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            entries.extend(found);
         }
-        let key = bucket_start(pe.due_date.to_chrono(), mode);
-        let bucket = buckets
-            .entry(key)
-            .or_insert_with(|| empty_bucket(key, mode));
-
-        match pe.flow_type {
-            FlowType::Income => bucket.planned_income += pe.amount_estimated,
-            FlowType::Expense => bucket.planned_expense += pe.amount_estimated,
+        for pe in entries {
+            let key = bucket_start(pe.due_date.to_chrono(), mode);
+            planned_items.entry(key).or_default().push(PlannedItem {
+                id: pe.id.map(|i| i.to_hex()).unwrap_or_default(),
+                name: pe.name,
+                amount_estimated: pe.amount_estimated,
+                due_date: fmt_iso(pe.due_date.to_chrono()),
+                flow_type: pe.flow_type.as_str().to_string(),
+                status: pe.status.as_str().to_string(),
+            });
         }
-        bucket.net_planned = bucket.planned_income - bucket.planned_expense;
-        bucket.planned_entries.push(PlannedItem {
-            id: pe.id.map(|i| i.to_hex()).unwrap_or_default(),
-            name: pe.name,
-            amount_estimated: pe.amount_estimated,
-            due_date: fmt_iso(pe.due_date.to_chrono()),
-            flow_type: pe.flow_type.as_str().to_string(),
-            status: pe.status.as_str().to_string(),
-        });
     }
 
     let mut list: Vec<TimelineBucket> = Vec::new();
-    let mut running_real = base_income - base_expense;
-    let mut running_planned = base_planned_income - base_planned_expense;
+    let mut running_real = real.as_ref().map_or(0.0, |(base, _)| *base);
+    let mut running_planned = planned.as_ref().map_or(0.0, |(base, _)| *base);
 
     let mut cursor_start = bucket_start(start, mode);
-    let end_limit = bucket_start(end, mode);
-
-    while cursor_start < end_limit {
-        let mut bucket = buckets
-            .remove(&cursor_start)
-            .unwrap_or_else(|| empty_bucket(cursor_start, mode));
-        running_real += bucket.net_real;
-        running_planned += bucket.net_planned;
-        bucket.cumulative_real = running_real;
-        bucket.cumulative_planned = running_planned;
+    while cursor_start < end {
+        let cursor_end = next_bucket(cursor_start, mode);
+        let mut bucket = empty_bucket(cursor_start, cursor_end);
+        if let Some((_, totals)) = &real {
+            let (income, expense) = totals.get(&cursor_start).copied().unwrap_or_default();
+            running_real += income - expense;
+            bucket.real_income = Some(income);
+            bucket.real_expense = Some(expense);
+            bucket.net_real = Some(income - expense);
+            bucket.cumulative_real = Some(running_real);
+            if with_items {
+                bucket.transactions = Some(tx_items.remove(&cursor_start).unwrap_or_default());
+            }
+        }
+        if let Some((_, totals)) = &planned {
+            let (income, expense) = totals.get(&cursor_start).copied().unwrap_or_default();
+            running_planned += income - expense;
+            bucket.planned_income = Some(income);
+            bucket.planned_expense = Some(expense);
+            bucket.net_planned = Some(income - expense);
+            bucket.cumulative_planned = Some(running_planned);
+            if with_items {
+                bucket.planned_entries =
+                    Some(planned_items.remove(&cursor_start).unwrap_or_default());
+            }
+        }
         list.push(bucket);
-        cursor_start = next_bucket(cursor_start, mode);
+        cursor_start = cursor_end;
     }
 
     Ok(Json(list))
 }
 
-fn empty_bucket(start: ChronoDateTime<Utc>, mode: Mode) -> TimelineBucket {
-    let end = match mode {
-        Mode::Day => start + chrono::Duration::days(1),
-        Mode::Week => start + chrono::Duration::days(7),
-        Mode::Month => {
-            let mut d = start;
-            d = d
-                .with_day(1)
-                .unwrap_or(start)
-                .with_month(start.month() % 12 + 1)
-                .unwrap_or(start);
-            if start.month() == 12 {
-                d = d.with_year(start.year() + 1).unwrap_or(d);
-            }
-            d
+fn planned_window_filter(company_id: &ObjectId, window: &Document) -> Document {
+    doc! {
+        "company_id": company_id,
+        "due_date": window.clone(),
+        "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+    }
+}
+
+/// Income and expense per bucket, grouped in Mongo. `kind_field` holds
+/// `income` or `expense`; other kinds, such as transfers, are left out.
+/// Documents of the `union_with` collection matching the same filter count
+/// too.
+async fn bucket_totals<T: Send + Sync>(
+    collection: &Collection<T>,
+    filter: Document,
+    date_field: &str,
+    kind_field: &str,
+    amount_field: &str,
+    mode: Mode,
+    union_with: Option<&str>,
+) -> mongodb::error::Result<HashMap<ChronoDateTime<Utc>, (f64, f64)>> {
+    let mut trunc = doc! { "date": format!("${date_field}"), "unit": mode.as_str() };
+    if matches!(mode, Mode::Week) {
+        trunc.insert("startOfWeek", "monday");
+    }
+    let mut pipeline = vec![doc! { "$match": filter.clone() }];
+    if let Some(coll) = union_with {
+        pipeline.push(doc! { "$unionWith": { "coll": coll, "pipeline": [{ "$match": filter }] } });
+    }
+    pipeline.extend([doc! { "$group": {
+        "_id": { "bucket": { "$dateTrunc": trunc }, "kind": format!("${kind_field}") },
+        "total": { "$sum": format!("${amount_field}") },
+    }}]);
+    let mut totals: HashMap<ChronoDateTime<Utc>, (f64, f64)> = HashMap::new();
+    let mut cursor = collection.aggregate(pipeline).await?;
+    while let Some(doc) = cursor.try_next().await? {
+        let Ok(id) = doc.get_document("_id") else {
+            continue;
+        };
+        let (Ok(bucket), Ok(kind)) = (id.get_datetime("bucket"), id.get_str("kind")) else {
+            continue;
+        };
+        let total = doc.get_f64("total").unwrap_or(0.0);
+        let entry = totals.entry(bucket.to_chrono()).or_default();
+        match kind {
+            "income" => entry.0 += total,
+            "expense" => entry.1 += total,
+            _ => {}
         }
-        Mode::Year => start
-            .with_month(1)
-            .unwrap_or(start)
-            .with_year(start.year() + 1)
-            .unwrap_or(start),
-    };
+    }
+    Ok(totals)
+}
 
+fn empty_bucket(start: ChronoDateTime<Utc>, end: ChronoDateTime<Utc>) -> TimelineBucket {
     TimelineBucket {
         start: fmt_iso(start),
         end: fmt_iso(end),
-        real_income: 0.0,
-        real_expense: 0.0,
-        planned_income: 0.0,
-        planned_expense: 0.0,
-        net_real: 0.0,
-        net_planned: 0.0,
-        cumulative_real: 0.0,
-        cumulative_planned: 0.0,
-        transactions: Vec::new(),
-        planned_entries: Vec::new(),
+        real_income: None,
+        real_expense: None,
+        planned_income: None,
+        planned_expense: None,
+        net_real: None,
+        net_planned: None,
+        cumulative_real: None,
+        cumulative_planned: None,
+        transactions: None,
+        planned_entries: None,
     }
 }
 
@@ -316,10 +468,26 @@ fn next_bucket(dt: ChronoDateTime<Utc>, mode: Mode) -> ChronoDateTime<Utc> {
     }
 }
 
+/// Start of the bucket `count` buckets before `dt`.
+fn buckets_before(dt: ChronoDateTime<Utc>, mode: Mode, count: u32) -> ChronoDateTime<Utc> {
+    match mode {
+        Mode::Day => dt - chrono::Duration::days(count.into()),
+        Mode::Week => dt - chrono::Duration::days(i64::from(count) * 7),
+        Mode::Month => dt - Months::new(count),
+        Mode::Year => dt - Months::new(count * 12),
+    }
+}
+
+/// RFC 3339 timestamps, or plain dates taken as midnight UTC.
 fn parse_iso(value: &str) -> Option<ChronoDateTime<Utc>> {
     ChronoDateTime::parse_from_rfc3339(value)
         .map(|dt| dt.with_timezone(&Utc))
         .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+        })
 }
 
 fn fmt_iso(dt: ChronoDateTime<Utc>) -> String {
@@ -389,8 +557,37 @@ mod tests {
         let parsed = parse_iso("2026-05-04T10:15:30Z").unwrap();
 
         assert_eq!(fmt_iso(parsed), "2026-05-04T10:15:30.000Z");
+        assert_eq!(
+            parse_iso("2026-05-04"),
+            Some(Utc.with_ymd_and_hms(2026, 5, 4, 0, 0, 0).unwrap())
+        );
         assert!(parse_iso("not-a-date").is_none());
     }
+
+    #[test]
+    fn metrics_default_to_both_series_and_reject_unknown_names() {
+        let both = Metrics::parse(None).unwrap();
+        assert!(both.real && both.planned);
+        let real = Metrics::parse(Some("real")).unwrap();
+        assert!(real.real && !real.planned);
+        let listed = Metrics::parse(Some("planned, real")).unwrap();
+        assert!(listed.real && listed.planned);
+        assert!(Metrics::parse(Some("real,forecast")).is_none());
+    }
+
+    #[test]
+    fn buckets_before_steps_back_whole_periods() {
+        let dt = Utc.with_ymd_and_hms(2026, 5, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            buckets_before(dt, Mode::Month, 12),
+            Utc.with_ymd_and_hms(2025, 5, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            buckets_before(dt, Mode::Week, 2),
+            Utc.with_ymd_and_hms(2026, 4, 17, 0, 0, 0).unwrap()
+        );
+    }
 }
 
 async fn sum_transactions_before(
@@ -402,6 +599,7 @@ async fn sum_transactions_before(
         doc! { "$match": {
             "company_id": company_id,
             "date": { "$lt": DateTime::from_chrono(before) },
+            "is_confirmed": { "$ne": false },
         }},
         doc! { "$group": {
             "_id": "$transaction_type",
@@ -429,11 +627,16 @@ async fn sum_planned_before(
     company_id: &ObjectId,
     before: ChronoDateTime<Utc>,
 ) -> mongodb::error::Result<(f64, f64)> {
+    let filter = doc! {
+        "company_id": company_id,
+        "due_date": { "$lt": DateTime::from_chrono(before) },
+        "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+    };
     let pipeline = vec![
-        doc! { "$match": {
-            "company_id": company_id,
-            "due_date": { "$lt": DateTime::from_chrono(before) },
-            "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+        doc! { "$match": filter.clone() },
+        doc! { "$unionWith": {
+            "coll": PLANNED_ENTRIES_ARCHIVE,
+            "pipeline": [{ "$match": filter }],
         }},
         doc! { "$group": {
             "_id": "$flow_type",
@@ -1,24 +1,55 @@
 // session.rs
 // Session middleware to protect routes and extractor to access session data.
 
-use std::{env, sync::Arc};
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use axum::{
     extract::{FromRequestParts, Request, State},
-    http::{HeaderMap, StatusCode, header::COOKIE, request::Parts},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode, Uri,
+        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, REFERER},
+        request::Parts,
+    },
     middleware::Next,
-    response::{IntoResponse, Response},
+    response::{IntoResponse, Redirect, Response},
 };
 use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
 
-use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, oid::ObjectId};
 
 use crate::{
-    models::UserPermission,
-    state::{AppState, UserWithCompany, find_user_by_session},
+    flash::{Flash, with_flash},
+    models::{AccessEventKind, UserPermission, UserRole},
+    routes::login::set_cookies_for_host,
+    state::{
+        AccountAccess, AppState, PortalAccess, UserWithCompany, find_portal_session,
+        find_session_user, find_user_by_api_token, find_user_by_session, record_access_event,
+        refresh_session, session_ttl_seconds, set_session_flash, take_session_flash,
+    },
+    template_context::{TemplateContext, with_template_context},
 };
 
 pub const SESSION_COOKIE_NAME: &str = "session";
+/// Cookie of the contact portal. It is scoped to `/portal` and checked only by
+/// `require_portal_session`, so it never opens the rest of the app, and a user
+/// session never opens the portal.
+pub const PORTAL_COOKIE_NAME: &str = "portal_session";
+/// Cookie of a "remember me" login: a refresh token that opens a new session
+/// when the session cookie is missing or expired.
+pub const REFRESH_COOKIE_NAME: &str = "remember";
+/// Response header with the seconds left on the browser session, so scripts
+/// can warn before it runs out. Not sent for API token requests.
+pub const SESSION_EXPIRES_HEADER: &str = "x-session-expires-in";
+
+/// Marks a `/metrics` request that came with the scrape token instead of a
+/// session.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsScrape;
 
 #[derive(Clone)]
 pub struct SessionData {
@@ -31,17 +62,21 @@ pub async fn require_session(
     mut request: Request,
     next: Next,
 ) -> Result<Response, Response> {
-    let tokens = extract_cookies(request.headers(), SESSION_COOKIE_NAME);
-    if tokens.is_empty() {
-        return Err(unauthorized_response());
+    if is_metrics_scrape(&state, &request) {
+        request.extensions_mut().insert(MetricsScrape);
+        return Ok(next.run(request).await);
     }
 
+    let tokens = extract_cookies(request.headers(), SESSION_COOKIE_NAME);
+
     // Try all cookies with the session name until one is valid
     let mut found = None;
+    let mut expires_at = None;
     for token in tokens {
-        match find_user_by_session(&state, &token).await {
-            Ok(Some(user)) => {
+        match find_session_user(&state, &token).await {
+            Ok(Some((user, expiry))) => {
                 found = Some((user, token));
+                expires_at = Some(expiry);
                 break;
             }
             Ok(None) => continue,
@@ -53,40 +88,361 @@ pub async fn require_session(
         }
     }
 
-    if let Some((mut user, token)) = found {
-        // Select active company strictly by a trusted tenant subdomain if present.
-        if let Some(host) = request.headers().get("host").and_then(|h| h.to_str().ok()) {
-            if let Some(sub) = tenant_subdomain_from_host(host) {
-                if let Some(idx) = user
-                    .company_slugs
-                    .iter()
-                    .position(|s| s.eq_ignore_ascii_case(sub))
-                {
-                    user.company_id = user.company_ids[idx].clone();
-                    user.company_slug = user.company_slugs[idx].clone();
-                    user.company_name = user.company_names[idx].clone();
-                    if let Some(role) = user.company_roles.get(idx) {
-                        user.role = role.clone();
-                    }
-                    user.permissions = user
-                        .company_permissions
-                        .get(idx)
-                        .cloned()
-                        .unwrap_or_default();
-                } else {
-                    // Subdominio no corresponde a ninguna compañía del usuario
-                    return Err(unauthorized_response());
+    // A "remember me" login opens a new session once the last one is gone;
+    // its cookie goes out with the response.
+    let mut renewed = false;
+    if found.is_none() {
+        for token in extract_cookies(request.headers(), REFRESH_COOKIE_NAME) {
+            match refresh_session(&state, &token).await {
+                Ok(Some(session)) => {
+                    found = Some(session);
+                    renewed = true;
+                    expires_at = Some(DateTime::from_system_time(
+                        SystemTime::now() + Duration::from_secs(session_ttl_seconds()),
+                    ));
+                    break;
+                }
+                Ok(None) => continue,
+                Err(_) => {
+                    return Err(
+                        (StatusCode::INTERNAL_SERVER_ERROR, "session refresh failed")
+                            .into_response(),
+                    );
                 }
             }
         }
+    }
 
+    // Without a browser session, scripts may read the data endpoints with a
+    // personal access token. It carries no session token, so it cannot log out.
+    if found.is_none()
+        && is_api_token_request(&request)
+        && let Some(api_token) = bearer_token(request.headers())
+    {
+        match find_user_by_api_token(&state, &api_token).await {
+            Ok(Some(user)) => found = Some((user, String::new())),
+            Ok(None) => {}
+            Err(_) => {
+                return Err(
+                    (StatusCode::INTERNAL_SERVER_ERROR, "token lookup failed").into_response()
+                );
+            }
+        }
+    }
+
+    if let Some((mut user, token)) = found {
+        // Select active company strictly by a trusted tenant subdomain if present.
+        if !select_tenant_company(&mut user, request.headers()) {
+            return Err(unauthorized_response());
+        }
+        if is_read_only_write(&request, &user) {
+            return Err(read_only_response(&state, request.headers(), &token).await);
+        }
+
+        let renewed_cookie = renewed.then(|| {
+            let host = request
+                .headers()
+                .get("host")
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("localhost")
+                .to_string();
+            (token.clone(), host, user.company_slug.clone())
+        });
+        let format_preferences = user.format_preferences.clone();
+        let admin_action = is_admin_action(&request, &user).then(|| {
+            (
+                user.company_id,
+                user.id,
+                user.username.clone(),
+                client_ip(request.headers()),
+                format!("{} {}", request.method(), request.uri().path()),
+            )
+        });
+        // Browser sessions only: an API token has no session to keep it on.
+        let flash_token = (!token.is_empty()).then(|| token.clone());
+        let flash = match &flash_token {
+            Some(token) if is_page_request(&request) => take_session_flash(&state, token)
+                .await
+                .unwrap_or_else(|err| {
+                    eprintln!("[flash] lookup failed: {err}");
+                    None
+                }),
+            _ => None,
+        };
+        let context = TemplateContext::for_user(&user);
         request.extensions_mut().insert(SessionData { user, token });
-        Ok(next.run(request).await)
+        let mut response = with_flash(
+            flash.clone(),
+            crate::filters::with_format(
+                format_preferences,
+                with_template_context(context, next.run(request)),
+            ),
+        )
+        .await;
+        if let Some((token, host, slug)) = renewed_cookie {
+            set_cookies_for_host(&mut response, &token, &host, &slug);
+        }
+        // A handler that renewed the session already sent the new figure.
+        if let Some(expires_at) = expires_at
+            && !response.headers().contains_key(SESSION_EXPIRES_HEADER)
+        {
+            response.headers_mut().insert(
+                SESSION_EXPIRES_HEADER,
+                HeaderValue::from(seconds_until(expires_at)),
+            );
+        }
+        // A page that redirected instead of rendering passes its message on.
+        let is_redirect = response.status().is_redirection();
+        let pending = response
+            .extensions_mut()
+            .remove::<Flash>()
+            .or(flash.filter(|_| is_redirect));
+        if let (Some(flash), Some(token)) = (pending, &flash_token)
+            && let Err(err) = set_session_flash(&state, token, &flash).await
+        {
+            eprintln!("[flash] could not keep message: {err}");
+        }
+        let succeeded = response.status().is_success() || response.status().is_redirection();
+        if let Some((company_id, user_id, username, ip, detail)) =
+            admin_action.filter(|_| succeeded)
+        {
+            let recorded = record_access_event(
+                &state,
+                AccessEventKind::AdminAction,
+                vec![company_id],
+                Some(user_id),
+                &username,
+                ip,
+                Some(detail),
+            )
+            .await;
+            if let Err(err) = recorded {
+                eprintln!("[access log] failed to record admin action: {err}");
+            }
+        }
+        Ok(response)
+    } else if is_page_request(&request) {
+        // A page opened without a session goes to the sign-in page, which
+        // comes back here once the code is accepted.
+        Err(Redirect::to(&login_page_url(request.uri())).into_response())
     } else {
         Err(unauthorized_response())
     }
 }
 
+/// Whole seconds from now until `at`, zero once it passed.
+pub fn seconds_until(at: DateTime) -> i64 {
+    ((at.timestamp_millis() - DateTime::now().timestamp_millis()) / 1000).max(0)
+}
+
+/// Makes the company of the request's tenant subdomain the active one.
+/// `false` when the subdomain is not one of the user's companies.
+fn select_tenant_company(user: &mut UserWithCompany, headers: &HeaderMap) -> bool {
+    let Some(sub) = headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .and_then(tenant_subdomain_from_host)
+    else {
+        return true;
+    };
+    let Some(idx) = user
+        .company_slugs
+        .iter()
+        .position(|s| s.eq_ignore_ascii_case(sub))
+    else {
+        // Subdominio no corresponde a ninguna compañía del usuario
+        return false;
+    };
+    user.company_id = user.company_ids[idx];
+    user.company_slug = user.company_slugs[idx].clone();
+    user.company_name = user.company_names[idx].clone();
+    if let Some(role) = user.company_roles.get(idx) {
+        user.role = role.clone();
+    }
+    user.permissions = user
+        .company_permissions
+        .get(idx)
+        .cloned()
+        .unwrap_or_default();
+    user.account_ids = user
+        .company_account_ids
+        .get(idx)
+        .cloned()
+        .unwrap_or_default();
+    true
+}
+
+/// Template context of a page open without a session, such as the sign-in
+/// page: the signed-in user's when the request carries a valid session
+/// cookie, signed out otherwise.
+pub async fn optional_template_context(state: &AppState, headers: &HeaderMap) -> TemplateContext {
+    for token in extract_cookies(headers, SESSION_COOKIE_NAME) {
+        if let Ok(Some(mut user)) = find_user_by_session(state, &token).await {
+            if select_tenant_company(&mut user, headers) {
+                return TemplateContext::for_user(&user);
+            }
+            break;
+        }
+    }
+    TemplateContext::default()
+}
+
+/// Page loads of the browser, which show the flash message. Scripts fetching
+/// data in the background do not ask for HTML, so they leave it alone.
+fn is_page_request(request: &Request) -> bool {
+    request.method() == Method::GET
+        && request
+            .headers()
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Read-only data endpoints reachable with a personal access token.
+const API_TOKEN_PATHS: &[&str] = &["/api/tiempo"];
+/// Same, for endpoints that take a path parameter after the prefix.
+const API_TOKEN_PREFIXES: &[&str] = &["/api/v1/transactions/by_external/"];
+
+fn is_api_token_request(request: &Request) -> bool {
+    let path = request.uri().path();
+    matches!(request.method().as_str(), "GET" | "HEAD")
+        && (API_TOKEN_PATHS.contains(&path)
+            || API_TOKEN_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix)))
+}
+
+/// Prometheus reads `/metrics` with the bearer `METRICS_TOKEN`, which opens
+/// nothing else.
+const METRICS_PATH: &str = "/metrics";
+
+/// Compares the SHA-256 of both values byte by byte without stopping early,
+/// so the time taken says nothing about how much of `given` matched.
+fn token_matches(given: &str, expected: &str) -> bool {
+    Sha256::digest(given.as_bytes())
+        .iter()
+        .zip(Sha256::digest(expected.as_bytes()).iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+fn is_metrics_scrape(state: &AppState, request: &Request) -> bool {
+    let Some(expected) = state.metrics_token.as_deref() else {
+        return false;
+    };
+    request.uri().path() == METRICS_PATH
+        && matches!(request.method().as_str(), "GET" | "HEAD")
+        && bearer_token(request.headers()).is_some_and(|given| token_matches(&given, expected))
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
+
+/// Changes made by an admin of the active company, or by a superadmin,
+/// through the admin pages or the admin API; reads are not logged.
+fn is_admin_action(request: &Request, user: &UserWithCompany) -> bool {
+    let path = request.uri().path();
+    (user.role.is_admin() || user.is_superadmin)
+        && !matches!(request.method().as_str(), "GET" | "HEAD" | "OPTIONS")
+        && (path.starts_with("/admin/") || path.starts_with("/api/admin/"))
+}
+
+/// What a read-only role may still post: signing out, keeping the session
+/// alive and its own account and dashboard settings.
+const READ_ONLY_ALLOWED_PATHS: &[&str] = &[
+    "/logout",
+    "/api/session/renew",
+    "/api/account",
+    "/api/me/dashboard",
+];
+const READ_ONLY_ALLOWED_PREFIXES: &[&str] = &["/account"];
+
+/// Role the request acts with: the one in the company of a
+/// `/admin/companies/{id}/...` path, else the one in the active company.
+fn role_for_request<'a>(request: &Request, user: &'a UserWithCompany) -> &'a UserRole {
+    let company_id = request
+        .uri()
+        .path()
+        .split('/')
+        .skip_while(|segment| *segment != "companies")
+        .nth(1)
+        .and_then(|segment| segment.parse::<ObjectId>().ok());
+    company_id
+        .and_then(|id| user.company_ids.iter().position(|cid| *cid == id))
+        .and_then(|idx| user.company_roles.get(idx))
+        .unwrap_or(&user.role)
+}
+
+/// A request that would change data made with a read-only role (auditors).
+/// Checked here rather than in each handler, so new modules are covered
+/// without touching them.
+fn is_read_only_write(request: &Request, user: &UserWithCompany) -> bool {
+    let path = request.uri().path();
+    !matches!(request.method().as_str(), "GET" | "HEAD" | "OPTIONS")
+        && !READ_ONLY_ALLOWED_PATHS.contains(&path)
+        && !READ_ONLY_ALLOWED_PREFIXES
+            .iter()
+            .any(|prefix| path == *prefix || path.starts_with(&format!("{prefix}/")))
+        && role_for_request(request, user).is_read_only()
+}
+
+/// Forms go back to the page they came from with the message; scripts and
+/// API clients get a 403 with it.
+async fn read_only_response(state: &AppState, headers: &HeaderMap, token: &str) -> Response {
+    const MESSAGE: &str = "Tu rol de auditor es de solo lectura.";
+    let is_form = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with("application/x-www-form-urlencoded")
+                || value.starts_with("multipart/form-data")
+        });
+    if !is_form || token.is_empty() {
+        return (
+            StatusCode::FORBIDDEN,
+            axum::Json(serde_json::json!({ "error": MESSAGE })),
+        )
+            .into_response();
+    }
+    if let Err(err) = set_session_flash(state, token, &Flash::error(MESSAGE)).await {
+        eprintln!("[flash] could not keep message: {err}");
+    }
+    // Only paths of this site, never another host from the header.
+    let back = headers
+        .get(REFERER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<axum::http::Uri>().ok())
+        .and_then(|uri| {
+            uri.path_and_query()
+                .map(|pq| pq.as_str().to_string())
+                .filter(|path| path.starts_with('/') && !path.starts_with("//"))
+        })
+        .unwrap_or_else(|| "/".to_string());
+    Redirect::to(&back).into_response()
+}
+
+/// Client address as reported by the reverse proxy. The app is not exposed
+/// directly, so without these headers the address is unknown.
+pub fn client_ip(headers: &HeaderMap) -> Option<String> {
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next());
+    let real_ip = headers.get("x-real-ip").and_then(|v| v.to_str().ok());
+    forwarded
+        .or(real_ip)
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .map(str::to_string)
+}
+
 /// Gate routes so they are only reachable when the active tenant is the test
 /// tenant. Must run AFTER `require_session` (it reads the `SessionData` that
 /// middleware inserts). Returns 404 elsewhere so the test-only surface (Swagger,
@@ -142,6 +498,51 @@ pub fn tenant_subdomain_from_host(host: &str) -> Option<&str> {
     None
 }
 
+#[derive(Clone)]
+pub struct PortalSessionData {
+    pub access: PortalAccess,
+    pub token: String,
+}
+
+/// Gate for the contact portal. Without a valid portal session for the
+/// company of the tenant subdomain (when there is one) it sends the browser to
+/// the portal sign-in page.
+pub async fn require_portal_session(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let tenant = request
+        .headers()
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .and_then(tenant_subdomain_from_host)
+        .map(str::to_string);
+    for token in extract_cookies(request.headers(), PORTAL_COOKIE_NAME) {
+        match find_portal_session(&state, &token).await {
+            Ok(Some(access)) => {
+                if tenant
+                    .as_deref()
+                    .is_some_and(|sub| !sub.eq_ignore_ascii_case(&access.company.slug))
+                {
+                    continue;
+                }
+                request
+                    .extensions_mut()
+                    .insert(PortalSessionData { access, token });
+                return Ok(next.run(request).await);
+            }
+            Ok(None) => continue,
+            Err(_) => {
+                return Err(
+                    (StatusCode::INTERNAL_SERVER_ERROR, "session lookup failed").into_response()
+                );
+            }
+        }
+    }
+    Err(Redirect::to("/portal/login").into_response())
+}
+
 pub struct SessionUser(pub SessionData);
 
 impl SessionUser {
@@ -161,12 +562,27 @@ impl SessionUser {
         self.0.user.role.is_admin()
     }
 
+    /// Admin or auditor of the active company; see `UserRole::can_view_admin`.
+    pub fn can_view_admin(&self) -> bool {
+        self.0.user.role.can_view_admin()
+    }
+
+    /// Platform operator, regardless of the role in the active company.
+    pub fn is_superadmin(&self) -> bool {
+        self.0.user.is_superadmin
+    }
+
     pub fn active_role(&self) -> &crate::models::UserRole {
         &self.0.user.role
     }
 
     pub fn has_permission(&self, permission: UserPermission) -> bool {
-        self.is_admin() || self.0.user.permissions.contains(&permission)
+        self.can_view_admin() || self.0.user.permissions.contains(&permission)
+    }
+
+    /// Accounts of the active company this user may work with.
+    pub fn account_access(&self) -> AccountAccess {
+        AccountAccess::from_ids(&self.0.user.account_ids)
     }
 
     pub fn active_company_id(&self) -> &ObjectId {
@@ -208,11 +624,107 @@ where
     }
 }
 
+/// The contact signed in to the portal; only available behind
+/// `require_portal_session`.
+pub struct PortalContact(pub PortalSessionData);
+
+impl PortalContact {
+    pub fn contact(&self) -> &crate::models::Contact {
+        &self.0.access.contact
+    }
+
+    pub fn company(&self) -> &crate::models::Company {
+        &self.0.access.company
+    }
+
+    pub fn token(&self) -> &str {
+        &self.0.token
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl<S> FromRequestParts<S> for PortalContact
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> BoxFuture<'static, Result<Self, Self::Rejection>> {
+        let data = parts
+            .extensions
+            .get::<PortalSessionData>()
+            .cloned()
+            .map(PortalContact)
+            .ok_or_else(unauthorized_response);
+        Box::pin(async move { data })
+    }
+}
+
 fn unauthorized_response() -> Response {
     (StatusCode::UNAUTHORIZED, "unauthorized").into_response()
 }
 
-fn extract_cookies(headers: &HeaderMap, name: &str) -> Vec<String> {
+/// Query parameter of the sign-in page with the page to return to.
+pub const LOGIN_NEXT_PARAM: &str = "next";
+/// Sections a sign-in may return to.
+const LOGIN_RETURN_PREFIXES: &[&str] = &[
+    "/account",
+    "/admin",
+    "/overview",
+    "/print",
+    "/tiempo",
+    "/v2",
+];
+/// Longer return paths are dropped rather than cut short.
+const LOGIN_RETURN_MAX_LEN: usize = 2048;
+
+/// `value` when it is a path (and query) of this site under one of
+/// `LOGIN_RETURN_PREFIXES`. Other hosts, schemes and sections give `None`, so
+/// a crafted sign-in link cannot send the user anywhere else.
+pub fn safe_return_path(value: &str) -> Option<String> {
+    if value.len() > LOGIN_RETURN_MAX_LEN
+        || !value.starts_with('/')
+        || value.starts_with("//")
+        || value
+            .chars()
+            .any(|c| c == '\\' || c == '#' || c.is_control())
+    {
+        return None;
+    }
+    let uri = value.parse::<Uri>().ok()?;
+    if uri.scheme().is_some() || uri.authority().is_some() {
+        return None;
+    }
+    let path = uri.path();
+    if path.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+    let allowed = LOGIN_RETURN_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    });
+    if !allowed {
+        return None;
+    }
+    uri.path_and_query().map(|pq| pq.as_str().to_string())
+}
+
+/// The sign-in page, carrying `uri` to return to when it may be.
+fn login_page_url(uri: &Uri) -> String {
+    let requested = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    match safe_return_path(requested) {
+        Some(next) => format!(
+            "/?{LOGIN_NEXT_PARAM}={}",
+            form_urlencoded::byte_serialize(next.as_bytes()).collect::<String>()
+        ),
+        None => "/".to_string(),
+    }
+}
+
+pub(crate) fn extract_cookies(headers: &HeaderMap, name: &str) -> Vec<String> {
     headers
         .get_all(COOKIE)
         .into_iter()
@@ -247,7 +759,15 @@ pub(crate) fn test_env_lock() -> std::sync::MutexGuard<'static, ()> {
 
 #[cfg(test)]
 mod tests {
-    use super::tenant_subdomain_from_host;
+    use super::{login_page_url, safe_return_path, tenant_subdomain_from_host, token_matches};
+
+    #[test]
+    fn scrape_token_must_match_exactly() {
+        assert!(token_matches("scrape-secret", "scrape-secret"));
+        assert!(!token_matches("scrape-secreT", "scrape-secret"));
+        assert!(!token_matches("scrape", "scrape-secret"));
+        assert!(!token_matches("", "scrape-secret"));
+    }
 
     fn env_lock() -> std::sync::MutexGuard<'static, ()> {
         super::test_env_lock()
@@ -300,4 +820,38 @@ mod tests {
         assert_eq!(tenant_subdomain_from_host("acme.evil.test"), None);
         assert_eq!(tenant_subdomain_from_host("127.0.0.1:8090"), None);
     }
+
+    #[test]
+    fn return_paths_stay_within_the_app() {
+        assert_eq!(
+            safe_return_path("/admin/transactions?month=2026-03").as_deref(),
+            Some("/admin/transactions?month=2026-03")
+        );
+        assert_eq!(safe_return_path("/tiempo").as_deref(), Some("/tiempo"));
+        assert_eq!(
+            safe_return_path("/v2/accounts").as_deref(),
+            Some("/v2/accounts")
+        );
+        assert_eq!(safe_return_path("//evil.test/admin"), None);
+        assert_eq!(safe_return_path("https://evil.test/admin"), None);
+        assert_eq!(safe_return_path("/\\evil.test"), None);
+        assert_eq!(safe_return_path("/administrator"), None);
+        assert_eq!(safe_return_path("/admin/../logout"), None);
+        assert_eq!(safe_return_path("/logout"), None);
+        assert_eq!(safe_return_path("/api/me"), None);
+        assert_eq!(safe_return_path("admin"), None);
+    }
+
+    #[test]
+    fn login_page_carries_the_requested_page() {
+        let uri = "/admin/accounts?q=caja chica"
+            .replace(' ', "%20")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            login_page_url(&uri),
+            "/?next=%2Fadmin%2Faccounts%3Fq%3Dcaja%2520chica"
+        );
+        assert_eq!(login_page_url(&"/api/me".parse().unwrap()), "/");
+    }
 }
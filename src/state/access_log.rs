@@ -0,0 +1,217 @@
+// Access log: logins, failed logins and admin actions, summarized per day for
+// the company's security page.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::models::{AccessEvent, AccessEventKind};
+
+use super::AppState;
+
+/// Failed logins of one user in one day that are reported as an anomaly.
+pub const FAILED_LOGIN_ALERT: u64 = 5;
+
+pub async fn record_access_event(
+    state: &AppState,
+    kind: AccessEventKind,
+    company_ids: Vec<ObjectId>,
+    user_id: Option<ObjectId>,
+    username: &str,
+    ip: Option<String>,
+    detail: Option<String>,
+) -> Result<()> {
+    state
+        .access_events
+        .insert_one(AccessEvent {
+            id: None,
+            company_ids,
+            kind,
+            user_id,
+            username: username.to_string(),
+            ip,
+            detail,
+            created_at: DateTime::now(),
+        })
+        .await?;
+    Ok(())
+}
+
+/// Events visible to the company since `since`, newest first.
+pub async fn list_access_events(
+    state: &AppState,
+    company_id: &ObjectId,
+    since: DateTime,
+) -> Result<Vec<AccessEvent>> {
+    let events = state
+        .for_reports(&state.access_events)
+        .find(doc! { "company_ids": company_id, "created_at": { "$gte": since } })
+        .sort(doc! { "created_at": -1 })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(events)
+}
+
+/// Event counts of one UTC day.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessDay {
+    pub day: NaiveDate,
+    pub sessions: u64,
+    pub failed_logins: u64,
+    pub admin_actions: u64,
+}
+
+fn event_day(event: &AccessEvent) -> NaiveDate {
+    event.created_at.to_chrono().date_naive()
+}
+
+/// Counts per day, newest day first; days without events are left out.
+pub fn summarize_access_by_day(events: &[AccessEvent]) -> Vec<AccessDay> {
+    let mut days: BTreeMap<NaiveDate, AccessDay> = BTreeMap::new();
+    for event in events {
+        let day = event_day(event);
+        let entry = days.entry(day).or_insert_with(|| AccessDay {
+            day,
+            ..AccessDay::default()
+        });
+        match event.kind {
+            AccessEventKind::SessionCreated => entry.sessions += 1,
+            AccessEventKind::LoginFailed => entry.failed_logins += 1,
+            AccessEventKind::AdminAction => entry.admin_actions += 1,
+        }
+    }
+    days.into_values().rev().collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessAnomaly {
+    pub day: NaiveDate,
+    pub username: String,
+    pub description: String,
+}
+
+/// Flags bursts of failed logins (at least [`FAILED_LOGIN_ALERT`] for one
+/// user in a day) and sessions opened from an address the user had not used
+/// earlier in `events`. Users whose first session is in the window set their
+/// baseline and are not flagged for it. Newest first.
+pub fn access_anomalies(events: &[AccessEvent]) -> Vec<AccessAnomaly> {
+    let mut ordered: Vec<&AccessEvent> = events.iter().collect();
+    ordered.sort_by_key(|event| event.created_at);
+
+    let mut anomalies = Vec::new();
+    let mut failures: BTreeMap<(NaiveDate, &str), u64> = BTreeMap::new();
+    let mut known_ips: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for event in &ordered {
+        match event.kind {
+            AccessEventKind::LoginFailed => {
+                *failures
+                    .entry((event_day(event), event.username.as_str()))
+                    .or_default() += 1;
+            }
+            AccessEventKind::SessionCreated => {
+                let Some(ip) = event.ip.as_deref() else {
+                    continue;
+                };
+                let seen = known_ips.entry(event.username.as_str()).or_default();
+                if !seen.is_empty() && !seen.contains(ip) {
+                    anomalies.push(AccessAnomaly {
+                        day: event_day(event),
+                        username: event.username.clone(),
+                        description: format!("Inicio de sesión desde una IP nueva ({ip})"),
+                    });
+                }
+                seen.insert(ip);
+            }
+            AccessEventKind::AdminAction => {}
+        }
+    }
+    for ((day, username), count) in failures {
+        if count >= FAILED_LOGIN_ALERT {
+            anomalies.push(AccessAnomaly {
+                day,
+                username: username.to_string(),
+                description: format!("{count} accesos fallidos en un día"),
+            });
+        }
+    }
+    anomalies.sort_by(|a, b| b.day.cmp(&a.day).then_with(|| a.username.cmp(&b.username)));
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: AccessEventKind, username: &str, ip: Option<&str>, at: &str) -> AccessEvent {
+        AccessEvent {
+            id: None,
+            company_ids: vec![],
+            kind,
+            user_id: None,
+            username: username.to_string(),
+            ip: ip.map(str::to_string),
+            detail: None,
+            created_at: DateTime::parse_rfc3339_str(at).unwrap(),
+        }
+    }
+
+    #[test]
+    fn days_are_counted_and_bursts_and_new_addresses_flagged() {
+        let mut events = vec![
+            event(
+                AccessEventKind::SessionCreated,
+                "ana",
+                Some("10.0.0.1"),
+                "2026-05-01T09:00:00Z",
+            ),
+            event(
+                AccessEventKind::AdminAction,
+                "ana",
+                None,
+                "2026-05-01T09:05:00Z",
+            ),
+            event(
+                AccessEventKind::SessionCreated,
+                "ana",
+                Some("10.0.0.1"),
+                "2026-05-02T09:00:00Z",
+            ),
+            event(
+                AccessEventKind::SessionCreated,
+                "ana",
+                Some("203.0.113.9"),
+                "2026-05-03T23:00:00Z",
+            ),
+        ];
+        for minute in 10..15 {
+            events.push(event(
+                AccessEventKind::LoginFailed,
+                "luis",
+                None,
+                &format!("2026-05-02T10:{minute}:00Z"),
+            ));
+        }
+        events.push(event(
+            AccessEventKind::LoginFailed,
+            "ana",
+            None,
+            "2026-05-02T10:00:00Z",
+        ));
+
+        let days = summarize_access_by_day(&events);
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].day, NaiveDate::from_ymd_opt(2026, 5, 3).unwrap());
+        assert_eq!((days[1].sessions, days[1].failed_logins), (1, 6));
+        assert_eq!(days[2].admin_actions, 1);
+
+        let anomalies = access_anomalies(&events);
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].username, "ana");
+        assert!(anomalies[0].description.contains("203.0.113.9"));
+        assert_eq!(anomalies[1].username, "luis");
+        assert_eq!(anomalies[1].description, "5 accesos fallidos en un día");
+    }
+}
@@ -0,0 +1,188 @@
+// Accounts a user may work with in a company, e.g. only the petty cash. The
+// list lives on the membership (`UserCompany::account_ids`); an empty list
+// leaves every account open. Restricted users only see the transactions that
+// touch their accounts and the planned entries and plans expected on them,
+// and can only move money through those accounts.
+
+use anyhow::{Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Document, doc, oid::ObjectId};
+
+use crate::models::{Account, Transaction};
+
+use super::{AppState, CompanyEvent, CompanyEventKind};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AccountAccess {
+    #[default]
+    All,
+    Only(Vec<ObjectId>),
+}
+
+impl AccountAccess {
+    pub fn from_ids(account_ids: &[ObjectId]) -> Self {
+        if account_ids.is_empty() {
+            AccountAccess::All
+        } else {
+            AccountAccess::Only(account_ids.to_vec())
+        }
+    }
+
+    pub fn allows(&self, account_id: &ObjectId) -> bool {
+        match self {
+            AccountAccess::All => true,
+            AccountAccess::Only(ids) => ids.contains(account_id),
+        }
+    }
+
+    /// Whether the transaction touches one of the accounts.
+    pub fn allows_transaction(&self, transaction: &Transaction) -> bool {
+        match self {
+            AccountAccess::All => true,
+            AccountAccess::Only(_) => [&transaction.account_from_id, &transaction.account_to_id]
+                .into_iter()
+                .flatten()
+                .any(|id| self.allows(id)),
+        }
+    }
+
+    /// Whether the event is about a record the lists show the user:
+    /// transactions and planned entries on one of the accounts, and every
+    /// forecast.
+    pub fn allows_event(&self, event: &CompanyEvent) -> bool {
+        match (self, event.kind) {
+            (AccountAccess::All, _) | (_, CompanyEventKind::ForecastReady) => true,
+            (AccountAccess::Only(_), _) => event.account_ids.iter().any(|id| self.allows(id)),
+        }
+    }
+
+    /// Fails unless every account given is allowed; for records about to be
+    /// created or changed.
+    pub fn ensure<'a>(&self, account_ids: impl IntoIterator<Item = &'a ObjectId>) -> Result<()> {
+        if account_ids.into_iter().all(|id| self.allows(id)) {
+            Ok(())
+        } else {
+            bail!("account not allowed for this user")
+        }
+    }
+
+    /// `filter` narrowed to the transactions that touch one of the accounts.
+    pub fn restrict_transactions(&self, filter: Document) -> Document {
+        match self {
+            AccountAccess::All => filter,
+            AccountAccess::Only(ids) => doc! {
+                "$and": [
+                    filter,
+                    { "$or": [
+                        { "account_from_id": { "$in": ids.as_slice() } },
+                        { "account_to_id": { "$in": ids.as_slice() } },
+                    ] },
+                ],
+            },
+        }
+    }
+}
+
+/// Accounts of the company the user may work with, by name.
+pub async fn list_accessible_accounts(
+    state: &AppState,
+    company_id: &ObjectId,
+    access: &AccountAccess,
+) -> Result<Vec<Account>> {
+    let filter = match access {
+        AccountAccess::All => doc! { "company_id": company_id },
+        AccountAccess::Only(ids) => {
+            doc! { "company_id": company_id, "_id": { "$in": ids.as_slice() } }
+        }
+    };
+    let accounts = state
+        .accounts
+        .find(filter)
+        .sort(doc! { "name": 1 })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(accounts)
+}
+
+/// Limits the user to `account_ids` in the company, or lifts the limit when
+/// empty. Every account must belong to the company.
+pub async fn set_account_access(
+    state: &AppState,
+    user_id: &ObjectId,
+    company_id: &ObjectId,
+    account_ids: &[ObjectId],
+) -> Result<()> {
+    let mut account_ids = account_ids.to_vec();
+    account_ids.sort();
+    account_ids.dedup();
+    let found = state
+        .accounts
+        .count_documents(
+            doc! { "_id": { "$in": account_ids.as_slice() }, "company_id": company_id },
+        )
+        .await?;
+    if found != account_ids.len() as u64 {
+        bail!("account does not belong to the company");
+    }
+    let res = state
+        .user_companies
+        .update_one(
+            doc! { "user_id": user_id, "company_id": company_id },
+            doc! { "$set": { "account_ids": account_ids } },
+        )
+        .await?;
+    if res.matched_count == 0 {
+        bail!("user is not a member of the company");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restricted_access_only_lets_its_accounts_through() {
+        let petty_cash = ObjectId::new();
+        let bank = ObjectId::new();
+        assert_eq!(AccountAccess::from_ids(&[]), AccountAccess::All);
+        let access = AccountAccess::from_ids(&[petty_cash]);
+        assert!(access.allows(&petty_cash));
+        assert!(!access.allows(&bank));
+        assert!(access.ensure([&petty_cash]).is_ok());
+        assert!(access.ensure([&petty_cash, &bank]).is_err());
+        assert!(AccountAccess::All.ensure([&bank]).is_ok());
+
+        let filter = doc! { "company_id": ObjectId::new() };
+        assert_eq!(
+            AccountAccess::All.restrict_transactions(filter.clone()),
+            filter
+        );
+        assert!(access.restrict_transactions(filter).contains_key("$and"));
+    }
+
+    #[test]
+    fn restricted_access_only_hears_of_its_accounts() {
+        let petty_cash = ObjectId::new();
+        let bank = ObjectId::new();
+        let event = |kind: CompanyEventKind, account_ids: Vec<ObjectId>| CompanyEvent {
+            company_id: ObjectId::new(),
+            kind,
+            id: ObjectId::new(),
+            account_ids,
+        };
+        let access = AccountAccess::from_ids(&[petty_cash]);
+        assert!(access.allows_event(&event(
+            CompanyEventKind::TransactionCreated,
+            vec![bank, petty_cash]
+        )));
+        assert!(!access.allows_event(&event(CompanyEventKind::TransactionCreated, vec![bank])));
+        assert!(!access.allows_event(&event(CompanyEventKind::TransactionCreated, vec![])));
+        assert!(!access.allows_event(&event(CompanyEventKind::EntryCovered, vec![bank])));
+        assert!(access.allows_event(&event(CompanyEventKind::ForecastReady, vec![])));
+        assert!(
+            AccountAccess::All.allows_event(&event(CompanyEventKind::EntryCovered, vec![bank]))
+        );
+    }
+}
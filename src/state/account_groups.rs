@@ -0,0 +1,344 @@
+// Account groups: headings a company lists its accounts under ("Bancos",
+// "Tarjetas", "Inversiones"). Groups and the accounts inside them keep the
+// order the admin drags them into on the accounts page, which shows each
+// group with its balances and a subtotal per currency. Accounts without a
+// group, or whose group was deleted, are listed last.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{Account, AccountGroup};
+
+use super::{AppState, get_account_by_id};
+
+pub async fn list_account_groups(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<AccountGroup>> {
+    Ok(state
+        .account_groups
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "position": 1, "name": 1 })
+        .await?
+        .try_collect()
+        .await?)
+}
+
+pub async fn get_account_group_by_id(
+    state: &AppState,
+    id: &ObjectId,
+) -> Result<Option<AccountGroup>> {
+    Ok(state.account_groups.find_one(doc! { "_id": id }).await?)
+}
+
+/// Creates a group after the company's existing ones.
+pub async fn create_account_group(
+    state: &AppState,
+    company_id: &ObjectId,
+    name: &str,
+) -> Result<ObjectId> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("El nombre del grupo es obligatorio");
+    }
+    let exists = state
+        .account_groups
+        .find_one(doc! { "company_id": company_id, "name": name })
+        .await?
+        .is_some();
+    if exists {
+        bail!("Ya existe un grupo llamado {name}");
+    }
+    let position = state
+        .account_groups
+        .count_documents(doc! { "company_id": company_id })
+        .await? as i32;
+    let res = state
+        .account_groups
+        .insert_one(AccountGroup {
+            id: None,
+            company_id: *company_id,
+            name: name.to_string(),
+            position,
+            created_at: Some(DateTime::from_system_time(SystemTime::now())),
+        })
+        .await?;
+    res.inserted_id
+        .as_object_id()
+        .context("account group insert missing _id")
+}
+
+/// Removes the group; its accounts are left without one.
+pub async fn delete_account_group(state: &AppState, group: &AccountGroup) -> Result<()> {
+    let id = group.id.context("account group without _id")?;
+    state
+        .accounts
+        .update_many(
+            doc! { "company_id": group.company_id, "group_id": id },
+            doc! { "$unset": { "group_id": "" } },
+        )
+        .await?;
+    state.account_groups.delete_one(doc! { "_id": id }).await?;
+    Ok(())
+}
+
+/// Moves the account to `group_id` (`None` leaves it without a group), at
+/// the end of it. Does nothing when the account is already there.
+pub async fn set_account_group(
+    state: &AppState,
+    company_id: &ObjectId,
+    account_id: &ObjectId,
+    group_id: Option<ObjectId>,
+) -> Result<()> {
+    let account = get_account_by_id(state, account_id)
+        .await?
+        .context("account not found")?;
+    if account.company_id != *company_id {
+        bail!("account belongs to another company");
+    }
+    if account.group_id == group_id {
+        return Ok(());
+    }
+    if let Some(group_id) = group_id.as_ref() {
+        let group = get_account_group_by_id(state, group_id)
+            .await?
+            .context("El grupo no existe")?;
+        if group.company_id != *company_id {
+            bail!("El grupo no existe");
+        }
+    }
+    let mut in_group = doc! { "company_id": company_id };
+    match group_id {
+        Some(id) => in_group.insert("group_id", id),
+        None => in_group.insert("group_id", doc! { "$exists": false }),
+    };
+    let position = state.accounts.count_documents(in_group).await? as i32;
+    let update = match group_id {
+        Some(id) => doc! { "$set": { "group_id": id, "position": position } },
+        None => doc! { "$set": { "position": position }, "$unset": { "group_id": "" } },
+    };
+    state
+        .accounts
+        .update_one(doc! { "_id": account_id }, update)
+        .await?;
+    Ok(())
+}
+
+/// One group of the order sent by the accounts page: the group (`None` for
+/// the accounts without one) and its accounts, top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountOrderGroup {
+    pub group_id: Option<ObjectId>,
+    pub account_ids: Vec<ObjectId>,
+}
+
+/// Stores the order of the company's groups and accounts as listed in
+/// `order`, moving each account to the group it is listed under. Groups and
+/// accounts left out keep their place.
+pub async fn reorder_accounts(
+    state: &AppState,
+    company_id: &ObjectId,
+    order: &[AccountOrderGroup],
+) -> Result<()> {
+    let groups: HashSet<ObjectId> = list_account_groups(state, company_id)
+        .await?
+        .into_iter()
+        .filter_map(|group| group.id)
+        .collect();
+    let accounts: HashSet<ObjectId> = state
+        .accounts
+        .find(doc! { "company_id": company_id })
+        .await?
+        .try_collect::<Vec<Account>>()
+        .await?
+        .into_iter()
+        .filter_map(|account| account.id)
+        .collect();
+
+    let mut seen_groups = HashSet::new();
+    let mut seen_accounts = HashSet::new();
+    for entry in order {
+        if !seen_groups.insert(entry.group_id) {
+            bail!("Un grupo aparece más de una vez");
+        }
+        if let Some(group_id) = entry.group_id.as_ref()
+            && !groups.contains(group_id)
+        {
+            bail!("El grupo no existe");
+        }
+        for account_id in &entry.account_ids {
+            if !accounts.contains(account_id) {
+                bail!("La cuenta no existe");
+            }
+            if !seen_accounts.insert(*account_id) {
+                bail!("Una cuenta aparece más de una vez");
+            }
+        }
+    }
+
+    for (position, group_id) in order.iter().filter_map(|entry| entry.group_id).enumerate() {
+        state
+            .account_groups
+            .update_one(
+                doc! { "_id": group_id },
+                doc! { "$set": { "position": position as i32 } },
+            )
+            .await?;
+    }
+    for entry in order {
+        for (position, account_id) in entry.account_ids.iter().enumerate() {
+            let update = match entry.group_id {
+                Some(group_id) => {
+                    doc! { "$set": { "group_id": group_id, "position": position as i32 } }
+                }
+                None => doc! {
+                    "$set": { "position": position as i32 },
+                    "$unset": { "group_id": "" },
+                },
+            };
+            state
+                .accounts
+                .update_one(doc! { "_id": account_id }, update)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// A group of the accounts page and its accounts in order. `group` is `None`
+/// for the accounts without a group.
+#[derive(Debug, Clone)]
+pub struct AccountGroupSection {
+    pub group: Option<AccountGroup>,
+    pub accounts: Vec<Account>,
+}
+
+/// Splits `accounts` by group, following the order of `groups` (already
+/// sorted) and ending with the accounts without a known group. Every group
+/// gets a section, even an empty one, so accounts can be dragged into it.
+pub fn group_accounts(
+    groups: Vec<AccountGroup>,
+    accounts: Vec<Account>,
+) -> Vec<AccountGroupSection> {
+    let mut sections: Vec<AccountGroupSection> = groups
+        .into_iter()
+        .map(|group| AccountGroupSection {
+            group: Some(group),
+            accounts: Vec::new(),
+        })
+        .collect();
+    let mut ungrouped = Vec::new();
+    for account in accounts {
+        let section = account.group_id.and_then(|group_id| {
+            sections
+                .iter_mut()
+                .find(|section| section.group.as_ref().and_then(|group| group.id) == Some(group_id))
+        });
+        match section {
+            Some(section) => section.accounts.push(account),
+            None => ungrouped.push(account),
+        }
+    }
+    sections.push(AccountGroupSection {
+        group: None,
+        accounts: ungrouped,
+    });
+    for section in &mut sections {
+        section.accounts.sort_by(|a, b| {
+            a.position
+                .cmp(&b.position)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+    sections
+}
+
+/// Adds up `(currency, amount)` pairs per currency, by currency code.
+/// Amounts in different currencies are never added together.
+pub fn currency_subtotals<'a>(
+    amounts: impl IntoIterator<Item = (&'a str, f64)>,
+) -> Vec<(String, f64)> {
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for (currency, amount) in amounts {
+        *totals.entry(currency).or_default() += amount;
+    }
+    totals
+        .into_iter()
+        .map(|(currency, total)| (currency.to_string(), (total * 100.0).round() / 100.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AccountType;
+
+    fn group(name: &str, position: i32) -> AccountGroup {
+        AccountGroup {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            name: name.to_string(),
+            position,
+            created_at: None,
+        }
+    }
+
+    fn account(name: &str, group_id: Option<ObjectId>, position: i32) -> Account {
+        Account {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            name: name.to_string(),
+            account_type: AccountType::Bank,
+            currency: "MXN".to_string(),
+            is_active: true,
+            created_at: None,
+            updated_at: None,
+            notes: None,
+            opening_balance: 0.0,
+            opening_date: None,
+            opening_balance_changes: Vec::new(),
+            group_id,
+            position,
+        }
+    }
+
+    #[test]
+    fn accounts_follow_their_group_then_position() {
+        let banks = group("Bancos", 0);
+        let cards = group("Tarjetas", 1);
+        let sections = group_accounts(
+            vec![banks.clone(), cards.clone()],
+            vec![
+                account("Santander", banks.id, 1),
+                account("Caja", None, 0),
+                account("BBVA", banks.id, 0),
+                account("Huérfana", Some(ObjectId::new()), 0),
+            ],
+        );
+        let names: Vec<Vec<&str>> = sections
+            .iter()
+            .map(|section| section.accounts.iter().map(|a| a.name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            names,
+            vec![vec!["BBVA", "Santander"], vec![], vec!["Caja", "Huérfana"]]
+        );
+        assert_eq!(sections[1].group.as_ref().unwrap().name, "Tarjetas");
+        assert!(sections[2].group.is_none());
+    }
+
+    #[test]
+    fn subtotals_never_mix_currencies() {
+        let totals = currency_subtotals([("USD", 10.0), ("MXN", 100.5), ("USD", 0.25)]);
+        assert_eq!(
+            totals,
+            vec![("MXN".to_string(), 100.5), ("USD".to_string(), 10.25)]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, doc, oid::ObjectId};
+
+use crate::models::{FlowType, PlannedStatus};
+
+use super::AppState;
+
+/// Labels of the aging buckets, in days past due.
+pub const AGING_BUCKETS: [&str; 4] = ["0-30", "31-60", "61-90", "90+"];
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Outstanding amount of the past-due commitments of one contact and flow
+/// type: receivables for income, payables for expense.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgingRow {
+    pub flow_type: FlowType,
+    pub contact_id: Option<ObjectId>,
+    /// Amounts per `AGING_BUCKETS` entry.
+    pub buckets: [f64; 4],
+    pub entries: i64,
+}
+
+impl AgingRow {
+    pub fn total(&self) -> f64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// Ages the open planned entries of the company that are due on or before
+/// `now`. The outstanding amount is the estimate minus the confirmed
+/// transactions covering it; fully paid entries are left out. Rows come
+/// sorted by flow type and largest total first.
+pub async fn aging_report(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<Vec<AgingRow>> {
+    let bucket = |min_days: i64, max_days: Option<i64>| {
+        let mut conditions = vec![Bson::Document(
+            doc! { "$gte": ["$days_past_due", min_days] },
+        )];
+        if let Some(max_days) = max_days {
+            conditions.push(Bson::Document(
+                doc! { "$lte": ["$days_past_due", max_days] },
+            ));
+        }
+        doc! { "$sum": { "$cond": [{ "$and": conditions }, "$outstanding", 0.0] } }
+    };
+    let pipeline = vec![
+        doc! { "$match": {
+            "company_id": company_id,
+            "due_date": { "$lte": now },
+            "status": { "$in": [
+                PlannedStatus::Planned.as_str(),
+                PlannedStatus::PartiallyCovered.as_str(),
+                PlannedStatus::Overdue.as_str(),
+            ] },
+        }},
+        // Drafts do not cover anything until they are confirmed, as in
+        // recalculate_planned_entry_status.
+        doc! { "$lookup": {
+            "from": "transactions",
+            "let": { "entry_id": "$_id" },
+            "pipeline": [
+                { "$match": {
+                    "$expr": { "$eq": ["$planned_entry_id", "$$entry_id"] },
+                    "is_confirmed": { "$ne": false },
+                }},
+                { "$group": { "_id": null, "paid": { "$sum": "$amount" } } },
+            ],
+            "as": "payments",
+        }},
+        doc! { "$project": {
+            "flow_type": 1,
+            "contact_id": { "$ifNull": ["$contact_id", null] },
+            "outstanding": { "$subtract": [
+                "$amount_estimated",
+                { "$ifNull": [{ "$arrayElemAt": ["$payments.paid", 0] }, 0.0] },
+            ]},
+            "days_past_due": { "$floor": {
+                "$divide": [{ "$subtract": [now, "$due_date"] }, DAY_MS]
+            }},
+        }},
+        doc! { "$match": { "outstanding": { "$gt": 0.0 } } },
+        doc! { "$group": {
+            "_id": { "flow_type": "$flow_type", "contact_id": "$contact_id" },
+            "b0": bucket(0, Some(30)),
+            "b1": bucket(31, Some(60)),
+            "b2": bucket(61, Some(90)),
+            "b3": bucket(91, None),
+            "entries": { "$sum": 1 },
+        }},
+    ];
+
+    let mut rows = Vec::new();
+    let mut cursor = state
+        .for_reports(&state.planned_entries)
+        .aggregate(pipeline)
+        .await?;
+    while let Some(row) = cursor.try_next().await? {
+        let key = row.get_document("_id")?;
+        let flow_type = match key.get_str("flow_type")? {
+            "income" => FlowType::Income,
+            _ => FlowType::Expense,
+        };
+        let amount = |field: &str| match row.get(field) {
+            Some(Bson::Double(v)) => *v,
+            Some(Bson::Int32(v)) => f64::from(*v),
+            Some(Bson::Int64(v)) => *v as f64,
+            _ => 0.0,
+        };
+        rows.push(AgingRow {
+            flow_type,
+            contact_id: key.get_object_id("contact_id").ok(),
+            buckets: [amount("b0"), amount("b1"), amount("b2"), amount("b3")],
+            entries: match row.get("entries") {
+                Some(Bson::Int32(n)) => i64::from(*n),
+                Some(Bson::Int64(n)) => *n,
+                _ => 0,
+            },
+        });
+    }
+    rows.sort_by(|a, b| {
+        (a.flow_type != FlowType::Income)
+            .cmp(&(b.flow_type != FlowType::Income))
+            .then(b.total().total_cmp(&a.total()))
+    });
+    Ok(rows)
+}
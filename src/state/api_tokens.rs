@@ -0,0 +1,88 @@
+// Personal access tokens: created and revoked by their owner under /account,
+// accepted as `Authorization: Bearer` on the read-only data endpoints.
+
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::models::ApiToken;
+
+use super::{AppState, UserWithCompany, get_user_by_id};
+
+/// Marks the tokens of this app so they are easy to spot in scripts and logs.
+pub const API_TOKEN_PREFIX: &str = "alf_";
+/// Characters of the token kept in the clear to recognize it in the list.
+const SHOWN_PREFIX_LEN: usize = 8;
+
+pub(super) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Creates a token for the user and returns it. This is the only time the
+/// token is available; afterwards only its hash is kept.
+pub async fn create_api_token(state: &AppState, user_id: &ObjectId, name: &str) -> Result<String> {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = format!("{API_TOKEN_PREFIX}{}", BASE32_NOPAD.encode(&bytes));
+    state
+        .api_tokens
+        .insert_one(ApiToken {
+            id: None,
+            user_id: *user_id,
+            name: name.to_string(),
+            token_hash: hash_token(&token),
+            prefix: token.chars().take(SHOWN_PREFIX_LEN).collect(),
+            created_at: DateTime::now(),
+            last_used_at: None,
+        })
+        .await?;
+    Ok(token)
+}
+
+/// Tokens of the user, newest first.
+pub async fn list_api_tokens(state: &AppState, user_id: &ObjectId) -> Result<Vec<ApiToken>> {
+    state
+        .api_tokens
+        .find(doc! { "user_id": user_id })
+        .sort(doc! { "created_at": -1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Deletes one of the user's tokens. Returns `false` when the user has no
+/// token with that id.
+pub async fn revoke_api_token(state: &AppState, user_id: &ObjectId, id: &ObjectId) -> Result<bool> {
+    let res = state
+        .api_tokens
+        .delete_one(doc! { "_id": id, "user_id": user_id })
+        .await?;
+    Ok(res.deleted_count > 0)
+}
+
+/// Owner of the token, when it exists and the owner is active. Records when
+/// the token was last used.
+pub async fn find_user_by_api_token(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<UserWithCompany>> {
+    let Some(api_token) = state
+        .api_tokens
+        .find_one_and_update(
+            doc! { "token_hash": hash_token(token) },
+            doc! { "$set": { "last_used_at": DateTime::now() } },
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    let user = get_user_by_id(state, &api_token.user_id).await?;
+    Ok(user.filter(|user| user.is_active))
+}
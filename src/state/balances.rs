@@ -0,0 +1,297 @@
+use anyhow::{Context, Result};
+use chrono::{Duration as ChronoDuration, NaiveTime, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, doc, oid::ObjectId};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use crate::metrics::metrics;
+use crate::models::{Account, AccountBalanceSnapshot, OpeningBalanceChange, Transaction};
+
+use super::AppState;
+
+/// Days of daily snapshots the nightly job keeps filled in for each account.
+pub const BALANCE_SNAPSHOT_BACKFILL_DAYS: i64 = 90;
+
+/// Start of the UTC day `at` falls in.
+pub fn utc_day_start(at: DateTime) -> DateTime {
+    let day = at.to_chrono().date_naive().and_time(NaiveTime::MIN);
+    DateTime::from_chrono(day.and_utc())
+}
+
+/// Latest snapshot of the account taken at or before `at`.
+async fn latest_balance_snapshot(
+    state: &AppState,
+    account_id: &ObjectId,
+    at: DateTime,
+) -> Result<Option<AccountBalanceSnapshot>> {
+    state
+        .balance_snapshots
+        .find_one(doc! { "account_id": account_id, "as_of": { "$lte": at } })
+        .sort(doc! { "as_of": -1 })
+        .await
+        .map_err(Into::into)
+}
+
+/// Opening balance of the account and the day it is as of.
+async fn account_opening(
+    state: &AppState,
+    account_id: &ObjectId,
+) -> Result<(f64, Option<DateTime>)> {
+    Ok(state
+        .accounts
+        .find_one(doc! { "_id": account_id })
+        .await?
+        .map(|account| (account.opening_balance, account.opening_date))
+        .unwrap_or((0.0, None)))
+}
+
+/// Balance of the account just before `at`: the opening balance, plus money
+/// in through `account_to_id` minus money out through `account_from_id`,
+/// counting confirmed transactions from the opening date on. Before the
+/// opening date the account has no balance. Starts from the latest snapshot
+/// and only sums the transactions dated after it.
+pub async fn account_balance_at(
+    state: &AppState,
+    account_id: &ObjectId,
+    at: DateTime,
+) -> Result<f64> {
+    let (opening_balance, opening_date) = account_opening(state, account_id).await?;
+    if opening_date.is_some_and(|opening| at < opening) {
+        return Ok(0.0);
+    }
+    let snapshot = latest_balance_snapshot(state, account_id, at)
+        .await?
+        .filter(|snapshot| opening_date.is_none_or(|opening| snapshot.as_of >= opening));
+    let mut date = doc! { "$lt": at };
+    let base = match snapshot {
+        Some(snapshot) => {
+            date.insert("$gte", snapshot.as_of);
+            snapshot.balance
+        }
+        None => {
+            if let Some(opening) = opening_date {
+                date.insert("$gte", opening);
+            }
+            opening_balance
+        }
+    };
+
+    let pipeline = vec![
+        doc! { "$match": {
+            "$or": [{ "account_from_id": account_id }, { "account_to_id": account_id }],
+            "is_confirmed": { "$ne": false },
+            "date": date,
+        }},
+        doc! { "$group": {
+            "_id": null,
+            "net": { "$sum": { "$cond": [
+                { "$eq": ["$account_to_id", account_id] },
+                "$amount",
+                { "$multiply": ["$amount", -1] },
+            ]}},
+        }},
+    ];
+    let mut cursor = state.transactions.aggregate(pipeline).await?;
+    let net = match cursor.try_next().await? {
+        Some(row) => match row.get("net") {
+            Some(Bson::Double(v)) => *v,
+            Some(Bson::Int32(v)) => f64::from(*v),
+            Some(Bson::Int64(v)) => *v as f64,
+            _ => 0.0,
+        },
+        None => 0.0,
+    };
+    Ok(base + net)
+}
+
+/// Snapshots of the account taken on or after `from`, oldest first.
+pub async fn list_balance_snapshots(
+    state: &AppState,
+    account_id: &ObjectId,
+    from: DateTime,
+) -> Result<Vec<AccountBalanceSnapshot>> {
+    state
+        .balance_snapshots
+        .find(doc! { "account_id": account_id, "as_of": { "$gte": from } })
+        .sort(doc! { "as_of": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Latest confirmed transactions moving money in or out of the account and
+/// dated before `until`, newest first. Those before the opening date are left
+/// out, like in the balance.
+pub async fn list_account_movements(
+    state: &AppState,
+    account_id: &ObjectId,
+    until: DateTime,
+    limit: i64,
+) -> Result<Vec<Transaction>> {
+    let mut date = doc! { "$lt": until };
+    if let (_, Some(opening)) = account_opening(state, account_id).await? {
+        date.insert("$gte", opening);
+    }
+    state
+        .transactions
+        .find(doc! {
+            "$or": [{ "account_from_id": account_id }, { "account_to_id": account_id }],
+            "is_confirmed": { "$ne": false },
+            "date": date,
+        })
+        .sort(doc! { "date": -1, "_id": -1 })
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Drops the snapshots a change to `tx` makes stale: those of its accounts
+/// taken after the transaction date. Drafts do not count toward balances, so
+/// they leave snapshots alone. The nightly job fills the gap again.
+pub async fn invalidate_balance_snapshots(state: &AppState, tx: &Transaction) -> Result<()> {
+    if !tx.is_confirmed {
+        return Ok(());
+    }
+    let accounts: Vec<ObjectId> = tx
+        .account_from_id
+        .into_iter()
+        .chain(tx.account_to_id)
+        .collect();
+    if accounts.is_empty() {
+        return Ok(());
+    }
+    state
+        .balance_snapshots
+        .delete_many(doc! { "account_id": { "$in": accounts }, "as_of": { "$gt": tx.date } })
+        .await?;
+    Ok(())
+}
+
+async fn snapshot_account(state: &AppState, account: &Account, now: DateTime) -> Result<u64> {
+    let Some(account_id) = account.id else {
+        return Ok(0);
+    };
+    let today = utc_day_start(now).to_chrono();
+    let mut first = today - ChronoDuration::days(BALANCE_SNAPSHOT_BACKFILL_DAYS - 1);
+    if let Some(created_at) = account.created_at {
+        first = first.max(utc_day_start(created_at).to_chrono());
+    }
+
+    let existing: HashSet<i64> =
+        list_balance_snapshots(state, &account_id, DateTime::from_chrono(first))
+            .await?
+            .into_iter()
+            .map(|snapshot| snapshot.as_of.timestamp_millis())
+            .collect();
+
+    let mut written = 0;
+    let mut day = first;
+    while day <= today {
+        let as_of = DateTime::from_chrono(day);
+        if !existing.contains(&as_of.timestamp_millis()) {
+            // Days are filled oldest first, so each one starts from the
+            // snapshot written just before it.
+            let balance = account_balance_at(state, &account_id, as_of).await?;
+            state
+                .balance_snapshots
+                .update_one(
+                    doc! { "account_id": account_id, "as_of": as_of },
+                    doc! { "$set": {
+                        "company_id": account.company_id,
+                        "balance": balance,
+                        "created_at": now,
+                    }},
+                )
+                .upsert(true)
+                .await?;
+            written += 1;
+        }
+        day += ChronoDuration::days(1);
+    }
+    Ok(written)
+}
+
+/// Sets the opening balance of the account and the day it is as of
+/// (`date` is moved to the start of its UTC day), and logs the change on the
+/// account. Every balance may move, so the account's snapshots are taken
+/// again.
+pub async fn set_opening_balance(
+    state: &AppState,
+    account: &Account,
+    balance: f64,
+    date: Option<DateTime>,
+    user_id: Option<ObjectId>,
+    username: &str,
+    reason: Option<String>,
+) -> Result<()> {
+    let account_id = account.id.context("account missing _id")?;
+    let date = date.map(utc_day_start);
+    let now = DateTime::now();
+    let change = OpeningBalanceChange {
+        changed_at: now,
+        user_id,
+        username: username.to_string(),
+        previous_balance: account.opening_balance,
+        previous_date: account.opening_date,
+        balance,
+        date,
+        reason,
+    };
+    state
+        .accounts
+        .update_one(
+            doc! { "_id": account_id },
+            doc! {
+                "$set": {
+                    "opening_balance": balance,
+                    "opening_date": date,
+                    "updated_at": now,
+                },
+                "$push": { "opening_balance_changes": mongodb::bson::to_bson(&change)? },
+            },
+        )
+        .await?;
+
+    state
+        .balance_snapshots
+        .delete_many(doc! { "account_id": account_id })
+        .await?;
+    let updated = Account {
+        opening_balance: balance,
+        opening_date: date,
+        ..account.clone()
+    };
+    snapshot_account(state, &updated, now).await?;
+    Ok(())
+}
+
+/// Makes sure every account has a snapshot for each of the last
+/// `BALANCE_SNAPSHOT_BACKFILL_DAYS` days up to today, writing the missing
+/// ones. Returns how many snapshots were written.
+pub async fn snapshot_account_balances(state: &AppState, now: DateTime) -> Result<u64> {
+    let mut written = 0;
+    let mut cursor = state.accounts.find(doc! {}).await?;
+    while let Some(account) = cursor.try_next().await? {
+        written += snapshot_account(state, &account, now).await?;
+    }
+    Ok(written)
+}
+
+/// Runs `snapshot_account_balances` right away and then once a day.
+pub fn spawn_balance_snapshot_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        loop {
+            ticker.tick().await;
+            let result = snapshot_account_balances(&state, DateTime::from_chrono(Utc::now())).await;
+            metrics().record_task_run("balance_snapshots", result.is_ok());
+            match result {
+                Ok(written) => println!("balance snapshots: {written} written"),
+                Err(err) => eprintln!("balance snapshots failed: {err:?}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,420 @@
+// Bank connections and the movements pulled through them. A pull stages each
+// new movement as pending; it reaches the ledger only when someone accepts it
+// under a category from the review screen.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use chrono::{Duration as ChronoDuration, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::{
+    bank_sync::{BankSyncProvider, bank_provider_from_env},
+    metrics::metrics,
+    models::{
+        BankConnection, BankProvider, BankSyncStatus, SyncedBankTransaction, TransactionType,
+    },
+};
+
+use super::{
+    AppState,
+    finance::{
+        create_transaction, ensure_account_active_in_company, ensure_category_matches_flow,
+        set_transaction_external_refs,
+    },
+};
+
+/// Days the first pull of a connection reaches back.
+pub const BANK_SYNC_INITIAL_DAYS: i64 = 30;
+/// Days before the last pull that every pull reads again, for movements the
+/// bank posts late. Those already staged are not staged twice.
+const BANK_SYNC_OVERLAP_DAYS: i64 = 3;
+/// Description given to movements the bank left without one.
+const UNNAMED_MOVEMENT: &str = "Movimiento bancario";
+
+/// Links `account_id` to the account `external_account_id` of the provider
+/// link `external_link_id`.
+pub async fn create_bank_connection(
+    state: &AppState,
+    company_id: &ObjectId,
+    account_id: &ObjectId,
+    provider: BankProvider,
+    external_link_id: &str,
+    external_account_id: &str,
+) -> Result<ObjectId> {
+    let (external_link_id, external_account_id) =
+        (external_link_id.trim(), external_account_id.trim());
+    if external_link_id.is_empty() || external_account_id.is_empty() {
+        bail!("link and account ids are required");
+    }
+    ensure_account_active_in_company(state, account_id, company_id).await?;
+    let existing = state
+        .bank_connections
+        .count_documents(doc! {
+            "company_id": company_id,
+            "provider": provider.as_str(),
+            "external_link_id": external_link_id,
+            "external_account_id": external_account_id,
+        })
+        .await?;
+    if existing > 0 {
+        bail!("bank account is already connected");
+    }
+
+    let result = state
+        .bank_connections
+        .insert_one(BankConnection {
+            id: None,
+            company_id: *company_id,
+            account_id: *account_id,
+            provider,
+            external_link_id: external_link_id.to_string(),
+            external_account_id: external_account_id.to_string(),
+            is_active: true,
+            last_synced_at: None,
+            last_error: None,
+            created_at: DateTime::now(),
+        })
+        .await?;
+    result
+        .inserted_id
+        .as_object_id()
+        .context("inserted connection id is not an ObjectId")
+}
+
+pub async fn list_bank_connections(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<BankConnection>> {
+    Ok(state
+        .bank_connections
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "created_at": 1 })
+        .await?
+        .try_collect()
+        .await?)
+}
+
+pub async fn get_bank_connection(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<Option<BankConnection>> {
+    Ok(state
+        .bank_connections
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?)
+}
+
+/// Removes the connection with the movements it staged. Transactions already
+/// accepted stay in the ledger.
+pub async fn delete_bank_connection(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<bool> {
+    let deleted = state
+        .bank_connections
+        .delete_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .deleted_count;
+    if deleted > 0 {
+        state
+            .bank_transactions
+            .delete_many(doc! { "connection_id": id })
+            .await?;
+    }
+    Ok(deleted > 0)
+}
+
+/// Pulls the movements of `connection` up to `now` through `provider` and
+/// stages the new ones as pending. Returns how many were staged. The outcome
+/// is recorded on the connection: a failure keeps `last_synced_at`, so the
+/// next pull covers the missed days.
+pub async fn pull_bank_connection(
+    state: &AppState,
+    connection: &BankConnection,
+    provider: &dyn BankSyncProvider,
+    now: DateTime,
+) -> Result<usize> {
+    let connection_id = connection.id.context("connection without id")?;
+    let from = match connection.last_synced_at {
+        Some(last) => last.to_chrono() - ChronoDuration::days(BANK_SYNC_OVERLAP_DAYS),
+        None => now.to_chrono() - ChronoDuration::days(BANK_SYNC_INITIAL_DAYS),
+    };
+    let movements = match provider
+        .fetch_movements(connection, from.date_naive(), now.to_chrono().date_naive())
+        .await
+    {
+        Ok(movements) => movements,
+        Err(err) => {
+            state
+                .bank_connections
+                .update_one(
+                    doc! { "_id": connection_id },
+                    doc! { "$set": { "last_error": format!("{err:#}") } },
+                )
+                .await?;
+            return Err(err);
+        }
+    };
+
+    let mut staged = 0;
+    for movement in movements.iter().filter(|movement| movement.amount != 0.0) {
+        let date = movement
+            .date
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc();
+        let description = match movement.description.trim() {
+            "" => UNNAMED_MOVEMENT,
+            description => description,
+        };
+        let result = state
+            .bank_transactions
+            .update_one(
+                doc! { "connection_id": connection_id, "external_id": &movement.external_id },
+                doc! { "$setOnInsert": {
+                    "company_id": connection.company_id,
+                    "account_id": connection.account_id,
+                    "date": DateTime::from_chrono(date),
+                    "description": description,
+                    "amount": movement.amount,
+                    "reference": movement.reference.as_deref(),
+                    "status": BankSyncStatus::Pending.as_str(),
+                    "created_at": now,
+                } },
+            )
+            .upsert(true)
+            .await?;
+        if result.upserted_id.is_some() {
+            staged += 1;
+        }
+    }
+    state
+        .bank_connections
+        .update_one(
+            doc! { "_id": connection_id },
+            doc! { "$set": { "last_synced_at": now }, "$unset": { "last_error": "" } },
+        )
+        .await?;
+    Ok(staged)
+}
+
+/// Outcome of one run of `sync_bank_connections`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BankSyncReport {
+    pub connections: u64,
+    pub staged: u64,
+    pub failures: u64,
+}
+
+/// Pulls every active connection of an active company through the provider
+/// `provider_for` returns for it. Connections whose provider is not
+/// configured are skipped; a failed one is retried on the next run.
+pub async fn sync_bank_connections(
+    state: &AppState,
+    provider_for: impl Fn(BankProvider) -> Option<Box<dyn BankSyncProvider>>,
+    now: DateTime,
+) -> Result<BankSyncReport> {
+    let companies: Vec<ObjectId> = state
+        .companies
+        .distinct(
+            "_id",
+            doc! { "is_active": { "$ne": false }, "archived_at": { "$exists": false } },
+        )
+        .await?
+        .into_iter()
+        .filter_map(|id| id.as_object_id())
+        .collect();
+    let connections: Vec<BankConnection> = state
+        .bank_connections
+        .find(doc! { "is_active": true, "company_id": { "$in": companies } })
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut report = BankSyncReport::default();
+    for connection in connections {
+        let Some(provider) = provider_for(connection.provider) else {
+            eprintln!(
+                "bank sync: {} is not configured, skipping connection {:?}",
+                connection.provider.as_str(),
+                connection.id
+            );
+            continue;
+        };
+        report.connections += 1;
+        match pull_bank_connection(state, &connection, provider.as_ref(), now).await {
+            Ok(staged) => report.staged += staged as u64,
+            Err(err) => {
+                eprintln!("bank sync: connection {:?} failed: {err:?}", connection.id);
+                report.failures += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Runs `sync_bank_connections` with the providers configured in the
+/// environment right away and then every six hours.
+pub fn spawn_bank_sync_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(6 * 60 * 60));
+        loop {
+            ticker.tick().await;
+            let now = DateTime::from_chrono(Utc::now());
+            let result = sync_bank_connections(&state, bank_provider_from_env, now).await;
+            metrics().record_task_run("bank_sync", result.is_ok());
+            match result {
+                Ok(report) => {
+                    if report.staged > 0 || report.failures > 0 {
+                        println!(
+                            "bank sync: {} connections, {} movements staged, {} failed",
+                            report.connections, report.staged, report.failures
+                        );
+                    }
+                }
+                Err(err) => eprintln!("bank sync failed: {err:?}"),
+            }
+        }
+    });
+}
+
+/// Staged movements of the company waiting for review, oldest first.
+pub async fn list_pending_bank_transactions(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<SyncedBankTransaction>> {
+    Ok(state
+        .bank_transactions
+        .find(doc! {
+            "company_id": company_id,
+            "status": BankSyncStatus::Pending.as_str(),
+        })
+        .sort(doc! { "date": 1, "_id": 1 })
+        .await?
+        .try_collect()
+        .await?)
+}
+
+/// Records a pending movement as a confirmed transaction of its account
+/// under `category_id`: deposits as income into the account, withdrawals as
+/// expenses from it. Returns the new transaction's id.
+pub async fn accept_bank_transaction(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    category_id: &ObjectId,
+) -> Result<ObjectId> {
+    let pending = doc! {
+        "_id": id,
+        "company_id": company_id,
+        "status": BankSyncStatus::Pending.as_str(),
+    };
+    let staged = state
+        .bank_transactions
+        .find_one(pending.clone())
+        .await?
+        .context("movement not found or already reviewed")?;
+    let transaction_type = if staged.amount > 0.0 {
+        TransactionType::Income
+    } else {
+        TransactionType::Expense
+    };
+    ensure_category_matches_flow(state, category_id, company_id, &transaction_type).await?;
+
+    // Claimed before recording, so accepting twice does not record it twice.
+    let claimed = state
+        .bank_transactions
+        .update_one(
+            pending,
+            doc! { "$set": { "status": BankSyncStatus::Accepted.as_str() } },
+        )
+        .await?;
+    if claimed.modified_count == 0 {
+        bail!("movement not found or already reviewed");
+    }
+
+    let (account_from_id, account_to_id) = match transaction_type {
+        TransactionType::Income => (None, Some(staged.account_id)),
+        _ => (Some(staged.account_id), None),
+    };
+    let provider = state
+        .bank_connections
+        .find_one(doc! { "_id": staged.connection_id })
+        .await?
+        .map(|connection| connection.provider.label())
+        .unwrap_or("banco");
+    let notes = match &staged.reference {
+        Some(reference) => format!("Sincronizado de {provider} · ref. {reference}"),
+        None => format!("Sincronizado de {provider}"),
+    };
+    let recorded = create_transaction(
+        state,
+        company_id,
+        staged.date,
+        &staged.description,
+        transaction_type,
+        category_id,
+        account_from_id,
+        account_to_id,
+        staged.amount.abs(),
+        None,
+        None,
+        true,
+        Some(notes),
+        None,
+        None,
+        None,
+        None,
+    )
+    .await;
+    let transaction_id = match recorded {
+        Ok(transaction_id) => transaction_id,
+        Err(err) => {
+            state
+                .bank_transactions
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "status": BankSyncStatus::Pending.as_str() } },
+                )
+                .await?;
+            return Err(err);
+        }
+    };
+    if let Some(reference) = &staged.reference {
+        set_transaction_external_refs(state, &transaction_id, company_id, None, Some(reference))
+            .await?;
+    }
+    state
+        .bank_transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "transaction_id": transaction_id } },
+        )
+        .await?;
+    Ok(transaction_id)
+}
+
+/// Discards a pending movement. It stays staged as rejected so the next
+/// pull does not bring it back.
+pub async fn reject_bank_transaction(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<bool> {
+    let result = state
+        .bank_transactions
+        .update_one(
+            doc! {
+                "_id": id,
+                "company_id": company_id,
+                "status": BankSyncStatus::Pending.as_str(),
+            },
+            doc! { "$set": { "status": BankSyncStatus::Rejected.as_str() } },
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
@@ -0,0 +1,308 @@
+// Monthly category budgets: how much of each budget the current month has
+// used, and the 80%/100% alerts. A threshold is recorded on the category the
+// first time it is crossed in a month; the chat task announces it later.
+// The month is the current period of the company's fiscal calendar.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, doc, oid::ObjectId};
+
+use crate::models::{BudgetAlert, Category};
+
+use super::{AppState, FiscalPeriod, company_fiscal_period};
+
+/// Percentages of a budget that raise an alert, lowest first.
+pub const BUDGET_ALERT_THRESHOLDS: [u32; 2] = [80, 100];
+
+/// How much of its monthly budget a category has used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetUsage {
+    pub category_id: ObjectId,
+    pub name: String,
+    pub budget: f64,
+    pub spent: f64,
+}
+
+impl BudgetUsage {
+    pub fn percent(&self) -> f64 {
+        if self.budget <= 0.0 {
+            return 0.0;
+        }
+        self.spent / self.budget * 100.0
+    }
+
+    /// Highest alert threshold reached, if any.
+    pub fn threshold(&self) -> Option<u32> {
+        let percent = self.percent();
+        BUDGET_ALERT_THRESHOLDS
+            .iter()
+            .rev()
+            .copied()
+            .find(|threshold| percent >= f64::from(*threshold))
+    }
+}
+
+/// Key of the company's period `now` falls in, as kept in
+/// `BudgetAlert::month`: `YYYY-MM` for calendar months.
+pub async fn budget_month(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<String> {
+    Ok(company_fiscal_period(state, company_id, now).await?.key)
+}
+
+async fn budgeted_categories(state: &AppState, company_id: &ObjectId) -> Result<Vec<Category>> {
+    state
+        .categories
+        .find(doc! { "company_id": company_id, "monthly_budget": { "$gt": 0.0 } })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+async fn usage_of(
+    state: &AppState,
+    company_id: &ObjectId,
+    categories: &[Category],
+    period: &FiscalPeriod,
+) -> Result<Vec<BudgetUsage>> {
+    let ids: Vec<ObjectId> = categories.iter().filter_map(|c| c.id).collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (start, end) = (period.start_date(), period.end_date());
+    let mut spent: HashMap<ObjectId, f64> = HashMap::new();
+    let mut totals = state
+        .transactions
+        .aggregate(vec![
+            doc! { "$match": {
+                "company_id": company_id,
+                "category_id": { "$in": &ids },
+                "is_confirmed": { "$ne": false },
+                "date": { "$gte": start, "$lt": end },
+            }},
+            doc! { "$group": { "_id": "$category_id", "total": { "$sum": "$amount" } } },
+        ])
+        .await?;
+    while let Some(total) = totals.try_next().await? {
+        let Ok(id) = total.get_object_id("_id") else {
+            continue;
+        };
+        let value = match total.get("total") {
+            Some(Bson::Double(v)) => *v,
+            Some(Bson::Int32(v)) => f64::from(*v),
+            Some(Bson::Int64(v)) => *v as f64,
+            _ => 0.0,
+        };
+        spent.insert(id, value);
+    }
+    Ok(categories
+        .iter()
+        .filter_map(|category| {
+            let id = category.id?;
+            Some(BudgetUsage {
+                category_id: id,
+                name: category.name.clone(),
+                budget: category.monthly_budget?,
+                spent: spent.get(&id).copied().unwrap_or(0.0),
+            })
+        })
+        .collect())
+}
+
+/// Budget use this month of every category of the company with a budget,
+/// keyed by category.
+pub async fn budget_usage(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<HashMap<ObjectId, BudgetUsage>> {
+    let categories = budgeted_categories(state, company_id).await?;
+    let period = company_fiscal_period(state, company_id, now).await?;
+    Ok(usage_of(state, company_id, &categories, &period)
+        .await?
+        .into_iter()
+        .map(|usage| (usage.category_id, usage))
+        .collect())
+}
+
+/// Records on each budgeted category of the company the threshold it has
+/// reached this month, when higher than the one already recorded. Returns the
+/// categories that just crossed one. Runs after every new transaction and
+/// from the chat task, which also catches imports and edits.
+pub async fn check_budget_alerts(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<Vec<BudgetUsage>> {
+    let categories = budgeted_categories(state, company_id).await?;
+    let period = company_fiscal_period(state, company_id, now).await?;
+    let month = period.key.clone();
+    let recorded: HashMap<ObjectId, u32> = categories
+        .iter()
+        .filter_map(|category| {
+            let alert = category.budget_alert.as_ref()?;
+            (alert.month == month).then_some((category.id?, alert.threshold))
+        })
+        .collect();
+
+    let mut crossed = Vec::new();
+    for usage in usage_of(state, company_id, &categories, &period).await? {
+        let Some(threshold) = usage.threshold() else {
+            continue;
+        };
+        if recorded
+            .get(&usage.category_id)
+            .is_some_and(|previous| *previous >= threshold)
+        {
+            continue;
+        }
+        let alert = BudgetAlert {
+            month: month.clone(),
+            threshold,
+            notified: false,
+        };
+        // Conditional, so two transactions saved at once announce it once.
+        let result = state
+            .categories
+            .update_one(
+                doc! {
+                    "_id": usage.category_id,
+                    "$or": [
+                        { "budget_alert.month": { "$ne": &month } },
+                        { "budget_alert.threshold": { "$lt": threshold } },
+                    ],
+                },
+                doc! { "$set": { "budget_alert": mongodb::bson::to_bson(&alert)? } },
+            )
+            .await?;
+        if result.modified_count > 0 {
+            crossed.push(usage);
+        }
+    }
+    Ok(crossed)
+}
+
+/// Categories whose threshold of this month was not announced yet, with the
+/// threshold.
+pub async fn pending_budget_alerts(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<Vec<(BudgetUsage, u32)>> {
+    let period = company_fiscal_period(state, company_id, now).await?;
+    let month = period.key.clone();
+    let categories: Vec<Category> = budgeted_categories(state, company_id)
+        .await?
+        .into_iter()
+        .filter(|category| {
+            category
+                .budget_alert
+                .as_ref()
+                .is_some_and(|alert| alert.month == month && !alert.notified)
+        })
+        .collect();
+    let thresholds: HashMap<ObjectId, u32> = categories
+        .iter()
+        .filter_map(|c| Some((c.id?, c.budget_alert.as_ref()?.threshold)))
+        .collect();
+    Ok(usage_of(state, company_id, &categories, &period)
+        .await?
+        .into_iter()
+        .filter_map(|usage| {
+            let threshold = *thresholds.get(&usage.category_id)?;
+            Some((usage, threshold))
+        })
+        .collect())
+}
+
+/// Marks the alerts of these categories as announced, unless a higher
+/// threshold was recorded in the meantime.
+pub async fn mark_budget_alerts_notified(
+    state: &AppState,
+    company_id: &ObjectId,
+    alerts: &[(BudgetUsage, u32)],
+    now: DateTime,
+) -> Result<()> {
+    let month = budget_month(state, company_id, now).await?;
+    for (usage, threshold) in alerts {
+        state
+            .categories
+            .update_one(
+                doc! {
+                    "_id": usage.category_id,
+                    "budget_alert.month": &month,
+                    "budget_alert.threshold": *threshold,
+                },
+                doc! { "$set": { "budget_alert.notified": true } },
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Sets or clears the monthly budget of a category. The alerts of the month
+/// start over against the new amount.
+pub async fn set_category_budget(
+    state: &AppState,
+    id: &ObjectId,
+    monthly_budget: Option<f64>,
+) -> Result<()> {
+    let update = match monthly_budget.filter(|amount| *amount > 0.0) {
+        Some(amount) => doc! {
+            "$set": { "monthly_budget": amount },
+            "$unset": { "budget_alert": "" },
+        },
+        None => doc! { "$unset": { "monthly_budget": "", "budget_alert": "" } },
+    };
+    state
+        .categories
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(budget: f64, spent: f64) -> BudgetUsage {
+        BudgetUsage {
+            category_id: ObjectId::new(),
+            name: "Renta".into(),
+            budget,
+            spent,
+        }
+    }
+
+    #[test]
+    fn thresholds_follow_the_share_of_the_budget_used() {
+        assert_eq!(usage(1000.0, 799.99).threshold(), None);
+        assert_eq!(usage(1000.0, 800.0).threshold(), Some(80));
+        assert_eq!(usage(1000.0, 999.0).threshold(), Some(80));
+        assert_eq!(usage(1000.0, 1000.0).threshold(), Some(100));
+        assert_eq!(usage(1000.0, 2500.0).threshold(), Some(100));
+        assert_eq!(usage(0.0, 10.0).threshold(), None);
+    }
+
+    #[test]
+    fn months_run_from_the_first_to_the_next_first() {
+        use crate::{models::FiscalCalendar, state::fiscal_period};
+
+        let now = DateTime::parse_rfc3339_str("2025-12-15T10:00:00Z").unwrap();
+        let period = fiscal_period(&FiscalCalendar::default(), now.to_chrono());
+        assert_eq!(
+            period.start_date().try_to_rfc3339_string().unwrap(),
+            "2025-12-01T00:00:00Z"
+        );
+        assert_eq!(
+            period.end_date().try_to_rfc3339_string().unwrap(),
+            "2026-01-01T00:00:00Z"
+        );
+        assert_eq!(period.key, "2025-12");
+    }
+}
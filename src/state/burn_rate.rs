@@ -0,0 +1,269 @@
+// Burn rate and runway of a company: how much cash it consumes per month, how
+// long its balances last at that pace and how both compare with the period
+// before.
+
+use anyhow::Result;
+use chrono::{Datelike, Months, TimeZone, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{Account, FlowType, PlannedStatus};
+
+use super::{
+    AppState, account_balance_at, companies::company_default_currency, company_period_figures,
+};
+
+/// Months averaged when the caller does not choose.
+pub const BURN_RATE_DEFAULT_MONTHS: u32 = 3;
+/// Longest window accepted, in months.
+pub const BURN_RATE_MAX_MONTHS: u32 = 24;
+
+/// Confirmed income and expense over a window of whole months.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnPeriod {
+    pub from: DateTime,
+    pub to: DateTime,
+    pub income: f64,
+    pub expense: f64,
+}
+
+impl BurnPeriod {
+    /// Average monthly expense.
+    pub fn gross_burn(&self, months: u32) -> f64 {
+        self.expense / f64::from(months.max(1))
+    }
+
+    /// Average monthly expense not covered by income; negative when the
+    /// company earns more than it spends.
+    pub fn net_burn(&self, months: u32) -> f64 {
+        (self.expense - self.income) / f64::from(months.max(1))
+    }
+}
+
+/// Where the monthly net flow used for the runway comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunwayBasis {
+    /// Open planned entries due within the next `months` months.
+    Planned,
+    /// Nothing is planned ahead, so the average of the last `months` months.
+    History,
+}
+
+impl RunwayBasis {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunwayBasis::Planned => "planned",
+            RunwayBasis::History => "history",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnRateAnalytics {
+    pub currency: String,
+    pub months: u32,
+    /// Balance of the active accounts in the company currency at `now`.
+    /// Accounts in other currencies are left out rather than mixed in.
+    pub cash_balance: f64,
+    /// The `months` whole months before the current one.
+    pub current: BurnPeriod,
+    /// The `months` whole months before `current`.
+    pub previous: BurnPeriod,
+    /// Open planned entries due from `now` to `months` months later, at their
+    /// estimate.
+    pub planned_income: f64,
+    pub planned_expense: f64,
+}
+
+impl BurnRateAnalytics {
+    pub fn burn_rate(&self) -> f64 {
+        self.current.net_burn(self.months)
+    }
+
+    /// Monthly net flow expected from now on and where it comes from: the
+    /// planned entries when there are any, otherwise the recent history.
+    pub fn projected_monthly_net(&self) -> (f64, RunwayBasis) {
+        if self.planned_income != 0.0 || self.planned_expense != 0.0 {
+            (
+                (self.planned_income - self.planned_expense) / f64::from(self.months.max(1)),
+                RunwayBasis::Planned,
+            )
+        } else {
+            (-self.burn_rate(), RunwayBasis::History)
+        }
+    }
+
+    /// Months until the cash balance reaches zero at the projected monthly
+    /// net flow. `None` when the flow is not negative, so cash does not run
+    /// out; zero when there is no cash left.
+    pub fn runway_months(&self) -> Option<f64> {
+        let (monthly_net, _) = self.projected_monthly_net();
+        if monthly_net >= 0.0 {
+            None
+        } else if self.cash_balance <= 0.0 {
+            Some(0.0)
+        } else {
+            Some(self.cash_balance / -monthly_net)
+        }
+    }
+}
+
+/// Relative change from `previous` to `current`, as a fraction; `None` when
+/// there is nothing to compare against.
+pub fn trend_delta(current: f64, previous: f64) -> Option<f64> {
+    (previous != 0.0).then(|| (current - previous) / previous.abs())
+}
+
+fn months_before(at: chrono::DateTime<Utc>, months: u32) -> chrono::DateTime<Utc> {
+    at.checked_sub_months(Months::new(months)).unwrap_or(at)
+}
+
+async fn burn_period(
+    state: &AppState,
+    company_id: &ObjectId,
+    from: chrono::DateTime<Utc>,
+    to: chrono::DateTime<Utc>,
+) -> Result<BurnPeriod> {
+    let (from, to) = (DateTime::from_chrono(from), DateTime::from_chrono(to));
+    let figures = company_period_figures(state, company_id, from, to).await?;
+    Ok(BurnPeriod {
+        from,
+        to,
+        income: figures.income,
+        expense: figures.expense,
+    })
+}
+
+/// Burn rate of the company over the `months` whole months before the one
+/// containing `now`, compared with the `months` before those, plus the cash
+/// and planned flows the runway is computed from.
+pub async fn burn_rate_analytics(
+    state: &AppState,
+    company_id: &ObjectId,
+    months: u32,
+    now: DateTime,
+) -> Result<BurnRateAnalytics> {
+    let months = months.clamp(1, BURN_RATE_MAX_MONTHS);
+    let currency = company_default_currency(state, company_id).await?;
+
+    let today = now.to_chrono();
+    let month_start = Utc
+        .with_ymd_and_hms(today.year(), today.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(today);
+    let current_start = months_before(month_start, months);
+    let previous_start = months_before(current_start, months);
+    let current = burn_period(state, company_id, current_start, month_start).await?;
+    let previous = burn_period(state, company_id, previous_start, current_start).await?;
+
+    let accounts: Vec<Account> = state
+        .for_reports(&state.accounts)
+        .find(doc! { "company_id": company_id, "is_active": true, "currency": &currency })
+        .await?
+        .try_collect()
+        .await?;
+    let mut cash_balance = 0.0;
+    for account in accounts {
+        if let Some(account_id) = account.id {
+            cash_balance += account_balance_at(state, &account_id, now).await?;
+        }
+    }
+
+    let horizon = DateTime::from_chrono(
+        today
+            .checked_add_months(Months::new(months))
+            .unwrap_or(today),
+    );
+    let mut planned_income = 0.0;
+    let mut planned_expense = 0.0;
+    let mut cursor = state
+        .for_reports(&state.planned_entries)
+        .find(doc! {
+            "company_id": company_id,
+            "due_date": { "$gte": now, "$lt": horizon },
+            "status": { "$in": [
+                PlannedStatus::Planned.as_str(),
+                PlannedStatus::PartiallyCovered.as_str(),
+                PlannedStatus::Overdue.as_str(),
+            ] },
+        })
+        .await?;
+    while let Some(entry) = cursor.try_next().await? {
+        match entry.flow_type {
+            FlowType::Income => planned_income += entry.amount_estimated,
+            FlowType::Expense => planned_expense += entry.amount_estimated,
+        }
+    }
+
+    Ok(BurnRateAnalytics {
+        currency,
+        months,
+        cash_balance,
+        current,
+        previous,
+        planned_income,
+        planned_expense,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analytics(
+        cash_balance: f64,
+        planned_income: f64,
+        planned_expense: f64,
+    ) -> BurnRateAnalytics {
+        let period = |income, expense| BurnPeriod {
+            from: DateTime::from_millis(0),
+            to: DateTime::from_millis(0),
+            income,
+            expense,
+        };
+        BurnRateAnalytics {
+            currency: "MXN".into(),
+            months: 3,
+            cash_balance,
+            current: period(30_000.0, 60_000.0),
+            previous: period(30_000.0, 45_000.0),
+            planned_income,
+            planned_expense,
+        }
+    }
+
+    #[test]
+    fn runway_follows_planned_flows_before_history() {
+        // Burning 10,000 a month with nothing planned: 12 months of cash.
+        let history = analytics(120_000.0, 0.0, 0.0);
+        assert_eq!(history.burn_rate(), 10_000.0);
+        assert_eq!(
+            history.projected_monthly_net(),
+            (-10_000.0, RunwayBasis::History)
+        );
+        assert_eq!(history.runway_months(), Some(12.0));
+
+        // Planned flows win: 60,000 out over 3 months lasts 6 months.
+        let planned = analytics(120_000.0, 0.0, 60_000.0);
+        assert_eq!(planned.runway_months(), Some(6.0));
+
+        // More income than expense planned: cash does not run out.
+        assert_eq!(
+            analytics(120_000.0, 90_000.0, 60_000.0).runway_months(),
+            None
+        );
+        assert_eq!(analytics(-5.0, 0.0, 0.0).runway_months(), Some(0.0));
+    }
+
+    #[test]
+    fn trend_delta_compares_with_the_previous_period() {
+        let report = analytics(0.0, 0.0, 0.0);
+        assert_eq!(report.previous.net_burn(report.months), 5_000.0);
+        assert_eq!(
+            trend_delta(report.burn_rate(), report.previous.net_burn(report.months)),
+            Some(1.0)
+        );
+        assert_eq!(trend_delta(-50.0, -100.0), Some(0.5));
+        assert_eq!(trend_delta(10.0, 0.0), None);
+    }
+}
@@ -0,0 +1,255 @@
+// Card pre-authorizations. A card hold shows up before the charge and may
+// still change amount or vanish, so a transaction put on hold goes back to
+// unconfirmed: every balance, report and coverage sum already skips those.
+// Capturing it confirms it for the final amount; voiding it leaves it out for
+// good. Holds still open past their expiry are voided by the scheduler.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use chrono::{Duration as ChronoDuration, Utc};
+use mongodb::bson::{DateTime, Document, doc, oid::ObjectId};
+
+use crate::{
+    metrics::metrics,
+    models::{CardHold, HoldStatus, Transaction, TransactionType},
+};
+
+use super::{
+    AppState,
+    balances::invalidate_balance_snapshots,
+    budgets::check_budget_alerts,
+    finance::{get_transaction_by_id, recalculate_planned_entry_status},
+    income_statement::invalidate_monthly_summary,
+    taxes::transaction_tax,
+};
+
+/// Days a hold stays open when none is given; cards usually release theirs
+/// within a week.
+pub const DEFAULT_HOLD_DAYS: i64 = 7;
+/// Longest hold accepted.
+pub const MAX_HOLD_DAYS: i64 = 31;
+
+/// When a hold placed at `from` for `days` expires.
+pub fn hold_expiry(from: DateTime, days: i64) -> DateTime {
+    DateTime::from_chrono(from.to_chrono() + ChronoDuration::days(days))
+}
+
+async fn company_transaction(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<Transaction> {
+    get_transaction_by_id(state, id)
+        .await?
+        .filter(|tx| tx.company_id == *company_id)
+        .context("Movimiento no encontrado")
+}
+
+/// The open hold of `tx`, or an error naming why it cannot be settled.
+fn open_hold(tx: &Transaction) -> Result<&CardHold> {
+    match &tx.hold {
+        Some(hold) if hold.status == HoldStatus::Held => Ok(hold),
+        Some(hold) => bail!("La retención ya no está abierta ({})", hold.status.label()),
+        None => bail!("El movimiento no es una retención de tarjeta"),
+    }
+}
+
+/// Marks the transaction `id` as a card hold of its current amount, open for
+/// `days`. It leaves balances until it is captured.
+pub async fn place_card_hold(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    days: i64,
+) -> Result<()> {
+    let tx = company_transaction(state, company_id, id).await?;
+    if tx.transaction_type == TransactionType::Transfer {
+        bail!("Las transferencias no se retienen");
+    }
+    if tx.refund_of.is_some() || tx.fx_adjustment.is_some() {
+        bail!("Solo los cargos y abonos pueden ser retenciones");
+    }
+    if tx.hold.is_some() {
+        bail!("El movimiento ya fue una retención");
+    }
+    if tx.amount <= 0.0 {
+        bail!("El monto de la retención debe ser mayor a cero");
+    }
+    if !(1..=MAX_HOLD_DAYS).contains(&days) {
+        bail!("La retención dura de 1 a {MAX_HOLD_DAYS} días");
+    }
+    let now = DateTime::now();
+    let hold = CardHold {
+        status: HoldStatus::Held,
+        authorized_amount: tx.amount,
+        expires_at: hold_expiry(now, days),
+        settled_at: None,
+        expired: false,
+    };
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "is_confirmed": false,
+                "hold": mongodb::bson::to_bson(&hold)?,
+                "updated_at": now,
+            } },
+        )
+        .await?;
+
+    // The copy as it was, so a confirmed movement leaves its balances.
+    invalidate_balance_snapshots(state, &tx).await?;
+    invalidate_monthly_summary(state, &tx).await?;
+    if let Some(pe_id) = tx.planned_entry_id {
+        let _ = recalculate_planned_entry_status(state, &pe_id).await;
+    }
+    Ok(())
+}
+
+/// Confirms an open hold for `amount`, or for the authorized amount without
+/// one, and recomputes its tax when the amount changes.
+pub async fn capture_card_hold(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    amount: Option<f64>,
+) -> Result<()> {
+    let tx = company_transaction(state, company_id, id).await?;
+    let hold = open_hold(&tx)?.clone();
+    let amount = amount.unwrap_or(tx.amount);
+    if amount <= 0.0 {
+        bail!("El monto cobrado debe ser mayor a cero");
+    }
+    let tax = transaction_tax(
+        state,
+        company_id,
+        &tx.transaction_type,
+        &tx.category_id,
+        amount,
+        tx.tax.as_ref(),
+    )
+    .await?;
+    let now = DateTime::now();
+    let hold = CardHold {
+        status: HoldStatus::Captured,
+        settled_at: Some(now),
+        ..hold
+    };
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "is_confirmed": true,
+                "amount": amount,
+                "tax": mongodb::bson::to_bson(&tax)?,
+                "hold": mongodb::bson::to_bson(&hold)?,
+                "updated_at": now,
+            } },
+        )
+        .await?;
+
+    let captured = Transaction {
+        is_confirmed: true,
+        amount,
+        ..tx
+    };
+    invalidate_balance_snapshots(state, &captured).await?;
+    invalidate_monthly_summary(state, &captured).await?;
+    if let Some(pe_id) = captured.planned_entry_id {
+        let _ = recalculate_planned_entry_status(state, &pe_id).await;
+    }
+    if let Err(err) = check_budget_alerts(state, company_id, now).await {
+        eprintln!("budget alerts: check failed: {err:?}");
+    }
+    Ok(())
+}
+
+/// Releases an open hold. The transaction stays as a record, out of
+/// balances.
+pub async fn void_card_hold(state: &AppState, company_id: &ObjectId, id: &ObjectId) -> Result<()> {
+    let tx = company_transaction(state, company_id, id).await?;
+    open_hold(&tx)?;
+    let now = DateTime::now();
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id, "hold.status": HoldStatus::Held.as_str() },
+            doc! { "$set": {
+                "hold.status": HoldStatus::Voided.as_str(),
+                "hold.settled_at": now,
+                "updated_at": now,
+            } },
+        )
+        .await?;
+    Ok(())
+}
+
+fn expired_holds_filter(now: DateTime) -> Document {
+    doc! {
+        "hold.status": HoldStatus::Held.as_str(),
+        "hold.expires_at": { "$lte": now },
+    }
+}
+
+/// Voids every hold still open at its expiry and returns how many. Held
+/// transactions are already out of balances, so nothing else changes.
+pub async fn expire_card_holds(state: &AppState, now: DateTime) -> Result<u64> {
+    let res = state
+        .transactions
+        .update_many(
+            expired_holds_filter(now),
+            doc! { "$set": {
+                "hold.status": HoldStatus::Voided.as_str(),
+                "hold.settled_at": now,
+                "hold.expired": true,
+                "updated_at": now,
+            } },
+        )
+        .await?;
+    Ok(res.modified_count)
+}
+
+/// Runs `expire_card_holds` right away and then every hour.
+pub fn spawn_card_hold_expiry_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            ticker.tick().await;
+            let result = expire_card_holds(&state, DateTime::from_chrono(Utc::now())).await;
+            metrics().record_task_run("card_hold_expiry", result.is_ok());
+            match result {
+                Ok(0) => {}
+                Ok(voided) => println!("card holds: {voided} expired holds voided"),
+                Err(err) => eprintln!("card hold expiry failed: {err:?}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_is_days_after_the_hold() {
+        let from = DateTime::from_millis(0);
+        assert_eq!(
+            hold_expiry(from, 7),
+            DateTime::from_millis(7 * 24 * 60 * 60 * 1000)
+        );
+    }
+
+    #[test]
+    fn only_open_holds_past_their_expiry_are_voided() {
+        let now = DateTime::from_millis(1_000);
+        let filter = expired_holds_filter(now);
+        assert_eq!(filter.get_str("hold.status").unwrap(), "held");
+        assert_eq!(
+            filter.get_document("hold.expires_at").unwrap(),
+            &doc! { "$lte": now }
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// Working capital calendar: net cash movement of each day around today,
+// confirmed transactions up to today and open planned entries from today on,
+// so the days where cash runs short stand out on a heatmap. Planned income
+// is also given weighted by the probability of the plan that generated it.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::Duration;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, Document, doc, oid::ObjectId};
+
+use crate::models::PlannedStatus;
+
+use super::{AppState, companies::company_default_currency, utc_day_start};
+
+/// Days shown on each side of today when the caller does not choose.
+pub const CASH_CALENDAR_DEFAULT_DAYS: u32 = 90;
+/// Widest window accepted on each side of today, in days.
+pub const CASH_CALENDAR_MAX_DAYS: u32 = 366;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Cash movement of one day. Amounts are summed as recorded, like the
+/// overview does, without converting currencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CashDay {
+    /// First instant of the day (UTC).
+    pub date: DateTime,
+    /// Confirmed income minus expense. Transfers only move cash between
+    /// accounts and are left out.
+    pub actual: f64,
+    /// What is still owed on the open planned entries due that day, income
+    /// minus expense. Only from today on: earlier ones are in the aging
+    /// report.
+    pub planned: f64,
+    /// `planned` with the income of each recurring plan multiplied by its
+    /// probability: what is likely to move rather than what is promised.
+    pub planned_expected: f64,
+}
+
+impl CashDay {
+    pub fn net(&self) -> f64 {
+        self.actual + self.planned
+    }
+
+    /// Net movement counting planned income at its expected value.
+    pub fn expected_net(&self) -> f64 {
+        self.actual + self.planned_expected
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CashCalendar {
+    pub currency: String,
+    /// First instant of today: actual movements end here, planned ones start.
+    pub today: DateTime,
+    /// Every day of the window, oldest first.
+    pub days: Vec<CashDay>,
+}
+
+fn amount(value: Option<&Bson>) -> f64 {
+    match value {
+        Some(Bson::Double(v)) => *v,
+        Some(Bson::Int32(v)) => f64::from(*v),
+        Some(Bson::Int64(v)) => *v as f64,
+        _ => 0.0,
+    }
+}
+
+/// Every day from `days` days before the one containing `now` to `days` days
+/// after it, both included, with its movement. One aggregation over the
+/// transactions with the planned entries unioned in.
+pub async fn cash_calendar(
+    state: &AppState,
+    company_id: &ObjectId,
+    days: u32,
+    now: DateTime,
+) -> Result<CashCalendar> {
+    let today = utc_day_start(now);
+    let span = i64::from(days) * DAY_MS;
+    let from = DateTime::from_millis(today.timestamp_millis() - span);
+    let tomorrow = DateTime::from_millis(today.timestamp_millis() + DAY_MS);
+    let to = DateTime::from_millis(tomorrow.timestamp_millis() + span);
+    let signed = |amount: Bson, flow: &str| -> Document {
+        doc! { "$cond": [
+            { "$eq": [flow, "income"] },
+            amount.clone(),
+            { "$multiply": [amount, -1.0] },
+        ]}
+    };
+    let pipeline = vec![
+        doc! { "$match": {
+            "company_id": company_id,
+            "is_confirmed": { "$ne": false },
+            "transaction_type": { "$in": ["income", "expense"] },
+            "date": { "$gte": from, "$lt": tomorrow },
+        }},
+        doc! { "$project": {
+            "day": { "$dateToString": { "format": "%Y-%m-%d", "date": "$date" } },
+            "actual": signed("$amount".into(), "$transaction_type"),
+            "planned": { "$literal": 0.0 },
+            "planned_expected": { "$literal": 0.0 },
+        }},
+        doc! { "$unionWith": {
+            "coll": "planned_entries",
+            "pipeline": [
+                { "$match": {
+                    "company_id": company_id,
+                    "due_date": { "$gte": today, "$lt": to },
+                    "status": { "$in": [
+                        PlannedStatus::Planned.as_str(),
+                        PlannedStatus::PartiallyCovered.as_str(),
+                        PlannedStatus::Overdue.as_str(),
+                    ] },
+                }},
+                // Same outstanding amount as the aging report: drafts do not
+                // cover anything yet.
+                { "$lookup": {
+                    "from": "transactions",
+                    "let": { "entry_id": "$_id" },
+                    "pipeline": [
+                        { "$match": {
+                            "$expr": { "$eq": ["$planned_entry_id", "$$entry_id"] },
+                            "is_confirmed": { "$ne": false },
+                        }},
+                        { "$group": { "_id": null, "paid": { "$sum": "$amount" } } },
+                    ],
+                    "as": "payments",
+                }},
+                { "$lookup": {
+                    "from": "recurring_plans",
+                    "localField": "recurring_plan_id",
+                    "foreignField": "_id",
+                    "as": "plan",
+                }},
+                { "$project": {
+                    "flow_type": 1,
+                    "share": { "$cond": [
+                        { "$eq": ["$flow_type", "income"] },
+                        { "$divide": [
+                            { "$ifNull": [{ "$arrayElemAt": ["$plan.probability", 0] }, 100.0] },
+                            100.0,
+                        ]},
+                        1.0,
+                    ]},
+                    "day": { "$dateToString": { "format": "%Y-%m-%d", "date": "$due_date" } },
+                    "outstanding": { "$subtract": [
+                        "$amount_estimated",
+                        { "$ifNull": [{ "$arrayElemAt": ["$payments.paid", 0] }, 0.0] },
+                    ]},
+                }},
+                { "$match": { "outstanding": { "$gt": 0.0 } } },
+                { "$project": {
+                    "day": 1,
+                    "actual": { "$literal": 0.0 },
+                    "planned": signed("$outstanding".into(), "$flow_type"),
+                    "planned_expected": signed(
+                        doc! { "$multiply": ["$outstanding", "$share"] }.into(),
+                        "$flow_type",
+                    ),
+                }},
+            ],
+        }},
+        doc! { "$group": {
+            "_id": "$day",
+            "actual": { "$sum": "$actual" },
+            "planned": { "$sum": "$planned" },
+            "planned_expected": { "$sum": "$planned_expected" },
+        }},
+    ];
+
+    let mut totals: HashMap<String, (f64, f64, f64)> = HashMap::new();
+    let mut cursor = state
+        .for_reports(&state.transactions)
+        .aggregate(pipeline)
+        .await?;
+    while let Some(row) = cursor.try_next().await? {
+        let Ok(day) = row.get_str("_id") else {
+            continue;
+        };
+        totals.insert(
+            day.to_string(),
+            (
+                amount(row.get("actual")),
+                amount(row.get("planned")),
+                amount(row.get("planned_expected")),
+            ),
+        );
+    }
+
+    let first = from.to_chrono().date_naive();
+    let days = (0..=i64::from(days) * 2)
+        .map(|offset| {
+            let day = first + Duration::days(offset);
+            let (actual, planned, planned_expected) = totals
+                .get(&day.format("%Y-%m-%d").to_string())
+                .copied()
+                .unwrap_or_default();
+            CashDay {
+                date: DateTime::from_millis(from.timestamp_millis() + offset * DAY_MS),
+                actual,
+                planned,
+                planned_expected,
+            }
+        })
+        .collect();
+    Ok(CashCalendar {
+        currency: company_default_currency(state, company_id).await?,
+        today,
+        days,
+    })
+}
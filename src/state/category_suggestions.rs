@@ -0,0 +1,191 @@
+// Categories each account is used with most, kept in `account_category_usage`
+// as a summary of the transactions so the transaction form can suggest them.
+// Saving a transaction moves the counters of its accounts by one; the summary
+// is only rebuilt from the transactions by a migration, and for one company
+// after bulk repairs that recategorize its transactions.
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{Document, doc, oid::ObjectId},
+};
+
+use crate::models::{AccountCategoryUsage, Transaction, TransactionType};
+
+use super::AppState;
+
+/// Categories suggested for an account on the transaction form.
+pub const CATEGORY_SUGGESTIONS: i64 = 3;
+
+/// The summary rows `tx` counts in: one per account it moves money through.
+fn usage_keys(tx: &Transaction) -> Vec<Document> {
+    let mut accounts: Vec<ObjectId> = tx.account_from_id.into_iter().collect();
+    if let Some(to) = tx.account_to_id
+        && !accounts.contains(&to)
+    {
+        accounts.push(to);
+    }
+    accounts
+        .into_iter()
+        .map(|account_id| {
+            doc! {
+                "account_id": account_id,
+                "category_id": tx.category_id,
+                "transaction_type": tx.transaction_type.as_str(),
+            }
+        })
+        .collect()
+}
+
+/// Counts a new transaction in the summaries of its accounts, drafts
+/// included: they still say which category the account is used with.
+pub async fn record_category_usage(state: &AppState, tx: &Transaction) -> Result<()> {
+    for key in usage_keys(tx) {
+        state
+            .category_usage
+            .update_one(
+                key,
+                doc! {
+                    "$inc": { "count": 1_i64 },
+                    "$max": { "last_used_at": tx.date },
+                    "$setOnInsert": { "company_id": tx.company_id },
+                },
+            )
+            .upsert(true)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Takes a deleted transaction out of the summaries; a category no longer
+/// used with the account is dropped from them. `last_used_at` keeps the
+/// latest date it was counted with, which only breaks ties.
+pub async fn forget_category_usage(state: &AppState, tx: &Transaction) -> Result<()> {
+    for key in usage_keys(tx) {
+        state
+            .category_usage
+            .update_one(key.clone(), doc! { "$inc": { "count": -1_i64 } })
+            .await?;
+        let mut emptied = key;
+        emptied.insert("count", doc! { "$lte": 0_i64 });
+        state.category_usage.delete_many(emptied).await?;
+    }
+    Ok(())
+}
+
+/// Moves an edited transaction from the summary rows of `before` to those of
+/// `after`. Edits that keep its accounts, category and type only move
+/// `last_used_at` forward.
+pub async fn move_category_usage(
+    state: &AppState,
+    before: &Transaction,
+    after: &Transaction,
+) -> Result<()> {
+    if usage_keys(before) == usage_keys(after) {
+        for key in usage_keys(after) {
+            state
+                .category_usage
+                .update_one(key, doc! { "$max": { "last_used_at": after.date } })
+                .await?;
+        }
+        return Ok(());
+    }
+    forget_category_usage(state, before).await?;
+    record_category_usage(state, after).await
+}
+
+/// Summary rows of the transactions `filter` matches, as
+/// `AccountCategoryUsage` documents.
+fn usage_pipeline(filter: Document) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$project": {
+            "company_id": 1,
+            "category_id": 1,
+            "transaction_type": 1,
+            "date": 1,
+            "accounts": { "$setUnion": [[
+                { "$ifNull": ["$account_from_id", null] },
+                { "$ifNull": ["$account_to_id", null] },
+            ]] },
+        }},
+        doc! { "$unwind": "$accounts" },
+        doc! { "$match": { "accounts": { "$type": "objectId" } } },
+        doc! { "$group": {
+            "_id": {
+                "account_id": "$accounts",
+                "category_id": "$category_id",
+                "transaction_type": "$transaction_type",
+            },
+            "company_id": { "$first": "$company_id" },
+            "count": { "$sum": 1_i64 },
+            "last_used_at": { "$max": "$date" },
+        }},
+        doc! { "$project": {
+            "_id": 0,
+            "company_id": 1,
+            "account_id": "$_id.account_id",
+            "category_id": "$_id.category_id",
+            "transaction_type": "$_id.transaction_type",
+            "count": 1,
+            "last_used_at": 1,
+        }},
+    ]
+}
+
+/// Builds every account's summary again from the transactions, replacing
+/// what `account_category_usage` holds. For the migration that introduced the
+/// counters; returns the summary rows written.
+pub(super) async fn rebuild_category_usage(db: &Database) -> Result<u64> {
+    let mut pipeline = usage_pipeline(doc! {});
+    pipeline.push(doc! { "$out": "account_category_usage" });
+    db.collection::<Document>("transactions")
+        .aggregate(pipeline)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(db
+        .collection::<Document>("account_category_usage")
+        .count_documents(doc! {})
+        .await?)
+}
+
+/// Builds the summaries of the company's accounts again from its
+/// transactions, for changes made past the counters such as integrity
+/// repairs.
+pub async fn rebuild_company_category_usage(state: &AppState, company_id: &ObjectId) -> Result<()> {
+    let usage: Vec<AccountCategoryUsage> = state
+        .transactions
+        .aggregate(usage_pipeline(doc! { "company_id": company_id }))
+        .with_type::<AccountCategoryUsage>()
+        .await?
+        .try_collect()
+        .await?;
+    state
+        .category_usage
+        .delete_many(doc! { "company_id": company_id })
+        .await?;
+    if !usage.is_empty() {
+        state.category_usage.insert_many(usage).await?;
+    }
+    Ok(())
+}
+
+/// Categories used most with the account in transactions of
+/// `transaction_type`, most used first and the latest used breaking ties.
+pub async fn suggested_categories(
+    state: &AppState,
+    account_id: &ObjectId,
+    transaction_type: &TransactionType,
+) -> Result<Vec<ObjectId>> {
+    let usage: Vec<AccountCategoryUsage> = state
+        .category_usage
+        .find(doc! { "account_id": account_id, "transaction_type": transaction_type.as_str() })
+        .sort(doc! { "count": -1, "last_used_at": -1 })
+        .limit(CATEGORY_SUGGESTIONS)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(usage.into_iter().map(|u| u.category_id).collect())
+}
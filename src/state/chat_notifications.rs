@@ -0,0 +1,622 @@
+// Overdue alerts, budget alerts and digests delivered to each company's chat,
+// email recipients and webhook. The notification settings of the company
+// pick the channels, events, digest schedule and quiet hours; companies that
+// never saved them keep the events of their chat settings. The progress of
+// the task is kept on the chat settings so a restart neither repeats nor
+// skips messages.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{Datelike, Duration as ChronoDuration, FixedOffset, Timelike, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, doc, oid::ObjectId};
+
+use crate::{
+    filters::format_money,
+    metrics::metrics,
+    models::{
+        ChatChannel, Company, DigestFrequency, FlowType, FormatPreferences, NotificationSettings,
+        PlannedEntry, PlannedStatus, TransactionType,
+    },
+    notifier::{ChatNotifier, chat_notifier_from_env, send_notification_email},
+};
+
+use super::{
+    AppState, aging_report,
+    balances::utc_day_start,
+    budgets::{
+        BudgetUsage, check_budget_alerts, mark_budget_alerts_notified, pending_budget_alerts,
+    },
+    webhooks::{WebhookEvent, notify_webhook_message},
+};
+
+/// Hour of the day (UTC) from which the daily digest of companies without
+/// notification settings goes out: 7:00 in Mexico City.
+pub const DIGEST_HOUR_UTC: u32 = 13;
+/// Days ahead the digest counts upcoming commitments for.
+const DIGEST_UPCOMING_DAYS: i64 = 7;
+/// Entries listed one by one in an overdue alert; the rest are counted.
+const ALERT_LISTED_ENTRIES: usize = 10;
+
+const OPEN_STATUSES: [PlannedStatus; 3] = [
+    PlannedStatus::Planned,
+    PlannedStatus::PartiallyCovered,
+    PlannedStatus::Overdue,
+];
+
+fn open_statuses() -> Vec<&'static str> {
+    OPEN_STATUSES.iter().map(PlannedStatus::as_str).collect()
+}
+
+/// `$1,234.50 MXN`: chats have no user preferences to follow.
+fn amount(value: f64, currency: &str) -> String {
+    let preferences = FormatPreferences {
+        decimal_separator: ".".to_string(),
+        thousands_separator: ",".to_string(),
+        ..FormatPreferences::default()
+    };
+    format_money(value, currency, &preferences)
+}
+
+/// Message announcing the entries that just fell due, or `None` when there
+/// are none.
+pub fn overdue_alert_text(
+    company: &str,
+    currency: &str,
+    entries: &[PlannedEntry],
+) -> Option<String> {
+    if entries.is_empty() {
+        return None;
+    }
+    let mut text = format!("⚠️ {company}: {} compromisos vencidos\n", entries.len());
+    for entry in entries.iter().take(ALERT_LISTED_ENTRIES) {
+        let kind = match entry.flow_type {
+            FlowType::Income => "por cobrar",
+            FlowType::Expense => "por pagar",
+        };
+        text.push_str(&format!(
+            "• {} — {} {kind}, vencía {}\n",
+            entry.name,
+            amount(
+                entry.amount_estimated,
+                entry.currency.as_deref().unwrap_or(currency)
+            ),
+            entry.due_date.to_chrono().format("%Y-%m-%d"),
+        ));
+    }
+    if entries.len() > ALERT_LISTED_ENTRIES {
+        text.push_str(&format!(
+            "… y {} más\n",
+            entries.len() - ALERT_LISTED_ENTRIES
+        ));
+    }
+    Some(text.trim_end().to_string())
+}
+
+/// Message announcing the categories that reached 80% or 100% of their
+/// monthly budget, or `None` when there are none.
+pub fn budget_alert_text(
+    company: &str,
+    currency: &str,
+    alerts: &[(BudgetUsage, u32)],
+) -> Option<String> {
+    if alerts.is_empty() {
+        return None;
+    }
+    let mut text = format!("💸 {company}: presupuesto del mes\n");
+    for (usage, threshold) in alerts {
+        let reached = if *threshold >= 100 {
+            "agotado"
+        } else {
+            "al 80%"
+        };
+        text.push_str(&format!(
+            "• {} — {reached}: {} de {} ({:.0}%)\n",
+            usage.name,
+            amount(usage.spent, currency),
+            amount(usage.budget, currency),
+            usage.percent(),
+        ));
+    }
+    Some(text.trim_end().to_string())
+}
+
+/// Figures of the daily digest of one company.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailyDigest {
+    pub overdue_receivable: f64,
+    pub overdue_receivable_entries: i64,
+    pub overdue_payable: f64,
+    pub overdue_payable_entries: i64,
+    pub upcoming_entries: i64,
+    pub upcoming_income: f64,
+    pub upcoming_expense: f64,
+    pub yesterday_income: f64,
+    pub yesterday_expense: f64,
+}
+
+impl DailyDigest {
+    pub fn text(&self, company: &str, currency: &str, day: &str) -> String {
+        format!(
+            "📊 Resumen de {company} — {day}\n\
+             Vencido por cobrar: {} ({})\n\
+             Vencido por pagar: {} ({})\n\
+             Próximos {DIGEST_UPCOMING_DAYS} días: {} compromisos, {} por cobrar y {} por pagar\n\
+             Ayer: {} de ingresos y {} de egresos",
+            amount(self.overdue_receivable, currency),
+            self.overdue_receivable_entries,
+            amount(self.overdue_payable, currency),
+            self.overdue_payable_entries,
+            self.upcoming_entries,
+            amount(self.upcoming_income, currency),
+            amount(self.upcoming_expense, currency),
+            amount(self.yesterday_income, currency),
+            amount(self.yesterday_expense, currency),
+        )
+    }
+}
+
+fn as_f64(value: Option<&Bson>) -> f64 {
+    match value {
+        Some(Bson::Double(v)) => *v,
+        Some(Bson::Int32(v)) => f64::from(*v),
+        Some(Bson::Int64(v)) => *v as f64,
+        _ => 0.0,
+    }
+}
+
+pub async fn daily_digest(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<DailyDigest> {
+    let mut digest = DailyDigest::default();
+    for row in aging_report(state, company_id, now).await? {
+        match row.flow_type {
+            FlowType::Income => {
+                digest.overdue_receivable += row.total();
+                digest.overdue_receivable_entries += row.entries;
+            }
+            FlowType::Expense => {
+                digest.overdue_payable += row.total();
+                digest.overdue_payable_entries += row.entries;
+            }
+        }
+    }
+
+    let until = DateTime::from_chrono(now.to_chrono() + ChronoDuration::days(DIGEST_UPCOMING_DAYS));
+    let mut upcoming = state
+        .planned_entries
+        .find(doc! {
+            "company_id": company_id,
+            "due_date": { "$gt": now, "$lt": until },
+            "status": { "$in": open_statuses() },
+        })
+        .await?;
+    while let Some(entry) = upcoming.try_next().await? {
+        digest.upcoming_entries += 1;
+        match entry.flow_type {
+            FlowType::Income => digest.upcoming_income += entry.amount_estimated,
+            FlowType::Expense => digest.upcoming_expense += entry.amount_estimated,
+        }
+    }
+
+    let today = utc_day_start(now);
+    let yesterday = DateTime::from_chrono(today.to_chrono() - ChronoDuration::days(1));
+    let mut totals = state
+        .transactions
+        .aggregate(vec![
+            doc! { "$match": {
+                "company_id": company_id,
+                "is_confirmed": { "$ne": false },
+                "date": { "$gte": yesterday, "$lt": today },
+            }},
+            doc! { "$group": { "_id": "$transaction_type", "total": { "$sum": "$amount" } } },
+        ])
+        .await?;
+    while let Some(total) = totals.try_next().await? {
+        let value = as_f64(total.get("total"));
+        match total.get_str("_id").unwrap_or_default() {
+            t if t == TransactionType::Income.as_str() => digest.yesterday_income += value,
+            t if t == TransactionType::Expense.as_str() => digest.yesterday_expense += value,
+            _ => {}
+        }
+    }
+    Ok(digest)
+}
+
+/// Settings the notification task follows for `company`: the saved ones,
+/// or for a company that never saved them, its chat with the events of its
+/// chat settings and the digest every day from `DIGEST_HOUR_UTC`.
+pub fn notification_settings(company: &Company) -> NotificationSettings {
+    if let Some(settings) = &company.notifications {
+        return settings.clone();
+    }
+    let chat = &company.chat_notifications;
+    NotificationSettings {
+        chat: chat.channel.is_some(),
+        overdue_alerts: chat.overdue_alerts,
+        budget_alerts: chat.budget_alerts,
+        digest: if chat.daily_digest {
+            DigestFrequency::Daily
+        } else {
+            DigestFrequency::Never
+        },
+        digest_hour: DIGEST_HOUR_UTC,
+        utc_offset: String::new(),
+        ..NotificationSettings::default()
+    }
+}
+
+/// `now` in the offset of `settings`; UTC when unset or unreadable.
+pub fn local_time(settings: &NotificationSettings, now: DateTime) -> chrono::DateTime<FixedOffset> {
+    let offset = settings
+        .utc_offset
+        .parse()
+        .unwrap_or_else(|_| FixedOffset::east_opt(0).expect("zero offset"));
+    now.to_chrono().with_timezone(&offset)
+}
+
+/// Day (`YYYY-MM-DD`, local) of the digest due at `local`, or `None` when
+/// none is due or that day's already went out.
+pub fn digest_due(
+    settings: &NotificationSettings,
+    local: &chrono::DateTime<FixedOffset>,
+    last_digest_on: Option<&str>,
+) -> Option<String> {
+    let on_day = match settings.digest {
+        DigestFrequency::Never => false,
+        DigestFrequency::Daily => true,
+        DigestFrequency::Weekly => {
+            local.weekday().num_days_from_monday() == settings.digest_weekday
+        }
+    };
+    let day = local.format("%Y-%m-%d").to_string();
+    (on_day && local.hour() >= settings.digest_hour && last_digest_on != Some(day.as_str()))
+        .then_some(day)
+}
+
+/// Messages sent by one run of `send_chat_notifications`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChatNotificationReport {
+    pub alerts_sent: u64,
+    pub digests_sent: u64,
+    pub budget_alerts_sent: u64,
+    /// Failed deliveries, one per channel.
+    pub failures: u64,
+}
+
+/// Where one company's messages go on a run.
+struct Channels<'a> {
+    chat: Option<(Box<dyn ChatNotifier>, &'a str)>,
+    emails: &'a [String],
+    webhook: bool,
+}
+
+impl Channels<'_> {
+    fn is_empty(&self) -> bool {
+        self.chat.is_none() && self.emails.is_empty() && !self.webhook
+    }
+
+    /// Sends `text` through every channel. Returns whether one of them took
+    /// it: only then is the message done, so it is not repeated on the
+    /// channels that worked because another failed.
+    async fn deliver(
+        &self,
+        state: &AppState,
+        company: &Company,
+        event: WebhookEvent,
+        subject: &str,
+        text: &str,
+        report: &mut ChatNotificationReport,
+    ) -> bool {
+        let mut delivered = false;
+        if let Some((notifier, chat_id)) = &self.chat {
+            match notifier.send_message(chat_id, text).await {
+                Ok(()) => delivered = true,
+                Err(err) => {
+                    eprintln!(
+                        "chat notifications: {} to the chat of {} failed: {err:?}",
+                        event.as_str(),
+                        company.name
+                    );
+                    report.failures += 1;
+                }
+            }
+        }
+        if !self.emails.is_empty() {
+            match send_notification_email(state.mailer.as_ref(), self.emails, subject, text).await {
+                Ok(()) => delivered = true,
+                Err(err) => {
+                    eprintln!(
+                        "chat notifications: {} to the emails of {} failed: {err:?}",
+                        event.as_str(),
+                        company.name
+                    );
+                    report.failures += 1;
+                }
+            }
+        }
+        if self.webhook
+            && let Some(company_id) = company.id
+        {
+            match notify_webhook_message(state, &company_id, event, text).await {
+                Ok(queued) => delivered |= queued,
+                Err(err) => {
+                    eprintln!(
+                        "chat notifications: {} to the webhook of {} failed: {err:?}",
+                        event.as_str(),
+                        company.name
+                    );
+                    report.failures += 1;
+                }
+            }
+        }
+        delivered
+    }
+}
+
+/// Sends the alerts and digests due at `now` to every active company with
+/// somewhere to send them, following its notification settings; chats go
+/// through the notifier `notifier_for` returns for their channel. Alerts
+/// cover the entries that fell due since the previous run; the first one
+/// looks back a day. The digest goes out once on each day of its schedule,
+/// from its hour. Budget alerts announce the thresholds recorded since the
+/// last run, checking the budgets again first. Nothing is sent in the quiet
+/// hours; it waits for the first run after them. A message no channel took
+/// is retried on the next run.
+pub async fn send_chat_notifications(
+    state: &AppState,
+    notifier_for: impl Fn(ChatChannel) -> Option<Box<dyn ChatNotifier>>,
+    now: DateTime,
+) -> Result<ChatNotificationReport> {
+    let mut report = ChatNotificationReport::default();
+    let companies: Vec<Company> = state
+        .companies
+        .find(doc! {
+            "is_active": { "$ne": false },
+            "archived_at": { "$exists": false },
+            "$or": [
+                { "chat_notifications.channel": { "$ne": null } },
+                { "notifications": { "$exists": true } },
+            ],
+        })
+        .await?
+        .try_collect()
+        .await?;
+
+    for company in companies {
+        let Some(company_id) = company.id else {
+            continue;
+        };
+        let settings = notification_settings(&company);
+        let progress = &company.chat_notifications;
+        let local = local_time(&settings, now);
+        if settings
+            .quiet_hours
+            .is_some_and(|quiet| quiet.contains(local.hour()))
+        {
+            continue;
+        }
+        let chat = match progress.target().filter(|_| settings.chat) {
+            Some((channel, chat_id)) => match notifier_for(channel) {
+                Some(notifier) => Some((notifier, chat_id)),
+                None => {
+                    eprintln!(
+                        "chat notifications: {} is not configured, skipping {}",
+                        channel.as_str(),
+                        company.name
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        let channels = Channels {
+            chat,
+            emails: if settings.email {
+                &settings.email_recipients
+            } else {
+                &[]
+            },
+            webhook: settings.webhook && !company.webhooks.url.is_empty(),
+        };
+        if channels.is_empty() {
+            continue;
+        }
+        let currency = if company.default_currency.trim().is_empty() {
+            "MXN"
+        } else {
+            company.default_currency.as_str()
+        };
+
+        if settings.overdue_alerts {
+            let since = progress.overdue_checked_at.unwrap_or_else(|| {
+                DateTime::from_chrono(now.to_chrono() - ChronoDuration::days(1))
+            });
+            let entries: Vec<PlannedEntry> = state
+                .planned_entries
+                .find(doc! {
+                    "company_id": company_id,
+                    "due_date": { "$gte": since, "$lt": now },
+                    "status": { "$in": open_statuses() },
+                })
+                .sort(doc! { "due_date": 1 })
+                .await?
+                .try_collect()
+                .await?;
+            let delivered = match overdue_alert_text(&company.name, currency, &entries) {
+                Some(text) => {
+                    let subject = format!("Compromisos vencidos de {}", company.name);
+                    let sent = channels
+                        .deliver(
+                            state,
+                            &company,
+                            WebhookEvent::OverdueAlert,
+                            &subject,
+                            &text,
+                            &mut report,
+                        )
+                        .await;
+                    if sent {
+                        report.alerts_sent += 1;
+                    }
+                    sent
+                }
+                None => true,
+            };
+            if delivered {
+                state
+                    .companies
+                    .update_one(
+                        doc! { "_id": company_id },
+                        doc! { "$set": { "chat_notifications.overdue_checked_at": now } },
+                    )
+                    .await?;
+            }
+        }
+
+        if settings.budget_alerts {
+            check_budget_alerts(state, &company_id, now).await?;
+            let alerts = pending_budget_alerts(state, &company_id, now).await?;
+            if let Some(text) = budget_alert_text(&company.name, currency, &alerts) {
+                let subject = format!("Presupuesto de {}", company.name);
+                if channels
+                    .deliver(
+                        state,
+                        &company,
+                        WebhookEvent::BudgetAlert,
+                        &subject,
+                        &text,
+                        &mut report,
+                    )
+                    .await
+                {
+                    report.budget_alerts_sent += 1;
+                    mark_budget_alerts_notified(state, &company_id, &alerts, now).await?;
+                }
+            }
+        }
+
+        if let Some(day) = digest_due(&settings, &local, progress.last_digest_on.as_deref()) {
+            let text =
+                daily_digest(state, &company_id, now)
+                    .await?
+                    .text(&company.name, currency, &day);
+            let subject = format!("Resumen de {} — {day}", company.name);
+            if channels
+                .deliver(
+                    state,
+                    &company,
+                    WebhookEvent::Digest,
+                    &subject,
+                    &text,
+                    &mut report,
+                )
+                .await
+            {
+                report.digests_sent += 1;
+                state
+                    .companies
+                    .update_one(
+                        doc! { "_id": company_id },
+                        doc! { "$set": { "chat_notifications.last_digest_on": &day } },
+                    )
+                    .await?;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Runs `send_chat_notifications` with the notifiers configured in the
+/// environment right away and then every hour.
+pub fn spawn_chat_notification_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            ticker.tick().await;
+            let now = DateTime::from_chrono(Utc::now());
+            let result = send_chat_notifications(&state, chat_notifier_from_env, now).await;
+            metrics().record_task_run("chat_notifications", result.is_ok());
+            match result {
+                Ok(report) => {
+                    if report != ChatNotificationReport::default() {
+                        println!(
+                            "chat notifications: {} alerts, {} digests, {} budget alerts, {} failed",
+                            report.alerts_sent,
+                            report.digests_sent,
+                            report.budget_alerts_sent,
+                            report.failures
+                        );
+                    }
+                }
+                Err(err) => eprintln!("chat notifications failed: {err:?}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_text_lists_every_figure() {
+        let digest = DailyDigest {
+            overdue_receivable: 1500.0,
+            overdue_receivable_entries: 2,
+            upcoming_entries: 1,
+            upcoming_expense: 12000.0,
+            ..DailyDigest::default()
+        };
+        let text = digest.text("Acme", "MXN", "2025-03-01");
+        assert!(text.starts_with("📊 Resumen de Acme — 2025-03-01"));
+        assert!(text.contains("Vencido por cobrar: $1,500.00 MXN (2)"));
+        assert!(text.contains("1 compromisos, $0.00 MXN por cobrar y $12,000.00 MXN por pagar"));
+    }
+
+    #[test]
+    fn digest_follows_the_local_schedule_and_quiet_hours_wrap_midnight() {
+        use crate::models::QuietHours;
+
+        let settings = NotificationSettings {
+            digest: DigestFrequency::Weekly,
+            digest_hour: 8,
+            digest_weekday: 0,
+            utc_offset: "-06:00".to_string(),
+            ..NotificationSettings::default()
+        };
+        // Monday 2025-03-10, 14:30 UTC is 8:30 in -06:00.
+        let monday = DateTime::parse_rfc3339_str("2025-03-10T14:30:00Z").unwrap();
+        let local = local_time(&settings, monday);
+        assert_eq!(
+            digest_due(&settings, &local, None),
+            Some("2025-03-10".to_string())
+        );
+        assert_eq!(digest_due(&settings, &local, Some("2025-03-10")), None);
+        // 7:30 local is before the hour, and Tuesday is not the day.
+        let early = DateTime::parse_rfc3339_str("2025-03-10T13:30:00Z").unwrap();
+        assert_eq!(
+            digest_due(&settings, &local_time(&settings, early), None),
+            None
+        );
+        let tuesday = DateTime::parse_rfc3339_str("2025-03-11T14:30:00Z").unwrap();
+        assert_eq!(
+            digest_due(&settings, &local_time(&settings, tuesday), None),
+            None
+        );
+        let daily = NotificationSettings {
+            digest: DigestFrequency::Daily,
+            ..settings.clone()
+        };
+        assert!(digest_due(&daily, &local_time(&daily, tuesday), None).is_some());
+
+        let night = QuietHours { start: 22, end: 7 };
+        assert!(night.contains(23) && night.contains(0) && night.contains(6));
+        assert!(!night.contains(7) && !night.contains(21));
+        let lunch = QuietHours { start: 14, end: 16 };
+        assert!(lunch.contains(15) && !lunch.contains(16));
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{Context, Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{Comment, CommentEntity, Notification};
+
+use super::AppState;
+
+/// Longest comment body accepted, in characters.
+pub const COMMENT_MAX_CHARS: usize = 4000;
+
+/// Characters of the body copied into a mention notification.
+const EXCERPT_CHARS: usize = 140;
+
+/// Usernames mentioned as `@username` in `body`, in order and without
+/// repeats. Usernames may be email-like (`@ana@example.com`); a trailing
+/// period ends the sentence, not the name.
+pub fn mentioned_usernames(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for word in body.split_whitespace() {
+        let Some(rest) = word.strip_prefix('@') else {
+            continue;
+        };
+        let name = rest
+            .split([',', ';', ':', '!', '?', '(', ')', '"', '\''])
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('.');
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Company owning the record a comment is left on.
+async fn entity_company(
+    state: &AppState,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+) -> Result<Option<ObjectId>> {
+    let filter = doc! { "_id": entity_id };
+    Ok(match entity {
+        CommentEntity::Transaction => state
+            .transactions
+            .find_one(filter)
+            .await?
+            .map(|tx| tx.company_id),
+        CommentEntity::PlannedEntry => state
+            .planned_entries
+            .find_one(filter)
+            .await?
+            .map(|entry| entry.company_id),
+        CommentEntity::RecurringPlan => state
+            .recurring_plans
+            .find_one(filter)
+            .await?
+            .map(|plan| plan.company_id),
+    })
+}
+
+/// Mentioned users who can read the comment: active admins of the company,
+/// other than the author.
+async fn resolve_mentions(
+    state: &AppState,
+    company_id: &ObjectId,
+    author_id: &ObjectId,
+    body: &str,
+) -> Result<Vec<ObjectId>> {
+    let mut ids = Vec::new();
+    for username in mentioned_usernames(body) {
+        let Some(user) = state.users.find_one(doc! { "username": &username }).await? else {
+            continue;
+        };
+        let Some(user_id) = user.id else {
+            continue;
+        };
+        if &user_id == author_id || !user.is_active || ids.contains(&user_id) {
+            continue;
+        }
+        let is_admin = state
+            .user_companies
+            .find_one(doc! { "user_id": user_id, "company_id": company_id, "role": "admin" })
+            .await?
+            .is_some();
+        if is_admin {
+            ids.push(user_id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Stores a comment on a record of `company_id` and notifies the users it
+/// mentions. A reply to a reply is attached to the top-level comment.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_comment(
+    state: &AppState,
+    company_id: &ObjectId,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+    parent_id: Option<ObjectId>,
+    author_id: &ObjectId,
+    author_name: &str,
+    body: &str,
+) -> Result<Comment> {
+    let body = body.trim();
+    if body.is_empty() {
+        bail!("comment body is empty");
+    }
+    if body.chars().count() > COMMENT_MAX_CHARS {
+        bail!("comment body is longer than {COMMENT_MAX_CHARS} characters");
+    }
+    if entity_company(state, entity, entity_id).await? != Some(*company_id) {
+        bail!("comment target not found");
+    }
+    let parent_id = match parent_id {
+        Some(id) => {
+            let parent = get_comment(state, &id)
+                .await?
+                .context("parent comment not found")?;
+            if parent.entity != entity || &parent.entity_id != entity_id {
+                bail!("parent comment belongs to another record");
+            }
+            Some(parent.parent_id.unwrap_or(id))
+        }
+        None => None,
+    };
+
+    let mut comment = Comment {
+        id: None,
+        company_id: *company_id,
+        entity,
+        entity_id: *entity_id,
+        parent_id,
+        author_id: *author_id,
+        author_name: author_name.to_string(),
+        body: body.to_string(),
+        mentions: resolve_mentions(state, company_id, author_id, body).await?,
+        created_at: DateTime::now(),
+    };
+    let id = state
+        .comments
+        .insert_one(&comment)
+        .await?
+        .inserted_id
+        .as_object_id()
+        .context("comment insert did not return an ObjectId")?;
+    comment.id = Some(id);
+
+    if !comment.mentions.is_empty() {
+        let excerpt: String = comment.body.chars().take(EXCERPT_CHARS).collect();
+        let notifications = comment.mentions.iter().map(|user_id| Notification {
+            id: None,
+            company_id: *company_id,
+            user_id: *user_id,
+            comment_id: id,
+            entity,
+            entity_id: *entity_id,
+            author_name: comment.author_name.clone(),
+            excerpt: excerpt.clone(),
+            created_at: comment.created_at,
+            read_at: None,
+        });
+        state.notifications.insert_many(notifications).await?;
+    }
+    Ok(comment)
+}
+
+pub async fn get_comment(state: &AppState, id: &ObjectId) -> Result<Option<Comment>> {
+    state
+        .comments
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(Into::into)
+}
+
+/// Comments and replies on a record, oldest first.
+pub async fn list_comments(
+    state: &AppState,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+) -> Result<Vec<Comment>> {
+    state
+        .comments
+        .find(doc! { "entity": entity.as_str(), "entity_id": entity_id })
+        .sort(doc! { "created_at": 1, "_id": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Deletes a comment together with its replies and their notifications.
+pub async fn delete_comment(state: &AppState, id: &ObjectId) -> Result<()> {
+    let ids: Vec<ObjectId> = state
+        .comments
+        .find(doc! { "$or": [{ "_id": id }, { "parent_id": id }] })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .filter_map(|comment| comment.id)
+        .collect();
+    state
+        .notifications
+        .delete_many(doc! { "comment_id": { "$in": &ids } })
+        .await?;
+    state
+        .comments
+        .delete_many(doc! { "_id": { "$in": &ids } })
+        .await?;
+    Ok(())
+}
+
+/// Drops the thread of a record that is being deleted.
+pub async fn delete_comments_for(
+    state: &AppState,
+    entity: CommentEntity,
+    entity_id: &ObjectId,
+) -> Result<()> {
+    let filter = doc! { "entity": entity.as_str(), "entity_id": entity_id };
+    state.notifications.delete_many(filter.clone()).await?;
+    state.comments.delete_many(filter).await?;
+    Ok(())
+}
+
+/// Latest notifications of the user in one company, newest first.
+pub async fn list_notifications(
+    state: &AppState,
+    user_id: &ObjectId,
+    company_id: &ObjectId,
+    limit: i64,
+) -> Result<Vec<Notification>> {
+    state
+        .notifications
+        .find(doc! { "user_id": user_id, "company_id": company_id })
+        .sort(doc! { "created_at": -1, "_id": -1 })
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Marks the user's unread notifications in the company as read.
+pub async fn mark_notifications_read(
+    state: &AppState,
+    user_id: &ObjectId,
+    company_id: &ObjectId,
+) -> Result<u64> {
+    let result = state
+        .notifications
+        .update_many(
+            doc! { "user_id": user_id, "company_id": company_id, "read_at": null },
+            doc! { "$set": { "read_at": DateTime::now() } },
+        )
+        .await?;
+    Ok(result.modified_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_are_found_once_without_punctuation() {
+        assert_eq!(
+            mentioned_usernames(
+                "@ana@example.com revisa esto, y tú @luis. Gracias @ana@example.com!"
+            ),
+            vec!["ana@example.com".to_string(), "luis".to_string()]
+        );
+        assert!(mentioned_usernames("correo ana@example.com sin mención @").is_empty());
+    }
+}
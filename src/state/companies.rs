@@ -4,9 +4,12 @@ use mongodb::bson::{DateTime, doc, oid::ObjectId};
 use slug::slugify;
 use std::time::SystemTime;
 
-use crate::models::Company;
+use crate::models::{
+    ChatNotifications, Company, CompanyBranding, CompanyOnboarding, CompanyStorage,
+    CompanyWebhooks, FiscalCalendar, LoginLinkMode, NotificationSettings, RequiredFieldPolicy,
+};
 
-use super::AppState;
+use super::{AppState, IntegrityEntity, find_dependencies, set_archived};
 
 pub async fn list_companies(state: &AppState) -> Result<Vec<Company>> {
     let mut cursor = state.companies.find(doc! {}).await?;
@@ -62,6 +65,18 @@ pub async fn create_company(
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
+            sso_domains: Vec::new(),
+            login_links: LoginLinkMode::Off,
+            archived_at: None,
+            purge_after: None,
+            branding: CompanyBranding::default(),
+            chat_notifications: ChatNotifications::default(),
+            notifications: None,
+            onboarding: CompanyOnboarding::default(),
+            storage: CompanyStorage::default(),
+            webhooks: CompanyWebhooks::default(),
+            fiscal_calendar: FiscalCalendar::default(),
+            required_fields: RequiredFieldPolicy::default(),
         })
         .await?;
 
@@ -113,65 +128,136 @@ pub async fn update_company(
     Ok(())
 }
 
+/// Replaces the branding of the company.
+pub async fn update_company_branding(
+    state: &AppState,
+    id: &ObjectId,
+    branding: &CompanyBranding,
+) -> Result<()> {
+    state
+        .companies
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "branding": mongodb::bson::to_bson(branding)?,
+                "updated_at": DateTime::from_system_time(SystemTime::now())
+            } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Replaces the chat settings of the company. The progress of the
+/// notification task is kept, except that turning overdue alerts off forgets
+/// it: turned back on, they start from the last day instead of catching up.
+pub async fn update_company_chat_notifications(
+    state: &AppState,
+    id: &ObjectId,
+    settings: &ChatNotifications,
+) -> Result<()> {
+    let channel = settings.channel.map(|channel| channel.as_str());
+    let mut update = doc! { "$set": {
+        "chat_notifications.channel": channel,
+        "chat_notifications.chat_id": settings.chat_id.as_str(),
+        "chat_notifications.overdue_alerts": settings.overdue_alerts,
+        "chat_notifications.daily_digest": settings.daily_digest,
+        "chat_notifications.budget_alerts": settings.budget_alerts,
+        "updated_at": DateTime::from_system_time(SystemTime::now())
+    } };
+    if !settings.overdue_alerts {
+        update.insert(
+            "$unset",
+            doc! { "chat_notifications.overdue_checked_at": "" },
+        );
+    }
+    state
+        .companies
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the notification settings of the company, which from then on
+/// decide over the event choices of its chat settings. As there, turning
+/// overdue alerts off forgets how far they got.
+pub async fn update_company_notification_settings(
+    state: &AppState,
+    id: &ObjectId,
+    settings: &NotificationSettings,
+) -> Result<()> {
+    let mut update = doc! { "$set": {
+        "notifications": mongodb::bson::to_bson(settings)?,
+        "updated_at": DateTime::from_system_time(SystemTime::now())
+    } };
+    if !settings.overdue_alerts {
+        update.insert(
+            "$unset",
+            doc! { "chat_notifications.overdue_checked_at": "" },
+        );
+    }
+    state
+        .companies
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    Ok(())
+}
+
+/// Sets whether the company's staff may sign in with an emailed link.
+pub async fn update_company_login_links(
+    state: &AppState,
+    id: &ObjectId,
+    mode: LoginLinkMode,
+) -> Result<()> {
+    state
+        .companies
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "login_links": mode.as_str(),
+                "updated_at": DateTime::from_system_time(SystemTime::now())
+            } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Replaces the webhook URL, secret and events of the company. How the last
+/// delivery went is kept until the next one.
+pub async fn update_company_webhooks(
+    state: &AppState,
+    id: &ObjectId,
+    settings: &CompanyWebhooks,
+) -> Result<()> {
+    state
+        .companies
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "webhooks.url": settings.url.as_str(),
+                "webhooks.secret": settings.secret.as_str(),
+                "webhooks.forecast_completed": settings.forecast_completed,
+                "updated_at": DateTime::from_system_time(SystemTime::now())
+            } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Deletes the company, or deactivates it while any of its records remain.
 pub async fn delete_company(state: &AppState, id: &ObjectId) -> Result<()> {
-    let has_dependents = state
-        .accounts
-        .find_one(doc! { "company_id": id })
+    if !find_dependencies(state, IntegrityEntity::Company, id, id)
         .await?
-        .is_some()
-        || state
-            .categories
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some()
-        || state
-            .contacts
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some()
-        || state
-            .recurring_plans
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some()
-        || state
-            .planned_entries
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some()
-        || state
-            .transactions
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some()
-        || state
-            .forecasts
-            .find_one(doc! { "company_id": id })
-            .await?
-            .is_some();
-
-    if has_dependents {
-        state
-            .companies
-            .update_one(
-                doc! { "_id": id },
-                doc! { "$set": {
-                    "is_active": false,
-                    "updated_at": DateTime::from_system_time(SystemTime::now())
-                } },
-            )
-            .await?;
-        return Ok(());
+        .is_empty()
+    {
+        return set_archived(state, IntegrityEntity::Company, id, true).await;
     }
 
     state.companies.delete_one(doc! { "_id": id }).await?;
     Ok(())
 }
 
-pub(super) async fn company_default_currency(
-    state: &AppState,
-    company_id: &ObjectId,
-) -> Result<String> {
+/// Currency the company's amounts are kept in; MXN when it has none.
+pub async fn company_default_currency(state: &AppState, company_id: &ObjectId) -> Result<String> {
     let company = state
         .companies
         .find_one(doc! { "_id": company_id })
@@ -0,0 +1,327 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection,
+    bson::{Bson, DateTime, Document, doc, oid::ObjectId},
+    options::FindOptions,
+};
+use serde::de::DeserializeOwned;
+use slug::slugify;
+use std::{collections::HashMap, time::SystemTime};
+
+use crate::models::{CustomFieldDefinition, CustomFieldEntity, CustomFieldType};
+
+use super::AppState;
+
+/// Query parameters named `cf_<key>` filter by custom field values.
+pub const CUSTOM_FIELD_PARAM_PREFIX: &str = "cf_";
+
+fn entity_collection(state: &AppState, entity: CustomFieldEntity) -> Collection<Document> {
+    match entity {
+        CustomFieldEntity::Transaction => state.transactions.clone_with_type(),
+        CustomFieldEntity::Contact => state.contacts.clone_with_type(),
+    }
+}
+
+/// `"Centro de costos"` -> `"centro_de_costos"`.
+pub fn custom_field_key(label: &str) -> String {
+    slugify(label).replace('-', "_")
+}
+
+pub async fn list_custom_fields(
+    state: &AppState,
+    company_id: &ObjectId,
+    entity: Option<CustomFieldEntity>,
+) -> Result<Vec<CustomFieldDefinition>> {
+    let mut filter = doc! { "company_id": company_id };
+    if let Some(entity) = entity {
+        filter.insert("entity", entity.as_str());
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "entity": 1, "created_at": 1 })
+        .build();
+    Ok(state
+        .custom_fields
+        .find(filter)
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?)
+}
+
+pub async fn get_custom_field_by_id(
+    state: &AppState,
+    id: &ObjectId,
+) -> Result<Option<CustomFieldDefinition>> {
+    Ok(state.custom_fields.find_one(doc! { "_id": id }).await?)
+}
+
+pub async fn create_custom_field(
+    state: &AppState,
+    company_id: &ObjectId,
+    entity: CustomFieldEntity,
+    label: &str,
+    field_type: CustomFieldType,
+    required: bool,
+) -> Result<ObjectId> {
+    let label = label.trim();
+    let key = custom_field_key(label);
+    if key.is_empty() {
+        bail!("El nombre del campo es obligatorio");
+    }
+    let exists = state
+        .custom_fields
+        .find_one(doc! { "company_id": company_id, "entity": entity.as_str(), "key": &key })
+        .await?
+        .is_some();
+    if exists {
+        bail!("Ya existe un campo llamado {label}");
+    }
+    let res = state
+        .custom_fields
+        .insert_one(CustomFieldDefinition {
+            id: None,
+            company_id: *company_id,
+            entity,
+            key,
+            label: label.to_string(),
+            field_type,
+            required,
+            created_at: Some(DateTime::from_system_time(SystemTime::now())),
+        })
+        .await?;
+    res.inserted_id
+        .as_object_id()
+        .context("custom field insert missing _id")
+}
+
+/// Removes the definition and the values stored under its key.
+pub async fn delete_custom_field(state: &AppState, field: &CustomFieldDefinition) -> Result<()> {
+    let id = field.id.context("custom field without _id")?;
+    entity_collection(state, field.entity)
+        .update_many(
+            doc! { "company_id": field.company_id },
+            doc! { "$unset": { format!("custom_fields.{}", field.key): "" } },
+        )
+        .await?;
+    state.custom_fields.delete_one(doc! { "_id": id }).await?;
+    Ok(())
+}
+
+/// Converts a submitted value to the BSON type of the field. Empty input is
+/// `None`, except for booleans where it means `false` (an unchecked box).
+pub fn parse_custom_field_value(
+    field: &CustomFieldDefinition,
+    raw: &str,
+) -> Result<Option<Bson>, String> {
+    let raw = raw.trim();
+    if field.field_type == CustomFieldType::Boolean {
+        return match raw.to_lowercase().as_str() {
+            "" | "false" | "0" | "no" | "off" => Ok(Some(Bson::Boolean(false))),
+            "true" | "1" | "si" | "sí" | "on" => Ok(Some(Bson::Boolean(true))),
+            _ => Err(format!("{} debe ser sí o no", field.label)),
+        };
+    }
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    match field.field_type {
+        CustomFieldType::Text => Ok(Some(Bson::String(raw.to_string()))),
+        CustomFieldType::Number => raw
+            .replace(',', "")
+            .parse::<f64>()
+            .map(|value| Some(Bson::Double(value)))
+            .map_err(|_| format!("{} debe ser un número", field.label)),
+        CustomFieldType::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| Some(Bson::DateTime(DateTime::from_chrono(date.and_utc()))))
+            .ok_or_else(|| format!("{} debe ser una fecha AAAA-MM-DD", field.label)),
+        CustomFieldType::Boolean => unreachable!(),
+    }
+}
+
+/// Builds the `custom_fields` map from submitted values keyed by field key.
+/// Keys without a definition are dropped; missing required values fail.
+pub fn custom_field_values(
+    fields: &[CustomFieldDefinition],
+    raw: &HashMap<String, String>,
+) -> Result<Document, String> {
+    let mut values = Document::new();
+    for field in fields {
+        let raw_value = raw.get(&field.key).map(String::as_str).unwrap_or_default();
+        match parse_custom_field_value(field, raw_value)? {
+            Some(value) => {
+                values.insert(field.key.clone(), value);
+            }
+            None if field.required => return Err(format!("{} es obligatorio", field.label)),
+            None => {}
+        }
+    }
+    Ok(values)
+}
+
+/// Text shown in forms and listings for a stored value.
+pub fn custom_field_display(field: &CustomFieldDefinition, value: Option<&Bson>) -> String {
+    match (field.field_type, value) {
+        (_, None) => String::new(),
+        (CustomFieldType::Date, Some(Bson::DateTime(date))) => {
+            date.to_chrono().format("%Y-%m-%d").to_string()
+        }
+        (CustomFieldType::Boolean, Some(Bson::Boolean(value))) => {
+            if *value { "Sí" } else { "No" }.to_string()
+        }
+        (_, Some(Bson::String(value))) => value.clone(),
+        (_, Some(Bson::Double(value))) => value.to_string(),
+        (_, Some(other)) => other.to_string(),
+    }
+}
+
+/// Replaces the custom field values of one document of the company.
+pub async fn set_custom_field_values(
+    state: &AppState,
+    entity: CustomFieldEntity,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    values: Document,
+) -> Result<()> {
+    entity_collection(state, entity)
+        .update_one(
+            doc! { "_id": id, "company_id": company_id },
+            doc! { "$set": { "custom_fields": values } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Documents of the company matching a `custom_field_filter`.
+pub async fn find_by_custom_fields<T>(
+    collection: &Collection<T>,
+    company_id: &ObjectId,
+    filter: Document,
+) -> Result<Vec<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    let mut query = doc! { "company_id": company_id };
+    query.extend(filter);
+    Ok(collection.find(query).await?.try_collect().await?)
+}
+
+pub(super) fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// MongoDB conditions for the `cf_<key>` parameters of a query string. Text
+/// matches case-insensitively anywhere in the value; other types must be
+/// equal. Empty parameters and unknown keys are ignored.
+pub fn custom_field_filter(
+    fields: &[CustomFieldDefinition],
+    params: &HashMap<String, String>,
+) -> Result<Document, String> {
+    let mut filter = Document::new();
+    for field in fields {
+        let Some(raw) = params.get(&format!("{CUSTOM_FIELD_PARAM_PREFIX}{}", field.key)) else {
+            continue;
+        };
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let path = format!("custom_fields.{}", field.key);
+        let condition = match field.field_type {
+            CustomFieldType::Text => Bson::Document(doc! {
+                "$regex": escape_regex(raw.trim()),
+                "$options": "i",
+            }),
+            _ => match parse_custom_field_value(field, raw)? {
+                Some(value) => value,
+                None => continue,
+            },
+        };
+        filter.insert(path, condition);
+    }
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(key: &str, field_type: CustomFieldType, required: bool) -> CustomFieldDefinition {
+        CustomFieldDefinition {
+            id: None,
+            company_id: ObjectId::new(),
+            entity: CustomFieldEntity::Transaction,
+            key: key.into(),
+            label: key.into(),
+            field_type,
+            required,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn values_are_typed_and_required_fields_enforced() {
+        let fields = vec![
+            field("centro", CustomFieldType::Text, true),
+            field("litros", CustomFieldType::Number, false),
+            field("vence", CustomFieldType::Date, false),
+            field("deducible", CustomFieldType::Boolean, false),
+        ];
+        let raw: HashMap<String, String> = [
+            ("centro", " Planta "),
+            ("litros", "1,250.5"),
+            ("vence", "2025-06-30"),
+            ("otro", "x"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let values = custom_field_values(&fields, &raw).unwrap();
+        assert_eq!(values.get_str("centro").unwrap(), "Planta");
+        assert_eq!(values.get_f64("litros").unwrap(), 1250.5);
+        assert!(values.get_datetime("vence").is_ok());
+        assert!(!values.get_bool("deducible").unwrap());
+        assert!(!values.contains_key("otro"));
+        assert_eq!(
+            custom_field_display(&fields[2], values.get("vence")),
+            "2025-06-30"
+        );
+
+        assert!(custom_field_values(&fields, &HashMap::new()).is_err());
+        let bad = HashMap::from([
+            ("centro".to_string(), "Planta".to_string()),
+            ("litros".to_string(), "mucho".to_string()),
+        ]);
+        assert!(custom_field_values(&fields, &bad).is_err());
+    }
+
+    #[test]
+    fn filter_uses_prefixed_params_and_escapes_text() {
+        let fields = vec![
+            field("centro", CustomFieldType::Text, false),
+            field("deducible", CustomFieldType::Boolean, false),
+        ];
+        let params = HashMap::from([
+            ("cf_centro".to_string(), "a.b".to_string()),
+            ("cf_deducible".to_string(), "true".to_string()),
+            ("centro".to_string(), "ignored".to_string()),
+        ]);
+        let filter = custom_field_filter(&fields, &params).unwrap();
+        assert_eq!(
+            filter.get_document("custom_fields.centro").unwrap(),
+            &doc! { "$regex": "a\\.b", "$options": "i" }
+        );
+        assert!(filter.get_bool("custom_fields.deducible").unwrap());
+        assert_eq!(custom_field_key("Centro de costos"), "centro_de_costos");
+    }
+}
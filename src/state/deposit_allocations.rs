@@ -0,0 +1,325 @@
+// Planned income split across deposit accounts. A customer may pay one
+// invoice into several accounts: each share is a percentage of the estimate
+// or a fixed amount, and whatever the shares leave is still expected in the
+// entry's own account. Account projections spread the pending income by these
+// shares, and income covering the entry has to land in one of its accounts.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{DepositAllocation, FlowType, PlannedEntry, PlannedStatus};
+
+use super::{AppState, finance::ensure_account_active_in_company};
+
+/// Most accounts one income can be split into.
+pub const MAX_DEPOSIT_ALLOCATIONS: usize = 10;
+
+/// Amount expected in one account and how much of it already arrived.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepositProgress {
+    pub account_id: ObjectId,
+    pub expected: f64,
+    /// Confirmed income linked to the entry that landed in the account.
+    pub received: f64,
+}
+
+impl DepositProgress {
+    pub fn pending(&self) -> f64 {
+        (self.expected - self.received).max(0.0)
+    }
+}
+
+/// What each account should receive of `amount`: the fixed shares, then the
+/// percentages, then the rest in `account_expected_id`. Shares are capped so
+/// they never add up to more than `amount`, which payments may have lowered
+/// since they were set.
+pub fn deposit_shares(
+    account_expected_id: &ObjectId,
+    amount: f64,
+    allocations: &[DepositAllocation],
+) -> Vec<(ObjectId, f64)> {
+    let mut shares: Vec<(ObjectId, f64)> = Vec::new();
+    let mut left = amount.max(0.0);
+    let fixed = allocations.iter().filter(|a| a.amount.is_some());
+    let percent = allocations.iter().filter(|a| a.amount.is_none());
+    for allocation in fixed.chain(percent) {
+        let share = match (allocation.amount, allocation.percentage) {
+            (Some(value), _) => value,
+            (None, Some(pct)) => amount * pct / 100.0,
+            (None, None) => 0.0,
+        }
+        .min(left);
+        left -= share;
+        add_share(&mut shares, allocation.account_id, share);
+    }
+    if left >= 0.005 || shares.is_empty() {
+        add_share(&mut shares, *account_expected_id, left);
+    }
+    shares
+}
+
+fn add_share(shares: &mut Vec<(ObjectId, f64)>, account_id: ObjectId, amount: f64) {
+    match shares.iter_mut().find(|(id, _)| *id == account_id) {
+        Some((_, total)) => *total += amount,
+        None => shares.push((account_id, amount)),
+    }
+}
+
+/// Accounts income covering `entry` may land in.
+pub fn deposit_accounts(entry: &PlannedEntry) -> Vec<ObjectId> {
+    let mut accounts = vec![entry.account_expected_id];
+    for allocation in &entry.deposit_allocations {
+        if !accounts.contains(&allocation.account_id) {
+            accounts.push(allocation.account_id);
+        }
+    }
+    accounts
+}
+
+/// Checks a split before it is stored; the messages are shown as is.
+pub fn validate_deposit_allocations(
+    flow_type: &FlowType,
+    amount_estimated: f64,
+    allocations: &[DepositAllocation],
+) -> Result<()> {
+    if allocations.is_empty() {
+        return Ok(());
+    }
+    if *flow_type != FlowType::Income {
+        bail!("Solo los ingresos se reparten entre cuentas");
+    }
+    if allocations.len() > MAX_DEPOSIT_ALLOCATIONS {
+        bail!("Un ingreso se reparte en {MAX_DEPOSIT_ALLOCATIONS} cuentas como máximo");
+    }
+    let mut assigned = 0.0;
+    for (index, allocation) in allocations.iter().enumerate() {
+        if allocations[..index]
+            .iter()
+            .any(|other| other.account_id == allocation.account_id)
+        {
+            bail!("Cada cuenta aparece una sola vez en el reparto");
+        }
+        assigned += match (allocation.percentage, allocation.amount) {
+            (Some(pct), None) if pct > 0.0 && pct <= 100.0 => amount_estimated * pct / 100.0,
+            (None, Some(amount)) if amount > 0.0 => amount,
+            (Some(_), None) => bail!("El porcentaje va de más de 0 a 100"),
+            (None, Some(_)) => bail!("El monto de cada cuenta debe ser mayor a cero"),
+            _ => bail!("Cada cuenta lleva un porcentaje o un monto, solo uno de los dos"),
+        };
+    }
+    if assigned > amount_estimated + 0.005 {
+        bail!("El reparto suma más que el monto estimado");
+    }
+    Ok(())
+}
+
+/// Replaces the split of the income entry `id`; an empty one expects it all
+/// in its own account again. Marks the entry as customized so regenerating
+/// its plan keeps the split.
+pub async fn set_deposit_allocations(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    allocations: Vec<DepositAllocation>,
+) -> Result<()> {
+    let entry = state
+        .planned_entries
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("Compromiso no encontrado")?;
+    validate_deposit_allocations(&entry.flow_type, entry.amount_estimated, &allocations)?;
+    for allocation in &allocations {
+        ensure_account_active_in_company(state, &allocation.account_id, company_id)
+            .await
+            .context("La cuenta del reparto no está activa en la compañía")?;
+    }
+    let now = DateTime::now();
+    let update = if allocations.is_empty() {
+        doc! {
+            "$unset": { "deposit_allocations": "" },
+            "$set": { "is_customized": true, "updated_at": now },
+        }
+    } else {
+        doc! { "$set": {
+            "deposit_allocations": mongodb::bson::to_bson(&allocations)?,
+            "is_customized": true,
+            "updated_at": now,
+        } }
+    };
+    state
+        .planned_entries
+        .update_one(doc! { "_id": id, "company_id": company_id }, update)
+        .await?;
+    Ok(())
+}
+
+/// Income linked to each of `entry_ids` by the account it landed in.
+async fn received_by_account(
+    state: &AppState,
+    entry_ids: &[ObjectId],
+) -> Result<HashMap<(ObjectId, ObjectId), f64>> {
+    let mut received = HashMap::new();
+    if entry_ids.is_empty() {
+        return Ok(received);
+    }
+    // Drafts do not cover anything until they are confirmed.
+    let mut cursor = state
+        .transactions
+        .find(doc! {
+            "planned_entry_id": { "$in": entry_ids },
+            "is_confirmed": { "$ne": false },
+        })
+        .await?;
+    while let Some(tx) = cursor.try_next().await? {
+        let (Some(entry_id), Some(account_id)) = (tx.planned_entry_id, tx.account_to_id) else {
+            continue;
+        };
+        *received.entry((entry_id, account_id)).or_insert(0.0) += tx.amount;
+    }
+    Ok(received)
+}
+
+fn progress_of(
+    entry: &PlannedEntry,
+    received: &HashMap<(ObjectId, ObjectId), f64>,
+) -> Vec<DepositProgress> {
+    deposit_shares(
+        &entry.account_expected_id,
+        entry.amount_estimated,
+        &entry.deposit_allocations,
+    )
+    .into_iter()
+    .map(|(account_id, expected)| DepositProgress {
+        account_id,
+        expected,
+        received: entry
+            .id
+            .and_then(|id| received.get(&(id, account_id)).copied())
+            .unwrap_or(0.0),
+    })
+    .collect()
+}
+
+/// Expected and received amount of each deposit account of `entry`.
+pub async fn deposit_progress(
+    state: &AppState,
+    entry: &PlannedEntry,
+) -> Result<Vec<DepositProgress>> {
+    let received = match entry.id {
+        Some(id) => received_by_account(state, &[id]).await?,
+        None => HashMap::new(),
+    };
+    Ok(progress_of(entry, &received))
+}
+
+/// Income still to arrive in each account of the company from the open
+/// entries due up to `until`, overdue ones included, spread by their splits.
+pub async fn pending_deposits(
+    state: &AppState,
+    company_id: &ObjectId,
+    until: DateTime,
+) -> Result<HashMap<ObjectId, f64>> {
+    let entries: Vec<PlannedEntry> = state
+        .planned_entries
+        .find(doc! {
+            "company_id": company_id,
+            "flow_type": "income",
+            "due_date": { "$lte": until },
+            "status": { "$in": [
+                PlannedStatus::Planned.as_str(),
+                PlannedStatus::PartiallyCovered.as_str(),
+                PlannedStatus::Overdue.as_str(),
+            ] },
+        })
+        .await?
+        .try_collect()
+        .await?;
+    let ids: Vec<ObjectId> = entries.iter().filter_map(|entry| entry.id).collect();
+    let received = received_by_account(state, &ids).await?;
+    let mut pending = HashMap::new();
+    for entry in &entries {
+        for progress in progress_of(entry, &received) {
+            *pending.entry(progress.account_id).or_insert(0.0) += progress.pending();
+        }
+    }
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(account_id: ObjectId, pct: f64) -> DepositAllocation {
+        DepositAllocation {
+            account_id,
+            percentage: Some(pct),
+            amount: None,
+        }
+    }
+
+    fn fixed(account_id: ObjectId, amount: f64) -> DepositAllocation {
+        DepositAllocation {
+            account_id,
+            percentage: None,
+            amount: Some(amount),
+        }
+    }
+
+    #[test]
+    fn shares_take_fixed_amounts_then_percentages_and_leave_the_rest() {
+        let (main, savings, payroll) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let shares = deposit_shares(
+            &main,
+            1_000.0,
+            &[percent(savings, 30.0), fixed(payroll, 200.0)],
+        );
+        assert_eq!(
+            shares,
+            vec![(payroll, 200.0), (savings, 300.0), (main, 500.0)]
+        );
+
+        // A payment lowered the estimate below the fixed share.
+        let shares = deposit_shares(&main, 150.0, &[fixed(payroll, 200.0)]);
+        assert_eq!(shares, vec![(payroll, 150.0)]);
+
+        assert_eq!(deposit_shares(&main, 80.0, &[]), vec![(main, 80.0)]);
+    }
+
+    #[test]
+    fn splits_must_fit_the_estimate() {
+        let (savings, payroll) = (ObjectId::new(), ObjectId::new());
+        let income = FlowType::Income;
+        assert!(
+            validate_deposit_allocations(
+                &income,
+                1_000.0,
+                &[percent(savings, 50.0), fixed(payroll, 500.0)]
+            )
+            .is_ok()
+        );
+        assert!(
+            validate_deposit_allocations(
+                &income,
+                1_000.0,
+                &[percent(savings, 60.0), fixed(payroll, 500.0)]
+            )
+            .is_err()
+        );
+        assert!(validate_deposit_allocations(&income, 1_000.0, &[percent(savings, 0.0)]).is_err());
+        assert!(
+            validate_deposit_allocations(
+                &income,
+                1_000.0,
+                &[percent(savings, 10.0), fixed(savings, 10.0)]
+            )
+            .is_err()
+        );
+        assert!(
+            validate_deposit_allocations(&FlowType::Expense, 1_000.0, &[percent(savings, 10.0)])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// Company events pushed to open pages: a broadcast channel in AppState that
+// the finance functions publish to and `/events` streams from, filtered to
+// the listener's company and the accounts it may see.
+
+use mongodb::bson::oid::ObjectId;
+use tokio::sync::broadcast;
+
+use super::AppState;
+
+/// Events buffered per listener; a listener further behind skips ahead and is
+/// told to reload instead.
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompanyEventKind {
+    TransactionCreated,
+    /// A planned entry became fully covered by its transactions.
+    EntryCovered,
+    ForecastReady,
+}
+
+impl CompanyEventKind {
+    /// Name of the SSE event.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompanyEventKind::TransactionCreated => "transaction_created",
+            CompanyEventKind::EntryCovered => "entry_covered",
+            CompanyEventKind::ForecastReady => "forecast_ready",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompanyEvent {
+    pub company_id: ObjectId,
+    pub kind: CompanyEventKind,
+    /// The transaction, planned entry, forecast or scenario group the event is
+    /// about.
+    pub id: ObjectId,
+    /// Accounts the transaction moves money through or the planned entry is
+    /// expected on; empty for forecasts, which cover the whole company.
+    pub account_ids: Vec<ObjectId>,
+}
+
+pub fn event_channel() -> broadcast::Sender<CompanyEvent> {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
+
+/// Sends the event to whoever is listening. Nobody listening is not an error.
+pub fn publish_event(
+    state: &AppState,
+    company_id: &ObjectId,
+    kind: CompanyEventKind,
+    id: ObjectId,
+    account_ids: Vec<ObjectId>,
+) {
+    let _ = state.events.send(CompanyEvent {
+        company_id: *company_id,
+        kind,
+        id,
+        account_ids,
+    });
+}
@@ -0,0 +1,361 @@
+// Fake finance records for manual QA and load testing: plausible accounts,
+// categories, contacts, planned entries and transactions for one company, in
+// whatever quantity the pagination or report under test needs. Only built
+// with the `dev-factory` feature, which refuses release builds.
+//
+// Records go through the regular `create_*` functions, so references,
+// balance snapshots and events behave as with records typed in by hand.
+// Every record carries `FACTORY_NOTE` in its notes.
+
+use anyhow::{Result, bail};
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::IndexedRandom};
+use slug::slugify;
+
+use super::{
+    AppState,
+    finance::{
+        create_account, create_category, create_contact, create_planned_entry, create_transaction,
+    },
+};
+use crate::models::{AccountType, ContactType, FlowType, PlannedStatus, TransactionType};
+
+/// Most records one call creates.
+pub const FACTORY_MAX_COUNT: usize = 1000;
+
+/// Notes of every generated record, to find them again.
+pub const FACTORY_NOTE: &str = "Dato de prueba generado";
+
+const FIRST_NAMES: &[&str] = &[
+    "Ana", "Luis", "María", "José", "Carmen", "Jorge", "Lucía", "Miguel", "Sofía", "Ricardo",
+];
+const LAST_NAMES: &[&str] = &[
+    "García",
+    "Hernández",
+    "López",
+    "Martínez",
+    "Pérez",
+    "Ramírez",
+    "Torres",
+    "Flores",
+];
+const BUSINESSES: &[&str] = &[
+    "Distribuidora",
+    "Comercializadora",
+    "Servicios",
+    "Grupo",
+    "Consultores",
+    "Papelería",
+    "Transportes",
+];
+const BUSINESS_NAMES: &[&str] = &[
+    "del Norte",
+    "Azteca",
+    "Central",
+    "Pacífico",
+    "Bajío",
+    "Integrales",
+    "Monterrey",
+];
+const INCOME_CONCEPTS: &[&str] = &[
+    "Venta de mostrador",
+    "Cobro de factura",
+    "Anticipo de cliente",
+    "Servicio de consultoría",
+    "Intereses bancarios",
+];
+const EXPENSE_CONCEPTS: &[&str] = &[
+    "Renta de oficina",
+    "Pago a proveedor",
+    "Nómina",
+    "Luz",
+    "Internet y teléfono",
+    "Papelería",
+    "Gasolina",
+    "Comisiones bancarias",
+];
+const ACCOUNT_NAMES: &[&str] = &[
+    "BBVA",
+    "Banorte",
+    "Santander",
+    "Banamex",
+    "HSBC",
+    "Caja chica",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactoryEntity {
+    Accounts,
+    Categories,
+    Contacts,
+    PlannedEntries,
+    Transactions,
+}
+
+impl FactoryEntity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "accounts" => Some(FactoryEntity::Accounts),
+            "categories" => Some(FactoryEntity::Categories),
+            "contacts" => Some(FactoryEntity::Contacts),
+            "planned_entries" => Some(FactoryEntity::PlannedEntries),
+            "transactions" => Some(FactoryEntity::Transactions),
+            _ => None,
+        }
+    }
+}
+
+fn pick<'a>(rng: &mut StdRng, values: &[&'a str]) -> &'a str {
+    values.choose(rng).copied().unwrap_or_default()
+}
+
+fn amount(rng: &mut StdRng, min: f64, max: f64) -> f64 {
+    (rng.random_range(min..max) * 100.0).round() / 100.0
+}
+
+/// Day `offset` days away from today.
+fn days_from_today(offset: i64) -> DateTime {
+    DateTime::from_chrono(Utc::now() + Duration::days(offset))
+}
+
+fn note() -> Option<String> {
+    Some(FACTORY_NOTE.to_string())
+}
+
+/// Accounts and categories of each flow type the company has, creating one
+/// of each when it has none, so entries and transactions have something to
+/// point at.
+async fn ensure_links(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<(Vec<ObjectId>, Vec<ObjectId>, Vec<ObjectId>)> {
+    let filter = doc! { "company_id": company_id };
+    let mut accounts = Vec::new();
+    let mut cursor = state.accounts.find(filter.clone()).await?;
+    while let Some(account) = cursor.try_next().await? {
+        accounts.extend(account.id);
+    }
+    if accounts.is_empty() {
+        accounts.push(
+            create_account(
+                state,
+                company_id,
+                "Cuenta de prueba",
+                AccountType::Bank,
+                "",
+                true,
+                note(),
+            )
+            .await?,
+        );
+    }
+
+    let (mut income, mut expense) = (Vec::new(), Vec::new());
+    let mut cursor = state.categories.find(filter).await?;
+    while let Some(category) = cursor.try_next().await? {
+        match category.flow_type {
+            FlowType::Income => income.extend(category.id),
+            FlowType::Expense => expense.extend(category.id),
+        }
+    }
+    for (ids, name, flow_type) in [
+        (&mut income, "Ingresos de prueba", FlowType::Income),
+        (&mut expense, "Gastos de prueba", FlowType::Expense),
+    ] {
+        if ids.is_empty() {
+            ids.push(create_category(state, company_id, name, flow_type, None, note()).await?);
+        }
+    }
+    Ok((accounts, income, expense))
+}
+
+/// Creates `count` fake records of `entity` for the company and returns
+/// their ids. Transactions fall within the last year and planned entries
+/// between two months ago and six months ahead.
+pub async fn generate_fake_records(
+    state: &AppState,
+    company_id: &ObjectId,
+    entity: FactoryEntity,
+    count: usize,
+) -> Result<Vec<ObjectId>> {
+    if count == 0 || count > FACTORY_MAX_COUNT {
+        bail!("count must be between 1 and {FACTORY_MAX_COUNT}");
+    }
+    // `ThreadRng` cannot be held across the inserts below.
+    let mut rng = StdRng::from_rng(&mut rand::rng());
+    let mut ids = Vec::with_capacity(count);
+
+    match entity {
+        FactoryEntity::Accounts => {
+            let types = [
+                AccountType::Bank,
+                AccountType::Cash,
+                AccountType::CreditCard,
+            ];
+            for n in 1..=count {
+                let name = format!("{} {n}", pick(&mut rng, ACCOUNT_NAMES));
+                let account_type = types.choose(&mut rng).cloned().unwrap_or(AccountType::Bank);
+                ids.push(
+                    create_account(state, company_id, &name, account_type, "", true, note())
+                        .await?,
+                );
+            }
+        }
+        FactoryEntity::Categories => {
+            for n in 1..=count {
+                let (concepts, flow_type) = if rng.random_bool(0.3) {
+                    (INCOME_CONCEPTS, FlowType::Income)
+                } else {
+                    (EXPENSE_CONCEPTS, FlowType::Expense)
+                };
+                let name = format!("{} {n}", pick(&mut rng, concepts));
+                ids.push(create_category(state, company_id, &name, flow_type, None, note()).await?);
+            }
+        }
+        FactoryEntity::Contacts => {
+            let types = [
+                ContactType::Customer,
+                ContactType::Supplier,
+                ContactType::Service,
+            ];
+            for _ in 0..count {
+                let (name, email) = if rng.random_bool(0.5) {
+                    let first = pick(&mut rng, FIRST_NAMES);
+                    let last = pick(&mut rng, LAST_NAMES);
+                    let email = format!("{}.{}@example.com", slugify(first), slugify(last));
+                    (format!("{first} {last}"), email)
+                } else {
+                    let name = format!(
+                        "{} {}",
+                        pick(&mut rng, BUSINESSES),
+                        pick(&mut rng, BUSINESS_NAMES)
+                    );
+                    let email = format!("contacto{}@example.com", rng.random_range(100..10_000));
+                    (name, email)
+                };
+                let phone = format!("55{:08}", rng.random_range(0..100_000_000));
+                let contact_type = types
+                    .choose(&mut rng)
+                    .cloned()
+                    .unwrap_or(ContactType::Other);
+                ids.push(
+                    create_contact(
+                        state,
+                        company_id,
+                        &name,
+                        contact_type,
+                        None,
+                        Some(email),
+                        Some(phone),
+                        note(),
+                    )
+                    .await?,
+                );
+            }
+        }
+        FactoryEntity::PlannedEntries => {
+            let (accounts, income, expense) = ensure_links(state, company_id).await?;
+            for _ in 0..count {
+                let (concepts, categories, flow_type) = if rng.random_bool(0.4) {
+                    (INCOME_CONCEPTS, &income, FlowType::Income)
+                } else {
+                    (EXPENSE_CONCEPTS, &expense, FlowType::Expense)
+                };
+                let name = pick(&mut rng, concepts).to_string();
+                let category_id = categories[rng.random_range(0..categories.len())];
+                let account_id = accounts[rng.random_range(0..accounts.len())];
+                let amount_estimated = amount(&mut rng, 500.0, 80_000.0);
+                let due_date = days_from_today(rng.random_range(-60..=180));
+                ids.push(
+                    create_planned_entry(
+                        state,
+                        company_id,
+                        None,
+                        None,
+                        None,
+                        &name,
+                        flow_type,
+                        &category_id,
+                        &account_id,
+                        None,
+                        amount_estimated,
+                        due_date,
+                        PlannedStatus::Planned,
+                        note(),
+                    )
+                    .await?,
+                );
+            }
+        }
+        FactoryEntity::Transactions => {
+            let (accounts, income, expense) = ensure_links(state, company_id).await?;
+            for _ in 0..count {
+                let account_id = accounts[rng.random_range(0..accounts.len())];
+                let (concepts, categories, transaction_type, from, to) = if rng.random_bool(0.4) {
+                    (
+                        INCOME_CONCEPTS,
+                        &income,
+                        TransactionType::Income,
+                        None,
+                        Some(account_id),
+                    )
+                } else {
+                    (
+                        EXPENSE_CONCEPTS,
+                        &expense,
+                        TransactionType::Expense,
+                        Some(account_id),
+                        None,
+                    )
+                };
+                let description = pick(&mut rng, concepts).to_string();
+                let category_id = categories[rng.random_range(0..categories.len())];
+                let amount = amount(&mut rng, 50.0, 50_000.0);
+                let date = days_from_today(-rng.random_range(0..365));
+                ids.push(
+                    create_transaction(
+                        state,
+                        company_id,
+                        date,
+                        &description,
+                        transaction_type,
+                        &category_id,
+                        from,
+                        to,
+                        amount,
+                        None,
+                        None,
+                        true,
+                        note(),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .await?,
+                );
+            }
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factory_entities_parse_from_their_path_segment() {
+        assert_eq!(
+            FactoryEntity::parse("planned_entries"),
+            Some(FactoryEntity::PlannedEntries)
+        );
+        assert_eq!(
+            FactoryEntity::parse("transactions"),
+            Some(FactoryEntity::Transactions)
+        );
+        assert_eq!(FactoryEntity::parse("users"), None);
+    }
+}
@@ -1,15 +1,41 @@
 use anyhow::{Context, Result, bail};
 use chrono::{DateTime as ChronoDateTime, Datelike, Months, TimeZone, Timelike, Utc};
 use futures::stream::TryStreamExt;
-use mongodb::bson::{DateTime, doc, oid::ObjectId};
-use std::time::SystemTime;
+use mongodb::bson::{Bson, DateTime, Document, doc, oid::ObjectId};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::ReturnDocument;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use crate::metrics::metrics;
 use crate::models::{
-    Account, AccountType, Category, Contact, ContactType, FlowType, Forecast, PlannedEntry,
-    PlannedStatus, RecurringPlan, Transaction, TransactionType,
+    Account, AccountType, AmountRevision, Category, CommentEntity, Contact, ContactType,
+    CoverageAdjustment, FlowType, Forecast, ForecastScenario, HoldStatus, PlannedEntry,
+    PlannedStatus, RecurringPlan, ScenarioWeights, Transaction, TransactionType,
 };
 
-use super::{AppState, PLANNED_MONTHS_AHEAD, companies::company_default_currency};
+use super::{
+    AppState, IntegrityEntity, PLANNED_MONTHS_AHEAD,
+    balances::{invalidate_balance_snapshots, utc_day_start},
+    budgets::check_budget_alerts,
+    category_suggestions::{forget_category_usage, move_category_usage, record_category_usage},
+    comments::delete_comments_for,
+    companies::company_default_currency,
+    custom_fields::escape_regex,
+    deposit_allocations::deposit_accounts,
+    events::{CompanyEventKind, publish_event},
+    find_dependencies,
+    fiscal_calendar::{FiscalPeriod, company_fiscal_calendar, fiscal_period},
+    income_statement::invalidate_monthly_summary,
+    plan_versions::snapshot_recurring_plan,
+    portal::revoke_portal_access,
+    sequences::{SequenceKind, next_reference},
+    taxes::transaction_tax,
+    webhooks::notify_forecast_completed,
+};
 
 pub async fn list_accounts(state: &AppState) -> Result<Vec<Account>> {
     let mut cursor = state.accounts.find(doc! {}).await?;
@@ -55,6 +81,11 @@ pub async fn create_account(
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
+            opening_balance: 0.0,
+            opening_date: None,
+            opening_balance_changes: Vec::new(),
+            group_id: None,
+            position: 0,
         })
         .await?;
     res.inserted_id
@@ -88,6 +119,11 @@ pub async fn get_or_create_sat_account(
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes: Some("Cuenta automática para CFDIs importados".to_string()),
+            opening_balance: 0.0,
+            opening_date: None,
+            opening_balance_changes: Vec::new(),
+            group_id: None,
+            position: 0,
         })
         .await?;
     res.inserted_id
@@ -134,35 +170,30 @@ pub async fn delete_account(
     id: &ObjectId,
     company_id: &ObjectId,
 ) -> Result<()> {
-    // Integrity checks are scoped to the account's own company, matching the
-    // multi-tenant model: an account must only be blocked by records that live
-    // in the same tenant, never by another tenant's (or orphaned) data.
-    let has_transactions = state
-        .transactions
-        .find_one(doc! { "company_id": company_id, "$or": [
-            { "account_from_id": id },
-            { "account_to_id": id }
-        ]})
-        .await?
-        .is_some();
-    // Recurring plans are soft-deleted (is_active: false); a deleted plan must
-    // not keep blocking the account, so only active plans count as a reference.
-    let has_plans = state
-        .recurring_plans
-        .find_one(doc! { "company_id": company_id, "account_expected_id": id, "is_active": true })
-        .await?
-        .is_some();
-    let has_planned_entries = state
-        .planned_entries
-        .find_one(doc! { "company_id": company_id, "account_expected_id": id })
+    if !find_dependencies(state, IntegrityEntity::Account, id, company_id)
         .await?
-        .is_some();
-
-    if has_transactions || has_plans || has_planned_entries {
+        .is_empty()
+    {
         bail!("account has related records; deactivate instead of deleting");
     }
 
     state.accounts.delete_one(doc! { "_id": id }).await?;
+    state
+        .balance_snapshots
+        .delete_many(doc! { "account_id": id })
+        .await?;
+    state
+        .account_valuations
+        .delete_many(doc! { "account_id": id })
+        .await?;
+    state
+        .bank_connections
+        .delete_many(doc! { "account_id": id })
+        .await?;
+    state
+        .bank_transactions
+        .delete_many(doc! { "account_id": id })
+        .await?;
     Ok(())
 }
 
@@ -175,6 +206,45 @@ pub async fn list_categories(state: &AppState) -> Result<Vec<Category>> {
     Ok(items)
 }
 
+/// Company records whose name starts with `prefix`, ignoring case. The
+/// `{company_id, name}` index keeps the scan within the company.
+fn name_prefix_filter(company_id: &ObjectId, prefix: &str) -> Document {
+    let mut filter = doc! { "company_id": company_id };
+    let prefix = prefix.trim();
+    if !prefix.is_empty() {
+        filter.insert(
+            "name",
+            doc! { "$regex": format!("^{}", escape_regex(prefix)), "$options": "i" },
+        );
+    }
+    filter
+}
+
+/// Categories of the company whose name starts with `prefix`, by name,
+/// optionally only those of one flow type. An empty prefix returns the first
+/// `limit` categories.
+pub async fn search_categories(
+    state: &AppState,
+    company_id: &ObjectId,
+    prefix: &str,
+    flow_type: Option<&FlowType>,
+    limit: i64,
+) -> Result<Vec<Category>> {
+    let mut filter = name_prefix_filter(company_id, prefix);
+    if let Some(flow_type) = flow_type {
+        filter.insert("flow_type", flow_type.as_str());
+    }
+    state
+        .categories
+        .find(filter)
+        .sort(doc! { "company_id": 1, "name": 1 })
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
 pub async fn get_category_by_id(state: &AppState, id: &ObjectId) -> Result<Option<Category>> {
     state
         .categories
@@ -202,6 +272,9 @@ pub async fn create_category(
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
+            monthly_budget: None,
+            budget_alert: None,
+            tax_profile_id: None,
         })
         .await?;
     res.inserted_id
@@ -235,7 +308,18 @@ pub async fn update_category(
     Ok(())
 }
 
+/// Deletes the category unless movements, plans, orders, projects or
+/// subcategories of its company still use it.
 pub async fn delete_category(state: &AppState, id: &ObjectId) -> Result<()> {
+    let Some(category) = state.categories.find_one(doc! { "_id": id }).await? else {
+        return Ok(());
+    };
+    if !find_dependencies(state, IntegrityEntity::Category, id, &category.company_id)
+        .await?
+        .is_empty()
+    {
+        bail!("category has related records; reassign them before deleting");
+    }
     state.categories.delete_one(doc! { "_id": id }).await?;
     Ok(())
 }
@@ -249,6 +333,25 @@ pub async fn list_contacts(state: &AppState) -> Result<Vec<Contact>> {
     Ok(items)
 }
 
+/// Contacts of the company whose name starts with `prefix`, by name. An
+/// empty prefix returns the first `limit` contacts.
+pub async fn search_contacts(
+    state: &AppState,
+    company_id: &ObjectId,
+    prefix: &str,
+    limit: i64,
+) -> Result<Vec<Contact>> {
+    state
+        .contacts
+        .find(name_prefix_filter(company_id, prefix))
+        .sort(doc! { "company_id": 1, "name": 1 })
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
 pub async fn get_contact_by_id(state: &AppState, id: &ObjectId) -> Result<Option<Contact>> {
     state
         .contacts
@@ -277,9 +380,11 @@ pub async fn create_contact(
             rfc,
             email,
             phone,
+            payment_terms_days: None,
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
+            custom_fields: Document::new(),
         })
         .await?;
     res.inserted_id
@@ -346,8 +451,62 @@ pub async fn update_contact(
     Ok(())
 }
 
+/// Longest payment terms accepted on a contact, in days.
+pub const MAX_PAYMENT_TERMS_DAYS: i32 = 365;
+
+/// Sets or clears the payment terms of a contact.
+pub async fn set_contact_payment_terms(
+    state: &AppState,
+    id: &ObjectId,
+    payment_terms_days: Option<i32>,
+) -> Result<()> {
+    let update = match payment_terms_days {
+        Some(days) => doc! { "$set": { "payment_terms_days": days } },
+        None => doc! { "$unset": { "payment_terms_days": "" } },
+    };
+    state
+        .contacts
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    Ok(())
+}
+
+/// `issued` plus the payment terms, in whole days.
+pub fn due_date_from_terms(issued: DateTime, payment_terms_days: i32) -> DateTime {
+    DateTime::from_millis(
+        issued.timestamp_millis() + i64::from(payment_terms_days) * 24 * 60 * 60 * 1000,
+    )
+}
+
+/// Due date the payment terms of the contact give to something issued at
+/// `issued`; `None` when there is no contact or it has no terms.
+pub async fn contact_due_date(
+    state: &AppState,
+    contact_id: Option<&ObjectId>,
+    issued: DateTime,
+) -> Result<Option<DateTime>> {
+    let Some(contact_id) = contact_id else {
+        return Ok(None);
+    };
+    Ok(get_contact_by_id(state, contact_id)
+        .await?
+        .and_then(|contact| contact.payment_terms_days)
+        .map(|days| due_date_from_terms(issued, days)))
+}
+
+/// Deletes the contact unless records of its company still reference it.
 pub async fn delete_contact(state: &AppState, id: &ObjectId) -> Result<()> {
+    let Some(contact) = state.contacts.find_one(doc! { "_id": id }).await? else {
+        return Ok(());
+    };
+    if !find_dependencies(state, IntegrityEntity::Contact, id, &contact.company_id)
+        .await?
+        .is_empty()
+    {
+        bail!("contact has related records; reassign them before deleting");
+    }
     state.contacts.delete_one(doc! { "_id": id }).await?;
+    revoke_portal_access(state, id).await?;
     Ok(())
 }
 
@@ -360,6 +519,83 @@ pub async fn list_recurring_plans(state: &AppState) -> Result<Vec<RecurringPlan>
     Ok(items)
 }
 
+/// Index-level summary of the planned entries generated by one recurring plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringPlanCoverage {
+    /// Earliest open entry due from `now` on.
+    pub next_due_date: Option<DateTime>,
+    /// Entries neither covered nor cancelled, overdue ones included.
+    pub open_entries: i64,
+    /// Estimated amount of the open entries due within the next 12 months.
+    pub committed_next_12_months: f64,
+}
+
+/// Coverage of every recurring plan of the company with open entries, keyed by
+/// plan id. One aggregation for all plans; plans without open entries are
+/// absent.
+pub async fn recurring_plan_coverage(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: ChronoDateTime<Utc>,
+) -> Result<HashMap<ObjectId, RecurringPlanCoverage>> {
+    let from = DateTime::from_chrono(now);
+    let horizon = DateTime::from_chrono(
+        now.checked_add_months(Months::new(12))
+            .context("coverage horizon out of range")?,
+    );
+    let open_statuses = [
+        PlannedStatus::Planned.as_str(),
+        PlannedStatus::PartiallyCovered.as_str(),
+        PlannedStatus::Overdue.as_str(),
+    ];
+    let pipeline = vec![
+        doc! { "$match": {
+            "company_id": company_id,
+            "recurring_plan_id": { "$type": "objectId" },
+            "status": { "$in": open_statuses.to_vec() },
+        }},
+        doc! { "$group": {
+            "_id": "$recurring_plan_id",
+            "open_entries": { "$sum": 1 },
+            // $min skips the nulls left by entries already past due.
+            "next_due_date": { "$min": {
+                "$cond": [{ "$gte": ["$due_date", from] }, "$due_date", null]
+            }},
+            "committed": { "$sum": {
+                "$cond": [
+                    { "$and": [
+                        { "$gte": ["$due_date", from] },
+                        { "$lt": ["$due_date", horizon] },
+                    ]},
+                    "$amount_estimated",
+                    0.0,
+                ]
+            }},
+        }},
+    ];
+
+    let mut coverage = HashMap::new();
+    let mut cursor = state.planned_entries.aggregate(pipeline).await?;
+    while let Some(row) = cursor.try_next().await? {
+        let Ok(plan_id) = row.get_object_id("_id") else {
+            continue;
+        };
+        coverage.insert(
+            plan_id,
+            RecurringPlanCoverage {
+                next_due_date: row.get_datetime("next_due_date").ok().copied(),
+                open_entries: match row.get("open_entries") {
+                    Some(Bson::Int32(n)) => i64::from(*n),
+                    Some(Bson::Int64(n)) => *n,
+                    _ => 0,
+                },
+                committed_next_12_months: row.get_f64("committed").unwrap_or(0.0),
+            },
+        );
+    }
+    Ok(coverage)
+}
+
 pub async fn get_recurring_plan_by_id(
     state: &AppState,
     id: &ObjectId,
@@ -391,7 +627,7 @@ pub async fn create_recurring_plan(
     let version = 1;
     let now = DateTime::from_system_time(SystemTime::now());
 
-    let mut plan = RecurringPlan {
+    let plan = RecurringPlan {
         id: None,
         company_id: company_id.clone(),
         name: name.to_string(),
@@ -406,21 +642,41 @@ pub async fn create_recurring_plan(
         end_date,
         is_active,
         version,
+        scenario_weights: None,
+        probability: None,
+        skipped_due_dates: Vec::new(),
         created_at: Some(now),
         updated_at: None,
         notes,
     };
+    let (id, _) = insert_recurring_plan(state, plan, true).await?;
+    Ok(id)
+}
 
+/// Stores a new plan with its first version snapshot. With
+/// `generate_entries` its planned entries are added right away; otherwise
+/// the daily extension task adds them. Returns the plan id and how many
+/// entries were added.
+pub(super) async fn insert_recurring_plan(
+    state: &AppState,
+    mut plan: RecurringPlan,
+    generate_entries: bool,
+) -> Result<(ObjectId, u64)> {
     let res = state.recurring_plans.insert_one(plan.clone()).await?;
     let id = res
         .inserted_id
         .as_object_id()
         .context("recurring plan insert missing _id")?;
 
-    plan.id = Some(id.clone());
-    generate_planned_entries_for_plan(state, &plan, PLANNED_MONTHS_AHEAD).await?;
+    plan.id = Some(id);
+    snapshot_recurring_plan(state, &plan).await?;
+    let generated = if generate_entries {
+        generate_planned_entries_for_plan(state, &plan, PLANNED_MONTHS_AHEAD, Utc::now()).await?
+    } else {
+        0
+    };
 
-    Ok(id)
+    Ok((id, generated))
 }
 
 pub async fn update_recurring_plan(
@@ -447,28 +703,40 @@ pub async fn update_recurring_plan(
         .await?
         .context("recurring plan not found")?;
 
-    let mut new_version = existing.version;
-    let significant_change = existing.name != name
-        || existing.flow_type != flow_type
-        || existing.category_id != *category_id
-        || existing.account_expected_id != *account_expected_id
-        || existing.contact_id != contact_id
-        || (existing.amount_estimated - amount_estimated).abs() > f64::EPSILON
-        || existing.frequency != frequency
-        || existing.day_of_month != day_of_month
-        || existing.start_date != start_date
-        || existing.end_date != end_date
-        || existing.is_active != is_active;
+    let mut updated_plan = RecurringPlan {
+        id: Some(*id),
+        company_id: *company_id,
+        name: name.to_string(),
+        flow_type,
+        category_id: *category_id,
+        account_expected_id: *account_expected_id,
+        contact_id,
+        amount_estimated,
+        frequency: frequency.to_string(),
+        day_of_month,
+        start_date,
+        end_date,
+        is_active,
+        version: existing.version,
+        scenario_weights: existing.scenario_weights,
+        probability: existing.probability,
+        skipped_due_dates: existing.skipped_due_dates.clone(),
+        created_at: existing.created_at,
+        updated_at: Some(DateTime::from_system_time(SystemTime::now())),
+        notes,
+    };
 
+    let significant_change = plan_changed_significantly(&existing, &updated_plan);
     if significant_change {
-        new_version += 1;
+        // Plans created before versions were stored have no snapshot of the
+        // version being replaced yet.
+        snapshot_recurring_plan(state, &existing).await?;
+        updated_plan.version += 1;
     }
 
-    let final_end_date = if !is_active {
-        Some(DateTime::from_system_time(SystemTime::now()))
-    } else {
-        end_date
-    };
+    if !is_active {
+        updated_plan.end_date = Some(DateTime::from_system_time(SystemTime::now()));
+    }
 
     state
         .recurring_plans
@@ -477,7 +745,7 @@ pub async fn update_recurring_plan(
             doc! { "$set": {
                 "company_id": company_id,
                 "name": name,
-                "flow_type": flow_type.as_str(),
+                "flow_type": updated_plan.flow_type.as_str(),
                 "category_id": category_id,
                 "account_expected_id": account_expected_id,
                 "contact_id": contact_id,
@@ -485,34 +753,18 @@ pub async fn update_recurring_plan(
                 "frequency": frequency,
                 "day_of_month": day_of_month,
                 "start_date": start_date,
-                "end_date": final_end_date,
+                "end_date": updated_plan.end_date,
                 "is_active": is_active,
-                "version": new_version,
-                "notes": notes.clone(),
-                "updated_at": DateTime::from_system_time(SystemTime::now()),
+                "version": updated_plan.version,
+                "notes": updated_plan.notes.clone(),
+                "updated_at": updated_plan.updated_at,
             } },
         )
         .await?;
 
-    let updated_plan = RecurringPlan {
-        id: Some(id.clone()),
-        company_id: company_id.clone(),
-        name: name.to_string(),
-        flow_type,
-        category_id: category_id.clone(),
-        account_expected_id: account_expected_id.clone(),
-        contact_id,
-        amount_estimated,
-        frequency: frequency.to_string(),
-        day_of_month,
-        start_date,
-        end_date: final_end_date,
-        is_active,
-        version: new_version,
-        created_at: existing.created_at,
-        updated_at: Some(DateTime::from_system_time(SystemTime::now())),
-        notes,
-    };
+    if significant_change {
+        snapshot_recurring_plan(state, &updated_plan).await?;
+    }
 
     if is_active {
         regenerate_planned_entries(state, &updated_plan).await?;
@@ -523,6 +775,106 @@ pub async fn update_recurring_plan(
     Ok(())
 }
 
+/// Whether saving `updated` over `existing` changes what the plan generates,
+/// which bumps its version. Notes alone do not.
+pub fn plan_changed_significantly(existing: &RecurringPlan, updated: &RecurringPlan) -> bool {
+    existing.name != updated.name
+        || existing.flow_type != updated.flow_type
+        || existing.category_id != updated.category_id
+        || existing.account_expected_id != updated.account_expected_id
+        || existing.contact_id != updated.contact_id
+        || (existing.amount_estimated - updated.amount_estimated).abs() > f64::EPSILON
+        || existing.frequency != updated.frequency
+        || existing.day_of_month != updated.day_of_month
+        || existing.start_date != updated.start_date
+        || existing.end_date != updated.end_date
+        || existing.is_active != updated.is_active
+}
+
+/// Effect of saving a plan on its planned entries.
+#[derive(Debug, Clone, Default)]
+pub struct PlanRegenerationPreview {
+    /// Open future entries that are deleted.
+    pub removed: Vec<PlannedEntry>,
+    /// Open future entries edited by hand, which are kept as they are.
+    pub kept: Vec<PlannedEntry>,
+    /// Due dates of the entries generated afterwards.
+    pub created: Vec<DateTime>,
+}
+
+impl PlanRegenerationPreview {
+    /// True when saving leaves the entries untouched.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.created.is_empty()
+    }
+}
+
+/// What `update_recurring_plan` would do to the entries of `existing` when
+/// saved as `updated`, without writing anything.
+pub async fn preview_recurring_plan_update(
+    state: &AppState,
+    existing: &RecurringPlan,
+    updated: &RecurringPlan,
+) -> Result<PlanRegenerationPreview> {
+    let plan_id = existing.id.as_ref().context("recurring plan missing _id")?;
+    let now = Utc::now();
+    let open: Vec<PlannedEntry> = state
+        .planned_entries
+        .find(doc! {
+            "recurring_plan_id": plan_id,
+            "status": { "$in": [PlannedStatus::Planned.as_str(), PlannedStatus::PartiallyCovered.as_str()] },
+            "due_date": { "$gte": DateTime::from_chrono(now) },
+        })
+        .sort(doc! { "due_date": 1 })
+        .await?
+        .try_collect()
+        .await?;
+    let (kept, removed): (Vec<_>, Vec<_>) = open.into_iter().partition(|e| e.is_customized);
+    let mut preview = PlanRegenerationPreview {
+        removed,
+        kept,
+        created: Vec::new(),
+    };
+    if !updated.is_active {
+        return Ok(preview);
+    }
+
+    let mut plan = updated.clone();
+    plan.version = existing.version + i32::from(plan_changed_significantly(existing, updated));
+    let until = now
+        .checked_add_months(Months::new(PLANNED_MONTHS_AHEAD))
+        .context("planning horizon out of range")?;
+    let due_dates = upcoming_due_dates(&plan, until, now);
+    let Some(first) = due_dates.first() else {
+        return Ok(preview);
+    };
+    let removed_ids: Vec<ObjectId> = preview.removed.iter().filter_map(|e| e.id).collect();
+    let remaining: Vec<PlannedEntry> = state
+        .planned_entries
+        .find(doc! {
+            "recurring_plan_id": plan_id,
+            "_id": { "$nin": removed_ids },
+            "due_date": { "$gte": utc_day_start(*first) },
+        })
+        .await?
+        .try_collect()
+        .await?;
+    preview.created = due_dates
+        .into_iter()
+        .filter(|due| {
+            let day = utc_day_start(*due);
+            !existing.skipped_due_dates.contains(&day)
+                && !remaining.iter().any(|e| {
+                    e.original_due_date.map(utc_day_start) == Some(day)
+                        || (utc_day_start(e.due_date) == day
+                            && (e.is_customized
+                                || e.recurring_plan_version == Some(plan.version)))
+                })
+        })
+        .collect();
+    Ok(preview)
+}
+
 pub async fn delete_recurring_plan(state: &AppState, id: &ObjectId) -> Result<()> {
     let now = DateTime::from_system_time(SystemTime::now());
     state
@@ -595,13 +947,20 @@ pub async fn create_planned_entry(
             original_amount_estimated: None,
             due_date,
             original_due_date: None,
+            generated_due_date: None,
             status: PlannedStatus::Planned,
+            is_customized: false,
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
             cfdi_uuid: None,
             currency: None,
             cfdi_folio: None,
+            coverage_adjustment: None,
+            slip_count: 0,
+            amount_revisions: Vec::new(),
+            exchange_rate: None,
+            deposit_allocations: Vec::new(),
         })
         .await?;
     res.inserted_id
@@ -609,6 +968,9 @@ pub async fn create_planned_entry(
         .context("planned entry insert missing _id")
 }
 
+/// Saves a hand edit of the planned entry. A new `amount_estimated` is
+/// logged on the entry as an [`AmountRevision`] by `username`, so the figure
+/// the plan generated is not lost.
 pub async fn update_planned_entry(
     state: &AppState,
     id: &ObjectId,
@@ -624,101 +986,550 @@ pub async fn update_planned_entry(
     due_date: DateTime,
     status: PlannedStatus,
     notes: Option<String>,
+    user_id: Option<ObjectId>,
+    username: &str,
 ) -> Result<()> {
+    let existing = get_planned_entry_by_id(state, id)
+        .await?
+        .context("planned entry not found")?;
+    let now = DateTime::from_system_time(SystemTime::now());
+    let mut update = doc! { "$set": {
+        "company_id": company_id,
+        "recurring_plan_id": recurring_plan_id,
+        "recurring_plan_version": recurring_plan_version,
+        "name": name,
+        "flow_type": flow_type.as_str(),
+        "category_id": category_id,
+        "account_expected_id": account_expected_id,
+        "contact_id": contact_id,
+        "amount_estimated": amount_estimated,
+        "due_date": due_date,
+        "status": status.as_str(),
+        "notes": notes,
+        "is_customized": true,
+        "updated_at": now,
+    } };
+    if (existing.amount_estimated - amount_estimated).abs() >= 0.005 {
+        let revision = AmountRevision {
+            changed_at: now,
+            user_id,
+            username: username.to_string(),
+            previous_amount: existing.amount_estimated,
+            amount: amount_estimated,
+        };
+        update.insert(
+            "$push",
+            doc! { "amount_revisions": mongodb::bson::to_bson(&revision)? },
+        );
+    }
+    // The first move keeps the generated day, so the plan does not add it
+    // back (see `generate_planned_entries_for_plan`).
+    if existing.due_date != due_date && existing.original_due_date.is_none() {
+        update
+            .get_document_mut("$set")?
+            .insert("original_due_date", existing.due_date);
+    }
     state
         .planned_entries
-        .update_one(
-            doc! { "_id": id },
-            doc! { "$set": {
-                "company_id": company_id,
-                "recurring_plan_id": recurring_plan_id,
-                "recurring_plan_version": recurring_plan_version,
-                "name": name,
-                "flow_type": flow_type.as_str(),
-                "category_id": category_id,
-                "account_expected_id": account_expected_id,
-                "contact_id": contact_id,
-                "amount_estimated": amount_estimated,
-                "due_date": due_date,
-                "status": status.as_str(),
-                "notes": notes,
-                "updated_at": DateTime::from_system_time(SystemTime::now()),
-            } },
-        )
+        .update_one(doc! { "_id": id }, update)
         .await?;
     let _ = recalculate_planned_entry_status(state, id).await;
     Ok(())
 }
 
-pub async fn update_planned_entry_project_links(
+/// Whether a planned entry may be moved to `target` by hand, given the
+/// confirmed payments that cover it. Cancelling is allowed unless the entry is
+/// already covered; any other status has to agree with the payments, since it
+/// is recalculated from them. Planned and overdue only differ by the due date,
+/// so either one reopens an entry without payments.
+pub fn check_planned_status_change(
+    entry: &PlannedEntry,
+    covered_amount: f64,
+    target: &PlannedStatus,
+) -> Result<(), String> {
+    if &entry.status == target {
+        return Ok(());
+    }
+    match target {
+        PlannedStatus::Cancelled if entry.status.is_covered() => {
+            Err("El compromiso ya está cubierto por sus pagos; no se puede cancelar".into())
+        }
+        PlannedStatus::Cancelled => Ok(()),
+        PlannedStatus::Planned | PlannedStatus::Overdue if covered_amount > 0.0 => {
+            Err("El compromiso tiene pagos registrados; márcalo como parcial o cubierto".into())
+        }
+        PlannedStatus::PartiallyCovered
+            if covered_amount <= 0.0 || covered_amount >= entry.amount_estimated =>
+        {
+            Err("Los pagos registrados no cubren el compromiso parcialmente".into())
+        }
+        PlannedStatus::Covered if covered_amount < entry.amount_estimated => {
+            Err("Los pagos registrados no cubren el monto del compromiso".into())
+        }
+        PlannedStatus::OverCovered if coverage_excess(entry, covered_amount) <= 0.0 => {
+            Err("Los pagos registrados no exceden el monto del compromiso".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Stores a status chosen by hand (see [`check_planned_status_change`]) and
+/// marks the entry as edited, so plan regeneration leaves it alone. A
+/// reopened entry is then recalculated, ending up planned, overdue or
+/// (partially) covered as its payments and due date say.
+pub async fn set_planned_entry_status(
     state: &AppState,
     id: &ObjectId,
     company_id: &ObjectId,
-    project_id: Option<ObjectId>,
-    parent_planned_entry_id: Option<ObjectId>,
+    status: PlannedStatus,
 ) -> Result<()> {
     state
         .planned_entries
         .update_one(
             doc! { "_id": id, "company_id": company_id },
             doc! { "$set": {
-                "project_id": project_id,
-                "parent_planned_entry_id": parent_planned_entry_id,
+                "status": status.as_str(),
+                "is_customized": true,
                 "updated_at": DateTime::from_system_time(SystemTime::now()),
-            }},
+            } },
         )
         .await?;
-    Ok(())
+    recalculate_planned_entry_status(state, id).await
 }
 
-pub async fn delete_planned_entry(state: &AppState, id: &ObjectId) -> Result<()> {
-    state.planned_entries.delete_one(doc! { "_id": id }).await?;
-    Ok(())
+/// Most days an entry can be rolled forward by hand.
+pub const ROLL_FORWARD_MAX_DAYS: u32 = 366;
+
+/// Due date an overdue entry is rolled forward to: `days` days from today,
+/// or else the first due date of the recurring plan that generated it from
+/// today on. Only open entries due before today can be rolled forward, and
+/// entries without a plan need `days`.
+pub fn roll_forward_due_date(
+    entry: &PlannedEntry,
+    plan: Option<&RecurringPlan>,
+    days: Option<u32>,
+    now: ChronoDateTime<Utc>,
+) -> Result<DateTime, String> {
+    if !matches!(
+        entry.status,
+        PlannedStatus::Planned | PlannedStatus::PartiallyCovered | PlannedStatus::Overdue
+    ) {
+        return Err(format!(
+            "«{}» ya está cubierto o cancelado; no se puede posponer",
+            entry.name
+        ));
+    }
+    let today = utc_day_start(DateTime::from_chrono(now)).to_chrono();
+    if entry.due_date.to_chrono() >= today {
+        return Err(format!("«{}» todavía no vence", entry.name));
+    }
+    if let Some(days) = days {
+        if days == 0 || days > ROLL_FORWARD_MAX_DAYS {
+            return Err(format!(
+                "Los días deben estar entre 1 y {ROLL_FORWARD_MAX_DAYS}"
+            ));
+        }
+        return Ok(DateTime::from_chrono(
+            today + chrono::Duration::days(i64::from(days)),
+        ));
+    }
+    let Some(plan) = plan else {
+        return Err(format!(
+            "«{}» no viene de un plan recurrente; indica los días",
+            entry.name
+        ));
+    };
+    if !plan.is_active {
+        return Err(format!(
+            "El plan de «{}» está inactivo; indica los días",
+            entry.name
+        ));
+    }
+    let until = now.checked_add_months(Months::new(24)).unwrap_or(now);
+    upcoming_due_dates(plan, until, now)
+        .into_iter()
+        .find(|due| due.to_chrono() >= today)
+        .ok_or_else(|| {
+            format!(
+                "El plan de «{}» ya no tiene fechas siguientes; indica los días",
+                entry.name
+            )
+        })
 }
 
-pub async fn get_planned_entry_by_cfdi_uuid(
+/// Moves an overdue entry to `due_date` (see [`roll_forward_due_date`]) and
+/// counts the slip, for the reliability of its contact. The entry is marked
+/// as edited and keeps its first due date in `original_due_date`, so plan
+/// regeneration neither touches it nor fills the day it left. Open entries of
+/// the same plan already due that day are merged into it (see
+/// [`merge_plan_entries_into`]). Its status is recalculated. Returns the
+/// slips counted so far.
+pub async fn roll_forward_planned_entry(
     state: &AppState,
+    id: &ObjectId,
     company_id: &ObjectId,
-    cfdi_uuid: &str,
-) -> Result<Option<PlannedEntry>> {
-    state
+    due_date: DateTime,
+) -> Result<i32> {
+    let existing = state
         .planned_entries
-        .find_one(doc! { "company_id": company_id, "cfdi_uuid": cfdi_uuid })
-        .await
-        .map_err(Into::into)
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("planned entry not found")?;
+    let mut set_doc = doc! {
+        "due_date": due_date,
+        "is_customized": true,
+        "updated_at": DateTime::from_system_time(SystemTime::now()),
+    };
+    if existing.original_due_date.is_none() {
+        set_doc.insert("original_due_date", existing.due_date);
+    }
+    let entry = state
+        .planned_entries
+        .find_one_and_update(
+            doc! { "_id": id, "company_id": company_id },
+            doc! { "$set": set_doc, "$inc": { "slip_count": 1 } },
+        )
+        .return_document(ReturnDocument::After)
+        .await?
+        .context("planned entry not found")?;
+    if let Some(plan_id) = entry.recurring_plan_id {
+        merge_plan_entries_into(state, &entry, &plan_id).await?;
+    }
+    recalculate_planned_entry_status(state, id).await?;
+    Ok(entry.slip_count)
 }
 
-pub async fn create_or_update_planned_entry_from_cfdi(
+/// Folds the other open entries of `plan_id` due the same day as `entry`
+/// into it: their amounts are added to it, their payments move to it and
+/// they are deleted. Deleting records the day on the plan, so it is not
+/// generated again either.
+async fn merge_plan_entries_into(
     state: &AppState,
-    company_id: &ObjectId,
-    due_date: DateTime,
-    name: &str,
-    flow_type: FlowType,
-    category_id: &ObjectId,
-    account_expected_id: &ObjectId,
-    contact_id: Option<ObjectId>,
-    amount_estimated: f64,
-    cfdi_uuid: &str,
-    currency: Option<String>,
-    cfdi_folio: Option<String>,
-    notes: Option<String>,
-) -> Result<(ObjectId, bool)> {
-    if let Some(existing) = get_planned_entry_by_cfdi_uuid(state, company_id, cfdi_uuid).await? {
-        let id = existing.id.context("planned entry missing _id")?;
+    entry: &PlannedEntry,
+    plan_id: &ObjectId,
+) -> Result<()> {
+    let id = entry.id.context("planned entry missing _id")?;
+    let day_start = utc_day_start(entry.due_date);
+    let day_end = DateTime::from_millis(day_start.timestamp_millis() + 24 * 60 * 60 * 1000);
+    let same_day: Vec<PlannedEntry> = state
+        .planned_entries
+        .find(doc! {
+            "_id": { "$ne": id },
+            "company_id": entry.company_id,
+            "recurring_plan_id": plan_id,
+            "status": { "$in": [
+                PlannedStatus::Planned.as_str(),
+                PlannedStatus::PartiallyCovered.as_str(),
+                PlannedStatus::Overdue.as_str(),
+            ] },
+            "due_date": { "$gte": day_start, "$lt": day_end },
+        })
+        .await?
+        .try_collect()
+        .await?;
+    for other in same_day {
+        let Some(other_id) = other.id else {
+            continue;
+        };
+        state
+            .transactions
+            .update_many(
+                doc! { "planned_entry_id": other_id },
+                doc! { "$set": { "planned_entry_id": id } },
+            )
+            .await?;
         state
             .planned_entries
             .update_one(
-                doc! { "_id": &id, "company_id": company_id },
-                doc! { "$set": {
-                    "due_date": due_date,
-                    "name": name,
-                    "flow_type": flow_type.as_str(),
-                    "category_id": category_id,
-                    "account_expected_id": account_expected_id,
-                    "contact_id": contact_id,
-                    "amount_estimated": amount_estimated,
-                    "currency": currency,
-                    "cfdi_folio": cfdi_folio,
+                doc! { "_id": id },
+                doc! { "$inc": { "amount_estimated": other.amount_estimated } },
+            )
+            .await?;
+        delete_planned_entry(state, &other_id).await?;
+    }
+    Ok(())
+}
+
+/// Sum of the confirmed transactions linked to the planned entry.
+pub async fn planned_entry_covered_amount(
+    state: &AppState,
+    planned_entry_id: &ObjectId,
+) -> Result<f64> {
+    let mut total = 0_f64;
+    // Drafts do not cover anything until they are confirmed. Documents
+    // written before the flag existed count as confirmed.
+    let mut cursor = state
+        .transactions
+        .find(doc! { "planned_entry_id": planned_entry_id, "is_confirmed": { "$ne": false } })
+        .await?;
+    while let Some(tx) = cursor.try_next().await? {
+        total += tx.amount;
+    }
+    Ok(total)
+}
+
+/// Part of `covered_amount` above the amount of the entry that no adjustment
+/// entry accounts for yet. Rounding cents are not an excess.
+pub fn coverage_excess(entry: &PlannedEntry, covered_amount: f64) -> f64 {
+    let adjusted = entry
+        .coverage_adjustment
+        .as_ref()
+        .map_or(0.0, |adjustment| adjustment.amount);
+    let excess = covered_amount - entry.amount_estimated - adjusted;
+    if excess < 0.005 { 0.0 } else { excess }
+}
+
+/// Every transaction linked to the planned entry, drafts included, oldest
+/// first.
+pub async fn planned_entry_transactions(
+    state: &AppState,
+    planned_entry_id: &ObjectId,
+) -> Result<Vec<Transaction>> {
+    state
+        .transactions
+        .find(doc! { "planned_entry_id": planned_entry_id })
+        .sort(doc! { "date": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Unlinks a transaction from its planned entry, which is recalculated.
+/// Returns the entry it covered, if any.
+pub async fn detach_transaction_from_planned_entry(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+) -> Result<Option<ObjectId>> {
+    let tx = state
+        .transactions
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("transaction not found")?;
+    let Some(planned_entry_id) = tx.planned_entry_id else {
+        return Ok(None);
+    };
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! {
+                "$unset": { "planned_entry_id": "" },
+                "$set": { "updated_at": DateTime::from_system_time(SystemTime::now()) },
+            },
+        )
+        .await?;
+    recalculate_planned_entry_status(state, &planned_entry_id).await?;
+    Ok(Some(planned_entry_id))
+}
+
+/// Moves the excess of an over-covered planned entry to an entry of the
+/// opposite flow, due now: a credit owed to the customer when an income was
+/// overpaid, a refund owed by the supplier when an expense was. A later
+/// excess is added to the same adjustment entry while it exists. The original
+/// entry then counts as covered. Returns the adjustment entry.
+pub async fn create_coverage_adjustment(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+) -> Result<ObjectId> {
+    let pe = state
+        .planned_entries
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("planned entry not found")?;
+    let covered = planned_entry_covered_amount(state, id).await?;
+    let excess = coverage_excess(&pe, covered);
+    if excess <= 0.0 {
+        bail!("planned entry is not over-covered");
+    }
+
+    let previous = pe.coverage_adjustment.clone();
+    let existing = match previous.as_ref() {
+        Some(adjustment) => {
+            state
+                .planned_entries
+                .find_one(doc! { "_id": adjustment.entry_id, "company_id": company_id })
+                .await?
+        }
+        None => None,
+    };
+    let (entry_id, amount) = match (existing, previous) {
+        (Some(existing), Some(previous)) => {
+            state
+                .planned_entries
+                .update_one(
+                    doc! { "_id": previous.entry_id },
+                    doc! { "$set": {
+                        "amount_estimated": existing.amount_estimated + excess,
+                        "updated_at": DateTime::from_system_time(SystemTime::now()),
+                    } },
+                )
+                .await?;
+            recalculate_planned_entry_status(state, &previous.entry_id).await?;
+            (previous.entry_id, previous.amount + excess)
+        }
+        _ => {
+            let (flow_type, category, name) = match pe.flow_type {
+                FlowType::Income => (
+                    FlowType::Expense,
+                    "Saldos a favor de clientes",
+                    format!("Saldo a favor: {}", pe.name),
+                ),
+                FlowType::Expense => (
+                    FlowType::Income,
+                    "Reembolsos de proveedores",
+                    format!("Reembolso pendiente: {}", pe.name),
+                ),
+            };
+            let category_id =
+                get_or_create_category(state, company_id, category, flow_type.clone()).await?;
+            let entry_id = create_planned_entry(
+                state,
+                company_id,
+                None,
+                None,
+                None,
+                &name,
+                flow_type,
+                &category_id,
+                &pe.account_expected_id,
+                pe.contact_id,
+                excess,
+                DateTime::from_system_time(SystemTime::now()),
+                PlannedStatus::Planned,
+                Some(format!("Excedente de los pagos de \"{}\"", pe.name)),
+            )
+            .await?;
+            (entry_id, excess)
+        }
+    };
+
+    let adjustment = mongodb::bson::to_bson(&CoverageAdjustment { entry_id, amount })?;
+    state
+        .planned_entries
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "coverage_adjustment": adjustment,
+                "updated_at": DateTime::from_system_time(SystemTime::now()),
+            } },
+        )
+        .await?;
+    recalculate_planned_entry_status(state, id).await?;
+    Ok(entry_id)
+}
+
+pub async fn update_planned_entry_project_links(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    project_id: Option<ObjectId>,
+    parent_planned_entry_id: Option<ObjectId>,
+) -> Result<()> {
+    state
+        .planned_entries
+        .update_one(
+            doc! { "_id": id, "company_id": company_id },
+            doc! { "$set": {
+                "project_id": project_id,
+                "parent_planned_entry_id": parent_planned_entry_id,
+                "updated_at": DateTime::from_system_time(SystemTime::now()),
+            }},
+        )
+        .await?;
+    Ok(())
+}
+
+/// Deletes the planned entry. When it came from a recurring plan, its due
+/// day is recorded on the plan so the daily generation does not add it back.
+pub async fn delete_planned_entry(state: &AppState, id: &ObjectId) -> Result<()> {
+    let deleted = state
+        .planned_entries
+        .find_one_and_delete(doc! { "_id": id })
+        .await?;
+    if let Some((plan_id, entry)) = deleted
+        .as_ref()
+        .and_then(|entry| entry.recurring_plan_id.map(|plan_id| (plan_id, entry)))
+    {
+        let day = utc_day_start(entry.original_due_date.unwrap_or(entry.due_date));
+        state
+            .recurring_plans
+            .update_one(
+                doc! { "_id": plan_id },
+                doc! { "$addToSet": { "skipped_due_dates": day } },
+            )
+            .await?;
+    }
+    delete_comments_for(state, CommentEntity::PlannedEntry, id).await?;
+
+    // Entries whose excess went to this one are over-covered again.
+    let adjusted: Vec<PlannedEntry> = state
+        .planned_entries
+        .find(doc! { "coverage_adjustment.entry_id": id })
+        .await?
+        .try_collect()
+        .await?;
+    for entry in adjusted {
+        let Some(entry_id) = entry.id else {
+            continue;
+        };
+        state
+            .planned_entries
+            .update_one(
+                doc! { "_id": entry_id },
+                doc! { "$unset": { "coverage_adjustment": "" } },
+            )
+            .await?;
+        let _ = recalculate_planned_entry_status(state, &entry_id).await;
+    }
+    Ok(())
+}
+
+pub async fn get_planned_entry_by_cfdi_uuid(
+    state: &AppState,
+    company_id: &ObjectId,
+    cfdi_uuid: &str,
+) -> Result<Option<PlannedEntry>> {
+    state
+        .planned_entries
+        .find_one(doc! { "company_id": company_id, "cfdi_uuid": cfdi_uuid })
+        .await
+        .map_err(Into::into)
+}
+
+pub async fn create_or_update_planned_entry_from_cfdi(
+    state: &AppState,
+    company_id: &ObjectId,
+    due_date: DateTime,
+    name: &str,
+    flow_type: FlowType,
+    category_id: &ObjectId,
+    account_expected_id: &ObjectId,
+    contact_id: Option<ObjectId>,
+    amount_estimated: f64,
+    cfdi_uuid: &str,
+    currency: Option<String>,
+    cfdi_folio: Option<String>,
+    notes: Option<String>,
+) -> Result<(ObjectId, bool)> {
+    if let Some(existing) = get_planned_entry_by_cfdi_uuid(state, company_id, cfdi_uuid).await? {
+        let id = existing.id.context("planned entry missing _id")?;
+        state
+            .planned_entries
+            .update_one(
+                doc! { "_id": &id, "company_id": company_id },
+                doc! { "$set": {
+                    "due_date": due_date,
+                    "name": name,
+                    "flow_type": flow_type.as_str(),
+                    "category_id": category_id,
+                    "account_expected_id": account_expected_id,
+                    "contact_id": contact_id,
+                    "amount_estimated": amount_estimated,
+                    "currency": currency,
+                    "cfdi_folio": cfdi_folio,
                     "notes": notes,
                     "updated_at": DateTime::from_system_time(SystemTime::now()),
                 }},
@@ -747,13 +1558,20 @@ pub async fn create_or_update_planned_entry_from_cfdi(
             original_amount_estimated: None,
             due_date,
             original_due_date: None,
+            generated_due_date: None,
             status: PlannedStatus::Planned,
+            is_customized: false,
             created_at: Some(DateTime::from_system_time(SystemTime::now())),
             updated_at: None,
             notes,
             cfdi_uuid: Some(cfdi_uuid.to_string()),
             currency,
             cfdi_folio,
+            coverage_adjustment: None,
+            slip_count: 0,
+            amount_revisions: Vec::new(),
+            exchange_rate: None,
+            deposit_allocations: Vec::new(),
         })
         .await?;
     let id = res
@@ -905,6 +1723,55 @@ pub async fn get_transaction_by_id(state: &AppState, id: &ObjectId) -> Result<Op
         .map_err(Into::into)
 }
 
+/// The transaction of the company an integrator knows by `external_id`.
+pub async fn find_transaction_by_external_id(
+    state: &AppState,
+    company_id: &ObjectId,
+    external_id: &str,
+) -> Result<Option<Transaction>> {
+    state
+        .transactions
+        .find_one(doc! { "company_id": company_id, "external_id": external_id })
+        .await
+        .map_err(Into::into)
+}
+
+/// Records the external id and the bank reference of a transaction; the ones
+/// given as `None` are left as they are. Fails when another transaction of
+/// the company already has the external id.
+pub async fn set_transaction_external_refs(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    external_id: Option<&str>,
+    bank_reference: Option<&str>,
+) -> Result<()> {
+    let mut set = doc! {};
+    if let Some(external_id) = external_id {
+        if let Some(other) = find_transaction_by_external_id(state, company_id, external_id).await?
+            && other.id.as_ref() != Some(id)
+        {
+            bail!("external_id is already used by another transaction");
+        }
+        set.insert("external_id", external_id);
+    }
+    if let Some(bank_reference) = bank_reference {
+        set.insert("bank_reference", bank_reference);
+    }
+    if set.is_empty() {
+        return Ok(());
+    }
+    // The unique index still catches two writes racing past the check.
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id, "company_id": company_id },
+            doc! { "$set": set },
+        )
+        .await?;
+    Ok(())
+}
+
 pub async fn create_transaction(
     state: &AppState,
     company_id: &ObjectId,
@@ -937,39 +1804,75 @@ pub async fn create_transaction(
     if let Some(project_id) = project_id.as_ref() {
         ensure_project_in_company(state, project_id, company_id).await?;
     }
+    let tax = transaction_tax(
+        state,
+        company_id,
+        &transaction_type,
+        category_id,
+        amount,
+        None,
+    )
+    .await?;
 
-    let res = state
-        .transactions
-        .insert_one(Transaction {
-            id: None,
-            company_id: company_id.clone(),
-            date,
-            description: description.to_string(),
-            transaction_type: transaction_type.clone(),
-            category_id: category_id.clone(),
-            account_from_id,
-            account_to_id,
-            amount,
-            planned_entry_id,
-            project_id,
-            is_confirmed,
-            created_at: Some(DateTime::from_system_time(SystemTime::now())),
-            updated_at: None,
-            contact_id,
-            cfdi_uuid,
-            currency,
-            cfdi_folio,
-            notes,
-        })
-        .await?;
+    let transaction = Transaction {
+        id: None,
+        company_id: *company_id,
+        date,
+        description: description.to_string(),
+        transaction_type: transaction_type.clone(),
+        category_id: *category_id,
+        account_from_id,
+        account_to_id,
+        amount,
+        planned_entry_id,
+        project_id,
+        is_confirmed,
+        created_at: Some(DateTime::from_system_time(SystemTime::now())),
+        updated_at: None,
+        contact_id,
+        cfdi_uuid,
+        currency,
+        cfdi_folio,
+        reference: Some(next_reference(state, company_id, SequenceKind::Transaction, date).await?),
+        external_id: None,
+        bank_reference: None,
+        notes,
+        tags: Vec::new(),
+        custom_fields: Document::new(),
+        tax,
+        refund_of: None,
+        exchange_rate: None,
+        fx_adjustment: None,
+        hold: None,
+    };
+    let res = state.transactions.insert_one(&transaction).await?;
+    invalidate_balance_snapshots(state, &transaction).await?;
+    invalidate_monthly_summary(state, &transaction).await?;
+    record_category_usage(state, &transaction).await?;
 
     if let Some(pe_id) = planned_entry_id {
         let _ = recalculate_planned_entry_status(state, &pe_id).await;
     }
 
-    res.inserted_id
+    let id = res
+        .inserted_id
         .as_object_id()
-        .context("transaction insert missing _id")
+        .context("transaction insert missing _id")?;
+    publish_event(
+        state,
+        company_id,
+        CompanyEventKind::TransactionCreated,
+        id,
+        transaction
+            .account_from_id
+            .into_iter()
+            .chain(transaction.account_to_id)
+            .collect(),
+    );
+    if let Err(err) = check_budget_alerts(state, company_id, DateTime::now()).await {
+        eprintln!("budget alerts: check failed: {err:?}");
+    }
+    Ok(id)
 }
 
 pub async fn create_transaction_from_cfdi(
@@ -984,6 +1887,15 @@ pub async fn create_transaction_from_cfdi(
     cfdi_uuid: Option<String>,
     contact_id: Option<ObjectId>,
 ) -> Result<ObjectId> {
+    let tax = transaction_tax(
+        state,
+        company_id,
+        &transaction_type,
+        category_id,
+        amount,
+        None,
+    )
+    .await?;
     let res = state
         .transactions
         .insert_one(Transaction {
@@ -1005,7 +1917,19 @@ pub async fn create_transaction_from_cfdi(
             cfdi_uuid,
             currency: None,
             cfdi_folio: None,
+            reference: Some(
+                next_reference(state, company_id, SequenceKind::Transaction, date).await?,
+            ),
+            external_id: None,
+            bank_reference: None,
             notes,
+            tags: Vec::new(),
+            custom_fields: Document::new(),
+            tax,
+            refund_of: None,
+            exchange_rate: None,
+            fx_adjustment: None,
+            hold: None,
         })
         .await?;
 
@@ -1034,6 +1958,12 @@ pub async fn update_transaction(
         .find_one(doc! { "_id": id })
         .await?
         .context("transaction not found")?;
+    // A card hold is confirmed only by capturing it.
+    let is_confirmed = is_confirmed
+        && existing
+            .hold
+            .as_ref()
+            .is_none_or(|hold| hold.status == HoldStatus::Captured);
 
     validate_transaction_links(
         state,
@@ -1045,6 +1975,16 @@ pub async fn update_transaction(
         planned_entry_id.as_ref(),
     )
     .await?;
+    // A rate typed on the transaction is kept; otherwise the category decides.
+    let tax = transaction_tax(
+        state,
+        company_id,
+        &transaction_type,
+        category_id,
+        amount,
+        existing.tax.as_ref(),
+    )
+    .await?;
 
     state
         .transactions
@@ -1062,51 +2002,265 @@ pub async fn update_transaction(
                 "planned_entry_id": planned_entry_id,
                 "is_confirmed": is_confirmed,
                 "notes": notes,
+                "tax": mongodb::bson::to_bson(&tax)?,
+                "updated_at": DateTime::from_system_time(SystemTime::now()),
+            } },
+        )
+        .await?;
+
+    let updated = Transaction {
+        date,
+        transaction_type: transaction_type.clone(),
+        category_id: *category_id,
+        account_from_id,
+        account_to_id,
+        is_confirmed,
+        ..existing.clone()
+    };
+    invalidate_balance_snapshots(state, &existing).await?;
+    invalidate_monthly_summary(state, &existing).await?;
+    invalidate_balance_snapshots(state, &updated).await?;
+    invalidate_monthly_summary(state, &updated).await?;
+    move_category_usage(state, &existing, &updated).await?;
+
+    if existing.planned_entry_id != planned_entry_id {
+        if let Some(old) = existing.planned_entry_id {
+            let _ = recalculate_planned_entry_status(state, &old).await;
+        }
+    }
+    if let Some(new_pe) = planned_entry_id {
+        let _ = recalculate_planned_entry_status(state, &new_pe).await;
+    }
+
+    Ok(())
+}
+
+/// Find or create a category named `name` with the given flow_type for a company.
+pub async fn get_or_create_category(
+    state: &AppState,
+    company_id: &ObjectId,
+    name: &str,
+    flow_type: FlowType,
+) -> Result<ObjectId> {
+    if let Some(existing) = state
+        .categories
+        .find_one(doc! { "company_id": company_id, "name": name })
+        .await?
+    {
+        return existing.id.context("category missing _id");
+    }
+    create_category(state, company_id, name, flow_type, None, None).await
+}
+
+pub async fn delete_transaction(state: &AppState, id: &ObjectId) -> Result<()> {
+    let existing = state.transactions.find_one(doc! { "_id": id }).await?;
+
+    state.transactions.delete_one(doc! { "_id": id }).await?;
+    // The receipt stays with the company and can be used again.
+    state
+        .receipts
+        .update_many(
+            doc! { "transaction_id": id },
+            doc! { "$unset": { "transaction_id": "" } },
+        )
+        .await?;
+    delete_comments_for(state, CommentEntity::Transaction, id).await?;
+
+    if let Some(tx) = existing {
+        invalidate_balance_snapshots(state, &tx).await?;
+        invalidate_monthly_summary(state, &tx).await?;
+        forget_category_usage(state, &tx).await?;
+        if let Some(pe_id) = tx.planned_entry_id {
+            let _ = recalculate_planned_entry_status(state, &pe_id).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Draft transactions (`is_confirmed == false`) of a company, oldest first.
+/// They stay out of balances and planned-entry coverage until confirmed.
+/// Card holds are captured or voided instead, so they are not drafts.
+pub async fn list_pending_transactions(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<Transaction>> {
+    let mut cursor = state
+        .transactions
+        .find(doc! { "company_id": company_id, "is_confirmed": false, "hold": { "$exists": false } })
+        .sort(doc! { "date": 1 })
+        .await?;
+    let mut items = Vec::new();
+    while let Some(transaction) = cursor.try_next().await? {
+        items.push(transaction);
+    }
+    Ok(items)
+}
+
+/// Confirms the drafts among `ids` that belong to `company_id` and returns how
+/// many changed. Planned entries linked to them are recalculated, since they
+/// only start counting toward coverage now.
+pub async fn confirm_transactions(
+    state: &AppState,
+    company_id: &ObjectId,
+    ids: &[ObjectId],
+) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let filter = doc! {
+        "_id": { "$in": ids },
+        "company_id": company_id,
+        "is_confirmed": false,
+        "hold": { "$exists": false },
+    };
+    let mut planned_ids = Vec::new();
+    let mut drafts = Vec::new();
+    let mut cursor = state.transactions.find(filter.clone()).await?;
+    while let Some(tx) = cursor.try_next().await? {
+        drafts.push(tx.clone());
+        if let Some(pe_id) = tx.planned_entry_id
+            && !planned_ids.contains(&pe_id)
+        {
+            planned_ids.push(pe_id);
+        }
+    }
+
+    let res = state
+        .transactions
+        .update_many(
+            filter,
+            doc! { "$set": {
+                "is_confirmed": true,
                 "updated_at": DateTime::from_system_time(SystemTime::now()),
             } },
         )
         .await?;
 
-    if existing.planned_entry_id != planned_entry_id {
-        if let Some(old) = existing.planned_entry_id {
-            let _ = recalculate_planned_entry_status(state, &old).await;
-        }
+    for tx in drafts {
+        let confirmed = Transaction {
+            is_confirmed: true,
+            ..tx
+        };
+        invalidate_balance_snapshots(state, &confirmed).await?;
+        invalidate_monthly_summary(state, &confirmed).await?;
     }
-    if let Some(new_pe) = planned_entry_id {
-        let _ = recalculate_planned_entry_status(state, &new_pe).await;
+    for pe_id in planned_ids {
+        let _ = recalculate_planned_entry_status(state, &pe_id).await;
     }
 
-    Ok(())
+    Ok(res.modified_count)
 }
 
-/// Find or create a category named `name` with the given flow_type for a company.
-pub async fn get_or_create_category(
+/// Change applied to every transaction selected in a bulk edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionBulkAction {
+    SetCategory(ObjectId),
+    AddTag(String),
+    Confirm,
+}
+
+/// Outcome of a bulk edit for one transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionBulkResult {
+    pub id: ObjectId,
+    pub error: Option<String>,
+}
+
+/// Applies `action` to each transaction independently: a transaction that is
+/// missing, belongs to another company or does not accept the change gets an
+/// error in its result and the rest are still updated.
+pub async fn bulk_edit_transactions(
     state: &AppState,
     company_id: &ObjectId,
-    name: &str,
-    flow_type: FlowType,
-) -> Result<ObjectId> {
-    if let Some(existing) = state
-        .categories
-        .find_one(doc! { "company_id": company_id, "name": name })
-        .await?
-    {
-        return existing.id.context("category missing _id");
+    ids: &[ObjectId],
+    action: &TransactionBulkAction,
+) -> Result<Vec<TransactionBulkResult>> {
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        let error = match bulk_edit_transaction(state, company_id, id, action).await {
+            Ok(()) => None,
+            Err(err) => Some(err.to_string()),
+        };
+        results.push(TransactionBulkResult { id: *id, error });
     }
-    create_category(state, company_id, name, flow_type, None, None).await
+    Ok(results)
 }
 
-pub async fn delete_transaction(state: &AppState, id: &ObjectId) -> Result<()> {
-    let existing = state.transactions.find_one(doc! { "_id": id }).await?;
-
-    state.transactions.delete_one(doc! { "_id": id }).await?;
+async fn bulk_edit_transaction(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+    action: &TransactionBulkAction,
+) -> Result<()> {
+    let tx = state
+        .transactions
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("transaction not found")?;
+    let now = DateTime::from_system_time(SystemTime::now());
+    let update = match action {
+        TransactionBulkAction::SetCategory(category_id) => {
+            if tx.planned_entry_id.is_some() {
+                ensure_category_in_company(state, category_id, company_id).await?;
+            } else {
+                ensure_category_matches_flow(state, category_id, company_id, &tx.transaction_type)
+                    .await?;
+            }
+            let tax = transaction_tax(
+                state,
+                company_id,
+                &tx.transaction_type,
+                category_id,
+                tx.amount,
+                tx.tax.as_ref(),
+            )
+            .await?;
+            doc! { "$set": {
+                "category_id": category_id,
+                "tax": mongodb::bson::to_bson(&tax)?,
+                "updated_at": now,
+            } }
+        }
+        TransactionBulkAction::AddTag(tag) => doc! {
+            "$addToSet": { "tags": tag },
+            "$set": { "updated_at": now },
+        },
+        TransactionBulkAction::Confirm => {
+            if tx.is_confirmed {
+                return Ok(());
+            }
+            if tx.hold.is_some() {
+                bail!("Las retenciones de tarjeta se cobran o se anulan");
+            }
+            doc! { "$set": { "is_confirmed": true, "updated_at": now } }
+        }
+    };
+    state
+        .transactions
+        .update_one(doc! { "_id": id }, update)
+        .await?;
 
-    if let Some(tx) = existing {
-        if let Some(pe_id) = tx.planned_entry_id {
+    if let TransactionBulkAction::SetCategory(category_id) = action {
+        let recategorized = Transaction {
+            category_id: *category_id,
+            ..tx.clone()
+        };
+        move_category_usage(state, &tx, &recategorized).await?;
+        invalidate_monthly_summary(state, &tx).await?;
+    }
+    if *action == TransactionBulkAction::Confirm {
+        let planned_entry_id = tx.planned_entry_id;
+        let confirmed = Transaction {
+            is_confirmed: true,
+            ..tx
+        };
+        invalidate_balance_snapshots(state, &confirmed).await?;
+        invalidate_monthly_summary(state, &confirmed).await?;
+        if let Some(pe_id) = planned_entry_id {
             let _ = recalculate_planned_entry_status(state, &pe_id).await;
         }
     }
-
     Ok(())
 }
 
@@ -1157,16 +2311,27 @@ pub async fn create_forecast(
             projected_income_total,
             projected_expense_total,
             projected_net,
+            weighted_income_total: None,
             initial_balance,
             final_balance,
             details,
             scenario_name,
+            scenario_group_id: None,
+            reference: Some(
+                next_reference(state, company_id, SequenceKind::Forecast, generated_at).await?,
+            ),
             notes,
         })
         .await?;
-    res.inserted_id
+    let id = res
+        .inserted_id
         .as_object_id()
-        .context("forecast insert missing _id")
+        .context("forecast insert missing _id")?;
+    publish_event(state, company_id, CompanyEventKind::ForecastReady, id, Vec::new());
+    if let Err(err) = notify_forecast_completed(state, company_id, &id).await {
+        eprintln!("[webhooks] forecast.completed not sent: {err}");
+    }
+    Ok(id)
 }
 
 pub async fn update_forecast(
@@ -1217,6 +2382,239 @@ pub async fn delete_forecast(state: &AppState, id: &ObjectId) -> Result<()> {
     Ok(())
 }
 
+/// Sets the chance, in percent, that an income plan's money arrives, or
+/// clears it with `None` so its entries count in full.
+pub async fn set_recurring_plan_probability(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    probability: Option<f64>,
+) -> Result<()> {
+    let update = match probability {
+        Some(pct) => doc! { "$set": {
+            "probability": pct,
+            "updated_at": DateTime::from_system_time(SystemTime::now()),
+        } },
+        None => doc! {
+            "$unset": { "probability": "" },
+            "$set": { "updated_at": DateTime::from_system_time(SystemTime::now()) },
+        },
+    };
+    state
+        .recurring_plans
+        .update_one(doc! { "_id": id, "company_id": company_id }, update)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_recurring_plan_scenario_weights(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    weights: Option<ScenarioWeights>,
+) -> Result<()> {
+    let update = match weights {
+        Some(w) => doc! { "$set": {
+            "scenario_weights": { "best": w.best, "expected": w.expected, "worst": w.worst },
+            "updated_at": DateTime::from_system_time(SystemTime::now()),
+        } },
+        None => doc! {
+            "$unset": { "scenario_weights": "" },
+            "$set": { "updated_at": DateTime::from_system_time(SystemTime::now()) },
+        },
+    };
+    state
+        .recurring_plans
+        .update_one(doc! { "_id": id, "company_id": company_id }, update)
+        .await?;
+    Ok(())
+}
+
+/// Projects the planned entries due in `[start_date, end_date]` three times
+/// (best, expected, worst) and stores each variant as a forecast sharing one
+/// `scenario_group_id`, which is returned. Only income is weighted: an entry
+/// uses the weights of the recurring plan that generated it, or
+/// `default_weights` when it has none, and each variant also gets the income
+/// weighted by the plan's probability. Expenses count at their estimate in
+/// every variant, and cancelled entries are skipped. The details of each
+/// variant break the totals down by period of the company's fiscal calendar.
+pub async fn generate_scenario_forecasts(
+    state: &AppState,
+    company_id: &ObjectId,
+    generated_by_user_id: Option<ObjectId>,
+    start_date: DateTime,
+    end_date: DateTime,
+    initial_balance: Option<f64>,
+    default_weights: ScenarioWeights,
+) -> Result<ObjectId> {
+    if end_date < start_date {
+        bail!("forecast end date must not precede its start date");
+    }
+    let currency = company_default_currency(state, company_id).await?;
+    let calendar = company_fiscal_calendar(state, company_id).await?;
+
+    let mut plan_weights = std::collections::HashMap::new();
+    let mut plan_shares = std::collections::HashMap::new();
+    let mut plans = state
+        .recurring_plans
+        .find(doc! { "company_id": company_id })
+        .await?;
+    while let Some(plan) = plans.try_next().await? {
+        let Some(id) = plan.id else {
+            continue;
+        };
+        plan_shares.insert(id, plan.expected_share());
+        if let Some(weights) = plan.scenario_weights {
+            plan_weights.insert(id, weights);
+        }
+    }
+
+    // (estimated income, weights, probability, period) so each variant only
+    // re-weights.
+    let mut income: Vec<(f64, ScenarioWeights, f64, usize)> = Vec::new();
+    let mut expense_total = 0_f64;
+    let mut periods: Vec<FiscalPeriod> = Vec::new();
+    let mut period_expenses: Vec<f64> = Vec::new();
+    let mut entries = state
+        .planned_entries
+        .find(doc! {
+            "company_id": company_id,
+            "due_date": { "$gte": start_date, "$lte": end_date },
+            "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+        })
+        .await?;
+    while let Some(entry) = entries.try_next().await? {
+        let period = fiscal_period(&calendar, entry.due_date.to_chrono());
+        let period_index = match periods.iter().position(|p| p.key == period.key) {
+            Some(index) => index,
+            None => {
+                periods.push(period);
+                period_expenses.push(0.0);
+                periods.len() - 1
+            }
+        };
+        match entry.flow_type {
+            FlowType::Income => {
+                let weights = entry
+                    .recurring_plan_id
+                    .and_then(|id| plan_weights.get(&id).copied())
+                    .unwrap_or(default_weights);
+                let share = entry
+                    .recurring_plan_id
+                    .and_then(|id| plan_shares.get(&id).copied())
+                    .unwrap_or(1.0);
+                income.push((entry.amount_estimated, weights, share, period_index));
+            }
+            FlowType::Expense => {
+                expense_total += entry.amount_estimated;
+                period_expenses[period_index] += entry.amount_estimated;
+            }
+        }
+    }
+    let income_base: f64 = income.iter().map(|(amount, _, _, _)| amount).sum();
+    let mut period_order: Vec<usize> = (0..periods.len()).collect();
+    period_order.sort_by_key(|index| periods[*index].start);
+
+    let group_id = ObjectId::new();
+    let generated_at = DateTime::from_system_time(SystemTime::now());
+    for scenario in ForecastScenario::ALL {
+        let income_total: f64 = income
+            .iter()
+            .map(|(amount, weights, _, _)| amount * scenario.weight(weights))
+            .sum();
+        let weighted_income_total: f64 = income
+            .iter()
+            .map(|(amount, weights, share, _)| amount * scenario.weight(weights) * share)
+            .sum();
+        let net = income_total - expense_total;
+        let mut period_income = vec![0_f64; periods.len()];
+        for (amount, weights, _, index) in &income {
+            period_income[*index] += amount * scenario.weight(weights);
+        }
+        let period_details: Vec<serde_json::Value> = period_order
+            .iter()
+            .map(|index| {
+                let period = &periods[*index];
+                serde_json::json!({
+                    "period": period.key,
+                    "start": period.start.to_rfc3339(),
+                    "end": period.end.to_rfc3339(),
+                    "income": period_income[*index],
+                    "expense": period_expenses[*index],
+                    "net": period_income[*index] - period_expenses[*index],
+                })
+            })
+            .collect();
+        let details = serde_json::json!({
+            "scenario": scenario.as_str(),
+            "income_base": income_base,
+            "income_entries": income.len(),
+            "default_weight": scenario.weight(&default_weights),
+            "periods": period_details,
+        });
+        state
+            .forecasts
+            .insert_one(Forecast {
+                id: None,
+                company_id: *company_id,
+                generated_at,
+                generated_by_user_id,
+                start_date,
+                end_date,
+                currency: currency.clone(),
+                projected_income_total: income_total,
+                projected_expense_total: expense_total,
+                projected_net: net,
+                weighted_income_total: Some(weighted_income_total),
+                initial_balance,
+                final_balance: initial_balance.map(|balance| balance + net),
+                details: Some(details.to_string()),
+                scenario_name: Some(scenario.as_str().to_string()),
+                scenario_group_id: Some(group_id),
+                reference: Some(
+                    next_reference(state, company_id, SequenceKind::Forecast, generated_at).await?,
+                ),
+                notes: None,
+            })
+            .await?;
+    }
+
+    publish_event(
+        state,
+        company_id,
+        CompanyEventKind::ForecastReady,
+        group_id,
+        Vec::new(),
+    );
+    if let Err(err) = notify_forecast_completed(state, company_id, &group_id).await {
+        eprintln!("[webhooks] forecast.completed not sent: {err}");
+    }
+    Ok(group_id)
+}
+
+/// Forecasts of one scenario group in best, expected, worst order.
+pub async fn list_scenario_forecasts(
+    state: &AppState,
+    company_id: &ObjectId,
+    group_id: &ObjectId,
+) -> Result<Vec<Forecast>> {
+    let mut cursor = state
+        .forecasts
+        .find(doc! { "company_id": company_id, "scenario_group_id": group_id })
+        .await?;
+    let mut items = Vec::new();
+    while let Some(forecast) = cursor.try_next().await? {
+        items.push(forecast);
+    }
+    items.sort_by_key(|f| {
+        ForecastScenario::ALL
+            .iter()
+            .position(|s| f.scenario_name.as_deref() == Some(s.as_str()))
+            .unwrap_or(ForecastScenario::ALL.len())
+    });
+    Ok(items)
+}
+
 async fn validate_transaction_links(
     state: &AppState,
     company_id: &ObjectId,
@@ -1263,7 +2661,8 @@ async fn validate_transaction_links(
         // The planned entry is the authority on flow type; only check company ownership
         // of the category, not its flow_type (which may differ from the entry's).
         ensure_category_in_company(state, category_id, company_id).await?;
-        ensure_planned_entry_alignment(state, pe_id, company_id, transaction_type).await?;
+        ensure_planned_entry_alignment(state, pe_id, company_id, transaction_type, account_to_id)
+            .await?;
     } else {
         ensure_category_matches_flow(state, category_id, company_id, transaction_type).await?;
     }
@@ -1271,7 +2670,7 @@ async fn validate_transaction_links(
     Ok(())
 }
 
-async fn ensure_account_active_in_company(
+pub(super) async fn ensure_account_active_in_company(
     state: &AppState,
     account_id: &ObjectId,
     company_id: &ObjectId,
@@ -1308,7 +2707,7 @@ async fn ensure_category_in_company(
     Ok(())
 }
 
-async fn ensure_category_matches_flow(
+pub(super) async fn ensure_category_matches_flow(
     state: &AppState,
     category_id: &ObjectId,
     company_id: &ObjectId,
@@ -1342,6 +2741,7 @@ async fn ensure_planned_entry_alignment(
     planned_entry_id: &ObjectId,
     company_id: &ObjectId,
     transaction_type: &TransactionType,
+    account_to_id: Option<&ObjectId>,
 ) -> Result<()> {
     let pe = state
         .planned_entries
@@ -1357,12 +2757,20 @@ async fn ensure_planned_entry_alignment(
         bail!("planned entry is cancelled");
     }
 
-    match (transaction_type.clone(), pe.flow_type) {
+    match (transaction_type.clone(), &pe.flow_type) {
         (TransactionType::Income, FlowType::Income)
         | (TransactionType::Expense, FlowType::Expense) => {}
         _ => bail!("planned entry flow_type mismatches transaction type"),
     }
 
+    // A split income only lands in the accounts of its split.
+    if let Some(account) = account_to_id
+        && !pe.deposit_allocations.is_empty()
+        && !deposit_accounts(&pe).contains(account)
+    {
+        bail!("account is not one of the deposit accounts of the planned entry");
+    }
+
     Ok(())
 }
 
@@ -1407,7 +2815,7 @@ async fn ensure_parent_planned_entry_in_company(
     Ok(())
 }
 
-async fn recalculate_planned_entry_status(
+pub(super) async fn recalculate_planned_entry_status(
     state: &AppState,
     planned_entry_id: &ObjectId,
 ) -> Result<()> {
@@ -1424,19 +2832,14 @@ async fn recalculate_planned_entry_status(
         return Ok(());
     }
 
-    let mut total = 0_f64;
-    let mut cursor = state
-        .transactions
-        .find(doc! { "planned_entry_id": planned_entry_id })
-        .await?;
-    while let Some(tx) = cursor.try_next().await? {
-        total += tx.amount;
-    }
+    let total = planned_entry_covered_amount(state, planned_entry_id).await?;
 
     let mut status = if total <= 0.0 {
         PlannedStatus::Planned
     } else if total < pe.amount_estimated {
         PlannedStatus::PartiallyCovered
+    } else if coverage_excess(&pe, total) > 0.0 {
+        PlannedStatus::OverCovered
     } else {
         PlannedStatus::Covered
     };
@@ -1461,6 +2864,15 @@ async fn recalculate_planned_entry_status(
                 } },
             )
             .await?;
+        if status.is_covered() && !pe.status.is_covered() {
+            publish_event(
+                state,
+                &pe.company_id,
+                CompanyEventKind::EntryCovered,
+                *planned_entry_id,
+                vec![pe.account_expected_id],
+            );
+        }
     }
 
     Ok(())
@@ -1473,7 +2885,8 @@ pub async fn regenerate_planned_entries(state: &AppState, plan: &RecurringPlan)
 
     let plan_id = plan.id.as_ref().unwrap();
     delete_future_open_entries(state, plan_id).await?;
-    generate_planned_entries_for_plan(state, plan, PLANNED_MONTHS_AHEAD).await
+    generate_planned_entries_for_plan(state, plan, PLANNED_MONTHS_AHEAD, Utc::now()).await?;
+    Ok(())
 }
 
 pub async fn regenerate_planned_entries_for_plan_id(
@@ -1493,6 +2906,7 @@ pub async fn regenerate_planned_entries_for_plan_id(
     regenerate_planned_entries(state, &plan).await
 }
 
+/// Deletes the open future entries of the plan, except those edited by hand.
 async fn delete_future_open_entries(state: &AppState, plan_id: &ObjectId) -> Result<()> {
     let now = DateTime::from_system_time(SystemTime::now());
     state
@@ -1501,145 +2915,228 @@ async fn delete_future_open_entries(state: &AppState, plan_id: &ObjectId) -> Res
             "recurring_plan_id": plan_id,
             "status": { "$in": [PlannedStatus::Planned.as_str(), PlannedStatus::PartiallyCovered.as_str()] },
             "due_date": { "$gte": now },
+            "is_customized": { "$ne": true },
         })
         .await?;
     Ok(())
 }
 
+/// Adds the entries of `plan` due from the current period up to
+/// `months_ahead` months after `now`. An entry is only inserted when the plan
+/// has none for the same version and due day, so running it again (or after
+/// entries were paid) never duplicates them; the unique index on
+/// `generated_due_date` settles two runs racing for the same entry. Days
+/// already covered by an entry edited by hand, of any version, are skipped,
+/// as are days an entry was moved away from and days whose entry was deleted
+/// (the plan's `skipped_due_dates`). Returns how many were added.
 async fn generate_planned_entries_for_plan(
     state: &AppState,
     plan: &RecurringPlan,
     months_ahead: u32,
-) -> Result<()> {
+    now: ChronoDateTime<Utc>,
+) -> Result<u64> {
     if !plan.is_active {
-        return Ok(());
+        return Ok(0);
     }
     let Some(plan_id) = plan.id.as_ref() else {
-        return Ok(());
+        return Ok(0);
     };
 
-    let now_ref = Utc::now();
-    let due_dates = upcoming_due_dates(plan, months_ahead, now_ref);
-
+    let until = now
+        .checked_add_months(Months::new(months_ahead))
+        .context("planning horizon out of range")?;
+    let due_dates = upcoming_due_dates(plan, until, now);
+    let Some(first) = due_dates.first() else {
+        return Ok(0);
+    };
+    let first_day = utc_day_start(*first);
+    let mut taken_days: Vec<DateTime> = plan.skipped_due_dates.clone();
+    let mut cursor = state
+        .planned_entries
+        .find(doc! {
+            "recurring_plan_id": plan_id,
+            "$or": [
+                { "is_customized": true, "due_date": { "$gte": first_day } },
+                { "original_due_date": { "$gte": first_day } },
+            ],
+        })
+        .await?;
+    while let Some(entry) = cursor.try_next().await? {
+        if entry.is_customized {
+            taken_days.push(utc_day_start(entry.due_date));
+        }
+        if let Some(original) = entry.original_due_date {
+            taken_days.push(utc_day_start(original));
+        }
+    }
+    let mut inserted = 0;
     for due in due_dates {
-        let _ = state
+        // Matched by day: entries generated before due dates were pinned to
+        // the plan's time of day carry whatever time they were created at.
+        let day_start = utc_day_start(due);
+        if taken_days.contains(&day_start) {
+            continue;
+        }
+        let entry = PlannedEntry {
+            id: None,
+            company_id: plan.company_id,
+            recurring_plan_id: Some(*plan_id),
+            recurring_plan_version: Some(plan.version),
+            service_order_id: None,
+            project_id: None,
+            parent_planned_entry_id: None,
+            name: format!("{} {}", plan.name, due.to_chrono().date_naive()),
+            flow_type: plan.flow_type.clone(),
+            category_id: plan.category_id,
+            account_expected_id: plan.account_expected_id,
+            contact_id: plan.contact_id,
+            amount_estimated: plan.amount_estimated,
+            original_amount_estimated: None,
+            due_date: due,
+            original_due_date: None,
+            generated_due_date: Some(due),
+            status: PlannedStatus::Planned,
+            is_customized: false,
+            created_at: Some(DateTime::from_system_time(SystemTime::now())),
+            updated_at: None,
+            notes: plan.notes.clone(),
+            cfdi_uuid: None,
+            currency: None,
+            cfdi_folio: None,
+            coverage_adjustment: None,
+            slip_count: 0,
+            amount_revisions: Vec::new(),
+            exchange_rate: None,
+            deposit_allocations: Vec::new(),
+        };
+        let mut fields = mongodb::bson::to_document(&entry)?;
+        fields.remove("recurring_plan_id");
+        fields.remove("recurring_plan_version");
+        let day_end = DateTime::from_millis(day_start.timestamp_millis() + 24 * 60 * 60 * 1000);
+        let result = state
             .planned_entries
-            .insert_one(PlannedEntry {
-                id: None,
-                company_id: plan.company_id.clone(),
-                recurring_plan_id: Some(plan_id.clone()),
-                recurring_plan_version: Some(plan.version),
-                service_order_id: None,
-                project_id: None,
-                parent_planned_entry_id: None,
-                name: format!("{} {}", plan.name, due.to_chrono().date_naive()),
-                flow_type: plan.flow_type.clone(),
-                category_id: plan.category_id.clone(),
-                account_expected_id: plan.account_expected_id.clone(),
-                contact_id: plan.contact_id.clone(),
-                amount_estimated: plan.amount_estimated,
-                original_amount_estimated: None,
-                due_date: due,
-                original_due_date: None,
-                status: PlannedStatus::Planned,
-                created_at: Some(DateTime::from_system_time(SystemTime::now())),
-                updated_at: None,
-                notes: plan.notes.clone(),
-                cfdi_uuid: None,
-                currency: None,
-                cfdi_folio: None,
-            })
-            .await?;
+            .update_one(
+                doc! {
+                    "recurring_plan_id": plan_id,
+                    "recurring_plan_version": plan.version,
+                    "due_date": { "$gte": day_start, "$lt": day_end },
+                },
+                doc! { "$setOnInsert": fields },
+            )
+            .upsert(true)
+            .await;
+        match result {
+            Ok(result) if result.upserted_id.is_some() => inserted += 1,
+            Ok(_) => {}
+            // Another run inserted it between the match and the insert: it
+            // already exists.
+            Err(err) if is_duplicate_key(&err) => {}
+            Err(err) => return Err(err.into()),
+        }
     }
-    Ok(())
+    metrics().record_planned_entries_generated(inserted);
+    Ok(inserted)
+}
+
+/// Whether MongoDB turned a write down because of a unique index.
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == DUPLICATE_KEY,
+        ErrorKind::Command(error) => error.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// Tops up every active plan so its entries reach `PLANNED_MONTHS_AHEAD`
+/// months past `now`. Plans only get entries generated when they are saved,
+/// so without this the horizon shrinks month after month. Returns how many
+/// entries were added.
+pub async fn extend_planned_entries(state: &AppState, now: DateTime) -> Result<u64> {
+    let mut inserted = 0;
+    let mut cursor = state
+        .recurring_plans
+        .find(doc! { "is_active": true })
+        .await?;
+    while let Some(plan) = cursor.try_next().await? {
+        inserted +=
+            generate_planned_entries_for_plan(state, &plan, PLANNED_MONTHS_AHEAD, now.to_chrono())
+                .await?;
+    }
+    Ok(inserted)
 }
 
+/// Runs `extend_planned_entries` right away and then once a day, so the
+/// horizon never falls more than a day behind. Runs with nothing to add write
+/// nothing.
+pub fn spawn_planned_entry_extension_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        loop {
+            ticker.tick().await;
+            let result = extend_planned_entries(&state, DateTime::from_chrono(Utc::now())).await;
+            metrics().record_task_run("planned_entry_extension", result.is_ok());
+            match result {
+                Ok(inserted) => println!("planned entries: {inserted} added to reach the horizon"),
+                Err(err) => eprintln!("planned entry extension failed: {err:?}"),
+            }
+        }
+    });
+}
+
+/// Due dates of `plan` from its occurrence in the current period (which may
+/// already be past) up to, not including, `until`. Dates derive only from the
+/// plan's start date and day of month, never from `now_ref`'s time of day, so
+/// every run yields the same dates.
 fn upcoming_due_dates(
     plan: &RecurringPlan,
-    months_ahead: u32,
+    until: ChronoDateTime<Utc>,
     now_ref: ChronoDateTime<Utc>,
 ) -> Vec<DateTime> {
     let start = plan.start_date.to_chrono();
-    let mut dates = Vec::new();
     let end_limit = plan.end_date.map(|d| d.to_chrono());
+    let in_range = |candidate: ChronoDateTime<Utc>| {
+        candidate < until && end_limit.is_none_or(|end| candidate <= end)
+    };
+    let mut dates = Vec::new();
 
-    match plan.frequency.to_lowercase().as_str() {
+    let step_days = match plan.frequency.to_lowercase().as_str() {
         "monthly" => {
-            let anchor = align_to_day(start, plan.day_of_month);
-            let base = if now_ref.date_naive() > anchor.date_naive() {
-                align_to_day(now_ref, plan.day_of_month)
+            let day = plan.day_of_month.or(Some(start.day() as i32));
+            let anchor = align_to_day(start, day);
+            let elapsed = if now_ref.date_naive() > anchor.date_naive() {
+                (now_ref.year() - anchor.year()) * 12 + now_ref.month() as i32
+                    - anchor.month() as i32
             } else {
-                anchor
+                0
             };
-
-            for i in 0..months_ahead {
-                let candidate = base
-                    .checked_add_months(Months::new(i.into()))
-                    .unwrap_or(base);
-                if candidate < start {
-                    continue;
-                }
-                if let Some(end) = end_limit {
-                    if candidate > end {
-                        break;
-                    }
-                }
-                dates.push(DateTime::from_chrono(candidate));
-            }
-        }
-        "weekly" => {
-            let step = chrono::Duration::days(7);
-            let mut current = start;
-            while current + step <= now_ref {
-                current = current + step;
-            }
-            for _ in 0..months_ahead {
-                if let Some(end) = end_limit {
-                    if current > end {
-                        break;
-                    }
-                }
-                if current >= start {
-                    dates.push(DateTime::from_chrono(current));
-                }
-                current = current + step;
-            }
-        }
-        "biweekly" => {
-            let step = chrono::Duration::days(14);
-            let mut current = start;
-            while current + step <= now_ref {
-                current = current + step;
-            }
-            for _ in 0..months_ahead {
-                if let Some(end) = end_limit {
-                    if current > end {
-                        break;
-                    }
-                }
-                if current >= start {
-                    dates.push(DateTime::from_chrono(current));
+            let mut offset = elapsed.max(0) as u32;
+            while let Some(month) = anchor.checked_add_months(Months::new(offset)) {
+                let candidate = align_to_day(month, day);
+                if !in_range(candidate) {
+                    break;
                 }
-                current = current + step;
-            }
-        }
-        _ => {
-            let step = chrono::Duration::days(30);
-            let mut current = if now_ref > start { now_ref } else { start };
-            for _ in 0..months_ahead {
-                if current >= start {
-                    if let Some(end) = end_limit {
-                        if current > end {
-                            break;
-                        }
-                    }
-                    dates.push(DateTime::from_chrono(current));
+                if candidate >= start {
+                    dates.push(DateTime::from_chrono(candidate));
                 }
-                current = current + step;
+                offset += 1;
             }
+            return dates;
         }
-    }
+        "weekly" => 7,
+        "biweekly" => 14,
+        _ => 30,
+    };
 
+    let step = chrono::Duration::days(step_days);
+    let mut current = start;
+    while current + step <= now_ref {
+        current += step;
+    }
+    while in_range(current) {
+        dates.push(DateTime::from_chrono(current));
+        current += step;
+    }
     dates
 }
 
@@ -1676,3 +3173,152 @@ fn clamp_day(year: i32, month: u32, day: i32) -> u32 {
             last_day
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32) -> ChronoDateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    fn plan(
+        frequency: &str,
+        day_of_month: Option<i32>,
+        start: ChronoDateTime<Utc>,
+        end: Option<ChronoDateTime<Utc>>,
+    ) -> RecurringPlan {
+        RecurringPlan {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            name: "Renta".into(),
+            flow_type: FlowType::Expense,
+            category_id: ObjectId::new(),
+            account_expected_id: ObjectId::new(),
+            contact_id: None,
+            amount_estimated: 1000.0,
+            frequency: frequency.into(),
+            day_of_month,
+            start_date: DateTime::from_chrono(start),
+            end_date: end.map(DateTime::from_chrono),
+            is_active: true,
+            version: 1,
+            scenario_weights: None,
+            probability: None,
+            skipped_due_dates: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            notes: None,
+        }
+    }
+
+    fn days(dates: Vec<DateTime>) -> Vec<String> {
+        dates
+            .into_iter()
+            .map(|date| date.to_chrono().format("%Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn monthly_day_is_clamped_to_short_months() {
+        let plan = plan("monthly", Some(31), at(2025, 1, 31), None);
+        assert_eq!(
+            days(upcoming_due_dates(&plan, at(2025, 5, 1), at(2025, 1, 1))),
+            ["2025-01-31", "2025-02-28", "2025-03-31", "2025-04-30"]
+        );
+    }
+
+    #[test]
+    fn monthly_without_day_follows_start_date_from_current_period() {
+        let plan = plan("monthly", None, at(2025, 1, 15), None);
+        assert_eq!(
+            days(upcoming_due_dates(&plan, at(2025, 6, 1), at(2025, 3, 20))),
+            ["2025-03-15", "2025-04-15", "2025-05-15"]
+        );
+    }
+
+    #[test]
+    fn weekly_steps_from_start_and_ignores_day_of_month() {
+        // Plans saved before day_of_month was validated may still carry one.
+        let plan = plan("weekly", Some(20), at(2025, 1, 6), None);
+        assert_eq!(
+            days(upcoming_due_dates(&plan, at(2025, 2, 4), at(2025, 1, 20))),
+            ["2025-01-20", "2025-01-27", "2025-02-03"]
+        );
+    }
+
+    #[test]
+    fn end_date_is_inclusive() {
+        let plan = plan("monthly", Some(10), at(2025, 1, 10), Some(at(2025, 3, 10)));
+        assert_eq!(
+            days(upcoming_due_dates(&plan, at(2026, 1, 1), at(2025, 1, 1))),
+            ["2025-01-10", "2025-02-10", "2025-03-10"]
+        );
+    }
+
+    #[test]
+    fn payment_terms_add_whole_days() {
+        let issued = DateTime::from_chrono(Utc.with_ymd_and_hms(2025, 1, 15, 9, 30, 0).unwrap());
+        let due = due_date_from_terms(issued, 30);
+        assert_eq!(due.try_to_rfc3339_string().unwrap(), "2025-02-14T09:30:00Z");
+        assert_eq!(due_date_from_terms(issued, 0), issued);
+    }
+
+    fn entry(status: PlannedStatus, due: ChronoDateTime<Utc>) -> PlannedEntry {
+        PlannedEntry {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            recurring_plan_id: None,
+            recurring_plan_version: None,
+            service_order_id: None,
+            project_id: None,
+            parent_planned_entry_id: None,
+            name: "Renta".into(),
+            flow_type: FlowType::Expense,
+            category_id: ObjectId::new(),
+            account_expected_id: ObjectId::new(),
+            contact_id: None,
+            amount_estimated: 1000.0,
+            original_amount_estimated: None,
+            due_date: DateTime::from_chrono(due),
+            original_due_date: None,
+            generated_due_date: None,
+            status,
+            is_customized: false,
+            created_at: None,
+            updated_at: None,
+            notes: None,
+            cfdi_uuid: None,
+            currency: None,
+            cfdi_folio: None,
+            coverage_adjustment: None,
+            slip_count: 0,
+            amount_revisions: Vec::new(),
+            exchange_rate: None,
+            deposit_allocations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn overdue_entries_roll_to_the_next_plan_date_or_by_days() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 20, 15, 0, 0).unwrap();
+        let monthly = plan("monthly", Some(10), at(2025, 1, 10), None);
+        let overdue = entry(PlannedStatus::Overdue, at(2025, 2, 10));
+        let rolled = |plan, days| {
+            roll_forward_due_date(&overdue, plan, days, now)
+                .map(|date| date.to_chrono().format("%Y-%m-%d").to_string())
+        };
+
+        assert_eq!(rolled(Some(&monthly), None).unwrap(), "2025-04-10");
+        assert_eq!(rolled(Some(&monthly), Some(5)).unwrap(), "2025-03-25");
+        assert!(rolled(None, None).is_err());
+        assert!(rolled(None, Some(0)).is_err());
+        let ended = plan("monthly", Some(10), at(2025, 1, 10), Some(at(2025, 3, 10)));
+        assert!(rolled(Some(&ended), None).is_err());
+
+        let not_due = entry(PlannedStatus::Planned, at(2025, 3, 20));
+        assert!(roll_forward_due_date(&not_due, None, Some(5), now).is_err());
+        let covered = entry(PlannedStatus::Covered, at(2025, 2, 10));
+        assert!(roll_forward_due_date(&covered, None, Some(5), now).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+// Fiscal calendars: the twelve periods a company's year is reported in.
+// Without a setting they are calendar months from January, keyed `YYYY-MM`
+// like before; companies that close on 4-4-5 style calendars get periods of
+// whole weeks keyed `FY2025-P03`. The income statement, the budgets and the
+// forecasts ask here for the period a date falls in instead of cutting
+// calendar months themselves.
+
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Days, Months, NaiveDate, TimeZone, Utc};
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::FiscalCalendar;
+
+use super::{AppState, clear_monthly_summaries};
+
+/// Periods in a fiscal year.
+pub const FISCAL_PERIODS_PER_YEAR: u32 = 12;
+
+/// One reporting period of a fiscal calendar, `[start, end)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiscalPeriod {
+    /// `YYYY-MM` for calendar months, e.g. `FY2025-P03` for week patterns.
+    pub key: String,
+    /// Calendar year the fiscal year starts in.
+    pub year: i32,
+    /// 1 to 12 within the fiscal year.
+    pub number: u32,
+    pub start: chrono::DateTime<Utc>,
+    pub end: chrono::DateTime<Utc>,
+}
+
+impl FiscalPeriod {
+    pub fn start_date(&self) -> DateTime {
+        DateTime::from_chrono(self.start)
+    }
+
+    pub fn end_date(&self) -> DateTime {
+        DateTime::from_chrono(self.end)
+    }
+}
+
+fn midnight(date: NaiveDate) -> chrono::DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+}
+
+/// First day of the fiscal year named `year`.
+fn fiscal_year_start(calendar: &FiscalCalendar, year: i32) -> NaiveDate {
+    let first = NaiveDate::from_ymd_opt(year, calendar.start_month.clamp(1, 12), 1)
+        .unwrap_or(NaiveDate::MIN);
+    if calendar.pattern.quarter_weeks().is_none() {
+        return first;
+    }
+    let weekday = first.weekday().num_days_from_monday();
+    let offset = (calendar.start_weekday % 7 + 7 - weekday) % 7;
+    first + Days::new(u64::from(offset))
+}
+
+/// Weeks in each period of the fiscal year `year`; the 53rd week of long
+/// years goes to the last period.
+fn period_weeks(calendar: &FiscalCalendar, quarter: [u32; 3], year: i32) -> [u32; 12] {
+    let days = (fiscal_year_start(calendar, year + 1) - fiscal_year_start(calendar, year))
+        .num_days()
+        .max(0);
+    let mut weeks = [0; 12];
+    for (i, week) in weeks.iter_mut().enumerate() {
+        *week = quarter[i % 3];
+    }
+    weeks[11] += u32::try_from(days / 7).unwrap_or(52).saturating_sub(52);
+    weeks
+}
+
+/// Period `number` (1 to 12) of the fiscal year `year`.
+pub fn fiscal_period_by_number(calendar: &FiscalCalendar, year: i32, number: u32) -> FiscalPeriod {
+    let number = number.clamp(1, FISCAL_PERIODS_PER_YEAR);
+    let year_start = fiscal_year_start(calendar, year);
+    match calendar.pattern.quarter_weeks() {
+        None => {
+            let start = midnight(year_start) + Months::new(number - 1);
+            FiscalPeriod {
+                key: start.format("%Y-%m").to_string(),
+                year,
+                number,
+                start,
+                end: start + Months::new(1),
+            }
+        }
+        Some(quarter) => {
+            let weeks = period_weeks(calendar, quarter, year);
+            let before: u32 = weeks[..(number - 1) as usize].iter().sum();
+            let start = year_start + Days::new(u64::from(before) * 7);
+            let end = start + Days::new(u64::from(weeks[(number - 1) as usize]) * 7);
+            FiscalPeriod {
+                key: format!("FY{year}-P{number:02}"),
+                year,
+                number,
+                start: midnight(start),
+                end: midnight(end),
+            }
+        }
+    }
+}
+
+/// The period `at` falls in.
+pub fn fiscal_period(calendar: &FiscalCalendar, at: chrono::DateTime<Utc>) -> FiscalPeriod {
+    let day = at.date_naive();
+    let mut year = day.year();
+    if day < fiscal_year_start(calendar, year) {
+        year -= 1;
+    }
+    (1..FISCAL_PERIODS_PER_YEAR)
+        .map(|number| fiscal_period_by_number(calendar, year, number))
+        .find(|period| at < period.end)
+        .unwrap_or_else(|| fiscal_period_by_number(calendar, year, FISCAL_PERIODS_PER_YEAR))
+}
+
+/// The period right before `period`.
+pub fn previous_fiscal_period(calendar: &FiscalCalendar, period: &FiscalPeriod) -> FiscalPeriod {
+    if period.number > 1 {
+        fiscal_period_by_number(calendar, period.year, period.number - 1)
+    } else {
+        fiscal_period_by_number(calendar, period.year - 1, FISCAL_PERIODS_PER_YEAR)
+    }
+}
+
+/// The same period of the fiscal year before.
+pub fn fiscal_period_year_before(calendar: &FiscalCalendar, period: &FiscalPeriod) -> FiscalPeriod {
+    fiscal_period_by_number(calendar, period.year - 1, period.number)
+}
+
+/// The `count` periods up to and including the one `at` falls in, oldest
+/// first.
+pub fn fiscal_periods_until(
+    calendar: &FiscalCalendar,
+    at: chrono::DateTime<Utc>,
+    count: u32,
+) -> Vec<FiscalPeriod> {
+    let mut periods = vec![fiscal_period(calendar, at)];
+    for _ in 1..count {
+        let previous = previous_fiscal_period(calendar, &periods[periods.len() - 1]);
+        periods.push(previous);
+    }
+    periods.reverse();
+    periods
+}
+
+/// The company's fiscal calendar, calendar months when it has none.
+pub async fn company_fiscal_calendar(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<FiscalCalendar> {
+    let company = state
+        .companies
+        .find_one(doc! { "_id": company_id })
+        .await?
+        .context("company not found")?;
+    Ok(company.fiscal_calendar)
+}
+
+/// The period of the company's calendar `now` falls in.
+pub async fn company_fiscal_period(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<FiscalPeriod> {
+    let calendar = company_fiscal_calendar(state, company_id).await?;
+    Ok(fiscal_period(&calendar, now.to_chrono()))
+}
+
+/// Saves the company's fiscal calendar. The stored period summaries are cut
+/// by the old one, so they are dropped and rebuilt on the next read.
+pub async fn update_company_fiscal_calendar(
+    state: &AppState,
+    id: &ObjectId,
+    calendar: &FiscalCalendar,
+) -> Result<()> {
+    if !(1..=12).contains(&calendar.start_month) {
+        bail!("El mes de inicio va de 1 a 12");
+    }
+    if calendar.start_weekday > 6 {
+        bail!("El día de inicio va de lunes a domingo");
+    }
+    let update = if calendar.is_default() {
+        doc! {
+            "$unset": { "fiscal_calendar": "" },
+            "$set": { "updated_at": DateTime::from_system_time(SystemTime::now()) },
+        }
+    } else {
+        doc! { "$set": {
+            "fiscal_calendar": {
+                "pattern": calendar.pattern.as_str(),
+                "start_month": i64::from(calendar.start_month),
+                "start_weekday": i64::from(calendar.start_weekday),
+            },
+            "updated_at": DateTime::from_system_time(SystemTime::now()),
+        } }
+    };
+    state
+        .companies
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    clear_monthly_summaries(state, id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FiscalPattern;
+
+    fn at(value: &str) -> chrono::DateTime<Utc> {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn weeks(pattern: FiscalPattern) -> FiscalCalendar {
+        FiscalCalendar {
+            pattern,
+            start_month: 1,
+            start_weekday: 0,
+        }
+    }
+
+    #[test]
+    fn calendar_months_keep_their_keys() {
+        let calendar = FiscalCalendar::default();
+        let period = fiscal_period(&calendar, at("2025-03-31T23:00:00Z"));
+        assert_eq!(period.key, "2025-03");
+        assert_eq!(period.number, 3);
+        assert_eq!(period.start, at("2025-03-01T00:00:00Z"));
+        assert_eq!(period.end, at("2025-04-01T00:00:00Z"));
+        assert_eq!(fiscal_period_year_before(&calendar, &period).key, "2024-03");
+    }
+
+    #[test]
+    fn fiscal_years_can_start_in_another_month() {
+        let calendar = FiscalCalendar {
+            start_month: 4,
+            ..FiscalCalendar::default()
+        };
+        let period = fiscal_period(&calendar, at("2026-02-10T00:00:00Z"));
+        assert_eq!(period.key, "2026-02");
+        assert_eq!((period.year, period.number), (2025, 11));
+    }
+
+    #[test]
+    fn four_four_five_periods_are_whole_weeks() {
+        let calendar = weeks(FiscalPattern::Weeks445);
+        // 2025 starts on Monday January 6th.
+        let first = fiscal_period_by_number(&calendar, 2025, 1);
+        assert_eq!(first.key, "FY2025-P01");
+        assert_eq!(first.start, at("2025-01-06T00:00:00Z"));
+        assert_eq!(first.end, at("2025-02-03T00:00:00Z"));
+        let third = fiscal_period_by_number(&calendar, 2025, 3);
+        assert_eq!((third.end - third.start).num_days(), 35);
+        // The last days of the year before January 6th belong to FY2024.
+        let period = fiscal_period(&calendar, at("2025-01-03T12:00:00Z"));
+        assert_eq!((period.year, period.number), (2024, 12));
+        assert_eq!(period.end, first.start);
+    }
+
+    #[test]
+    fn the_extra_week_of_long_years_goes_to_the_last_period() {
+        let calendar = weeks(FiscalPattern::Weeks544);
+        // FY2029 runs from Monday January 1st 2029 to Monday January 7th 2030.
+        let last = fiscal_period_by_number(&calendar, 2029, 12);
+        assert_eq!((last.end - last.start).num_days(), 35);
+        assert_eq!(last.end, at("2030-01-07T00:00:00Z"));
+        assert_eq!(fiscal_period_by_number(&calendar, 2030, 1).start, last.end);
+    }
+
+    #[test]
+    fn periods_until_walk_back_across_years() {
+        let calendar = weeks(FiscalPattern::Weeks454);
+        let periods = fiscal_periods_until(&calendar, at("2025-02-10T00:00:00Z"), 3);
+        let keys: Vec<&str> = periods.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["FY2024-P12", "FY2025-P01", "FY2025-P02"]);
+        for pair in periods.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+// Chart data of a forecast: what it projected and what actually happened,
+// one point per period of the company's fiscal calendar (calendar months
+// unless it has one). Generated forecasts keep their projection per period
+// in `details`; the totals of the rest are spread over the periods by the
+// share of the forecast's days each one covers. Actual figures are whole
+// periods read from the same summaries as the income statement, and periods
+// that have not started yet have none.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use mongodb::bson::{DateTime, oid::ObjectId};
+
+use crate::models::{FiscalCalendar, FlowType, Forecast};
+
+use super::{
+    AppState, FiscalPeriod, company_fiscal_calendar, fiscal_period,
+    income_statement::monthly_summary, previous_fiscal_period,
+};
+
+/// Most periods a chart covers; longer forecasts are cut at their start.
+pub const FORECAST_CHART_MAX_PERIODS: usize = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastChartPoint {
+    pub period: String,
+    pub start: DateTime,
+    pub end: DateTime,
+    pub projected_income: f64,
+    pub projected_expense: f64,
+    pub projected_net: f64,
+    pub actual_income: Option<f64>,
+    pub actual_expense: Option<f64>,
+    pub actual_net: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastChartSeries {
+    pub forecast_id: ObjectId,
+    pub reference: Option<String>,
+    pub scenario_name: Option<String>,
+    pub currency: String,
+    pub points: Vec<ForecastChartPoint>,
+}
+
+/// Periods of `calendar` that overlap `[start, end]`, oldest first.
+pub fn forecast_periods(
+    calendar: &FiscalCalendar,
+    start: DateTime,
+    end: DateTime,
+) -> Vec<FiscalPeriod> {
+    let first_start = fiscal_period(calendar, start.to_chrono()).start;
+    let mut periods = vec![fiscal_period(calendar, end.to_chrono())];
+    while periods.len() < FORECAST_CHART_MAX_PERIODS
+        && periods[periods.len() - 1].start > first_start
+    {
+        let previous = previous_fiscal_period(calendar, &periods[periods.len() - 1]);
+        periods.push(previous);
+    }
+    periods.reverse();
+    periods
+}
+
+/// Income and expense per period key stored by `generate_scenario_forecasts`.
+fn detail_periods(details: Option<&str>) -> Option<HashMap<String, (f64, f64)>> {
+    let value: serde_json::Value = serde_json::from_str(details?).ok()?;
+    let periods = value.get("periods")?.as_array()?;
+    Some(
+        periods
+            .iter()
+            .filter_map(|period| {
+                Some((
+                    period.get("period")?.as_str()?.to_string(),
+                    (
+                        period.get("income")?.as_f64()?,
+                        period.get("expense")?.as_f64()?,
+                    ),
+                ))
+            })
+            .collect(),
+    )
+}
+
+/// Projected income and expense of each period. The breakdown in the
+/// details is used when all of its periods are among `periods`, i.e. the
+/// calendar did not change since the forecast was generated.
+pub fn projected_by_period(forecast: &Forecast, periods: &[FiscalPeriod]) -> Vec<(f64, f64)> {
+    if let Some(stored) = detail_periods(forecast.details.as_deref())
+        && stored
+            .keys()
+            .all(|key| periods.iter().any(|period| &period.key == key))
+    {
+        return periods
+            .iter()
+            .map(|period| stored.get(&period.key).copied().unwrap_or_default())
+            .collect();
+    }
+    let start = forecast.start_date.timestamp_millis();
+    let end = forecast.end_date.timestamp_millis();
+    let total = (end - start) as f64;
+    periods
+        .iter()
+        .enumerate()
+        .map(|(index, period)| {
+            let share = if total <= 0.0 {
+                // A one-day forecast falls entirely in its first period.
+                if index == 0 { 1.0 } else { 0.0 }
+            } else {
+                let from = period.start_date().timestamp_millis().max(start);
+                let to = period.end_date().timestamp_millis().min(end);
+                (to - from).max(0) as f64 / total
+            };
+            (
+                forecast.projected_income_total * share,
+                forecast.projected_expense_total * share,
+            )
+        })
+        .collect()
+}
+
+/// The chart series of `forecast`, cut by the company's fiscal calendar.
+pub async fn forecast_chart(
+    state: &AppState,
+    company_id: &ObjectId,
+    forecast: &Forecast,
+) -> Result<ForecastChartSeries> {
+    let calendar = company_fiscal_calendar(state, company_id).await?;
+    let periods = forecast_periods(&calendar, forecast.start_date, forecast.end_date);
+    let projected = projected_by_period(forecast, &periods);
+    let now = DateTime::now();
+    let mut points = Vec::with_capacity(periods.len());
+    for (period, (income, expense)) in periods.iter().zip(projected) {
+        let actual = if period.start_date() <= now {
+            let summary = monthly_summary(state, company_id, period).await?;
+            let total = |flow_type: FlowType| -> f64 {
+                summary
+                    .lines
+                    .iter()
+                    .filter(|line| line.flow_type == flow_type)
+                    .map(|line| line.amount)
+                    .sum()
+            };
+            Some((total(FlowType::Income), total(FlowType::Expense)))
+        } else {
+            None
+        };
+        points.push(ForecastChartPoint {
+            period: period.key.clone(),
+            start: period.start_date(),
+            end: period.end_date(),
+            projected_income: income,
+            projected_expense: expense,
+            projected_net: income - expense,
+            actual_income: actual.map(|(income, _)| income),
+            actual_expense: actual.map(|(_, expense)| expense),
+            actual_net: actual.map(|(income, expense)| income - expense),
+        });
+    }
+    Ok(ForecastChartSeries {
+        forecast_id: forecast.id.unwrap_or_default(),
+        reference: forecast.reference.clone(),
+        scenario_name: forecast.scenario_name.clone(),
+        currency: forecast.currency.clone(),
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> DateTime {
+        DateTime::parse_rfc3339_str(value).unwrap()
+    }
+
+    fn forecast(start: &str, end: &str, details: Option<&str>) -> Forecast {
+        Forecast {
+            id: None,
+            company_id: ObjectId::new(),
+            generated_at: DateTime::now(),
+            generated_by_user_id: None,
+            start_date: date(start),
+            end_date: date(end),
+            currency: "MXN".into(),
+            projected_income_total: 900.0,
+            projected_expense_total: 300.0,
+            projected_net: 600.0,
+            weighted_income_total: None,
+            initial_balance: None,
+            final_balance: None,
+            details: details.map(str::to_string),
+            scenario_name: None,
+            scenario_group_id: None,
+            reference: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn periods_cover_the_whole_forecast() {
+        let periods = forecast_periods(
+            &FiscalCalendar::default(),
+            date("2026-01-15T00:00:00Z"),
+            date("2026-03-31T23:59:59Z"),
+        );
+        let keys: Vec<&str> = periods.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["2026-01", "2026-02", "2026-03"]);
+    }
+
+    #[test]
+    fn totals_are_spread_by_days_without_a_breakdown() {
+        let forecast = forecast("2026-01-01T00:00:00Z", "2026-03-01T00:00:00Z", None);
+        let periods = forecast_periods(
+            &FiscalCalendar::default(),
+            forecast.start_date,
+            forecast.end_date,
+        );
+        let projected = projected_by_period(&forecast, &periods);
+        // 31 of the 59 days are in January, 28 in February, none in March.
+        assert!((projected[0].0 - 900.0 * 31.0 / 59.0).abs() < 0.001);
+        assert!((projected[1].1 - 300.0 * 28.0 / 59.0).abs() < 0.001);
+        assert_eq!(projected[2], (0.0, 0.0));
+    }
+
+    #[test]
+    fn generated_breakdowns_are_used_when_the_periods_match() {
+        let details = r#"{"periods": [
+            {"period": "2026-02", "income": 500.0, "expense": 100.0, "net": 400.0}
+        ]}"#;
+        let forecast = forecast(
+            "2026-01-01T00:00:00Z",
+            "2026-02-28T00:00:00Z",
+            Some(details),
+        );
+        let periods = forecast_periods(
+            &FiscalCalendar::default(),
+            forecast.start_date,
+            forecast.end_date,
+        );
+        assert_eq!(
+            projected_by_period(&forecast, &periods),
+            [(0.0, 0.0), (500.0, 100.0)]
+        );
+
+        // Keys of another calendar fall back to spreading the totals.
+        let stale = r#"{"periods": [{"period": "FY2026-P01", "income": 1.0, "expense": 0.0}]}"#;
+        let forecast = Forecast {
+            details: Some(stale.into()),
+            ..forecast
+        };
+        let projected = projected_by_period(&forecast, &periods);
+        assert!(projected[0].0 > 1.0);
+    }
+}
@@ -0,0 +1,813 @@
+// Realized exchange results: what a company gains or loses because a
+// foreign currency moved between the rate an amount was booked at and the
+// rate it was settled at. Rates are units of the company's currency per unit
+// of the foreign one, typed on movements and commitments (`exchange_rate`).
+// Two settlements realize a result:
+// - foreign-currency income covering a commitment booked at another rate:
+//   amount * (rate of the income - rate of the commitment);
+// - a transfer out of a foreign-currency account into an account of another
+//   currency: amount * (rate of the transfer - average rate of the money in
+//   the account). The average moves with each rated deposit (income, or a
+//   purchase from an account in the company's currency) by its share of the
+//   balance; withdrawals leave it as is.
+// Each result is recorded as an adjustment transaction without accounts in
+// the company's currency (income for gains, expense for losses), so it shows
+// in the income statement without moving any balance.
+
+use std::{collections::HashMap, time::SystemTime};
+
+use anyhow::{Context, Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection,
+    bson::{self, DateTime, Document, doc, oid::ObjectId},
+};
+
+use crate::models::{Account, FlowType, FxAdjustment, PlannedEntry, Transaction, TransactionType};
+
+use super::{
+    AppState, SequenceKind,
+    balances::invalidate_balance_snapshots,
+    budgets::check_budget_alerts,
+    category_suggestions::{move_category_usage, record_category_usage},
+    companies::company_default_currency,
+    create_category,
+    events::{CompanyEventKind, publish_event},
+    finance::delete_transaction,
+    income_statement::invalidate_monthly_summary,
+    next_reference,
+};
+
+/// Categories the adjustments go to, created the first time one is needed.
+pub const FX_GAIN_CATEGORY: &str = "Ganancia cambiaria";
+pub const FX_LOSS_CATEGORY: &str = "Pérdida cambiaria";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FxSettlementKind {
+    /// Foreign-currency income covering a commitment.
+    Collection,
+    /// Transfer out of a foreign-currency account into another currency.
+    Transfer,
+}
+
+impl FxSettlementKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FxSettlementKind::Collection => "collection",
+            FxSettlementKind::Transfer => "transfer",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FxSettlementKind::Collection => "Cobro",
+            FxSettlementKind::Transfer => "Transferencia",
+        }
+    }
+}
+
+/// Result realized by one settlement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxResult {
+    pub transaction_id: ObjectId,
+    pub date: DateTime,
+    pub description: String,
+    pub reference: Option<String>,
+    pub kind: FxSettlementKind,
+    pub currency: String,
+    /// In `currency`.
+    pub amount: f64,
+    pub booked_rate: f64,
+    pub settled_rate: f64,
+    /// In the company's currency, rounded to cents; positive is a gain.
+    pub result: f64,
+}
+
+impl FxResult {
+    fn adjustment(&self) -> FxAdjustment {
+        FxAdjustment {
+            source_id: self.transaction_id,
+            currency: self.currency.clone(),
+            amount: self.amount,
+            booked_rate: self.booked_rate,
+            settled_rate: self.settled_rate,
+        }
+    }
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Foreign money in an account and its average rate. Deposits of unknown
+/// rate count at the average, or not at all while there is none.
+#[derive(Default)]
+struct Holding {
+    balance: f64,
+    rate: Option<f64>,
+}
+
+impl Holding {
+    fn deposit(&mut self, amount: f64, rate: Option<f64>) {
+        if amount < 0.0 {
+            return self.withdraw(-amount);
+        }
+        match (rate, self.rate) {
+            (Some(rate), Some(average)) => {
+                let balance = self.balance + amount;
+                self.rate = Some((self.balance * average + amount * rate) / balance);
+                self.balance = balance;
+            }
+            (Some(rate), None) => {
+                self.rate = Some(rate);
+                self.balance = amount;
+            }
+            (None, Some(_)) => self.balance += amount,
+            (None, None) => {}
+        }
+    }
+
+    fn withdraw(&mut self, amount: f64) {
+        self.balance = (self.balance - amount).max(0.0);
+    }
+}
+
+/// Currency a transaction's amount is in: the CFDI's, or else that of the
+/// account money comes out of (into, for income).
+fn transaction_currency<'a>(
+    tx: &'a Transaction,
+    accounts: &'a HashMap<ObjectId, String>,
+) -> Option<&'a str> {
+    let account = match tx.transaction_type {
+        TransactionType::Income => tx.account_to_id,
+        _ => tx.account_from_id,
+    };
+    tx.currency
+        .as_deref()
+        .or_else(|| account.and_then(|id| accounts.get(&id)).map(String::as_str))
+}
+
+/// Results realized by `transactions` (confirmed, oldest first, without
+/// adjustments), given the currency of each account and the booked rate of
+/// each commitment that has one.
+pub fn realized_fx_results(
+    company_currency: &str,
+    accounts: &HashMap<ObjectId, String>,
+    booked_rates: &HashMap<ObjectId, f64>,
+    transactions: &[Transaction],
+) -> Vec<FxResult> {
+    let foreign = |id: &ObjectId| {
+        accounts
+            .get(id)
+            .map(String::as_str)
+            .filter(|currency| *currency != company_currency)
+    };
+    let mut holdings: HashMap<ObjectId, Holding> = HashMap::new();
+    let mut results = Vec::new();
+    for tx in transactions {
+        let Some(id) = tx.id else { continue };
+        let currency = transaction_currency(tx, accounts);
+        let mut realized = |kind, currency: &str, booked_rate: f64, settled_rate: f64| {
+            results.push(FxResult {
+                transaction_id: id,
+                date: tx.date,
+                description: tx.description.clone(),
+                reference: tx.reference.clone(),
+                kind,
+                currency: currency.to_string(),
+                amount: tx.amount,
+                booked_rate,
+                settled_rate,
+                result: round_cents(tx.amount * (settled_rate - booked_rate)),
+            })
+        };
+
+        match tx.transaction_type {
+            TransactionType::Income => {
+                if let Some(currency) = currency.filter(|currency| *currency != company_currency)
+                    && let Some(settled) = tx.exchange_rate
+                    && let Some(booked) = tx
+                        .planned_entry_id
+                        .and_then(|entry| booked_rates.get(&entry).copied())
+                {
+                    realized(FxSettlementKind::Collection, currency, booked, settled);
+                }
+                if let Some(to) = tx.account_to_id
+                    && foreign(&to).is_some_and(|foreign| currency == Some(foreign))
+                {
+                    holdings
+                        .entry(to)
+                        .or_default()
+                        .deposit(tx.amount, tx.exchange_rate);
+                }
+            }
+            TransactionType::Expense => {
+                if let Some(from) = tx.account_from_id
+                    && foreign(&from).is_some()
+                {
+                    holdings.entry(from).or_default().withdraw(tx.amount);
+                }
+            }
+            TransactionType::Transfer => {
+                let (Some(from), Some(to)) = (tx.account_from_id, tx.account_to_id) else {
+                    continue;
+                };
+                let average = holdings.get(&from).and_then(|holding| holding.rate);
+                if foreign(&from).is_none()
+                    && foreign(&to).is_some()
+                    && let Some(rate) = tx.exchange_rate
+                {
+                    // Buying foreign money: the amount is in the company's
+                    // currency and the rate is the destination's.
+                    holdings
+                        .entry(to)
+                        .or_default()
+                        .deposit(tx.amount / rate, Some(rate));
+                }
+                if let Some(from_currency) = foreign(&from) {
+                    if accounts.get(&to).map(String::as_str) == Some(from_currency) {
+                        // Same currency: the money keeps its rate.
+                        holdings
+                            .entry(to)
+                            .or_default()
+                            .deposit(tx.amount, tx.exchange_rate.or(average));
+                    } else if let Some(settled) = tx.exchange_rate
+                        && let Some(booked) = average
+                    {
+                        realized(FxSettlementKind::Transfer, from_currency, booked, settled);
+                    }
+                    holdings.entry(from).or_default().withdraw(tx.amount);
+                }
+            }
+        }
+    }
+    results
+}
+
+/// Where results and adjustments are read from: the report read preference
+/// for the report, the primary when adjustments are recorded from them, so
+/// a lagging secondary never decides what gets written.
+#[derive(Debug, Clone, Copy)]
+enum Reads {
+    Report,
+    Primary,
+}
+
+impl Reads {
+    fn of<T: Send + Sync>(self, state: &AppState, collection: &Collection<T>) -> Collection<T> {
+        match self {
+            Reads::Report => state.for_reports(collection),
+            Reads::Primary => collection.clone(),
+        }
+    }
+}
+
+/// Results realized by the company's movements so far.
+pub async fn company_fx_results(state: &AppState, company_id: &ObjectId) -> Result<Vec<FxResult>> {
+    realized_results(state, company_id, Reads::Primary).await
+}
+
+async fn realized_results(
+    state: &AppState,
+    company_id: &ObjectId,
+    reads: Reads,
+) -> Result<Vec<FxResult>> {
+    let company_currency = company_default_currency(state, company_id).await?;
+    let accounts: Vec<Account> = reads
+        .of(state, &state.accounts)
+        .find(doc! { "company_id": company_id })
+        .await?
+        .try_collect()
+        .await?;
+    let accounts: HashMap<ObjectId, String> = accounts
+        .into_iter()
+        .filter_map(|account| Some((account.id?, account.currency)))
+        .collect();
+    let foreign: Vec<ObjectId> = accounts
+        .iter()
+        .filter(|(_, currency)| **currency != company_currency)
+        .map(|(id, _)| *id)
+        .collect();
+
+    let entries: Vec<PlannedEntry> = reads
+        .of(state, &state.planned_entries)
+        .find(doc! { "company_id": company_id, "exchange_rate": { "$type": "number" } })
+        .await?
+        .try_collect()
+        .await?;
+    let booked_rates: HashMap<ObjectId, f64> = entries
+        .into_iter()
+        .filter_map(|entry| Some((entry.id?, entry.exchange_rate?)))
+        .collect();
+
+    let transactions: Vec<Transaction> = reads
+        .of(state, &state.transactions)
+        .find(doc! {
+            "company_id": company_id,
+            "is_confirmed": { "$ne": false },
+            "fx_adjustment": { "$exists": false },
+            "$or": [
+                { "exchange_rate": { "$type": "number" } },
+                { "account_from_id": { "$in": &foreign } },
+                { "account_to_id": { "$in": &foreign } },
+            ],
+        })
+        .sort(doc! { "date": 1, "_id": 1 })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(realized_fx_results(
+        &company_currency,
+        &accounts,
+        &booked_rates,
+        &transactions,
+    ))
+}
+
+/// Adjustments recorded for the company, by the settlement they come from.
+async fn fx_adjustments(
+    state: &AppState,
+    company_id: &ObjectId,
+    reads: Reads,
+) -> Result<HashMap<ObjectId, Transaction>> {
+    let adjustments: Vec<Transaction> = reads
+        .of(state, &state.transactions)
+        .find(doc! { "company_id": company_id, "fx_adjustment": { "$type": "object" } })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(adjustments
+        .into_iter()
+        .filter_map(|tx| Some((tx.fx_adjustment.as_ref()?.source_id, tx)))
+        .collect())
+}
+
+/// Gain (positive) or loss an adjustment records.
+fn recorded_result(adjustment: &Transaction) -> f64 {
+    match adjustment.transaction_type {
+        TransactionType::Income => adjustment.amount,
+        _ => -adjustment.amount,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FxReportLine {
+    pub result: FxResult,
+    /// What the adjustment recorded for it says, if there is one.
+    pub recorded: Option<f64>,
+}
+
+impl FxReportLine {
+    /// The adjustment is missing or records another figure.
+    pub fn is_pending(&self) -> bool {
+        match self.recorded {
+            Some(recorded) => (recorded - self.result.result).abs() >= 0.005,
+            None => self.result.result != 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FxReport {
+    pub currency: String,
+    pub lines: Vec<FxReportLine>,
+    pub gains: f64,
+    pub losses: f64,
+    pub net: f64,
+    /// Settlements without an up-to-date adjustment, plus adjustments of
+    /// settlements that no longer realize anything.
+    pub pending: usize,
+}
+
+/// Results realized by settlements dated in `[from, to)`, with what their
+/// adjustments recorded.
+pub async fn fx_report(
+    state: &AppState,
+    company_id: &ObjectId,
+    from: DateTime,
+    to: DateTime,
+) -> Result<FxReport> {
+    let mut adjustments = fx_adjustments(state, company_id, Reads::Report).await?;
+    let mut lines = Vec::new();
+    for result in realized_results(state, company_id, Reads::Report).await? {
+        let recorded = adjustments
+            .remove(&result.transaction_id)
+            .map(|adjustment| recorded_result(&adjustment));
+        if result.date >= from && result.date < to {
+            lines.push(FxReportLine { result, recorded });
+        }
+    }
+    let stale = adjustments
+        .values()
+        .filter(|adjustment| adjustment.date >= from && adjustment.date < to)
+        .count();
+    let gains = lines
+        .iter()
+        .map(|line| line.result.result.max(0.0))
+        .sum::<f64>();
+    let losses = lines
+        .iter()
+        .map(|line| (-line.result.result).max(0.0))
+        .sum::<f64>();
+    Ok(FxReport {
+        currency: company_default_currency(state, company_id).await?,
+        pending: lines.iter().filter(|line| line.is_pending()).count() + stale,
+        lines,
+        gains: round_cents(gains),
+        losses: round_cents(losses),
+        net: round_cents(gains - losses),
+    })
+}
+
+/// Category of the adjustments of `flow_type`, created if missing.
+async fn fx_category(
+    state: &AppState,
+    company_id: &ObjectId,
+    flow_type: FlowType,
+) -> Result<ObjectId> {
+    let name = match flow_type {
+        FlowType::Income => FX_GAIN_CATEGORY,
+        FlowType::Expense => FX_LOSS_CATEGORY,
+    };
+    let existing = state
+        .categories
+        .find_one(doc! {
+            "company_id": company_id,
+            "name": name,
+            "flow_type": flow_type.as_str(),
+        })
+        .await?;
+    match existing.and_then(|category| category.id) {
+        Some(id) => Ok(id),
+        None => {
+            create_category(
+                state,
+                company_id,
+                name,
+                flow_type,
+                None,
+                Some("Ajustes por diferencia cambiaria".to_string()),
+            )
+            .await
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FxRecordSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// Brings the company's adjustments in line with its realized results:
+/// creates the missing ones, updates those whose figures changed and removes
+/// those whose settlement no longer realizes anything. Balances, monthly
+/// summaries and category usage follow, as with any other movement, and
+/// budget alerts are checked once at the end when anything changed.
+pub async fn record_fx_adjustments(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<FxRecordSummary> {
+    let currency = company_default_currency(state, company_id).await?;
+    let mut adjustments = fx_adjustments(state, company_id, Reads::Primary).await?;
+    let mut summary = FxRecordSummary::default();
+    for result in company_fx_results(state, company_id).await? {
+        let current = adjustments.remove(&result.transaction_id);
+        if result.result == 0.0 {
+            if let Some(current) = current {
+                delete_adjustment(state, &current).await?;
+                summary.removed += 1;
+            }
+            continue;
+        }
+        let (transaction_type, flow_type) = if result.result > 0.0 {
+            (TransactionType::Income, FlowType::Income)
+        } else {
+            (TransactionType::Expense, FlowType::Expense)
+        };
+        let amount = result.result.abs();
+        let adjustment = result.adjustment();
+        let description = format!("Diferencia cambiaria: {}", result.description);
+        match current {
+            Some(current)
+                if current.amount == amount
+                    && current.transaction_type == transaction_type
+                    && current.date == result.date
+                    && current.fx_adjustment.as_ref() == Some(&adjustment) => {}
+            Some(current) => {
+                let category_id = fx_category(state, company_id, flow_type).await?;
+                state
+                    .transactions
+                    .update_one(
+                        doc! { "_id": current.id },
+                        doc! { "$set": {
+                            "date": result.date,
+                            "description": &description,
+                            "transaction_type": transaction_type.as_str(),
+                            "category_id": category_id,
+                            "amount": amount,
+                            "fx_adjustment": bson::to_bson(&adjustment)?,
+                            "updated_at": DateTime::from_system_time(SystemTime::now()),
+                        } },
+                    )
+                    .await?;
+                let updated = Transaction {
+                    date: result.date,
+                    description,
+                    transaction_type,
+                    category_id,
+                    amount,
+                    fx_adjustment: Some(adjustment),
+                    ..current.clone()
+                };
+                invalidate_balance_snapshots(state, &current).await?;
+                invalidate_monthly_summary(state, &current).await?;
+                invalidate_balance_snapshots(state, &updated).await?;
+                invalidate_monthly_summary(state, &updated).await?;
+                move_category_usage(state, &current, &updated).await?;
+                summary.updated += 1;
+            }
+            None => {
+                let category_id = fx_category(state, company_id, flow_type).await?;
+                let transaction = Transaction {
+                    id: None,
+                    company_id: *company_id,
+                    date: result.date,
+                    description,
+                    transaction_type,
+                    category_id,
+                    account_from_id: None,
+                    account_to_id: None,
+                    amount,
+                    planned_entry_id: None,
+                    project_id: None,
+                    is_confirmed: true,
+                    created_at: Some(DateTime::from_system_time(SystemTime::now())),
+                    updated_at: None,
+                    contact_id: None,
+                    cfdi_uuid: None,
+                    currency: Some(currency.clone()),
+                    cfdi_folio: None,
+                    reference: Some(
+                        next_reference(state, company_id, SequenceKind::Transaction, result.date)
+                            .await?,
+                    ),
+                    external_id: None,
+                    bank_reference: None,
+                    notes: None,
+                    tags: Vec::new(),
+                    custom_fields: Document::new(),
+                    tax: None,
+                    refund_of: None,
+                    exchange_rate: None,
+                    fx_adjustment: Some(adjustment),
+                    hold: None,
+                };
+                let res = state.transactions.insert_one(&transaction).await?;
+                invalidate_balance_snapshots(state, &transaction).await?;
+                invalidate_monthly_summary(state, &transaction).await?;
+                record_category_usage(state, &transaction).await?;
+                let id = res
+                    .inserted_id
+                    .as_object_id()
+                    .context("transaction insert missing _id")?;
+                // Adjustments move no account, so only unrestricted users
+                // hear of them, as with the transaction list.
+                publish_event(
+                    state,
+                    company_id,
+                    CompanyEventKind::TransactionCreated,
+                    id,
+                    Vec::new(),
+                );
+                summary.created += 1;
+            }
+        }
+    }
+    for adjustment in adjustments.values() {
+        delete_adjustment(state, adjustment).await?;
+        summary.removed += 1;
+    }
+    if summary != FxRecordSummary::default()
+        && let Err(err) = check_budget_alerts(state, company_id, DateTime::now()).await
+    {
+        eprintln!("budget alerts: check failed: {err:?}");
+    }
+    Ok(summary)
+}
+
+async fn delete_adjustment(state: &AppState, adjustment: &Transaction) -> Result<()> {
+    delete_transaction(state, &adjustment.id.context("adjustment missing _id")?).await
+}
+
+fn check_exchange_rate(rate: Option<f64>) -> Result<()> {
+    if rate.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+        bail!("El tipo de cambio debe ser mayor a cero");
+    }
+    Ok(())
+}
+
+fn exchange_rate_update(rate: Option<f64>) -> Document {
+    match rate {
+        Some(rate) => doc! { "$set": { "exchange_rate": rate } },
+        None => doc! { "$unset": { "exchange_rate": "" } },
+    }
+}
+
+/// Sets or clears the rate of a transaction.
+pub async fn set_transaction_exchange_rate(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    rate: Option<f64>,
+) -> Result<()> {
+    check_exchange_rate(rate)?;
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id, "company_id": company_id },
+            exchange_rate_update(rate),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Sets or clears the booked rate of a planned entry.
+pub async fn set_planned_entry_exchange_rate(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    rate: Option<f64>,
+) -> Result<()> {
+    check_exchange_rate(rate)?;
+    state
+        .planned_entries
+        .update_one(
+            doc! { "_id": id, "company_id": company_id },
+            exchange_rate_update(rate),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(
+        transaction_type: TransactionType,
+        from: Option<ObjectId>,
+        to: Option<ObjectId>,
+        amount: f64,
+        rate: Option<f64>,
+    ) -> Transaction {
+        Transaction {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            date: DateTime::now(),
+            description: "Movimiento".into(),
+            transaction_type,
+            category_id: ObjectId::new(),
+            account_from_id: from,
+            account_to_id: to,
+            amount,
+            planned_entry_id: None,
+            project_id: None,
+            is_confirmed: true,
+            created_at: None,
+            updated_at: None,
+            contact_id: None,
+            cfdi_uuid: None,
+            currency: None,
+            cfdi_folio: None,
+            reference: None,
+            external_id: None,
+            bank_reference: None,
+            notes: None,
+            tags: Vec::new(),
+            custom_fields: Document::new(),
+            tax: None,
+            refund_of: None,
+            exchange_rate: rate,
+            fx_adjustment: None,
+            hold: None,
+        }
+    }
+
+    #[test]
+    fn collections_realize_the_difference_with_the_booked_rate() {
+        let (usd, mxn) = (ObjectId::new(), ObjectId::new());
+        let accounts = HashMap::from([(usd, "USD".to_string()), (mxn, "MXN".to_string())]);
+        let invoice = ObjectId::new();
+        let booked = HashMap::from([(invoice, 17.0)]);
+
+        let mut income = tx(TransactionType::Income, None, Some(usd), 1000.0, Some(17.5));
+        income.planned_entry_id = Some(invoice);
+        // Domestic income realizes nothing, whatever its rate.
+        let mut domestic = tx(TransactionType::Income, None, Some(mxn), 1000.0, Some(2.0));
+        domestic.planned_entry_id = Some(invoice);
+
+        let results = realized_fx_results("MXN", &accounts, &booked, &[income, domestic]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, FxSettlementKind::Collection);
+        assert_eq!(results[0].currency, "USD");
+        assert_eq!(results[0].result, 500.0);
+    }
+
+    #[test]
+    fn transfers_realize_against_the_moving_average() {
+        let [usd, savings, mxn, euros] = std::array::from_fn(|_| ObjectId::new());
+        let accounts = HashMap::from([
+            (usd, "USD".to_string()),
+            (savings, "USD".to_string()),
+            (mxn, "MXN".to_string()),
+            (euros, "EUR".to_string()),
+        ]);
+        let transactions = [
+            tx(TransactionType::Income, None, Some(usd), 100.0, Some(17.0)),
+            tx(TransactionType::Income, None, Some(usd), 100.0, Some(19.0)),
+            // Withdrawals leave the average at 18.
+            tx(TransactionType::Expense, Some(usd), None, 50.0, None),
+            tx(
+                TransactionType::Transfer,
+                Some(usd),
+                Some(mxn),
+                100.0,
+                Some(17.5),
+            ),
+            // Same-currency transfers carry the average along.
+            tx(
+                TransactionType::Transfer,
+                Some(usd),
+                Some(savings),
+                50.0,
+                None,
+            ),
+            tx(
+                TransactionType::Transfer,
+                Some(savings),
+                Some(mxn),
+                50.0,
+                Some(18.6),
+            ),
+            // Buying dollars adds them at the rate of the purchase.
+            tx(
+                TransactionType::Transfer,
+                Some(mxn),
+                Some(usd),
+                900.0,
+                Some(18.0),
+            ),
+            tx(
+                TransactionType::Transfer,
+                Some(usd),
+                Some(mxn),
+                50.0,
+                Some(18.2),
+            ),
+            // Nothing to compare a transfer out of an account never funded with.
+            tx(
+                TransactionType::Transfer,
+                Some(euros),
+                Some(mxn),
+                10.0,
+                Some(20.0),
+            ),
+        ];
+        let results = realized_fx_results("MXN", &accounts, &HashMap::new(), &transactions);
+        let figures: Vec<(f64, f64)> = results
+            .iter()
+            .map(|result| (result.booked_rate, result.result))
+            .collect();
+        assert_eq!(figures, [(18.0, -50.0), (18.0, 30.0), (18.0, 10.0)]);
+        assert!(
+            results
+                .iter()
+                .all(|result| result.kind == FxSettlementKind::Transfer)
+        );
+    }
+
+    #[test]
+    fn report_lines_are_pending_until_an_adjustment_matches() {
+        let result = FxResult {
+            transaction_id: ObjectId::new(),
+            date: DateTime::now(),
+            description: "Cobro".into(),
+            reference: None,
+            kind: FxSettlementKind::Collection,
+            currency: "USD".into(),
+            amount: 10.0,
+            booked_rate: 17.0,
+            settled_rate: 16.0,
+            result: -10.0,
+        };
+        let line = |recorded| FxReportLine {
+            result: result.clone(),
+            recorded,
+        };
+        assert!(line(None).is_pending());
+        assert!(line(Some(10.0)).is_pending());
+        assert!(!line(Some(-10.0)).is_pending());
+    }
+}
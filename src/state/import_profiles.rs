@@ -0,0 +1,123 @@
+// Column mappings for CSV bank statements. A CSV upload is kept as a draft
+// while its columns are previewed and remapped; importing it saves the
+// mapping as a named profile, which later files with the same header start
+// from.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId, to_bson};
+
+use crate::models::{CsvMapping, ImportDraft, ImportProfile};
+
+use super::AppState;
+
+/// How long an uploaded CSV waits for its columns to be confirmed.
+pub const IMPORT_DRAFT_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// Keeps an uploaded CSV statement until it is imported, and returns its id.
+pub async fn create_import_draft(state: &AppState, mut draft: ImportDraft) -> Result<ObjectId> {
+    draft.id = None;
+    draft.expires_at = DateTime::from_system_time(
+        SystemTime::now() + Duration::from_secs(IMPORT_DRAFT_TTL_SECONDS),
+    );
+    let res = state.import_drafts.insert_one(&draft).await?;
+    res.inserted_id
+        .as_object_id()
+        .ok_or_else(|| anyhow::anyhow!("import draft without id"))
+}
+
+/// The company's draft `id`, unless it expired.
+pub async fn get_import_draft(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<Option<ImportDraft>> {
+    Ok(state
+        .import_drafts
+        .find_one(doc! {
+            "_id": id,
+            "company_id": company_id,
+            "expires_at": { "$gt": DateTime::now() },
+        })
+        .await?)
+}
+
+pub async fn delete_import_draft(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<()> {
+    state
+        .import_drafts
+        .delete_one(doc! { "_id": id, "company_id": company_id })
+        .await?;
+    Ok(())
+}
+
+/// The company's saved profiles, by name.
+pub async fn list_import_profiles(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<ImportProfile>> {
+    state
+        .import_profiles
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "name": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// The profile saved for files with this header, the latest one when
+/// several match.
+pub async fn find_import_profile_for_header(
+    state: &AppState,
+    company_id: &ObjectId,
+    header: &[String],
+) -> Result<Option<ImportProfile>> {
+    Ok(state
+        .import_profiles
+        .find_one(doc! { "company_id": company_id, "header": header })
+        .sort(doc! { "updated_at": -1 })
+        .await?)
+}
+
+/// Saves the mapping under `name`, replacing the company's profile of that
+/// name.
+pub async fn save_import_profile(
+    state: &AppState,
+    company_id: &ObjectId,
+    name: &str,
+    header: &[String],
+    mapping: &CsvMapping,
+) -> Result<()> {
+    state
+        .import_profiles
+        .update_one(
+            doc! { "company_id": company_id, "name": name },
+            doc! { "$set": {
+                "header": header,
+                "mapping": to_bson(mapping)?,
+                "updated_at": DateTime::now(),
+            }},
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// Deletes one of the company's profiles; `false` when there was none.
+pub async fn delete_import_profile(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<bool> {
+    let res = state
+        .import_profiles
+        .delete_one(doc! { "_id": id, "company_id": company_id })
+        .await?;
+    Ok(res.deleted_count > 0)
+}
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use mongodb::bson::{Bson, DateTime, doc, oid::ObjectId};
+
+use crate::import::{StatementFormat, StatementLine};
+use crate::models::TransactionType;
+
+use super::{
+    AppState,
+    finance::{
+        create_transaction, ensure_account_active_in_company, ensure_category_matches_flow,
+        set_transaction_external_refs,
+    },
+};
+
+/// Description given to movements the bank left without one.
+const UNNAMED_MOVEMENT: &str = "Movimiento importado";
+
+/// Outcome of importing a statement into an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementImport {
+    pub imported: usize,
+    /// Lines skipped because the account already has that movement.
+    pub duplicates: usize,
+}
+
+fn day_bounds(date: NaiveDate) -> (DateTime, DateTime) {
+    let start = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let end = start.checked_add_days(Days::new(1)).unwrap_or(start);
+    (DateTime::from_chrono(start), DateTime::from_chrono(end))
+}
+
+fn line_description(line: &StatementLine) -> &str {
+    match line.description.trim() {
+        "" => UNNAMED_MOVEMENT,
+        description => description,
+    }
+}
+
+/// Records statement lines as unconfirmed transactions of `account_id`:
+/// deposits as income into the account, withdrawals as expenses from it, each
+/// under the category given for its flow, so they wait in the pending list
+/// for review. A line is skipped when the account already has the bank
+/// reference it carries, or as many movements with the same day, amount and
+/// description as the file brings, so uploading the same statement twice
+/// imports nothing new.
+#[allow(clippy::too_many_arguments)]
+pub async fn import_statement_lines(
+    state: &AppState,
+    company_id: &ObjectId,
+    account_id: &ObjectId,
+    income_category_id: &ObjectId,
+    expense_category_id: &ObjectId,
+    format: StatementFormat,
+    lines: &[StatementLine],
+) -> Result<StatementImport> {
+    // Checked up front so a bad choice does not leave half a statement in.
+    ensure_account_active_in_company(state, account_id, company_id).await?;
+    if lines.iter().any(|line| line.amount > 0.0) {
+        ensure_category_matches_flow(
+            state,
+            income_category_id,
+            company_id,
+            &TransactionType::Income,
+        )
+        .await?;
+    }
+    if lines.iter().any(|line| line.amount < 0.0) {
+        ensure_category_matches_flow(
+            state,
+            expense_category_id,
+            company_id,
+            &TransactionType::Expense,
+        )
+        .await?;
+    }
+
+    // Existing copies are counted before inserting, so repeated movements
+    // inside the file (two equal charges on one day) are all kept.
+    let mut existing: Vec<((NaiveDate, u64, &str), u64)> = Vec::new();
+    for line in lines.iter().filter(|line| line.amount != 0.0) {
+        let key = (line.date, line.amount.to_bits(), line_description(line));
+        if existing.iter().any(|(k, _)| *k == key) {
+            continue;
+        }
+        let (start, end) = day_bounds(line.date);
+        let count = state
+            .transactions
+            .count_documents(doc! {
+                "company_id": company_id,
+                "date": { "$gte": start, "$lt": end },
+                "amount": line.amount.abs(),
+                "description": line_description(line),
+                "$or": [
+                    { "account_from_id": account_id },
+                    { "account_to_id": account_id },
+                ],
+            })
+            .await?;
+        existing.push((key, count));
+    }
+
+    // The bank's own id of a movement (OFX `FITID`) is matched exactly.
+    let in_file: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| line.reference.as_deref())
+        .collect();
+    let stored = if in_file.is_empty() {
+        Vec::new()
+    } else {
+        state
+            .transactions
+            .distinct(
+                "bank_reference",
+                doc! {
+                    "company_id": company_id,
+                    "bank_reference": { "$in": &in_file },
+                    "$or": [
+                        { "account_from_id": account_id },
+                        { "account_to_id": account_id },
+                    ],
+                },
+            )
+            .await?
+    };
+    let mut references: HashSet<String> = stored
+        .into_iter()
+        .filter_map(|reference| match reference {
+            Bson::String(reference) => Some(reference),
+            _ => None,
+        })
+        .collect();
+
+    let mut summary = StatementImport::default();
+    for line in lines.iter().filter(|line| line.amount != 0.0) {
+        let key = (line.date, line.amount.to_bits(), line_description(line));
+        if let Some(reference) = &line.reference
+            && !references.insert(reference.clone())
+        {
+            if let Some((_, remaining)) = existing.iter_mut().find(|(k, n)| *k == key && *n > 0) {
+                *remaining -= 1;
+            }
+            summary.duplicates += 1;
+            continue;
+        }
+        if let Some((_, remaining)) = existing.iter_mut().find(|(k, n)| *k == key && *n > 0) {
+            *remaining -= 1;
+            summary.duplicates += 1;
+            continue;
+        }
+        let (transaction_type, category_id, account_from_id, account_to_id) = if line.amount > 0.0 {
+            (
+                TransactionType::Income,
+                income_category_id,
+                None,
+                Some(*account_id),
+            )
+        } else {
+            (
+                TransactionType::Expense,
+                expense_category_id,
+                Some(*account_id),
+                None,
+            )
+        };
+        let notes = match &line.reference {
+            Some(reference) => format!("Importado de {} · ref. {reference}", format.label()),
+            None => format!("Importado de {}", format.label()),
+        };
+        let id = create_transaction(
+            state,
+            company_id,
+            day_bounds(line.date).0,
+            line_description(line),
+            transaction_type,
+            category_id,
+            account_from_id,
+            account_to_id,
+            line.amount.abs(),
+            None,
+            None,
+            false,
+            Some(notes),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+        if let Some(reference) = &line.reference {
+            set_transaction_external_refs(state, &id, company_id, None, Some(reference)).await?;
+        }
+        summary.imported += 1;
+    }
+    Ok(summary)
+}
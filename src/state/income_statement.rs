@@ -0,0 +1,332 @@
+// Income statement: confirmed income and expense per category and month,
+// next to the same months a year before. Months are the periods of the
+// company's fiscal calendar, calendar months unless it has one. It reads
+// `monthly_summaries`, one document per company and period that is dropped
+// whenever a transaction of that period changes and rebuilt on the next
+// read, so a year of history costs a couple dozen small reads instead of
+// scanning the transactions.
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use serde::Deserialize;
+
+use crate::models::{FlowType, MonthlySummary, MonthlySummaryLine, Transaction, TransactionType};
+
+use super::{
+    AppState, FiscalPeriod, companies::company_default_currency, company_fiscal_calendar,
+    fiscal_period_year_before, fiscal_periods_until,
+};
+
+/// Months shown when the caller does not choose.
+pub const INCOME_STATEMENT_DEFAULT_MONTHS: u32 = 12;
+/// Longest window accepted, in months.
+pub const INCOME_STATEMENT_MAX_MONTHS: u32 = 24;
+
+/// `YYYY-MM` of the month `at` falls in.
+fn month_key(at: chrono::DateTime<Utc>) -> String {
+    at.format("%Y-%m").to_string()
+}
+
+/// Drops the summary of the period `tx` is dated in. Drafts are not part of
+/// the summary, but confirming one goes through here with the confirmed copy.
+pub async fn invalidate_monthly_summary(state: &AppState, tx: &Transaction) -> Result<()> {
+    if !tx.is_confirmed {
+        return Ok(());
+    }
+    // Summaries built before they carried their bounds are calendar months.
+    state
+        .monthly_summaries
+        .delete_many(doc! {
+            "company_id": tx.company_id,
+            "$or": [
+                { "start": { "$lte": tx.date }, "end": { "$gt": tx.date } },
+                { "start": { "$exists": false }, "month": month_key(tx.date.to_chrono()) },
+            ],
+        })
+        .await?;
+    Ok(())
+}
+
+/// Drops every summary of the company, after changes that touch many
+/// transactions at once.
+pub async fn clear_monthly_summaries(state: &AppState, company_id: &ObjectId) -> Result<()> {
+    state
+        .monthly_summaries
+        .delete_many(doc! { "company_id": company_id })
+        .await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct SummaryRow {
+    category_id: ObjectId,
+    transaction_type: TransactionType,
+    amount: f64,
+}
+
+/// Sums the confirmed income and expense of the period per category and
+/// stores the result. Transfers move money between the company's own
+/// accounts, so they stay out. The sums are read from the primary: a
+/// summary built on a lagging secondary would stay stale until the next
+/// change of the period.
+async fn build_monthly_summary(
+    state: &AppState,
+    company_id: &ObjectId,
+    period: &FiscalPeriod,
+) -> Result<MonthlySummary> {
+    let pipeline = vec![
+        doc! { "$match": {
+            "company_id": company_id,
+            "is_confirmed": { "$ne": false },
+            "transaction_type": { "$in": ["income", "expense"] },
+            "date": { "$gte": period.start_date(), "$lt": period.end_date() },
+        }},
+        doc! { "$group": {
+            "_id": { "category_id": "$category_id", "transaction_type": "$transaction_type" },
+            "amount": { "$sum": "$amount" },
+        }},
+        doc! { "$project": {
+            "_id": 0,
+            "category_id": "$_id.category_id",
+            "transaction_type": "$_id.transaction_type",
+            "amount": { "$toDouble": "$amount" },
+        }},
+    ];
+    let rows: Vec<SummaryRow> = state
+        .transactions
+        .aggregate(pipeline)
+        .with_type::<SummaryRow>()
+        .await?
+        .try_collect()
+        .await?;
+    let lines = rows
+        .into_iter()
+        .map(|row| MonthlySummaryLine {
+            category_id: row.category_id,
+            flow_type: match row.transaction_type {
+                TransactionType::Income => FlowType::Income,
+                _ => FlowType::Expense,
+            },
+            amount: row.amount,
+        })
+        .collect();
+
+    let summary = MonthlySummary {
+        id: None,
+        company_id: *company_id,
+        month: period.key.clone(),
+        start: Some(period.start_date()),
+        end: Some(period.end_date()),
+        lines,
+        built_at: DateTime::now(),
+    };
+    state
+        .monthly_summaries
+        .replace_one(
+            doc! { "company_id": company_id, "month": &summary.month },
+            &summary,
+        )
+        .upsert(true)
+        .await?;
+    Ok(summary)
+}
+
+/// The stored summary of the period, built when missing. Reads with the
+/// report read preference.
+pub(super) async fn monthly_summary(
+    state: &AppState,
+    company_id: &ObjectId,
+    period: &FiscalPeriod,
+) -> Result<MonthlySummary> {
+    let stored = state
+        .for_reports(&state.monthly_summaries)
+        .find_one(doc! { "company_id": company_id, "month": &period.key })
+        .await?;
+    match stored {
+        Some(summary) => Ok(summary),
+        None => build_monthly_summary(state, company_id, period).await,
+    }
+}
+
+/// One category of the statement: its amount in each month of the window
+/// and in the same month a year before.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomeStatementLine {
+    pub category_id: ObjectId,
+    pub flow_type: FlowType,
+    pub amounts: Vec<f64>,
+    pub previous: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomeStatement {
+    pub currency: String,
+    /// Key of each period, oldest first; the last is the current one.
+    /// `YYYY-MM` for calendar months.
+    pub months: Vec<String>,
+    /// The same months a year before.
+    pub previous_months: Vec<String>,
+    pub lines: Vec<IncomeStatementLine>,
+}
+
+impl IncomeStatement {
+    /// Per month total of the lines of `flow_type`, in the window and a
+    /// year before.
+    pub fn totals(&self, flow_type: &FlowType) -> (Vec<f64>, Vec<f64>) {
+        let mut current = vec![0.0; self.months.len()];
+        let mut previous = vec![0.0; self.months.len()];
+        for line in self.lines.iter().filter(|l| &l.flow_type == flow_type) {
+            for (i, amount) in line.amounts.iter().enumerate() {
+                current[i] += amount;
+            }
+            for (i, amount) in line.previous.iter().enumerate() {
+                previous[i] += amount;
+            }
+        }
+        (current, previous)
+    }
+}
+
+/// Lays the summaries of the window (`current`) and of the year before
+/// (`previous`), both oldest first, out as one line per category and flow.
+fn statement_lines(
+    current: &[MonthlySummary],
+    previous: &[MonthlySummary],
+) -> Vec<IncomeStatementLine> {
+    let months = current.len();
+    let mut lines: Vec<IncomeStatementLine> = Vec::new();
+    for (is_previous, summaries) in [(false, current), (true, previous)] {
+        for (i, summary) in summaries.iter().enumerate() {
+            for entry in &summary.lines {
+                let index = match lines.iter().position(|line| {
+                    line.category_id == entry.category_id && line.flow_type == entry.flow_type
+                }) {
+                    Some(index) => index,
+                    None => {
+                        lines.push(IncomeStatementLine {
+                            category_id: entry.category_id,
+                            flow_type: entry.flow_type.clone(),
+                            amounts: vec![0.0; months],
+                            previous: vec![0.0; months],
+                        });
+                        lines.len() - 1
+                    }
+                };
+                let line = &mut lines[index];
+                if is_previous {
+                    line.previous[i] += entry.amount;
+                } else {
+                    line.amounts[i] += entry.amount;
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Income statement of the `months` periods up to and including the one
+/// containing `now`, each compared with the same period of the fiscal year
+/// before. Amounts are added up as recorded, like the other company figures.
+pub async fn income_statement(
+    state: &AppState,
+    company_id: &ObjectId,
+    months: u32,
+    now: DateTime,
+) -> Result<IncomeStatement> {
+    let months = months.clamp(1, INCOME_STATEMENT_MAX_MONTHS);
+    let currency = company_default_currency(state, company_id).await?;
+    let calendar = company_fiscal_calendar(state, company_id).await?;
+
+    let mut current = Vec::new();
+    let mut previous = Vec::new();
+    for period in fiscal_periods_until(&calendar, now.to_chrono(), months) {
+        current.push(monthly_summary(state, company_id, &period).await?);
+        let year_before = fiscal_period_year_before(&calendar, &period);
+        previous.push(monthly_summary(state, company_id, &year_before).await?);
+    }
+    Ok(IncomeStatement {
+        currency,
+        months: current.iter().map(|s| s.month.clone()).collect(),
+        previous_months: previous.iter().map(|s| s.month.clone()).collect(),
+        lines: statement_lines(&current, &previous),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(month: &str, lines: &[(ObjectId, FlowType, f64)]) -> MonthlySummary {
+        MonthlySummary {
+            id: None,
+            company_id: ObjectId::new(),
+            month: month.to_string(),
+            start: None,
+            end: None,
+            lines: lines
+                .iter()
+                .map(|(category_id, flow_type, amount)| MonthlySummaryLine {
+                    category_id: *category_id,
+                    flow_type: flow_type.clone(),
+                    amount: *amount,
+                })
+                .collect(),
+            built_at: DateTime::now(),
+        }
+    }
+
+    #[test]
+    fn lines_line_up_each_month_with_the_year_before() {
+        let (sales, rent) = (ObjectId::new(), ObjectId::new());
+        let current = [
+            summary("2025-02", &[(sales, FlowType::Income, 100.0)]),
+            summary(
+                "2025-03",
+                &[
+                    (sales, FlowType::Income, 150.0),
+                    (rent, FlowType::Expense, 40.0),
+                ],
+            ),
+        ];
+        let previous = [
+            summary("2024-02", &[(rent, FlowType::Expense, 30.0)]),
+            summary("2024-03", &[]),
+        ];
+        let statement = IncomeStatement {
+            currency: "MXN".into(),
+            months: vec!["2025-02".into(), "2025-03".into()],
+            previous_months: vec!["2024-02".into(), "2024-03".into()],
+            lines: statement_lines(&current, &previous),
+        };
+
+        assert_eq!(statement.lines.len(), 2);
+        assert_eq!(statement.lines[0].amounts, vec![100.0, 150.0]);
+        assert_eq!(statement.lines[0].previous, vec![0.0, 0.0]);
+        assert_eq!(statement.lines[1].amounts, vec![0.0, 40.0]);
+        assert_eq!(statement.lines[1].previous, vec![30.0, 0.0]);
+        assert_eq!(
+            statement.totals(&FlowType::Expense),
+            (vec![0.0, 40.0], vec![30.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn months_are_calendar_months() {
+        use crate::models::FiscalCalendar;
+        use crate::state::{fiscal_period, previous_fiscal_period};
+        use chrono::TimeZone;
+
+        let calendar = FiscalCalendar::default();
+        let at = Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap();
+        let period = fiscal_period(&calendar, at);
+        assert_eq!(period.key, month_key(at));
+        assert_eq!(month_key(period.end), "2025-01");
+        assert_eq!(
+            fiscal_period_year_before(&calendar, &period).key,
+            "2023-12"
+        );
+        assert_eq!(previous_fiscal_period(&calendar, &period).key, "2024-11");
+    }
+}
@@ -0,0 +1,618 @@
+// Referential integrity: which documents still point at a record, so deletes
+// can be refused (or turned into an archive) with an exact reason.
+
+use anyhow::{Result, bail};
+use bson::{Bson, Document, doc, oid::ObjectId};
+use futures::stream::TryStreamExt;
+use mongodb::{Collection, bson::DateTime};
+use serde::Serialize;
+use std::time::SystemTime;
+
+use crate::{
+    models::FlowType,
+    state::{AppState, clear_monthly_summaries, create_category, rebuild_company_category_usage},
+};
+
+/// Records whose deletion is guarded by [`find_dependencies`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityEntity {
+    Account,
+    Category,
+    Contact,
+    Company,
+}
+
+impl IntegrityEntity {
+    /// Parses the plural path segment used by the admin routes.
+    pub fn from_slug(value: &str) -> Option<Self> {
+        match value {
+            "accounts" => Some(Self::Account),
+            "categories" => Some(Self::Category),
+            "contacts" => Some(Self::Contact),
+            "companies" => Some(Self::Company),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Account => "accounts",
+            Self::Category => "categories",
+            Self::Contact => "contacts",
+            Self::Company => "companies",
+        }
+    }
+
+    /// Only records with an `is_active` flag can be archived instead of
+    /// deleted.
+    pub fn can_archive(&self) -> bool {
+        matches!(self, Self::Account | Self::Company)
+    }
+}
+
+/// Documents of one collection that reference the record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dependency {
+    pub collection: &'static str,
+    /// Spanish name of the collection, for the UI.
+    pub label: &'static str,
+    pub count: u64,
+}
+
+/// Appends a [`Dependency`] when `filter` matches any document.
+async fn count_dependents<T: Send + Sync>(
+    found: &mut Vec<Dependency>,
+    collection: &Collection<T>,
+    (name, label): (&'static str, &'static str),
+    filter: Document,
+) -> Result<()> {
+    let count = collection.count_documents(filter).await?;
+    if count > 0 {
+        found.push(Dependency {
+            collection: name,
+            label,
+            count,
+        });
+    }
+    Ok(())
+}
+
+const TRANSACTIONS: (&str, &str) = ("transactions", "Movimientos");
+const RECURRING_PLANS: (&str, &str) = ("recurring_plans", "Planes recurrentes");
+const PLANNED_ENTRIES: (&str, &str) = ("planned_entries", "Compromisos");
+const ARCHIVED_PLANNED_ENTRIES: (&str, &str) =
+    ("planned_entries_archive", "Compromisos archivados");
+const ORDERS: (&str, &str) = ("service_orders", "Órdenes de servicio");
+const PROJECTS: (&str, &str) = ("projects", "Proyectos");
+
+/// Documents that still reference the record, one entry per collection with
+/// at least one match. Lookups are scoped to `company_id`, so another
+/// tenant's (or orphaned) data never blocks a record; for a company it is
+/// the company itself.
+///
+/// Recurring plans are soft-deleted (`is_active: false`), so only active
+/// plans count as a reference.
+pub async fn find_dependencies(
+    state: &AppState,
+    entity: IntegrityEntity,
+    id: &ObjectId,
+    company_id: &ObjectId,
+) -> Result<Vec<Dependency>> {
+    let mut found = Vec::new();
+    match entity {
+        IntegrityEntity::Account => {
+            count_dependents(
+                &mut found,
+                &state.transactions,
+                TRANSACTIONS,
+                doc! { "company_id": company_id, "$or": [
+                    { "account_from_id": id },
+                    { "account_to_id": id }
+                ]},
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.recurring_plans,
+                RECURRING_PLANS,
+                doc! { "company_id": company_id, "account_expected_id": id, "is_active": true },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries,
+                PLANNED_ENTRIES,
+                doc! { "company_id": company_id, "account_expected_id": id },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries_archive,
+                ARCHIVED_PLANNED_ENTRIES,
+                doc! { "company_id": company_id, "account_expected_id": id },
+            )
+            .await?;
+        }
+        IntegrityEntity::Category | IntegrityEntity::Contact => {
+            let field = if entity == IntegrityEntity::Category {
+                "category_id"
+            } else {
+                "contact_id"
+            };
+            count_dependents(
+                &mut found,
+                &state.transactions,
+                TRANSACTIONS,
+                doc! { "company_id": company_id, field: id },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.recurring_plans,
+                RECURRING_PLANS,
+                doc! { "company_id": company_id, field: id, "is_active": true },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries,
+                PLANNED_ENTRIES,
+                doc! { "company_id": company_id, field: id },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries_archive,
+                ARCHIVED_PLANNED_ENTRIES,
+                doc! { "company_id": company_id, field: id },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.orders,
+                ORDERS,
+                doc! { "company_id": company_id, field: id },
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.projects,
+                PROJECTS,
+                doc! { "company_id": company_id, field: id },
+            )
+            .await?;
+            if entity == IntegrityEntity::Category {
+                count_dependents(
+                    &mut found,
+                    &state.categories,
+                    ("categories", "Subcategorías"),
+                    doc! { "company_id": company_id, "parent_id": id },
+                )
+                .await?;
+            }
+        }
+        IntegrityEntity::Company => {
+            let filter = doc! { "company_id": id };
+            count_dependents(
+                &mut found,
+                &state.accounts,
+                ("accounts", "Cuentas"),
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.categories,
+                ("categories", "Categorías"),
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.contacts,
+                ("contacts", "Contactos"),
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.recurring_plans,
+                RECURRING_PLANS,
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries,
+                PLANNED_ENTRIES,
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.planned_entries_archive,
+                ARCHIVED_PLANNED_ENTRIES,
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.transactions,
+                TRANSACTIONS,
+                filter.clone(),
+            )
+            .await?;
+            count_dependents(
+                &mut found,
+                &state.forecasts,
+                ("forecasts", "Proyecciones"),
+                filter,
+            )
+            .await?;
+        }
+    }
+    Ok(found)
+}
+
+/// Archives (`archived: true`) or restores a record by flipping its
+/// `is_active` flag. Fails for entities without one.
+pub async fn set_archived(
+    state: &AppState,
+    entity: IntegrityEntity,
+    id: &ObjectId,
+    archived: bool,
+) -> Result<()> {
+    let update = doc! { "$set": {
+        "is_active": !archived,
+        "updated_at": DateTime::from_system_time(SystemTime::now())
+    } };
+    match entity {
+        IntegrityEntity::Account => {
+            state
+                .accounts
+                .update_one(doc! { "_id": id }, update)
+                .await?;
+        }
+        IntegrityEntity::Company => {
+            state
+                .companies
+                .update_one(doc! { "_id": id }, update)
+                .await?;
+        }
+        IntegrityEntity::Category | IntegrityEntity::Contact => {
+            bail!("{} cannot be archived", entity.as_str());
+        }
+    }
+    Ok(())
+}
+
+/// Kind of record a dangling reference points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanTarget {
+    Account,
+    Category,
+    Contact,
+}
+
+impl OrphanTarget {
+    pub fn collection(&self) -> &'static str {
+        match self {
+            Self::Account => "accounts",
+            Self::Category => "categories",
+            Self::Contact => "contacts",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Account => "Cuentas",
+            Self::Category => "Categorías",
+            Self::Contact => "Contactos",
+        }
+    }
+}
+
+/// Documents of one collection whose `field` points at a missing record.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrphanedReference {
+    pub target: OrphanTarget,
+    pub collection: &'static str,
+    /// Spanish name of the collection, for the UI.
+    pub label: &'static str,
+    pub field: &'static str,
+    pub count: u64,
+    /// The missing ids, sorted.
+    #[serde(serialize_with = "serialize_hex_ids")]
+    pub missing_ids: Vec<ObjectId>,
+}
+
+fn serialize_hex_ids<S: serde::Serializer>(
+    ids: &[ObjectId],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(ids.iter().map(|id| id.to_hex()))
+}
+
+/// Orphaned references of one kind, as reported by
+/// [`find_orphaned_references`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrphanGroup {
+    pub target: OrphanTarget,
+    pub label: &'static str,
+    pub count: u64,
+    pub references: Vec<OrphanedReference>,
+}
+
+/// Placeholder category orphaned category references are reassigned to.
+pub const PLACEHOLDER_CATEGORY: &str = "Sin categoría";
+
+/// Every reference checked by [`find_orphaned_references`]:
+/// (target, (collection, label), field).
+const ORPHAN_CHECKS: &[(OrphanTarget, (&str, &str), &str)] = &[
+    (OrphanTarget::Category, TRANSACTIONS, "category_id"),
+    (OrphanTarget::Category, RECURRING_PLANS, "category_id"),
+    (OrphanTarget::Category, PLANNED_ENTRIES, "category_id"),
+    (OrphanTarget::Category, ORDERS, "category_id"),
+    (OrphanTarget::Category, PROJECTS, "category_id"),
+    (
+        OrphanTarget::Category,
+        ("categories", "Subcategorías"),
+        "parent_id",
+    ),
+    (OrphanTarget::Contact, TRANSACTIONS, "contact_id"),
+    (OrphanTarget::Contact, RECURRING_PLANS, "contact_id"),
+    (OrphanTarget::Contact, PLANNED_ENTRIES, "contact_id"),
+    (OrphanTarget::Contact, ORDERS, "contact_id"),
+    (OrphanTarget::Contact, PROJECTS, "contact_id"),
+    (OrphanTarget::Account, TRANSACTIONS, "account_from_id"),
+    (OrphanTarget::Account, TRANSACTIONS, "account_to_id"),
+    (
+        OrphanTarget::Account,
+        RECURRING_PLANS,
+        "account_expected_id",
+    ),
+    (
+        OrphanTarget::Account,
+        PLANNED_ENTRIES,
+        "account_expected_id",
+    ),
+    (OrphanTarget::Account, ORDERS, "account_id"),
+];
+
+/// Counts, per missing id, the company's documents whose `field` holds an id
+/// with no matching record in the target collection.
+async fn missing_references<T: Send + Sync>(
+    collection: &Collection<T>,
+    company_id: &ObjectId,
+    field: &str,
+    target: OrphanTarget,
+) -> Result<Vec<(ObjectId, u64)>> {
+    let pipeline = vec![
+        doc! { "$match": { "company_id": company_id, field: { "$type": "objectId" } } },
+        doc! { "$lookup": {
+            "from": target.collection(),
+            "localField": field,
+            "foreignField": "_id",
+            "as": "_target",
+        }},
+        doc! { "$match": { "_target": { "$size": 0 } } },
+        doc! { "$group": { "_id": format!("${field}"), "count": { "$sum": 1 } } },
+        doc! { "$sort": { "_id": 1 } },
+    ];
+    let mut cursor = collection.aggregate(pipeline).await?;
+    let mut missing = Vec::new();
+    while let Some(row) = cursor.try_next().await? {
+        let (Ok(id), Some(count)) = (
+            row.get_object_id("_id"),
+            row.get("count").and_then(Bson::as_i32),
+        ) else {
+            continue;
+        };
+        missing.push((id, count as u64));
+    }
+    Ok(missing)
+}
+
+/// References of the company's documents to accounts, categories or contacts
+/// that no longer exist (a delete that skipped [`find_dependencies`], a
+/// partial import), grouped by the kind of record that is missing. Kinds
+/// without orphans are left out.
+pub async fn find_orphaned_references(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Vec<OrphanGroup>> {
+    let mut groups: Vec<OrphanGroup> = Vec::new();
+    for &(target, (collection, label), field) in ORPHAN_CHECKS {
+        let missing = match collection {
+            "transactions" => {
+                missing_references(&state.transactions, company_id, field, target).await?
+            }
+            "recurring_plans" => {
+                missing_references(&state.recurring_plans, company_id, field, target).await?
+            }
+            "planned_entries" => {
+                missing_references(&state.planned_entries, company_id, field, target).await?
+            }
+            "service_orders" => {
+                missing_references(&state.orders, company_id, field, target).await?
+            }
+            "projects" => missing_references(&state.projects, company_id, field, target).await?,
+            _ => missing_references(&state.categories, company_id, field, target).await?,
+        };
+        if missing.is_empty() {
+            continue;
+        }
+        let reference = OrphanedReference {
+            target,
+            collection,
+            label,
+            field,
+            count: missing.iter().map(|(_, count)| count).sum(),
+            missing_ids: missing.into_iter().map(|(id, _)| id).collect(),
+        };
+        match groups.iter_mut().find(|group| group.target == target) {
+            Some(group) => {
+                group.count += reference.count;
+                group.references.push(reference);
+            }
+            None => groups.push(OrphanGroup {
+                target,
+                label: target.label(),
+                count: reference.count,
+                references: vec![reference],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+/// Documents changed by [`fix_orphaned_categories`] or
+/// [`clear_orphaned_contacts`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OrphanFix {
+    pub updated: u64,
+    /// Placeholder categories created for the reassignment.
+    pub created_categories: u64,
+}
+
+/// The company's [`PLACEHOLDER_CATEGORY`] of `flow_type`, created when
+/// missing.
+async fn placeholder_category(
+    state: &AppState,
+    company_id: &ObjectId,
+    flow_type: FlowType,
+    fix: &mut OrphanFix,
+) -> Result<ObjectId> {
+    let existing = state
+        .categories
+        .find_one(doc! {
+            "company_id": company_id,
+            "name": PLACEHOLDER_CATEGORY,
+            "flow_type": flow_type.as_str(),
+        })
+        .await?;
+    if let Some(id) = existing.and_then(|category| category.id) {
+        return Ok(id);
+    }
+    fix.created_categories += 1;
+    create_category(
+        state,
+        company_id,
+        PLACEHOLDER_CATEGORY,
+        flow_type,
+        None,
+        Some("Creada por la revisión de referencias huérfanas.".to_string()),
+    )
+    .await
+}
+
+fn now() -> DateTime {
+    DateTime::from_system_time(SystemTime::now())
+}
+
+/// Documents of `collection` matching `filter`.
+async fn count_in(state: &AppState, collection: &str, filter: Document) -> Result<u64> {
+    Ok(match collection {
+        "transactions" => state.transactions.count_documents(filter).await?,
+        "recurring_plans" => state.recurring_plans.count_documents(filter).await?,
+        "planned_entries" => state.planned_entries.count_documents(filter).await?,
+        "service_orders" => state.orders.count_documents(filter).await?,
+        "projects" => state.projects.count_documents(filter).await?,
+        _ => state.categories.count_documents(filter).await?,
+    })
+}
+
+/// Applies `update` to the documents of `collection` matching `filter`.
+async fn update_in(
+    state: &AppState,
+    collection: &str,
+    filter: Document,
+    update: Document,
+) -> Result<u64> {
+    let result = match collection {
+        "transactions" => state.transactions.update_many(filter, update).await?,
+        "recurring_plans" => state.recurring_plans.update_many(filter, update).await?,
+        "planned_entries" => state.planned_entries.update_many(filter, update).await?,
+        "service_orders" => state.orders.update_many(filter, update).await?,
+        "projects" => state.projects.update_many(filter, update).await?,
+        _ => state.categories.update_many(filter, update).await?,
+    };
+    Ok(result.modified_count)
+}
+
+/// Reassigns required category references that point at a missing category
+/// to the company's [`PLACEHOLDER_CATEGORY`] of the matching flow type
+/// (transfers count as expenses), creating it when needed. Optional ones
+/// (orders, projects) are cleared, and subcategories whose parent is gone
+/// become top-level.
+pub async fn fix_orphaned_categories(state: &AppState, company_id: &ObjectId) -> Result<OrphanFix> {
+    let mut fix = OrphanFix::default();
+    let groups = find_orphaned_references(state, company_id).await?;
+    let Some(group) = groups
+        .into_iter()
+        .find(|group| group.target == OrphanTarget::Category)
+    else {
+        return Ok(fix);
+    };
+    for reference in group.references {
+        let filter = doc! {
+            "company_id": company_id,
+            reference.field: { "$in": &reference.missing_ids },
+        };
+        if matches!(
+            reference.collection,
+            "service_orders" | "projects" | "categories"
+        ) {
+            let update =
+                doc! { "$unset": { reference.field: "" }, "$set": { "updated_at": now() } };
+            fix.updated += update_in(state, reference.collection, filter, update).await?;
+            continue;
+        }
+        for flow_type in [FlowType::Income, FlowType::Expense] {
+            let mut filter = filter.clone();
+            if reference.collection == "transactions" {
+                let kind = match flow_type {
+                    FlowType::Income => Bson::from("income"),
+                    FlowType::Expense => Bson::from(doc! { "$ne": "income" }),
+                };
+                filter.insert("transaction_type", kind);
+            } else {
+                filter.insert("flow_type", flow_type.as_str());
+            }
+            if count_in(state, reference.collection, filter.clone()).await? == 0 {
+                continue;
+            }
+            let placeholder = placeholder_category(state, company_id, flow_type, &mut fix).await?;
+            let update = doc! { "$set": { "category_id": placeholder, "updated_at": now() } };
+            fix.updated += update_in(state, reference.collection, filter, update).await?;
+        }
+    }
+    // Transactions moved to the placeholder change their category in the
+    // income statement and in the category suggestions of their accounts.
+    clear_monthly_summaries(state, company_id).await?;
+    rebuild_company_category_usage(state, company_id).await?;
+    Ok(fix)
+}
+
+/// Clears (`$unset`) every contact reference that points at a missing
+/// contact; the contact is optional everywhere.
+pub async fn clear_orphaned_contacts(state: &AppState, company_id: &ObjectId) -> Result<OrphanFix> {
+    let mut fix = OrphanFix::default();
+    let groups = find_orphaned_references(state, company_id).await?;
+    let Some(group) = groups
+        .into_iter()
+        .find(|group| group.target == OrphanTarget::Contact)
+    else {
+        return Ok(fix);
+    };
+    for reference in group.references {
+        let filter = doc! {
+            "company_id": company_id,
+            "contact_id": { "$in": &reference.missing_ids },
+        };
+        let update = doc! { "$unset": { "contact_id": "" }, "$set": { "updated_at": now() } };
+        fix.updated += update_in(state, reference.collection, filter, update).await?;
+    }
+    Ok(fix)
+}
@@ -0,0 +1,112 @@
+// Sign-in by emailed link. A company can let its staff ask for a one-time
+// link instead of typing their TOTP code, or on top of it. Links last
+// `LOGIN_LINK_TTL_SECONDS`, work once and only their hash is stored.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use mongodb::bson::{DateTime, doc};
+use rand::RngCore;
+
+use crate::models::{LoginLink, LoginLinkMode, UserRole};
+
+use super::{
+    AppState, UserWithCompany, api_tokens::hash_token, create_session, find_user,
+    get_company_by_id, get_user_by_id,
+};
+
+pub const LOGIN_LINK_TTL_SECONDS: u64 = 15 * 60;
+
+/// How `user` may sign in with a link: their company's setting when they
+/// are staff in every company they belong to, `Off` for anyone who is an
+/// admin or auditor anywhere.
+pub async fn login_link_mode(state: &AppState, user: &UserWithCompany) -> Result<LoginLinkMode> {
+    let staff_only = user.role == UserRole::Staff
+        && user
+            .company_roles
+            .iter()
+            .all(|role| *role == UserRole::Staff);
+    if !staff_only || user.is_superadmin {
+        return Ok(LoginLinkMode::Off);
+    }
+    Ok(get_company_by_id(state, &user.company_id)
+        .await?
+        .map(|company| company.login_links)
+        .unwrap_or_default())
+}
+
+/// Creates a sign-in link for `username`, replacing any earlier one, and
+/// returns the user with the token. Unknown, deactivated and not allowed
+/// users get `None`. `next` must already be a safe return path.
+pub async fn request_login_link(
+    state: &AppState,
+    username: &str,
+    next: Option<String>,
+) -> Result<Option<(UserWithCompany, String)>> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Ok(None);
+    }
+    let Some(user) = find_user(state, username).await? else {
+        return Ok(None);
+    };
+    if !user.is_active || login_link_mode(state, &user).await?.is_off() {
+        return Ok(None);
+    }
+    state
+        .login_links
+        .delete_many(doc! { "user_id": user.id })
+        .await?;
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = BASE32_NOPAD.encode(&bytes);
+    state
+        .login_links
+        .insert_one(LoginLink {
+            id: None,
+            user_id: user.id,
+            token_hash: hash_token(&token),
+            next,
+            expires_at: DateTime::from_system_time(
+                SystemTime::now() + Duration::from_secs(LOGIN_LINK_TTL_SECONDS),
+            ),
+        })
+        .await?;
+    Ok(Some((user, token)))
+}
+
+/// What a used sign-in link opened.
+pub struct RedeemedLoginLink {
+    pub user: UserWithCompany,
+    pub session_token: String,
+    pub next: Option<String>,
+}
+
+/// Trades a sign-in link for a session. The link works once; an unknown or
+/// expired token gives `None`, and so does one whose user was deactivated
+/// or whose company stopped allowing links since it was sent.
+pub async fn redeem_login_link(state: &AppState, token: &str) -> Result<Option<RedeemedLoginLink>> {
+    let Some(link) = state
+        .login_links
+        .find_one_and_delete(doc! { "token_hash": hash_token(token) })
+        .await?
+    else {
+        return Ok(None);
+    };
+    if link.expires_at.to_system_time() <= SystemTime::now() {
+        return Ok(None);
+    }
+    let Some(user) = get_user_by_id(state, &link.user_id).await? else {
+        return Ok(None);
+    };
+    if !user.is_active || login_link_mode(state, &user).await?.is_off() {
+        return Ok(None);
+    }
+    let session_token = create_session(state, &user.username).await?;
+    Ok(Some(RedeemedLoginLink {
+        user,
+        session_token,
+        next: link.next,
+    }))
+}
@@ -0,0 +1,301 @@
+// Versioned schema migrations. Each one runs once per database, in version
+// order, and is recorded in `migrations`. They run at startup (unless
+// `MIGRATE_ON_STARTUP=0`) and through `cargo run --bin migrate`.
+//
+// Migrations must be idempotent: two instances starting at once may both run a
+// pending one before either records it.
+
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use futures::{future::BoxFuture, stream::TryStreamExt};
+use mongodb::{
+    Database,
+    bson::{DateTime, Document, doc},
+};
+
+use crate::models::{AppliedMigration, SequenceCounter};
+
+use super::category_suggestions::rebuild_category_usage;
+use super::sequences::{SequenceKind, format_reference, increment_sequence};
+
+const MIGRATIONS_COLLECTION: &str = "migrations";
+
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    /// Returns the documents changed, or that would change on a dry run.
+    run: for<'a> fn(&'a Database, bool) -> BoxFuture<'a, Result<u64>>,
+}
+
+/// Every migration, oldest first. New ones are appended with the next version.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "users_email_to_username",
+        run: users_email_to_username,
+    },
+    Migration {
+        version: 2,
+        name: "drop_sessions_without_user_id",
+        run: drop_sessions_without_user_id,
+    },
+    Migration {
+        version: 3,
+        name: "backfill_references",
+        run: backfill_references,
+    },
+    Migration {
+        version: 4,
+        name: "drop_plaintext_email_changes",
+        run: drop_plaintext_email_changes,
+    },
+    Migration {
+        version: 5,
+        name: "rebuild_category_usage",
+        run: rebuild_category_usage_counters,
+    },
+    Migration {
+        version: 6,
+        name: "drop_plaintext_portal_tokens",
+        run: drop_plaintext_portal_tokens,
+    },
+];
+
+/// A migration and when it was applied, if it was.
+pub struct MigrationStatus {
+    pub version: i32,
+    pub name: &'static str,
+    pub applied_at: Option<DateTime>,
+}
+
+/// A migration run by `run_migrations`.
+pub struct MigrationRun {
+    pub version: i32,
+    pub name: &'static str,
+    pub documents: u64,
+}
+
+pub async fn migration_status(db: &Database) -> Result<Vec<MigrationStatus>> {
+    let applied: Vec<AppliedMigration> = db
+        .collection::<AppliedMigration>(MIGRATIONS_COLLECTION)
+        // Reindex records share the collection under string ids.
+        .find(doc! { "_id": { "$type": "int" } })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name,
+            applied_at: applied
+                .iter()
+                .find(|record| record.version == migration.version)
+                .map(|record| record.applied_at),
+        })
+        .collect())
+}
+
+/// Runs the pending migrations in order, logging each one. A dry run only
+/// counts the documents each would change and records nothing. Stops at the
+/// first failure, leaving that migration and the later ones pending.
+pub async fn run_migrations(db: &Database, dry_run: bool) -> Result<Vec<MigrationRun>> {
+    let records = db.collection::<AppliedMigration>(MIGRATIONS_COLLECTION);
+    let pending: Vec<&Migration> = migration_status(db)
+        .await?
+        .iter()
+        .filter(|status| status.applied_at.is_none())
+        .filter_map(|status| MIGRATIONS.iter().find(|m| m.version == status.version))
+        .collect();
+
+    let mut runs = Vec::new();
+    for (done, migration) in pending.iter().enumerate() {
+        let label = if dry_run { "dry run" } else { "running" };
+        println!(
+            "[migrations] {label} {}/{}: {} {}",
+            done + 1,
+            pending.len(),
+            migration.version,
+            migration.name
+        );
+        let documents = (migration.run)(db, dry_run).await.with_context(|| {
+            format!("migration {} {} failed", migration.version, migration.name)
+        })?;
+        if !dry_run {
+            records
+                .insert_one(AppliedMigration {
+                    version: migration.version,
+                    name: migration.name.to_string(),
+                    applied_at: DateTime::now(),
+                    documents: documents as i64,
+                })
+                .await?;
+        }
+        println!(
+            "[migrations] {} {}: {documents} documents",
+            migration.version, migration.name
+        );
+        runs.push(MigrationRun {
+            version: migration.version,
+            name: migration.name,
+            documents,
+        });
+    }
+    Ok(runs)
+}
+
+/// Applies `update` to the documents matching `filter`, or counts them on a
+/// dry run.
+async fn update_matching(
+    db: &Database,
+    collection: &str,
+    filter: Document,
+    update: Document,
+    dry_run: bool,
+) -> Result<u64> {
+    let collection = db.collection::<Document>(collection);
+    if dry_run {
+        return Ok(collection.count_documents(filter).await?);
+    }
+    Ok(collection.update_many(filter, update).await?.modified_count)
+}
+
+/// The user login identifier moved from `email` to `username` (it was never a
+/// validated address, just a unique handle). Renames email-only docs and drops
+/// any stray `email` left on docs that already have a `username`.
+fn users_email_to_username(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        let renamed = update_matching(
+            db,
+            "users",
+            doc! { "email": { "$exists": true }, "username": { "$exists": false } },
+            doc! { "$rename": { "email": "username" } },
+            dry_run,
+        )
+        .await?;
+        let cleaned = update_matching(
+            db,
+            "users",
+            doc! { "email": { "$exists": true }, "username": { "$exists": true } },
+            doc! { "$unset": { "email": "" } },
+            dry_run,
+        )
+        .await?;
+        Ok(renamed + cleaned)
+    })
+}
+
+/// Sessions used to link to the user through `user_email` (the username).
+/// They are keyed by `user_id` now; legacy sessions are dropped so those users
+/// simply log in again.
+fn drop_sessions_without_user_id(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        let sessions = db.collection::<Document>("sessions");
+        let filter = doc! { "user_id": { "$exists": false } };
+        if dry_run {
+            return Ok(sessions.count_documents(filter).await?);
+        }
+        Ok(sessions.delete_many(filter).await?.deleted_count)
+    })
+}
+
+/// Numbers the transactions and forecasts created before references existed,
+/// oldest first within each company and year.
+fn backfill_references(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        let counters = db.collection::<SequenceCounter>("sequences");
+        let mut numbered = 0;
+        for (collection, date_field, kind) in [
+            ("transactions", "date", SequenceKind::Transaction),
+            ("forecasts", "generated_at", SequenceKind::Forecast),
+        ] {
+            let collection = db.collection::<Document>(collection);
+            let filter = doc! { "reference": { "$exists": false } };
+            if dry_run {
+                numbered += collection.count_documents(filter).await?;
+                continue;
+            }
+            let mut cursor = collection
+                .find(filter)
+                .sort(doc! { date_field: 1, "_id": 1 })
+                .await?;
+            while let Some(document) = cursor.try_next().await? {
+                let (Ok(id), Ok(company_id), Ok(date)) = (
+                    document.get_object_id("_id"),
+                    document.get_object_id("company_id"),
+                    document.get_datetime(date_field),
+                ) else {
+                    continue;
+                };
+                let year = date.to_chrono().year();
+                let number = increment_sequence(&counters, &company_id, kind, year).await?;
+                collection
+                    .update_one(
+                        doc! { "_id": id },
+                        doc! { "$set": { "reference": format_reference(kind, year, number) } },
+                    )
+                    .await?;
+                numbered += 1;
+            }
+        }
+        Ok(numbered)
+    })
+}
+
+/// Pending email changes used to keep their token in the clear. They are
+/// looked up by its hash now; the old ones are dropped and the users ask for
+/// the change again.
+fn drop_plaintext_email_changes(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        let changes = db.collection::<Document>("email_changes");
+        let filter = doc! { "token_hash": { "$exists": false } };
+        if dry_run {
+            return Ok(changes.count_documents(filter).await?);
+        }
+        Ok(changes.delete_many(filter).await?.deleted_count)
+    })
+}
+
+/// Account category usage went from being rebuilt per account on every write
+/// to counters moved by each write, which start from a full rebuild.
+fn rebuild_category_usage_counters(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        if dry_run {
+            return Ok(db
+                .collection::<Document>("account_category_usage")
+                .count_documents(doc! {})
+                .await?);
+        }
+        rebuild_category_usage(db).await
+    })
+}
+
+/// Portal links and sessions stored their tokens in the clear before only
+/// the hash was kept. Those contacts ask for a new link.
+fn drop_plaintext_portal_tokens(db: &Database, dry_run: bool) -> BoxFuture<'_, Result<u64>> {
+    Box::pin(async move {
+        let filter = doc! { "token_hash": { "$exists": false } };
+        let mut documents = 0;
+        for name in ["portal_links", "portal_sessions"] {
+            let collection = db.collection::<Document>(name);
+            documents += if dry_run {
+                collection.count_documents(filter.clone()).await?
+            } else {
+                collection.delete_many(filter.clone()).await?.deleted_count
+            };
+        }
+        Ok(documents)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MIGRATIONS;
+
+    #[test]
+    fn migration_versions_increase_without_gaps() {
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, idx as i32 + 1, "{}", migration.name);
+        }
+    }
+}
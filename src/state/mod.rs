@@ -1,15 +1,25 @@
 // state module: AppState, initialization, and re-exports of submodules.
 
 use anyhow::Result;
-use mongodb::{Client, Collection};
+use mongodb::{
+    Client, Collection,
+    options::{ClientOptions, SelectionCriteria},
+};
 use serde::Serialize;
-use std::{collections::HashMap, env, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, atomic::AtomicUsize},
+};
 use tokio::sync::Mutex;
 
+use crate::mailer::{Mailer, mailer_from_env};
 use crate::models::{
-    Account, Category, Company, ConceptStatus, Contact, Forecast, PlannedEntry, Project,
-    ProjectConcept, RecurringPlan, Resource, ResourceLog, ResourceUsage, ResourceUsageAllocation,
-    SatConfig, ServiceOrder, Session, Transaction, User, UserCompany,
+    AccessEvent, Account, AccountBalanceSnapshot, AccountGroup, AccountCategoryUsage, AccountValuation, ApiToken, BankConnection, Category,
+    Comment, Company, ConceptStatus, Contact, CustomFieldDefinition, EmailChange, Forecast, ImportDraft, ImportProfile, LoginLink,
+    MonthlySummary, Notification, PlannedEntry, PortalLink, PortalSession, Project, ProjectConcept, Receipt, RecurringPlan, RefreshToken,
+    RecurringPlanVersion, Resource, ResourceLog, ResourceUsage, ResourceUsageAllocation, SatConfig,
+    SequenceCounter, ServiceOrder, Session, SsoIdentity, SyncedBankTransaction, TaxProfile, TextReplacement, Transaction, User, UserCompany,
 };
 use bson::Document;
 
@@ -42,46 +52,183 @@ pub struct CfdiJob {
 
 pub type JobStore = Arc<Mutex<HashMap<String, CfdiJob>>>;
 
+mod access_log;
+mod account_access;
+mod account_groups;
+mod aging;
+mod api_tokens;
+mod balances;
+mod bank_sync;
+mod budgets;
+mod burn_rate;
+mod card_holds;
+mod cash_calendar;
+mod category_suggestions;
+mod chat_notifications;
+mod comments;
 mod companies;
+mod custom_fields;
+mod deposit_allocations;
+mod events;
+#[cfg(feature = "dev-factory")]
+mod factory;
 mod finance;
+mod fiscal_calendar;
+mod forecast_chart;
+mod fx_results;
+mod import_profiles;
+mod imports;
+mod income_statement;
+mod integrity;
+mod login_links;
+mod migrations;
 mod orders;
+mod offboarding;
+mod onboarding;
+mod option_bundle;
+mod overview;
+mod plan_imports;
+mod plan_input;
+mod plan_versions;
+mod planned_archive;
+mod portal;
 mod project_concepts;
+mod receipts;
+mod refunds;
+mod reindex;
+mod remember_me;
+mod report_reads;
+mod required_fields;
 mod projects;
 mod resource_logs;
 mod resource_usages;
 mod resources;
+mod retention;
 mod sat_configs;
 mod seed;
+mod seed_yaml;
+mod sequences;
+mod sso;
+mod storage;
+mod taxes;
+mod text_replace;
 mod users;
+mod valuations;
+mod webhooks;
 
+pub use access_log::*;
+pub use account_access::*;
+pub use account_groups::*;
+pub use aging::*;
+pub use api_tokens::*;
+pub use balances::*;
+pub use bank_sync::*;
+pub use budgets::*;
+pub use burn_rate::*;
+pub use card_holds::*;
+pub use cash_calendar::*;
+pub use category_suggestions::*;
+pub use chat_notifications::*;
+pub use comments::*;
 pub use companies::*;
+pub use custom_fields::*;
+pub use deposit_allocations::*;
+pub use events::*;
+#[cfg(feature = "dev-factory")]
+pub use factory::*;
 pub use finance::*;
+pub use fiscal_calendar::*;
+pub use forecast_chart::*;
+pub use fx_results::*;
+pub use import_profiles::*;
+pub use imports::*;
+pub use income_statement::*;
+pub use integrity::*;
+pub use login_links::*;
+pub use migrations::*;
 pub use orders::*;
+pub use offboarding::*;
+pub use onboarding::*;
+pub use option_bundle::*;
+pub use overview::*;
+pub use plan_imports::*;
+pub use plan_input::*;
+pub use plan_versions::*;
+pub use planned_archive::*;
+pub use portal::*;
 pub use project_concepts::*;
 pub use projects::*;
+pub use receipts::*;
+pub use refunds::*;
+pub use reindex::*;
+pub use remember_me::*;
+pub use report_reads::*;
+pub use required_fields::*;
 pub use resource_logs::*;
 pub use resource_usages::*;
 pub use resources::*;
+pub use retention::*;
 pub use sat_configs::*;
+pub use seed_yaml::*;
+pub use sequences::*;
+pub use sso::*;
+pub use storage::*;
+pub use taxes::*;
+pub use text_replace::*;
 pub use users::*;
+pub use valuations::*;
+pub use webhooks::*;
 
+/// Default session lifetime; see `session_ttl_seconds`.
 pub const SESSION_TTL_SECONDS: u64 = 60 * 60 * 24; // 1 day
 pub const PLANNED_MONTHS_AHEAD: u32 = 24;
 
 #[derive(Clone)]
 pub struct AppState {
     pub jobs: JobStore,
+    /// Background index rebuild started from the maintenance page.
+    pub reindex: ReindexStore,
+    /// Webhook deliveries queued or still retrying.
+    pub webhook_deliveries: Arc<AtomicUsize>,
+    pub events: tokio::sync::broadcast::Sender<CompanyEvent>,
     pub users: Collection<User>,
     pub user_companies: Collection<UserCompany>,
     pub companies: Collection<Company>,
     pub sessions: Collection<Session>,
+    pub refresh_tokens: Collection<RefreshToken>,
+    pub login_links: Collection<LoginLink>,
+    pub email_changes: Collection<EmailChange>,
+    pub sso_identities: Collection<SsoIdentity>,
+    pub api_tokens: Collection<ApiToken>,
+    pub access_events: Collection<AccessEvent>,
     pub accounts: Collection<Account>,
+    pub account_groups: Collection<AccountGroup>,
+    pub balance_snapshots: Collection<AccountBalanceSnapshot>,
+    pub account_valuations: Collection<AccountValuation>,
+    pub category_usage: Collection<AccountCategoryUsage>,
+    pub monthly_summaries: Collection<MonthlySummary>,
+    pub bank_connections: Collection<BankConnection>,
+    pub bank_transactions: Collection<SyncedBankTransaction>,
     pub categories: Collection<Category>,
     pub contacts: Collection<Contact>,
     pub recurring_plans: Collection<RecurringPlan>,
+    pub plan_versions: Collection<RecurringPlanVersion>,
     pub planned_entries: Collection<PlannedEntry>,
+    /// Old covered and cancelled entries moved out of `planned_entries`.
+    pub planned_entries_archive: Collection<PlannedEntry>,
     pub transactions: Collection<Transaction>,
+    pub text_replacements: Collection<TextReplacement>,
+    pub import_profiles: Collection<ImportProfile>,
+    pub import_drafts: Collection<ImportDraft>,
+    pub comments: Collection<Comment>,
+    pub notifications: Collection<Notification>,
+    pub receipts: Collection<Receipt>,
+    pub custom_fields: Collection<CustomFieldDefinition>,
+    pub tax_profiles: Collection<TaxProfile>,
     pub forecasts: Collection<Forecast>,
+    pub sequences: Collection<SequenceCounter>,
+    pub portal_links: Collection<PortalLink>,
+    pub portal_sessions: Collection<PortalSession>,
     pub cfdis: Collection<Document>,
     pub sat_configs: Collection<SatConfig>,
     pub orders: Collection<ServiceOrder>,
@@ -92,6 +239,24 @@ pub struct AppState {
     pub resource_logs: Collection<ResourceLog>,
     pub resource_usages: Collection<ResourceUsage>,
     pub resource_usage_allocations: Collection<ResourceUsageAllocation>,
+    /// Read preference of report queries; `None` reads from the primary.
+    /// See `AppState::for_reports`.
+    pub report_reads: Option<SelectionCriteria>,
+    /// Outgoing email; see `mailer.rs`.
+    pub mailer: Arc<dyn Mailer>,
+    /// Lets webhooks go to loopback and private addresses, for receivers that
+    /// run next to the app (`WEBHOOKS_ALLOW_PRIVATE_NETWORKS=1`).
+    pub webhooks_private_networks: bool,
+    /// Bearer token Prometheus scrapes `/metrics` with (`METRICS_TOKEN`).
+    pub metrics_token: Option<String>,
+}
+
+/// Drops the whole database behind `state`. Only meant for throwaway
+/// databases such as demo sandboxes.
+pub async fn drop_database(state: &AppState) -> Result<()> {
+    let db_name = state.users.namespace().db;
+    state.users.client().database(&db_name).drop().await?;
+    Ok(())
 }
 
 pub async fn init_state() -> Result<AppState> {
@@ -103,29 +268,17 @@ pub async fn init_state() -> Result<AppState> {
 
 pub async fn init_state_with_db_name(uri: &str, db_name: &str) -> Result<AppState> {
     println!("Connecting to MongoDB at {}", uri);
-    let client = Client::with_uri_str(uri).await?;
+    let mut options = ClientOptions::parse(uri).await?;
+    options.command_event_handler = Some(crate::metrics::mongo_command_handler(
+        crate::metrics::slow_query_threshold_from_env(),
+    ));
+    let client = Client::with_options(options)?;
     let db = client.database(&db_name);
 
     seed::ensure_collections(&db).await?;
-
-    // One-time migration: the user login identifier moved from `email` to
-    // `username` (it was never a validated address, just a unique handle).
-    // Idempotent and safe: rename only email-only docs, and drop any stray
-    // `email` left on docs that already have a `username`.
-    {
-        let users = db.collection::<Document>("users");
-        let _ = users
-            .update_many(
-                mongodb::bson::doc! { "email": { "$exists": true }, "username": { "$exists": false } },
-                mongodb::bson::doc! { "$rename": { "email": "username" } },
-            )
-            .await;
-        let _ = users
-            .update_many(
-                mongodb::bson::doc! { "email": { "$exists": true }, "username": { "$exists": true } },
-                mongodb::bson::doc! { "$unset": { "email": "" } },
-            )
-            .await;
+    // Large collections can get theirs built later from the maintenance page.
+    if env::var("INDEXES_ON_STARTUP").as_deref() != Ok("0") {
+        reindex::ensure_indexes(&db).await?;
     }
 
     // Only seed when the database is effectively empty (no users).
@@ -137,19 +290,54 @@ pub async fn init_state_with_db_name(uri: &str, db_name: &str) -> Result<AppStat
         seed::seed_sample_finance(&db, company_ids.values().next().cloned()).await?;
     }
 
+    // After seeding, so the sample data is brought up to date like any other.
+    if env::var("MIGRATE_ON_STARTUP").as_deref() != Ok("0") {
+        migrations::run_migrations(&db, false).await?;
+    }
+    let report_reads = report_reads::report_reads_from_env(&db).await;
+
     Ok(AppState {
         jobs: Arc::new(Mutex::new(HashMap::new())),
+        webhook_deliveries: Arc::new(AtomicUsize::new(0)),
+        reindex: Arc::new(Mutex::new(None)),
+        events: event_channel(),
         users: db.collection::<User>("users"),
         user_companies: db.collection::<UserCompany>("user_companies"),
         companies: db.collection::<Company>("company"),
         sessions: db.collection::<Session>("sessions"),
+        refresh_tokens: db.collection::<RefreshToken>("refresh_tokens"),
+        login_links: db.collection::<LoginLink>("login_links"),
+        email_changes: db.collection::<EmailChange>("email_changes"),
+        sso_identities: db.collection::<SsoIdentity>("sso_identities"),
+        api_tokens: db.collection::<ApiToken>("api_tokens"),
+        access_events: db.collection::<AccessEvent>("access_events"),
         accounts: db.collection::<Account>("accounts"),
+        account_groups: db.collection::<AccountGroup>("account_groups"),
+        balance_snapshots: db.collection::<AccountBalanceSnapshot>("balance_snapshots"),
+        account_valuations: db.collection::<AccountValuation>("account_valuations"),
+        category_usage: db.collection::<AccountCategoryUsage>("account_category_usage"),
+        monthly_summaries: db.collection::<MonthlySummary>("monthly_summaries"),
+        bank_connections: db.collection::<BankConnection>("bank_connections"),
+        bank_transactions: db.collection::<SyncedBankTransaction>("bank_transactions"),
         categories: db.collection::<Category>("categories"),
         contacts: db.collection::<Contact>("contacts"),
         recurring_plans: db.collection::<RecurringPlan>("recurring_plans"),
+        plan_versions: db.collection::<RecurringPlanVersion>("plan_versions"),
         planned_entries: db.collection::<PlannedEntry>("planned_entries"),
+        planned_entries_archive: db.collection::<PlannedEntry>(PLANNED_ENTRIES_ARCHIVE),
         transactions: db.collection::<Transaction>("transactions"),
+        text_replacements: db.collection::<TextReplacement>("text_replacements"),
+        import_profiles: db.collection::<ImportProfile>("import_profiles"),
+        import_drafts: db.collection::<ImportDraft>("import_drafts"),
+        comments: db.collection::<Comment>("comments"),
+        notifications: db.collection::<Notification>("notifications"),
+        receipts: db.collection::<Receipt>("receipts"),
+        custom_fields: db.collection::<CustomFieldDefinition>("custom_fields"),
+        tax_profiles: db.collection::<TaxProfile>("tax_profiles"),
         forecasts: db.collection::<Forecast>("forecasts"),
+        sequences: db.collection::<SequenceCounter>("sequences"),
+        portal_links: db.collection::<PortalLink>("portal_links"),
+        portal_sessions: db.collection::<PortalSession>("portal_sessions"),
         cfdis: db.collection::<Document>("cfdis"),
         sat_configs: db.collection::<SatConfig>("sat_configs"),
         orders: db.collection::<ServiceOrder>("service_orders"),
@@ -161,5 +349,13 @@ pub async fn init_state_with_db_name(uri: &str, db_name: &str) -> Result<AppStat
         resource_usages: db.collection::<ResourceUsage>("resource_usages"),
         resource_usage_allocations: db
             .collection::<ResourceUsageAllocation>("resource_usage_allocations"),
+        report_reads,
+        mailer: mailer_from_env(),
+        webhooks_private_networks: env::var("WEBHOOKS_ALLOW_PRIVATE_NETWORKS").as_deref()
+            == Ok("1"),
+        metrics_token: env::var("METRICS_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty()),
     })
 }
@@ -0,0 +1,336 @@
+use anyhow::{Context, Result};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection,
+    bson::{Bson, DateTime, Document, doc, oid::ObjectId},
+};
+use std::{
+    env,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use super::AppState;
+
+/// Tombstone field set on every document of an off-boarded company.
+pub const ARCHIVED_AT_FIELD: &str = "archived_at";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffboardReport {
+    pub archived_documents: u64,
+    /// Users whose membership in the company was removed.
+    pub revoked_members: u64,
+    /// Revoked members left without any company; they are deactivated.
+    pub deactivated_users: u64,
+    pub purge_after: DateTime,
+}
+
+/// Collections whose documents belong to a company through an ObjectId
+/// `company_id`. CFDIs are handled apart because they store the hex string.
+fn company_collections(state: &AppState) -> Vec<(&'static str, Collection<Document>)> {
+    vec![
+        ("accounts", state.accounts.clone_with_type()),
+        ("account_groups", state.account_groups.clone_with_type()),
+        ("balance_snapshots", state.balance_snapshots.clone_with_type()),
+        ("account_valuations", state.account_valuations.clone_with_type()),
+        ("account_category_usage", state.category_usage.clone_with_type()),
+        ("monthly_summaries", state.monthly_summaries.clone_with_type()),
+        ("bank_connections", state.bank_connections.clone_with_type()),
+        ("bank_transactions", state.bank_transactions.clone_with_type()),
+        ("categories", state.categories.clone_with_type()),
+        ("contacts", state.contacts.clone_with_type()),
+        ("recurring_plans", state.recurring_plans.clone_with_type()),
+        ("plan_versions", state.plan_versions.clone_with_type()),
+        ("planned_entries", state.planned_entries.clone_with_type()),
+        (
+            "planned_entries_archive",
+            state.planned_entries_archive.clone_with_type(),
+        ),
+        ("transactions", state.transactions.clone_with_type()),
+        ("text_replacements", state.text_replacements.clone_with_type()),
+        ("import_profiles", state.import_profiles.clone_with_type()),
+        ("import_drafts", state.import_drafts.clone_with_type()),
+        ("comments", state.comments.clone_with_type()),
+        ("notifications", state.notifications.clone_with_type()),
+        ("receipts", state.receipts.clone_with_type()),
+        ("custom_fields", state.custom_fields.clone_with_type()),
+        ("forecasts", state.forecasts.clone_with_type()),
+        ("sequences", state.sequences.clone_with_type()),
+        ("portal_links", state.portal_links.clone_with_type()),
+        ("portal_sessions", state.portal_sessions.clone_with_type()),
+        ("sat_configs", state.sat_configs.clone_with_type()),
+        ("orders", state.orders.clone_with_type()),
+        ("projects", state.projects.clone_with_type()),
+        ("concept_statuses", state.concept_statuses.clone_with_type()),
+        ("project_concepts", state.project_concepts.clone_with_type()),
+        ("resources", state.resources.clone_with_type()),
+        ("resource_logs", state.resource_logs.clone_with_type()),
+        ("resource_usages", state.resource_usages.clone_with_type()),
+        (
+            "resource_usage_allocations",
+            state.resource_usage_allocations.clone_with_type(),
+        ),
+    ]
+}
+
+/// Every document of the company in one BSON document, one array per
+/// collection, plus the members with their roles. SAT key passwords and user
+/// TOTP secrets are left out.
+pub async fn export_company_bundle(state: &AppState, company_id: &ObjectId) -> Result<Document> {
+    let company = state
+        .companies
+        .clone_with_type::<Document>()
+        .find_one(doc! { "_id": company_id })
+        .await?
+        .context("company not found")?;
+
+    let mut bundle = doc! {
+        "format": "alfredo-company-export",
+        "version": 1,
+        "exported_at": DateTime::from_system_time(SystemTime::now()),
+        "company": company,
+    };
+    for (name, collection) in company_collections(state) {
+        let docs: Vec<Document> = state
+            .for_reports(&collection)
+            .find(doc! { "company_id": company_id })
+            .await?
+            .try_collect()
+            .await?;
+        let docs: Vec<Bson> = docs
+            .into_iter()
+            .map(|mut document| {
+                if name == "sat_configs" {
+                    document.remove("key_password");
+                }
+                Bson::Document(document)
+            })
+            .collect();
+        bundle.insert(name, docs);
+    }
+    let cfdis: Vec<Document> = state
+        .for_reports(&state.cfdis)
+        .find(doc! { "company_id": company_id.to_hex() })
+        .await?
+        .try_collect()
+        .await?;
+    bundle.insert("cfdis", cfdis);
+
+    let mut members = Vec::new();
+    let mut cursor = state
+        .user_companies
+        .find(doc! { "company_id": company_id })
+        .await?;
+    while let Some(membership) = cursor.try_next().await? {
+        let Some(user) = state
+            .users
+            .find_one(doc! { "_id": membership.user_id })
+            .await?
+        else {
+            continue;
+        };
+        members.push(doc! {
+            "user_id": membership.user_id,
+            "username": user.username,
+            "role": membership.role.as_str(),
+        });
+    }
+    bundle.insert("members", members);
+
+    Ok(bundle)
+}
+
+/// Writes an export bundle as JSON under `COMPANY_EXPORT_DIR` (default
+/// `exports`) so it outlives the hard deletion. Returns the file path.
+pub fn save_company_export(bundle: &Document, slug: &str, now: SystemTime) -> Result<PathBuf> {
+    let dir = PathBuf::from(env::var("COMPANY_EXPORT_DIR").unwrap_or_else(|_| "exports".into()));
+    std::fs::create_dir_all(&dir)?;
+    let stamp = DateTime::from_system_time(now)
+        .to_chrono()
+        .format("%Y%m%d%H%M%S");
+    let path = dir.join(format!("{slug}-{stamp}.json"));
+    let json = Bson::Document(bundle.clone()).into_relaxed_extjson();
+    std::fs::write(&path, serde_json::to_vec_pretty(&json)?)?;
+    Ok(path)
+}
+
+/// Archives the company instead of deleting it: every child document gets an
+/// `archived_at` tombstone, members lose access, and the company is
+/// deactivated and scheduled for `purge_archived_companies` after `grace`.
+pub async fn offboard_company(
+    state: &AppState,
+    company_id: &ObjectId,
+    grace: Duration,
+    now: SystemTime,
+) -> Result<OffboardReport> {
+    let archived_at = DateTime::from_system_time(now);
+    let purge_after = DateTime::from_system_time(now + grace);
+    let tombstone = doc! { "$set": { ARCHIVED_AT_FIELD: archived_at } };
+
+    let mut archived_documents = 0;
+    for (_, collection) in company_collections(state) {
+        archived_documents += collection
+            .update_many(doc! { "company_id": company_id }, tombstone.clone())
+            .await?
+            .modified_count;
+    }
+    archived_documents += state
+        .cfdis
+        .update_many(doc! { "company_id": company_id.to_hex() }, tombstone)
+        .await?
+        .modified_count;
+
+    let (revoked_members, deactivated_users) = revoke_company_members(state, company_id).await?;
+
+    state
+        .companies
+        .update_one(
+            doc! { "_id": company_id },
+            doc! { "$set": {
+                "is_active": false,
+                ARCHIVED_AT_FIELD: archived_at,
+                "purge_after": purge_after,
+                "updated_at": archived_at,
+            } },
+        )
+        .await?;
+
+    Ok(OffboardReport {
+        archived_documents,
+        revoked_members,
+        deactivated_users,
+        purge_after,
+    })
+}
+
+/// Drops the company from every user, both the membership documents and the
+/// lists embedded in the user. Returns (members revoked, users deactivated).
+async fn revoke_company_members(state: &AppState, company_id: &ObjectId) -> Result<(u64, u64)> {
+    let mut user_ids: Vec<ObjectId> = Vec::new();
+    let mut cursor = state
+        .user_companies
+        .find(doc! { "company_id": company_id })
+        .await?;
+    while let Some(membership) = cursor.try_next().await? {
+        user_ids.push(membership.user_id);
+    }
+    let mut cursor = state
+        .users
+        .find(doc! { "$or": [ { "company": company_id }, { "companies": company_id } ] })
+        .await?;
+    while let Some(user) = cursor.try_next().await? {
+        if let Some(id) = user.id
+            && !user_ids.contains(&id)
+        {
+            user_ids.push(id);
+        }
+    }
+
+    let mut deactivated = 0;
+    for user_id in &user_ids {
+        let Some(user) = state.users.find_one(doc! { "_id": user_id }).await? else {
+            continue;
+        };
+        // Keep the current primary first so it stays primary when possible.
+        let mut remaining: Vec<ObjectId> = Vec::new();
+        for id in user.company_id.iter().chain(user.company_ids.iter()) {
+            if id != company_id && !remaining.contains(id) {
+                remaining.push(*id);
+            }
+        }
+        let mut cursor = state
+            .user_companies
+            .find(doc! { "user_id": user_id, "company_id": { "$ne": company_id } })
+            .await?;
+        while let Some(membership) = cursor.try_next().await? {
+            if !remaining.contains(&membership.company_id) {
+                remaining.push(membership.company_id);
+            }
+        }
+
+        let update = match remaining.first() {
+            Some(primary) => doc! {
+                "$pull": { "companies": company_id },
+                "$set": { "company": primary },
+            },
+            None => doc! {
+                "$pull": { "companies": company_id },
+                "$unset": { "company": "" },
+                "$set": { "is_active": false },
+            },
+        };
+        state
+            .users
+            .update_one(doc! { "_id": user_id }, update)
+            .await?;
+        if remaining.is_empty() {
+            state
+                .sessions
+                .delete_many(doc! { "user_id": user_id })
+                .await?;
+            state
+                .refresh_tokens
+                .delete_many(doc! { "user_id": user_id })
+                .await?;
+            deactivated += 1;
+        }
+    }
+    state
+        .user_companies
+        .delete_many(doc! { "company_id": company_id })
+        .await?;
+
+    Ok((user_ids.len() as u64, deactivated))
+}
+
+/// Hard-deletes companies whose grace period ended, with all their
+/// documents. Stored FIEL files of their SAT configurations and uploaded
+/// receipts are removed too.
+/// Returns how many companies were purged.
+pub async fn purge_archived_companies(state: &AppState, now: SystemTime) -> Result<u64> {
+    let due: Vec<ObjectId> = state
+        .companies
+        .find(doc! { "purge_after": { "$lte": DateTime::from_system_time(now) } })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .filter_map(|company| company.id)
+        .collect();
+
+    for company_id in &due {
+        let mut cursor = state
+            .sat_configs
+            .find(doc! { "company_id": company_id })
+            .await?;
+        while let Some(config) = cursor.try_next().await? {
+            let _ = std::fs::remove_file(&config.cer_path);
+            let _ = std::fs::remove_file(&config.key_path);
+        }
+        let mut cursor = state
+            .receipts
+            .find(doc! { "company_id": company_id })
+            .await?;
+        while let Some(receipt) = cursor.try_next().await? {
+            let _ = std::fs::remove_file(&receipt.path);
+        }
+        for (_, collection) in company_collections(state) {
+            collection
+                .delete_many(doc! { "company_id": company_id })
+                .await?;
+        }
+        state
+            .cfdis
+            .delete_many(doc! { "company_id": company_id.to_hex() })
+            .await?;
+        state
+            .user_companies
+            .delete_many(doc! { "company_id": company_id })
+            .await?;
+        state
+            .companies
+            .delete_one(doc! { "_id": company_id })
+            .await?;
+    }
+    Ok(due.len() as u64)
+}
@@ -0,0 +1,140 @@
+// Getting-started checklist of a company: its first account, category,
+// contact, recurring plan and transaction. Steps are ticked from the records
+// the company has and remembered on the company document, so the checklist
+// goes away for good once every step has been done.
+
+use anyhow::Result;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use super::{AppState, companies::get_company_by_id};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnboardingStep {
+    Account,
+    Category,
+    Contact,
+    RecurringPlan,
+    Transaction,
+}
+
+impl OnboardingStep {
+    /// Order in which the checklist suggests the steps.
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::Account,
+        OnboardingStep::Category,
+        OnboardingStep::Contact,
+        OnboardingStep::RecurringPlan,
+        OnboardingStep::Transaction,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnboardingStep::Account => "account",
+            OnboardingStep::Category => "category",
+            OnboardingStep::Contact => "contact",
+            OnboardingStep::RecurringPlan => "recurring_plan",
+            OnboardingStep::Transaction => "transaction",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OnboardingStep::Account => "Crea tu primera cuenta",
+            OnboardingStep::Category => "Crea tu primera categoría",
+            OnboardingStep::Contact => "Registra un cliente o proveedor",
+            OnboardingStep::RecurringPlan => "Crea tu primer plan recurrente",
+            OnboardingStep::Transaction => "Registra tu primer movimiento",
+        }
+    }
+
+    /// Page where the step is done.
+    pub fn href(&self) -> &'static str {
+        match self {
+            OnboardingStep::Account => "/admin/accounts/new",
+            OnboardingStep::Category => "/admin/categories/new",
+            OnboardingStep::Contact => "/admin/contacts/new",
+            OnboardingStep::RecurringPlan => "/admin/recurring_plans/new",
+            OnboardingStep::Transaction => "/admin/transactions/new",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnboardingChecklist {
+    /// Every step in `OnboardingStep::ALL` order, with whether it is done.
+    pub steps: Vec<(OnboardingStep, bool)>,
+}
+
+impl OnboardingChecklist {
+    pub fn done_count(&self) -> usize {
+        self.steps.iter().filter(|(_, done)| *done).count()
+    }
+}
+
+async fn has_records(
+    state: &AppState,
+    company_id: &ObjectId,
+    step: OnboardingStep,
+) -> Result<bool> {
+    let filter = doc! { "company_id": company_id };
+    let count = match step {
+        OnboardingStep::Account => state.accounts.count_documents(filter).limit(1).await?,
+        OnboardingStep::Category => state.categories.count_documents(filter).limit(1).await?,
+        OnboardingStep::Contact => state.contacts.count_documents(filter).limit(1).await?,
+        OnboardingStep::RecurringPlan => {
+            state
+                .recurring_plans
+                .count_documents(filter)
+                .limit(1)
+                .await?
+        }
+        OnboardingStep::Transaction => state.transactions.count_documents(filter).limit(1).await?,
+    };
+    Ok(count > 0)
+}
+
+/// Checklist of the company, or `None` once every step has been done. Steps
+/// found done for the first time are saved, and the whole checklist is
+/// marked completed when the last one is.
+pub async fn onboarding_checklist(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<Option<OnboardingChecklist>> {
+    let Some(company) = get_company_by_id(state, company_id).await? else {
+        return Ok(None);
+    };
+    if company.onboarding.completed_at.is_some() {
+        return Ok(None);
+    }
+
+    let mut steps = Vec::new();
+    let mut newly_done = Vec::new();
+    for step in OnboardingStep::ALL {
+        let recorded = company
+            .onboarding
+            .completed_steps
+            .iter()
+            .any(|key| key == step.as_str());
+        let done = recorded || has_records(state, company_id, step).await?;
+        if done && !recorded {
+            newly_done.push(step.as_str());
+        }
+        steps.push((step, done));
+    }
+
+    let completed = steps.iter().all(|(_, done)| *done);
+    if completed || !newly_done.is_empty() {
+        let mut update = doc! {
+            "$addToSet": { "onboarding.completed_steps": { "$each": newly_done } },
+        };
+        if completed {
+            update.insert("$set", doc! { "onboarding.completed_at": DateTime::now() });
+        }
+        state
+            .companies
+            .update_one(doc! { "_id": company_id }, update)
+            .await?;
+    }
+
+    Ok((!completed).then_some(OnboardingChecklist { steps }))
+}
@@ -0,0 +1,55 @@
+// Every record of a company the finance forms offer as an option, read in
+// one go for GET /api/options/bundle. Sorted by name so the same data always
+// serializes the same way and keeps its ETag.
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Collection,
+    bson::{doc, oid::ObjectId},
+};
+use serde::de::DeserializeOwned;
+
+use crate::models::{Account, Category, Contact, RecurringPlan};
+
+use super::{AccountAccess, AppState, list_accessible_accounts};
+
+#[derive(Debug, Default)]
+pub struct CompanyOptionRecords {
+    pub accounts: Vec<Account>,
+    pub categories: Vec<Category>,
+    pub contacts: Vec<Contact>,
+    pub plans: Vec<RecurringPlan>,
+}
+
+async fn by_name<T>(collection: &Collection<T>, company_id: &ObjectId) -> Result<Vec<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    collection
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "company_id": 1, "name": 1, "_id": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Active accounts the user may use, and all categories, contacts and active
+/// recurring plans of the company.
+pub async fn company_option_records(
+    state: &AppState,
+    company_id: &ObjectId,
+    access: &AccountAccess,
+) -> Result<CompanyOptionRecords> {
+    let mut accounts = list_accessible_accounts(state, company_id, access).await?;
+    accounts.retain(|a| a.is_active);
+    let mut plans = by_name(&state.recurring_plans, company_id).await?;
+    plans.retain(|p| p.is_active);
+    Ok(CompanyOptionRecords {
+        accounts,
+        categories: by_name(&state.categories, company_id).await?,
+        contacts: by_name(&state.contacts, company_id).await?,
+        plans,
+    })
+}
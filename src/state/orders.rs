@@ -4,7 +4,7 @@ use futures::stream::TryStreamExt;
 use std::time::SystemTime;
 
 use super::AppState;
-use super::finance::create_planned_entry;
+use super::finance::{contact_due_date, create_planned_entry};
 use crate::models::{FlowType, OrderItem, OrderStatus, PlannedStatus, ServiceOrder};
 
 pub async fn list_orders(state: &AppState, company_id: &ObjectId) -> Result<Vec<ServiceOrder>> {
@@ -123,9 +123,14 @@ pub async fn confirm_order(
     };
     let order_id = order.id.as_ref().context("order missing _id")?;
 
-    let due_date = order
+    // The service is billed when it is done; the customer's payment terms
+    // run from then.
+    let issued = order
         .scheduled_at
         .unwrap_or_else(|| DateTime::from_system_time(SystemTime::now()));
+    let due_date = contact_due_date(state, order.contact_id.as_ref(), issued)
+        .await?
+        .unwrap_or(issued);
 
     let planned_id = create_planned_entry(
         state,
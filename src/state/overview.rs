@@ -0,0 +1,168 @@
+use anyhow::Result;
+use chrono::{Datelike, Months, TimeZone, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use std::collections::HashMap;
+
+use crate::models::{FlowType, PlannedStatus, TransactionType};
+
+use super::{AppState, companies::company_default_currency};
+
+/// Headline numbers for one company, as shown on the cross-company overview.
+#[derive(Debug, Clone)]
+pub struct CompanyOverview {
+    pub company_id: ObjectId,
+    pub currency: String,
+    /// Income minus expenses over every confirmed transaction. Transfers move
+    /// money between the company's own accounts and do not change it.
+    pub cash_position: f64,
+    /// Open commitments (planned, partially covered or overdue) already past
+    /// their due date.
+    pub overdue_count: u64,
+    pub overdue_amount: f64,
+    /// Income minus expenses for the calendar month (UTC) containing `now`.
+    pub month_net: f64,
+}
+
+pub async fn company_overview(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<CompanyOverview> {
+    let currency = company_default_currency(state, company_id).await?;
+    let (month_start, month_end) = month_bounds(now);
+
+    let mut cash_position = 0_f64;
+    let mut month_net = 0_f64;
+    let mut cursor = state
+        .for_reports(&state.transactions)
+        .find(doc! { "company_id": company_id, "is_confirmed": { "$ne": false } })
+        .await?;
+    while let Some(tx) = cursor.try_next().await? {
+        let signed = match tx.transaction_type {
+            TransactionType::Income => tx.amount,
+            TransactionType::Expense => -tx.amount,
+            TransactionType::Transfer => 0.0,
+        };
+        cash_position += signed;
+        if tx.date >= month_start && tx.date < month_end {
+            month_net += signed;
+        }
+    }
+
+    let mut overdue_count = 0;
+    let mut overdue_amount = 0_f64;
+    let mut cursor = state
+        .for_reports(&state.planned_entries)
+        .find(doc! {
+            "company_id": company_id,
+            "due_date": { "$lt": now },
+            "status": { "$in": [
+                PlannedStatus::Planned.as_str(),
+                PlannedStatus::PartiallyCovered.as_str(),
+                PlannedStatus::Overdue.as_str(),
+            ] },
+        })
+        .await?;
+    while let Some(entry) = cursor.try_next().await? {
+        overdue_count += 1;
+        overdue_amount += entry.amount_estimated;
+    }
+
+    Ok(CompanyOverview {
+        company_id: *company_id,
+        currency,
+        cash_position,
+        overdue_count,
+        overdue_amount,
+        month_net,
+    })
+}
+
+/// One company's flows over a period, in its own currency, for the
+/// consolidated group report.
+#[derive(Debug, Clone)]
+pub struct CompanyPeriodFigures {
+    pub company_id: ObjectId,
+    pub currency: String,
+    /// Confirmed income and expense transactions dated in the period.
+    /// Transfers stay inside the company and are left out.
+    pub income: f64,
+    pub expense: f64,
+    /// Planned entries due in the period at their estimate, the same basis
+    /// as the expected scenario of a forecast. Cancelled entries are skipped.
+    pub projected_income: f64,
+    pub projected_expense: f64,
+}
+
+/// Actual and projected flows of the company in `[from, to)`.
+pub async fn company_period_figures(
+    state: &AppState,
+    company_id: &ObjectId,
+    from: DateTime,
+    to: DateTime,
+) -> Result<CompanyPeriodFigures> {
+    let mut figures = CompanyPeriodFigures {
+        company_id: *company_id,
+        currency: company_default_currency(state, company_id).await?,
+        income: 0.0,
+        expense: 0.0,
+        projected_income: 0.0,
+        projected_expense: 0.0,
+    };
+
+    let mut cursor = state
+        .for_reports(&state.transactions)
+        .find(doc! {
+            "company_id": company_id,
+            "is_confirmed": { "$ne": false },
+            "date": { "$gte": from, "$lt": to },
+        })
+        .await?;
+    while let Some(tx) = cursor.try_next().await? {
+        match tx.transaction_type {
+            TransactionType::Income => figures.income += tx.amount,
+            TransactionType::Expense => figures.expense += tx.amount,
+            TransactionType::Transfer => {}
+        }
+    }
+
+    let mut cursor = state
+        .for_reports(&state.planned_entries)
+        .find(doc! {
+            "company_id": company_id,
+            "due_date": { "$gte": from, "$lt": to },
+            "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+        })
+        .await?;
+    while let Some(entry) = cursor.try_next().await? {
+        match entry.flow_type {
+            FlowType::Income => figures.projected_income += entry.amount_estimated,
+            FlowType::Expense => figures.projected_expense += entry.amount_estimated,
+        }
+    }
+
+    Ok(figures)
+}
+
+/// Units of `target` per unit of `currency`: 1 for the same currency,
+/// otherwise the rate the caller supplied for `currency`, if any.
+pub fn conversion_rate(currency: &str, target: &str, rates: &HashMap<String, f64>) -> Option<f64> {
+    if currency == target {
+        Some(1.0)
+    } else {
+        rates.get(currency).copied()
+    }
+}
+
+/// First instant of the calendar month (UTC) containing `now` and of the
+/// month after it.
+pub fn month_bounds(now: DateTime) -> (DateTime, DateTime) {
+    let now = now.to_chrono();
+    let start = Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now);
+    let end = start.checked_add_months(Months::new(1)).unwrap_or(start);
+    (DateTime::from_chrono(start), DateTime::from_chrono(end))
+}
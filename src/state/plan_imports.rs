@@ -0,0 +1,332 @@
+// Recurring plans brought from a spreadsheet. Every row is checked and its
+// category and account resolved by name first; nothing is saved unless all
+// rows are valid, so a file can be fixed and uploaded again without
+// duplicating plans.
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::{
+    import::{PlanSheetRow, parse_sheet_amount, parse_sheet_date},
+    models::{Account, Category, FlowType, RecurringPlan, RequiredFieldPolicy},
+};
+
+use super::{AppState, PlanInput, company_required_fields, finance::insert_recurring_plan};
+
+/// What is wrong with one row of the sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanImportError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Outcome of importing a plan sheet. When `errors` is not empty nothing was
+/// imported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlanImport {
+    pub imported: usize,
+    pub entries_generated: u64,
+    pub errors: Vec<PlanImportError>,
+}
+
+fn sheet_flow(value: &str) -> Option<FlowType> {
+    match value.trim().to_lowercase().as_str() {
+        "income" | "ingreso" | "ingresos" | "entrada" => Some(FlowType::Income),
+        "expense" | "gasto" | "gastos" | "egreso" | "egresos" | "salida" => Some(FlowType::Expense),
+        _ => None,
+    }
+}
+
+/// Frequency of the sheet as stored, taking the Spanish names too.
+fn sheet_frequency(value: &str) -> String {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "diario" | "diaria" => "daily".to_string(),
+        "semanal" => "weekly".to_string(),
+        "quincenal" | "catorcenal" => "biweekly".to_string(),
+        "mensual" => "monthly".to_string(),
+        "trimestral" => "quarterly".to_string(),
+        "anual" => "yearly".to_string(),
+        _ => value,
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}
+
+/// Checks one row against the company's categories, active accounts and
+/// required fields and builds its plan, or says everything that is wrong
+/// with it. Sheets carry no contact, notes or end date, so a policy
+/// requiring one of them rejects the rows it applies to.
+fn plan_from_row(
+    row: &PlanSheetRow,
+    company_id: &ObjectId,
+    categories: &[Category],
+    accounts: &[Account],
+    required: &RequiredFieldPolicy,
+    now: DateTime,
+) -> Result<RecurringPlan, String> {
+    let mut problems = Vec::new();
+    if row.name.trim().is_empty() {
+        problems.push("falta el nombre".to_string());
+    }
+    let flow_type = sheet_flow(&row.flow);
+    if flow_type.is_none() {
+        problems.push(format!("flujo {:?} no es ingreso ni gasto", row.flow));
+    }
+    let category_id = match categories
+        .iter()
+        .filter(|c| same_name(&c.name, &row.category))
+        .find(|c| flow_type.as_ref().is_none_or(|flow| c.flow_type == *flow))
+    {
+        Some(category) => category.id,
+        None => {
+            problems.push(format!(
+                "no hay una categoría {:?} de ese flujo",
+                row.category
+            ));
+            None
+        }
+    };
+    let account_id = match accounts.iter().find(|a| same_name(&a.name, &row.account)) {
+        Some(account) => account.id,
+        None => {
+            problems.push(format!("no hay una cuenta activa {:?}", row.account));
+            None
+        }
+    };
+    let amount = parse_sheet_amount(&row.amount).filter(|amount| *amount > 0.0);
+    if amount.is_none() {
+        problems.push(format!("monto {:?} no es un número positivo", row.amount));
+    }
+    let day_of_month = match row.day.trim() {
+        "" => Ok(None),
+        day => day.parse::<i32>().map(Some),
+    };
+    if day_of_month.is_err() {
+        problems.push(format!("día {:?} no es un número", row.day));
+    }
+    let start_date = parse_sheet_date(&row.start).and_then(|date| date.and_hms_opt(0, 0, 0));
+    if start_date.is_none() {
+        problems.push(format!("fecha de inicio {:?} no es válida", row.start));
+    }
+
+    let frequency = sheet_frequency(&row.frequency);
+    let schedule = match (day_of_month, start_date) {
+        (Ok(day_of_month), Some(start)) => {
+            let start_date = DateTime::from_chrono(start.and_utc());
+            match (PlanInput {
+                frequency: &frequency,
+                day_of_month,
+                start_date,
+                end_date: None,
+                flow_type: flow_type.as_ref(),
+                contact_id: None,
+                notes: None,
+                required,
+            })
+            .validate()
+            {
+                Ok(frequency) => Some((frequency, day_of_month, start_date)),
+                Err(err) => {
+                    problems.push(err.to_string());
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    match (flow_type, category_id, account_id, amount, schedule) {
+        (
+            Some(flow_type),
+            Some(category_id),
+            Some(account_id),
+            Some(amount),
+            Some((frequency, day_of_month, start_date)),
+        ) if problems.is_empty() => Ok(RecurringPlan {
+            id: None,
+            company_id: *company_id,
+            name: row.name.trim().to_string(),
+            flow_type,
+            category_id,
+            account_expected_id: account_id,
+            contact_id: None,
+            amount_estimated: amount,
+            frequency,
+            day_of_month,
+            start_date,
+            end_date: None,
+            is_active: true,
+            version: 1,
+            scenario_weights: None,
+            probability: None,
+            skipped_due_dates: Vec::new(),
+            created_at: Some(now),
+            updated_at: None,
+            notes: None,
+        }),
+        _ => Err(problems.join("; ")),
+    }
+}
+
+/// Creates a recurring plan per row of the sheet, resolving the category and
+/// account by name within the company. With `generate_entries` their planned
+/// entries are added right away; otherwise the daily extension task adds
+/// them.
+pub async fn import_recurring_plans(
+    state: &AppState,
+    company_id: &ObjectId,
+    rows: &[PlanSheetRow],
+    generate_entries: bool,
+) -> Result<PlanImport> {
+    let categories: Vec<Category> = state
+        .categories
+        .find(doc! { "company_id": company_id })
+        .await?
+        .try_collect()
+        .await?;
+    let accounts: Vec<Account> = state
+        .accounts
+        .find(doc! { "company_id": company_id, "is_active": true })
+        .await?
+        .try_collect()
+        .await?;
+    let required = company_required_fields(state, company_id).await?;
+
+    let now = DateTime::now();
+    let mut summary = PlanImport::default();
+    let mut plans = Vec::new();
+    for row in rows {
+        match plan_from_row(row, company_id, &categories, &accounts, &required, now) {
+            Ok(plan) => plans.push(plan),
+            Err(message) => summary.errors.push(PlanImportError {
+                line: row.line,
+                message,
+            }),
+        }
+    }
+    if !summary.errors.is_empty() {
+        return Ok(summary);
+    }
+
+    for plan in plans {
+        let (_, generated) = insert_recurring_plan(state, plan, generate_entries).await?;
+        summary.imported += 1;
+        summary.entries_generated += generated;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(flow: &str, category: &str, frequency: &str, day: &str) -> PlanSheetRow {
+        PlanSheetRow {
+            line: 2,
+            name: "Renta".into(),
+            flow: flow.into(),
+            category: category.into(),
+            account: "banco".into(),
+            amount: "1,500.00".into(),
+            frequency: frequency.into(),
+            day: day.into(),
+            start: "01/03/2025".into(),
+        }
+    }
+
+    #[test]
+    fn rows_resolve_names_and_report_every_problem() {
+        let company_id = ObjectId::new();
+        let category = |name: &str, flow_type| Category {
+            id: Some(ObjectId::new()),
+            company_id,
+            name: name.into(),
+            flow_type,
+            parent_id: None,
+            created_at: None,
+            updated_at: None,
+            notes: None,
+            monthly_budget: None,
+            budget_alert: None,
+            tax_profile_id: None,
+        };
+        // One name used by both flows: the row's flow picks which.
+        let categories = vec![
+            category("Renta", FlowType::Income),
+            category("Renta", FlowType::Expense),
+        ];
+        let accounts = vec![Account {
+            id: Some(ObjectId::new()),
+            company_id,
+            name: "Banco".into(),
+            account_type: crate::models::AccountType::Bank,
+            currency: "MXN".into(),
+            is_active: true,
+            created_at: None,
+            updated_at: None,
+            notes: None,
+            opening_balance: 0.0,
+            opening_date: None,
+            opening_balance_changes: Vec::new(),
+            group_id: None,
+            position: 0,
+        }];
+        let now = DateTime::now();
+
+        let plan = plan_from_row(
+            &row("gasto", "RENTA", "Mensual", "5"),
+            &company_id,
+            &categories,
+            &accounts,
+            &RequiredFieldPolicy::default(),
+            now,
+        )
+        .unwrap();
+        assert_eq!(plan.flow_type, FlowType::Expense);
+        assert_eq!(plan.category_id, categories[1].id.unwrap());
+        assert_eq!(plan.account_expected_id, accounts[0].id.unwrap());
+        assert_eq!(plan.amount_estimated, 1500.0);
+        assert_eq!(plan.frequency, "monthly");
+        assert_eq!(plan.day_of_month, Some(5));
+        assert_eq!(
+            plan.start_date.to_chrono().date_naive(),
+            chrono::NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+        );
+
+        let err = plan_from_row(
+            &row("otro", "Luz", "semanal", "5"),
+            &company_id,
+            &categories,
+            &accounts,
+            &RequiredFieldPolicy::default(),
+            now,
+        )
+        .unwrap_err();
+        assert!(err.contains("no es ingreso ni gasto"));
+        assert!(err.contains("categoría \"Luz\""));
+        assert!(err.contains("Día del mes solo aplica"));
+
+        // Sheets have no contact column.
+        let required = RequiredFieldPolicy {
+            plans: vec![crate::models::RequiredFieldRule {
+                field: crate::models::RequiredField::Contact,
+                applies_to: crate::models::RequiredFieldScope::Expense,
+            }],
+            transactions: Vec::new(),
+        };
+        let err = plan_from_row(
+            &row("gasto", "Renta", "Mensual", "5"),
+            &company_id,
+            &categories,
+            &accounts,
+            &required,
+            now,
+        )
+        .unwrap_err();
+        assert!(err.contains("Contacto es obligatorio"));
+    }
+}
@@ -0,0 +1,228 @@
+// Checks on the schedule of a recurring plan (frequency, day of month and
+// dates) and on the fields the company's policy requires, shared by the
+// admin form, the JSON API and the sheet import so all reject the same input
+// with the same message.
+
+use std::fmt;
+
+use mongodb::bson::{DateTime, oid::ObjectId};
+
+use crate::models::{FlowType, RequiredField, RequiredFieldPolicy, RequiredFieldTarget};
+
+use super::{MissingRequiredField, check_required_fields, is_filled};
+
+/// Frequencies a recurring plan can repeat at.
+pub const PLAN_FREQUENCIES: [&str; 6] = [
+    "daily",
+    "weekly",
+    "biweekly",
+    "monthly",
+    "quarterly",
+    "yearly",
+];
+
+/// Whether `frequency` repeats on a calendar day, so a day of month applies.
+pub fn is_monthly_frequency(frequency: &str) -> bool {
+    matches!(frequency, "monthly" | "quarterly" | "yearly")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanInputError {
+    UnknownFrequency(String),
+    /// Monthly plans need the day they fall on.
+    DayOfMonthRequired,
+    DayOfMonthOutOfRange(i32),
+    /// Weekly-type plans repeat from the start date; a day of month would be
+    /// silently ignored.
+    DayOfMonthNotAllowed(String),
+    EndBeforeStart,
+    /// A field the company's required-field policy asks for is empty.
+    FieldRequired(MissingRequiredField),
+}
+
+impl fmt::Display for PlanInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanInputError::UnknownFrequency(frequency) => {
+                write!(f, "Frecuencia no válida: {frequency}")
+            }
+            PlanInputError::DayOfMonthRequired => {
+                write!(f, "Día del mes es obligatorio en planes mensuales")
+            }
+            PlanInputError::DayOfMonthOutOfRange(day) => {
+                write!(f, "Día del mes debe estar entre 1 y 31 (se recibió {day})")
+            }
+            PlanInputError::DayOfMonthNotAllowed(_) => write!(
+                f,
+                "Día del mes solo aplica a planes mensuales, trimestrales o anuales"
+            ),
+            PlanInputError::EndBeforeStart => {
+                write!(f, "Fecha fin no puede ser anterior a la fecha de inicio")
+            }
+            PlanInputError::FieldRequired(missing) => missing.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PlanInputError {}
+
+/// Schedule and policy-controlled fields of a recurring plan as submitted.
+#[derive(Debug, Clone)]
+pub struct PlanInput<'a> {
+    pub frequency: &'a str,
+    pub day_of_month: Option<i32>,
+    pub start_date: DateTime,
+    pub end_date: Option<DateTime>,
+    /// `None` while the flow is unknown, e.g. a sheet row with a typo in it;
+    /// only rules for all flows apply then.
+    pub flow_type: Option<&'a FlowType>,
+    pub contact_id: Option<&'a ObjectId>,
+    pub notes: Option<&'a str>,
+    /// The company's required fields; see `company_required_fields`.
+    pub required: &'a RequiredFieldPolicy,
+}
+
+impl PlanInput<'_> {
+    /// Checks the schedule and the required fields and returns the
+    /// frequency trimmed and lowercased, as it is stored.
+    pub fn validate(&self) -> Result<String, PlanInputError> {
+        let frequency = self.frequency.trim().to_lowercase();
+        if !PLAN_FREQUENCIES.contains(&frequency.as_str()) {
+            return Err(PlanInputError::UnknownFrequency(
+                self.frequency.trim().to_string(),
+            ));
+        }
+        match self.day_of_month {
+            Some(_) if !is_monthly_frequency(&frequency) => {
+                return Err(PlanInputError::DayOfMonthNotAllowed(frequency));
+            }
+            Some(day) if !(1..=31).contains(&day) => {
+                return Err(PlanInputError::DayOfMonthOutOfRange(day));
+            }
+            None if frequency == "monthly" => return Err(PlanInputError::DayOfMonthRequired),
+            _ => {}
+        }
+        if self.end_date.is_some_and(|end| end < self.start_date) {
+            return Err(PlanInputError::EndBeforeStart);
+        }
+        check_required_fields(
+            self.required,
+            RequiredFieldTarget::Plans,
+            self.flow_type,
+            |field| match field {
+                RequiredField::Contact => self.contact_id.is_some(),
+                RequiredField::Notes => is_filled(self.notes),
+                RequiredField::EndDate => self.end_date.is_some(),
+                RequiredField::PlannedEntry => true,
+            },
+        )
+        .map_err(PlanInputError::FieldRequired)?;
+        Ok(frequency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(frequency: &str, day_of_month: Option<i32>) -> Result<String, PlanInputError> {
+        PlanInput {
+            frequency,
+            day_of_month,
+            start_date: DateTime::from_millis(1_704_067_200_000),
+            end_date: None,
+            flow_type: Some(&FlowType::Expense),
+            contact_id: None,
+            notes: None,
+            required: &RequiredFieldPolicy::default(),
+        }
+        .validate()
+    }
+
+    #[test]
+    fn day_of_month_depends_on_frequency() {
+        assert_eq!(input(" Monthly ", Some(31)), Ok("monthly".to_string()));
+        assert_eq!(input("quarterly", None), Ok("quarterly".to_string()));
+        assert_eq!(input("yearly", Some(1)), Ok("yearly".to_string()));
+        assert_eq!(input("weekly", None), Ok("weekly".to_string()));
+
+        assert_eq!(
+            input("monthly", None),
+            Err(PlanInputError::DayOfMonthRequired)
+        );
+        assert_eq!(
+            input("monthly", Some(45)),
+            Err(PlanInputError::DayOfMonthOutOfRange(45))
+        );
+        assert_eq!(
+            input("yearly", Some(0)),
+            Err(PlanInputError::DayOfMonthOutOfRange(0))
+        );
+        for frequency in ["daily", "weekly", "biweekly"] {
+            assert_eq!(
+                input(frequency, Some(10)),
+                Err(PlanInputError::DayOfMonthNotAllowed(frequency.to_string()))
+            );
+        }
+        assert_eq!(
+            input("hourly", None),
+            Err(PlanInputError::UnknownFrequency("hourly".to_string()))
+        );
+    }
+
+    #[test]
+    fn end_date_cannot_precede_start_date() {
+        let start = DateTime::from_millis(1_704_067_200_000);
+        let required = RequiredFieldPolicy::default();
+        let plan = |end_millis: i64| PlanInput {
+            frequency: "weekly",
+            day_of_month: None,
+            start_date: start,
+            end_date: Some(DateTime::from_millis(end_millis)),
+            flow_type: Some(&FlowType::Income),
+            contact_id: None,
+            notes: None,
+            required: &required,
+        };
+        assert_eq!(
+            plan(1_704_067_200_000 - 1).validate(),
+            Err(PlanInputError::EndBeforeStart)
+        );
+        assert!(plan(1_704_067_200_000).validate().is_ok());
+    }
+
+    #[test]
+    fn company_policies_require_optional_fields_by_flow() {
+        use crate::models::{RequiredFieldRule, RequiredFieldScope};
+
+        let required = RequiredFieldPolicy {
+            plans: vec![RequiredFieldRule {
+                field: RequiredField::Contact,
+                applies_to: RequiredFieldScope::Expense,
+            }],
+            transactions: Vec::new(),
+        };
+        let contact = ObjectId::new();
+        let plan = |flow_type: &FlowType, contact_id: Option<&ObjectId>| {
+            PlanInput {
+                frequency: "weekly",
+                day_of_month: None,
+                start_date: DateTime::from_millis(1_704_067_200_000),
+                end_date: None,
+                flow_type: Some(flow_type),
+                contact_id,
+                notes: None,
+                required: &required,
+            }
+            .validate()
+        };
+
+        assert!(plan(&FlowType::Income, None).is_ok());
+        assert!(plan(&FlowType::Expense, Some(&contact)).is_ok());
+        let err = plan(&FlowType::Expense, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Contacto es obligatorio en los planes de gasto de esta empresa"
+        );
+    }
+}
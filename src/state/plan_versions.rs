@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, DateTime, doc, oid::ObjectId};
+use std::collections::HashMap;
+
+use crate::models::{RecurringPlan, RecurringPlanVersion};
+
+use super::AppState;
+
+/// One plan field that differs between two versions. Values are rendered as
+/// plain strings (ids in hex, dates as `YYYY-MM-DD`); `None` means unset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanFieldChange {
+    pub field: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Stores the plan as it is now under its current version. Snapshots are
+/// immutable: saving a version that already has one leaves it untouched.
+pub async fn snapshot_recurring_plan(state: &AppState, plan: &RecurringPlan) -> Result<()> {
+    let plan_id = plan.id.context("recurring plan missing _id")?;
+    let snapshot = RecurringPlanVersion {
+        id: None,
+        company_id: plan.company_id,
+        recurring_plan_id: plan_id,
+        version: plan.version,
+        plan: plan.clone(),
+        created_at: plan
+            .updated_at
+            .or(plan.created_at)
+            .unwrap_or_else(DateTime::now),
+    };
+    let mut fields = bson::to_document(&snapshot)?;
+    fields.remove("recurring_plan_id");
+    fields.remove("version");
+
+    state
+        .plan_versions
+        .update_one(
+            doc! { "recurring_plan_id": plan_id, "version": plan.version },
+            doc! { "$setOnInsert": fields },
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// Stored versions of a plan, oldest first.
+pub async fn list_plan_versions(
+    state: &AppState,
+    plan_id: &ObjectId,
+) -> Result<Vec<RecurringPlanVersion>> {
+    state
+        .plan_versions
+        .find(doc! { "recurring_plan_id": plan_id })
+        .sort(doc! { "version": 1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// How many planned entries each version of the plan produced.
+pub async fn count_planned_entries_per_plan_version(
+    state: &AppState,
+    plan_id: &ObjectId,
+) -> Result<HashMap<i32, usize>> {
+    let mut counts = HashMap::new();
+    let mut cursor = state
+        .planned_entries
+        .find(doc! { "recurring_plan_id": plan_id })
+        .await?;
+    while let Some(entry) = cursor.try_next().await? {
+        if let Some(version) = entry.recurring_plan_version {
+            *counts.entry(version).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+fn date_value(date: &DateTime) -> String {
+    date.to_chrono().format("%Y-%m-%d").to_string()
+}
+
+/// Fields that changed from `before` to `after`, in form order. Bookkeeping
+/// fields (version, timestamps, scenario weights) are not compared.
+pub fn diff_plan_versions(before: &RecurringPlan, after: &RecurringPlan) -> Vec<PlanFieldChange> {
+    let fields = |plan: &RecurringPlan| -> [(&'static str, Option<String>); 12] {
+        [
+            ("name", Some(plan.name.clone())),
+            ("flow_type", Some(plan.flow_type.as_str().to_string())),
+            ("category_id", Some(plan.category_id.to_hex())),
+            (
+                "account_expected_id",
+                Some(plan.account_expected_id.to_hex()),
+            ),
+            ("contact_id", plan.contact_id.map(|id| id.to_hex())),
+            (
+                "amount_estimated",
+                Some(format!("{:.2}", plan.amount_estimated)),
+            ),
+            ("frequency", Some(plan.frequency.clone())),
+            ("day_of_month", plan.day_of_month.map(|day| day.to_string())),
+            ("start_date", Some(date_value(&plan.start_date))),
+            ("end_date", plan.end_date.as_ref().map(date_value)),
+            ("is_active", Some(plan.is_active.to_string())),
+            ("notes", plan.notes.clone()),
+        ]
+    };
+    fields(before)
+        .into_iter()
+        .zip(fields(after))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, before), (_, after))| PlanFieldChange {
+            field,
+            before,
+            after,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FlowType;
+
+    fn plan() -> RecurringPlan {
+        RecurringPlan {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            name: "Renta".into(),
+            flow_type: FlowType::Expense,
+            category_id: ObjectId::new(),
+            account_expected_id: ObjectId::new(),
+            contact_id: None,
+            amount_estimated: 1000.0,
+            frequency: "monthly".into(),
+            day_of_month: Some(5),
+            start_date: DateTime::from_millis(1_704_067_200_000),
+            end_date: None,
+            is_active: true,
+            version: 1,
+            scenario_weights: None,
+            probability: None,
+            skipped_due_dates: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn diff_lists_changed_fields_only() {
+        let before = plan();
+        let mut after = before.clone();
+        after.version = 2;
+        after.updated_at = Some(DateTime::now());
+        assert!(diff_plan_versions(&before, &after).is_empty());
+
+        after.amount_estimated = 1250.5;
+        after.day_of_month = None;
+        after.notes = Some("Nuevo contrato".into());
+        assert_eq!(
+            diff_plan_versions(&before, &after),
+            vec![
+                PlanFieldChange {
+                    field: "amount_estimated",
+                    before: Some("1000.00".into()),
+                    after: Some("1250.50".into()),
+                },
+                PlanFieldChange {
+                    field: "day_of_month",
+                    before: Some("5".into()),
+                    after: None,
+                },
+                PlanFieldChange {
+                    field: "notes",
+                    before: None,
+                    after: Some("Nuevo contrato".into()),
+                },
+            ]
+        );
+    }
+}
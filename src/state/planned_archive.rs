@@ -0,0 +1,299 @@
+// Archive of old planned entries. Covered and cancelled entries due longer
+// ago than the configured age move from `planned_entries` to
+// `planned_entries_archive`, so listings only go through live commitments.
+// The archived documents keep their ids and fields; the `/api/tiempo` planned
+// series read both collections, and entries can be moved back at any time.
+
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, Document, doc, oid::ObjectId};
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{metrics::metrics, models::PlannedStatus};
+
+use super::AppState;
+
+pub const PLANNED_ENTRIES_ARCHIVE: &str = "planned_entries_archive";
+
+/// Set on archived entries; restoring removes it.
+const MOVED_AT_FIELD: &str = "moved_to_archive_at";
+
+/// Entries moved per round trip.
+const ARCHIVE_BATCH: i64 = 500;
+
+/// Shortest age accepted, so the entries the recurring plans still look at
+/// are never archived.
+pub const MIN_PLANNED_ARCHIVE_AGE_DAYS: u64 = 90;
+
+/// How old a covered or cancelled entry must be before the archive job
+/// moves it, and how often the job runs. Configured through
+/// `PLANNED_ARCHIVE_AGE_DAYS` (0 turns the job off) and
+/// `PLANNED_ARCHIVE_INTERVAL_HOURS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedArchivePolicy {
+    pub age_days: u64,
+    pub interval_hours: u64,
+}
+
+impl Default for PlannedArchivePolicy {
+    fn default() -> Self {
+        Self {
+            age_days: 730,
+            interval_hours: 24,
+        }
+    }
+}
+
+impl PlannedArchivePolicy {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        let age_days = match read("PLANNED_ARCHIVE_AGE_DAYS", defaults.age_days) {
+            0 => 0,
+            days => days.max(MIN_PLANNED_ARCHIVE_AGE_DAYS),
+        };
+        Self {
+            age_days,
+            interval_hours: read("PLANNED_ARCHIVE_INTERVAL_HOURS", defaults.interval_hours).max(1),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.age_days > 0
+    }
+
+    /// Entries due before this are old enough to archive.
+    pub fn cutoff(&self, now: SystemTime) -> DateTime {
+        DateTime::from_system_time(now - Duration::from_secs(self.age_days * 60 * 60 * 24))
+    }
+}
+
+fn archivable_filter(company_id: Option<&ObjectId>, cutoff: DateTime) -> Document {
+    let mut filter = doc! {
+        "status": { "$in": [PlannedStatus::Covered.as_str(), PlannedStatus::Cancelled.as_str()] },
+        "due_date": { "$lt": cutoff },
+    };
+    if let Some(company_id) = company_id {
+        filter.insert("company_id", company_id);
+    }
+    filter
+}
+
+/// Moves the documents matching `filter` from `from` to the collection named
+/// `into`, in batches. `set` and `unset` adjust them on the way; a copy left
+/// behind by an interrupted run is replaced, so running again finishes it.
+async fn move_documents(
+    from: &mongodb::Collection<Document>,
+    into: &str,
+    filter: Document,
+    set: Option<Document>,
+    unset: Option<&str>,
+) -> Result<u64> {
+    let mut moved = 0;
+    loop {
+        let ids: Vec<ObjectId> = from
+            .find(filter.clone())
+            .projection(doc! { "_id": 1 })
+            .limit(ARCHIVE_BATCH)
+            .await?
+            .try_collect::<Vec<Document>>()
+            .await?
+            .iter()
+            .filter_map(|document| document.get_object_id("_id").ok())
+            .collect();
+        if ids.is_empty() {
+            return Ok(moved);
+        }
+        let mut pipeline = vec![doc! { "$match": { "_id": { "$in": &ids } } }];
+        if let Some(set) = &set {
+            pipeline.push(doc! { "$set": set.clone() });
+        }
+        if let Some(field) = unset {
+            pipeline.push(doc! { "$unset": field });
+        }
+        pipeline.push(doc! { "$merge": {
+            "into": into,
+            "on": "_id",
+            "whenMatched": "replace",
+            "whenNotMatched": "insert",
+        }});
+        from.aggregate(pipeline).await?;
+        let deleted = from.delete_many(doc! { "_id": { "$in": &ids } }).await?;
+        moved += deleted.deleted_count;
+    }
+}
+
+/// Moves the covered and cancelled entries due before `cutoff` to the
+/// archive, for one company or, without `company_id`, for all of them.
+pub async fn archive_planned_entries(
+    state: &AppState,
+    company_id: Option<&ObjectId>,
+    cutoff: DateTime,
+    now: SystemTime,
+) -> Result<u64> {
+    move_documents(
+        &state.planned_entries.clone_with_type(),
+        PLANNED_ENTRIES_ARCHIVE,
+        archivable_filter(company_id, cutoff),
+        Some(doc! { MOVED_AT_FIELD: DateTime::from_system_time(now) }),
+        None,
+    )
+    .await
+}
+
+/// Moves the company's archived entries due on or after `since` (all of them
+/// without it) back to `planned_entries`.
+pub async fn restore_archived_planned_entries(
+    state: &AppState,
+    company_id: &ObjectId,
+    since: Option<DateTime>,
+) -> Result<u64> {
+    let mut filter = doc! { "company_id": company_id };
+    if let Some(since) = since {
+        filter.insert("due_date", doc! { "$gte": since });
+    }
+    let live = state.planned_entries.namespace().coll;
+    move_documents(
+        &state.planned_entries_archive.clone_with_type(),
+        &live,
+        filter,
+        None,
+        Some(MOVED_AT_FIELD),
+    )
+    .await
+}
+
+/// Planned entries of a company, split for the maintenance page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlannedArchiveCounts {
+    pub live: u64,
+    /// Live entries the next run would archive.
+    pub archivable: u64,
+    pub archived: u64,
+}
+
+pub async fn planned_archive_counts(
+    state: &AppState,
+    company_id: &ObjectId,
+    policy: &PlannedArchivePolicy,
+    now: SystemTime,
+) -> Result<PlannedArchiveCounts> {
+    let company = doc! { "company_id": company_id };
+    let archivable = if policy.is_enabled() {
+        state
+            .planned_entries
+            .count_documents(archivable_filter(Some(company_id), policy.cutoff(now)))
+            .await?
+    } else {
+        0
+    };
+    Ok(PlannedArchiveCounts {
+        live: state
+            .planned_entries
+            .count_documents(company.clone())
+            .await?,
+        archivable,
+        archived: state
+            .planned_entries_archive
+            .count_documents(company)
+            .await?,
+    })
+}
+
+/// Reclaims the space the archived documents left in `planned_entries`.
+/// Deployments whose user may not run `compact` just skip it.
+async fn compact_planned_entries(state: &AppState) -> bool {
+    let namespace = state.planned_entries.namespace();
+    state
+        .planned_entries
+        .client()
+        .database(&namespace.db)
+        .run_command(doc! { "compact": namespace.coll })
+        .await
+        .is_ok()
+}
+
+/// Archives every company's old entries right away and then every
+/// `interval_hours`, compacting `planned_entries` after a run that moved
+/// something. Does nothing when the policy is off.
+pub fn spawn_planned_archive_task(state: Arc<AppState>, policy: PlannedArchivePolicy) {
+    if !policy.is_enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(policy.interval_hours * 60 * 60));
+        loop {
+            ticker.tick().await;
+            let now = SystemTime::now();
+            let result = archive_planned_entries(&state, None, policy.cutoff(now), now).await;
+            metrics().record_task_run("planned_archive", result.is_ok());
+            match result {
+                Ok(0) => {}
+                Ok(moved) => {
+                    let compacted = compact_planned_entries(&state).await;
+                    println!(
+                        "planned entry archive: {moved} entries archived{}",
+                        if compacted {
+                            ", collection compacted"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+                Err(err) => eprintln!("planned entry archive failed: {err:?}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archivable_entries_are_old_covered_or_cancelled() {
+        let cutoff = DateTime::from_millis(1_000);
+        let company = ObjectId::new();
+        let filter = archivable_filter(Some(&company), cutoff);
+        assert_eq!(filter.get_object_id("company_id").unwrap(), company);
+        assert_eq!(
+            filter.get_document("due_date").unwrap(),
+            &doc! { "$lt": cutoff }
+        );
+        assert_eq!(
+            filter.get_document("status").unwrap(),
+            &doc! { "$in": ["covered", "cancelled"] }
+        );
+        assert!(!archivable_filter(None, cutoff).contains_key("company_id"));
+    }
+
+    #[test]
+    fn cutoff_is_age_days_before_now() {
+        let policy = PlannedArchivePolicy {
+            age_days: 1,
+            interval_hours: 24,
+        };
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(3 * 24 * 60 * 60);
+        assert_eq!(
+            policy.cutoff(now),
+            DateTime::from_millis(2 * 24 * 60 * 60 * 1000)
+        );
+        assert!(
+            !PlannedArchivePolicy {
+                age_days: 0,
+                ..policy
+            }
+            .is_enabled()
+        );
+    }
+}
@@ -0,0 +1,268 @@
+// Contact portal: one-time sign-in links sent to a contact's email and the
+// sessions they open. Only the hashes of their tokens are stored. Everything
+// the portal reads goes through this module and is filtered by the contact and
+// company of the session.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use data_encoding::BASE32_NOPAD;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{DateTime, doc, oid::ObjectId},
+    options::FindOptions,
+};
+use rand::RngCore;
+
+use crate::models::{Company, Contact, PlannedEntry, PlannedStatus, PortalLink, PortalSession};
+
+use super::{AppState, api_tokens::hash_token, custom_fields::escape_regex};
+
+pub const PORTAL_LINK_TTL_SECONDS: u64 = 60 * 60 * 24; // 1 day
+pub const PORTAL_SESSION_TTL_SECONDS: u64 = 60 * 60 * 24 * 7; // 7 days
+/// Invoices listed in the portal, newest first.
+const PORTAL_INVOICE_LIMIT: i64 = 200;
+
+fn new_token() -> String {
+    let mut token_bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut token_bytes);
+    BASE32_NOPAD.encode(&token_bytes)
+}
+
+fn expires_in(seconds: u64) -> DateTime {
+    DateTime::from_system_time(SystemTime::now() + Duration::from_secs(seconds))
+}
+
+/// Creates a sign-in link for `contact`, replacing any earlier one, and
+/// returns its token.
+pub async fn create_portal_link(state: &AppState, contact: &Contact) -> Result<String> {
+    let contact_id = contact.id.context("contact missing _id")?;
+    state
+        .portal_links
+        .delete_many(doc! { "contact_id": contact_id })
+        .await?;
+    let token = new_token();
+    state
+        .portal_links
+        .insert_one(PortalLink {
+            id: None,
+            token_hash: hash_token(&token),
+            company_id: contact.company_id,
+            contact_id,
+            expires_at: expires_in(PORTAL_LINK_TTL_SECONDS),
+        })
+        .await?;
+    Ok(token)
+}
+
+/// Creates a sign-in link for every contact of an active company whose email
+/// is `email`, ignoring case. `company_slug` narrows the search to one
+/// company, as when the request comes from its subdomain. Returns each
+/// contact with its token; an unknown email gives an empty list.
+pub async fn request_portal_links(
+    state: &AppState,
+    email: &str,
+    company_slug: Option<&str>,
+) -> Result<Vec<(Contact, String)>> {
+    let email = email.trim();
+    if email.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut company_filter = doc! {
+        "is_active": { "$ne": false },
+        "archived_at": { "$exists": false },
+    };
+    if let Some(slug) = company_slug {
+        company_filter.insert("slug", slug.to_lowercase());
+    }
+    let company_ids: Vec<ObjectId> = state
+        .companies
+        .find(company_filter)
+        .await?
+        .try_collect::<Vec<Company>>()
+        .await?
+        .into_iter()
+        .filter_map(|company| company.id)
+        .collect();
+    if company_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let contacts: Vec<Contact> = state
+        .contacts
+        .find(doc! {
+            "company_id": { "$in": company_ids },
+            "email": { "$regex": format!("^{}$", escape_regex(email)), "$options": "i" },
+        })
+        .await?
+        .try_collect()
+        .await?;
+    let mut links = Vec::with_capacity(contacts.len());
+    for contact in contacts {
+        let token = create_portal_link(state, &contact).await?;
+        links.push((contact, token));
+    }
+    Ok(links)
+}
+
+/// Trades a sign-in link for a portal session. The link works once; an
+/// unknown or expired token gives `None`.
+pub async fn redeem_portal_link(state: &AppState, token: &str) -> Result<Option<String>> {
+    let Some(link) = state
+        .portal_links
+        .find_one_and_delete(doc! { "token_hash": hash_token(token) })
+        .await?
+    else {
+        return Ok(None);
+    };
+    if link.expires_at.to_system_time() <= SystemTime::now() {
+        return Ok(None);
+    }
+    let session_token = new_token();
+    state
+        .portal_sessions
+        .insert_one(PortalSession {
+            id: None,
+            token_hash: hash_token(&session_token),
+            company_id: link.company_id,
+            contact_id: link.contact_id,
+            expires_at: expires_in(PORTAL_SESSION_TTL_SECONDS),
+        })
+        .await?;
+    Ok(Some(session_token))
+}
+
+/// Contact and company behind a live portal session.
+#[derive(Debug, Clone)]
+pub struct PortalAccess {
+    pub contact: Contact,
+    pub company: Company,
+}
+
+/// Looks up the portal session behind `token`. Expired sessions, deleted
+/// contacts and inactive companies give `None`.
+pub async fn find_portal_session(state: &AppState, token: &str) -> Result<Option<PortalAccess>> {
+    let Some(session) = state
+        .portal_sessions
+        .find_one(doc! { "token_hash": hash_token(token) })
+        .await?
+    else {
+        return Ok(None);
+    };
+    if session.expires_at.to_system_time() <= SystemTime::now() {
+        return Ok(None);
+    }
+    let Some(contact) = state
+        .contacts
+        .find_one(doc! { "_id": session.contact_id, "company_id": session.company_id })
+        .await?
+    else {
+        return Ok(None);
+    };
+    let company = state
+        .companies
+        .find_one(doc! { "_id": session.company_id })
+        .await?
+        .filter(|company| company.is_active && company.archived_at.is_none());
+    Ok(company.map(|company| PortalAccess { contact, company }))
+}
+
+pub async fn delete_portal_session(state: &AppState, token: &str) -> Result<()> {
+    state
+        .portal_sessions
+        .delete_many(doc! { "token_hash": hash_token(token) })
+        .await?;
+    Ok(())
+}
+
+/// Drops the pending links and open sessions of a contact.
+pub async fn revoke_portal_access(state: &AppState, contact_id: &ObjectId) -> Result<()> {
+    state
+        .portal_links
+        .delete_many(doc! { "contact_id": contact_id })
+        .await?;
+    state
+        .portal_sessions
+        .delete_many(doc! { "contact_id": contact_id })
+        .await?;
+    Ok(())
+}
+
+/// Planned payments and collections of the contact, cancelled ones left out,
+/// by due date.
+pub async fn portal_planned_entries(
+    state: &AppState,
+    company_id: &ObjectId,
+    contact_id: &ObjectId,
+) -> Result<Vec<PlannedEntry>> {
+    let entries = state
+        .planned_entries
+        .find(doc! {
+            "company_id": company_id,
+            "contact_id": contact_id,
+            "status": { "$ne": PlannedStatus::Cancelled.as_str() },
+        })
+        .sort(doc! { "due_date": 1 })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(entries)
+}
+
+/// Invoice of the company issued to or by the contact, as the portal shows it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortalInvoice {
+    pub uuid: String,
+    pub folio: String,
+    pub fecha: String,
+    pub total: String,
+    pub moneda: String,
+    /// True when the company issued it to the contact.
+    pub issued_to_contact: bool,
+}
+
+/// Invoices of the company where the contact's RFC is the issuer or the
+/// receiver, newest first. A contact without RFC has none.
+pub async fn portal_invoices(
+    state: &AppState,
+    company_id: &ObjectId,
+    rfc: Option<&str>,
+) -> Result<Vec<PortalInvoice>> {
+    let Some(rfc) = rfc.map(str::trim).filter(|rfc| !rfc.is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let rfc = rfc.to_uppercase();
+    let options = FindOptions::builder()
+        .sort(doc! { "comprobante.fecha": -1 })
+        .limit(PORTAL_INVOICE_LIMIT)
+        .build();
+    let docs: Vec<bson::Document> = state
+        .cfdis
+        .find(doc! {
+            "company_id": company_id.to_hex(),
+            "$or": [ { "emisor.rfc": &rfc }, { "receptor.rfc": &rfc } ],
+        })
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    let field = |doc: &bson::Document, nested: &str, key: &str| {
+        doc.get_document(nested)
+            .ok()
+            .and_then(|d| d.get_str(key).ok())
+            .unwrap_or("")
+            .to_string()
+    };
+    Ok(docs
+        .iter()
+        .map(|doc| PortalInvoice {
+            uuid: doc.get_str("uuid").unwrap_or("").to_string(),
+            folio: field(doc, "comprobante", "folio"),
+            fecha: field(doc, "comprobante", "fecha"),
+            total: field(doc, "comprobante", "total"),
+            moneda: field(doc, "comprobante", "moneda"),
+            issued_to_contact: field(doc, "receptor", "rfc") == rfc,
+        })
+        .collect())
+}
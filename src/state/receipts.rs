@@ -0,0 +1,77 @@
+use anyhow::{Context, Result, bail};
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use std::time::SystemTime;
+
+use crate::models::Receipt;
+use crate::ocr::ReceiptSuggestion;
+
+use super::AppState;
+
+pub async fn create_receipt(
+    state: &AppState,
+    company_id: &ObjectId,
+    file_name: &str,
+    content_type: &str,
+    path: &str,
+    ocr_text: Option<String>,
+    suggestion: &ReceiptSuggestion,
+) -> Result<ObjectId> {
+    let receipt = Receipt {
+        id: None,
+        company_id: *company_id,
+        transaction_id: None,
+        file_name: file_name.to_string(),
+        content_type: content_type.to_string(),
+        path: path.to_string(),
+        ocr_text,
+        suggested_amount: suggestion.amount,
+        suggested_date: suggestion
+            .date
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| DateTime::from_chrono(d.and_utc())),
+        suggested_merchant: suggestion.merchant.clone(),
+        uploaded_at: DateTime::from_system_time(SystemTime::now()),
+    };
+    let res = state.receipts.insert_one(receipt).await?;
+    res.inserted_id
+        .as_object_id()
+        .context("receipt insert missing _id")
+}
+
+pub async fn get_receipt_by_id(state: &AppState, id: &ObjectId) -> Result<Option<Receipt>> {
+    Ok(state.receipts.find_one(doc! { "_id": id }).await?)
+}
+
+/// Links a receipt of `company_id` that is not attached yet to a transaction.
+pub async fn attach_receipt_to_transaction(
+    state: &AppState,
+    receipt_id: &ObjectId,
+    company_id: &ObjectId,
+    transaction_id: &ObjectId,
+) -> Result<()> {
+    let res = state
+        .receipts
+        .update_one(
+            doc! {
+                "_id": receipt_id,
+                "company_id": company_id,
+                "transaction_id": { "$exists": false },
+            },
+            doc! { "$set": { "transaction_id": transaction_id } },
+        )
+        .await?;
+    if res.matched_count == 0 {
+        bail!("receipt not found or already attached");
+    }
+    Ok(())
+}
+
+pub async fn find_receipt_for_transaction(
+    state: &AppState,
+    transaction_id: &ObjectId,
+) -> Result<Option<Receipt>> {
+    Ok(state
+        .receipts
+        .find_one(doc! { "transaction_id": transaction_id })
+        .await?)
+}
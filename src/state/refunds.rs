@@ -0,0 +1,144 @@
+// Refunds and negative adjustments: money given back on an income or
+// received back on an expense. A refund is written as a transaction of the
+// same type, category, accounts, contact and planned entry as the original
+// with a negative amount, linked to it through `refund_of`. Every sum over
+// `amount` (balances, category reports, monthly summaries, taxes and the
+// coverage of planned entries) nets it without knowing about refunds, and
+// revenue is no longer inflated by returns booked as income.
+
+use anyhow::{Context, Result, bail};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{Transaction, TransactionType};
+
+use super::{AppState, create_transaction, get_transaction_by_id, transaction_tax};
+
+/// Refunds of a transaction, oldest first.
+pub async fn list_refunds(state: &AppState, original_id: &ObjectId) -> Result<Vec<Transaction>> {
+    Ok(state
+        .transactions
+        .find(doc! { "refund_of": original_id })
+        .sort(doc! { "date": 1 })
+        .await?
+        .try_collect()
+        .await?)
+}
+
+/// Part of the original amount the refunds already gave back.
+pub fn refunded_amount(refunds: &[Transaction]) -> f64 {
+    refunds.iter().map(|refund| -refund.amount).sum()
+}
+
+/// Checks that `amount` can still be refunded from a transaction of
+/// `original_amount` that already had `refunded` given back. Rounding cents
+/// are tolerated.
+pub fn check_refund_amount(original_amount: f64, refunded: f64, amount: f64) -> Result<()> {
+    if amount <= 0.0 {
+        bail!("El monto del reembolso debe ser mayor a cero");
+    }
+    let available = original_amount - refunded;
+    if amount - available > 0.005 {
+        bail!("El reembolso excede lo que queda por reembolsar ({available:.2})");
+    }
+    Ok(())
+}
+
+/// Records a refund of `amount` (positive) against the transaction
+/// `original_id` and returns its id. Transfers, drafts and refunds cannot be
+/// refunded, and the refunds of a transaction never add up to more than its
+/// amount.
+pub async fn create_refund(
+    state: &AppState,
+    company_id: &ObjectId,
+    original_id: &ObjectId,
+    date: DateTime,
+    amount: f64,
+    description: &str,
+    notes: Option<String>,
+) -> Result<ObjectId> {
+    let original = get_transaction_by_id(state, original_id)
+        .await?
+        .filter(|tx| tx.company_id == *company_id)
+        .context("Movimiento no encontrado")?;
+    if original.transaction_type == TransactionType::Transfer {
+        bail!("Las transferencias no se reembolsan");
+    }
+    if original.refund_of.is_some() {
+        bail!("Un reembolso no se puede reembolsar");
+    }
+    if !original.is_confirmed {
+        bail!("Confirma el movimiento antes de reembolsarlo");
+    }
+    if date < original.date {
+        bail!("El reembolso no puede ser anterior al movimiento original");
+    }
+    let refunds = list_refunds(state, original_id).await?;
+    check_refund_amount(original.amount, refunded_amount(&refunds), amount)?;
+
+    let description = match description.trim() {
+        "" => format!("Reembolso: {}", original.description),
+        description => description.to_string(),
+    };
+    let id = create_transaction(
+        state,
+        company_id,
+        date,
+        &description,
+        original.transaction_type.clone(),
+        &original.category_id,
+        original.account_from_id,
+        original.account_to_id,
+        -amount,
+        original.planned_entry_id,
+        original.project_id,
+        true,
+        notes,
+        None,
+        original.contact_id,
+        original.currency.clone(),
+        None,
+    )
+    .await?;
+
+    // A rate typed on the original applies to the refund as well.
+    let tax = transaction_tax(
+        state,
+        company_id,
+        &original.transaction_type,
+        &original.category_id,
+        -amount,
+        original.tax.as_ref(),
+    )
+    .await?;
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "refund_of": original_id,
+                "tax": mongodb::bson::to_bson(&tax)?,
+            } },
+        )
+        .await?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refunds_up_to_the_original_amount() {
+        assert!(check_refund_amount(100.0, 0.0, 100.0).is_ok());
+        assert!(check_refund_amount(100.0, 60.0, 40.0).is_ok());
+        assert!(check_refund_amount(100.0, 60.0, 40.004).is_ok());
+        assert!(check_refund_amount(100.0, 60.0, 40.5).is_err());
+    }
+
+    #[test]
+    fn refund_amount_must_be_positive() {
+        assert!(check_refund_amount(100.0, 0.0, 0.0).is_err());
+        assert!(check_refund_amount(100.0, 0.0, -5.0).is_err());
+    }
+}
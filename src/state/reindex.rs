@@ -0,0 +1,341 @@
+// Indexes the app relies on and their rebuild in the background. Startup
+// creates the ones in `index_plan` unless `INDEXES_ON_STARTUP=0`; on large
+// collections that is better left to a superadmin, who starts the build from
+// the maintenance page (or `cargo run --bin migrate -- --reindex`) while the
+// app keeps serving. Every index is sent with `background: true`, one at a
+// time, and a finished run is recorded in `migrations`.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use mongodb::{
+    Database, IndexModel,
+    bson::{DateTime, Document, doc},
+    options::IndexOptions,
+};
+use tokio::sync::Mutex;
+
+use crate::{metrics::metrics, models::ReindexRecord};
+
+use super::AppState;
+
+const MIGRATIONS_COLLECTION: &str = "migrations";
+/// `name` of the reindex records, next to the numbered migrations.
+const REINDEX_RECORD_NAME: &str = "reindex";
+
+/// An index of `collection`.
+pub struct PlannedIndex {
+    pub collection: &'static str,
+    pub model: IndexModel,
+}
+
+impl PlannedIndex {
+    fn new(collection: &'static str, keys: Document, options: Option<IndexOptions>) -> Self {
+        PlannedIndex {
+            collection,
+            model: IndexModel::builder().keys(keys).options(options).build(),
+        }
+    }
+
+    /// `collection.name`, with the name MongoDB gives the index by default.
+    pub fn label(&self) -> String {
+        let name = self
+            .model
+            .keys
+            .iter()
+            .map(|(field, order)| format!("{field}_{order}"))
+            .collect::<Vec<_>>()
+            .join("_");
+        format!("{}.{name}", self.collection)
+    }
+
+    /// The model with the background option set.
+    fn background_model(&self) -> IndexModel {
+        let mut model = self.model.clone();
+        let mut options = model.options.take().unwrap_or_default();
+        options.background = Some(true);
+        model.options = Some(options);
+        model
+    }
+}
+
+fn unique() -> Option<IndexOptions> {
+    Some(IndexOptions::builder().unique(true).build())
+}
+
+fn hashed_tokens() -> Option<IndexOptions> {
+    Some(
+        IndexOptions::builder()
+            .unique(true)
+            .partial_filter_expression(doc! { "token_hash": { "$type": "string" } })
+            .build(),
+    )
+}
+
+/// Every index created at startup and by the background rebuild.
+pub fn index_plan() -> Vec<PlannedIndex> {
+    vec![
+        PlannedIndex::new("categories", doc! { "company_id": 1, "name": 1 }, None),
+        PlannedIndex::new("contacts", doc! { "company_id": 1, "name": 1 }, None),
+        PlannedIndex::new(
+            "account_category_usage",
+            doc! { "account_id": 1, "transaction_type": 1, "count": -1 },
+            None,
+        ),
+        PlannedIndex::new(
+            "account_groups",
+            doc! { "company_id": 1, "position": 1 },
+            None,
+        ),
+        PlannedIndex::new(
+            "account_valuations",
+            doc! { "account_id": 1, "as_of": -1 },
+            unique(),
+        ),
+        PlannedIndex::new(
+            "monthly_summaries",
+            doc! { "company_id": 1, "month": 1 },
+            unique(),
+        ),
+        PlannedIndex::new(
+            "bank_transactions",
+            doc! { "connection_id": 1, "external_id": 1 },
+            unique(),
+        ),
+        // Only transactions that carry an external id take part.
+        PlannedIndex::new(
+            "transactions",
+            doc! { "company_id": 1, "external_id": 1 },
+            Some(
+                IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "external_id": { "$type": "string" } })
+                    .build(),
+            ),
+        ),
+        PlannedIndex::new(
+            "transactions",
+            doc! { "refund_of": 1 },
+            Some(
+                IndexOptions::builder()
+                    .partial_filter_expression(doc! { "refund_of": { "$type": "objectId" } })
+                    .build(),
+            ),
+        ),
+        PlannedIndex::new("api_tokens", doc! { "token_hash": 1 }, unique()),
+        PlannedIndex::new("refresh_tokens", doc! { "token_hash": 1 }, unique()),
+        PlannedIndex::new("login_links", doc! { "token_hash": 1 }, unique()),
+        PlannedIndex::new("email_changes", doc! { "token_hash": 1 }, unique()),
+        // Partial until the migration drops the portal tokens stored before
+        // they were hashed, which have no `token_hash`.
+        PlannedIndex::new("portal_links", doc! { "token_hash": 1 }, hashed_tokens()),
+        PlannedIndex::new("portal_sessions", doc! { "token_hash": 1 }, hashed_tokens()),
+        PlannedIndex::new(
+            "access_events",
+            doc! { "company_ids": 1, "created_at": -1 },
+            None,
+        ),
+        PlannedIndex::new(
+            "text_replacements",
+            doc! { "company_id": 1, "created_at": -1 },
+            None,
+        ),
+        PlannedIndex::new(
+            "import_profiles",
+            doc! { "company_id": 1, "name": 1 },
+            unique(),
+        ),
+        // One generated entry per plan version and due date, so overlapping
+        // extension runs cannot both insert it. Entries added by hand carry no
+        // `generated_due_date` and stay out of it.
+        PlannedIndex::new(
+            "planned_entries",
+            doc! {
+                "company_id": 1,
+                "recurring_plan_id": 1,
+                "recurring_plan_version": 1,
+                "generated_due_date": 1,
+            },
+            Some(
+                IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "generated_due_date": { "$type": "date" } })
+                    .build(),
+            ),
+        ),
+        PlannedIndex::new(
+            "planned_entries_archive",
+            doc! { "company_id": 1, "due_date": 1 },
+            None,
+        ),
+    ]
+}
+
+/// Creates the indexes of `index_plan` in the foreground, as startup does.
+/// Existing indexes are left as they are.
+pub async fn ensure_indexes(db: &Database) -> Result<()> {
+    for index in index_plan() {
+        db.collection::<Document>(index.collection)
+            .create_index(index.model)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Where a background rebuild is, or how the last one ended.
+#[derive(Debug, Clone)]
+pub struct ReindexProgress {
+    /// Indexes in the plan.
+    pub total: usize,
+    /// Indexes already built.
+    pub built: usize,
+    /// `collection.index` being built now.
+    pub current: Option<String>,
+    pub started_at: DateTime,
+    /// Unset while it runs.
+    pub finished_at: Option<DateTime>,
+    /// Why it stopped early; the indexes after `built` were not sent.
+    pub error: Option<String>,
+}
+
+impl ReindexProgress {
+    pub fn is_running(&self) -> bool {
+        self.finished_at.is_none()
+    }
+}
+
+/// The rebuild this process started last, if any.
+pub type ReindexStore = Arc<Mutex<Option<ReindexProgress>>>;
+
+async fn update_progress(store: &ReindexStore, change: impl FnOnce(&mut ReindexProgress)) {
+    if let Some(progress) = store.lock().await.as_mut() {
+        change(progress);
+    }
+}
+
+/// Builds every index of the plan in the background, one at a time, keeping
+/// `store` up to date, and records the run in `migrations`. Stops at the
+/// first failure; builds already sent are kept.
+pub async fn run_reindex(db: &Database, store: &ReindexStore) -> Result<usize> {
+    let plan = index_plan();
+    let started_at = DateTime::now();
+    *store.lock().await = Some(ReindexProgress {
+        total: plan.len(),
+        built: 0,
+        current: None,
+        started_at,
+        finished_at: None,
+        error: None,
+    });
+
+    let result = match build_plan(db, store, &plan).await {
+        Ok(()) => record_reindex(db, started_at, plan.len()).await,
+        failed => failed,
+    };
+    let error = result.as_ref().err().map(|err| format!("{err:#}"));
+    update_progress(store, |progress| {
+        progress.current = None;
+        progress.finished_at = Some(DateTime::now());
+        progress.error = error;
+    })
+    .await;
+    result.map(|()| plan.len())
+}
+
+async fn build_plan(db: &Database, store: &ReindexStore, plan: &[PlannedIndex]) -> Result<()> {
+    for (done, index) in plan.iter().enumerate() {
+        let label = index.label();
+        println!("[reindex] building {}/{}: {label}", done + 1, plan.len());
+        update_progress(store, |progress| progress.current = Some(label.clone())).await;
+        db.collection::<Document>(index.collection)
+            .create_index(index.background_model())
+            .await
+            .with_context(|| format!("index {label} failed"))?;
+        update_progress(store, |progress| progress.built = done + 1).await;
+    }
+    Ok(())
+}
+
+async fn record_reindex(db: &Database, started_at: DateTime, indexes: usize) -> Result<()> {
+    let applied_at = DateTime::now();
+    db.collection::<ReindexRecord>(MIGRATIONS_COLLECTION)
+        .insert_one(ReindexRecord {
+            id: format!("{REINDEX_RECORD_NAME}-{}", started_at.timestamp_millis()),
+            name: REINDEX_RECORD_NAME.to_string(),
+            started_at,
+            applied_at,
+            indexes: indexes as i64,
+        })
+        .await?;
+    println!("[reindex] {indexes} indexes built");
+    Ok(())
+}
+
+/// The last rebuild recorded in `migrations`, by this or any other process.
+pub async fn last_reindex(db: &Database) -> Result<Option<ReindexRecord>> {
+    Ok(db
+        .collection::<ReindexRecord>(MIGRATIONS_COLLECTION)
+        .find_one(doc! { "name": REINDEX_RECORD_NAME })
+        .sort(doc! { "applied_at": -1 })
+        .await?)
+}
+
+/// The database behind `state`.
+pub fn app_database(state: &AppState) -> Database {
+    state.users.client().database(&state.users.namespace().db)
+}
+
+/// Starts a background rebuild unless one is already running in this
+/// process; returns whether it started.
+pub async fn start_reindex(state: Arc<AppState>) -> bool {
+    {
+        let mut current = state.reindex.lock().await;
+        if current.as_ref().is_some_and(ReindexProgress::is_running) {
+            return false;
+        }
+        // Marks it as running until `run_reindex` takes over.
+        *current = Some(ReindexProgress {
+            total: index_plan().len(),
+            built: 0,
+            current: None,
+            started_at: DateTime::now(),
+            finished_at: None,
+            error: None,
+        });
+    }
+    tokio::spawn(async move {
+        let db = app_database(&state);
+        let result = run_reindex(&db, &state.reindex).await;
+        metrics().record_task_run("reindex", result.is_ok());
+        if let Err(err) = result {
+            eprintln!("[reindex] failed: {err:?}");
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuilt_indexes_keep_their_options_and_run_in_the_background() {
+        let plan = index_plan();
+        let external_id = plan
+            .iter()
+            .find(|index| index.label() == "transactions.company_id_1_external_id_1")
+            .unwrap();
+        let options = external_id.background_model().options.unwrap();
+        assert_eq!(options.background, Some(true));
+        assert_eq!(options.unique, Some(true));
+        assert!(options.partial_filter_expression.is_some());
+
+        let categories = &plan[0];
+        assert_eq!(categories.label(), "categories.company_id_1_name_1");
+        assert_eq!(
+            categories.background_model().options.unwrap().background,
+            Some(true)
+        );
+        assert!(categories.model.options.is_none());
+    }
+}
@@ -0,0 +1,136 @@
+// Session lifetimes and "remember me". A session lasts `session_ttl_seconds`;
+// a login with "remember me" also gets a refresh token that opens a new
+// session whenever the current one is missing or expired, for
+// `remember_me_ttl_seconds`. A user still working when the session is about
+// to run out can renew it for another `session_ttl_seconds`; pages warn
+// `session_warning_seconds` ahead.
+
+use std::{
+    env,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use rand::RngCore;
+
+use crate::models::RefreshToken;
+
+use super::{
+    AppState, SESSION_TTL_SECONDS, UserWithCompany, api_tokens::hash_token, create_session,
+    get_user_by_id,
+};
+
+/// How long before expiry pages warn about it unless
+/// `SESSION_WARNING_SECONDS` says otherwise.
+pub const SESSION_WARNING_SECONDS: u64 = 2 * 60;
+
+/// How long a "remember me" login lasts unless `REMEMBER_ME_TTL_SECONDS` says
+/// otherwise.
+pub const REMEMBER_ME_TTL_SECONDS: u64 = 60 * 60 * 24 * 30; // 30 days
+
+fn ttl_from_env(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(default)
+}
+
+/// Lifetime of a session token: `SESSION_TTL_SECONDS` from the environment,
+/// one day by default.
+pub fn session_ttl_seconds() -> u64 {
+    ttl_from_env("SESSION_TTL_SECONDS", SESSION_TTL_SECONDS)
+}
+
+/// How long before the session expires pages show the warning:
+/// `SESSION_WARNING_SECONDS` from the environment, two minutes by default.
+/// Never longer than a session.
+pub fn session_warning_seconds() -> u64 {
+    ttl_from_env("SESSION_WARNING_SECONDS", SESSION_WARNING_SECONDS).min(session_ttl_seconds())
+}
+
+/// Lifetime of a refresh token: `REMEMBER_ME_TTL_SECONDS` from the
+/// environment, thirty days by default. Never shorter than a session.
+pub fn remember_me_ttl_seconds() -> u64 {
+    ttl_from_env("REMEMBER_ME_TTL_SECONDS", REMEMBER_ME_TTL_SECONDS).max(session_ttl_seconds())
+}
+
+/// Creates a refresh token for the user and returns it. Only its hash is
+/// kept.
+pub async fn create_refresh_token(state: &AppState, user_id: &ObjectId) -> Result<String> {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    let token = BASE32_NOPAD.encode(&bytes);
+    let now = SystemTime::now();
+    state
+        .refresh_tokens
+        .insert_one(RefreshToken {
+            id: None,
+            user_id: *user_id,
+            token_hash: hash_token(&token),
+            created_at: DateTime::from_system_time(now),
+            expires_at: DateTime::from_system_time(
+                now + Duration::from_secs(remember_me_ttl_seconds()),
+            ),
+        })
+        .await?;
+    Ok(token)
+}
+
+/// Opens a new session with a refresh token. Returns the user and the new
+/// session token, or `None` when the token is unknown, expired or its owner
+/// was deactivated. The refresh token stays valid until it expires, so
+/// requests racing after the session ran out all get through.
+pub async fn refresh_session(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<(UserWithCompany, String)>> {
+    let Some(refresh) = state
+        .refresh_tokens
+        .find_one(doc! {
+            "token_hash": hash_token(token),
+            "expires_at": { "$gt": DateTime::now() },
+        })
+        .await?
+    else {
+        return Ok(None);
+    };
+    let Some(user) = get_user_by_id(state, &refresh.user_id).await? else {
+        return Ok(None);
+    };
+    if !user.is_active {
+        return Ok(None);
+    }
+    let session = create_session(state, &user.username).await?;
+    Ok(Some((user, session)))
+}
+
+/// Pushes a live session's expiry to `session_ttl_seconds` from now and
+/// returns it; `None` when the session is unknown or already expired, since
+/// those have to sign in again.
+pub async fn renew_session(state: &AppState, token: &str) -> Result<Option<DateTime>> {
+    let now = SystemTime::now();
+    let expires_at = DateTime::from_system_time(now + Duration::from_secs(session_ttl_seconds()));
+    let renewed = state
+        .sessions
+        .update_one(
+            doc! {
+                "token": token,
+                "expires_at": { "$gt": DateTime::from_system_time(now) },
+            },
+            doc! { "$set": { "expires_at": expires_at } },
+        )
+        .await?;
+    Ok((renewed.matched_count > 0).then_some(expires_at))
+}
+
+/// Forgets a refresh token, on logout.
+pub async fn delete_refresh_token(state: &AppState, token: &str) -> Result<()> {
+    state
+        .refresh_tokens
+        .delete_one(doc! { "token_hash": hash_token(token) })
+        .await?;
+    Ok(())
+}
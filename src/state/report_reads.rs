@@ -0,0 +1,165 @@
+// Where the heavy read-only queries (reports, exports, dashboard figures) are
+// sent. With a replica set they can be pointed at secondaries through
+// `MONGODB_REPORT_READ_PREFERENCE` and `MONGODB_REPORT_READ_TAGS`, so they
+// stop competing with writes on the primary. Reads whose results are written
+// back (the monthly summaries the income statement stores, the exchange
+// results FX adjustments are recorded from) and anything else read from the
+// primary as before.
+
+use std::{collections::HashMap, env, time::Duration};
+
+use mongodb::{
+    Collection, Database,
+    bson::doc,
+    options::{CollectionOptions, ReadPreference, ReadPreferenceOptions, SelectionCriteria},
+};
+
+use super::AppState;
+
+/// Smallest `maxStalenessSeconds` the servers accept.
+const MIN_MAX_STALENESS_SECONDS: u64 = 90;
+
+/// Read preference for report queries from its three settings. An empty
+/// mode (or `primary`) keeps them on the primary. `secondary` is relaxed to
+/// `secondaryPreferred`, and so are tags given without a mode, so reports
+/// still load while no matching secondary is reachable.
+///
+/// `tags` holds tag sets separated by `;`, each a list of `name:value`
+/// pairs separated by `,`: `region:mx,usage:reports;usage:reports`.
+pub fn parse_report_read_preference(
+    mode: &str,
+    tags: &str,
+    max_staleness_seconds: &str,
+) -> Result<Option<ReadPreference>, String> {
+    let mut tag_sets = Vec::new();
+    for set in tags.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut tag_set = HashMap::new();
+        for pair in set.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((name, value)) = pair.split_once(':') else {
+                return Err(format!("read tag `{pair}` is not name:value"));
+            };
+            tag_set.insert(name.trim().to_string(), value.trim().to_string());
+        }
+        tag_sets.push(tag_set);
+    }
+
+    let max_staleness = match max_staleness_seconds.trim() {
+        "" => None,
+        value => match value.parse::<u64>() {
+            Ok(seconds) if seconds >= MIN_MAX_STALENESS_SECONDS => {
+                Some(Duration::from_secs(seconds))
+            }
+            _ => {
+                return Err(format!(
+                    "max staleness must be at least {MIN_MAX_STALENESS_SECONDS} seconds"
+                ));
+            }
+        },
+    };
+
+    let options = (!tag_sets.is_empty() || max_staleness.is_some()).then(|| {
+        ReadPreferenceOptions::builder()
+            .tag_sets((!tag_sets.is_empty()).then_some(tag_sets))
+            .max_staleness(max_staleness)
+            .build()
+    });
+    let mode = match mode.trim() {
+        "" if options.is_some() => "secondaryPreferred",
+        mode => mode,
+    };
+    match mode {
+        "" | "primary" if options.is_none() => Ok(None),
+        "" | "primary" => Err("tags and max staleness need a mode other than primary".into()),
+        "primaryPreferred" => Ok(Some(ReadPreference::PrimaryPreferred { options })),
+        "secondary" | "secondaryPreferred" => {
+            Ok(Some(ReadPreference::SecondaryPreferred { options }))
+        }
+        "nearest" => Ok(Some(ReadPreference::Nearest { options })),
+        other => Err(format!("unknown read preference `{other}`")),
+    }
+}
+
+/// Selection criteria for report queries on `db`, read from the
+/// environment. `None` (reads stay on the primary) when nothing is set, when
+/// the settings are invalid, or when `db` is not behind a replica set.
+pub(super) async fn report_reads_from_env(db: &Database) -> Option<SelectionCriteria> {
+    let setting = |name: &str| env::var(name).unwrap_or_default();
+    let preference = match parse_report_read_preference(
+        &setting("MONGODB_REPORT_READ_PREFERENCE"),
+        &setting("MONGODB_REPORT_READ_TAGS"),
+        &setting("MONGODB_REPORT_MAX_STALENESS_SECONDS"),
+    ) {
+        Ok(Some(preference)) => preference,
+        Ok(None) => return None,
+        Err(err) => {
+            eprintln!(
+                "[mongo] ignoring report read preference, reports read from the primary: {err}"
+            );
+            return None;
+        }
+    };
+
+    let replica_set = db
+        .run_command(doc! { "hello": 1 })
+        .await
+        .map(|hello| hello.contains_key("setName"))
+        .unwrap_or(false);
+    if !replica_set {
+        eprintln!("[mongo] not a replica set, reports read from the primary");
+        return None;
+    }
+    println!("Report queries read with {preference}");
+    Some(SelectionCriteria::ReadPreference(preference))
+}
+
+impl AppState {
+    /// `collection` with the read preference of report queries, for reports,
+    /// exports and dashboard figures. The same collection when reports read
+    /// from the primary.
+    pub fn for_reports<T: Send + Sync>(&self, collection: &Collection<T>) -> Collection<T> {
+        let Some(criteria) = &self.report_reads else {
+            return collection.clone();
+        };
+        let namespace = collection.namespace();
+        collection
+            .client()
+            .database(&namespace.db)
+            .collection_with_options(
+                &namespace.coll,
+                CollectionOptions::builder()
+                    .selection_criteria(criteria.clone())
+                    .build(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_read_preference_falls_back_to_preferred_modes() {
+        assert_eq!(parse_report_read_preference("", "", ""), Ok(None));
+        assert_eq!(parse_report_read_preference("primary", "", ""), Ok(None));
+        assert_eq!(
+            parse_report_read_preference("secondary", "", ""),
+            Ok(Some(ReadPreference::SecondaryPreferred { options: None }))
+        );
+
+        let Ok(Some(ReadPreference::SecondaryPreferred {
+            options: Some(options),
+        })) = parse_report_read_preference("", "region:mx, usage:reports;usage:reports", "120")
+        else {
+            panic!("tags alone should read from secondaries");
+        };
+        let tag_sets = options.tag_sets.unwrap();
+        assert_eq!(tag_sets.len(), 2);
+        assert_eq!(tag_sets[0].get("region").map(String::as_str), Some("mx"));
+        assert_eq!(options.max_staleness, Some(Duration::from_secs(120)));
+
+        assert!(parse_report_read_preference("fastest", "", "").is_err());
+        assert!(parse_report_read_preference("nearest", "usage", "").is_err());
+        assert!(parse_report_read_preference("nearest", "", "30").is_err());
+        assert!(parse_report_read_preference("primary", "usage:reports", "").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+// Required-field policies: optional fields of recurring plans and
+// transactions that a company makes mandatory, per flow (e.g. a contact on
+// every expense plan). Without a policy nothing beyond the usual fields is
+// required. Plans check theirs in `PlanInput::validate`, shared by the form,
+// the API and the sheet import; transactions with
+// `check_transaction_required_fields` from the form and the API.
+
+use std::{fmt, time::SystemTime};
+
+use anyhow::{Context, Result, bail};
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{
+    FlowType, RequiredField, RequiredFieldPolicy, RequiredFieldRule, RequiredFieldScope,
+    RequiredFieldTarget, TransactionType,
+};
+
+use super::AppState;
+
+/// A field the company's policy requires was left empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingRequiredField {
+    pub target: RequiredFieldTarget,
+    pub rule: RequiredFieldRule,
+}
+
+impl fmt::Display for MissingRequiredField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let records = self.target.label();
+        match self.rule.applies_to {
+            RequiredFieldScope::All => write!(
+                f,
+                "{} es obligatorio en todos los {records} de esta empresa",
+                self.rule.field.label()
+            ),
+            RequiredFieldScope::Income => write!(
+                f,
+                "{} es obligatorio en los {records} de ingreso de esta empresa",
+                self.rule.field.label()
+            ),
+            RequiredFieldScope::Expense => write!(
+                f,
+                "{} es obligatorio en los {records} de gasto de esta empresa",
+                self.rule.field.label()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MissingRequiredField {}
+
+/// Fails with the first rule of `target` that applies to a record of `flow`
+/// (`None` for transfers) and whose field `filled` says is empty.
+pub fn check_required_fields(
+    policy: &RequiredFieldPolicy,
+    target: RequiredFieldTarget,
+    flow: Option<&FlowType>,
+    filled: impl Fn(RequiredField) -> bool,
+) -> Result<(), MissingRequiredField> {
+    match policy
+        .rules(target)
+        .iter()
+        .find(|rule| rule.applies_to.applies_to(flow) && !filled(rule.field))
+    {
+        Some(rule) => Err(MissingRequiredField {
+            target,
+            rule: *rule,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether an optional text field counts as filled.
+pub fn is_filled(value: Option<&str>) -> bool {
+    value.is_some_and(|value| !value.trim().is_empty())
+}
+
+/// The policy check of a transaction as submitted.
+pub fn check_transaction_required_fields(
+    policy: &RequiredFieldPolicy,
+    transaction_type: &TransactionType,
+    notes: Option<&str>,
+    planned_entry_id: Option<&ObjectId>,
+) -> Result<(), MissingRequiredField> {
+    let flow = match transaction_type {
+        TransactionType::Income => Some(FlowType::Income),
+        TransactionType::Expense => Some(FlowType::Expense),
+        TransactionType::Transfer => None,
+    };
+    check_required_fields(
+        policy,
+        RequiredFieldTarget::Transactions,
+        flow.as_ref(),
+        |field| match field {
+            RequiredField::Notes => is_filled(notes),
+            RequiredField::PlannedEntry => planned_entry_id.is_some(),
+            RequiredField::Contact | RequiredField::EndDate => true,
+        },
+    )
+}
+
+pub async fn company_required_fields(
+    state: &AppState,
+    company_id: &ObjectId,
+) -> Result<RequiredFieldPolicy> {
+    let company = state
+        .companies
+        .find_one(doc! { "_id": company_id })
+        .await?
+        .context("company not found")?;
+    Ok(company.required_fields)
+}
+
+/// Each field may have one rule per target, and only fields the target's
+/// forms take.
+pub fn check_required_field_policy(policy: &RequiredFieldPolicy) -> Result<(), String> {
+    for target in RequiredFieldTarget::ALL {
+        let rules = policy.rules(target);
+        for (index, rule) in rules.iter().enumerate() {
+            if !target.fields().contains(&rule.field) {
+                return Err(format!(
+                    "{} no es un campo de los {}",
+                    rule.field.label(),
+                    target.label()
+                ));
+            }
+            if rules[..index].iter().any(|other| other.field == rule.field) {
+                return Err(format!(
+                    "{} tiene más de una regla en los {}",
+                    rule.field.label(),
+                    target.label()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Saves the company's policy; see `check_required_field_policy`.
+pub async fn update_company_required_fields(
+    state: &AppState,
+    id: &ObjectId,
+    policy: &RequiredFieldPolicy,
+) -> Result<()> {
+    if let Err(message) = check_required_field_policy(policy) {
+        bail!(message);
+    }
+    let update = if policy.is_empty() {
+        doc! {
+            "$unset": { "required_fields": "" },
+            "$set": { "updated_at": DateTime::from_system_time(SystemTime::now()) },
+        }
+    } else {
+        doc! { "$set": {
+            "required_fields": mongodb::bson::to_bson(policy)?,
+            "updated_at": DateTime::from_system_time(SystemTime::now()),
+        } }
+    };
+    state
+        .companies
+        .update_one(doc! { "_id": id }, update)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(rules: &[(RequiredField, RequiredFieldScope)]) -> RequiredFieldPolicy {
+        RequiredFieldPolicy {
+            plans: Vec::new(),
+            transactions: rules
+                .iter()
+                .map(|(field, applies_to)| RequiredFieldRule {
+                    field: *field,
+                    applies_to: *applies_to,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rules_apply_by_flow() {
+        let policy = policy(&[
+            (RequiredField::Notes, RequiredFieldScope::Expense),
+            (RequiredField::PlannedEntry, RequiredFieldScope::Income),
+        ]);
+        let entry = ObjectId::new();
+        let check = |kind: TransactionType, notes: Option<&str>, entry: Option<&ObjectId>| {
+            check_transaction_required_fields(&policy, &kind, notes, entry)
+        };
+
+        assert!(check(TransactionType::Transfer, None, None).is_ok());
+        assert!(check(TransactionType::Expense, Some("Factura A-12"), None).is_ok());
+        let missing = check(TransactionType::Expense, Some("  "), None).unwrap_err();
+        assert_eq!(missing.rule.field, RequiredField::Notes);
+        assert_eq!(
+            missing.to_string(),
+            "Notas es obligatorio en los movimientos de gasto de esta empresa"
+        );
+        assert!(check(TransactionType::Income, None, Some(&entry)).is_ok());
+        assert_eq!(
+            check(TransactionType::Income, Some("x"), None)
+                .unwrap_err()
+                .rule
+                .field,
+            RequiredField::PlannedEntry
+        );
+    }
+
+    #[test]
+    fn policies_take_one_rule_per_field_of_the_target() {
+        let rule = |field| RequiredFieldRule {
+            field,
+            applies_to: RequiredFieldScope::All,
+        };
+        let plans = |rules: Vec<RequiredFieldRule>| RequiredFieldPolicy {
+            plans: rules,
+            transactions: Vec::new(),
+        };
+        assert!(check_required_field_policy(&plans(vec![rule(RequiredField::Contact)])).is_ok());
+        assert!(
+            check_required_field_policy(&plans(vec![rule(RequiredField::PlannedEntry)])).is_err()
+        );
+        assert!(
+            check_required_field_policy(&plans(vec![
+                rule(RequiredField::Notes),
+                rule(RequiredField::Notes)
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn rules_for_all_flows_cover_transfers() {
+        let policy = policy(&[(RequiredField::Notes, RequiredFieldScope::All)]);
+        let missing =
+            check_transaction_required_fields(&policy, &TransactionType::Transfer, None, None)
+                .unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "Notas es obligatorio en todos los movimientos de esta empresa"
+        );
+        assert!(
+            check_transaction_required_fields(
+                &RequiredFieldPolicy::default(),
+                &TransactionType::Transfer,
+                None,
+                None
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::metrics::metrics;
+
+use super::{AppState, purge_archived_companies, revoke_portal_access};
+
+/// Name a contact keeps after `ContactErasure::Anonymize`.
+pub const ANONYMIZED_CONTACT_NAME: &str = "Contacto anonimizado";
+
+/// How much personal data `erase_contact_personal_data` removes. Either way
+/// the contact document itself stays, so transactions, planned entries and
+/// recurring plans keep pointing at it and their totals do not change. The
+/// contact also loses its portal links and sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactErasure {
+    /// Drop email, phone and notes.
+    Purge,
+    /// Purge, plus replace the name and drop the RFC so the contact can no
+    /// longer be tied to a person.
+    Anonymize,
+}
+
+impl ContactErasure {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "purge" => Some(ContactErasure::Purge),
+            "anonymize" => Some(ContactErasure::Anonymize),
+            _ => None,
+        }
+    }
+}
+
+pub async fn erase_contact_personal_data(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    mode: ContactErasure,
+) -> Result<()> {
+    let now = DateTime::from_system_time(SystemTime::now());
+    let update = match mode {
+        ContactErasure::Purge => doc! {
+            "$unset": { "email": "", "phone": "", "notes": "" },
+            "$set": { "updated_at": now },
+        },
+        ContactErasure::Anonymize => doc! {
+            "$unset": { "email": "", "phone": "", "notes": "", "rfc": "" },
+            "$set": { "name": ANONYMIZED_CONTACT_NAME, "updated_at": now },
+        },
+    };
+    state
+        .contacts
+        .update_one(doc! { "_id": id, "company_id": company_id }, update)
+        .await?;
+    revoke_portal_access(state, id).await?;
+    Ok(())
+}
+
+/// How long expired records are kept before the retention sweep deletes
+/// them, plus how often the sweep runs. Configured through
+/// `RETENTION_SESSION_DAYS`, `RETENTION_EMAIL_CHANGE_DAYS`,
+/// `RETENTION_ACCESS_LOG_DAYS` and `RETENTION_INTERVAL_HOURS`; a value of 0
+/// days deletes as soon as the record expires. Mention notifications go away
+/// with their comments, so sessions, pending email changes and the access log
+/// are the only stores the policy covers. Portal links and sessions, and the
+/// refresh tokens of "remember me" logins, go as soon as they expire.
+/// `COMPANY_PURGE_GRACE_DAYS` is the grace period an off-boarded company is
+/// kept archived; the sweep hard-deletes it once it is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub session_days: u64,
+    pub email_change_days: u64,
+    /// Age after which access log events are deleted.
+    pub access_log_days: u64,
+    pub interval_hours: u64,
+    pub company_purge_grace_days: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            session_days: 30,
+            email_change_days: 7,
+            access_log_days: 365,
+            interval_hours: 24,
+            company_purge_grace_days: 30,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            session_days: read("RETENTION_SESSION_DAYS", defaults.session_days),
+            email_change_days: read("RETENTION_EMAIL_CHANGE_DAYS", defaults.email_change_days),
+            access_log_days: read("RETENTION_ACCESS_LOG_DAYS", defaults.access_log_days),
+            interval_hours: read("RETENTION_INTERVAL_HOURS", defaults.interval_hours).max(1),
+            company_purge_grace_days: read(
+                "COMPANY_PURGE_GRACE_DAYS",
+                defaults.company_purge_grace_days,
+            ),
+        }
+    }
+
+    pub fn company_purge_grace(&self) -> Duration {
+        Duration::from_secs(self.company_purge_grace_days * 60 * 60 * 24)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionReport {
+    /// Sessions plus expired "remember me" refresh tokens and sign-in links.
+    pub sessions_deleted: u64,
+    pub email_changes_deleted: u64,
+    pub access_events_deleted: u64,
+    pub companies_purged: u64,
+    pub portal_access_deleted: u64,
+    /// CSV statements uploaded and never imported.
+    pub import_drafts_deleted: u64,
+}
+
+pub async fn apply_retention(
+    state: &AppState,
+    policy: &RetentionPolicy,
+    now: SystemTime,
+) -> Result<RetentionReport> {
+    let cutoff =
+        |days: u64| DateTime::from_system_time(now - Duration::from_secs(days * 60 * 60 * 24));
+    let sessions = state
+        .sessions
+        .delete_many(doc! { "expires_at": { "$lt": cutoff(policy.session_days) } })
+        .await?;
+    let email_changes = state
+        .email_changes
+        .delete_many(doc! { "expires_at": { "$lt": cutoff(policy.email_change_days) } })
+        .await?;
+    let access_events = state
+        .access_events
+        .delete_many(doc! { "created_at": { "$lt": cutoff(policy.access_log_days) } })
+        .await?;
+    let expired = doc! { "expires_at": { "$lt": DateTime::from_system_time(now) } };
+    let portal_links = state.portal_links.delete_many(expired.clone()).await?;
+    let portal_sessions = state.portal_sessions.delete_many(expired.clone()).await?;
+    let refresh_tokens = state.refresh_tokens.delete_many(expired.clone()).await?;
+    let login_links = state.login_links.delete_many(expired.clone()).await?;
+    let import_drafts = state.import_drafts.delete_many(expired).await?;
+    let companies_purged = purge_archived_companies(state, now).await?;
+    Ok(RetentionReport {
+        sessions_deleted: sessions.deleted_count
+            + refresh_tokens.deleted_count
+            + login_links.deleted_count,
+        email_changes_deleted: email_changes.deleted_count,
+        access_events_deleted: access_events.deleted_count,
+        companies_purged,
+        portal_access_deleted: portal_links.deleted_count + portal_sessions.deleted_count,
+        import_drafts_deleted: import_drafts.deleted_count,
+    })
+}
+
+/// Runs `apply_retention` right away and then every `interval_hours`.
+pub fn spawn_retention_task(state: Arc<AppState>, policy: RetentionPolicy) {
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(Duration::from_secs(policy.interval_hours * 60 * 60));
+        loop {
+            ticker.tick().await;
+            let result = apply_retention(&state, &policy, SystemTime::now()).await;
+            metrics().record_task_run("retention", result.is_ok());
+            match result {
+                Ok(report) => println!(
+                    "retention sweep: {} sessions, {} email changes, {} access events, {} portal links and sessions deleted, {} import drafts deleted, {} companies purged",
+                    report.sessions_deleted,
+                    report.email_changes_deleted,
+                    report.access_events_deleted,
+                    report.portal_access_deleted,
+                    report.import_drafts_deleted,
+                    report.companies_purged
+                ),
+                Err(err) => eprintln!("retention sweep failed: {err:?}"),
+            }
+        }
+    });
+}
@@ -11,10 +11,14 @@ use std::{
 };
 
 use crate::models::{
-    Account, Category, Company, ConceptStatus, Contact, Forecast, PlannedEntry, RecurringPlan,
-    SeedUser, Transaction, User, UserCompany,
+    Account, Category, ChatNotifications, Company, CompanyBranding, CompanyOnboarding,
+    CompanyStorage, CompanyWebhooks, ConceptStatus, Contact, FiscalCalendar, Forecast,
+    FormatPreferences, LoginLinkMode, PlannedEntry, RecurringPlan, RequiredFieldPolicy, SeedUser,
+    Transaction, User, UserCompany,
 };
 
+use super::{category_suggestions::rebuild_category_usage, seed_yaml::parse_seed_yaml};
+
 pub(super) async fn is_database_empty(db: &Database) -> Result<bool> {
     let users_coll = db.collection::<User>("users");
     let count = users_coll.estimated_document_count().await?;
@@ -58,6 +62,11 @@ pub(super) fn derive_company_names(users: &[SeedUser]) -> Vec<String> {
     companies
 }
 
+/// Indexes behind the name search of the form pickers, the access log, the
+/// category suggestions, the account valuations, the monthly summaries, the
+/// API token lookup, the bank sync upserts, the external ids and refunds of
+/// transactions, the archived planned entries and the account groups. Creating
+/// an index that already exists is a no-op.
 pub(super) async fn ensure_collections(db: &Database) -> Result<()> {
     let existing = db.list_collection_names().await?;
     if !existing.iter().any(|name| name == "users") {
@@ -72,9 +81,51 @@ pub(super) async fn ensure_collections(db: &Database) -> Result<()> {
     if !existing.iter().any(|name| name == "sessions") {
         db.create_collection("sessions").await?;
     }
+    if !existing.iter().any(|name| name == "email_changes") {
+        db.create_collection("email_changes").await?;
+    }
+    if !existing.iter().any(|name| name == "sso_identities") {
+        db.create_collection("sso_identities").await?;
+    }
+    if !existing.iter().any(|name| name == "migrations") {
+        db.create_collection("migrations").await?;
+    }
+    if !existing.iter().any(|name| name == "api_tokens") {
+        db.create_collection("api_tokens").await?;
+    }
+    if !existing.iter().any(|name| name == "refresh_tokens") {
+        db.create_collection("refresh_tokens").await?;
+    }
+    if !existing.iter().any(|name| name == "login_links") {
+        db.create_collection("login_links").await?;
+    }
+    if !existing.iter().any(|name| name == "access_events") {
+        db.create_collection("access_events").await?;
+    }
     if !existing.iter().any(|name| name == "accounts") {
         db.create_collection("accounts").await?;
     }
+    if !existing.iter().any(|name| name == "account_groups") {
+        db.create_collection("account_groups").await?;
+    }
+    if !existing.iter().any(|name| name == "balance_snapshots") {
+        db.create_collection("balance_snapshots").await?;
+    }
+    if !existing.iter().any(|name| name == "account_valuations") {
+        db.create_collection("account_valuations").await?;
+    }
+    if !existing.iter().any(|name| name == "account_category_usage") {
+        db.create_collection("account_category_usage").await?;
+    }
+    if !existing.iter().any(|name| name == "monthly_summaries") {
+        db.create_collection("monthly_summaries").await?;
+    }
+    if !existing.iter().any(|name| name == "bank_connections") {
+        db.create_collection("bank_connections").await?;
+    }
+    if !existing.iter().any(|name| name == "bank_transactions") {
+        db.create_collection("bank_transactions").await?;
+    }
     if !existing.iter().any(|name| name == "categories") {
         db.create_collection("categories").await?;
     }
@@ -84,15 +135,57 @@ pub(super) async fn ensure_collections(db: &Database) -> Result<()> {
     if !existing.iter().any(|name| name == "recurring_plans") {
         db.create_collection("recurring_plans").await?;
     }
+    if !existing.iter().any(|name| name == "plan_versions") {
+        db.create_collection("plan_versions").await?;
+    }
     if !existing.iter().any(|name| name == "planned_entries") {
         db.create_collection("planned_entries").await?;
     }
+    if !existing
+        .iter()
+        .any(|name| name == "planned_entries_archive")
+    {
+        db.create_collection("planned_entries_archive").await?;
+    }
     if !existing.iter().any(|name| name == "transactions") {
         db.create_collection("transactions").await?;
     }
+    if !existing.iter().any(|name| name == "text_replacements") {
+        db.create_collection("text_replacements").await?;
+    }
+    if !existing.iter().any(|name| name == "import_profiles") {
+        db.create_collection("import_profiles").await?;
+    }
+    if !existing.iter().any(|name| name == "import_drafts") {
+        db.create_collection("import_drafts").await?;
+    }
+    if !existing.iter().any(|name| name == "comments") {
+        db.create_collection("comments").await?;
+    }
+    if !existing.iter().any(|name| name == "notifications") {
+        db.create_collection("notifications").await?;
+    }
+    if !existing.iter().any(|name| name == "receipts") {
+        db.create_collection("receipts").await?;
+    }
+    if !existing.iter().any(|name| name == "custom_fields") {
+        db.create_collection("custom_fields").await?;
+    }
+    if !existing.iter().any(|name| name == "tax_profiles") {
+        db.create_collection("tax_profiles").await?;
+    }
     if !existing.iter().any(|name| name == "forecasts") {
         db.create_collection("forecasts").await?;
     }
+    if !existing.iter().any(|name| name == "sequences") {
+        db.create_collection("sequences").await?;
+    }
+    if !existing.iter().any(|name| name == "portal_links") {
+        db.create_collection("portal_links").await?;
+    }
+    if !existing.iter().any(|name| name == "portal_sessions") {
+        db.create_collection("portal_sessions").await?;
+    }
     if !existing.iter().any(|name| name == "projects") {
         db.create_collection("projects").await?;
     }
@@ -147,6 +240,18 @@ pub(super) async fn seed_default_companies(
                 created_at: None,
                 updated_at: None,
                 notes: None,
+                sso_domains: Vec::new(),
+                login_links: LoginLinkMode::Off,
+                archived_at: None,
+                purge_after: None,
+                branding: CompanyBranding::default(),
+                chat_notifications: ChatNotifications::default(),
+                notifications: None,
+                onboarding: CompanyOnboarding::default(),
+                storage: CompanyStorage::default(),
+                webhooks: CompanyWebhooks::default(),
+                fiscal_calendar: FiscalCalendar::default(),
+                required_fields: RequiredFieldPolicy::default(),
             })
             .await?;
         let id = result
@@ -239,6 +344,7 @@ pub(super) async fn seed_default_users(
                         "secret": &user.secret,
                         "company": &primary_company_id,
                         "companies": &companies_final,
+                        "is_superadmin": user.is_superadmin,
                     }
                 },
             )
@@ -252,6 +358,12 @@ pub(super) async fn seed_default_users(
                     secret: user.secret.clone(),
                     company_id: Some(primary_company_id.clone()),
                     company_ids: companies_final.clone(),
+                    is_active: true,
+                    format_preferences: FormatPreferences::default(),
+                    is_superadmin: user.is_superadmin,
+                    display_name: None,
+                    avatar_path: None,
+                    avatar_content_type: None,
                 })
                 .await?;
             inserted
@@ -276,6 +388,8 @@ pub(super) async fn seed_default_users(
                     company_id: cid.clone(),
                     role: role_final.clone(),
                     permissions: Vec::new(),
+                    account_ids: Vec::new(),
+                    dashboard_widgets: None,
                 })
                 .await;
         }
@@ -283,6 +397,36 @@ pub(super) async fn seed_default_users(
     Ok(())
 }
 
+/// Finance records of a seed, linked by the ids they were written with;
+/// `insert_seed_data` gives them fresh ids in the target company.
+pub(super) struct SeedData {
+    pub accounts: Vec<Account>,
+    pub categories: Vec<Category>,
+    pub contacts: Vec<Contact>,
+    pub recurring_plans: Vec<RecurringPlan>,
+    pub planned_entries: Vec<PlannedEntry>,
+    pub transactions: Vec<Transaction>,
+    pub forecasts: Vec<Forecast>,
+}
+
+/// The sample finance data: the YAML file in SEED_FILE when set, otherwise
+/// the JSON files (each one optional).
+fn load_sample_finance() -> Result<SeedData> {
+    if let Ok(path) = env::var("SEED_FILE") {
+        let contents = fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+        return parse_seed_yaml(&contents).with_context(|| format!("loading {path}"));
+    }
+    Ok(SeedData {
+        accounts: load_json_array("ACCOUNTS_FILE", "./data/accounts.json")?,
+        categories: load_json_array("CATEGORIES_FILE", "./data/categories.json")?,
+        contacts: load_json_array("CONTACTS_FILE", "./data/contacts.json")?,
+        recurring_plans: load_json_array("RECURRING_PLANS_FILE", "./data/recurring_plans.json")?,
+        planned_entries: load_json_array("PLANNED_ENTRIES_FILE", "./data/planned_entries.json")?,
+        transactions: load_json_array("TRANSACTIONS_FILE", "./data/transactions.json")?,
+        forecasts: load_json_array("FORECASTS_FILE", "./data/forecasts.json")?,
+    })
+}
+
 pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<ObjectId>) -> Result<()> {
     let company_id = if let Some(id) = company_id {
         id
@@ -295,6 +439,17 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
         return Ok(());
     }
 
+    insert_seed_data(db, &company_id, load_sample_finance()?).await
+}
+
+/// Inserts `data` into the company, remapping the references between its
+/// records to the ids they get.
+pub(super) async fn insert_seed_data(
+    db: &Database,
+    company_id: &ObjectId,
+    data: SeedData,
+) -> Result<()> {
+    let accounts_coll = db.collection::<Account>("accounts");
     let categories_coll = db.collection::<Category>("categories");
     let contacts_coll = db.collection::<Contact>("contacts");
     let plans_coll = db.collection::<RecurringPlan>("recurring_plans");
@@ -302,20 +457,8 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
     let tx_coll = db.collection::<Transaction>("transactions");
     let forecast_coll = db.collection::<Forecast>("forecasts");
 
-    let accounts_seed: Vec<Account> = load_json_array("ACCOUNTS_FILE", "./data/accounts.json")?;
-    let categories_seed: Vec<Category> =
-        load_json_array("CATEGORIES_FILE", "./data/categories.json")?;
-    let contacts_seed: Vec<Contact> = load_json_array("CONTACTS_FILE", "./data/contacts.json")?;
-    let plans_seed: Vec<RecurringPlan> =
-        load_json_array("RECURRING_PLANS_FILE", "./data/recurring_plans.json")?;
-    let planned_seed: Vec<PlannedEntry> =
-        load_json_array("PLANNED_ENTRIES_FILE", "./data/planned_entries.json")?;
-    let tx_seed: Vec<Transaction> =
-        load_json_array("TRANSACTIONS_FILE", "./data/transactions.json")?;
-    let forecasts_seed: Vec<Forecast> = load_json_array("FORECASTS_FILE", "./data/forecasts.json")?;
-
     let mut account_map = HashMap::new();
-    for acc in accounts_seed {
+    for acc in data.accounts {
         let old_id = acc.id.unwrap_or_else(ObjectId::new);
         let res = accounts_coll
             .insert_one(Account {
@@ -328,6 +471,11 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 created_at: acc.created_at,
                 updated_at: acc.updated_at,
                 notes: acc.notes,
+                opening_balance: acc.opening_balance,
+                opening_date: acc.opening_date,
+                opening_balance_changes: Vec::new(),
+                group_id: None,
+                position: 0,
             })
             .await?;
         let new_id = res
@@ -338,7 +486,7 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
     }
 
     let mut category_map = HashMap::new();
-    for cat in categories_seed {
+    for cat in data.categories {
         let old_id = cat.id.unwrap_or_else(ObjectId::new);
         let parent_old = cat.parent_id;
         let parent_new = parent_old.and_then(|p| category_map.get(&p).cloned());
@@ -352,6 +500,9 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 created_at: cat.created_at,
                 updated_at: cat.updated_at,
                 notes: cat.notes,
+                monthly_budget: cat.monthly_budget,
+                budget_alert: None,
+                tax_profile_id: None,
             })
             .await?;
         let new_id = res
@@ -362,7 +513,7 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
     }
 
     let mut contact_map = HashMap::new();
-    for contact in contacts_seed {
+    for contact in data.contacts {
         let old_id = contact.id.unwrap_or_else(ObjectId::new);
         let res = contacts_coll
             .insert_one(Contact {
@@ -373,9 +524,11 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 rfc: None,
                 email: contact.email,
                 phone: contact.phone,
+                payment_terms_days: None,
                 created_at: contact.created_at,
                 updated_at: contact.updated_at,
                 notes: contact.notes,
+                custom_fields: contact.custom_fields,
             })
             .await?;
         let new_id = res
@@ -386,7 +539,7 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
     }
 
     let mut plan_map = HashMap::new();
-    for plan in plans_seed {
+    for plan in data.recurring_plans {
         let old_id = plan.id.unwrap_or_else(ObjectId::new);
         let category_id = remap_id(&category_map, &plan.category_id)?;
         let account_expected_id = remap_id(&account_map, &plan.account_expected_id)?;
@@ -408,6 +561,9 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 end_date: plan.end_date,
                 is_active: plan.is_active,
                 version: plan.version,
+                scenario_weights: plan.scenario_weights,
+                probability: plan.probability,
+                skipped_due_dates: plan.skipped_due_dates,
                 created_at: plan.created_at,
                 updated_at: plan.updated_at,
                 notes: plan.notes,
@@ -421,7 +577,7 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
     }
 
     let mut planned_map = HashMap::new();
-    for pe in planned_seed {
+    for pe in data.planned_entries {
         let old_id = pe.id.unwrap_or_else(ObjectId::new);
         let category_id = remap_id(&category_map, &pe.category_id)?;
         let account_expected_id = remap_id(&account_map, &pe.account_expected_id)?;
@@ -446,13 +602,20 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 original_amount_estimated: None,
                 due_date: pe.due_date,
                 original_due_date: None,
+                generated_due_date: None,
                 status: pe.status,
+                is_customized: pe.is_customized,
                 created_at: pe.created_at,
                 updated_at: pe.updated_at,
                 notes: pe.notes,
                 cfdi_uuid: None,
                 currency: None,
                 cfdi_folio: None,
+                coverage_adjustment: None,
+                slip_count: 0,
+                amount_revisions: Vec::new(),
+                exchange_rate: pe.exchange_rate,
+                deposit_allocations: Vec::new(),
             })
             .await?;
         let new_id = res
@@ -462,7 +625,7 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
         planned_map.insert(old_id, new_id);
     }
 
-    for tx in tx_seed {
+    for tx in data.transactions {
         let category_id = remap_id(&category_map, &tx.category_id)?;
         let account_from_id = tx
             .account_from_id
@@ -492,12 +655,25 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 cfdi_uuid: None,
                 currency: None,
                 cfdi_folio: None,
+                // Numbered by the `backfill_references` migration, in date order.
+                reference: None,
+                external_id: None,
+                bank_reference: None,
                 notes: tx.notes,
+                tags: tx.tags,
+                custom_fields: tx.custom_fields,
+                tax: None,
+                refund_of: None,
+                exchange_rate: tx.exchange_rate,
+                fx_adjustment: None,
+                hold: None,
             })
             .await?;
     }
+    // Inserted past `create_transaction`, so their category counters are not.
+    rebuild_category_usage(db).await?;
 
-    for fc in forecasts_seed {
+    for fc in data.forecasts {
         let _ = forecast_coll
             .insert_one(Forecast {
                 id: None,
@@ -510,10 +686,13 @@ pub(super) async fn seed_sample_finance(db: &Database, company_id: Option<Object
                 projected_income_total: fc.projected_income_total,
                 projected_expense_total: fc.projected_expense_total,
                 projected_net: fc.projected_net,
+                weighted_income_total: fc.weighted_income_total,
                 initial_balance: fc.initial_balance,
                 final_balance: fc.final_balance,
                 details: fc.details,
                 scenario_name: fc.scenario_name,
+                scenario_group_id: fc.scenario_group_id,
+                reference: None,
                 notes: fc.notes,
             })
             .await?;
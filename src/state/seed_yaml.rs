@@ -0,0 +1,364 @@
+// Seed files in YAML, where records point at each other by name
+// (`category: Renta`) instead of by pre-linked ObjectIds. Records take the
+// fields of the JSON seed files minus `_id` and `company_id`; the names are
+// resolved here and the result goes through the same insertion as the JSON
+// seed. `data/seed.example.yaml` documents the format.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use mongodb::{
+    Database,
+    bson::{doc, oid::ObjectId},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+
+use crate::models::{Account, Company};
+
+use super::seed::{SeedData, insert_seed_data};
+
+type Record = Map<String, Value>;
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SeedFile {
+    accounts: Vec<Record>,
+    categories: Vec<Record>,
+    contacts: Vec<Record>,
+    recurring_plans: Vec<Record>,
+    planned_entries: Vec<Record>,
+    transactions: Vec<Record>,
+    forecasts: Vec<Record>,
+}
+
+/// Fields read as dates: `YYYY-MM-DD` (midnight UTC) or RFC 3339.
+const DATE_FIELDS: &[&str] = &[
+    "date",
+    "due_date",
+    "start_date",
+    "end_date",
+    "opening_date",
+    "generated_at",
+    "created_at",
+    "updated_at",
+];
+
+/// Ids given to the records of one section, by name. `None` marks a name
+/// shared by several records, which cannot be referenced.
+struct Names {
+    kind: &'static str,
+    ids: HashMap<String, Option<ObjectId>>,
+}
+
+impl Names {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            ids: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, name: Option<&str>, id: ObjectId) {
+        if let Some(name) = name {
+            self.ids
+                .entry(name.to_string())
+                .and_modify(|id| *id = None)
+                .or_insert(Some(id));
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Result<ObjectId> {
+        match self.ids.get(name) {
+            Some(Some(id)) => Ok(*id),
+            Some(None) => bail!("more than one {} is named \"{name}\"", self.kind),
+            None => bail!("unknown {} \"{name}\"", self.kind),
+        }
+    }
+}
+
+fn oid(id: ObjectId) -> Value {
+    json!({ "$oid": id.to_hex() })
+}
+
+fn parse_date(value: &str) -> Result<String> {
+    let date = match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(day) => day.and_time(Default::default()).and_utc(),
+        Err(_) => chrono::DateTime::parse_from_rfc3339(value)
+            .with_context(|| format!("\"{value}\" is not a YYYY-MM-DD or RFC 3339 date"))?
+            .with_timezone(&Utc),
+    };
+    Ok(date.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Replaces the `key: name` reference of `record` with `field: id`.
+fn link(record: &mut Record, key: &str, field: &str, names: &Names) -> Result<Option<ObjectId>> {
+    match record.remove(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(name)) => {
+            let id = names.resolve(&name).with_context(|| format!("`{key}`"))?;
+            record.insert(field.to_string(), oid(id));
+            Ok(Some(id))
+        }
+        Some(other) => bail!("`{key}` must be a name, found {other}"),
+    }
+}
+
+/// `record` as the JSON of its model: with `id`, a placeholder `company_id`
+/// (the insertion sets the real one) and dates in extended JSON.
+fn finish<T: DeserializeOwned>(mut record: Record, id: ObjectId) -> Result<T> {
+    for field in ["_id", "company_id"] {
+        if record.contains_key(field) {
+            bail!("`{field}` is assigned when seeding; leave it out");
+        }
+    }
+    for field in DATE_FIELDS {
+        if let Some(Value::String(value)) = record.get(*field) {
+            let date = parse_date(value).with_context(|| format!("`{field}`"))?;
+            record.insert(field.to_string(), json!({ "$date": date }));
+        }
+    }
+    record.insert("_id".into(), oid(id));
+    record.insert("company_id".into(), oid(ObjectId::from_bytes([0; 12])));
+    Ok(serde_json::from_value(Value::Object(record))?)
+}
+
+/// "recurring_plans[2] (Renta oficina)", to point at a record in errors.
+fn describe(section: &str, index: usize, record: &Record) -> String {
+    match name(record) {
+        Some(name) => format!("{section}[{index}] ({name})"),
+        None => format!("{section}[{index}]"),
+    }
+}
+
+fn name(record: &Record) -> Option<&str> {
+    record.get("name").and_then(Value::as_str)
+}
+
+/// Sets `field` to the category's flow when the record leaves it out.
+fn default_flow(
+    record: &mut Record,
+    field: &str,
+    category: Option<ObjectId>,
+    flows: &HashMap<ObjectId, Value>,
+) {
+    if !record.contains_key(field)
+        && let Some(flow) = category.and_then(|id| flows.get(&id))
+    {
+        record.insert(field.to_string(), flow.clone());
+    }
+}
+
+/// Reads a YAML seed, resolving the references by name. Categories can only
+/// name a parent listed before them.
+pub(super) fn parse_seed_yaml(contents: &str) -> Result<SeedData> {
+    let file: SeedFile = serde_yaml::from_str(contents)?;
+
+    let mut accounts = Names::new("account");
+    let mut data_accounts = Vec::new();
+    for (index, record) in file.accounts.into_iter().enumerate() {
+        let at = describe("accounts", index, &record);
+        let id = ObjectId::new();
+        accounts.add(name(&record), id);
+        data_accounts.push(finish(record, id).context(at)?);
+    }
+
+    let mut categories = Names::new("category");
+    let mut flows = HashMap::new();
+    let mut data_categories = Vec::new();
+    for (index, mut record) in file.categories.into_iter().enumerate() {
+        let at = describe("categories", index, &record);
+        let id = ObjectId::new();
+        link(&mut record, "parent", "parent_id", &categories)
+            .context("parents go before their subcategories")
+            .context(at.clone())?;
+        categories.add(name(&record), id);
+        if let Some(flow) = record.get("flow_type") {
+            flows.insert(id, flow.clone());
+        }
+        data_categories.push(finish(record, id).context(at)?);
+    }
+
+    let mut contacts = Names::new("contact");
+    let mut data_contacts = Vec::new();
+    for (index, record) in file.contacts.into_iter().enumerate() {
+        let at = describe("contacts", index, &record);
+        let id = ObjectId::new();
+        contacts.add(name(&record), id);
+        data_contacts.push(finish(record, id).context(at)?);
+    }
+
+    let mut plans = Names::new("recurring plan");
+    let mut data_plans = Vec::new();
+    for (index, mut record) in file.recurring_plans.into_iter().enumerate() {
+        let at = describe("recurring_plans", index, &record);
+        let id = ObjectId::new();
+        plans.add(name(&record), id);
+        let plan = (|| {
+            let category = link(&mut record, "category", "category_id", &categories)?;
+            link(&mut record, "account", "account_expected_id", &accounts)?;
+            link(&mut record, "contact", "contact_id", &contacts)?;
+            default_flow(&mut record, "flow_type", category, &flows);
+            finish(record, id)
+        })()
+        .context(at)?;
+        data_plans.push(plan);
+    }
+
+    let mut entries = Names::new("planned entry");
+    let mut data_entries = Vec::new();
+    for (index, mut record) in file.planned_entries.into_iter().enumerate() {
+        let at = describe("planned_entries", index, &record);
+        let id = ObjectId::new();
+        entries.add(name(&record), id);
+        let entry = (|| {
+            let category = link(&mut record, "category", "category_id", &categories)?;
+            link(&mut record, "account", "account_expected_id", &accounts)?;
+            link(&mut record, "contact", "contact_id", &contacts)?;
+            link(&mut record, "plan", "recurring_plan_id", &plans)?;
+            default_flow(&mut record, "flow_type", category, &flows);
+            finish(record, id)
+        })()
+        .context(at)?;
+        data_entries.push(entry);
+    }
+
+    let mut data_transactions = Vec::new();
+    for (index, mut record) in file.transactions.into_iter().enumerate() {
+        let at = format!("transactions[{index}]");
+        let transaction = (|| {
+            let category = link(&mut record, "category", "category_id", &categories)?;
+            link(&mut record, "account_from", "account_from_id", &accounts)?;
+            link(&mut record, "account_to", "account_to_id", &accounts)?;
+            link(&mut record, "planned_entry", "planned_entry_id", &entries)?;
+            default_flow(&mut record, "transaction_type", category, &flows);
+            finish(record, ObjectId::new())
+        })()
+        .context(at)?;
+        data_transactions.push(transaction);
+    }
+
+    let mut data_forecasts = Vec::new();
+    for (index, record) in file.forecasts.into_iter().enumerate() {
+        data_forecasts
+            .push(finish(record, ObjectId::new()).context(format!("forecasts[{index}]"))?);
+    }
+
+    Ok(SeedData {
+        accounts: data_accounts,
+        categories: data_categories,
+        contacts: data_contacts,
+        recurring_plans: data_plans,
+        planned_entries: data_entries,
+        transactions: data_transactions,
+        forecasts: data_forecasts,
+    })
+}
+
+/// Loads a YAML seed into the company with `slug`, which must have no
+/// accounts yet (seeds are for bootstrapping, not for merging).
+pub async fn seed_company_from_yaml(db: &Database, slug: &str, contents: &str) -> Result<ObjectId> {
+    let data = parse_seed_yaml(contents)?;
+    let company_id = db
+        .collection::<Company>("company")
+        .find_one(doc! { "slug": slug })
+        .await?
+        .and_then(|company| company.id)
+        .with_context(|| format!("no company with slug {slug}"))?;
+    let existing = db
+        .collection::<Account>("accounts")
+        .count_documents(doc! { "company_id": company_id })
+        .await?;
+    if existing > 0 {
+        bail!("company {slug} already has accounts; seeds only go into empty companies");
+    }
+    insert_seed_data(db, &company_id, data).await?;
+    Ok(company_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FlowType, TransactionType};
+
+    #[test]
+    fn example_seed_links_records_by_name() {
+        let data = parse_seed_yaml(include_str!("../../data/seed.example.yaml")).unwrap();
+        let category = |name: &str| {
+            data.categories
+                .iter()
+                .find(|category| category.name == name)
+                .unwrap()
+        };
+        let account = |name: &str| {
+            data.accounts
+                .iter()
+                .find(|account| account.name == name)
+                .unwrap()
+                .id
+        };
+
+        assert_eq!(category("Renta").parent_id, category("Gastos fijos").id);
+        let rent = &data.recurring_plans[0];
+        assert_eq!(Some(rent.category_id), category("Renta").id);
+        assert_eq!(Some(rent.account_expected_id), account("Banco principal"));
+        assert_eq!(rent.flow_type, FlowType::Expense);
+        assert_eq!(
+            rent.start_date.try_to_rfc3339_string().unwrap(),
+            "2024-11-01T00:00:00Z"
+        );
+
+        let payroll = &data.planned_entries[1];
+        assert_eq!(payroll.recurring_plan_id, data.recurring_plans[1].id);
+        assert_eq!(payroll.flow_type, FlowType::Income);
+
+        let transfer = &data.transactions[2];
+        assert_eq!(transfer.transaction_type, TransactionType::Transfer);
+        assert_eq!(transfer.account_to_id, account("Efectivo caja"));
+        assert_eq!(
+            data.transactions[1].planned_entry_id,
+            data.planned_entries[1].id
+        );
+        assert_eq!(
+            data.transactions[1].transaction_type,
+            TransactionType::Income
+        );
+        assert_eq!(data.forecasts.len(), 1);
+    }
+
+    #[test]
+    fn unknown_and_ambiguous_names_point_at_the_record() {
+        let error = |yaml: &str| format!("{:#}", parse_seed_yaml(yaml).err().unwrap());
+
+        let unknown = error(
+            "categories:\n  - {name: Renta, flow_type: expense}\n\
+             recurring_plans:\n  - {name: Oficina, category: Rentas, account: Banco, \
+             amount_estimated: 1, frequency: monthly, start_date: 2025-01-01}\n",
+        );
+        assert!(
+            unknown.starts_with("recurring_plans[0] (Oficina)"),
+            "{unknown}"
+        );
+        assert!(unknown.contains("unknown category \"Rentas\""), "{unknown}");
+
+        let ambiguous = error(
+            "accounts:\n  - {name: Banco, account_type: bank, currency: MXN}\n  \
+             - {name: Banco, account_type: bank, currency: USD}\n\
+             categories:\n  - {name: Renta, flow_type: expense}\n\
+             transactions:\n  - {date: 2025-01-01, description: x, category: Renta, \
+             account_from: Banco, amount: 1}\n",
+        );
+        assert!(ambiguous.contains("transactions[0]"), "{ambiguous}");
+        assert!(ambiguous.contains("more than one account is named \"Banco\""));
+
+        let late_parent = error(
+            "categories:\n  - {name: Luz, flow_type: expense, parent: Servicios}\n  \
+             - {name: Servicios, flow_type: expense}\n",
+        );
+        assert!(late_parent.contains("parents go before their subcategories"));
+
+        assert!(error("accounts:\n  - {_id: x, name: Banco}\n").contains("leave it out"));
+        assert!(error("cuentas: []\n").contains("unknown field"));
+    }
+}
@@ -0,0 +1,96 @@
+// Company numbering sequences behind the human-readable references of
+// transactions and forecasts, e.g. `TX-2025-0001`. One counter per company,
+// kind and year in `sequences`, incremented atomically.
+
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use mongodb::{
+    Collection,
+    bson::{DateTime, doc, oid::ObjectId},
+    options::ReturnDocument,
+};
+
+use crate::models::SequenceCounter;
+
+use super::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceKind {
+    Transaction,
+    Forecast,
+}
+
+impl SequenceKind {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            SequenceKind::Transaction => "TX",
+            SequenceKind::Forecast => "FC",
+        }
+    }
+}
+
+pub fn format_reference(kind: SequenceKind, year: i32, number: i64) -> String {
+    format!("{}-{year}-{number:04}", kind.prefix())
+}
+
+/// Takes the next number of the counter, creating it at 1.
+pub(super) async fn increment_sequence(
+    counters: &Collection<SequenceCounter>,
+    company_id: &ObjectId,
+    kind: SequenceKind,
+    year: i32,
+) -> Result<i64> {
+    let counter = counters
+        .find_one_and_update(
+            doc! { "_id": format!("{}:{}:{year}", company_id.to_hex(), kind.prefix()) },
+            doc! {
+                "$inc": { "value": 1_i64 },
+                "$setOnInsert": { "company_id": company_id, "kind": kind.prefix(), "year": year },
+            },
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await?
+        .context("sequence counter missing after upsert")?;
+    Ok(counter.value)
+}
+
+/// Next number of the company's `kind` sequence for `year`. Numbers are never
+/// handed out twice, even to concurrent callers; deleted documents leave gaps.
+pub async fn next_sequence(
+    state: &AppState,
+    company_id: &ObjectId,
+    kind: SequenceKind,
+    year: i32,
+) -> Result<i64> {
+    increment_sequence(&state.sequences, company_id, kind, year).await
+}
+
+/// Reference for a new document dated `date`, numbered within its year.
+pub async fn next_reference(
+    state: &AppState,
+    company_id: &ObjectId,
+    kind: SequenceKind,
+    date: DateTime,
+) -> Result<String> {
+    let year = date.to_chrono().year();
+    let number = next_sequence(state, company_id, kind, year).await?;
+    Ok(format_reference(kind, year, number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_pad_the_number_within_the_year() {
+        assert_eq!(
+            format_reference(SequenceKind::Transaction, 2025, 1),
+            "TX-2025-0001"
+        );
+        assert_eq!(
+            format_reference(SequenceKind::Forecast, 2026, 12345),
+            "FC-2026-12345"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use std::time::SystemTime;
+
+use crate::models::{Company, SsoIdentity, UserRole};
+use crate::totp::{DEFAULT_SECRET_BYTES, generate_base32_secret_n};
+
+use super::{AppState, create_user, get_user_by_id, username_taken};
+
+/// What `sso_login_user` decided for an identity coming back from the
+/// provider. Only `Existing` and `Provisioned` end in a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SsoLoginOutcome {
+    /// The identity was already linked; carries the user's username.
+    Existing(String),
+    /// A new user was created in the company owning the email domain.
+    Provisioned(String),
+    /// The linked user is deactivated.
+    Inactive,
+    /// The provider did not return a verified email, so the identity cannot
+    /// be matched to a company.
+    MissingEmail,
+    /// A local user already uses this email. It has to be linked from the
+    /// account page while logged in, otherwise anyone controlling an identity
+    /// with that address at the provider could take the account over.
+    AccountExists,
+    /// No company claims the email domain.
+    NoCompanyForDomain,
+}
+
+/// Lowercased domain part of an email address.
+pub fn email_domain(email: &str) -> Option<String> {
+    let (local, domain) = email.trim().rsplit_once('@')?;
+    let domain = domain.trim().to_lowercase();
+    if local.is_empty() || domain.is_empty() || !domain.contains('.') {
+        return None;
+    }
+    Some(domain)
+}
+
+/// The company, other than `exclude`, that already claims any of `domains`.
+pub async fn sso_domain_owner(
+    state: &AppState,
+    domains: &[String],
+    exclude: Option<&ObjectId>,
+) -> Result<Option<Company>> {
+    if domains.is_empty() {
+        return Ok(None);
+    }
+    let mut filter = doc! { "sso_domains": { "$in": domains } };
+    if let Some(exclude) = exclude {
+        filter.insert("_id", doc! { "$ne": exclude });
+    }
+    Ok(state.companies.find_one(filter).await?)
+}
+
+/// Replaces the email domains whose SSO users land in `company_id`. A domain
+/// can only belong to one company.
+pub async fn set_company_sso_domains(
+    state: &AppState,
+    company_id: &ObjectId,
+    domains: &[String],
+) -> Result<()> {
+    if let Some(other) = sso_domain_owner(state, domains, Some(company_id)).await? {
+        anyhow::bail!("domain already assigned to company '{}'", other.name);
+    }
+    state
+        .companies
+        .update_one(
+            doc! { "_id": company_id },
+            doc! { "$set": { "sso_domains": domains } },
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn find_company_by_email_domain(
+    state: &AppState,
+    email: &str,
+) -> Result<Option<Company>> {
+    let Some(domain) = email_domain(email) else {
+        return Ok(None);
+    };
+    Ok(state
+        .companies
+        .find_one(doc! { "sso_domains": domain, "is_active": true })
+        .await?)
+}
+
+pub async fn find_sso_identity(
+    state: &AppState,
+    issuer: &str,
+    subject: &str,
+) -> Result<Option<SsoIdentity>> {
+    Ok(state
+        .sso_identities
+        .find_one(doc! { "issuer": issuer, "subject": subject })
+        .await?)
+}
+
+pub async fn list_sso_identities(state: &AppState, user_id: &ObjectId) -> Result<Vec<SsoIdentity>> {
+    let cursor = state
+        .sso_identities
+        .find(doc! { "user_id": user_id })
+        .sort(doc! { "linked_at": 1 })
+        .await?;
+    Ok(cursor.try_collect().await?)
+}
+
+/// Links the provider identity to `user_id`. Linking an identity the user
+/// already has only refreshes its email; one linked to someone else fails.
+pub async fn link_sso_identity(
+    state: &AppState,
+    user_id: &ObjectId,
+    issuer: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<()> {
+    if let Some(existing) = find_sso_identity(state, issuer, subject).await? {
+        if existing.user_id != *user_id {
+            anyhow::bail!("identity already linked to another user");
+        }
+        state
+            .sso_identities
+            .update_one(
+                doc! { "_id": existing.id },
+                doc! { "$set": { "email": email } },
+            )
+            .await?;
+        return Ok(());
+    }
+    state
+        .sso_identities
+        .insert_one(SsoIdentity {
+            id: None,
+            user_id: *user_id,
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            email: email.map(str::to_string),
+            linked_at: DateTime::from_system_time(SystemTime::now()),
+        })
+        .await?;
+    Ok(())
+}
+
+/// Returns whether an identity of `user_id` was removed.
+pub async fn unlink_sso_identity(
+    state: &AppState,
+    user_id: &ObjectId,
+    identity_id: &ObjectId,
+) -> Result<bool> {
+    let res = state
+        .sso_identities
+        .delete_one(doc! { "_id": identity_id, "user_id": user_id })
+        .await?;
+    Ok(res.deleted_count > 0)
+}
+
+/// Creates a staff user named after `email` in `company_id`. The TOTP secret
+/// is random and never shown; an admin can reset it if the user also needs
+/// code login.
+pub async fn provision_sso_user(
+    state: &AppState,
+    email: &str,
+    company_id: &ObjectId,
+) -> Result<ObjectId> {
+    let secret = generate_base32_secret_n(DEFAULT_SECRET_BYTES);
+    create_user(state, email, &secret, &[(*company_id, UserRole::Staff)]).await
+}
+
+/// Resolves the local user for an identity returned by the provider,
+/// provisioning one when the email domain belongs to a company.
+/// `email` must only be passed when the provider marked it as verified.
+pub async fn sso_login_user(
+    state: &AppState,
+    issuer: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<SsoLoginOutcome> {
+    if let Some(identity) = find_sso_identity(state, issuer, subject).await? {
+        let user = get_user_by_id(state, &identity.user_id)
+            .await?
+            .context("linked user not found")?;
+        if !user.is_active {
+            return Ok(SsoLoginOutcome::Inactive);
+        }
+        return Ok(SsoLoginOutcome::Existing(user.username));
+    }
+
+    let Some(email) = email.map(|e| e.trim().to_lowercase()) else {
+        return Ok(SsoLoginOutcome::MissingEmail);
+    };
+    if username_taken(state, &email, None).await? {
+        return Ok(SsoLoginOutcome::AccountExists);
+    }
+    let Some(company_id) = find_company_by_email_domain(state, &email)
+        .await?
+        .and_then(|company| company.id)
+    else {
+        return Ok(SsoLoginOutcome::NoCompanyForDomain);
+    };
+
+    let user_id = provision_sso_user(state, &email, &company_id).await?;
+    link_sso_identity(state, &user_id, issuer, subject, Some(&email)).await?;
+    Ok(SsoLoginOutcome::Provisioned(email))
+}
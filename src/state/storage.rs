@@ -0,0 +1,203 @@
+// Disk space taken by each company's uploaded files: receipts, the logo and
+// the SAT certificates. The running total lives on the company and is moved
+// by the handlers that write or remove those files. `reserve_storage` checks
+// the quota and counts the new bytes in a single update, so two uploads at
+// the same time cannot both slip under it.
+
+use std::{fmt, path::Path, time::SystemTime};
+
+use anyhow::{Context, Result};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use tokio::fs;
+
+use crate::models::{Receipt, SatConfig};
+
+use super::{AppState, get_company_by_id};
+
+/// An upload that does not fit in what is left of the company's quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageQuotaExceeded {
+    pub used_bytes: i64,
+    pub quota_bytes: i64,
+    pub file_bytes: i64,
+}
+
+impl fmt::Display for StorageQuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "El archivo ({}) excede el espacio de la compañía: usa {} de {}. Pide a un superadministrador que amplíe la cuota.",
+            format_bytes(self.file_bytes),
+            format_bytes(self.used_bytes),
+            format_bytes(self.quota_bytes)
+        )
+    }
+}
+
+impl std::error::Error for StorageQuotaExceeded {}
+
+/// The quota error behind `err`, when that is why `reserve_storage` failed.
+pub fn quota_exceeded(err: &anyhow::Error) -> Option<StorageQuotaExceeded> {
+    err.downcast_ref::<StorageQuotaExceeded>().copied()
+}
+
+/// `bytes` as a short size for people: `850 B`, `12.5 KB`, `3.2 MB`.
+pub fn format_bytes(bytes: i64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    const GB: f64 = MB * 1024.0;
+    let value = bytes as f64;
+    if value < KB {
+        format!("{bytes} B")
+    } else if value < MB {
+        format!("{:.1} KB", value / KB)
+    } else if value < GB {
+        format!("{:.1} MB", value / MB)
+    } else {
+        format!("{:.1} GB", value / GB)
+    }
+}
+
+/// Size of a stored file, or 0 when it is gone.
+pub async fn stored_file_bytes(path: impl AsRef<Path>) -> u64 {
+    fs::metadata(path).await.map(|meta| meta.len()).unwrap_or(0)
+}
+
+/// Counts `bytes` against the company before its file is written. Fails with
+/// `StorageQuotaExceeded` when they would take it over its quota.
+pub async fn reserve_storage(state: &AppState, company_id: &ObjectId, bytes: u64) -> Result<()> {
+    let bytes = bytes as i64;
+    let res = state
+        .companies
+        .update_one(
+            doc! {
+                "_id": company_id,
+                "$or": [
+                    { "storage.quota_bytes": null },
+                    { "$expr": { "$lte": [
+                        { "$add": [{ "$ifNull": ["$storage.used_bytes", 0] }, bytes] },
+                        "$storage.quota_bytes",
+                    ] } },
+                ],
+            },
+            doc! { "$inc": { "storage.used_bytes": bytes } },
+        )
+        .await?;
+    if res.matched_count == 1 {
+        return Ok(());
+    }
+    let storage = get_company_by_id(state, company_id)
+        .await?
+        .context("company not found")?
+        .storage;
+    Err(StorageQuotaExceeded {
+        used_bytes: storage.used_bytes,
+        quota_bytes: storage.quota_bytes.unwrap_or_default(),
+        file_bytes: bytes,
+    }
+    .into())
+}
+
+/// Gives back the bytes of a removed file, or of a reservation whose file
+/// was never written. The total never drops below zero.
+pub async fn release_storage(state: &AppState, company_id: &ObjectId, bytes: u64) -> Result<()> {
+    if bytes == 0 {
+        return Ok(());
+    }
+    state
+        .companies
+        .update_one(
+            doc! { "_id": company_id },
+            vec![doc! { "$set": { "storage.used_bytes": { "$max": [
+                0,
+                { "$subtract": [{ "$ifNull": ["$storage.used_bytes", 0] }, bytes as i64] },
+            ] } } }],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Sets the most the company may store, or lifts the limit with `None`.
+/// Files already stored are kept even when they exceed the new quota.
+pub async fn set_storage_quota(
+    state: &AppState,
+    company_id: &ObjectId,
+    quota_bytes: Option<i64>,
+) -> Result<()> {
+    let now = DateTime::from_system_time(SystemTime::now());
+    let update = match quota_bytes {
+        Some(quota) => doc! { "$set": { "storage.quota_bytes": quota, "updated_at": now } },
+        None => doc! {
+            "$unset": { "storage.quota_bytes": "" },
+            "$set": { "updated_at": now },
+        },
+    };
+    state
+        .companies
+        .update_one(doc! { "_id": company_id }, update)
+        .await?;
+    Ok(())
+}
+
+/// Measures the company's files on disk and stores the total, correcting any
+/// drift and counting files uploaded before usage was tracked.
+pub async fn recount_storage(state: &AppState, company_id: &ObjectId) -> Result<i64> {
+    let company = get_company_by_id(state, company_id)
+        .await?
+        .context("company not found")?;
+    let mut paths: Vec<String> = company.branding.logo_path.into_iter().collect();
+    let receipts: Vec<Receipt> = state
+        .receipts
+        .find(doc! { "company_id": company_id })
+        .await?
+        .try_collect()
+        .await?;
+    paths.extend(receipts.into_iter().map(|receipt| receipt.path));
+    let sat_configs: Vec<SatConfig> = state
+        .sat_configs
+        .find(doc! { "company_id": company_id })
+        .await?
+        .try_collect()
+        .await?;
+    for config in sat_configs {
+        paths.push(config.cer_path);
+        paths.push(config.key_path);
+    }
+
+    let mut used_bytes = 0;
+    for path in paths {
+        used_bytes += stored_file_bytes(&path).await as i64;
+    }
+    state
+        .companies
+        .update_one(
+            doc! { "_id": company_id },
+            doc! { "$set": { "storage.used_bytes": used_bytes } },
+        )
+        .await?;
+    Ok(used_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota_error_names_the_sizes() {
+        assert_eq!(format_bytes(850), "850 B");
+        assert_eq!(format_bytes(12_800), "12.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+        let err = anyhow::Error::new(StorageQuotaExceeded {
+            used_bytes: 9 * 1024 * 1024,
+            quota_bytes: 10 * 1024 * 1024,
+            file_bytes: 2 * 1024 * 1024,
+        });
+        let exceeded = quota_exceeded(&err).unwrap();
+        assert_eq!(
+            exceeded.to_string(),
+            "El archivo (2.0 MB) excede el espacio de la compañía: usa 9.0 MB de 10.0 MB. Pide a un superadministrador que amplíe la cuota."
+        );
+        assert!(quota_exceeded(&anyhow::anyhow!("db down")).is_none());
+    }
+}
@@ -0,0 +1,578 @@
+// Tax profiles: the taxes a company applies to its categories ("IVA 16%",
+// "Exento"). Income and expense movements take the profile of their
+// category, or the company's default one, and keep the tax included in their
+// amount, so the monthly tax report adds up what was collected and paid
+// without entering it on each movement. A rate typed on a movement overrides
+// the profile and is kept until cleared.
+
+use std::{collections::HashMap, time::SystemTime};
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Months, TimeZone, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, DateTime, doc, oid::ObjectId};
+use serde::Deserialize;
+
+use crate::models::{TaxProfile, Transaction, TransactionTax, TransactionType};
+
+use super::{AppState, companies::company_default_currency};
+
+/// Name stored on a movement whose rate was typed by hand.
+pub const MANUAL_TAX_NAME: &str = "Manual";
+
+/// Tax included in `amount` at `rate` percent, rounded to cents.
+pub fn included_tax(amount: f64, rate: f64) -> f64 {
+    if rate <= 0.0 {
+        return 0.0;
+    }
+    (amount * rate / (100.0 + rate) * 100.0).round() / 100.0
+}
+
+fn validate_rate(rate: f64) -> Result<()> {
+    if !(0.0..=100.0).contains(&rate) {
+        bail!("La tasa debe estar entre 0 y 100");
+    }
+    Ok(())
+}
+
+fn profile_tax(profile: &TaxProfile, amount: f64) -> TransactionTax {
+    let rate = if profile.exempt { 0.0 } else { profile.rate };
+    TransactionTax {
+        profile_id: profile.id,
+        name: profile.name.clone(),
+        rate,
+        exempt: profile.exempt,
+        amount: included_tax(amount, rate),
+        is_override: false,
+    }
+}
+
+fn manual_tax(rate: f64, amount: f64) -> TransactionTax {
+    TransactionTax {
+        profile_id: None,
+        name: MANUAL_TAX_NAME.to_string(),
+        rate,
+        exempt: false,
+        amount: included_tax(amount, rate),
+        is_override: true,
+    }
+}
+
+/// The profile that applies to a category with `category_profile`: its own
+/// or, without one, the company's default.
+fn applicable_profile(
+    profiles: &[TaxProfile],
+    category_profile: Option<ObjectId>,
+) -> Option<&TaxProfile> {
+    match category_profile {
+        Some(id) => profiles.iter().find(|profile| profile.id == Some(id)),
+        None => profiles.iter().find(|profile| profile.is_default),
+    }
+}
+
+/// Tax of a movement: none for transfers, the rate typed on `previous` when
+/// it was overridden, or the profile of the category.
+fn movement_tax(
+    transaction_type: &TransactionType,
+    amount: f64,
+    profile: Option<&TaxProfile>,
+    previous: Option<&TransactionTax>,
+) -> Option<TransactionTax> {
+    if *transaction_type == TransactionType::Transfer {
+        return None;
+    }
+    match previous {
+        Some(tax) if tax.is_override => Some(manual_tax(tax.rate, amount)),
+        _ => profile.map(|profile| profile_tax(profile, amount)),
+    }
+}
+
+pub async fn list_tax_profiles(state: &AppState, company_id: &ObjectId) -> Result<Vec<TaxProfile>> {
+    Ok(state
+        .tax_profiles
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "name": 1 })
+        .await?
+        .try_collect()
+        .await?)
+}
+
+pub async fn get_tax_profile_by_id(state: &AppState, id: &ObjectId) -> Result<Option<TaxProfile>> {
+    Ok(state.tax_profiles.find_one(doc! { "_id": id }).await?)
+}
+
+/// Creates a profile. Exempt profiles are stored with a zero rate.
+pub async fn create_tax_profile(
+    state: &AppState,
+    company_id: &ObjectId,
+    name: &str,
+    rate: f64,
+    exempt: bool,
+) -> Result<ObjectId> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("El nombre del impuesto es obligatorio");
+    }
+    validate_rate(rate)?;
+    let exists = state
+        .tax_profiles
+        .find_one(doc! { "company_id": company_id, "name": name })
+        .await?
+        .is_some();
+    if exists {
+        bail!("Ya existe un impuesto llamado {name}");
+    }
+    let res = state
+        .tax_profiles
+        .insert_one(TaxProfile {
+            id: None,
+            company_id: *company_id,
+            name: name.to_string(),
+            rate: if exempt { 0.0 } else { rate },
+            exempt,
+            is_default: false,
+            created_at: Some(DateTime::from_system_time(SystemTime::now())),
+        })
+        .await?;
+    res.inserted_id
+        .as_object_id()
+        .context("tax profile insert missing _id")
+}
+
+/// Makes `id` the company's default profile, or leaves the company without
+/// one, and applies the change to the movements it reaches.
+pub async fn set_default_tax_profile(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: Option<&ObjectId>,
+) -> Result<()> {
+    state
+        .tax_profiles
+        .update_many(
+            doc! { "company_id": company_id, "is_default": true },
+            doc! { "$set": { "is_default": false } },
+        )
+        .await?;
+    if let Some(id) = id {
+        state
+            .tax_profiles
+            .update_one(
+                doc! { "_id": id, "company_id": company_id },
+                doc! { "$set": { "is_default": true } },
+            )
+            .await?;
+    }
+    reapply_tax_profiles(state, company_id, None).await
+}
+
+/// Removes the profile. Its categories fall back to the default profile and
+/// their movements are recalculated.
+pub async fn delete_tax_profile(state: &AppState, profile: &TaxProfile) -> Result<()> {
+    let id = profile.id.context("tax profile without _id")?;
+    state
+        .categories
+        .update_many(
+            doc! { "company_id": profile.company_id, "tax_profile_id": id },
+            doc! { "$unset": { "tax_profile_id": "" } },
+        )
+        .await?;
+    state.tax_profiles.delete_one(doc! { "_id": id }).await?;
+    reapply_tax_profiles(state, &profile.company_id, None).await
+}
+
+/// Sets the profile of a category (`None` follows the company default) and
+/// recalculates the category's movements.
+pub async fn set_category_tax_profile(
+    state: &AppState,
+    company_id: &ObjectId,
+    category_id: &ObjectId,
+    tax_profile_id: Option<ObjectId>,
+) -> Result<()> {
+    if let Some(profile_id) = tax_profile_id.as_ref() {
+        let profile = get_tax_profile_by_id(state, profile_id)
+            .await?
+            .context("tax profile not found")?;
+        if profile.company_id != *company_id {
+            bail!("tax profile belongs to another company");
+        }
+    }
+    let update = match tax_profile_id {
+        Some(id) => doc! { "$set": { "tax_profile_id": id } },
+        None => doc! { "$unset": { "tax_profile_id": "" } },
+    };
+    state
+        .categories
+        .update_one(
+            doc! { "_id": category_id, "company_id": company_id },
+            update,
+        )
+        .await?;
+    reapply_tax_profiles(state, company_id, Some(category_id)).await
+}
+
+/// Tax of a new or edited movement. `previous` is the tax it had, so a rate
+/// typed by hand survives edits of the amount or the category.
+pub async fn transaction_tax(
+    state: &AppState,
+    company_id: &ObjectId,
+    transaction_type: &TransactionType,
+    category_id: &ObjectId,
+    amount: f64,
+    previous: Option<&TransactionTax>,
+) -> Result<Option<TransactionTax>> {
+    if *transaction_type == TransactionType::Transfer || previous.is_some_and(|t| t.is_override) {
+        return Ok(movement_tax(transaction_type, amount, None, previous));
+    }
+    let category_profile = state
+        .categories
+        .find_one(doc! { "_id": category_id })
+        .await?
+        .and_then(|category| category.tax_profile_id);
+    let profiles = list_tax_profiles(state, company_id).await?;
+    Ok(movement_tax(
+        transaction_type,
+        amount,
+        applicable_profile(&profiles, category_profile),
+        previous,
+    ))
+}
+
+/// Types a rate on a movement, replacing its category's profile, or with
+/// `None` goes back to the profile.
+pub async fn set_transaction_tax_rate(
+    state: &AppState,
+    id: &ObjectId,
+    company_id: &ObjectId,
+    rate: Option<f64>,
+) -> Result<()> {
+    let tx = state
+        .transactions
+        .find_one(doc! { "_id": id, "company_id": company_id })
+        .await?
+        .context("transaction not found")?;
+    let tax = match rate {
+        Some(_) if tx.transaction_type == TransactionType::Transfer => {
+            bail!("Las transferencias no llevan impuestos")
+        }
+        Some(rate) => {
+            validate_rate(rate)?;
+            Some(manual_tax(rate, tx.amount))
+        }
+        None => {
+            transaction_tax(
+                state,
+                company_id,
+                &tx.transaction_type,
+                &tx.category_id,
+                tx.amount,
+                None,
+            )
+            .await?
+        }
+    };
+    state
+        .transactions
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "tax": bson::to_bson(&tax)? } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Recalculates the tax of the company's income and expense movements that
+/// follow a profile, all of them or those of one category.
+async fn reapply_tax_profiles(
+    state: &AppState,
+    company_id: &ObjectId,
+    category_id: Option<&ObjectId>,
+) -> Result<()> {
+    let profiles = list_tax_profiles(state, company_id).await?;
+    let mut category_filter = doc! { "company_id": company_id };
+    let mut filter = doc! {
+        "company_id": company_id,
+        "transaction_type": { "$in": ["income", "expense"] },
+        "tax.is_override": { "$ne": true },
+    };
+    if let Some(category_id) = category_id {
+        category_filter.insert("_id", category_id);
+        filter.insert("category_id", category_id);
+    }
+    let category_profiles: HashMap<ObjectId, Option<ObjectId>> = state
+        .categories
+        .find(category_filter)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .filter_map(|category| category.id.map(|id| (id, category.tax_profile_id)))
+        .collect();
+
+    let mut cursor = state.transactions.find(filter).await?;
+    while let Some(tx) = cursor.try_next().await? {
+        let Some(id) = tx.id else {
+            continue;
+        };
+        let profile = applicable_profile(
+            &profiles,
+            category_profiles.get(&tx.category_id).copied().flatten(),
+        );
+        let tax = movement_tax(&tx.transaction_type, tx.amount, profile, None);
+        if tax != tx.tax {
+            state
+                .transactions
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "tax": bson::to_bson(&tax)? } },
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Taxes of one name and rate in the report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxReportLine {
+    pub name: String,
+    pub rate: f64,
+    pub exempt: bool,
+    /// Income before tax, and the tax it carried.
+    pub collected_base: f64,
+    pub collected: f64,
+    /// Expenses before tax, and the tax they carried.
+    pub paid_base: f64,
+    pub paid: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaxReport {
+    pub currency: String,
+    /// `YYYY-MM`.
+    pub month: String,
+    /// Highest rate first, exempt last.
+    pub lines: Vec<TaxReportLine>,
+    pub collected: f64,
+    pub paid: f64,
+    /// Collected minus paid: to pay when positive, in favor when negative.
+    pub net: f64,
+    /// Confirmed income and expense of the month without a tax, e.g. from
+    /// categories without a profile and no company default.
+    pub untaxed_count: u64,
+}
+
+#[derive(Deserialize)]
+struct TaxRow {
+    name: String,
+    rate: f64,
+    #[serde(default)]
+    exempt: bool,
+    transaction_type: TransactionType,
+    amount: f64,
+    tax: f64,
+}
+
+fn report_lines(rows: Vec<TaxRow>) -> Vec<TaxReportLine> {
+    let mut lines: Vec<TaxReportLine> = Vec::new();
+    for row in rows {
+        let index = match lines.iter().position(|line| {
+            line.name == row.name && line.rate == row.rate && line.exempt == row.exempt
+        }) {
+            Some(index) => index,
+            None => {
+                lines.push(TaxReportLine {
+                    name: row.name.clone(),
+                    rate: row.rate,
+                    exempt: row.exempt,
+                    collected_base: 0.0,
+                    collected: 0.0,
+                    paid_base: 0.0,
+                    paid: 0.0,
+                });
+                lines.len() - 1
+            }
+        };
+        let line = &mut lines[index];
+        match row.transaction_type {
+            TransactionType::Income => {
+                line.collected_base += row.amount - row.tax;
+                line.collected += row.tax;
+            }
+            _ => {
+                line.paid_base += row.amount - row.tax;
+                line.paid += row.tax;
+            }
+        }
+    }
+    lines.sort_by(|a, b| {
+        a.exempt
+            .cmp(&b.exempt)
+            .then(b.rate.total_cmp(&a.rate))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    lines
+}
+
+/// Tax collected on income and paid on expenses in the month `at` falls in,
+/// from confirmed movements.
+pub async fn tax_report(
+    state: &AppState,
+    company_id: &ObjectId,
+    at: chrono::DateTime<Utc>,
+) -> Result<TaxReport> {
+    let start = Utc
+        .with_ymd_and_hms(at.year(), at.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(at);
+    let end = start + Months::new(1);
+    let period = doc! {
+        "company_id": company_id,
+        "is_confirmed": { "$ne": false },
+        "transaction_type": { "$in": ["income", "expense"] },
+        "date": { "$gte": DateTime::from_chrono(start), "$lt": DateTime::from_chrono(end) },
+    };
+    let transactions = state.for_reports(&state.transactions);
+
+    let mut taxed = period.clone();
+    taxed.insert("tax", doc! { "$type": "object" });
+    let pipeline = vec![
+        doc! { "$match": taxed },
+        doc! { "$group": {
+            "_id": {
+                "name": "$tax.name",
+                "rate": "$tax.rate",
+                "exempt": "$tax.exempt",
+                "transaction_type": "$transaction_type",
+            },
+            "amount": { "$sum": "$amount" },
+            "tax": { "$sum": "$tax.amount" },
+        }},
+        doc! { "$project": {
+            "_id": 0,
+            "name": "$_id.name",
+            "rate": { "$toDouble": "$_id.rate" },
+            "exempt": { "$ifNull": ["$_id.exempt", false] },
+            "transaction_type": "$_id.transaction_type",
+            "amount": { "$toDouble": "$amount" },
+            "tax": { "$toDouble": "$tax" },
+        }},
+    ];
+    let rows: Vec<TaxRow> = transactions
+        .aggregate(pipeline)
+        .with_type::<TaxRow>()
+        .await?
+        .try_collect()
+        .await?;
+    let lines = report_lines(rows);
+
+    let mut untaxed = period;
+    untaxed.insert("tax", doc! { "$not": { "$type": "object" } });
+    let untaxed_count = transactions.count_documents(untaxed).await?;
+
+    let collected = lines.iter().map(|line| line.collected).sum::<f64>();
+    let paid = lines.iter().map(|line| line.paid).sum::<f64>();
+    Ok(TaxReport {
+        currency: company_default_currency(state, company_id).await?,
+        month: start.format("%Y-%m").to_string(),
+        lines,
+        collected,
+        paid,
+        net: collected - paid,
+        untaxed_count,
+    })
+}
+
+/// Tax of a transaction as its form shows it, e.g. "IVA 16%: 137.93".
+pub fn transaction_tax_label(tx: &Transaction) -> Option<String> {
+    tx.tax.as_ref().map(|tax| {
+        if tax.exempt {
+            tax.name.clone()
+        } else if tax.is_override {
+            format!("Tasa manual de {}%: {:.2}", tax.rate, tax.amount)
+        } else {
+            format!("{}: {:.2}", tax.name, tax.amount)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, rate: f64, exempt: bool, is_default: bool) -> TaxProfile {
+        TaxProfile {
+            id: Some(ObjectId::new()),
+            company_id: ObjectId::new(),
+            name: name.into(),
+            rate,
+            exempt,
+            is_default,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn tax_is_included_in_the_amount() {
+        assert_eq!(included_tax(116.0, 16.0), 16.0);
+        assert_eq!(included_tax(1000.0, 16.0), 137.93);
+        assert_eq!(included_tax(500.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn categories_use_their_profile_or_the_default_and_overrides_stay() {
+        let iva = profile("IVA 16%", 16.0, false, true);
+        let exento = profile("Exento", 0.0, true, false);
+        let profiles = vec![iva.clone(), exento.clone()];
+
+        let default = applicable_profile(&profiles, None);
+        let tax = movement_tax(&TransactionType::Income, 116.0, default, None).unwrap();
+        assert_eq!((tax.name.as_str(), tax.amount), ("IVA 16%", 16.0));
+
+        let own = applicable_profile(&profiles, exento.id);
+        let tax = movement_tax(&TransactionType::Expense, 116.0, own, None).unwrap();
+        assert!(tax.exempt);
+        assert_eq!(tax.amount, 0.0);
+
+        let manual = manual_tax(8.0, 108.0);
+        let tax = movement_tax(&TransactionType::Expense, 216.0, default, Some(&manual)).unwrap();
+        assert!(tax.is_override);
+        assert_eq!(tax.amount, 16.0);
+
+        assert_eq!(
+            movement_tax(&TransactionType::Transfer, 116.0, default, None),
+            None
+        );
+    }
+
+    #[test]
+    fn report_lines_split_collected_and_paid_per_rate() {
+        let row = |name: &str, rate, exempt, transaction_type, amount, tax| TaxRow {
+            name: String::from(name),
+            rate,
+            exempt,
+            transaction_type,
+            amount,
+            tax,
+        };
+        let lines = report_lines(vec![
+            row("Exento", 0.0, true, TransactionType::Expense, 50.0, 0.0),
+            row("IVA 16%", 16.0, false, TransactionType::Income, 232.0, 32.0),
+            row(
+                "IVA 16%",
+                16.0,
+                false,
+                TransactionType::Expense,
+                116.0,
+                16.0,
+            ),
+            row("IVA 8%", 8.0, false, TransactionType::Income, 108.0, 8.0),
+        ]);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].name, "IVA 16%");
+        assert_eq!((lines[0].collected_base, lines[0].collected), (200.0, 32.0));
+        assert_eq!((lines[0].paid_base, lines[0].paid), (100.0, 16.0));
+        assert_eq!(lines[1].name, "IVA 8%");
+        assert_eq!(lines[2].name, "Exento");
+        assert_eq!(lines[2].paid_base, 50.0);
+    }
+}
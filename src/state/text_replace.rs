@@ -0,0 +1,305 @@
+// Find-and-replace over the descriptions and notes of a company's
+// transactions within a date range, e.g. to fix a misspelled supplier after
+// an import. The same query is previewed before it is applied; every run
+// that changes something is kept in `text_replacements` with each value
+// before and after, so a wrong replacement can be traced and undone by hand.
+
+use anyhow::{Result, bail};
+use chrono::Duration as ChronoDuration;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, Document, doc, oid::ObjectId};
+
+use crate::models::{TextChange, TextField, TextReplacement, Transaction};
+
+use super::{AccountAccess, AppState, custom_fields::escape_regex, utc_day_start};
+
+/// Most transactions one replacement may rewrite.
+pub const TEXT_REPLACE_MAX_ROWS: usize = 500;
+
+/// Length in bytes of the `needle` match at the start of `haystack`.
+fn match_len(haystack: &str, needle: &str, case_sensitive: bool) -> Option<usize> {
+    let mut chars = haystack.char_indices();
+    let mut end = 0;
+    for n in needle.chars() {
+        let (at, h) = chars.next()?;
+        let same = h == n || (!case_sensitive && h.to_lowercase().eq(n.to_lowercase()));
+        if !same {
+            return None;
+        }
+        end = at + h.len_utf8();
+    }
+    Some(end)
+}
+
+/// `value` with every occurrence of `find` replaced and the ends trimmed, or
+/// `None` when nothing matches or the text would stay the same.
+pub fn replace_text(
+    value: &str,
+    find: &str,
+    replace: &str,
+    case_sensitive: bool,
+) -> Option<String> {
+    if find.is_empty() {
+        return None;
+    }
+    let mut replaced = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut found = false;
+    while let Some(c) = rest.chars().next() {
+        if let Some(len) = match_len(rest, find, case_sensitive) {
+            replaced.push_str(replace);
+            rest = &rest[len..];
+            found = true;
+        } else {
+            replaced.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    let replaced = replaced.trim();
+    (found && replaced != value).then(|| replaced.to_string())
+}
+
+/// What to replace, where and between which days.
+#[derive(Debug, Clone)]
+pub struct TextReplaceQuery {
+    pub company_id: ObjectId,
+    pub find: String,
+    pub replace: String,
+    pub fields: Vec<TextField>,
+    /// First and last day searched, both included.
+    pub from: DateTime,
+    pub to: DateTime,
+    pub case_sensitive: bool,
+}
+
+impl TextReplaceQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.find.trim().is_empty() {
+            return Err("Indica el texto a buscar.".into());
+        }
+        if self.fields.is_empty() {
+            return Err("Elige al menos un campo.".into());
+        }
+        if self.from > self.to {
+            return Err("La fecha inicial no puede ser posterior a la final.".into());
+        }
+        Ok(())
+    }
+
+    fn filter(&self) -> Document {
+        let pattern = escape_regex(&self.find);
+        let options = if self.case_sensitive { "" } else { "i" };
+        let fields: Vec<Document> = self
+            .fields
+            .iter()
+            .map(|field| doc! { field.as_str(): { "$regex": &pattern, "$options": options } })
+            .collect();
+        let until =
+            DateTime::from_chrono(utc_day_start(self.to).to_chrono() + ChronoDuration::days(1));
+        doc! {
+            "company_id": self.company_id,
+            "date": { "$gte": utc_day_start(self.from), "$lt": until },
+            "$or": fields,
+        }
+    }
+}
+
+/// One text the replacement would rewrite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextReplaceRow {
+    pub transaction_id: ObjectId,
+    pub date: DateTime,
+    pub field: TextField,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextReplacePreview {
+    pub rows: Vec<TextReplaceRow>,
+    /// More than `TEXT_REPLACE_MAX_ROWS` transactions match; only the first
+    /// ones are listed and the replacement is refused.
+    pub truncated: bool,
+}
+
+impl TextReplacePreview {
+    pub fn transaction_count(&self) -> usize {
+        let mut ids: Vec<&ObjectId> = self.rows.iter().map(|row| &row.transaction_id).collect();
+        ids.dedup();
+        ids.len()
+    }
+
+    /// Why the replacement cannot be applied as previewed.
+    pub fn error(&self) -> Option<String> {
+        if self.truncated {
+            return Some(format!(
+                "Coinciden más de {TEXT_REPLACE_MAX_ROWS} movimientos; acota las fechas o el texto."
+            ));
+        }
+        if self
+            .rows
+            .iter()
+            .any(|row| row.field == TextField::Description && row.after.is_empty())
+        {
+            return Some("El reemplazo dejaría movimientos sin descripción.".into());
+        }
+        None
+    }
+}
+
+/// Texts of the transactions the user can see that `query` would change, by
+/// date.
+pub async fn preview_text_replacement(
+    state: &AppState,
+    query: &TextReplaceQuery,
+    access: &AccountAccess,
+) -> Result<TextReplacePreview> {
+    if query.validate().is_err() {
+        return Ok(TextReplacePreview::default());
+    }
+    let transactions: Vec<Transaction> = state
+        .transactions
+        .find(access.restrict_transactions(query.filter()))
+        .sort(doc! { "date": 1, "_id": 1 })
+        .limit(TEXT_REPLACE_MAX_ROWS as i64 + 1)
+        .await?
+        .try_collect()
+        .await?;
+
+    let truncated = transactions.len() > TEXT_REPLACE_MAX_ROWS;
+    let mut rows = Vec::new();
+    for tx in transactions.into_iter().take(TEXT_REPLACE_MAX_ROWS) {
+        let Some(transaction_id) = tx.id else {
+            continue;
+        };
+        for field in &query.fields {
+            let before = match field {
+                TextField::Description => tx.description.as_str(),
+                TextField::Notes => tx.notes.as_deref().unwrap_or_default(),
+            };
+            if let Some(after) =
+                replace_text(before, &query.find, &query.replace, query.case_sensitive)
+            {
+                rows.push(TextReplaceRow {
+                    transaction_id,
+                    date: tx.date,
+                    field: *field,
+                    before: before.to_string(),
+                    after,
+                });
+            }
+        }
+    }
+    Ok(TextReplacePreview { rows, truncated })
+}
+
+/// Applies `query` to the transactions the user can see and records the run.
+/// Texts edited since the preview are left alone. Fails when the preview has
+/// an error or nothing to change.
+pub async fn apply_text_replacement(
+    state: &AppState,
+    query: &TextReplaceQuery,
+    access: &AccountAccess,
+    user_id: Option<ObjectId>,
+    username: &str,
+) -> Result<TextReplacement> {
+    if let Err(err) = query.validate() {
+        bail!(err);
+    }
+    let preview = preview_text_replacement(state, query, access).await?;
+    if let Some(err) = preview.error() {
+        bail!(err);
+    }
+    if preview.rows.is_empty() {
+        bail!("nothing to replace");
+    }
+
+    let now = DateTime::now();
+    let mut changes = Vec::with_capacity(preview.rows.len());
+    for row in preview.rows {
+        let field = row.field.as_str();
+        let update = if row.after.is_empty() {
+            doc! { "$unset": { field: "" }, "$set": { "updated_at": now } }
+        } else {
+            doc! { "$set": { field: &row.after, "updated_at": now } }
+        };
+        let res = state
+            .transactions
+            .update_one(
+                doc! { "_id": row.transaction_id, field: &row.before },
+                update,
+            )
+            .await?;
+        if res.modified_count == 1 {
+            changes.push(TextChange {
+                transaction_id: row.transaction_id,
+                field: row.field,
+                before: row.before,
+                after: row.after,
+            });
+        }
+    }
+
+    let mut replacement = TextReplacement {
+        id: None,
+        company_id: query.company_id,
+        find: query.find.clone(),
+        replace: query.replace.clone(),
+        fields: query.fields.clone(),
+        case_sensitive: query.case_sensitive,
+        from: utc_day_start(query.from),
+        to: utc_day_start(query.to),
+        changes,
+        user_id,
+        username: username.to_string(),
+        created_at: now,
+    };
+    let res = state.text_replacements.insert_one(&replacement).await?;
+    replacement.id = res.inserted_id.as_object_id();
+    Ok(replacement)
+}
+
+/// Latest replacements run in the company, newest first.
+pub async fn list_text_replacements(
+    state: &AppState,
+    company_id: &ObjectId,
+    limit: i64,
+) -> Result<Vec<TextReplacement>> {
+    state
+        .text_replacements
+        .find(doc! { "company_id": company_id })
+        .sort(doc! { "created_at": -1 })
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_text_matches_every_occurrence() {
+        assert_eq!(
+            replace_text("Pago Telmex / telmex", "TELMEX", "Telmex", false),
+            Some("Pago Telmex / Telmex".into())
+        );
+        assert_eq!(
+            replace_text("Pago Telmex / telmex", "telmex", "Telmex", true),
+            Some("Pago Telmex / Telmex".into())
+        );
+        assert_eq!(
+            replace_text("Papelería", "ERÍA", "eria", false),
+            Some("Papeleria".into())
+        );
+        assert_eq!(replace_text("Renta", "luz", "agua", false), None);
+        assert_eq!(replace_text("Renta", "Renta", "Renta", true), None);
+        assert_eq!(
+            replace_text("Renta oficina", "oficina", "", false),
+            Some("Renta".into())
+        );
+        assert_eq!(replace_text("Renta", "", "x", false), None);
+    }
+}
@@ -1,14 +1,22 @@
 use anyhow::{Context, Result};
 use data_encoding::BASE32_NOPAD;
 use futures::stream::TryStreamExt;
-use mongodb::bson::{DateTime, Document, doc, from_document, oid::ObjectId};
+use mongodb::bson::{DateTime, Document, doc, from_document, oid::ObjectId, to_bson};
 use rand::RngCore;
 use slug::slugify;
-use std::time::{Duration, SystemTime};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 
-use crate::models::{Session, User, UserCompany, UserPermission, UserRole};
+use crate::models::{
+    EmailChange, Flash, FormatPreferences, Session, User, UserCompany, UserPermission, UserRole,
+};
 
-use super::{AppState, SESSION_TTL_SECONDS};
+use super::{AppState, api_tokens::hash_token, session_ttl_seconds};
+
+/// How long an email change confirmation link stays valid.
+pub const EMAIL_CHANGE_TTL_SECONDS: u64 = 60 * 60 * 24; // 1 day
 
 #[derive(Clone)]
 pub struct UserWithCompany {
@@ -23,8 +31,24 @@ pub struct UserWithCompany {
     pub company_names: Vec<String>,
     pub company_roles: Vec<UserRole>,
     pub company_permissions: Vec<Vec<UserPermission>>,
+    /// Account access list of each company, empty for every account.
+    pub company_account_ids: Vec<Vec<ObjectId>>,
     pub role: UserRole,
     pub permissions: Vec<UserPermission>,
+    /// Accounts of the active company the user is limited to, if any.
+    pub account_ids: Vec<ObjectId>,
+    pub is_active: bool,
+    pub format_preferences: FormatPreferences,
+    pub is_superadmin: bool,
+    pub display_name: Option<String>,
+    pub has_avatar: bool,
+}
+
+impl UserWithCompany {
+    /// Display name when set, otherwise the username.
+    pub fn label(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
 }
 
 pub async fn find_user(state: &AppState, username: &str) -> Result<Option<UserWithCompany>> {
@@ -36,11 +60,15 @@ pub async fn find_user(state: &AppState, username: &str) -> Result<Option<UserWi
 }
 
 pub async fn create_session(state: &AppState, username: &str) -> Result<String> {
+    let user = state
+        .users
+        .find_one(doc! { "username": username })
+        .await?
+        .context("user not found for session")?;
+    let user_id = user.id.context("user missing _id")?;
     let _ = state
         .sessions
-        // `user_email` is the internal session→user link field; it carries the
-        // username value (kept named as-is to avoid a sessions migration).
-        .delete_many(doc! { "user_email": username.to_string() })
+        .delete_many(doc! { "user_id": &user_id })
         .await;
 
     let mut token_bytes = [0u8; 32];
@@ -48,15 +76,16 @@ pub async fn create_session(state: &AppState, username: &str) -> Result<String>
     let token = BASE32_NOPAD.encode(&token_bytes);
 
     let expires_at =
-        DateTime::from_system_time(SystemTime::now() + Duration::from_secs(SESSION_TTL_SECONDS));
+        DateTime::from_system_time(SystemTime::now() + Duration::from_secs(session_ttl_seconds()));
 
     state
         .sessions
         .insert_one(Session {
             id: None,
             token: token.clone(),
-            user_email: username.to_string(),
+            user_id,
             expires_at,
+            flash: None,
         })
         .await?;
 
@@ -67,6 +96,14 @@ pub async fn find_user_by_session(
     state: &AppState,
     token: &str,
 ) -> Result<Option<UserWithCompany>> {
+    Ok(find_session_user(state, token).await?.map(|(user, _)| user))
+}
+
+/// The user of a live session and when the session expires.
+pub async fn find_session_user(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<(UserWithCompany, DateTime)>> {
     if let Some(session) = state.sessions.find_one(doc! { "token": token }).await? {
         let expires_at = session.expires_at.to_system_time();
         if expires_at <= SystemTime::now() {
@@ -74,7 +111,10 @@ pub async fn find_user_by_session(
             let _ = state.sessions.delete_one(doc! { "token": token }).await;
             return Ok(None);
         }
-        find_user(state, &session.user_email).await
+        match get_user_by_id(state, &session.user_id).await? {
+            Some(user) if user.is_active => Ok(Some((user, session.expires_at))),
+            _ => Ok(None),
+        }
     } else {
         Ok(None)
     }
@@ -161,6 +201,12 @@ pub async fn create_user_with_permissions(
             secret: secret.to_string(),
             company_id: Some(primary),
             company_ids: company_ids.clone(),
+            is_active: true,
+            format_preferences: FormatPreferences::default(),
+            is_superadmin: false,
+            display_name: None,
+            avatar_path: None,
+            avatar_content_type: None,
         })
         .await?;
     let uid = res
@@ -181,6 +227,8 @@ pub async fn create_user_with_permissions(
                 company_id: cid.clone(),
                 role: role.clone(),
                 permissions: permissions.clone(),
+                account_ids: Vec::new(),
+                dashboard_widgets: None,
             })
             .await;
     }
@@ -225,6 +273,16 @@ pub async fn update_user_with_permissions(
         )
         .await?;
 
+    // Account access lists and dashboard layouts are set apart; keep them
+    // for the companies the user stays in.
+    let mut account_ids = HashMap::new();
+    let mut dashboards = HashMap::new();
+    let mut cursor = state.user_companies.find(doc! { "user_id": id }).await?;
+    while let Some(membership) = cursor.try_next().await? {
+        account_ids.insert(membership.company_id, membership.account_ids);
+        dashboards.insert(membership.company_id, membership.dashboard_widgets);
+    }
+
     let _ = state
         .user_companies
         .delete_many(doc! { "user_id": id })
@@ -238,6 +296,8 @@ pub async fn update_user_with_permissions(
                 company_id: cid.clone(),
                 role: role.clone(),
                 permissions: permissions.clone(),
+                account_ids: account_ids.remove(cid).unwrap_or_default(),
+                dashboard_widgets: dashboards.remove(cid).flatten(),
             })
             .await;
     }
@@ -296,6 +356,8 @@ pub async fn add_user_to_company(
                 company_id: company_id.clone(),
                 role,
                 permissions: Vec::new(),
+                account_ids: Vec::new(),
+                dashboard_widgets: None,
             })
             .await?;
     }
@@ -304,19 +366,205 @@ pub async fn add_user_to_company(
 }
 
 pub async fn delete_user(state: &AppState, id: &ObjectId) -> Result<()> {
-    state.users.delete_one(doc! { "_id": id }).await?;
+    let removed = state.users.find_one_and_delete(doc! { "_id": id }).await?;
+    if let Some(path) = removed.and_then(|user| user.avatar_path) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
     let _ = state
         .user_companies
         .delete_many(doc! { "user_id": id })
         .await;
+    let _ = state.sessions.delete_many(doc! { "user_id": id }).await;
+    let _ = state
+        .refresh_tokens
+        .delete_many(doc! { "user_id": id })
+        .await;
+    let _ = state.login_links.delete_many(doc! { "user_id": id }).await;
+    let _ = state
+        .email_changes
+        .delete_many(doc! { "user_id": id })
+        .await;
+    let _ = state
+        .sso_identities
+        .delete_many(doc! { "user_id": id })
+        .await;
+    let _ = state.api_tokens.delete_many(doc! { "user_id": id }).await;
     Ok(())
 }
 
+/// Activates or deactivates a user. Deactivating also drops every session and
+/// refresh token the user holds so the change takes effect immediately instead
+/// of at session TTL.
+pub async fn set_user_active(state: &AppState, id: &ObjectId, active: bool) -> Result<()> {
+    let res = state
+        .users
+        .update_one(doc! { "_id": id }, doc! { "$set": { "is_active": active } })
+        .await?;
+    if res.matched_count == 0 {
+        anyhow::bail!("user not found");
+    }
+    if !active {
+        state.sessions.delete_many(doc! { "user_id": id }).await?;
+        state
+            .refresh_tokens
+            .delete_many(doc! { "user_id": id })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Decimal and thousands separators a user may pick. Thousands may also be
+/// empty (no grouping), but never the same as the decimal separator.
+pub const DECIMAL_SEPARATORS: [&str; 2] = [".", ","];
+pub const THOUSANDS_SEPARATORS: [&str; 4] = ["", ",", ".", " "];
+/// UTC offsets a user may show times in; empty is UTC.
+pub const UTC_OFFSETS: [&str; 9] = [
+    "", "-08:00", "-07:00", "-06:00", "-05:00", "-04:00", "-03:00", "+01:00", "+02:00",
+];
+
+pub async fn set_user_format_preferences(
+    state: &AppState,
+    id: &ObjectId,
+    preferences: &FormatPreferences,
+) -> Result<()> {
+    if !DECIMAL_SEPARATORS.contains(&preferences.decimal_separator.as_str()) {
+        anyhow::bail!("unsupported decimal separator");
+    }
+    if !THOUSANDS_SEPARATORS.contains(&preferences.thousands_separator.as_str()) {
+        anyhow::bail!("unsupported thousands separator");
+    }
+    if preferences.thousands_separator == preferences.decimal_separator {
+        anyhow::bail!("thousands and decimal separators must differ");
+    }
+    if !UTC_OFFSETS.contains(&preferences.utc_offset.as_str()) {
+        anyhow::bail!("unsupported UTC offset");
+    }
+    let res = state
+        .users
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "format_preferences": {
+                "decimal_separator": &preferences.decimal_separator,
+                "thousands_separator": &preferences.thousands_separator,
+                "date_format": preferences.date_format.as_str(),
+                "utc_offset": &preferences.utc_offset,
+            }}},
+        )
+        .await?;
+    if res.matched_count == 0 {
+        anyhow::bail!("user not found");
+    }
+    Ok(())
+}
+
+/// Records a pending change of the user's login identifier and returns the
+/// confirmation token, of which only the hash is kept. Any earlier pending
+/// change for the user is replaced.
+pub async fn request_email_change(
+    state: &AppState,
+    user_id: &ObjectId,
+    new_username: &str,
+) -> Result<String> {
+    if username_taken(state, new_username, Some(user_id)).await? {
+        anyhow::bail!("username '{new_username}' already exists");
+    }
+    state
+        .email_changes
+        .delete_many(doc! { "user_id": user_id })
+        .await?;
+
+    let mut token_bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut token_bytes);
+    let token = BASE32_NOPAD.encode(&token_bytes);
+    let expires_at = DateTime::from_system_time(
+        SystemTime::now() + Duration::from_secs(EMAIL_CHANGE_TTL_SECONDS),
+    );
+
+    state
+        .email_changes
+        .insert_one(EmailChange {
+            id: None,
+            user_id: *user_id,
+            new_username: new_username.to_string(),
+            token_hash: hash_token(&token),
+            expires_at,
+        })
+        .await?;
+
+    Ok(token)
+}
+
+pub async fn pending_email_change(
+    state: &AppState,
+    user_id: &ObjectId,
+) -> Result<Option<EmailChange>> {
+    let pending = state
+        .email_changes
+        .find_one(doc! { "user_id": user_id })
+        .await?;
+    Ok(pending.filter(|change| change.expires_at.to_system_time() > SystemTime::now()))
+}
+
+/// Applies the pending change behind `token` for `user_id`. Returns the new
+/// username, or `None` when the token is unknown, expired, belongs to another
+/// user, or the username was taken in the meantime.
+pub async fn confirm_email_change(
+    state: &AppState,
+    user_id: &ObjectId,
+    token: &str,
+) -> Result<Option<String>> {
+    let Some(change) = state
+        .email_changes
+        .find_one_and_delete(doc! { "token_hash": hash_token(token), "user_id": user_id })
+        .await?
+    else {
+        return Ok(None);
+    };
+    if change.expires_at.to_system_time() <= SystemTime::now()
+        || username_taken(state, &change.new_username, Some(user_id)).await?
+    {
+        return Ok(None);
+    }
+    state
+        .users
+        .update_one(
+            doc! { "_id": user_id },
+            doc! { "$set": { "username": &change.new_username } },
+        )
+        .await?;
+    Ok(Some(change.new_username))
+}
+
 pub async fn delete_session(state: &AppState, token: &str) -> Result<()> {
     let _ = state.sessions.delete_one(doc! { "token": token }).await?;
     Ok(())
 }
 
+/// Leaves `flash` for the next page the session opens, replacing any
+/// message not shown yet.
+pub async fn set_session_flash(state: &AppState, token: &str, flash: &Flash) -> Result<()> {
+    state
+        .sessions
+        .update_one(
+            doc! { "token": token },
+            doc! { "$set": { "flash": to_bson(flash)? } },
+        )
+        .await?;
+    Ok(())
+}
+
+/// Removes and returns the message left for the session, if any.
+pub async fn take_session_flash(state: &AppState, token: &str) -> Result<Option<Flash>> {
+    let session = state
+        .sessions
+        .find_one_and_update(
+            doc! { "token": token, "flash": { "$exists": true } },
+            doc! { "$unset": { "flash": "" } },
+        )
+        .await?;
+    Ok(session.and_then(|session| session.flash))
+}
+
 async fn build_user_with_company(state: &AppState, user: User) -> Result<UserWithCompany> {
     let id = user.id.context("user missing _id")?;
     let mut memberships = Vec::new();
@@ -347,6 +595,7 @@ async fn build_user_with_company(state: &AppState, user: User) -> Result<UserWit
     let mut company_slugs = Vec::new();
     let mut company_roles = Vec::new();
     let mut company_permissions = Vec::new();
+    let mut company_account_ids = Vec::new();
     for cid in &all_company_ids {
         if let Some(c) = state.companies.find_one(doc! { "_id": cid }).await? {
             company_names.push(c.name.clone());
@@ -362,8 +611,14 @@ async fn build_user_with_company(state: &AppState, user: User) -> Result<UserWit
             .find(|m| &m.company_id == cid)
             .map(|m| m.permissions.clone())
             .unwrap_or_default();
+        let account_ids_for_company = memberships
+            .iter()
+            .find(|m| &m.company_id == cid)
+            .map(|m| m.account_ids.clone())
+            .unwrap_or_default();
         company_roles.push(role_for_company);
         company_permissions.push(permissions_for_company);
+        company_account_ids.push(account_ids_for_company);
     }
     let primary_company = state
         .companies
@@ -390,6 +645,7 @@ async fn build_user_with_company(state: &AppState, user: User) -> Result<UserWit
 
     let effective_role = company_roles.get(0).cloned().unwrap_or(UserRole::Staff);
     let effective_permissions = company_permissions.first().cloned().unwrap_or_default();
+    let effective_account_ids = company_account_ids.first().cloned().unwrap_or_default();
     Ok(UserWithCompany {
         id,
         username: user.username,
@@ -402,11 +658,91 @@ async fn build_user_with_company(state: &AppState, user: User) -> Result<UserWit
         company_names,
         company_roles,
         company_permissions,
+        company_account_ids,
         role: effective_role,
         permissions: effective_permissions,
+        account_ids: effective_account_ids,
+        is_active: user.is_active,
+        format_preferences: user.format_preferences,
+        is_superadmin: user.is_superadmin,
+        display_name: user.display_name,
+        has_avatar: user.avatar_path.is_some(),
     })
 }
 
+/// Longest display name accepted, in characters.
+pub const MAX_DISPLAY_NAME_CHARS: usize = 80;
+
+/// Sets the name shown for the user; blank clears it so the username shows
+/// again.
+pub async fn set_user_display_name(
+    state: &AppState,
+    id: &ObjectId,
+    display_name: &str,
+) -> Result<()> {
+    let display_name = display_name.trim();
+    if display_name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+        anyhow::bail!("display name too long");
+    }
+    let update = if display_name.is_empty() {
+        doc! { "$unset": { "display_name": "" } }
+    } else {
+        doc! { "$set": { "display_name": display_name } }
+    };
+    let res = state.users.update_one(doc! { "_id": id }, update).await?;
+    if res.matched_count == 0 {
+        anyhow::bail!("user not found");
+    }
+    Ok(())
+}
+
+/// Stored avatar file of the user and its content type, if any.
+pub async fn user_avatar(state: &AppState, id: &ObjectId) -> Result<Option<(String, String)>> {
+    let user = state.users.find_one(doc! { "_id": id }).await?;
+    Ok(user.and_then(|user| user.avatar_path.zip(user.avatar_content_type)))
+}
+
+/// Points the user at a new avatar file (`path`, `content_type`) or, with
+/// `None`, removes it. Returns the path of the file it replaced so the
+/// caller can delete it.
+pub async fn set_user_avatar(
+    state: &AppState,
+    id: &ObjectId,
+    avatar: Option<(&str, &str)>,
+) -> Result<Option<String>> {
+    let update = match avatar {
+        Some((path, content_type)) => doc! { "$set": {
+            "avatar_path": path,
+            "avatar_content_type": content_type,
+        }},
+        None => doc! { "$unset": { "avatar_path": "", "avatar_content_type": "" } },
+    };
+    let previous = state
+        .users
+        .find_one_and_update(doc! { "_id": id }, update)
+        .await?
+        .context("user not found")?;
+    Ok(previous.avatar_path)
+}
+
+/// Display label (display name, or username when unset) of each of `ids`
+/// that still exists.
+pub async fn user_labels(state: &AppState, ids: &[ObjectId]) -> Result<HashMap<ObjectId, String>> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let users: Vec<User> = state
+        .users
+        .find(doc! { "_id": { "$in": ids } })
+        .await?
+        .try_collect()
+        .await?;
+    Ok(users
+        .into_iter()
+        .filter_map(|user| Some((user.id?, user.display_name.unwrap_or(user.username))))
+        .collect())
+}
+
 pub async fn update_user_company_permissions(
     state: &AppState,
     user_id: &ObjectId,
@@ -422,3 +758,40 @@ pub async fn update_user_company_permissions(
         .await?;
     Ok(())
 }
+
+/// Widget keys of the user's dashboard in the company, in order; `None` when
+/// the user keeps the default layout.
+pub async fn user_dashboard_widgets(
+    state: &AppState,
+    user_id: &ObjectId,
+    company_id: &ObjectId,
+) -> Result<Option<Vec<String>>> {
+    Ok(state
+        .user_companies
+        .find_one(doc! { "user_id": user_id, "company_id": company_id })
+        .await?
+        .and_then(|membership| membership.dashboard_widgets))
+}
+
+/// Stores the user's dashboard layout in the company, or goes back to the
+/// default one with `None`. Keys are checked by the caller against the
+/// widget registry. False when the user has no membership record there.
+pub async fn set_user_dashboard_widgets(
+    state: &AppState,
+    user_id: &ObjectId,
+    company_id: &ObjectId,
+    widgets: Option<&[String]>,
+) -> Result<bool> {
+    let update = match widgets {
+        Some(widgets) => doc! { "$set": { "dashboard_widgets": widgets } },
+        None => doc! { "$unset": { "dashboard_widgets": "" } },
+    };
+    let res = state
+        .user_companies
+        .update_one(
+            doc! { "user_id": user_id, "company_id": company_id },
+            update,
+        )
+        .await?;
+    Ok(res.matched_count > 0)
+}
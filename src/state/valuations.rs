@@ -0,0 +1,270 @@
+// Market valuations of investment accounts and the net worth built on them.
+// A revaluation records what the broker says the account is worth on a day;
+// nothing is posted as a transaction, so cash flow reports and balances stay
+// as they are. The difference between the market value and the balance is the
+// unrealized gain, which only the net worth counts.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use chrono::Duration as ChronoDuration;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+
+use crate::models::{Account, AccountType, AccountValuation};
+
+use super::{AppState, account_balance_at, companies::company_default_currency, utc_day_start};
+
+/// Market value of one account in a revaluation.
+#[derive(Debug, Clone)]
+pub struct ValuationInput {
+    pub account_id: ObjectId,
+    pub market_value: f64,
+    pub notes: Option<String>,
+}
+
+/// Latest valuation of the account as of `at` or before.
+pub async fn latest_account_valuation(
+    state: &AppState,
+    account_id: &ObjectId,
+    at: DateTime,
+) -> Result<Option<AccountValuation>> {
+    state
+        .account_valuations
+        .find_one(doc! { "account_id": account_id, "as_of": { "$lte": at } })
+        .sort(doc! { "as_of": -1 })
+        .await
+        .map_err(Into::into)
+}
+
+/// Every valuation of the account, newest first.
+pub async fn list_account_valuations(
+    state: &AppState,
+    account_id: &ObjectId,
+) -> Result<Vec<AccountValuation>> {
+    state
+        .account_valuations
+        .find(doc! { "account_id": account_id })
+        .sort(doc! { "as_of": -1 })
+        .await?
+        .try_collect()
+        .await
+        .map_err(Into::into)
+}
+
+/// Records the market value of several investment accounts of the company as
+/// of the day `as_of` falls in, replacing any valuation of that same day.
+/// Every input is checked before anything is written: the accounts must be
+/// investment accounts of the company and the day cannot precede their
+/// latest valuation. Returns the ids of the valuations, in input order.
+pub async fn record_account_valuations(
+    state: &AppState,
+    company_id: &ObjectId,
+    as_of: DateTime,
+    inputs: &[ValuationInput],
+    recorded_by: Option<ObjectId>,
+) -> Result<Vec<ObjectId>> {
+    if inputs.is_empty() {
+        bail!("no account to revalue");
+    }
+    let as_of = utc_day_start(as_of);
+    let end_of_day = DateTime::from_chrono(as_of.to_chrono() + ChronoDuration::days(1));
+
+    let mut planned = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if !input.market_value.is_finite() {
+            bail!("market value must be a number");
+        }
+        if planned
+            .iter()
+            .any(|(other, _): &(&ValuationInput, f64)| other.account_id == input.account_id)
+        {
+            bail!("account listed twice");
+        }
+        let Some(account) = state
+            .accounts
+            .find_one(doc! { "_id": input.account_id, "company_id": company_id })
+            .await?
+        else {
+            bail!("account not found in the company");
+        };
+        if account.account_type != AccountType::Investment {
+            bail!("only investment accounts are revalued");
+        }
+        let latest = latest_account_valuation(state, &input.account_id, DateTime::MAX).await?;
+        if latest.is_some_and(|latest| latest.as_of > as_of) {
+            bail!("a later valuation of the account exists");
+        }
+        let previous_gain = state
+            .account_valuations
+            .find_one(doc! { "account_id": input.account_id, "as_of": { "$lt": as_of } })
+            .sort(doc! { "as_of": -1 })
+            .await?
+            .map(|valuation| valuation.unrealized_gain)
+            .unwrap_or(0.0);
+        planned.push((input, previous_gain));
+    }
+
+    let mut ids = Vec::with_capacity(planned.len());
+    for (input, previous_gain) in planned {
+        let book_balance = account_balance_at(state, &input.account_id, end_of_day).await?;
+        let unrealized_gain = input.market_value - book_balance;
+        let valuation = AccountValuation {
+            id: None,
+            company_id: *company_id,
+            account_id: input.account_id,
+            as_of,
+            market_value: input.market_value,
+            book_balance,
+            unrealized_gain,
+            adjustment: unrealized_gain - previous_gain,
+            notes: input.notes.clone(),
+            recorded_by,
+            created_at: DateTime::now(),
+        };
+        state
+            .account_valuations
+            .delete_one(doc! { "account_id": input.account_id, "as_of": as_of })
+            .await?;
+        let res = state.account_valuations.insert_one(&valuation).await?;
+        ids.push(res.inserted_id.as_object_id().unwrap_or_default());
+    }
+    Ok(ids)
+}
+
+/// One account in the net worth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetWorthLine {
+    pub account_id: ObjectId,
+    pub name: String,
+    pub account_type: AccountType,
+    pub currency: String,
+    /// Balance from the recorded transactions.
+    pub book_balance: f64,
+    /// Gain of the latest valuation, zero for accounts never revalued.
+    pub unrealized_gain: f64,
+    pub valued_at: Option<DateTime>,
+}
+
+impl NetWorthLine {
+    /// The balance plus the unrealized gain. Movements after the valuation
+    /// count at face value.
+    pub fn value(&self) -> f64 {
+        self.book_balance + self.unrealized_gain
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetWorth {
+    pub currency: String,
+    pub as_of: DateTime,
+    pub lines: Vec<NetWorthLine>,
+}
+
+/// Book balance, unrealized gain and their sum.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetWorthTotals {
+    pub book_balance: f64,
+    pub unrealized_gain: f64,
+    pub value: f64,
+}
+
+impl NetWorth {
+    /// Totals per currency; the company currency is not mixed with others.
+    pub fn totals(&self) -> HashMap<String, NetWorthTotals> {
+        let mut totals: HashMap<String, NetWorthTotals> = HashMap::new();
+        for line in &self.lines {
+            let total = totals.entry(line.currency.clone()).or_default();
+            total.book_balance += line.book_balance;
+            total.unrealized_gain += line.unrealized_gain;
+            total.value += line.value();
+        }
+        totals
+    }
+}
+
+/// Net worth of the company at `now`: every active account at its balance,
+/// investment accounts also with the unrealized gain of their latest
+/// valuation.
+pub async fn net_worth_report(
+    state: &AppState,
+    company_id: &ObjectId,
+    now: DateTime,
+) -> Result<NetWorth> {
+    let currency = company_default_currency(state, company_id).await?;
+    let accounts: Vec<Account> = state
+        .for_reports(&state.accounts)
+        .find(doc! { "company_id": company_id, "is_active": true })
+        .sort(doc! { "name": 1 })
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut lines = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        let Some(account_id) = account.id else {
+            continue;
+        };
+        let book_balance = account_balance_at(state, &account_id, now).await?;
+        let valuation = if account.account_type == AccountType::Investment {
+            latest_account_valuation(state, &account_id, now).await?
+        } else {
+            None
+        };
+        lines.push(NetWorthLine {
+            account_id,
+            name: account.name,
+            account_type: account.account_type,
+            currency: account.currency,
+            book_balance,
+            unrealized_gain: valuation.as_ref().map_or(0.0, |v| v.unrealized_gain),
+            valued_at: valuation.map(|v| v.as_of),
+        });
+    }
+    Ok(NetWorth {
+        currency,
+        as_of: now,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(currency: &str, book_balance: f64, unrealized_gain: f64) -> NetWorthLine {
+        NetWorthLine {
+            account_id: ObjectId::new(),
+            name: "Cuenta".into(),
+            account_type: AccountType::Investment,
+            currency: currency.into(),
+            book_balance,
+            unrealized_gain,
+            valued_at: None,
+        }
+    }
+
+    #[test]
+    fn net_worth_adds_unrealized_gains_per_currency() {
+        let report = NetWorth {
+            currency: "MXN".into(),
+            as_of: DateTime::from_millis(0),
+            lines: vec![
+                line("MXN", 10_000.0, 0.0),
+                line("MXN", 50_000.0, 2_500.0),
+                line("USD", 1_000.0, -100.0),
+            ],
+        };
+        assert_eq!(report.lines[1].value(), 52_500.0);
+        let totals = report.totals();
+        assert_eq!(
+            totals["MXN"],
+            NetWorthTotals {
+                book_balance: 60_000.0,
+                unrealized_gain: 2_500.0,
+                value: 62_500.0,
+            }
+        );
+        assert_eq!(totals["USD"].value, 900.0);
+    }
+}
@@ -0,0 +1,468 @@
+// Company webhooks: events POSTed as JSON to the URL set on the company, so
+// BI and other downstream systems can pull fresh data as soon as it exists.
+// Each body is signed with the company's secret (HMAC-SHA256 in
+// `X-Webhook-Signature`) and delivered in the background, retried a couple of
+// times; the outcome of the last delivery is kept on the company.
+//
+// The URL is chosen by a tenant admin, so each delivery resolves its host and
+// refuses loopback, private, link-local and other non-public addresses, does
+// not follow redirects, and records only whether it was delivered: neither
+// the reason nor the answer of the endpoint is shown back.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, doc, oid::ObjectId};
+use rand::RngCore;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::models::{CompanyWebhooks, Forecast};
+
+use super::{AppState, get_company_by_id};
+
+/// Seconds to wait before each delivery attempt; the first goes right away.
+const RETRY_DELAYS: [u64; 3] = [0, 10, 60];
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// What the company sees of a failed delivery.
+pub const WEBHOOK_DELIVERY_FAILED: &str = "no se pudo entregar";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    /// Sent from the settings page to check the URL and secret.
+    Ping,
+    ForecastCompleted,
+    /// Notification messages, when the notification settings pick the
+    /// webhook as a channel.
+    OverdueAlert,
+    BudgetAlert,
+    Digest,
+}
+
+impl WebhookEvent {
+    /// Name sent in the body and the `X-Webhook-Event` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Ping => "ping",
+            WebhookEvent::ForecastCompleted => "forecast.completed",
+            WebhookEvent::OverdueAlert => "alert.overdue",
+            WebhookEvent::BudgetAlert => "alert.budget",
+            WebhookEvent::Digest => "digest",
+        }
+    }
+}
+
+/// A fresh signing secret for a company's webhooks.
+pub fn new_webhook_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", BASE32_NOPAD.encode(&bytes).to_lowercase())
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// `X-Webhook-Signature` of `body`: `sha256=` and the hex HMAC-SHA256 of the
+/// exact bytes sent, keyed with the company's secret.
+pub fn webhook_signature(secret: &str, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        HEXLOWER.encode(&hmac_sha256(secret.as_bytes(), body))
+    )
+}
+
+/// Body of `forecast.completed`: the forecast (or scenario group) that was
+/// generated and the headline numbers of each of its forecasts.
+pub fn forecast_completed_payload(
+    company_id: &ObjectId,
+    id: &ObjectId,
+    forecasts: &[Forecast],
+    sent_at: DateTime,
+) -> Value {
+    let date = |value: DateTime| value.to_chrono().format("%Y-%m-%d").to_string();
+    json!({
+        "event": WebhookEvent::ForecastCompleted.as_str(),
+        "company_id": company_id.to_hex(),
+        "forecast_id": id.to_hex(),
+        "scenario_group_id": forecasts
+            .iter()
+            .find_map(|forecast| forecast.scenario_group_id)
+            .map(|group_id| group_id.to_hex()),
+        "sent_at": sent_at.to_chrono().to_rfc3339(),
+        "forecasts": forecasts
+            .iter()
+            .map(|forecast| json!({
+                "id": forecast.id.map(|id| id.to_hex()),
+                "reference": forecast.reference,
+                "scenario": forecast.scenario_name,
+                "start_date": date(forecast.start_date),
+                "end_date": date(forecast.end_date),
+                "currency": forecast.currency,
+                "projected_income_total": forecast.projected_income_total,
+                "projected_expense_total": forecast.projected_expense_total,
+                "projected_net": forecast.projected_net,
+                "weighted_income_total": forecast.weighted_income_total,
+                "initial_balance": forecast.initial_balance,
+                "final_balance": forecast.final_balance,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (carrier-grade NAT).
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments, benchmarking and reserved.
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+/// Whether `ip` is an address of the public internet: not loopback, private,
+/// link-local (cloud metadata included), shared, reserved or unique local.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ipv4(mapped);
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local, link-local and the old site-local.
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || (first & 0xffc0) == 0xfec0
+                // Documentation and the NAT64 prefix, which reaches IPv4.
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+                || ip.segments()[..6] == Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0).segments()[..6])
+        }
+    }
+}
+
+/// Client for one delivery to `url`. The host is resolved here and pinned,
+/// so the connection goes to the addresses checked, and redirects are not
+/// followed. Unless `private_networks`, a host with any non-public address
+/// is refused.
+async fn delivery_client(url: &str, private_networks: bool) -> Result<reqwest::Client> {
+    let parsed = reqwest::Url::parse(url)?;
+    let port = parsed
+        .port_or_known_default()
+        .context("webhook URL without port")?;
+    let builder = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    let host = parsed.host_str().context("webhook URL without host")?;
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let (builder, addrs) = match literal.parse::<IpAddr>() {
+        Ok(ip) => (builder, vec![SocketAddr::new(ip, port)]),
+        Err(_) => {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+            (builder.resolve_to_addrs(host, &addrs), addrs)
+        }
+    };
+    if addrs.is_empty() {
+        bail!("{url} did not resolve");
+    }
+    if !private_networks && let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        bail!("{url} resolves to the non-public address {}", addr.ip());
+    }
+    Ok(builder.build()?)
+}
+
+/// POSTs `body` once.
+async fn post_webhook(
+    state: &AppState,
+    url: &str,
+    secret: &str,
+    event: WebhookEvent,
+    delivery_id: &str,
+    body: &[u8],
+) -> Result<()> {
+    let client = delivery_client(url, state.webhooks_private_networks).await?;
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Event", event.as_str())
+        .header("X-Webhook-Id", delivery_id)
+        .header("X-Webhook-Signature", webhook_signature(secret, body))
+        .body(body.to_vec())
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("{url} answered {}", response.status());
+    }
+    Ok(())
+}
+
+async fn record_delivery(state: &AppState, company_id: &ObjectId, delivered: bool) {
+    let now = DateTime::now();
+    let update = if delivered {
+        doc! {
+            "$set": { "webhooks.last_delivery_at": now },
+            "$unset": { "webhooks.last_error": "" },
+        }
+    } else {
+        doc! {
+            "$set": {
+                "webhooks.last_delivery_at": now,
+                "webhooks.last_error": WEBHOOK_DELIVERY_FAILED,
+            },
+        }
+    };
+    if let Err(err) = state
+        .companies
+        .update_one(doc! { "_id": company_id }, update)
+        .await
+    {
+        eprintln!("[webhooks] could not record delivery: {err}");
+    }
+}
+
+/// Webhook settings of the company when it wants `event`.
+async fn webhook_for(
+    state: &AppState,
+    company_id: &ObjectId,
+    event: WebhookEvent,
+) -> Result<Option<CompanyWebhooks>> {
+    let Some(company) = get_company_by_id(state, company_id).await? else {
+        return Ok(None);
+    };
+    let settings = company.webhooks;
+    let wanted = match event {
+        WebhookEvent::ForecastCompleted => settings.forecast_completed,
+        // The notification settings already chose these.
+        WebhookEvent::Ping
+        | WebhookEvent::OverdueAlert
+        | WebhookEvent::BudgetAlert
+        | WebhookEvent::Digest => true,
+    };
+    Ok((wanted && !settings.url.is_empty()).then_some(settings))
+}
+
+/// Delivers `body` in the background, retrying on failure, and records how
+/// it went on the company.
+fn spawn_delivery(
+    state: &AppState,
+    company_id: &ObjectId,
+    settings: CompanyWebhooks,
+    event: WebhookEvent,
+    body: Vec<u8>,
+) {
+    let state = state.clone();
+    let company_id = *company_id;
+    state.webhook_deliveries.fetch_add(1, Ordering::Relaxed);
+    tokio::spawn(async move {
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let mut error = None;
+        for delay in RETRY_DELAYS {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            match post_webhook(
+                &state,
+                &settings.url,
+                &settings.secret,
+                event,
+                &delivery_id,
+                &body,
+            )
+            .await
+            {
+                Ok(()) => {
+                    error = None;
+                    break;
+                }
+                Err(err) => error = Some(err.to_string()),
+            }
+        }
+        if let Some(error) = &error {
+            eprintln!("[webhooks] {} failed: {error}", event.as_str());
+        }
+        record_delivery(&state, &company_id, error.is_none()).await;
+        state.webhook_deliveries.fetch_sub(1, Ordering::Relaxed);
+    });
+}
+
+/// Announces a forecast, or the forecasts of a scenario group, once they are
+/// stored. `id` is the one the `ForecastReady` event carries. Returns once
+/// the delivery is queued.
+pub async fn notify_forecast_completed(
+    state: &AppState,
+    company_id: &ObjectId,
+    id: &ObjectId,
+) -> Result<()> {
+    let event = WebhookEvent::ForecastCompleted;
+    let Some(settings) = webhook_for(state, company_id, event).await? else {
+        return Ok(());
+    };
+    let forecasts: Vec<Forecast> = state
+        .forecasts
+        .find(doc! {
+            "company_id": company_id,
+            "$or": [{ "_id": id }, { "scenario_group_id": id }],
+        })
+        .sort(doc! { "_id": 1 })
+        .await?
+        .try_collect()
+        .await?;
+    if forecasts.is_empty() {
+        return Ok(());
+    }
+    let payload = forecast_completed_payload(company_id, id, &forecasts, DateTime::now());
+    spawn_delivery(
+        state,
+        company_id,
+        settings,
+        event,
+        serde_json::to_vec(&payload)?,
+    );
+    Ok(())
+}
+
+/// Queues a notification message (an alert or the digest) for the company's
+/// webhook. Returns whether there was a URL to send it to.
+pub async fn notify_webhook_message(
+    state: &AppState,
+    company_id: &ObjectId,
+    event: WebhookEvent,
+    text: &str,
+) -> Result<bool> {
+    let Some(settings) = webhook_for(state, company_id, event).await? else {
+        return Ok(false);
+    };
+    let payload = json!({
+        "event": event.as_str(),
+        "company_id": company_id.to_hex(),
+        "text": text,
+        "sent_at": DateTime::now().to_chrono().to_rfc3339(),
+    });
+    spawn_delivery(
+        state,
+        company_id,
+        settings,
+        event,
+        serde_json::to_vec(&payload)?,
+    );
+    Ok(true)
+}
+
+/// Companies whose last webhook delivery failed.
+pub async fn failed_webhook_companies(state: &AppState) -> Result<u64> {
+    Ok(state
+        .companies
+        .count_documents(doc! { "webhooks.last_error": { "$exists": true } })
+        .await?)
+}
+
+/// Sends a `ping` with no data to check the URL and secret, and waits for
+/// the answer. The error is for the server log, not for the company.
+pub async fn send_test_webhook(state: &AppState, url: &str, secret: &str) -> Result<()> {
+    let body = serde_json::to_vec(&json!({
+        "event": WebhookEvent::Ping.as_str(),
+        "sent_at": DateTime::now().to_chrono().to_rfc3339(),
+    }))?;
+    let delivery_id = uuid::Uuid::new_v4().to_string();
+    post_webhook(state, url, secret, WebhookEvent::Ping, &delivery_id, &body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_sha256_of_the_body() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            webhook_signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn only_public_addresses_receive_webhooks() {
+        for public in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public_ip(public.parse().unwrap()), "{public}");
+        }
+        for internal in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public_ip(internal.parse().unwrap()), "{internal}");
+        }
+    }
+
+    #[test]
+    fn forecast_payload_carries_the_headline_numbers() {
+        let (company_id, group_id) = (ObjectId::new(), ObjectId::new());
+        let now = DateTime::now();
+        let forecast = Forecast {
+            id: Some(ObjectId::new()),
+            company_id,
+            generated_at: now,
+            generated_by_user_id: None,
+            start_date: now,
+            end_date: now,
+            currency: "MXN".into(),
+            projected_income_total: 1000.0,
+            projected_expense_total: 400.0,
+            projected_net: 600.0,
+            weighted_income_total: Some(900.0),
+            initial_balance: Some(50.0),
+            final_balance: Some(650.0),
+            details: None,
+            scenario_name: Some("expected".into()),
+            scenario_group_id: Some(group_id),
+            reference: Some("FC-2025-0001".into()),
+            notes: None,
+        };
+        let payload = forecast_completed_payload(&company_id, &group_id, &[forecast], now);
+        assert_eq!(payload["event"], "forecast.completed");
+        assert_eq!(payload["forecast_id"], group_id.to_hex());
+        assert_eq!(payload["scenario_group_id"], group_id.to_hex());
+        assert_eq!(payload["forecasts"][0]["projected_net"], 600.0);
+        assert_eq!(payload["forecasts"][0]["scenario"], "expected");
+        assert_eq!(payload["forecasts"][0]["reference"], "FC-2025-0001");
+    }
+}
@@ -0,0 +1,191 @@
+// Template context: who is looking at the page being rendered. The session
+// middleware sets it next to the flash message and the format preferences,
+// so any template can hide the actions the user may not take instead of
+// showing buttons that end in a 403.
+
+use mongodb::bson::oid::ObjectId;
+
+use crate::{
+    demo::demo_mode_enabled,
+    models::{UserPermission, UserRole},
+    oidc::OidcConfig,
+    state::UserWithCompany,
+};
+
+tokio::task_local! {
+    /// Context of the request being rendered.
+    static CONTEXT: TemplateContext;
+}
+
+/// Deployment switches templates may look at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureFlags {
+    /// `DEMO_MODE`: every visitor gets a throwaway sandbox.
+    pub demo: bool,
+    /// SSO login is configured.
+    pub sso: bool,
+    /// Built with the `dev-factory` feature.
+    pub dev_factory: bool,
+}
+
+impl FeatureFlags {
+    pub fn from_env() -> Self {
+        Self {
+            demo: demo_mode_enabled(),
+            sso: OidcConfig::from_env().is_some(),
+            dev_factory: cfg!(feature = "dev-factory"),
+        }
+    }
+}
+
+/// Signed-in user, as seen in the active company.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentUser {
+    pub id: ObjectId,
+    /// Display name when set, otherwise the username.
+    pub label: String,
+    pub role: UserRole,
+    pub permissions: Vec<UserPermission>,
+    pub is_superadmin: bool,
+    pub company_id: ObjectId,
+    pub company_slug: String,
+    pub company_name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateContext {
+    /// `None` on pages rendered without a session.
+    pub user: Option<CurrentUser>,
+    pub features: FeatureFlags,
+}
+
+impl TemplateContext {
+    pub fn for_user(user: &UserWithCompany) -> Self {
+        Self {
+            user: Some(CurrentUser {
+                id: user.id,
+                label: user.label().to_string(),
+                role: user.role.clone(),
+                permissions: user.permissions.clone(),
+                is_superadmin: user.is_superadmin,
+                company_id: user.company_id,
+                company_slug: user.company_slug.clone(),
+                company_name: user.company_name.clone(),
+            }),
+            features: FeatureFlags::from_env(),
+        }
+    }
+
+    /// Admin of the active company.
+    pub fn is_admin(&self) -> bool {
+        self.user.as_ref().is_some_and(|user| user.role.is_admin())
+    }
+
+    /// Admin or auditor of the active company: gets the admin navigation.
+    pub fn can_view_admin(&self) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|user| user.role.can_view_admin())
+    }
+
+    /// Auditor of the active company: sees the admin pages but cannot
+    /// change anything.
+    pub fn is_read_only(&self) -> bool {
+        self.user
+            .as_ref()
+            .is_some_and(|user| user.role.is_read_only())
+    }
+
+    pub fn is_superadmin(&self) -> bool {
+        self.user.as_ref().is_some_and(|user| user.is_superadmin)
+    }
+
+    /// Whether the user holds `permission` (e.g. `"view_timeline"`) in the
+    /// active company. Admins hold them all, as in `SessionUser::has_permission`.
+    pub fn can(&self, permission: &str) -> bool {
+        self.user.as_ref().is_some_and(|user| {
+            user.role.can_view_admin()
+                || user
+                    .permissions
+                    .iter()
+                    .any(|held| held.as_str() == permission)
+        })
+    }
+
+    /// Whether the user holds any of the space separated `permissions`.
+    pub fn can_any(&self, permissions: &str) -> bool {
+        permissions
+            .split_whitespace()
+            .any(|permission| self.can(permission))
+    }
+
+    /// Name of the active company, empty without a session.
+    pub fn company_name(&self) -> &str {
+        self.user
+            .as_ref()
+            .map(|user| user.company_name.as_str())
+            .unwrap_or_default()
+    }
+}
+
+/// Runs `future` (a request handler) with `context` available to the
+/// templates it renders.
+pub async fn with_template_context<F: Future>(context: TemplateContext, future: F) -> F::Output {
+    CONTEXT.scope(context, future).await
+}
+
+/// Context of the page being rendered; signed out outside a session. Called
+/// from the templates.
+pub fn current_context() -> TemplateContext {
+    CONTEXT.try_with(Clone::clone).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staff(permissions: Vec<UserPermission>) -> TemplateContext {
+        TemplateContext {
+            user: Some(CurrentUser {
+                id: ObjectId::new(),
+                label: "ana".to_string(),
+                role: UserRole::Staff,
+                permissions,
+                is_superadmin: false,
+                company_id: ObjectId::new(),
+                company_slug: "acme".to_string(),
+                company_name: "Acme".to_string(),
+            }),
+            features: FeatureFlags::default(),
+        }
+    }
+
+    #[test]
+    fn permissions_follow_the_role() {
+        let viewer = staff(vec![UserPermission::ViewTimeline]);
+        assert!(viewer.can("view_timeline"));
+        assert!(!viewer.can("view_projects"));
+        assert!(viewer.can_any("view_projects view_timeline"));
+        assert!(!viewer.is_admin());
+
+        let mut admin = staff(Vec::new());
+        if let Some(user) = admin.user.as_mut() {
+            user.role = UserRole::Admin;
+        }
+        assert!(admin.is_admin());
+        assert!(admin.can("view_projects"));
+
+        let anonymous = TemplateContext::default();
+        assert!(anonymous.user.is_none());
+        assert!(!anonymous.can_any("view_projects view_timeline"));
+        assert_eq!(anonymous.company_name(), "");
+    }
+
+    #[tokio::test]
+    async fn context_is_read_inside_the_scope_only() {
+        let context = staff(Vec::new());
+        let seen = with_template_context(context.clone(), async { current_context() }).await;
+        assert_eq!(seen, context);
+        assert_eq!(current_context(), TemplateContext::default());
+    }
+}
@@ -0,0 +1,297 @@
+//! In-process harness for handler tests, behind the `test-harness` feature.
+//!
+//! `build_router` wires the handlers like `main.rs` does, minus the Swagger UI,
+//! the test-tenant reports and the SPA assets, so tests drive requests through
+//! `tower::ServiceExt::oneshot` without binding a socket. The fixture builders
+//! create the companies, users and recurring plans those tests start from, and
+//! `RecordingMailer` stands in for the mail relay.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use axum::{Router, middleware};
+use futures::future::BoxFuture;
+use mongodb::bson::{DateTime, oid::ObjectId};
+
+use crate::{
+    mailer::{Email, Mailer},
+    metrics,
+    models::{AccountType, FlowType, UserPermission, UserRole},
+    routes,
+    session::{SESSION_COOKIE_NAME, require_session},
+    state::{
+        AppState, create_account, create_category, create_company, create_recurring_plan,
+        create_session, create_user_with_permissions,
+    },
+    uploads::BodyLimits,
+};
+
+/// Router with every page and API handler behind the same session middleware
+/// as production, using the default body limits.
+pub fn build_router(state: Arc<AppState>) -> Router {
+    let limits = BodyLimits::default();
+    let protected = routes::protected_routes(&limits).into_router().route_layer(
+        middleware::from_fn_with_state(state.clone(), require_session),
+    );
+
+    Router::new()
+        .merge(routes::public_router(state.clone()))
+        .merge(protected)
+        .layer(limits.form_layer())
+        .layer(middleware::from_fn(metrics::track_requests))
+        .with_state(state)
+}
+
+/// Paths of every route behind the session middleware, in the order they are
+/// registered, so tests can sweep them all.
+pub fn protected_paths() -> Vec<&'static str> {
+    routes::protected_routes(&BodyLimits::default())
+        .paths()
+        .to_vec()
+}
+
+/// `Cookie` header value that sends `token` as the session.
+pub fn session_cookie(token: &str) -> String {
+    format!("{SESSION_COOKIE_NAME}={token}")
+}
+
+/// `Host` header that selects the company with `slug` as the active tenant.
+pub fn tenant_host(slug: &str) -> String {
+    format!("{slug}.miapp.local")
+}
+
+/// Active company, created with [`CompanyFixture::create`].
+pub struct CompanyFixture {
+    name: String,
+    slug: String,
+    currency: String,
+}
+
+impl CompanyFixture {
+    /// Company named after its slug, in MXN.
+    pub fn new(slug: &str) -> Self {
+        Self {
+            name: slug.to_string(),
+            slug: slug.to_string(),
+            currency: "MXN".to_string(),
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn currency(mut self, currency: &str) -> Self {
+        self.currency = currency.to_string();
+        self
+    }
+
+    pub async fn create(self, state: &AppState) -> Result<ObjectId> {
+        create_company(state, &self.name, &self.slug, &self.currency, true, None).await
+    }
+}
+
+/// User with one membership per `admin_of`/`staff_of` call; the first one is
+/// the primary company.
+pub struct UserFixture {
+    username: String,
+    secret: String,
+    memberships: Vec<(ObjectId, UserRole, Vec<UserPermission>)>,
+}
+
+impl UserFixture {
+    pub fn new(username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            secret: "SECRET".to_string(),
+            memberships: Vec::new(),
+        }
+    }
+
+    pub fn secret(mut self, secret: &str) -> Self {
+        self.secret = secret.to_string();
+        self
+    }
+
+    pub fn admin_of(mut self, company_id: &ObjectId) -> Self {
+        self.memberships
+            .push((*company_id, UserRole::Admin, Vec::new()));
+        self
+    }
+
+    /// Read-only admin of the company; see `UserRole::Auditor`.
+    pub fn auditor_of(mut self, company_id: &ObjectId) -> Self {
+        self.memberships
+            .push((*company_id, UserRole::Auditor, Vec::new()));
+        self
+    }
+
+    pub fn staff_of(mut self, company_id: &ObjectId, permissions: &[UserPermission]) -> Self {
+        self.memberships
+            .push((*company_id, UserRole::Staff, permissions.to_vec()));
+        self
+    }
+
+    pub async fn create(self, state: &AppState) -> Result<ObjectId> {
+        create_user_with_permissions(state, &self.username, &self.secret, &self.memberships).await
+    }
+
+    /// Creates the user and logs it in. Returns the user id and the session
+    /// token for [`session_cookie`].
+    pub async fn create_with_session(self, state: &AppState) -> Result<(ObjectId, String)> {
+        let username = self.username.clone();
+        let user_id = self.create(state).await?;
+        let token = create_session(state, &username).await?;
+        Ok((user_id, token))
+    }
+}
+
+/// Monthly recurring plan of 100 starting on 2026-01-01. Without an explicit
+/// category or account, one named after the plan is created for it.
+pub struct RecurringPlanFixture {
+    company_id: ObjectId,
+    name: String,
+    flow_type: FlowType,
+    amount: f64,
+    frequency: String,
+    day_of_month: Option<i32>,
+    start_date: DateTime,
+    category_id: Option<ObjectId>,
+    account_id: Option<ObjectId>,
+}
+
+impl RecurringPlanFixture {
+    pub fn new(company_id: &ObjectId, name: &str) -> Self {
+        Self {
+            company_id: *company_id,
+            name: name.to_string(),
+            flow_type: FlowType::Expense,
+            amount: 100.0,
+            frequency: "monthly".to_string(),
+            day_of_month: Some(1),
+            start_date: DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z")
+                .expect("valid fixture date"),
+            category_id: None,
+            account_id: None,
+        }
+    }
+
+    pub fn income(mut self) -> Self {
+        self.flow_type = FlowType::Income;
+        self
+    }
+
+    pub fn amount(mut self, amount: f64) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn frequency(mut self, frequency: &str, day_of_month: Option<i32>) -> Self {
+        self.frequency = frequency.to_string();
+        self.day_of_month = day_of_month;
+        self
+    }
+
+    pub fn start_date(mut self, start_date: DateTime) -> Self {
+        self.start_date = start_date;
+        self
+    }
+
+    pub fn category(mut self, category_id: &ObjectId) -> Self {
+        self.category_id = Some(*category_id);
+        self
+    }
+
+    pub fn account(mut self, account_id: &ObjectId) -> Self {
+        self.account_id = Some(*account_id);
+        self
+    }
+
+    pub async fn create(self, state: &AppState) -> Result<ObjectId> {
+        let category_id = match self.category_id {
+            Some(id) => id,
+            None => {
+                create_category(
+                    state,
+                    &self.company_id,
+                    &self.name,
+                    self.flow_type.clone(),
+                    None,
+                    None,
+                )
+                .await?
+            }
+        };
+        let account_id = match self.account_id {
+            Some(id) => id,
+            None => {
+                create_account(
+                    state,
+                    &self.company_id,
+                    &self.name,
+                    AccountType::Bank,
+                    "",
+                    true,
+                    None,
+                )
+                .await?
+            }
+        };
+        create_recurring_plan(
+            state,
+            &self.company_id,
+            &self.name,
+            self.flow_type,
+            &category_id,
+            &account_id,
+            None,
+            self.amount,
+            &self.frequency,
+            self.day_of_month,
+            self.start_date,
+            None,
+            true,
+            1,
+            None,
+        )
+        .await
+    }
+}
+
+/// Mailer that keeps what it is given, for tests to read the links sent.
+/// Install it with `state.mailer = mailer.clone()`.
+#[derive(Default)]
+pub struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl RecordingMailer {
+    /// Every email sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// The `token` query parameter of the last link sent to `to`.
+    pub fn last_token_for(&self, to: &str) -> Option<String> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.to == to)
+            .and_then(|email| email.text.split("token=").nth(1))
+            .map(|rest| {
+                rest.chars()
+                    .take_while(char::is_ascii_alphanumeric)
+                    .collect()
+            })
+    }
+}
+
+impl Mailer for RecordingMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        self.sent.lock().unwrap().push(email.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
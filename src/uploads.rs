@@ -0,0 +1,104 @@
+//! Request body limits and checks on uploaded files.
+//!
+//! Every route accepts small bodies only (forms and JSON); routes that take
+//! file uploads are layered with a larger allowance. Uploaded files are
+//! identified by their content, never by the name or type the client sent.
+
+use std::env;
+
+use axum::extract::DefaultBodyLimit;
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Largest request bodies accepted, per route class.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    /// Forms and JSON payloads.
+    pub form_bytes: usize,
+    /// Multipart uploads (receipts, SAT certificates, bank statements).
+    pub upload_bytes: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            form_bytes: 256 * 1024,
+            // Room for the two 2 MiB SAT files plus the other fields.
+            upload_bytes: 6 * 1024 * 1024,
+        }
+    }
+}
+
+impl BodyLimits {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |key: &str, default: usize| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            form_bytes: read("BODY_LIMIT_FORM_BYTES", defaults.form_bytes),
+            upload_bytes: read("BODY_LIMIT_UPLOAD_BYTES", defaults.upload_bytes),
+        }
+    }
+
+    /// Router-wide default. Form, JSON and multipart extractors stop reading
+    /// past it and answer 413.
+    pub fn form_layer(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.form_bytes)
+    }
+
+    /// Layer for upload routes. Raises the extractor limit set by
+    /// `form_layer` and rejects bodies announcing a larger size before any of
+    /// it is read.
+    pub fn upload_layer(&self) -> (RequestBodyLimitLayer, DefaultBodyLimit) {
+        (
+            RequestBodyLimitLayer::new(self.upload_bytes),
+            DefaultBodyLimit::max(self.upload_bytes),
+        )
+    }
+}
+
+/// Content type of a receipt-like file, told from its leading bytes.
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\xFF\xD8\xFF") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+/// Whether `data` starts like a DER structure (an ASN.1 SEQUENCE), as the
+/// SAT `.cer` certificates and `.key` private keys do.
+pub fn is_der_encoded(data: &[u8]) -> bool {
+    data.first() == Some(&0x30)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_by_content_not_name() {
+        assert_eq!(
+            sniff_content_type(b"\xFF\xD8\xFF\xE0rest"),
+            Some("image/jpeg")
+        );
+        assert_eq!(
+            sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_content_type(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(sniff_content_type(b"<html>"), None);
+        assert!(is_der_encoded(b"\x30\x82\x04"));
+        assert!(!is_der_encoded(b"-----BEGIN CERTIFICATE-----"));
+    }
+}
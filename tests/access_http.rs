@@ -564,7 +564,10 @@ async fn users_json_api_crud_scopes_and_enforces_admin() {
     assert_eq!(status, StatusCode::OK, "{body}");
     let new_oid = bson::oid::ObjectId::parse_str(&new_id).unwrap();
     let updated = get_user_by_id(&state, &new_oid).await.unwrap().unwrap();
-    assert_eq!(updated.username, "users-json-updated@example.com");
+    // The rename waits for the user to confirm it from the new address.
+    assert_eq!(updated.username, "users-json-new@example.com");
+    let pending = pending_email_change(&state, &new_oid).await.unwrap().unwrap();
+    assert_eq!(pending.new_username, "users-json-updated@example.com");
     assert_eq!(updated.role, UserRole::Admin);
 
     // cannot delete yourself
@@ -643,6 +646,66 @@ async fn protected_endpoints_require_authentication() {
     common::teardown(Some(ctx)).await;
 }
 
+#[tokio::test]
+async fn pages_without_a_session_go_to_login_and_keep_the_target() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let shared = Arc::new(ctx.state.clone());
+
+    let page = |path: &str| {
+        Request::builder()
+            .uri(path)
+            .header("host", "localhost")
+            .header(header::ACCEPT, "text/html,application/xhtml+xml")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = build_app(shared.clone())
+        .oneshot(page("/admin/transactions?month=2026-03"))
+        .await
+        .expect("request failed");
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/?next=%2Fadmin%2Ftransactions%3Fmonth%3D2026-03"
+    );
+
+    // A page outside the allow-list is not carried along.
+    let res = build_app(shared.clone())
+        .oneshot(page("/setup"))
+        .await
+        .expect("request failed");
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/");
+
+    // The login page keeps a safe target in the form and drops any other.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        "localhost",
+        "/?next=%2Fadmin%2Ftransactions",
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"name="next" value="/admin/transactions""#));
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        "localhost",
+        "/?next=%2F%2Fevil.test%2Fadmin",
+        "",
+    )
+    .await;
+    assert!(!body.contains(r#"name="next""#));
+
+    // API calls still get a plain 401.
+    assert_requires_auth_get(&shared, "/api/admin/accounts").await;
+
+    common::teardown(Some(ctx)).await;
+}
+
 
 #[tokio::test]
 async fn cross_tenant_record_access_is_denied() {
@@ -1234,3 +1297,770 @@ async fn test_tooling_is_gated_to_login_and_the_test_tenant() {
     common::teardown(Some(ctx)).await;
 }
 
+
+#[tokio::test]
+async fn demo_mode_provisions_sandbox_per_visitor_and_drops_it_on_logout() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let uri = std::env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".into());
+    let hub = DemoHub::new(&uri, &ctx.db_name, build_app).with_max_sandboxes(1);
+    let enter_demo = || {
+        Request::builder()
+            .method("POST")
+            .uri(DEMO_ENTRY_PATH)
+            .header("host", "localhost")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Without a sandbox nothing is provisioned; the visitor is sent to the
+    // demo page.
+    let res = hub
+        .clone()
+        .router()
+        .oneshot(
+            Request::builder()
+                .uri("/api/me")
+                .header("host", "localhost")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()[header::LOCATION], DEMO_ENTRY_PATH);
+    assert_eq!(hub.sandbox_count().await, 0);
+
+    let res = hub.clone().router().oneshot(enter_demo()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let cookies: Vec<String> = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next().map(str::to_string))
+        .collect();
+    assert!(cookies.iter().any(|c| c.starts_with(DEMO_COOKIE_NAME)));
+    assert!(cookies.iter().any(|c| c.starts_with(SESSION_COOKIE_NAME)));
+    assert_eq!(hub.sandbox_count().await, 1);
+    let cookie_header = cookies.join("; ");
+
+    // The cap is reached: another visitor is turned away.
+    let res = hub.clone().router().oneshot(enter_demo()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hub.sandbox_count().await, 1);
+
+    // The visitor keeps landing in the same sandbox while the cookie is set.
+    let res = hub
+        .clone()
+        .router()
+        .oneshot(
+            Request::builder()
+                .uri("/api/me")
+                .header("host", "localhost")
+                .header("cookie", &cookie_header)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hub.sandbox_count().await, 1);
+
+    let res = hub
+        .clone()
+        .router()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/logout")
+                .header("host", "localhost")
+                .header("cookie", &cookie_header)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hub.sandbox_count().await, 0);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn demo_sandboxes_in_use_survive_the_sweep_and_idle_ones_do_not() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let uri = std::env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".into());
+    let idle_ttl = std::time::Duration::from_secs(1);
+    let hub = DemoHub::new(&uri, &ctx.db_name, build_app)
+        .with_max_sandboxes(2)
+        .with_idle_ttl(idle_ttl);
+    // Entering the demo sweeps before provisioning; returns the new cookies.
+    let enter_demo = || {
+        let hub = hub.clone();
+        async move {
+            let res = hub
+                .router()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(DEMO_ENTRY_PATH)
+                        .header("host", "localhost")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::SEE_OTHER);
+            res.headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .filter_map(|v| v.split(';').next().map(str::to_string))
+                .collect::<Vec<_>>()
+                .join("; ")
+        }
+    };
+    let me = |cookie_header: &str| {
+        hub.clone().router().oneshot(
+            Request::builder()
+                .uri("/api/me")
+                .header("host", "localhost")
+                .header("cookie", cookie_header)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // The first visitor keeps working past the idle TTL counted from creation.
+    let active = enter_demo().await;
+    for _ in 0..4 {
+        tokio::time::sleep(idle_ttl / 3).await;
+        let res = me(&active).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    enter_demo().await;
+    assert_eq!(hub.sandbox_count().await, 2);
+    assert_eq!(me(&active).await.unwrap().status(), StatusCode::OK);
+
+    // Once both sit idle, the next visitor's arrival drops them.
+    tokio::time::sleep(idle_ttl + idle_ttl / 2).await;
+    enter_demo().await;
+    assert_eq!(hub.sandbox_count().await, 1);
+    let res = me(&active).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()[header::LOCATION], DEMO_ENTRY_PATH);
+
+    common::teardown(Some(ctx)).await;
+}
+
+async fn request_with_bearer(
+    app: Router,
+    host: &str,
+    method: &str,
+    path: &str,
+    token: &str,
+) -> StatusCode {
+    let req = Request::builder()
+        .method(method)
+        .uri(path)
+        .header("host", host)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(req).await.expect("request failed").status()
+}
+
+#[tokio::test]
+async fn api_tokens_only_read_tiempo_and_stop_working_when_revoked() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let admin = list_users(&state).await.unwrap().remove(0);
+    let host = format!("{}.miapp.local", admin.company_slug);
+    let session = create_session(&state, &admin.username).await.unwrap();
+
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/account/tokens",
+        &session,
+        "name=Tablero".into(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "token creation failed: {body}");
+    let token = body
+        .split("data-new-token")
+        .nth(1)
+        .and_then(|rest| rest.split('>').nth(1))
+        .and_then(|rest| rest.split('<').next())
+        .expect("new token is shown once")
+        .trim()
+        .to_string();
+    assert!(token.starts_with("alf_"));
+
+    let app = build_app(shared.clone());
+    let status = request_with_bearer(app, &host, "GET", "/api/tiempo", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Tokens are read-only and limited to the data endpoints.
+    let app = build_app(shared.clone());
+    let status = request_with_bearer(app, &host, "GET", "/api/account", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let app = build_app(shared.clone());
+    let status = request_with_bearer(app, &host, "POST", "/logout", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let app = build_app(shared.clone());
+    let status = request_with_bearer(app, &host, "GET", "/api/tiempo", "alf_unknown").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(app, &host, "/account", &session).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains(&token), "the token is never shown again");
+    let token_id = body
+        .split("/account/tokens/")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .expect("token listed on the account page")
+        .to_string();
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/account/tokens/{token_id}/revoke"),
+        &session,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/account?token_revoked=1"));
+
+    let app = build_app(shared.clone());
+    let status = request_with_bearer(app, &host, "GET", "/api/tiempo", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn harness_fixtures_drive_the_router_in_process() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let app = test_harness::build_router(Arc::new(state.clone()));
+
+    let company = CompanyFixture::new("fixture-co")
+        .name("Fixture Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("fixture-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("fixture-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    RecurringPlanFixture::new(&company, "Renta de fixture")
+        .amount(500.0)
+        .create(&state)
+        .await
+        .unwrap();
+
+    let request = |token: &str, path: &str| {
+        Request::builder()
+            .uri(path)
+            .header("host", tenant_host("fixture-co"))
+            .header("cookie", session_cookie(token))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = app
+        .clone()
+        .oneshot(request(&admin_token, "/admin/recurring_plans"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), 1024 * 1024).await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("Renta de fixture"));
+
+    let res = app
+        .oneshot(request(&staff_token, "/api/tiempo"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn account_access_limits_what_a_user_sees_and_moves() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("caja-co")
+        .name("Caja Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("caja-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (clerk_id, clerk_token) = UserFixture::new("caja-clerk@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("caja-co");
+
+    let petty_cash = create_account(
+        &state,
+        &company,
+        "Caja chica",
+        AccountType::Cash,
+        "",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Banco principal",
+        AccountType::Bank,
+        "",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let category = create_category(&state, &company, "Gastos", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let mut spend = Vec::new();
+    for (description, account) in [("Garrafones", petty_cash), ("Renta oficina", bank)] {
+        spend.push(
+            create_transaction(
+                &state,
+                &company,
+                DateTime::now(),
+                description,
+                TransactionType::Expense,
+                &category,
+                Some(account),
+                None,
+                100.0,
+                None,
+                None,
+                true,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+    }
+    let path = format!("/api/admin/users/{}/accounts", clerk_id.to_hex());
+
+    assert_requires_auth_post(&shared, &path).await;
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &admin_token,
+        serde_json::json!({ "account_ids": [bson::oid::ObjectId::new().to_hex()] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &admin_token,
+        serde_json::json!({ "account_ids": [petty_cash.to_hex()] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/users/{}", clerk_id.to_hex()),
+        &admin_token,
+    )
+    .await;
+    assert!(body.contains(&petty_cash.to_hex()));
+
+    // The clerk only sees the petty cash and what moves through it.
+    for path in ["/admin/transactions", "/api/admin/transactions/data"] {
+        let (status, body) =
+            get_with_cookie(build_app(shared.clone()), &host, path, &clerk_token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Garrafones"), "{path}");
+        assert!(!body.contains("Renta oficina"), "{path}");
+    }
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts",
+        &clerk_token,
+    )
+    .await;
+    assert!(body.contains("Caja chica"));
+    assert!(!body.contains("Banco principal"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/transactions/{}", spend[1].to_hex()),
+        &clerk_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/comments",
+        &clerk_token,
+        format!(
+            "entity=transaction&entity_id={}&body=Hola",
+            spend[1].to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let payload = |account: bson::oid::ObjectId| {
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Papeleria",
+            "transaction_type": "expense",
+            "category_id": category.to_hex(),
+            "account_from_id": account.to_hex(),
+            "amount": 80.0,
+            "is_confirmed": true
+        })
+    };
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &clerk_token,
+        payload(bank),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &clerk_token,
+        payload(petty_cash),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+
+    // Admins without a list keep every account.
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/data",
+        &admin_token,
+    )
+    .await;
+    assert!(body.contains("Renta oficina"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn account_access_guards_planned_entry_changes() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("compromisos-co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("compromisos-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (clerk_id, clerk_token) = UserFixture::new("compromisos-clerk@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("compromisos-co");
+
+    let mut accounts = Vec::new();
+    for name in ["Caja chica", "Banco principal"] {
+        accounts.push(
+            create_account(&state, &company, name, AccountType::Bank, "", true, None)
+                .await
+                .unwrap(),
+        );
+    }
+    let (petty_cash, bank) = (accounts[0], accounts[1]);
+    let category = create_category(&state, &company, "Gastos", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/users/{}/accounts", clerk_id.to_hex()),
+        &admin_token,
+        serde_json::json!({ "account_ids": [petty_cash.to_hex()] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    // Each entry is over-covered by a payment from the bank.
+    let mut entries = Vec::new();
+    let mut payments = Vec::new();
+    for (name, account) in [("Renta", bank), ("Garrafones", petty_cash)] {
+        let entry = create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Expense,
+            &category,
+            &account,
+            None,
+            100.0,
+            DateTime::now(),
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+        let payment = create_transaction(
+            &state,
+            &company,
+            DateTime::now(),
+            name,
+            TransactionType::Expense,
+            &category,
+            Some(bank),
+            None,
+            150.0,
+            Some(entry),
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        entries.push(entry);
+        payments.push(payment);
+    }
+
+    // The bank's entry is off limits to the clerk; so is unlinking the bank
+    // payment from the petty cash entry.
+    let denied = [
+        format!("/admin/planned_entries/{}/delete", entries[0].to_hex()),
+        format!("/admin/planned_entries/{}/adjust", entries[0].to_hex()),
+        format!(
+            "/admin/planned_entries/{}/transactions/{}/detach",
+            entries[0].to_hex(),
+            payments[0].to_hex()
+        ),
+        format!(
+            "/admin/planned_entries/{}/transactions/{}/detach",
+            entries[1].to_hex(),
+            payments[1].to_hex()
+        ),
+    ];
+    for path in &denied {
+        let status = post_form_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            path,
+            &clerk_token,
+            String::new(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+    }
+    let entry = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.id == Some(entries[0]))
+        .expect("entry kept");
+    assert!(entry.coverage_adjustment.is_none());
+
+    // The admin keeps every account.
+    for path in &denied[1..3] {
+        let status = post_form_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            path,
+            &admin_token,
+            String::new(),
+        )
+        .await;
+        assert!(status.is_redirection(), "{path}: {status}");
+    }
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn dashboard_layout_is_chosen_and_ordered_per_user() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("panel-co")
+        .name("Panel Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("panel-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("panel-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("panel-co");
+    let keys = |body: &str| -> Vec<String> {
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        json["widgets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|w| w["key"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["is_default"], true);
+    assert_eq!(json["available"].as_array().unwrap().len(), 5);
+    assert_eq!(keys(&body)[0], "burn_rate");
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+        serde_json::json!({ "widgets": ["overdue", "burn_rate"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(keys(&body), vec!["overdue", "burn_rate"]);
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+    )
+    .await;
+    assert_eq!(keys(&body), vec!["overdue", "burn_rate"]);
+    assert!(body.contains("\"is_default\":false"));
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+        serde_json::json!({ "widgets": ["clima"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Widget desconocido"));
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+        serde_json::json!({ "widgets": ["overdue", "overdue"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &admin_token,
+        serde_json::json!({ "widgets": null }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"is_default\":true"));
+    assert_eq!(keys(&body).len(), 5);
+
+    // Finance widgets stay out of reach for staff.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/me/dashboard",
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(keys(&body).is_empty());
+    let (status, _) = post_json_with_cookie(
+        build_app(shared),
+        &host,
+        "/api/me/dashboard",
+        &staff_token,
+        serde_json::json!({ "widgets": ["burn_rate"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
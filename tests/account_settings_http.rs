@@ -0,0 +1,497 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn account_json_profile_redacts_secret_and_updates_from_payload() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let mut state = ctx.state.clone();
+    let mailer = Arc::new(RecordingMailer::default());
+    state.mailer = mailer.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(
+        &state,
+        "Account JSON Co",
+        "account-json-co",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let user_id = create_user_with_permissions(
+        &state,
+        "account-json@example.com",
+        "OLDSECRET",
+        &[(company, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "account-json@example.com")
+        .await
+        .unwrap();
+    let host = "account-json-co.miapp.local";
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, "/api/account", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let profile: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(profile["username"], "account-json@example.com");
+    assert!(profile.get("secret").is_none());
+    assert!(profile.get("otpauth_url").is_none());
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/account",
+        &token,
+        serde_json::json!({
+            "username": "account-json-updated@example.com",
+            "secret": "NEWSECRET"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        response["pending_username"],
+        "account-json-updated@example.com"
+    );
+    let updated = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert_eq!(
+        updated.username, "account-json@example.com",
+        "username must not change before confirmation"
+    );
+    assert_eq!(updated.secret, "NEWSECRET");
+
+    // The link goes to the new address; only the token's hash is stored.
+    let link_token = mailer
+        .last_token_for("account-json-updated@example.com")
+        .expect("confirmation link");
+    let pending = pending_email_change(&state, &user_id)
+        .await
+        .unwrap()
+        .expect("pending email change");
+    assert_ne!(pending.token_hash, link_token);
+
+    // Confirming the token applies the change and keeps the session alive.
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/account/confirm_email?token={link_token}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let updated = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert_eq!(updated.username, "account-json-updated@example.com");
+    assert!(
+        pending_email_change(&state, &user_id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, "/api/account", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let profile: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(profile["username"], "account-json-updated@example.com");
+
+    common::teardown(Some(ctx)).await;
+}
+
+/// A blank `secret` on the account update keeps the existing one, so a user can
+/// save an email change without knowing or rotating their TOTP secret.
+#[tokio::test]
+async fn account_json_blank_secret_keeps_existing() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(
+        &state,
+        "Account Keep Co",
+        "account-keep-co",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let user_id = create_user_with_permissions(
+        &state,
+        "account-keep@example.com",
+        "KEEPME",
+        &[(company, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "account-keep@example.com")
+        .await
+        .unwrap();
+    let host = "account-keep-co.miapp.local";
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/account",
+        &token,
+        serde_json::json!({
+            "username": "account-keep-renamed@example.com",
+            "secret": ""
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let updated = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert_eq!(updated.username, "account-keep@example.com");
+    assert_eq!(
+        updated.secret, "KEEPME",
+        "blank secret must keep the old one"
+    );
+    let pending = pending_email_change(&state, &user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(pending.new_username, "account-keep-renamed@example.com");
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn company_admin_json_endpoints_enforce_admin_and_update_metadata() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Admin Company JSON A",
+        "admin-company-json-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Admin Company JSON B",
+        "admin-company-json-b",
+        "USD",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "company-admin-json@example.com",
+        "SECRET",
+        &[
+            (company_a, UserRole::Admin, vec![]),
+            (company_b, UserRole::Admin, vec![]),
+        ],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "company-staff-json@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    let admin_token = create_session(&state, "company-admin-json@example.com")
+        .await
+        .unwrap();
+    let staff_token = create_session(&state, "company-staff-json@example.com")
+        .await
+        .unwrap();
+    let host = "admin-company-json-a.miapp.local";
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/companies",
+        &admin_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let companies: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert!(
+        companies
+            .iter()
+            .any(|company| company["slug"] == "admin-company-json-a")
+    );
+    assert!(
+        companies
+            .iter()
+            .any(|company| company["slug"] == "admin-company-json-b")
+    );
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/companies",
+        &admin_token,
+        serde_json::json!({
+            "name": "Admin Company JSON C",
+            "slug": "admin-company-json-c",
+            "default_currency": "MXN",
+            "is_active": true,
+            "notes": "Created through JSON"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(created["id"].as_str().is_some());
+
+    // Staff must be forbidden on the company JSON admin endpoints. Assert this
+    // BEFORE renaming company_a's slug below, otherwise the host subdomain no
+    // longer resolves to the staff user's company and the request would 401
+    // instead of 403.
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/companies",
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/api/admin/companies/{}/update", company_b.to_hex()),
+        &staff_token,
+        serde_json::json!({
+            "name": "Forbidden",
+            "slug": "forbidden",
+            "default_currency": "MXN"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let path = format!("/api/admin/companies/{}/update", company_a.to_hex());
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &path,
+        &admin_token,
+        serde_json::json!({
+            "name": "Admin Company JSON A Updated",
+            "slug": "admin-company-json-a-updated",
+            "default_currency": "USD",
+            "is_active": false,
+            "notes": "Updated through JSON"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let updated = list_companies(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|company| company.id.as_ref() == Some(&company_a))
+        .unwrap();
+    assert_eq!(updated.name, "Admin Company JSON A Updated");
+    assert_eq!(updated.slug, "admin-company-json-a-updated");
+    assert_eq!(updated.default_currency, "USD");
+    assert!(!updated.is_active);
+
+    common::teardown(Some(ctx)).await;
+}
+
+/// Regression: updating a user must persist a new TOTP secret (not silently
+/// keep the old one).
+#[tokio::test]
+async fn user_update_persists_new_secret() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let company = create_company(&state, "Secret Co", "secret-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let id = create_user_with_permissions(
+        &state,
+        "secret-update@example.com",
+        "OLDSECRETAAAAAAAAAAAA",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+
+    update_user_with_permissions(
+        &state,
+        &id,
+        "secret-update@example.com",
+        "NEWSECRETBBBBBBBBBBBB",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+
+    let user = get_user_by_id(&state, &id).await.unwrap().unwrap();
+    assert_eq!(user.secret, "NEWSECRETBBBBBBBBBBBB");
+
+    common::teardown(Some(ctx)).await;
+}
+
+/// Usernames (the login identifier, stored in `email`) must be unique: a second
+/// create with the same username is rejected.
+#[tokio::test]
+async fn duplicate_username_is_rejected() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let company = create_company(&state, "Uniq Co", "uniq-co", "MXN", true, None)
+        .await
+        .unwrap();
+    create_user_with_permissions(
+        &state,
+        "dup@example.com",
+        "SECRETAAAAAAAAAAAAAA",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+
+    let again = create_user_with_permissions(
+        &state,
+        "dup@example.com",
+        "OTHERSECRETBBBBBBBBBB",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await;
+    assert!(again.is_err(), "a duplicate username must be rejected");
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn format_preferences_change_how_amounts_and_dates_render() {
+    let Some((ctx, setup)) = admin_company("format", "Format Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        admin_id,
+        token,
+        host,
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2024-03-05T00:00:00Z").unwrap(),
+        "Sale",
+        TransactionType::Income,
+        &sales,
+        None,
+        Some(account),
+        1400.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let statement = format!("/admin/accounts/{}/statement", account.to_hex());
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, &statement, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("1400.00 MXN"));
+    assert!(body.contains("2024-03-05"));
+
+    // Same separator for thousands and decimals would be ambiguous.
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/account/format",
+        &token,
+        "decimal_separator=.&thousands_separator=.&date_format=iso".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/account?format_invalid=1"));
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/account/format",
+        &token,
+        "decimal_separator=%2C&thousands_separator=.&date_format=day_month_year&utc_offset=-06%3A00"
+            .to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/account?format_saved=1"));
+    let saved = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    assert_eq!(saved.format_preferences.decimal_separator, ",");
+    assert_eq!(saved.format_preferences.utc_offset, "-06:00");
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, &statement, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("$1.400,00 MXN"));
+    assert!(body.contains("05/03/2024"));
+
+    // Only the listed offsets are kept.
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared),
+        &host,
+        "/account/format",
+        &token,
+        "decimal_separator=.&thousands_separator=&date_format=iso&utc_offset=%2B13%3A00"
+            .to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/account?format_invalid=1"));
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,552 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn account_statement_uses_balance_snapshots() {
+    let Some((ctx, setup)) = admin_company("balance", "Balance Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = create_company(&state, "Balance Other", "balance-other", "MXN", true, None)
+        .await
+        .unwrap();
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let foreign_account =
+        create_account(&state, &other, "Bank", AccountType::Bank, "MXN", true, None)
+            .await
+            .unwrap();
+
+    let days_ago = |days: i64| {
+        DateTime::from_millis(DateTime::now().timestamp_millis() - days * 24 * 60 * 60 * 1000)
+    };
+    let mut draft = None;
+    for (description, tx_type, category, amount, days, confirmed) in [
+        ("Sale", TransactionType::Income, sales, 1000.0, 10, true),
+        ("Rent", TransactionType::Expense, rent, 200.0, 5, true),
+        ("Water", TransactionType::Expense, rent, 50.0, 2, false),
+    ] {
+        let (from, to) = match tx_type {
+            TransactionType::Income => (None, Some(account)),
+            _ => (Some(account), None),
+        };
+        let id = create_transaction(
+            &state,
+            &company,
+            days_ago(days),
+            description,
+            tx_type,
+            &category,
+            from,
+            to,
+            amount,
+            None,
+            None,
+            confirmed,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        if !confirmed {
+            draft = Some(id);
+        }
+    }
+
+    let now = DateTime::now();
+    // Both accounts were created today, so each only gets a snapshot for today.
+    assert_eq!(
+        alfredodev::state::snapshot_account_balances(&state, now)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        alfredodev::state::snapshot_account_balances(&state, now)
+            .await
+            .unwrap(),
+        0
+    );
+    let today = alfredodev::state::utc_day_start(now);
+    let snapshots = alfredodev::state::list_balance_snapshots(&state, &account, today)
+        .await
+        .unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].balance, 800.0);
+
+    // Confirming a backdated draft makes today's snapshot stale.
+    alfredodev::state::confirm_transactions(&state, &company, &[draft.unwrap()])
+        .await
+        .unwrap();
+    assert!(
+        alfredodev::state::list_balance_snapshots(&state, &account, today)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        alfredodev::state::account_balance_at(&state, &account, DateTime::now())
+            .await
+            .unwrap(),
+        750.0
+    );
+    alfredodev::state::snapshot_account_balances(&state, now)
+        .await
+        .unwrap();
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/accounts/{}/statement", account.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("750.00 MXN"));
+    assert!(body.contains("data-balance-chart"));
+    assert_eq!(body.matches("data-statement-row").count(), 3);
+
+    let (status, _) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!("/admin/accounts/{}/statement", foreign_account.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn opening_balance_counts_from_its_date_and_logs_adjustments() {
+    let Some((ctx, setup)) = admin_company("opening", "Opening Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (description, date, amount) in [
+        ("Cobro diciembre", "2025-12-20T00:00:00Z", 300.0),
+        ("Cobro enero", "2026-01-15T00:00:00Z", 200.0),
+    ] {
+        create_transaction(
+            &state,
+            &company,
+            DateTime::parse_rfc3339_str(date).unwrap(),
+            description,
+            TransactionType::Income,
+            &sales,
+            None,
+            Some(account),
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    let path = format!("/admin/accounts/{}/opening_balance", account.to_hex());
+    let statement = format!("/admin/accounts/{}/statement", account.to_hex());
+
+    let (status, location, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "opening_balance=mucho&opening_date=2026-01-01".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(location.is_none());
+    assert!(body.contains("Saldo inicial debe ser numérico"));
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "opening_balance=1000&opening_date=2026-01-01&reason=Estado+de+cuenta".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some(statement.as_str()));
+
+    // December's payment is already part of the opening balance.
+    let balance_at = |date: &str| {
+        let state = state.clone();
+        let date = DateTime::parse_rfc3339_str(date).unwrap();
+        async move {
+            alfredodev::state::account_balance_at(&state, &account, date)
+                .await
+                .unwrap()
+        }
+    };
+    assert_eq!(balance_at("2025-12-31T00:00:00Z").await, 0.0);
+    assert_eq!(balance_at("2026-01-10T00:00:00Z").await, 1000.0);
+    assert_eq!(balance_at("2026-02-01T00:00:00Z").await, 1200.0);
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, &statement, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-opening-balance"));
+    assert!(body.contains("data-statement-opening"));
+    assert_eq!(body.matches("data-statement-row").count(), 1);
+
+    let (status, body) = get_with_cookie(build_app(shared), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-opening-balance-change").count(), 1);
+    assert!(body.contains("Estado de cuenta"));
+    assert!(body.contains("opening-admin@example.com"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn revaluation_records_unrealized_gains_for_the_net_worth() {
+    use chrono::{Duration, Utc};
+
+    let Some((ctx, setup)) = admin_company("revaluation", "Revaluation Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let deposits = create_category(&state, &company, "Deposits", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let broker = create_account(
+        &state,
+        &company,
+        "Broker",
+        AccountType::Investment,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let ten_days_ago = DateTime::from_chrono(Utc::now() - Duration::days(10));
+    for (account, amount) in [(broker, 10_000.0), (bank, 5_000.0)] {
+        create_transaction(
+            &state,
+            &company,
+            ten_days_ago,
+            "Deposit",
+            TransactionType::Income,
+            &deposits,
+            None,
+            Some(account),
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    // The batch form skips blank values.
+    let five_days_ago = (Utc::now() - Duration::days(5))
+        .format("%Y-%m-%d")
+        .to_string();
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/admin/accounts/revaluation",
+        &token,
+        format!(
+            "as_of={five_days_ago}&notes=Cierre&value_{}=11000&value_{}=",
+            broker.to_hex(),
+            bank.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        location.as_deref(),
+        Some("/admin/accounts/revaluation?recorded=1")
+    );
+
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts/revaluations",
+        &token,
+        serde_json::json!({
+            "as_of": today,
+            "valuations": [{ "account_id": broker.to_hex(), "market_value": 12_500.0 }],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // Only investment accounts, and never before their latest valuation.
+    for (account, as_of) in [(bank, &today), (broker, &five_days_ago)] {
+        let (status, _) = post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/accounts/revaluations",
+            &token,
+            serde_json::json!({
+                "as_of": as_of,
+                "valuations": [{ "account_id": account.to_hex(), "market_value": 1.0 }],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/accounts/{}/valuations", broker.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let valuations: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(valuations.as_array().unwrap().len(), 2);
+    assert_eq!(valuations[0]["book_balance"], 10_000.0);
+    assert_eq!(valuations[0]["unrealized_gain"], 2_500.0);
+    assert_eq!(valuations[0]["adjustment"], 1_500.0);
+    assert_eq!(valuations[1]["notes"], "Cierre");
+
+    // Valuations are non-cash: the balance keeps coming from transactions.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/net-worth",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["currency"], "MXN");
+    assert_eq!(report["book_balance"], 15_000.0);
+    assert_eq!(report["unrealized_gain"], 2_500.0);
+    assert_eq!(report["net_worth"], 17_500.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!("/admin/accounts/{}/statement", broker.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-valuation-row").count(), 2);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn accounts_are_grouped_and_reordered_with_subtotals() {
+    let Some((ctx, setup)) = admin_company("groups", "Groups Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let mut accounts = Vec::new();
+    for (name, currency) in [("BBVA", "MXN"), ("Santander", "MXN"), ("Chase", "USD")] {
+        accounts.push(
+            create_account(
+                &state,
+                &company,
+                name,
+                AccountType::Bank,
+                currency,
+                true,
+                None,
+            )
+            .await
+            .unwrap(),
+        );
+    }
+    let mut groups = Vec::new();
+    for name in ["Operación", "Reservas"] {
+        let (status, body) = post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/account_groups",
+            &token,
+            serde_json::json!({ "name": name }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+        groups.push(created["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/account_groups",
+        &token,
+        serde_json::json!({ "name": "Reservas" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Reservas first, holding Chase and Santander; BBVA left without a group.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts/reorder",
+        &token,
+        serde_json::json!({ "groups": [
+            { "group_id": groups[1], "account_ids": [accounts[2].to_hex(), accounts[1].to_hex()] },
+            { "group_id": groups[0], "account_ids": [] },
+            { "group_id": null, "account_ids": [accounts[0].to_hex()] },
+        ] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/account_groups",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let listed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(listed[0]["name"], "Reservas");
+    assert_eq!(listed[1]["name"], "Operación");
+
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts",
+        &token,
+    )
+    .await;
+    let rows: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    let group_of =
+        |name: &str| rows.iter().find(|row| row["name"] == name).unwrap()["group_id"].clone();
+    assert_eq!(group_of("Chase"), groups[1].as_str());
+    assert_eq!(group_of("BBVA"), serde_json::Value::Null);
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/accounts", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.find("Chase").unwrap() < body.find("Santander").unwrap());
+    assert!(body.find("Santander").unwrap() < body.find("BBVA").unwrap());
+    assert!(body.contains("data-group-subtotal"));
+
+    // An account of another company is rejected.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts/reorder",
+        &token,
+        serde_json::json!({ "groups": [
+            { "group_id": groups[0], "account_ids": [mongodb::bson::oid::ObjectId::new().to_hex()] },
+        ] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/account_groups/{}/delete", groups[1]),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let chase = state
+        .accounts
+        .find_one(doc! { "_id": accounts[2] })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(chase.group_id, None);
+
+    common::teardown(Some(ctx)).await;
+}
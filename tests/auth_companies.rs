@@ -3,10 +3,13 @@ mod common;
 
 use alfredodev::models::{AccountType, UserPermission, UserRole};
 use alfredodev::state::{
-    add_user_to_company, create_account, create_company, create_session, create_user,
-    create_user_with_permissions, delete_account, delete_company, delete_session, delete_user,
-    find_user_by_session, get_company_by_id, get_user_by_id, list_companies, list_users,
-    update_company, update_user, update_user_with_permissions,
+    RetentionPolicy, SsoLoginOutcome, add_user_to_company, apply_retention, confirm_email_change, create_account, create_company, create_session,
+    create_user, create_user_with_permissions, delete_account, delete_company, delete_session,
+    delete_user, find_user_by_session, get_company_by_id, get_user_by_id, list_companies,
+    list_users, pending_email_change, request_email_change, set_user_active, update_company,
+    update_user, update_user_with_permissions, find_user, link_sso_identity, list_sso_identities,
+    set_company_sso_domains, sso_login_user, unlink_sso_identity, ARCHIVED_AT_FIELD,
+    export_company_bundle, offboard_company, purge_archived_companies,
 };
 
 #[tokio::test]
@@ -202,6 +205,145 @@ async fn sessions_resolve_user() {
     common::teardown(Some(ctx)).await;
 }
 
+#[tokio::test]
+async fn email_change_requires_confirmation_and_keeps_sessions() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let primary = list_companies(&state).await.unwrap()[0].id.unwrap();
+    let user_id = create_user(
+        &state,
+        "rename-me@example.com",
+        "secret123",
+        &[(primary, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let other_id = create_user(
+        &state,
+        "rename-other@example.com",
+        "secret123",
+        &[(primary, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "rename-me@example.com")
+        .await
+        .unwrap();
+
+    // A username held by someone else cannot be requested.
+    assert!(
+        request_email_change(&state, &user_id, "rename-other@example.com")
+            .await
+            .is_err()
+    );
+
+    let change_token = request_email_change(&state, &user_id, "renamed@example.com")
+        .await
+        .unwrap();
+    let pending = pending_email_change(&state, &user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(pending.new_username, "renamed@example.com");
+    let user = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert_eq!(user.username, "rename-me@example.com");
+
+    // Tokens are bound to the user that requested the change.
+    assert!(
+        confirm_email_change(&state, &other_id, &change_token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let confirmed = confirm_email_change(&state, &user_id, &change_token)
+        .await
+        .unwrap();
+    assert_eq!(confirmed.as_deref(), Some("renamed@example.com"));
+    assert!(
+        pending_email_change(&state, &user_id)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let fetched = find_user_by_session(&state, &token).await.unwrap().unwrap();
+    assert_eq!(fetched.username, "renamed@example.com");
+
+    // A token can only be used once.
+    assert!(
+        confirm_email_change(&state, &user_id, &change_token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn deactivating_user_revokes_sessions() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let primary = list_companies(&state).await.unwrap()[0].id.unwrap();
+    let user_id = create_user(
+        &state,
+        "inactive@example.com",
+        "secret123",
+        &[(primary, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "inactive@example.com")
+        .await
+        .unwrap();
+    assert!(
+        find_user_by_session(&state, &token)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    set_user_active(&state, &user_id, false).await.unwrap();
+    let user = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert!(!user.is_active);
+    assert!(
+        find_user_by_session(&state, &token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // A session created after deactivation still does not resolve.
+    let token = create_session(&state, "inactive@example.com")
+        .await
+        .unwrap();
+    assert!(
+        find_user_by_session(&state, &token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    set_user_active(&state, &user_id, true).await.unwrap();
+    assert!(
+        find_user_by_session(&state, &token)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
 #[tokio::test]
 async fn companies_crud_and_deletion_rules_work() {
     let ctx = match common::setup_state().await {
@@ -271,3 +413,266 @@ async fn companies_crud_and_deletion_rules_work() {
 
     common::teardown(Some(ctx)).await;
 }
+
+#[tokio::test]
+async fn retention_sweep_deletes_long_expired_sessions_only() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let primary = list_companies(&state).await.unwrap()[0].id.unwrap();
+    create_user(
+        &state,
+        "stale@example.com",
+        "secret",
+        &[(primary, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    create_user(
+        &state,
+        "fresh@example.com",
+        "secret",
+        &[(primary, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let stale = create_session(&state, "stale@example.com").await.unwrap();
+    let fresh = create_session(&state, "fresh@example.com").await.unwrap();
+    let long_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60 * 24 * 45);
+    state
+        .sessions
+        .update_one(
+            bson::doc! { "token": &stale },
+            bson::doc! { "$set": { "expires_at": bson::DateTime::from_system_time(long_ago) } },
+        )
+        .await
+        .unwrap();
+
+    let report = apply_retention(
+        &state,
+        &RetentionPolicy::default(),
+        std::time::SystemTime::now(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.sessions_deleted, 1);
+    assert!(
+        state
+            .sessions
+            .find_one(bson::doc! { "token": &stale })
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        find_user_by_session(&state, &fresh)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+#[tokio::test]
+async fn sso_login_provisions_by_domain_and_requires_linking_existing_users() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let issuer = "https://idp.example.com";
+
+    let acme = create_company(&state, "Acme SSO", "acme-sso", "", true, None)
+        .await
+        .unwrap();
+    set_company_sso_domains(&state, &acme, &["acme.com".to_string()])
+        .await
+        .unwrap();
+    let other = create_company(&state, "Other SSO", "other-sso", "", true, None)
+        .await
+        .unwrap();
+    assert!(
+        set_company_sso_domains(&state, &other, &["acme.com".to_string()])
+            .await
+            .is_err()
+    );
+
+    // Unknown domain and unverified email never create users.
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-x", Some("x@unknown.org"))
+            .await
+            .unwrap(),
+        SsoLoginOutcome::NoCompanyForDomain
+    );
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-x", None).await.unwrap(),
+        SsoLoginOutcome::MissingEmail
+    );
+
+    // First login provisions a staff user in the company owning the domain.
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-ana", Some("Ana@Acme.com"))
+            .await
+            .unwrap(),
+        SsoLoginOutcome::Provisioned("ana@acme.com".to_string())
+    );
+    let ana = find_user(&state, "ana@acme.com").await.unwrap().unwrap();
+    assert_eq!(ana.company_ids, vec![acme]);
+    assert_eq!(ana.role, UserRole::Staff);
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-ana", Some("ana@acme.com"))
+            .await
+            .unwrap(),
+        SsoLoginOutcome::Existing("ana@acme.com".to_string())
+    );
+
+    // An existing TOTP user is not taken over by a matching email; the
+    // identity has to be linked first.
+    let bob = create_user(&state, "bob@acme.com", "secret", &[(acme, UserRole::Admin)])
+        .await
+        .unwrap();
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-bob", Some("bob@acme.com"))
+            .await
+            .unwrap(),
+        SsoLoginOutcome::AccountExists
+    );
+    link_sso_identity(&state, &bob, issuer, "sub-bob", Some("bob@acme.com"))
+        .await
+        .unwrap();
+    assert!(
+        link_sso_identity(&state, &ana.id, issuer, "sub-bob", None)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-bob", Some("bob@acme.com"))
+            .await
+            .unwrap(),
+        SsoLoginOutcome::Existing("bob@acme.com".to_string())
+    );
+
+    set_user_active(&state, &bob, false).await.unwrap();
+    assert_eq!(
+        sso_login_user(&state, issuer, "sub-bob", None)
+            .await
+            .unwrap(),
+        SsoLoginOutcome::Inactive
+    );
+
+    let identities = list_sso_identities(&state, &bob).await.unwrap();
+    assert_eq!(identities.len(), 1);
+    let identity_id = identities[0].id.unwrap();
+    assert!(
+        !unlink_sso_identity(&state, &ana.id, &identity_id)
+            .await
+            .unwrap()
+    );
+    assert!(
+        unlink_sso_identity(&state, &bob, &identity_id)
+            .await
+            .unwrap()
+    );
+    assert!(list_sso_identities(&state, &bob).await.unwrap().is_empty());
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn offboarding_archives_revokes_and_purges_after_grace() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+
+    let keep = list_companies(&state).await.unwrap()[0].id.clone().unwrap();
+    let gone = create_company(&state, "Baja SA", "baja-sa", "", true, None)
+        .await
+        .unwrap();
+    let acc = create_account(&state, &gone, "Caja", AccountType::Cash, "", true, None)
+        .await
+        .unwrap();
+    let shared = create_user(
+        &state,
+        "shared@example.com",
+        "secret",
+        &[(gone, UserRole::Admin), (keep, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let lonely = create_user(
+        &state,
+        "lonely@example.com",
+        "secret",
+        &[(gone, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let lonely_session = create_session(&state, "lonely@example.com").await.unwrap();
+
+    let bundle = export_company_bundle(&state, &gone).await.unwrap();
+    assert_eq!(bundle.get_array("accounts").unwrap().len(), 1);
+    assert_eq!(bundle.get_array("members").unwrap().len(), 2);
+
+    let now = std::time::SystemTime::now();
+    let grace = std::time::Duration::from_secs(60 * 60 * 24 * 30);
+    let report = offboard_company(&state, &gone, grace, now).await.unwrap();
+    assert_eq!(report.revoked_members, 2);
+    assert_eq!(report.deactivated_users, 1);
+    assert!(report.archived_documents >= 1);
+
+    // Children stay in place with a tombstone until the purge.
+    let account = state
+        .accounts
+        .clone_with_type::<bson::Document>()
+        .find_one(bson::doc! { "_id": acc })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(account.get_datetime(ARCHIVED_AT_FIELD).is_ok());
+    let company = get_company_by_id(&state, &gone).await.unwrap().unwrap();
+    assert!(!company.is_active);
+    assert!(company.archived_at.is_some());
+
+    // Members lose access: the shared user falls back to the other company,
+    // the one without any company left is deactivated and logged out.
+    let shared_user = get_user_by_id(&state, &shared).await.unwrap().unwrap();
+    assert_eq!(shared_user.company_id, keep);
+    assert_eq!(shared_user.company_ids, vec![keep]);
+    let lonely_user = state
+        .users
+        .find_one(bson::doc! { "_id": lonely })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!lonely_user.is_active);
+    assert!(
+        find_user_by_session(&state, &lonely_session)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // Nothing is purged before the grace period ends.
+    assert_eq!(purge_archived_companies(&state, now).await.unwrap(), 0);
+    let report = apply_retention(&state, &RetentionPolicy::default(), now + grace)
+        .await
+        .unwrap();
+    assert_eq!(report.companies_purged, 1);
+    assert!(get_company_by_id(&state, &gone).await.unwrap().is_none());
+    assert!(
+        state
+            .accounts
+            .find_one(bson::doc! { "_id": acc })
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(get_company_by_id(&state, &keep).await.unwrap().is_some());
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,550 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use std::collections::HashMap;
+
+use bson::oid::ObjectId;
+use common::harness::*;
+
+/// Admin routes any signed-in user may use: a staff member can list the
+/// companies they belong to and create a new one of their own.
+const OPEN_TO_SIGNED_IN: &[(&str, &str)] = &[
+    ("GET", "/admin/companies"),
+    ("POST", "/admin/companies"),
+    ("GET", "/admin/companies/new"),
+];
+
+/// Posts a read-only auditor may still make: signing out, keeping the session
+/// alive and its own account.
+fn auditor_may_post(path: &str) -> bool {
+    matches!(path, "/logout" | "/api/session/renew" | "/api/account")
+        || path == "/account"
+        || path.starts_with("/account/")
+}
+
+fn is_admin_path(path: &str) -> bool {
+    path.starts_with("/admin") || path.starts_with("/api/admin")
+}
+
+/// Fills the path parameters with records of the fixture company: an `{id}`
+/// after `accounts` gets the account, and so on. Parameters with no fixture
+/// get a fresh id, which no handler should answer with data either.
+fn concrete_path(template: &str, ids: &HashMap<&str, ObjectId>) -> String {
+    let mut previous = String::new();
+    let mut segments = Vec::new();
+    for segment in template.split('/') {
+        let value = match segment {
+            "{entity}" => "accounts".to_string(),
+            "{transaction_id}" => ids["transactions"].to_hex(),
+            "{project_id}" => ids["projects"].to_hex(),
+            "{uuid}" => "00000000-0000-0000-0000-000000000000".to_string(),
+            "{id}" => ids
+                .get(previous.replace('-', "_").as_str())
+                .copied()
+                .unwrap_or_else(ObjectId::new)
+                .to_hex(),
+            segment if segment.starts_with('{') => ObjectId::new().to_hex(),
+            segment => segment.to_string(),
+        };
+        previous = value.clone();
+        segments.push(value);
+    }
+    segments.join("/")
+}
+
+fn request(method: &str, path: &str, host: &str, token: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(path)
+        .header("host", host);
+    if let Some(token) = token {
+        builder = builder.header("cookie", session_cookie(token));
+    }
+    if method == "POST" {
+        let (content_type, body) = if path.starts_with("/api/") {
+            ("application/json", "{}")
+        } else {
+            ("application/x-www-form-urlencoded", "")
+        };
+        return builder
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+/// Methods a route answers, read from the `Allow` header of the 405 an
+/// unused method gets once past the session middleware.
+async fn route_methods(app: &Router, path: &str, host: &str, token: &str) -> Vec<String> {
+    let res = app
+        .clone()
+        .oneshot(request("PATCH", path, host, Some(token)))
+        .await
+        .unwrap();
+    assert_eq!(
+        res.status(),
+        StatusCode::METHOD_NOT_ALLOWED,
+        "PATCH {path} should not be routed"
+    );
+    res.headers()
+        .get(header::ALLOW)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .split(',')
+        .map(|method| method.trim().to_string())
+        .filter(|method| method == "GET" || method == "POST")
+        .collect()
+}
+
+// Requests carry an empty body, so a form handler may reject one before it
+// gets to its checks. The sweep is a floor: it catches handlers that serve
+// another company's records or admin pages without asking who is calling.
+#[tokio::test]
+async fn every_protected_route_turns_away_anonymous_staff_and_foreign_admins() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let app = build_app(Arc::new(state.clone()));
+
+    let company = CompanyFixture::new("sweep-co")
+        .name("Sweep Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let other = CompanyFixture::new("sweep-other")
+        .name("Sweep Other")
+        .create(&state)
+        .await
+        .unwrap();
+    let (admin_id, admin_token) = UserFixture::new("sweep-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("sweep-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, auditor_token) = UserFixture::new("sweep-auditor@example.com")
+        .auditor_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, foreign_token) = UserFixture::new("sweep-foreign@example.com")
+        .admin_of(&other)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("sweep-co");
+    let foreign_host = tenant_host("sweep-other");
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let contact = create_contact(
+        &state,
+        &company,
+        "Cliente",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let plan = RecurringPlanFixture::new(&company, "Renta")
+        .create(&state)
+        .await
+        .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Cobro",
+        FlowType::Income,
+        &category,
+        &account,
+        None,
+        1000.0,
+        DateTime::now(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Cobro",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(account),
+        1000.0,
+        Some(entry),
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let forecast = create_forecast(
+        &state,
+        &company,
+        DateTime::now(),
+        None,
+        DateTime::now(),
+        DateTime::now(),
+        "MXN",
+        1000.0,
+        0.0,
+        1000.0,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let project = create_project(
+        &state,
+        &company,
+        "Proyecto",
+        None,
+        None,
+        None,
+        ProjectPriority::Medium,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let resource = create_resource(
+        &state,
+        &company,
+        "Montacargas",
+        ResourceType::Equipment,
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let ids: HashMap<&str, ObjectId> = HashMap::from([
+        ("accounts", account),
+        ("categories", category),
+        ("contacts", contact),
+        ("recurring_plans", plan),
+        ("planned_entries", entry),
+        ("transactions", transaction),
+        ("forecasts", forecast),
+        ("projects", project),
+        ("resources", resource),
+        ("companies", company),
+        ("users", admin_id),
+    ]);
+
+    let mut checked = 0;
+    for template in protected_paths() {
+        let path = concrete_path(template, &ids);
+        let methods = route_methods(&app, &path, &host, &admin_token).await;
+        assert!(
+            !methods.is_empty(),
+            "{template} answers neither GET nor POST"
+        );
+
+        for method in &methods {
+            let res = app
+                .clone()
+                .oneshot(request(method, &path, &host, None))
+                .await
+                .unwrap();
+            assert_eq!(
+                res.status(),
+                StatusCode::UNAUTHORIZED,
+                "anonymous {method} {template} must require a session"
+            );
+
+            // Auditors are turned away before any handler runs.
+            if method == "POST" && !auditor_may_post(template) {
+                let res = app
+                    .clone()
+                    .oneshot(request(method, &path, &host, Some(&auditor_token)))
+                    .await
+                    .unwrap();
+                if path.starts_with("/api/") {
+                    assert_eq!(
+                        res.status(),
+                        StatusCode::FORBIDDEN,
+                        "auditor POST {template}"
+                    );
+                } else {
+                    assert_eq!(
+                        res.headers().get(header::LOCATION).map(|v| v.as_bytes()),
+                        Some("/".as_bytes()),
+                        "auditor POST {template} answered {}",
+                        res.status()
+                    );
+                }
+            }
+
+            if !is_admin_path(template) {
+                continue;
+            }
+            if !OPEN_TO_SIGNED_IN.contains(&(method.as_str(), template)) {
+                let res = app
+                    .clone()
+                    .oneshot(request(method, &path, &host, Some(&staff_token)))
+                    .await
+                    .unwrap();
+                assert!(
+                    res.status().is_client_error(),
+                    "staff {method} {template} answered {}",
+                    res.status()
+                );
+            }
+            if template.contains('{') {
+                let res = app
+                    .clone()
+                    .oneshot(request(method, &path, &foreign_host, Some(&foreign_token)))
+                    .await
+                    .unwrap();
+                assert!(
+                    res.status().is_client_error(),
+                    "admin of another company {method} {template} answered {}",
+                    res.status()
+                );
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 100, "only {checked} admin routes were swept");
+
+    // Nothing above touched the fixture company's records.
+    assert!(
+        get_user_by_id(&state, &admin_id)
+            .await
+            .unwrap()
+            .is_some_and(|user| user.is_active)
+    );
+    assert!(
+        list_accounts(&state)
+            .await
+            .unwrap()
+            .iter()
+            .any(|a| a.id == Some(account))
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn pages_only_offer_what_the_role_allows() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("nav-co")
+        .name("Nav Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("nav-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("nav-staff@example.com")
+        .staff_of(&company, &[UserPermission::ViewTimeline])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("nav-co");
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/tiempo", &staff_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"href="/tiempo""#));
+    assert!(!body.contains(r#"href="/admin/users""#));
+    assert!(!body.contains(r#"href="/admin/projects""#));
+    assert!(!body.contains("bulkPayTimelineBtn\" class"));
+    assert!(body.contains("const canPay = false;"));
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/tiempo", &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"href="/admin/users""#));
+    assert!(body.contains(r#"href="/admin/projects""#));
+    assert!(body.contains("const canPay = true;"));
+
+    // The sign-in page has no session middleware but shows the same menu.
+    let (status, body) = get_with_cookie(build_app(shared.clone()), &host, "/", &staff_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"href="/tiempo""#));
+    assert!(!body.contains(r#"href="/admin/users""#));
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, "/", "expired").await;
+    assert!(!body.contains(r#"href="/tiempo""#));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn auditors_read_and_export_but_cannot_change_anything() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let own = CompanyFixture::new("audit-own")
+        .name("Audit Own")
+        .create(&state)
+        .await
+        .unwrap();
+    let audited = CompanyFixture::new("audit-co")
+        .name("Audit Co")
+        .create(&state)
+        .await
+        .unwrap();
+    // Admin of their own firm, auditor of the client.
+    let (_, token) = UserFixture::new("auditor@example.com")
+        .admin_of(&own)
+        .auditor_of(&audited)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("audit-co");
+    let own_host = tenant_host("audit-own");
+    create_account(
+        &state,
+        &audited,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/accounts", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Banco"));
+    assert!(body.contains("data-read-only"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/companies/{}/export", audited.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/accounts",
+        &token,
+        serde_json::json!({ "name": "Caja", "account_type": "cash", "currency": "MXN" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("solo lectura"));
+
+    // A form goes back to the page it came from.
+    let req = Request::builder()
+        .method("POST")
+        .uri("/admin/accounts")
+        .header("host", &host)
+        .header("cookie", session_cookie(&token))
+        .header("referer", format!("http://{host}/admin/accounts/new"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from("name=Caja&account_type=cash&currency=MXN"))
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert!(res.status().is_redirection());
+    assert_eq!(res.headers()[header::LOCATION], "/admin/accounts/new");
+    assert_eq!(
+        list_accounts(&state)
+            .await
+            .unwrap()
+            .iter()
+            .filter(|a| a.company_id == audited)
+            .count(),
+        1
+    );
+
+    // The role that counts is the one in the company the path names, not the
+    // active one.
+    let calendar = serde_json::json!({ "pattern": "4-4-5" });
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &own_host,
+        &format!("/api/admin/companies/{}/fiscal_calendar", audited.to_hex()),
+        &token,
+        calendar.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &own_host,
+        &format!("/api/admin/companies/{}/fiscal_calendar", own.to_hex()),
+        &token,
+        calendar,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // Secrets stay with admins: the users page lists the admin, but neither
+    // their TOTP secret nor a fresh one is handed out.
+    let (admin_id, _) = UserFixture::new("audit-co-admin@example.com")
+        .admin_of(&audited)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/users", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("audit-co-admin@example.com"));
+    for path in [
+        format!("/admin/users/{}/edit", admin_id.to_hex()),
+        format!("/admin/users/{}/qrcode", admin_id.to_hex()),
+        format!("/api/admin/users/{}", admin_id.to_hex()),
+        "/secret".to_string(),
+    ] {
+        let (status, _) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+    }
+
+    common::teardown(Some(ctx)).await;
+}
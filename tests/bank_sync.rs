@@ -0,0 +1,294 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use std::sync::Mutex;
+
+use alfredodev::{
+    bank_sync::{BankMovement, BankSyncProvider},
+    models::{BankConnection, BankProvider},
+    state::{BankSyncReport, list_bank_connections, sync_bank_connections},
+};
+use anyhow::bail;
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use common::harness::*;
+use futures::future::BoxFuture;
+
+/// Answers with a fixed list of movements and keeps the windows asked for.
+#[derive(Clone, Default)]
+struct FakeBank {
+    movements: Vec<BankMovement>,
+    windows: Arc<Mutex<Vec<(NaiveDate, NaiveDate)>>>,
+    fail: bool,
+}
+
+impl BankSyncProvider for FakeBank {
+    fn fetch_movements<'a>(
+        &'a self,
+        _connection: &'a BankConnection,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<BankMovement>>> {
+        self.windows.lock().unwrap().push((from, to));
+        Box::pin(async move {
+            if self.fail {
+                bail!("bank is down");
+            }
+            Ok(self.movements.clone())
+        })
+    }
+}
+
+fn movement(external_id: &str, day: u32, description: &str, amount: f64) -> BankMovement {
+    BankMovement {
+        external_id: external_id.to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+        description: description.to_string(),
+        amount,
+        reference: Some(format!("REF-{external_id}")),
+    }
+}
+
+#[tokio::test]
+async fn synced_movements_wait_for_review_before_reaching_the_ledger() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("bank-co")
+        .name("Bank Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("bank-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("bank-co");
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let fees = create_category(
+        &state,
+        &company,
+        "Comisiones",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/bank_sync/connections",
+        &token,
+        format!(
+            "account_id={}&provider=plaid&external_link_id=link-1&external_account_id=acc-1",
+            account.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/bank_sync/connections",
+        &token,
+        format!(
+            "account_id={}&provider=belvo&external_link_id=link-1&external_account_id=acc-1",
+            account.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let connections = list_bank_connections(&state, &company).await.unwrap();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].provider, BankProvider::Belvo);
+
+    let bank = FakeBank {
+        movements: vec![
+            movement("tx-1", 4, "SPEI RECIBIDO CLIENTE", 1500.0),
+            movement("tx-2", 5, "COMISION MANEJO", -50.0),
+        ],
+        ..FakeBank::default()
+    };
+    let now = Utc.with_ymd_and_hms(2025, 3, 10, 12, 0, 0).unwrap();
+    let report = sync_bank_connections(
+        &state,
+        |_| Some(Box::new(bank.clone()) as Box<dyn BankSyncProvider>),
+        DateTime::from_chrono(now),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        report,
+        BankSyncReport {
+            connections: 1,
+            staged: 2,
+            failures: 0
+        }
+    );
+    // Nothing reaches the ledger until it is reviewed.
+    assert!(list_transactions(&state).await.unwrap().is_empty());
+
+    // The next pull reads a few days back again without staging twice.
+    let later = now + Duration::hours(6);
+    let report = sync_bank_connections(
+        &state,
+        |_| Some(Box::new(bank.clone()) as Box<dyn BankSyncProvider>),
+        DateTime::from_chrono(later),
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.staged, 0);
+    let windows = bank.windows.lock().unwrap().clone();
+    assert_eq!(
+        windows,
+        vec![
+            (
+                NaiveDate::from_ymd_opt(2025, 2, 8).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 10).unwrap()
+            ),
+            (
+                NaiveDate::from_ymd_opt(2025, 3, 7).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 10).unwrap()
+            ),
+        ]
+    );
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/bank_sync", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("SPEI RECIBIDO CLIENTE"));
+    assert!(body.contains("COMISION MANEJO"));
+
+    let staged = |external_id: &'static str| {
+        let state = state.clone();
+        async move {
+            state
+                .bank_transactions
+                .find_one(doc! { "external_id": external_id })
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+    let deposit = staged("tx-1").await.id.unwrap();
+    let fee = staged("tx-2").await.id.unwrap();
+
+    // A withdrawal cannot go under an income category.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/bank_sync/transactions/{}/accept", fee.to_hex()),
+        &token,
+        format!("category_id={}", sales.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let accept = format!("/admin/bank_sync/transactions/{}/accept", deposit.to_hex());
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &accept,
+        &token,
+        format!("category_id={}", sales.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let transactions = list_transactions(&state).await.unwrap();
+    assert_eq!(transactions.len(), 1);
+    let recorded = &transactions[0];
+    assert_eq!(recorded.transaction_type, TransactionType::Income);
+    assert_eq!(recorded.amount, 1500.0);
+    assert_eq!(recorded.account_to_id, Some(account));
+    assert!(recorded.is_confirmed);
+    assert_eq!(
+        recorded.notes.as_deref(),
+        Some("Sincronizado de Belvo · ref. REF-tx-1")
+    );
+    assert_eq!(staged("tx-1").await.transaction_id, recorded.id);
+
+    // Accepting twice records it once.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &accept,
+        &token,
+        format!("category_id={}", sales.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/bank_sync/transactions/{}/reject", fee.to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    // Discarded movements cannot be accepted afterwards.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/bank_sync/transactions/{}/accept", fee.to_hex()),
+        &token,
+        format!("category_id={}", fees.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/bank_sync", &token).await;
+    assert!(!body.contains("COMISION MANEJO"));
+    assert!(body.contains("No hay movimientos sincronizados por revisar."));
+
+    // Reviewed movements are not staged again, and a failed pull is recorded
+    // on the connection without moving its sync mark.
+    let report = sync_bank_connections(
+        &state,
+        |_| Some(Box::new(bank.clone()) as Box<dyn BankSyncProvider>),
+        DateTime::from_chrono(later + Duration::hours(6)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.staged, 0);
+    let failing = FakeBank {
+        fail: true,
+        ..FakeBank::default()
+    };
+    let report = sync_bank_connections(
+        &state,
+        |_| Some(Box::new(failing.clone()) as Box<dyn BankSyncProvider>),
+        DateTime::from_chrono(later + Duration::hours(12)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.failures, 1);
+    let connection = &list_bank_connections(&state, &company).await.unwrap()[0];
+    assert_eq!(connection.last_error.as_deref(), Some("bank is down"));
+    assert_eq!(
+        connection.last_synced_at,
+        Some(DateTime::from_chrono(later + Duration::hours(6)))
+    );
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,289 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::{get_company_by_id, get_user_by_id};
+use common::harness::*;
+
+#[tokio::test]
+async fn company_branding_is_saved_and_shown_to_members() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Brand Co", "brand-co", "MXN", true, None)
+        .await
+        .unwrap();
+    create_user_with_permissions(
+        &state,
+        "brand-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "brand-staff@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    let admin_token = create_session(&state, "brand-admin@example.com")
+        .await
+        .unwrap();
+    let staff_token = create_session(&state, "brand-staff@example.com")
+        .await
+        .unwrap();
+    let host = "brand-co.miapp.local";
+    let branding_path = format!("/admin/companies/{}/branding", company.to_hex());
+    let logo: &[u8] = b"\x89PNG\r\n\x1a\nFAKEPNGDATA";
+
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("data-branding-logo"));
+
+    // A bad color is refused and nothing is stored.
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[
+            ("color", None, b"azul"),
+            ("footer_text", None, b"RFC BCO010101AAA"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("#rrggbb"));
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert!(stored.branding.is_empty());
+
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[
+            ("color", None, b"#0369A1"),
+            ("footer_text", None, b"RFC BCO010101AAA"),
+            ("logo", Some("logo.png"), logo),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert_eq!(stored.branding.color.as_deref(), Some("#0369a1"));
+    assert_eq!(
+        stored.branding.footer_text.as_deref(),
+        Some("RFC BCO010101AAA")
+    );
+    assert_eq!(
+        stored.branding.logo_content_type.as_deref(),
+        Some("image/png")
+    );
+
+    // Any member of the company gets the branding for the page header.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/me/companies",
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let companies: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(companies[0]["brand_color"], "#0369a1");
+    assert_eq!(companies[0]["footer_text"], "RFC BCO010101AAA");
+    assert_eq!(companies[0]["logo_url"], "/branding/logo");
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/branding/logo",
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.ends_with("PNGDATA"));
+
+    // Something that is not a PNG or JPEG is not accepted as a logo.
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[("logo", Some("logo.png"), b"<svg></svg>")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("PNG o JPG"));
+
+    // Removing the logo keeps nothing behind; blank fields clear the rest.
+    let logo_path = stored.branding.logo_path.clone().unwrap();
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[
+            ("remove_logo", None, b"true"),
+            ("color", None, b""),
+            ("footer_text", None, b""),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert!(stored.branding.is_empty());
+    assert!(!std::path::Path::new(&logo_path).exists());
+    let (status, _) =
+        get_with_cookie(build_app(shared), host, "/branding/logo", &staff_token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let _ = std::fs::remove_dir_all(format!("uploads/branding/{}", company.to_hex()));
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn profile_name_and_avatar_are_shown_to_company_members() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Face Co", "face-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other = create_company(&state, "Other Face Co", "other-face-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let user_id = create_user_with_permissions(
+        &state,
+        "face-user@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "face-peer@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "face-stranger@example.com",
+        "SECRET",
+        &[(other, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "face-user@example.com")
+        .await
+        .unwrap();
+    let peer_token = create_session(&state, "face-peer@example.com")
+        .await
+        .unwrap();
+    let stranger_token = create_session(&state, "face-stranger@example.com")
+        .await
+        .unwrap();
+    let host = "face-co.miapp.local";
+    let avatar_path = format!("/users/{}/avatar", user_id.to_hex());
+
+    // Only images are accepted as avatars.
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/account/profile",
+        &token,
+        &[
+            ("display_name", None, b"Ana Flores"),
+            ("avatar", Some("me.pdf"), b"%PDF-1.7"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("PNG, JPG o WEBP"));
+
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/account/profile",
+        &token,
+        &[
+            ("display_name", None, b"  Ana Flores "),
+            ("avatar", Some("me.png"), b"\x89PNG\r\n\x1a\nFAKEAVATAR"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    // The header reads name and avatar from /setup.
+    let (status, body) = get_with_cookie(build_app(shared.clone()), host, "/setup", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let setup: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(setup["display_name"], "Ana Flores");
+    assert_eq!(setup["avatar_url"], avatar_path.as_str());
+
+    // Members of the company see the avatar; others do not.
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, &avatar_path, &peer_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.ends_with("FAKEAVATAR"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        "other-face-co.miapp.local",
+        &avatar_path,
+        &stranger_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // A blank name and `remove_avatar` go back to the username alone.
+    let stored = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert!(stored.has_avatar);
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/account/profile",
+        &token,
+        &[
+            ("display_name", None, b""),
+            ("remove_avatar", None, b"true"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let stored = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    assert_eq!(stored.display_name, None);
+    assert!(!stored.has_avatar);
+    assert_eq!(stored.label(), "face-user@example.com");
+    let (status, _) = get_with_cookie(build_app(shared), host, &avatar_path, &peer_token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let _ = std::fs::remove_dir_all(format!("uploads/avatars/{}", user_id.to_hex()));
+    common::teardown(Some(ctx)).await;
+}
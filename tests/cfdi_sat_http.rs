@@ -506,6 +506,22 @@ async fn sat_config_upload_json_creates_config_and_enforces_admin() {
     assert!(!body.contains("private.key"));
     assert!(!body.contains("cert.cer"));
 
+    // PEM files are not what the SAT issues -> rejected by content
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/sat-configs/upload",
+        &admin_token,
+        &[
+            ("rfc", None, b"ddd010101ddd"),
+            ("key_password", None, b"x"),
+            ("cer_file", Some("c.cer"), b"-----BEGIN CERTIFICATE-----"),
+            ("key_file", Some("k.key"), key_bytes),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
     // missing key_file -> validation error
     let (status, _) = post_multipart_with_cookie(
         build_app(shared.clone()),
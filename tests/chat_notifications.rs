@@ -0,0 +1,463 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use std::sync::Mutex;
+
+use alfredodev::{
+    models::ChatChannel,
+    notifier::ChatNotifier,
+    state::{ChatNotificationReport, get_company_by_id, send_chat_notifications},
+};
+use chrono::{Duration, TimeZone, Utc};
+use common::harness::*;
+use futures::future::BoxFuture;
+
+/// Keeps the messages instead of sending them.
+#[derive(Clone, Default)]
+struct RecordingNotifier(Arc<Mutex<Vec<(String, String)>>>);
+
+impl ChatNotifier for RecordingNotifier {
+    fn send_message<'a>(
+        &'a self,
+        chat_id: &'a str,
+        text: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        self.0
+            .lock()
+            .unwrap()
+            .push((chat_id.to_string(), text.to_string()));
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn overdue_alerts_and_digest_reach_the_company_chat_once() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("chat-co")
+        .name("Chat Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("chat-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("chat-co");
+    let path = format!("/admin/companies/{}/notifications", company.to_hex());
+
+    // A Telegram chat id must be numeric or an @channel.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "channel=telegram&chat_id=acme&overdue_alerts=true".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "channel=telegram&chat_id=-100123&overdue_alerts=true&daily_digest=true".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let settings = get_company_by_id(&state, &company)
+        .await
+        .unwrap()
+        .unwrap()
+        .chat_notifications;
+    assert_eq!(settings.target(), Some((ChatChannel::Telegram, "-100123")));
+
+    let now = Utc.with_ymd_and_hms(2025, 3, 10, 14, 0, 0).unwrap();
+    let category = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (name, due) in [
+        ("Renta marzo", now - Duration::hours(3)),
+        ("Renta febrero", now - Duration::days(20)),
+    ] {
+        create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Expense,
+            &category,
+            &account,
+            None,
+            12000.0,
+            DateTime::from_chrono(due),
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    // The first run alerts what fell due in the last day and sends the
+    // digest; a second run the same hour sends nothing.
+    let notifier = RecordingNotifier::default();
+    let notifier_for = |channel: ChatChannel| {
+        (channel == ChatChannel::Telegram)
+            .then(|| Box::new(notifier.clone()) as Box<dyn ChatNotifier>)
+    };
+    let report = send_chat_notifications(&state, notifier_for, DateTime::from_chrono(now))
+        .await
+        .unwrap();
+    assert_eq!(
+        report,
+        ChatNotificationReport {
+            alerts_sent: 1,
+            digests_sent: 1,
+            budget_alerts_sent: 0,
+            failures: 0,
+        }
+    );
+    {
+        let sent = notifier.0.lock().unwrap();
+        assert!(sent.iter().all(|(chat_id, _)| chat_id == "-100123"));
+        assert!(sent[0].1.contains("Renta marzo"));
+        assert!(!sent[0].1.contains("Renta febrero"));
+        assert!(sent[1].1.contains("Vencido por pagar: $24,000.00 MXN (2)"));
+    }
+    let report = send_chat_notifications(&state, notifier_for, DateTime::from_chrono(now))
+        .await
+        .unwrap();
+    assert_eq!(report, ChatNotificationReport::default());
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn budget_thresholds_flag_the_category_and_reach_the_chat_once() {
+    use alfredodev::{
+        models::ChatNotifications,
+        state::{get_category_by_id, update_company_chat_notifications},
+    };
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("budget-co")
+        .name("Budget Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("budget-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("budget-co");
+    update_company_chat_notifications(
+        &state,
+        &company,
+        &ChatNotifications {
+            channel: Some(ChatChannel::Telegram),
+            chat_id: "-100777".to_string(),
+            budget_alerts: true,
+            ..ChatNotifications::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/categories",
+        &token,
+        format!(
+            "name=Publicidad&company_id={}&flow_type=expense&monthly_budget=-5",
+            company.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/categories",
+        &token,
+        format!(
+            "name=Publicidad&company_id={}&flow_type=expense&monthly_budget=1000",
+            company.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let category = list_categories(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|c| c.company_id == company && c.name == "Publicidad")
+        .unwrap();
+    assert_eq!(category.monthly_budget, Some(1000.0));
+    let category_id = category.id.unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let spend = |amount: f64| {
+        create_transaction(
+            &state,
+            &company,
+            DateTime::from_chrono(Utc::now()),
+            "Anuncios",
+            TransactionType::Expense,
+            &category_id,
+            Some(account),
+            None,
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    };
+
+    // Crossing 80% is recorded as soon as the transaction is saved, and the
+    // index flags the row.
+    spend(850.0).await.unwrap();
+    let alert = get_category_by_id(&state, &category_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .budget_alert
+        .unwrap();
+    assert_eq!((alert.threshold, alert.notified), (80, false));
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/categories",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-budget-percent=\"85\""));
+
+    let notifier = RecordingNotifier::default();
+    let notifier_for = |channel: ChatChannel| {
+        (channel == ChatChannel::Telegram)
+            .then(|| Box::new(notifier.clone()) as Box<dyn ChatNotifier>)
+    };
+    let now = || DateTime::from_chrono(Utc::now());
+    let report = send_chat_notifications(&state, notifier_for, now())
+        .await
+        .unwrap();
+    assert_eq!(report.budget_alerts_sent, 1);
+    let report = send_chat_notifications(&state, notifier_for, now())
+        .await
+        .unwrap();
+    assert_eq!(report, ChatNotificationReport::default());
+
+    // Going over the budget is announced again, once.
+    spend(200.0).await.unwrap();
+    let report = send_chat_notifications(&state, notifier_for, now())
+        .await
+        .unwrap();
+    assert_eq!(report.budget_alerts_sent, 1);
+    {
+        let sent = notifier.0.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(
+            sent[0]
+                .1
+                .contains("Publicidad — al 80%: $850.00 MXN de $1,000.00 MXN (85%)")
+        );
+        assert!(
+            sent[1]
+                .1
+                .contains("Publicidad — agotado: $1,050.00 MXN de $1,000.00 MXN (105%)")
+        );
+    }
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn notification_settings_pick_channels_weekly_digest_and_quiet_hours() {
+    use alfredodev::models::{DigestFrequency, QuietHours};
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let mut state = ctx.state.clone();
+    let mailer = Arc::new(RecordingMailer::default());
+    state.mailer = mailer.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("quiet-co")
+        .name("Quiet Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("quiet-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("quiet-co");
+    let post = |path: String, body: &'static str| {
+        let app = build_app(shared.clone());
+        let (host, token) = (host.clone(), token.clone());
+        async move { post_form_with_cookie(app, &host, &path, &token, body.into()).await }
+    };
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/notifications/settings",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-notification-settings"));
+
+    // The chat can only be picked once it is set.
+    let settings = "chat=true&email=true&email_recipients=Finanzas%40quiet.mx&overdue_alerts=true\
+                    &digest=weekly&digest_weekday=0&digest_hour=8&utc_offset=-06%3A00\
+                    &quiet_start=22&quiet_end=7";
+    assert_eq!(
+        post("/admin/notifications/settings".to_string(), settings).await,
+        StatusCode::BAD_REQUEST
+    );
+    let chat_path = format!("/admin/companies/{}/notifications", company.to_hex());
+    assert_eq!(
+        post(chat_path.clone(), "channel=telegram&chat_id=-100555").await,
+        StatusCode::SEE_OTHER
+    );
+    assert_eq!(
+        post("/admin/notifications/settings".to_string(), settings).await,
+        StatusCode::SEE_OTHER
+    );
+    let saved = get_company_by_id(&state, &company)
+        .await
+        .unwrap()
+        .unwrap()
+        .notifications
+        .unwrap();
+    assert_eq!(
+        saved.email_recipients,
+        vec!["finanzas@quiet.mx".to_string()]
+    );
+    assert_eq!(saved.digest, DigestFrequency::Weekly);
+    assert_eq!(saved.quiet_hours, Some(QuietHours { start: 22, end: 7 }));
+
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, &chat_path, &token).await;
+    assert!(body.contains("data-chat-managed"));
+
+    let category = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    // Monday 2025-03-10; 4:00 UTC is 22:00 of Sunday in -06:00.
+    let at = |hour: u32, day: u32| {
+        DateTime::from_chrono(Utc.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap())
+    };
+    create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Renta marzo",
+        FlowType::Expense,
+        &category,
+        &account,
+        None,
+        9000.0,
+        at(2, 10),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let notifier = RecordingNotifier::default();
+    let notifier_for = |channel: ChatChannel| {
+        (channel == ChatChannel::Telegram)
+            .then(|| Box::new(notifier.clone()) as Box<dyn ChatNotifier>)
+    };
+    // Nothing goes out in the quiet hours; it waits for them to end.
+    let report = send_chat_notifications(&state, notifier_for, at(4, 10))
+        .await
+        .unwrap();
+    assert_eq!(report, ChatNotificationReport::default());
+    // 7:00 local: the alert, but the digest is due from 8:00.
+    let report = send_chat_notifications(&state, notifier_for, at(13, 10))
+        .await
+        .unwrap();
+    assert_eq!((report.alerts_sent, report.digests_sent), (1, 0));
+    let report = send_chat_notifications(&state, notifier_for, at(14, 10))
+        .await
+        .unwrap();
+    assert_eq!((report.alerts_sent, report.digests_sent), (0, 1));
+    // Weekly: not on Tuesday.
+    let report = send_chat_notifications(&state, notifier_for, at(14, 11))
+        .await
+        .unwrap();
+    assert_eq!(report, ChatNotificationReport::default());
+    {
+        let sent = notifier.0.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].1.contains("Renta marzo"));
+        assert!(sent[1].1.starts_with("📊 Resumen de Quiet Co — 2025-03-10"));
+    }
+    // The recipients get the same messages by email.
+    let emails = mailer.sent();
+    assert_eq!(emails.len(), 2);
+    assert!(emails.iter().all(|email| email.to == "finanzas@quiet.mx"));
+    assert_eq!(emails[1].subject, "Resumen de Quiet Co — 2025-03-10");
+
+    common::teardown(Some(ctx)).await;
+}
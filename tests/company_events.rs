@@ -0,0 +1,146 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::CompanyEventKind;
+use chrono::Utc;
+use common::harness::*;
+
+#[tokio::test]
+async fn finance_changes_are_broadcast_to_the_company_stream() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("events-co")
+        .name("Events Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("events-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("events-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("events-co");
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Cobro marzo",
+        FlowType::Income,
+        &category,
+        &account,
+        None,
+        1000.0,
+        DateTime::from_chrono(Utc::now()),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let mut events = state.events.subscribe();
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::from_chrono(Utc::now()),
+        "Cobro marzo",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(account),
+        1000.0,
+        Some(entry),
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let covered = events.try_recv().unwrap();
+    assert_eq!(covered.company_id, company);
+    assert_eq!(covered.kind, CompanyEventKind::EntryCovered);
+    assert_eq!(covered.id, entry);
+    assert_eq!(covered.account_ids, vec![account]);
+    let created = events.try_recv().unwrap();
+    assert_eq!(created.kind, CompanyEventKind::TransactionCreated);
+    assert_eq!(created.id, transaction);
+    assert_eq!(created.account_ids, vec![account]);
+
+    let forecast = create_forecast(
+        &state,
+        &company,
+        DateTime::from_chrono(Utc::now()),
+        None,
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        DateTime::parse_rfc3339_str("2026-12-31T00:00:00Z").unwrap(),
+        "MXN",
+        1000.0,
+        500.0,
+        500.0,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let ready = events.try_recv().unwrap();
+    assert_eq!(ready.kind, CompanyEventKind::ForecastReady);
+    assert_eq!(ready.id, forecast);
+    assert!(ready.account_ids.is_empty());
+    assert!(events.try_recv().is_err());
+
+    // The stream itself: admins only, served as text/event-stream. The body
+    // never ends, so only the headers are read.
+    assert_requires_auth_get(&shared, "/events").await;
+    let (status, _) =
+        get_with_cookie(build_app(shared.clone()), &host, "/events", &staff_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let req = Request::builder()
+        .uri("/events")
+        .header("host", &host)
+        .header("cookie", session_cookie(&admin_token))
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()),
+        Some("text/event-stream")
+    );
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,114 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::get_company_by_id;
+use common::harness::*;
+
+/// Value of the hidden `confirm_token` field of a confirmation page.
+fn confirm_token(page: &str) -> String {
+    let start = page
+        .find(r#"name="confirm_token" value=""#)
+        .expect("confirmation form")
+        + r#"name="confirm_token" value=""#.len();
+    page[start..]
+        .split('"')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[tokio::test]
+async fn destructive_actions_go_through_the_confirmation_page() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("confirm-co")
+        .name("Confirm Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let doomed = CompanyFixture::new("doomed-co")
+        .name("Doomed Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("confirm-admin@example.com")
+        .admin_of(&company)
+        .admin_of(&doomed)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let staff = UserFixture::new("confirm-staff@example.com")
+        .staff_of(&company, &[])
+        .create(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("confirm-co");
+
+    // A bare POST, like the old delete button, only leads to the page.
+    let path = format!("/admin/users/{}/delete", staff.to_hex());
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some(path.as_str()));
+    assert!(get_user_by_id(&state, &staff).await.unwrap().is_some());
+
+    let (status, page) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("confirm-staff@example.com"));
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("confirm_token={}", confirm_token(&page)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(get_user_by_id(&state, &staff).await.unwrap().is_none());
+
+    // With the name required, the company goes only once it is typed.
+    unsafe {
+        std::env::set_var("COMPANY_DELETE_REQUIRE_NAME", "1");
+    }
+    let path = format!("/admin/companies/{}/delete", doomed.to_hex());
+    let (status, page) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains(r#"name="confirm_name""#));
+    let confirm = confirm_token(&page);
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("confirm_token={confirm}&confirm_name=Doomed"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(get_company_by_id(&state, &doomed).await.unwrap().is_some());
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("confirm_token={confirm}&confirm_name=Doomed+Co"),
+    )
+    .await;
+    unsafe {
+        std::env::remove_var("COMPANY_DELETE_REQUIRE_NAME");
+    }
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert!(get_company_by_id(&state, &doomed).await.unwrap().is_none());
+}
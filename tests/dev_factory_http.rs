@@ -0,0 +1,99 @@
+//! Only runs with `cargo test --features dev-factory`.
+#![cfg(feature = "dev-factory")]
+
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::FACTORY_NOTE;
+use common::harness::*;
+
+#[tokio::test]
+async fn dev_factory_fills_the_active_company_with_fake_records() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("factory-co")
+        .name("Factory Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("factory-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("factory-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("factory-co");
+    let count = |n: usize| serde_json::json!({ "count": n });
+
+    assert_requires_auth_post(&shared, "/dev/factory/transactions").await;
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/dev/factory/transactions",
+        &staff_token,
+        count(5),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/dev/factory/users",
+        &admin_token,
+        count(5),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/dev/factory/transactions",
+        &admin_token,
+        count(5000),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Transactions on an empty company bring their own account and
+    // categories along.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/dev/factory/transactions",
+        &admin_token,
+        count(25),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"created\":25"));
+    let transactions = list_transactions(&state).await.unwrap();
+    assert_eq!(transactions.len(), 25);
+    assert!(transactions.iter().all(|tx| {
+        tx.company_id == company
+            && tx.reference.is_some()
+            && tx.notes.as_deref() == Some(FACTORY_NOTE)
+    }));
+    assert_eq!(list_accounts(&state).await.unwrap().len(), 1);
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/dev/factory/contacts",
+        &admin_token,
+        count(10),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list_contacts(&state).await.unwrap().len(), 10);
+
+    common::teardown(Some(ctx)).await;
+}
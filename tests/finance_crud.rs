@@ -5,11 +5,11 @@ use alfredodev::state::{
     create_account, create_category, create_company, create_contact, create_forecast,
     create_or_update_planned_entry_from_cfdi, create_planned_entry, create_recurring_plan,
     create_transaction, delete_account, delete_category, delete_contact, delete_forecast,
-    delete_planned_entry, delete_recurring_plan, delete_transaction, get_account_by_id,
-    get_category_by_id, get_contact_by_id, get_forecast_by_id, get_planned_entry_by_cfdi_uuid,
-    get_planned_entry_by_id, get_transaction_by_id, list_accounts, list_categories, list_companies,
-    list_contacts, list_forecasts, list_planned_entries, list_recurring_plans, list_transactions,
-    pay_planned_entry,
+    delete_planned_entry, delete_recurring_plan, delete_transaction, extend_planned_entries,
+    get_account_by_id, get_category_by_id, get_contact_by_id, get_forecast_by_id,
+    get_planned_entry_by_cfdi_uuid, get_planned_entry_by_id, get_transaction_by_id, list_accounts,
+    list_categories, list_companies, list_contacts, list_forecasts, list_planned_entries,
+    list_recurring_plans, list_transactions, pay_planned_entry, recurring_plan_coverage,
 };
 
 #[path = "common/mod.rs"]
@@ -184,6 +184,92 @@ async fn recurring_plans_seed_and_creation_work() {
     common::teardown(Some(ctx)).await;
 }
 
+#[tokio::test]
+async fn recurring_plan_coverage_matches_open_entries() {
+    let ctx = match common::setup_state().await {
+        Some(s) => s,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let company_id = list_companies(&state).await.unwrap()[0].id.clone().unwrap();
+
+    let cat_id = create_category(&state, &company_id, "Cov", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let acc_id = create_account(
+        &state,
+        &company_id,
+        "Cov Account",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let plan_id = create_recurring_plan(
+        &state,
+        &company_id,
+        "Coverage Plan",
+        FlowType::Expense,
+        &cat_id,
+        &acc_id,
+        None,
+        250.0,
+        "monthly",
+        Some(1),
+        now(),
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let today = Utc::now();
+    let horizon = DateTime::from_chrono(today + chrono::Months::new(12));
+    let from = DateTime::from_chrono(today);
+    let open: Vec<_> = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.recurring_plan_id == Some(plan_id))
+        .filter(|e| !matches!(e.status, PlannedStatus::Covered | PlannedStatus::Cancelled))
+        .collect();
+    assert!(!open.is_empty());
+
+    let coverage = recurring_plan_coverage(&state, &company_id, today)
+        .await
+        .unwrap();
+    let plan = coverage.get(&plan_id).expect("plan has open entries");
+    assert_eq!(plan.open_entries, open.len() as i64);
+    assert_eq!(
+        plan.next_due_date,
+        open.iter().map(|e| e.due_date).filter(|d| *d >= from).min()
+    );
+    let committed: f64 = open
+        .iter()
+        .filter(|e| e.due_date >= from && e.due_date < horizon)
+        .map(|e| e.amount_estimated)
+        .sum();
+    assert!((plan.committed_next_12_months - committed).abs() < 1e-6);
+
+    // Other companies never see the plan.
+    let other = create_company(&state, "Cov Other", "cov-other", "", true, None)
+        .await
+        .unwrap();
+    assert!(
+        recurring_plan_coverage(&state, &other, today)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    delete_recurring_plan(&state, &plan_id).await.unwrap();
+    common::teardown(Some(ctx)).await;
+}
+
 #[tokio::test]
 async fn planned_entries_crud_works() {
     let ctx = match common::setup_state().await {
@@ -577,3 +663,278 @@ async fn delete_account_integrity_check_is_company_scoped() {
 
     common::teardown(Some(ctx)).await;
 }
+
+#[tokio::test]
+async fn planned_entry_extension_tops_up_the_horizon_once() {
+    let ctx = match common::setup_state().await {
+        Some(s) => s,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let company_id = create_company(&state, "Horizon Co", "horizon-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let cat_id = create_category(&state, &company_id, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let acc_id = create_account(
+        &state,
+        &company_id,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let mut plans = Vec::new();
+    for (name, frequency, day) in [("Rent", "monthly", Some(10)), ("Payroll", "weekly", None)] {
+        let id = create_recurring_plan(
+            &state,
+            &company_id,
+            name,
+            FlowType::Expense,
+            &cat_id,
+            &acc_id,
+            None,
+            100.0,
+            frequency,
+            day,
+            DateTime::parse_rfc3339_str("2024-01-01T00:00:00Z").unwrap(),
+            None,
+            true,
+            1,
+            None,
+        )
+        .await
+        .unwrap();
+        plans.push(id);
+    }
+    let entries_of = |plan_id| {
+        let state = state.clone();
+        async move {
+            list_planned_entries(&state)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|e| e.recurring_plan_id == Some(plan_id))
+                .collect::<Vec<_>>()
+        }
+    };
+    let monthly = entries_of(plans[0]).await;
+    assert_eq!(monthly.len(), 24);
+    // Weekly plans cover the same 24 months, not 24 occurrences.
+    let weekly = entries_of(plans[1]).await;
+    let horizon = Utc::now() + chrono::Months::new(24);
+    let last = weekly.iter().map(|e| e.due_date.to_chrono()).max().unwrap();
+    assert!(last < horizon && last + chrono::Duration::days(7) >= horizon);
+
+    // Paying an entry does not make it look missing.
+    let paid = monthly.iter().map(|e| e.id.unwrap()).next().unwrap();
+    state
+        .planned_entries
+        .update_one(
+            mongodb::bson::doc! { "_id": paid },
+            mongodb::bson::doc! { "$set": { "status": PlannedStatus::Covered.as_str() } },
+        )
+        .await
+        .unwrap();
+    assert_eq!(extend_planned_entries(&state, now()).await.unwrap(), 0);
+
+    // Three months later the monthly plan needs three more entries and the
+    // weekly one about thirteen; a second run adds nothing.
+    let later = DateTime::from_chrono(Utc::now() + chrono::Months::new(3));
+    let added = extend_planned_entries(&state, later).await.unwrap();
+    assert_eq!(entries_of(plans[0]).await.len(), 27);
+    assert_eq!(
+        added,
+        3 + (entries_of(plans[1]).await.len() - weekly.len()) as u64
+    );
+    assert_eq!(extend_planned_entries(&state, later).await.unwrap(), 0);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn overlapping_planned_entry_extensions_insert_each_entry_once() {
+    let ctx = match common::setup_state().await {
+        Some(s) => s,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let company_id = create_company(&state, "Overlap Co", "overlap-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let cat_id = create_category(&state, &company_id, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let acc_id = create_account(
+        &state,
+        &company_id,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let plan_id = create_recurring_plan(
+        &state,
+        &company_id,
+        "Rent",
+        FlowType::Expense,
+        &cat_id,
+        &acc_id,
+        None,
+        100.0,
+        "monthly",
+        Some(10),
+        DateTime::parse_rfc3339_str("2024-01-01T00:00:00Z").unwrap(),
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Two instances extending at once: whichever loses the race on an entry
+    // takes it as already there.
+    let later = DateTime::from_chrono(Utc::now() + chrono::Months::new(6));
+    let (first, second) = tokio::join!(
+        extend_planned_entries(&state, later),
+        extend_planned_entries(&state, later)
+    );
+    assert_eq!(first.unwrap() + second.unwrap(), 6);
+
+    let entries: Vec<_> = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.recurring_plan_id == Some(plan_id))
+        .collect();
+    assert_eq!(entries.len(), 30);
+    let mut days: Vec<_> = entries
+        .iter()
+        .map(|e| e.generated_due_date.expect("generated by the plan"))
+        .collect();
+    days.sort();
+    days.dedup();
+    assert_eq!(days.len(), entries.len());
+
+    common::teardown(Some(ctx)).await;
+}
+#[tokio::test]
+async fn transactions_and_forecasts_are_numbered_per_company_and_year() {
+    let ctx = match common::setup_state().await {
+        Some(s) => s,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let company_id = create_company(&state, "Seq Co", "seq-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other_id = create_company(&state, "Seq Other", "seq-other", "MXN", true, None)
+        .await
+        .unwrap();
+    let cat_id = create_category(
+        &state,
+        &company_id,
+        "Seq Cat",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let other_cat = create_category(&state, &other_id, "Seq Cat", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+
+    let reference_of = |company_id, cat_id, date| {
+        let state = state.clone();
+        async move {
+            let id = create_transaction(
+                &state,
+                &company_id,
+                date,
+                "Seq TX",
+                TransactionType::Expense,
+                &cat_id,
+                None,
+                None,
+                10.0,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+            get_transaction_by_id(&state, &id)
+                .await
+                .unwrap()
+                .unwrap()
+                .reference
+                .unwrap()
+        }
+    };
+    let in_2025 = DateTime::builder()
+        .year(2025)
+        .month(3)
+        .day(1)
+        .build()
+        .unwrap();
+    let in_2026 = DateTime::builder()
+        .year(2026)
+        .month(1)
+        .day(5)
+        .build()
+        .unwrap();
+    assert_eq!(
+        reference_of(company_id, cat_id, in_2025).await,
+        "TX-2025-0001"
+    );
+    assert_eq!(
+        reference_of(company_id, cat_id, in_2025).await,
+        "TX-2025-0002"
+    );
+    assert_eq!(
+        reference_of(company_id, cat_id, in_2026).await,
+        "TX-2026-0001"
+    );
+    assert_eq!(
+        reference_of(other_id, other_cat, in_2025).await,
+        "TX-2025-0001"
+    );
+
+    let fc_id = create_forecast(
+        &state,
+        &company_id,
+        in_2025,
+        None,
+        in_2025,
+        in_2026,
+        "MXN",
+        0.0,
+        0.0,
+        0.0,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let forecast = get_forecast_by_id(&state, &fc_id).await.unwrap().unwrap();
+    assert_eq!(forecast.reference.as_deref(), Some("FC-2025-0001"));
+
+    common::teardown(Some(ctx)).await;
+}
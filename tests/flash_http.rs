@@ -0,0 +1,51 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn saved_settings_show_a_flash_on_the_next_page_only() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("flash-co")
+        .name("Flash Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("flash-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("flash-co");
+    let path = format!("/admin/companies/{}/webhooks", company.to_hex());
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "url=https://bi.example.com/hooks".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    // Background requests of the previous page do not take the message.
+    let (status, body) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains("data-flash="));
+
+    let (status, body) =
+        get_page_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"data-flash="success""#));
+    assert!(body.contains("Webhooks guardados."));
+
+    let (_, body) = get_page_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert!(!body.contains("data-flash="));
+}
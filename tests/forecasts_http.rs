@@ -0,0 +1,388 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn forecast_generate_links_weighted_scenarios_and_compares_them() {
+    let Some((ctx, setup)) = admin_company("scenario", "Scenario Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let income_category = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let expense_category = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Inactive so creating it does not generate entries of its own.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Risky sales",
+            "flow_type": "income",
+            "category_id": income_category.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "amount_estimated": 1000.0,
+            "frequency": "monthly",
+            "day_of_month": 1,
+            "start_date": "2030-01-01T00:00:00Z",
+            "is_active": false,
+            "version": 1,
+            "scenario_weights": { "best": 1.2, "expected": 1.0, "worst": 0.5 },
+            "probability": 50.0
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let plan_id = mongodb::bson::oid::ObjectId::parse_str(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()["id"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let due = DateTime::parse_rfc3339_str("2030-01-15T00:00:00Z").unwrap();
+    for (plan, name, flow, category, amount) in [
+        (
+            Some(plan_id),
+            "Risky sale",
+            FlowType::Income,
+            &income_category,
+            1000.0,
+        ),
+        (
+            None,
+            "Steady sale",
+            FlowType::Income,
+            &income_category,
+            500.0,
+        ),
+        (None, "Rent", FlowType::Expense, &expense_category, 300.0),
+    ] {
+        create_planned_entry(
+            &state,
+            &company,
+            plan,
+            plan.map(|_| 1),
+            None,
+            name,
+            flow,
+            category,
+            &account,
+            None,
+            amount,
+            due,
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/forecasts/generate",
+        &token,
+        serde_json::json!({
+            "start_date": "2030-01-01T00:00:00Z",
+            "end_date": "2030-01-31T23:59:59Z",
+            "initial_balance": 100.0,
+            "worst_weight": 0.8
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let group_id = created["group_id"].as_str().unwrap().to_string();
+    let forecasts = created["forecasts"].as_array().unwrap();
+    let names: Vec<&str> = forecasts
+        .iter()
+        .map(|f| f["scenario_name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["best", "expected", "worst"]);
+    assert!(forecasts.iter().all(|f| f["scenario_group_id"] == group_id));
+
+    // Plan weights win for the risky sale; the run's weights cover the rest.
+    let income: Vec<f64> = forecasts
+        .iter()
+        .map(|f| f["projected_income_total"].as_f64().unwrap())
+        .collect();
+    assert!((income[0] - (1200.0 + 550.0)).abs() < 1e-6);
+    assert!((income[1] - 1500.0).abs() < 1e-6);
+    assert!((income[2] - (500.0 + 400.0)).abs() < 1e-6);
+    let worst = &forecasts[2];
+    assert_eq!(worst["projected_expense_total"].as_f64().unwrap(), 300.0);
+    assert!((worst["final_balance"].as_f64().unwrap() - 700.0).abs() < 1e-6);
+    // The risky sale only has even odds of arriving.
+    let weighted: Vec<f64> = forecasts
+        .iter()
+        .map(|f| f["weighted_income_total"].as_f64().unwrap())
+        .collect();
+    assert!((weighted[1] - (500.0 + 500.0)).abs() < 1e-6);
+    assert!((weighted[2] - (250.0 + 400.0)).abs() < 1e-6);
+
+    // Expenses have no probability.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Rent plan",
+            "flow_type": "expense",
+            "category_id": expense_category.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "amount_estimated": 300.0,
+            "frequency": "monthly",
+            "day_of_month": 1,
+            "start_date": "2030-01-01T00:00:00Z",
+            "is_active": false,
+            "probability": 80.0
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/forecasts/scenarios/{group_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-scenario-column").count(), 3);
+    assert!(body.contains("Pesimista"));
+    assert!(body.contains("900.00"));
+    assert!(body.contains("Neto esperado"));
+    assert!(body.contains("350.00"));
+
+    let (status, _body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!(
+            "/admin/forecasts/scenarios/{}",
+            mongodb::bson::oid::ObjectId::new().to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn forecast_chart_compares_projected_with_actual_and_another_forecast() {
+    use chrono::{Datelike, TimeZone};
+
+    let Some((ctx, setup)) = admin_company("chart", "Chart Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Venta",
+        TransactionType::Income,
+        &sales,
+        None,
+        Some(account),
+        500.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // This month and the next one.
+    let now = chrono::Utc::now();
+    let month_start = chrono::Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .unwrap();
+    let end = month_start + chrono::Duration::days(40);
+    let forecast = |income: f64, scenario: Option<&str>| {
+        let state = state.clone();
+        let scenario = scenario.map(str::to_string);
+        async move {
+            create_forecast(
+                &state,
+                &company,
+                DateTime::now(),
+                None,
+                DateTime::from_chrono(month_start),
+                DateTime::from_chrono(end),
+                "MXN",
+                income,
+                0.0,
+                income,
+                None,
+                None,
+                None,
+                scenario,
+                None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let main = forecast(800.0, None).await;
+    let other = forecast(400.0, Some("worst")).await;
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/forecasts/{}/chart?compare={}",
+            main.to_hex(),
+            other.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let chart: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let points = chart["forecast"]["points"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!(points[0]["period"], now.format("%Y-%m").to_string());
+    assert_eq!(points[0]["actual_income"], 500.0);
+    assert!(points[1]["actual_net"].is_null());
+    let projected: f64 = points
+        .iter()
+        .map(|p| p["projected_income"].as_f64().unwrap())
+        .sum();
+    assert!((projected - 800.0).abs() < 0.001);
+    assert_eq!(chart["compare"]["id"], other.to_hex());
+    assert!(
+        chart["compare"]["label"]
+            .as_str()
+            .unwrap()
+            .starts_with("Pesimista")
+    );
+    assert_eq!(chart["compare"]["points"].as_array().unwrap().len(), 2);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/forecasts/{}/chart", main.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let chart: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(chart["compare"].is_null());
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/forecasts/{}/edit", main.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!(
+        r#"data-forecast-chart="/admin/forecasts/{}/chart""#,
+        main.to_hex()
+    )));
+    assert!(body.contains(&format!(r#"<option value="{}">"#, other.to_hex())));
+
+    // Forecasts of another company cannot be overlaid.
+    let other_company = CompanyFixture::new("chart-other")
+        .create(&state)
+        .await
+        .unwrap();
+    let foreign = create_forecast(
+        &state,
+        &other_company,
+        DateTime::now(),
+        None,
+        DateTime::from_chrono(month_start),
+        DateTime::from_chrono(end),
+        "MXN",
+        1.0,
+        0.0,
+        1.0,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/forecasts/{}/chart?compare={}",
+            main.to_hex(),
+            foreign.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/forecasts/{}/chart?compare=nope", main.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,1546 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn custom_fields_are_stored_typed_rendered_and_filterable() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Custom Co", "custom-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let admin_id = create_user(
+        &state,
+        "custom-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host = "custom-co.miapp.local";
+
+    for (label, entity, field_type, required) in [
+        ("Centro de costos", "contact", "text", true),
+        ("Límite de crédito", "contact", "number", false),
+        ("Deducible", "transaction", "boolean", false),
+    ] {
+        let (status, body) = post_json_with_cookie(
+            build_app(shared.clone()),
+            host,
+            "/api/admin/custom_fields",
+            &token,
+            serde_json::json!({
+                "label": label,
+                "entity": entity,
+                "field_type": field_type,
+                "required": required,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+    }
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/custom_fields",
+        &token,
+        serde_json::json!({ "label": "centro de costos", "entity": "contact", "field_type": "text" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/contacts/new",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("name=\"cf_centro_de_costos\""));
+
+    // The required field is enforced, then values are stored with their type.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/contacts",
+        &token,
+        format!(
+            "name=Sin+centro&company_id={}&contact_type=customer",
+            company.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    for (name, centro) in [("Planta", "Planta+Norte"), ("Oficina", "Corporativo")] {
+        let status = post_form_with_cookie(
+            build_app(shared.clone()),
+            host,
+            "/admin/contacts",
+            &token,
+            format!(
+                "name={name}&company_id={}&contact_type=customer&cf_centro_de_costos={centro}&cf_limite_de_credito=1500",
+                company.to_hex()
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    let stored = state
+        .contacts
+        .find_one(doc! { "company_id": company, "name": "Planta" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        stored.custom_fields.get_str("centro_de_costos").unwrap(),
+        "Planta Norte"
+    );
+    assert_eq!(
+        stored.custom_fields.get_f64("limite_de_credito").unwrap(),
+        1500.0
+    );
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/contacts?cf_centro_de_costos=norte",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let rows: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rows.as_array().unwrap().len(), 1);
+    assert_eq!(rows[0]["name"], "Planta");
+    assert_eq!(rows[0]["custom_fields"]["limite_de_credito"], 1500.0);
+
+    let category = create_category(&state, &company, "Custom", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    for (description, deducible) in [("Con factura", true), ("Sin factura", false)] {
+        let (status, body) = post_json_with_cookie(
+            build_app(shared.clone()),
+            host,
+            "/api/admin/transactions",
+            &token,
+            serde_json::json!({
+                "date": "2026-07-01T12:00:00Z",
+                "description": description,
+                "transaction_type": "expense",
+                "category_id": category.to_hex(),
+                "amount": 10.0,
+                "custom_fields": { "deducible": deducible },
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+    }
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/transactions/data?cf_deducible=true",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let items: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(items.as_array().unwrap().len(), 1);
+    assert_eq!(items[0]["description"], "Con factura");
+
+    // Deleting a definition drops its stored values.
+    let field = state
+        .custom_fields
+        .find_one(doc! { "company_id": company, "key": "limite_de_credito" })
+        .await
+        .unwrap()
+        .unwrap();
+    let (status, _) = post_json_with_cookie(
+        build_app(shared),
+        host,
+        &format!(
+            "/api/admin/custom_fields/{}/delete",
+            field.id.unwrap().to_hex()
+        ),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let stored = state
+        .contacts
+        .find_one(doc! { "company_id": company, "name": "Planta" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!stored.custom_fields.contains_key("limite_de_credito"));
+    assert!(stored.custom_fields.contains_key("centro_de_costos"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn request_bodies_are_limited_per_route_class() {
+    let Some((ctx, setup)) = admin_company("limits", "Limits Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let limits = BodyLimits::default();
+
+    // A form bigger than the form limit is refused before reaching the handler.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/categories",
+        &token,
+        format!(
+            "name={}&flow_type=income",
+            "a".repeat(limits.form_bytes + 1)
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        state
+            .categories
+            .count_documents(doc! { "company_id": company })
+            .await
+            .unwrap(),
+        0
+    );
+
+    // Upload routes take more than a form, but not without bound.
+    let mut oversized = b"%PDF-".to_vec();
+    oversized.resize(limits.upload_bytes + 1, b' ');
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/receipts",
+        &token,
+        &[("receipt", Some("ticket.pdf"), &oversized)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // The file content decides the type, not its name.
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/receipts",
+        &token,
+        &[("receipt", Some("ticket.png"), b"<html>not an image</html>")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let mut larger_than_form = b"%PDF-1.7\n".to_vec();
+    larger_than_form.resize(limits.form_bytes * 2, b' ');
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared),
+        &host,
+        "/api/admin/transactions/receipts",
+        &token,
+        &[("receipt", Some("scan"), &larger_than_form)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let uploaded: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let receipt_id = uploaded["receipt_id"].as_str().unwrap();
+    let receipt = state
+        .receipts
+        .find_one(doc! { "_id": bson::oid::ObjectId::parse_str(receipt_id).unwrap() })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(receipt.content_type, "application/pdf");
+
+    let _ = std::fs::remove_dir_all(format!("uploads/receipts/{}", company.to_hex()));
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn comments_thread_on_edit_pages_and_notify_mentioned_admins() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Notes Co", "notes-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other = create_company(&state, "Other Notes", "other-notes", "MXN", true, None)
+        .await
+        .unwrap();
+    let ana = create_user(
+        &state,
+        "ana-notes@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let beto = create_user(
+        &state,
+        "beto-notes@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let caro = create_user(
+        &state,
+        "caro-notes@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    create_user(
+        &state,
+        "dani-notes@example.com",
+        "SECRET",
+        &[(other, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let ana_token = create_session(&state, "ana-notes@example.com")
+        .await
+        .unwrap();
+    let beto_token = create_session(&state, "beto-notes@example.com")
+        .await
+        .unwrap();
+    let dani_token = create_session(&state, "dani-notes@example.com")
+        .await
+        .unwrap();
+    let host = "notes-co.miapp.local";
+
+    let category = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let tx = create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Rent",
+        TransactionType::Expense,
+        &category,
+        Some(account),
+        None,
+        500.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let edit_path = format!("/admin/transactions/{}/edit", tx.to_hex());
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        host,
+        "/admin/comments",
+        &ana_token,
+        format!(
+            "entity=transaction&entity_id={}&body=Factura+pendiente%2C+%40beto-notes%40example.com+y+%40caro-notes%40example.com.",
+            tx.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let location = location.unwrap();
+    assert!(
+        location.starts_with(&format!("{edit_path}#comment-")),
+        "{location}"
+    );
+    let root_id = location.rsplit("#comment-").next().unwrap().to_string();
+
+    let (status, _, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        host,
+        "/admin/comments",
+        &beto_token,
+        format!(
+            "entity=transaction&entity_id={}&parent_id={root_id}&body=Ya+la+ped%C3%AD",
+            tx.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, &edit_path, &ana_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Factura pendiente"));
+    assert!(body.contains("Ya la pedí"));
+    assert_eq!(body.matches("data-comment-reply").count(), 1);
+    // Ana can only delete her own comment, not Beto's reply.
+    assert_eq!(body.matches("action=\"/admin/comments/").count(), 1);
+
+    // Only the admin was notified; staff cannot open the transaction.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/notifications",
+        &beto_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-notification ").count(), 1);
+    assert!(body.contains("ana-notes@example.com"));
+    assert!(body.contains(&format!("{edit_path}#comment-{root_id}")));
+    assert_eq!(
+        state
+            .notifications
+            .count_documents(doc! { "user_id": caro })
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        state
+            .notifications
+            .count_documents(doc! { "user_id": beto, "read_at": null })
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        state
+            .notifications
+            .count_documents(doc! { "user_id": ana })
+            .await
+            .unwrap(),
+        0
+    );
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        "other-notes.miapp.local",
+        "/admin/comments",
+        &dani_token,
+        format!("entity=transaction&entity_id={}&body=Hola", tx.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let delete_path = format!("/admin/comments/{root_id}/delete");
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &delete_path,
+        &beto_token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        host,
+        &delete_path,
+        &ana_token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        location.as_deref(),
+        Some(format!("{edit_path}#comments").as_str())
+    );
+    assert_eq!(state.comments.count_documents(doc! {}).await.unwrap(), 0);
+    assert_eq!(
+        state.notifications.count_documents(doc! {}).await.unwrap(),
+        0
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn option_pickers_search_by_prefix_within_the_company() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Picker Co", "picker-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other = create_company(&state, "Other Picker", "other-picker", "MXN", true, None)
+        .await
+        .unwrap();
+    create_user(
+        &state,
+        "picker-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let token = create_session(&state, "picker-admin@example.com")
+        .await
+        .unwrap();
+    let host = "picker-co.miapp.local";
+
+    for name in ["Acme", "acero del norte", "Beta (MX)"] {
+        create_contact(
+            &state,
+            &company,
+            name,
+            ContactType::Customer,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    create_contact(
+        &state,
+        &other,
+        "Acme Otro",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+
+    let search = |path: &'static str| {
+        let shared = shared.clone();
+        let token = token.clone();
+        async move {
+            let (status, body) = get_with_cookie(build_app(shared), host, path, &token).await;
+            assert_eq!(status, StatusCode::OK, "{path}: {body}");
+            serde_json::from_str::<Vec<serde_json::Value>>(&body)
+                .unwrap()
+                .into_iter()
+                .map(|item| item["label"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(
+        search("/api/options/contacts?q=AC").await,
+        vec!["Acme", "acero del norte"]
+    );
+    assert_eq!(
+        search("/api/options/contacts?q=beta%20(").await,
+        vec!["Beta (MX)"]
+    );
+    assert_eq!(
+        search("/api/options/contacts?q=.*").await,
+        Vec::<String>::new()
+    );
+    assert_eq!(search("/api/options/contacts?limit=1").await, vec!["Acme"]);
+    assert_eq!(
+        search("/api/options/categories?q=ven").await,
+        vec!["Ventas"]
+    );
+
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/options/accounts",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Past the full-list size the form only renders the first names and
+    // leaves the rest to the search picker.
+    let many: Vec<_> = (0..200)
+        .map(|i| doc! { "company_id": company, "name": format!("Zeta {i:03}"), "contact_type": "customer" })
+        .collect();
+    state
+        .contacts
+        .clone_with_type::<mongodb::bson::Document>()
+        .insert_many(many)
+        .await
+        .unwrap();
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/planned_entries/new",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-options-search=\"/api/options/contacts\""));
+    assert!(body.contains("acero del norte"));
+    assert!(!body.contains("Zeta 199"));
+    assert!(!body.contains("Acme Otro"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn transaction_form_asks_for_the_type_first_and_only_its_fields() {
+    let Some((ctx, setup)) = admin_company("steps", "Steps Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap()
+        .to_hex();
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let cash = create_account(
+        &state,
+        &company,
+        "Cash",
+        AccountType::Cash,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap()
+    .to_hex();
+    let get = |path: String| {
+        let host = host.clone();
+        let app = build_app(shared.clone());
+        let token = token.clone();
+        async move { get_with_cookie(app, &host, &path, &token).await }
+    };
+
+    let (status, body) = get("/admin/transactions/new".into()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-transaction-type-choice=").count(), 3);
+    assert!(!body.contains(r#"name="category_id""#));
+
+    let (status, body) = get("/admin/transactions/new?transaction_type=income".into()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"name="account_to_id""#));
+    assert!(!body.contains(r#"name="account_from_id""#));
+    assert!(body.contains(&sales));
+    assert!(!body.contains(&rent.to_hex()));
+    assert!(body.contains("/api/options/categories?flow_type=income"));
+
+    // Switching to a transfer keeps the chosen account and asks for both.
+    let (status, body) = get(format!(
+        "/admin/transactions/fields?transaction_type=transfer&account_from_id={cash}"
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"name="account_from_id""#));
+    assert!(body.contains(r#"name="account_to_id""#));
+    assert!(body.contains(&format!(r#"value="{cash}" selected"#)));
+    assert!(body.contains(&sales) && body.contains(&rent.to_hex()));
+
+    // A category of the other flow is dropped, unless a planned entry is linked.
+    let (_, body) = get(format!(
+        "/admin/transactions/fields?transaction_type=expense&category_id={sales}"
+    ))
+    .await;
+    assert!(!body.contains(&sales));
+    assert!(!body.contains(r#"name="account_to_id""#));
+    let (_, body) = get(format!(
+        "/admin/transactions/fields?transaction_type=expense&category_id={sales}&planned_entry_id={}",
+        mongodb::bson::oid::ObjectId::new().to_hex()
+    ))
+    .await;
+    assert!(body.contains(&format!(r#"value="{sales}" selected"#)));
+
+    let (status, _) = get("/admin/transactions/fields?transaction_type=refund".into()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get("/api/options/categories?flow_type=income".into()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Sales") && !body.contains("Rent"));
+    let (status, _) = get("/api/options/categories?flow_type=refund".into()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "March rent",
+        TransactionType::Expense,
+        &rent,
+        Some(bank),
+        None,
+        500.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, body) = get(format!("/admin/transactions/{}/edit", transaction.to_hex())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!(r#"value="{}" selected"#, bank.to_hex())));
+    assert!(!body.contains(r#"name="account_to_id""#));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn transaction_form_suggests_the_categories_an_account_is_used_with() {
+    use alfredodev::state::{TransactionBulkAction, bulk_edit_transactions, delete_transaction};
+    use futures::TryStreamExt;
+
+    let Some((ctx, setup)) = admin_company("suggest", "Suggest Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let card_fees = create_category(
+        &state,
+        &company,
+        "Gastos de tarjeta",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let food = create_category(&state, &company, "Comida", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let card = create_account(
+        &state,
+        &company,
+        "Tarjeta",
+        AccountType::CreditCard,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let spend = |category: bson::oid::ObjectId, times: usize| {
+        let state = state.clone();
+        async move {
+            for _ in 0..times {
+                create_transaction(
+                    &state,
+                    &company,
+                    DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+                    "Card purchase",
+                    TransactionType::Expense,
+                    &category,
+                    Some(card),
+                    None,
+                    10.0,
+                    None,
+                    None,
+                    true,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            }
+        }
+    };
+    spend(card_fees, 3).await;
+    spend(food, 1).await;
+
+    let fields = |query: String| {
+        let host = host.clone();
+        let shared = shared.clone();
+        let token = token.clone();
+        async move {
+            get_with_cookie(
+                build_app(shared),
+                &host,
+                &format!("/admin/transactions/fields?{query}"),
+                &token,
+            )
+            .await
+        }
+    };
+    let selected = |id: bson::oid::ObjectId| format!(r#"value="{}" selected>"#, id.to_hex());
+    let chip = |id: bson::oid::ObjectId| format!(r#"data-category-suggestion="{}""#, id.to_hex());
+
+    let (status, body) = fields(format!(
+        "transaction_type=expense&account_from_id={}",
+        card.to_hex()
+    ))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&selected(card_fees)));
+    assert_eq!(body.matches("data-category-suggestion=").count(), 2);
+    assert!(body.find(&chip(card_fees)) < body.find(&chip(food)));
+
+    // A category chosen by hand stays; without an account nothing is
+    // suggested.
+    let (_, body) = fields(format!(
+        "transaction_type=expense&account_from_id={}&category_id={}",
+        card.to_hex(),
+        food.to_hex()
+    ))
+    .await;
+    assert!(body.contains(&selected(food)));
+    let (_, body) = fields("transaction_type=expense".to_string()).await;
+    assert!(!body.contains("data-category-suggestion="));
+
+    // The summary follows new transactions.
+    spend(food, 3).await;
+    let (_, body) = fields(format!(
+        "transaction_type=expense&account_from_id={}",
+        card.to_hex()
+    ))
+    .await;
+    assert!(body.contains(&selected(food)));
+
+    // Recategorizing and deleting take transactions out of the summary.
+    let food_spend: Vec<_> = state
+        .transactions
+        .find(doc! { "category_id": food })
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let food_ids: Vec<_> = food_spend.iter().filter_map(|tx| tx.id).collect();
+    bulk_edit_transactions(
+        &state,
+        &company,
+        &food_ids[..2],
+        &TransactionBulkAction::SetCategory(card_fees),
+    )
+    .await
+    .unwrap();
+    let (_, body) = fields(format!(
+        "transaction_type=expense&account_from_id={}",
+        card.to_hex()
+    ))
+    .await;
+    assert!(body.contains(&selected(card_fees)));
+    for id in &food_ids[2..] {
+        delete_transaction(&state, id).await.unwrap();
+    }
+    let (_, body) = fields(format!(
+        "transaction_type=expense&account_from_id={}",
+        card.to_hex()
+    ))
+    .await;
+    assert_eq!(body.matches("data-category-suggestion=").count(), 1);
+    assert!(!body.contains(&chip(food)));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn clone_prefills_a_new_form_without_saving() {
+    let Some((ctx, setup)) = admin_company("clone", "Clone Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let today = DateTime::from_chrono(
+        chrono::Utc::now()
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc(),
+    )
+    .try_to_rfc3339_string()
+    .unwrap();
+
+    let plan = RecurringPlanFixture::new(&company, "Renta bodega")
+        .create(&state)
+        .await
+        .unwrap();
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/recurring_plans/{}/clone", plan.to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"action="/admin/recurring_plans""#));
+    assert!(body.contains("Copia de «Renta bodega»"));
+    assert!(body.contains(&format!(r#"value="{today}""#)));
+
+    let plans = list_recurring_plans(&state).await.unwrap();
+    let source = plans.iter().find(|p| p.id == Some(plan)).unwrap();
+    let category = source.category_id;
+    let account = source.account_expected_id;
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2025-02-10T00:00:00Z").unwrap(),
+        "Pago de internet",
+        TransactionType::Expense,
+        &category,
+        Some(account),
+        None,
+        899.0,
+        None,
+        None,
+        true,
+        Some("Plan empresarial".into()),
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let before = list_transactions(&state).await.unwrap().len();
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/clone", transaction.to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"action="/admin/transactions""#));
+    assert!(body.contains("Pago de internet"));
+    assert!(body.contains("Plan empresarial"));
+    assert!(body.contains(&format!(r#"value="{today}""#)));
+    assert_eq!(list_transactions(&state).await.unwrap().len(), before);
+
+    // A quarter is followed by the next quarter.
+    let forecast = create_forecast(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2025-01-01T00:00:00Z").unwrap(),
+        None,
+        DateTime::parse_rfc3339_str("2025-01-01T00:00:00Z").unwrap(),
+        DateTime::parse_rfc3339_str("2025-03-31T00:00:00Z").unwrap(),
+        "MXN",
+        1000.0,
+        400.0,
+        600.0,
+        Some(5000.0),
+        Some(5600.0),
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/forecasts/{}/clone", forecast.to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"action="/admin/forecasts""#));
+    assert!(body.contains("2025-04-01T00:00:00Z"));
+    assert!(body.contains("2025-06-30T00:00:00Z"));
+    assert!(body.contains(r#"value="5600""#));
+    assert!(body.contains(r#"value="6200""#));
+
+    // Records of another company are not cloned.
+    let other = CompanyFixture::new("clone-other")
+        .create(&state)
+        .await
+        .unwrap();
+    let foreign = RecurringPlanFixture::new(&other, "Ajena")
+        .create(&state)
+        .await
+        .unwrap();
+    let (status, _, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/recurring_plans/{}/clone", foreign.to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn new_forms_are_prefilled_from_the_query_string() {
+    let Some((ctx, setup)) = admin_company("prefill", "Prefill Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = CompanyFixture::new("prefill-other")
+        .name("Prefill Other")
+        .create(&state)
+        .await
+        .unwrap();
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let foreign = create_category(&state, &other, "Ajena", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Renta de oficina",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        300.0,
+        DateTime::parse_rfc3339_str("2026-02-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-02-01T12:00:00Z").unwrap(),
+        "Anticipo",
+        TransactionType::Expense,
+        &rent,
+        Some(account),
+        None,
+        100.0,
+        Some(entry),
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // The planned entry brings its type, category, account, name and the
+    // amount still pending.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/transactions/new?planned_entry_id={}",
+            entry.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("value=\"Renta de oficina\""));
+    assert!(body.contains("value=\"200.00\""));
+    assert!(body.contains(&format!("value=\"{}\" selected", rent.to_hex())));
+    assert!(body.contains(&format!("value=\"{}\" selected", account.to_hex())));
+    assert!(body.contains(&format!("value=\"{}\" selected", entry.to_hex())));
+    assert!(body.contains("value=\"expense\" selected"));
+
+    // Explicit values win, and the category alone implies the type.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/transactions/new?category_id={}&account_to_id={}&amount=150&description=Cobro&date=2026-03-05",
+            sales.to_hex(),
+            account.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("value=\"income\" selected"));
+    assert!(body.contains("value=\"150.00\""));
+    assert!(body.contains("value=\"Cobro\""));
+    assert!(body.contains("2026-03-05T00:00:00"));
+    assert!(body.contains(&format!("value=\"{}\" selected", sales.to_hex())));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/planned_entries/new?category_id={}&account_expected_id={}&amount=99.5&name=Cobro%20mensual",
+            sales.to_hex(),
+            account.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("value=\"income\" selected"));
+    assert!(body.contains("value=\"99.50\""));
+    assert!(body.contains("value=\"Cobro mensual\""));
+
+    for (path, expected) in [
+        (
+            format!("/admin/transactions/new?category_id={}", foreign.to_hex()),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            format!(
+                "/admin/planned_entries/new?category_id={}",
+                foreign.to_hex()
+            ),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            format!(
+                "/admin/transactions/new?planned_entry_id={}",
+                bson::oid::ObjectId::new().to_hex()
+            ),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "/admin/transactions/new?transaction_type=expense&amount=abc".to_string(),
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let (status, _) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+        assert_eq!(status, expected, "{path}");
+    }
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn required_field_policies_apply_to_plans_and_transactions() {
+    let Some((ctx, setup)) = admin_company("policy", "Policy Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let landlord = create_contact(
+        &state,
+        &company,
+        "Arrendador",
+        ContactType::Supplier,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Without a policy a plan needs no contact.
+    let plan =
+        |flow_type: &str, category: bson::oid::ObjectId, contact: Option<bson::oid::ObjectId>| {
+            serde_json::json!({
+                "name": "Plan",
+                "flow_type": flow_type,
+                "category_id": category.to_hex(),
+                "account_expected_id": account.to_hex(),
+                "contact_id": contact.map(|id| id.to_hex()),
+                "amount_estimated": 100.0,
+                "frequency": "weekly",
+                "start_date": "2026-01-05T00:00:00Z",
+            })
+        };
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        plan("expense", rent, None),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let policy_url = format!("/api/admin/companies/{}/required_fields", company.to_hex());
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &policy_url,
+        &token,
+        serde_json::json!({ "plans": [{ "field": "planned_entry" }] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("error"));
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &policy_url,
+        &token,
+        serde_json::json!({
+            "plans": [{ "field": "contact", "applies_to": "expense" }],
+            "transactions": [{ "field": "notes", "applies_to": "expense" }],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let saved: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(saved["plans"][0]["field"], "contact");
+    assert_eq!(saved["transactions"][0]["applies_to"], "expense");
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        plan("expense", rent, None),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Contacto es obligatorio en los planes de gasto"));
+    for payload in [
+        plan("expense", rent, Some(landlord)),
+        plan("income", sales, None),
+    ] {
+        let (status, body) = post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/recurring-plans",
+            &token,
+            payload,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+    }
+
+    // The plan form shows the message and keeps what was typed.
+    let form = format!(
+        "name=Renta+oficina&company_id={}&flow_type=expense&category_id={}&account_expected_id={}&contact_id=&amount_estimated=100&frequency=weekly&day_of_month=&start_date=2026-01-05T00%3A00%3A00Z&end_date=&version=1&is_active=true&notes=",
+        company.to_hex(),
+        rent.to_hex(),
+        account.to_hex()
+    );
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/admin/recurring_plans",
+        &token,
+        form,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Contacto es obligatorio"));
+    assert!(body.contains("Renta oficina"));
+
+    let transaction = |notes: &str| {
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Renta julio",
+            "transaction_type": "expense",
+            "category_id": rent.to_hex(),
+            "account_from_id": account.to_hex(),
+            "amount": 100.0,
+            "is_confirmed": true,
+            "notes": notes,
+        })
+    };
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        transaction(" "),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Notas es obligatorio en los movimientos de gasto"));
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        transaction("Recibo 7"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    // The transaction form goes back to a prefilled new form.
+    let form = format!(
+        "date=2026-07-01T12%3A00%3A00Z&description=Renta+agosto&transaction_type=expense&category_id={}&account_from_id={}&amount=100&is_confirmed=true&notes=",
+        rent.to_hex(),
+        account.to_hex()
+    );
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions",
+        &token,
+        form,
+    )
+    .await;
+    assert!(status.is_redirection());
+    let location = location.unwrap();
+    assert!(location.starts_with("/admin/transactions/new?"));
+    assert!(location.contains("description=Renta+agosto"));
+    let (status, _) = get_with_cookie(build_app(shared.clone()), &host, &location, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/companies/{}/required_fields", company.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"data-required-field="plans.contact""#));
+    assert!(body.contains(r#"<option value="expense" selected>"#));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn option_bundle_lists_company_options_with_an_etag() {
+    let Some((ctx, setup)) = admin_company("bundle", "Bundle Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = CompanyFixture::new("other-bundle")
+        .name("Other Bundle")
+        .create(&state)
+        .await
+        .unwrap();
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "USD",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    create_account(
+        &state,
+        &company,
+        "Cerrada",
+        AccountType::Bank,
+        "USD",
+        false,
+        None,
+    )
+    .await
+    .unwrap();
+    create_contact(
+        &state,
+        &company,
+        "Acme",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    create_contact(
+        &state,
+        &other,
+        "Ajeno",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    RecurringPlanFixture::new(&company, "Igualas")
+        .income()
+        .category(&category)
+        .account(&account)
+        .amount(900.0)
+        .create(&state)
+        .await
+        .unwrap();
+
+    let fetch = |etag: Option<String>| {
+        let app = build_app(shared.clone());
+        let mut req = Request::builder()
+            .uri("/api/options/bundle")
+            .header("host", host.clone())
+            .header("cookie", session_cookie(&token));
+        if let Some(etag) = etag {
+            req = req.header(header::IF_NONE_MATCH, etag);
+        }
+        let req = req.body(Body::empty()).unwrap();
+        async move {
+            let res = app.oneshot(req).await.unwrap();
+            let status = res.status();
+            let etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+            let body = to_bytes(res.into_body(), 1024 * 1024).await.unwrap();
+            (status, etag, String::from_utf8_lossy(&body).to_string())
+        }
+    };
+
+    let (status, etag, body) = fetch(None).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let names = |key: &str| -> Vec<String> {
+        json[key]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(names("accounts"), vec!["Banco"]);
+    assert_eq!(names("categories"), vec!["Ventas"]);
+    assert_eq!(names("contacts"), vec!["Acme"]);
+    assert_eq!(names("plans"), vec!["Igualas"]);
+    assert_eq!(json["plans"][0]["account_id"], account.to_hex());
+    assert_eq!(json["plans"][0]["amount"], 900.0);
+
+    let (status, same, body) = fetch(Some(etag.clone())).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(same, etag);
+    assert!(body.is_empty());
+
+    create_contact(
+        &state,
+        &company,
+        "Beta",
+        ContactType::Supplier,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, changed, body) = fetch(Some(etag.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(changed, etag);
+    assert!(body.contains("Beta"));
+
+    common::teardown(Some(ctx)).await;
+}
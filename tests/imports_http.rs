@@ -0,0 +1,321 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn bank_statements_import_into_the_chosen_account_once() {
+    use futures::TryStreamExt;
+
+    let Some((ctx, setup)) = admin_company("import", "Import Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let fees = create_category(&state, &company, "Fees", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let (bank_hex, sales_hex, fees_hex) = (bank.to_hex(), sales.to_hex(), fees.to_hex());
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/new",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-statement-import"));
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/import",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&bank_hex) && body.contains(&sales_hex) && body.contains(&fees_hex));
+
+    let ofx = "OFXHEADER:100\r\nDATA:OFXSGML\r\n\r\n<OFX><BANKTRANLIST>\r\n<STMTTRN>\r\n<TRNTYPE>DEBIT\r\n<DTPOSTED>20240305\r\n<TRNAMT>-45.00\r\n<FITID>F1\r\n<NAME>COMISION\r\n</STMTTRN>\r\n<STMTTRN>\r\n<TRNTYPE>DEBIT\r\n<DTPOSTED>20240305\r\n<TRNAMT>-45.00\r\n<FITID>F2\r\n<NAME>COMISION\r\n</STMTTRN>\r\n<STMTTRN>\r\n<TRNTYPE>CREDIT\r\n<DTPOSTED>20240306\r\n<TRNAMT>1500.00\r\n<FITID>F3\r\n<NAME>DEPOSITO\r\n</STMTTRN>\r\n</BANKTRANLIST></OFX>\r\n";
+    let upload = |file: &'static [u8]| {
+        let host = host.clone();
+        let app = build_app(shared.clone());
+        let token = token.clone();
+        let (bank, sales, fees) = (bank_hex.clone(), sales_hex.clone(), fees_hex.clone());
+        async move {
+            post_multipart_with_cookie(
+                app,
+                &host,
+                "/admin/transactions/import",
+                &token,
+                &[
+                    ("account_id", None, bank.as_bytes()),
+                    ("income_category_id", None, sales.as_bytes()),
+                    ("expense_category_id", None, fees.as_bytes()),
+                    ("statement", Some("estado.ofx"), file),
+                ],
+            )
+            .await
+        }
+    };
+
+    let (status, body) = upload(ofx.as_bytes()).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("Archivo OFX"));
+    assert!(body.contains("data-imported class=\"mt-1 text-2xl font-semibold text-slate-800\">3<"));
+
+    let imported: Vec<_> = state
+        .transactions
+        .find(doc! { "company_id": company })
+        .sort(doc! { "amount": 1 })
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(imported.len(), 3);
+    assert!(imported.iter().all(|tx| !tx.is_confirmed));
+    assert_eq!(imported[0].transaction_type, TransactionType::Expense);
+    assert_eq!(imported[0].account_from_id, Some(bank));
+    assert_eq!(imported[0].category_id, fees);
+    assert_eq!(imported[0].amount, 45.0);
+    assert_eq!(imported[2].transaction_type, TransactionType::Income);
+    assert_eq!(imported[2].account_to_id, Some(bank));
+    assert_eq!(imported[2].category_id, sales);
+    assert_eq!(
+        imported[2].notes.as_deref(),
+        Some("Importado de OFX · ref. F3")
+    );
+
+    // The same movements again, now from a QIF export, are all known.
+    let qif = b"!Type:Bank\nD05/03/2024\nT-45.00\nPCOMISION\n^\nD05/03/2024\nT-45.00\nPCOMISION\n^\nD06/03/2024\nT1,500.00\nPDEPOSITO\n^\n";
+    let (status, body) = upload(qif).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(
+        body.contains("data-duplicates class=\"mt-1 text-2xl font-semibold text-slate-800\">3<")
+    );
+    assert_eq!(
+        state
+            .transactions
+            .count_documents(doc! { "company_id": company })
+            .await
+            .unwrap(),
+        3
+    );
+
+    let (status, body) = upload(b"esto no es un estado de cuenta").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("No se pudo leer el archivo"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn csv_statements_are_previewed_remapped_and_remembered() {
+    let Some((ctx, setup)) = admin_company("mapping", "Mapping Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let fees = create_category(&state, &company, "Fees", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let (bank_hex, sales_hex, fees_hex) = (bank.to_hex(), sales.to_hex(), fees.to_hex());
+    let upload = |file: &'static [u8]| {
+        let app = build_app(shared.clone());
+        let host = host.clone();
+        let token = token.clone();
+        let (bank, sales, fees) = (bank_hex.clone(), sales_hex.clone(), fees_hex.clone());
+        async move {
+            post_multipart_with_cookie(
+                app,
+                &host,
+                "/admin/transactions/import",
+                &token,
+                &[
+                    ("account_id", None, bank.as_bytes()),
+                    ("income_category_id", None, sales.as_bytes()),
+                    ("expense_category_id", None, fees.as_bytes()),
+                    ("statement", Some("norte.csv"), file),
+                ],
+            )
+            .await
+        }
+    };
+    let draft_path = |state: AppState| async move {
+        let draft = state
+            .import_drafts
+            .find_one(doc! {})
+            .sort(doc! { "_id": -1 })
+            .await
+            .unwrap()
+            .expect("draft saved");
+        format!("/admin/transactions/import/{}", draft.id.unwrap().to_hex())
+    };
+
+    // Headers no bank uses: nothing is recorded and the first columns are
+    // offered until they are remapped.
+    let (status, _) = upload(
+        b"Ref;Fec.;Movimiento;Importe MXN\nR-1;14/03/2024;Renta;-1.250,00\nR-2;15/03/2024;Cobro;3.000,00\nR-3;16/03/2024;Saldo;\n",
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        state
+            .transactions
+            .count_documents(doc! { "company_id": company })
+            .await
+            .unwrap(),
+        0
+    );
+    let path = draft_path(state.clone()).await;
+    let (status, body) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("data-csv-preview"));
+    assert!(body.contains("<strong data-preview-invalid>2</strong>"));
+    assert!(body.contains("value=\"norte\""));
+
+    let mapping = "delimiter=semicolon&date=1&description=2&amount=3&debit=&credit=&reference=0&date_order=auto&decimal_mark=comma";
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("{mapping}&profile_name=Banco+Norte&action=preview"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("<strong data-preview-lines>2</strong>"));
+    assert!(body.contains("<strong data-preview-skipped>1</strong>"));
+    assert!(body.contains("<strong data-preview-invalid>0</strong>"));
+    assert!(body.contains("-1250.00"));
+
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("{mapping}&profile_name=&action=import"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("data-import-preview-error"));
+
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("{mapping}&profile_name=Banco+Norte&action=import"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("data-imported class=\"mt-1 text-2xl font-semibold text-slate-800\">2<"));
+    assert!(body.contains("data-saved-profile"));
+    let rent = state
+        .transactions
+        .find_one(doc! { "company_id": company, "category_id": fees })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(rent.amount, 1250.0);
+    assert_eq!(rent.description, "Renta");
+    let (status, _) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let profile = state
+        .import_profiles
+        .find_one(doc! { "company_id": company })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(profile.name, "Banco Norte");
+    assert_eq!(profile.header, ["ref", "fec.", "movimiento", "importe mxn"]);
+    assert_eq!(profile.mapping.amount, Some(3));
+
+    // The next file of that bank starts from the saved columns.
+    let (status, _) =
+        upload(b"Ref;Fec.;Movimiento;Importe MXN\nR-4;20/03/2024;Luz;-450,50\n").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let path = draft_path(state.clone()).await;
+    let (status, body) = get_with_cookie(build_app(shared.clone()), &host, &path, &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("data-applied-profile"));
+    assert!(body.contains("<strong data-preview-lines>1</strong>"));
+    assert!(body.contains("-450.50"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/import",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-import-profile=\"Banco Norte\""));
+    let delete_path = format!(
+        "/admin/transactions/import/profiles/{}/delete",
+        profile.id.unwrap().to_hex()
+    );
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &delete_path,
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        state
+            .import_profiles
+            .count_documents(doc! { "company_id": company })
+            .await
+            .unwrap(),
+        0
+    );
+
+    common::teardown(Some(ctx)).await;
+}
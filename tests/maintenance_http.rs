@@ -0,0 +1,1031 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+/// The company danger-zone JSON endpoints delete all CFDIs / transactions for a
+/// company (scoped + count returned), and reject non-admins.
+#[tokio::test]
+async fn company_danger_zone_delete_all_endpoints() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Danger Co", "danger-co", "MXN", true, None)
+        .await
+        .unwrap();
+    create_user_with_permissions(
+        &state,
+        "danger-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "danger-staff@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    let admin_token = create_session(&state, "danger-admin@example.com")
+        .await
+        .unwrap();
+    let staff_token = create_session(&state, "danger-staff@example.com")
+        .await
+        .unwrap();
+    let host = "danger-co.miapp.local";
+    let cid = company.to_hex();
+
+    // Seed data the way each collection stores company_id: CFDIs by hex string,
+    // transactions by ObjectId.
+    state
+        .cfdis
+        .insert_one(mongodb::bson::doc! { "company_id": &cid, "uuid": "u-danger-1" })
+        .await
+        .unwrap();
+    state
+        .transactions
+        .clone_with_type::<mongodb::bson::Document>()
+        .insert_one(mongodb::bson::doc! { "company_id": company, "amount": 1.0 })
+        .await
+        .unwrap();
+
+    // Staff cannot use the danger zone.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/api/admin/companies/{cid}/cfdis/delete_all"),
+        &staff_token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Admin deletes all CFDIs and gets the count back.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/api/admin/companies/{cid}/cfdis/delete_all"),
+        &admin_token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["ok"], true);
+    assert_eq!(v["deleted"], 1);
+
+    // Admin deletes all transactions and gets the count back.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/api/admin/companies/{cid}/transactions/delete_all"),
+        &admin_token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["ok"], true);
+    assert_eq!(v["deleted"], 1);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn contact_erase_api_anonymizes_personal_data_and_keeps_transactions() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(&state, "Erase A", "erase-a", "MXN", true, None)
+        .await
+        .unwrap();
+    let company_b = create_company(&state, "Erase B", "erase-b", "MXN", true, None)
+        .await
+        .unwrap();
+    let admin_id = create_user(
+        &state,
+        "erase-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host_a = "erase-a.miapp.local";
+
+    let contact = create_contact(
+        &state,
+        &company_a,
+        "Juana Pérez",
+        ContactType::Customer,
+        Some("PEJJ800101AAA".into()),
+        Some("juana@example.com".into()),
+        Some("555-0101".into()),
+        Some("Prefiere WhatsApp".into()),
+    )
+    .await
+    .unwrap();
+    let foreign_contact = create_contact(
+        &state,
+        &company_b,
+        "Foreign",
+        ContactType::Customer,
+        None,
+        Some("foreign@example.com".into()),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category = create_category(
+        &state,
+        &company_a,
+        "Erase Sales",
+        FlowType::Income,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account = create_account(
+        &state,
+        &company_a,
+        "Erase Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let transaction = create_transaction(
+        &state,
+        &company_a,
+        DateTime::now(),
+        "Erase sale",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(account),
+        250.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        Some(contact),
+        Some("MXN".into()),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/contacts/{}/erase", contact.to_hex()),
+        &token,
+        serde_json::json!({ "mode": "shred" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/contacts/{}/erase", foreign_contact.to_hex()),
+        &token,
+        serde_json::json!({ "mode": "purge" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/contacts/{}/erase", contact.to_hex()),
+        &token,
+        serde_json::json!({ "mode": "anonymize" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    let app = build_app(shared);
+    let (status, body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/contacts/{}", contact.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail: serde_json::Value = serde_json::from_str(&body).expect("contact JSON");
+    assert_eq!(detail["name"], "Contacto anonimizado");
+    assert!(detail["email"].is_null());
+    assert!(detail["phone"].is_null());
+    assert!(detail["notes"].is_null());
+    assert!(detail["rfc"].is_null());
+
+    let kept = list_transactions(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|tx| tx.id == Some(transaction))
+        .expect("transaction kept");
+    assert_eq!(kept.contact_id, Some(contact));
+    assert_eq!(kept.amount, 250.0);
+
+    let untouched = list_contacts(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|c| c.id == Some(foreign_contact))
+        .unwrap();
+    assert_eq!(untouched.email.as_deref(), Some("foreign@example.com"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn dependencies_explain_blocked_deletes_and_archive_is_offered() {
+    use alfredodev::state::get_account_by_id;
+
+    let Some((ctx, setup)) = admin_company("integrity", "Integrity Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = create_company(
+        &state,
+        "Other Integrity Co",
+        "other-integrity-co",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let used = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let unused = create_category(&state, &company, "Spare", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let foreign = create_account(&state, &other, "Bank", AccountType::Bank, "MXN", true, None)
+        .await
+        .unwrap();
+    let landlord = create_contact(
+        &state,
+        &company,
+        "Landlord",
+        ContactType::Supplier,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "March rent",
+        TransactionType::Expense,
+        &used,
+        Some(bank),
+        None,
+        1200.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        Some(landlord),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let dependencies = |path: String| {
+        let host = host.clone();
+        let shared = shared.clone();
+        let token = token.clone();
+        async move {
+            let (status, body) = get_with_cookie(build_app(shared), &host, &path, &token).await;
+            assert_eq!(status, StatusCode::OK, "{path}: {body}");
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    let account = dependencies(format!("/admin/accounts/{}/dependencies", bank.to_hex())).await;
+    assert_eq!(account["can_delete"], false);
+    assert_eq!(account["can_archive"], true);
+    assert_eq!(account["is_active"], true);
+    assert_eq!(account["dependencies"][0]["collection"], "transactions");
+    assert_eq!(account["dependencies"][0]["count"], 1);
+
+    let category = dependencies(format!("/admin/categories/{}/dependencies", used.to_hex())).await;
+    assert_eq!(category["can_delete"], false);
+    assert_eq!(category["can_archive"], false);
+    let contact = dependencies(format!(
+        "/admin/contacts/{}/dependencies",
+        landlord.to_hex()
+    ))
+    .await;
+    assert_eq!(contact["dependencies"][0]["collection"], "transactions");
+    let spare = dependencies(format!(
+        "/admin/categories/{}/dependencies",
+        unused.to_hex()
+    ))
+    .await;
+    assert_eq!(spare["can_delete"], true);
+    assert_eq!(spare["dependencies"].as_array().unwrap().len(), 0);
+
+    // Categories and contacts in use can no longer be deleted.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/categories/{}/delete", used.to_hex()),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/contacts/{}/delete", landlord.to_hex()),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Archive and restore flip the account's active flag.
+    let archive = format!("/admin/accounts/{}/archive", bank.to_hex());
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &archive,
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        !get_account_by_id(&state, &bank)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_active
+    );
+    let restore = format!("/admin/accounts/{}/restore", bank.to_hex());
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &restore,
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        get_account_by_id(&state, &bank)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_active
+    );
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/categories/{}/archive", used.to_hex()),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/widgets/{}/dependencies", bank.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/accounts/{}/dependencies", foreign.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn text_replacement_previews_applies_and_records_the_run() {
+    use alfredodev::state::get_transaction_by_id;
+    use chrono::{Duration, Utc};
+
+    let Some((ctx, setup)) = admin_company("replace", "Replace Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let phone = create_category(&state, &company, "Phone", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let mut ids = Vec::new();
+    for (days_ago, description, notes) in [
+        (5, "Pago Telmx", None),
+        (3, "telmx", Some("Recibo TELMX marzo".to_string())),
+        (40, "Pago Telmx", None),
+    ] {
+        let id = create_transaction(
+            &state,
+            &company,
+            DateTime::from_chrono(Utc::now() - Duration::days(days_ago)),
+            description,
+            TransactionType::Expense,
+            &phone,
+            Some(bank),
+            None,
+            100.0,
+            None,
+            None,
+            true,
+            notes,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        ids.push(id);
+    }
+
+    let from = (Utc::now() - Duration::days(10))
+        .format("%Y-%m-%d")
+        .to_string();
+    let to = Utc::now().format("%Y-%m-%d").to_string();
+    let form = format!("find=Telmx&replace=Telmex&in_description=1&in_notes=1&from={from}&to={to}");
+
+    // The preview lists every text in the range and changes nothing.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/replace?{form}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-replace-row").count(), 3);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/replace",
+        &token,
+        serde_json::json!({
+            "find": "Telmx",
+            "replace": "Telmex",
+            "fields": ["description", "notes"],
+            "from": from,
+            "to": to,
+            "dry_run": true,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let preview: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(preview["changes"].as_array().unwrap().len(), 3);
+    assert!(preview["id"].is_null());
+    let untouched = get_transaction_by_id(&state, &ids[0])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(untouched.description, "Pago Telmx");
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/replace",
+        &token,
+        form,
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        location.as_deref(),
+        Some("/admin/transactions/replace?applied=3")
+    );
+
+    let first = get_transaction_by_id(&state, &ids[0])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.description, "Pago Telmex");
+    let second = get_transaction_by_id(&state, &ids[1])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.description, "Telmex");
+    assert_eq!(second.notes.as_deref(), Some("Recibo Telmex marzo"));
+    let outside = get_transaction_by_id(&state, &ids[2])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(outside.description, "Pago Telmx");
+
+    let run = state
+        .text_replacements
+        .find_one(bson::doc! { "company_id": company })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(run.changes.len(), 3);
+    assert_eq!(run.username, "replace-admin@example.com");
+    assert_eq!(run.changes[0].before, "Pago Telmx");
+
+    // A description may not end up empty.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/replace",
+        &token,
+        serde_json::json!({
+            "find": "telmex",
+            "replace": "",
+            "fields": ["description"],
+            "from": from,
+            "to": to,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        "/admin/transactions/replace?applied=3",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-replace-history").count(), 1);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn orphaned_references_are_reported_and_fixed() {
+    use alfredodev::state::{get_planned_entry_by_id, get_transaction_by_id};
+
+    let Some((ctx, setup)) = admin_company("orphans", "Orphans Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let landlord = create_contact(
+        &state,
+        &company,
+        "Arrendador",
+        ContactType::Supplier,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2025-03-10T00:00:00Z").unwrap(),
+        "Pago de renta",
+        TransactionType::Expense,
+        &rent,
+        Some(account),
+        None,
+        1500.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        Some(landlord),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Cobro de marzo",
+        FlowType::Income,
+        &sales,
+        &account,
+        None,
+        900.0,
+        DateTime::parse_rfc3339_str("2025-03-20T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Deleted behind the integrity checks' back.
+    for id in [&rent, &sales] {
+        state
+            .categories
+            .delete_one(doc! { "_id": id })
+            .await
+            .unwrap();
+    }
+    state
+        .contacts
+        .delete_one(doc! { "_id": &landlord })
+        .await
+        .unwrap();
+
+    let report = |shared: Arc<AppState>| {
+        let token = token.clone();
+        let host = host.clone();
+        async move {
+            let (status, body) =
+                get_with_cookie(build_app(shared), &host, "/admin/integrity/orphans", &token).await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+    let found = report(shared.clone()).await;
+    assert_eq!(found["count"], 3);
+    let groups = found["groups"].as_array().unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0]["target"], "category");
+    assert_eq!(groups[0]["count"], 2);
+    assert_eq!(groups[0]["references"][0]["collection"], "transactions");
+    assert_eq!(groups[0]["references"][0]["missing_ids"][0], rent.to_hex());
+    assert_eq!(groups[0]["references"][1]["collection"], "planned_entries");
+    assert_eq!(groups[1]["target"], "contact");
+    assert_eq!(groups[1]["references"][0]["field"], "contact_id");
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/integrity/orphans/fix",
+        &token,
+        serde_json::json!({ "action": "reassign_categories" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let fixed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(fixed["updated"], 2);
+    assert_eq!(fixed["created_categories"], 2);
+    assert_eq!(fixed["groups"].as_array().unwrap().len(), 1);
+
+    let placeholders = list_categories(&state).await.unwrap();
+    let placeholder = |flow_type: FlowType| {
+        placeholders
+            .iter()
+            .find(|c| {
+                c.company_id == company && c.name == "Sin categoría" && c.flow_type == flow_type
+            })
+            .and_then(|c| c.id)
+            .unwrap()
+    };
+    let moved = get_transaction_by_id(&state, &transaction)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(moved.category_id, placeholder(FlowType::Expense));
+    let moved = get_planned_entry_by_id(&state, &entry)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(moved.category_id, placeholder(FlowType::Income));
+    // The account's category counters follow the reassigned transaction.
+    let usage = state
+        .category_usage
+        .find_one(bson::doc! { "account_id": account, "transaction_type": "expense" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(usage.category_id, placeholder(FlowType::Expense));
+    assert_eq!(usage.count, 1);
+    assert_eq!(
+        state
+            .category_usage
+            .count_documents(bson::doc! { "category_id": rent })
+            .await
+            .unwrap(),
+        0
+    );
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/integrity/orphans/fix",
+        &token,
+        serde_json::json!({ "action": "clear_contacts" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let moved = get_transaction_by_id(&state, &transaction)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(moved.contact_id, None);
+    assert_eq!(report(shared.clone()).await["count"], 0);
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/integrity/orphans/fix",
+        &token,
+        serde_json::json!({ "action": "drop_everything" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn old_planned_entries_are_archived_and_restored() {
+    use chrono::{Datelike, Utc};
+
+    let Some((ctx, setup)) = admin_company("archive", "Archive Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let today = Utc::now();
+    let recent = format!("{}-{:02}-01T00:00:00Z", today.year(), today.month());
+    for (name, due, status, amount) in [
+        (
+            "Renta cubierta",
+            "2020-03-01T00:00:00Z",
+            PlannedStatus::Covered,
+            100.0,
+        ),
+        (
+            "Renta cancelada",
+            "2020-04-01T00:00:00Z",
+            PlannedStatus::Cancelled,
+            50.0,
+        ),
+        (
+            "Renta pendiente",
+            "2020-05-01T00:00:00Z",
+            PlannedStatus::Planned,
+            30.0,
+        ),
+        (
+            "Renta reciente",
+            recent.as_str(),
+            PlannedStatus::Covered,
+            20.0,
+        ),
+    ] {
+        create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Expense,
+            &rent,
+            &account,
+            None,
+            amount,
+            DateTime::parse_rfc3339_str(due).unwrap(),
+            status,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let counts = |shared: Arc<AppState>| {
+        let token = token.clone();
+        let host = host.clone();
+        async move {
+            let (status, body) = get_with_cookie(
+                build_app(shared),
+                &host,
+                "/api/admin/maintenance/planned-entries",
+                &token,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            let counts: serde_json::Value = serde_json::from_str(&body).unwrap();
+            (
+                counts["live"].clone(),
+                counts["archivable"].clone(),
+                counts["archived"].clone(),
+            )
+        }
+    };
+    let planned_2020 = |shared: Arc<AppState>| {
+        let token = token.clone();
+        let host = host.clone();
+        async move {
+            let (status, body) = get_with_cookie(
+                build_app(shared),
+                &host,
+                "/api/tiempo?mode=year&from=2020-01-01&to=2021-01-01&metrics=planned",
+                &token,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            let buckets: serde_json::Value = serde_json::from_str(&body).unwrap();
+            buckets[0]["planned_expense"].clone()
+        }
+    };
+    assert_eq!(counts(shared.clone()).await, (4.into(), 2.into(), 0.into()));
+    assert_eq!(planned_2020(shared.clone()).await, 130.0);
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/maintenance/planned_entries/archive",
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(counts(shared.clone()).await, (2.into(), 0.into(), 2.into()));
+    assert_eq!(
+        state
+            .planned_entries
+            .count_documents(doc! { "name": "Renta cubierta" })
+            .await
+            .unwrap(),
+        0
+    );
+    // Reports that add up history still see the archived entries.
+    assert_eq!(planned_2020(shared.clone()).await, 130.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/maintenance",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-planned-archive"));
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/maintenance/planned-entries/restore",
+        &token,
+        serde_json::json!({ "since": "2020-04-01" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let restored: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(restored["moved"], 1);
+    assert_eq!(restored["archived"], 1);
+    let entry = state
+        .planned_entries
+        .clone_with_type::<mongodb::bson::Document>()
+        .find_one(doc! { "name": "Renta cancelada" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!entry.contains_key("moved_to_archive_at"));
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/maintenance/planned-entries/restore",
+        &token,
+        serde_json::json!({ "since": "abril" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/maintenance/planned_entries/restore",
+        &token,
+        "since=".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(counts(shared.clone()).await, (4.into(), 2.into(), 0.into()));
+
+    common::teardown(Some(ctx)).await;
+}
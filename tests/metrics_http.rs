@@ -0,0 +1,370 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::create_api_token;
+use common::harness::*;
+
+#[tokio::test]
+async fn metrics_are_served_to_superadmins_only() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("metrics-co")
+        .name("Metrics Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (admin_id, admin_token) = UserFixture::new("metrics-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (_, staff_token) = UserFixture::new("metrics-staff@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (root_id, root_token) = UserFixture::new("metrics-root@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    state
+        .users
+        .update_one(
+            doc! { "_id": root_id },
+            doc! { "$set": { "is_superadmin": true } },
+        )
+        .await
+        .unwrap();
+    let host = tenant_host("metrics-co");
+
+    assert_requires_auth_get(&shared, "/metrics").await;
+    for token in [&staff_token, &admin_token] {
+        let (status, _) =
+            get_with_cookie(build_app(shared.clone()), &host, "/metrics", token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    // Requests are labelled with the route template, and Mongo commands run
+    // by the handlers are timed.
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/recurring_plans",
+        &admin_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/metrics", &root_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(
+        "http_requests_total{method=\"GET\",route=\"/admin/recurring_plans\",status=\"200\"}"
+    ));
+    assert!(
+        body.contains("mongodb_command_duration_seconds_count{command=\"find\",outcome=\"ok\"}")
+    );
+    assert!(body.contains("# TYPE logins_total counter"));
+    assert!(body.contains("# TYPE planned_entries_generated_total counter"));
+
+    // Personal access tokens do not reach the metrics, not even an admin's.
+    let api_token = create_api_token(&state, &admin_id, "prometheus")
+        .await
+        .unwrap();
+    let req = Request::builder()
+        .uri("/metrics")
+        .header("host", &host)
+        .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_ne!(res.status(), StatusCode::OK);
+
+    // The scrape token reads the metrics without a session, and nothing else.
+    let scraper = Arc::new(AppState {
+        metrics_token: Some("scrape-secret".to_string()),
+        ..state.clone()
+    });
+    let scrape = |path: &'static str, token: &'static str| {
+        let app = build_app(scraper.clone());
+        let host = host.clone();
+        async move {
+            let req = Request::builder()
+                .uri(path)
+                .header("host", &host)
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req).await.unwrap().status()
+        }
+    };
+    assert_eq!(scrape("/metrics", "scrape-secret").await, StatusCode::OK);
+    assert_eq!(
+        scrape("/metrics", "scrape-secreT").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        scrape("/api/tiempo", "scrape-secret").await,
+        StatusCode::UNAUTHORIZED
+    );
+    // Without METRICS_TOKEN set, no bearer opens them.
+    assert_eq!(
+        build_app(shared.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .header("host", &host)
+                    .header(header::AUTHORIZATION, "Bearer scrape-secret")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+            .unwrap()
+            .status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn status_is_public_and_reports_version_database_and_tasks() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let shared = Arc::new(ctx.state.clone());
+    alfredodev::metrics::metrics().record_task_run("retention", true);
+    let company = CompanyFixture::new("status-co")
+        .create(&ctx.state)
+        .await
+        .unwrap();
+    ctx.state
+        .companies
+        .update_one(
+            doc! { "_id": company },
+            doc! { "$set": { "webhooks.last_error": "no se pudo entregar" } },
+        )
+        .await
+        .unwrap();
+
+    let req = Request::builder()
+        .uri("/status")
+        .header("host", tenant_host("status-co"))
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), 1024 * 1024).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["status"], "ok");
+    assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(report["database"]["ok"], true);
+    assert_eq!(report["pending_jobs"], 0);
+    assert_eq!(report["webhook_deliveries"]["pending"], 0);
+    assert_eq!(report["webhook_deliveries"]["failed"], 1);
+    assert!(
+        report["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|task| task["name"] == "retention" && task["last_success_at"].is_string())
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn slow_queries_are_listed_for_superadmins() {
+    use alfredodev::metrics::{SlowQuery, metrics};
+    use std::time::{Duration, SystemTime};
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("slow-co")
+        .name("Slow Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("slow-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (root_id, root_token) = UserFixture::new("slow-root@example.com")
+        .staff_of(&company, &[])
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    state
+        .users
+        .update_one(
+            doc! { "_id": root_id },
+            doc! { "$set": { "is_superadmin": true } },
+        )
+        .await
+        .unwrap();
+    let host = tenant_host("slow-co");
+
+    let shape = r#"{ "company_id": "?", "slow_test_marker": "?" }"#;
+    for millis in [800, 1_200] {
+        metrics().record_slow_query(SlowQuery {
+            at: SystemTime::now(),
+            command: "find".to_string(),
+            collection: "transactions".to_string(),
+            shape: shape.to_string(),
+            duration: Duration::from_millis(millis),
+            failed: false,
+        });
+    }
+
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/slow_queries",
+        &admin_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/slow_queries",
+        &root_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-slow-query-group"));
+    assert!(body.contains("slow_test_marker"));
+    assert!(body.contains("1200 ms"));
+    assert!(body.contains(r#"href="/admin/slow_queries""#));
+
+    let (_, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/metrics", &root_token).await;
+    assert!(body.contains("# TYPE mongodb_slow_commands_total counter"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn superadmins_rebuild_indexes_in_the_background() {
+    use alfredodev::state::{app_database, index_plan, migration_status};
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("reindex-co")
+        .name("Reindex Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("reindex-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let (root_id, root_token) = UserFixture::new("reindex-root@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    state
+        .users
+        .update_one(
+            doc! { "_id": root_id },
+            doc! { "$set": { "is_superadmin": true } },
+        )
+        .await
+        .unwrap();
+    let host = tenant_host("reindex-co");
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/maintenance/reindex",
+        &admin_token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/maintenance",
+        &admin_token,
+    )
+    .await;
+    assert!(!body.contains("data-reindex"));
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/maintenance/reindex",
+        &root_token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let mut reindex = serde_json::Value::Null;
+    for _ in 0..100 {
+        let (status, body) = get_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/maintenance/reindex",
+            &root_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        reindex = serde_json::from_str(&body).unwrap();
+        if reindex["running"] == false {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(reindex["running"], false);
+    assert_eq!(reindex["error"], serde_json::Value::Null);
+    assert_eq!(reindex["built"], index_plan().len());
+    assert!(reindex["last_completed_at"].is_string());
+
+    // Recorded next to the migrations without getting in their way.
+    let db = app_database(&shared);
+    let record = db
+        .collection::<bson::Document>("migrations")
+        .find_one(doc! { "name": "reindex" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        record.get_i64("indexes").unwrap(),
+        index_plan().len() as i64
+    );
+    assert!(migration_status(&db).await.is_ok());
+
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/maintenance",
+        &root_token,
+    )
+    .await;
+    assert!(body.contains("data-reindex"));
+    assert!(body.contains("Reconstruir índices"));
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,1715 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn planned_entry_json_mutations_scope_and_create_payment_side_effects() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Planned Entry Mutation A",
+        "planned-entry-mutation-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Planned Entry Mutation B",
+        "planned-entry-mutation-b",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let admin_id = create_user(
+        &state,
+        "planned-entry-mutation-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host_a = "planned-entry-mutation-a.miapp.local";
+
+    let category_a = create_category(
+        &state,
+        &company_a,
+        "Planned Entry Category A",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category_b = create_category(
+        &state,
+        &company_b,
+        "Planned Entry Category B",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_a = create_account(
+        &state,
+        &company_a,
+        "Planned Entry Account A",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/planned-entries",
+        &token,
+        serde_json::json!({
+            "name": "Planned entry created",
+            "flow_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 100.0,
+            "due_date": "2026-07-01T00:00:00Z",
+            "status": "planned",
+            "notes": "created"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).expect("create response JSON");
+    let entry_id = created["id"].as_str().expect("created id").to_string();
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/planned-entries/{entry_id}/update"),
+        &token,
+        serde_json::json!({
+            "name": "Planned entry updated",
+            "flow_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 120.0,
+            "due_date": "2026-07-02T00:00:00Z",
+            "status": "planned",
+            "notes": "updated"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let updated: serde_json::Value = serde_json::from_str(&body).expect("update response JSON");
+    assert_eq!(
+        updated["side_effects"]["planned_entry_recalculated"],
+        entry_id
+    );
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/planned-entries",
+        &token,
+        serde_json::json!({
+            "name": "Bad tenant ref",
+            "flow_type": "expense",
+            "category_id": category_b.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 1.0,
+            "due_date": "2026-07-01T00:00:00Z",
+            "status": "planned"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/planned-entries/{entry_id}/pay"),
+        &token,
+        serde_json::json!({
+            "paid_at": "2026-07-03T00:00:00Z",
+            "amount": 120.0,
+            "account_id": account_a.to_hex(),
+            "notes": "paid"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let paid: serde_json::Value = serde_json::from_str(&body).expect("pay response JSON");
+    assert_eq!(paid["side_effects"]["transaction_created"], true);
+    assert_eq!(paid["side_effects"]["planned_entry_recalculated"], entry_id);
+    let transactions = list_transactions(&state).await.unwrap();
+    assert!(transactions.iter().any(|tx| {
+        tx.company_id == company_a
+            && tx.planned_entry_id.as_ref().map(|id| id.to_hex()) == Some(entry_id.clone())
+    }));
+
+    let bulk_a = create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "Bulk pay A",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        10.0,
+        DateTime::parse_rfc3339_str("2026-08-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let bulk_b = create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "Bulk pay B",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        20.0,
+        DateTime::parse_rfc3339_str("2026-08-02T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/planned-entries/bulk-pay",
+        &token,
+        serde_json::json!({
+            "entry_ids": [bulk_a.to_hex(), bulk_b.to_hex()],
+            "paid_at": "2026-08-03T00:00:00Z",
+            "account_id": account_a.to_hex()
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let bulk_paid: serde_json::Value = serde_json::from_str(&body).expect("bulk pay response JSON");
+    assert_eq!(bulk_paid["side_effects"]["transactions_created"], 2);
+
+    let delete_target = create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "Delete planned entry",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        5.0,
+        DateTime::parse_rfc3339_str("2026-09-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!(
+            "/api/admin/planned-entries/{}/delete",
+            delete_target.to_hex()
+        ),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn planned_entry_pay_endpoint_creates_transaction() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let user = list_users(&state).await.unwrap().remove(0);
+    let host = format!("{}.miapp.local", user.company_slug);
+    let token = create_session(&state, &user.username).await.unwrap();
+    let company_id = user.company_id;
+    let project_id = create_project(
+        &state,
+        &company_id,
+        "Proyecto Pago CFDI",
+        None,
+        None,
+        None,
+        ProjectPriority::Medium,
+        Some(1000.0),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let entry = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.company_id == company_id && entry.id.is_some())
+        .expect("seeded planned entry for active company");
+    let entry_id = entry.id.unwrap().to_hex();
+
+    let account = list_accounts(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|account| {
+            account.company_id == company_id && account.is_active && account.id.is_some()
+        })
+        .expect("seeded active account for active company");
+    let account_id = account.id.unwrap().to_hex();
+    let initial_transactions = list_transactions(&state).await.unwrap().len();
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        &host,
+        &format!("/admin/planned_entries/{entry_id}/pay?return_to=/tiempo"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "pay form must render");
+    assert!(body.contains("Registrar pago"));
+    assert!(body.contains(&entry.name));
+    assert!(body.contains(r#"name="return_to" value="/tiempo""#));
+
+    let app = build_app(shared);
+    let (status, location, _body) = post_form_with_cookie_response(
+        app,
+        &host,
+        &format!("/admin/planned_entries/{entry_id}/pay"),
+        &token,
+        format!(
+            "paid_at=2026-05-04&amount=123.45&account_id={account_id}&project_id={}&notes=Pago+de+prueba&return_to=/tiempo",
+            project_id.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER, "pay submit must redirect");
+    assert_eq!(location.as_deref(), Some("/tiempo"));
+
+    let transactions = list_transactions(&state).await.unwrap();
+    assert_eq!(transactions.len(), initial_transactions + 1);
+    assert!(
+        transactions.iter().any(|tx| tx.planned_entry_id == entry.id
+            && tx.project_id == Some(project_id)
+            && (tx.amount - 123.45).abs() < f64::EPSILON),
+        "payment must create linked transaction"
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn planned_entry_pay_validation_rerenders_form_instead_of_blank_page() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let user = list_users(&state).await.unwrap().remove(0);
+    let host = format!("{}.miapp.local", user.company_slug);
+    let token = create_session(&state, &user.username).await.unwrap();
+    let company_id = user.company_id;
+
+    let entry = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.company_id == company_id && entry.id.is_some())
+        .expect("seeded planned entry for active company");
+    let entry_id = entry.id.unwrap().to_hex();
+    let initial_transactions = list_transactions(&state).await.unwrap().len();
+
+    let app = build_app(shared);
+    let (status, _location, body) = post_form_with_cookie_response(
+        app,
+        &host,
+        &format!("/admin/planned_entries/{entry_id}/pay"),
+        &token,
+        "paid_at=2026-05-04&amount=123.45&account_id=not-an-id&return_to=/tiempo".into(),
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "invalid pay submit must re-render the form"
+    );
+    assert!(body.contains("Selecciona una cuenta válida"));
+    assert!(body.contains(r#"name="return_to" value="/tiempo""#));
+    assert_eq!(
+        list_transactions(&state).await.unwrap().len(),
+        initial_transactions
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn planned_entries_bulk_pay_creates_transactions() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let user = list_users(&state).await.unwrap().remove(0);
+    let host = format!("{}.miapp.local", user.company_slug);
+    let token = create_session(&state, &user.username).await.unwrap();
+    let company_id = user.company_id;
+
+    let account = list_accounts(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|account| {
+            account.company_id == company_id && account.is_active && account.id.is_some()
+        })
+        .expect("seeded active account for active company");
+    let account_id = account.id.as_ref().unwrap().to_hex();
+    let category = list_categories(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|category| {
+            category.company_id == company_id && category.flow_type == FlowType::Expense
+        })
+        .expect("seeded expense category for active company");
+    create_planned_entry(
+        &state,
+        &company_id,
+        None,
+        None,
+        None,
+        "Pago masivo extra",
+        FlowType::Expense,
+        category.id.as_ref().unwrap(),
+        account.id.as_ref().unwrap(),
+        None,
+        345.67,
+        DateTime::parse_rfc3339_str("2026-05-05T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let entries: Vec<_> = list_planned_entries(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.company_id == company_id && entry.id.is_some())
+        .filter(|entry| {
+            !matches!(
+                entry.status,
+                PlannedStatus::Covered | PlannedStatus::Cancelled
+            )
+        })
+        .take(2)
+        .collect();
+    assert_eq!(entries.len(), 2, "payable entries present");
+    let entry_ids = entries
+        .iter()
+        .map(|entry| entry.id.as_ref().unwrap().to_hex())
+        .collect::<Vec<_>>()
+        .join(",");
+    let initial_transactions = list_transactions(&state).await.unwrap().len();
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        &host,
+        &format!("/admin/planned_entries/bulk_pay?ids={entry_ids}&return_to=/tiempo"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Pagar compromisos seleccionados"));
+
+    let app = build_app(shared);
+    let (status, location, _body) = post_form_with_cookie_response(
+        app,
+        &host,
+        "/admin/planned_entries/bulk_pay",
+        &token,
+        format!(
+            "entry_ids={entry_ids}&paid_at=2026-05-04&account_id={account_id}&return_to=/tiempo"
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/tiempo"));
+    assert_eq!(
+        list_transactions(&state).await.unwrap().len(),
+        initial_transactions + 2
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn contact_payment_terms_fill_in_blank_due_dates() {
+    use alfredodev::state::get_planned_entry_by_id;
+    use chrono::{Duration, Utc};
+
+    let Some((ctx, setup)) = admin_company("terms", "Terms Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/contacts",
+        &token,
+        serde_json::json!({
+            "name": "Cliente a crédito",
+            "contact_type": "customer",
+            "payment_terms_days": 400
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/contacts",
+        &token,
+        serde_json::json!({
+            "name": "Cliente a crédito",
+            "contact_type": "customer",
+            "payment_terms_days": 30
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let contact_id = created["id"].as_str().unwrap().to_string();
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/contacts/{contact_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(detail["payment_terms_days"], 30);
+
+    let entry = |due_date: &str, contact: Option<&str>| {
+        serde_json::json!({
+            "name": "Factura",
+            "flow_type": "income",
+            "category_id": category.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "contact_id": contact,
+            "amount_estimated": 500.0,
+            "due_date": due_date,
+            "status": "planned"
+        })
+    };
+    let id_of = |body: &str| {
+        let created: serde_json::Value = serde_json::from_str(body).unwrap();
+        mongodb::bson::oid::ObjectId::parse_str(created["id"].as_str().unwrap()).unwrap()
+    };
+
+    // Blank: today plus the contact's 30 days.
+    let before = Utc::now();
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/planned-entries",
+        &token,
+        entry("", Some(&contact_id)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created = get_planned_entry_by_id(&state, &id_of(&body))
+        .await
+        .unwrap();
+    let due = created.unwrap().due_date.to_chrono();
+    assert!(due >= before + Duration::days(30));
+    assert!(due <= Utc::now() + Duration::days(30));
+
+    // A date entered by hand wins over the terms.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/planned-entries",
+        &token,
+        entry("2026-07-01T00:00:00Z", Some(&contact_id)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created = get_planned_entry_by_id(&state, &id_of(&body))
+        .await
+        .unwrap();
+    let due = created.unwrap().due_date.to_chrono();
+    assert_eq!(due.to_rfc3339(), "2026-07-01T00:00:00+00:00");
+
+    // Nothing to go by without a contact.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/planned-entries",
+        &token,
+        entry("", None),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn planned_entry_status_changes_from_the_list_follow_the_payments() {
+    use alfredodev::state::get_planned_entry_by_id;
+
+    let Some((ctx, setup)) = admin_company("status", "Status Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let category = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let due = DateTime::parse_rfc3339_str("2099-01-01T00:00:00Z").unwrap();
+    let mut entries = Vec::new();
+    for name in ["Unpaid rent", "Half paid rent"] {
+        let id = create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Expense,
+            &category,
+            &bank,
+            None,
+            100.0,
+            due,
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+        entries.push(id);
+    }
+    let (unpaid, half_paid) = (entries[0], entries[1]);
+    create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "First half",
+        TransactionType::Expense,
+        &category,
+        Some(bank),
+        None,
+        40.0,
+        Some(half_paid),
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let change = |id: bson::oid::ObjectId, status: &'static str| {
+        let shared = shared.clone();
+        let host = host.clone();
+        let token = token.clone();
+        async move {
+            post_form_with_cookie_response(
+                build_app(shared),
+                &host,
+                &format!("/admin/planned_entries/{}/status", id.to_hex()),
+                &token,
+                format!("status={status}"),
+            )
+            .await
+        }
+    };
+    let status_of = |id: bson::oid::ObjectId| {
+        let state = state.clone();
+        async move {
+            get_planned_entry_by_id(&state, &id)
+                .await
+                .unwrap()
+                .unwrap()
+                .status
+        }
+    };
+
+    let (status, _, body) = change(unpaid, "covered").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body.contains("data-status-error"));
+    assert_eq!(status_of(unpaid).await, PlannedStatus::Planned);
+
+    let (status, location, _) = change(unpaid, "cancelled").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/admin/planned_entries"));
+    assert_eq!(status_of(unpaid).await, PlannedStatus::Cancelled);
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/planned_entries",
+        &token,
+    )
+    .await;
+    assert_eq!(body.matches(r#"data-status-action="planned""#).count(), 1);
+
+    // A cancelled entry without payments cannot come back as covered, only
+    // reopened.
+    let (status, _, _) = change(unpaid, "covered").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(status_of(unpaid).await, PlannedStatus::Cancelled);
+    let (status, _, _) = change(unpaid, "planned").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(status_of(unpaid).await, PlannedStatus::Planned);
+
+    let (status, _, _) = change(half_paid, "planned").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(status_of(half_paid).await, PlannedStatus::PartiallyCovered);
+    let (status, _, _) = change(half_paid, "paid").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn overpaid_entries_warn_and_move_the_excess_to_an_adjustment() {
+    use alfredodev::state::{
+        delete_planned_entry, get_planned_entry_by_id, get_transaction_by_id, update_transaction,
+    };
+
+    let Some((ctx, setup)) = admin_company("overpaid", "Overpaid Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Factura 12",
+        FlowType::Income,
+        &category,
+        &bank,
+        None,
+        100.0,
+        DateTime::parse_rfc3339_str("2099-01-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let mut payments = Vec::new();
+    for (description, amount) in [("Anticipo", 60.0), ("Finiquito", 60.0)] {
+        let id = create_transaction(
+            &state,
+            &company,
+            DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+            description,
+            TransactionType::Income,
+            &category,
+            None,
+            Some(bank),
+            amount,
+            Some(entry),
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        payments.push(id);
+    }
+    let status_of = |id: bson::oid::ObjectId| {
+        let state = state.clone();
+        async move { get_planned_entry_by_id(&state, &id).await.unwrap().unwrap() }
+    };
+
+    // Paid 120 of 100: flagged, not silently covered.
+    assert_eq!(status_of(entry).await.status, PlannedStatus::OverCovered);
+    let edit_path = format!("/admin/planned_entries/{}/edit", entry.to_hex());
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, &edit_path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-coverage-transaction=").count(), 2);
+    assert!(body.contains("data-coverage-excess"));
+
+    // Detaching the second payment from the entry leaves it partially covered.
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/planned_entries/{}/transactions/{}/detach",
+            entry.to_hex(),
+            payments[1].to_hex()
+        ),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some(edit_path.as_str()));
+    let detached = get_transaction_by_id(&state, &payments[1])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(detached.planned_entry_id, None);
+    assert_eq!(
+        status_of(entry).await.status,
+        PlannedStatus::PartiallyCovered
+    );
+
+    // Linking it again from the transaction form and detaching it from the
+    // transaction side works the same way.
+    update_transaction(
+        &state,
+        &payments[1],
+        &company,
+        detached.date,
+        "Finiquito",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(bank),
+        60.0,
+        Some(entry),
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(status_of(entry).await.status, PlannedStatus::OverCovered);
+    let tx_edit = format!("/admin/transactions/{}/edit", payments[1].to_hex());
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, &tx_edit, &token).await;
+    assert!(body.contains("data-covered-entry"));
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/detach", payments[1].to_hex()),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some(tx_edit.as_str()));
+    assert_eq!(
+        status_of(entry).await.status,
+        PlannedStatus::PartiallyCovered
+    );
+
+    // Nothing to adjust without an excess.
+    let adjust_path = format!("/admin/planned_entries/{}/adjust", entry.to_hex());
+    let (status, _, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &adjust_path,
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // The excess of an overpaid income becomes a credit owed to the customer,
+    // and the entry counts as covered.
+    update_transaction(
+        &state,
+        &payments[1],
+        &company,
+        detached.date,
+        "Finiquito",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(bank),
+        60.0,
+        Some(entry),
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &adjust_path,
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let original = status_of(entry).await;
+    assert_eq!(original.status, PlannedStatus::Covered);
+    let adjustment = original.coverage_adjustment.unwrap();
+    assert_eq!(adjustment.amount, 20.0);
+    assert_eq!(
+        location,
+        Some(format!(
+            "/admin/planned_entries/{}/edit",
+            adjustment.entry_id.to_hex()
+        ))
+    );
+    let credit = status_of(adjustment.entry_id).await;
+    assert_eq!(credit.flow_type, FlowType::Expense);
+    assert_eq!(credit.amount_estimated, 20.0);
+    assert_eq!(credit.account_expected_id, bank);
+
+    // Deleting the adjustment brings the warning back.
+    delete_planned_entry(&state, &adjustment.entry_id)
+        .await
+        .unwrap();
+    let original = status_of(entry).await;
+    assert_eq!(original.status, PlannedStatus::OverCovered);
+    assert!(original.coverage_adjustment.is_none());
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn over_coverage_actions_are_refused_to_foreign_and_restricted_users() {
+    let Some((ctx, setup)) = admin_company("overpaid-guard", "Overpaid Guard Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let other = CompanyFixture::new("overpaid-guard-other-co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, foreign_token) = UserFixture::new("overpaid-guard-foreign@example.com")
+        .admin_of(&other)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let foreign_host = tenant_host("overpaid-guard-other-co");
+    let (clerk_id, clerk_token) = UserFixture::new("overpaid-guard-clerk@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let petty_cash = create_account(
+        &state,
+        &company,
+        "Caja chica",
+        AccountType::Cash,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/users/{}/accounts", clerk_id.to_hex()),
+        &token,
+        serde_json::json!({ "account_ids": [petty_cash.to_hex()] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    // 120 paid into the bank against an entry of 100.
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Factura 13",
+        FlowType::Income,
+        &category,
+        &bank,
+        None,
+        100.0,
+        DateTime::parse_rfc3339_str("2099-01-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let payment = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "Pago",
+        TransactionType::Income,
+        &category,
+        None,
+        Some(bank),
+        120.0,
+        Some(entry),
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let paths = [
+        format!("/admin/planned_entries/{}/adjust", entry.to_hex()),
+        format!(
+            "/admin/planned_entries/{}/transactions/{}/detach",
+            entry.to_hex(),
+            payment.to_hex()
+        ),
+        format!("/admin/transactions/{}/detach", payment.to_hex()),
+    ];
+    for (who, caller_host, caller_token) in [
+        ("admin of another company", &foreign_host, &foreign_token),
+        ("user limited to petty cash", &host, &clerk_token),
+    ] {
+        for path in &paths {
+            let (status, _, _) = post_form_with_cookie_response(
+                build_app(shared.clone()),
+                caller_host,
+                path,
+                caller_token,
+                String::new(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{who}: {path}");
+        }
+    }
+
+    // Nothing moved: the payment is still linked and no adjustment exists.
+    let kept = alfredodev::state::get_planned_entry_by_id(&state, &entry)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kept.status, PlannedStatus::OverCovered);
+    assert!(kept.coverage_adjustment.is_none());
+    let linked = alfredodev::state::get_transaction_by_id(&state, &payment)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(linked.planned_entry_id, Some(entry));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn overdue_entries_roll_forward_and_count_their_slips() {
+    use alfredodev::state::{extend_planned_entries, get_planned_entry_by_id, utc_day_start};
+    use bson::oid::ObjectId;
+    use chrono::Duration;
+
+    let Some((ctx, setup)) = admin_company("roll-forward", "Roll Forward Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let today = utc_day_start(DateTime::now()).to_chrono();
+    let plan = create_recurring_plan(
+        &state,
+        &company,
+        "Cleaning",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        800.0,
+        "weekly",
+        None,
+        DateTime::from_chrono(today - Duration::days(21)),
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = |name: &'static str, plan_id: Option<ObjectId>, due_in_days: i64| {
+        create_planned_entry(
+            &state,
+            &company,
+            plan_id,
+            plan_id.map(|_| 1),
+            None,
+            name,
+            FlowType::Expense,
+            &rent,
+            &account,
+            None,
+            800.0,
+            DateTime::from_chrono(today + Duration::days(due_in_days)),
+            PlannedStatus::Planned,
+            None,
+        )
+    };
+    let from_plan = entry("Missed cleaning", Some(plan), -3).await.unwrap();
+    let one_off = entry("Late invoice", None, -10).await.unwrap();
+    let upcoming = entry("Next invoice", None, 5).await.unwrap();
+
+    // Without days, the entry moves to the plan's next date.
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/planned_entries/{}/roll_forward", from_plan.to_hex()),
+        &token,
+        "days=".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/admin/planned_entries"));
+    let rolled = get_planned_entry_by_id(&state, &from_plan)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(rolled.due_date.to_chrono(), today);
+    assert_eq!(rolled.slip_count, 1);
+    assert!(rolled.is_customized);
+
+    // The entry generated for that day was merged into the rolled one, and
+    // the daily generation does not add it back.
+    let due_today = |entries: Vec<alfredodev::models::PlannedEntry>| {
+        entries
+            .into_iter()
+            .filter(|e| {
+                e.recurring_plan_id == Some(plan) && utc_day_start(e.due_date).to_chrono() == today
+            })
+            .map(|e| e.id.unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        due_today(list_planned_entries(&state).await.unwrap()),
+        vec![from_plan]
+    );
+    extend_planned_entries(&state, DateTime::now())
+        .await
+        .unwrap();
+    assert_eq!(
+        due_today(list_planned_entries(&state).await.unwrap()),
+        vec![from_plan]
+    );
+
+    // Nothing moves when one of the entries cannot, or has nowhere to go.
+    for payload in [
+        serde_json::json!({ "entry_ids": [one_off.to_hex(), upcoming.to_hex()], "days": 7 }),
+        serde_json::json!({ "entry_ids": [one_off.to_hex()] }),
+    ] {
+        let (status, _) = post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/planned-entries/roll-forward",
+            &token,
+            payload,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+    let untouched = get_planned_entry_by_id(&state, &one_off)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(untouched.slip_count, 0);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/planned-entries/roll-forward",
+        &token,
+        serde_json::json!({ "entry_ids": [one_off.to_hex()], "days": 7 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let rolled: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rolled[0]["slip_count"], 1);
+    assert_eq!(
+        DateTime::parse_rfc3339_str(rolled[0]["due_date"].as_str().unwrap())
+            .unwrap()
+            .to_chrono(),
+        today + Duration::days(7)
+    );
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!("/api/admin/planned-entries/{}", one_off.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(data["slip_count"], 1);
+    assert_eq!(data["status"], "planned");
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn print_views_show_a_receipt_and_the_month_schedule() {
+    let Some((ctx, setup)) = admin_company("print", "Print Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (name, due) in [
+        ("Renta marzo", "2025-03-05T00:00:00Z"),
+        ("Renta abril", "2025-04-05T00:00:00Z"),
+    ] {
+        create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Expense,
+            &rent,
+            &account,
+            None,
+            1500.0,
+            DateTime::parse_rfc3339_str(due).unwrap(),
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    let transaction = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2025-03-10T00:00:00Z").unwrap(),
+        "Pago de renta",
+        TransactionType::Expense,
+        &rent,
+        Some(account),
+        None,
+        1500.0,
+        None,
+        None,
+        true,
+        Some("Transferencia al arrendador".into()),
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let receipt_path = format!("/print/transactions/{}", transaction.to_hex());
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, &receipt_path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Comprobante de movimiento"));
+    assert!(body.contains("Print Co"));
+    assert!(body.contains("Pago de renta"));
+    assert!(body.contains("Banco"));
+    assert!(body.contains("1500.00 MXN"));
+    assert!(body.contains("Transferencia al arrendador"));
+    assert!(body.contains(&format!("{receipt_path}/pdf")));
+    let (_, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/edit", transaction.to_hex()),
+        &token,
+    )
+    .await;
+    assert!(body.contains(&format!(r#"href="{receipt_path}""#)));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/print/planned_entries?month=2025-03",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Compromisos de marzo de 2025"));
+    assert!(body.contains("Renta marzo"));
+    assert!(!body.contains("Renta abril"));
+    assert!(body.contains("?month=2025-02"));
+    assert!(body.contains("?month=2025-04"));
+    assert!(body.contains("/print/planned_entries/pdf?month=2025-03"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/print/planned_entries?month=marzo",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Another company's transaction is not printed.
+    let other = CompanyFixture::new("print-other")
+        .create(&state)
+        .await
+        .unwrap();
+    let other_category = create_category(&state, &other, "Otra", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let other_account =
+        create_account(&state, &other, "Caja", AccountType::Cash, "MXN", true, None)
+            .await
+            .unwrap();
+    let foreign = create_transaction(
+        &state,
+        &other,
+        DateTime::parse_rfc3339_str("2025-03-10T00:00:00Z").unwrap(),
+        "Ajeno",
+        TransactionType::Income,
+        &other_category,
+        None,
+        Some(other_account),
+        10.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, _) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!("/print/transactions/{}", foreign.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn amount_edits_of_planned_entries_keep_the_original_figure() {
+    let Some((ctx, setup)) = admin_company("revision", "Revision Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Renta",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        1_000.0,
+        DateTime::parse_rfc3339_str("2026-07-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let update = |amount: f64, notes: &'static str| {
+        let token = token.clone();
+        let host = host.clone();
+        let shared = shared.clone();
+        async move {
+            let (status, body) = post_json_with_cookie(
+                build_app(shared),
+                &host,
+                &format!("/api/admin/planned-entries/{}/update", entry.to_hex()),
+                &token,
+                serde_json::json!({
+                    "name": "Renta",
+                    "flow_type": "expense",
+                    "category_id": rent.to_hex(),
+                    "account_expected_id": account.to_hex(),
+                    "amount_estimated": amount,
+                    "due_date": "2026-07-01T00:00:00Z",
+                    "status": "planned",
+                    "notes": notes
+                }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+        }
+    };
+    update(1_150.0, "aumento").await;
+    // Editing something else leaves the amount history alone.
+    update(1_150.0, "solo notas").await;
+    update(1_100.0, "negociado").await;
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/planned-entries/{}", entry.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let revisions = detail["amount_revisions"].as_array().unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[0]["previous_amount"], 1_000.0);
+    assert_eq!(revisions[0]["amount"], 1_150.0);
+    assert_eq!(revisions[0]["username"], "revision-admin@example.com");
+    assert_eq!(revisions[1]["amount"], 1_100.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/planned_entries/{}/edit", entry.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-amount-history"));
+    assert_eq!(body.matches("data-amount-revision>").count(), 2);
+    assert!(body.contains("+100.00"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn planned_income_is_split_across_deposit_accounts() {
+    let Some((ctx, setup)) = admin_company("split", "Split Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let account = |name: &'static str| {
+        let state = state.clone();
+        async move {
+            create_account(&state, &company, name, AccountType::Bank, "MXN", true, None)
+                .await
+                .unwrap()
+        }
+    };
+    let bank = account("Banco").await;
+    let savings = account("Ahorro").await;
+    let cash = account("Caja").await;
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let due = DateTime::from_millis(DateTime::now().timestamp_millis() + 10 * 24 * 60 * 60 * 1000);
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Factura cliente",
+        FlowType::Income,
+        &sales,
+        &bank,
+        None,
+        1_000.0,
+        due,
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let path = format!("/api/admin/planned-entries/{}/deposits", entry.to_hex());
+
+    // Shares above the estimate are rejected.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        serde_json::json!({ "allocations": [
+            { "account_id": savings.to_hex(), "percentage": 60.0 },
+            { "account_id": cash.to_hex(), "amount": 500.0 }
+        ] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        serde_json::json!({ "allocations": [
+            { "account_id": savings.to_hex(), "percentage": 30.0 }
+        ] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let accounts = data["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0]["account_id"], savings.to_hex());
+    assert_eq!(accounts[0]["expected"], 300.0);
+    assert_eq!(accounts[1]["account_id"], bank.to_hex());
+    assert_eq!(accounts[1]["expected"], 700.0);
+
+    // Income covering the entry only lands in its deposit accounts.
+    let deposit = |account_id: mongodb::bson::oid::ObjectId, amount: f64| {
+        let state = state.clone();
+        async move {
+            create_transaction(
+                &state,
+                &company,
+                DateTime::now(),
+                "Pago cliente",
+                TransactionType::Income,
+                &sales,
+                None,
+                Some(account_id),
+                amount,
+                Some(entry),
+                None,
+                true,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+        }
+    };
+    assert!(deposit(cash, 300.0).await.is_err());
+    deposit(savings, 300.0).await.unwrap();
+
+    let until = DateTime::from_millis(due.timestamp_millis() + 1);
+    let pending = alfredodev::state::pending_deposits(&state, &company, until)
+        .await
+        .unwrap();
+    assert_eq!(pending.get(&bank).copied(), Some(700.0));
+    assert_eq!(pending.get(&savings).copied(), Some(0.0));
+    assert!(!pending.contains_key(&cash));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/planned_entries/{}/edit", entry.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-deposit-split"));
+    assert!(body.contains(&format!(
+        "name=\"share_{}\" value=\"30%\"",
+        savings.to_hex()
+    )));
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/accounts", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Por recibir (30 días)"));
+    assert!(body.contains("data-pending-deposits"));
+
+    common::teardown(Some(ctx)).await;
+}
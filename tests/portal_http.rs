@@ -0,0 +1,266 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::{
+    session::PORTAL_COOKIE_NAME,
+    state::{RetentionPolicy, apply_retention, request_portal_links},
+};
+use chrono::{Duration, Utc};
+use common::harness::*;
+
+/// GET with a raw `Cookie` header; returns the status, the `Location` and the
+/// `Set-Cookie` headers, and the body.
+async fn get_with_raw_cookie(
+    app: Router,
+    host: &str,
+    path: &str,
+    cookie: &str,
+) -> (StatusCode, Option<String>, Vec<String>, String) {
+    let req = Request::builder()
+        .uri(path)
+        .header("host", host)
+        .header("cookie", cookie)
+        .body(Body::empty())
+        .unwrap();
+    let res = app.oneshot(req).await.expect("request failed");
+    let status = res.status();
+    let location = res
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let cookies = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect();
+    let body_bytes = to_bytes(res.into_body(), 1024 * 1024)
+        .await
+        .expect("body read failed");
+    (
+        status,
+        location,
+        cookies,
+        String::from_utf8_lossy(&body_bytes).to_string(),
+    )
+}
+
+#[tokio::test]
+async fn contact_portal_shows_only_the_contacts_own_records() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let mut state = ctx.state.clone();
+    let mailer = Arc::new(RecordingMailer::default());
+    state.mailer = mailer.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("portal-co")
+        .name("Portal Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("portal-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("portal-co");
+
+    let mut contacts = Vec::new();
+    for (name, rfc, email) in [
+        ("Ana", "AAA010101AAA", "ana@example.com"),
+        ("Beto", "BBB010101BBB", "beto@example.com"),
+    ] {
+        let id = create_contact(
+            &state,
+            &company,
+            name,
+            ContactType::Customer,
+            Some(rfc.to_string()),
+            Some(email.to_string()),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        contacts.push(id);
+    }
+    let (ana, beto) = (contacts[0], contacts[1]);
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (name, contact) in [("Anticipo Ana", ana), ("Anticipo Beto", beto)] {
+        create_planned_entry(
+            &state,
+            &company,
+            None,
+            None,
+            None,
+            name,
+            FlowType::Income,
+            &category,
+            &account,
+            Some(contact),
+            5000.0,
+            DateTime::from_chrono(Utc::now() + Duration::days(10)),
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    for (uuid, rfc) in [("UUID-ANA", "AAA010101AAA"), ("UUID-BETO", "BBB010101BBB")] {
+        state
+            .cfdis
+            .insert_one(doc! {
+                "company_id": company.to_hex(),
+                "uuid": uuid,
+                "emisor": { "rfc": "PCO010101AAA", "nombre": "Portal Co" },
+                "receptor": { "rfc": rfc },
+                "comprobante": { "fecha": "2025-03-01T10:00:00", "total": "1160.00", "moneda": "MXN" },
+            })
+            .await
+            .unwrap();
+    }
+
+    // The email is matched ignoring case; an unknown one gets no link.
+    assert!(
+        request_portal_links(&state, "nadie@example.com", Some("portal-co"))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let links = request_portal_links(&state, "ANA@example.com", Some("portal-co"))
+        .await
+        .unwrap();
+    assert_eq!(links.len(), 1);
+    // Only the hash of the token is stored.
+    assert_eq!(
+        state
+            .portal_links
+            .count_documents(doc! { "token": { "$exists": true } })
+            .await
+            .unwrap(),
+        0
+    );
+
+    // Asking from the login page emails the link instead.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/portal/login",
+        "",
+        "email=ana%40example.com".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let token = mailer.last_token_for("ana@example.com").unwrap();
+    let link = format!("/portal/auth?token={token}");
+
+    let (status, location, cookies, _) =
+        get_with_raw_cookie(build_app(shared.clone()), &host, &link, "").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/portal"));
+    assert_eq!(cookies.len(), 1);
+    assert!(cookies[0].starts_with(&format!("{PORTAL_COOKIE_NAME}=")));
+    assert!(cookies[0].contains("Path=/portal"));
+    let portal_cookie = cookies[0].split(';').next().unwrap().to_string();
+
+    let (status, _, _, body) =
+        get_with_raw_cookie(build_app(shared.clone()), &host, "/portal", &portal_cookie).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Anticipo Ana"));
+    assert!(body.contains("UUID-ANA"));
+    assert!(!body.contains("Anticipo Beto"));
+    assert!(!body.contains("UUID-BETO"));
+
+    // The link works once.
+    let (status, _, cookies, _) =
+        get_with_raw_cookie(build_app(shared.clone()), &host, &link, "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(cookies.is_empty());
+
+    // Neither session opens the pages of the other, and the portal session is
+    // bound to its company's subdomain.
+    let (status, location, _, _) = get_with_raw_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/portal",
+        &session_cookie(&admin_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/portal/login"));
+    let (status, _, _, _) = get_with_raw_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/contacts",
+        &portal_cookie,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _, _, _) = get_with_raw_cookie(
+        build_app(shared.clone()),
+        &tenant_host("otra-co"),
+        "/portal",
+        &portal_cookie,
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    // An admin link opens the portal too; revoking access closes the session.
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/contacts/{}/portal/link", ana.to_hex()),
+        &admin_token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("/portal/auth?token="));
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/contacts/{}/portal/revoke", ana.to_hex()),
+        &admin_token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, _, _, _) =
+        get_with_raw_cookie(build_app(shared.clone()), &host, "/portal", &portal_cookie).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        state.portal_links.count_documents(doc! {}).await.unwrap(),
+        0
+    );
+
+    // Expired links are swept with the rest of the retention policy.
+    request_portal_links(&state, "beto@example.com", None)
+        .await
+        .unwrap();
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(2 * 24 * 60 * 60);
+    let report = apply_retention(&state, &RetentionPolicy::default(), later)
+        .await
+        .unwrap();
+    assert_eq!(report.portal_access_deleted, 1);
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,949 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn recurring_plan_json_mutations_scope_and_report_generation_side_effects() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Recurring Plan Mutation A",
+        "recurring-plan-mutation-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Recurring Plan Mutation B",
+        "recurring-plan-mutation-b",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let admin_id = create_user(
+        &state,
+        "recurring-plan-mutation-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host_a = "recurring-plan-mutation-a.miapp.local";
+
+    let category_a = create_category(
+        &state,
+        &company_a,
+        "Recurring Plan Category A",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category_b = create_category(
+        &state,
+        &company_b,
+        "Recurring Plan Category B",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_a = create_account(
+        &state,
+        &company_a,
+        "Recurring Plan Account A",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Recurring plan created",
+            "flow_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 100.0,
+            "frequency": "monthly",
+            "day_of_month": 15,
+            "start_date": "2026-07-01T00:00:00Z",
+            "is_active": true,
+            "version": 1,
+            "notes": "created"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).expect("create response JSON");
+    let plan_id = created["id"].as_str().expect("created id").to_string();
+    assert!(
+        created["side_effects"]["planned_entries_generated"]
+            .as_u64()
+            .unwrap_or(0)
+            > 0
+    );
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/recurring-plans/{plan_id}/update"),
+        &token,
+        serde_json::json!({
+            "name": "Recurring plan updated",
+            "flow_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 125.0,
+            "frequency": "monthly",
+            "day_of_month": 20,
+            "start_date": "2026-07-01T00:00:00Z",
+            "is_active": true,
+            "version": 1,
+            "notes": "updated"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let updated: serde_json::Value = serde_json::from_str(&body).expect("update response JSON");
+    assert_eq!(updated["side_effects"]["previous_version"], 1);
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Bad tenant ref",
+            "flow_type": "expense",
+            "category_id": category_b.to_hex(),
+            "account_expected_id": account_a.to_hex(),
+            "amount_estimated": 1.0,
+            "frequency": "monthly",
+            "day_of_month": 1,
+            "start_date": "2026-07-01T00:00:00Z"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/recurring-plans/{plan_id}/generate"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let generated: serde_json::Value = serde_json::from_str(&body).expect("generate response JSON");
+    assert!(
+        generated["side_effects"]["planned_entries_after"]
+            .as_u64()
+            .unwrap_or(0)
+            > 0
+    );
+
+    let app = build_app(shared);
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/recurring-plans/{plan_id}/delete"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn order_json_mutations_scope_and_report_planned_entry_side_effects() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Order Mutation A",
+        "order-mutation-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Order Mutation B",
+        "order-mutation-b",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let admin_id = create_user(
+        &state,
+        "order-mutation-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host_a = "order-mutation-a.miapp.local";
+
+    let category_a = create_category(
+        &state,
+        &company_a,
+        "Order Category A",
+        FlowType::Income,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category_b = create_category(
+        &state,
+        &company_b,
+        "Order Category B",
+        FlowType::Income,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_a = create_account(
+        &state,
+        &company_a,
+        "Order Account A",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/orders",
+        &token,
+        serde_json::json!({
+            "title": "Order created",
+            "category_id": category_a.to_hex(),
+            "account_id": account_a.to_hex(),
+            "status": "confirmed",
+            "amount": 250.0,
+            "scheduled_at": "2026-07-01T00:00:00Z",
+            "items": [{"description": "Service", "quantity": 1.0, "unit_price": 250.0}],
+            "notes": "created"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).expect("create response JSON");
+    let order_id = created["id"].as_str().expect("created id").to_string();
+    assert_eq!(created["side_effects"]["planned_entry_created"], true);
+    assert!(
+        list_planned_entries(&state)
+            .await
+            .unwrap()
+            .into_iter()
+            .any(
+                |entry| entry.service_order_id.as_ref().map(|id| id.to_hex())
+                    == Some(order_id.clone())
+            )
+    );
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/orders/{order_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    assert!(body.contains("Order created"));
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/orders",
+        &token,
+        serde_json::json!({
+            "title": "Bad tenant ref",
+            "category_id": category_b.to_hex(),
+            "account_id": account_a.to_hex(),
+            "status": "pending",
+            "amount": 1.0
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/orders/{order_id}/update"),
+        &token,
+        serde_json::json!({
+            "title": "Order updated",
+            "category_id": category_a.to_hex(),
+            "account_id": account_a.to_hex(),
+            "status": "in_progress",
+            "amount": 300.0,
+            "items": [{"description": "Service", "quantity": 2.0, "unit_price": 150.0}]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/orders/{order_id}/complete"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let completed: serde_json::Value = serde_json::from_str(&body).expect("complete response JSON");
+    assert_eq!(completed["side_effects"]["order_completed"], true);
+
+    let app = build_app(shared);
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/orders/{order_id}/delete"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+
+    common::teardown(Some(ctx)).await;
+}
+
+/// Generating planned entries from an INACTIVE recurring plan is rejected with a
+/// clear message (this is the 400 the SPA must surface, not a generic error).
+#[tokio::test]
+async fn recurring_plan_generate_rejects_inactive() {
+    let Some((ctx, setup)) = admin_company("recur-inactive", "Recur Inactive Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let category = create_category(&state, &company, "Cat", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Acc",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Create an INACTIVE plan.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Inactive plan",
+            "flow_type": "expense",
+            "category_id": category.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "amount_estimated": 100.0,
+            "frequency": "monthly",
+            "day_of_month": 1,
+            "start_date": "2026-07-01T00:00:00Z",
+            "is_active": false,
+            "version": 1
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let plan_id = serde_json::from_str::<serde_json::Value>(&body).unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Generate → rejected with an explanatory message.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/recurring-plans/{plan_id}/generate"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "body: {body}");
+    assert!(
+        body.contains("inactive"),
+        "expected an 'inactive' reason, got: {body}"
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn recurring_plan_versions_keep_snapshots_and_show_field_diffs() {
+    let Some((ctx, setup)) = admin_company("versions", "Versions Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = create_company(
+        &state,
+        "Versions Other",
+        "versions-other",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let start = DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap();
+    let plan_id = create_recurring_plan(
+        &state,
+        &company,
+        "Office rent",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        250.0,
+        "monthly",
+        Some(10),
+        start,
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let update = |amount: f64| {
+        alfredodev::state::update_recurring_plan(
+            &state,
+            &plan_id,
+            &company,
+            "Office rent",
+            FlowType::Expense,
+            &rent,
+            &account,
+            None,
+            amount,
+            "monthly",
+            Some(10),
+            start,
+            None,
+            true,
+            1,
+            None,
+        )
+    };
+    update(300.0).await.unwrap();
+    // Saving without changes keeps the version and adds no snapshot.
+    update(300.0).await.unwrap();
+
+    let versions = alfredodev::state::list_plan_versions(&state, &plan_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(versions[0].plan.amount_estimated, 250.0);
+    assert_eq!(versions[1].plan.amount_estimated, 300.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/recurring_plans/{}/versions", plan_id.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-plan-version=").count(), 2);
+    assert_eq!(body.matches("data-plan-change").count(), 1);
+    assert!(body.contains("Monto estimado"));
+    assert!(body.contains("250.00"));
+    assert!(body.contains("300.00"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/planned_entries",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!(
+        "/admin/recurring_plans/{}/versions#v2",
+        plan_id.to_hex()
+    )));
+
+    let foreign_category = create_category(&state, &other, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let foreign_account =
+        create_account(&state, &other, "Bank", AccountType::Bank, "MXN", true, None)
+            .await
+            .unwrap();
+    let foreign_plan = create_recurring_plan(
+        &state,
+        &other,
+        "Other rent",
+        FlowType::Expense,
+        &foreign_category,
+        &foreign_account,
+        None,
+        100.0,
+        "monthly",
+        Some(10),
+        start,
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, _) = get_with_cookie(
+        build_app(shared),
+        &host,
+        &format!("/admin/recurring_plans/{}/versions", foreign_plan.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn plan_edits_ask_before_regenerating_and_keep_customized_entries() {
+    let Some((ctx, setup)) = admin_company("regen", "Regen Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let start = DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap();
+    let plan_id = create_recurring_plan(
+        &state,
+        &company,
+        "Office rent",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        250.0,
+        "monthly",
+        Some(10),
+        start,
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let now = DateTime::now();
+    let future_entries = |entries: Vec<alfredodev::models::PlannedEntry>| {
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter(|e| e.recurring_plan_id == Some(plan_id) && e.due_date >= now)
+            .collect();
+        entries.sort_by_key(|e| e.due_date);
+        entries
+    };
+    let before = future_entries(list_planned_entries(&state).await.unwrap());
+    assert!(before.len() > 2);
+    let customized = before[1].clone();
+    let customized_id = customized.id.unwrap();
+    alfredodev::state::update_planned_entry(
+        &state,
+        &customized_id,
+        &company,
+        customized.recurring_plan_id,
+        customized.recurring_plan_version,
+        "Office rent (negotiated)",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        999.0,
+        customized.due_date,
+        PlannedStatus::Planned,
+        None,
+        None,
+        "test",
+    )
+    .await
+    .unwrap();
+
+    let form = |confirm: bool| {
+        let mut body = format!(
+            "name=Office+rent&company_id={}&flow_type=expense&category_id={}&account_expected_id={}&contact_id=&amount_estimated=300&frequency=monthly&day_of_month=10&start_date=2026-01-01T00%3A00%3A00Z&end_date=&version=1&notes=&is_active=true",
+            company.to_hex(),
+            rent.to_hex(),
+            account.to_hex()
+        );
+        if confirm {
+            body.push_str("&confirm=true");
+        }
+        body
+    };
+    let path = format!("/admin/recurring_plans/{}/update", plan_id.to_hex());
+
+    // Without confirmation nothing is saved; the changes are listed instead.
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        form(false),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Confirmar y guardar"));
+    assert!(body.contains(r#"name="confirm" value="true""#));
+    assert!(body.contains("data-preview-kept"));
+    assert!(body.contains("Office rent (negotiated)"));
+    assert!(body.contains(&format!("Se eliminarán ({})", before.len() - 1)));
+    assert!(body.contains("Se conservan por haberse editado a mano (1)"));
+    let plan = alfredodev::state::get_recurring_plan_by_id(&state, &plan_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(plan.version, 1);
+    assert_eq!(plan.amount_estimated, 250.0);
+
+    let (status, location, _) =
+        post_form_with_cookie_response(build_app(shared.clone()), &host, &path, &token, form(true))
+            .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/admin/recurring_plans"));
+    let plan = alfredodev::state::get_recurring_plan_by_id(&state, &plan_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(plan.version, 2);
+
+    let after = future_entries(list_planned_entries(&state).await.unwrap());
+    assert_eq!(after.len(), before.len());
+    let kept = after
+        .iter()
+        .find(|e| e.id == Some(customized_id))
+        .expect("customized entry kept");
+    assert!(kept.is_customized);
+    assert_eq!(kept.amount_estimated, 999.0);
+    assert!(
+        after
+            .iter()
+            .filter(|e| e.id != Some(customized_id))
+            .all(|e| e.recurring_plan_version == Some(2) && e.amount_estimated == 300.0)
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn plan_day_of_month_is_checked_against_frequency() {
+    let Some((ctx, setup)) = admin_company("schedule", "Schedule Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let form = |frequency: &str, day_of_month: &str| {
+        format!(
+            "name=Office+rent&company_id={}&flow_type=expense&category_id={}&account_expected_id={}&contact_id=&amount_estimated=300&frequency={frequency}&day_of_month={day_of_month}&start_date=2026-01-01T00%3A00%3A00Z&end_date=&version=1&notes=&is_active=true",
+            company.to_hex(),
+            rent.to_hex(),
+            account.to_hex()
+        )
+    };
+
+    // The form is shown again with the reason and nothing is saved.
+    for (frequency, day_of_month, message) in [
+        ("monthly", "45", "Día del mes debe estar entre 1 y 31"),
+        (
+            "monthly",
+            "",
+            "Día del mes es obligatorio en planes mensuales",
+        ),
+        ("weekly", "10", "Día del mes solo aplica a planes mensuales"),
+    ] {
+        let (status, _, body) = post_form_with_cookie_response(
+            build_app(shared.clone()),
+            &host,
+            "/admin/recurring_plans",
+            &token,
+            form(frequency, day_of_month),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{frequency} {day_of_month}");
+        assert!(body.contains(message), "{frequency} {day_of_month}");
+    }
+    assert!(list_recurring_plans(&state).await.unwrap().is_empty());
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/recurring_plans",
+        &token,
+        form("weekly", ""),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let plan = list_recurring_plans(&state).await.unwrap().remove(0);
+    assert_eq!(plan.day_of_month, None);
+
+    // Editing runs the same checks.
+    let (status, _, body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/admin/recurring_plans/{}/update",
+            plan.id.unwrap().to_hex()
+        ),
+        &token,
+        form("biweekly", "5"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Día del mes solo aplica a planes mensuales"));
+
+    // The JSON API answers with the same message.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Payroll",
+            "flow_type": "expense",
+            "category_id": rent.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "amount_estimated": 100.0,
+            "frequency": "weekly",
+            "day_of_month": 15,
+            "start_date": "2026-01-01T00:00:00Z"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        error["error"],
+        "Día del mes solo aplica a planes mensuales, trimestrales o anuales"
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn recurring_plans_import_from_csv_reports_rows_or_creates_all() {
+    let Some((ctx, setup)) = admin_company("plans", "Plans Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let upload = |file: &'static [u8], generate: bool| {
+        let host = host.clone();
+        let app = build_app(shared.clone());
+        let token = token.clone();
+        async move {
+            let mut fields: Vec<(&str, Option<&str>, &[u8])> =
+                vec![("sheet", Some("planes.csv"), file)];
+            if generate {
+                fields.push(("generate_entries", None, &b"true"[..]));
+            }
+            post_multipart_with_cookie(app, &host, "/admin/recurring_plans/import", &token, &fields)
+                .await
+        }
+    };
+
+    // One bad row keeps the whole file out.
+    let bad = "nombre,flujo,categoria,cuenta,monto,frecuencia,dia,inicio\nRenta oficina,gasto,renta,banco,12500,mensual,5,2025-01-01\nCobro,ingreso,Ventas,Caja,abc,semanal,3,2025-01-06\n";
+    let (status, body) = upload(bad.as_bytes(), true).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("data-row-error"));
+    assert!(body.contains("no hay una cuenta activa"));
+    assert!(body.contains("Día del mes solo aplica"));
+    assert!(!body.contains(">2</td>"));
+    assert!(body.contains(">3</td>"));
+    assert!(list_recurring_plans(&state).await.unwrap().is_empty());
+
+    let good = "nombre,flujo,categoria,cuenta,monto,frecuencia,dia,inicio\nRenta oficina,gasto,renta,banco,\"12,500.00\",mensual,5,2025-01-01\nCobro,ingreso,Ventas,Banco,800,semanal,,06/01/2025\n";
+    let (status, body) = upload(good.as_bytes(), false).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("data-imported class=\"mt-1 text-2xl font-semibold text-slate-800\">2<"));
+    let plans = list_recurring_plans(&state).await.unwrap();
+    assert_eq!(plans.len(), 2);
+    let office = plans.iter().find(|p| p.name == "Renta oficina").unwrap();
+    assert_eq!(office.category_id, rent);
+    assert_eq!(office.account_expected_id, bank);
+    assert_eq!(office.amount_estimated, 12500.0);
+    assert_eq!(office.frequency, "monthly");
+    assert_eq!(office.day_of_month, Some(5));
+    // Without immediate generation the entries wait for the daily task.
+    assert!(list_planned_entries(&state).await.unwrap().is_empty());
+
+    let single = "nombre;flujo;categoria;cuenta;monto;frecuencia;dia;inicio\nRenta bodega;gasto;Renta;Banco;3000;mensual;10;2025-01-01\n";
+    let (status, body) = upload(single.as_bytes(), true).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let warehouse = list_recurring_plans(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|p| p.name == "Renta bodega")
+        .unwrap();
+    let entries = list_planned_entries(&state).await.unwrap();
+    assert!(!entries.is_empty());
+    assert!(entries.iter().all(|e| e.recurring_plan_id == warehouse.id));
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,1980 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn finance_endpoints_render_seeded_data() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    // Use the first seeded user/session and its active company for host/subdomain.
+    let user = list_users(&state).await.unwrap().remove(0);
+    let host = format!("{}.miapp.local", user.company_slug);
+    let token = create_session(&state, &user.username).await.unwrap();
+
+    // Collect expected strings from the database to assert they appear in responses.
+    let companies = list_companies(&state).await.unwrap();
+    assert!(!companies.is_empty(), "seeded companies present");
+    let accounts = list_accounts(&state).await.unwrap();
+    let categories = list_categories(&state).await.unwrap();
+    let contacts = list_contacts(&state).await.unwrap();
+    let plans = list_recurring_plans(&state).await.unwrap();
+    let planned_entries = list_planned_entries(&state).await.unwrap();
+    let transactions = list_transactions(&state).await.unwrap();
+    let forecasts = list_forecasts(&state).await.unwrap();
+
+    assert!(!accounts.is_empty(), "seeded accounts present");
+    assert!(!categories.is_empty(), "seeded categories present");
+    assert!(!contacts.is_empty(), "seeded contacts present");
+    assert!(!plans.is_empty(), "seeded recurring plans present");
+    assert!(
+        !planned_entries.is_empty(),
+        "seeded planned entries present"
+    );
+    assert!(!transactions.is_empty(), "seeded transactions present");
+    assert!(!forecasts.is_empty(), "seeded forecasts present");
+
+    let cases = vec![
+        ("/admin/accounts", accounts[0].name.clone()),
+        ("/admin/categories", categories[0].name.clone()),
+        ("/admin/contacts", contacts[0].name.clone()),
+        ("/admin/recurring_plans", plans[0].name.clone()),
+        ("/admin/planned_entries", planned_entries[0].name.clone()),
+        ("/admin/transactions", "tx-root".to_string()),
+        (
+            "/api/admin/transactions/data",
+            transactions[0].description.clone(),
+        ),
+        ("/admin/forecasts", forecasts[0].currency.clone()),
+    ];
+
+    for (path, expected) in cases {
+        let app = build_app(shared.clone());
+        let (status, body) = get_with_cookie(app, &host, path, &token).await;
+        assert_eq!(status, StatusCode::OK, "GET {path} must return 200");
+        assert!(
+            body.contains(&expected),
+            "page {path} should contain seeded data: {expected}"
+        );
+    }
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn finance_json_endpoints_scope_to_active_tenant() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Finance JSON A",
+        "finance-json-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Finance JSON B",
+        "finance-json-b",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let user_id = create_user(
+        &state,
+        "finance-json-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin), (company_b, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let user = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    let token = create_session(&state, &user.username).await.unwrap();
+    let host_a = "finance-json-a.miapp.local";
+
+    let category_a = create_category(
+        &state,
+        &company_a,
+        "finance-json-category-a",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category_b = create_category(
+        &state,
+        &company_b,
+        "finance-json-category-b",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_a = create_account(
+        &state,
+        &company_a,
+        "finance-json-account-a",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_b = create_account(
+        &state,
+        &company_b,
+        "finance-json-account-b",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let forecast_a = create_forecast(
+        &state,
+        &company_a,
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        None,
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        DateTime::parse_rfc3339_str("2026-12-31T00:00:00Z").unwrap(),
+        "MXN",
+        1000.0,
+        500.0,
+        500.0,
+        None,
+        None,
+        None,
+        Some("finance-json-forecast-a".into()),
+        None,
+    )
+    .await
+    .unwrap();
+    let forecast_b = create_forecast(
+        &state,
+        &company_b,
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        None,
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        DateTime::parse_rfc3339_str("2026-12-31T00:00:00Z").unwrap(),
+        "MXN",
+        1000.0,
+        500.0,
+        500.0,
+        None,
+        None,
+        None,
+        Some("finance-json-forecast-b".into()),
+        None,
+    )
+    .await
+    .unwrap();
+    let recurring_plan_a = create_recurring_plan(
+        &state,
+        &company_a,
+        "finance-json-plan-a",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        250.0,
+        "monthly",
+        Some(10),
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+    let recurring_plan_b = create_recurring_plan(
+        &state,
+        &company_b,
+        "finance-json-plan-b",
+        FlowType::Expense,
+        &category_b,
+        &account_b,
+        None,
+        250.0,
+        "monthly",
+        Some(10),
+        DateTime::parse_rfc3339_str("2026-01-01T00:00:00Z").unwrap(),
+        None,
+        true,
+        1,
+        None,
+    )
+    .await
+    .unwrap();
+    let planned_entry_a = create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "finance-json-entry-a",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        75.0,
+        DateTime::parse_rfc3339_str("2026-02-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let planned_entry_b = create_planned_entry(
+        &state,
+        &company_b,
+        None,
+        None,
+        None,
+        "finance-json-entry-b",
+        FlowType::Expense,
+        &category_b,
+        &account_b,
+        None,
+        75.0,
+        DateTime::parse_rfc3339_str("2026-02-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let transaction_a = create_transaction(
+        &state,
+        &company_a,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "finance-json-transaction-a",
+        TransactionType::Expense,
+        &category_a,
+        Some(account_a),
+        None,
+        42.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        Some("MXN".into()),
+        None,
+    )
+    .await
+    .unwrap();
+    let transaction_b = create_transaction(
+        &state,
+        &company_b,
+        DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+        "finance-json-transaction-b",
+        TransactionType::Expense,
+        &category_b,
+        Some(account_b),
+        None,
+        42.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        Some("MXN".into()),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let list_cases = vec![
+        (
+            "/api/admin/accounts",
+            "finance-json-account-a",
+            "finance-json-account-b",
+        ),
+        (
+            "/api/admin/categories",
+            "finance-json-category-a",
+            "finance-json-category-b",
+        ),
+        (
+            "/api/admin/forecasts",
+            "finance-json-forecast-a",
+            "finance-json-forecast-b",
+        ),
+        (
+            "/api/admin/recurring-plans",
+            "finance-json-plan-a",
+            "finance-json-plan-b",
+        ),
+        (
+            "/api/admin/planned-entries",
+            "finance-json-entry-a",
+            "finance-json-entry-b",
+        ),
+        (
+            "/api/admin/transactions/data",
+            "finance-json-transaction-a",
+            "finance-json-transaction-b",
+        ),
+    ];
+
+    for (path, visible, hidden) in list_cases {
+        let app = build_app(shared.clone());
+        let (status, body) = get_with_cookie(app, host_a, path, &token).await;
+        assert_eq!(status, StatusCode::OK, "GET {path} must return 200");
+        serde_json::from_str::<serde_json::Value>(&body).expect("response must be JSON");
+        assert!(
+            body.contains(visible),
+            "{path} must include active tenant data"
+        );
+        assert!(
+            !body.contains(hidden),
+            "{path} must not include other tenant data"
+        );
+    }
+
+    let detail_cases = vec![
+        (
+            format!("/api/admin/accounts/{}", account_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-account-a",
+        ),
+        (
+            format!("/api/admin/accounts/{}", account_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+        (
+            format!("/api/admin/categories/{}", category_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-category-a",
+        ),
+        (
+            format!("/api/admin/categories/{}", category_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+        (
+            format!("/api/admin/forecasts/{}", forecast_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-forecast-a",
+        ),
+        (
+            format!("/api/admin/forecasts/{}", forecast_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+        (
+            format!("/api/admin/recurring-plans/{}", recurring_plan_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-plan-a",
+        ),
+        (
+            format!("/api/admin/recurring-plans/{}", recurring_plan_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+        (
+            format!("/api/admin/planned-entries/{}", planned_entry_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-entry-a",
+        ),
+        (
+            format!("/api/admin/planned-entries/{}", planned_entry_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+        (
+            format!("/api/admin/transactions/{}", transaction_a.to_hex()),
+            StatusCode::OK,
+            "finance-json-transaction-a",
+        ),
+        (
+            format!("/api/admin/transactions/{}", transaction_b.to_hex()),
+            StatusCode::FORBIDDEN,
+            "",
+        ),
+    ];
+
+    for (path, expected_status, expected_body) in detail_cases {
+        let app = build_app(shared.clone());
+        let (status, body) = get_with_cookie(app, host_a, &path, &token).await;
+        assert_eq!(
+            status, expected_status,
+            "GET {path} returned unexpected status"
+        );
+        if expected_status == StatusCode::OK {
+            serde_json::from_str::<serde_json::Value>(&body).expect("response must be JSON");
+            assert!(
+                body.contains(expected_body),
+                "{path} should return expected record"
+            );
+        }
+    }
+
+    let staff_id = create_user(
+        &state,
+        "finance-json-staff@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let staff = get_user_by_id(&state, &staff_id).await.unwrap().unwrap();
+    let staff_token = create_session(&state, &staff.username).await.unwrap();
+    let app = build_app(shared);
+    let (status, _) = get_with_cookie(app, host_a, "/api/admin/accounts", &staff_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn overview_rolls_up_admin_companies_and_hides_staff_figures() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Overview Alpha",
+        "overview-alpha",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(&state, "Overview Beta", "overview-beta", "MXN", true, None)
+        .await
+        .unwrap();
+    let user_id = create_user(
+        &state,
+        "overview-user@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin), (company_b, UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let user = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    let token = create_session(&state, &user.username).await.unwrap();
+
+    let income = create_category(
+        &state,
+        &company_a,
+        "Overview Income",
+        FlowType::Income,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let expense = create_category(
+        &state,
+        &company_a,
+        "Overview Expense",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account = create_account(
+        &state,
+        &company_a,
+        "Overview Account",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (category, tx_type, amount, date) in [
+        (&income, TransactionType::Income, 100.0, DateTime::now()),
+        (
+            &expense,
+            TransactionType::Expense,
+            30.0,
+            DateTime::parse_rfc3339_str("2020-01-15T00:00:00Z").unwrap(),
+        ),
+    ] {
+        let (from, to) = match tx_type {
+            TransactionType::Income => (None, Some(account)),
+            _ => (Some(account), None),
+        };
+        create_transaction(
+            &state,
+            &company_a,
+            date,
+            "overview tx",
+            tx_type,
+            category,
+            from,
+            to,
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            Some("MXN".into()),
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "Overview overdue",
+        FlowType::Expense,
+        &expense,
+        &account,
+        None,
+        45.0,
+        DateTime::parse_rfc3339_str("2020-02-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared);
+    let (status, body) =
+        get_with_cookie(app, "overview-alpha.miapp.local", "/overview", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Overview Alpha"));
+    assert!(body.contains("Overview Beta"));
+    assert!(body.contains("70.00"), "cash position: {body}");
+    assert!(body.contains("1 · 45.00"), "overdue entries: {body}");
+    assert!(body.contains("100.00"), "month net: {body}");
+    assert!(body.contains("Sin acceso a las finanzas"));
+    assert!(body.contains("https://overview-beta.miapp.local/account"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn onboarding_checklist_ticks_steps_and_goes_away_once_completed() {
+    use alfredodev::state::{delete_account, get_company_by_id};
+
+    let Some((ctx, setup)) = admin_company("onboarding", "Onboarding Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/overview", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Primeros pasos"), "checklist: {body}");
+    assert!(body.contains("0 de 5"));
+    assert!(body.contains("href=\"/admin/accounts/new\""));
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/admin/accounts", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-empty-state"));
+    assert!(body.contains("Crear tu primera cuenta"));
+
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, "/overview", &token).await;
+    assert!(body.contains("2 de 5"), "checklist: {body}");
+    assert_eq!(body.matches("data-onboarding-done").count(), 2);
+
+    // A done step stays done even when its records go away.
+    delete_account(&state, &account, &company).await.unwrap();
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, "/overview", &token).await;
+    assert!(body.contains("2 de 5"), "checklist: {body}");
+
+    create_contact(
+        &state,
+        &company,
+        "Cliente",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    RecurringPlanFixture::new(&company, "Renta")
+        .create(&state)
+        .await
+        .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Primer cobro",
+        TransactionType::Income,
+        &category,
+        None,
+        None,
+        100.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, "/overview", &token).await;
+    assert!(!body.contains("Primeros pasos"), "checklist: {body}");
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert!(stored.onboarding.completed_at.is_some());
+    assert_eq!(stored.onboarding.completed_steps.len(), 5);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn index_pages_total_amounts_per_currency_and_convert_with_rates() {
+    let Some((ctx, setup)) = admin_company("totals", "Totals Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let income = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let expense = create_category(&state, &company, "Compras", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let pesos = create_account(
+        &state,
+        &company,
+        "Banco MXN",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let dollars = create_account(
+        &state,
+        &company,
+        "Banco USD",
+        AccountType::Bank,
+        "USD",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    for (tx_type, category, from, to, amount) in [
+        (TransactionType::Income, &income, None, Some(pesos), 100.0),
+        (
+            TransactionType::Expense,
+            &expense,
+            Some(dollars),
+            None,
+            10.0,
+        ),
+        (
+            TransactionType::Transfer,
+            &expense,
+            Some(pesos),
+            Some(dollars),
+            40.0,
+        ),
+    ] {
+        create_transaction(
+            &state,
+            &company,
+            DateTime::now(),
+            "Movimiento",
+            tx_type,
+            category,
+            from,
+            to,
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Pago proveedor",
+        FlowType::Expense,
+        &expense,
+        &dollars,
+        None,
+        20.0,
+        DateTime::now(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains("data-currency-total=\"MXN\""),
+        "totals: {body}"
+    );
+    assert!(body.contains("data-currency-total=\"USD\""));
+    assert!(!body.contains("data-converted-total"));
+    assert!(body.contains("name=\"rate_USD\""));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions?rate_USD=17.5",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-converted-total"), "totals: {body}");
+    // 100 MXN of income against 10 USD of expense; the transfer is left out.
+    assert!(body.contains("75.00"), "converted net: {body}");
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/planned_entries",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        body.contains("data-currency-total=\"USD\""),
+        "totals: {body}"
+    );
+    assert!(!body.contains("data-currency-total=\"MXN\""));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn aging_report_buckets_outstanding_past_due_entries() {
+    let Some((ctx, setup)) = admin_company("aging", "Aging Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let client = create_contact(
+        &state,
+        &company,
+        "Cliente Norte",
+        ContactType::Customer,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let days_ago = |days: i64| {
+        DateTime::from_millis(DateTime::now().timestamp_millis() - days * 24 * 60 * 60 * 1000)
+    };
+    let entry = |name: &'static str, flow: FlowType, amount: f64, due: DateTime| {
+        let state = state.clone();
+        let category = if flow == FlowType::Income {
+            sales
+        } else {
+            rent
+        };
+        let contact = (flow == FlowType::Income).then_some(client);
+        async move {
+            create_planned_entry(
+                &state,
+                &company,
+                None,
+                None,
+                None,
+                name,
+                flow,
+                &category,
+                &account,
+                contact,
+                amount,
+                due,
+                PlannedStatus::Planned,
+                None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let partly_paid = entry("Invoice A", FlowType::Income, 1000.0, days_ago(10)).await;
+    entry("Invoice B", FlowType::Income, 300.0, days_ago(45)).await;
+    entry("Invoice C", FlowType::Income, 500.0, days_ago(200)).await;
+    entry("Rent", FlowType::Expense, 800.0, days_ago(70)).await;
+    entry("Future rent", FlowType::Expense, 800.0, days_ago(-20)).await;
+
+    for (amount, confirmed) in [(400.0, true), (100.0, false)] {
+        create_transaction(
+            &state,
+            &company,
+            DateTime::now(),
+            "Payment invoice A",
+            TransactionType::Income,
+            &sales,
+            None,
+            Some(account),
+            amount,
+            Some(partly_paid),
+            None,
+            confirmed,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let rows = alfredodev::state::aging_report(&state, &company, DateTime::now())
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].flow_type, FlowType::Income);
+    assert_eq!(rows[0].contact_id, Some(client));
+    assert_eq!(rows[0].buckets, [600.0, 300.0, 0.0, 500.0]);
+    assert_eq!(rows[0].entries, 3);
+    assert_eq!(rows[1].contact_id, None);
+    assert_eq!(rows[1].buckets, [0.0, 0.0, 800.0, 0.0]);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/reports/aging",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-aging-row").count(), 2);
+    assert!(body.contains("Cliente Norte"));
+    assert!(body.contains("1400.00"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        "/admin/reports/aging?format=csv",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next(),
+        Some("tipo,contacto,0-30,31-60,61-90,90+,total,compromisos")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("Por cobrar,Cliente Norte,600.00,300.00,0.00,500.00,1400.00,3")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("Por pagar,Sin contacto,0.00,0.00,800.00,0.00,800.00,1")
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn burn_rate_api_reports_runway_and_trend() {
+    use chrono::{Datelike, Duration, TimeZone, Utc};
+
+    let Some((ctx, setup)) = admin_company("burn", "Burn Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // With a one-month window, last month is the current period and the one
+    // before it the previous period.
+    let today = Utc::now();
+    let month_start = Utc
+        .with_ymd_and_hms(today.year(), today.month(), 1, 0, 0, 0)
+        .unwrap();
+    let last_month = DateTime::from_chrono(month_start - Duration::days(15));
+    let month_before = DateTime::from_chrono(month_start - Duration::days(45));
+    for (date, kind, category, amount) in [
+        (month_before, TransactionType::Income, sales, 10_000.0),
+        (month_before, TransactionType::Expense, rent, 2_000.0),
+        (last_month, TransactionType::Expense, rent, 3_000.0),
+    ] {
+        let (from, to) = if kind == TransactionType::Income {
+            (None, Some(account))
+        } else {
+            (Some(account), None)
+        };
+        create_transaction(
+            &state, &company, date, "Burn", kind, &category, from, to, amount, None, None, true,
+            None, None, None, None, None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/burn-rate?months=1",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["currency"], "MXN");
+    assert_eq!(report["cash_balance"], 5_000.0);
+    assert_eq!(report["current"]["net_burn"], 3_000.0);
+    assert_eq!(report["previous"]["net_burn"], -8_000.0);
+    assert_eq!(report["deltas"]["gross_burn"], 0.5);
+    assert_eq!(report["deltas"]["income"], -1.0);
+    assert_eq!(report["runway_basis"], "history");
+    let runway = report["runway_months"].as_f64().unwrap();
+    assert!((runway - 5.0 / 3.0).abs() < 1e-9);
+
+    // Planned commitments take over from the history.
+    create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Next rent",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        2_500.0,
+        DateTime::from_chrono(today + Duration::days(5)),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/burn-rate?months=1",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["runway_basis"], "planned");
+    assert_eq!(report["projected_monthly_net"], -2_500.0);
+    assert_eq!(report["runway_months"], 2.0);
+
+    let (status, _) = get_with_cookie(
+        build_app(shared),
+        &host,
+        "/api/admin/reports/burn-rate?months=0",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn cash_calendar_api_nets_actual_past_and_planned_future_days() {
+    use chrono::{Duration, Utc};
+
+    let Some((ctx, setup)) = admin_company("calendar", "Calendar Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let now = Utc::now();
+    let rent_due = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Renta",
+        FlowType::Expense,
+        &rent,
+        &account,
+        None,
+        1000.0,
+        DateTime::from_chrono(now + Duration::days(5)),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/recurring-plans",
+        &token,
+        serde_json::json!({
+            "name": "Venta dudosa",
+            "flow_type": "income",
+            "category_id": sales.to_hex(),
+            "account_expected_id": account.to_hex(),
+            "amount_estimated": 400.0,
+            "frequency": "monthly",
+            "day_of_month": 1,
+            "start_date": "2030-01-01T00:00:00Z",
+            "is_active": false,
+            "probability": 25.0
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let doubtful_plan = mongodb::bson::oid::ObjectId::parse_str(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()["id"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+    create_planned_entry(
+        &state,
+        &company,
+        Some(doubtful_plan),
+        Some(1),
+        None,
+        "Venta dudosa",
+        FlowType::Income,
+        &sales,
+        &account,
+        None,
+        400.0,
+        DateTime::from_chrono(now + Duration::days(2)),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let advance = Some(rent_due);
+    for (description, tx_type, category, amount, days_ago, entry) in [
+        ("Cobro", TransactionType::Income, sales, 500.0, 3, None),
+        ("Papelería", TransactionType::Expense, rent, 200.0, 3, None),
+        ("Renta", TransactionType::Expense, rent, 300.0, 0, advance),
+        ("Borrador", TransactionType::Expense, rent, 50.0, 3, None),
+    ] {
+        let (from, to) = match tx_type {
+            TransactionType::Income => (None, Some(account)),
+            _ => (Some(account), None),
+        };
+        create_transaction(
+            &state,
+            &company,
+            DateTime::from_chrono(now - Duration::days(days_ago)),
+            description,
+            tx_type,
+            &category,
+            from,
+            to,
+            amount,
+            entry,
+            None,
+            description != "Borrador",
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/cash-calendar?days=7",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let calendar: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let day = |offset: i64| {
+        (now + Duration::days(offset))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    assert_eq!(calendar["today"], day(0));
+    let days = calendar["days"].as_array().unwrap();
+    assert_eq!(days.len(), 15);
+    assert_eq!(days[0]["date"], day(-7));
+    assert_eq!(days[14]["date"], day(7));
+    let find = |offset: i64| {
+        days.iter()
+            .find(|d| d["date"] == day(offset))
+            .unwrap()
+            .clone()
+    };
+    // The draft does not count until it is confirmed.
+    assert_eq!(find(-3)["actual"], 300.0);
+    assert_eq!(find(-3)["planned"], 0.0);
+    assert_eq!(find(0)["actual"], -300.0);
+    // Only what is still owed on the rent is planned.
+    assert_eq!(find(5)["planned"], -700.0);
+    assert_eq!(find(5)["net"], -700.0);
+    assert_eq!(find(5)["expected_net"], -700.0);
+    assert_eq!(find(6)["net"], 0.0);
+    // Planned income also counts at its plan's probability.
+    assert_eq!(find(2)["planned"], 400.0);
+    assert_eq!(find(2)["planned_expected"], 100.0);
+    assert_eq!(find(2)["expected_net"], 100.0);
+
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/cash-calendar?days=0",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        "/admin/reports/cash_calendar?days=30",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-cash-calendar=\"30\""));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn consolidated_report_converts_admin_companies_to_one_currency() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let mexico = create_company(&state, "Group Mexico", "group-mexico", "MXN", true, None)
+        .await
+        .unwrap();
+    let texas = create_company(&state, "Group Texas", "group-texas", "USD", true, None)
+        .await
+        .unwrap();
+    let branch = create_company(&state, "Group Branch", "group-branch", "MXN", true, None)
+        .await
+        .unwrap();
+    let user_id = create_user(
+        &state,
+        "group-admin@example.com",
+        "SECRET",
+        &[
+            (mexico, UserRole::Admin),
+            (texas, UserRole::Admin),
+            (branch, UserRole::Staff),
+        ],
+    )
+    .await
+    .unwrap();
+    let user = get_user_by_id(&state, &user_id).await.unwrap().unwrap();
+    let token = create_session(&state, &user.username).await.unwrap();
+    let host = "group-mexico.miapp.local";
+
+    let january = DateTime::parse_rfc3339_str("2024-01-15T00:00:00Z").unwrap();
+    for (company, currency, flows) in [
+        (&mexico, "MXN", vec![(TransactionType::Income, 1000.0)]),
+        (
+            &texas,
+            "USD",
+            vec![
+                (TransactionType::Income, 100.0),
+                (TransactionType::Expense, 20.0),
+            ],
+        ),
+    ] {
+        let income = create_category(&state, company, "Sales", FlowType::Income, None, None)
+            .await
+            .unwrap();
+        let expense = create_category(&state, company, "Rent", FlowType::Expense, None, None)
+            .await
+            .unwrap();
+        let account = create_account(
+            &state,
+            company,
+            "Bank",
+            AccountType::Bank,
+            currency,
+            true,
+            None,
+        )
+        .await
+        .unwrap();
+        for (tx_type, amount) in flows {
+            let (category, from, to) = match tx_type {
+                TransactionType::Income => (&income, None, Some(account)),
+                _ => (&expense, Some(account), None),
+            };
+            create_transaction(
+                &state, company, january, "group tx", tx_type, category, from, to, amount, None,
+                None, true, None, None, None, None, None,
+            )
+            .await
+            .unwrap();
+        }
+        create_planned_entry(
+            &state,
+            company,
+            None,
+            None,
+            None,
+            "Group projection",
+            FlowType::Income,
+            &income,
+            &account,
+            None,
+            50.0,
+            DateTime::parse_rfc3339_str("2024-01-20T00:00:00Z").unwrap(),
+            PlannedStatus::Planned,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let base = format!(
+        "/overview/consolidated?company={}&company={}&currency=MXN&from=2024-01-01&to=2024-01-31",
+        mexico.to_hex(),
+        texas.to_hex()
+    );
+    let (status, body) = get_with_cookie(build_app(shared.clone()), host, &base, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Indica el tipo de cambio a MXN de: USD"));
+    assert!(!body.contains("data-consolidated-row"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("{base}&rate_USD=20"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-consolidated-row").count(), 2);
+    let total = &body[body.find("data-consolidated-total").unwrap()..];
+    // 1000 MXN + 100 USD income, 20 USD expense, 50 MXN + 50 USD projected.
+    for amount in ["3000.00", "400.00", "2600.00", "1050.00"] {
+        assert!(total.contains(amount), "{amount} missing: {total}");
+    }
+
+    // Staff members cannot pull a company's figures into the report.
+    let (status, _) = get_with_cookie(
+        build_app(shared),
+        host,
+        &format!(
+            "/overview/consolidated?company={}&company={}",
+            mexico.to_hex(),
+            branch.to_hex()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn tiempo_api_groups_custom_windows_by_granularity_and_metric() {
+    let Some((ctx, setup)) = admin_company("tiempo", "Tiempo Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let bank = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let movements = [
+        ("2026-01-15T00:00:00Z", TransactionType::Income, 5.0),
+        ("2026-03-02T00:00:00Z", TransactionType::Income, 100.0),
+        ("2026-03-20T00:00:00Z", TransactionType::Expense, 40.0),
+        ("2026-04-05T00:00:00Z", TransactionType::Expense, 10.0),
+    ];
+    for (date, tx_type, amount) in movements {
+        let income = matches!(tx_type, TransactionType::Income);
+        create_transaction(
+            &state,
+            &company,
+            DateTime::parse_rfc3339_str(date).unwrap(),
+            "Movimiento",
+            tx_type,
+            if income { &sales } else { &rent },
+            (!income).then_some(bank),
+            income.then_some(bank),
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Renta abril",
+        FlowType::Expense,
+        &rent,
+        &bank,
+        None,
+        75.0,
+        DateTime::parse_rfc3339_str("2026-04-10T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/tiempo?granularity=month&from=2026-03-01&to=2026-05-01&metrics=real&items=false",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let buckets: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let buckets = buckets.as_array().unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0]["start"], "2026-03-01T00:00:00.000Z");
+    assert_eq!(buckets[0]["real_income"], 100.0);
+    assert_eq!(buckets[0]["real_expense"], 40.0);
+    assert_eq!(buckets[0]["cumulative_real"], 65.0);
+    assert_eq!(buckets[1]["cumulative_real"], 55.0);
+    assert!(buckets[0].get("planned_income").is_none());
+    assert!(buckets[0].get("transactions").is_none());
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/tiempo?mode=week&from=2026-04-06&to=2026-04-13&metrics=planned",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let buckets: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let buckets = buckets.as_array().unwrap();
+    assert_eq!(buckets.len(), 1);
+    assert_eq!(buckets[0]["planned_expense"], 75.0);
+    assert_eq!(buckets[0]["planned_entries"].as_array().unwrap().len(), 1);
+    assert!(buckets[0].get("real_income").is_none());
+
+    for query in [
+        "metrics=real,forecast",
+        "granularity=quarter",
+        "from=2026-05-01&to=2026-03-01",
+    ] {
+        let (status, _) = get_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            &format!("/api/tiempo?{query}"),
+            &token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn income_statement_compares_months_with_the_year_before() {
+    use alfredodev::state::update_transaction;
+    use chrono::{Datelike, Months, TimeZone, Utc};
+
+    let Some((ctx, setup)) = admin_company("statement", "Statement Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let today = Utc::now();
+    let this_month = Utc
+        .with_ymd_and_hms(today.year(), today.month(), 1, 12, 0, 0)
+        .unwrap();
+    let last_year = this_month - Months::new(12);
+    let mut rent_id = None;
+    for (date, kind, category, amount) in [
+        (this_month, TransactionType::Income, sales, 1_000.0),
+        (this_month, TransactionType::Expense, rent, 300.0),
+        (last_year, TransactionType::Income, sales, 800.0),
+    ] {
+        let (from, to) = if kind == TransactionType::Income {
+            (None, Some(account))
+        } else {
+            (Some(account), None)
+        };
+        let id = create_transaction(
+            &state,
+            &company,
+            DateTime::from_chrono(date),
+            "Resultado",
+            kind.clone(),
+            &category,
+            from,
+            to,
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        if kind == TransactionType::Expense {
+            rent_id = Some(id);
+        }
+    }
+
+    let statement = |shared: Arc<AppState>| {
+        let token = token.clone();
+        let host = host.clone();
+        async move {
+            let (status, body) = get_with_cookie(
+                build_app(shared),
+                &host,
+                "/api/admin/reports/income-statement?months=3",
+                &token,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+    let report = statement(shared.clone()).await;
+    assert_eq!(report["months"].as_array().unwrap().len(), 3);
+    assert_eq!(report["months"][2], this_month.format("%Y-%m").to_string());
+    assert_eq!(
+        report["previous_months"][2],
+        last_year.format("%Y-%m").to_string()
+    );
+    assert_eq!(report["income"]["rows"][0]["name"], "Ventas");
+    assert_eq!(report["income"]["rows"][0]["amounts"][2], 1_000.0);
+    assert_eq!(report["income"]["rows"][0]["previous_total"], 800.0);
+    assert_eq!(report["income"]["total"]["change"], 0.25);
+    assert_eq!(report["expense"]["total"]["total"], 300.0);
+    assert_eq!(report["net"]["amounts"][2], 700.0);
+    assert_eq!(
+        state
+            .monthly_summaries
+            .count_documents(doc! { "company_id": &company })
+            .await
+            .unwrap(),
+        6
+    );
+
+    // Editing a transaction drops its month's summary, so the next read
+    // sees the change.
+    update_transaction(
+        &state,
+        &rent_id.unwrap(),
+        &company,
+        DateTime::from_chrono(this_month),
+        "Resultado",
+        TransactionType::Expense,
+        &rent,
+        Some(account),
+        None,
+        450.0,
+        None,
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let report = statement(shared.clone()).await;
+    assert_eq!(report["expense"]["total"]["total"], 450.0);
+    assert_eq!(report["net"]["amounts"][2], 550.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/reports/income_statement",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Estado de resultados"));
+    assert!(body.contains("data-statement-net"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/reports/income_statement?months=36",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn fiscal_calendar_groups_the_income_statement_by_fiscal_period() {
+    use alfredodev::models::{FiscalCalendar, FiscalPattern};
+    use alfredodev::state::fiscal_period;
+
+    let Some((ctx, setup)) = admin_company("fiscal", "Fiscal Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Venta",
+        TransactionType::Income,
+        &sales,
+        None,
+        Some(account),
+        500.0,
+        None,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let statement = || {
+        let token = token.clone();
+        let host = host.clone();
+        let shared = shared.clone();
+        async move {
+            let (status, body) = get_with_cookie(
+                build_app(shared),
+                &host,
+                "/api/admin/reports/income-statement?months=3",
+                &token,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+    // Reading once stores summaries cut by calendar months.
+    let report = statement().await;
+    assert_eq!(
+        report["months"][2],
+        chrono::Utc::now().format("%Y-%m").to_string()
+    );
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/companies/{}/fiscal_calendar", company.to_hex()),
+        &token,
+        serde_json::json!({ "pattern": "4-4-5", "start_month": 1, "start_weekday": 0 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let saved: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(saved["pattern"], "4-4-5");
+    assert_eq!(saved["periods"].as_array().unwrap().len(), 12);
+
+    let calendar = FiscalCalendar {
+        pattern: FiscalPattern::Weeks445,
+        start_month: 1,
+        start_weekday: 0,
+    };
+    let current = fiscal_period(&calendar, chrono::Utc::now());
+    let report = statement().await;
+    assert_eq!(report["months"][2], current.key.as_str());
+    assert!(current.key.starts_with("FY"));
+    assert_eq!(report["income"]["rows"][0]["amounts"][2], 500.0);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/companies/{}/fiscal_calendar", company.to_hex()),
+        &token,
+        serde_json::json!({ "pattern": "13-periods" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("error"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/companies/{}/fiscal_calendar", company.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-fiscal-period=").count(), 12);
+    assert!(body.contains(&format!("data-fiscal-period=\"{}\"", current.key)));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn fx_report_records_realized_exchange_results_as_adjustments() {
+    let Some((ctx, setup)) = admin_company("fx", "FX Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let dollars = create_account(
+        &state,
+        &company,
+        "Banco USD",
+        AccountType::Bank,
+        "USD",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/planned-entries",
+        &token,
+        serde_json::json!({
+            "name": "Factura cliente",
+            "flow_type": "income",
+            "category_id": sales.to_hex(),
+            "account_expected_id": dollars.to_hex(),
+            "amount_estimated": 100.0,
+            "due_date": "2026-06-30T00:00:00Z",
+            "status": "planned",
+            "exchange_rate": 17.0,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let entry: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let entry_id = entry["id"].as_str().unwrap().to_string();
+
+    let collection = |rate: f64| {
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Cobro factura",
+            "transaction_type": "income",
+            "category_id": sales.to_hex(),
+            "account_to_id": dollars.to_hex(),
+            "amount": 100.0,
+            "planned_entry_id": entry_id,
+            "is_confirmed": true,
+            "exchange_rate": rate,
+        })
+    };
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        collection(0.0),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        collection(18.0),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+
+    let report_path = "/api/admin/reports/fx?from=2026-01-01&to=2026-12-31";
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, report_path, &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["lines"][0]["kind"], "collection");
+    assert_eq!(report["lines"][0]["result"], 100.0);
+    assert_eq!(report["lines"][0]["recorded"], serde_json::Value::Null);
+    assert_eq!(report["gains"], 100.0);
+    assert_eq!(report["pending"], 1);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/fx/record",
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let summary: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(summary["created"], 1);
+
+    let (_, body) = get_with_cookie(build_app(shared.clone()), &host, report_path, &token).await;
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["lines"][0]["recorded"], 100.0);
+    assert_eq!(report["pending"], 0);
+    let adjustment = state
+        .transactions
+        .find_one(doc! { "company_id": company, "fx_adjustment": { "$exists": true } })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(adjustment.transaction_type, TransactionType::Income);
+    assert_eq!(adjustment.amount, 100.0);
+    assert!(adjustment.account_to_id.is_none());
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/reports/fx?from=2026-01-01&to=2026-12-31",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-fx-line"));
+    assert!(!body.contains("data-fx-pending"));
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,472 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn security_page_reports_failed_logins_and_admin_actions() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Secure Co", "secure-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other = create_company(
+        &state,
+        "Other Secure Co",
+        "other-secure-co",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let admin_id = create_user(
+        &state,
+        "secure-admin@example.com",
+        "SECRET",
+        &[(company.clone(), UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    create_user(
+        &state,
+        "other-secure-admin@example.com",
+        "SECRET",
+        &[(other.clone(), UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let other_token = create_session(&state, "other-secure-admin@example.com")
+        .await
+        .unwrap();
+    create_user(
+        &state,
+        "secure-staff@example.com",
+        "SECRET",
+        &[(company.clone(), UserRole::Staff)],
+    )
+    .await
+    .unwrap();
+    let staff_token = create_session(&state, "secure-staff@example.com")
+        .await
+        .unwrap();
+    let host = "secure-co.miapp.local";
+
+    for _ in 0..5 {
+        let (status, _) = post_json_with_cookie(
+            build_app(shared.clone()),
+            host,
+            "/login",
+            "",
+            serde_json::json!({ "username": "secure-admin@example.com", "code": "000000" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let spare = create_category(&state, &company, "Spare", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let delete_path = format!("/admin/categories/{}/delete", spare.to_hex());
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &delete_path,
+        &token,
+        String::new(),
+    )
+    .await;
+    assert!(status.is_redirection());
+
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, "/admin/security", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-access-event").count(), 6);
+    assert_eq!(body.matches("data-security-anomaly").count(), 1);
+    assert!(body.contains("5 accesos fallidos en un día"));
+    assert!(body.contains(&format!("POST {delete_path}")));
+
+    let (status, csv) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/security?days=7&format=csv",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("fecha_utc,tipo,usuario,ip,detalle"));
+    assert_eq!(csv.matches(",login_failed,").count(), 5);
+    assert_eq!(csv.matches(",admin_action,").count(), 1);
+
+    // Another company's admin sees none of it; staff cannot open the page.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        "other-secure-co.miapp.local",
+        "/admin/security",
+        &other_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-access-event").count(), 0);
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/admin/security",
+        &staff_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn remember_me_cookie_renews_the_session_until_logout() {
+    use alfredodev::session::REFRESH_COOKIE_NAME;
+    use alfredodev::state::{create_refresh_token, delete_session, set_user_active};
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("remember-co")
+        .name("Remember Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (user_id, token) = UserFixture::new("remember-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("remember-co");
+    let refresh = create_refresh_token(&state, &user_id).await.unwrap();
+    delete_session(&state, &token).await.unwrap();
+
+    // The session is gone; the refresh cookie opens a new one and hands it out.
+    let get_me = |cookie: String| {
+        Request::builder()
+            .uri("/api/me")
+            .header("host", &host)
+            .header("cookie", cookie)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let res = build_app(shared.clone())
+        .oneshot(get_me(format!("{REFRESH_COOKIE_NAME}={refresh}")))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let renewed = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| value.strip_prefix(&format!("{SESSION_COOKIE_NAME}=")))
+        .and_then(|value| value.split(';').next())
+        .map(str::to_string)
+        .expect("renewed session cookie");
+    let (status, _) = get_with_cookie(build_app(shared.clone()), &host, "/api/me", &renewed).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A deactivated user cannot come back with it.
+    set_user_active(&state, &user_id, false).await.unwrap();
+    let res = build_app(shared.clone())
+        .oneshot(get_me(format!("{REFRESH_COOKIE_NAME}={refresh}")))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // Logging out forgets the refresh token along with the session.
+    set_user_active(&state, &user_id, true).await.unwrap();
+    let refresh = create_refresh_token(&state, &user_id).await.unwrap();
+    let session = create_session(&state, "remember-admin@example.com")
+        .await
+        .unwrap();
+    let req = Request::builder()
+        .method("POST")
+        .uri("/logout")
+        .header("host", &host)
+        .header(
+            "cookie",
+            format!(
+                "{}; {REFRESH_COOKIE_NAME}={refresh}",
+                session_cookie(&session)
+            ),
+        )
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = build_app(shared.clone())
+        .oneshot(get_me(format!("{REFRESH_COOKIE_NAME}={refresh}")))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn session_expiry_is_reported_and_renewed() {
+    use alfredodev::session::SESSION_EXPIRES_HEADER;
+    use alfredodev::state::delete_session;
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("expiry-co")
+        .name("Expiry Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("expiry-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("expiry-co");
+
+    // Every response of a browser session says how long it has left.
+    let req = Request::builder()
+        .uri("/api/me")
+        .header("host", &host)
+        .header("cookie", session_cookie(&token))
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let left: i64 = res.headers()[SESSION_EXPIRES_HEADER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(left > 60 * 60, "{left}");
+
+    // Two minutes left: the status reports it without renewing.
+    let soon = DateTime::from_millis(DateTime::now().timestamp_millis() + 90_000);
+    state
+        .sessions
+        .update_one(
+            doc! { "token": &token },
+            doc! { "$set": { "expires_at": soon } },
+        )
+        .await
+        .unwrap();
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), &host, "/api/session", &token).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(data["expires_in"].as_i64().unwrap() <= 90);
+    assert_eq!(data["warn_before"], 120);
+    assert_eq!(data["renew_url"], "/api/session/renew");
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/session/renew",
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(data["expires_in"].as_i64().unwrap() > 60 * 60);
+
+    // A session that is gone has to sign in again.
+    delete_session(&state, &token).await.unwrap();
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/session/renew",
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn staff_sign_in_links_follow_the_company_setting() {
+    use alfredodev::state::request_login_link;
+    use alfredodev::totp::{build_totp, generate_base32_secret_n};
+
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let mut state = ctx.state.clone();
+    let mailer = Arc::new(RecordingMailer::default());
+    state.mailer = mailer.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("link-co")
+        .name("Link Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, admin_token) = UserFixture::new("link-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let secret = generate_base32_secret_n(20);
+    UserFixture::new("link-staff@example.com")
+        .secret(&secret)
+        .staff_of(&company, &[])
+        .create(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("link-co");
+    let totp_login = || async {
+        let code = build_totp("Link Co", "link-staff@example.com", &secret)
+            .unwrap()
+            .generate_current()
+            .unwrap();
+        post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/login",
+            "",
+            serde_json::json!({ "username": "link-staff@example.com", "code": code }),
+        )
+        .await
+    };
+    let open_link = |token: String| {
+        let app = build_app(shared.clone());
+        let host = host.clone();
+        async move {
+            let req = Request::builder()
+                .uri(format!("/login/link?token={token}"))
+                .header("host", &host)
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(req).await.unwrap()
+        }
+    };
+
+    // Off by default: asking looks the same but no link is made.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/login/link",
+        "",
+        serde_json::json!({ "username": "link-staff@example.com" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(mailer.sent().is_empty());
+    assert!(
+        request_login_link(&state, "link-staff@example.com", None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // Links only: the TOTP code is refused and a link opens the session on
+    // the page it was asked from, once.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/security/login-links",
+        &admin_token,
+        "mode=alternative".to_string(),
+    )
+    .await;
+    assert!(status.is_redirection());
+    let (_, page) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/security",
+        &admin_token,
+    )
+    .await;
+    assert!(page.contains(r#"<option value="alternative" selected>"#));
+
+    let (status, body) = totp_login().await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body.contains("enlaces de acceso"), "{body}");
+    assert!(
+        request_login_link(&state, "link-admin@example.com", None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // The link is emailed to the username and only there.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/login/link",
+        "",
+        serde_json::json!({ "username": "link-staff@example.com", "next": "/tiempo" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "link-staff@example.com");
+    assert!(
+        sent[0].text.contains("/login/link?token="),
+        "{}",
+        sent[0].text
+    );
+    let token = mailer
+        .last_token_for("link-staff@example.com")
+        .expect("emailed token");
+    let res = open_link(token.clone()).await;
+    assert!(res.status().is_redirection());
+    assert_eq!(res.headers()[header::LOCATION], "/tiempo");
+    let session = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| value.strip_prefix(&format!("{SESSION_COOKIE_NAME}=")))
+        .and_then(|value| value.split(';').next())
+        .map(str::to_string)
+        .expect("session cookie");
+    let (status, _) = get_with_cookie(build_app(shared.clone()), &host, "/api/me", &session).await;
+    assert_eq!(status, StatusCode::OK);
+    let res = open_link(token).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // As a supplement the code works again; turning links off voids the
+    // ones already sent.
+    let (_, pending) = request_login_link(&state, "link-staff@example.com", None)
+        .await
+        .unwrap()
+        .expect("staff link");
+    for mode in ["supplement", "off"] {
+        let status = post_form_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/admin/security/login-links",
+            &admin_token,
+            format!("mode={mode}"),
+        )
+        .await;
+        assert!(status.is_redirection());
+        let (status, body) = totp_login().await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+    assert_eq!(open_link(pending).await.status(), StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
@@ -2,9 +2,11 @@
 mod common;
 
 use alfredodev::state::{
-    list_accounts, list_categories, list_contacts, list_forecasts, list_planned_entries,
-    list_recurring_plans, list_transactions,
+    MIGRATIONS, list_accounts, list_categories, list_contacts, list_forecasts,
+    list_planned_entries, list_recurring_plans, list_transactions, migration_status,
+    run_migrations,
 };
+use bson::{Document, doc};
 
 #[tokio::test]
 async fn seed_populates_finance_collections() {
@@ -34,3 +36,50 @@ async fn seed_populates_finance_collections() {
 
     common::teardown(Some(ctx)).await;
 }
+
+#[tokio::test]
+async fn migrations_run_once_and_dry_run_changes_nothing() {
+    let Some(ctx) = common::setup_state().await else {
+        return;
+    };
+    let db = ctx.state.users.client().database(&ctx.db_name);
+
+    // Startup already applied and recorded every migration.
+    let status = migration_status(&db).await.unwrap();
+    assert_eq!(status.len(), MIGRATIONS.len());
+    assert!(status.iter().all(|s| s.applied_at.is_some()));
+    assert!(run_migrations(&db, false).await.unwrap().is_empty());
+
+    // A legacy user left behind, with migration 1 pending again.
+    let users = db.collection::<Document>("users");
+    users
+        .insert_one(doc! { "email": "legacy@example.com", "secret": "SECRET" })
+        .await
+        .unwrap();
+    db.collection::<Document>("migrations")
+        .delete_one(doc! { "_id": 1 })
+        .await
+        .unwrap();
+
+    let dry = run_migrations(&db, true).await.unwrap();
+    assert_eq!(dry.len(), 1);
+    assert_eq!(dry[0].documents, 1);
+    let legacy = users
+        .find_one(doc! { "email": "legacy@example.com" })
+        .await
+        .unwrap();
+    assert!(legacy.is_some(), "dry run leaves the document untouched");
+    assert!(migration_status(&db).await.unwrap()[0].applied_at.is_none());
+
+    let applied = run_migrations(&db, false).await.unwrap();
+    assert_eq!(applied.len(), 1);
+    let migrated = users
+        .find_one(doc! { "username": "legacy@example.com" })
+        .await
+        .unwrap()
+        .expect("email renamed to username");
+    assert!(!migrated.contains_key("email"));
+    assert!(migration_status(&db).await.unwrap()[0].applied_at.is_some());
+
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,166 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use alfredodev::state::get_company_by_id;
+use common::harness::*;
+
+#[tokio::test]
+async fn storage_quota_is_set_by_superadmins_and_enforced_on_uploads() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Quota Co", "quota-co", "MXN", true, None)
+        .await
+        .unwrap();
+    create_user_with_permissions(
+        &state,
+        "quota-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin, vec![])],
+    )
+    .await
+    .unwrap();
+    create_user_with_permissions(
+        &state,
+        "quota-root@example.com",
+        "SECRET",
+        &[(company, UserRole::Staff, vec![])],
+    )
+    .await
+    .unwrap();
+    state
+        .users
+        .update_one(
+            doc! { "username": "quota-root@example.com" },
+            doc! { "$set": { "is_superadmin": true } },
+        )
+        .await
+        .unwrap();
+    let admin_token = create_session(&state, "quota-admin@example.com")
+        .await
+        .unwrap();
+    let root_token = create_session(&state, "quota-root@example.com")
+        .await
+        .unwrap();
+    let host = "quota-co.miapp.local";
+    let storage_path = format!("/admin/companies/{}/storage", company.to_hex());
+    let storage_api = format!("/api/admin/companies/{}/storage", company.to_hex());
+    let branding_path = format!("/admin/companies/{}/branding", company.to_hex());
+    let logo: &[u8] = b"\x89PNG\r\n\x1a\nFAKEPNGDATA";
+
+    // The company admin sees the usage but cannot change the quota.
+    let (status, body) =
+        get_with_cookie(build_app(shared.clone()), host, &storage_path, &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("sin límite"));
+    assert!(!body.contains("name=\"quota_mb\""));
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &storage_path,
+        &admin_token,
+        "quota_mb=1".into(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &storage_api,
+        &admin_token,
+        serde_json::json!({ "quota_bytes": 10 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, location, _) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        host,
+        &storage_path,
+        &root_token,
+        "quota_mb=1".into(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some(storage_path.as_str()));
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert_eq!(stored.storage.quota_bytes, Some(1024 * 1024));
+
+    // Uploads are counted against the company.
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[("logo", Some("logo.png"), logo)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert_eq!(stored.storage.used_bytes, logo.len() as i64);
+
+    // Saving the quota recounts the files on disk.
+    let quota = logo.len() as i64 + 10;
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &storage_api,
+        &root_token,
+        serde_json::json!({ "quota_bytes": quota }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(data["used_bytes"], logo.len() as i64);
+    assert_eq!(data["quota_bytes"], quota);
+
+    // A file that does not fit is refused with the sizes involved.
+    let receipt: &[u8] = b"%PDF-1.7 receipt larger than what is left";
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/transactions/receipts",
+        &admin_token,
+        &[("receipt", Some("ticket.pdf"), receipt)],
+    )
+    .await;
+    assert_eq!(status, StatusCode::INSUFFICIENT_STORAGE);
+    assert!(body.contains("excede el espacio"));
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[("logo", Some("logo.png"), b"\x89PNG\r\n\x1a\nA LARGER LOGO")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("excede el espacio"));
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert_eq!(stored.storage.used_bytes, logo.len() as i64);
+
+    // Removing the logo gives its bytes back.
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &branding_path,
+        &admin_token,
+        &[("remove_logo", None, b"true")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let stored = get_company_by_id(&state, &company).await.unwrap().unwrap();
+    assert_eq!(stored.storage.used_bytes, 0);
+
+    let (status, body) = get_with_cookie(build_app(shared), host, &storage_api, &admin_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(data["used_bytes"], 0);
+
+    let _ = std::fs::remove_dir_all(format!("uploads/branding/{}", company.to_hex()));
+    common::teardown(Some(ctx)).await;
+}
@@ -0,0 +1,1447 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use common::harness::*;
+
+#[tokio::test]
+async fn transaction_json_mutations_scope_and_report_side_effects() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company_a = create_company(
+        &state,
+        "Transaction Mutation A",
+        "transaction-mutation-a",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let company_b = create_company(
+        &state,
+        "Transaction Mutation B",
+        "transaction-mutation-b",
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let admin_id = create_user(
+        &state,
+        "transaction-mutation-admin@example.com",
+        "SECRET",
+        &[(company_a, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let host_a = "transaction-mutation-a.miapp.local";
+
+    let category_a = create_category(
+        &state,
+        &company_a,
+        "Transaction Mutation Category A",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let category_b = create_category(
+        &state,
+        &company_b,
+        "Transaction Mutation Category B",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let account_a = create_account(
+        &state,
+        &company_a,
+        "Transaction Mutation Account A",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let planned_entry = create_planned_entry(
+        &state,
+        &company_a,
+        None,
+        None,
+        None,
+        "Transaction Mutation Planned Entry",
+        FlowType::Expense,
+        &category_a,
+        &account_a,
+        None,
+        100.0,
+        DateTime::parse_rfc3339_str("2026-07-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/transactions",
+        &token,
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Transaction mutation created",
+            "transaction_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_from_id": account_a.to_hex(),
+            "amount": 42.5,
+            "planned_entry_id": planned_entry.to_hex(),
+            "is_confirmed": true,
+            "notes": "created"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).expect("create response JSON");
+    assert_eq!(
+        created["side_effects"]["planned_entry_recalculated"],
+        planned_entry.to_hex()
+    );
+    let transaction_id = created["id"].as_str().expect("created id").to_string();
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/transactions/{transaction_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Transaction mutation created"));
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/transactions/{transaction_id}/update"),
+        &token,
+        serde_json::json!({
+            "date": "2026-07-02T12:00:00Z",
+            "description": "Transaction mutation updated",
+            "transaction_type": "expense",
+            "category_id": category_a.to_hex(),
+            "account_from_id": account_a.to_hex(),
+            "amount": 50.0,
+            "planned_entry_id": planned_entry.to_hex(),
+            "is_confirmed": false,
+            "notes": "updated"
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/transactions/{transaction_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let updated: serde_json::Value = serde_json::from_str(&body).expect("detail response JSON");
+    assert_eq!(updated["description"], "Transaction mutation updated");
+    assert_eq!(updated["amount"], 50.0);
+    assert_eq!(updated["is_confirmed"], false);
+
+    let app = build_app(shared.clone());
+    let (status, body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/admin/transactions/{transaction_id}/row/edit"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-inline-tx-form"));
+    assert!(body.contains("Transaction mutation updated"));
+
+    let app = build_app(shared.clone());
+    let (status, _location, body) = post_form_with_cookie_response(
+        app,
+        host_a,
+        &format!("/admin/transactions/{transaction_id}/row"),
+        &token,
+        format!(
+            "date=2026-07-03T12%3A00%3A00Z&transaction_type=expense&description=Inline&category_id={}&account_from_id={}&amount=abc",
+            category_a.to_hex(),
+            account_a.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body.contains("data-inline-tx-form"));
+
+    let app = build_app(shared.clone());
+    let (status, _location, body) = post_form_with_cookie_response(
+        app,
+        host_a,
+        &format!("/admin/transactions/{transaction_id}/row"),
+        &token,
+        format!(
+            "date=2026-07-03T12%3A00%3A00Z&transaction_type=expense&description=Inline+edited&category_id={}&account_from_id={}&amount=60&planned_entry_id={}&is_confirmed=true",
+            category_a.to_hex(),
+            account_a.to_hex(),
+            planned_entry.to_hex()
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Inline edited"));
+    assert!(body.contains("data-inline-tx-edit"));
+
+    let app = build_app(shared.clone());
+    let (status, _body) = post_json_with_cookie(
+        app,
+        host_a,
+        "/api/admin/transactions",
+        &token,
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Bad tenant ref",
+            "transaction_type": "expense",
+            "category_id": category_b.to_hex(),
+            "amount": 1.0,
+            "is_confirmed": true
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = build_app(shared.clone());
+    let (status, body) = post_json_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/transactions/{transaction_id}/delete"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let deleted: serde_json::Value = serde_json::from_str(&body).expect("delete response JSON");
+    assert_eq!(
+        deleted["side_effects"]["planned_entry_recalculated"],
+        planned_entry.to_hex()
+    );
+
+    let app = build_app(shared);
+    let (status, _body) = get_with_cookie(
+        app,
+        host_a,
+        &format!("/api/admin/transactions/{transaction_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn pending_transactions_stay_out_of_balances_until_bulk_confirmed() {
+    let Some((ctx, setup)) = admin_company("pending", "Pending Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = create_company(&state, "Pending Other", "pending-other", "MXN", true, None)
+        .await
+        .unwrap();
+
+    let category = create_category(&state, &company, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let other_category = create_category(&state, &other, "Rent", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let other_account =
+        create_account(&state, &other, "Bank", AccountType::Bank, "MXN", true, None)
+            .await
+            .unwrap();
+    let due = DateTime::parse_rfc3339_str("2099-01-15T00:00:00Z").unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Office rent",
+        FlowType::Expense,
+        &category,
+        &account,
+        None,
+        200.0,
+        due,
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let draft = create_transaction(
+        &state,
+        &company,
+        DateTime::now(),
+        "Draft rent payment",
+        TransactionType::Expense,
+        &category,
+        Some(account),
+        None,
+        200.0,
+        Some(entry),
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let foreign_draft = create_transaction(
+        &state,
+        &other,
+        DateTime::now(),
+        "Foreign draft",
+        TransactionType::Expense,
+        &other_category,
+        Some(other_account),
+        None,
+        50.0,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let entry_status = |entries: Vec<alfredodev::models::PlannedEntry>| {
+        entries
+            .into_iter()
+            .find(|e| e.id == Some(entry))
+            .unwrap()
+            .status
+    };
+    assert_eq!(
+        entry_status(list_planned_entries(&state).await.unwrap()),
+        PlannedStatus::Planned
+    );
+    let overview = company_overview(&state, &company, DateTime::now())
+        .await
+        .unwrap();
+    assert_eq!(overview.cash_position, 0.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/pending",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.matches("data-pending-transaction").count(), 1);
+    assert!(body.contains("Draft rent payment"));
+    assert!(!body.contains("Foreign draft"));
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/pending",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let pending: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(pending.as_array().unwrap().len(), 1);
+
+    // Ids from another company are ignored rather than confirmed.
+    let (status, location, _body) = post_form_with_cookie_response(
+        build_app(shared.clone()),
+        &host,
+        "/admin/transactions/confirm",
+        &token,
+        format!("ids={}&ids={}", draft.to_hex(), foreign_draft.to_hex()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(
+        location.as_deref(),
+        Some("/admin/transactions/pending?confirmed=1")
+    );
+
+    assert_eq!(
+        entry_status(list_planned_entries(&state).await.unwrap()),
+        PlannedStatus::Covered
+    );
+    let overview = company_overview(&state, &company, DateTime::now())
+        .await
+        .unwrap();
+    assert_eq!(overview.cash_position, -200.0);
+    let foreign = list_transactions(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|tx| tx.id == Some(foreign_draft))
+        .unwrap();
+    assert!(!foreign.is_confirmed);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/confirm",
+        &token,
+        serde_json::json!({ "ids": [draft.to_hex()] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()["confirmed"],
+        0
+    );
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        &host,
+        "/admin/transactions/pending",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("No hay movimientos pendientes"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn receipt_upload_prefills_form_and_links_to_created_transaction() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let state = ctx.state.clone();
+    let shared = Arc::new(state.clone());
+
+    let company = create_company(&state, "Receipts Co", "receipts-co", "MXN", true, None)
+        .await
+        .unwrap();
+    let other = create_company(&state, "Receipts B", "receipts-b", "MXN", true, None)
+        .await
+        .unwrap();
+    let admin_id = create_user(
+        &state,
+        "receipts-admin@example.com",
+        "SECRET",
+        &[(company, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let admin = get_user_by_id(&state, &admin_id).await.unwrap().unwrap();
+    let token = create_session(&state, &admin.username).await.unwrap();
+    let other_admin_id = create_user(
+        &state,
+        "receipts-other@example.com",
+        "SECRET",
+        &[(other, UserRole::Admin)],
+    )
+    .await
+    .unwrap();
+    let other_admin = get_user_by_id(&state, &other_admin_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let other_token = create_session(&state, &other_admin.username).await.unwrap();
+    let host = "receipts-co.miapp.local";
+    let category = create_category(&state, &company, "Receipts", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Receipts Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let (status, _) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/transactions/receipts",
+        &token,
+        &[("receipt", Some("notes.txt"), b"plain text")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let (status, body) = post_multipart_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/transactions/receipts",
+        &token,
+        &[("receipt", Some("ticket.png"), b"\x89PNG fake image")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let uploaded: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let receipt_id = uploaded["receipt_id"].as_str().unwrap().to_string();
+
+    // The form carries the receipt so the new transaction gets linked to it.
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        host,
+        &format!("/admin/transactions/new?receipt={receipt_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&format!("name=\"receipt_id\" value=\"{receipt_id}\"")));
+    assert!(body.contains("ticket.png"));
+
+    // Receipts of another company are not reachable.
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        "receipts-b.miapp.local",
+        &format!("/admin/transactions/receipts/{receipt_id}"),
+        &other_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        host,
+        "/api/admin/transactions",
+        &token,
+        serde_json::json!({
+            "date": "2026-07-01T12:00:00Z",
+            "description": "Ticket",
+            "transaction_type": "expense",
+            "category_id": category.to_hex(),
+            "account_from_id": account.to_hex(),
+            "amount": 99.9,
+            "receipt_id": receipt_id,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(created["side_effects"]["receipt_attached"], true);
+    let receipt = state
+        .receipts
+        .find_one(doc! { "_id": bson::oid::ObjectId::parse_str(&receipt_id).unwrap() })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        receipt.transaction_id.map(|id| id.to_hex()).as_deref(),
+        created["id"].as_str()
+    );
+
+    let (status, body) = get_with_cookie(
+        build_app(shared),
+        host,
+        &format!("/admin/transactions/receipts/{receipt_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("fake image"));
+
+    let _ = std::fs::remove_dir_all(format!("uploads/receipts/{}", company.to_hex()));
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn transactions_bulk_edit_reports_per_row_results() {
+    let Some((ctx, setup)) = admin_company("bulk", "Bulk Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let other = create_company(&state, "Bulk Other", "bulk-other", "MXN", true, None)
+        .await
+        .unwrap();
+
+    let imported = create_category(&state, &company, "Imported", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let office = create_category(&state, &company, "Office", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let sales = create_category(&state, &company, "Sales", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let foreign_category = create_category(&state, &other, "Office", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Bank",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let other_account =
+        create_account(&state, &other, "Bank", AccountType::Bank, "MXN", true, None)
+            .await
+            .unwrap();
+
+    let mut ids = Vec::new();
+    for description in ["Paper", "Toner"] {
+        let id = create_transaction(
+            &state,
+            &company,
+            DateTime::now(),
+            description,
+            TransactionType::Expense,
+            &imported,
+            Some(account),
+            None,
+            10.0,
+            None,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        ids.push(id.to_hex());
+    }
+    let foreign = create_transaction(
+        &state,
+        &other,
+        DateTime::now(),
+        "Foreign",
+        TransactionType::Expense,
+        &foreign_category,
+        Some(other_account),
+        None,
+        10.0,
+        None,
+        None,
+        false,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let bulk = |payload: serde_json::Value| {
+        let host = host.clone();
+        let app = build_app(shared.clone());
+        let token = token.clone();
+        async move {
+            let (status, body) =
+                post_json_with_cookie(app, &host, "/api/admin/transactions/bulk", &token, payload)
+                    .await;
+            (
+                status,
+                serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            )
+        }
+    };
+    let stored = |id: &str| {
+        let state = state.clone();
+        let id = mongodb::bson::oid::ObjectId::parse_str(id).unwrap();
+        async move {
+            alfredodev::state::get_transaction_by_id(&state, &id)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+    };
+
+    // Ids of another company are reported, not updated.
+    let mut targets = ids.clone();
+    targets.push(foreign.to_hex());
+    let (status, body) = bulk(serde_json::json!({
+        "ids": targets,
+        "action": "set_category",
+        "category_id": office.to_hex(),
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    assert_eq!(body["updated"], 2);
+    assert_eq!(body["failed"], 1);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results[2]["id"], foreign.to_hex());
+    assert_eq!(results[2]["ok"], false);
+    assert!(results[2]["error"].is_string());
+    assert_eq!(stored(&ids[0]).await.category_id, office);
+    assert_eq!(stored(&ids[1]).await.category_id, office);
+    assert_eq!(
+        stored(&foreign.to_hex()).await.category_id,
+        foreign_category
+    );
+
+    // Categories of the wrong flow or company fail per row.
+    for category in [sales, foreign_category] {
+        let (status, body) = bulk(serde_json::json!({
+            "ids": [ids[0]],
+            "action": "set_category",
+            "category_id": category.to_hex(),
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["failed"], 1);
+        assert_eq!(stored(&ids[0]).await.category_id, office);
+    }
+
+    for _ in 0..2 {
+        let (status, body) = bulk(serde_json::json!({
+            "ids": ids,
+            "action": "add_tag",
+            "tag": " importado ",
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["updated"], 2);
+    }
+    assert_eq!(stored(&ids[0]).await.tags, vec!["importado".to_string()]);
+
+    let (status, body) = bulk(serde_json::json!({ "ids": ids, "action": "confirm" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["updated"], 2);
+    assert!(stored(&ids[1]).await.is_confirmed);
+
+    let (status, _) = bulk(serde_json::json!({ "ids": ids, "action": "add_tag" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = bulk(serde_json::json!({ "ids": ids, "action": "archive" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions/data",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let items: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|item| item["tags"] == serde_json::json!(["importado"]))
+    );
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn transactions_are_deduplicated_and_found_by_external_id() {
+    use alfredodev::state::create_api_token;
+
+    let Some((ctx, setup)) = admin_company("external", "External Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        admin_id,
+        token,
+        host,
+    } = setup;
+    let other = CompanyFixture::new("external-other")
+        .name("External Other")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, other_token) = UserFixture::new("external-other@example.com")
+        .admin_of(&other)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+
+    let category = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let payload = |description: &str, external_id: &str| {
+        serde_json::json!({
+            "date": "2026-03-02T00:00:00Z",
+            "description": description,
+            "transaction_type": "income",
+            "category_id": category.to_hex(),
+            "account_to_id": account.to_hex(),
+            "amount": 250.0,
+            "external_id": external_id,
+            "bank_reference": "SPEI-0001"
+        })
+    };
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        payload("Cobro", "erp-1"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let id = created["id"].as_str().unwrap().to_string();
+
+    // Sending the same movement again returns the one already recorded.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        payload("Cobro", "erp-1"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "body: {body}");
+    let repeated: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(repeated["id"], id.as_str());
+    assert_eq!(repeated["duplicate"], true);
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        payload("Otro cobro", "erp-2"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let second: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let second_id = second["id"].as_str().unwrap();
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/transactions/{second_id}/update"),
+        &token,
+        payload("Otro cobro", "erp-1"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/v1/transactions/by_external/erp-1",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let found: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(found["id"], id.as_str());
+    assert_eq!(found["external_id"], "erp-1");
+    assert_eq!(found["bank_reference"], "SPEI-0001");
+
+    // Integrators read it with a personal access token.
+    let api_token = create_api_token(&state, &admin_id, "erp").await.unwrap();
+    let req = Request::builder()
+        .uri("/api/v1/transactions/by_external/erp-2")
+        .header("host", &host)
+        .header(header::AUTHORIZATION, format!("Bearer {api_token}"))
+        .body(Body::empty())
+        .unwrap();
+    let res = build_app(shared.clone()).oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // External ids are per company.
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &tenant_host("external-other"),
+        "/api/v1/transactions/by_external/erp-1",
+        &other_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn tax_profiles_apply_to_movements_and_feed_the_monthly_report() {
+    use alfredodev::state::set_category_tax_profile;
+    use chrono::{Datelike, TimeZone, Utc};
+
+    let Some((ctx, setup)) = admin_company("tax", "Tax Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let mut profile_ids = Vec::new();
+    for payload in [
+        serde_json::json!({ "name": "IVA 16%", "rate": 16.0, "is_default": true }),
+        serde_json::json!({ "name": "Exento", "exempt": true }),
+    ] {
+        let (status, body) = post_json_with_cookie(
+            build_app(shared.clone()),
+            &host,
+            "/api/admin/tax_profiles",
+            &token,
+            payload,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+        profile_ids.push(created["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/tax_profiles",
+        &token,
+        serde_json::json!({ "name": "Excesivo", "rate": 120.0 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let rent = create_category(&state, &company, "Renta", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    let supplies = create_category(&state, &company, "Papelería", FlowType::Expense, None, None)
+        .await
+        .unwrap();
+    set_category_tax_profile(
+        &state,
+        &company,
+        &rent,
+        Some(profile_ids[1].parse().unwrap()),
+    )
+    .await
+    .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let today = Utc::now();
+    let this_month = Utc
+        .with_ymd_and_hms(today.year(), today.month(), 1, 12, 0, 0)
+        .unwrap();
+    for (kind, category, amount) in [
+        (TransactionType::Income, sales, 1_160.0),
+        (TransactionType::Expense, rent, 500.0),
+    ] {
+        let (from, to) = if kind == TransactionType::Income {
+            (None, Some(account))
+        } else {
+            (Some(account), None)
+        };
+        create_transaction(
+            &state,
+            &company,
+            DateTime::from_chrono(this_month),
+            "Impuesto",
+            kind,
+            &category,
+            from,
+            to,
+            amount,
+            None,
+            None,
+            true,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    // A rate typed on the movement replaces the category's profile.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/transactions",
+        &token,
+        serde_json::json!({
+            "date": this_month.to_rfc3339(),
+            "description": "Hojas",
+            "transaction_type": "expense",
+            "category_id": supplies.to_hex(),
+            "account_from_id": account.to_hex(),
+            "amount": 116.0,
+            "tax_rate": 8.0
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!(
+            "/api/admin/transactions/{}",
+            created["id"].as_str().unwrap()
+        ),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let detail: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(detail["tax_name"], "Manual");
+    assert_eq!(detail["tax_rate"], 8.0);
+    assert_eq!(detail["tax_amount"], 8.59);
+
+    let month = this_month.format("%Y-%m").to_string();
+    let report = |shared: Arc<AppState>| {
+        let token = token.clone();
+        let host = host.clone();
+        let path = format!("/api/admin/reports/taxes?month={month}");
+        async move {
+            let (status, body) = get_with_cookie(build_app(shared), &host, &path, &token).await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        }
+    };
+    let taxes = report(shared.clone()).await;
+    assert_eq!(taxes["month"], month);
+    let lines = taxes["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["name"], "IVA 16%");
+    assert_eq!(lines[0]["collected_base"], 1_000.0);
+    assert_eq!(lines[0]["collected"], 160.0);
+    assert_eq!(lines[1]["name"], "Manual");
+    assert_eq!(lines[1]["paid"], 8.59);
+    assert_eq!(lines[2]["name"], "Exento");
+    assert_eq!(lines[2]["paid_base"], 500.0);
+    assert!((taxes["net"].as_f64().unwrap() - 151.41).abs() < 1e-6);
+    assert_eq!(taxes["untaxed_count"], 0);
+
+    // Without a default the sale has no tax; the typed rate stays.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/tax_profiles/{}/default", profile_ids[0]),
+        &token,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let taxes = report(shared.clone()).await;
+    assert_eq!(taxes["collected"], 0.0);
+    assert_eq!(taxes["paid"], 8.59);
+    assert_eq!(taxes["untaxed_count"], 1);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/reports/taxes?month={month}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-untaxed"));
+    assert!(body.contains("Saldo a favor"));
+    let (status, _) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/reports/taxes?month=2026-13",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn refunds_net_out_of_balances_and_planned_entry_coverage() {
+    use alfredodev::state::{account_balance_at, get_planned_entry_by_id};
+
+    let Some((ctx, setup)) = admin_company("refund", "Refund Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+
+    let sales = create_category(&state, &company, "Ventas", FlowType::Income, None, None)
+        .await
+        .unwrap();
+    let account = create_account(
+        &state,
+        &company,
+        "Banco",
+        AccountType::Bank,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let entry = create_planned_entry(
+        &state,
+        &company,
+        None,
+        None,
+        None,
+        "Pedido 12",
+        FlowType::Income,
+        &sales,
+        &account,
+        None,
+        1_000.0,
+        DateTime::parse_rfc3339_str("2026-07-01T00:00:00Z").unwrap(),
+        PlannedStatus::Planned,
+        None,
+    )
+    .await
+    .unwrap();
+    let sale = create_transaction(
+        &state,
+        &company,
+        DateTime::parse_rfc3339_str("2026-06-20T00:00:00Z").unwrap(),
+        "Pedido 12",
+        TransactionType::Income,
+        &sales,
+        None,
+        Some(account),
+        1_000.0,
+        Some(entry),
+        None,
+        true,
+        None,
+        None,
+        None,
+        Some("MXN".into()),
+        None,
+    )
+    .await
+    .unwrap();
+    let refund = |amount: f64| {
+        let token = token.clone();
+        let host = host.clone();
+        let shared = shared.clone();
+        async move {
+            post_json_with_cookie(
+                build_app(shared),
+                &host,
+                &format!("/api/admin/transactions/{}/refund", sale.to_hex()),
+                &token,
+                serde_json::json!({ "amount": amount, "date": "2026-06-25T00:00:00Z" }),
+            )
+            .await
+        }
+    };
+
+    let (status, body) = refund(300.0).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let refund_id: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let refund_id = refund_id["id"].as_str().unwrap().to_string();
+    // Only 700 is left to give back.
+    let (status, _) = refund(800.0).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let balance = account_balance_at(
+        &state,
+        &account,
+        DateTime::parse_rfc3339_str("2026-06-30T00:00:00Z").unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!((balance - 700.0).abs() < 0.001, "balance {balance}");
+    let entry = get_planned_entry_by_id(&state, &entry)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.status, PlannedStatus::PartiallyCovered);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/transactions/{refund_id}"),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(detail["refund_of"], sale.to_hex());
+    assert_eq!(detail["amount"], -300.0);
+    assert_eq!(detail["transaction_type"], "income");
+    assert_eq!(detail["description"], "Reembolso: Pedido 12");
+
+    // A refund cannot be refunded again.
+    let (status, _) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/api/admin/transactions/{refund_id}/refund"),
+        &token,
+        serde_json::json!({ "amount": 10.0, "date": "2026-06-26T00:00:00Z" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/edit", sale.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-refunds"));
+    assert_eq!(body.matches("data-refund class").count(), 1);
+    assert!(body.contains("Registrar reembolso"));
+
+    common::teardown(Some(ctx)).await;
+}
+
+#[tokio::test]
+async fn card_holds_stay_out_of_balances_until_captured() {
+    let Some((ctx, setup)) = admin_company("hold", "Hold Co").await else {
+        return;
+    };
+    let AdminCompany {
+        state,
+        shared,
+        company,
+        token,
+        host,
+        ..
+    } = setup;
+    let card = create_account(
+        &state,
+        &company,
+        "Tarjeta",
+        AccountType::CreditCard,
+        "MXN",
+        true,
+        None,
+    )
+    .await
+    .unwrap();
+    let category = create_category(
+        &state,
+        &company,
+        "Restaurantes",
+        FlowType::Expense,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    let charge = |description: &'static str, amount: f64| {
+        let state = state.clone();
+        async move {
+            create_transaction(
+                &state,
+                &company,
+                DateTime::parse_rfc3339_str("2026-03-01T00:00:00Z").unwrap(),
+                description,
+                TransactionType::Expense,
+                &category,
+                Some(card),
+                None,
+                amount,
+                None,
+                None,
+                true,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+        }
+    };
+    let dinner = charge("Cena", 100.0).await;
+    let hotel = charge("Hotel", 50.0).await;
+    let gas = charge("Gasolina", 30.0).await;
+    let balance = || async {
+        alfredodev::state::account_balance_at(&state, &card, DateTime::now())
+            .await
+            .unwrap()
+    };
+    let before = balance().await;
+    let api = |path: String, payload: serde_json::Value| {
+        let app = build_app(shared.clone());
+        let (host, token) = (host.clone(), token.clone());
+        async move { post_json_with_cookie(app, &host, &path, &token, payload).await }
+    };
+
+    // A hold leaves the balance until it is captured for the final amount.
+    let (status, body) = api(
+        format!("/api/admin/transactions/{}/hold", dinner.to_hex()),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("\"status\":\"held\""));
+    assert_eq!(balance().await, before + 100.0);
+    let pending = alfredodev::state::list_pending_transactions(&state, &company)
+        .await
+        .unwrap();
+    assert!(pending.is_empty());
+
+    let (status, body) = api(
+        format!("/api/admin/transactions/{}/capture", dinner.to_hex()),
+        serde_json::json!({ "amount": 120.0 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("\"status\":\"captured\""));
+    assert!(body.contains("\"authorized_amount\":100.0"));
+    assert_eq!(balance().await, before - 20.0);
+
+    // Placed from the edit page, then voided: it never comes back.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/hold", hotel.to_hex()),
+        &token,
+        "days=3".into(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, body) = api(
+        format!("/api/admin/transactions/{}/void", hotel.to_hex()),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body.contains("\"status\":\"voided\""));
+    let (status, _) = api(
+        format!("/api/admin/transactions/{}/capture", hotel.to_hex()),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(balance().await, before + 30.0);
+
+    let (status, body) = get_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("/admin/transactions/{}/edit", hotel.to_hex()),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-hold-status=\"voided\""));
+
+    // Stale holds are voided by the expiry task.
+    let (status, _) = api(
+        format!("/api/admin/transactions/{}/hold", gas.to_hex()),
+        serde_json::json!({ "days": 1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let tomorrow =
+        DateTime::from_millis(DateTime::now().timestamp_millis() + 2 * 24 * 60 * 60 * 1000);
+    assert_eq!(
+        alfredodev::state::expire_card_holds(&state, tomorrow)
+            .await
+            .unwrap(),
+        1
+    );
+    let gas_tx = alfredodev::state::get_transaction_by_id(&state, &gas)
+        .await
+        .unwrap()
+        .unwrap();
+    let hold = gas_tx.hold.unwrap();
+    assert_eq!(hold.status, alfredodev::models::HoldStatus::Voided);
+    assert!(hold.expired);
+    assert!(!gas_tx.is_confirmed);
+
+    common::teardown(Some(ctx)).await;
+}
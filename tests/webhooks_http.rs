@@ -0,0 +1,177 @@
+#[path = "common/mod.rs"]
+mod common;
+
+use std::time::Duration;
+
+use alfredodev::state::{get_company_by_id, webhook_signature};
+use axum::{body::Bytes, http::HeaderMap};
+use common::harness::*;
+use tokio::sync::mpsc;
+
+/// Listens on a local port and hands over every POST it receives.
+async fn webhook_receiver() -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send((headers, body));
+                StatusCode::NO_CONTENT
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, receiver)
+}
+
+#[tokio::test]
+async fn generated_forecasts_are_announced_to_the_company_webhook() {
+    let ctx = match common::setup_state().await {
+        Some(c) => c,
+        None => return,
+    };
+    let mut state = ctx.state.clone();
+    // The receiver below listens on loopback.
+    state.webhooks_private_networks = true;
+    let shared = Arc::new(state.clone());
+
+    let company = CompanyFixture::new("hook-co")
+        .name("Hook Co")
+        .create(&state)
+        .await
+        .unwrap();
+    let (_, token) = UserFixture::new("hook-admin@example.com")
+        .admin_of(&company)
+        .create_with_session(&state)
+        .await
+        .unwrap();
+    let host = tenant_host("hook-co");
+    let path = format!("/admin/companies/{}/webhooks", company.to_hex());
+    let (url, mut received) = webhook_receiver().await;
+
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        "url=bi.example.com&forecast_completed=true".to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("url={url}&forecast_completed=true"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let settings = get_company_by_id(&state, &company)
+        .await
+        .unwrap()
+        .unwrap()
+        .webhooks;
+    assert_eq!(settings.url, url);
+    assert!(settings.secret.starts_with("whsec_"));
+
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        "/api/admin/forecasts/generate",
+        &token,
+        serde_json::json!({
+            "start_date": "2030-01-01T00:00:00Z",
+            "end_date": "2030-01-31T23:59:59Z",
+            "initial_balance": 100.0,
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "body: {body}");
+    let created: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .expect("webhook not delivered")
+        .unwrap();
+    assert_eq!(headers["x-webhook-event"], "forecast.completed");
+    assert_eq!(
+        headers["x-webhook-signature"].to_str().unwrap(),
+        webhook_signature(&settings.secret, &body)
+    );
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["event"], "forecast.completed");
+    assert_eq!(payload["forecast_id"], created["group_id"]);
+    assert_eq!(payload["forecasts"].as_array().unwrap().len(), 3);
+    assert_eq!(payload["forecasts"][0]["initial_balance"], 100.0);
+
+    // The ping from the settings page goes to the same place.
+    let (status, body) = post_json_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &format!("{path}/test"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("data-webhook-message"), "body: {body}");
+    let (headers, _) = received.recv().await.unwrap();
+    assert_eq!(headers["x-webhook-event"], "ping");
+
+    // Otherwise loopback and private addresses are refused, and the page
+    // only says the ping was not delivered.
+    let public_only = Arc::new(AppState {
+        webhooks_private_networks: false,
+        ..state.clone()
+    });
+    let (status, body) = post_json_with_cookie(
+        build_app(public_only),
+        &host,
+        &format!("{path}/test"),
+        &token,
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("No se pudo entregar el ping."), "body: {body}");
+    assert!(!body.contains("127.0.0.1:"), "body: {body}");
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), received.recv())
+            .await
+            .is_err()
+    );
+
+    // Without the event checked nothing is sent.
+    let status = post_form_with_cookie(
+        build_app(shared.clone()),
+        &host,
+        &path,
+        &token,
+        format!("url={url}"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, _) = post_json_with_cookie(
+        build_app(shared),
+        &host,
+        "/api/admin/forecasts/generate",
+        &token,
+        serde_json::json!({
+            "start_date": "2030-02-01T00:00:00Z",
+            "end_date": "2030-02-28T23:59:59Z",
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), received.recv())
+            .await
+            .is_err()
+    );
+
+    common::teardown(Some(ctx)).await;
+}